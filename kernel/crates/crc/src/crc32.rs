@@ -0,0 +1,46 @@
+//! * 32-bit CRC (IEEE 802.3) calculation.
+//!
+//! 参考 Linux Kernel lib/crc32.c
+//!
+//! This is the CRC used by Ethernet, zlib/gzip and the EFI GUID Partition
+//! Table (GPT) headers and entry arrays.
+//!
+//! x^32 + x^26 + x^23 + x^22 + x^16 + x^12 + x^11 + x^10 + x^8 + x^7 +
+//! x^5 + x^4 + x^2 + x + 1
+
+use crate::tables::crc32::CRC32_TABLE;
+
+/// crc32_le - Calculate bitwise little-endian Ethernet AUTODIN II CRC32
+///
+/// 与Linux一样，这里不对输入和输出取反，调用者需要自行处理。
+///
+/// ## 参数
+///
+/// - `crc`: seed value for computation. (u32)~0 for Ethernet, sometimes 0 for
+///   other uses, or the previous crc32 value if computing incrementally.
+/// - `buf`: pointer to buffer over which CRC32 is run
+pub fn crc32_le(mut crc: u32, buf: &[u8]) -> u32 {
+    for &byte in buf {
+        crc = CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        let crc = !crc32_le(!0, b"123456789");
+        assert_eq!(crc, 0xcbf43926);
+    }
+
+    #[test]
+    fn crc32_incremental() {
+        let buf = b"0123456789abcdef";
+        let whole = crc32_le(!0, buf);
+        let part = crc32_le(crc32_le(!0, &buf[..5]), &buf[5..]);
+        assert_eq!(whole, part);
+    }
+}
@@ -0,0 +1,47 @@
+//! * 32-bit CRC (Castagnoli) calculation.
+//!
+//! 参考 Linux Kernel lib/crc32.c
+//!
+//! CRC32C uses the Castagnoli polynomial, which is also implemented by the
+//! `crc32` instruction of SSE4.2, and is used by iSCSI, ext4/btrfs metadata
+//! and block integrity.
+//!
+//! x^32 + x^28 + x^27 + x^26 + x^25 + x^23 + x^22 + x^20 + x^19 + x^18 +
+//! x^14 + x^13 + x^11 + x^10 + x^9 + x^8 + x^6 + 1
+
+use crate::tables::crc32c::CRC32C_TABLE;
+
+/// crc32c_le_generic - Calculate bitwise little-endian CRC32C
+///
+/// 与Linux一样，这里不对输入和输出取反，调用者需要自行处理。
+///
+/// ## 参数
+///
+/// - `crc`: seed value for computation. (u32)~0 for a new CRC calculation,
+///   or the previous crc32c value if computing incrementally.
+/// - `buf`: pointer to buffer over which CRC32C is run
+pub fn crc32c_le_generic(mut crc: u32, buf: &[u8]) -> u32 {
+    for &byte in buf {
+        crc = CRC32C_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32c_check_value() {
+        let crc = !crc32c_le_generic(!0, b"123456789");
+        assert_eq!(crc, 0xe3069283);
+    }
+
+    #[test]
+    fn crc32c_incremental() {
+        let buf = b"0123456789abcdef";
+        let whole = crc32c_le_generic(!0, buf);
+        let part = crc32c_le_generic(crc32c_le_generic(!0, &buf[..5]), &buf[5..]);
+        assert_eq!(whole, part);
+    }
+}
@@ -6,5 +6,7 @@
 #[cfg(test)]
 extern crate std;
 
+pub mod crc32;
+pub mod crc32c;
 pub mod crc64;
 pub mod tables;
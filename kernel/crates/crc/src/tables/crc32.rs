@@ -0,0 +1,22 @@
+/// CRC32（IEEE 802.3）的查找表，多项式为0x04C11DB7，按位反转后为0xEDB88320
+pub const CRC32_TABLE: [u32; 256] = crc32_table(0xEDB88320);
+
+const fn crc32_table(poly: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ poly
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
@@ -1 +1,3 @@
+pub mod crc32;
+pub mod crc32c;
 pub mod crc64;
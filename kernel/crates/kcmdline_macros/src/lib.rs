@@ -30,15 +30,18 @@ macro_rules! kernel_cmdline_param_arg {
 ///
 /// # 参数
 /// - `$varname`: 参数的变量名
-/// - `$name`: 参数的名称
+/// - `$name`: 参数的名称。带有模块前缀的参数（如`printk.time`）用字符串字面量表示
 /// - `$default_str`: 默认值
 #[macro_export]
 macro_rules! kernel_cmdline_param_kv {
     ($varname:ident, $name:ident, $default_str:expr) => {
+        $crate::kernel_cmdline_param_kv!($varname, stringify!($name), $default_str);
+    };
+    ($varname:ident, $name:expr, $default_str:expr) => {
         #[::linkme::distributed_slice(crate::init::cmdline::KCMDLINE_PARAM_KV)]
         static $varname: crate::init::cmdline::KernelCmdlineParameter =
             crate::init::cmdline::KernelCmdlineParamBuilder::new(
-                stringify!($name),
+                $name,
                 crate::init::cmdline::KCmdlineParamType::KV,
             )
             .default_str($default_str)
@@ -51,17 +54,20 @@ macro_rules! kernel_cmdline_param_kv {
 ///
 /// # 参数
 /// - `$varname`: 参数的变量名
-/// - `$name`: 参数的名称
+/// - `$name`: 参数的名称。带有模块前缀的参数（如`ramoops.mem_size`）用字符串字面量表示
 /// - `$default_str`: 默认值
 #[macro_export]
 macro_rules! kernel_cmdline_param_early_kv {
     ($varname:ident, $name:ident, $default_str:expr) => {
+        $crate::kernel_cmdline_param_early_kv!($varname, stringify!($name), $default_str);
+    };
+    ($varname:ident, $name:expr, $default_str:expr) => {
         #[::linkme::distributed_slice(crate::init::cmdline::KCMDLINE_PARAM_EARLY_KV)]
         static $varname: crate::init::cmdline::KernelCmdlineParameter = {
             static ___KV: crate::init::cmdline::KernelCmdlineEarlyKV = {
                 const { assert!($default_str.len() < KernelCmdlineEarlyKV::VALUE_MAX_LEN) };
                 crate::init::cmdline::KernelCmdlineParamBuilder::new(
-                    stringify!($name),
+                    $name,
                     crate::init::cmdline::KCmdlineParamType::EarlyKV,
                 )
                 .default_str($default_str)
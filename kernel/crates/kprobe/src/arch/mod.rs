@@ -54,6 +54,36 @@ pub trait KprobeOps: Send {
     fn break_address(&self) -> usize;
 }
 
+/// 内核提供给kprobe的辅助操作
+///
+/// 内核代码只读、数据不可执行时，kprobe不能直接修改代码，也不能在普通内存中单步执行原指令
+pub trait KprobeAuxiliaryOps: Send + Sync {
+    /// 把`data`写入内核代码`addr`处
+    fn text_poke(&self, addr: usize, data: &[u8]);
+    /// 分配一个可执行的指令槽，用于单步执行被替换的指令。返回槽的地址
+    fn alloc_insn_slot(&self) -> Option<usize>;
+    /// 释放由[`KprobeAuxiliaryOps::alloc_insn_slot`]分配的指令槽
+    fn free_insn_slot(&self, slot: usize);
+}
+
+static mut AUXILIARY_OPS: Option<&'static dyn KprobeAuxiliaryOps> = None;
+
+/// # 注册辅助操作
+///
+/// 没有注册时，kprobe直接修改代码，并在堆上单步执行原指令
+///
+/// ## 安全性
+///
+/// 必须在安装第一个kprobe之前调用，并且只能调用一次
+pub unsafe fn register_auxiliary_ops(ops: &'static dyn KprobeAuxiliaryOps) {
+    AUXILIARY_OPS = Some(ops);
+}
+
+#[allow(dead_code)]
+pub(crate) fn auxiliary_ops() -> Option<&'static dyn KprobeAuxiliaryOps> {
+    unsafe { *core::ptr::addr_of!(AUXILIARY_OPS) }
+}
+
 struct ProbeHandler {
     func: fn(&dyn ProbeArgs),
 }
@@ -1,4 +1,4 @@
-use crate::{KprobeBasic, KprobeBuilder, KprobeOps};
+use crate::{auxiliary_ops, KprobeBasic, KprobeBuilder, KprobeOps};
 use alloc::string::ToString;
 use alloc::sync::Arc;
 use core::{
@@ -10,6 +10,17 @@ use yaxpeax_arch::LengthedInstruction;
 const EBREAK_INST: u8 = 0xcc; // x86_64: 0xcc
 const MAX_INSTRUCTION_SIZE: usize = 15; // x86_64 max instruction length
 
+/// 修改内核代码
+unsafe fn write_text(address: usize, data: &[u8]) {
+    match auxiliary_ops() {
+        Some(ops) => ops.text_poke(address, data),
+        None => {
+            core::ptr::copy(data.as_ptr(), address as *mut u8, data.len());
+            core::arch::x86_64::_mm_mfence();
+        }
+    }
+}
+
 pub struct Kprobe {
     basic: KprobeBasic,
     point: Arc<X86KprobePoint>,
@@ -20,18 +31,18 @@ pub struct X86KprobePoint {
     addr: usize,
     old_instruction: [u8; MAX_INSTRUCTION_SIZE],
     old_instruction_len: usize,
+    /// 单步执行原指令的指令槽，为`None`时在`old_instruction`中执行
+    insn_slot: Option<usize>,
 }
 
 impl Drop for X86KprobePoint {
     fn drop(&mut self) {
         let address = self.addr;
         unsafe {
-            core::ptr::copy(
-                self.old_instruction.as_ptr(),
-                address as *mut u8,
-                self.old_instruction_len,
-            );
-            core::arch::x86_64::_mm_mfence();
+            write_text(address, &self.old_instruction[..self.old_instruction_len]);
+        }
+        if let (Some(slot), Some(ops)) = (self.insn_slot, auxiliary_ops()) {
+            ops.free_insn_slot(slot);
         }
         let decoder = yaxpeax_x86::amd64::InstDecoder::default();
         let inst = decoder.decode_slice(&self.old_instruction).unwrap();
@@ -95,14 +106,19 @@ impl KprobeBuilder {
         let inst = decoder.decode_slice(&inst_tmp).unwrap();
         let len = inst.len().to_const();
         log::trace!("inst: {:?}, len: {:?}", inst.to_string(), len);
+        let insn_slot = auxiliary_ops().and_then(|ops| {
+            let slot = ops.alloc_insn_slot()?;
+            ops.text_poke(slot, &inst_tmp[..len as usize]);
+            Some(slot)
+        });
         let point = Arc::new(X86KprobePoint {
             addr: address,
             old_instruction: inst_tmp,
             old_instruction_len: len as usize,
+            insn_slot,
         });
         unsafe {
-            core::ptr::write_volatile(address as *mut u8, EBREAK_INST);
-            core::arch::x86_64::_mm_mfence();
+            write_text(address, &[EBREAK_INST]);
         }
         log::trace!(
             "Kprobe::install: address: {:#x}, func_name: {:?}",
@@ -124,10 +140,11 @@ impl KprobeOps for X86KprobePoint {
         self.addr + self.old_instruction_len
     }
     fn single_step_address(&self) -> usize {
-        self.old_instruction.as_ptr() as usize
+        self.insn_slot
+            .unwrap_or(self.old_instruction.as_ptr() as usize)
     }
     fn debug_address(&self) -> usize {
-        self.old_instruction.as_ptr() as usize + self.old_instruction_len
+        self.single_step_address() + self.old_instruction_len
     }
     fn break_address(&self) -> usize {
         self.addr
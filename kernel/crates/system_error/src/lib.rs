@@ -5,6 +5,7 @@
 use num_derive::{FromPrimitive, ToPrimitive};
 
 mod another_ext4;
+mod strerror;
 
 #[repr(i32)]
 #[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone)]
@@ -337,4 +338,15 @@ mod tests {
     fn it_works() {
         assert_eq!(SystemError::EPERM.to_posix_errno(), -1);
     }
+
+    #[test]
+    fn strerror() {
+        assert_eq!(SystemError::EIO.name(), "EIO");
+        assert_eq!(SystemError::EAGAIN_OR_EWOULDBLOCK.name(), "EAGAIN");
+        extern crate alloc;
+        assert_eq!(
+            alloc::format!("{}", SystemError::ENOENT),
+            "ENOENT (No such file or directory)"
+        );
+    }
 }
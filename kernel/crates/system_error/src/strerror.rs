@@ -0,0 +1,314 @@
+//! 错误码的名字和描述，用于在内核日志中打印错误
+
+use core::fmt;
+
+use super::SystemError;
+
+impl SystemError {
+    /// 错误码的名字，例如`EIO`
+    pub fn name(&self) -> &'static str {
+        match self {
+            SystemError::EPERM => "EPERM",
+            SystemError::ENOENT => "ENOENT",
+            SystemError::ESRCH => "ESRCH",
+            SystemError::EINTR => "EINTR",
+            SystemError::EIO => "EIO",
+            SystemError::ENXIO => "ENXIO",
+            SystemError::E2BIG => "E2BIG",
+            SystemError::ENOEXEC => "ENOEXEC",
+            SystemError::EBADF => "EBADF",
+            SystemError::ECHILD => "ECHILD",
+            SystemError::EAGAIN_OR_EWOULDBLOCK => "EAGAIN",
+            SystemError::ENOMEM => "ENOMEM",
+            SystemError::EACCES => "EACCES",
+            SystemError::EFAULT => "EFAULT",
+            SystemError::ENOTBLK => "ENOTBLK",
+            SystemError::EBUSY => "EBUSY",
+            SystemError::EEXIST => "EEXIST",
+            SystemError::EXDEV => "EXDEV",
+            SystemError::ENODEV => "ENODEV",
+            SystemError::ENOTDIR => "ENOTDIR",
+            SystemError::EISDIR => "EISDIR",
+            SystemError::EINVAL => "EINVAL",
+            SystemError::ENFILE => "ENFILE",
+            SystemError::EMFILE => "EMFILE",
+            SystemError::ENOTTY => "ENOTTY",
+            SystemError::ETXTBSY => "ETXTBSY",
+            SystemError::EFBIG => "EFBIG",
+            SystemError::ENOSPC => "ENOSPC",
+            SystemError::ESPIPE => "ESPIPE",
+            SystemError::EROFS => "EROFS",
+            SystemError::EMLINK => "EMLINK",
+            SystemError::EPIPE => "EPIPE",
+            SystemError::EDOM => "EDOM",
+            SystemError::ERANGE => "ERANGE",
+            SystemError::EDEADLK_OR_EDEADLOCK => "EDEADLK",
+            SystemError::ENAMETOOLONG => "ENAMETOOLONG",
+            SystemError::ENOLCK => "ENOLCK",
+            SystemError::ENOSYS => "ENOSYS",
+            SystemError::ENOTEMPTY => "ENOTEMPTY",
+            SystemError::ELOOP => "ELOOP",
+            SystemError::ENOMSG => "ENOMSG",
+            SystemError::EIDRM => "EIDRM",
+            SystemError::ECHRNG => "ECHRNG",
+            SystemError::EL2NSYNC => "EL2NSYNC",
+            SystemError::EL3HLT => "EL3HLT",
+            SystemError::EL3RST => "EL3RST",
+            SystemError::ELNRNG => "ELNRNG",
+            SystemError::EUNATCH => "EUNATCH",
+            SystemError::ENOCSI => "ENOCSI",
+            SystemError::EL2HLT => "EL2HLT",
+            SystemError::EBADE => "EBADE",
+            SystemError::EBADR => "EBADR",
+            SystemError::EXFULL => "EXFULL",
+            SystemError::ENOANO => "ENOANO",
+            SystemError::EBADRQC => "EBADRQC",
+            SystemError::EBADSLT => "EBADSLT",
+            SystemError::EBFONT => "EBFONT",
+            SystemError::ENOSTR => "ENOSTR",
+            SystemError::ENODATA => "ENODATA",
+            SystemError::ETIME => "ETIME",
+            SystemError::ENOSR => "ENOSR",
+            SystemError::ENONET => "ENONET",
+            SystemError::ENOPKG => "ENOPKG",
+            SystemError::EREMOTE => "EREMOTE",
+            SystemError::ENOLINK => "ENOLINK",
+            SystemError::EADV => "EADV",
+            SystemError::ESRMNT => "ESRMNT",
+            SystemError::ECOMM => "ECOMM",
+            SystemError::EPROTO => "EPROTO",
+            SystemError::EMULTIHOP => "EMULTIHOP",
+            SystemError::EDOTDOT => "EDOTDOT",
+            SystemError::EBADMSG => "EBADMSG",
+            SystemError::EOVERFLOW => "EOVERFLOW",
+            SystemError::ENOTUNIQ => "ENOTUNIQ",
+            SystemError::EBADFD => "EBADFD",
+            SystemError::EREMCHG => "EREMCHG",
+            SystemError::ELIBACC => "ELIBACC",
+            SystemError::ELIBBAD => "ELIBBAD",
+            SystemError::ELIBSCN => "ELIBSCN",
+            SystemError::ELIBMAX => "ELIBMAX",
+            SystemError::ELIBEXEC => "ELIBEXEC",
+            SystemError::EILSEQ => "EILSEQ",
+            SystemError::ERESTART => "ERESTART",
+            SystemError::ESTRPIPE => "ESTRPIPE",
+            SystemError::EUSERS => "EUSERS",
+            SystemError::ENOTSOCK => "ENOTSOCK",
+            SystemError::EDESTADDRREQ => "EDESTADDRREQ",
+            SystemError::EMSGSIZE => "EMSGSIZE",
+            SystemError::EPROTOTYPE => "EPROTOTYPE",
+            SystemError::ENOPROTOOPT => "ENOPROTOOPT",
+            SystemError::EPROTONOSUPPORT => "EPROTONOSUPPORT",
+            SystemError::ESOCKTNOSUPPORT => "ESOCKTNOSUPPORT",
+            SystemError::EOPNOTSUPP_OR_ENOTSUP => "EOPNOTSUPP",
+            SystemError::EPFNOSUPPORT => "EPFNOSUPPORT",
+            SystemError::EAFNOSUPPORT => "EAFNOSUPPORT",
+            SystemError::EADDRINUSE => "EADDRINUSE",
+            SystemError::EADDRNOTAVAIL => "EADDRNOTAVAIL",
+            SystemError::ENETDOWN => "ENETDOWN",
+            SystemError::ENETUNREACH => "ENETUNREACH",
+            SystemError::ENETRESET => "ENETRESET",
+            SystemError::ECONNABORTED => "ECONNABORTED",
+            SystemError::ECONNRESET => "ECONNRESET",
+            SystemError::ENOBUFS => "ENOBUFS",
+            SystemError::EISCONN => "EISCONN",
+            SystemError::ENOTCONN => "ENOTCONN",
+            SystemError::ESHUTDOWN => "ESHUTDOWN",
+            SystemError::ETOOMANYREFS => "ETOOMANYREFS",
+            SystemError::ETIMEDOUT => "ETIMEDOUT",
+            SystemError::ECONNREFUSED => "ECONNREFUSED",
+            SystemError::EHOSTDOWN => "EHOSTDOWN",
+            SystemError::EHOSTUNREACH => "EHOSTUNREACH",
+            SystemError::EALREADY => "EALREADY",
+            SystemError::EINPROGRESS => "EINPROGRESS",
+            SystemError::ESTALE => "ESTALE",
+            SystemError::EUCLEAN => "EUCLEAN",
+            SystemError::ENOTNAM => "ENOTNAM",
+            SystemError::ENAVAIL => "ENAVAIL",
+            SystemError::EISNAM => "EISNAM",
+            SystemError::EREMOTEIO => "EREMOTEIO",
+            SystemError::EDQUOT => "EDQUOT",
+            SystemError::ENOMEDIUM => "ENOMEDIUM",
+            SystemError::EMEDIUMTYPE => "EMEDIUMTYPE",
+            SystemError::ECANCELED => "ECANCELED",
+            SystemError::ENOKEY => "ENOKEY",
+            SystemError::EKEYEXPIRED => "EKEYEXPIRED",
+            SystemError::EKEYREVOKED => "EKEYREVOKED",
+            SystemError::EKEYREJECTED => "EKEYREJECTED",
+            SystemError::EOWNERDEAD => "EOWNERDEAD",
+            SystemError::ENOTRECOVERABLE => "ENOTRECOVERABLE",
+            SystemError::ERFKILL => "ERFKILL",
+            SystemError::EHWPOISON => "EHWPOISON",
+            SystemError::ERESTARTSYS => "ERESTARTSYS",
+            SystemError::ERESTARTNOINTR => "ERESTARTNOINTR",
+            SystemError::ERESTARTNOHAND => "ERESTARTNOHAND",
+            SystemError::ENOIOCTLCMD => "ENOIOCTLCMD",
+            SystemError::ERESTART_RESTARTBLOCK => "ERESTART_RESTARTBLOCK",
+            SystemError::EVMXONFailed => "EVMXONFailed",
+            SystemError::EVMXOFFFailed => "EVMXOFFFailed",
+            SystemError::EVMWRITEFailed => "EVMWRITEFailed",
+            SystemError::EVMREADFailed => "EVMREADFailed",
+            SystemError::EVMPRTLDFailed => "EVMPRTLDFailed",
+            SystemError::EVMLAUNCHFailed => "EVMLAUNCHFailed",
+            SystemError::KVM_HVA_ERR_BAD => "KVM_HVA_ERR_BAD",
+            SystemError::MAXERRNO => "MAXERRNO",
+        }
+    }
+
+    /// 错误码的描述，与glibc的`strerror`一致，例如`Input/output error`
+    pub fn description(&self) -> &'static str {
+        match self {
+            SystemError::EPERM => "Operation not permitted",
+            SystemError::ENOENT => "No such file or directory",
+            SystemError::ESRCH => "No such process",
+            SystemError::EINTR => "Interrupted system call",
+            SystemError::EIO => "Input/output error",
+            SystemError::ENXIO => "No such device or address",
+            SystemError::E2BIG => "Argument list too long",
+            SystemError::ENOEXEC => "Exec format error",
+            SystemError::EBADF => "Bad file descriptor",
+            SystemError::ECHILD => "No child processes",
+            SystemError::EAGAIN_OR_EWOULDBLOCK => "Resource temporarily unavailable",
+            SystemError::ENOMEM => "Cannot allocate memory",
+            SystemError::EACCES => "Permission denied",
+            SystemError::EFAULT => "Bad address",
+            SystemError::ENOTBLK => "Block device required",
+            SystemError::EBUSY => "Device or resource busy",
+            SystemError::EEXIST => "File exists",
+            SystemError::EXDEV => "Invalid cross-device link",
+            SystemError::ENODEV => "No such device",
+            SystemError::ENOTDIR => "Not a directory",
+            SystemError::EISDIR => "Is a directory",
+            SystemError::EINVAL => "Invalid argument",
+            SystemError::ENFILE => "Too many open files in system",
+            SystemError::EMFILE => "Too many open files",
+            SystemError::ENOTTY => "Inappropriate ioctl for device",
+            SystemError::ETXTBSY => "Text file busy",
+            SystemError::EFBIG => "File too large",
+            SystemError::ENOSPC => "No space left on device",
+            SystemError::ESPIPE => "Illegal seek",
+            SystemError::EROFS => "Read-only file system",
+            SystemError::EMLINK => "Too many links",
+            SystemError::EPIPE => "Broken pipe",
+            SystemError::EDOM => "Numerical argument out of domain",
+            SystemError::ERANGE => "Numerical result out of range",
+            SystemError::EDEADLK_OR_EDEADLOCK => "Resource deadlock avoided",
+            SystemError::ENAMETOOLONG => "File name too long",
+            SystemError::ENOLCK => "No locks available",
+            SystemError::ENOSYS => "Function not implemented",
+            SystemError::ENOTEMPTY => "Directory not empty",
+            SystemError::ELOOP => "Too many levels of symbolic links",
+            SystemError::ENOMSG => "No message of desired type",
+            SystemError::EIDRM => "Identifier removed",
+            SystemError::ECHRNG => "Channel number out of range",
+            SystemError::EL2NSYNC => "Level 2 not synchronized",
+            SystemError::EL3HLT => "Level 3 halted",
+            SystemError::EL3RST => "Level 3 reset",
+            SystemError::ELNRNG => "Link number out of range",
+            SystemError::EUNATCH => "Protocol driver not attached",
+            SystemError::ENOCSI => "No CSI structure available",
+            SystemError::EL2HLT => "Level 2 halted",
+            SystemError::EBADE => "Invalid exchange",
+            SystemError::EBADR => "Invalid request descriptor",
+            SystemError::EXFULL => "Exchange full",
+            SystemError::ENOANO => "No anode",
+            SystemError::EBADRQC => "Invalid request code",
+            SystemError::EBADSLT => "Invalid slot",
+            SystemError::EBFONT => "Bad font file format",
+            SystemError::ENOSTR => "Device not a stream",
+            SystemError::ENODATA => "No data available",
+            SystemError::ETIME => "Timer expired",
+            SystemError::ENOSR => "Out of streams resources",
+            SystemError::ENONET => "Machine is not on the network",
+            SystemError::ENOPKG => "Package not installed",
+            SystemError::EREMOTE => "Object is remote",
+            SystemError::ENOLINK => "Link has been severed",
+            SystemError::EADV => "Advertise error",
+            SystemError::ESRMNT => "Srmount error",
+            SystemError::ECOMM => "Communication error on send",
+            SystemError::EPROTO => "Protocol error",
+            SystemError::EMULTIHOP => "Multihop attempted",
+            SystemError::EDOTDOT => "RFS specific error",
+            SystemError::EBADMSG => "Bad message",
+            SystemError::EOVERFLOW => "Value too large for defined data type",
+            SystemError::ENOTUNIQ => "Name not unique on network",
+            SystemError::EBADFD => "File descriptor in bad state",
+            SystemError::EREMCHG => "Remote address changed",
+            SystemError::ELIBACC => "Can not access a needed shared library",
+            SystemError::ELIBBAD => "Accessing a corrupted shared library",
+            SystemError::ELIBSCN => ".lib section in a.out corrupted",
+            SystemError::ELIBMAX => "Attempting to link in too many shared libraries",
+            SystemError::ELIBEXEC => "Cannot exec a shared library directly",
+            SystemError::EILSEQ => "Invalid or incomplete multibyte or wide character",
+            SystemError::ERESTART => "Interrupted system call should be restarted",
+            SystemError::ESTRPIPE => "Streams pipe error",
+            SystemError::EUSERS => "Too many users",
+            SystemError::ENOTSOCK => "Socket operation on non-socket",
+            SystemError::EDESTADDRREQ => "Destination address required",
+            SystemError::EMSGSIZE => "Message too long",
+            SystemError::EPROTOTYPE => "Protocol wrong type for socket",
+            SystemError::ENOPROTOOPT => "Protocol not available",
+            SystemError::EPROTONOSUPPORT => "Protocol not supported",
+            SystemError::ESOCKTNOSUPPORT => "Socket type not supported",
+            SystemError::EOPNOTSUPP_OR_ENOTSUP => "Operation not supported",
+            SystemError::EPFNOSUPPORT => "Protocol family not supported",
+            SystemError::EAFNOSUPPORT => "Address family not supported by protocol",
+            SystemError::EADDRINUSE => "Address already in use",
+            SystemError::EADDRNOTAVAIL => "Cannot assign requested address",
+            SystemError::ENETDOWN => "Network is down",
+            SystemError::ENETUNREACH => "Network is unreachable",
+            SystemError::ENETRESET => "Network dropped connection on reset",
+            SystemError::ECONNABORTED => "Software caused connection abort",
+            SystemError::ECONNRESET => "Connection reset by peer",
+            SystemError::ENOBUFS => "No buffer space available",
+            SystemError::EISCONN => "Transport endpoint is already connected",
+            SystemError::ENOTCONN => "Transport endpoint is not connected",
+            SystemError::ESHUTDOWN => "Cannot send after transport endpoint shutdown",
+            SystemError::ETOOMANYREFS => "Too many references: cannot splice",
+            SystemError::ETIMEDOUT => "Connection timed out",
+            SystemError::ECONNREFUSED => "Connection refused",
+            SystemError::EHOSTDOWN => "Host is down",
+            SystemError::EHOSTUNREACH => "No route to host",
+            SystemError::EALREADY => "Operation already in progress",
+            SystemError::EINPROGRESS => "Operation now in progress",
+            SystemError::ESTALE => "Stale file handle",
+            SystemError::EUCLEAN => "Structure needs cleaning",
+            SystemError::ENOTNAM => "Not a XENIX named type file",
+            SystemError::ENAVAIL => "No XENIX semaphores available",
+            SystemError::EISNAM => "Is a named type file",
+            SystemError::EREMOTEIO => "Remote I/O error",
+            SystemError::EDQUOT => "Disk quota exceeded",
+            SystemError::ENOMEDIUM => "No medium found",
+            SystemError::EMEDIUMTYPE => "Wrong medium type",
+            SystemError::ECANCELED => "Operation canceled",
+            SystemError::ENOKEY => "Required key not available",
+            SystemError::EKEYEXPIRED => "Key has expired",
+            SystemError::EKEYREVOKED => "Key has been revoked",
+            SystemError::EKEYREJECTED => "Key was rejected by service",
+            SystemError::EOWNERDEAD => "Owner died",
+            SystemError::ENOTRECOVERABLE => "State not recoverable",
+            SystemError::ERFKILL => "Operation not possible due to RF-kill",
+            SystemError::EHWPOISON => "Memory page has hardware error",
+            SystemError::ERESTARTSYS => "Restart system call",
+            SystemError::ERESTARTNOINTR => "Restart system call without interruption",
+            SystemError::ERESTARTNOHAND => "Restart if no signal handler",
+            SystemError::ENOIOCTLCMD => "No ioctl command",
+            SystemError::ERESTART_RESTARTBLOCK => "Restart with restart_syscall",
+            SystemError::EVMXONFailed => "VMXON failed",
+            SystemError::EVMXOFFFailed => "VMXOFF failed",
+            SystemError::EVMWRITEFailed => "VMWRITE failed",
+            SystemError::EVMREADFailed => "VMREAD failed",
+            SystemError::EVMPRTLDFailed => "VMPTRLD failed",
+            SystemError::EVMLAUNCHFailed => "VMLAUNCH failed",
+            SystemError::KVM_HVA_ERR_BAD => "Bad host virtual address",
+            SystemError::MAXERRNO => "Unknown error",
+        }
+    }
+}
+
+/// 格式为`EIO (Input/output error)`
+impl fmt::Display for SystemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name(), self.description())
+    }
+}
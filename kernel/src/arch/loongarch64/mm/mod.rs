@@ -55,6 +55,8 @@ impl MemoryManagementArch for LoongArch64MMArch {
 
     const ENTRY_FLAG_GLOBAL: usize = 0;
 
+    const ENTRY_FLAG_SOFT_DIRTY: usize = 0;
+
     const PHYS_OFFSET: usize = 0x9000_0000_0000_0000;
 
     const KERNEL_LINK_OFFSET: usize = 0;
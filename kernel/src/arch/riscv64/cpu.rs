@@ -1,10 +1,12 @@
 use alloc::vec::Vec;
+use log::warn;
 use sbi_rt::HartMask;
 
 use crate::{
+    driver::open_firmware::fdt::open_firmware_fdt_driver,
     init::boot_params,
     mm::percpu::{PerCpu, PerCpuVar},
-    smp::cpu::{ProcessorId, SmpCpuManager},
+    smp::cpu::{smp_cpu_manager, ProcessorId, SmpCpuManager},
 };
 
 /// 栈对齐
@@ -28,7 +30,7 @@ impl Into<HartMask> for ProcessorId {
     fn into(self) -> HartMask {
         let base = self.data() as usize / RISCV_XLEN;
         let offset = self.data() as usize & (RISCV_XLEN - 1);
-        HartMask::from_mask_base(offset, base)
+        HartMask::from_mask_base(1 << offset, base)
     }
 }
 /// 重置cpu
@@ -131,8 +133,53 @@ pub(super) fn init_local_context() {
     ctx.sync_to_cpu();
 }
 
+/// 在AP上设置本地上下文，必须在AP上最先执行
+pub(super) fn init_local_context_ap(cpu: ProcessorId) {
+    let ctx = unsafe { local_context().force_get(cpu) };
+    ctx.sync_to_cpu();
+}
+
 impl SmpCpuManager {
-    pub fn arch_init(_boot_cpu: ProcessorId) {
-        // todo: 读取所有可用的CPU
+    /// 从设备树中读取所有的hart
+    ///
+    /// CPU的编号就是hart id。SBI不支持HSM扩展时无法启动其他hart，它们只标记为possible
+    pub fn arch_init(boot_cpu: ProcessorId) {
+        let fdt = match open_firmware_fdt_driver().fdt_ref() {
+            Ok(fdt) => fdt,
+            Err(e) => {
+                warn!("smp: can't read fdt: {:?}, only boot hart is used", e);
+                return;
+            }
+        };
+        let hsm = sbi_rt::probe_extension(sbi_rt::Hsm).is_available();
+        if !hsm {
+            warn!("smp: SBI HSM extension is not available, only boot hart is used");
+        }
+
+        let harts = fdt.all_nodes().filter(|node| {
+            node.name.starts_with("cpu@")
+                && node.property("device_type").and_then(|x| x.as_str()) == Some("cpu")
+                && node
+                    .property("status")
+                    .and_then(|x| x.as_str())
+                    .map_or(true, |s| s == "okay" || s == "ok")
+        });
+        for node in harts {
+            let Some(hartid) = node.reg().and_then(|mut x| x.next()) else {
+                continue;
+            };
+            let hartid = hartid.starting_address as usize;
+            if hartid >= PerCpu::MAX_CPU_NUM as usize {
+                warn!("smp: hart {} exceeds the max cpu num, ignored", hartid);
+                continue;
+            }
+            let cpu = ProcessorId::new(hartid as u32);
+            unsafe {
+                smp_cpu_manager().set_possible_cpu(cpu, true);
+                if hsm || cpu == boot_cpu {
+                    smp_cpu_manager().set_present_cpu(cpu, true);
+                }
+            }
+        }
     }
 }
@@ -18,7 +18,6 @@ use crate::filesystem::devfs::LockedDevFSInode;
 use crate::filesystem::{
     devfs::{DevFS, DeviceINode},
     kernfs::KernFSInode,
-    mbr::MbrDiskPartionTable,
     vfs::{IndexNode, InodeMode, Metadata},
 };
 use crate::libs::mutex::MutexGuard;
@@ -867,10 +866,7 @@ impl BlockDevice for MMC {
     }
 
     fn partitions(&self) -> Vec<Arc<Partition>> {
-        let device = self.self_ref.upgrade().unwrap() as Arc<dyn BlockDevice>;
-        let mbr_table = MbrDiskPartionTable::from_disk(device.clone())
-            .expect("Failed to get MBR partition table");
-        mbr_table.partitions(Arc::downgrade(&device))
+        self.blkdev_meta().partitions()
     }
 }
 
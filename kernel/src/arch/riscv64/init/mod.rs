@@ -1,5 +1,5 @@
 use fdt::node::FdtNode;
-use log::{debug, info};
+use log::{debug, info, warn};
 use system_error::SystemError;
 
 use crate::{
@@ -8,7 +8,10 @@ use crate::{
         init::boot::{early_boot_init, BootProtocol},
         mm::init::mm_early_init,
     },
-    driver::{firmware::efi::init::efi_init, open_firmware::fdt::open_firmware_fdt_driver},
+    driver::{
+        clocksource::timer_riscv::init_riscv_time_clocksource, firmware::efi::init::efi_init,
+        open_firmware::fdt::open_firmware_fdt_driver,
+    },
     init::{boot_params, init::start_kernel},
     mm::{memblock::mem_block_manager, PhysAddr, VirtAddr},
     print, println,
@@ -78,7 +81,7 @@ unsafe extern "C" fn kernel_main(hartid: usize, fdt_paddr: usize) -> ! {
 }
 
 /// 设置中断、异常处理函数
-fn setup_trap_vector() {
+pub(super) fn setup_trap_vector() {
     let ptr = handle_exception as *const () as usize;
 
     unsafe {
@@ -167,6 +170,8 @@ pub fn setup_arch() -> Result<(), SystemError> {
 
 #[inline(never)]
 pub fn setup_arch_post() -> Result<(), SystemError> {
-    // todo
+    if let Err(e) = init_riscv_time_clocksource() {
+        warn!("riscv time clocksource init failed: {:?}", e);
+    }
     return Ok(());
 }
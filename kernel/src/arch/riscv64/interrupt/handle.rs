@@ -7,7 +7,7 @@ use log::{error, trace};
 use system_error::SystemError;
 
 use super::TrapFrame;
-use crate::exception::ebreak::EBreak;
+use crate::exception::{ebreak::EBreak, HardirqGuard};
 use crate::{arch::syscall::syscall_handler, driver::irqchip::riscv_intc::riscv_intc_irq};
 
 type ExceptionHandler = fn(&mut TrapFrame) -> Result<(), SystemError>;
@@ -42,6 +42,7 @@ unsafe extern "C" fn riscv64_do_irq(trap_frame: &mut TrapFrame) {
 
 /// 处理中断
 fn riscv64_do_interrupt(trap_frame: &mut TrapFrame) {
+    let _hardirq_guard = HardirqGuard::enter();
     riscv_intc_irq(trap_frame);
 }
 
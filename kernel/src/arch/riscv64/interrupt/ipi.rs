@@ -1,21 +1,108 @@
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use alloc::sync::Arc;
+use log::warn;
 use sbi_rt::HartMask;
 
 use crate::{
-    arch::mm::RiscV64MMArch,
-    exception::ipi::{IpiKind, IpiTarget},
-    smp::core::smp_get_processor_id,
+    arch::{interrupt::TrapFrame, mm::RiscV64MMArch},
+    driver::irqchip::riscv_intc::riscv_intc_assicate_irq,
+    exception::{
+        ipi::{IpiKind, IpiTarget, KickCpuIpiHandler},
+        irqdata::IrqLineStatus,
+        irqdesc::{irq_desc_manager, IrqDesc, IrqFlowHandler, IrqHandler},
+        HardwareIrqNumber,
+    },
+    mm::percpu::PerCpu,
+    smp::{
+        core::smp_get_processor_id,
+        cpu::{smp_cpu_manager, ProcessorId},
+    },
 };
 
+/// S态软件中断，SBI通过它投递IPI
+const IRQ_S_SOFT: HardwareIrqNumber = HardwareIrqNumber::new(1);
+
+/// 等待处理的IPI：唤醒CPU
+const IPI_PENDING_KICK_CPU: usize = 1 << 0;
+
+/// 每个CPU等待处理的IPI。SBI只有一种IPI，种类记录在这里，收到软件中断之后再分发
+static IPI_PENDING: [AtomicUsize; PerCpu::MAX_CPU_NUM as usize] =
+    [const { AtomicUsize::new(0) }; PerCpu::MAX_CPU_NUM as usize];
+
 #[inline(always)]
 pub fn send_ipi(kind: IpiKind, target: IpiTarget) {
-    let mask = Into::into(target);
     match kind {
-        IpiKind::KickCpu => todo!(),
-        IpiKind::FlushTLB => RiscV64MMArch::remote_invalidate_all_with_mask(mask).ok(),
-        IpiKind::SpecVector(_) => todo!(),
+        IpiKind::KickCpu => sbi_send_ipi(target, IPI_PENDING_KICK_CPU),
+        IpiKind::FlushTLB => {
+            RiscV64MMArch::remote_invalidate_all_with_mask(Into::into(target)).ok();
+        }
+        IpiKind::SpecVector(vec) => {
+            warn!("riscv64: SBI can't send IPI with vector {:?}", vec);
+        }
     };
 }
 
+/// 标记目标CPU等待处理的IPI，然后通过SBI发送软件中断
+fn sbi_send_ipi(target: IpiTarget, pending: usize) {
+    let current = smp_get_processor_id();
+    let mark = |cpu: ProcessorId| {
+        if let Some(p) = IPI_PENDING.get(cpu.data() as usize) {
+            p.fetch_or(pending, Ordering::SeqCst);
+        }
+    };
+    match target {
+        IpiTarget::Current => mark(current),
+        IpiTarget::Specified(cpu) => mark(cpu),
+        IpiTarget::All | IpiTarget::Other => smp_cpu_manager()
+            .present_cpus()
+            .iter_cpu()
+            .filter(|cpu| target == IpiTarget::All || *cpu != current)
+            .for_each(mark),
+    }
+    // 标记对目标CPU可见之后才能发送中断
+    fence(Ordering::SeqCst);
+
+    let r = sbi_rt::send_ipi(Into::into(target));
+    if r.is_err() {
+        warn!("riscv64: sbi send_ipi failed: {:?}", r);
+    }
+}
+
+/// 初始化SBI IPI使用的软件中断
+#[inline(never)]
+pub fn riscv_sbi_ipi_irq_desc_init() {
+    let virq = riscv_intc_assicate_irq(IRQ_S_SOFT).unwrap();
+    let desc = irq_desc_manager().lookup(virq).unwrap();
+
+    desc.modify_status(IrqLineStatus::IRQ_LEVEL, IrqLineStatus::empty());
+    desc.set_handler(&RiscvSbiIpiIrqFlowHandler);
+    riscv_sbi_ipi_init_local();
+}
+
+/// 在当前hart上允许接收IPI
+pub fn riscv_sbi_ipi_init_local() {
+    unsafe { riscv::register::sie::set_bits(1 << IRQ_S_SOFT.data()) };
+}
+
+#[derive(Debug)]
+struct RiscvSbiIpiIrqFlowHandler;
+
+impl IrqFlowHandler for RiscvSbiIpiIrqFlowHandler {
+    fn handle(&self, irq_desc: &Arc<IrqDesc>, _trap_frame: &mut TrapFrame) {
+        // 先清除软件中断再取走等待处理的IPI，避免丢失处理期间新到达的IPI
+        unsafe { core::arch::asm!("csrc sip, {0}", in(reg) 1usize << IRQ_S_SOFT.data()) };
+        fence(Ordering::SeqCst);
+
+        let cpu = smp_get_processor_id().data() as usize;
+        let pending = IPI_PENDING[cpu].swap(0, Ordering::SeqCst);
+        if pending & IPI_PENDING_KICK_CPU != 0 {
+            let irq = irq_desc.irq_data().irq();
+            KickCpuIpiHandler.handle(irq, None, None).ok();
+        }
+    }
+}
+
 impl Into<HartMask> for IpiTarget {
     fn into(self) -> HartMask {
         match self {
@@ -12,6 +12,7 @@ use crate::{
         MMArch,
     },
     driver::firmware::efi::efi_manager,
+    filesystem::pstore::ram::ramoops_early_reserve,
     libs::lib_ui::screen_manager::scm_disable_put_to_window,
     mm::{
         allocator::{buddy::BuddyAllocator, bump::BumpAllocator, page_frame::FrameAllocator},
@@ -69,6 +70,7 @@ pub(super) unsafe fn riscv_mm_init() -> Result<(), SystemError> {
     mem_block_manager()
         .reserve_block(KERNEL_BEGIN_PA, KERNEL_END_PA - KERNEL_BEGIN_PA)
         .expect("Failed to reserve kernel memory");
+    ramoops_early_reserve();
     // 开启S-mode User Memory Access，允许内核访问用户空间
     riscv::register::sstatus::set_sum();
 
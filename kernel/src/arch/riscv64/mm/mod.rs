@@ -125,6 +125,8 @@ impl MemoryManagementArch for RiscV64MMArch {
     const ENTRY_FLAG_ACCESSED: usize = (1 << 6);
     const ENTRY_FLAG_DIRTY: usize = (1 << 7);
     const ENTRY_FLAG_GLOBAL: usize = (1 << 5);
+    /// 使用RSW的第9位
+    const ENTRY_FLAG_SOFT_DIRTY: usize = (1 << 9);
 
     const PHYS_OFFSET: usize = 0xffff_ffc0_0000_0000;
     const KERNEL_LINK_OFFSET: usize = 0x1000000;
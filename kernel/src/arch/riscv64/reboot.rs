@@ -1,13 +1,38 @@
+use core::hint::spin_loop;
+
+use log::error;
+
 /// # 功能
 ///
-/// 执行系统重启操作。该函数会尝试使用不同的方法来重启系统，直到成功为止。
+/// 执行系统重启操作。通过SBI系统重置扩展冷重启
 pub(crate) fn machine_restart(_cmd: Option<&str>) -> ! {
-    todo!();
+    let r = sbi_rt::system_reset(sbi_rt::ColdReboot, sbi_rt::NoReason);
+    error!("machine_restart: sbi system_reset failed: {:?}", r);
+    stop_this_cpu();
 }
 
 /// # 功能
 ///
-/// 执行系统停止操作
+/// 执行系统停止操作。SBI没有单独的停机操作，与关机相同
 pub(crate) fn machine_halt() -> ! {
-    todo!();
+    machine_power_off();
+}
+
+/// # 功能
+///
+/// 执行系统关机操作。通过SBI系统重置扩展关机
+pub(crate) fn machine_power_off() -> ! {
+    let r = sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason);
+    error!("machine_power_off: sbi system_reset failed: {:?}", r);
+    stop_this_cpu();
+}
+
+/// 停止当前hart。SBI HSM可用时把hart交还给固件，否则一直等待
+fn stop_this_cpu() -> ! {
+    unsafe { riscv::register::sstatus::clear_sie() };
+    sbi_rt::hart_stop();
+    loop {
+        riscv::asm::wfi();
+        spin_loop();
+    }
 }
@@ -1,22 +1,141 @@
-use log::warn;
+//! RISC-V的多核启动
+//!
+//! 通过SBI HSM扩展启动其他hart。hart从`smp_ap_entry`开始执行，此时没有开启MMU，
+//! 它从[`ApStartInfo`]中取出内核页表和栈，开启MMU之后跳转到内核虚拟地址继续执行。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/riscv/kernel/cpu_ops_sbi.c
+
+use core::{
+    mem::offset_of,
+    sync::atomic::{compiler_fence, fence, Ordering},
+};
+
+use log::{debug, warn};
 use system_error::SystemError;
 
-use crate::smp::{
-    cpu::{CpuHpCpuState, ProcessorId},
-    SMPArch,
+use crate::{
+    arch::{interrupt::ipi::riscv_sbi_ipi_init_local, CurrentIrqArch, MMArch},
+    exception::InterruptArch,
+    mm::{MemoryManagementArch, VirtAddr},
+    smp::{
+        cpu::{CpuHpCpuState, ProcessorId},
+        init::smp_ap_start_stage2,
+        SMPArch,
+    },
 };
 
+use super::{cpu::init_local_context_ap, init::setup_trap_vector};
+
+/// AP启动时需要的信息，AP在开启MMU之前通过物理地址访问
+#[repr(C)]
+struct ApStartInfo {
+    /// 内核页表的satp
+    satp: usize,
+    /// 栈顶的虚拟地址
+    stack: usize,
+    /// 内核虚拟地址与物理地址的差
+    va_pa_offset: usize,
+}
+
+/// 同一时刻只会启动一个AP，启动下一个AP之前会等待上一个启动完成
+static mut AP_START_INFO: ApStartInfo = ApStartInfo {
+    satp: 0,
+    stack: 0,
+    va_pa_offset: 0,
+};
+
+const SR_FS_VS: usize = 0x6600;
+
+/// AP的入口，此时没有开启MMU
+///
+/// ## 参数
+/// - `a0`: hart id
+/// - `a1`: [`ApStartInfo`]的物理地址
+#[unsafe(naked)]
+unsafe extern "C" fn smp_ap_entry() -> ! {
+    core::arch::naked_asm!(
+        "
+        csrw sie, zero
+        csrw sip, zero
+        csrw sscratch, zero
+        li t0, {sr_fs_vs}
+        csrc sstatus, t0
+
+        ld sp, {off_stack}(a1)
+        ld t0, {off_satp}(a1)
+        ld t1, {off_offset}(a1)
+
+        // 开启MMU之后取指会发生异常，此时跳转到stvec，也就是下面标号的虚拟地址
+        lla t2, 2f
+        add t2, t2, t1
+        csrw stvec, t2
+        sfence.vma
+        csrw satp, t0
+        sfence.vma
+        jr t2
+
+        .balign 4
+    2:
+        tail {stage1}
+        ",
+        sr_fs_vs = const SR_FS_VS,
+        off_stack = const offset_of!(ApStartInfo, stack),
+        off_satp = const offset_of!(ApStartInfo, satp),
+        off_offset = const offset_of!(ApStartInfo, va_pa_offset),
+        stage1 = sym smp_ap_start_stage1,
+    );
+}
+
+unsafe extern "C" fn smp_ap_start_stage1(hartid: usize) -> ! {
+    init_local_context_ap(ProcessorId::new(hartid as u32));
+    setup_trap_vector();
+    CurrentIrqArch::interrupt_disable();
+
+    // 每个hart都需要单独打开外部中断和软件中断
+    unsafe { riscv::register::sie::set_sext() };
+    riscv_sbi_ipi_init_local();
+
+    debug!("smp_ap_start_stage1: hart {}", hartid);
+    smp_ap_start_stage2();
+}
+
 pub struct RiscV64SMPArch;
 
 impl SMPArch for RiscV64SMPArch {
     #[inline(never)]
     fn prepare_cpus() -> Result<(), SystemError> {
-        warn!("RiscV64SMPArch::prepare_cpus() is not implemented");
+        // hart在SmpCpuManager::arch_init中已经从设备树中读取
         Ok(())
     }
 
-    fn start_cpu(_cpu_id: ProcessorId, _hp_state: &CpuHpCpuState) -> Result<(), SystemError> {
-        warn!("RiscV64SMPArch::start_cpu() is not implemented");
+    fn start_cpu(cpu_id: ProcessorId, hp_state: &CpuHpCpuState) -> Result<(), SystemError> {
+        let hartid = cpu_id.data() as usize;
+        let thread = hp_state.thread().as_ref().ok_or(SystemError::EINVAL)?;
+        let stack = unsafe { thread.kernel_stack_force_ref() }
+            .stack_max_address()
+            .data()
+            - 16;
+
+        let entry_va = VirtAddr::new(smp_ap_entry as usize);
+        let entry_pa = unsafe { MMArch::virt_2_phys(entry_va) }.ok_or(SystemError::EINVAL)?;
+        let info_va = VirtAddr::new(core::ptr::addr_of!(AP_START_INFO) as usize);
+        let info_pa = unsafe { MMArch::virt_2_phys(info_va) }.ok_or(SystemError::EINVAL)?;
+
+        unsafe {
+            AP_START_INFO = ApStartInfo {
+                satp: riscv::register::satp::read().bits(),
+                stack,
+                va_pa_offset: entry_va.data() - entry_pa.data(),
+            };
+        }
+        compiler_fence(Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+
+        let r = sbi_rt::hart_start(hartid, entry_pa.data(), info_pa.data());
+        if r.is_err() {
+            warn!("smp: failed to start hart {}: {:?}", hartid, r);
+            return Err(SystemError::EIO);
+        }
         Ok(())
     }
 }
@@ -20,8 +20,10 @@ use crate::exception::IrqNumber;
 use crate::mm::percpu::PerCpu;
 use crate::smp::core::smp_get_processor_id;
 use crate::smp::cpu::ProcessorId;
+use crate::time::clockevents::{
+    clockevents_handle_event, clockevents_register_device, ClockEventDevice, ClockEventFeatures,
+};
 use crate::time::clocksource::HZ;
-use crate::time::tick_common::tick_handle_periodic;
 use alloc::string::ToString;
 use alloc::sync::Arc;
 pub use drop;
@@ -40,7 +42,6 @@ const APIC_CALIBRATE_MS: u64 = 10;
 static mut LOCAL_APIC_TIMERS: [RefCell<LocalApicTimer>; PerCpu::MAX_CPU_NUM as usize] =
     [const { RefCell::new(LocalApicTimer::new()) }; PerCpu::MAX_CPU_NUM as usize];
 
-#[allow(dead_code)]
 #[inline(always)]
 pub(super) fn local_apic_timer_instance(
     cpu_id: ProcessorId,
@@ -92,8 +93,70 @@ pub fn apic_timer_init() {
         )
         .expect("Apic timer init failed");
 
-    LocalApicTimerIntrController.install();
-    LocalApicTimerIntrController.enable();
+    let dev = Arc::new(LocalApicClockEvent {
+        cpu: smp_get_processor_id(),
+    });
+    clockevents_register_device(dev).expect("Apic timer init failed");
+}
+
+/// 本地APIC定时器作为时钟事件设备，每个CPU一个
+#[derive(Debug)]
+struct LocalApicClockEvent {
+    cpu: ProcessorId,
+}
+
+impl ClockEventDevice for LocalApicClockEvent {
+    fn name(&self) -> &str {
+        "lapic"
+    }
+
+    fn rating(&self) -> u32 {
+        100
+    }
+
+    fn features(&self) -> ClockEventFeatures {
+        ClockEventFeatures::PERIODIC | ClockEventFeatures::ONESHOT
+    }
+
+    fn freq(&self) -> u64 {
+        // 校准得到的initial_count是一个滴答的计数
+        local_apic_timer_instance(self.cpu).initial_count * HZ
+    }
+
+    fn set_state_periodic(&self) -> Result<(), SystemError> {
+        LocalApicTimerIntrController.install();
+        LocalApicTimerIntrController.enable();
+        Ok(())
+    }
+
+    fn set_state_oneshot(&self) -> Result<(), SystemError> {
+        let mut local_apic_timer = local_apic_timer_instance_mut(self.cpu);
+        let initial_count = local_apic_timer
+            .calibrate_initial_count()
+            .unwrap_or_else(LocalApicTimer::periodic_default_initial_count);
+        local_apic_timer.init(
+            LocalApicTimerMode::Oneshot,
+            initial_count,
+            LocalApicTimer::DIVISOR as u32,
+        );
+        Ok(())
+    }
+
+    fn set_state_shutdown(&self) -> Result<(), SystemError> {
+        LocalApicTimerIntrController.disable();
+        Ok(())
+    }
+
+    fn set_next_event(&self, delta: u64) -> Result<(), SystemError> {
+        let mut local_apic_timer = local_apic_timer_instance_mut(self.cpu);
+        if !matches!(local_apic_timer.mode, LocalApicTimerMode::Oneshot) {
+            return Err(SystemError::EINVAL);
+        }
+        // 写入initial count会启动一次计数，不修改记录的每个滴答的计数
+        CurrentApic.set_timer_initial_count(delta);
+        local_apic_timer.triggered = false;
+        Ok(())
+    }
 }
 
 /// 初始化本地APIC定时器的中断描述符
@@ -169,7 +232,6 @@ impl LocalApicTimerIntrController {
         local_apic_timer.start_current();
     }
 
-    #[allow(dead_code)]
     pub(super) fn disable(&self) {
         let cpu_id = smp_get_processor_id();
         let local_apic_timer = local_apic_timer_instance_mut(cpu_id);
@@ -280,7 +342,7 @@ impl LocalApicTimer {
         self.triggered = false;
         match mode {
             LocalApicTimerMode::Periodic => self.install_periodic_mode(initial_count, divisor),
-            LocalApicTimerMode::Oneshot => todo!(),
+            LocalApicTimerMode::Oneshot => self.install_oneshot_mode(initial_count, divisor),
             LocalApicTimerMode::Deadline => todo!(),
         }
     }
@@ -300,6 +362,22 @@ impl LocalApicTimer {
         self.set_initial_cnt(initial_count);
     }
 
+    /// 单次触发模式，`initial_count`记录一个滴答的计数，由调用者设置下一次中断的计数
+    fn install_oneshot_mode(&mut self, initial_count: u64, divisor: u32) {
+        debug!(
+            "install_oneshot_mode: initial_count = {}, divisor = {}",
+            initial_count, divisor
+        );
+        self.mode = LocalApicTimerMode::Oneshot;
+        self.initial_count = initial_count;
+        self.set_divisor(divisor);
+        self.setup_lvt(
+            APIC_TIMER_IRQ_NUM.data() as u8,
+            false,
+            LocalApicTimerMode::Oneshot,
+        );
+    }
+
     fn setup_lvt(&mut self, vector: u8, mask: bool, mode: LocalApicTimerMode) {
         let mode: u32 = mode as u32;
         let data = (mode << 17) | (vector as u32) | (if mask { 1 << 16 } else { 0 });
@@ -345,8 +423,14 @@ impl LocalApicTimer {
     }
 
     pub(super) fn handle_irq(trap_frame: &TrapFrame) -> Result<IrqReturn, SystemError> {
-        // sched_update_jiffies();
-        tick_handle_periodic(trap_frame);
+        let cpu_id = smp_get_processor_id();
+        if matches!(
+            local_apic_timer_instance(cpu_id).mode,
+            LocalApicTimerMode::Oneshot
+        ) {
+            local_apic_timer_instance_mut(cpu_id).triggered = true;
+        }
+        clockevents_handle_event(trap_frame);
         return Ok(IrqReturn::Handled);
     }
 }
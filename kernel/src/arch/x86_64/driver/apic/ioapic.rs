@@ -4,10 +4,11 @@ use acpi::madt::Madt;
 use alloc::sync::Arc;
 use bit_field::BitField;
 use bitflags::bitflags;
-use log::{debug, info};
+use log::{debug, info, warn};
 use system_error::SystemError;
 
 use crate::{
+    arch::mptable::mp_table,
     driver::acpi::acpi_manager,
     exception::{
         handle::{edge_irq_handler, fast_eoi_irq_handler},
@@ -70,32 +71,7 @@ impl IoApic {
         INIT_STATE.call_once(|| {
             info!("Initializing ioapic...");
 
-            // get ioapic base from acpi
-
-            let madt = acpi_manager()
-                .tables()
-                .unwrap()
-                .find_table::<Madt>()
-                .expect("IoApic::new(): failed to find MADT");
-
-            let io_apic_paddr = madt
-                .entries()
-                .find(|x| {
-                    if let acpi::madt::MadtEntry::IoApic(_x) = x {
-                        return true;
-                    }
-                    return false;
-                })
-                .and_then(|x| {
-                    if let acpi::madt::MadtEntry::IoApic(x) = x {
-                        Some(x.io_apic_address)
-                    } else {
-                        None
-                    }
-                })
-                .unwrap();
-
-            let phys_base = PhysAddr::new(io_apic_paddr as usize);
+            let phys_base = Self::find_phys_base();
 
             let mmio_guard = mmio_pool()
                 .create_mmio(0x1000)
@@ -130,6 +106,38 @@ impl IoApic {
         return result.unwrap();
     }
 
+    /// IO APIC的默认物理地址，MP表和ACPI都没有描述IO APIC时使用
+    const DEFAULT_PHYS_BASE: usize = 0xfec0_0000;
+
+    /// 从ACPI的MADT获取IO APIC的地址，没有ACPI时使用MP表
+    fn find_phys_base() -> PhysAddr {
+        if let Some(tables) = acpi_manager().tables() {
+            let madt = tables
+                .find_table::<Madt>()
+                .expect("IoApic::new(): failed to find MADT");
+
+            let io_apic_paddr = madt
+                .entries()
+                .find_map(|x| {
+                    if let acpi::madt::MadtEntry::IoApic(x) = x {
+                        Some(x.io_apic_address)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+            return PhysAddr::new(io_apic_paddr as usize);
+        }
+
+        match mp_table().and_then(|mp| mp.ioapic_paddr) {
+            Some(paddr) => paddr,
+            None => {
+                warn!("IoApic: no ACPI or MP table, using the default address");
+                PhysAddr::new(Self::DEFAULT_PHYS_BASE)
+            }
+        }
+    }
+
     /// Disable all interrupts.
     #[allow(dead_code)]
     pub fn disable_all(&mut self) {
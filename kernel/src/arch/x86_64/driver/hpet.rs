@@ -270,6 +270,24 @@ impl Hpet {
         return period;
     }
 
+    /// HPET主计数器的频率(Hz)
+    pub fn frequency(&self) -> u64 {
+        let (inner_guard, regs) = unsafe { self.hpet_regs() };
+        let freq = regs.frequency();
+
+        drop(inner_guard);
+        return freq;
+    }
+
+    /// HPET主计数器是否为64位
+    pub fn counter_is_64bit(&self) -> bool {
+        let (inner_guard, regs) = unsafe { self.hpet_regs() };
+        let r = regs.counter_is_64bit();
+
+        drop(inner_guard);
+        return r;
+    }
+
     /// 处理HPET的中断
     pub(super) fn handle_irq(&self, timer_num: u32) {
         if timer_num == 0 {
@@ -367,8 +385,8 @@ impl Hpet {
 }
 
 pub fn hpet_init() -> Result<(), SystemError> {
-    let hpet_info =
-        HpetInfo::new(acpi_manager().tables().unwrap()).map_err(|_| SystemError::ENODEV)?;
+    let hpet_info = HpetInfo::new(acpi_manager().tables().ok_or(SystemError::ENODEV)?)
+        .map_err(|_| SystemError::ENODEV)?;
 
     let hpet_instance = Hpet::new(hpet_info)?;
     unsafe {
@@ -1,5 +1,6 @@
 pub mod apic;
 pub mod hpet;
+pub mod pcspkr;
 pub mod rtc;
 pub mod tsc;
 pub mod video;
@@ -0,0 +1,41 @@
+//! PC喇叭
+//!
+//! PC喇叭由PIT的2号通道驱动：通道2工作在方波模式，并打开0x61端口的bit0（通道2的gate）
+//! 和bit1（喇叭使能）即可发声。
+//!
+//! 参考 Linux drivers/input/misc/pcspkr.c
+
+use crate::{
+    arch::{io::PortIOArch, CurrentPortIOArch},
+    libs::spinlock::SpinLock,
+    time::PIT_TICK_RATE,
+};
+
+const PIT_CH2_PORT: u16 = 0x42;
+const PIT_CMD_PORT: u16 = 0x43;
+const SPEAKER_PORT: u16 = 0x61;
+
+static PCSPKR_LOCK: SpinLock<()> = SpinLock::new(());
+
+/// 以`hz`的频率发声，`hz`为0时停止发声
+pub fn pcspkr_tone(hz: u32) {
+    let _guard = PCSPKR_LOCK.lock_irqsave();
+    unsafe {
+        if hz == 0 || hz as u64 >= PIT_TICK_RATE {
+            let v = CurrentPortIOArch::in8(SPEAKER_PORT) & !0x03;
+            CurrentPortIOArch::out8(SPEAKER_PORT, v);
+            return;
+        }
+
+        let count = (PIT_TICK_RATE / hz as u64) as u16;
+        // 通道2，先低后高字节，模式3（方波），二进制计数
+        CurrentPortIOArch::out8(PIT_CMD_PORT, 0xb6);
+        CurrentPortIOArch::out8(PIT_CH2_PORT, (count & 0xff) as u8);
+        CurrentPortIOArch::out8(PIT_CH2_PORT, (count >> 8) as u8);
+
+        let v = CurrentPortIOArch::in8(SPEAKER_PORT);
+        if v & 0x03 != 0x03 {
+            CurrentPortIOArch::out8(SPEAKER_PORT, v | 0x03);
+        }
+    }
+}
@@ -106,7 +106,17 @@ impl TSCManager {
             // TODO：使用cpuid指令查询或者读取msr寄存器或中相关信息或者使用 PIT 辅助计算的方式获取 tsc 频率，存放在 CPU_KHZ 中
             todo!("detect TSC and CPU frequency by cpuid or msr or pit");
         } else {
-            Self::set_cpu_khz(Self::calibrate_cpu_by_pit_hpet_pmtimer()?);
+            match Self::calibrate_cpu_by_pit_hpet_pmtimer() {
+                Ok(khz) => Self::set_cpu_khz(khz),
+                // 微虚拟机可能没有PIT、HPET和ACPI PM Timer，此时使用kvm-clock提供的频率
+                Err(e) if Self::tsc_khz() != 0 => {
+                    warn!(
+                        "Failed to calibrate TSC: {:?}, using the known TSC frequency",
+                        e
+                    );
+                }
+                Err(e) => return Err(e),
+            }
         }
 
         if Self::tsc_khz() == 0 {
@@ -3,13 +3,15 @@ use core::{
     sync::atomic::{compiler_fence, Ordering},
 };
 
-use log::debug;
+use log::{debug, warn};
 use system_error::SystemError;
 use x86::dtables::DescriptorTablePointer;
 
 use crate::{
     arch::{fpu::FpState, interrupt::trap::arch_trap_init, process::table::TSSManager},
-    driver::clocksource::{acpi_pm::init_acpi_pm_clocksource, kvm_clock},
+    driver::clocksource::{
+        acpi_pm::init_acpi_pm_clocksource, hpet::init_hpet_clocksource, kvm_clock,
+    },
     init::init::start_kernel,
     mm::{MemoryManagementArch, PhysAddr},
 };
@@ -81,6 +83,8 @@ unsafe extern "C" fn kernel_main(
 pub fn early_setup_arch() -> Result<(), SystemError> {
     // 初始化 XSAVE 支持（必须在任何 FPU 状态保存/恢复之前）
     FpState::init_xsave_support();
+    // 检测CET影子栈支持
+    crate::arch::process::shstk::shstk_cpu_init();
 
     let stack_start = unsafe { *(head_stack_start as *const u64) } as usize;
     debug!("head_stack_start={:#x}\n", stack_start);
@@ -118,6 +122,9 @@ pub fn setup_arch_post() -> Result<(), SystemError> {
     let ret = hpet_init();
     if ret.is_ok() {
         hpet_instance().hpet_enable().expect("hpet enable failed");
+        if let Err(e) = init_hpet_clocksource() {
+            warn!("hpet clocksource init failed: {:?}", e);
+        }
     } else if !kvmclock_ok {
         init_acpi_pm_clocksource().expect("acpi_pm_timer inits failed");
     }
@@ -132,6 +139,6 @@ fn set_current_core_tss(stack_start: usize, ist0: usize) {
         "set_current_core_tss: stack_start={:#x}, ist0={:#x}\n",
         stack_start, ist0
     );
-    current_tss.set_rsp(x86::Ring::Ring0, stack_start as u64);
+    unsafe { TSSManager::set_kernel_stack(stack_start) };
     current_tss.set_ist(0, ist0 as u64);
 }
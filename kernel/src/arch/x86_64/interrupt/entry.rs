@@ -46,6 +46,7 @@ macro_rules! interrupt_handler {
 
             #[unsafe(naked)]
             #[no_mangle]
+            #[link_section = ".entry.text"]
             unsafe extern "C" fn [<irq_handler $name>]() {
                 core::arch::naked_asm!(
                     concat!(
@@ -55,11 +56,8 @@ macro_rules! interrupt_handler {
                         save_all_regs!(),
                         "\n",
                         "
-                        mov rdi, rsp
-                        lea rax, ret_from_intr[rip]
-                        push rax
-                        mov rsi, {irqnum}
-                        jmp x86_64_do_irq
+                        mov qword ptr [rsp + 0x88], {irqnum}
+                        jmp irq_entry_common
                         "
                     ),
                     irqnum = const($name)
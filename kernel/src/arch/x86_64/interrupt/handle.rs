@@ -2,26 +2,23 @@ use core::intrinsics::likely;
 
 use crate::{
     arch::driver::apic::{apic_timer::APIC_TIMER_IRQ_NUM, CurrentApic, LocalAPIC},
-    exception::{irqdesc::irq_desc_manager, softirq::do_softirq, IrqNumber},
+    exception::{irqdesc::irq_desc_manager, softirq::do_softirq, HardirqGuard, IrqNumber},
     process::{
         utils::{current_pcb_flags, current_pcb_preempt_count},
         ProcessFlags,
     },
-    sched::{SchedMode, __schedule},
+    sched::{__schedule, SchedMode},
 };
 
 use super::TrapFrame;
 
 #[no_mangle]
 unsafe extern "C" fn x86_64_do_irq(trap_frame: &mut TrapFrame, vector: u32) {
-    // swapgs
-
-    if trap_frame.is_from_user() {
-        x86_64::registers::segmentation::GS::swap();
-    }
+    // 从用户态进入时，swapgs已经在irq_entry_common中完成
 
     // 由于x86上面，虚拟中断号与物理中断号是一一对应的，所以这里直接使用vector作为中断号来查询irqdesc
 
+    let hardirq_guard = HardirqGuard::enter();
     let desc = irq_desc_manager().lookup(IrqNumber::new(vector));
 
     if likely(desc.is_some()) {
@@ -35,6 +32,7 @@ unsafe extern "C" fn x86_64_do_irq(trap_frame: &mut TrapFrame, vector: u32) {
     } else {
         CurrentApic.send_eoi();
     }
+    drop(hardirq_guard);
 
     do_softirq();
 
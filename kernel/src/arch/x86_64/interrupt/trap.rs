@@ -10,8 +10,13 @@ use super::{
 use crate::exception::debug::DebugException;
 use crate::exception::ebreak::EBreak;
 use crate::{
-    arch::{CurrentIrqArch, MMArch},
+    arch::{ipc::signal::Signal, CurrentIrqArch, MMArch},
+    debug::{
+        oops::oops,
+        taint::{add_taint, TaintFlags},
+    },
     exception::InterruptArch,
+    ipc::kill::send_signal_to_pid,
     mm::VirtAddr,
     process::ProcessManager,
     smp::core::smp_get_processor_id,
@@ -38,6 +43,7 @@ extern "C" {
     fn trap_machine_check();
     fn trap_SIMD_exception();
     fn trap_virtualization_exception();
+    fn trap_control_protection();
 }
 
 bitflags! {
@@ -108,10 +114,22 @@ pub fn arch_trap_init() -> Result<(), SystemError> {
         set_intr_gate(18, 0, VirtAddr::new(trap_machine_check as usize));
         set_intr_gate(19, 0, VirtAddr::new(trap_SIMD_exception as usize));
         set_intr_gate(20, 0, VirtAddr::new(trap_virtualization_exception as usize));
+        set_intr_gate(21, 0, VirtAddr::new(trap_control_protection as usize));
     }
     return Ok(());
 }
 
+/// 处理可以归咎于当前进程的异常
+///
+/// 在用户态发生时向当前进程发送`sig`，在内核态发生时按oops处理，参见[`oops`]
+fn do_error_trap(regs: &TrapFrame, sig: Signal, reason: &str) {
+    if regs.is_from_user() {
+        let _ = send_signal_to_pid(ProcessManager::current_pcb().raw_pid(), sig);
+        return;
+    }
+    oops(reason);
+}
+
 /// 处理除法错误 0 #DE
 #[no_mangle]
 unsafe extern "C" fn do_divide_error(regs: &'static TrapFrame, error_code: u64) {
@@ -123,7 +141,7 @@ unsafe extern "C" fn do_divide_error(regs: &'static TrapFrame, error_code: u64)
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("Divide Error");
+    do_error_trap(regs, Signal::SIGFPE, "Divide Error");
 }
 
 /// 处理调试异常 1 #DB
@@ -179,7 +197,7 @@ unsafe extern "C" fn do_overflow(regs: &'static TrapFrame, error_code: u64) {
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("Overflow Exception");
+    do_error_trap(regs, Signal::SIGSEGV, "Overflow Exception");
 }
 
 /// 处理BOUND指令检查异常 5 #BR
@@ -193,7 +211,7 @@ unsafe extern "C" fn do_bounds(regs: &'static TrapFrame, error_code: u64) {
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("Bounds Check");
+    do_error_trap(regs, Signal::SIGSEGV, "Bounds Check");
 }
 
 /// 处理未定义操作码异常 6 #UD
@@ -207,7 +225,7 @@ unsafe extern "C" fn do_undefined_opcode(regs: &'static TrapFrame, error_code: u
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("Undefined Opcode");
+    do_error_trap(regs, Signal::SIGILL, "Undefined Opcode");
 }
 
 /// 处理设备不可用异常(FPU不存在) 7 #NM
@@ -221,7 +239,7 @@ unsafe extern "C" fn do_dev_not_avaliable(regs: &'static TrapFrame, error_code:
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("Device Not Available");
+    do_error_trap(regs, Signal::SIGSEGV, "Device Not Available");
 }
 
 /// 处理双重错误 8 #DF
@@ -299,7 +317,7 @@ unsafe extern "C" fn do_segment_not_exists(regs: &'static TrapFrame, error_code:
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("Segment Not Exists");
+    do_error_trap(regs, Signal::SIGBUS, "Segment Not Exists");
 }
 
 /// 处理栈段错误 12 #SS
@@ -313,7 +331,7 @@ unsafe extern "C" fn do_stack_segment_fault(regs: &'static TrapFrame, error_code
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("Stack Segment Fault");
+    do_error_trap(regs, Signal::SIGBUS, "Stack Segment Fault");
 }
 
 /// 处理一般保护异常 13 #GP
@@ -360,7 +378,7 @@ Segment Selector Index: {:#x}\n
         msg1, msg2, msg3,
         error_code & 0xfff8
     );
-    panic!("General Protection");
+    do_error_trap(regs, Signal::SIGSEGV, "General Protection");
 }
 
 /// 处理页错误 14 #PF
@@ -442,7 +460,7 @@ unsafe extern "C" fn do_x87_FPU_error(regs: &'static TrapFrame, error_code: u64)
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("x87 FPU Error");
+    do_error_trap(regs, Signal::SIGFPE, "x87 FPU Error");
 }
 
 /// 处理对齐检查 17 #AC
@@ -456,7 +474,7 @@ unsafe extern "C" fn do_alignment_check(regs: &'static TrapFrame, error_code: u6
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("Alignment Check");
+    do_error_trap(regs, Signal::SIGBUS, "Alignment Check");
 }
 
 /// 处理机器检查 18 #MC
@@ -470,6 +488,7 @@ unsafe extern "C" fn do_machine_check(regs: &'static TrapFrame, error_code: u64)
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
+    add_taint(TaintFlags::MACHINE_CHECK);
     panic!("Machine Check");
 }
 
@@ -484,7 +503,7 @@ unsafe extern "C" fn do_SIMD_exception(regs: &'static TrapFrame, error_code: u64
         smp_get_processor_id().data(),
         ProcessManager::current_pid()
     );
-    panic!("SIMD Exception");
+    do_error_trap(regs, Signal::SIGFPE, "SIMD Exception");
 }
 
 /// 处理虚拟化异常 20 #VE
@@ -501,6 +520,47 @@ unsafe extern "C" fn do_virtualization_exception(regs: &'static TrapFrame, error
     panic!("Virtualization Exception");
 }
 
+/// 处理控制流保护异常 21 #CP
+///
+/// 用户态影子栈上的返回地址与栈上的不一致时产生，向进程发送SIGSEGV
+#[no_mangle]
+unsafe extern "C" fn do_control_protection(regs: &'static TrapFrame, error_code: u64) {
+    const CP_ERR_MSG: [&str; 6] = [
+        "unknown",
+        "near ret",
+        "far/iret",
+        "endbranch",
+        "rstorssp",
+        "setssbsy",
+    ];
+    let msg = CP_ERR_MSG
+        .get((error_code & 0x7fff) as usize)
+        .unwrap_or(&CP_ERR_MSG[0]);
+
+    if !regs.is_from_user() {
+        error!(
+            "do_control_protection(21), \tError code: {:#x} ({}),\trsp: {:#x},\trip: {:#x},\t CPU: {}, \tpid: {:?}",
+            error_code,
+            msg,
+            regs.rsp,
+            regs.rip,
+            smp_get_processor_id().data(),
+            ProcessManager::current_pid()
+        );
+        oops("Control Protection Fault");
+    }
+
+    warn!(
+        "pid {:?} control protection fault ({}), rip: {:#x}, rsp: {:#x}, error code: {:#x}",
+        ProcessManager::current_pid(),
+        msg,
+        regs.rip,
+        regs.rsp,
+        error_code
+    );
+    let _ = send_signal_to_pid(ProcessManager::current_pcb().raw_pid(), Signal::SIGSEGV);
+}
+
 #[no_mangle]
 unsafe extern "C" fn ignore_int_handler(_regs: &'static TrapFrame, _error_code: u64) {
     warn!("Unknown interrupt.");
@@ -16,7 +16,10 @@ use crate::{
     arch::{
         fpu::FpState,
         interrupt::TrapFrame,
-        process::table::{USER_CS, USER_DS},
+        process::{
+            shstk::{restore_signal_shadow_stack, setup_signal_shadow_stack},
+            table::{USER_CS, USER_DS},
+        },
         syscall::nr::SYS_RESTART_SYSCALL,
         CurrentIrqArch, MMArch,
     },
@@ -775,6 +778,16 @@ impl SignalArch for X86_64SignalArch {
 
         let frame = unsafe { &*frame_ptr };
 
+        // 弹出影子栈上的信号帧，失败说明影子栈被篡改
+        if restore_signal_shadow_stack().is_err() {
+            error!("sys_rt_sigreturn: bad shadow stack frame");
+            let _ = crate::ipc::kill::send_signal_to_pid(
+                ProcessManager::current_pcb().raw_pid(),
+                Signal::SIGSEGV,
+            );
+            return trap_frame.rax;
+        }
+
         // 1. 恢复信号掩码（从 1024-bit 用户态格式转换到 64-bit 内核格式）
         let mut sigmask = frame.ucontext.uc_sigmask.to_kernel_sigset();
         set_current_blocked(&mut sigmask);
@@ -932,6 +945,16 @@ fn setup_frame(
         SystemError::EFAULT
     })?;
 
+    // 启用了影子栈时，在影子栈上压入信号帧和restorer地址
+    setup_signal_shadow_stack(ret_code_ptr as usize).map_err(|_| {
+        error!("In setup_frame: failed to setup shadow stack frame");
+        let _ = crate::ipc::kill::send_signal_to_pid(
+            ProcessManager::current_pcb().raw_pid(),
+            Signal::SIGSEGV,
+        );
+        SystemError::EFAULT
+    })?;
+
     // 获取栈帧的可变引用（唯一需要 unsafe 的地方）
     let frame = unsafe { &mut *frame_ptr };
 
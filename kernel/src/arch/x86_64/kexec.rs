@@ -1,5 +1,6 @@
 #![allow(function_casts_as_integer)]
 
+use crate::arch::mm::protect::set_memory_x;
 use crate::arch::MMArch;
 use crate::init::boot_params;
 use crate::init::kexec::Kimage;
@@ -7,7 +8,7 @@ use crate::libs::spinlock::SpinLock;
 use crate::mm::ident_map::{ident_map_page, ident_map_pages, ident_pt_alloc};
 use crate::mm::kernel_mapper::KernelMapper;
 use crate::mm::MemoryManagementArch;
-use crate::mm::{page::EntryFlags, PhysAddr};
+use crate::mm::{page::EntryFlags, PhysAddr, VirtAddr};
 use alloc::rc::Rc;
 use core::mem::transmute;
 use system_error::SystemError;
@@ -157,6 +158,9 @@ pub fn machine_kexec(kimage: Rc<SpinLock<Kimage>>) {
     let relocate_kernel_ptr: usize =
         control_page_virt + relocate_kernel as usize - __relocate_kernel_start as usize;
 
+    // 初始化完成之后直接映射区是不可执行的
+    unsafe { set_memory_x(VirtAddr::new(control_page_virt), 1) };
+
     let relocate_kernel_func: RelocateKernelFn = unsafe { transmute(relocate_kernel_ptr) };
 
     let arg1 = kimage.lock().head;
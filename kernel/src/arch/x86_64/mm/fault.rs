@@ -1,5 +1,5 @@
 use alloc::sync::Arc;
-use core::{intrinsics::unlikely, panic};
+use core::{cell::Cell, intrinsics::unlikely, panic};
 use log::error;
 use x86::{bits64::rflags::RFlags, controlregs::Cr4};
 
@@ -10,6 +10,7 @@ use crate::{
         mm::{MemoryManagementArch, X86_64MMArch},
         CurrentIrqArch, MMArch,
     },
+    debug::oops::oops,
     exception::{extable::ExceptionTableManager, InterruptArch},
     ipc::signal_types::{SigCode, SigInfo, SigType},
     mm::{
@@ -37,6 +38,12 @@ impl X86_64MMArch {
             return true;
         }
 
+        // 影子栈访问只能落在影子栈VMA中；影子栈VMA没有VM_WRITE，
+        // 所以普通写入会在下面的权限检查中被拒绝
+        if error_code.contains(X86PfErrorCode::X86_PF_SHSTK) {
+            return !vm_flags.contains(VmFlags::VM_SHADOW_STACK);
+        }
+
         if !Self::vma_access_permitted(
             vma.clone(),
             error_code.contains(X86PfErrorCode::X86_PF_WRITE),
@@ -124,11 +131,9 @@ impl X86_64MMArch {
         );
     }
 
-    pub fn page_fault_oops(regs: &TrapFrame, error_code: X86PfErrorCode, address: VirtAddr) {
-        if regs.is_from_user() {
-            Self::show_fault_oops(regs, error_code, address);
-        }
-        panic!()
+    pub fn page_fault_oops(regs: &TrapFrame, error_code: X86PfErrorCode, address: VirtAddr) -> ! {
+        Self::show_fault_oops(regs, error_code, address);
+        oops("Page Fault")
     }
 
     /// 内核态缺页异常处理
@@ -156,27 +161,24 @@ impl X86_64MMArch {
             if address.data() >= guard_page.data()
                 && address.data() < guard_page.data() + guard_page_size
             {
-                // 发生在内核栈保护页上
+                // 发生在内核栈保护页上，栈已经用完，无法再结束当前进程
                 error!(
                     "kernel stack guard page fault at {:#x}, guard page range: {:#x} - {:#x}",
                     address.data(),
                     guard_page.data(),
                     guard_page.data() + guard_page_size
                 );
+                panic!("kernel stack overflow, pid: {}", pcb.raw_pid().data());
             }
         }
-        panic!(
-            "do_kern_addr_fault has not yet been implemented, 
-        fault address: {:#x},
-        rip: {:#x},
-        error_code: {:#b}, 
-        pid: {}\n",
+        error!(
+            "BUG: unable to handle page fault for address: {:#x}, rip: {:#x}, pid: {}",
             address.data(),
             regs.rip,
-            error_code,
             pcb.raw_pid().data()
         );
-        //TODO https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/mm/fault.c#do_kern_addr_fault
+        drop(pcb);
+        Self::page_fault_oops(regs, error_code, address);
     }
 
     /// 尝试使用异常表修复页错误
@@ -223,6 +225,24 @@ impl X86_64MMArch {
         regs: &'static mut TrapFrame,
         error_code: X86PfErrorCode,
         address: VirtAddr,
+    ) {
+        let kernel_bug = Cell::new(false);
+        Self::__do_user_addr_fault(regs, error_code, address, &kernel_bug);
+        if kernel_bug.get() {
+            // 此时已经释放了地址空间的锁，可以结束当前进程
+            oops("Illegal user space access from kernel");
+        }
+    }
+
+    /// 用户态缺页异常处理的实现
+    ///
+    /// 内核代码访问用户地址失败、又没有异常表修复时，设置`kernel_bug`并返回，
+    /// 由调用者在释放地址空间的锁之后按oops处理
+    unsafe fn __do_user_addr_fault(
+        regs: &'static mut TrapFrame,
+        error_code: X86PfErrorCode,
+        address: VirtAddr,
+        kernel_bug: &Cell<bool>,
     ) {
         // log::debug!("fault at {:?}:{:?}",
         // address,
@@ -304,7 +324,8 @@ impl X86_64MMArch {
                     r.rip,
                     address.data()
                 );
-                panic!("Illegal user space access from kernel");
+                kernel_bug.set(true);
+                return true;
             }
             false // 不是内核访问，继续正常流程
         };
@@ -426,9 +447,10 @@ impl X86_64MMArch {
                 return;
             }
             let mapper = &mut space_guard.user_mapper.utable;
-            let message = PageFaultMessage::new(vma.clone(), address, flags, mapper);
+            let mut message = PageFaultMessage::new(vma.clone(), address, flags, mapper);
 
-            fault = PageFaultHandler::handle_mm_fault(message);
+            fault = PageFaultHandler::handle_mm_fault(&mut message);
+            let retry_io = message.take_retry_io();
 
             if fault.contains(VmFaultReason::VM_FAULT_COMPLETED) {
                 return;
@@ -436,6 +458,20 @@ impl X86_64MMArch {
 
             if unlikely(fault.contains(VmFaultReason::VM_FAULT_RETRY)) {
                 flags |= FaultFlags::FAULT_FLAG_TRIED;
+                if let Some(retry_io) = retry_io {
+                    // 读入文件页期间不持有地址空间锁，同一进程的其他线程可以继续处理缺页
+                    drop(space_guard);
+                    retry_io.run();
+
+                    // 用户态缺页在等待I/O期间收到致命信号时不再重试，返回用户态处理信号
+                    if regs.is_from_user()
+                        && flags.contains(FaultFlags::FAULT_FLAG_KILLABLE)
+                        && Signal::fatal_signal_pending(&ProcessManager::current_pcb())
+                    {
+                        return;
+                    }
+                    space_guard = current_address_space.write();
+                }
             } else {
                 break;
             }
@@ -454,12 +490,14 @@ impl X86_64MMArch {
                 if Self::try_fixup_exception(regs, error_code, address) {
                     return;
                 }
-                panic!(
+                error!(
                     "kernel access to user addr failed without fixup, fault: {:?}, addr: {:#x}, rip: {:#x}",
                     fault,
                     address.data(),
                     regs.rip
                 );
+                kernel_bug.set(true);
+                return;
             }
 
             // 用户态 fault：发送对应信号
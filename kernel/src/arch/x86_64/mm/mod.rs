@@ -4,6 +4,8 @@ pub mod barrier;
 pub mod bump;
 pub mod fault;
 pub mod pkru;
+pub mod protect;
+pub mod pti;
 
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -12,6 +14,7 @@ use x86_64::registers::model_specific::EferFlags;
 
 use crate::driver::serial::serial8250::send_to_default_serial8250_port;
 
+use crate::filesystem::pstore::ram::ramoops_early_reserve;
 use crate::init::boot::boot_callbacks;
 use crate::libs::align::page_align_up;
 use crate::libs::lib_ui::screen_manager::scm_disable_put_to_window;
@@ -19,6 +22,7 @@ use crate::libs::spinlock::SpinLock;
 
 use crate::mm::allocator::page_frame::{FrameAllocator, PageFrameCount, PageFrameUsage};
 use crate::mm::memblock::mem_block_manager;
+use crate::mm::memtest;
 use crate::mm::ucontext::LockedVMA;
 use crate::{
     arch::MMArch,
@@ -113,6 +117,8 @@ impl MemoryManagementArch for X86_64MMArch {
     const ENTRY_FLAG_DIRTY: usize = 1 << 6;
     const ENTRY_FLAG_HUGE_PAGE: usize = 1 << 7;
     const ENTRY_FLAG_GLOBAL: usize = 1 << 8;
+    /// 使用软件可用的第11位
+    const ENTRY_FLAG_SOFT_DIRTY: usize = 1 << 11;
 
     /// 物理地址与虚拟地址的偏移量
     /// 0xffff_8000_0000_0000
@@ -164,6 +170,7 @@ impl MemoryManagementArch for X86_64MMArch {
         boot_callbacks()
             .early_init_memory_blocks()
             .expect("init memory area failed");
+        ramoops_early_reserve();
         debug!("bootstrap info: {:#x?}", unsafe { BOOTSTRAP_MM_INFO });
         debug!("phys[0]=virt[0x{:x}]", unsafe {
             MMArch::phys_2_virt(PhysAddr::new(0)).unwrap().data()
@@ -175,11 +182,34 @@ impl MemoryManagementArch for X86_64MMArch {
         send_to_default_serial8250_port("x86 64 mm init done\n\0".as_bytes());
     }
 
+    unsafe fn arch_post_init() {
+        // 必须在创建第一个用户地址空间之前完成
+        pti::pti_init();
+    }
+
+    unsafe fn free_initmem() {
+        protect::free_initmem();
+    }
+
+    unsafe fn mark_rodata_ro() {
+        protect::mark_rodata_ro();
+    }
+
+    unsafe fn text_poke(addr: VirtAddr, data: &[u8]) -> Result<(), SystemError> {
+        protect::text_poke(addr, data)
+    }
+
+    fn with_kernel_text_writable<R>(f: impl FnOnce() -> R) -> R {
+        protect::with_kernel_text_writable(f)
+    }
+
     /// @brief 刷新TLB中，关于指定虚拟地址的条目
     unsafe fn invalidate_page(address: VirtAddr) {
         compiler_fence(Ordering::SeqCst);
         asm!("invlpg [{0}]", in(reg) address.data(), options(nostack, preserves_flags));
         compiler_fence(Ordering::SeqCst);
+        // invlpg只刷新当前（内核态）PCID
+        pti::mark_user_tlb_stale();
     }
 
     /// @brief 刷新TLB中，所有的条目
@@ -210,6 +240,8 @@ impl MemoryManagementArch for X86_64MMArch {
         compiler_fence(Ordering::SeqCst);
         asm!("mov cr3, {}", in(reg) table.data(), options(nostack, preserves_flags));
         compiler_fence(Ordering::SeqCst);
+        // 写CR3只刷新内核态PCID
+        pti::mark_user_tlb_stale();
     }
 
     /// @brief 判断虚拟地址是否合法
@@ -231,8 +263,13 @@ impl MemoryManagementArch for X86_64MMArch {
     /// @return 新的页表
     fn setup_new_usermapper() -> Result<crate::mm::ucontext::UserMapper, SystemError> {
         let new_umapper: crate::mm::page::PageMapper<X86_64MMArch, LockedFrameAllocator> = unsafe {
-            PageMapper::create(PageTableKind::User, LockedFrameAllocator)
-                .ok_or(SystemError::ENOMEM)?
+            if pti::pti_enabled() {
+                let table = pti::alloc_user_pgd_pair().ok_or(SystemError::ENOMEM)?;
+                PageMapper::new(PageTableKind::User, table, LockedFrameAllocator)
+            } else {
+                PageMapper::create(PageTableKind::User, LockedFrameAllocator)
+                    .ok_or(SystemError::ENOMEM)?
+            }
         };
 
         let current_ktable: KernelMapper = KernelMapper::lock();
@@ -252,6 +289,17 @@ impl MemoryManagementArch for X86_64MMArch {
         return Ok(crate::mm::ucontext::UserMapper::new(new_umapper));
     }
 
+    unsafe fn free_usermapper_table(table: PhysAddr) {
+        if !pti::free_user_pgd_pair(table) {
+            LockedFrameAllocator.free(table, PageFrameCount::new(1));
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn top_level_entry_updated(table: PhysAddr, index: usize, entry: usize) {
+        pti::sync_user_pgd_entry(table, index, entry);
+    }
+
     const PAGE_SIZE: usize = 1 << Self::PAGE_SHIFT;
 
     const PAGE_OFFSET_MASK: usize = Self::PAGE_SIZE - 1;
@@ -381,6 +429,8 @@ impl MemoryManagementArch for X86_64MMArch {
             let mut cr0_val = cr0();
             cr0_val.insert(Cr0::CR0_WRITE_PROTECT);
             cr0_write(cr0_val);
+            // CR4.CET依赖CR0.WP，写保护打开后才能重新打开CET
+            crate::arch::process::shstk::cet_cr4_set(true);
             // log::debug!("CR0.WP bit enabled for kernel write protection");
         }
     }
@@ -389,6 +439,8 @@ impl MemoryManagementArch for X86_64MMArch {
     fn disable_kernel_wp() {
         unsafe {
             use x86::controlregs::{cr0, cr0_write, Cr0};
+            // CR4.CET为1时不能清除CR0.WP
+            crate::arch::process::shstk::cet_cr4_set(false);
             let mut cr0_val = cr0();
             cr0_val.remove(Cr0::CR0_WRITE_PROTECT);
             cr0_write(cr0_val);
@@ -568,6 +620,7 @@ impl VirtAddr {
     }
 }
 
+#[link_section = ".init.text"]
 unsafe fn allocator_init() {
     let virt_offset = VirtAddr::new(page_align_up(BOOTSTRAP_MM_INFO.unwrap().start_brk));
 
@@ -638,6 +691,21 @@ unsafe fn allocator_init() {
         bump_allocator.offset() / 1024
     );
 
+    // 启动时内存测试需要访问所有的物理内存，因此要提前切换到新页表。
+    // head.S中建立的帧缓冲区映射在新页表中不存在，所以要先关闭显示输出
+    if memtest::memtest_passes() != 0 {
+        scm_disable_put_to_window();
+        compiler_fence(Ordering::SeqCst);
+        crate::mm::page::PageMapper::<MMArch, _>::new(
+            PageTableKind::Kernel,
+            new_page_table,
+            &mut bump_allocator,
+        )
+        .make_current();
+        compiler_fence(Ordering::SeqCst);
+        unsafe { memtest::early_memtest(PhysAddr::new(bump_allocator.offset())) };
+    }
+
     // 初始化buddy_allocator
     let buddy_allocator = unsafe { BuddyAllocator::<X86_64MMArch>::new(bump_allocator).unwrap() };
     // 设置全局的页帧分配器
@@ -0,0 +1,223 @@
+//! 内核映射的写保护与W^X
+//!
+//! 启动阶段内核镜像以及整个直接映射区都是可读、可写、可执行的。initcall执行完毕之后：
+//! - [`free_initmem`]释放`.init.text`段（只在启动阶段使用的代码）；
+//! - [`mark_rodata_ro`]把`.text`设置为只读、可执行，`.rodata`设置为只读、不可执行，
+//!   直接映射区中的其他页都设置为不可执行。
+//!
+//! 此后修改内核代码（kprobe等调试功能）需要通过[`text_poke`]，它临时建立一个可写、不可执行的别名映射，
+//! 写完之后立即取消。对于直接修改代码的第三方库（static keys），使用[`with_kernel_text_writable`]。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/mm/init_64.c#1368
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use log::info;
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    libs::{
+        align::{page_align_down, page_align_up},
+        spinlock::SpinLock,
+    },
+    mm::{
+        allocator::page_frame::{FrameAllocator, PageFrameCount},
+        kernel_mapper::KernelMapper,
+        memblock::mem_block_manager,
+        mmio_buddy::mmio_pool,
+        page::{EntryFlags, InactiveFlusher, PageFlushAll},
+        MemoryManagementArch, VirtAddr,
+    },
+};
+
+use super::{LockedFrameAllocator, BOOTSTRAP_MM_INFO};
+
+extern "C" {
+    fn __init_begin();
+    fn __init_end();
+}
+
+/// 内核代码是否已经被设置为只读
+static KERNEL_TEXT_RO: AtomicBool = AtomicBool::new(false);
+
+/// 串行化对内核代码的修改
+static TEXT_POKE_LOCK: SpinLock<()> = SpinLock::new(());
+
+/// 内核代码是否已经被设置为只读
+pub fn kernel_text_ro() -> bool {
+    KERNEL_TEXT_RO.load(Ordering::Acquire)
+}
+
+/// 刷新所有CPU上的TLB
+fn flush_tlb_all() {
+    PageFlushAll::<MMArch>::new().flush();
+    drop(InactiveFlusher::new());
+}
+
+/// 修改直接映射区中`range`内每一页的读写、执行权限，保留其他标志位（例如缓存策略）
+unsafe fn set_range_flags(range: Range<usize>, write: bool, execute: bool) {
+    let mut kernel_mapper = KernelMapper::lock();
+    let mapper = kernel_mapper
+        .as_mut()
+        .expect("kernel mapper is locked recursively");
+    for vaddr in range.step_by(MMArch::PAGE_SIZE) {
+        let vaddr = VirtAddr::new(vaddr);
+        if let Some((_, flags)) = mapper.translate(vaddr) {
+            if let Some(flush) = mapper.remap(vaddr, flags.set_write(write).set_execute(execute)) {
+                flush.ignore();
+            }
+        }
+    }
+}
+
+/// # 功能
+///
+/// 释放`.init.text`段。调用之后不能再调用被放入该段的函数
+pub unsafe fn free_initmem() {
+    let start = page_align_down(__init_begin as usize);
+    let end = page_align_up(__init_end as usize);
+    if start >= end {
+        return;
+    }
+
+    // 先变成普通的数据页，再交给伙伴分配器
+    set_range_flags(start..end, true, false);
+    flush_tlb_all();
+
+    for vaddr in (start..end).step_by(MMArch::PAGE_SIZE) {
+        let paddr = MMArch::virt_2_phys(VirtAddr::new(vaddr)).unwrap();
+        LockedFrameAllocator.free(paddr, PageFrameCount::new(1));
+    }
+    info!(
+        "Freeing unused kernel image (initmem) memory: {}K",
+        (end - start) / 1024
+    );
+}
+
+/// # 功能
+///
+/// 把内核代码设置为只读、可执行，只读数据设置为只读、不可执行，直接映射区中的其他页设置为不可执行
+pub unsafe fn mark_rodata_ro() {
+    let info = BOOTSTRAP_MM_INFO.unwrap();
+    let text = page_align_down(info.kernel_code_start)..page_align_up(info.kernel_code_end);
+    let rodata = page_align_down(info.kernel_rodata_start)..page_align_up(info.kernel_rodata_end);
+
+    let total_num = mem_block_manager().total_initial_memory_regions();
+    for i in 0..total_num {
+        let area = mem_block_manager().get_initial_memory_region(i).unwrap();
+        let start = MMArch::phys_2_virt(area.base).unwrap().data();
+        let end = page_align_up(start + area.size);
+        let mut cur = page_align_down(start);
+        while cur < end {
+            // 把区域按与内核代码、只读数据的交界切分
+            let (next, write, execute) = if text.contains(&cur) {
+                (text.end, false, true)
+            } else if rodata.contains(&cur) {
+                (rodata.end, false, false)
+            } else {
+                let mut next = end;
+                for boundary in [text.start, rodata.start] {
+                    if boundary > cur && boundary < next {
+                        next = boundary;
+                    }
+                }
+                (next, true, false)
+            };
+            let next = next.min(end);
+            set_range_flags(cur..next, write, execute);
+            cur = next;
+        }
+    }
+    flush_tlb_all();
+    KERNEL_TEXT_RO.store(true, Ordering::Release);
+
+    info!(
+        "Write protecting the kernel text: {}K, read-only data: {}K",
+        (text.end - text.start) / 1024,
+        (rodata.end - rodata.start) / 1024
+    );
+}
+
+/// # 功能
+///
+/// 把直接映射区中从`addr`开始的`count`页设置为可读、可写、可执行。
+///
+/// 只用于即将离开当前内核的场景（kexec的控制页），其他情况不应破坏W^X
+pub unsafe fn set_memory_x(addr: VirtAddr, count: usize) {
+    let start = page_align_down(addr.data());
+    set_range_flags(start..start + count * MMArch::PAGE_SIZE, true, true);
+    flush_tlb_all();
+}
+
+/// # 功能
+///
+/// 通过临时的别名映射修改内核代码，用于kprobe等合法的代码修改。
+///
+/// 内核代码还没有被设置为只读时直接写入。
+///
+/// ## 参数
+/// - `addr`: 被修改的代码的地址
+/// - `data`: 写入的内容，长度不能超过一页
+///
+/// ## 返回值
+/// - `Ok(())`: 成功
+/// - `Err(SystemError::EINVAL)`: `data`太长
+/// - `Err(SystemError::EFAULT)`: `addr`不在直接映射区中
+///
+/// ## 安全性
+///
+/// 调用者需要保证其他CPU不会执行到正在被修改的指令，或者修改本身是原子的（例如写入单字节的int3）
+pub unsafe fn text_poke(addr: VirtAddr, data: &[u8]) -> Result<(), SystemError> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    if data.len() > MMArch::PAGE_SIZE {
+        return Err(SystemError::EINVAL);
+    }
+    if !kernel_text_ro() {
+        core::ptr::copy(data.as_ptr(), addr.data() as *mut u8, data.len());
+        core::arch::x86_64::_mm_mfence();
+        return Ok(());
+    }
+
+    let page = page_align_down(addr.data());
+    let offset = addr.data() - page;
+    // 直接映射区中虚拟地址连续的页，物理地址也是连续的
+    let paddr = MMArch::virt_2_phys(VirtAddr::new(page)).ok_or(SystemError::EFAULT)?;
+    let len = page_align_up(offset + data.len());
+
+    let _guard = TEXT_POKE_LOCK.lock_irqsave();
+    let alias = mmio_pool().create_mmio(2 * MMArch::PAGE_SIZE)?;
+    alias.map_phys_with_flags(paddr, len, EntryFlags::new().set_write(true))?;
+    core::ptr::copy(
+        data.as_ptr(),
+        (alias.vaddr() + offset).data() as *mut u8,
+        data.len(),
+    );
+    core::arch::x86_64::_mm_mfence();
+    // 释放时取消别名映射
+    drop(alias);
+    Ok(())
+}
+
+/// # 功能
+///
+/// 在当前CPU上临时允许写入只读的内核代码，执行`f`。
+///
+/// 仅用于在原地修改代码、无法改用[`text_poke`]的第三方库（例如static keys）。
+/// 执行期间关闭中断并清除CR0.WP，只对当前CPU生效
+pub fn with_kernel_text_writable<R>(f: impl FnOnce() -> R) -> R {
+    if !kernel_text_ro() {
+        return f();
+    }
+    // 持有锁期间中断是关闭的，不会切换到其他任务
+    let _guard = TEXT_POKE_LOCK.lock_irqsave();
+    MMArch::disable_kernel_wp();
+    let r = f();
+    MMArch::enable_kernel_wp();
+    r
+}
@@ -0,0 +1,307 @@
+//! 内核页表隔离（Kernel Page Table Isolation, KPTI）
+//!
+//! 用于缓解Meltdown一类的漏洞。开启后，每个进程的顶级页表由两个相邻的页组成（8K对齐）：
+//! - 低地址的页是内核态页表，与原来一样包含用户空间和整个内核空间的映射；
+//! - 高地址的页是用户态页表，用户空间部分与内核态页表共用下级页表，
+//!   内核空间部分来自一个全局的模板页表，只映射入口代码、GDT/IDT/TSS以及每个CPU的入口区域。
+//!
+//! 入口代码（`asm/entry.S`）在用户态与内核态之间切换时翻转CR3的第12位。
+//! CPU支持PCID时，用户态使用另一个PCID（第11位为1），切换页表时不需要刷新TLB；
+//! 内核修改页表之后，会在下一次返回用户态时刷新用户态PCID的TLB。
+//!
+//! 命令行参数`pti=off`关闭KPTI，`pti=on`强制开启，默认在CPU受Meltdown影响时开启。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/mm/pti.c
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use alloc::collections::BTreeSet;
+use log::{info, warn};
+use x86::{
+    controlregs::{cr4, cr4_write, Cr4},
+    cpuid::{cpuid, CpuId},
+    dtables::{sgdt, sidt, DescriptorTablePointer},
+    msr::rdmsr,
+};
+
+use crate::{
+    arch::{
+        process::{entry_area::CpuEntryArea, table::TSSManager},
+        MMArch,
+    },
+    init::cmdline::KernelCmdlineEarlyKV,
+    libs::{
+        align::{page_align_down, page_align_up},
+        spinlock::SpinLock,
+    },
+    mm::{
+        allocator::page_frame::{FrameAllocator, PageFrameCount},
+        page::{EntryFlags, PageTable},
+        MemoryManagementArch, PageTableKind, PhysAddr, VirtAddr,
+    },
+};
+
+use super::{LockedFrameAllocator, PageMapper};
+
+kernel_cmdline_param_early_kv!(PTI_PARAM, pti, "");
+
+/// CR3中用于选择用户态页表的位
+pub const PTI_USER_PGTABLE_BIT: usize = 1 << 12;
+/// CR3中用于选择用户态PCID的位
+pub const PTI_USER_PCID_BIT: usize = 1 << 11;
+/// 写CR3时不刷新TLB
+const X86_CR3_PCID_NOFLUSH: usize = 1 << 63;
+
+const MSR_IA32_ARCH_CAPABILITIES: u32 = 0x10a;
+/// IA32_ARCH_CAPABILITIES.RDCL_NO: CPU不受Meltdown影响
+const ARCH_CAP_RDCL_NO: u64 = 1 << 0;
+
+static PTI_ENABLED: AtomicBool = AtomicBool::new(false);
+static PCID_ENABLED: AtomicBool = AtomicBool::new(false);
+/// 用户态页表内核空间部分的模板（顶级页表的物理地址）
+static PTI_TEMPLATE: AtomicUsize = AtomicUsize::new(0);
+/// 按KPTI方式分配的顶级页表（内核态页表的物理地址）
+static USER_PGD_PAIRS: SpinLock<BTreeSet<usize>> = SpinLock::new(BTreeSet::new());
+
+extern "C" {
+    fn __entry_text_start();
+    fn __entry_text_end();
+}
+
+/// 是否开启了KPTI
+#[inline(always)]
+pub fn pti_enabled() -> bool {
+    PTI_ENABLED.load(Ordering::Relaxed)
+}
+
+/// CPU是否可能受Meltdown影响
+fn cpu_vulnerable_to_meltdown() -> bool {
+    if let Some(vendor) = CpuId::new().get_vendor_info() {
+        if matches!(vendor.as_str(), "AuthenticAMD" | "HygonGenuine") {
+            return false;
+        }
+    }
+    // CPUID.(EAX=7,ECX=0):EDX[29] 表示支持IA32_ARCH_CAPABILITIES
+    if cpuid!(0x7, 0x0).edx & (1 << 29) != 0
+        && unsafe { rdmsr(MSR_IA32_ARCH_CAPABILITIES) } & ARCH_CAP_RDCL_NO != 0
+    {
+        return false;
+    }
+    true
+}
+
+fn pti_wanted() -> bool {
+    match PTI_PARAM.value_str() {
+        Some("off") => false,
+        Some("on") => true,
+        _ => cpu_vulnerable_to_meltdown(),
+    }
+}
+
+/// # 功能
+///
+/// 在BSP上初始化KPTI：构建用户态页表的模板，并打开当前CPU的KPTI。
+///
+/// 必须在创建第一个用户地址空间之前调用，之后创建的用户地址空间都会使用成对的顶级页表。
+#[link_section = ".init.text"]
+pub unsafe fn pti_init() {
+    if !pti_wanted() {
+        info!("KPTI disabled");
+        return;
+    }
+
+    let template = match build_template() {
+        Some(t) => t,
+        None => {
+            warn!("KPTI: failed to build the user page table template, disabled");
+            return;
+        }
+    };
+    PTI_TEMPLATE.store(template.data(), Ordering::SeqCst);
+
+    // CPUID.01H:ECX[17] PCID
+    PCID_ENABLED.store(cpuid!(0x1).ecx & (1 << 17) != 0, Ordering::SeqCst);
+    PTI_ENABLED.store(true, Ordering::SeqCst);
+
+    pti_cpu_init();
+    // 让TSS.rsp0改为指向入口栈
+    TSSManager::set_kernel_stack(CpuEntryArea::current().kernel_rsp0());
+
+    info!(
+        "KPTI enabled{}",
+        if PCID_ENABLED.load(Ordering::Relaxed) {
+            " with PCID"
+        } else {
+            ""
+        }
+    );
+}
+
+/// # 功能
+///
+/// 在当前CPU上打开KPTI（BSP在[`pti_init`]中调用，AP在启动时调用）。
+///
+/// 关闭全局页，避免内核的TLB项在用户态仍然可用；支持PCID时打开CR4.PCIDE。
+pub fn pti_cpu_init() {
+    if !pti_enabled() {
+        return;
+    }
+    let pcid = PCID_ENABLED.load(Ordering::Relaxed);
+    unsafe {
+        let mut flags = cr4();
+        flags.remove(Cr4::CR4_ENABLE_GLOBAL_PAGES);
+        if pcid {
+            // 打开PCIDE时CR3[11:0]必须为0，内核态页表总是使用0号PCID
+            flags.insert(Cr4::CR4_ENABLE_PCID);
+        }
+        cr4_write(flags);
+
+        let (user_bits, kernel_bits) = if pcid {
+            (
+                PTI_USER_PGTABLE_BIT | PTI_USER_PCID_BIT | X86_CR3_PCID_NOFLUSH,
+                X86_CR3_PCID_NOFLUSH,
+            )
+        } else {
+            (PTI_USER_PGTABLE_BIT, 0)
+        };
+        let area = CpuEntryArea::current();
+        area.enable_pti(user_bits, kernel_bits);
+        // 切换PCID之前的用户态TLB项可能已经过期
+        area.set_flush_user_pcid();
+    }
+}
+
+/// 构建用户态页表中内核空间部分的模板
+#[link_section = ".init.text"]
+unsafe fn build_template() -> Option<PhysAddr> {
+    let mut mapper = PageMapper::create(PageTableKind::User, LockedFrameAllocator)?;
+
+    let mut gdtr: DescriptorTablePointer<u64> = Default::default();
+    let mut idtr: DescriptorTablePointer<u64> = Default::default();
+    sgdt(&mut gdtr);
+    sidt(&mut idtr);
+
+    let entry_start = __entry_text_start as usize;
+    let entry_end = __entry_text_end as usize;
+    let (tss_start, tss_size) = TSSManager::region();
+    let (cea_start, cea_size) = CpuEntryArea::region();
+
+    // 入口代码
+    map_kernel_range(
+        &mut mapper,
+        entry_start,
+        entry_end - entry_start,
+        false,
+        true,
+    )?;
+    // 从用户态进入内核时CPU会访问GDT（可能写入访问位）、IDT和TSS
+    map_kernel_range(
+        &mut mapper,
+        gdtr.base as usize,
+        gdtr.limit as usize + 1,
+        true,
+        false,
+    )?;
+    map_kernel_range(
+        &mut mapper,
+        idtr.base as usize,
+        idtr.limit as usize + 1,
+        false,
+        false,
+    )?;
+    map_kernel_range(&mut mapper, tss_start, tss_size, true, false)?;
+    // 入口栈以及syscall入口使用的数据
+    map_kernel_range(&mut mapper, cea_start, cea_size, true, false)?;
+
+    Some(mapper.table().phys())
+}
+
+/// 把内核中`[start, start+len)`所在的页按相同的虚拟地址映射到模板中
+#[link_section = ".init.text"]
+unsafe fn map_kernel_range(
+    mapper: &mut PageMapper,
+    start: usize,
+    len: usize,
+    write: bool,
+    execute: bool,
+) -> Option<()> {
+    let flags = EntryFlags::new()
+        .set_user(false)
+        .set_write(write)
+        .set_execute(execute);
+    let end = page_align_up(start + len);
+    for virt in (page_align_down(start)..end).step_by(MMArch::PAGE_SIZE) {
+        let virt = VirtAddr::new(virt);
+        // 不同的区域可能落在同一页中
+        if mapper.translate(virt).is_some() {
+            continue;
+        }
+        let phys = MMArch::virt_2_phys(virt)?;
+        mapper.map_phys(virt, phys, flags)?.ignore();
+    }
+    Some(())
+}
+
+/// # 功能
+///
+/// 为用户地址空间分配一对顶级页表（8K对齐），并用模板填充用户态页表的内核空间部分。
+///
+/// ## 返回值
+/// - `Some(PhysAddr)`: 内核态页表的物理地址，用户态页表紧随其后
+/// - `None`: 内存不足
+pub unsafe fn alloc_user_pgd_pair() -> Option<PhysAddr> {
+    let (paddr, count) = LockedFrameAllocator.allocate(PageFrameCount::new(2))?;
+    if paddr.data() & (2 * MMArch::PAGE_SIZE - 1) != 0 {
+        // 伙伴分配器返回的块总是按大小对齐的，这里只是以防万一
+        LockedFrameAllocator.free(paddr, count);
+        return None;
+    }
+    let kernel_vaddr = MMArch::phys_2_virt(paddr)?;
+    MMArch::write_bytes(kernel_vaddr, 0, 2 * MMArch::PAGE_SIZE);
+
+    let template = PageTable::<MMArch>::new(
+        VirtAddr::new(0),
+        PhysAddr::new(PTI_TEMPLATE.load(Ordering::Relaxed)),
+        MMArch::PAGE_LEVELS - 1,
+    );
+    let user_table = PageTable::<MMArch>::new(
+        VirtAddr::new(0),
+        paddr + MMArch::PAGE_SIZE,
+        MMArch::PAGE_LEVELS - 1,
+    );
+    for i in MMArch::PAGE_KERNEL_INDEX..MMArch::PAGE_ENTRY_NUM {
+        user_table.set_entry(i, template.entry(i)?);
+    }
+
+    USER_PGD_PAIRS.lock_irqsave().insert(paddr.data());
+    Some(paddr)
+}
+
+/// 释放顶级页表。如果它是由[`alloc_user_pgd_pair`]分配的，则连同用户态页表一起释放并返回`true`
+pub unsafe fn free_user_pgd_pair(table: PhysAddr) -> bool {
+    if !USER_PGD_PAIRS.lock_irqsave().remove(&table.data()) {
+        return false;
+    }
+    LockedFrameAllocator.free(table, PageFrameCount::new(2));
+    true
+}
+
+/// 内核态页表的用户空间顶级页表项被修改后，同步到用户态页表中
+pub unsafe fn sync_user_pgd_entry(table: PhysAddr, index: usize, entry: usize) {
+    if index >= MMArch::PAGE_KERNEL_INDEX || !pti_enabled() {
+        return;
+    }
+    if !USER_PGD_PAIRS.lock_irqsave().contains(&table.data()) {
+        return;
+    }
+    let user_table = MMArch::phys_2_virt(table + MMArch::PAGE_SIZE).unwrap();
+    MMArch::write::<usize>(user_table + index * MMArch::PAGE_ENTRY_SIZE, entry);
+}
+
+/// 内核刷新了当前CPU的TLB之后调用：用户态PCID中的TLB项也需要在下次返回用户态时刷新
+#[inline(always)]
+pub fn mark_user_tlb_stale() {
+    if PCID_ENABLED.load(Ordering::Relaxed) && pti_enabled() {
+        unsafe { CpuEntryArea::current().set_flush_user_pcid() };
+    }
+}
@@ -14,6 +14,7 @@ pub mod kprobe;
 pub mod kvm_para;
 pub mod libs;
 pub mod mm;
+pub mod mptable;
 pub mod msi;
 pub mod pci;
 pub mod process;
@@ -0,0 +1,224 @@
+//! Intel MultiProcessor规范的MP表
+//!
+//! 没有ACPI的平台（例如Firecracker这样的微虚拟机）通过MP表描述处理器和IO APIC。
+//! 这里只解析启动需要的部分：处理器的APIC ID和IO APIC的地址，中断路由使用默认的ISA映射。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/kernel/mpparse.c
+
+use alloc::vec::Vec;
+use log::{info, warn};
+use system_error::SystemError;
+
+use crate::{
+    libs::{
+        align::{page_align_down, page_align_up},
+        lazy_init::Lazy,
+    },
+    mm::{
+        mmio_buddy::{mmio_pool, MMIOSpaceGuard},
+        percpu::PerCpu,
+        PhysAddr,
+    },
+    smp::cpu::ProcessorId,
+};
+
+use super::smp::SMP_BOOT_DATA;
+
+/// MP浮动指针结构的签名
+const MPF_SIGNATURE: &[u8; 4] = b"_MP_";
+/// MP配置表的签名
+const MPC_SIGNATURE: &[u8; 4] = b"PCMP";
+const MPF_LEN: usize = 16;
+const MPC_HEADER_LEN: usize = 44;
+
+const MP_PROCESSOR: u8 = 0;
+const MP_BUS: u8 = 1;
+const MP_IOAPIC: u8 = 2;
+const MP_INTSRC: u8 = 3;
+const MP_LINTSRC: u8 = 4;
+
+const CPU_ENABLED: u8 = 1 << 0;
+const CPU_BOOTPROCESSOR: u8 = 1 << 1;
+const MPC_APIC_USABLE: u8 = 1 << 0;
+
+/// MP表中启动需要的信息
+#[derive(Debug)]
+pub struct MpTableInfo {
+    /// BSP的APIC ID
+    pub bsp_apic_id: u8,
+    /// 可用的AP的APIC ID
+    pub ap_apic_ids: Vec<u8>,
+    /// 第一个可用的IO APIC的物理地址
+    pub ioapic_paddr: Option<PhysAddr>,
+}
+
+static MP_TABLE: Lazy<Option<MpTableInfo>> = Lazy::new();
+
+/// # 获取MP表
+///
+/// 第一次调用时按照MP规范查找并解析，没有MP表时返回None
+pub fn mp_table() -> Option<&'static MpTableInfo> {
+    if !MP_TABLE.initialized() {
+        let info = match find_mpf() {
+            Some(mpf) => parse_mpc(mpf).map_err(|e| warn!("mptable: bad MP table: {:?}", e)),
+            None => Err(()),
+        };
+        MP_TABLE.init(info.ok());
+    }
+    MP_TABLE.get().as_ref()
+}
+
+/// 一段映射到内核的物理内存
+struct PhysWindow {
+    _guard: MMIOSpaceGuard,
+    ptr: *const u8,
+    len: usize,
+}
+
+impl PhysWindow {
+    fn map(paddr: usize, len: usize) -> Result<Self, SystemError> {
+        let start = page_align_down(paddr);
+        let size = page_align_up(paddr + len) - start;
+        let guard = mmio_pool().create_mmio(size)?;
+        unsafe { guard.map_phys(PhysAddr::new(start), size) }?;
+        let ptr = (guard.vaddr().data() + (paddr - start)) as *const u8;
+        Ok(Self {
+            _guard: guard,
+            ptr,
+            len,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+fn checksum_ok(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) == 0
+}
+
+/// # 在一段物理内存中查找MP浮动指针结构
+///
+/// ## 返回值
+/// 配置表的物理地址
+fn scan_mpf(paddr: usize, len: usize) -> Option<usize> {
+    let window = PhysWindow::map(paddr, len).ok()?;
+    window
+        .bytes()
+        .chunks_exact(MPF_LEN)
+        .find(|mpf| &mpf[0..4] == MPF_SIGNATURE && mpf[8] == 1 && checksum_ok(mpf))
+        .and_then(|mpf| {
+            // feature1不为0表示使用规范中的默认配置，没有配置表，目前不支持
+            if mpf[11] != 0 {
+                warn!(
+                    "mptable: default configuration {} is not supported",
+                    mpf[11]
+                );
+                return None;
+            }
+            let physptr = u32::from_le_bytes(mpf[4..8].try_into().unwrap()) as usize;
+            (physptr != 0).then_some(physptr)
+        })
+}
+
+/// 按照MP规范的顺序查找：EBDA的第一个KB，常规内存的最后一个KB，BIOS只读区
+fn find_mpf() -> Option<usize> {
+    let ebda = PhysWindow::map(0x40e, 2)
+        .map(|w| (u16::from_le_bytes([w.bytes()[0], w.bytes()[1]]) as usize) << 4)
+        .unwrap_or(0);
+    if ebda != 0 {
+        if let Some(mpc) = scan_mpf(ebda, 1024) {
+            return Some(mpc);
+        }
+    }
+    scan_mpf(639 * 1024, 1024).or_else(|| scan_mpf(0xf0000, 0x10000))
+}
+
+fn parse_mpc(paddr: usize) -> Result<MpTableInfo, SystemError> {
+    let header = PhysWindow::map(paddr, MPC_HEADER_LEN)?;
+    let hdr = header.bytes();
+    if &hdr[0..4] != MPC_SIGNATURE {
+        return Err(SystemError::EINVAL);
+    }
+    let length = u16::from_le_bytes([hdr[4], hdr[5]]) as usize;
+    if length < MPC_HEADER_LEN {
+        return Err(SystemError::EINVAL);
+    }
+    let table = PhysWindow::map(paddr, length)?;
+    let table = table.bytes();
+    if !checksum_ok(table) {
+        return Err(SystemError::EINVAL);
+    }
+
+    let mut info = MpTableInfo {
+        bsp_apic_id: 0,
+        ap_apic_ids: Vec::new(),
+        ioapic_paddr: None,
+    };
+    let mut bsp = None;
+    let mut pos = MPC_HEADER_LEN;
+    while pos < length {
+        let entry_len = match table[pos] {
+            MP_PROCESSOR => 20,
+            MP_BUS | MP_IOAPIC | MP_INTSRC | MP_LINTSRC => 8,
+            t => {
+                // 未知的表项长度未知，之后的表项都无法解析
+                warn!("mptable: unknown entry type {} at offset {}", t, pos);
+                break;
+            }
+        };
+        if pos + entry_len > length {
+            return Err(SystemError::EINVAL);
+        }
+        let entry = &table[pos..pos + entry_len];
+        match entry[0] {
+            MP_PROCESSOR if entry[3] & CPU_ENABLED != 0 => {
+                if entry[3] & CPU_BOOTPROCESSOR != 0 {
+                    bsp = Some(entry[1]);
+                } else {
+                    info.ap_apic_ids.push(entry[1]);
+                }
+            }
+            MP_IOAPIC if entry[3] & MPC_APIC_USABLE != 0 && info.ioapic_paddr.is_none() => {
+                info.ioapic_paddr = Some(PhysAddr::new(u32::from_le_bytes(
+                    entry[4..8].try_into().unwrap(),
+                ) as usize));
+            }
+            _ => {}
+        }
+        pos += entry_len;
+    }
+    info.bsp_apic_id = bsp.ok_or(SystemError::EINVAL)?;
+
+    info!(
+        "mptable: found at {:#x}, bsp: {}, aps: {:?}, ioapic: {:?}",
+        paddr, info.bsp_apic_id, info.ap_apic_ids, info.ioapic_paddr
+    );
+    Ok(info)
+}
+
+/// # 根据MP表初始化SMP启动信息
+///
+/// 在没有ACPI时代替[`early_acpi_boot_init`](super::acpi::early_acpi_boot_init)
+pub(super) fn early_mptable_boot_init() -> Result<(), SystemError> {
+    let mp = mp_table().ok_or(SystemError::ENODEV)?;
+    unsafe {
+        SMP_BOOT_DATA.set_phys_id(ProcessorId::new(0), mp.bsp_apic_id as usize);
+        let mut cnt = ProcessorId::new(1);
+        for ap in mp.ap_apic_ids.iter() {
+            if cnt.data() >= PerCpu::MAX_CPU_NUM {
+                break;
+            }
+            SMP_BOOT_DATA.set_phys_id(cnt, *ap as usize);
+            cnt = ProcessorId::new(cnt.data() + 1);
+        }
+        SMP_BOOT_DATA.set_cpu_count(cnt.data());
+        SMP_BOOT_DATA.mark_initialized();
+    }
+    info!(
+        "early_mptable_boot_init: cpu_count: {}",
+        SMP_BOOT_DATA.cpu_count()
+    );
+    Ok(())
+}
@@ -102,7 +102,7 @@ fn x86_64_pci_init() -> Result<(), SystemError> {
 fn discover_ecam_root() -> Result<(), PciError> {
     let mcfg = acpi_manager()
         .tables()
-        .expect("get acpi_manager table error")
+        .ok_or(PciError::McfgTableNotFound)?
         .find_table::<Mcfg>()
         .map_err(|_| PciError::McfgTableNotFound)?;
     for mcfg_entry in mcfg.entries() {
@@ -0,0 +1,141 @@
+//! 每个CPU的入口区域
+//!
+//! 内核态的gsbase指向当前CPU的入口区域，syscall入口通过它找到系统调用栈并暂存用户态rsp。
+//!
+//! 开启KPTI时，入口区域同时被映射到每个进程的用户态页表中：
+//! 中断/异常从用户态进入时，CPU先把栈帧压到这里的入口栈上，入口代码切换到内核页表之后，
+//! 再把栈帧搬到当前进程的内核栈；返回用户态时则反过来，先把iret帧搬到入口栈，再切换到用户态页表。
+//!
+//! 入口代码（`asm/entry.S`）中按固定的偏移访问这里的字段，修改布局时需要同时修改那里的`CEA_*`常量。
+
+use core::mem::offset_of;
+
+use x86::msr::{wrmsr, IA32_KERNEL_GSBASE};
+
+use crate::{mm::percpu::PerCpu, smp::core::smp_get_processor_id};
+
+/// 入口区域的大小
+pub const CPU_ENTRY_AREA_SIZE: usize = 8192;
+
+/// 入口区域头部（各个字段）的大小，剩下的空间都用作入口栈
+const CPU_ENTRY_AREA_HEADER_SIZE: usize = 0x40;
+
+#[repr(C, align(4096))]
+pub struct CpuEntryArea {
+    /// 系统调用栈的栈顶，从当前进程的`X86_64GSData`拷贝过来
+    kaddr: usize,
+    /// syscall入口暂存用户态rsp
+    uaddr: usize,
+    /// 当前进程内核栈的栈顶
+    kernel_rsp0: usize,
+    /// 是否开启了KPTI
+    pti_enabled: usize,
+    /// 返回用户态时需要或到CR3上的位（用户态页表、用户PCID、NOFLUSH）
+    user_cr3_bits: usize,
+    /// 进入内核时需要或到CR3上的位（NOFLUSH）
+    kernel_cr3_bits: usize,
+    /// 下次返回用户态时需要刷新用户PCID的TLB
+    flush_user_pcid: usize,
+    /// 入口栈的栈顶
+    entry_stack_top: usize,
+    entry_stack: [u8; CPU_ENTRY_AREA_SIZE - CPU_ENTRY_AREA_HEADER_SIZE],
+}
+
+const _: () = {
+    assert!(core::mem::size_of::<CpuEntryArea>() == CPU_ENTRY_AREA_SIZE);
+    assert!(offset_of!(CpuEntryArea, kaddr) == 0x0);
+    assert!(offset_of!(CpuEntryArea, uaddr) == 0x8);
+    assert!(offset_of!(CpuEntryArea, kernel_rsp0) == 0x10);
+    assert!(offset_of!(CpuEntryArea, pti_enabled) == 0x18);
+    assert!(offset_of!(CpuEntryArea, user_cr3_bits) == 0x20);
+    assert!(offset_of!(CpuEntryArea, kernel_cr3_bits) == 0x28);
+    assert!(offset_of!(CpuEntryArea, flush_user_pcid) == 0x30);
+    assert!(offset_of!(CpuEntryArea, entry_stack_top) == 0x38);
+    assert!(offset_of!(CpuEntryArea, entry_stack) == CPU_ENTRY_AREA_HEADER_SIZE);
+};
+
+/// 所有CPU的入口区域
+static mut CPU_ENTRY_AREAS: [CpuEntryArea; PerCpu::MAX_CPU_NUM as usize] =
+    [const { CpuEntryArea::new() }; PerCpu::MAX_CPU_NUM as usize];
+
+impl CpuEntryArea {
+    const fn new() -> Self {
+        Self {
+            kaddr: 0,
+            uaddr: 0,
+            kernel_rsp0: 0,
+            pti_enabled: 0,
+            user_cr3_bits: 0,
+            kernel_cr3_bits: 0,
+            flush_user_pcid: 0,
+            entry_stack_top: 0,
+            entry_stack: [0; CPU_ENTRY_AREA_SIZE - CPU_ENTRY_AREA_HEADER_SIZE],
+        }
+    }
+
+    /// 获取当前CPU的入口区域
+    ///
+    /// ## 安全性
+    ///
+    /// 调用者需要保证在使用返回值期间不会被迁移到其他CPU上
+    #[allow(static_mut_refs)]
+    pub unsafe fn current() -> &'static mut CpuEntryArea {
+        let area = &mut CPU_ENTRY_AREAS[smp_get_processor_id().data() as usize];
+        if area.entry_stack_top == 0 {
+            // 入口栈按16字节对齐，与CPU切换到rsp0时的对齐方式一致
+            area.entry_stack_top = (area.entry_stack.as_ptr_range().end as usize) & !0xf;
+        }
+        area
+    }
+
+    /// 所有CPU的入口区域所占的虚拟地址范围（起始地址，字节数）
+    #[allow(static_mut_refs)]
+    pub fn region() -> (usize, usize) {
+        unsafe {
+            (
+                CPU_ENTRY_AREAS.as_ptr() as usize,
+                core::mem::size_of_val(&CPU_ENTRY_AREAS),
+            )
+        }
+    }
+
+    /// 把当前进程的系统调用栈写入入口区域，并让KernelGsbase指向入口区域
+    pub unsafe fn load_kernel_gsbase(&mut self, kaddr: usize) {
+        self.kaddr = kaddr;
+        wrmsr(IA32_KERNEL_GSBASE, self as *mut Self as u64);
+    }
+
+    /// 当前进程内核栈的栈顶
+    pub fn kernel_rsp0(&self) -> usize {
+        self.kernel_rsp0
+    }
+
+    pub fn set_kernel_rsp0(&mut self, rsp0: usize) {
+        self.kernel_rsp0 = rsp0;
+    }
+
+    /// 入口栈的栈顶
+    pub fn entry_stack_top(&self) -> usize {
+        self.entry_stack_top
+    }
+
+    pub fn pti_enabled(&self) -> bool {
+        self.pti_enabled != 0
+    }
+
+    /// 设置本CPU的KPTI参数
+    ///
+    /// ## 参数
+    /// - `user_cr3_bits`: 返回用户态时或到CR3上的位
+    /// - `kernel_cr3_bits`: 进入内核时或到CR3上的位
+    pub fn enable_pti(&mut self, user_cr3_bits: usize, kernel_cr3_bits: usize) {
+        self.user_cr3_bits = user_cr3_bits;
+        self.kernel_cr3_bits = kernel_cr3_bits;
+        self.pti_enabled = 1;
+    }
+
+    /// 标记用户PCID的TLB已经过期，下次返回用户态时刷新
+    pub fn set_flush_user_pcid(&mut self) {
+        self.flush_user_pcid = 1;
+    }
+}
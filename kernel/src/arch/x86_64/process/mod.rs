@@ -16,7 +16,7 @@ use x86::{controlregs::Cr4, segmentation::SegmentSelector};
 
 use crate::{
     arch::process::table::TSSManager,
-    exception::InterruptArch,
+    exception::{ipi::tlb_flush_set_online, InterruptArch},
     libs::spinlock::SpinLockGuard,
     mm::VirtAddr,
     process::{
@@ -28,7 +28,9 @@ use crate::{
 };
 
 use self::{
+    entry_area::CpuEntryArea,
     kthread::kernel_thread_bootstrap_stage1,
+    shstk::{shstk_alloc_thread_stack, ShstkState},
     syscall::ARCH_SET_FS,
     table::{switch_fs_and_gs, KERNEL_DS, USER_DS},
 };
@@ -38,8 +40,10 @@ use super::{
     syscall::X86_64GSData, CurrentIrqArch,
 };
 
+pub mod entry_area;
 pub mod idle;
 pub mod kthread;
+pub mod shstk;
 pub mod syscall;
 pub mod table;
 
@@ -83,6 +87,8 @@ pub struct ArchPCBInfo {
     gsdata: X86_64GSData,
     /// 浮点寄存器的状态
     fp_state: Option<FpState>,
+    /// 用户态影子栈的状态
+    shstk: ShstkState,
 }
 
 #[allow(dead_code)]
@@ -118,6 +124,7 @@ impl ArchPCBInfo {
             fs: KERNEL_DS,
             gs: KERNEL_DS,
             fp_state: None,
+            shstk: ShstkState::default(),
         };
 
         r.rsp = kstack.stack_max_address().data() - 8;
@@ -213,12 +220,9 @@ impl ArchPCBInfo {
         }
     }
 
-    /// 将gsdata写入KernelGsbase寄存器
+    /// 将gsdata拷贝到当前CPU的入口区域，并让KernelGsbase寄存器指向入口区域
     pub unsafe fn store_kernel_gsbase(&self) {
-        x86::msr::wrmsr(
-            x86::msr::IA32_KERNEL_GSBASE,
-            &self.gsdata as *const X86_64GSData as u64,
-        );
+        CpuEntryArea::current().load_kernel_gsbase(self.gsdata.kaddr.data());
     }
 
     /// ### 初始化系统调用栈，不得与PCB内核栈冲突(即传入的应该是一个新的栈，避免栈损坏)
@@ -242,6 +246,14 @@ impl ArchPCBInfo {
         &mut self.fp_state
     }
 
+    pub fn shstk(&self) -> &ShstkState {
+        &self.shstk
+    }
+
+    pub fn shstk_mut(&mut self) -> &mut ShstkState {
+        &mut self.shstk
+    }
+
     /// ### 克隆ArchPCBInfo,需要注意gsdata也是对应clone的
     pub fn clone_all(&self) -> Self {
         Self {
@@ -261,6 +273,7 @@ impl ArchPCBInfo {
             gs: self.gs,
             gsdata: self.gsdata.clone(),
             fp_state: self.fp_state,
+            shstk: self.shstk,
         }
     }
 
@@ -313,6 +326,13 @@ impl ProcessManager {
         let clone_flags = clone_args.flags;
         let mut child_trapframe = *current_trapframe;
 
+        // 分配影子栈可能睡眠，必须在获取ArchPCBInfo的锁之前完成
+        let child_shstk = if new_pcb.flags().contains(ProcessFlags::KTHREAD) {
+            ShstkState::default()
+        } else {
+            shstk_alloc_thread_stack(current_pcb, clone_flags, clone_args.stack_size)?
+        };
+
         // 子进程的返回值为0
         child_trapframe.set_return_value(0);
 
@@ -352,6 +372,7 @@ impl ProcessManager {
         new_arch_guard.fs = current_arch_guard.fs;
         new_arch_guard.gs = current_arch_guard.gs;
         new_arch_guard.fp_state = current_arch_guard.fp_state;
+        new_arch_guard.shstk = child_shstk;
 
         // 拷贝浮点寄存器的状态
         if let Some(fp_state) = current_arch_guard.fp_state.as_ref() {
@@ -397,6 +418,10 @@ impl ProcessManager {
         // 切换gsbase
         Self::switch_gsbase(&prev, &next);
 
+        // 切换用户态影子栈
+        prev.arch_info_irqsave().shstk_mut().save();
+        next.arch_info_irqsave().shstk().restore();
+
         // 切换地址空间（无锁快速路径）
         let next_addr_space = next.basic().user_vm().unwrap();
         compiler_fence(Ordering::SeqCst);
@@ -416,10 +441,7 @@ impl ProcessManager {
         ProcessManager::current_pcb().preempt_enable();
 
         // 切换tss
-        TSSManager::current_tss().set_rsp(
-            x86::Ring::Ring0,
-            next.kernel_stack().stack_max_address().data() as u64,
-        );
+        TSSManager::set_kernel_stack(next.kernel_stack().stack_max_address().data());
         PROCESS_SWITCH_RESULT.as_mut().unwrap().get_mut().prev_pcb = Some(prev);
         PROCESS_SWITCH_RESULT.as_mut().unwrap().get_mut().next_pcb = Some(next);
         // debug!("switch tss ok");
@@ -638,6 +660,7 @@ pub(super) fn stop_this_cpu() -> ! {
 
     // 将当前cpu标记为offline
     smp_cpu_manager().set_online_cpu(cpu_id, false);
+    tlb_flush_set_online(false);
     CurrentApic.disable_local_apic();
 
     loop {
@@ -0,0 +1,485 @@
+//! 用户态影子栈（Intel CET Shadow Stack）
+//!
+//! 用户程序通过`arch_prctl(ARCH_SHSTK_ENABLE, ARCH_SHSTK_SHSTK)`为当前线程启用影子栈。
+//! 内核为线程分配一块影子栈内存（页表项为只读+脏），只有`call`/`ret`等影子栈指令能够写入，
+//! 返回地址与影子栈不一致时CPU产生#CP异常，内核向进程发送SIGSEGV。
+//!
+//! 目前不支持用户态的WRSS指令，即只提供Linux中不启用`ARCH_SHSTK_WRSS`的ABI。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/kernel/shstk.c
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::sync::Arc;
+use system_error::SystemError;
+use x86::{
+    controlregs::{cr0, Cr0},
+    cpuid::cpuid,
+    msr::{rdmsr, wrmsr},
+};
+
+use crate::{
+    arch::MMArch,
+    mm::{
+        allocator::page_frame::{PageFrameCount, VirtPageFrame},
+        syscall::{MapFlags, ProtFlags},
+        ucontext::AddressSpace,
+        MemoryManagementArch, VirtAddr, VmFlags,
+    },
+    process::{fork::CloneFlags, resource::RLimitID, ProcessControlBlock, ProcessManager},
+    syscall::user_access::{UserBufferReader, UserBufferWriter},
+};
+
+pub const ARCH_SHSTK_ENABLE: usize = 0x5001;
+pub const ARCH_SHSTK_DISABLE: usize = 0x5002;
+pub const ARCH_SHSTK_LOCK: usize = 0x5003;
+pub const ARCH_SHSTK_UNLOCK: usize = 0x5004;
+pub const ARCH_SHSTK_STATUS: usize = 0x5005;
+
+bitflags! {
+    /// `ARCH_SHSTK_*`操作的特性位
+    #[derive(Default)]
+    pub struct ShstkFeatures: usize {
+        /// 影子栈
+        const SHSTK = 1 << 0;
+        /// 用户态WRSS指令
+        const WRSS = 1 << 1;
+    }
+}
+
+const MSR_IA32_U_CET: u32 = 0x6a0;
+const MSR_IA32_PL3_SSP: u32 = 0x6a7;
+/// IA32_U_CET.SH_STK_EN
+const CET_SHSTK_EN: u64 = 1 << 0;
+/// CR4.CET
+const X86_CR4_CET: usize = 1 << 23;
+/// 影子栈上一个条目的大小
+const SS_FRAME_SIZE: usize = 8;
+/// 影子栈的最大默认大小
+const SHSTK_MAX_DEFAULT_SIZE: u64 = 1 << 32;
+
+/// CPU是否支持用户态影子栈
+static USER_SHSTK_SUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// 线程的影子栈状态
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShstkState {
+    /// 已启用的特性
+    features: ShstkFeatures,
+    /// 已锁定、不能再启用或关闭的特性
+    locked: ShstkFeatures,
+    /// 内核为线程分配的影子栈的起始地址，为0表示不由本线程负责释放
+    base: usize,
+    /// 影子栈大小
+    size: usize,
+    /// 线程被切换出去时的用户态SSP
+    ssp: usize,
+}
+
+impl ShstkState {
+    /// 线程是否启用了影子栈
+    pub fn enabled(&self) -> bool {
+        self.features.contains(ShstkFeatures::SHSTK)
+    }
+
+    /// 切换出当前线程前保存用户态SSP
+    ///
+    /// ## 安全性
+    ///
+    /// 只能对当前CPU上正在运行的线程调用
+    pub unsafe fn save(&mut self) {
+        if self.enabled() {
+            self.ssp = rdmsr(MSR_IA32_PL3_SSP) as usize;
+        }
+    }
+
+    /// 切换到本线程时恢复影子栈相关的MSR
+    ///
+    /// ## 安全性
+    ///
+    /// 只能在切换到本线程的过程中调用
+    pub unsafe fn restore(&self) {
+        if !user_shstk_supported() {
+            return;
+        }
+        if self.enabled() {
+            wrmsr(MSR_IA32_PL3_SSP, self.ssp as u64);
+            wrmsr(MSR_IA32_U_CET, CET_SHSTK_EN);
+        } else {
+            wrmsr(MSR_IA32_U_CET, 0);
+        }
+    }
+}
+
+/// CPU是否支持用户态影子栈
+#[inline]
+pub fn user_shstk_supported() -> bool {
+    USER_SHSTK_SUPPORTED.load(Ordering::Relaxed)
+}
+
+/// # 功能
+///
+/// 检测当前CPU的影子栈支持，并在CR0.WP已经打开时设置CR4.CET。
+/// BSP在早期初始化时调用，此时CR0.WP还没有打开，CR4.CET会在之后打开写保护时设置。
+pub fn shstk_cpu_init() {
+    let res = cpuid!(0x7, 0x0);
+    // CPUID.(EAX=7,ECX=0):ECX[7] CET_SS
+    if res.ecx & (1 << 7) == 0 {
+        return;
+    }
+    USER_SHSTK_SUPPORTED.store(true, Ordering::Relaxed);
+    if unsafe { cr0() }.contains(Cr0::CR0_WRITE_PROTECT) {
+        cet_cr4_set(true);
+    }
+}
+
+/// # 功能
+///
+/// 打开或关闭当前CPU的CR4.CET。
+///
+/// CR4.CET为1时不允许清除CR0.WP，因此内核临时关闭写保护前必须先关闭CET，
+/// 重新打开写保护之后再打开CET。CPU不支持影子栈时什么都不做。
+pub fn cet_cr4_set(enable: bool) {
+    if !user_shstk_supported() {
+        return;
+    }
+    unsafe {
+        let mut cr4: usize;
+        core::arch::asm!("mov {}, cr4", out(reg) cr4, options(nomem, nostack, preserves_flags));
+        let new = if enable {
+            cr4 | X86_CR4_CET
+        } else {
+            cr4 & !X86_CR4_CET
+        };
+        if new != cr4 {
+            core::arch::asm!("mov cr4, {}", in(reg) new, options(nostack, preserves_flags));
+        }
+    }
+}
+
+/// 计算影子栈的大小：指定了大小时按页对齐，否则取min(RLIMIT_STACK, 4G)
+fn adjust_shstk_size(size: usize) -> usize {
+    if size != 0 {
+        return page_align_up(size);
+    }
+    let rlimit = ProcessManager::current_pcb()
+        .get_rlimit(RLimitID::Stack)
+        .rlim_cur;
+    page_align_up(rlimit.min(SHSTK_MAX_DEFAULT_SIZE) as usize)
+}
+
+fn page_align_up(size: usize) -> usize {
+    (size + MMArch::PAGE_SIZE - 1) & !(MMArch::PAGE_SIZE - 1)
+}
+
+fn current_user_vm() -> Result<Arc<AddressSpace>, SystemError> {
+    ProcessManager::current_pcb()
+        .basic()
+        .user_vm()
+        .ok_or(SystemError::EINVAL)
+}
+
+/// # 功能
+///
+/// 在当前地址空间中分配一块影子栈。
+///
+/// 影子栈按需分配物理页，VMA带有`VM_SHADOW_STACK`标志且没有`VM_WRITE`，
+/// 普通的写入（包括内核的copy_to_user）都会失败，只有影子栈访问能够写入。
+///
+/// ## 返回值
+/// - `Ok(usize)`: 影子栈的起始地址
+fn alloc_shstk(size: usize) -> Result<usize, SystemError> {
+    let vm = current_user_vm()?;
+    let mut space = vm.write();
+    let start = space.map_anonymous(
+        VirtAddr::new(0),
+        size,
+        ProtFlags::PROT_READ,
+        MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+        false,
+        false,
+    )?;
+    let vma = space
+        .mappings
+        .contains(start.virt_address())
+        .ok_or(SystemError::ENOMEM)?;
+    let mut guard = vma.lock();
+    let vm_flags = *guard.vm_flags() | VmFlags::VM_SHADOW_STACK;
+    guard.set_vm_flags(vm_flags);
+    guard.set_flags();
+    Ok(start.virt_address().data())
+}
+
+fn free_shstk(base: usize, size: usize) -> Result<(), SystemError> {
+    let vm = current_user_vm()?;
+    let count = PageFrameCount::from_bytes(size).ok_or(SystemError::EINVAL)?;
+    vm.write()
+        .munmap(VirtPageFrame::new(VirtAddr::new(base)), count)
+}
+
+fn shstk_setup(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+    if pcb.arch_info_irqsave().shstk().enabled() {
+        return Ok(());
+    }
+    if !user_shstk_supported() {
+        return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+    }
+
+    let size = adjust_shstk_size(0);
+    let base = alloc_shstk(size)?;
+    let ssp = base + size;
+
+    let mut arch_info = pcb.arch_info_irqsave();
+    let state = arch_info.shstk_mut();
+    state.features.insert(ShstkFeatures::SHSTK);
+    state.base = base;
+    state.size = size;
+    state.ssp = ssp;
+    unsafe { state.restore() };
+    Ok(())
+}
+
+fn shstk_disable(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+    let (base, size) = {
+        let mut arch_info = pcb.arch_info_irqsave();
+        let state = arch_info.shstk_mut();
+        if !state.enabled() {
+            return Ok(());
+        }
+        let region = (state.base, state.size);
+        state.features.remove(ShstkFeatures::SHSTK);
+        state.base = 0;
+        state.size = 0;
+        state.ssp = 0;
+        unsafe {
+            wrmsr(MSR_IA32_U_CET, 0);
+            wrmsr(MSR_IA32_PL3_SSP, 0);
+        }
+        region
+    };
+    if base != 0 {
+        free_shstk(base, size)?;
+    }
+    Ok(())
+}
+
+/// # 功能
+///
+/// 处理`arch_prctl`的`ARCH_SHSTK_*`选项。
+///
+/// ## 参数
+/// - `option`: `ARCH_SHSTK_ENABLE`等
+/// - `arg2`: 特性位，`ARCH_SHSTK_STATUS`时为用户态的输出地址
+///
+/// ## 返回值
+/// - `Ok(0)`: 成功
+/// - `Err(SystemError::EPERM)`: 特性已经被锁定
+/// - `Err(SystemError::EINVAL)`: 未知的特性，或者一次指定了多个特性
+/// - `Err(SystemError::EOPNOTSUPP_OR_ENOTSUP)`: CPU不支持影子栈，或者请求启用WRSS
+pub fn shstk_prctl(option: usize, arg2: usize) -> Result<usize, SystemError> {
+    let pcb = ProcessManager::current_pcb();
+
+    if option == ARCH_SHSTK_STATUS {
+        let features = pcb.arch_info_irqsave().shstk().features.bits();
+        let mut writer =
+            UserBufferWriter::new(arg2 as *mut usize, core::mem::size_of::<usize>(), true)?;
+        writer.copy_one_to_user(&features, 0)?;
+        return Ok(0);
+    }
+
+    let features = ShstkFeatures::from_bits(arg2).ok_or(SystemError::EINVAL)?;
+
+    if option == ARCH_SHSTK_LOCK {
+        pcb.arch_info_irqsave().shstk_mut().locked.insert(features);
+        return Ok(0);
+    }
+
+    // 与Linux一致，解锁只允许通过ptrace进行
+    if option == ARCH_SHSTK_UNLOCK {
+        return Err(SystemError::EINVAL);
+    }
+
+    if pcb.arch_info_irqsave().shstk().locked.intersects(features) {
+        return Err(SystemError::EPERM);
+    }
+    // 一次只能启用或关闭一个特性
+    if features.bits().count_ones() != 1 {
+        return Err(SystemError::EINVAL);
+    }
+
+    match (option, features) {
+        (ARCH_SHSTK_ENABLE, ShstkFeatures::SHSTK) => shstk_setup(&pcb)?,
+        (ARCH_SHSTK_DISABLE, ShstkFeatures::SHSTK) => shstk_disable(&pcb)?,
+        // 不支持WRSS，关闭总是成功
+        (ARCH_SHSTK_ENABLE, ShstkFeatures::WRSS) => return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP),
+        (ARCH_SHSTK_DISABLE, ShstkFeatures::WRSS) => {}
+        _ => return Err(SystemError::EINVAL),
+    }
+    Ok(0)
+}
+
+/// # 功能
+///
+/// fork/clone时为子线程准备影子栈状态。
+///
+/// - 父线程没有启用影子栈：子线程也不启用
+/// - `CLONE_VFORK`：子线程在父线程的影子栈上运行，直到exec
+/// - 不共享地址空间：子进程使用父进程影子栈的写时复制副本，SSP保持不变
+/// - `CLONE_VM`：在共享的地址空间中为子线程分配新的影子栈
+///
+/// 可能需要分配内存，调用时不能持有任何ArchPCBInfo的锁。
+pub fn shstk_alloc_thread_stack(
+    current: &Arc<ProcessControlBlock>,
+    clone_flags: CloneFlags,
+    stack_size: usize,
+) -> Result<ShstkState, SystemError> {
+    let mut state = {
+        let mut arch_info = current.arch_info_irqsave();
+        let state = arch_info.shstk_mut();
+        unsafe { state.save() };
+        *state
+    };
+    if !state.enabled() {
+        return Ok(ShstkState::default());
+    }
+
+    if clone_flags.contains(CloneFlags::CLONE_VFORK) {
+        state.base = 0;
+        state.size = 0;
+        return Ok(state);
+    }
+    if !clone_flags.contains(CloneFlags::CLONE_VM) {
+        return Ok(state);
+    }
+
+    let size = adjust_shstk_size(stack_size);
+    let base = alloc_shstk(size)?;
+    state.base = base;
+    state.size = size;
+    state.ssp = base + size;
+    Ok(state)
+}
+
+/// exec时重置影子栈状态。旧的影子栈随旧的地址空间一起释放
+pub fn shstk_reset_on_exec(pcb: &Arc<ProcessControlBlock>) {
+    *pcb.arch_info_irqsave().shstk_mut() = ShstkState::default();
+    if user_shstk_supported() {
+        unsafe {
+            wrmsr(MSR_IA32_U_CET, 0);
+            wrmsr(MSR_IA32_PL3_SSP, 0);
+        }
+    }
+}
+
+/// 以影子栈访问的方式向用户态影子栈写入8字节（WRUSS指令）
+fn write_user_shstk_64(addr: usize, val: u64) -> Result<(), SystemError> {
+    if !VirtAddr::new(addr).check_user() {
+        return Err(SystemError::EFAULT);
+    }
+    // WRUSS需要CR4.CET，确保当前不处于关闭写保护的区间内
+    MMArch::enable_kernel_wp();
+
+    let err: usize;
+    unsafe {
+        core::arch::asm!(
+            "2:",
+            "wrussq {val}, ({addr})",
+            "xor {err}, {err}",
+            "jmp 3f",
+            "4:",
+            "mov $1, {err}",
+            "3:",
+            ".pushsection __ex_table, \"a\"",
+            ".balign 8",
+            ".quad 2b - .",
+            ".quad 4b - . + 8",
+            ".popsection",
+            addr = in(reg) addr,
+            val = in(reg) val,
+            err = out(reg) err,
+            options(att_syntax, nostack)
+        );
+    }
+    if err != 0 {
+        return Err(SystemError::EFAULT);
+    }
+    Ok(())
+}
+
+fn read_user_shstk_64(addr: usize) -> Result<u64, SystemError> {
+    let reader = UserBufferReader::new(addr as *const u64, SS_FRAME_SIZE, true)?;
+    let mut val = 0u64;
+    reader.copy_one_from_user_checked(&mut val, 0)?;
+    Ok(val)
+}
+
+/// 检查`addr`是否位于影子栈VMA中
+fn in_shstk_vma(addr: usize) -> Result<bool, SystemError> {
+    let vm = current_user_vm()?;
+    let space = vm.read();
+    Ok(space
+        .mappings
+        .contains(VirtAddr::new(addr))
+        .is_some_and(|vma| vma.lock().vm_flags().contains(VmFlags::VM_SHADOW_STACK)))
+}
+
+/// # 功能
+///
+/// 进入信号处理函数前，在影子栈上压入信号帧：先压入信号发生前的SSP（最高位置1，
+/// 防止被当作返回地址使用），再压入restorer地址，使处理函数的`ret`能通过影子栈检查。
+///
+/// ## 参数
+/// - `restorer`: 信号处理函数返回的地址（sa_restorer）
+pub fn setup_signal_shadow_stack(restorer: usize) -> Result<(), SystemError> {
+    let pcb = ProcessManager::current_pcb();
+    if !pcb.arch_info_irqsave().shstk().enabled() {
+        return Ok(());
+    }
+    if restorer == 0 {
+        return Err(SystemError::EINVAL);
+    }
+
+    let target_ssp = unsafe { rdmsr(MSR_IA32_PL3_SSP) } as usize;
+    if target_ssp == 0 || target_ssp % SS_FRAME_SIZE != 0 {
+        return Err(SystemError::EINVAL);
+    }
+    let mut ssp = target_ssp - SS_FRAME_SIZE;
+    write_user_shstk_64(ssp, target_ssp as u64 | (1 << 63))?;
+    ssp -= SS_FRAME_SIZE;
+    write_user_shstk_64(ssp, restorer as u64)?;
+
+    unsafe { wrmsr(MSR_IA32_PL3_SSP, ssp as u64) };
+    Ok(())
+}
+
+/// # 功能
+///
+/// rt_sigreturn时从影子栈弹出信号帧，恢复信号发生前的SSP。
+/// restorer地址此时已经被处理函数的`ret`弹出。
+pub fn restore_signal_shadow_stack() -> Result<(), SystemError> {
+    let pcb = ProcessManager::current_pcb();
+    if !pcb.arch_info_irqsave().shstk().enabled() {
+        return Ok(());
+    }
+
+    let ssp = unsafe { rdmsr(MSR_IA32_PL3_SSP) } as usize;
+    if ssp % SS_FRAME_SIZE != 0 {
+        return Err(SystemError::EINVAL);
+    }
+    let token = read_user_shstk_64(ssp)?;
+    // 没有最高位的条目是普通的返回地址，不是信号帧
+    if token & (1 << 63) == 0 {
+        return Err(SystemError::EINVAL);
+    }
+    let target_ssp = (token & !(1 << 63)) as usize;
+    if target_ssp % SS_FRAME_SIZE != 0 || target_ssp <= ssp {
+        return Err(SystemError::EINVAL);
+    }
+    // 恢复的SSP必须仍然在影子栈中
+    if !in_shstk_vma(ssp)? || !in_shstk_vma(target_ssp - SS_FRAME_SIZE)? {
+        return Err(SystemError::EINVAL);
+    }
+
+    unsafe { wrmsr(MSR_IA32_PL3_SSP, target_ssp as u64) };
+    Ok(())
+}
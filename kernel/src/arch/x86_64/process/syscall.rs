@@ -4,7 +4,10 @@ use system_error::SystemError;
 use crate::{
     arch::{
         interrupt::TrapFrame,
-        process::table::{USER_CS, USER_DS},
+        process::{
+            shstk::{shstk_prctl, shstk_reset_on_exec, ARCH_SHSTK_ENABLE, ARCH_SHSTK_STATUS},
+            table::{USER_CS, USER_DS},
+        },
         MMArch,
     },
     mm::{access_ok, MemoryManagementArch, VirtAddr},
@@ -43,6 +46,9 @@ impl Syscall {
         regs.rflags = 0x200;
         regs.rax = 1;
 
+        // 新程序需要重新通过arch_prctl启用影子栈
+        shstk_reset_on_exec(&ProcessManager::current_pcb());
+
         // debug!("regs: {:?}\n", regs);
 
         // crate::debug!(
@@ -66,6 +72,11 @@ impl Syscall {
     ///
     /// 这种设计符合Linux语义:EINVAL表示"无效选项",其他错误表示"选项有效但操作失败"
     pub fn arch_prctl(option: usize, arg2: usize) -> Result<usize, SystemError> {
+        // 影子栈的操作需要分配内存，不能在持有arch_info锁的do_arch_prctl_64中处理
+        if (ARCH_SHSTK_ENABLE..=ARCH_SHSTK_STATUS).contains(&option) {
+            return shstk_prctl(option, arg2);
+        }
+
         let pcb = ProcessManager::current_pcb();
         let result = Self::do_arch_prctl_64(&pcb, option, arg2, true);
 
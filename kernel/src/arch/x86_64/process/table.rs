@@ -5,6 +5,8 @@ use crate::{
     smp::core::smp_get_processor_id,
 };
 
+use super::entry_area::CpuEntryArea;
+
 // === 段选择子在GDT中的索引 ===
 /// kernel code segment selector
 pub const KERNEL_CS: SegmentSelector = SegmentSelector::new(1, Ring::Ring0);
@@ -43,6 +45,31 @@ impl TSSManager {
         &mut TSS_MANAGER.tss[smp_get_processor_id().data() as usize]
     }
 
+    /// 设置当前CPU从用户态进入内核时使用的内核栈
+    ///
+    /// 开启KPTI时，TSS中的rsp0固定指向入口栈，真正的内核栈记录在CPU入口区域中，由入口代码切换过去
+    pub unsafe fn set_kernel_stack(stack_top: usize) {
+        let area = CpuEntryArea::current();
+        area.set_kernel_rsp0(stack_top);
+        let rsp0 = if area.pti_enabled() {
+            area.entry_stack_top()
+        } else {
+            stack_top
+        };
+        Self::current_tss().set_rsp(Ring::Ring0, rsp0 as u64);
+    }
+
+    /// 所有CPU的TSS所占的虚拟地址范围（起始地址，字节数）
+    #[allow(static_mut_refs)]
+    pub fn region() -> (usize, usize) {
+        unsafe {
+            (
+                TSS_MANAGER.tss.as_ptr() as usize,
+                core::mem::size_of_val(&TSS_MANAGER.tss),
+            )
+        }
+    }
+
     /// 加载当前CPU的TSS
     pub unsafe fn load_tr() {
         let index = (10 + smp_get_processor_id().data() * 2) as u16;
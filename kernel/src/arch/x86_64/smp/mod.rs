@@ -26,6 +26,7 @@ use crate::{
 use super::{
     acpi::early_acpi_boot_init,
     interrupt::ipi::{ipi_send_smp_init, ipi_send_smp_startup},
+    mptable::early_mptable_boot_init,
     CurrentIrqArch,
 };
 
@@ -80,15 +81,12 @@ unsafe extern "C" fn smp_ap_start_stage1() -> ! {
     debug!("smp_ap_start_stage1: id: {}\n", id.data());
     let current_idle = ProcessManager::idle_pcb()[smp_get_processor_id().data() as usize].clone();
 
-    let tss = TSSManager::current_tss();
-
-    tss.set_rsp(
-        x86::Ring::Ring0,
-        current_idle.kernel_stack().stack_max_address().data() as u64,
-    );
+    crate::arch::mm::pti::pti_cpu_init();
+    TSSManager::set_kernel_stack(current_idle.kernel_stack().stack_max_address().data());
     TSSManager::load_tr();
 
     CurrentIrqArch::arch_ap_early_irq_init().expect("arch_ap_early_irq_init failed");
+    crate::arch::process::shstk::shstk_cpu_init();
 
     smp_ap_start_stage2();
 }
@@ -200,7 +198,11 @@ pub struct X86_64SMPArch;
 impl SMPArch for X86_64SMPArch {
     #[inline(never)]
     fn prepare_cpus() -> Result<(), SystemError> {
-        early_acpi_boot_init()?;
+        if let Err(e) = early_acpi_boot_init() {
+            // 没有ACPI时从MP表获取处理器信息
+            log::warn!("early_acpi_boot_init failed: {:?}, trying MP table", e);
+            early_mptable_boot_init()?;
+        }
         X86_64_SMP_MANAGER.build_cpu_map()?;
         return Ok(());
     }
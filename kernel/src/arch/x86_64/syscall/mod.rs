@@ -25,8 +25,8 @@ pub mod nr;
 
 /// ### 存储PCB系统调用栈以及在syscall过程中暂存用户态rsp的结构体
 ///
-/// 在syscall指令中将会从该结构体中读取系统调用栈和暂存rsp,
-/// 使用`gsbase`寄存器实现，后续如果需要使用gsbase寄存器，需要相应设置正确的偏移量
+/// 进程切换时，系统调用栈会被拷贝到当前CPU的入口区域（见[`crate::arch::process::entry_area`]），
+/// syscall入口通过`gsbase`寄存器从入口区域中读取系统调用栈和暂存rsp，两者的偏移量保持一致
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub(super) struct X86_64GSData {
@@ -0,0 +1,328 @@
+//! 访问MMIO的指令的解码
+//!
+//! 客户机访问没有内存槽的物理地址时，EPT违例的退出信息中只有地址，没有访问的宽度和数据，
+//! 需要取出并解码引起退出的指令，才能把访问交给用户态的设备模型。
+//! 这里只支持设备驱动访问寄存器时常用的MOV、MOVZX，其余指令交给用户态报告错误。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/kvm/emulate.c
+
+use system_error::SystemError;
+
+use crate::virt::vm::kvm_host::Vm;
+
+use super::kvm_host::KvmReg;
+
+/// x86指令的最大长度
+pub const MAX_INSN_LEN: usize = 15;
+
+/// 按ModRM中的编号排列的通用寄存器
+const GPRS: [KvmReg; 16] = [
+    KvmReg::VcpuRegsRax,
+    KvmReg::VcpuRegsRcx,
+    KvmReg::VcpuRegsRdx,
+    KvmReg::VcpuRegsRbx,
+    KvmReg::VcpuRegsRsp,
+    KvmReg::VcpuRegsRbp,
+    KvmReg::VcpuRegsRsi,
+    KvmReg::VcpuRegsRdi,
+    KvmReg::VcpuRegsR8,
+    KvmReg::VcpuRegsR9,
+    KvmReg::VcpuRegsR10,
+    KvmReg::VcpuRegsR11,
+    KvmReg::VcpuRegsR12,
+    KvmReg::VcpuRegsR13,
+    KvmReg::VcpuRegsR14,
+    KvmReg::VcpuRegsR15,
+];
+
+/// 指令中的寄存器操作数
+#[derive(Debug, Clone, Copy)]
+pub struct GprOperand {
+    pub reg: KvmReg,
+    /// 没有REX前缀时，8位操作数的4-7号寄存器是AH、CH、DH、BH
+    pub high_byte: bool,
+}
+
+/// 指令对MMIO的操作
+#[derive(Debug, Clone, Copy)]
+pub enum MmioOp {
+    /// 读MMIO，零扩展到`dest_size`字节之后写入寄存器
+    Load { dest: GprOperand, dest_size: usize },
+    /// 把寄存器的值写入MMIO
+    StoreReg(GprOperand),
+    /// 把立即数写入MMIO
+    StoreImm(u64),
+}
+
+/// 解码之后的MMIO访问
+#[derive(Debug, Clone, Copy)]
+pub struct MmioInsn {
+    /// 指令的长度
+    pub len: usize,
+    /// 访问的字节数
+    pub size: usize,
+    pub op: MmioOp,
+}
+
+/// # 解码访问MMIO的指令
+///
+/// ## 参数
+/// - `insn`: 从RIP开始取出的指令字节，可以比指令长
+/// - `code_size`: 代码段的默认操作数大小，16位代码为2，32位为4，64位为8
+///
+/// ## 返回值
+/// - `None`: 不支持的指令，或者指令的操作数不在内存中
+pub fn decode_mmio_insn(insn: &[u8], code_size: usize) -> Option<MmioInsn> {
+    let mut i = 0;
+    let mut opsize_override = false;
+    let mut addrsize_override = false;
+    loop {
+        match *insn.get(i)? {
+            0x66 => opsize_override = true,
+            0x67 => addrsize_override = true,
+            // 段前缀不影响访问的物理地址（已经在退出信息中）
+            0x26 | 0x2e | 0x36 | 0x3e | 0x64 | 0x65 => {}
+            _ => break,
+        }
+        i += 1;
+    }
+
+    let mut rex = 0u8;
+    if code_size == 8 {
+        if let b @ 0x40..=0x4f = *insn.get(i)? {
+            rex = b;
+            i += 1;
+        }
+    }
+    let rex_w = rex & 0x8 != 0;
+    let rex_r = rex & 0x4 != 0;
+
+    let op_size = match (rex_w, code_size, opsize_override) {
+        (true, _, _) => 8,
+        (false, 2, false) | (false, 4 | 8, true) => 2,
+        _ => 4,
+    };
+    let addr_size = match (code_size, addrsize_override) {
+        (8, false) => 8,
+        (2, false) | (4, true) => 2,
+        _ => 4,
+    };
+
+    let mut opcode = *insn.get(i)?;
+    i += 1;
+    let two_byte = opcode == 0x0f;
+    if two_byte {
+        opcode = *insn.get(i)?;
+        i += 1;
+    }
+
+    let modrm = *insn.get(i)?;
+    i += 1;
+    let md = modrm >> 6;
+    let reg = ((modrm >> 3) & 7) as usize | if rex_r { 8 } else { 0 };
+    let rm = modrm & 7;
+    // 操作数是寄存器，不可能访问MMIO
+    if md == 3 {
+        return None;
+    }
+
+    // 跳过SIB和偏移量，访问的地址已经在退出信息中
+    let disp = if addr_size == 2 {
+        match md {
+            0 if rm == 6 => 2,
+            0 => 0,
+            1 => 1,
+            _ => 2,
+        }
+    } else {
+        let mut disp = match md {
+            0 => 0,
+            1 => 1,
+            _ => 4,
+        };
+        if rm == 4 {
+            let sib = *insn.get(i)?;
+            i += 1;
+            if md == 0 && sib & 7 == 5 {
+                disp = 4;
+            }
+        } else if md == 0 && rm == 5 {
+            disp = 4;
+        }
+        disp
+    };
+    i += disp;
+
+    let gpr = |size: usize| {
+        if size == 1 && rex == 0 && (4..8).contains(&reg) {
+            GprOperand {
+                reg: GPRS[reg - 4],
+                high_byte: true,
+            }
+        } else {
+            GprOperand {
+                reg: GPRS[reg],
+                high_byte: false,
+            }
+        }
+    };
+
+    let (size, op) = match (two_byte, opcode) {
+        // MOV r/m8, r8
+        (false, 0x88) => (1, MmioOp::StoreReg(gpr(1))),
+        // MOV r/m, r
+        (false, 0x89) => (op_size, MmioOp::StoreReg(gpr(op_size))),
+        // MOV r8, r/m8
+        (false, 0x8a) => (
+            1,
+            MmioOp::Load {
+                dest: gpr(1),
+                dest_size: 1,
+            },
+        ),
+        // MOV r, r/m
+        (false, 0x8b) => (
+            op_size,
+            MmioOp::Load {
+                dest: gpr(op_size),
+                dest_size: op_size,
+            },
+        ),
+        // MOV r/m8, imm8
+        (false, 0xc6) if reg & 7 == 0 => {
+            let imm = *insn.get(i)?;
+            i += 1;
+            (1, MmioOp::StoreImm(imm as u64))
+        }
+        // MOV r/m, imm16/imm32，64位操作数时立即数符号扩展
+        (false, 0xc7) if reg & 7 == 0 => {
+            let imm_size = op_size.min(4);
+            let mut raw = [0u8; 8];
+            raw[..imm_size].copy_from_slice(insn.get(i..i + imm_size)?);
+            i += imm_size;
+            let mut imm = u64::from_le_bytes(raw);
+            if op_size == 8 {
+                imm = imm as u32 as i32 as i64 as u64;
+            }
+            (op_size, MmioOp::StoreImm(imm))
+        }
+        // MOVZX r, r/m8
+        (true, 0xb6) => (
+            1,
+            MmioOp::Load {
+                dest: gpr(op_size),
+                dest_size: op_size,
+            },
+        ),
+        // MOVZX r, r/m16
+        (true, 0xb7) => (
+            2,
+            MmioOp::Load {
+                dest: gpr(op_size),
+                dest_size: op_size,
+            },
+        ),
+        _ => return None,
+    };
+
+    if i > insn.len() || i > MAX_INSN_LEN {
+        return None;
+    }
+    Some(MmioInsn { len: i, size, op })
+}
+
+/// 客户机的分页模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestPaging {
+    /// 没有开启分页，线性地址就是物理地址
+    None,
+    /// 32位分页，`pse`为CR4.PSE
+    Bits32 { pse: bool },
+    /// PAE分页
+    Pae,
+    /// IA-32e的4级分页
+    Long,
+}
+
+const PTE_PRESENT: u64 = 1 << 0;
+const PTE_PAGE_SIZE: u64 = 1 << 7;
+const PTE_ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// # 遍历客户机页表，把线性地址转换为客户机物理地址
+///
+/// 只用于取指令，不检查访问权限，也不设置访问位
+///
+/// ## 返回值
+/// - `Err(SystemError::EFAULT)`: 线性地址没有映射，或者页表不在内存槽中
+pub fn gva_to_gpa(vm: &Vm, paging: GuestPaging, cr3: u64, gva: u64) -> Result<u64, SystemError> {
+    let read_entry = |gpa: u64, size: usize| -> Result<u64, SystemError> {
+        let mut raw = [0u8; 8];
+        vm.read_guest(gpa, &mut raw[..size])?;
+        let entry = u64::from_le_bytes(raw);
+        if entry & PTE_PRESENT == 0 {
+            return Err(SystemError::EFAULT);
+        }
+        Ok(entry)
+    };
+
+    match paging {
+        GuestPaging::None => Ok(gva & 0xffff_ffff),
+        GuestPaging::Bits32 { pse } => {
+            let gva = gva & 0xffff_ffff;
+            let pde = read_entry((cr3 & 0xffff_f000) + ((gva >> 22) << 2), 4)?;
+            if pse && pde & PTE_PAGE_SIZE != 0 {
+                // 4M页，物理地址的32-39位在PDE的13-20位
+                let base = (pde & 0xffc0_0000) | ((pde & 0x001f_e000) << 19);
+                return Ok(base | (gva & 0x003f_ffff));
+            }
+            let pte = read_entry((pde & 0xffff_f000) + (((gva >> 12) & 0x3ff) << 2), 4)?;
+            Ok((pte & 0xffff_f000) | (gva & 0xfff))
+        }
+        GuestPaging::Pae | GuestPaging::Long => {
+            let (mut table, mut shift) = if paging == GuestPaging::Pae {
+                let gva = gva & 0xffff_ffff;
+                let pdpte = read_entry((cr3 & 0xffff_ffe0) + ((gva >> 30) << 3), 8)?;
+                (pdpte & PTE_ADDR_MASK, 21)
+            } else {
+                (cr3 & PTE_ADDR_MASK, 39)
+            };
+            loop {
+                let entry = read_entry(table + (((gva >> shift) & 0x1ff) << 3), 8)?;
+                let page_mask = (1u64 << shift) - 1;
+                if shift == 12 || ((shift == 21 || shift == 30) && entry & PTE_PAGE_SIZE != 0) {
+                    return Ok((entry & PTE_ADDR_MASK & !page_mask) | (gva & page_mask));
+                }
+                table = entry & PTE_ADDR_MASK;
+                shift -= 9;
+            }
+        }
+    }
+}
+
+/// # 从客户机的线性地址取出指令
+///
+/// 指令可能跨页，每一页分别转换地址
+///
+/// ## 返回值
+/// - `Ok(usize)`: 取到的字节数。后一页不可读时只返回前一页中的部分
+pub fn fetch_insn(
+    vm: &Vm,
+    paging: GuestPaging,
+    cr3: u64,
+    linear_rip: u64,
+    buf: &mut [u8; MAX_INSN_LEN],
+) -> Result<usize, SystemError> {
+    let mut done = 0;
+    while done < MAX_INSN_LEN {
+        let gva = linear_rip.wrapping_add(done as u64);
+        let n = (MAX_INSN_LEN - done).min(0x1000 - (gva & 0xfff) as usize);
+        let r = gva_to_gpa(vm, paging, cr3, gva)
+            .and_then(|gpa| vm.read_guest(gpa, &mut buf[done..done + n]));
+        match r {
+            Ok(()) => done += n,
+            Err(e) if done == 0 => return Err(e),
+            Err(_) => break,
+        }
+    }
+    Ok(done)
+}
@@ -27,19 +27,21 @@ use crate::{
         vm::{
             asm::{hyperv, kvm_msr, KvmX86Asm, MiscEnable, MsrData, VcpuSegment},
             cpuid::KvmCpuidEntry2,
+            emulate::GprOperand,
             kvm_host::KvmReg,
             mmu::kvm_mmu::LockedKvmMmu,
-            uapi::{UapiKvmSegmentRegs, KVM_SYNC_X86_VALID_FIELDS},
+            uapi::{kvm_exit, UapiKvmSegmentRegs, KVM_SYNC_X86_VALID_FIELDS},
             vmx::{vmcs::ControlsType, vmx_info},
             x86_kvm_manager, x86_kvm_manager_mut, x86_kvm_ops,
         },
     },
     mm::VirtAddr,
+    process::ProcessManager,
     smp::{core::smp_get_processor_id, cpu::ProcessorId},
     virt::vm::{
         kvm_host::{
             mem::GfnToHvaCache,
-            vcpu::{GuestDebug, VirtCpu},
+            vcpu::{GuestDebug, KvmRunPages, VirtCpu},
             MutilProcessorState, Vm,
         },
         user_api::{UapiKvmRun, UapiKvmSegment},
@@ -149,6 +151,30 @@ pub struct X86VcpuArch {
 
     /* set at EPT violation at this point */
     pub exit_qual: u64,
+
+    /// 交给用户态完成的读操作，下一次KVM_RUN时把用户态提供的数据写回寄存器
+    pub pending_user_read: Option<UserspaceRead>,
+}
+
+/// 交给用户态完成的读操作的数据所在的位置
+#[derive(Debug, Clone, Copy)]
+pub enum UserspaceReadSource {
+    /// IN指令，数据在PIO数据页
+    Pio,
+    /// MMIO读，数据在`kvm_run.mmio.data`
+    Mmio,
+}
+
+/// 交给用户态完成的读操作
+#[derive(Debug, Clone, Copy)]
+pub struct UserspaceRead {
+    pub source: UserspaceReadSource,
+    /// 目的寄存器
+    pub dest: GprOperand,
+    /// 读取的字节数
+    pub size: usize,
+    /// 零扩展之后写入寄存器的字节数
+    pub dest_size: usize,
 }
 
 impl X86VcpuArch {
@@ -163,6 +189,7 @@ impl X86VcpuArch {
         ret.mp_state = MutilProcessorState::Runnable;
 
         ret.apic = None;
+        ret.pending_user_read = None;
         //max_phyaddr=?? fztodo
         *ret
     }
@@ -350,6 +377,38 @@ impl X86VcpuArch {
         return self.regs[reg as usize];
     }
 
+    /// 按操作数的宽度读通用寄存器
+    pub fn read_gpr(&self, gpr: GprOperand, size: usize) -> u64 {
+        let val = self.read_reg(gpr.reg);
+        if gpr.high_byte {
+            return (val >> 8) & 0xff;
+        }
+        match size {
+            1 => val & 0xff,
+            2 => val & 0xffff,
+            4 => val & 0xffff_ffff,
+            _ => val,
+        }
+    }
+
+    /// # 按操作数的宽度写通用寄存器
+    ///
+    /// 与硬件相同，写32位操作数时清零高32位，写8位、16位操作数时保留其余的位
+    pub fn write_gpr(&mut self, gpr: GprOperand, size: usize, val: u64) {
+        let old = self.read_reg(gpr.reg);
+        let new = if gpr.high_byte {
+            (old & !0xff00) | ((val & 0xff) << 8)
+        } else {
+            match size {
+                1 => (old & !0xff) | (val & 0xff),
+                2 => (old & !0xffff) | (val & 0xffff),
+                4 => val & 0xffff_ffff,
+                _ => val,
+            }
+        };
+        self.write_reg_raw(gpr.reg, new);
+    }
+
     #[inline]
     pub fn read_reg_raw(&mut self, reg: KvmReg) -> u64 {
         if self.regs_avail.get(reg as usize) == Some(true) {
@@ -643,14 +702,34 @@ impl VirtCpu {
 
     #[inline]
     pub fn kvm_run(&self) -> &UapiKvmRun {
-        self.run.as_ref().unwrap()
+        self.run.as_deref().unwrap()
     }
 
     #[inline]
-    pub fn kvm_run_mut(&mut self) -> &mut Box<UapiKvmRun> {
+    pub fn kvm_run_mut(&mut self) -> &mut KvmRunPages {
         self.run.as_mut().unwrap()
     }
 
+    /// # 用户态处理完上一次的exit之后，把它提供的数据写回目的寄存器
+    ///
+    /// 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/kvm/x86.c#complete_fast_pio_in
+    fn complete_userspace_io(&mut self) {
+        let Some(read) = self.arch.pending_user_read.take() else {
+            return;
+        };
+        let mut raw = [0u8; 8];
+        let run = self.kvm_run_mut();
+        match read.source {
+            UserspaceReadSource::Pio => {
+                raw[..read.size].copy_from_slice(&run.pio_data()[..read.size])
+            }
+            UserspaceReadSource::Mmio => raw[..read.size]
+                .copy_from_slice(unsafe { &run.__bindgen_anon_1.mmio.data[..read.size] }),
+        }
+        self.arch
+            .write_gpr(read.dest, read.dest_size, u64::from_le_bytes(raw));
+    }
+
     pub fn run(&mut self) -> Result<usize, SystemError> {
         self.load();
 
@@ -674,7 +753,10 @@ impl VirtCpu {
 
         // TODO: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/kvm/x86.c#11174 - 11196
 
+        self.complete_userspace_io();
+
         if self.kvm_run().immediate_exit != 0 {
+            self.kvm_run_mut().exit_reason = kvm_exit::KVM_EXIT_INTR;
             return Err(SystemError::EINTR);
         }
 
@@ -685,20 +767,41 @@ impl VirtCpu {
         Ok(0)
     }
 
+    /// # 运行客户机，直到遇到需要用户态处理的exit
+    ///
+    /// 返回`Ok(())`时，exit的原因和参数已经填写在kvm_run中
     fn vcpu_run(&mut self, vm: &Vm) -> Result<(), SystemError> {
         self.arch.l1tf_flush_l1d = true;
 
         loop {
             self.arch.at_instruction_boundary = false;
-            if self.can_running() {
-                self.enter_guest(vm)?;
+            let r = if self.can_running() {
+                self.enter_guest(vm)?
             } else {
                 todo!()
             };
+
+            if r < 0 {
+                return Err(SystemError::from_posix_errno(r).unwrap_or(SystemError::EIO));
+            }
+            if r == 0 {
+                return Ok(());
+            }
+
+            let pcb = ProcessManager::current_pcb();
+            if pcb.has_pending_signal_fast() && pcb.has_pending_not_masked_signal() {
+                self.kvm_run_mut().exit_reason = kvm_exit::KVM_EXIT_INTR;
+                return Err(SystemError::EINTR);
+            }
         }
     }
 
-    fn enter_guest(&mut self, vm: &Vm) -> Result<(), SystemError> {
+    /// # 进入客户机一次并处理VM exit
+    ///
+    /// ## 返回值
+    /// - `Ok(1)`: exit已经在内核中处理完，可以继续运行客户机
+    /// - `Ok(0)`: 需要返回用户态处理
+    fn enter_guest(&mut self, vm: &Vm) -> Result<i32, SystemError> {
         let req_immediate_exit = false;
 
         warn!("request {:?}", self.request);
@@ -886,10 +989,7 @@ impl VirtCpu {
 
         // TODO: 一些中断或者tsc操作
 
-        match x86_kvm_ops().handle_exit(self, vm, exit_fastpath) {
-            Err(err) => return Err(err),
-            Ok(_) => Ok(()),
-        }
+        x86_kvm_ops().handle_exit(self, vm, exit_fastpath)
     }
 
     fn flush_tlb_all(&mut self) {
@@ -23,6 +23,7 @@ use super::driver::tsc::TSCManager;
 
 mod asm;
 mod cpuid;
+pub mod emulate;
 pub(super) mod exit;
 pub mod kvm_host;
 pub mod mem;
@@ -59,6 +59,13 @@ pub struct UapiKvmDtable {
     pub padding: [u16; 3usize],
 }
 
+/// KVM_EXIT_IO的方向
+pub const KVM_EXIT_IO_IN: u8 = 1;
+pub const KVM_EXIT_IO_OUT: u8 = 0;
+
+/// KVM_EXIT_INTERNAL_ERROR的子错误码：无法模拟指令
+pub const KVM_INTERNAL_ERROR_EMULATION: u32 = 1;
+
 #[allow(dead_code)]
 pub mod kvm_exit {
     pub const KVM_EXIT_UNKNOWN: u32 = 0;
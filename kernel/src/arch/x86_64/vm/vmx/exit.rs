@@ -1,10 +1,37 @@
 use bitfield_struct::bitfield;
 use system_error::SystemError;
-use x86::vmx::vmcs::{guest, ro};
+use x86::{
+    controlregs::Cr4,
+    vmx::vmcs::{guest, ro},
+};
 
 use crate::{
-    arch::vm::asm::{IntrInfo, VmxAsm},
-    virt::vm::kvm_host::{vcpu::VirtCpu, Vm},
+    arch::{
+        vm::{
+            asm::{IntrInfo, VmxAsm},
+            emulate::{
+                decode_mmio_insn, fetch_insn, GprOperand, GuestPaging, MmioOp, MAX_INSN_LEN,
+            },
+            kvm_host::{
+                vcpu::{UserspaceRead, UserspaceReadSource},
+                KvmReg,
+            },
+            mmu::kvm_mmu::PAGE_SHIFT,
+            uapi::{kvm_exit, KVM_EXIT_IO_IN, KVM_EXIT_IO_OUT, KVM_INTERNAL_ERROR_EMULATION},
+        },
+        MMArch,
+    },
+    mm::MemoryManagementArch,
+    virt::vm::{
+        kvm_host::{
+            vcpu::{VirtCpu, KVM_PIO_PAGE_OFFSET},
+            Vm,
+        },
+        user_api::{
+            UapiKvmRunBindgenTy1BindgenTy12, UapiKvmRunBindgenTy1BindgenTy4,
+            UapiKvmRunBindgenTy1BindgenTy6,
+        },
+    },
 };
 
 use super::{ept::EptViolationExitQual, vmx_info, PageFaultErr};
@@ -257,7 +284,7 @@ impl VmxExitHandlers {
         // let _guest_rflags = vmx_vmread(VmcsFields::GUEST_RFLAGS as u32).unwrap();
         match basic {
             VmxExitReasonBasic::IO_INSTRUCTION => {
-                return Some(Self::handle_io(vcpu));
+                return Some(Self::handle_io(vcpu, vm));
             }
             VmxExitReasonBasic::EPT_VIOLATION => {
                 let r = Some(Self::handle_ept_violation(vcpu, vm));
@@ -274,8 +301,161 @@ impl VmxExitHandlers {
         }
     }
 
-    fn handle_io(_vcpu: &mut VirtCpu) -> Result<i32, SystemError> {
-        todo!();
+    /// # 处理I/O指令引起的VM exit
+    ///
+    /// 访问的端口注册在VM的PIO总线上时直接在内核中完成，否则交给用户态（KVM_EXIT_IO），
+    /// 数据放在kvm_run之后的PIO数据页中。字符串I/O（INS/OUTS）需要模拟指令，目前不支持
+    ///
+    /// 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/kvm/vmx/vmx.c#5156
+    fn handle_io(vcpu: &mut VirtCpu, vm: &Vm) -> Result<i32, SystemError> {
+        let exit_qualification = vcpu.get_exit_qual();
+        // 退出条件的格式见Intel SDM卷3 表28-5
+        let size = (exit_qualification & 7) as usize + 1;
+        let is_in = exit_qualification & (1 << 3) != 0;
+        let string = exit_qualification & (1 << 4) != 0;
+        let port = (exit_qualification >> 16) as u16;
+
+        vcpu.stat.io_exits += 1;
+
+        if string {
+            return Ok(Self::emulation_failure(vcpu));
+        }
+
+        let rax = GprOperand {
+            reg: KvmReg::VcpuRegsRax,
+            high_byte: false,
+        };
+        let rip = VmxAsm::vmx_vmread(guest::RIP) + VmxAsm::vmx_vmread(ro::VMEXIT_INSTRUCTION_LEN);
+
+        if let Some((dev, offset)) = vm.pio_bus().find(port as u64, size as u64) {
+            if is_in {
+                let mut data = [0u8; 8];
+                dev.read(vcpu, vm, offset, &mut data[..size])?;
+                // 32位的IN会清零RAX的高32位，8/16位的IN只改写低位
+                vcpu.arch.write_gpr(rax, size, u64::from_le_bytes(data));
+            } else {
+                let data = vcpu.arch.read_gpr(rax, size).to_le_bytes();
+                dev.write(vcpu, vm, offset, &data[..size])?;
+            }
+            vcpu.arch.write_reg_raw(KvmReg::VcpuRegsRip, rip);
+            return Ok(1);
+        }
+
+        // 交给用户态之前就跳过这条指令，IN读到的数据在下一次KVM_RUN时写入RAX
+        vcpu.arch.write_reg_raw(KvmReg::VcpuRegsRip, rip);
+        if is_in {
+            vcpu.arch.pending_user_read = Some(UserspaceRead {
+                source: UserspaceReadSource::Pio,
+                dest: rax,
+                size,
+                dest_size: size,
+            });
+        }
+        let data = vcpu.arch.read_gpr(rax, size).to_le_bytes();
+        let run = vcpu.kvm_run_mut();
+        if !is_in {
+            run.pio_data()[..size].copy_from_slice(&data[..size]);
+        }
+        run.exit_reason = kvm_exit::KVM_EXIT_IO;
+        run.__bindgen_anon_1.io = UapiKvmRunBindgenTy1BindgenTy4 {
+            direction: if is_in {
+                KVM_EXIT_IO_IN
+            } else {
+                KVM_EXIT_IO_OUT
+            },
+            size: size as u8,
+            port,
+            count: 1,
+            data_offset: (KVM_PIO_PAGE_OFFSET * MMArch::PAGE_SIZE) as u64,
+        };
+        Ok(0)
+    }
+
+    /// # 处理对MMIO的访问
+    ///
+    /// 客户机访问的物理地址不在任何内存槽中时，解码引起退出的指令，
+    /// 把访问交给用户态的设备模型（KVM_EXIT_MMIO）。读操作在下一次KVM_RUN时
+    /// 把用户态填写在`kvm_run.mmio.data`中的数据写回目的寄存器
+    fn handle_mmio(vcpu: &mut VirtCpu, vm: &Vm, gpa: u64) -> Result<i32, SystemError> {
+        vcpu.stat.mmio_exits += 1;
+
+        let paging = if !vcpu.arch.is_paging() {
+            GuestPaging::None
+        } else if vcpu.arch.is_long_mode() {
+            GuestPaging::Long
+        } else if vcpu.arch.is_pae() {
+            GuestPaging::Pae
+        } else {
+            GuestPaging::Bits32 {
+                pse: !vcpu.arch.read_cr4_bits(Cr4::CR4_ENABLE_PSE).is_empty(),
+            }
+        };
+        let cr3 = VmxAsm::vmx_vmread(guest::CR3);
+        let rip = VmxAsm::vmx_vmread(guest::RIP);
+        let linear_rip = VmxAsm::vmx_vmread(guest::CS_BASE).wrapping_add(rip);
+        // 代码段访问权限的第13位为L，第14位为D/B
+        let cs_ar = VmxAsm::vmx_vmread(guest::CS_ACCESS_RIGHTS);
+        let code_size = if vcpu.arch.is_long_mode() && cs_ar & (1 << 13) != 0 {
+            8
+        } else if cs_ar & (1 << 14) != 0 {
+            4
+        } else {
+            2
+        };
+
+        let mut insn = [0u8; MAX_INSN_LEN];
+        let decoded = fetch_insn(vm, paging, cr3, linear_rip, &mut insn)
+            .ok()
+            .and_then(|n| decode_mmio_insn(&insn[..n], code_size));
+        let Some(mmio) = decoded else {
+            return Ok(Self::emulation_failure(vcpu));
+        };
+
+        vcpu.arch
+            .write_reg_raw(KvmReg::VcpuRegsRip, rip + mmio.len as u64);
+
+        let mut data = [0u8; 8];
+        let is_write = match mmio.op {
+            MmioOp::Load { dest, dest_size } => {
+                vcpu.arch.pending_user_read = Some(UserspaceRead {
+                    source: UserspaceReadSource::Mmio,
+                    dest,
+                    size: mmio.size,
+                    dest_size,
+                });
+                false
+            }
+            MmioOp::StoreReg(src) => {
+                data = vcpu.arch.read_gpr(src, mmio.size).to_le_bytes();
+                true
+            }
+            MmioOp::StoreImm(imm) => {
+                data = imm.to_le_bytes();
+                true
+            }
+        };
+
+        let run = vcpu.kvm_run_mut();
+        run.exit_reason = kvm_exit::KVM_EXIT_MMIO;
+        run.__bindgen_anon_1.mmio = UapiKvmRunBindgenTy1BindgenTy6 {
+            phys_addr: gpa,
+            data,
+            len: mmio.size as u32,
+            is_write: is_write as u8,
+        };
+        Ok(0)
+    }
+
+    /// 无法模拟引起退出的指令，交给用户态报告错误
+    fn emulation_failure(vcpu: &mut VirtCpu) -> i32 {
+        let run = vcpu.kvm_run_mut();
+        run.exit_reason = kvm_exit::KVM_EXIT_INTERNAL_ERROR;
+        run.__bindgen_anon_1.internal = UapiKvmRunBindgenTy1BindgenTy12 {
+            suberror: KVM_INTERNAL_ERROR_EMULATION,
+            ndata: 0,
+            data: [0; 16],
+        };
+        0
     }
 
     fn handle_external_interrupt(vcpu: &mut VirtCpu) -> Result<i32, SystemError> {
@@ -297,6 +477,10 @@ impl VmxExitHandlers {
             VmxAsm::vmx_vmwrite(guest::INTERRUPTIBILITY_STATE, 0x8); //GUEST_INTR_STATE_NMI
         }
         let gpa = VmxAsm::vmx_vmread(ro::GUEST_PHYSICAL_ADDR_FULL);
+        // 没有内存槽的地址由用户态的设备模型处理
+        if vcpu.gfn_to_memslot(gpa >> PAGE_SHIFT, vm).is_none() {
+            return Self::handle_mmio(vcpu, vm, gpa);
+        }
         //let exit_qualification = VmxAsm::vmx_vmread(ro::EXIT_QUALIFICATION);
         // trace_kvm_page_fault(vcpu, gpa, exit_qualification);//
 
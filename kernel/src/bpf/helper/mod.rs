@@ -7,6 +7,7 @@ use crate::bpf::helper::print::trace_printf;
 use crate::bpf::map::{BpfCallBackFn, BpfMap};
 use crate::include::bindings::linux_bpf::BPF_F_CURRENT_CPU;
 use crate::libs::lazy_init::Lazy;
+use crate::libs::string::strscpy;
 use crate::smp::core::smp_get_processor_id;
 use crate::syscall::user_access::check_and_clone_cstr;
 use crate::time::Instant;
@@ -333,10 +334,12 @@ unsafe fn raw_probe_read_user_str(dst: *mut c_void, size: u32, unsafe_ptr: *cons
 
 pub fn probe_read_user_str(dst: &mut [u8], src: *const u8) -> Result<usize> {
     let str = check_and_clone_cstr(src, None).unwrap();
-    let len = str.as_bytes().len();
-    let copy_len = len.min(dst.len() - 1); // Leave space for NULL terminator
-    dst[..copy_len].copy_from_slice(&str.as_bytes()[..copy_len]);
-    dst[copy_len] = 0; // Null-terminate the string
+    // 过长时截断，保证以0结尾
+    let copy_len = match strscpy(dst, str.as_bytes()) {
+        Ok(len) => len,
+        Err(_) if !dst.is_empty() => dst.len() - 1,
+        Err(e) => return Err(e),
+    };
     Ok(copy_len + 1) // Return length including NULL terminator
 }
 
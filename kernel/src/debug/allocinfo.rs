@@ -0,0 +1,121 @@
+//! `/sys/kernel/debug/allocinfo`
+//!
+//! - `allocinfo`: 每个分配点上存活的字节数和对象数量
+//! - `allocinfo_leaks`: 读取时按增长量从大到小列出自上一次快照以来存活字节数增长的分配点，
+//!   写入任意内容时重新拍摄快照
+//!
+//! 内容在打开文件时生成，参见[`crate::mm::alloc_tag`]。
+
+use alloc::string::{String, ToString};
+use system_error::SystemError;
+
+use crate::{
+    debug::sysfs::debugfs_kobj,
+    driver::base::kobject::KObject,
+    filesystem::{
+        kernfs::{
+            callback::{KernCallbackData, KernFSCallback, KernInodePrivateData},
+            KernFSInodeArgs, KernInodeType,
+        },
+        vfs::{InodeMode, PollStatus},
+    },
+    mm::alloc_tag::{alloc_leak_report, alloc_tag_snapshot, allocinfo_report},
+};
+
+#[derive(Debug)]
+struct AllocInfoCallBack {
+    leaks: bool,
+}
+
+static ALLOCINFO: AllocInfoCallBack = AllocInfoCallBack { leaks: false };
+static ALLOCINFO_LEAKS: AllocInfoCallBack = AllocInfoCallBack { leaks: true };
+
+impl KernFSCallback for AllocInfoCallBack {
+    fn open(&self, mut data: KernCallbackData) -> Result<(), SystemError> {
+        let report = if self.leaks {
+            alloc_leak_report()
+        } else {
+            allocinfo_report()
+        };
+        data.private_data_mut()
+            .replace(KernInodePrivateData::AllocInfo(report));
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        mut data: KernCallbackData,
+        buf: &mut [u8],
+        offset: usize,
+    ) -> Result<usize, SystemError> {
+        let report = data
+            .private_data_mut()
+            .as_mut()
+            .and_then(|p| p.alloc_info())
+            .ok_or(SystemError::EINVAL)?;
+        let bytes = report.as_bytes();
+        if offset >= bytes.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(bytes.len() - offset);
+        buf[..len].copy_from_slice(&bytes[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn write(
+        &self,
+        _data: KernCallbackData,
+        buf: &[u8],
+        _offset: usize,
+    ) -> Result<usize, SystemError> {
+        if !self.leaks {
+            return Err(SystemError::EPERM);
+        }
+        alloc_tag_snapshot();
+        Ok(buf.len())
+    }
+
+    fn poll(&self, _data: KernCallbackData) -> Result<PollStatus, SystemError> {
+        Ok(PollStatus::READ)
+    }
+}
+
+impl KernInodePrivateData {
+    pub fn alloc_info(&mut self) -> Option<&mut String> {
+        return match self {
+            KernInodePrivateData::AllocInfo(report) => Some(report),
+            _ => None,
+        };
+    }
+}
+
+fn kernel_inode_provider_allocinfo() -> KernFSInodeArgs {
+    KernFSInodeArgs {
+        mode: InodeMode::S_IRUSR,
+        callback: Some(&ALLOCINFO),
+        inode_type: KernInodeType::File,
+        size: Some(4096),
+        private_data: None,
+    }
+}
+
+fn kernel_inode_provider_allocinfo_leaks() -> KernFSInodeArgs {
+    KernFSInodeArgs {
+        mode: InodeMode::S_IRUSR | InodeMode::S_IWUSR,
+        callback: Some(&ALLOCINFO_LEAKS),
+        inode_type: KernInodeType::File,
+        size: Some(4096),
+        private_data: None,
+    }
+}
+
+/// 创建`/sys/kernel/debug/allocinfo`和`/sys/kernel/debug/allocinfo_leaks`
+pub fn init_debugfs_allocinfo() -> Result<(), SystemError> {
+    let root_dir = debugfs_kobj().inode().ok_or(SystemError::ENOENT)?;
+    root_dir.add_file_lazy("allocinfo".to_string(), kernel_inode_provider_allocinfo)?;
+    root_dir.add_file_lazy(
+        "allocinfo_leaks".to_string(),
+        kernel_inode_provider_allocinfo_leaks,
+    )?;
+    Ok(())
+}
@@ -1,2 +1,3 @@
 pub mod loglevel;
 pub mod mm;
+pub mod sink;
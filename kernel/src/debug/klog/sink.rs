@@ -0,0 +1,247 @@
+//! 内核日志输出端
+//!
+//! 内核日志会写到多个输出端：kmsg缓冲区、串口、屏幕和netconsole。
+//! 每个输出端有自己的日志级别和格式选项，默认配置为：
+//! - kmsg: 7，记录所有日志
+//! - serial: 7 (DEBUG)
+//! - screen: 6 (INFO)
+//! - netconsole: 7 (DEBUG)
+//!
+//! 串口、屏幕和netconsole属于控制台，除了自己的级别之外还受console_loglevel
+//! （`loglevel=`、`/proc/sys/kernel/printk`）的限制；kmsg只看自己的级别。
+//!
+//! 配置格式为若干个`<输出端>:<级别>[:color|nocolor]`，以逗号或空白分隔，
+//! 例如`serial:debug,screen:warn:nocolor`。级别可以是0-7、级别名（emerg、alert、
+//! crit、err、warn、notice、info、debug），`all`等同于7，`off`关闭该输出端。
+//! 可以通过`klog.sinks=`命令行参数或者`/proc/sys/kernel/printk_sinks`修改配置。
+
+use core::{
+    fmt::{self, Display},
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use system_error::SystemError;
+
+use super::loglevel::{LogLevel, KERNEL_LOG_LEVEL};
+
+/// 所有输出端的配置
+pub static LOG_SINKS: LogSinks = LogSinks::new();
+
+/// 关闭输出端时使用的级别
+pub const SINK_LEVEL_OFF: u8 = u8::MAX;
+
+/// klog.sinks命令行参数
+///
+/// 示例：klog.sinks=serial:debug,screen:warn
+kernel_cmdline_param_kv!(KLOG_SINKS_PARAM, "klog.sinks", "");
+
+/// 日志输出端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSink {
+    Kmsg = 0,
+    Serial = 1,
+    Screen = 2,
+    Netconsole = 3,
+}
+
+impl LogSink {
+    pub const ALL: [LogSink; 4] = [
+        LogSink::Kmsg,
+        LogSink::Serial,
+        LogSink::Screen,
+        LogSink::Netconsole,
+    ];
+
+    /// 控制台类的输出端
+    pub const CONSOLES: [LogSink; 3] = [LogSink::Serial, LogSink::Screen, LogSink::Netconsole];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogSink::Kmsg => "kmsg",
+            LogSink::Serial => "serial",
+            LogSink::Screen => "screen",
+            LogSink::Netconsole => "netconsole",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sink| sink.name() == name)
+    }
+
+    /// 是否受console_loglevel限制
+    pub fn is_console(&self) -> bool {
+        *self != LogSink::Kmsg
+    }
+}
+
+/// 每个输出端的日志级别和格式选项
+#[derive(Debug)]
+pub struct LogSinks {
+    /// 输出端的日志级别，级别小于等于此值的消息才会输出，`SINK_LEVEL_OFF`表示关闭
+    levels: [AtomicU8; 4],
+    /// 是否用ANSI颜色标出错误和警告
+    color: [AtomicBool; 4],
+}
+
+impl LogSinks {
+    const fn new() -> Self {
+        Self {
+            levels: [
+                AtomicU8::new(7),
+                AtomicU8::new(7),
+                AtomicU8::new(6),
+                AtomicU8::new(7),
+            ],
+            color: [
+                AtomicBool::new(false),
+                AtomicBool::new(true),
+                AtomicBool::new(true),
+                AtomicBool::new(true),
+            ],
+        }
+    }
+
+    pub fn level(&self, sink: LogSink) -> u8 {
+        self.levels[sink as usize].load(Ordering::Acquire)
+    }
+
+    /// 设置输出端的日志级别
+    ///
+    /// ## 参数
+    /// - `level`: 0-7，或者`SINK_LEVEL_OFF`
+    pub fn set_level(&self, sink: LogSink, level: u8) -> Result<(), SystemError> {
+        if level > 7 && level != SINK_LEVEL_OFF {
+            return Err(SystemError::EINVAL);
+        }
+        self.levels[sink as usize].store(level, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn color(&self, sink: LogSink) -> bool {
+        self.color[sink as usize].load(Ordering::Relaxed)
+    }
+
+    /// 设置输出端是否使用颜色，kmsg不保存颜色
+    pub fn set_color(&self, sink: LogSink, color: bool) -> Result<(), SystemError> {
+        if !sink.is_console() && color {
+            return Err(SystemError::EINVAL);
+        }
+        self.color[sink as usize].store(color, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 级别为`level`的消息是否应该写到`sink`
+    pub fn should_emit(&self, sink: LogSink, level: &LogLevel) -> bool {
+        let threshold = self.level(sink);
+        if threshold == SINK_LEVEL_OFF || level.clone() as u8 > threshold {
+            return false;
+        }
+        !sink.is_console() || KERNEL_LOG_LEVEL.should_print(level.clone())
+    }
+
+    /// 是否有任何一个输出端需要级别为`level`的消息
+    pub fn any_enabled(&self, level: &LogLevel) -> bool {
+        LogSink::ALL
+            .into_iter()
+            .any(|sink| self.should_emit(sink, level))
+    }
+
+    /// 解析并应用配置
+    ///
+    /// 只有整个配置都合法时才会生效，没有提到的输出端保持不变
+    ///
+    /// ## 返回值
+    /// - `Err(EINVAL)`: 配置为空，或者有不认识的输出端、级别、选项
+    pub fn apply(&self, config: &str) -> Result<(), SystemError> {
+        let mut levels: [Option<u8>; 4] = [None; 4];
+        let mut colors: [Option<bool>; 4] = [None; 4];
+        let mut empty = true;
+
+        for entry in config
+            .split(|c: char| c == ',' || c.is_ascii_whitespace())
+            .filter(|s| !s.is_empty())
+        {
+            empty = false;
+            let mut parts = entry.splitn(3, |c| c == ':' || c == '=');
+            let sink = parts
+                .next()
+                .and_then(LogSink::from_name)
+                .ok_or(SystemError::EINVAL)?;
+            let level = parts
+                .next()
+                .and_then(parse_level)
+                .ok_or(SystemError::EINVAL)?;
+            levels[sink as usize] = Some(level);
+            match parts.next() {
+                None => {}
+                Some("color") if sink.is_console() => colors[sink as usize] = Some(true),
+                Some("nocolor") => colors[sink as usize] = Some(false),
+                Some(_) => return Err(SystemError::EINVAL),
+            }
+        }
+        if empty {
+            return Err(SystemError::EINVAL);
+        }
+
+        for sink in LogSink::ALL {
+            if let Some(level) = levels[sink as usize] {
+                self.set_level(sink, level)?;
+            }
+            if let Some(color) = colors[sink as usize] {
+                self.set_color(sink, color)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 输出当前配置，格式与[`LogSinks::apply`]接受的相同
+impl Display for LogSinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, sink) in LogSink::ALL.into_iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            match self.level(sink) {
+                SINK_LEVEL_OFF => write!(f, "{}:off", sink.name())?,
+                level => write!(f, "{}:{}", sink.name(), level)?,
+            }
+            if sink.is_console() && !self.color(sink) {
+                f.write_str(":nocolor")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 解析日志级别，返回0-7或者`SINK_LEVEL_OFF`
+fn parse_level(s: &str) -> Option<u8> {
+    let level = match s {
+        "emerg" => 0,
+        "alert" => 1,
+        "crit" => 2,
+        "err" | "error" => 3,
+        "warn" | "warning" => 4,
+        "notice" => 5,
+        "info" => 6,
+        "debug" | "all" => 7,
+        "off" => SINK_LEVEL_OFF,
+        _ => s.parse::<u8>().ok().filter(|level| *level <= 7)?,
+    };
+    Some(level)
+}
+
+/// 处理klog.sinks参数
+///
+/// 在cmdline参数解析完成后调用
+pub fn handle_klog_sinks_param() {
+    let Some(value) = KLOG_SINKS_PARAM.value_str() else {
+        return;
+    };
+    if value.is_empty() {
+        return;
+    }
+    if LOG_SINKS.apply(value).is_err() {
+        log::warn!("klog.sinks: invalid value '{}'", value);
+    }
+}
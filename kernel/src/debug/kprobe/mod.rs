@@ -1,10 +1,13 @@
+use crate::arch::MMArch;
 use crate::debug::kprobe::args::KprobeInfo;
+use crate::libs::once::Once;
 use crate::libs::rwlock::RwLock;
 use crate::libs::spinlock::SpinLock;
+use crate::mm::{MemoryManagementArch, VirtAddr};
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
-use kprobe::{Kprobe, KprobeBuilder, KprobeOps, KprobePoint};
+use kprobe::{Kprobe, KprobeAuxiliaryOps, KprobeBuilder, KprobeOps, KprobePoint};
 use system_error::SystemError;
 
 pub mod args;
@@ -16,6 +19,45 @@ pub static KPROBE_MANAGER: SpinLock<KprobeManager> = SpinLock::new(KprobeManager
 static KPROBE_POINT_LIST: SpinLock<BTreeMap<usize, Arc<KprobePoint>>> =
     SpinLock::new(BTreeMap::new());
 
+/// 每个指令槽的大小，能放下一条最长的指令
+const KPROBE_INSN_SLOT_SIZE: usize = 16;
+const KPROBE_INSN_SLOT_NUM: usize = 4096 / KPROBE_INSN_SLOT_SIZE;
+
+/// 单步执行被替换指令的指令槽。放在代码段中，内核代码被设置为只读之后仍然可以执行
+#[repr(C, align(4096))]
+struct KprobeInsnSlots([u8; KPROBE_INSN_SLOT_SIZE * KPROBE_INSN_SLOT_NUM]);
+
+#[link_section = ".text.kprobe_insn_slots"]
+static KPROBE_INSN_SLOTS: KprobeInsnSlots =
+    KprobeInsnSlots([0xcc; KPROBE_INSN_SLOT_SIZE * KPROBE_INSN_SLOT_NUM]);
+static KPROBE_INSN_SLOT_USED: SpinLock<[bool; KPROBE_INSN_SLOT_NUM]> =
+    SpinLock::new([false; KPROBE_INSN_SLOT_NUM]);
+
+/// 通过`MMArch::text_poke`修改内核代码，并从[`KPROBE_INSN_SLOTS`]中分配指令槽
+struct KernelKprobeAuxiliaryOps;
+
+impl KprobeAuxiliaryOps for KernelKprobeAuxiliaryOps {
+    fn text_poke(&self, addr: usize, data: &[u8]) {
+        unsafe { MMArch::text_poke(VirtAddr::new(addr), data) }
+            .unwrap_or_else(|e| panic!("kprobe: failed to patch text at {:#x}: {:?}", addr, e));
+    }
+
+    fn alloc_insn_slot(&self) -> Option<usize> {
+        let mut used = KPROBE_INSN_SLOT_USED.lock_irqsave();
+        let index = used.iter().position(|u| !*u)?;
+        used[index] = true;
+        Some(KPROBE_INSN_SLOTS.0.as_ptr() as usize + index * KPROBE_INSN_SLOT_SIZE)
+    }
+
+    fn free_insn_slot(&self, slot: usize) {
+        let index = (slot - KPROBE_INSN_SLOTS.0.as_ptr() as usize) / KPROBE_INSN_SLOT_SIZE;
+        KPROBE_INSN_SLOT_USED.lock_irqsave()[index] = false;
+    }
+}
+
+static KPROBE_AUXILIARY_OPS: KernelKprobeAuxiliaryOps = KernelKprobeAuxiliaryOps;
+static KPROBE_AUXILIARY_OPS_INIT: Once = Once::new();
+
 /// 管理所有的kprobe探测点
 #[derive(Debug, Default)]
 pub struct KprobeManager {
@@ -148,6 +190,9 @@ pub fn kprobe_test() {
 /// ## 参数
 /// - `kprobe_info`: kprobe的信息
 pub fn register_kprobe(kprobe_info: KprobeInfo) -> Result<LockKprobe, SystemError> {
+    KPROBE_AUXILIARY_OPS_INIT.call_once(|| unsafe {
+        kprobe::register_auxiliary_ops(&KPROBE_AUXILIARY_OPS);
+    });
     let kprobe_builder = KprobeBuilder::try_from(kprobe_info)?;
     let address = kprobe_builder.probe_addr();
     let existed_point = KPROBE_POINT_LIST.lock().get(&address).map(Clone::clone);
@@ -1,7 +1,14 @@
+#[cfg(feature = "alloc_profiling")]
+pub mod allocinfo;
 pub mod jump_label;
 pub mod klog;
 pub mod kprobe;
+pub mod oops;
+pub mod page_tables;
 pub mod panic;
+pub mod stack_protector;
 pub mod sysfs;
+pub mod taint;
+pub mod textui;
 pub mod traceback;
 pub mod tracing;
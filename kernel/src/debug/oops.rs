@@ -0,0 +1,113 @@
+//! 内核oops
+//!
+//! 内核代码在进程上下文中出错（例如空指针解引用、除零）时，错误通常只会影响当前进程。
+//! 这时打印错误信息和调用栈，把内核标记为tainted，然后结束当前进程（相当于被SIGSEGV杀死），
+//! 系统继续运行，而不是整个系统panic。以下情况仍然会panic：
+//! - 在中断上下文（硬中断或softirq）中出错
+//! - 出错的是idle进程或init进程，或者进程在退出的过程中再次出错
+//! - 设置了`panic_on_oops`（`/proc/sys/kernel/panic_on_oops`，或者内核命令行参数`oops=panic`）
+//!
+//! 出错的进程持有的锁不会被释放，因此oops之后系统可能不再稳定，
+//! `/proc/sys/kernel/tainted`中会带有`D`标志。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/kernel/dumpstack.c
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use log::{error, warn};
+
+use crate::{
+    arch::ipc::signal::Signal,
+    debug::{
+        panic::hook::print_stack_trace,
+        taint::{add_taint, TaintDisplay, TaintFlags},
+    },
+    exception::{bottom_half::local_bh_reset, in_interrupt},
+    process::{ProcessFlags, ProcessManager, RawPid},
+    smp::core::smp_get_processor_id,
+};
+
+/// 内核命令行参数，`oops=panic`表示oops时panic
+kernel_cmdline_param_kv!(OOPS_PARAM, oops, "");
+
+static PANIC_ON_OOPS: AtomicBool = AtomicBool::new(false);
+static OOPS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// 处理oops参数
+///
+/// 在cmdline参数解析完成后调用
+pub fn handle_oops_param() {
+    match OOPS_PARAM.value_str() {
+        None | Some("") => {}
+        Some("panic") => PANIC_ON_OOPS.store(true, Ordering::SeqCst),
+        Some(value) => warn!("oops: invalid value '{}', must be 'panic'", value),
+    }
+}
+
+/// oops时是否panic
+pub fn panic_on_oops() -> bool {
+    PANIC_ON_OOPS.load(Ordering::Relaxed)
+}
+
+pub fn set_panic_on_oops(enable: bool) {
+    PANIC_ON_OOPS.store(enable, Ordering::SeqCst);
+}
+
+/// 内核在当前进程的上下文中发生了不可恢复的错误
+///
+/// 打印错误信息和调用栈，然后结束当前进程。不能只结束当前进程时（见模块文档），panic。
+///
+/// ## 参数
+/// - `reason`: 错误的简短描述，例如`"General Protection"`
+pub fn oops(reason: &str) -> ! {
+    if !ProcessManager::initialized() {
+        panic!("{}", reason);
+    }
+    let count = OOPS_COUNT.fetch_add(1, Ordering::SeqCst) + 1;
+    let pcb = ProcessManager::current_pcb();
+    error!(
+        "Oops: {} [#{}] CPU: {} PID: {} {}",
+        reason,
+        count,
+        smp_get_processor_id().data(),
+        pcb.raw_pid().data(),
+        TaintDisplay
+    );
+    print_stack_trace();
+    add_taint(TaintFlags::DIE);
+
+    if in_interrupt() {
+        panic!("Fatal exception in interrupt");
+    }
+    if panic_on_oops() {
+        panic!("Fatal exception");
+    }
+    if ProcessManager::idle_pcb()
+        .iter()
+        .any(|idle| Arc::ptr_eq(idle, &pcb))
+    {
+        panic!("Attempted to kill the idle task!");
+    }
+    if pcb.raw_pid() == RawPid(1) {
+        panic!("Attempted to kill init!");
+    }
+    if pcb.flags().contains(ProcessFlags::EXITING) {
+        panic!("Recursive fault in exiting task");
+    }
+
+    // 出错的执行流不会再释放它持有的锁和守卫，否则退出时会因为禁止抢占而无法调度
+    let preempt_count = pcb.preempt_count();
+    if preempt_count != 0 {
+        warn!(
+            "note: pid {} exited with preempt_count {}",
+            pcb.raw_pid().data(),
+            preempt_count
+        );
+        unsafe { pcb.set_preempt_count(0) };
+    }
+    unsafe { local_bh_reset() };
+    drop(pcb);
+
+    ProcessManager::exit(Signal::SIGSEGV as usize);
+}
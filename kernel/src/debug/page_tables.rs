@@ -0,0 +1,215 @@
+//! `/sys/kernel/debug/page_tables`
+//!
+//! - `kernel`: 内核空间的页表
+//! - `current_user`: 读取者自己的用户空间的页表
+//! - `pid`: 写入进程号，选择`user`和`check`查看的进程（0表示读取者自己）
+//! - `user`: 所选进程的用户空间的页表
+//! - `check`: 检查内核空间和所选进程的用户空间的页表不变式（W^X、用户位）
+//!
+//! 转储的内容在打开文件时生成，参见[`crate::mm::ptdump`]。
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::string::{String, ToString};
+use system_error::SystemError;
+
+use crate::{
+    debug::sysfs::debugfs_kobj,
+    driver::base::kobject::KObject,
+    filesystem::{
+        kernfs::{
+            callback::{KernCallbackData, KernFSCallback, KernInodePrivateData},
+            KernFSInodeArgs, KernInodeType,
+        },
+        vfs::{InodeMode, PollStatus},
+    },
+    mm::ptdump::{ptdump_check, ptdump_kernel, ptdump_user},
+    process::RawPid,
+};
+
+/// `user`和`check`查看的进程，0表示读取者自己
+static PTDUMP_TARGET_PID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy)]
+enum PageTableDumpKind {
+    Kernel,
+    CurrentUser,
+    User,
+    Check,
+}
+
+#[derive(Debug)]
+struct PageTableDumpCallBack {
+    kind: PageTableDumpKind,
+}
+
+static PTDUMP_KERNEL: PageTableDumpCallBack = PageTableDumpCallBack {
+    kind: PageTableDumpKind::Kernel,
+};
+static PTDUMP_CURRENT_USER: PageTableDumpCallBack = PageTableDumpCallBack {
+    kind: PageTableDumpKind::CurrentUser,
+};
+static PTDUMP_USER: PageTableDumpCallBack = PageTableDumpCallBack {
+    kind: PageTableDumpKind::User,
+};
+static PTDUMP_CHECK: PageTableDumpCallBack = PageTableDumpCallBack {
+    kind: PageTableDumpKind::Check,
+};
+
+impl PageTableDumpCallBack {
+    fn generate(&self) -> Result<String, SystemError> {
+        let target = RawPid::new(PTDUMP_TARGET_PID.load(Ordering::Relaxed));
+        match self.kind {
+            PageTableDumpKind::Kernel => Ok(ptdump_kernel()),
+            PageTableDumpKind::CurrentUser => ptdump_user(RawPid::new(0)),
+            PageTableDumpKind::User => ptdump_user(target),
+            PageTableDumpKind::Check => Ok(ptdump_check(target).1),
+        }
+    }
+}
+
+impl KernFSCallback for PageTableDumpCallBack {
+    fn open(&self, mut data: KernCallbackData) -> Result<(), SystemError> {
+        let dump = self.generate()?;
+        data.private_data_mut()
+            .replace(KernInodePrivateData::PageTableDump(dump));
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        mut data: KernCallbackData,
+        buf: &mut [u8],
+        offset: usize,
+    ) -> Result<usize, SystemError> {
+        let dump = data
+            .private_data_mut()
+            .as_mut()
+            .and_then(|p| p.page_table_dump())
+            .ok_or(SystemError::EINVAL)?;
+        let bytes = dump.as_bytes();
+        if offset >= bytes.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(bytes.len() - offset);
+        buf[..len].copy_from_slice(&bytes[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn write(
+        &self,
+        _data: KernCallbackData,
+        _buf: &[u8],
+        _offset: usize,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EPERM)
+    }
+
+    fn poll(&self, _data: KernCallbackData) -> Result<PollStatus, SystemError> {
+        Ok(PollStatus::READ)
+    }
+}
+
+#[derive(Debug)]
+struct PageTableTargetPidCallBack;
+
+impl KernFSCallback for PageTableTargetPidCallBack {
+    fn open(&self, _data: KernCallbackData) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        _data: KernCallbackData,
+        buf: &mut [u8],
+        offset: usize,
+    ) -> Result<usize, SystemError> {
+        let str = format!("{}\n", PTDUMP_TARGET_PID.load(Ordering::Relaxed));
+        let str_bytes = str.as_bytes();
+        if offset >= str_bytes.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(str_bytes.len() - offset);
+        buf[..len].copy_from_slice(&str_bytes[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn write(
+        &self,
+        _data: KernCallbackData,
+        buf: &[u8],
+        _offset: usize,
+    ) -> Result<usize, SystemError> {
+        let pid: usize = String::from_utf8_lossy(buf)
+            .trim()
+            .parse()
+            .map_err(|_| SystemError::EINVAL)?;
+        PTDUMP_TARGET_PID.store(pid, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+
+    fn poll(&self, _data: KernCallbackData) -> Result<PollStatus, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+}
+
+impl KernInodePrivateData {
+    pub fn page_table_dump(&mut self) -> Option<&mut String> {
+        return match self {
+            KernInodePrivateData::PageTableDump(dump) => Some(dump),
+            _ => None,
+        };
+    }
+}
+
+fn dump_file_args(callback: &'static PageTableDumpCallBack) -> KernFSInodeArgs {
+    KernFSInodeArgs {
+        mode: InodeMode::S_IRUSR,
+        callback: Some(callback),
+        inode_type: KernInodeType::File,
+        size: Some(4096),
+        private_data: None,
+    }
+}
+
+fn kernel_inode_provider_kernel() -> KernFSInodeArgs {
+    dump_file_args(&PTDUMP_KERNEL)
+}
+
+fn kernel_inode_provider_current_user() -> KernFSInodeArgs {
+    dump_file_args(&PTDUMP_CURRENT_USER)
+}
+
+fn kernel_inode_provider_user() -> KernFSInodeArgs {
+    dump_file_args(&PTDUMP_USER)
+}
+
+fn kernel_inode_provider_check() -> KernFSInodeArgs {
+    dump_file_args(&PTDUMP_CHECK)
+}
+
+/// 创建`/sys/kernel/debug/page_tables`
+pub fn init_debugfs_page_tables() -> Result<(), SystemError> {
+    let root_dir = debugfs_kobj().inode().ok_or(SystemError::ENOENT)?;
+    let dir = root_dir.add_dir(
+        "page_tables".to_string(),
+        InodeMode::from_bits_truncate(0o500),
+        None,
+        None,
+    )?;
+    dir.add_file_lazy("kernel".to_string(), kernel_inode_provider_kernel)?;
+    dir.add_file_lazy(
+        "current_user".to_string(),
+        kernel_inode_provider_current_user,
+    )?;
+    dir.add_file_lazy("user".to_string(), kernel_inode_provider_user)?;
+    dir.add_file_lazy("check".to_string(), kernel_inode_provider_check)?;
+    dir.add_file(
+        "pid".to_string(),
+        InodeMode::S_IRUSR | InodeMode::S_IWUSR,
+        None,
+        None,
+        Some(&PageTableTargetPidCallBack),
+    )?;
+    Ok(())
+}
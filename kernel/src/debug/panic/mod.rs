@@ -4,7 +4,12 @@ use cfg_if::cfg_if;
 
 use log::error;
 
-use crate::process;
+use crate::{
+    debug::taint::TaintDisplay,
+    filesystem::pstore::{pstore_panic_begin, pstore_panic_dump},
+    libs::lib_ui::deferred_console::console_enter_sync_mode,
+    process,
+};
 use system_error::SystemError;
 
 cfg_if! {
@@ -40,10 +45,15 @@ impl Drop for PanicGuard {
 #[panic_handler]
 pub fn panic(info: &PanicInfo) -> ! {
     PANIC_COUNTER.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    // 不再等待console_flush线程，先把排队的屏幕输出刷出来
+    console_enter_sync_mode();
     error!(
-        "Kernel Panic Occurred. raw_pid: {}",
-        process::ProcessManager::current_pid().data()
+        "Kernel Panic Occurred. raw_pid: {} {}",
+        process::ProcessManager::current_pid().data(),
+        TaintDisplay
     );
+    // 之后的输出会保存到pstore
+    pstore_panic_begin();
 
     match info.location() {
         Some(loc) => {
@@ -64,6 +74,7 @@ pub fn panic(info: &PanicInfo) -> ! {
             "Panic Counter: {}, too many panics, halt.",
             PANIC_COUNTER.load(core::sync::atomic::Ordering::Relaxed)
         );
+        pstore_panic_dump();
         loop {}
     }
 
@@ -71,6 +82,7 @@ pub fn panic(info: &PanicInfo) -> ! {
     if info.can_unwind() {
         let guard = Box::new(PanicGuard::new());
         hook::print_stack_trace();
+        pstore_panic_dump();
         let _res = unwinding::panic::begin_panic(guard);
         // log::error!("panic unreachable: {:?}", _res.0);
     }
@@ -78,6 +90,7 @@ pub fn panic(info: &PanicInfo) -> ! {
         "Current PCB:\n\t{:?}",
         process::ProcessManager::current_pcb()
     );
+    pstore_panic_dump();
     process::ProcessManager::exit(usize::MAX);
 }
 
@@ -0,0 +1,42 @@
+//! 内核栈溢出保护（stack-smashing protection）
+//!
+//! 内核以`-Zstack-protector`编译时（见`kernel/src/Makefile`中的`STACK_PROTECTOR`），
+//! 编译器会在含有数组等局部变量的函数的栈帧中放入canary，函数返回前与`__stack_chk_guard`比较，
+//! 不一致时调用`__stack_chk_fail`。
+//!
+//! 对于`*-unknown-none`目标，LLVM只支持从全局变量`__stack_chk_guard`读取canary，
+//! 没有Linux那样基于段寄存器的per-cpu/per-task canary，所以这里使用每次启动时随机生成的全局canary：
+//! 在运行中切换它会让其他CPU上正在执行的函数返回时检查失败。
+
+use core::ptr::addr_of_mut;
+
+use crate::arch::rand::rand;
+
+/// 编译器插入的检查代码读取的canary。在[`boot_init_stack_canary`]之前使用这个固定的初始值
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static mut __stack_chk_guard: usize = 0x595e_9fbd_94fd_a766;
+
+/// # 功能
+///
+/// 在启动早期生成随机的canary
+///
+/// ## 安全性
+///
+/// 修改canary之后，调用链上已经保存了旧canary的函数返回时都会检查失败。
+/// 因此只能在永不返回的函数（`start_kernel`）中、开启中断和启动其他CPU之前调用，并且必须内联
+#[inline(always)]
+pub unsafe fn boot_init_stack_canary() {
+    // 最低字节置0，使得通过字符串操作越界时难以读出或者写对canary
+    let canary = (rand() ^ (rand() << 17)) & !0xff;
+    core::ptr::write_volatile(addr_of_mut!(__stack_chk_guard), canary);
+}
+
+/// canary被破坏时由编译器插入的代码调用
+///
+/// 声明为`extern "C"`，panic时不会展开（unwind）到栈已经被破坏的调用者中
+#[cfg(target_os = "none")]
+#[no_mangle]
+pub extern "C" fn __stack_chk_fail() -> ! {
+    panic!("stack-protector: kernel stack is corrupted");
+}
@@ -21,6 +21,10 @@ fn debugfs_init() -> Result<(), SystemError> {
         SYS_KERNEL_DEBUG_KOBJECT_INSTANCE = Some(debug_kobj);
     }
     super::tracing::init_debugfs_tracing()?;
+    super::page_tables::init_debugfs_page_tables()?;
+    super::textui::init_debugfs_textui()?;
+    #[cfg(feature = "alloc_profiling")]
+    super::allocinfo::init_debugfs_allocinfo()?;
     return Ok(());
 }
 
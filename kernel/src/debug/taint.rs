@@ -0,0 +1,116 @@
+//! 内核taint标志
+//!
+//! 记录内核运行过程中发生过的、可能影响其可靠性的事件（例如oops、机器检查异常），
+//! 便于分析问题报告。taint位只能设置，不能清除，通过`/proc/sys/kernel/tainted`读取，
+//! 并且会打印在oops和panic的信息中。
+//!
+//! 标志位的定义和显示格式与Linux相同。DragonOS目前没有可加载模块和故障注入框架，
+//! 与它们相关的标志位只能通过`/proc/sys/kernel/tainted`设置。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/panic.c
+
+use core::{
+    fmt::{self, Display},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+bitflags! {
+    /// taint标志位，与Linux的`TAINT_*`相同
+    pub struct TaintFlags: u32 {
+        /// 加载了专有模块
+        const PROPRIETARY_MODULE = 1 << 0;
+        /// 强制加载了模块
+        const FORCED_MODULE = 1 << 1;
+        /// 在不符合规格的系统上运行SMP内核
+        const CPU_OUT_OF_SPEC = 1 << 2;
+        /// 强制卸载了模块
+        const FORCED_RMMOD = 1 << 3;
+        /// 发生过机器检查异常
+        const MACHINE_CHECK = 1 << 4;
+        /// 发现过错误的页
+        const BAD_PAGE = 1 << 5;
+        /// 用户空间请求设置
+        const USER = 1 << 6;
+        /// 内核发生过oops
+        const DIE = 1 << 7;
+        /// 覆盖了ACPI表
+        const OVERRIDDEN_ACPI_TABLE = 1 << 8;
+        /// 内核发出过警告
+        const WARN = 1 << 9;
+        /// 加载了staging驱动
+        const CRAP = 1 << 10;
+        /// 使用了固件缺陷的变通方案
+        const FIRMWARE_WORKAROUND = 1 << 11;
+        /// 加载了树外模块
+        const OOT_MODULE = 1 << 12;
+        /// 加载了未签名的模块
+        const UNSIGNED_MODULE = 1 << 13;
+        /// 发生过软锁死
+        const SOFTLOCKUP = 1 << 14;
+        /// 应用了实时补丁
+        const LIVEPATCH = 1 << 15;
+        /// 发行版自定义
+        const AUX = 1 << 16;
+        /// 使用了结构体随机化插件
+        const RANDSTRUCT = 1 << 17;
+        /// 运行过内核测试
+        const TEST = 1 << 18;
+    }
+}
+
+/// 每个taint位在设置和未设置时显示的字符，与Linux的`taint_flags`相同
+const TAINT_CHARS: [(u8, u8); 19] = [
+    (b'P', b'G'),
+    (b'F', b' '),
+    (b'S', b' '),
+    (b'R', b' '),
+    (b'M', b' '),
+    (b'B', b' '),
+    (b'U', b' '),
+    (b'D', b' '),
+    (b'A', b' '),
+    (b'W', b' '),
+    (b'C', b' '),
+    (b'I', b' '),
+    (b'O', b' '),
+    (b'E', b' '),
+    (b'L', b' '),
+    (b'K', b' '),
+    (b'X', b' '),
+    (b'T', b' '),
+    (b'N', b' '),
+];
+
+static TAINTED: AtomicU32 = AtomicU32::new(0);
+
+/// 当前的taint位
+pub fn tainted() -> TaintFlags {
+    TaintFlags::from_bits_truncate(TAINTED.load(Ordering::Relaxed))
+}
+
+/// 设置taint位
+pub fn add_taint(flags: TaintFlags) {
+    TAINTED.fetch_or(flags.bits(), Ordering::SeqCst);
+}
+
+/// 以Linux的格式显示taint状态，例如`Not tainted`、`Tainted: G      D    `
+pub struct TaintDisplay;
+
+impl Display for TaintDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = tainted();
+        if flags.is_empty() {
+            return f.write_str("Not tainted");
+        }
+        f.write_str("Tainted: ")?;
+        for (bit, (set, unset)) in TAINT_CHARS.iter().enumerate() {
+            let c = if flags.bits() & (1 << bit) != 0 {
+                *set
+            } else {
+                *unset
+            };
+            write!(f, "{}", c as char)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+//! `/sys/kernel/debug/textui`
+//!
+//! 导出textui当前窗口显示在屏幕上的内容，便于自动化测试检查控制台的实际输出：
+//!
+//! - `text`: 纯文本，每行一个换行符
+//! - `cells`: 每个字符单元格的字符和颜色，格式参见[`TextuiSnapshot::to_cells`]
+//! - `screen.ppm`: 按textui字体渲染出的PPM图像
+//!
+//! 内容在打开文件时生成，同一个文件描述符读到的是同一时刻的快照。
+
+use alloc::{string::ToString, vec::Vec};
+use system_error::SystemError;
+
+use crate::{
+    debug::sysfs::debugfs_kobj,
+    driver::base::kobject::KObject,
+    filesystem::{
+        kernfs::{
+            callback::{KernCallbackData, KernFSCallback, KernInodePrivateData},
+            KernFSInodeArgs, KernInodeType,
+        },
+        vfs::{InodeMode, PollStatus},
+    },
+    libs::lib_ui::textui::{textui_snapshot, TextuiSnapshot},
+};
+
+#[derive(Debug, Clone, Copy)]
+enum TextuiDumpFormat {
+    Text,
+    Cells,
+    Ppm,
+}
+
+#[derive(Debug)]
+struct TextuiDumpCallBack {
+    format: TextuiDumpFormat,
+}
+
+static TEXTUI_TEXT: TextuiDumpCallBack = TextuiDumpCallBack {
+    format: TextuiDumpFormat::Text,
+};
+static TEXTUI_CELLS: TextuiDumpCallBack = TextuiDumpCallBack {
+    format: TextuiDumpFormat::Cells,
+};
+static TEXTUI_PPM: TextuiDumpCallBack = TextuiDumpCallBack {
+    format: TextuiDumpFormat::Ppm,
+};
+
+impl TextuiDumpCallBack {
+    fn generate(&self, snapshot: &TextuiSnapshot) -> Vec<u8> {
+        match self.format {
+            TextuiDumpFormat::Text => snapshot.to_text().into_bytes(),
+            TextuiDumpFormat::Cells => snapshot.to_cells(),
+            TextuiDumpFormat::Ppm => snapshot.to_ppm(),
+        }
+    }
+}
+
+impl KernFSCallback for TextuiDumpCallBack {
+    fn open(&self, mut data: KernCallbackData) -> Result<(), SystemError> {
+        let snapshot = textui_snapshot()?;
+        data.private_data_mut()
+            .replace(KernInodePrivateData::TextuiSnapshot(
+                self.generate(&snapshot),
+            ));
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        mut data: KernCallbackData,
+        buf: &mut [u8],
+        offset: usize,
+    ) -> Result<usize, SystemError> {
+        let bytes = data
+            .private_data_mut()
+            .as_mut()
+            .and_then(|p| p.textui_snapshot())
+            .ok_or(SystemError::EINVAL)?;
+        if offset >= bytes.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(bytes.len() - offset);
+        buf[..len].copy_from_slice(&bytes[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn write(
+        &self,
+        _data: KernCallbackData,
+        _buf: &[u8],
+        _offset: usize,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EPERM)
+    }
+
+    fn poll(&self, _data: KernCallbackData) -> Result<PollStatus, SystemError> {
+        Ok(PollStatus::READ)
+    }
+}
+
+impl KernInodePrivateData {
+    pub fn textui_snapshot(&mut self) -> Option<&mut Vec<u8>> {
+        return match self {
+            KernInodePrivateData::TextuiSnapshot(bytes) => Some(bytes),
+            _ => None,
+        };
+    }
+}
+
+fn dump_file_args(callback: &'static TextuiDumpCallBack) -> KernFSInodeArgs {
+    KernFSInodeArgs {
+        mode: InodeMode::S_IRUSR,
+        callback: Some(callback),
+        inode_type: KernInodeType::File,
+        size: Some(4096),
+        private_data: None,
+    }
+}
+
+fn kernel_inode_provider_text() -> KernFSInodeArgs {
+    dump_file_args(&TEXTUI_TEXT)
+}
+
+fn kernel_inode_provider_cells() -> KernFSInodeArgs {
+    dump_file_args(&TEXTUI_CELLS)
+}
+
+fn kernel_inode_provider_ppm() -> KernFSInodeArgs {
+    dump_file_args(&TEXTUI_PPM)
+}
+
+/// 创建`/sys/kernel/debug/textui`
+pub fn init_debugfs_textui() -> Result<(), SystemError> {
+    let root_dir = debugfs_kobj().inode().ok_or(SystemError::ENOENT)?;
+    let dir = root_dir.add_dir(
+        "textui".to_string(),
+        InodeMode::from_bits_truncate(0o500),
+        None,
+        None,
+    )?;
+    dir.add_file_lazy("text".to_string(), kernel_inode_provider_text)?;
+    dir.add_file_lazy("cells".to_string(), kernel_inode_provider_cells)?;
+    dir.add_file_lazy("screen.ppm".to_string(), kernel_inode_provider_ppm)?;
+    Ok(())
+}
@@ -14,7 +14,7 @@ use crate::{
 use acpi::{AcpiHandler, AcpiTables, PlatformInfo};
 use alloc::{string::ToString, sync::Arc};
 use core::{fmt::Debug, ptr::NonNull};
-use log::{error, info};
+use log::{error, info, warn};
 use system_error::SystemError;
 
 extern crate acpi;
@@ -70,11 +70,6 @@ impl AcpiManager {
         }
 
         let acpi_args = boot_params().read().acpi;
-        if let BootloaderAcpiArg::NotProvided = acpi_args {
-            error!("acpi_init(): ACPI not provided by bootloader");
-            return Err(SystemError::ENODEV);
-        }
-
         self.map_tables(acpi_args)?;
         self.bus_init()?;
         info!("Acpi Manager initialized.");
@@ -86,13 +81,7 @@ impl AcpiManager {
             BootloaderAcpiArg::Rsdt(rsdpv1) => Self::rsdp_paddr(&rsdpv1),
             BootloaderAcpiArg::Xsdt(rsdpv2) => Self::rsdp_paddr(&rsdpv2),
             BootloaderAcpiArg::Rsdp(rsdp) => rsdp,
-            _ => {
-                error!(
-                    "AcpiManager::map_tables(): unsupported acpi_args: {:?}",
-                    acpi_args
-                );
-                return Err(SystemError::ENODEV);
-            }
+            BootloaderAcpiArg::NotProvided => return Self::search_tables(),
         };
         let res = unsafe { acpi::AcpiTables::from_rsdp(AcpiHandlerImpl, table_paddr.data()) };
         match res {
@@ -143,6 +132,30 @@ impl AcpiManager {
         return rsdp_paddr;
     }
 
+    /// # 引导协议没有传递RSDP时，在EBDA和BIOS只读区中查找
+    ///
+    /// Firecracker等微虚拟机的PVH启动信息中可能没有RSDP的地址，也可能根本没有ACPI表
+    #[cfg(target_arch = "x86_64")]
+    fn search_tables() -> Result<(), SystemError> {
+        match unsafe { acpi::AcpiTables::search_for_rsdp_bios(AcpiHandlerImpl) } {
+            Ok(acpi_table) => {
+                info!("AcpiManager: found RSDP in the BIOS area");
+                Self::set_acpi_table(acpi_table);
+                Ok(())
+            }
+            Err(e) => {
+                warn!("AcpiManager: no ACPI tables found: {:?}", e);
+                Err(SystemError::ENODEV)
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn search_tables() -> Result<(), SystemError> {
+        error!("acpi_init(): ACPI not provided by bootloader");
+        Err(SystemError::ENODEV)
+    }
+
     fn set_acpi_table(acpi_table: AcpiTables<AcpiHandlerImpl>) {
         unsafe {
             __ACPI_TABLE = Some(acpi_table);
@@ -64,10 +64,12 @@ lazy_static! {
 }
 
 pub fn acpi_reboot() {
+    // 没有ACPI时由调用者尝试其他的重启方式
+    let Some(tables) = acpi_manager().tables() else {
+        return;
+    };
     // 获取FADT表
-    let fadt = acpi_manager()
-        .tables()
-        .unwrap()
+    let fadt = tables
         .find_table::<Fadt>()
         .expect("acpi_reboot(): failed to find Fadt table");
 
@@ -144,10 +146,11 @@ fn acpi_pci_reboot(reset_register: GenericAddress, reset_value: u8) {
 
 fn acpi_reset() {
     debug!("Acpi reset");
+    let Some(tables) = acpi_manager().tables() else {
+        return;
+    };
     // 获取FADT表
-    let fadt = acpi_manager()
-        .tables()
-        .unwrap()
+    let fadt = tables
         .find_table::<Fadt>()
         .expect("acpi_reboot(): failed to find Fadt table");
 
@@ -1,7 +1,12 @@
 use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::ptr::{self, NonNull};
 use system_error::SystemError;
 
-use crate::{libs::spinlock::SpinLock, mm::dma::DmaBuffer, sched::completion::Completion};
+use crate::{
+    libs::{error_context::ContextError, spinlock::SpinLock},
+    mm::{dma::DmaBuffer, gup::PinnedUserPages},
+    sched::completion::Completion,
+};
 
 use super::block_device::{BlockId, LBA_SIZE};
 
@@ -10,6 +15,10 @@ use super::block_device::{BlockId, LBA_SIZE};
 pub enum BioType {
     Read,
     Write,
+    /// 把设备易失性缓存中的数据写入持久存储（写屏障），不携带数据
+    Flush,
+    /// 通知设备一段扇区不再使用，不携带数据
+    Discard,
 }
 
 /// BIO请求状态
@@ -21,6 +30,14 @@ enum BioState {
     Failed,
 }
 
+/// BIO的数据缓冲区
+enum BioBuffer {
+    /// 内核分配的DMA缓冲区
+    Dma(DmaBuffer),
+    /// 固定住的用户缓冲区，设备直接读写用户页（O_DIRECT）
+    User(PinnedUserPages),
+}
+
 /// 单个BIO请求
 pub struct BioRequest {
     inner: SpinLock<InnerBioRequest>,
@@ -30,13 +47,17 @@ struct InnerBioRequest {
     bio_type: BioType,
     lba_start: BlockId,
     count: usize,
-    buffer: DmaBuffer,
+    buffer: BioBuffer,
     state: BioState,
     completion: Arc<Completion>,
     result: Option<Result<usize, SystemError>>,
+    /// 以[`BioRequest::fail`]完成时的错误上下文
+    error_context: Option<ContextError>,
     complete_callbacks: Vec<BioCompleteCallback>,
     /// virtio-drivers返回的token，用于中断时匹配
     token: Option<u16>,
+    /// 是否被合并到了已排队的请求中
+    merged: bool,
 }
 
 type BioCompleteCallback = Box<dyn Fn(Result<usize, SystemError>) + Send + Sync>;
@@ -50,12 +71,14 @@ impl BioRequest {
                 bio_type: BioType::Read,
                 lba_start,
                 count,
-                buffer,
+                buffer: BioBuffer::Dma(buffer),
                 state: BioState::Init,
                 completion: Arc::new(Completion::new()),
                 result: None,
+                error_context: None,
                 complete_callbacks: Vec::new(),
                 token: None,
+                merged: false,
             }),
         })
     }
@@ -71,12 +94,85 @@ impl BioRequest {
                 bio_type: BioType::Write,
                 lba_start,
                 count,
-                buffer,
+                buffer: BioBuffer::Dma(buffer),
+                state: BioState::Init,
+                completion: Arc::new(Completion::new()),
+                result: None,
+                error_context: None,
+                complete_callbacks: Vec::new(),
+                token: None,
+                merged: false,
+            }),
+        })
+    }
+
+    /// # 创建一个读入用户缓冲区的读请求
+    ///
+    /// 设备直接把数据写入`pages`，不经过内核缓冲区。`pages`的长度必须是扇区大小的整数倍
+    pub fn new_read_user(lba_start: BlockId, pages: PinnedUserPages) -> Arc<Self> {
+        Self::new_user(BioType::Read, lba_start, pages)
+    }
+
+    /// # 创建一个写出用户缓冲区的写请求
+    ///
+    /// 设备直接从`pages`读取数据，不经过内核缓冲区。`pages`的长度必须是扇区大小的整数倍
+    pub fn new_write_user(lba_start: BlockId, pages: PinnedUserPages) -> Arc<Self> {
+        Self::new_user(BioType::Write, lba_start, pages)
+    }
+
+    fn new_user(bio_type: BioType, lba_start: BlockId, pages: PinnedUserPages) -> Arc<Self> {
+        debug_assert!(pages.len() % LBA_SIZE == 0);
+        Arc::new(Self {
+            inner: SpinLock::new(InnerBioRequest {
+                bio_type,
+                lba_start,
+                count: pages.len() / LBA_SIZE,
+                buffer: BioBuffer::User(pages),
+                state: BioState::Init,
+                completion: Arc::new(Completion::new()),
+                result: None,
+                error_context: None,
+                complete_callbacks: Vec::new(),
+                token: None,
+                merged: false,
+            }),
+        })
+    }
+
+    /// 创建一个discard请求
+    pub fn new_discard(lba_start: BlockId, count: usize) -> Arc<Self> {
+        Arc::new(Self {
+            inner: SpinLock::new(InnerBioRequest {
+                bio_type: BioType::Discard,
+                lba_start,
+                count,
+                buffer: BioBuffer::Dma(DmaBuffer::alloc_bytes(0, Default::default())),
+                state: BioState::Init,
+                completion: Arc::new(Completion::new()),
+                result: None,
+                error_context: None,
+                complete_callbacks: Vec::new(),
+                token: None,
+                merged: false,
+            }),
+        })
+    }
+
+    /// 创建一个FLUSH请求
+    pub fn new_flush() -> Arc<Self> {
+        Arc::new(Self {
+            inner: SpinLock::new(InnerBioRequest {
+                bio_type: BioType::Flush,
+                lba_start: 0,
+                count: 0,
+                buffer: BioBuffer::Dma(DmaBuffer::alloc_bytes(0, Default::default())),
                 state: BioState::Init,
                 completion: Arc::new(Completion::new()),
                 result: None,
+                error_context: None,
                 complete_callbacks: Vec::new(),
                 token: None,
+                merged: false,
             }),
         })
     }
@@ -93,22 +189,56 @@ impl BioRequest {
     }
 
     /// 获取缓冲区的可变引用（仅用于提交时）
+    ///
+    /// 用户缓冲区在物理上不连续，此时返回空的切片，需要通过[`segments`](Self::segments)访问
     pub fn buffer_mut(&self) -> *mut [u8] {
         let mut inner = self.inner.lock_irqsave();
-        inner.buffer.as_mut_slice() as *mut [u8]
+        match &mut inner.buffer {
+            BioBuffer::Dma(buffer) => buffer.as_mut_slice() as *mut [u8],
+            BioBuffer::User(_) => ptr::slice_from_raw_parts_mut(NonNull::dangling().as_ptr(), 0),
+        }
     }
 
     /// 获取缓冲区的不可变引用
+    ///
+    /// 用户缓冲区在物理上不连续，此时返回空的切片，需要通过[`segments`](Self::segments)访问
     pub fn buffer(&self) -> *const [u8] {
         let inner = self.inner.lock_irqsave();
-        inner.buffer.as_slice() as *const [u8]
+        match &inner.buffer {
+            BioBuffer::Dma(buffer) => buffer.as_slice() as *const [u8],
+            BioBuffer::User(_) => ptr::slice_from_raw_parts(NonNull::dangling().as_ptr(), 0),
+        }
+    }
+
+    /// # 按顺序返回数据缓冲区的各个物理连续的段（仅用于提交时）
+    ///
+    /// 各段的长度都是扇区大小的整数倍，加起来正好是`count * LBA_SIZE`。不携带数据的请求返回空的列表
+    pub fn segments(&self) -> Vec<*mut [u8]> {
+        let mut inner = self.inner.lock_irqsave();
+        let len = inner.count * LBA_SIZE;
+        match &mut inner.buffer {
+            BioBuffer::Dma(_) if len == 0 => Vec::new(),
+            BioBuffer::Dma(buffer) => vec![&mut buffer.as_mut_slice()[..len] as *mut [u8]],
+            BioBuffer::User(pages) => pages.segments().collect(),
+        }
+    }
+
+    /// 能否与相邻的请求合并。discard请求由驱动按设备的限制拆分，用户缓冲区不能拼接到内核缓冲区中
+    pub fn is_mergeable(&self) -> bool {
+        let inner = self.inner.lock_irqsave();
+        inner.bio_type != BioType::Discard && matches!(inner.buffer, BioBuffer::Dma(_))
     }
 
     /// 将数据写入BIO缓冲区（用于同步回退路径）
     pub fn write_buffer(&self, data: &[u8]) {
         let mut inner = self.inner.lock_irqsave();
-        let copy_len = data.len().min(inner.buffer.len());
-        inner.buffer.as_mut_slice()[..copy_len].copy_from_slice(&data[..copy_len]);
+        match &mut inner.buffer {
+            BioBuffer::Dma(buffer) => {
+                let copy_len = data.len().min(buffer.len());
+                buffer.as_mut_slice()[..copy_len].copy_from_slice(&data[..copy_len]);
+            }
+            BioBuffer::User(pages) => pages.copy_from_slice(data),
+        }
     }
 
     /// 获取BIO类型
@@ -126,6 +256,16 @@ impl BioRequest {
         self.inner.lock_irqsave().count
     }
 
+    /// 标记为已与排队的请求合并，用于I/O统计
+    pub fn mark_merged(&self) {
+        self.inner.lock_irqsave().merged = true;
+    }
+
+    /// 是否被合并到了已排队的请求中
+    pub fn is_merged(&self) -> bool {
+        self.inner.lock_irqsave().merged
+    }
+
     /// 获取token
     #[allow(dead_code)]
     pub fn token(&self) -> Option<u16> {
@@ -154,6 +294,21 @@ impl BioRequest {
         completion.complete();
     }
 
+    /// # 以带上下文的错误完成BIO请求
+    ///
+    /// 等待者通过[`wait_context`](Self::wait_context)可以拿到完整的上下文链，其余的回调只能看到错误码
+    pub fn fail(&self, err: ContextError) {
+        let errno = err.errno();
+        {
+            let mut inner = self.inner.lock_irqsave();
+            if matches!(inner.state, BioState::Completed | BioState::Failed) {
+                return;
+            }
+            inner.error_context = Some(err);
+        }
+        self.complete(Err(errno));
+    }
+
     pub fn on_complete<F>(&self, callback: F)
     where
         F: Fn(Result<usize, SystemError>) + Send + Sync + 'static,
@@ -166,19 +321,38 @@ impl BioRequest {
         inner.complete_callbacks.push(Box::new(callback));
     }
 
-    /// 等待BIO完成并返回结果
+    /// BIO是否已经完成（成功或者失败）
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self.inner.lock_irqsave().state,
+            BioState::Completed | BioState::Failed
+        )
+    }
+
+    /// 等待BIO完成并返回结果，读入用户缓冲区的请求返回空的数据
     pub fn wait(&self) -> Result<Vec<u8>, SystemError> {
+        self.wait_context().map_err(|e| e.errno())
+    }
+
+    /// 等待BIO完成并返回结果，失败时带上驱动提供的错误上下文
+    pub fn wait_context(&self) -> Result<Vec<u8>, ContextError> {
         let completion = self.inner.lock_irqsave().completion.clone();
 
         // 等待完成
         completion.wait_for_completion()?;
 
         // 获取结果
-        let inner = self.inner.lock_irqsave();
-        match inner.result.as_ref() {
-            Some(Ok(_)) => Ok(inner.buffer.to_vec()),
-            Some(Err(e)) => Err(e.clone()),
-            None => Err(SystemError::EIO),
+        let mut inner = self.inner.lock_irqsave();
+        match inner.result.clone() {
+            Some(Ok(_)) => match &inner.buffer {
+                BioBuffer::Dma(buffer) => Ok(buffer.to_vec()),
+                BioBuffer::User(_) => Ok(Vec::new()),
+            },
+            Some(Err(e)) => Err(inner
+                .error_context
+                .take()
+                .unwrap_or_else(|| ContextError::new(e))),
+            None => Err(SystemError::EIO.into()),
         }
     }
 }
@@ -10,12 +10,18 @@ use crate::driver::base::{
     },
 };
 
+use crate::filesystem::{sysfs::AttributeGroup, vfs::InodeMode};
+use crate::libs::error_context::ErrorContext;
+use crate::process::io_accounting::{task_io_account_read, task_io_account_write};
 use alloc::{sync::Arc, vec::Vec};
 use core::any::Any;
 use log::error;
 use system_error::SystemError;
 
-use super::{disk_info::Partition, gendisk::GenDisk, manager::BlockDevMeta};
+use super::{
+    disk_info::Partition, gendisk::GenDisk, integrity::BlkIntegrity, manager::BlockDevMeta,
+    stats::StatGroup,
+};
 
 // 该文件定义了 Device 和 BlockDevice 的接口
 // Notice 设备错误码使用 Posix 规定的 int32_t 的错误码表示，而不是自己定义错误enum
@@ -284,17 +290,35 @@ pub trait BlockDevice: Device {
 
     /// # 函数的功能
     /// 经由Cache对块设备的读操作
+    ///
+    /// 这里是块设备层的边界，驱动通过[`BioRequest::fail`](super::bio::BioRequest::fail)
+    /// 提供的错误上下文在这里打印
     fn read_at(
         &self,
         lba_id_start: BlockId,
         count: usize,
         buf: &mut [u8],
     ) -> Result<usize, SystemError> {
-        let bio = self.submit_bio_read(lba_id_start, count)?;
-        let data = bio.wait()?;
-        let copy_len = core::cmp::min(buf.len(), data.len());
-        buf[..copy_len].copy_from_slice(&data[..copy_len]);
-        Ok(copy_len)
+        let read = |buf: &mut [u8]| -> Result<usize, SystemError> {
+            let bio = self.submit_bio_read(lba_id_start, count)?;
+            let data = bio.wait_context().with_context(|| {
+                format!(
+                    "{}: read {} sectors at lba {}",
+                    self.dev_name(),
+                    count,
+                    lba_id_start
+                )
+            })?;
+            let copy_len = core::cmp::min(buf.len(), data.len());
+            buf[..copy_len].copy_from_slice(&data[..copy_len]);
+            Ok(copy_len)
+        };
+        match self.integrity() {
+            Some(integrity) => {
+                integrity.read_verify(self.dev_name(), lba_id_start, count, buf, read)
+            }
+            None => read(buf),
+        }
     }
 
     /// # 函数的功能
@@ -305,9 +329,25 @@ pub trait BlockDevice: Device {
         count: usize,
         buf: &[u8],
     ) -> Result<usize, SystemError> {
-        let bio = self.submit_bio_write(lba_id_start, count, buf)?;
-        let _ = bio.wait()?;
-        Ok(core::cmp::min(buf.len(), count * LBA_SIZE))
+        if self.blkdev_meta().is_read_only() {
+            return Err(SystemError::EROFS);
+        }
+        let write = |buf: &[u8]| -> Result<usize, SystemError> {
+            let bio = self.submit_bio_write(lba_id_start, count, buf)?;
+            bio.wait_context().with_context(|| {
+                format!(
+                    "{}: write {} sectors at lba {}",
+                    self.dev_name(),
+                    count,
+                    lba_id_start
+                )
+            })?;
+            Ok(core::cmp::min(buf.len(), count * LBA_SIZE))
+        };
+        match self.integrity() {
+            Some(integrity) => integrity.write_generate(lba_id_start, count, buf, write),
+            None => write(buf),
+        }
     }
 
     fn write_at_bytes(&self, offset: usize, len: usize, buf: &[u8]) -> Result<usize, SystemError> {
@@ -384,11 +424,75 @@ pub trait BlockDevice: Device {
         return Ok(len);
     }
 
+    /// # 设备节点的默认权限
+    ///
+    /// 该磁盘的gendisk注册到devfs时使用的权限，之后可以通过chmod修改
+    fn devnode_mode(&self) -> InodeMode {
+        InodeMode::from_bits_truncate(0o660)
+    }
+
+    /// # `/sys/block/<dev>`下的额外属性组
+    ///
+    /// 磁盘注册时与`stat`等通用属性一起创建。属性的`show`/`store`收到的是`/sys/block/<dev>`目录的kobject，
+    /// 可以用[`BlockDevManager::lookup`](super::manager::BlockDevManager::lookup)按kobject的名字找到磁盘
+    fn sysfs_groups(&self) -> &'static [&'static dyn AttributeGroup] {
+        &[]
+    }
+
     /// # gendisk注册成功的回调函数
     fn callback_gendisk_registered(&self, _gendisk: &Arc<GenDisk>) -> Result<(), SystemError> {
         Ok(())
     }
 
+    /// # 数据完整性校验
+    ///
+    /// 返回`Some`时，`read_at`会校验读到的每个LBA，`write_at`会在写入后更新校验值。
+    /// 默认不启用
+    fn integrity(&self) -> Option<Arc<BlkIntegrity>> {
+        None
+    }
+
+    /// # 丢弃扇区（TRIM/discard）
+    ///
+    /// 通知设备`range`内的扇区不再保存有效数据，设备可以回收对应的存储空间，
+    /// 之后读取这些扇区得到的内容是未定义的。默认不支持
+    ///
+    /// ## 返回值
+    /// - `Ok(())`: 成功
+    /// - `Err(SystemError::EOPNOTSUPP_OR_ENOTSUP)`: 设备不支持discard
+    fn discard(&self, _range: GeneralBlockRange) -> Result<(), SystemError> {
+        Err(SystemError::EOPNOTSUPP_OR_ENOTSUP)
+    }
+
+    /// # 写屏障（flush）
+    ///
+    /// 把设备易失性缓存中的数据写入持久存储。返回之前，所有在调用前已经完成的写请求都已经持久化。
+    ///
+    /// 默认通过[`submit_bio`](Self::submit_bio)提交一个FLUSH请求，请求队列保证它排在之前提交的请求之后；
+    /// 驱动不支持异步BIO时回退到[`sync`](Self::sync)
+    fn flush(&self) -> Result<(), SystemError> {
+        let bio = super::bio::BioRequest::new_flush();
+        self.blkdev_meta()
+            .stats()
+            .account_bio(StatGroup::Flush, &bio);
+        match self.submit_bio(bio.clone()) {
+            Ok(()) => {
+                bio.wait_context()
+                    .with_context(|| format!("{}: flush", self.dev_name()))?;
+                Ok(())
+            }
+            Err(SystemError::ENOSYS) => {
+                let r = self.sync();
+                bio.complete(r.clone().map(|_| 0));
+                r
+            }
+            Err(e) => {
+                bio.complete(Err(e.clone()));
+                Err(e)
+            }
+        }
+    }
+
     /// 提交异步BIO请求（默认不支持，由驱动选择性实现）
     fn submit_bio(&self, _bio: Arc<super::bio::BioRequest>) -> Result<(), SystemError> {
         Err(SystemError::ENOSYS)
@@ -401,18 +505,23 @@ pub trait BlockDevice: Device {
         count: usize,
     ) -> Result<Arc<super::bio::BioRequest>, SystemError> {
         let bio = super::bio::BioRequest::new_read(lba_start, count);
-        match self.submit_bio(bio.clone()) {
-            Ok(()) => Ok(bio),
+        task_io_account_read(count * LBA_SIZE);
+        self.blkdev_meta()
+            .stats()
+            .account_bio(StatGroup::Read, &bio);
+        let r = match self.submit_bio(bio.clone()) {
+            Ok(()) => return Ok(bio),
             Err(SystemError::ENOSYS) => {
                 log::trace!("BlockDevice submit_bio_read ENOSYS, falling back to sync read");
                 let buf_ptr = bio.buffer_mut();
                 let buf = unsafe { &mut *buf_ptr };
-                self.read_at_sync(lba_start, count, &mut buf[..count * LBA_SIZE])?;
-                bio.complete(Ok(count * LBA_SIZE));
-                Ok(bio)
+                self.read_at_sync(lba_start, count, &mut buf[..count * LBA_SIZE])
+                    .map(|_| count * LBA_SIZE)
             }
             Err(e) => Err(e),
-        }
+        };
+        bio.complete(r.clone());
+        r.map(|_| bio)
     }
 
     /// 提交异步写BIO（优先 submit_bio，不支持则同步回退）
@@ -422,18 +531,26 @@ pub trait BlockDevice: Device {
         count: usize,
         data: &[u8],
     ) -> Result<Arc<super::bio::BioRequest>, SystemError> {
+        if self.blkdev_meta().is_read_only() {
+            return Err(SystemError::EROFS);
+        }
         let bio = super::bio::BioRequest::new_write(lba_start, count, data);
-        match self.submit_bio(bio.clone()) {
-            Ok(()) => Ok(bio),
+        task_io_account_write(count * LBA_SIZE);
+        self.blkdev_meta()
+            .stats()
+            .account_bio(StatGroup::Write, &bio);
+        let r = match self.submit_bio(bio.clone()) {
+            Ok(()) => return Ok(bio),
             Err(SystemError::ENOSYS) => {
                 let buf_ptr = bio.buffer();
                 let buf = unsafe { &*buf_ptr };
-                self.write_at_sync(lba_start, count, &buf[..count * LBA_SIZE])?;
-                bio.complete(Ok(count * LBA_SIZE));
-                Ok(bio)
+                self.write_at_sync(lba_start, count, &buf[..count * LBA_SIZE])
+                    .map(|_| count * LBA_SIZE)
             }
             Err(e) => Err(e),
-        }
+        };
+        bio.complete(r.clone());
+        r.map(|_| bio)
     }
 }
 
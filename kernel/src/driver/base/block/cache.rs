@@ -0,0 +1,906 @@
+//! 块缓存（buffer cache）
+//!
+//! 以(磁盘, LBA)为键缓存扇区，经过[`GenDisk`](super::gendisk::GenDisk)的读写都先访问这里：
+//! - 读：命中的扇区直接从缓存复制，未命中的连续扇区一次性从设备读入并加入缓存；
+//! - 写：只修改缓存中的扇区并标记为脏（write-back），由[`writeback`](crate::mm::writeback)
+//!   线程定期写回，或者在`GenDisk::sync`/`GenDisk::flush`时写回。
+//!
+//! 磁盘用diskseq标识，介质变化（例如loop设备更换后端文件）之后旧介质的缓存不会再被访问到。
+//!
+//! 缓存满时按LRU淘汰干净的扇区。脏扇区和正在写回的扇区不会被淘汰，
+//! 全部是脏扇区时由写入者同步写回自己磁盘上的脏扇区。
+//!
+//! 顺序读时由磁盘的[`BlockReadahead`](super::readahead::BlockReadahead)异步预读后面的扇区，
+//! 预读的数据在之后的读取中收进缓存。
+//!
+//! O_DIRECT的读写通过[`BlockCache::read_direct_user`]和[`BlockCache::write_direct_user`]绕过缓存，
+//! 用固定住的用户页构造BIO直接向设备提交，只负责与缓存中的同一段扇区保持一致。
+//! [`BlockCache::read_direct`]和[`BlockCache::write_direct`]同样绕过缓存，但是经过内核缓冲区。
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use log::warn;
+use lru::LruCache;
+use system_error::SystemError;
+
+use crate::{
+    libs::{error_context::ErrorContext, mutex::Mutex, spinlock::SpinLock},
+    mm::gup::PinnedUserPages,
+    process::io_accounting::{task_io_account_read, task_io_account_write},
+};
+
+use super::{
+    bio::{BioRequest, BioType},
+    block_device::{BlockDevice, BlockId, LBA_SIZE},
+    stats::StatGroup,
+};
+
+/// 缓存的扇区数（8M）
+const BLOCK_CACHE_CAPACITY: usize = 16384;
+/// 直接I/O每个请求的最大扇区数（1M）
+const DIRECT_IO_MAX_SECTORS: usize = 2048;
+/// 直接读写用户页时每个BIO的最大扇区数（128K）。用户缓冲区的每一页是BIO的一个段，
+/// 段数不能超过驱动的请求队列（virtio-blk的virtqueue只有64个描述符）
+const DIRECT_IO_USER_BIO_SECTORS: usize = 256;
+
+/// (diskseq, LBA)
+type CacheKey = (u64, BlockId);
+
+lazy_static! {
+    static ref BLOCK_CACHE: BlockCache = BlockCache::new();
+}
+
+#[inline]
+pub fn block_cache() -> &'static BlockCache {
+    &BLOCK_CACHE
+}
+
+struct CachedBlock {
+    data: Box<[u8; LBA_SIZE]>,
+    /// 内容比设备上的新
+    dirty: bool,
+    /// 正在被写回，写回完成之前不能淘汰
+    writeback: bool,
+}
+
+impl CachedBlock {
+    fn new(data: &[u8], dirty: bool) -> Self {
+        let mut block = Box::new([0u8; LBA_SIZE]);
+        block.copy_from_slice(&data[..LBA_SIZE]);
+        Self {
+            data: block,
+            dirty,
+            writeback: false,
+        }
+    }
+}
+
+/// 缓存中出现过的磁盘
+struct CachedDisk {
+    bdev: Weak<dyn BlockDevice>,
+    /// 串行化同一个磁盘的写回，保证同一个扇区的多次写回按顺序到达设备。
+    /// 按磁盘区分，loop设备写回时会经过下层磁盘的缓存
+    writeback_lock: Arc<Mutex<()>>,
+    /// 后台写回失败的错误，下一次同步该磁盘时返回
+    error: Option<SystemError>,
+}
+
+struct InnerBlockCache {
+    blocks: LruCache<CacheKey, CachedBlock>,
+    /// 脏扇区，按(磁盘, LBA)排序便于合并成连续的写请求
+    dirty: BTreeSet<CacheKey>,
+    disks: BTreeMap<u64, CachedDisk>,
+}
+
+impl InnerBlockCache {
+    fn register_disk(&mut self, disk: u64, bdev: &Arc<dyn BlockDevice>) {
+        self.disks.entry(disk).or_insert_with(|| CachedDisk {
+            bdev: Arc::downgrade(bdev),
+            writeback_lock: Arc::new(Mutex::new(())),
+            error: None,
+        });
+    }
+
+    fn write_block(&mut self, key: CacheKey, data: &[u8]) {
+        match self.blocks.get_mut(&key) {
+            Some(block) => {
+                block.data.copy_from_slice(&data[..LBA_SIZE]);
+                block.dirty = true;
+            }
+            None => {
+                self.blocks.put(key, CachedBlock::new(data, true));
+            }
+        }
+        self.dirty.insert(key);
+    }
+
+    /// 淘汰最久未使用的干净扇区，直到不超过容量。返回是否仍然超过容量
+    fn shrink(&mut self) -> bool {
+        let excess = self.blocks.len().saturating_sub(BLOCK_CACHE_CAPACITY);
+        if excess == 0 {
+            return false;
+        }
+        let victims: Vec<CacheKey> = self
+            .blocks
+            .iter()
+            .rev()
+            .filter(|(_, block)| !block.dirty && !block.writeback)
+            .map(|(key, _)| *key)
+            .take(excess)
+            .collect();
+        for key in victims.iter() {
+            self.blocks.pop(key);
+        }
+        victims.len() < excess
+    }
+}
+
+pub struct BlockCache {
+    inner: SpinLock<InnerBlockCache>,
+}
+
+impl BlockCache {
+    fn new() -> Self {
+        Self {
+            inner: SpinLock::new(InnerBlockCache {
+                blocks: LruCache::unbounded(),
+                dirty: BTreeSet::new(),
+                disks: BTreeMap::new(),
+            }),
+        }
+    }
+
+    #[inline]
+    fn disk_id(bdev: &Arc<dyn BlockDevice>) -> u64 {
+        bdev.blkdev_meta().diskseq()
+    }
+
+    /// # 读取扇区
+    ///
+    /// ## 参数
+    /// - `bdev`: 磁盘
+    /// - `lba`: 磁盘上的起始扇区号
+    /// - `count`: 扇区数
+    /// - `buf`: 输出缓冲区，长度至少为`count * LBA_SIZE`
+    ///
+    /// ## 返回值
+    /// 读取的字节数
+    pub fn read(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        lba: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        let len = count * LBA_SIZE;
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        let disk = Self::disk_id(bdev);
+        // 带完整性校验的磁盘需要在读取时校验，预读的数据没有经过校验
+        let readahead = bdev.integrity().is_none();
+        if readahead {
+            self.harvest_readahead(bdev, disk, lba, count);
+        }
+
+        let mut missing = Vec::new();
+        {
+            let mut inner = self.inner.lock();
+            for i in 0..count {
+                match inner.blocks.get(&(disk, lba + i)) {
+                    Some(block) => {
+                        buf[i * LBA_SIZE..(i + 1) * LBA_SIZE].copy_from_slice(&block.data[..])
+                    }
+                    None => missing.push(i),
+                }
+            }
+        }
+        let missed = !missing.is_empty();
+        if missed {
+            // 未命中的扇区按连续的区间从设备读取
+            for (start, end) in runs(&missing) {
+                bdev.read_at(
+                    lba + start,
+                    end - start,
+                    &mut buf[start * LBA_SIZE..end * LBA_SIZE],
+                )?;
+            }
+
+            let mut inner = self.inner.lock();
+            inner.register_disk(disk, bdev);
+            for i in missing {
+                let key = (disk, lba + i);
+                let slice = &mut buf[i * LBA_SIZE..(i + 1) * LBA_SIZE];
+                // 读设备期间被写入的扇区，以缓存中的内容为准
+                match inner.blocks.get(&key) {
+                    Some(block) => slice.copy_from_slice(&block.data[..]),
+                    None => {
+                        inner.blocks.put(key, CachedBlock::new(slice, false));
+                    }
+                }
+            }
+            inner.shrink();
+        }
+
+        if readahead {
+            self.start_readahead(bdev, disk, lba, count, missed);
+        }
+        Ok(len)
+    }
+
+    /// # 把已经完成的预读收进缓存
+    ///
+    /// 与`[lba, lba + count)`重叠的预读会等待它完成，其余未完成的预读留到之后
+    fn harvest_readahead(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        disk: u64,
+        lba: BlockId,
+        count: usize,
+    ) {
+        for ra in bdev.blkdev_meta().readahead().take_ready(disk, lba, count) {
+            // 预读失败时，未命中的扇区会被同步读取
+            let Ok(data) = ra.bio.wait() else {
+                continue;
+            };
+            let mut inner = self.inner.lock();
+            inner.register_disk(disk, bdev);
+            for i in 0..ra.count {
+                let key = (disk, ra.lba + i);
+                // 预读期间被写入或者读入的扇区，以缓存中的内容为准
+                if !inner.blocks.contains(&key) {
+                    inner
+                        .blocks
+                        .put(key, CachedBlock::new(&data[i * LBA_SIZE..], false));
+                }
+            }
+            inner.shrink();
+        }
+    }
+
+    /// 更新预读窗口，需要时提交新的预读
+    fn start_readahead(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        disk: u64,
+        lba: BlockId,
+        count: usize,
+        missed: bool,
+    ) {
+        let readahead = bdev.blkdev_meta().readahead();
+        let Some((start, n)) = readahead.on_read(lba, count, missed, bdev.disk_range().lba_end)
+        else {
+            return;
+        };
+        // 跳过开头已经在缓存中的扇区
+        let cached = {
+            let inner = self.inner.lock();
+            (0..n)
+                .take_while(|i| inner.blocks.contains(&(disk, start + i)))
+                .count()
+        };
+        if cached < n {
+            readahead.submit(bdev, disk, start + cached, n - cached);
+        }
+    }
+
+    /// # 写入扇区
+    ///
+    /// 只写入缓存，数据在之后写回设备。需要保证数据落盘时调用[`BlockCache::sync_disk`]
+    ///
+    /// ## 参数
+    /// - `bdev`: 磁盘
+    /// - `lba`: 磁盘上的起始扇区号
+    /// - `count`: 扇区数
+    /// - `buf`: 输入缓冲区，长度至少为`count * LBA_SIZE`
+    ///
+    /// ## 返回值
+    /// - `Ok(len)`: 写入的字节数
+    /// - `Err(SystemError::EROFS)`: 设备只读
+    /// - `Err(SystemError::EINVAL)`: 缓冲区太小或者超出了磁盘的范围
+    pub fn write(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        lba: BlockId,
+        count: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        if bdev.blkdev_meta().is_read_only() {
+            return Err(SystemError::EROFS);
+        }
+        let len = count * LBA_SIZE;
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        // 写回时才会访问设备，在这里提前检查范围
+        let end = lba.checked_add(count).ok_or(SystemError::EINVAL)?;
+        if end > bdev.disk_range().lba_end {
+            return Err(SystemError::EINVAL);
+        }
+        let disk = Self::disk_id(bdev);
+        bdev.blkdev_meta().readahead().invalidate(lba, count);
+
+        let full = {
+            let mut inner = self.inner.lock();
+            inner.register_disk(disk, bdev);
+            for i in 0..count {
+                inner.write_block((disk, lba + i), &buf[i * LBA_SIZE..(i + 1) * LBA_SIZE]);
+            }
+            inner.shrink()
+        };
+        if full {
+            // 错误会记录下来，在同步时返回
+            let _ = self.writeback_disk(disk);
+        }
+        Ok(len)
+    }
+
+    /// # 绕过缓存读取扇区
+    ///
+    /// 用于O_DIRECT。范围内还没有写回的脏扇区先写回设备，然后直接从设备读取，读到的数据不进入缓存
+    ///
+    /// ## 参数
+    /// - `bdev`: 磁盘
+    /// - `lba`: 磁盘上的起始扇区号
+    /// - `count`: 扇区数
+    /// - `buf`: 输出缓冲区，长度至少为`count * LBA_SIZE`
+    ///
+    /// ## 返回值
+    /// 读取的字节数
+    pub fn read_direct(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        lba: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        let len = count * LBA_SIZE;
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        self.writeback_range(bdev, lba, count)?;
+
+        let mut done = 0;
+        while done < count {
+            let n = core::cmp::min(count - done, DIRECT_IO_MAX_SECTORS);
+            bdev.read_at(
+                lba + done,
+                n,
+                &mut buf[done * LBA_SIZE..(done + n) * LBA_SIZE],
+            )?;
+            done += n;
+        }
+        Ok(len)
+    }
+
+    /// # 绕过缓存写入扇区
+    ///
+    /// 用于O_DIRECT。数据直接写到设备上，返回时已经完成（不包括设备自己的易失性缓存）。
+    /// 范围内的脏扇区先写回，写入之后丢弃范围内的缓存，之后经过缓存的读取会从设备读到新的数据
+    ///
+    /// ## 参数
+    /// - `bdev`: 磁盘
+    /// - `lba`: 磁盘上的起始扇区号
+    /// - `count`: 扇区数
+    /// - `buf`: 输入缓冲区，长度至少为`count * LBA_SIZE`
+    ///
+    /// ## 返回值
+    /// - `Ok(len)`: 写入的字节数
+    /// - `Err(SystemError::EROFS)`: 设备只读
+    /// - `Err(SystemError::EINVAL)`: 缓冲区太小或者超出了磁盘的范围
+    pub fn write_direct(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        lba: BlockId,
+        count: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        if bdev.blkdev_meta().is_read_only() {
+            return Err(SystemError::EROFS);
+        }
+        let len = count * LBA_SIZE;
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        let end = lba.checked_add(count).ok_or(SystemError::EINVAL)?;
+        if end > bdev.disk_range().lba_end {
+            return Err(SystemError::EINVAL);
+        }
+        // 先写回，避免之后的写回用旧数据覆盖这次写入
+        self.writeback_range(bdev, lba, count)?;
+
+        let mut done = 0;
+        let result = loop {
+            if done == count {
+                break Ok(len);
+            }
+            let n = core::cmp::min(count - done, DIRECT_IO_MAX_SECTORS);
+            if let Err(e) =
+                bdev.write_at(lba + done, n, &buf[done * LBA_SIZE..(done + n) * LBA_SIZE])
+            {
+                break Err(e);
+            }
+            done += n;
+        };
+        // 即使写入失败，已经写入的部分也让缓存失效
+        self.invalidate_range(bdev, lba, count);
+        result
+    }
+
+    /// # 绕过缓存读取扇区到用户缓冲区
+    ///
+    /// 用于O_DIRECT。与[`read_direct`](Self::read_direct)相同，但是设备直接把数据写入固定住的用户页。
+    /// 磁盘带有完整性元数据时需要校验读到的数据，经过内核缓冲区读取
+    ///
+    /// ## 参数
+    /// - `bdev`: 磁盘
+    /// - `lba`: 磁盘上的起始扇区号
+    /// - `pages`: 用户缓冲区，长度是扇区大小的整数倍
+    ///
+    /// ## 返回值
+    /// - `Ok(len)`: 读取的字节数
+    /// - `Err(SystemError::EINVAL)`: 超出了磁盘的范围
+    pub fn read_direct_user(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        lba: BlockId,
+        pages: &PinnedUserPages,
+    ) -> Result<usize, SystemError> {
+        let count = pages.len() / LBA_SIZE;
+        let end = lba.checked_add(count).ok_or(SystemError::EINVAL)?;
+        if end > bdev.disk_range().lba_end {
+            return Err(SystemError::EINVAL);
+        }
+        if bdev.integrity().is_some() {
+            let mut buf = vec![0u8; count * LBA_SIZE];
+            let len = self.read_direct(bdev, lba, count, &mut buf)?;
+            pages.copy_from_slice(&buf);
+            return Ok(len);
+        }
+        self.writeback_range(bdev, lba, count)?;
+        Self::submit_direct_user(bdev, BioType::Read, lba, pages)
+    }
+
+    /// # 绕过缓存把用户缓冲区写入扇区
+    ///
+    /// 用于O_DIRECT。与[`write_direct`](Self::write_direct)相同，但是设备直接从固定住的用户页读取数据。
+    /// 磁盘带有完整性元数据时需要计算校验信息，经过内核缓冲区写入
+    ///
+    /// ## 参数
+    /// - `bdev`: 磁盘
+    /// - `lba`: 磁盘上的起始扇区号
+    /// - `pages`: 用户缓冲区，长度是扇区大小的整数倍
+    ///
+    /// ## 返回值
+    /// - `Ok(len)`: 写入的字节数
+    /// - `Err(SystemError::EROFS)`: 设备只读
+    /// - `Err(SystemError::EINVAL)`: 超出了磁盘的范围
+    pub fn write_direct_user(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        lba: BlockId,
+        pages: &PinnedUserPages,
+    ) -> Result<usize, SystemError> {
+        let count = pages.len() / LBA_SIZE;
+        if bdev.integrity().is_some() {
+            let mut buf = vec![0u8; count * LBA_SIZE];
+            pages.copy_to_slice(&mut buf);
+            return self.write_direct(bdev, lba, count, &buf);
+        }
+        if bdev.blkdev_meta().is_read_only() {
+            return Err(SystemError::EROFS);
+        }
+        let end = lba.checked_add(count).ok_or(SystemError::EINVAL)?;
+        if end > bdev.disk_range().lba_end {
+            return Err(SystemError::EINVAL);
+        }
+        // 先写回，避免之后的写回用旧数据覆盖这次写入
+        self.writeback_range(bdev, lba, count)?;
+        let result = Self::submit_direct_user(bdev, BioType::Write, lba, pages);
+        // 即使写入失败，已经写入的部分也让缓存失效
+        self.invalidate_range(bdev, lba, count);
+        result
+    }
+
+    /// # 用用户页构造BIO并等待完成
+    ///
+    /// 缓冲区按[`DIRECT_IO_USER_BIO_SECTORS`]拆分成多个BIO，全部提交之后再等待。
+    /// 驱动不支持异步BIO时，逐个BIO经过内核缓冲区同步读写。
+    /// 某个BIO提交失败时不再提交后面的，但仍然等待已经提交的BIO完成
+    fn submit_direct_user(
+        bdev: &Arc<dyn BlockDevice>,
+        bio_type: BioType,
+        lba: BlockId,
+        pages: &PinnedUserPages,
+    ) -> Result<usize, SystemError> {
+        let count = pages.len() / LBA_SIZE;
+        let mut bios = Vec::new();
+        let mut result = Ok(count * LBA_SIZE);
+        let mut done = 0;
+        while done < count {
+            let n = core::cmp::min(count - done, DIRECT_IO_USER_BIO_SECTORS);
+            let part = pages.slice(done * LBA_SIZE, n * LBA_SIZE);
+            let (bio, group) = if bio_type == BioType::Read {
+                task_io_account_read(n * LBA_SIZE);
+                (BioRequest::new_read_user(lba + done, part), StatGroup::Read)
+            } else {
+                task_io_account_write(n * LBA_SIZE);
+                (
+                    BioRequest::new_write_user(lba + done, part),
+                    StatGroup::Write,
+                )
+            };
+            bdev.blkdev_meta().stats().account_bio(group, &bio);
+
+            let r = match bdev.submit_bio(bio.clone()) {
+                Ok(()) => Ok(()),
+                Err(SystemError::ENOSYS) => {
+                    let part = pages.slice(done * LBA_SIZE, n * LBA_SIZE);
+                    let mut buf = vec![0u8; n * LBA_SIZE];
+                    let r = if bio_type == BioType::Read {
+                        bdev.read_at_sync(lba + done, n, &mut buf)
+                            .map(|_| part.copy_from_slice(&buf))
+                    } else {
+                        part.copy_to_slice(&mut buf);
+                        bdev.write_at_sync(lba + done, n, &buf).map(|_| ())
+                    };
+                    bio.complete(r.clone().map(|_| n * LBA_SIZE));
+                    r
+                }
+                Err(e) => {
+                    bio.complete(Err(e.clone()));
+                    Err(e)
+                }
+            };
+            bios.push(bio);
+            if let Err(e) = r {
+                result = Err(e);
+                break;
+            }
+            done += n;
+        }
+
+        let op = if bio_type == BioType::Read {
+            "read"
+        } else {
+            "write"
+        };
+        // BIO持有用户页的引用，即使等待被信号打断，设备访问完成之前这些页也不会被释放
+        for bio in bios {
+            let r = bio.wait_context().with_context(|| {
+                format!(
+                    "{}: direct {} {} sectors at lba {}",
+                    bdev.dev_name(),
+                    op,
+                    bio.count(),
+                    bio.lba_start()
+                )
+            });
+            if let Err(e) = r {
+                // 只返回第一个错误，后面的错误也打印上下文
+                result = result.and(Err(e.into()));
+            }
+        }
+        result
+    }
+
+    /// 范围内有脏扇区或者正在写回的扇区时写回磁盘，返回时范围内的扇区都已经写到设备上
+    fn writeback_range(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        lba: BlockId,
+        count: usize,
+    ) -> Result<(), SystemError> {
+        let disk = Self::disk_id(bdev);
+        let pending = {
+            let inner = self.inner.lock();
+            inner
+                .dirty
+                .range((disk, lba)..(disk, lba.saturating_add(count)))
+                .next()
+                .is_some()
+                || (0..count).any(|i| {
+                    inner
+                        .blocks
+                        .peek(&(disk, lba + i))
+                        .is_some_and(|block| block.writeback)
+                })
+        };
+        // 写回持有磁盘的写回锁，会等待正在进行的写回完成
+        if pending {
+            self.writeback_disk(disk)?;
+        }
+        Ok(())
+    }
+
+    /// # 按字节读取
+    ///
+    /// ## 参数
+    /// - `bdev`: 磁盘
+    /// - `offset`: 磁盘上的字节偏移量
+    /// - `buf`: 输出缓冲区
+    pub fn read_bytes(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        let len = buf.len();
+        if offset.is_multiple_of(LBA_SIZE) && len.is_multiple_of(LBA_SIZE) {
+            return self.read(bdev, offset / LBA_SIZE, len / LBA_SIZE, buf);
+        }
+        let start = offset / LBA_SIZE;
+        let end = (offset + len).div_ceil(LBA_SIZE);
+        let mut temp = vec![0u8; (end - start) * LBA_SIZE];
+        self.read(bdev, start, end - start, &mut temp)?;
+        let begin = offset - start * LBA_SIZE;
+        buf.copy_from_slice(&temp[begin..begin + len]);
+        Ok(len)
+    }
+
+    /// # 按字节写入
+    ///
+    /// 不完整的扇区在缓存中原地修改，不会与同一扇区上的其他写入互相覆盖
+    ///
+    /// ## 参数
+    /// - `bdev`: 磁盘
+    /// - `offset`: 磁盘上的字节偏移量
+    /// - `buf`: 输入缓冲区
+    pub fn write_bytes(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        offset: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        let len = buf.len();
+        let end = offset + len;
+        let mut pos = offset;
+        while pos < end {
+            let lba = pos / LBA_SIZE;
+            let begin = pos % LBA_SIZE;
+            if begin == 0 && end - pos >= LBA_SIZE {
+                // 连续的完整扇区一次写入
+                let count = (end - pos) / LBA_SIZE;
+                self.write(
+                    bdev,
+                    lba,
+                    count,
+                    &buf[pos - offset..pos - offset + count * LBA_SIZE],
+                )?;
+                pos += count * LBA_SIZE;
+            } else {
+                let n = core::cmp::min(LBA_SIZE - begin, end - pos);
+                self.modify_block(bdev, lba, begin, &buf[pos - offset..pos - offset + n])?;
+                pos += n;
+            }
+        }
+        Ok(len)
+    }
+
+    /// 把`data`写入扇区`lba`中偏移量为`begin`的位置
+    fn modify_block(
+        &self,
+        bdev: &Arc<dyn BlockDevice>,
+        lba: BlockId,
+        begin: usize,
+        data: &[u8],
+    ) -> Result<(), SystemError> {
+        if bdev.blkdev_meta().is_read_only() {
+            return Err(SystemError::EROFS);
+        }
+        let disk = Self::disk_id(bdev);
+        bdev.blkdev_meta().readahead().invalidate(lba, 1);
+        let mut temp = [0u8; LBA_SIZE];
+        loop {
+            {
+                let mut guard = self.inner.lock();
+                let inner = &mut *guard;
+                if let Some(block) = inner.blocks.get_mut(&(disk, lba)) {
+                    block.data[begin..begin + data.len()].copy_from_slice(data);
+                    block.dirty = true;
+                    inner.dirty.insert((disk, lba));
+                    return Ok(());
+                }
+            }
+            // 先把扇区读入缓存。读入之后又被淘汰时重试
+            self.read(bdev, lba, 1, &mut temp)?;
+        }
+    }
+
+    /// # 同步磁盘
+    ///
+    /// 把磁盘的脏扇区写回设备（不包括设备自己的易失性缓存）
+    ///
+    /// ## 返回值
+    /// 写回失败或者之前的后台写回失败时，返回对应的错误
+    pub fn sync_disk(&self, bdev: &Arc<dyn BlockDevice>) -> Result<(), SystemError> {
+        let disk = Self::disk_id(bdev);
+        let result = self.writeback_disk(disk);
+        let error = self
+            .inner
+            .lock()
+            .disks
+            .get_mut(&disk)
+            .and_then(|d| d.error.take());
+        result.and(error.map_or(Ok(()), Err))
+    }
+
+    /// # 丢弃磁盘的缓存
+    ///
+    /// 用于介质变化或者磁盘被删除之后，脏扇区不会被写回
+    pub fn invalidate_disk(&self, bdev: &Arc<dyn BlockDevice>) {
+        let disk = Self::disk_id(bdev);
+        let inner = self.inner.lock();
+        let dirty = inner.dirty.range((disk, 0)..=(disk, BlockId::MAX)).count();
+        if dirty > 0 {
+            warn!(
+                "{}: dropping {} dirty cached sectors",
+                bdev.dev_name(),
+                dirty
+            );
+        }
+        drop(inner);
+        self.drop_disk(disk);
+    }
+
+    /// # 丢弃一段扇区的缓存
+    ///
+    /// 用于discard之后，这些扇区的内容不再有意义
+    pub fn invalidate_range(&self, bdev: &Arc<dyn BlockDevice>, lba: BlockId, count: usize) {
+        let disk = Self::disk_id(bdev);
+        bdev.blkdev_meta().readahead().invalidate(lba, count);
+        let mut inner = self.inner.lock();
+        for i in 0..count {
+            let key = (disk, lba + i);
+            inner.dirty.remove(&key);
+            inner.blocks.pop(&key);
+        }
+    }
+
+    /// # 丢弃一段扇区中干净的缓存
+    ///
+    /// 用于BLKFLSBUF，调用者应当先同步磁盘。脏扇区和正在写回的扇区会被保留
+    pub fn drop_clean_range(&self, bdev: &Arc<dyn BlockDevice>, lba: BlockId, count: usize) {
+        let disk = Self::disk_id(bdev);
+        let end = lba.saturating_add(count);
+        let mut inner = self.inner.lock();
+        let keys: Vec<CacheKey> = inner
+            .blocks
+            .iter()
+            .filter(|((d, l), block)| {
+                *d == disk && (lba..end).contains(l) && !block.dirty && !block.writeback
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        for key in keys.iter() {
+            inner.blocks.pop(key);
+        }
+    }
+
+    /// 脏扇区的数量，计入系统的脏页
+    pub fn dirty_sectors(&self) -> usize {
+        self.inner.lock().dirty.len()
+    }
+
+    /// 把所有磁盘的脏扇区写回设备
+    pub fn writeback_all(&self) {
+        let disks: Vec<u64> = {
+            let inner = self.inner.lock();
+            let mut disks: Vec<u64> = inner.dirty.iter().map(|(d, _)| *d).collect();
+            disks.dedup();
+            disks
+        };
+        for disk in disks {
+            // 错误会记录下来，在同步时返回
+            let _ = self.writeback_disk(disk);
+        }
+    }
+
+    /// 把一个磁盘的脏扇区写回设备
+    fn writeback_disk(&self, disk: u64) -> Result<(), SystemError> {
+        let (bdev, lock) = {
+            let inner = self.inner.lock();
+            match inner.disks.get(&disk) {
+                Some(d) => (d.bdev.upgrade(), d.writeback_lock.clone()),
+                None => return Ok(()),
+            }
+        };
+        let Some(bdev) = bdev else {
+            // 磁盘已经不存在了
+            self.drop_disk(disk);
+            return Ok(());
+        };
+
+        let _guard = lock.lock();
+        // 取出脏扇区的快照，标记为正在写回
+        let batch: Vec<(BlockId, Box<[u8; LBA_SIZE]>)> = {
+            let mut inner = self.inner.lock();
+            let keys: Vec<CacheKey> = inner
+                .dirty
+                .range((disk, 0)..=(disk, BlockId::MAX))
+                .cloned()
+                .collect();
+            let mut batch = Vec::with_capacity(keys.len());
+            for key in keys {
+                inner.dirty.remove(&key);
+                if let Some(block) = inner.blocks.peek_mut(&key) {
+                    block.dirty = false;
+                    block.writeback = true;
+                    batch.push((key.1, block.data.clone()));
+                }
+            }
+            batch
+        };
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let mut result = Ok(());
+        let mut i = 0;
+        while i < batch.len() {
+            // 合并连续的扇区
+            let mut j = i + 1;
+            while j < batch.len() && batch[j].0 == batch[j - 1].0 + 1 {
+                j += 1;
+            }
+            let mut buf = Vec::with_capacity((j - i) * LBA_SIZE);
+            for (_, data) in batch[i..j].iter() {
+                buf.extend_from_slice(&data[..]);
+            }
+            if let Err(e) = bdev.write_at(batch[i].0, j - i, &buf) {
+                warn!(
+                    "{}: failed to write back sectors {}..{}: {:?}",
+                    bdev.dev_name(),
+                    batch[i].0,
+                    batch[j - 1].0 + 1,
+                    e
+                );
+                result = Err(e);
+            }
+            i = j;
+        }
+
+        let mut inner = self.inner.lock();
+        for (lba, _) in batch.iter() {
+            if let Some(block) = inner.blocks.peek_mut(&(disk, *lba)) {
+                block.writeback = false;
+            }
+        }
+        if let (Err(e), Some(d)) = (&result, inner.disks.get_mut(&disk)) {
+            d.error = Some(e.clone());
+        }
+        inner.shrink();
+        result
+    }
+
+    fn drop_disk(&self, disk: u64) {
+        let mut inner = self.inner.lock();
+        inner.dirty.retain(|(d, _)| *d != disk);
+        let keys: Vec<CacheKey> = inner
+            .blocks
+            .iter()
+            .filter(|((d, _), _)| *d == disk)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in keys.iter() {
+            inner.blocks.pop(key);
+        }
+        inner.disks.remove(&disk);
+    }
+}
+
+/// 把有序的下标分成连续的区间`[start, end)`
+fn runs(indices: &[usize]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for &i in indices {
+        match runs.last_mut() {
+            Some((_, end)) if *end == i => *end += 1,
+            _ => runs.push((i, i + 1)),
+        }
+    }
+    runs
+}
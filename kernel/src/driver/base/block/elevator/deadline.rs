@@ -0,0 +1,199 @@
+//! deadline调度器
+//!
+//! 读写请求各自维护一个按LBA排序的队列和一个按到期时间排序的FIFO。
+//! 平时按LBA升序成批派发以减少寻道；FIFO头部的请求到期时从它开始新的一批，
+//! 保证每个请求的等待时间有上限。读请求优先，但写请求最多被饿`WRITES_STARVED`次。
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    vec::Vec,
+};
+use core::ops::Bound;
+
+use crate::{
+    driver::base::block::{
+        bio::{BioRequest, BioType},
+        block_device::BlockId,
+        request_queue::Request,
+    },
+    time::{clocksource::HZ, timer::clock},
+};
+
+use super::Elevator;
+
+/// 读请求的最长等待时间（jiffies），500ms
+const READ_EXPIRE: u64 = HZ / 2;
+/// 写请求的最长等待时间（jiffies），5s
+const WRITE_EXPIRE: u64 = 5 * HZ;
+/// 有写请求等待时，连续优先派发读请求的最大批次数
+const WRITES_STARVED: usize = 2;
+/// 一批中按LBA顺序连续派发的最大请求数
+const FIFO_BATCH: usize = 16;
+
+const READ: usize = 0;
+const WRITE: usize = 1;
+
+/// 排序队列的键：(起始LBA, 入队序号)，序号用于区分起始LBA相同的请求
+type SortKey = (BlockId, u64);
+
+struct FifoEntry {
+    expire: u64,
+    key: SortKey,
+}
+
+#[derive(Default)]
+pub struct DeadlineElevator {
+    sort_list: [BTreeMap<SortKey, Request>; 2],
+    fifo_list: [VecDeque<FifoEntry>; 2],
+    /// 当前批次中下一个要派发的请求
+    next_key: [Option<SortKey>; 2],
+    /// 当前批次已经派发的请求数
+    batching: usize,
+    /// 有写请求等待时，读请求已经连续占用的批次数
+    starved: usize,
+    next_seq: u64,
+}
+
+impl DeadlineElevator {
+    pub const NAME: &'static str = "deadline";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn dir_of(bio_type: BioType) -> usize {
+        match bio_type {
+            BioType::Read => READ,
+            // FLUSH由请求队列直接派发，不会进入调度器
+            BioType::Write | BioType::Flush | BioType::Discard => WRITE,
+        }
+    }
+
+    fn fifo_expired(&self, dir: usize, now: u64) -> bool {
+        self.fifo_list[dir]
+            .front()
+            .is_some_and(|entry| entry.expire <= now)
+    }
+
+    /// 前向合并后请求的起始LBA变了，需要更新它在排序队列和FIFO中的键
+    fn rekey(&mut self, dir: usize, old: SortKey, new: SortKey) {
+        if let Some(rq) = self.sort_list[dir].remove(&old) {
+            self.sort_list[dir].insert(new, rq);
+        }
+        if let Some(entry) = self.fifo_list[dir].iter_mut().find(|e| e.key == old) {
+            entry.key = new;
+        }
+        if self.next_key[dir] == Some(old) {
+            self.next_key[dir] = Some(new);
+        }
+    }
+
+    /// 从排序队列中取出`key`对应的请求，并记录同方向上的下一个请求
+    fn move_request(&mut self, dir: usize, key: SortKey) -> Option<Request> {
+        self.next_key = [None, None];
+        self.next_key[dir] = self.sort_list[dir]
+            .range((Bound::Excluded(key), Bound::Unbounded))
+            .next()
+            .map(|(k, _)| *k);
+        self.fifo_list[dir].retain(|e| e.key != key);
+        self.batching += 1;
+        self.sort_list[dir].remove(&key)
+    }
+}
+
+impl Elevator for DeadlineElevator {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn merge(&mut self, bio: &Arc<BioRequest>, max_sectors: usize) -> bool {
+        let dir = Self::dir_of(bio.bio_type());
+        let lba_start = bio.lba_start();
+        let lba_end = lba_start + bio.count();
+
+        // 后向合并：起始LBA不大于bio的最后一个请求
+        if let Some((_, rq)) = self.sort_list[dir]
+            .range_mut(..=(lba_start, u64::MAX))
+            .next_back()
+        {
+            if rq.try_back_merge(bio, max_sectors) {
+                return true;
+            }
+        }
+
+        // 前向合并：起始LBA恰好等于bio结束位置的请求
+        let front = self.sort_list[dir]
+            .range_mut((lba_end, 0)..)
+            .next()
+            .filter(|(key, _)| key.0 == lba_end);
+        if let Some((key, rq)) = front {
+            let old = *key;
+            if rq.try_front_merge(bio, max_sectors) {
+                self.rekey(dir, old, (lba_start, old.1));
+                return true;
+            }
+        }
+        false
+    }
+
+    fn add_request(&mut self, rq: Request) {
+        let dir = Self::dir_of(rq.bio_type());
+        let key = (rq.lba_start(), self.next_seq);
+        self.next_seq += 1;
+        let expire = rq.start_time()
+            + match dir {
+                READ => READ_EXPIRE,
+                _ => WRITE_EXPIRE,
+            };
+        self.fifo_list[dir].push_back(FifoEntry { expire, key });
+        self.sort_list[dir].insert(key, rq);
+    }
+
+    fn dispatch(&mut self) -> Option<Request> {
+        // 当前批次还没用完，继续按LBA顺序派发
+        if self.batching < FIFO_BATCH {
+            for dir in [READ, WRITE] {
+                if let Some(key) = self.next_key[dir] {
+                    return self.move_request(dir, key);
+                }
+            }
+        }
+
+        let has_reads = !self.sort_list[READ].is_empty();
+        let has_writes = !self.sort_list[WRITE].is_empty();
+        let dir = if has_reads && !(has_writes && self.starved >= WRITES_STARVED) {
+            if has_writes {
+                self.starved += 1;
+            }
+            READ
+        } else if has_writes {
+            self.starved = 0;
+            WRITE
+        } else {
+            return None;
+        };
+
+        // 开始新的一批：FIFO头部到期或者没有可以接着派发的请求时，从FIFO头部开始
+        let key = match self.next_key[dir] {
+            Some(key) if !self.fifo_expired(dir, clock()) => key,
+            _ => self.fifo_list[dir].front()?.key,
+        };
+        self.batching = 0;
+        self.move_request(dir, key)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sort_list.iter().all(|list| list.is_empty())
+    }
+
+    fn drain(&mut self) -> Vec<Request> {
+        self.fifo_list.iter_mut().for_each(|fifo| fifo.clear());
+        self.next_key = [None, None];
+        let mut rqs = Vec::new();
+        for list in self.sort_list.iter_mut() {
+            rqs.extend(core::mem::take(list).into_values());
+        }
+        rqs
+    }
+}
@@ -0,0 +1,70 @@
+//! 块设备I/O调度器（电梯算法）
+//!
+//! 每个[`RequestQueue`](super::request_queue::RequestQueue)持有一个调度器，
+//! 负责BIO合并时查找相邻请求，以及决定请求派发给驱动的顺序。
+//! 默认调度器可以通过内核命令行参数`elevator=`指定。
+
+mod deadline;
+mod noop;
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+
+use super::{bio::BioRequest, request_queue::Request};
+
+pub use self::{deadline::DeadlineElevator, noop::NoopElevator};
+
+kernel_cmdline_param_kv!(ELEVATOR_PARAM, elevator, "");
+
+/// 未指定时使用的调度器
+const DEFAULT_ELEVATOR: &str = DeadlineElevator::NAME;
+
+/// 所有可用调度器的名字
+pub const ELEVATOR_NAMES: &[&str] = &[NoopElevator::NAME, DeadlineElevator::NAME];
+
+/// 电梯调度器
+pub trait Elevator: Send + Sync {
+    /// 调度器的名字
+    fn name(&self) -> &'static str;
+
+    /// # 功能
+    ///
+    /// 尝试把`bio`合并进已排队的某个请求。
+    ///
+    /// ## 参数
+    /// - `bio`: 新提交的BIO
+    /// - `max_sectors`: 合并后请求允许的最大扇区数
+    ///
+    /// ## 返回值
+    /// - `true`: 已经合并，调用者不需要再为它创建请求
+    /// - `false`: 没有可以合并的请求
+    fn merge(&mut self, bio: &Arc<BioRequest>, max_sectors: usize) -> bool;
+
+    /// 加入一个新请求
+    fn add_request(&mut self, rq: Request);
+
+    /// 取出下一个应当派发给驱动的请求
+    fn dispatch(&mut self) -> Option<Request>;
+
+    /// 是否没有排队的请求
+    fn is_empty(&self) -> bool;
+
+    /// 取出所有排队的请求（用于切换调度器）
+    fn drain(&mut self) -> Vec<Request>;
+}
+
+/// 按名字创建调度器，名字未知时返回`None`
+pub fn elevator_alloc(name: &str) -> Option<Box<dyn Elevator>> {
+    match name.trim() {
+        NoopElevator::NAME => Some(Box::new(NoopElevator::new())),
+        DeadlineElevator::NAME => Some(Box::new(DeadlineElevator::new())),
+        _ => None,
+    }
+}
+
+/// 创建默认调度器。命令行参数指定了未知的名字时回退到内置默认值
+pub fn default_elevator() -> Box<dyn Elevator> {
+    ELEVATOR_PARAM
+        .value_str()
+        .and_then(elevator_alloc)
+        .unwrap_or_else(|| elevator_alloc(DEFAULT_ELEVATOR).unwrap())
+}
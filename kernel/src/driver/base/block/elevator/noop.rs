@@ -0,0 +1,50 @@
+//! noop调度器：按提交顺序派发，只做相邻合并
+
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
+
+use crate::driver::base::block::{bio::BioRequest, request_queue::Request};
+
+use super::Elevator;
+
+#[derive(Default)]
+pub struct NoopElevator {
+    queue: VecDeque<Request>,
+}
+
+impl NoopElevator {
+    pub const NAME: &'static str = "noop";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Elevator for NoopElevator {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn merge(&mut self, bio: &Arc<BioRequest>, max_sectors: usize) -> bool {
+        // 顺序I/O最可能与最近提交的请求相邻，从队尾开始找
+        self.queue
+            .iter_mut()
+            .rev()
+            .any(|rq| rq.try_back_merge(bio, max_sectors) || rq.try_front_merge(bio, max_sectors))
+    }
+
+    fn add_request(&mut self, rq: Request) {
+        self.queue.push_back(rq);
+    }
+
+    fn dispatch(&mut self) -> Option<Request> {
+        self.queue.pop_front()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    fn drain(&mut self) -> Vec<Request> {
+        self.queue.drain(..).collect()
+    }
+}
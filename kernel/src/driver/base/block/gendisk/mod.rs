@@ -1,7 +1,7 @@
 use core::{
     convert::TryFrom,
     ops::{Deref, DerefMut},
-    sync::atomic::{AtomicU32, Ordering},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use alloc::{
@@ -11,18 +11,71 @@ use alloc::{
 use hashbrown::HashMap;
 use system_error::SystemError;
 
-use super::block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE};
+use super::{
+    block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE},
+    cache::block_cache,
+    stats::StatGroup,
+};
 use crate::{
-    driver::{base::device::device_number::DeviceNumber, block::loop_device::LoopDevice},
+    driver::{
+        base::{block::manager::block_dev_manager, device::device_number::DeviceNumber},
+        block::{loop_device::LoopDevice, md::MdDevice, nbd::NbdDevice, zram::ZramDevice},
+    },
     filesystem::{
-        devfs::{DevFS, DeviceINode, LockedDevFSInode},
-        vfs::{utils::DName, IndexNode, InodeMode, Metadata},
+        devfs::{DevFS, DevNodeAttr, DeviceINode, LockedDevFSInode},
+        vfs::{
+            ioctl::{io, ior, IoctlArg},
+            utils::DName,
+            IndexNode, InodeMode, Metadata,
+        },
     },
     libs::{mutex::MutexGuard, rwlock::RwLock},
+    mm::gup::PinnedUserPages,
+    process::{cred::CAPFlags, ProcessManager},
 };
 
 const MINORS_PER_DISK: u32 = 256;
 
+/// 通用的块设备ioctl命令
+///
+/// 参考 Linux include/uapi/linux/fs.h
+struct BlockIoctlCmd;
+
+impl BlockIoctlCmd {
+    /// 设置设备是否只读，参数为`int *`
+    const BLKROSET: u32 = io(0x12, 93);
+    /// 获取设备是否只读，参数为`int *`
+    const BLKROGET: u32 = io(0x12, 94);
+    /// 重新扫描分区表
+    const BLKRRPART: u32 = io(0x12, 95);
+    /// 获取设备大小（以512字节扇区为单位），参数为`unsigned long *`
+    const BLKGETSIZE: u32 = io(0x12, 96);
+    /// 写回并丢弃设备的缓存
+    const BLKFLSBUF: u32 = io(0x12, 97);
+    /// 设置预读窗口上限（以512字节扇区为单位），参数直接是值
+    const BLKRASET: u32 = io(0x12, 98);
+    /// 获取预读窗口上限（以512字节扇区为单位），参数为`long *`
+    const BLKRAGET: u32 = io(0x12, 99);
+    /// 获取逻辑扇区大小，参数为`int *`
+    const BLKSSZGET: u32 = io(0x12, 104);
+    /// 获取块大小。与Linux一致，命令号中编码的是`size_t`，但参数实际为`int *`
+    const BLKBSZGET: u32 = ior::<usize>(0x12, 112);
+    /// 获取设备大小（以字节为单位），参数为`u64 *`
+    const BLKGETSIZE64: u32 = ior::<usize>(0x12, 114);
+    /// 获取最小I/O大小，参数为`unsigned int *`
+    const BLKIOMIN: u32 = io(0x12, 120);
+    /// 获取最佳I/O大小，参数为`unsigned int *`
+    const BLKIOOPT: u32 = io(0x12, 121);
+    /// 获取对齐偏移量，参数为`int *`
+    const BLKALIGNOFF: u32 = io(0x12, 122);
+    /// 获取物理扇区大小，参数为`unsigned int *`
+    const BLKPBSZGET: u32 = io(0x12, 123);
+    /// 获取discard之后读到的是否为0，参数为`unsigned int *`
+    const BLKDISCARDZEROES: u32 = io(0x12, 124);
+    /// 获取磁盘序列号，参数为`u64 *`
+    const BLKGETDISKSEQ: u32 = ior::<u64>(0x12, 128);
+}
+
 #[derive(Debug)]
 pub struct GenDisk {
     bdev: Weak<dyn BlockDevice>,
@@ -35,8 +88,12 @@ pub struct GenDisk {
     parent: RwLock<Weak<LockedDevFSInode>>,
     fs: RwLock<Weak<DevFS>>,
     metadata: Metadata,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
     /// 对应/dev/下的设备名
     name: DName,
+    /// 被打开的次数（包括[`GenDisk::claim`]），分区被打开时不能重新扫描分区表
+    open_count: AtomicUsize,
 }
 
 impl GenDisk {
@@ -74,9 +131,11 @@ impl GenDisk {
             fs: RwLock::new(Weak::default()),
             metadata: Metadata::new(
                 crate::filesystem::vfs::FileType::BlockDevice,
-                InodeMode::from_bits_truncate(0o755),
+                InodeMode::from_bits_truncate(0o660),
             ),
+            attr: DevNodeAttr::new(InodeMode::from_bits_truncate(0o660)),
             name: dev_name,
+            open_count: AtomicUsize::new(0),
         });
     }
 
@@ -104,7 +163,7 @@ impl GenDisk {
         let blocks = buf.len() / (1 << self.block_size_log2 as usize);
         let lba = self.block_offset_2_disk_blkid(start_block_offset);
 
-        return self.block_device().read_at(lba, blocks, buf);
+        return block_cache().read(&self.block_device(), lba, blocks, buf);
     }
 
     /// # read_at_bytes
@@ -118,9 +177,7 @@ impl GenDisk {
     pub fn read_at_bytes(&self, buf: &mut [u8], bytes_offset: usize) -> Result<usize, SystemError> {
         let start_lba = self.range.lba_start;
         let bytes_offset = self.disk_blkid_2_bytes(start_lba) + bytes_offset;
-        return self
-            .block_device()
-            .read_at_bytes(bytes_offset, buf.len(), buf);
+        return block_cache().read_bytes(&self.block_device(), bytes_offset, buf);
     }
 
     /// # 分区内的字节偏移量转换为磁盘上的字节偏移量
@@ -140,9 +197,87 @@ impl GenDisk {
     pub fn write_at_bytes(&self, buf: &[u8], bytes_offset: usize) -> Result<usize, SystemError> {
         let start_lba = self.range.lba_start;
         let bytes_offset = self.disk_blkid_2_bytes(start_lba) + bytes_offset;
-        return self
-            .block_device()
-            .write_at_bytes(bytes_offset, buf.len(), buf);
+        return block_cache().write_bytes(&self.block_device(), bytes_offset, buf);
+    }
+
+    /// # read_direct_bytes
+    ///
+    /// 绕过块缓存，按字节偏移量从分区中读取数据，用于O_DIRECT
+    ///
+    /// ## 参数
+    ///
+    /// - buf: 输出缓冲区，长度必须为LBA_SIZE的整数倍
+    /// - bytes_offset: 分区内的字节偏移量，必须按LBA_SIZE对齐
+    pub fn read_direct_bytes(
+        &self,
+        buf: &mut [u8],
+        bytes_offset: usize,
+    ) -> Result<usize, SystemError> {
+        if !bytes_offset.is_multiple_of(LBA_SIZE) || !buf.len().is_multiple_of(LBA_SIZE) {
+            return Err(SystemError::EINVAL);
+        }
+        let lba = self.disk_bytes_offset(bytes_offset) / LBA_SIZE;
+        return block_cache().read_direct(&self.block_device(), lba, buf.len() / LBA_SIZE, buf);
+    }
+
+    /// # write_direct_bytes
+    ///
+    /// 绕过块缓存，按字节偏移量向分区写入数据，用于O_DIRECT
+    ///
+    /// ## 参数
+    ///
+    /// - buf: 输入缓冲区，长度必须为LBA_SIZE的整数倍
+    /// - bytes_offset: 分区内的字节偏移量，必须按LBA_SIZE对齐
+    pub fn write_direct_bytes(
+        &self,
+        buf: &[u8],
+        bytes_offset: usize,
+    ) -> Result<usize, SystemError> {
+        if !bytes_offset.is_multiple_of(LBA_SIZE) || !buf.len().is_multiple_of(LBA_SIZE) {
+            return Err(SystemError::EINVAL);
+        }
+        let lba = self.disk_bytes_offset(bytes_offset) / LBA_SIZE;
+        return block_cache().write_direct(&self.block_device(), lba, buf.len() / LBA_SIZE, buf);
+    }
+
+    /// # read_direct_user_bytes
+    ///
+    /// 绕过块缓存，按字节偏移量从分区中读取数据到固定住的用户缓冲区，用于O_DIRECT
+    ///
+    /// ## 参数
+    ///
+    /// - pages: 用户缓冲区，长度必须为LBA_SIZE的整数倍
+    /// - bytes_offset: 分区内的字节偏移量，必须按LBA_SIZE对齐
+    pub fn read_direct_user_bytes(
+        &self,
+        pages: &PinnedUserPages,
+        bytes_offset: usize,
+    ) -> Result<usize, SystemError> {
+        if !bytes_offset.is_multiple_of(LBA_SIZE) || !pages.len().is_multiple_of(LBA_SIZE) {
+            return Err(SystemError::EINVAL);
+        }
+        let lba = self.disk_bytes_offset(bytes_offset) / LBA_SIZE;
+        return block_cache().read_direct_user(&self.block_device(), lba, pages);
+    }
+
+    /// # write_direct_user_bytes
+    ///
+    /// 绕过块缓存，按字节偏移量把固定住的用户缓冲区写入分区，用于O_DIRECT
+    ///
+    /// ## 参数
+    ///
+    /// - pages: 用户缓冲区，长度必须为LBA_SIZE的整数倍
+    /// - bytes_offset: 分区内的字节偏移量，必须按LBA_SIZE对齐
+    pub fn write_direct_user_bytes(
+        &self,
+        pages: &PinnedUserPages,
+        bytes_offset: usize,
+    ) -> Result<usize, SystemError> {
+        if !bytes_offset.is_multiple_of(LBA_SIZE) || !pages.len().is_multiple_of(LBA_SIZE) {
+            return Err(SystemError::EINVAL);
+        }
+        let lba = self.disk_bytes_offset(bytes_offset) / LBA_SIZE;
+        return block_cache().write_direct_user(&self.block_device(), lba, pages);
     }
 
     /// # write_at
@@ -160,7 +295,47 @@ impl GenDisk {
 
         let blocks = buf.len() / (1 << self.block_size_log2 as usize);
         let lba = self.block_offset_2_disk_blkid(start_block_offset);
-        return self.block_device().write_at(lba, blocks, buf);
+        return block_cache().write(&self.block_device(), lba, blocks, buf);
+    }
+
+    /// # discard
+    ///
+    /// 通知设备分区内的一段块不再使用
+    ///
+    /// ## 参数
+    ///
+    /// - start_block_offset: 分区内的起始块号
+    /// - count: 块的数量
+    ///
+    /// ## 返回值
+    ///
+    /// - `Err(EINVAL)`: 范围超出了分区
+    /// - `Err(EROFS)`: 设备只读
+    /// - `Err(EOPNOTSUPP_OR_ENOTSUP)`: 设备不支持discard
+    pub fn discard(&self, start_block_offset: BlockId, count: usize) -> Result<(), SystemError> {
+        if count == 0 {
+            return Ok(());
+        }
+        let end = start_block_offset
+            .checked_add(count)
+            .ok_or(SystemError::EINVAL)?;
+        if end > self.nr_sectors() {
+            return Err(SystemError::EINVAL);
+        }
+
+        let bdev = self.block_device();
+        if bdev.blkdev_meta().is_read_only() {
+            return Err(SystemError::EROFS);
+        }
+        let lba_start = self.block_offset_2_disk_blkid(start_block_offset);
+        let range = GeneralBlockRange::new(lba_start, lba_start + count).unwrap();
+        // 被丢弃的扇区不再需要写回
+        block_cache().invalidate_range(&bdev, lba_start, count);
+        let stats = bdev.blkdev_meta().stats().clone();
+        let start = stats.io_start(StatGroup::Discard);
+        let r = bdev.discard(range);
+        stats.io_done(StatGroup::Discard, count, false, start);
+        r
     }
 
     #[inline]
@@ -183,6 +358,15 @@ impl GenDisk {
         &self.range
     }
 
+    /// 以扇区为单位的大小。整盘的大小可能会变化（例如loop设备），因此从块设备实时获取
+    pub fn nr_sectors(&self) -> usize {
+        if self.idx.is_none() {
+            self.block_device().disk_range().len()
+        } else {
+            self.range.len()
+        }
+    }
+
     #[inline]
     pub fn device_num(&self) -> DeviceNumber {
         self.device_num
@@ -194,9 +378,21 @@ impl GenDisk {
     }
 
     /// # sync
-    /// 同步磁盘
+    /// 同步磁盘：写回块缓存中的脏扇区，再同步设备
     pub fn sync(&self) -> Result<(), SystemError> {
-        self.block_device().sync()
+        let bdev = self.block_device();
+        block_cache().sync_disk(&bdev)?;
+        bdev.sync()
+    }
+
+    /// # flush
+    ///
+    /// 写屏障：返回时，之前已经完成的写入都已经持久化。
+    /// 文件系统在写入依赖这些数据的元数据（例如“干净卸载”标志）之前调用
+    pub fn flush(&self) -> Result<(), SystemError> {
+        let bdev = self.block_device();
+        block_cache().sync_disk(&bdev)?;
+        bdev.flush()
     }
 
     pub fn symlink_name(&self) -> String {
@@ -208,6 +404,71 @@ impl GenDisk {
     pub fn block_size_log2(&self) -> u8 {
         self.block_size_log2
     }
+
+    /// # claim
+    ///
+    /// 在返回的[`GenDiskClaim`]存在期间，块设备被视为处于打开状态。
+    /// 用于挂载在该设备上的文件系统等不经过设备节点访问磁盘的使用者
+    pub fn claim(self: &Arc<Self>) -> GenDiskClaim {
+        self.on_open();
+        GenDiskClaim { disk: self.clone() }
+    }
+
+    /// 修改设备状态的ioctl需要CAP_SYS_ADMIN
+    fn require_sys_admin() -> Result<(), SystemError> {
+        if !ProcessManager::current_pcb()
+            .cred()
+            .has_capability(CAPFlags::CAP_SYS_ADMIN)
+        {
+            return Err(SystemError::EACCES);
+        }
+        Ok(())
+    }
+
+    /// 块设备被打开时调用
+    fn on_open(&self) {
+        self.open_count.fetch_add(1, Ordering::SeqCst);
+        if let Some(bdev) = self.bdev.upgrade() {
+            let any = BlockDevice::as_any_ref(&*bdev);
+            if let Some(loop_dev) = any.downcast_ref::<LoopDevice>() {
+                loop_dev.on_open();
+            } else if let Some(zram) = any.downcast_ref::<ZramDevice>() {
+                zram.on_open();
+            }
+        }
+    }
+
+    /// 块设备被关闭时调用，与[`GenDisk::on_open`]成对出现
+    fn on_release(&self) {
+        self.open_count.fetch_sub(1, Ordering::SeqCst);
+        // 设备可能已经被删除
+        if let Some(bdev) = self.bdev.upgrade() {
+            let any = BlockDevice::as_any_ref(&*bdev);
+            if let Some(loop_dev) = any.downcast_ref::<LoopDevice>() {
+                loop_dev.on_release();
+            } else if let Some(zram) = any.downcast_ref::<ZramDevice>() {
+                zram.on_release();
+            }
+        }
+    }
+}
+
+/// 对gendisk的一次打开引用，drop时释放，参见[`GenDisk::claim`]
+#[derive(Debug)]
+pub struct GenDiskClaim {
+    disk: Arc<GenDisk>,
+}
+
+impl GenDiskClaim {
+    pub fn disk(&self) -> &Arc<GenDisk> {
+        &self.disk
+    }
+}
+
+impl Drop for GenDiskClaim {
+    fn drop(&mut self) {
+        self.disk.on_release();
+    }
 }
 
 impl IndexNode for GenDisk {
@@ -251,23 +512,84 @@ impl IndexNode for GenDisk {
         self.write_at_bytes(&buf[..len], offset)
     }
 
+    fn read_direct(&self, offset: usize, len: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        if len > buf.len() {
+            return Err(SystemError::ENOBUFS);
+        }
+        // 与普通的读一样，读到分区末尾为止
+        let size = self.nr_sectors() * LBA_SIZE;
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = core::cmp::min(len, size - offset);
+        self.read_direct_bytes(&mut buf[..len], offset)
+    }
+
+    fn write_direct(&self, offset: usize, len: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        if len > buf.len() {
+            return Err(SystemError::E2BIG);
+        }
+        let size = self.nr_sectors() * LBA_SIZE;
+        if len > 0 && offset >= size {
+            return Err(SystemError::ENOSPC);
+        }
+        let len = core::cmp::min(len, size - offset.min(size));
+        self.write_direct_bytes(&buf[..len], offset)
+    }
+
+    fn read_direct_user(
+        &self,
+        offset: usize,
+        pages: &PinnedUserPages,
+    ) -> Result<usize, SystemError> {
+        let size = self.nr_sectors() * LBA_SIZE;
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = core::cmp::min(pages.len(), size - offset);
+        self.read_direct_user_bytes(&pages.slice(0, len), offset)
+    }
+
+    fn write_direct_user(
+        &self,
+        offset: usize,
+        pages: &PinnedUserPages,
+    ) -> Result<usize, SystemError> {
+        let size = self.nr_sectors() * LBA_SIZE;
+        if !pages.is_empty() && offset >= size {
+            return Err(SystemError::ENOSPC);
+        }
+        let len = core::cmp::min(pages.len(), size - offset.min(size));
+        self.write_direct_user_bytes(&pages.slice(0, len), offset)
+    }
+
+    fn direct_io_alignment(&self) -> Option<usize> {
+        Some(LBA_SIZE)
+    }
+
     fn list(&self) -> Result<alloc::vec::Vec<alloc::string::String>, system_error::SystemError> {
         Err(SystemError::ENOSYS)
     }
 
     fn metadata(&self) -> Result<crate::filesystem::vfs::Metadata, SystemError> {
         let mut meta = self.metadata.clone();
-        let bdev = self.block_device();
-        let range = bdev.disk_range();
-        let blocks = range.lba_end.saturating_sub(range.lba_start);
+        // 分区只报告自己的大小，而不是整个磁盘的大小
+        let blocks = self.nr_sectors();
         let size_in_bytes = blocks.saturating_mul(LBA_SIZE);
 
         meta.size = i64::try_from(size_in_bytes).unwrap_or(i64::MAX);
         meta.blocks = blocks;
         meta.blk_size = LBA_SIZE;
+        meta.raw_dev = self.device_num;
+        self.attr.apply(&mut meta);
         Ok(meta)
     }
 
+    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
+        self.attr.update(metadata);
+        Ok(())
+    }
+
     fn dname(&self) -> Result<DName, SystemError> {
         Ok(self.name.clone())
     }
@@ -284,6 +606,7 @@ impl IndexNode for GenDisk {
         &self,
         _data: MutexGuard<crate::filesystem::vfs::FilePrivateData>,
     ) -> Result<(), SystemError> {
+        self.on_release();
         Ok(())
     }
 
@@ -292,6 +615,7 @@ impl IndexNode for GenDisk {
         _data: MutexGuard<crate::filesystem::vfs::FilePrivateData>,
         _mode: &crate::filesystem::vfs::file::FileFlags,
     ) -> Result<(), SystemError> {
+        self.on_open();
         Ok(())
     }
 
@@ -301,12 +625,118 @@ impl IndexNode for GenDisk {
         data: usize,
         private_data: MutexGuard<crate::filesystem::vfs::FilePrivateData>,
     ) -> Result<usize, SystemError> {
+        let arg = IoctlArg::new(cmd, data);
+        match cmd {
+            BlockIoctlCmd::BLKROGET => {
+                let read_only = self.block_device().blkdev_meta().is_read_only();
+                arg.write(&(read_only as i32))?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKROSET => {
+                Self::require_sys_admin()?;
+                let read_only = arg.read::<i32>()? != 0;
+                // 只读状态目前以整个磁盘为单位
+                if self.idx.is_some() {
+                    return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+                }
+                self.block_device().blkdev_meta().set_read_only(read_only);
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKRRPART => {
+                Self::require_sys_admin()?;
+                if self.idx.is_some() {
+                    return Err(SystemError::EINVAL);
+                }
+                let bdev = self.block_device();
+                // 分区正在被使用（被打开或者挂载）时不能删除
+                let busy =
+                    bdev.blkdev_meta().inner().gendisks.values().any(|disk| {
+                        disk.idx.is_some() && disk.open_count.load(Ordering::SeqCst) > 0
+                    });
+                if busy {
+                    return Err(SystemError::EBUSY);
+                }
+                self.sync()?;
+                block_dev_manager().rescan_partitions(&bdev)?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKGETSIZE => {
+                arg.write(&(self.nr_sectors() as core::ffi::c_ulong))?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKFLSBUF => {
+                Self::require_sys_admin()?;
+                self.sync()?;
+                block_cache().drop_clean_range(
+                    &self.block_device(),
+                    self.block_offset_2_disk_blkid(0),
+                    self.nr_sectors(),
+                );
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKRASET => {
+                Self::require_sys_admin()?;
+                // 预读以整个磁盘为单位
+                self.block_device()
+                    .blkdev_meta()
+                    .readahead()
+                    .set_max_sectors(data);
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKRAGET => {
+                let sectors = self.block_device().blkdev_meta().readahead().max_sectors();
+                arg.write(&(sectors as core::ffi::c_long))?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKSSZGET => {
+                arg.write(&(LBA_SIZE as i32))?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKBSZGET => {
+                // 参数实际为`int *`，不按命令号中编码的大小校验
+                IoctlArg::new(0, data).write(&((1 << self.block_size_log2) as i32))?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKGETSIZE64 => {
+                arg.write(&((self.nr_sectors() * LBA_SIZE) as u64))?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKIOMIN | BlockIoctlCmd::BLKPBSZGET => {
+                arg.write(&((1 << self.block_size_log2) as u32))?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKIOOPT | BlockIoctlCmd::BLKDISCARDZEROES => {
+                arg.write(&0u32)?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKALIGNOFF => {
+                arg.write(&0i32)?;
+                return Ok(0);
+            }
+            BlockIoctlCmd::BLKGETDISKSEQ => {
+                arg.write(&self.block_device().blkdev_meta().diskseq())?;
+                return Ok(0);
+            }
+            _ => {}
+        }
+
         let bdev = self.block_device();
         if let Some(loop_dev) = BlockDevice::as_any_ref(&*bdev).downcast_ref::<LoopDevice>() {
-            loop_dev.ioctl(cmd, data, private_data)
-        } else {
-            Err(SystemError::ENOSYS)
+            return loop_dev.ioctl(cmd, data, private_data);
+        }
+        if let Some(md) = BlockDevice::as_any_ref(&*bdev).downcast_ref::<MdDevice>() {
+            return md.ioctl(cmd, data);
         }
+        if let Some(nbd) = BlockDevice::as_any_ref(&*bdev).downcast_ref::<NbdDevice>() {
+            return nbd.ioctl(cmd, data);
+        }
+        #[cfg(target_arch = "x86_64")]
+        if let Some(cdrom) = BlockDevice::as_any_ref(&*bdev)
+            .downcast_ref::<crate::driver::disk::ahci::atapi::LockedAhciCdrom>()
+        {
+            return cdrom.ioctl(cmd, data);
+        }
+        Err(SystemError::ENOSYS)
     }
 }
 
@@ -323,30 +753,18 @@ impl DeviceINode for GenDisk {
 #[derive(Default)]
 pub struct GenDiskMap {
     data: HashMap<u32, Arc<GenDisk>>,
-    max_idx: AtomicU32,
 }
 
 impl GenDiskMap {
     pub fn new() -> Self {
         GenDiskMap {
             data: HashMap::new(),
-            max_idx: AtomicU32::new(1),
         }
     }
 
-    #[inline]
-    #[allow(dead_code)]
-    pub fn max_idx(&self) -> u32 {
-        self.max_idx.load(Ordering::SeqCst)
-    }
-
-    #[inline]
-    pub fn alloc_idx(&self) -> u32 {
-        self.max_idx.fetch_add(1, Ordering::SeqCst)
-    }
-
+    /// 是否与已有分区的范围重叠（不考虑整个磁盘的gendisk）
     pub fn intersects(&self, range: &GeneralBlockRange) -> bool {
-        for (_, v) in self.iter() {
+        for (_, v) in self.iter().filter(|(_, v)| v.idx.is_some()) {
             if range.intersects_with(&v.range).is_some() {
                 return true;
             }
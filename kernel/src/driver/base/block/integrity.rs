@@ -0,0 +1,131 @@
+//! 块设备的数据完整性校验
+//!
+//! 为每个LBA保存一个CRC32C校验值（tag）。写入时生成tag，读取时重新计算并与保存的tag比较，
+//! 不一致时返回`EILSEQ`（对应Linux的`BLK_STS_PROTECTION`），以便与普通的I/O错误（`EIO`）区分。
+//!
+//! tag保存在哪里由驱动决定，驱动通过实现[`BlkIntegrityStore`]提供tag的存取，
+//! 并在[`BlockDevice::integrity`](super::block_device::BlockDevice::integrity)中返回[`BlkIntegrity`]。
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::{
+    fmt::Debug,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use log::warn;
+use system_error::SystemError;
+
+use crate::{driver::base::device::DevName, libs::crc::crc32c, libs::rwsem::RwSem};
+
+use super::block_device::{BlockId, LBA_SIZE};
+
+/// tag的存储后端
+pub trait BlkIntegrityStore: Send + Sync + Debug {
+    /// ## 读取从`lba_start`开始的`tags.len()`个LBA的tag
+    fn load_tags(&self, lba_start: BlockId, tags: &mut [u32]) -> Result<(), SystemError>;
+
+    /// ## 保存从`lba_start`开始的`tags.len()`个LBA的tag
+    fn store_tags(&self, lba_start: BlockId, tags: &[u32]) -> Result<(), SystemError>;
+}
+
+/// 块设备的完整性校验状态
+#[derive(Debug)]
+pub struct BlkIntegrity {
+    store: Arc<dyn BlkIntegrityStore>,
+    /// 数据与tag的写入需要作为一个整体，避免并发的读看到新数据和旧tag而误报
+    io_lock: RwSem<()>,
+    /// 校验通过的LBA数
+    verified: AtomicU64,
+    /// 校验失败的次数
+    mismatches: AtomicU64,
+}
+
+impl BlkIntegrity {
+    pub fn new(store: Arc<dyn BlkIntegrityStore>) -> Self {
+        Self {
+            store,
+            io_lock: RwSem::new(()),
+            verified: AtomicU64::new(0),
+            mismatches: AtomicU64::new(0),
+        }
+    }
+
+    /// ## 计算一个LBA的tag
+    pub fn generate_tag(block: &[u8]) -> u32 {
+        !crc32c(!0, block)
+    }
+
+    /// ## 计算`buf`中每个LBA的tag
+    pub fn generate_tags(buf: &[u8]) -> Vec<u32> {
+        buf.chunks(LBA_SIZE).map(Self::generate_tag).collect()
+    }
+
+    /// ## 读取数据并校验
+    ///
+    /// `read`负责从设备读取`count`个LBA的数据到`buf`，读取成功后逐个LBA校验。
+    ///
+    /// ## 返回值
+    /// - `Ok(usize)`: `read`的返回值
+    /// - `Err(SystemError::EILSEQ)`: 有LBA的校验和不匹配
+    pub fn read_verify(
+        &self,
+        dev_name: &DevName,
+        lba_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+        read: impl FnOnce(&mut [u8]) -> Result<usize, SystemError>,
+    ) -> Result<usize, SystemError> {
+        let _guard = self.io_lock.read();
+        let len = read(buf)?;
+
+        let count = count.min(len / LBA_SIZE);
+        let mut tags = vec![0u32; count];
+        self.store.load_tags(lba_start, &mut tags)?;
+
+        for (i, (block, tag)) in buf[..count * LBA_SIZE]
+            .chunks_exact(LBA_SIZE)
+            .zip(tags.iter())
+            .enumerate()
+        {
+            if Self::generate_tag(block) != *tag {
+                self.mismatches.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "{}: integrity check failed at sector {}",
+                    dev_name,
+                    lba_start + i
+                );
+                return Err(SystemError::EILSEQ);
+            }
+        }
+        self.verified.fetch_add(count as u64, Ordering::Relaxed);
+        Ok(len)
+    }
+
+    /// ## 写入数据并更新tag
+    ///
+    /// `write`负责把`buf`写入设备，写入成功后保存新数据的tag。
+    pub fn write_generate(
+        &self,
+        lba_start: BlockId,
+        count: usize,
+        buf: &[u8],
+        write: impl FnOnce(&[u8]) -> Result<usize, SystemError>,
+    ) -> Result<usize, SystemError> {
+        let len = (count * LBA_SIZE).min(buf.len());
+        let tags = Self::generate_tags(&buf[..len - len % LBA_SIZE]);
+
+        let _guard = self.io_lock.write();
+        let written = write(buf)?;
+        self.store.store_tags(lba_start, &tags)?;
+        Ok(written)
+    }
+
+    /// 校验通过的LBA数
+    pub fn verified(&self) -> u64 {
+        self.verified.load(Ordering::Relaxed)
+    }
+
+    /// 校验失败的次数
+    pub fn mismatches(&self) -> u64 {
+        self.mismatches.load(Ordering::Relaxed)
+    }
+}
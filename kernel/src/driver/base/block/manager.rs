@@ -1,6 +1,9 @@
-use core::{fmt::Formatter, sync::atomic::AtomicU32};
+use core::{
+    fmt::Formatter,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+};
 
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{string::String, sync::Arc, vec::Vec};
 use hashbrown::HashMap;
 use system_error::SystemError;
 use unified_init::macros::unified_init;
@@ -8,11 +11,15 @@ use unified_init::macros::unified_init;
 use crate::{
     driver::base::{
         block::gendisk::GenDisk,
-        device::{device_number::Major, DevName},
+        device::{
+            device_number::{DeviceNumber, Major},
+            DevName,
+        },
+        kobject::{KObject, KObjectState},
+        uevent::{kobject_get_path, kobject_uevent_env, uevent_send, KObjectAction},
     },
     filesystem::{
-        devfs::{devfs_register, devfs_unregister},
-        mbr::MbrDiskPartionTable,
+        devfs::{devfs_register, devfs_register_with_mode, devfs_unregister},
         vfs::{utils::DName, IndexNode},
     },
     init::initcall::INITCALL_POSTCORE,
@@ -21,11 +28,20 @@ use crate::{
 
 use super::{
     block_device::{BlockDevice, GeneralBlockRange},
+    cache::block_cache,
+    disk_info::Partition,
     gendisk::GenDiskMap,
+    partitions::scan_partitions,
+    readahead::BlockReadahead,
+    stats::BlockDevStats,
+    sysfs::{block_sysfs_add, block_sysfs_init, block_sysfs_remove},
 };
 
 static mut BLOCK_DEV_MANAGER: Option<BlockDevManager> = None;
 
+/// 全局的磁盘序列号分配器
+static DISKSEQ: AtomicU64 = AtomicU64::new(0);
+
 #[inline]
 pub fn block_dev_manager() -> &'static BlockDevManager {
     unsafe { BLOCK_DEV_MANAGER.as_ref().unwrap() }
@@ -36,7 +52,7 @@ pub fn block_dev_manager_init() -> Result<(), SystemError> {
     unsafe {
         BLOCK_DEV_MANAGER = Some(BlockDevManager::new());
     }
-    Ok(())
+    block_sysfs_init()
 }
 
 /// 磁盘设备管理器
@@ -78,30 +94,91 @@ impl BlockDevManager {
             inner.disks.remove(dev_name);
         };
         res?;
+        drop(inner);
+
+        if let Err(e) = block_sysfs_add(&dev) {
+            log::warn!("{}: failed to add to /sys/block: {:?}", dev.dev_name(), e);
+        }
+
+        // 与Linux一致，先通知磁盘，再通知磁盘上的各个分区
+        self.disk_uevent(&dev, KObjectAction::Add, &[]);
+        self.partitions_uevent(&dev, KObjectAction::Add);
         Ok(())
     }
 
-    /// 检测分区表，并创建gendisk
+    /// 为整个磁盘创建gendisk，然后检测分区表，为每个分区创建gendisk
+    ///
+    /// 分区表读取或解析失败不影响磁盘本身的注册
     fn check_partitions(&self, dev: &Arc<dyn BlockDevice>) -> Result<(), SystemError> {
-        if self.try_register_disk_by_mbr(dev).is_ok() {
-            return Ok(());
+        self.register_entire_disk_as_gendisk(dev)?;
+        if let Err(e) = self.add_partitions(dev) {
+            log::warn!("{}: failed to scan partitions: {:?}", dev.dev_name(), e);
         }
+        Ok(())
+    }
 
-        // use entire disk as a gendisk
-        self.register_entire_disk_as_gendisk(dev)
+    /// 扫描分区表，并为每个分区创建gendisk
+    fn add_partitions(&self, dev: &Arc<dyn BlockDevice>) -> Result<(), SystemError> {
+        for p in scan_partitions(dev)? {
+            let partno = p.partno as u32;
+            let part = Partition::new(
+                p.start_sector,
+                p.lba_start,
+                p.sectors_num,
+                Arc::downgrade(dev),
+                p.partno,
+            );
+            let range: GeneralBlockRange = p.try_into()?;
+            if let Err(e) = self.register_gendisk_with_range(dev, range, partno) {
+                log::warn!(
+                    "{}: failed to register partition {}: {:?}",
+                    dev.dev_name(),
+                    partno,
+                    e
+                );
+                continue;
+            }
+            dev.blkdev_meta().inner().partitions.push(part);
+        }
+        Ok(())
     }
 
-    fn try_register_disk_by_mbr(&self, dev: &Arc<dyn BlockDevice>) -> Result<(), SystemError> {
-        let mbr = MbrDiskPartionTable::from_disk(dev.clone())?;
-        let piter = mbr.partitions_raw();
-        let mut idx;
-        for p in piter {
-            idx = dev.blkdev_meta().inner().gendisks.alloc_idx();
-            self.register_gendisk_with_range(dev, p.try_into()?, idx)?;
+    /// ## 删除磁盘上所有分区的gendisk和设备节点，保留整个磁盘的gendisk
+    pub fn drop_partitions(&self, dev: &Arc<dyn BlockDevice>) -> Result<(), SystemError> {
+        let meta = dev.blkdev_meta();
+        let parts: Vec<Arc<GenDisk>> = meta
+            .inner()
+            .gendisks
+            .values()
+            .filter(|gendisk| gendisk.idx() != GenDisk::ENTIRE_DISK_IDX)
+            .cloned()
+            .collect();
+
+        for gendisk in parts {
+            let dname = gendisk.dname()?;
+            devfs_unregister(dname.as_ref(), gendisk.clone())?;
+            meta.inner().gendisks.remove(&gendisk.idx());
+            self.gendisk_uevent(dev, &gendisk, KObjectAction::Remove, &[]);
         }
+        meta.inner().partitions.clear();
         Ok(())
     }
 
+    /// ## 重新扫描磁盘的分区表
+    ///
+    /// 先删除已有的分区，再按照磁盘当前的内容重新创建。
+    /// 用于BLKRRPART，以及loop设备更换后端文件等介质变化的场景。
+    pub fn rescan_partitions(&self, dev: &Arc<dyn BlockDevice>) -> Result<(), SystemError> {
+        if !self.inner().disks.contains_key(dev.dev_name()) {
+            return Err(SystemError::ENODEV);
+        }
+        self.drop_partitions(dev)?;
+        let res = self.add_partitions(dev);
+        // 即使分区表扫描失败，已经创建的分区也需要通知用户态
+        self.partitions_uevent(dev, KObjectAction::Add);
+        res
+    }
+
     /// 将整个磁盘注册为gendisk
     fn register_entire_disk_as_gendisk(
         &self,
@@ -122,11 +199,14 @@ impl BlockDevManager {
         // 这里先拿到硬盘的设备名，然后在根据idx来生成gendisk的名字
         // 如果是整个磁盘，则idx为 None，名字为/dev/sda
         // 如果是分区，例如idx为1，则名字为/dev/sda1
-        // 以此类推
+        // 与Linux一样，设备名以数字结尾时加上'p'，例如/dev/loop0p1
         let dev_name = dev.dev_name();
         let (idx, dev_name) = match idx {
             GenDisk::ENTIRE_DISK_IDX => (None, DName::from(dev_name.name())),
-            id => (Some(id), DName::from(format!("{}{}", dev_name.name(), idx))),
+            id if dev_name.name().ends_with(|c: char| c.is_ascii_digit()) => {
+                (Some(id), DName::from(format!("{}p{}", dev_name.name(), id)))
+            }
+            id => (Some(id), DName::from(format!("{}{}", dev_name.name(), id))),
         };
 
         let gendisk = GenDisk::new(weak_dev, range, idx, dev_name);
@@ -142,8 +222,10 @@ impl BlockDevManager {
         let blk_meta = dev.blkdev_meta();
         let idx = gendisk.idx();
         let mut meta_inner = blk_meta.inner();
-        // 检查是否重复
-        if meta_inner.gendisks.intersects(gendisk.range()) {
+        // 检查是否重复。整个磁盘的gendisk与分区的gendisk允许重叠
+        if meta_inner.gendisks.contains_key(&idx)
+            || (idx != GenDisk::ENTIRE_DISK_IDX && meta_inner.gendisks.intersects(gendisk.range()))
+        {
             return Err(SystemError::EEXIST);
         }
 
@@ -154,14 +236,16 @@ impl BlockDevManager {
 
         // 注册到devfs
         let dname = gendisk.dname()?;
-        devfs_register(dname.as_ref(), gendisk.clone()).map_err(|e| {
-            log::error!(
-                "Failed to register gendisk {:?} to devfs: {:?}",
-                dname.as_ref(),
+        devfs_register_with_mode(dname.as_ref(), gendisk.clone(), dev.devnode_mode()).map_err(
+            |e| {
+                log::error!(
+                    "Failed to register gendisk {:?} to devfs: {:?}",
+                    dname.as_ref(),
+                    e
+                );
                 e
-            );
-            e
-        })?;
+            },
+        )?;
         Ok(())
     }
 
@@ -222,8 +306,16 @@ impl BlockDevManager {
         }
         drop(inner);
 
+        // 先通知分区的移除，最后通知磁盘本身，此时gendisk还在，可以取到设备号
+        self.partitions_uevent(dev, KObjectAction::Remove);
+        self.disk_uevent(dev, KObjectAction::Remove, &[]);
+
         let mut meta_inner = blk_meta.inner();
         meta_inner.gendisks.clear();
+        meta_inner.partitions.clear();
+        drop(meta_inner);
+        block_cache().invalidate_disk(dev);
+        block_sysfs_remove(dev);
         Ok(())
     }
     /// 通过路径查找gendisk
@@ -257,6 +349,34 @@ impl BlockDevManager {
         None
     }
 
+    /// 通过设备号查找gendisk，可以是整个磁盘，也可以是分区
+    pub fn lookup_gendisk_by_devnum(&self, devnum: DeviceNumber) -> Option<Arc<GenDisk>> {
+        self.inner().disks.values().find_map(|dev| {
+            dev.blkdev_meta()
+                .inner()
+                .gendisks
+                .values()
+                .find(|disk| disk.device_num() == devnum)
+                .cloned()
+        })
+    }
+
+    /// 按设备名查找磁盘设备
+    pub fn lookup(&self, name: &str) -> Option<Arc<dyn BlockDevice>> {
+        self.inner()
+            .disks
+            .values()
+            .find(|dev| dev.dev_name().as_str() == name)
+            .cloned()
+    }
+
+    /// 获取所有已注册的磁盘设备，按设备名排序
+    pub fn disks(&self) -> Vec<Arc<dyn BlockDevice>> {
+        let mut disks: Vec<Arc<dyn BlockDevice>> = self.inner().disks.values().cloned().collect();
+        disks.sort_by(|a, b| a.dev_name().as_str().cmp(b.dev_name().as_str()));
+        disks
+    }
+
     /// 打印所有的gendisk的路径
     pub fn print_gendisks(&self) {
         let mut disks = alloc::vec::Vec::new();
@@ -264,11 +384,9 @@ impl BlockDevManager {
         let inner = self.inner();
         for dev in inner.disks.values() {
             let meta = dev.blkdev_meta().inner();
-            for idx in meta.gendisks.keys() {
-                if idx == &GenDisk::ENTIRE_DISK_IDX {
-                    disks.push(format!("/dev/{}", dev.dev_name()));
-                } else {
-                    disks.push(format!("/dev/{}{}", dev.dev_name(), idx));
+            for gendisk in meta.gendisks.values() {
+                if let Ok(dname) = gendisk.dname() {
+                    disks.push(format!("/dev/{}", dname.as_ref()));
                 }
             }
         }
@@ -299,11 +417,136 @@ impl BlockDevManager {
             partno = path[last_digit..].parse().ok()?;
         }
 
-        let path = &path[..last_digit];
+        let mut path = &path[..last_digit];
+        // 设备名以数字结尾时，分区名中有'p'分隔，例如loop0p1
+        if let Some(disk) = path.strip_suffix('p') {
+            if disk.ends_with(|c: char| c.is_ascii_digit()) {
+                path = disk;
+            }
+        }
 
         Some((path, partno))
     }
 
+    /// ## 通知用户态磁盘的容量发生了变化
+    ///
+    /// 发送带有`RESIZE=1`的change uevent。与Linux一致，容量从0变化或变化为0时不通知，
+    /// 这种情况应当由介质变化事件覆盖。
+    ///
+    /// ### 参数
+    ///
+    /// - `dev`: 磁盘设备
+    /// - `old_sectors`: 变化前的容量（扇区数）
+    pub fn notify_capacity_change(&self, dev: &Arc<dyn BlockDevice>, old_sectors: usize) {
+        let new_sectors = dev.disk_range().len();
+        if old_sectors == new_sectors || old_sectors == 0 || new_sectors == 0 {
+            return;
+        }
+        log::info!(
+            "{}: detected capacity change from {} to {}",
+            dev.dev_name(),
+            old_sectors,
+            new_sectors
+        );
+        self.disk_uevent(dev, KObjectAction::Change, &["RESIZE=1"]);
+    }
+
+    /// ## 通知用户态磁盘的介质发生了变化
+    ///
+    /// 例如loop设备绑定、解绑或者更换了后端文件。这会使磁盘的diskseq递增，
+    /// 并发送带有`DISK_MEDIA_CHANGE=1`的change uevent。
+    pub fn notify_media_change(&self, dev: &Arc<dyn BlockDevice>) {
+        // 旧介质的缓存不再有效
+        block_cache().invalidate_disk(dev);
+        dev.blkdev_meta().readahead().reset();
+        dev.blkdev_meta().inc_diskseq();
+        self.disk_uevent(dev, KObjectAction::Change, &["DISK_MEDIA_CHANGE=1"]);
+    }
+
+    /// 发送整个磁盘的uevent
+    fn disk_uevent(&self, dev: &Arc<dyn BlockDevice>, action: KObjectAction, envp: &[&str]) {
+        let disk = dev
+            .blkdev_meta()
+            .inner()
+            .gendisks
+            .get(&GenDisk::ENTIRE_DISK_IDX)
+            .cloned();
+        match disk {
+            Some(disk) => self.gendisk_uevent(dev, &disk, action, envp),
+            None => log::warn!("{}: no gendisk for uevent {:?}", dev.dev_name(), action),
+        }
+    }
+
+    /// 为磁盘上的每个分区发送uevent，按分区号的顺序
+    fn partitions_uevent(&self, dev: &Arc<dyn BlockDevice>, action: KObjectAction) {
+        let mut parts: Vec<Arc<GenDisk>> = dev
+            .blkdev_meta()
+            .inner()
+            .gendisks
+            .values()
+            .filter(|gendisk| gendisk.idx() != GenDisk::ENTIRE_DISK_IDX)
+            .cloned()
+            .collect();
+        parts.sort_by_key(|gendisk| gendisk.idx());
+        for part in parts {
+            self.gendisk_uevent(dev, &part, action, &[]);
+        }
+    }
+
+    /// ## 发送gendisk的uevent
+    ///
+    /// 携带`DEVNAME`、`MAJOR`、`MINOR`，用户态（如mdev）据此创建或删除设备节点。
+    /// 分区没有自己的kobject，它的devpath位于所属磁盘的目录下。
+    ///
+    /// ### 参数
+    ///
+    /// - `dev`: gendisk所属的磁盘设备
+    /// - `gendisk`: 整个磁盘或者某个分区
+    /// - `action`: 动作类型
+    /// - `envp`: 额外的环境变量
+    fn gendisk_uevent(
+        &self,
+        dev: &Arc<dyn BlockDevice>,
+        gendisk: &Arc<GenDisk>,
+        action: KObjectAction,
+        envp: &[&str],
+    ) {
+        let Ok(dname) = gendisk.dname() else {
+            return;
+        };
+        let devnum = gendisk.device_num();
+        let is_disk = gendisk.idx() == GenDisk::ENTIRE_DISK_IDX;
+
+        let mut env = alloc::vec![
+            String::from("SUBSYSTEM=block"),
+            format!("DEVNAME={}", dname.as_ref()),
+            format!("MAJOR={}", devnum.major().data()),
+            format!("MINOR={}", devnum.minor()),
+        ];
+        if is_disk {
+            env.push(String::from("DEVTYPE=disk"));
+            env.push(format!("DISKSEQ={}", dev.blkdev_meta().diskseq()));
+        } else {
+            env.push(String::from("DEVTYPE=partition"));
+            env.push(format!("PARTN={}", gendisk.idx()));
+        }
+        env.extend(envp.iter().map(|e| String::from(*e)));
+        let env: Vec<&str> = env.iter().map(|e| e.as_str()).collect();
+
+        let kobj = dev.device() as Arc<dyn KObject>;
+        let res = if is_disk {
+            kobject_uevent_env(&kobj, action, &env)
+        } else if kobj.kobj_state().contains(KObjectState::UEVENT_SUPPRESS) {
+            Ok(())
+        } else {
+            let devpath = format!("{}/{}", kobject_get_path(&kobj), dname.as_ref());
+            uevent_send(action, &devpath, &env)
+        };
+        if let Err(e) = res {
+            log::warn!("{}: failed to send uevent: {:?}", dname.as_ref(), e);
+        }
+    }
+
     /// 获取对应major下一个可用的minor号
     pub(self) fn next_minor(&self, major: Major) -> u32 {
         let mut inner = self.inner();
@@ -311,8 +554,8 @@ impl BlockDevManager {
             .minors
             .entry(major)
             .or_insert_with(|| AtomicU32::new(0));
-        let base_minor = base.load(core::sync::atomic::Ordering::SeqCst);
-        base.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+        let base_minor = base.load(Ordering::SeqCst);
+        base.fetch_add(1, Ordering::SeqCst);
         base_minor
     }
 }
@@ -321,11 +564,21 @@ pub struct BlockDevMeta {
     pub devname: DevName,
     pub major: Major,
     pub base_minor: u32,
+    /// 磁盘序列号，每次介质变化都会分配一个新的全局唯一值，参见[`BlockDevMeta::diskseq`]
+    diskseq: AtomicU64,
+    /// 设备是否只读，参见[`BlockDevMeta::is_read_only`]
+    read_only: AtomicBool,
+    /// I/O统计
+    stats: Arc<BlockDevStats>,
+    /// 顺序读的预读状态
+    readahead: BlockReadahead,
     inner: Mutex<InnerBlockDevMeta>,
 }
 
 pub struct InnerBlockDevMeta {
     pub gendisks: GenDiskMap,
+    /// 从分区表中扫描出的分区
+    pub partitions: Vec<Arc<Partition>>,
     pub dev_idx: usize,
 }
 
@@ -335,8 +588,13 @@ impl BlockDevMeta {
             devname,
             major,
             base_minor: block_dev_manager().next_minor(major),
+            diskseq: AtomicU64::new(DISKSEQ.fetch_add(1, Ordering::SeqCst) + 1),
+            read_only: AtomicBool::new(false),
+            stats: BlockDevStats::new(),
+            readahead: BlockReadahead::new(),
             inner: Mutex::new(InnerBlockDevMeta {
                 gendisks: GenDiskMap::new(),
+                partitions: Vec::new(),
                 dev_idx: 0, // 默认索引为0
             }),
         }
@@ -345,6 +603,46 @@ impl BlockDevMeta {
     pub(crate) fn inner(&self) -> MutexGuard<'_, InnerBlockDevMeta> {
         self.inner.lock()
     }
+
+    /// 获取磁盘序列号
+    ///
+    /// 用户态可以通过它（BLKGETDISKSEQ）区分同一个设备节点上先后出现的不同介质，
+    /// 例如loop设备先后绑定的不同文件。
+    pub fn diskseq(&self) -> u64 {
+        self.diskseq.load(Ordering::SeqCst)
+    }
+
+    /// 获取从分区表中扫描出的分区
+    pub fn partitions(&self) -> Vec<Arc<Partition>> {
+        self.inner().partitions.clone()
+    }
+
+    /// 设备是否只读
+    ///
+    /// 只读设备上的写请求返回`EROFS`，也不能以读写方式挂载
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    /// 设置设备的只读状态，由驱动在介质或者后端的属性变化时调用
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::SeqCst);
+    }
+
+    /// 获取I/O统计
+    pub fn stats(&self) -> &Arc<BlockDevStats> {
+        &self.stats
+    }
+
+    /// 获取预读状态
+    pub fn readahead(&self) -> &BlockReadahead {
+        &self.readahead
+    }
+
+    fn inc_diskseq(&self) {
+        self.diskseq
+            .store(DISKSEQ.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+    }
 }
 
 impl core::fmt::Debug for BlockDevMeta {
@@ -1,9 +1,16 @@
 pub mod bio;
-pub mod bio_queue;
 pub mod block_device;
+pub mod cache;
 pub mod disk_info;
+pub mod elevator;
 pub mod gendisk;
+pub mod integrity;
 pub mod manager;
+pub mod partitions;
+pub mod readahead;
+pub mod request_queue;
+pub mod stats;
+mod sysfs;
 
 #[derive(Debug)]
 #[allow(dead_code)]
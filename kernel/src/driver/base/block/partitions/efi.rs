@@ -0,0 +1,202 @@
+//! GUID分区表（GPT）
+//!
+//! 参考 Linux block/partitions/efi.c 和 UEFI规范第5章。
+//! 主GPT头位于1号扇区，备份GPT头位于磁盘的最后一个扇区，
+//! 头部和分区表项数组都带有CRC32校验。主GPT无效时使用备份GPT。
+
+use alloc::vec::Vec;
+use kdepends::crc::crc32::crc32_le;
+use system_error::SystemError;
+
+use crate::driver::base::block::{block_device::LBA_SIZE, disk_info::Partition};
+
+use super::{le16, le32, le64, DiskReader, DISK_MAX_PARTS};
+
+/// GPT头的签名 "EFI PART"
+const GPT_HEADER_SIGNATURE: u64 = 0x5452_4150_2049_4645;
+/// GPT头中被CRC覆盖的最小长度
+const GPT_HEADER_MIN_SIZE: u32 = 92;
+/// 主GPT头所在的扇区
+const GPT_PRIMARY_PARTITION_TABLE_LBA: u64 = 1;
+/// 分区表项的最小长度
+const GPT_ENTRY_MIN_SIZE: u32 = 128;
+/// 分区表项数组的最大长度，防止损坏的头部导致过大的内存分配
+const GPT_ENTRY_ARRAY_MAX_BYTES: usize = 1024 * 1024;
+
+/// 保护性MBR中的分区类型
+const EFI_PMBR_OSTYPE: u8 = 0xEE;
+
+/// GPT头中需要用到的字段
+#[derive(Debug, Clone, Copy)]
+struct GptHeader {
+    my_lba: u64,
+    alternate_lba: u64,
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    partition_entry_lba: u64,
+    num_partition_entries: u32,
+    sizeof_partition_entry: u32,
+    partition_entry_array_crc32: u32,
+}
+
+impl GptHeader {
+    /// 解析并校验`lba`处的GPT头，无效时返回`None`
+    fn read(reader: &DiskReader, lba: u64) -> Option<Self> {
+        let buf = reader.read_sector(lba).ok()?;
+        if le64(&buf, 0) != GPT_HEADER_SIGNATURE {
+            return None;
+        }
+
+        let header_size = le32(&buf, 12);
+        if header_size < GPT_HEADER_MIN_SIZE || header_size as usize > LBA_SIZE {
+            log::debug!("gpt: header size {} at lba {} invalid", header_size, lba);
+            return None;
+        }
+        let header_crc = le32(&buf, 16);
+        let mut crc_buf = buf[..header_size as usize].to_vec();
+        crc_buf[16..20].fill(0);
+        if efi_crc32(&crc_buf) != header_crc {
+            log::warn!("gpt: header CRC mismatch at lba {}", lba);
+            return None;
+        }
+
+        let header = Self {
+            my_lba: le64(&buf, 24),
+            alternate_lba: le64(&buf, 32),
+            first_usable_lba: le64(&buf, 40),
+            last_usable_lba: le64(&buf, 48),
+            partition_entry_lba: le64(&buf, 72),
+            num_partition_entries: le32(&buf, 80),
+            sizeof_partition_entry: le32(&buf, 84),
+            partition_entry_array_crc32: le32(&buf, 88),
+        };
+
+        if header.my_lba != lba {
+            log::debug!("gpt: my_lba {} != lba {}", header.my_lba, lba);
+            return None;
+        }
+        let last_lba = reader.nr_sectors() - 1;
+        if header.first_usable_lba > header.last_usable_lba || header.last_usable_lba > last_lba {
+            log::debug!("gpt: usable range of header at lba {} invalid", lba);
+            return None;
+        }
+        // 表项长度必须是128 * 2^n
+        let entry_size = header.sizeof_partition_entry;
+        if entry_size < GPT_ENTRY_MIN_SIZE || !(entry_size / GPT_ENTRY_MIN_SIZE).is_power_of_two() {
+            log::debug!("gpt: partition entry size {} invalid", entry_size);
+            return None;
+        }
+        if header
+            .entries_bytes()
+            .is_none_or(|n| n > GPT_ENTRY_ARRAY_MAX_BYTES)
+        {
+            log::debug!("gpt: partition entry array too large");
+            return None;
+        }
+        Some(header)
+    }
+
+    fn entries_bytes(&self) -> Option<usize> {
+        (self.num_partition_entries as usize).checked_mul(self.sizeof_partition_entry as usize)
+    }
+
+    /// 读取并校验分区表项数组
+    fn read_entries(&self, reader: &DiskReader) -> Option<Vec<u8>> {
+        let bytes = self.entries_bytes()?;
+        let buf = reader
+            .read_sectors(self.partition_entry_lba, bytes.div_ceil(LBA_SIZE))
+            .ok()?;
+        if efi_crc32(&buf[..bytes]) != self.partition_entry_array_crc32 {
+            log::warn!("gpt: partition entry array CRC mismatch");
+            return None;
+        }
+        Some(buf)
+    }
+}
+
+/// GPT使用的CRC32，与zlib相同，对输入和输出取反
+fn efi_crc32(buf: &[u8]) -> u32 {
+    !crc32_le(!0, buf)
+}
+
+/// 0号扇区是否是保护性MBR
+///
+/// 与Linux一样，也接受同时包含其它分区的混合MBR
+fn has_protective_mbr(reader: &DiskReader) -> Result<bool, SystemError> {
+    let sector = reader.read_sector(0)?;
+    if le16(&sector, 510) != 0xAA55 {
+        return Ok(false);
+    }
+    Ok((0..4).any(|i| sector[446 + i * 16 + 4] == EFI_PMBR_OSTYPE))
+}
+
+/// # 解析GPT分区表
+///
+/// 没有保护性MBR或者主、备份GPT都无效时返回`Ok(None)`。
+/// 分区号为表项在数组中的下标加1，空表项不注册。
+pub(super) fn efi_partition(reader: &DiskReader) -> Result<Option<Vec<Partition>>, SystemError> {
+    if reader.nr_sectors() <= GPT_PRIMARY_PARTITION_TABLE_LBA || !has_protective_mbr(reader)? {
+        return Ok(None);
+    }
+
+    let last_lba = reader.nr_sectors() - 1;
+    let mut found = None;
+    // 主GPT头记录的备份位置优先，它无效时再尝试磁盘的最后一个扇区
+    let primary = GptHeader::read(reader, GPT_PRIMARY_PARTITION_TABLE_LBA);
+    if let Some(entries) = primary.and_then(|h| h.read_entries(reader)) {
+        found = primary.map(|h| (h, entries));
+    } else {
+        let mut candidates = Vec::with_capacity(2);
+        if let Some(h) = primary.filter(|h| h.alternate_lba <= last_lba) {
+            candidates.push(h.alternate_lba);
+        }
+        if !candidates.contains(&last_lba) {
+            candidates.push(last_lba);
+        }
+        for lba in candidates {
+            let alt = GptHeader::read(reader, lba);
+            if let Some(entries) = alt.and_then(|h| h.read_entries(reader)) {
+                log::warn!(
+                    "gpt: primary GPT invalid, using alternate GPT at lba {}",
+                    lba
+                );
+                found = alt.map(|h| (h, entries));
+                break;
+            }
+        }
+    }
+
+    let (header, entries) = match found {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+
+    let mut parts = Vec::new();
+    let count = header.num_partition_entries.min(DISK_MAX_PARTS) as usize;
+    for i in 0..count {
+        let entry = &entries[i * header.sizeof_partition_entry as usize..];
+        // 分区类型GUID全0表示未使用的表项
+        if entry[..16].iter().all(|&b| b == 0) {
+            continue;
+        }
+        let starting_lba = le64(entry, 32);
+        let ending_lba = le64(entry, 40);
+        if starting_lba > ending_lba
+            || starting_lba < header.first_usable_lba
+            || ending_lba > header.last_usable_lba
+        {
+            log::warn!(
+                "gpt: partition entry {} out of usable range, ignored",
+                i + 1
+            );
+            continue;
+        }
+        parts.push(Partition::new_raw(
+            starting_lba,
+            starting_lba,
+            ending_lba - starting_lba + 1,
+            i as u16 + 1,
+        ));
+    }
+    Ok(Some(parts))
+}
@@ -0,0 +1,147 @@
+//! 磁盘分区表解析
+//!
+//! 参考 Linux block/partitions/。块设备注册（或者loop设备绑定后端文件）时，
+//! 由[`BlockDevManager`](super::manager::BlockDevManager)调用[`scan_partitions`]，
+//! 按顺序尝试各种分区表格式，第一个识别成功的格式决定磁盘的分区。
+//!
+//! 目前支持：
+//! - GPT（[`efi`]），校验头部和分区表项数组的CRC32，主GPT损坏时使用备份GPT
+//! - MBR（[`msdos`]），包括扩展分区中的逻辑分区
+
+mod efi;
+mod msdos;
+
+use alloc::{sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use super::{
+    block_device::{BlockDevice, BlockId, LBA_SIZE},
+    disk_info::Partition,
+};
+
+/// 每个磁盘最多的分区数。每个磁盘有256个次设备号，其中0号用于整个磁盘
+pub const DISK_MAX_PARTS: u32 = 255;
+
+/// 分区表解析器，识别不了时返回`Ok(None)`，由下一个解析器继续尝试
+type PartitionParser = fn(&DiskReader) -> Result<Option<Vec<Partition>>, SystemError>;
+
+/// GPT磁盘的0号扇区是保护性MBR，所以GPT必须在MBR之前尝试
+const PARTITION_PARSERS: &[(&str, PartitionParser)] = &[
+    ("gpt", efi::efi_partition),
+    ("msdos", msdos::msdos_partition),
+];
+
+/// # 扫描磁盘上的分区表
+///
+/// ## 参数
+///
+/// - `disk`: 要扫描的磁盘
+///
+/// ## 返回值
+///
+/// - `Ok(Vec<Partition>)`: 识别出的分区，按分区号排序。没有可识别的分区表时为空
+/// - `Err(SystemError)`: 读取磁盘失败
+pub fn scan_partitions(disk: &Arc<dyn BlockDevice>) -> Result<Vec<Partition>, SystemError> {
+    let reader = DiskReader::new(disk);
+    if reader.nr_sectors() == 0 {
+        return Ok(Vec::new());
+    }
+
+    for (name, parser) in PARTITION_PARSERS {
+        if let Some(mut parts) = parser(&reader)? {
+            parts.retain_mut(|p| reader.check_bounds(p));
+            parts.sort_by_key(|p| p.partno);
+            log::info!(
+                "{}: {} partition table, {} partition(s)",
+                disk.dev_name(),
+                name,
+                parts.len()
+            );
+            return Ok(parts);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// 分区表解析器使用的读盘接口，以512字节的扇区为单位
+pub(super) struct DiskReader<'a> {
+    disk: &'a Arc<dyn BlockDevice>,
+    nr_sectors: u64,
+}
+
+impl<'a> DiskReader<'a> {
+    fn new(disk: &'a Arc<dyn BlockDevice>) -> Self {
+        Self {
+            disk,
+            nr_sectors: disk.disk_range().len() as u64,
+        }
+    }
+
+    /// 磁盘的总扇区数
+    #[inline]
+    pub fn nr_sectors(&self) -> u64 {
+        self.nr_sectors
+    }
+
+    /// 读取从`lba`开始的`count`个扇区，超出磁盘范围时返回`EINVAL`
+    pub fn read_sectors(&self, lba: u64, count: usize) -> Result<Vec<u8>, SystemError> {
+        if lba
+            .checked_add(count as u64)
+            .is_none_or(|end| end > self.nr_sectors)
+        {
+            return Err(SystemError::EINVAL);
+        }
+        let mut buf = vec![0u8; count * LBA_SIZE];
+        self.disk.read_at_sync(lba as BlockId, count, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// 读取一个扇区
+    #[inline]
+    pub fn read_sector(&self, lba: u64) -> Result<Vec<u8>, SystemError> {
+        self.read_sectors(lba, 1)
+    }
+
+    /// 与Linux一样，起始位置超出磁盘的分区被忽略，结束位置超出磁盘的分区被截断
+    fn check_bounds(&self, p: &mut Partition) -> bool {
+        if p.sectors_num == 0 || p.partno == 0 || p.partno as u32 > DISK_MAX_PARTS {
+            return false;
+        }
+        if p.lba_start >= self.nr_sectors {
+            log::warn!(
+                "{}: partition {} start {} beyond end of disk",
+                self.disk.dev_name(),
+                p.partno,
+                p.lba_start
+            );
+            return false;
+        }
+        if p.lba_start + p.sectors_num > self.nr_sectors {
+            log::warn!(
+                "{}: partition {} extends beyond end of disk, truncated",
+                self.disk.dev_name(),
+                p.partno
+            );
+            p.sectors_num = self.nr_sectors - p.lba_start;
+        }
+        true
+    }
+}
+
+/// 读取小端序的u16
+#[inline]
+pub(super) fn le16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+/// 读取小端序的u32
+#[inline]
+pub(super) fn le32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// 读取小端序的u64
+#[inline]
+pub(super) fn le64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
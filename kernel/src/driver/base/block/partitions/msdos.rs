@@ -0,0 +1,162 @@
+//! MBR（DOS）分区表
+//!
+//! 参考 Linux block/partitions/msdos.c。主分区使用1~4号，
+//! 扩展分区中的逻辑分区按EBR链表的顺序从5号开始编号。
+
+use alloc::vec::Vec;
+use system_error::SystemError;
+
+use crate::driver::base::block::disk_info::Partition;
+
+use super::{le16, le32, DiskReader, DISK_MAX_PARTS};
+
+/// 分区表在扇区中的偏移
+const PART_TABLE_OFFSET: usize = 446;
+/// 分区表项的大小
+const PART_ENTRY_SIZE: usize = 16;
+/// 扇区末尾的签名
+const MSDOS_LABEL_MAGIC: u16 = 0xAA55;
+
+/// 保护性MBR中的分区类型，说明磁盘使用GPT
+const EFI_PMBR_OSTYPE: u8 = 0xEE;
+/// 扩展分区的分区类型
+const DOS_EXTENDED_PARTITION: u8 = 0x05;
+const WIN98_EXTENDED_PARTITION: u8 = 0x0F;
+const LINUX_EXTENDED_PARTITION: u8 = 0x85;
+
+/// 扩展分区中最多跟随的EBR数，防止损坏的链表形成环
+const MAX_LOGICAL_PARTS: usize = DISK_MAX_PARTS as usize - 4;
+
+#[derive(Debug, Clone, Copy)]
+struct MsdosEntry {
+    boot_ind: u8,
+    sys_ind: u8,
+    start_sect: u32,
+    nr_sects: u32,
+}
+
+impl MsdosEntry {
+    fn parse(sector: &[u8], slot: usize) -> Self {
+        let off = PART_TABLE_OFFSET + slot * PART_ENTRY_SIZE;
+        Self {
+            boot_ind: sector[off],
+            sys_ind: sector[off + 4],
+            start_sect: le32(sector, off + 8),
+            nr_sects: le32(sector, off + 12),
+        }
+    }
+
+    fn is_extended(&self) -> bool {
+        matches!(
+            self.sys_ind,
+            DOS_EXTENDED_PARTITION | WIN98_EXTENDED_PARTITION | LINUX_EXTENDED_PARTITION
+        )
+    }
+
+    fn is_used(&self) -> bool {
+        self.sys_ind != 0 && self.nr_sects != 0
+    }
+}
+
+fn has_label_magic(sector: &[u8]) -> bool {
+    le16(sector, 510) == MSDOS_LABEL_MAGIC
+}
+
+/// # 解析MBR分区表
+///
+/// 以下情况不认为是MBR分区表，返回`Ok(None)`：
+/// - 没有0xAA55签名
+/// - 分区表项的引导标志不是0或0x80（通常是没有分区表的FAT引导扇区）
+/// - 是GPT的保护性MBR
+pub(super) fn msdos_partition(reader: &DiskReader) -> Result<Option<Vec<Partition>>, SystemError> {
+    let sector = reader.read_sector(0)?;
+    if !has_label_magic(&sector) {
+        return Ok(None);
+    }
+
+    let entries: Vec<MsdosEntry> = (0..4).map(|i| MsdosEntry::parse(&sector, i)).collect();
+    if entries
+        .iter()
+        .any(|e| e.boot_ind != 0 && e.boot_ind != 0x80)
+    {
+        return Ok(None);
+    }
+    if entries.iter().any(|e| e.sys_ind == EFI_PMBR_OSTYPE) {
+        return Ok(None);
+    }
+
+    let mut parts = Vec::new();
+    for (slot, entry) in entries.iter().enumerate() {
+        if !entry.is_used() {
+            continue;
+        }
+        if entry.is_extended() {
+            // 扩展分区本身只是逻辑分区的容器，不作为分区注册
+            parse_extended(reader, entry, &mut parts);
+            continue;
+        }
+        parts.push(Partition::new_raw(
+            entry.start_sect as u64,
+            entry.start_sect as u64,
+            entry.nr_sects as u64,
+            slot as u16 + 1,
+        ));
+    }
+    Ok(Some(parts))
+}
+
+/// 沿着EBR链表解析扩展分区中的逻辑分区
+///
+/// 每个EBR的第一项是逻辑分区，起始扇区相对于该EBR；第二项指向下一个EBR，
+/// 起始扇区相对于扩展分区的起始位置。读取失败或者链表损坏时保留已经解析出的分区。
+fn parse_extended(reader: &DiskReader, extended: &MsdosEntry, parts: &mut Vec<Partition>) {
+    let ext_start = extended.start_sect as u64;
+    let ext_end = ext_start + extended.nr_sects as u64;
+    let mut ebr = ext_start;
+    let mut partno = 5u16;
+
+    for _ in 0..MAX_LOGICAL_PARTS {
+        let sector = match reader.read_sector(ebr) {
+            Ok(sector) => sector,
+            Err(e) => {
+                log::warn!("msdos: failed to read EBR at sector {}: {:?}", ebr, e);
+                return;
+            }
+        };
+        if !has_label_magic(&sector) {
+            return;
+        }
+
+        let logical = MsdosEntry::parse(&sector, 0);
+        let next = MsdosEntry::parse(&sector, 1);
+
+        if logical.is_used() && !logical.is_extended() {
+            let start = ebr + logical.start_sect as u64;
+            if start + logical.nr_sects as u64 <= ext_end {
+                parts.push(Partition::new_raw(
+                    start,
+                    start,
+                    logical.nr_sects as u64,
+                    partno,
+                ));
+            } else {
+                log::warn!(
+                    "msdos: logical partition {} outside extended partition",
+                    partno
+                );
+            }
+            partno += 1;
+        }
+
+        if !next.is_used() || !next.is_extended() {
+            return;
+        }
+        let next_ebr = ext_start + next.start_sect as u64;
+        // EBR只能向后链接，这也保证了链表不会成环
+        if next_ebr <= ebr || next_ebr >= ext_end {
+            log::warn!("msdos: invalid EBR link at sector {}", ebr);
+            return;
+        }
+        ebr = next_ebr;
+    }
+}
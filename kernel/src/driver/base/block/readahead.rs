@@ -0,0 +1,233 @@
+//! 块设备预读
+//!
+//! 经过[块缓存](super::cache)的读取都会交给磁盘的[`BlockReadahead`]判断是否是顺序读。
+//! 顺序读时在已读位置之后异步提交预读BIO，后续的读取用到这段数据时，由读取者把它收进块缓存。
+//! BIO的完成回调可能在中断上下文中执行，因此不在回调中访问块缓存。
+//!
+//! 预读窗口是自适应的：
+//! - 预读的数据被读到（命中）时窗口加倍，直到上限（默认[`BLOCK_RA_DEFAULT_MAX_SECTORS`]，可以通过BLKRASET修改）；
+//! - 顺序读被打断，或者预读的数据还没被读到就已经从块缓存中淘汰（未命中）时窗口减半，
+//!   小于[`BLOCK_RA_MIN_SECTORS`]后不再预读，直到再次检测到顺序读。
+
+use alloc::{sync::Arc, vec::Vec};
+use core::cmp::{max, min};
+
+use crate::libs::spinlock::SpinLock;
+
+use super::{
+    bio::BioRequest,
+    block_device::{BlockDevice, BlockId},
+};
+
+/// 最小的预读窗口（扇区数，8K）
+pub const BLOCK_RA_MIN_SECTORS: usize = 16;
+/// 默认的预读窗口上限（扇区数，256K）
+pub const BLOCK_RA_DEFAULT_MAX_SECTORS: usize = 512;
+/// 通过BLKRASET能设置的窗口上限（扇区数，4M），不能超过块缓存的容量
+const BLOCK_RA_LIMIT_SECTORS: usize = 8192;
+/// 同时在途的预读BIO数
+const BLOCK_RA_MAX_INFLIGHT: usize = 2;
+
+/// 一个在途的预读BIO
+pub struct ReadaheadBio {
+    /// 提交时磁盘的diskseq，介质变化之后数据作废
+    pub disk: u64,
+    pub lba: BlockId,
+    pub count: usize,
+    pub bio: Arc<BioRequest>,
+}
+
+impl ReadaheadBio {
+    fn overlaps(&self, lba: BlockId, count: usize) -> bool {
+        self.lba < lba + count && lba < self.lba + self.count
+    }
+}
+
+struct InnerBlockReadahead {
+    /// 上一次读取结束的扇区（不包含）
+    prev_end: BlockId,
+    /// 当前窗口大小，为0表示没有检测到顺序读
+    window: usize,
+    /// 窗口上限
+    max_window: usize,
+    /// 已经预读的范围`[ra_start, ra_end)`
+    ra_start: BlockId,
+    ra_end: BlockId,
+    inflight: Vec<ReadaheadBio>,
+}
+
+/// 一个磁盘的预读状态
+pub struct BlockReadahead {
+    inner: SpinLock<InnerBlockReadahead>,
+}
+
+impl BlockReadahead {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(InnerBlockReadahead {
+                prev_end: 0,
+                window: 0,
+                max_window: BLOCK_RA_DEFAULT_MAX_SECTORS,
+                ra_start: 0,
+                ra_end: 0,
+                inflight: Vec::new(),
+            }),
+        }
+    }
+
+    /// 预读窗口上限（扇区数）
+    pub fn max_sectors(&self) -> usize {
+        self.inner.lock().max_window
+    }
+
+    /// # 设置预读窗口上限
+    ///
+    /// ## 参数
+    /// - `sectors`: 窗口上限（扇区数），为0时关闭预读，最大为8192（4M）
+    pub fn set_max_sectors(&self, sectors: usize) {
+        let sectors = min(sectors, BLOCK_RA_LIMIT_SECTORS);
+        let mut inner = self.inner.lock();
+        inner.max_window = sectors;
+        inner.window = min(inner.window, sectors);
+    }
+
+    /// # 取出可以收进块缓存的预读BIO
+    ///
+    /// 包括已经完成的BIO，以及与本次读取范围重叠的BIO（调用者需要等待它完成）。
+    /// 介质已经变化的BIO直接丢弃
+    ///
+    /// ## 参数
+    /// - `disk`: 磁盘当前的diskseq
+    /// - `lba`, `count`: 本次读取的范围
+    pub fn take_ready(&self, disk: u64, lba: BlockId, count: usize) -> Vec<ReadaheadBio> {
+        let mut inner = self.inner.lock();
+        if inner.inflight.is_empty() {
+            return Vec::new();
+        }
+        let (ready, pending) = core::mem::take(&mut inner.inflight)
+            .into_iter()
+            .filter(|rb| rb.disk == disk)
+            .partition(|rb| rb.bio.is_done() || rb.overlaps(lba, count));
+        inner.inflight = pending;
+        ready
+    }
+
+    /// # 丢弃与写入范围重叠的在途预读
+    ///
+    /// 预读的数据比写入的旧，不能再收进块缓存
+    pub fn invalidate(&self, lba: BlockId, count: usize) {
+        self.inner
+            .lock()
+            .inflight
+            .retain(|rb| !rb.overlaps(lba, count));
+    }
+
+    /// # 根据本次读取更新窗口
+    ///
+    /// ## 参数
+    /// - `lba`, `count`: 本次读取的范围
+    /// - `missed`: 本次读取是否有扇区需要从设备同步读取
+    /// - `disk_end`: 磁盘的结束扇区（不包含）
+    ///
+    /// ## 返回值
+    /// 需要预读的范围(起始扇区, 扇区数)
+    pub fn on_read(
+        &self,
+        lba: BlockId,
+        count: usize,
+        missed: bool,
+        disk_end: BlockId,
+    ) -> Option<(BlockId, usize)> {
+        let mut inner = self.inner.lock();
+        let end = lba + count;
+        let in_window = lba >= inner.ra_start && lba < inner.ra_end;
+        let sequential = lba == inner.prev_end || in_window;
+        inner.prev_end = end;
+
+        if !sequential {
+            // 随机读，之前预读的数据不会再被用到
+            inner.window /= 2;
+            inner.ra_start = 0;
+            inner.ra_end = 0;
+            return None;
+        }
+
+        if in_window && missed {
+            // 预读的数据在被读到之前就被淘汰了
+            inner.window /= 2;
+        } else if in_window {
+            inner.window = min(inner.window * 2, inner.max_window);
+        } else if inner.window < BLOCK_RA_MIN_SECTORS {
+            // 新检测到的顺序读
+            inner.window = min(
+                max(count.next_power_of_two() * 2, BLOCK_RA_MIN_SECTORS),
+                inner.max_window,
+            );
+        }
+        if inner.window < BLOCK_RA_MIN_SECTORS {
+            return None;
+        }
+
+        if inner.ra_end < end {
+            inner.ra_start = end;
+            inner.ra_end = end;
+        }
+        // 剩余的预读数据不足半个窗口时发起下一次预读
+        if inner.ra_end - end >= inner.window / 2
+            || inner.inflight.len() >= BLOCK_RA_MAX_INFLIGHT
+            || inner.ra_end >= disk_end
+        {
+            return None;
+        }
+        let start = inner.ra_end;
+        let n = min(inner.window, disk_end - start);
+        inner.ra_start = start;
+        inner.ra_end = start + n;
+        Some((start, n))
+    }
+
+    /// # 提交预读BIO
+    ///
+    /// 预读是尽力而为的，提交失败时不返回错误
+    ///
+    /// ## 参数
+    /// - `bdev`: 磁盘
+    /// - `disk`: 磁盘当前的diskseq
+    /// - `lba`, `count`: 预读的范围
+    pub fn submit(&self, bdev: &Arc<dyn BlockDevice>, disk: u64, lba: BlockId, count: usize) {
+        let bio = match bdev.submit_bio_read(lba, count) {
+            Ok(bio) => bio,
+            Err(e) => {
+                log::debug!(
+                    "{}: readahead of {} sectors at lba {} failed: {:?}",
+                    bdev.dev_name(),
+                    count,
+                    lba,
+                    e
+                );
+                return;
+            }
+        };
+        self.inner.lock().inflight.push(ReadaheadBio {
+            disk,
+            lba,
+            count,
+            bio,
+        });
+    }
+
+    /// 丢弃所有在途的预读，重置窗口
+    pub fn reset(&self) {
+        let mut inner = self.inner.lock();
+        inner.inflight.clear();
+        inner.window = 0;
+        inner.ra_start = 0;
+        inner.ra_end = 0;
+    }
+}
+
+impl Default for BlockReadahead {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,512 @@
+//! 块设备请求队列
+//!
+//! 上层提交的BIO先进入请求队列，与队列中相邻的请求进行前向/后向合并，
+//! 然后由电梯调度器（见[`super::elevator`]）决定派发给驱动的顺序。
+//! 驱动的工作线程从队列中取出[`Request`]，通过[`Request::into_bio`]得到一个连续的BIO再交给硬件。
+//!
+//! FLUSH请求是一个写屏障：它不经过电梯调度器，而是在之前派发的请求全部完成之后才派发，
+//! 它之后提交的请求也要等它派发之后才会进入调度器，因此不会被调度到它的前面。
+//!
+//! 设备有多个硬件队列时，每个请求按提交BIO的CPU映射到其中一个硬件队列（[`Request::hw_queue`]），
+//! 驱动把请求交给对应的硬件队列，不同CPU提交的IO可以在设备上并行处理。
+//!
+//! 请求有两种派发方式：
+//! - 驱动的工作线程通过[`RequestQueue::wait_for_work`]等待，再用[`RequestQueue::drain_batch`]取出请求；
+//! - 以[`RequestQueue::with_queue_rq`]创建的队列没有工作线程，提交BIO的CPU直接派发请求，
+//!   调用驱动的`queue_rq`回调把请求交给硬件。FLUSH等待的请求完成时，由完成它们的上下文继续派发。
+
+use alloc::{
+    boxed::Box,
+    collections::VecDeque,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    libs::{spinlock::SpinLock, wait_queue::WaitQueue},
+    smp::core::smp_get_processor_id,
+    time::timer::clock,
+};
+
+use super::{
+    bio::{BioRequest, BioType},
+    block_device::{BlockId, LBA_SIZE},
+    elevator::{default_elevator, elevator_alloc, Elevator},
+};
+
+/// 由一个或多个LBA连续、方向相同的BIO合并而成的请求
+pub struct Request {
+    bio_type: BioType,
+    lba_start: BlockId,
+    count: usize,
+    /// 按LBA升序排列的BIO
+    bios: Vec<Arc<BioRequest>>,
+    /// 入队时刻（jiffies）
+    start_time: u64,
+    /// 请求所属的硬件队列，由第一个BIO的提交CPU决定
+    hw_queue: usize,
+}
+
+impl Request {
+    fn new(bio: Arc<BioRequest>, hw_queue: usize) -> Self {
+        Self {
+            bio_type: bio.bio_type(),
+            lba_start: bio.lba_start(),
+            count: bio.count(),
+            bios: vec![bio],
+            start_time: clock(),
+            hw_queue,
+        }
+    }
+
+    /// 请求的方向
+    pub fn bio_type(&self) -> BioType {
+        self.bio_type
+    }
+
+    /// 起始LBA
+    pub fn lba_start(&self) -> BlockId {
+        self.lba_start
+    }
+
+    /// 结束LBA（不包含）
+    pub fn lba_end(&self) -> BlockId {
+        self.lba_start + self.count
+    }
+
+    /// 扇区数
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// 入队时刻（jiffies）
+    pub fn start_time(&self) -> u64 {
+        self.start_time
+    }
+
+    /// 合并进来的BIO数量
+    pub fn nr_bios(&self) -> usize {
+        self.bios.len()
+    }
+
+    /// 请求应当提交到的硬件队列，取值小于[`RequestQueue::nr_hw_queues`]
+    pub fn hw_queue(&self) -> usize {
+        self.hw_queue
+    }
+
+    /// # 功能
+    ///
+    /// 尝试把`bio`接到请求的末尾。
+    ///
+    /// ## 参数
+    /// - `bio`: 待合并的BIO
+    /// - `max_sectors`: 合并后请求允许的最大扇区数
+    ///
+    /// ## 返回值
+    /// - `true`: 合并成功
+    /// - `false`: 方向不同、不相邻、超过大小限制，或者有一方不能合并（见[`BioRequest::is_mergeable`]）
+    pub fn try_back_merge(&mut self, bio: &Arc<BioRequest>, max_sectors: usize) -> bool {
+        let count = bio.count();
+        if bio.bio_type() != self.bio_type
+            || !bio.is_mergeable()
+            || !self.bios[0].is_mergeable()
+            || bio.lba_start() != self.lba_end()
+            || self.count + count > max_sectors
+        {
+            return false;
+        }
+        self.count += count;
+        self.bios.push(bio.clone());
+        true
+    }
+
+    /// # 功能
+    ///
+    /// 尝试把`bio`插到请求的开头。成功后请求的起始LBA会变小。
+    ///
+    /// ## 参数
+    /// - `bio`: 待合并的BIO
+    /// - `max_sectors`: 合并后请求允许的最大扇区数
+    ///
+    /// ## 返回值
+    /// - `true`: 合并成功
+    /// - `false`: 方向不同、不相邻、超过大小限制，或者有一方不能合并（见[`BioRequest::is_mergeable`]）
+    pub fn try_front_merge(&mut self, bio: &Arc<BioRequest>, max_sectors: usize) -> bool {
+        let lba_start = bio.lba_start();
+        let count = bio.count();
+        if bio.bio_type() != self.bio_type
+            || !bio.is_mergeable()
+            || !self.bios[0].is_mergeable()
+            || lba_start + count != self.lba_start
+            || self.count + count > max_sectors
+        {
+            return false;
+        }
+        self.lba_start = lba_start;
+        self.count += count;
+        self.bios.insert(0, bio.clone());
+        true
+    }
+
+    /// 请求中所有的BIO都完成之后调用`callback`
+    fn on_complete<F>(&self, callback: F)
+    where
+        F: Fn(Result<usize, SystemError>) + Send + Sync + 'static,
+    {
+        // 合并后的BIO按顺序完成各个子BIO，最后一个完成时其他的都已经完成了
+        self.bios.last().unwrap().on_complete(callback);
+    }
+
+    /// 以同一个结果完成请求中的所有BIO（用于设备移除等无法派发的情况）
+    pub fn complete(self, result: Result<usize, SystemError>) {
+        for bio in self.bios {
+            let result = result.clone().map(|_| bio.count() * LBA_SIZE);
+            bio.complete(result);
+        }
+    }
+
+    /// # 功能
+    ///
+    /// 把请求转换成一个覆盖整个LBA区间的BIO，交给驱动处理。
+    ///
+    /// 只包含一个BIO时直接返回它；否则新建一个BIO，写请求会把各个BIO的数据拼接进去，
+    /// 新BIO完成时把读到的数据分发回各个BIO并逐个完成它们。
+    pub fn into_bio(mut self) -> Arc<BioRequest> {
+        if self.bios.len() == 1 {
+            return self.bios.pop().unwrap();
+        }
+
+        let merged = match self.bio_type {
+            BioType::Read => BioRequest::new_read(self.lba_start, self.count),
+            // 连续提交的多个FLUSH只需要下发一次
+            BioType::Flush => BioRequest::new_flush(),
+            // discard请求不参与合并，驱动按设备的限制拆分了它们
+            BioType::Discard => unreachable!("discard requests are never merged"),
+            BioType::Write => {
+                let mut data = Vec::with_capacity(self.count * LBA_SIZE);
+                for bio in self.bios.iter() {
+                    // SAFETY: 子BIO还没有提交给驱动，缓冲区不会被并发修改
+                    let buf = unsafe { &*bio.buffer() };
+                    data.extend_from_slice(&buf[..bio.count() * LBA_SIZE]);
+                }
+                BioRequest::new_write(self.lba_start, self.count, &data)
+            }
+        };
+
+        let bio_type = self.bio_type;
+        let bios = self.bios;
+        let merged_weak = Arc::downgrade(&merged);
+        merged.on_complete(move |result| {
+            // 回调由merged.complete()调用，此时merged一定存活
+            let merged = merged_weak.upgrade();
+            let mut offset = 0;
+            for bio in bios.iter() {
+                let len = bio.count() * LBA_SIZE;
+                match (&result, &merged) {
+                    (Ok(_), Some(merged)) => {
+                        if bio_type == BioType::Read {
+                            // SAFETY: merged已经完成，缓冲区不会再被驱动修改
+                            let buf = unsafe { &(*merged.buffer())[offset..offset + len] };
+                            bio.write_buffer(buf);
+                        }
+                        bio.complete(Ok(len));
+                    }
+                    (Ok(_), None) => bio.complete(Err(SystemError::EIO)),
+                    (Err(e), _) => bio.complete(Err(e.clone())),
+                }
+                offset += len;
+            }
+        });
+        merged
+    }
+}
+
+/// 驱动把派发出的请求交给硬件的回调，在派发请求的CPU上调用，不能睡眠
+pub type QueueRqFn = Box<dyn Fn(Request) + Send + Sync>;
+
+/// 块设备请求队列
+pub struct RequestQueue {
+    inner: SpinLock<InnerRequestQueue>,
+    wait_queue: WaitQueue,
+    batch_size: usize,
+    max_sectors: usize,
+    nr_hw_queues: usize,
+    /// 直接派发时驱动的回调，为`None`时由驱动的工作线程取出请求
+    queue_rq: Option<QueueRqFn>,
+}
+
+struct InnerRequestQueue {
+    elevator: Box<dyn Elevator>,
+    /// 还没有派发的FLUSH请求，以及排在第一个FLUSH之后提交的请求（按提交顺序）
+    barrier: VecDeque<Request>,
+    /// 已经派发给驱动、还没有完成的请求数
+    in_flight: usize,
+    /// 队列已关闭，不再接受新的请求
+    closed: bool,
+}
+
+impl InnerRequestQueue {
+    fn is_empty(&self) -> bool {
+        self.elevator.is_empty() && self.barrier.is_empty()
+    }
+
+    /// 是否有现在就可以派发的请求
+    fn has_ready(&self) -> bool {
+        !self.elevator.is_empty() || (!self.barrier.is_empty() && self.in_flight == 0)
+    }
+
+    fn queue_request(&mut self, bio: Arc<BioRequest>, max_sectors: usize, hw_queue: usize) {
+        if bio.bio_type() == BioType::Flush {
+            if let Some(last) = self.barrier.back_mut() {
+                // 两个FLUSH之间没有其他请求时合并成一个
+                if last.bio_type == BioType::Flush {
+                    last.bios.push(bio);
+                    return;
+                }
+            }
+            self.barrier.push_back(Request::new(bio, hw_queue));
+        } else if !self.barrier.is_empty() {
+            self.barrier.push_back(Request::new(bio, hw_queue));
+        } else if self.elevator.merge(&bio, max_sectors) {
+            bio.mark_merged();
+        } else {
+            self.elevator.add_request(Request::new(bio, hw_queue));
+        }
+    }
+
+    /// 取出下一个可以派发的请求。调度器中的请求都派发并完成之后才会派发FLUSH
+    fn dispatch(&mut self) -> Option<Request> {
+        if let Some(rq) = self.elevator.dispatch() {
+            return Some(rq);
+        }
+        if self.in_flight != 0 {
+            return None;
+        }
+        let flush = self.barrier.pop_front()?;
+        // FLUSH之后、下一个FLUSH之前的请求现在可以交给调度器了
+        while self
+            .barrier
+            .front()
+            .is_some_and(|rq| rq.bio_type != BioType::Flush)
+        {
+            let rq = self.barrier.pop_front().unwrap();
+            self.elevator.add_request(rq);
+        }
+        Some(flush)
+    }
+}
+
+impl RequestQueue {
+    pub const DEFAULT_BATCH_SIZE: usize = 16;
+    /// 合并后单个请求的默认最大扇区数（128KiB）
+    pub const DEFAULT_MAX_SECTORS: usize = 256;
+
+    /// 使用默认的电梯调度器创建请求队列
+    pub fn new() -> Arc<Self> {
+        Self::with_max_sectors(Self::DEFAULT_MAX_SECTORS)
+    }
+
+    /// 创建请求队列，并指定合并后单个请求的最大扇区数
+    pub fn with_max_sectors(max_sectors: usize) -> Arc<Self> {
+        Self::with_hw_queues(max_sectors, 1)
+    }
+
+    /// # 功能
+    ///
+    /// 为有多个硬件队列的设备创建请求队列。
+    ///
+    /// ## 参数
+    /// - `max_sectors`: 合并后单个请求的最大扇区数
+    /// - `nr_hw_queues`: 设备的硬件队列数，提交BIO的CPU按编号取模映射到硬件队列
+    pub fn with_hw_queues(max_sectors: usize, nr_hw_queues: usize) -> Arc<Self> {
+        Self::create(max_sectors, nr_hw_queues, None)
+    }
+
+    /// # 功能
+    ///
+    /// 创建直接派发的请求队列。提交BIO之后，提交者所在的CPU立即派发可以派发的请求，
+    /// 通过`queue_rq`交给驱动，请求按[`Request::hw_queue`]进入对应的硬件队列。
+    ///
+    /// ## 参数
+    /// - `max_sectors`: 合并后单个请求的最大扇区数
+    /// - `nr_hw_queues`: 设备的硬件队列数，提交BIO的CPU按编号取模映射到硬件队列
+    /// - `queue_rq`: 把请求交给硬件的回调，失败时由驱动完成请求
+    pub fn with_queue_rq(
+        max_sectors: usize,
+        nr_hw_queues: usize,
+        queue_rq: QueueRqFn,
+    ) -> Arc<Self> {
+        Self::create(max_sectors, nr_hw_queues, Some(queue_rq))
+    }
+
+    fn create(max_sectors: usize, nr_hw_queues: usize, queue_rq: Option<QueueRqFn>) -> Arc<Self> {
+        Arc::new(Self {
+            inner: SpinLock::new(InnerRequestQueue {
+                elevator: default_elevator(),
+                barrier: VecDeque::new(),
+                in_flight: 0,
+                closed: false,
+            }),
+            wait_queue: WaitQueue::default(),
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            max_sectors: max_sectors.max(1),
+            nr_hw_queues: nr_hw_queues.max(1),
+            queue_rq,
+        })
+    }
+
+    /// 硬件队列数
+    pub fn nr_hw_queues(&self) -> usize {
+        self.nr_hw_queues
+    }
+
+    /// 当前CPU提交的请求所属的硬件队列
+    fn current_hw_queue(&self) -> usize {
+        smp_get_processor_id().data() as usize % self.nr_hw_queues
+    }
+
+    /// 提交BIO（非阻塞）。能与已排队的请求合并时直接合并，否则作为新请求交给电梯调度器。
+    /// 直接派发的队列随后在当前CPU上派发请求
+    ///
+    /// 队列已经关闭时返回`ENODEV`
+    pub fn submit_bio(self: &Arc<Self>, bio: Arc<BioRequest>) -> Result<(), SystemError> {
+        let hw_queue = self.current_hw_queue();
+        let should_wakeup = {
+            let mut inner = self.inner.lock_irqsave();
+            if inner.closed {
+                return Err(SystemError::ENODEV);
+            }
+            let was_ready = inner.has_ready();
+            inner.queue_request(bio, self.max_sectors, hw_queue);
+            !was_ready && inner.has_ready()
+        };
+
+        if self.queue_rq.is_some() {
+            self.run_queue();
+        } else if should_wakeup {
+            self.wait_queue.wakeup(None);
+        }
+        Ok(())
+    }
+
+    /// 直接派发：把现在可以派发的请求逐个交给驱动的`queue_rq`
+    fn run_queue(self: &Arc<Self>) {
+        let Some(queue_rq) = self.queue_rq.as_ref() else {
+            return;
+        };
+        while let Some(rq) = self.fetch_request() {
+            queue_rq(rq);
+        }
+    }
+
+    /// 关闭队列。已经提交的请求仍然可以被取出，之后的提交会失败
+    pub fn close(&self) {
+        self.inner.lock_irqsave().closed = true;
+        self.wait_queue.wakeup(None);
+    }
+
+    /// 队列是否已经关闭
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock_irqsave().closed
+    }
+
+    /// 按电梯调度器的顺序取出下一个请求
+    pub fn fetch_request(self: &Arc<Self>) -> Option<Request> {
+        let rq = {
+            let mut inner = self.inner.lock_irqsave();
+            let rq = inner.dispatch()?;
+            inner.in_flight += 1;
+            rq
+        };
+        self.track_in_flight(core::slice::from_ref(&rq));
+        Some(rq)
+    }
+
+    /// 批量取出请求（用于worker线程）
+    pub fn drain_batch(self: &Arc<Self>) -> Vec<Request> {
+        let mut batch = Vec::new();
+        {
+            let mut inner = self.inner.lock_irqsave();
+            while batch.len() < self.batch_size {
+                match inner.dispatch() {
+                    Some(rq) => {
+                        batch.push(rq);
+                        inner.in_flight += 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+        self.track_in_flight(&batch);
+        batch
+    }
+
+    /// 为刚派发的请求注册完成回调，全部完成时唤醒等待派发FLUSH的worker，
+    /// 直接派发的队列则在完成请求的上下文中派发FLUSH
+    ///
+    /// 必须在释放队列的锁之后调用：BIO已经完成时回调会被立即执行
+    fn track_in_flight(self: &Arc<Self>, batch: &[Request]) {
+        for rq in batch {
+            let queue: Weak<Self> = Arc::downgrade(self);
+            rq.on_complete(move |_| {
+                let Some(queue) = queue.upgrade() else {
+                    return;
+                };
+                let wakeup = {
+                    let mut inner = queue.inner.lock_irqsave();
+                    inner.in_flight -= 1;
+                    inner.in_flight == 0 && !inner.barrier.is_empty()
+                };
+                if wakeup {
+                    if queue.queue_rq.is_some() {
+                        queue.run_queue();
+                    } else {
+                        queue.wait_queue.wakeup(None);
+                    }
+                }
+            });
+        }
+    }
+
+    /// 检查队列是否为空
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock_irqsave().is_empty()
+    }
+
+    /// 当前使用的电梯调度器的名字
+    pub fn elevator_name(&self) -> &'static str {
+        self.inner.lock_irqsave().elevator.name()
+    }
+
+    /// # 功能
+    ///
+    /// 切换电梯调度器，已排队的请求会转移到新的调度器中。
+    ///
+    /// ## 参数
+    /// - `name`: 调度器名字，例如`noop`、`deadline`
+    ///
+    /// ## 返回值
+    /// - `Ok(())`: 切换成功
+    /// - `Err(SystemError::EINVAL)`: 没有这个调度器
+    pub fn set_elevator(&self, name: &str) -> Result<(), SystemError> {
+        let mut elevator = elevator_alloc(name).ok_or(SystemError::EINVAL)?;
+        let mut inner = self.inner.lock_irqsave();
+        for rq in inner.elevator.drain() {
+            elevator.add_request(rq);
+        }
+        inner.elevator = elevator;
+        Ok(())
+    }
+
+    /// Worker等待新请求或者队列被关闭
+    pub fn wait_for_work(&self) -> Result<(), SystemError> {
+        self.wait_queue.wait_event_interruptible(
+            || {
+                let inner = self.inner.lock_irqsave();
+                inner.has_ready() || inner.closed
+            },
+            None::<fn()>,
+        )
+    }
+}
@@ -0,0 +1,205 @@
+//! 块设备的I/O统计
+//!
+//! 每个磁盘（[`BlockDevMeta`](super::manager::BlockDevMeta)）记录读、写、discard、flush请求的次数、
+//! 合并次数、扇区数和耗时，以及正在处理的请求数。通过`/sys/block/<dev>/stat`和`/proc/diskstats`
+//! 导出，字段与Linux相同，参见[`BlockDevStats::format_stat`]。
+//!
+//! 统计在[`BlockDevice`](super::block_device::BlockDevice)的默认BIO提交路径中完成，
+//! 经过gendisk缓存命中的读写不会到达设备，因此不计入。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/Documentation/admin-guide/iostats.rst
+
+use alloc::{string::String, sync::Arc};
+
+use crate::{libs::spinlock::SpinLock, time::Instant};
+
+use super::bio::BioRequest;
+
+/// 统计的分组
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatGroup {
+    Read = 0,
+    Write = 1,
+    Discard = 2,
+    Flush = 3,
+}
+
+impl StatGroup {
+    const COUNT: usize = 4;
+
+    /// 正在处理的请求按读、写两个方向计数，discard和flush都算作写
+    fn direction(self) -> usize {
+        match self {
+            StatGroup::Read => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// 一个分组的计数
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatCounters {
+    /// 完成的请求数
+    pub ios: u64,
+    /// 与已排队的请求合并的请求数
+    pub merges: u64,
+    /// 传输的扇区数
+    pub sectors: u64,
+    /// 请求的总耗时（毫秒）
+    pub ticks: u64,
+}
+
+/// 某一时刻的统计数据
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockDevStatsSnapshot {
+    pub groups: [StatCounters; StatGroup::COUNT],
+    /// 正在处理的读、写请求数
+    pub in_flight: [u64; 2],
+    /// 有请求正在处理的总时长（毫秒）
+    pub io_ticks: u64,
+    /// 所有请求耗时之和（毫秒）
+    pub time_in_queue: u64,
+}
+
+impl BlockDevStatsSnapshot {
+    pub fn group(&self, group: StatGroup) -> &StatCounters {
+        &self.groups[group as usize]
+    }
+}
+
+#[derive(Debug, Default)]
+struct InnerBlockDevStats {
+    data: BlockDevStatsSnapshot,
+    /// 上一次更新`io_ticks`的时间（毫秒）
+    stamp: u64,
+}
+
+impl InnerBlockDevStats {
+    /// 在正在处理的请求数变化之前调用，把上一次更新以来有请求在处理的时间计入`io_ticks`
+    fn update_io_ticks(&mut self, now: u64) {
+        if self.data.in_flight.iter().any(|n| *n != 0) {
+            self.data.io_ticks += now.saturating_sub(self.stamp);
+        }
+        self.stamp = now;
+    }
+}
+
+/// 块设备的I/O统计
+#[derive(Debug)]
+pub struct BlockDevStats {
+    inner: SpinLock<InnerBlockDevStats>,
+}
+
+fn now_ms() -> u64 {
+    Instant::now().total_millis().max(0) as u64
+}
+
+impl BlockDevStats {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: SpinLock::new(InnerBlockDevStats::default()),
+        })
+    }
+
+    /// # 开始统计一个请求
+    ///
+    /// ## 返回值
+    /// 请求开始的时间，需要传给[`io_done`](Self::io_done)
+    pub fn io_start(&self, group: StatGroup) -> u64 {
+        let now = now_ms();
+        let mut inner = self.inner.lock_irqsave();
+        inner.update_io_ticks(now);
+        inner.data.in_flight[group.direction()] += 1;
+        now
+    }
+
+    /// # 结束统计一个请求
+    ///
+    /// ## 参数
+    /// - `group`: 请求的分组，与[`io_start`](Self::io_start)相同
+    /// - `sectors`: 请求的扇区数
+    /// - `merged`: 请求是否与已排队的请求合并
+    /// - `start`: [`io_start`](Self::io_start)的返回值
+    pub fn io_done(&self, group: StatGroup, sectors: usize, merged: bool, start: u64) {
+        let now = now_ms();
+        let duration = now.saturating_sub(start);
+        let mut inner = self.inner.lock_irqsave();
+        inner.update_io_ticks(now);
+        let dir = group.direction();
+        inner.data.in_flight[dir] = inner.data.in_flight[dir].saturating_sub(1);
+        inner.data.time_in_queue += duration;
+        let counters = &mut inner.data.groups[group as usize];
+        counters.ios += 1;
+        counters.sectors += sectors as u64;
+        counters.ticks += duration;
+        if merged {
+            counters.merges += 1;
+        }
+    }
+
+    /// # 统计一个BIO
+    ///
+    /// 在BIO提交之前调用，BIO完成时自动结束统计
+    pub fn account_bio(self: &Arc<Self>, group: StatGroup, bio: &Arc<BioRequest>) {
+        let start = self.io_start(group);
+        let stats = self.clone();
+        let weak_bio = Arc::downgrade(bio);
+        bio.on_complete(move |_| {
+            let (sectors, merged) = weak_bio
+                .upgrade()
+                .map(|bio| (bio.count(), bio.is_merged()))
+                .unwrap_or((0, false));
+            stats.io_done(group, sectors, merged, start);
+        });
+    }
+
+    /// 获取当前的统计数据
+    pub fn snapshot(&self) -> BlockDevStatsSnapshot {
+        let now = now_ms();
+        let mut inner = self.inner.lock_irqsave();
+        inner.update_io_ticks(now);
+        inner.data
+    }
+
+    /// # 格式化统计数据
+    ///
+    /// 依次为：读请求数、读合并数、读扇区数、读耗时，写的四项，正在处理的请求数、`io_ticks`、
+    /// `time_in_queue`，discard的四项，flush请求数、flush耗时，共17个字段。
+    ///
+    /// ## 参数
+    /// - `width`: 每个字段的最小宽度，`/sys/block/<dev>/stat`中为8，`/proc/diskstats`中为0
+    pub fn format_stat(&self, width: usize) -> String {
+        let s = self.snapshot();
+        let read = s.group(StatGroup::Read);
+        let write = s.group(StatGroup::Write);
+        let discard = s.group(StatGroup::Discard);
+        let flush = s.group(StatGroup::Flush);
+        let fields = [
+            read.ios,
+            read.merges,
+            read.sectors,
+            read.ticks,
+            write.ios,
+            write.merges,
+            write.sectors,
+            write.ticks,
+            s.in_flight[0] + s.in_flight[1],
+            s.io_ticks,
+            s.time_in_queue,
+            discard.ios,
+            discard.merges,
+            discard.sectors,
+            discard.ticks,
+            flush.ios,
+            flush.ticks,
+        ];
+        let mut out = String::new();
+        for (i, v) in fields.iter().enumerate() {
+            if i != 0 {
+                out.push(' ');
+            }
+            out.push_str(&format!("{:>width$}", v, width = width));
+        }
+        out
+    }
+}
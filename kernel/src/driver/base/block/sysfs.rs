@@ -0,0 +1,215 @@
+//! `/sys/block`
+//!
+//! 每个已注册的磁盘在`/sys/block`下有一个以设备名命名的目录，包含：
+//! - `stat`: I/O统计，参见[`BlockDevStats::format_stat`](super::stats::BlockDevStats::format_stat)
+//! - `inflight`: 正在处理的读、写请求数
+//! - `fstype`: 整个磁盘和各个分区上探测到的文件系统，每行是`设备名 类型`，无法识别时类型为`unknown`
+//!
+//! 驱动还可以通过[`BlockDevice::sysfs_groups`]添加自己的属性，例如zram的`disksize`
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::kobject::{
+        CommonKobj, DynamicKObjKType, KObjType, KObject, KObjectManager, KObjectSysFSOps,
+    },
+    filesystem::{
+        sysfs::{
+            file::sysfs_emit_str, sysfs_instance, Attribute, AttributeGroup, SysFSOps,
+            SysFSOpsSupport, SYSFS_ATTR_MODE_RO,
+        },
+        vfs::{probe_fs, IndexNode, InodeMode},
+    },
+    libs::spinlock::SpinLock,
+};
+
+use super::{
+    block_device::BlockDevice, gendisk::GenDisk, manager::block_dev_manager, stats::BlockDevStats,
+};
+
+/// `/sys/block`的kobject
+static mut SYS_BLOCK_KOBJECT_INSTANCE: Option<Arc<CommonKobj>> = None;
+
+/// 每个磁盘在`/sys/block`下的目录，按设备名索引
+static BLOCK_KOBJS: SpinLock<BTreeMap<String, Arc<CommonKobj>>> = SpinLock::new(BTreeMap::new());
+
+#[inline(always)]
+fn sys_block_kobj() -> Option<Arc<CommonKobj>> {
+    unsafe { SYS_BLOCK_KOBJECT_INSTANCE.clone() }
+}
+
+/// 创建`/sys/block`目录
+pub(super) fn block_sysfs_init() -> Result<(), SystemError> {
+    let block_kobj = CommonKobj::new("block".to_string());
+    KObjectManager::init_and_add_kobj(block_kobj.clone(), Some(&DynamicKObjKType))?;
+    unsafe {
+        SYS_BLOCK_KOBJECT_INSTANCE = Some(block_kobj);
+    }
+    Ok(())
+}
+
+/// 把磁盘添加到`/sys/block`
+pub(super) fn block_sysfs_add(dev: &Arc<dyn BlockDevice>) -> Result<(), SystemError> {
+    let Some(block_kobj) = sys_block_kobj() else {
+        return Ok(());
+    };
+    let name = dev.dev_name().to_string();
+    let kobj = CommonKobj::new(name.clone());
+    kobj.set_parent(Some(Arc::downgrade(&(block_kobj as Arc<dyn KObject>))));
+    KObjectManager::init_and_add_kobj(kobj.clone(), Some(&BlockKObjType))?;
+    let groups = dev.sysfs_groups();
+    if !groups.is_empty() {
+        if let Err(e) = sysfs_instance().create_groups(&(kobj.clone() as Arc<dyn KObject>), groups)
+        {
+            KObjectManager::remove_kobj(kobj as Arc<dyn KObject>);
+            return Err(e);
+        }
+    }
+    BLOCK_KOBJS.lock().insert(name, kobj);
+    Ok(())
+}
+
+/// 把磁盘从`/sys/block`中移除
+pub(super) fn block_sysfs_remove(dev: &Arc<dyn BlockDevice>) {
+    let kobj = BLOCK_KOBJS.lock().remove(dev.dev_name().as_str());
+    if let Some(kobj) = kobj {
+        KObjectManager::remove_kobj(kobj as Arc<dyn KObject>);
+    }
+}
+
+/// 根据`/sys/block`下目录的kobject找到磁盘的I/O统计
+fn kobj_stats(kobj: &Arc<dyn KObject>) -> Result<Arc<BlockDevStats>, SystemError> {
+    let dev = block_dev_manager()
+        .lookup(&kobj.name())
+        .ok_or(SystemError::ENODEV)?;
+    Ok(dev.blkdev_meta().stats().clone())
+}
+
+#[derive(Debug)]
+struct BlockKObjType;
+
+impl KObjType for BlockKObjType {
+    fn sysfs_ops(&self) -> Option<&dyn SysFSOps> {
+        Some(&KObjectSysFSOps)
+    }
+
+    fn attribute_groups(&self) -> Option<&'static [&'static dyn AttributeGroup]> {
+        Some(&[&BlockAttrGroup])
+    }
+
+    fn release(&self, _kobj: Arc<dyn KObject>) {}
+}
+
+#[derive(Debug)]
+struct BlockAttrGroup;
+
+impl AttributeGroup for BlockAttrGroup {
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    fn attrs(&self) -> &[&'static dyn Attribute] {
+        &[&AttrStat, &AttrInflight, &AttrFsType]
+    }
+
+    fn is_visible(
+        &self,
+        _kobj: Arc<dyn KObject>,
+        attr: &'static dyn Attribute,
+    ) -> Option<InodeMode> {
+        Some(attr.mode())
+    }
+}
+
+#[derive(Debug)]
+struct AttrStat;
+
+impl Attribute for AttrStat {
+    fn name(&self) -> &str {
+        "stat"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    fn show(&self, kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let stats = kobj_stats(&kobj)?;
+        sysfs_emit_str(buf, &format!("{}\n", stats.format_stat(8)))
+    }
+}
+
+#[derive(Debug)]
+struct AttrInflight;
+
+impl Attribute for AttrInflight {
+    fn name(&self) -> &str {
+        "inflight"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    fn show(&self, kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let s = kobj_stats(&kobj)?.snapshot();
+        sysfs_emit_str(
+            buf,
+            &format!("{:>8} {:>8}\n", s.in_flight[0], s.in_flight[1]),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct AttrFsType;
+
+impl Attribute for AttrFsType {
+    fn name(&self) -> &str {
+        "fstype"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    fn show(&self, kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let dev = block_dev_manager()
+            .lookup(&kobj.name())
+            .ok_or(SystemError::ENODEV)?;
+        // 探测需要读磁盘，不能持有元数据的锁
+        let mut gendisks: Vec<Arc<GenDisk>> = dev
+            .blkdev_meta()
+            .inner()
+            .gendisks
+            .values()
+            .cloned()
+            .collect();
+        // 整个磁盘在最前面，之后按分区号排列
+        gendisks.sort_by_key(|gendisk| gendisk.idx().wrapping_add(1));
+
+        let mut out = String::new();
+        for gendisk in gendisks {
+            let fstype = probe_fs(&gendisk).map_or("unknown", |maker| maker.name());
+            out.push_str(&format!("{} {}\n", gendisk.dname()?.as_ref(), fstype));
+        }
+        sysfs_emit_str(buf, &out)
+    }
+}
@@ -0,0 +1,183 @@
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    filesystem::{
+        devfs::{DevFS, DevNodeAttr, DeviceINode, LockedDevFSInode},
+        devpts::{DevPtsFs, LockedDevPtsFSInode},
+        epoll::EPollItem,
+        vfs::{
+            file::FileFlags, utils::DName, FilePrivateData, FileSystem, FileType, IndexNode,
+            Metadata, PollableInode,
+        },
+    },
+    libs::{mutex::MutexGuard, rwlock::RwLock},
+};
+
+use super::CharDevice;
+
+/// 字符设备在devfs中的节点
+///
+/// 负责把VFS的文件操作转发到[`CharDevice`]，并保存节点自身的属性（权限、属主等），
+/// 驱动无需再关心devfs相关的细节。devpts中的pty节点也使用这个类型。
+#[derive(Debug)]
+pub struct CharDevInode {
+    dev: Arc<dyn CharDevice>,
+    parent: RwLock<Weak<dyn IndexNode>>,
+    fs: RwLock<Weak<dyn FileSystem>>,
+    metadata: Metadata,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
+    /// 对应/dev/下的设备名
+    name: DName,
+}
+
+impl CharDevInode {
+    pub fn new(dev: Arc<dyn CharDevice>) -> Arc<Self> {
+        let mode = dev.devnode_mode();
+        let mut metadata = Metadata::new(FileType::CharDevice, mode);
+        metadata.raw_dev = dev.id_table().device_number();
+        // 与 Linux devfs 一致的首选 I/O 块大小
+        metadata.blk_size = 1024;
+
+        return Arc::new(Self {
+            name: DName::from(dev.chrdev_name()),
+            dev,
+            parent: RwLock::new(Weak::<LockedDevFSInode>::new()),
+            fs: RwLock::new(Weak::<DevFS>::new()),
+            metadata,
+            attr: DevNodeAttr::new(mode),
+        });
+    }
+
+    #[inline]
+    pub fn char_device(&self) -> &Arc<dyn CharDevice> {
+        &self.dev
+    }
+}
+
+impl IndexNode for CharDevInode {
+    fn open(&self, data: MutexGuard<FilePrivateData>, mode: &FileFlags) -> Result<(), SystemError> {
+        self.dev.open(data, mode)
+    }
+
+    fn close(&self, data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        self.dev.close(data)
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        self.dev.read(offset, len, buf, data)
+    }
+
+    fn write_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &[u8],
+        data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        self.dev.write(offset, len, buf, data)
+    }
+
+    fn ioctl(
+        &self,
+        cmd: u32,
+        arg: usize,
+        data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        self.dev.ioctl(cmd, arg, data)
+    }
+
+    fn sync(&self) -> Result<(), SystemError> {
+        self.dev.sync()
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.read().upgrade().unwrap()
+    }
+
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        Err(SystemError::ENOTDIR)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        let mut meta = self.metadata.clone();
+        self.attr.apply(&mut meta);
+        Ok(meta)
+    }
+
+    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
+        self.attr.update(metadata);
+        Ok(())
+    }
+
+    fn resize(&self, _len: usize) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn dname(&self) -> Result<DName, SystemError> {
+        Ok(self.name.clone())
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        self.parent.read().upgrade().ok_or(SystemError::ENOENT)
+    }
+
+    fn as_pollable_inode(&self) -> Result<&dyn PollableInode, SystemError> {
+        Ok(self)
+    }
+}
+
+impl PollableInode for CharDevInode {
+    fn poll(&self, private_data: &FilePrivateData) -> Result<usize, SystemError> {
+        self.dev.poll(private_data)
+    }
+
+    fn add_epitem(
+        &self,
+        epitem: Arc<EPollItem>,
+        private_data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        self.dev.add_epitem(epitem, private_data)
+    }
+
+    fn remove_epitem(
+        &self,
+        epitem: &Arc<EPollItem>,
+        private_data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        self.dev.remove_epitem(epitem, private_data)
+    }
+}
+
+impl DeviceINode for CharDevInode {
+    fn set_fs(&self, fs: Weak<DevFS>) {
+        *self.fs.write() = fs;
+    }
+
+    fn set_parent(&self, parent: Weak<LockedDevFSInode>) {
+        *self.parent.write() = parent;
+    }
+
+    fn set_devpts_fs(&self, devpts: Weak<DevPtsFs>) {
+        *self.fs.write() = devpts;
+    }
+
+    fn set_devpts_parent(&self, parent: Weak<LockedDevPtsFSInode>) {
+        *self.parent.write() = parent;
+    }
+}
@@ -0,0 +1,92 @@
+use alloc::{string::String, sync::Arc};
+use hashbrown::HashMap;
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    filesystem::devfs::{devfs_register, devfs_unregister},
+    init::initcall::INITCALL_POSTCORE,
+    libs::mutex::{Mutex, MutexGuard},
+};
+
+use super::{char_inode::CharDevInode, CharDevice};
+
+static mut CHAR_DEV_MANAGER: Option<CharDevManager> = None;
+
+#[inline]
+pub fn char_dev_manager() -> &'static CharDevManager {
+    unsafe { CHAR_DEV_MANAGER.as_ref().unwrap() }
+}
+
+#[unified_init(INITCALL_POSTCORE)]
+pub fn char_dev_manager_init() -> Result<(), SystemError> {
+    unsafe {
+        CHAR_DEV_MANAGER = Some(CharDevManager::new());
+    }
+    Ok(())
+}
+
+/// 字符设备管理器
+///
+/// 与块设备的[`BlockDevManager`](crate::driver::base::block::manager::BlockDevManager)对应，
+/// 负责为字符设备创建devfs节点，并记录设备名与节点的对应关系。
+pub struct CharDevManager {
+    inner: Mutex<InnerCharDevManager>,
+}
+
+struct InnerCharDevManager {
+    /// 设备名 -> devfs节点
+    nodes: HashMap<String, Arc<CharDevInode>>,
+}
+
+impl CharDevManager {
+    pub fn new() -> Self {
+        CharDevManager {
+            inner: Mutex::new(InnerCharDevManager {
+                nodes: HashMap::new(),
+            }),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, InnerCharDevManager> {
+        self.inner.lock()
+    }
+
+    /// 注册字符设备，并在devfs中创建对应的设备节点
+    ///
+    /// 节点名由[`CharDevice::chrdev_name`]决定，默认权限由[`CharDevice::devnode_mode`]决定。
+    pub fn register(&self, dev: Arc<dyn CharDevice>) -> Result<(), SystemError> {
+        let mut inner = self.inner();
+        let name = dev.chrdev_name();
+        if inner.nodes.contains_key(&name) {
+            return Err(SystemError::EEXIST);
+        }
+
+        let inode = CharDevInode::new(dev);
+        devfs_register(&name, inode.clone())?;
+        inner.nodes.insert(name, inode);
+        return Ok(());
+    }
+
+    /// 注销字符设备，并删除devfs中对应的设备节点
+    pub fn unregister(&self, dev: &Arc<dyn CharDevice>) -> Result<(), SystemError> {
+        let mut inner = self.inner();
+        let name = dev.chrdev_name();
+        let inode = inner.nodes.get(&name).ok_or(SystemError::ENODEV)?;
+        if !Arc::ptr_eq(inode.char_device(), dev) {
+            return Err(SystemError::ENODEV);
+        }
+
+        devfs_unregister(&name, inode.clone())?;
+        inner.nodes.remove(&name);
+        return Ok(());
+    }
+
+    /// 根据设备名查找已注册的字符设备
+    pub fn lookup(&self, name: &str) -> Option<Arc<dyn CharDevice>> {
+        self.inner()
+            .nodes
+            .get(name)
+            .map(|inode| inode.char_device().clone())
+    }
+}
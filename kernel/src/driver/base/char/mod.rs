@@ -1,8 +1,19 @@
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc};
 use log::error;
 
 use system_error::SystemError;
 
+use crate::{
+    filesystem::{
+        epoll::EPollItem,
+        vfs::{file::FileFlags, FilePrivateData, InodeMode},
+    },
+    libs::mutex::MutexGuard,
+};
+
+pub mod char_inode;
+pub mod manager;
+
 use super::{
     device::{
         device_manager,
@@ -15,7 +26,36 @@ use super::{
     },
 };
 
+/// 字符设备
+///
+/// 驱动只需要实现这个trait，然后通过[`manager::char_dev_manager`]注册，
+/// 即可在devfs中得到一个设备节点。VFS对该节点的文件操作会由[`char_inode::CharDevInode`]
+/// 转发到这里，驱动不再需要各自实现`IndexNode`。
 pub trait CharDevice: Device {
+    /// 设备节点在/dev下的名字，默认为kobject的名字
+    fn chrdev_name(&self) -> String {
+        self.name()
+    }
+
+    /// 设备节点的默认权限，之后可以通过chmod修改
+    fn devnode_mode(&self) -> InodeMode {
+        InodeMode::from_bits_truncate(0o600)
+    }
+
+    /// @brief: 打开设备
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _flags: &FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    /// @brief: 关闭设备
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
     /// Notice buffer对应设备按字节划分，使用u8类型
     /// Notice offset应该从0开始计数
     ///
@@ -23,18 +63,68 @@ pub trait CharDevice: Device {
     /// @parameter offset: 起始字节偏移量
     /// @parameter len: 读取字节的数量
     /// @parameter buf: 目标数组
+    /// @parameter data: 打开设备时设置的文件私有数据
     /// @return: 如果操作成功，返回操作的长度(单位是字节)；否则返回错误码；如果操作异常，但是并没有检查出什么错误，将返回已操作的长度
-    fn read(&self, len: usize, buf: &mut [u8]) -> Result<usize, SystemError>;
+    fn read(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError>;
 
     /// @brief: 从设备的第offset个字节开始，把buf数组的len个byte，写入到设备中
     /// @parameter offset: 起始字节偏移量
     /// @parameter len: 读取字节的数量
     /// @parameter buf: 目标数组
+    /// @parameter data: 打开设备时设置的文件私有数据
     /// @return: 如果操作成功，返回操作的长度(单位是字节)；否则返回错误码；如果操作异常，但是并没有检查出什么错误，将返回已操作的长度
-    fn write(&self, len: usize, buf: &[u8]) -> Result<usize, SystemError>;
+    fn write(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &[u8],
+        data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError>;
+
+    /// @brief: 设备的ioctl。不认识的命令应当返回`ENOIOCTLCMD`
+    fn ioctl(
+        &self,
+        _cmd: u32,
+        _arg: usize,
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOIOCTLCMD)
+    }
+
+    /// @brief: 获取设备当前的poll状态（EPollEventType的位图）。
+    /// 不支持poll的设备返回`ENOSYS`
+    fn poll(&self, _data: &FilePrivateData) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    /// @brief: 向设备添加一个epoll item
+    fn add_epitem(
+        &self,
+        _epitem: Arc<EPollItem>,
+        _data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    /// @brief: 从设备中移除一个epoll item
+    fn remove_epitem(
+        &self,
+        _epitem: &Arc<EPollItem>,
+        _data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        Err(SystemError::ENOSYS)
+    }
 
-    /// @brief: 同步信息，把所有的dirty数据写回设备 - 待实现
-    fn sync(&self) -> Result<(), SystemError>;
+    /// @brief: 同步信息，把所有的dirty数据写回设备
+    fn sync(&self) -> Result<(), SystemError> {
+        Ok(())
+    }
 }
 
 /// @brief 字符设备框架函数集
@@ -19,6 +19,7 @@ use system_error::SystemError;
 use super::{
     bus::BusNotifyEvent,
     device_manager,
+    devres::devres_release_all,
     driver::{driver_manager, Driver, DriverManager},
     Device, DeviceManager,
 };
@@ -229,6 +230,7 @@ impl DeviceManager {
 
     /// 参考 https://code.dragonos.org.cn/xref/linux-6.1.9/drivers/base/dd.c?fi=driver_attach#528
     fn unbind_cleanup(&self, dev: &Arc<dyn Device>) {
+        devres_release_all(dev);
         dev.set_driver(None);
         // todo: 添加更多操作，清理数据
     }
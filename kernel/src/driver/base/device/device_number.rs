@@ -35,6 +35,18 @@ impl Major {
     pub const MMC_BLK_MAJOR: Self = Self::new(179);
     /// PMEM block device
     pub const PMEM_BLK_MAJOR: Self = Self::new(259);
+    /// RAM disk (brd)
+    pub const RAMDISK_MAJOR: Self = Self::new(1);
+    /// Software RAID (md)
+    pub const MD_MAJOR: Self = Self::new(9);
+    /// Network block device (nbd)
+    pub const NBD_MAJOR: Self = Self::new(43);
+    /// SCSI/ATAPI CD-ROM
+    pub const SCSI_CDROM_MAJOR: Self = Self::new(11);
+    /// 压缩内存块设备（zram）。Linux中为动态分配，这里固定使用常见的值
+    pub const ZRAM_MAJOR: Self = Self::new(252);
+    /// Device-mapper (dm-N)
+    pub const DEVICE_MAPPER_MAJOR: Self = Self::new(253);
 
     pub const HVC_MAJOR: Self = Self::new(229);
 
@@ -46,6 +58,8 @@ impl Major {
     }
     pub const LOOP_MAJOR: Self = Self::new(7);
     pub const LOOP_CONTROL_MAJOR: Self = Self::new(10);
+    /// 杂项字符设备（loop-control、mapper/control等）
+    pub const MISC_MAJOR: Self = Self::new(10);
 }
 
 impl Hash for Major {
@@ -0,0 +1,68 @@
+//! 设备管理的资源（devres）
+//!
+//! 驱动可以把资源的释放操作登记到设备上，设备与驱动解绑（包括probe失败）或者设备被释放时，
+//! 按照与登记相反的顺序执行这些释放操作，驱动不需要在每条错误路径上手动释放。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/base/devres.c
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+
+use crate::libs::spinlock::SpinLock;
+
+use super::Device;
+
+type DevresRelease = Box<dyn FnOnce() + Send + Sync>;
+
+/// 一个被设备管理的资源
+struct Devres {
+    /// 资源的名称，用于调试
+    name: &'static str,
+    release: DevresRelease,
+}
+
+/// 每个设备登记的资源，按设备对象的地址索引
+static DEVRES: SpinLock<BTreeMap<usize, Vec<Devres>>> = SpinLock::new(BTreeMap::new());
+
+fn dev_key(dev: &Arc<dyn Device>) -> usize {
+    Arc::as_ptr(dev) as *const () as usize
+}
+
+/// # 登记一个被设备管理的资源
+///
+/// ## 参数
+/// - `dev`: 资源所属的设备
+/// - `name`: 资源的名称，用于调试
+/// - `release`: 释放资源的操作，在设备解绑或者释放时执行
+pub fn devres_add<F>(dev: &Arc<dyn Device>, name: &'static str, release: F)
+where
+    F: FnOnce() + Send + Sync + 'static,
+{
+    DEVRES
+        .lock_irqsave()
+        .entry(dev_key(dev))
+        .or_default()
+        .push(Devres {
+            name,
+            release: Box::new(release),
+        });
+}
+
+/// # 释放设备登记的所有资源
+///
+/// 按照与登记相反的顺序执行释放操作
+///
+/// ## 返回值
+/// 释放的资源数
+pub fn devres_release_all(dev: &Arc<dyn Device>) -> usize {
+    let list = DEVRES.lock_irqsave().remove(&dev_key(dev));
+    let Some(list) = list else {
+        return 0;
+    };
+    let count = list.len();
+    // 在锁外执行，释放操作可能会睡眠或者登记新的资源
+    for res in list.into_iter().rev() {
+        log::trace!("devres: {}: releasing {}", dev.name(), res.name);
+        (res.release)();
+    }
+    count
+}
@@ -49,6 +49,7 @@ use super::{
 pub mod bus;
 pub mod dd;
 pub mod device_number;
+pub mod devres;
 pub mod driver;
 pub mod init;
 
@@ -498,8 +499,7 @@ impl KObjType for DeviceKObjType {
          * possible memory leak.
          */
 
-        // todo: 在引入devres之后再实现
-        // devres_release_all(kobj);
+        devres::devres_release_all(&dev);
         dev.release();
     }
 
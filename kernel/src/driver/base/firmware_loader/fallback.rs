@@ -0,0 +1,352 @@
+//! 通过sysfs由用户态提供固件
+//!
+//! 在文件系统中找不到固件时，创建`/sys/class/firmware/<name>`（名字中的`/`替换为`!`），
+//! 并发送带有`FIRMWARE=<name>`的add uevent。用户态（udev/mdev或者手动）按以下步骤提供固件：
+//! 1. 向`loading`写入`1`；
+//! 2. 把固件内容写入`data`；
+//! 3. 向`loading`写入`0`完成加载，或者写入`-1`放弃。
+//!
+//! `/sys/class/firmware/timeout`是等待的秒数，默认60秒。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/base/firmware_loader/fallback.c
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    driver::base::{
+        class::sys_class_kset,
+        kobject::{
+            CommonKobj, DynamicKObjKType, KObjType, KObject, KObjectManager, KObjectSysFSOps,
+        },
+        uevent::{kobject_uevent_env, KObjectAction},
+    },
+    filesystem::{
+        sysfs::{
+            file::sysfs_emit_str, sysfs_instance, Attribute, AttributeGroup, BinAttribute,
+            SysFSOps, SysFSOpsSupport, SYSFS_ATTR_MODE_RW,
+        },
+        vfs::InodeMode,
+    },
+    init::initcall::INITCALL_POSTCORE,
+    libs::{mutex::Mutex, spinlock::SpinLock, wait_queue::WaitQueue},
+    time::Duration,
+};
+
+use super::FIRMWARE_MAX_SIZE;
+
+/// 默认的等待时间（秒）
+const FW_DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// `/sys/class/firmware`的kobject
+static mut FW_CLASS_KOBJECT_INSTANCE: Option<Arc<CommonKobj>> = None;
+
+/// 等待用户态提供固件的时间（秒）
+static FW_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(FW_DEFAULT_TIMEOUT_SECS);
+
+/// 正在等待用户态提供的固件，按sysfs中的目录名索引
+static FW_PENDING: SpinLock<BTreeMap<String, Arc<FwPending>>> = SpinLock::new(BTreeMap::new());
+
+#[derive(Debug, Default)]
+struct FwPendingState {
+    /// 用户态正在写入`data`
+    loading: bool,
+    data: Vec<u8>,
+    /// 加载完成（`Ok`）或者被放弃（`Err`）
+    result: Option<Result<(), SystemError>>,
+}
+
+/// 一个正在等待用户态提供的固件
+#[derive(Debug)]
+struct FwPending {
+    state: Mutex<FwPendingState>,
+    wait_queue: WaitQueue,
+}
+
+impl FwPending {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(FwPendingState::default()),
+            wait_queue: WaitQueue::default(),
+        })
+    }
+
+    fn finish(&self, result: Result<(), SystemError>) {
+        let mut state = self.state.lock();
+        state.loading = false;
+        state.result = Some(result);
+        drop(state);
+        self.wait_queue.wake_all();
+    }
+}
+
+fn fw_pending_of(kobj: &Arc<dyn KObject>) -> Result<Arc<FwPending>, SystemError> {
+    FW_PENDING
+        .lock()
+        .get(&kobj.name())
+        .cloned()
+        .ok_or(SystemError::ENODEV)
+}
+
+/// 创建`/sys/class/firmware`
+#[unified_init(INITCALL_POSTCORE)]
+fn fw_sysfs_init() -> Result<(), SystemError> {
+    let fw_kobj = CommonKobj::new("firmware".to_string());
+    fw_kobj.set_parent(Some(Arc::downgrade(
+        &(sys_class_kset() as Arc<dyn KObject>),
+    )));
+    KObjectManager::init_and_add_kobj(fw_kobj.clone(), Some(&DynamicKObjKType))?;
+    sysfs_instance().create_file(&(fw_kobj.clone() as Arc<dyn KObject>), &AttrTimeout)?;
+    unsafe {
+        FW_CLASS_KOBJECT_INSTANCE = Some(fw_kobj);
+    }
+    Ok(())
+}
+
+/// # 等待用户态通过sysfs提供固件
+///
+/// ## 返回值
+/// - `Ok(Vec<u8>)`: 固件的内容
+/// - `Err(SystemError::EBUSY)`: 已经有同名的固件在等待
+/// - `Err(SystemError::ECANCELED)`: 用户态放弃了加载
+/// - `Err(SystemError::ETIMEDOUT)`: 超时
+pub(super) fn fw_load_from_user(name: &str) -> Result<Vec<u8>, SystemError> {
+    let parent = unsafe { FW_CLASS_KOBJECT_INSTANCE.clone() }.ok_or(SystemError::ENOENT)?;
+    let dir_name = name.replace('/', "!");
+    let pending = FwPending::new();
+    {
+        let mut list = FW_PENDING.lock();
+        if list.contains_key(&dir_name) {
+            return Err(SystemError::EBUSY);
+        }
+        list.insert(dir_name.clone(), pending.clone());
+    }
+
+    let kobj = CommonKobj::new(dir_name.clone());
+    kobj.set_parent(Some(Arc::downgrade(&(parent as Arc<dyn KObject>))));
+    let kobj = kobj as Arc<dyn KObject>;
+    let data_attr = Arc::new(AttrData) as Arc<dyn BinAttribute>;
+    let r = KObjectManager::init_and_add_kobj(kobj.clone(), Some(&FwKObjType))
+        .and_then(|_| sysfs_instance().create_bin_file(&kobj, &data_attr))
+        .and_then(|_| {
+            let timeout = FW_TIMEOUT_SECS.load(Ordering::Relaxed);
+            let env = [format!("FIRMWARE={}", name), format!("TIMEOUT={}", timeout)];
+            let env: Vec<&str> = env.iter().map(|e| e.as_str()).collect();
+            kobject_uevent_env(&kobj, KObjectAction::Add, &env)?;
+
+            pending
+                .wait_queue
+                .wait_until_timeout(
+                    || pending.state.lock().result.clone(),
+                    Duration::from_secs(timeout),
+                )
+                .map_err(|e| match e {
+                    SystemError::EAGAIN_OR_EWOULDBLOCK => SystemError::ETIMEDOUT,
+                    e => e,
+                })?
+        });
+
+    sysfs_instance().remove_bin_file(&kobj, &data_attr);
+    KObjectManager::remove_kobj(kobj);
+    FW_PENDING.lock().remove(&dir_name);
+
+    r.map(|_| core::mem::take(&mut pending.state.lock().data))
+}
+
+#[derive(Debug)]
+struct FwKObjType;
+
+impl KObjType for FwKObjType {
+    fn sysfs_ops(&self) -> Option<&dyn SysFSOps> {
+        Some(&KObjectSysFSOps)
+    }
+
+    fn attribute_groups(&self) -> Option<&'static [&'static dyn AttributeGroup]> {
+        Some(&[&FwAttrGroup])
+    }
+
+    fn release(&self, _kobj: Arc<dyn KObject>) {}
+}
+
+#[derive(Debug)]
+struct FwAttrGroup;
+
+impl AttributeGroup for FwAttrGroup {
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    fn attrs(&self) -> &[&'static dyn Attribute] {
+        &[&AttrLoading]
+    }
+
+    fn is_visible(
+        &self,
+        _kobj: Arc<dyn KObject>,
+        attr: &'static dyn Attribute,
+    ) -> Option<InodeMode> {
+        Some(attr.mode())
+    }
+}
+
+/// `/sys/class/firmware/timeout`
+#[derive(Debug)]
+struct AttrTimeout;
+
+impl Attribute for AttrTimeout {
+    fn name(&self) -> &str {
+        "timeout"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RW
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW | SysFSOpsSupport::ATTR_STORE
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        sysfs_emit_str(
+            buf,
+            &format!("{}\n", FW_TIMEOUT_SECS.load(Ordering::Relaxed)),
+        )
+    }
+
+    fn store(&self, _kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let secs: u64 = core::str::from_utf8(buf)
+            .map_err(|_| SystemError::EINVAL)?
+            .trim()
+            .parse()
+            .map_err(|_| SystemError::EINVAL)?;
+        // 与Linux一致，写入0恢复默认值
+        let secs = if secs == 0 {
+            FW_DEFAULT_TIMEOUT_SECS
+        } else {
+            secs
+        };
+        FW_TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+}
+
+/// `/sys/class/firmware/<name>/loading`
+#[derive(Debug)]
+struct AttrLoading;
+
+impl Attribute for AttrLoading {
+    fn name(&self) -> &str {
+        "loading"
+    }
+
+    fn mode(&self) -> InodeMode {
+        InodeMode::from_bits_truncate(0o644)
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW | SysFSOpsSupport::ATTR_STORE
+    }
+
+    fn show(&self, kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let loading = fw_pending_of(&kobj)?.state.lock().loading;
+        sysfs_emit_str(buf, if loading { "1\n" } else { "0\n" })
+    }
+
+    fn store(&self, kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let pending = fw_pending_of(&kobj)?;
+        let cmd = core::str::from_utf8(buf)
+            .map_err(|_| SystemError::EINVAL)?
+            .trim();
+        match cmd {
+            "1" => {
+                let mut state = pending.state.lock();
+                if state.result.is_none() {
+                    state.loading = true;
+                    state.data.clear();
+                }
+            }
+            "0" => {
+                if pending.state.lock().loading {
+                    pending.finish(Ok(()));
+                }
+            }
+            "-1" => pending.finish(Err(SystemError::ECANCELED)),
+            _ => return Err(SystemError::EINVAL),
+        }
+        Ok(buf.len())
+    }
+}
+
+/// `/sys/class/firmware/<name>/data`
+#[derive(Debug)]
+struct AttrData;
+
+impl Attribute for AttrData {
+    fn name(&self) -> &str {
+        "data"
+    }
+
+    fn mode(&self) -> InodeMode {
+        InodeMode::from_bits_truncate(0o600)
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::empty()
+    }
+}
+
+impl BinAttribute for AttrData {
+    fn support_battr(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::BATTR_READ | SysFSOpsSupport::BATTR_WRITE
+    }
+
+    fn write(
+        &self,
+        kobj: Arc<dyn KObject>,
+        buf: &[u8],
+        offset: usize,
+    ) -> Result<usize, SystemError> {
+        let pending = fw_pending_of(&kobj)?;
+        let mut state = pending.state.lock();
+        if !state.loading {
+            return Err(SystemError::ENODEV);
+        }
+        let end = offset.checked_add(buf.len()).ok_or(SystemError::EFBIG)?;
+        if end > FIRMWARE_MAX_SIZE {
+            return Err(SystemError::EFBIG);
+        }
+        if state.data.len() < end {
+            state.data.resize(end, 0);
+        }
+        state.data[offset..end].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn read(
+        &self,
+        kobj: Arc<dyn KObject>,
+        buf: &mut [u8],
+        offset: usize,
+    ) -> Result<usize, SystemError> {
+        let pending = fw_pending_of(&kobj)?;
+        let state = pending.state.lock();
+        if offset >= state.data.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(state.data.len() - offset);
+        buf[..len].copy_from_slice(&state.data[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn size(&self) -> usize {
+        0
+    }
+}
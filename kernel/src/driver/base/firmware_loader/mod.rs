@@ -0,0 +1,205 @@
+//! 固件加载
+//!
+//! 驱动通过[`request_firmware`]按名称（例如`iwlwifi-cc-a0-77.ucode`）获取固件，查找顺序为：
+//! 1. 已经加载、仍在使用中的固件；
+//! 2. 根文件系统中的`/lib/firmware/updates/<name>`和`/lib/firmware/<name>`
+//!    （使用initramfs作为根文件系统时，就是initramfs中的文件）；
+//! 3. 通过sysfs由用户态提供，参见[`fallback`]。
+//!
+//! 固件在最后一个引用被释放时释放。通过[`devm_request_firmware`]获取的固件由设备管理，
+//! 设备与驱动解绑时自动释放。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/base/firmware_loader/main.c
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use log::{info, warn};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::device::{devres::devres_add, Device},
+    filesystem::vfs::{
+        file::FileFlags, kernel_file::KernelFile, FileType, VFS_MAX_FOLLOW_SYMLINK_TIMES,
+    },
+    libs::{mutex::Mutex, spinlock::SpinLock},
+    process::namespace::mnt::root_mnt_namespace,
+};
+
+mod fallback;
+
+/// 查找固件的目录，按顺序查找
+const FIRMWARE_SEARCH_PATHS: &[&str] = &["/lib/firmware/updates", "/lib/firmware"];
+
+/// 单个固件的最大大小
+pub const FIRMWARE_MAX_SIZE: usize = 64 * 1024 * 1024;
+
+/// 已经加载的固件
+#[derive(Debug)]
+pub struct Firmware {
+    name: String,
+    data: Vec<u8>,
+}
+
+impl Firmware {
+    /// 固件的名称
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 固件的内容
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// 固件的大小（字节）
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// 仍在使用中的固件，同名的请求共享同一份数据
+static FW_CACHE: SpinLock<BTreeMap<String, Weak<Firmware>>> = SpinLock::new(BTreeMap::new());
+
+/// 串行化固件的加载，避免同一个固件被并发地重复加载
+static FW_LOAD_LOCK: Mutex<()> = Mutex::new(());
+
+fn fw_cache_lookup(name: &str) -> Option<Arc<Firmware>> {
+    FW_CACHE.lock().get(name).and_then(|fw| fw.upgrade())
+}
+
+fn fw_cache_insert(fw: &Arc<Firmware>) {
+    let mut cache = FW_CACHE.lock();
+    cache.retain(|_, fw| fw.strong_count() != 0);
+    cache.insert(fw.name.clone(), Arc::downgrade(fw));
+}
+
+/// 固件名是`/lib/firmware`下的相对路径，不能跳出该目录
+fn fw_check_name(name: &str) -> Result<(), SystemError> {
+    if name.is_empty() || name.starts_with('/') || name.split('/').any(|c| c == "..") {
+        return Err(SystemError::EINVAL);
+    }
+    Ok(())
+}
+
+/// 从根文件系统中读取固件
+fn fw_read_from_fs(name: &str) -> Result<Vec<u8>, SystemError> {
+    let root = root_mnt_namespace().root_inode();
+    for dir in FIRMWARE_SEARCH_PATHS {
+        let path = format!("{}/{}", dir, name);
+        let inode = match root.lookup_follow_symlink(&path, VFS_MAX_FOLLOW_SYMLINK_TIMES) {
+            Ok(inode) => inode,
+            Err(SystemError::ENOENT) => continue,
+            Err(e) => {
+                warn!("firmware: failed to look up {}: {:?}", path, e);
+                continue;
+            }
+        };
+        if inode.metadata()?.file_type != FileType::File {
+            continue;
+        }
+        return KernelFile::from_inode(inode, FileFlags::O_RDONLY)?.read_to_end(FIRMWARE_MAX_SIZE);
+    }
+    Err(SystemError::ENOENT)
+}
+
+fn do_request_firmware(
+    name: &str,
+    dev: Option<&Arc<dyn Device>>,
+    allow_fallback: bool,
+) -> Result<Arc<Firmware>, SystemError> {
+    fw_check_name(name)?;
+    if let Some(fw) = fw_cache_lookup(name) {
+        return Ok(fw);
+    }
+
+    let _guard = FW_LOAD_LOCK.lock();
+    // 等待锁期间可能已经被其他请求加载
+    if let Some(fw) = fw_cache_lookup(name) {
+        return Ok(fw);
+    }
+
+    let dev_name = dev.map(|dev| dev.name()).unwrap_or_default();
+    let data = match fw_read_from_fs(name) {
+        Ok(data) => data,
+        Err(SystemError::ENOENT) if allow_fallback => fallback::fw_load_from_user(name)
+            .inspect_err(|e| {
+                warn!(
+                    "{}: firmware {} not found and user helper failed: {:?}",
+                    dev_name, name, e
+                );
+            })?,
+        // 不回退时固件通常是可选的，找不到不需要警告
+        Err(SystemError::ENOENT) => return Err(SystemError::ENOENT),
+        Err(e) => {
+            warn!("{}: failed to load firmware {}: {:?}", dev_name, name, e);
+            return Err(e);
+        }
+    };
+
+    let fw = Arc::new(Firmware {
+        name: name.to_string(),
+        data,
+    });
+    fw_cache_insert(&fw);
+    info!(
+        "{}: loaded firmware {} ({} bytes)",
+        dev_name,
+        name,
+        fw.size()
+    );
+    Ok(fw)
+}
+
+/// # 请求固件
+///
+/// 在文件系统中找不到时，回退到由用户态通过sysfs提供，最多等待`/sys/class/firmware/timeout`秒。
+///
+/// ## 参数
+/// - `name`: 固件名，即`/lib/firmware`下的相对路径
+/// - `dev`: 请求固件的设备，用于日志
+///
+/// ## 返回值
+/// - `Ok(Arc<Firmware>)`: 固件，最后一个引用被释放时释放
+/// - `Err(SystemError::EINVAL)`: 固件名不合法
+/// - `Err(SystemError::EFBIG)`: 固件超过[`FIRMWARE_MAX_SIZE`]
+/// - `Err(SystemError::ETIMEDOUT)`: 等待用户态提供固件超时
+pub fn request_firmware(
+    name: &str,
+    dev: Option<&Arc<dyn Device>>,
+) -> Result<Arc<Firmware>, SystemError> {
+    do_request_firmware(name, dev, true)
+}
+
+/// # 请求固件，不回退到用户态
+///
+/// 用于可选的固件，在文件系统中找不到时直接返回`ENOENT`，不会等待
+pub fn request_firmware_direct(
+    name: &str,
+    dev: Option<&Arc<dyn Device>>,
+) -> Result<Arc<Firmware>, SystemError> {
+    do_request_firmware(name, dev, false)
+}
+
+/// # 释放固件
+///
+/// 与直接丢弃`Arc`等价，用于与Linux的接口保持一致
+pub fn release_firmware(fw: Arc<Firmware>) {
+    drop(fw);
+}
+
+/// # 请求由设备管理的固件
+///
+/// 与[`request_firmware`]相同，但设备与驱动解绑或者设备被释放时，设备持有的引用会被自动释放
+pub fn devm_request_firmware(
+    dev: &Arc<dyn Device>,
+    name: &str,
+) -> Result<Arc<Firmware>, SystemError> {
+    let fw = request_firmware(name, Some(dev))?;
+    let held = fw.clone();
+    devres_add(dev, "firmware", move || release_firmware(held));
+    Ok(fw)
+}
@@ -4,6 +4,7 @@ pub mod class;
 pub mod cpu;
 pub mod device;
 pub mod firmware;
+pub mod firmware_loader;
 pub mod hypervisor;
 pub mod init;
 pub mod kobject;
@@ -12,3 +13,4 @@ pub mod map;
 pub mod platform;
 pub mod subsys;
 pub mod swnode;
+pub mod uevent;
@@ -0,0 +1,143 @@
+//! kobject的uevent通知
+//!
+//! 通过NETLINK_KOBJECT_UEVENT多播，将设备的变化通知给用户态（如udev/mdev）。
+//!
+//! 参考 Linux lib/kobject_uevent.c
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use crate::net::socket::netlink::kobject::netlink_broadcast_uevent;
+
+use super::kobject::{KObject, KObjectState};
+
+/// uevent的序列号，每发送一条uevent递增
+static UEVENT_SEQNUM: AtomicU64 = AtomicU64::new(0);
+
+/// uevent消息的最大长度
+const UEVENT_BUFFER_SIZE: usize = 2048;
+
+/// kobject发生的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KObjectAction {
+    Add,
+    Remove,
+    Change,
+    Move,
+    Online,
+    Offline,
+    Bind,
+    Unbind,
+}
+
+impl KObjectAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KObjectAction::Add => "add",
+            KObjectAction::Remove => "remove",
+            KObjectAction::Change => "change",
+            KObjectAction::Move => "move",
+            KObjectAction::Online => "online",
+            KObjectAction::Offline => "offline",
+            KObjectAction::Bind => "bind",
+            KObjectAction::Unbind => "unbind",
+        }
+    }
+}
+
+/// 获取kobject在sysfs中相对于`/sys`的路径，例如`/devices/virtual/block/loop0`
+pub fn kobject_get_path(kobj: &Arc<dyn KObject>) -> String {
+    let mut names = Vec::new();
+    names.push(kobj.name());
+    let mut parent = kobj.parent().and_then(|p| p.upgrade());
+    while let Some(p) = parent {
+        names.push(p.name());
+        parent = p.parent().and_then(|p| p.upgrade());
+    }
+
+    let mut path = String::new();
+    for name in names.iter().rev() {
+        path.push('/');
+        path.push_str(name);
+    }
+    path
+}
+
+/// 向用户态发送kobject的uevent
+pub fn kobject_uevent(kobj: &Arc<dyn KObject>, action: KObjectAction) -> Result<(), SystemError> {
+    kobject_uevent_env(kobj, action, &[])
+}
+
+/// ## 向用户态发送kobject的uevent，并附带额外的环境变量
+///
+/// ### 参数
+///
+/// - `kobj`: 发生变化的kobject
+/// - `action`: 动作类型
+/// - `envp`: 额外的环境变量，格式为`KEY=VALUE`。如果其中没有`SUBSYSTEM`，
+///   则使用kobject所属kset的名称
+pub fn kobject_uevent_env(
+    kobj: &Arc<dyn KObject>,
+    action: KObjectAction,
+    envp: &[&str],
+) -> Result<(), SystemError> {
+    if kobj.kobj_state().contains(KObjectState::UEVENT_SUPPRESS) {
+        return Ok(());
+    }
+
+    let devpath = kobject_get_path(kobj);
+    let subsystem = kobj.kset().map(|kset| format!("SUBSYSTEM={}", kset.name()));
+    let mut env: Vec<&str> = Vec::with_capacity(envp.len() + 1);
+    if !envp.iter().any(|e| e.starts_with("SUBSYSTEM=")) {
+        if let Some(subsystem) = subsystem.as_deref() {
+            env.push(subsystem);
+        }
+    }
+    env.extend_from_slice(envp);
+
+    match action {
+        KObjectAction::Add => kobj.update_kobj_state(Some(KObjectState::ADD_UEVENT_SENT), None),
+        KObjectAction::Remove => {
+            kobj.update_kobj_state(Some(KObjectState::REMOVE_UEVENT_SENT), None)
+        }
+        _ => {}
+    }
+
+    uevent_send(action, &devpath, &env)
+}
+
+/// ## 以给定的设备路径向用户态发送uevent
+///
+/// 用于没有独立kobject的对象，例如磁盘分区，它们的devpath挂在所属磁盘的下面。
+///
+/// ### 参数
+///
+/// - `action`: 动作类型
+/// - `devpath`: 相对于`/sys`的路径，例如`/devices/virtual/block/loop0/loop0p1`
+/// - `envp`: 额外的环境变量，格式为`KEY=VALUE`，应当包含`SUBSYSTEM`
+pub fn uevent_send(action: KObjectAction, devpath: &str, envp: &[&str]) -> Result<(), SystemError> {
+    let seqnum = UEVENT_SEQNUM.fetch_add(1, Ordering::SeqCst) + 1;
+
+    // 消息格式："<action>@<devpath>\0KEY=VALUE\0KEY=VALUE\0..."
+    let mut buf: Vec<u8> = Vec::with_capacity(256);
+    let mut add_var = |var: &str| -> Result<(), SystemError> {
+        if buf.len() + var.len() + 1 > UEVENT_BUFFER_SIZE {
+            return Err(SystemError::ENOMEM);
+        }
+        buf.extend_from_slice(var.as_bytes());
+        buf.push(0);
+        Ok(())
+    };
+
+    add_var(&format!("{}@{}", action.as_str(), devpath))?;
+    add_var(&format!("ACTION={}", action.as_str()))?;
+    add_var(&format!("DEVPATH={}", devpath))?;
+    for env in envp {
+        add_var(env)?;
+    }
+    add_var(&format!("SEQNUM={}", seqnum))?;
+
+    netlink_broadcast_uevent(&buf)
+}
@@ -0,0 +1,538 @@
+//! 基于内存页的RAM disk块设备
+
+use alloc::{
+    collections::{btree_map::Entry, BTreeMap},
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{any::Any, fmt::Debug};
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    driver::base::{
+        block::{
+            block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE},
+            disk_info::Partition,
+            manager::BlockDevMeta,
+        },
+        class::Class,
+        device::{
+            bus::Bus,
+            device_number::{DeviceNumber, Major},
+            driver::Driver,
+            DevName, Device, DeviceCommonData, DeviceType, IdTable,
+        },
+        kobject::{KObjType, KObject, KObjectCommonData, KObjectState, LockedKObjectState},
+        kset::KSet,
+    },
+    filesystem::{
+        devfs::{DevFS, DeviceINode, LockedDevFSInode},
+        kernfs::KernFSInode,
+        vfs::{utils::DName, FilePrivateData, IndexNode, InodeFlags, InodeId, InodeMode, Metadata},
+    },
+    libs::{
+        mutex::MutexGuard,
+        rwlock::RwLock,
+        rwsem::{RwSemReadGuard, RwSemWriteGuard},
+        spinlock::{SpinLock, SpinLockGuard},
+    },
+    mm::{
+        allocator::page_frame::{
+            allocate_page_frames, deallocate_page_frames, PageFrameCount, PhysPageFrame,
+        },
+        MemoryManagementArch, PhysAddr, VirtAddr,
+    },
+};
+
+pub(super) const BRD_BASENAME: &str = "ram";
+
+/// RAM disk的一个数据页，第一次写入时分配，设备销毁时释放
+struct BrdPage {
+    paddr: PhysAddr,
+}
+
+impl BrdPage {
+    fn new() -> Result<Self, SystemError> {
+        let (paddr, _) =
+            unsafe { allocate_page_frames(PageFrameCount::ONE) }.ok_or(SystemError::ENOMEM)?;
+        let page = Self { paddr };
+        page.as_mut_slice().fill(0);
+        Ok(page)
+    }
+
+    fn vaddr(&self) -> VirtAddr {
+        unsafe { MMArch::phys_2_virt(self.paddr) }.unwrap()
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.vaddr().data() as *const u8, MMArch::PAGE_SIZE) }
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    fn as_mut_slice(&self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(self.vaddr().data() as *mut u8, MMArch::PAGE_SIZE)
+        }
+    }
+}
+
+impl Drop for BrdPage {
+    fn drop(&mut self) {
+        unsafe { deallocate_page_frames(PhysPageFrame::new(self.paddr), PageFrameCount::ONE) };
+    }
+}
+
+#[derive(Debug)]
+struct InnerBrdDevice {
+    device_common: DeviceCommonData,
+    kobject_common: KObjectCommonData,
+}
+
+#[cast_to([sync] Device)]
+pub struct BrdDevice {
+    blkdev_meta: BlockDevMeta,
+    inner: SpinLock<InnerBrdDevice>,
+    locked_kobj_state: LockedKObjectState,
+    self_ref: Weak<Self>,
+    parent: RwLock<Weak<LockedDevFSInode>>,
+    fs: RwLock<Weak<DevFS>>,
+    metadata: Metadata,
+    /// 设备容量（字节）
+    size: usize,
+    /// 已分配的数据页，key为页号
+    pages: SpinLock<BTreeMap<usize, BrdPage>>,
+}
+
+impl Debug for BrdDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BrdDevice")
+            .field("devname", &self.blkdev_meta.devname)
+            .field("size", &self.size)
+            .field("allocated_pages", &self.allocated_pages())
+            .finish()
+    }
+}
+
+impl BrdDevice {
+    /// 创建一个RAM disk
+    ///
+    /// ## 参数
+    ///
+    /// - `size`: 设备容量（字节），向下对齐到扇区大小
+    /// - `id`: 设备编号，设备名为`ram{id}`
+    pub fn new(size: usize, id: usize) -> Arc<Self> {
+        let size = size / LBA_SIZE * LBA_SIZE;
+        let devname = DevName::new(format!("{BRD_BASENAME}{id}"), id);
+
+        Arc::new_cyclic(|self_ref| {
+            let blkdev_meta = BlockDevMeta::new(devname, Major::RAMDISK_MAJOR);
+            let raw_dev = DeviceNumber::new(blkdev_meta.major, blkdev_meta.base_minor);
+
+            Self {
+                blkdev_meta,
+                inner: SpinLock::new(InnerBrdDevice {
+                    device_common: DeviceCommonData::default(),
+                    kobject_common: KObjectCommonData::default(),
+                }),
+                locked_kobj_state: LockedKObjectState::default(),
+                self_ref: self_ref.clone(),
+                parent: RwLock::new(Weak::default()),
+                fs: RwLock::new(Weak::default()),
+                metadata: Metadata {
+                    dev_id: 0,
+                    inode_id: InodeId::new(0),
+                    size: (size.min(i64::MAX as usize)) as i64,
+                    blk_size: LBA_SIZE,
+                    blocks: size / LBA_SIZE,
+                    atime: Default::default(),
+                    mtime: Default::default(),
+                    ctime: Default::default(),
+                    btime: Default::default(),
+                    file_type: crate::filesystem::vfs::FileType::BlockDevice,
+                    mode: InodeMode::from_bits_truncate(0o660),
+                    flags: InodeFlags::empty(),
+                    nlinks: 1,
+                    uid: 0,
+                    gid: 0,
+                    raw_dev,
+                },
+                size,
+                pages: SpinLock::new(BTreeMap::new()),
+            }
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// 已经分配的数据页数量
+    pub fn allocated_pages(&self) -> usize {
+        self.pages.lock().len()
+    }
+
+    fn inner(&self) -> SpinLockGuard<'_, InnerBrdDevice> {
+        self.inner.lock_irqsave()
+    }
+
+    /// 检查IO范围，返回(字节偏移, 字节长度)
+    fn check_range(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf_len: usize,
+    ) -> Result<(usize, usize), SystemError> {
+        let offset = lba_id_start
+            .checked_mul(LBA_SIZE)
+            .ok_or(SystemError::EOVERFLOW)?;
+        let len = count.checked_mul(LBA_SIZE).ok_or(SystemError::EOVERFLOW)?;
+        if len > buf_len {
+            return Err(SystemError::EINVAL);
+        }
+
+        let end = offset.checked_add(len).ok_or(SystemError::EOVERFLOW)?;
+        if end > self.size {
+            return Err(SystemError::ENOSPC);
+        }
+        Ok((offset, len))
+    }
+}
+
+impl IndexNode for BrdDevice {
+    fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
+        self.fs.read().upgrade().expect("BrdDevice fs is not set")
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let parent = self.parent.read();
+        if let Some(parent) = parent.upgrade() {
+            return Ok(parent as Arc<dyn IndexNode>);
+        }
+        Err(SystemError::ENOENT)
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn dname(&self) -> Result<DName, SystemError> {
+        Ok(DName::from(self.blkdev_meta.devname.clone().as_ref()))
+    }
+
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _mode: &crate::filesystem::vfs::file::FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+}
+
+impl DeviceINode for BrdDevice {
+    fn set_fs(&self, fs: Weak<DevFS>) {
+        *self.fs.write() = fs;
+    }
+
+    fn set_parent(&self, parent: Weak<LockedDevFSInode>) {
+        *self.parent.write() = parent;
+    }
+}
+
+impl BlockDevice for BrdDevice {
+    fn dev_name(&self) -> &DevName {
+        &self.blkdev_meta.devname
+    }
+
+    fn blkdev_meta(&self) -> &BlockDevMeta {
+        &self.blkdev_meta
+    }
+
+    fn disk_range(&self) -> GeneralBlockRange {
+        let blocks = self.size / LBA_SIZE;
+        GeneralBlockRange::new(0, blocks).unwrap_or(GeneralBlockRange {
+            lba_start: 0,
+            lba_end: 0,
+        })
+    }
+
+    fn read_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let (mut offset, len) = self.check_range(lba_id_start, count, buf.len())?;
+
+        let pages = self.pages.lock();
+        let mut done = 0;
+        while done < len {
+            let page_off = offset % MMArch::PAGE_SIZE;
+            let chunk = (MMArch::PAGE_SIZE - page_off).min(len - done);
+            let dst = &mut buf[done..done + chunk];
+            // 没有写入过的页读出来全为0
+            match pages.get(&(offset / MMArch::PAGE_SIZE)) {
+                Some(page) => dst.copy_from_slice(&page.as_slice()[page_off..page_off + chunk]),
+                None => dst.fill(0),
+            }
+            offset += chunk;
+            done += chunk;
+        }
+
+        Ok(len)
+    }
+
+    fn write_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let (mut offset, len) = self.check_range(lba_id_start, count, buf.len())?;
+
+        let mut pages = self.pages.lock();
+        let mut done = 0;
+        while done < len {
+            let page_off = offset % MMArch::PAGE_SIZE;
+            let chunk = (MMArch::PAGE_SIZE - page_off).min(len - done);
+            let page = match pages.entry(offset / MMArch::PAGE_SIZE) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => e.insert(BrdPage::new()?),
+            };
+            page.as_mut_slice()[page_off..page_off + chunk]
+                .copy_from_slice(&buf[done..done + chunk]);
+            offset += chunk;
+            done += chunk;
+        }
+
+        Ok(len)
+    }
+
+    fn sync(&self) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    /// 释放被完整覆盖的数据页，首尾不足一页的部分清零，之后读出来全为0
+    fn discard(&self, range: GeneralBlockRange) -> Result<(), SystemError> {
+        let count = range.lba_end - range.lba_start;
+        if count == 0 {
+            return Ok(());
+        }
+        let (offset, len) = self.check_range(range.lba_start, count, usize::MAX)?;
+        let end = offset + len;
+
+        let mut pages = self.pages.lock();
+        let first_full = offset.div_ceil(MMArch::PAGE_SIZE);
+        let last_full = end / MMArch::PAGE_SIZE;
+        if first_full < last_full {
+            let freed: Vec<usize> = pages
+                .range(first_full..last_full)
+                .map(|(idx, _)| *idx)
+                .collect();
+            for idx in freed {
+                pages.remove(&idx);
+            }
+        }
+
+        let zero_partial = |start: usize, end: usize| {
+            if start >= end {
+                return;
+            }
+            if let Some(page) = pages.get(&(start / MMArch::PAGE_SIZE)) {
+                let page_off = start % MMArch::PAGE_SIZE;
+                page.as_mut_slice()[page_off..page_off + (end - start)].fill(0);
+            }
+        };
+        if first_full > last_full {
+            // 范围落在同一页内
+            zero_partial(offset, end);
+        } else {
+            zero_partial(offset, (first_full * MMArch::PAGE_SIZE).min(end));
+            zero_partial((last_full * MMArch::PAGE_SIZE).max(offset), end);
+        }
+        Ok(())
+    }
+
+    fn blk_size_log2(&self) -> u8 {
+        9
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn device(&self) -> Arc<dyn Device> {
+        self.self_ref.upgrade().unwrap()
+    }
+
+    fn block_size(&self) -> usize {
+        LBA_SIZE
+    }
+
+    fn partitions(&self) -> Vec<Arc<Partition>> {
+        self.blkdev_meta().partitions()
+    }
+}
+
+impl Device for BrdDevice {
+    fn dev_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id_table(&self) -> IdTable {
+        IdTable::new(BRD_BASENAME.to_string(), None)
+    }
+
+    fn bus(&self) -> Option<Weak<dyn Bus>> {
+        self.inner().device_common.bus.clone()
+    }
+
+    fn set_bus(&self, bus: Option<Weak<dyn Bus>>) {
+        self.inner().device_common.bus = bus;
+    }
+
+    fn class(&self) -> Option<Arc<dyn Class>> {
+        let mut guard = self.inner();
+        let class = guard.device_common.class.clone()?.upgrade();
+        if class.is_none() {
+            guard.device_common.class = None;
+        }
+        class
+    }
+
+    fn set_class(&self, class: Option<Weak<dyn Class>>) {
+        self.inner().device_common.class = class;
+    }
+
+    fn driver(&self) -> Option<Arc<dyn Driver>> {
+        let mut guard = self.inner();
+        let driver = guard.device_common.driver.clone()?.upgrade();
+        if driver.is_none() {
+            guard.device_common.driver = None;
+        }
+        driver
+    }
+
+    fn set_driver(&self, driver: Option<Weak<dyn Driver>>) {
+        self.inner().device_common.driver = driver;
+    }
+
+    fn is_dead(&self) -> bool {
+        false
+    }
+
+    fn can_match(&self) -> bool {
+        self.inner().device_common.can_match
+    }
+
+    fn set_can_match(&self, can_match: bool) {
+        self.inner().device_common.can_match = can_match;
+    }
+
+    fn state_synced(&self) -> bool {
+        true
+    }
+
+    fn dev_parent(&self) -> Option<Weak<dyn Device>> {
+        self.inner().device_common.get_parent_weak_or_clear()
+    }
+
+    fn set_dev_parent(&self, parent: Option<Weak<dyn Device>>) {
+        self.inner().device_common.parent = parent;
+    }
+}
+
+impl KObject for BrdDevice {
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn set_inode(&self, inode: Option<Arc<KernFSInode>>) {
+        self.inner().kobject_common.kern_inode = inode;
+    }
+
+    fn inode(&self) -> Option<Arc<KernFSInode>> {
+        self.inner().kobject_common.kern_inode.clone()
+    }
+
+    fn parent(&self) -> Option<Weak<dyn KObject>> {
+        self.inner().kobject_common.parent.clone()
+    }
+
+    fn set_parent(&self, parent: Option<Weak<dyn KObject>>) {
+        self.inner().kobject_common.parent = parent;
+    }
+
+    fn kset(&self) -> Option<Arc<KSet>> {
+        self.inner().kobject_common.kset.clone()
+    }
+
+    fn set_kset(&self, kset: Option<Arc<KSet>>) {
+        self.inner().kobject_common.kset = kset;
+    }
+
+    fn kobj_type(&self) -> Option<&'static dyn KObjType> {
+        self.inner().kobject_common.kobj_type
+    }
+
+    fn name(&self) -> String {
+        self.dev_name().to_string()
+    }
+
+    fn set_name(&self, _name: String) {}
+
+    fn kobj_state(&self) -> RwSemReadGuard<'_, KObjectState> {
+        self.locked_kobj_state.read()
+    }
+
+    fn kobj_state_mut(&self) -> RwSemWriteGuard<'_, KObjectState> {
+        self.locked_kobj_state.write()
+    }
+
+    fn set_kobj_state(&self, state: KObjectState) {
+        *self.locked_kobj_state.write() = state;
+    }
+
+    fn set_kobj_type(&self, ktype: Option<&'static dyn KObjType>) {
+        self.inner().kobject_common.kobj_type = ktype;
+    }
+}
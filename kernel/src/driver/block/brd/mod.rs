@@ -0,0 +1,54 @@
+//! RAM disk驱动（brd）
+//!
+//! 启动时按照内核命令行参数创建`/dev/ram0`~`/dev/ram{rd_nr-1}`，每个设备的容量为`rd_size` KiB。
+//! 设备的数据页在第一次写入时才分配，未写入的区域读出来全为0。
+//! discard会释放被完整覆盖的数据页，可以用`blkdiscard`回收内存。
+
+mod device;
+
+use alloc::{string::ToString, sync::Arc};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    driver::base::block::{block_device::BlockDevice, manager::block_dev_manager},
+    init::initcall::INITCALL_DEVICE,
+};
+
+use self::device::BrdDevice;
+
+kernel_cmdline_param_kv!(RD_NR_PARAM, rd_nr, "");
+kernel_cmdline_param_kv!(RD_SIZE_PARAM, rd_size, "");
+
+/// 默认创建的RAM disk数量
+const DEFAULT_RD_NR: usize = 16;
+/// 默认的RAM disk容量（KiB）
+const DEFAULT_RD_SIZE_KB: usize = 4096;
+/// 最多创建的RAM disk数量
+const MAX_RD_NR: usize = 256;
+
+fn param_usize(value: Option<&str>, default: usize) -> usize {
+    value
+        .and_then(|s| s.trim().parse::<usize>().ok())
+        .unwrap_or(default)
+}
+
+#[unified_init(INITCALL_DEVICE)]
+fn brd_init() -> Result<(), SystemError> {
+    let nr = param_usize(RD_NR_PARAM.value_str(), DEFAULT_RD_NR).min(MAX_RD_NR);
+    let size_kb = param_usize(RD_SIZE_PARAM.value_str(), DEFAULT_RD_SIZE_KB);
+    let size = size_kb.checked_mul(1024).ok_or(SystemError::EINVAL)?;
+    if nr == 0 || size == 0 {
+        return Ok(());
+    }
+
+    for id in 0..nr {
+        let dev = BrdDevice::new(size, id);
+        let dev_name = dev.dev_name().to_string();
+        block_dev_manager().register(dev.clone() as Arc<dyn BlockDevice>)?;
+        log::debug!("brd: registered /dev/{} size={:#x}", dev_name, dev.size());
+    }
+    log::info!("brd: module loaded, {} devices of {} KiB", nr, size_kb);
+
+    Ok(())
+}
@@ -0,0 +1,495 @@
+use crate::{
+    driver::base::{
+        class::Class,
+        device::{
+            bus::Bus,
+            device_number::{DeviceNumber, Major},
+            driver::Driver,
+            Device, DeviceCommonData, DeviceType, IdTable,
+        },
+        kobject::{KObjType, KObject, KObjectCommonData, KObjectState, LockedKObjectState},
+        kset::KSet,
+    },
+    filesystem::{
+        devfs::{DevFS, DevNodeAttr, DeviceINode, LockedDevFSInode},
+        kernfs::KernFSInode,
+        vfs::{
+            file::FileFlags, FilePrivateData, FileType, IndexNode, InodeFlags, InodeId, InodeMode,
+            Metadata,
+        },
+    },
+    libs::{
+        mutex::MutexGuard,
+        rwlock::RwLock,
+        rwsem::{RwSemReadGuard, RwSemWriteGuard},
+        spinlock::{SpinLock, SpinLockGuard},
+    },
+    process::ProcessManager,
+    time::PosixTimeSpec,
+};
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    fmt::{Debug, Formatter},
+};
+use system_error::SystemError;
+
+use super::{
+    device::DmDevice,
+    ioctl::{
+        DmCommand, DmFlags, DmIoctlParam, DM_VERSION_MAJOR, DM_VERSION_MINOR, DM_VERSION_PATCHLEVEL,
+    },
+    manager::DmManager,
+    table::{DmTable, DM_TARGET_TYPES},
+};
+
+/// 控制设备的名字（sysfs中）
+pub const DM_CONTROL_NAME: &str = "device-mapper";
+/// 控制设备在/dev下的路径
+pub const DM_CONTROL_NODE: &str = "mapper/control";
+/// 控制设备的次设备号
+const DM_CONTROL_MINOR: u32 = 236;
+
+/// `/dev/mapper/control`
+///
+/// 一个字符设备，用户态（例如dmsetup）通过它的ioctl创建、删除device-mapper设备，
+/// 以及加载映射表、挂起和恢复设备，参见[`super::ioctl`]
+pub struct DmControlDevice {
+    inner: SpinLock<DmControlDeviceInner>,
+    locked_kobj_state: LockedKObjectState,
+    dm_mgr: Arc<DmManager>,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
+}
+
+struct DmControlDeviceInner {
+    device_common: DeviceCommonData,
+    kobject_common: KObjectCommonData,
+
+    parent: RwLock<Weak<LockedDevFSInode>>,
+    device_inode_fs: RwLock<Option<Weak<DevFS>>>,
+}
+
+impl DmControlDevice {
+    pub fn new(dm_mgr: Arc<DmManager>) -> Arc<Self> {
+        Arc::new(Self {
+            inner: SpinLock::new(DmControlDeviceInner {
+                kobject_common: KObjectCommonData::default(),
+                device_common: DeviceCommonData::default(),
+                parent: RwLock::new(Weak::default()),
+                device_inode_fs: RwLock::new(None),
+            }),
+            locked_kobj_state: LockedKObjectState::default(),
+            dm_mgr,
+            attr: DevNodeAttr::new(InodeMode::from_bits_truncate(0o600)),
+        })
+    }
+
+    fn inner(&'_ self) -> SpinLockGuard<'_, DmControlDeviceInner> {
+        self.inner.lock()
+    }
+
+    /// 把设备的状态填入ioctl的头部
+    fn fill_status(param: &mut DmIoctlParam, dev: &DmDevice) {
+        let query_inactive = param.header.flags().contains(DmFlags::QUERY_INACTIVE_TABLE);
+        let live = dev.live_table();
+        let inactive = dev.inactive_table();
+
+        let mut flags = param.header.flags();
+        flags.remove(
+            DmFlags::SUSPEND
+                | DmFlags::READONLY
+                | DmFlags::ACTIVE_PRESENT
+                | DmFlags::INACTIVE_PRESENT,
+        );
+        flags.set(DmFlags::SUSPEND, dev.is_suspended());
+        flags.set(DmFlags::ACTIVE_PRESENT, live.is_some());
+        flags.set(DmFlags::INACTIVE_PRESENT, inactive.is_some());
+        let table = if query_inactive { inactive } else { live };
+        if let Some(table) = table.as_ref() {
+            flags.set(DmFlags::READONLY, table.is_read_only());
+        }
+
+        let header = &mut param.header;
+        header.flags = flags.bits();
+        header.dev = dev.device_number().new_encode_dev() as u64;
+        header.open_count = 0;
+        header.event_nr = 0;
+        header.target_count = table.map(|t| t.entries().len() as u32).unwrap_or(0);
+        header.set_name(&dev.dm_name());
+        header.set_uuid(&dev.uuid());
+    }
+
+    /// 输出映射表中每个目标的状态或者参数
+    fn table_status(param: &mut DmIoctlParam, dev: &DmDevice) {
+        Self::fill_status(param, dev);
+        let flags = param.header.flags();
+        let table = if flags.contains(DmFlags::QUERY_INACTIVE_TABLE) {
+            dev.inactive_table()
+        } else {
+            dev.live_table()
+        };
+        let Some(table) = table else {
+            return;
+        };
+        let targets: Vec<(usize, usize, &str, String)> = table
+            .entries()
+            .iter()
+            .map(|e| {
+                let status = if flags.contains(DmFlags::STATUS_TABLE) {
+                    e.target.table()
+                } else {
+                    e.target.status()
+                };
+                (e.start, e.len, e.target_type, status)
+            })
+            .collect();
+        param.write_target_status(&targets);
+    }
+
+    fn do_ioctl(&self, cmd: DmCommand, param: &mut DmIoctlParam) -> Result<(), SystemError> {
+        match cmd {
+            DmCommand::Version => Ok(()),
+            DmCommand::RemoveAll => {
+                let busy = self.dm_mgr.remove_all();
+                if busy != 0 {
+                    log::warn!("device-mapper: {} device(s) could not be removed", busy);
+                }
+                Ok(())
+            }
+            DmCommand::ListDevices => {
+                let devices: Vec<(u64, String)> = self
+                    .dm_mgr
+                    .list()
+                    .iter()
+                    .map(|dev| (dev.device_number().new_encode_dev() as u64, dev.dm_name()))
+                    .collect();
+                param.write_name_list(&devices);
+                Ok(())
+            }
+            DmCommand::ListVersions => {
+                let versions: Vec<(&str, [u32; 3])> = DM_TARGET_TYPES
+                    .iter()
+                    .map(|(name, version, _)| (*name, *version))
+                    .collect();
+                param.write_target_versions(&versions);
+                Ok(())
+            }
+            DmCommand::DevCreate => {
+                let dev = self
+                    .dm_mgr
+                    .create(param.header.name()?, param.header.uuid()?)?;
+                Self::fill_status(param, &dev);
+                Ok(())
+            }
+            DmCommand::DevRemove => {
+                let dev = self.dm_mgr.find(&param.header)?;
+                self.dm_mgr.remove(&dev)
+            }
+            DmCommand::DevRename => {
+                let dev = self.dm_mgr.find(&param.header)?;
+                let new = param.input_str()?.to_string();
+                if param.header.flags().contains(DmFlags::UUID) {
+                    // 与Linux一致，只能为没有uuid的设备设置uuid
+                    if !dev.uuid().is_empty() || new.is_empty() {
+                        return Err(SystemError::EINVAL);
+                    }
+                    dev.set_uuid(&new);
+                } else {
+                    self.dm_mgr.rename(&dev, &new)?;
+                }
+                Self::fill_status(param, &dev);
+                Ok(())
+            }
+            DmCommand::DevSuspend => {
+                let dev = self.dm_mgr.find(&param.header)?;
+                let flags = param.header.flags();
+                if flags.contains(DmFlags::SUSPEND) {
+                    dev.suspend(!flags.contains(DmFlags::NOFLUSH))?;
+                } else {
+                    dev.resume()?;
+                }
+                Self::fill_status(param, &dev);
+                Ok(())
+            }
+            DmCommand::DevStatus => {
+                let dev = self.dm_mgr.find(&param.header)?;
+                Self::fill_status(param, &dev);
+                Ok(())
+            }
+            DmCommand::TableLoad => {
+                let dev = self.dm_mgr.find(&param.header)?;
+                let specs = param.target_specs()?;
+                if specs.is_empty() {
+                    return Err(SystemError::EINVAL);
+                }
+                let read_only = param.header.flags().contains(DmFlags::READONLY);
+                dev.load_table(DmTable::new(&specs, read_only)?);
+                Self::fill_status(param, &dev);
+                Ok(())
+            }
+            DmCommand::TableClear => {
+                let dev = self.dm_mgr.find(&param.header)?;
+                dev.clear_table();
+                Self::fill_status(param, &dev);
+                Ok(())
+            }
+            DmCommand::TableStatus => {
+                let dev = self.dm_mgr.find(&param.header)?;
+                Self::table_status(param, &dev);
+                Ok(())
+            }
+            DmCommand::DevWait
+            | DmCommand::TableDeps
+            | DmCommand::TargetMsg
+            | DmCommand::DevSetGeometry => Err(SystemError::ENOSYS),
+        }
+    }
+}
+
+impl DeviceINode for DmControlDevice {
+    fn set_fs(&self, fs: Weak<DevFS>) {
+        *self.inner().device_inode_fs.write() = Some(fs);
+    }
+
+    fn set_parent(&self, parent: Weak<LockedDevFSInode>) {
+        *self.inner().parent.write() = parent;
+    }
+}
+
+impl Debug for DmControlDevice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DmControlDevice").finish()
+    }
+}
+
+impl IndexNode for DmControlDevice {
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _mode: &FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        let mut metadata = Metadata {
+            dev_id: 0,
+            inode_id: InodeId::new(0),
+            size: 0,
+            blk_size: 0,
+            blocks: 0,
+            atime: PosixTimeSpec::default(),
+            mtime: PosixTimeSpec::default(),
+            ctime: PosixTimeSpec::default(),
+            btime: PosixTimeSpec::default(),
+            file_type: FileType::CharDevice,
+            mode: InodeMode::from_bits_truncate(0o600),
+            flags: InodeFlags::empty(),
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            raw_dev: DeviceNumber::new(Major::MISC_MAJOR, DM_CONTROL_MINOR),
+        };
+        self.attr.apply(&mut metadata);
+        Ok(metadata)
+    }
+
+    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
+        self.attr.update(metadata);
+        Ok(())
+    }
+
+    fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
+        if let Some(fs) = self
+            .inner()
+            .device_inode_fs
+            .read()
+            .as_ref()
+            .and_then(|w| w.upgrade())
+        {
+            return fs;
+        }
+        ProcessManager::current_mntns()
+            .root_inode()
+            .find("dev")
+            .expect("DmControlDevice: DevFS not mounted at /dev")
+            .fs()
+    }
+
+    fn ioctl(
+        &self,
+        cmd: u32,
+        data: usize,
+        _private_data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let command = DmCommand::from_cmd(cmd).ok_or(SystemError::ENOSYS)?;
+        let mut param = DmIoctlParam::read(data)?;
+        param.header.flags &= !DmFlags::BUFFER_FULL.bits();
+        self.do_ioctl(command, &mut param)?;
+        param.header.version = [DM_VERSION_MAJOR, DM_VERSION_MINOR, DM_VERSION_PATCHLEVEL];
+        param.write_back()?;
+        Ok(0)
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+}
+
+impl Device for DmControlDevice {
+    fn dev_type(&self) -> DeviceType {
+        DeviceType::Char
+    }
+
+    fn id_table(&self) -> IdTable {
+        IdTable::new(DM_CONTROL_NAME.to_string(), None)
+    }
+
+    fn bus(&self) -> Option<Weak<dyn Bus>> {
+        self.inner().device_common.bus.clone()
+    }
+
+    fn set_bus(&self, bus: Option<Weak<dyn Bus>>) {
+        self.inner().device_common.bus = bus;
+    }
+
+    fn class(&self) -> Option<Arc<dyn Class>> {
+        let mut guard = self.inner();
+        let r = guard.device_common.class.clone()?.upgrade();
+        if r.is_none() {
+            guard.device_common.class = None;
+        }
+        return r;
+    }
+
+    fn set_class(&self, class: Option<Weak<dyn Class>>) {
+        self.inner().device_common.class = class;
+    }
+
+    fn driver(&self) -> Option<Arc<dyn Driver>> {
+        let r = self.inner().device_common.driver.clone()?.upgrade();
+        if r.is_none() {
+            self.inner().device_common.driver = None;
+        }
+        return r;
+    }
+
+    fn set_driver(&self, driver: Option<Weak<dyn Driver>>) {
+        self.inner().device_common.driver = driver;
+    }
+
+    fn is_dead(&self) -> bool {
+        false
+    }
+
+    fn can_match(&self) -> bool {
+        self.inner().device_common.can_match
+    }
+
+    fn set_can_match(&self, can_match: bool) {
+        self.inner().device_common.can_match = can_match;
+    }
+
+    fn state_synced(&self) -> bool {
+        true
+    }
+
+    fn dev_parent(&self) -> Option<Weak<dyn Device>> {
+        self.inner().device_common.get_parent_weak_or_clear()
+    }
+
+    fn set_dev_parent(&self, parent: Option<Weak<dyn Device>>) {
+        self.inner().device_common.parent = parent;
+    }
+}
+
+impl KObject for DmControlDevice {
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn set_inode(&self, inode: Option<Arc<KernFSInode>>) {
+        self.inner().kobject_common.kern_inode = inode;
+    }
+
+    fn inode(&self) -> Option<Arc<KernFSInode>> {
+        self.inner().kobject_common.kern_inode.clone()
+    }
+
+    fn parent(&self) -> Option<Weak<dyn KObject>> {
+        self.inner().kobject_common.parent.clone()
+    }
+
+    fn set_parent(&self, parent: Option<Weak<dyn KObject>>) {
+        self.inner().kobject_common.parent = parent;
+    }
+
+    fn kset(&self) -> Option<Arc<KSet>> {
+        self.inner().kobject_common.kset.clone()
+    }
+
+    fn set_kset(&self, kset: Option<Arc<KSet>>) {
+        self.inner().kobject_common.kset = kset;
+    }
+
+    fn kobj_type(&self) -> Option<&'static dyn KObjType> {
+        self.inner().kobject_common.kobj_type
+    }
+
+    fn set_kobj_type(&self, ktype: Option<&'static dyn KObjType>) {
+        self.inner().kobject_common.kobj_type = ktype;
+    }
+
+    fn name(&self) -> String {
+        DM_CONTROL_NAME.to_string()
+    }
+
+    fn set_name(&self, _name: String) {
+        // do nothing
+    }
+
+    fn kobj_state(&'_ self) -> RwSemReadGuard<'_, KObjectState> {
+        self.locked_kobj_state.read()
+    }
+
+    fn kobj_state_mut(&'_ self) -> RwSemWriteGuard<'_, KObjectState> {
+        self.locked_kobj_state.write()
+    }
+
+    fn set_kobj_state(&self, state: KObjectState) {
+        *self.locked_kobj_state.write() = state;
+    }
+}
@@ -0,0 +1,211 @@
+//! crypt目标：透明地加密下层设备上的数据
+//!
+//! 参数与Linux的dm-crypt相同：`<加密方式> <密钥> <IV偏移> <设备> <起始扇区> [<可选参数个数> <可选参数>...]`。
+//! 加密方式只支持`aes-xts-plain64`和`aes-xts-plain`，使用[软件实现的XTS-AES](crate::libs::crypto::xts)，
+//! 密钥为64个（XTS-AES-128）或128个（XTS-AES-256）十六进制字符。
+//! 每个扇区是一个数据单元，tweak为扇区号加上IV偏移的小端序表示（`plain`只取低32位），
+//! 因此可以和Linux上用`cryptsetup --type plain -c aes-xts-plain64`打开的设备互相读写。
+//!
+//! 可选参数只支持`allow_discards`（把discard转发给下层设备，会泄露哪些扇区没有被使用）和
+//! `sector_size:512`，与工作队列有关的参数会被忽略。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/md/dm-crypt.c
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::fmt::Debug;
+use system_error::SystemError;
+
+use crate::{
+    driver::base::block::block_device::{BlockId, LBA_SIZE},
+    libs::crypto::{
+        aes::{zeroize, AES_BLOCK_SIZE},
+        xts::XtsAes,
+    },
+};
+
+use super::table::{parse_sector, DmDev, DmTarget};
+
+/// 由扇区号生成tweak的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CryptIvMode {
+    /// 扇区号的低32位
+    Plain,
+    /// 64位扇区号
+    Plain64,
+}
+
+pub struct CryptTarget {
+    dev: DmDev,
+    /// 在下层设备上的起始扇区
+    start: BlockId,
+    /// 加密方式，与映射表中的写法相同
+    cipher: &'static str,
+    iv_mode: CryptIvMode,
+    /// 计算tweak时加到扇区号上的偏移
+    iv_offset: u64,
+    /// 原始密钥，只用于输出映射表
+    key: Vec<u8>,
+    xts: XtsAes,
+    allow_discards: bool,
+}
+
+impl Debug for CryptTarget {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CryptTarget")
+            .field("dev", &self.dev)
+            .field("start", &self.start)
+            .field("cipher", &self.cipher)
+            .field("iv_offset", &self.iv_offset)
+            .field("allow_discards", &self.allow_discards)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CryptTarget {
+    pub fn create(len: usize, args: &[&str]) -> Result<Arc<dyn DmTarget>, SystemError> {
+        let [cipher, key, iv_offset, path, start, opt_args @ ..] = args else {
+            return Err(SystemError::EINVAL);
+        };
+        let (cipher, iv_mode) = match *cipher {
+            "aes-xts-plain64" => ("aes-xts-plain64", CryptIvMode::Plain64),
+            "aes-xts-plain" => ("aes-xts-plain", CryptIvMode::Plain),
+            _ => {
+                log::warn!(
+                    "device-mapper: crypt: unsupported cipher {}, only aes-xts-plain64 and aes-xts-plain are available",
+                    cipher
+                );
+                return Err(SystemError::EINVAL);
+            }
+        };
+        let iv_offset = iv_offset.parse::<u64>().map_err(|_| SystemError::EINVAL)?;
+        let allow_discards = Self::parse_opt_args(opt_args)?;
+        let start = parse_sector(start)?;
+        let dev = DmDev::open(path, start, len)?;
+
+        let mut key = parse_hex_key(key)?;
+        let xts = match XtsAes::new(&key) {
+            Ok(xts) => xts,
+            Err(e) => {
+                zeroize(&mut key);
+                log::warn!("device-mapper: crypt: invalid key for {}", cipher);
+                return Err(e);
+            }
+        };
+        Ok(Arc::new(Self {
+            dev,
+            start,
+            cipher,
+            iv_mode,
+            iv_offset,
+            key,
+            xts,
+            allow_discards,
+        }))
+    }
+
+    /// # 解析可选参数
+    ///
+    /// ## 返回值
+    /// 是否允许discard
+    fn parse_opt_args(args: &[&str]) -> Result<bool, SystemError> {
+        let Some((count, params)) = args.split_first() else {
+            return Ok(false);
+        };
+        if count.parse::<usize>() != Ok(params.len()) {
+            return Err(SystemError::EINVAL);
+        }
+        let mut allow_discards = false;
+        for param in params {
+            match *param {
+                "allow_discards" => allow_discards = true,
+                // 加密在提交I/O的进程中同步进行，没有工作队列
+                "same_cpu_crypt"
+                | "submit_from_crypt_cpus"
+                | "no_read_workqueue"
+                | "no_write_workqueue"
+                | "iv_large_sectors" => {}
+                "sector_size:512" => {}
+                _ => {
+                    log::warn!("device-mapper: crypt: unsupported option {}", param);
+                    return Err(SystemError::EINVAL);
+                }
+            }
+        }
+        Ok(allow_discards)
+    }
+
+    /// 目标内的扇区对应的tweak
+    fn tweak(&self, sector: BlockId) -> [u8; AES_BLOCK_SIZE] {
+        let iv = (sector as u64).wrapping_add(self.iv_offset);
+        let iv = match self.iv_mode {
+            CryptIvMode::Plain => iv & u32::MAX as u64,
+            CryptIvMode::Plain64 => iv,
+        };
+        let mut tweak = [0u8; AES_BLOCK_SIZE];
+        tweak[..8].copy_from_slice(&iv.to_le_bytes());
+        tweak
+    }
+}
+
+impl DmTarget for CryptTarget {
+    fn read(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError> {
+        self.dev.read(self.start + sector, buf)?;
+        for (i, data) in buf.chunks_exact_mut(LBA_SIZE).enumerate() {
+            self.xts.decrypt(&self.tweak(sector + i), data);
+        }
+        Ok(())
+    }
+
+    fn write(&self, sector: BlockId, buf: &[u8]) -> Result<(), SystemError> {
+        // 不能修改调用者的缓冲区，它可能还在块缓存中
+        let mut ciphertext = buf.to_vec();
+        for (i, data) in ciphertext.chunks_exact_mut(LBA_SIZE).enumerate() {
+            self.xts.encrypt(&self.tweak(sector + i), data);
+        }
+        self.dev.write(self.start + sector, &ciphertext)
+    }
+
+    fn discard(&self, sector: BlockId, count: usize) -> Result<(), SystemError> {
+        if !self.allow_discards {
+            return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+        }
+        self.dev.discard(self.start + sector, count)
+    }
+
+    fn flush(&self) -> Result<(), SystemError> {
+        self.dev.flush()
+    }
+
+    fn table(&self) -> String {
+        let key: String = self.key.iter().map(|b| format!("{:02x}", b)).collect();
+        let mut s = format!(
+            "{} {} {} {} {}",
+            self.cipher,
+            key,
+            self.iv_offset,
+            self.dev.name(),
+            self.start
+        );
+        if self.allow_discards {
+            s.push_str(" 1 allow_discards");
+        }
+        s
+    }
+}
+
+impl Drop for CryptTarget {
+    fn drop(&mut self) {
+        zeroize(&mut self.key);
+    }
+}
+
+/// 解析十六进制的密钥
+fn parse_hex_key(s: &str) -> Result<Vec<u8>, SystemError> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return Err(SystemError::EINVAL);
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| SystemError::EINVAL))
+        .collect()
+}
@@ -0,0 +1,573 @@
+//! device-mapper设备（/dev/dm-N）
+
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{any::Any, fmt::Debug};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::{
+        block::{
+            block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE},
+            cache::block_cache,
+            disk_info::Partition,
+            manager::{block_dev_manager, BlockDevMeta},
+        },
+        class::Class,
+        device::{
+            bus::Bus,
+            device_number::{DeviceNumber, Major},
+            driver::Driver,
+            DevName, Device, DeviceCommonData, DeviceType, IdTable,
+        },
+        kobject::{KObjType, KObject, KObjectCommonData, KObjectState, LockedKObjectState},
+        kset::KSet,
+    },
+    filesystem::{
+        devfs::{DevFS, DeviceINode, LockedDevFSInode},
+        kernfs::KernFSInode,
+        vfs::{utils::DName, FilePrivateData, IndexNode, InodeFlags, InodeId, InodeMode, Metadata},
+    },
+    libs::{
+        mutex::MutexGuard,
+        rwlock::RwLock,
+        rwsem::{RwSemReadGuard, RwSemWriteGuard},
+        spinlock::{SpinLock, SpinLockGuard},
+        wait_queue::WaitQueue,
+    },
+};
+
+use super::table::DmTable;
+
+pub(super) const DM_BASENAME: &str = "dm-";
+
+#[derive(Debug)]
+struct InnerDmDevice {
+    device_common: DeviceCommonData,
+    kobject_common: KObjectCommonData,
+}
+
+/// 设备的映射表和挂起状态
+#[derive(Debug, Default)]
+struct DmState {
+    /// 正在生效的映射表
+    live: Option<Arc<DmTable>>,
+    /// 已加载、在下一次恢复时生效的映射表
+    inactive: Option<Arc<DmTable>>,
+    suspended: bool,
+    /// 正在处理的I/O数
+    in_flight: usize,
+}
+
+/// 一个device-mapper设备
+///
+/// 设备刚创建时没有映射表，容量为0。通过TABLE_LOAD加载的映射表在DEV_SUSPEND恢复设备时生效；
+/// 设备挂起期间新的I/O会等待，直到设备恢复。
+#[cast_to([sync] Device)]
+pub struct DmDevice {
+    blkdev_meta: BlockDevMeta,
+    inner: SpinLock<InnerDmDevice>,
+    locked_kobj_state: LockedKObjectState,
+    self_ref: Weak<Self>,
+    parent: RwLock<Weak<LockedDevFSInode>>,
+    fs: RwLock<Weak<DevFS>>,
+    /// 设备名，即/dev/mapper/下的名字
+    name: SpinLock<String>,
+    uuid: SpinLock<String>,
+    state: SpinLock<DmState>,
+    /// 等待设备恢复，或者等待挂起时正在处理的I/O完成
+    wait_queue: WaitQueue,
+}
+
+impl Debug for DmDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DmDevice")
+            .field("devname", &self.blkdev_meta.devname)
+            .field("name", &self.dm_name())
+            .finish()
+    }
+}
+
+/// 一次I/O期间持有的映射表，drop时结束I/O
+struct DmIo<'a> {
+    dev: &'a DmDevice,
+    table: Arc<DmTable>,
+}
+
+impl Drop for DmIo<'_> {
+    fn drop(&mut self) {
+        let mut state = self.dev.state.lock_irqsave();
+        state.in_flight -= 1;
+        let wake = state.suspended && state.in_flight == 0;
+        drop(state);
+        if wake {
+            self.dev.wait_queue.wake_all();
+        }
+    }
+}
+
+impl DmDevice {
+    /// # 创建设备
+    ///
+    /// ## 参数
+    /// - `id`: 设备编号，设备节点为`/dev/dm-{id}`
+    /// - `name`: 设备名
+    /// - `uuid`: 设备的uuid，可以为空
+    pub fn new(id: usize, name: &str, uuid: &str) -> Arc<Self> {
+        let devname = DevName::new(format!("{DM_BASENAME}{id}"), id);
+        Arc::new_cyclic(|self_ref| Self {
+            blkdev_meta: BlockDevMeta::new(devname, Major::DEVICE_MAPPER_MAJOR),
+            inner: SpinLock::new(InnerDmDevice {
+                device_common: DeviceCommonData::default(),
+                kobject_common: KObjectCommonData::default(),
+            }),
+            locked_kobj_state: LockedKObjectState::default(),
+            self_ref: self_ref.clone(),
+            parent: RwLock::new(Weak::default()),
+            fs: RwLock::new(Weak::default()),
+            name: SpinLock::new(name.to_string()),
+            uuid: SpinLock::new(uuid.to_string()),
+            state: SpinLock::new(DmState::default()),
+            wait_queue: WaitQueue::default(),
+        })
+    }
+
+    fn inner(&self) -> SpinLockGuard<'_, InnerDmDevice> {
+        self.inner.lock_irqsave()
+    }
+
+    pub fn dm_name(&self) -> String {
+        self.name.lock().clone()
+    }
+
+    pub fn set_dm_name(&self, name: &str) {
+        *self.name.lock() = name.to_string();
+    }
+
+    pub fn uuid(&self) -> String {
+        self.uuid.lock().clone()
+    }
+
+    pub fn set_uuid(&self, uuid: &str) {
+        *self.uuid.lock() = uuid.to_string();
+    }
+
+    /// 整个设备的设备号
+    pub fn device_number(&self) -> DeviceNumber {
+        DeviceNumber::new(self.blkdev_meta.major, self.blkdev_meta.base_minor)
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.state.lock_irqsave().suspended
+    }
+
+    /// 正在生效的映射表
+    pub fn live_table(&self) -> Option<Arc<DmTable>> {
+        self.state.lock_irqsave().live.clone()
+    }
+
+    /// 已加载、尚未生效的映射表
+    pub fn inactive_table(&self) -> Option<Arc<DmTable>> {
+        self.state.lock_irqsave().inactive.clone()
+    }
+
+    /// 加载映射表，在下一次恢复设备时生效。已经加载的未生效映射表会被替换
+    pub fn load_table(&self, table: Arc<DmTable>) {
+        self.state.lock_irqsave().inactive = Some(table);
+    }
+
+    /// 丢弃已加载、尚未生效的映射表
+    pub fn clear_table(&self) {
+        self.state.lock_irqsave().inactive = None;
+    }
+
+    /// # 挂起设备
+    ///
+    /// 写回缓存中的数据，然后阻止新的I/O，并等待正在处理的I/O完成
+    ///
+    /// ## 参数
+    /// - `flush`: 是否在挂起前写回缓存中的数据
+    pub fn suspend(&self, flush: bool) -> Result<(), SystemError> {
+        if self.is_suspended() {
+            return Ok(());
+        }
+        if flush {
+            let bdev = self.self_ref.upgrade().unwrap() as Arc<dyn BlockDevice>;
+            block_cache().sync_disk(&bdev)?;
+        }
+        self.state.lock_irqsave().suspended = true;
+        self.wait_queue
+            .wait_until(|| (self.state.lock_irqsave().in_flight == 0).then_some(()));
+        if let Some(table) = self.live_table() {
+            table.flush()?;
+        }
+        Ok(())
+    }
+
+    /// # 恢复设备
+    ///
+    /// 如果有已加载的映射表，先让它生效，再恢复I/O
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::ENXIO)`: 设备没有任何映射表
+    pub fn resume(&self) -> Result<(), SystemError> {
+        let old_sectors = self.disk_range().len();
+        let (swapped, read_only) = {
+            let mut state = self.state.lock_irqsave();
+            let swapped = match state.inactive.take() {
+                Some(table) => {
+                    // 换表需要设备处于挂起状态，保证旧表上没有正在处理的I/O
+                    if !state.suspended && state.live.is_some() {
+                        state.inactive = Some(table);
+                        return Err(SystemError::EINVAL);
+                    }
+                    state.live = Some(table);
+                    true
+                }
+                None => false,
+            };
+            let Some(live) = state.live.as_ref() else {
+                return Err(SystemError::ENXIO);
+            };
+            let read_only = live.is_read_only();
+            state.suspended = false;
+            (swapped, read_only)
+        };
+        self.wait_queue.wake_all();
+
+        if swapped {
+            self.blkdev_meta.set_read_only(read_only);
+            let bdev = self.self_ref.upgrade().unwrap() as Arc<dyn BlockDevice>;
+            if old_sectors == 0 {
+                block_dev_manager().notify_media_change(&bdev);
+            } else {
+                block_cache().invalidate_disk(&bdev);
+                block_dev_manager().notify_capacity_change(&bdev, old_sectors);
+            }
+        }
+        Ok(())
+    }
+
+    /// 开始一次I/O，设备挂起时等待设备恢复
+    fn start_io(&self) -> Result<DmIo<'_>, SystemError> {
+        let table = self
+            .wait_queue
+            .wait_until(|| {
+                let mut state = self.state.lock_irqsave();
+                if state.suspended {
+                    return None;
+                }
+                let table = state.live.clone();
+                if table.is_some() {
+                    state.in_flight += 1;
+                }
+                Some(table)
+            })
+            .ok_or(SystemError::ENXIO)?;
+        Ok(DmIo { dev: self, table })
+    }
+}
+
+impl IndexNode for DmDevice {
+    fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
+        self.fs.read().upgrade().expect("DmDevice fs is not set")
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        let blocks = self.disk_range().len();
+        Ok(Metadata {
+            dev_id: 0,
+            inode_id: InodeId::new(0),
+            size: (blocks * LBA_SIZE) as i64,
+            blk_size: LBA_SIZE,
+            blocks,
+            atime: Default::default(),
+            mtime: Default::default(),
+            ctime: Default::default(),
+            btime: Default::default(),
+            file_type: crate::filesystem::vfs::FileType::BlockDevice,
+            mode: InodeMode::from_bits_truncate(0o660),
+            flags: InodeFlags::empty(),
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            raw_dev: self.device_number(),
+        })
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let parent = self.parent.read();
+        if let Some(parent) = parent.upgrade() {
+            return Ok(parent as Arc<dyn IndexNode>);
+        }
+        Err(SystemError::ENOENT)
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn dname(&self) -> Result<DName, SystemError> {
+        Ok(DName::from(self.blkdev_meta.devname.clone().as_ref()))
+    }
+
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _mode: &crate::filesystem::vfs::file::FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+}
+
+impl DeviceINode for DmDevice {
+    fn set_fs(&self, fs: Weak<DevFS>) {
+        *self.fs.write() = fs;
+    }
+
+    fn set_parent(&self, parent: Weak<LockedDevFSInode>) {
+        *self.parent.write() = parent;
+    }
+}
+
+impl BlockDevice for DmDevice {
+    fn dev_name(&self) -> &DevName {
+        &self.blkdev_meta.devname
+    }
+
+    fn blkdev_meta(&self) -> &BlockDevMeta {
+        &self.blkdev_meta
+    }
+
+    fn disk_range(&self) -> GeneralBlockRange {
+        let blocks = self.live_table().map(|t| t.nr_sectors()).unwrap_or(0);
+        GeneralBlockRange::new(0, blocks).unwrap_or(GeneralBlockRange {
+            lba_start: 0,
+            lba_end: 0,
+        })
+    }
+
+    fn read_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let len = count * LBA_SIZE;
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        let io = self.start_io()?;
+        io.table.read(lba_id_start, count, &mut buf[..len])?;
+        Ok(len)
+    }
+
+    fn write_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let len = count * LBA_SIZE;
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        let io = self.start_io()?;
+        io.table.write(lba_id_start, count, &buf[..len])?;
+        Ok(len)
+    }
+
+    fn sync(&self) -> Result<(), SystemError> {
+        match self.start_io() {
+            Ok(io) => io.table.flush(),
+            Err(SystemError::ENXIO) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn discard(&self, range: GeneralBlockRange) -> Result<(), SystemError> {
+        let io = self.start_io()?;
+        io.table.discard(range.lba_start, range.len())
+    }
+
+    fn blk_size_log2(&self) -> u8 {
+        9
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn device(&self) -> Arc<dyn Device> {
+        self.self_ref.upgrade().unwrap()
+    }
+
+    fn block_size(&self) -> usize {
+        LBA_SIZE
+    }
+
+    fn partitions(&self) -> Vec<Arc<Partition>> {
+        self.blkdev_meta().partitions()
+    }
+}
+
+impl Device for DmDevice {
+    fn dev_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id_table(&self) -> IdTable {
+        IdTable::new("dm".to_string(), None)
+    }
+
+    fn bus(&self) -> Option<Weak<dyn Bus>> {
+        self.inner().device_common.bus.clone()
+    }
+
+    fn set_bus(&self, bus: Option<Weak<dyn Bus>>) {
+        self.inner().device_common.bus = bus;
+    }
+
+    fn class(&self) -> Option<Arc<dyn Class>> {
+        let mut guard = self.inner();
+        let class = guard.device_common.class.clone()?.upgrade();
+        if class.is_none() {
+            guard.device_common.class = None;
+        }
+        class
+    }
+
+    fn set_class(&self, class: Option<Weak<dyn Class>>) {
+        self.inner().device_common.class = class;
+    }
+
+    fn driver(&self) -> Option<Arc<dyn Driver>> {
+        let mut guard = self.inner();
+        let driver = guard.device_common.driver.clone()?.upgrade();
+        if driver.is_none() {
+            guard.device_common.driver = None;
+        }
+        driver
+    }
+
+    fn set_driver(&self, driver: Option<Weak<dyn Driver>>) {
+        self.inner().device_common.driver = driver;
+    }
+
+    fn is_dead(&self) -> bool {
+        false
+    }
+
+    fn can_match(&self) -> bool {
+        self.inner().device_common.can_match
+    }
+
+    fn set_can_match(&self, can_match: bool) {
+        self.inner().device_common.can_match = can_match;
+    }
+
+    fn state_synced(&self) -> bool {
+        true
+    }
+
+    fn dev_parent(&self) -> Option<Weak<dyn Device>> {
+        self.inner().device_common.get_parent_weak_or_clear()
+    }
+
+    fn set_dev_parent(&self, parent: Option<Weak<dyn Device>>) {
+        self.inner().device_common.parent = parent;
+    }
+}
+
+impl KObject for DmDevice {
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn set_inode(&self, inode: Option<Arc<KernFSInode>>) {
+        self.inner().kobject_common.kern_inode = inode;
+    }
+
+    fn inode(&self) -> Option<Arc<KernFSInode>> {
+        self.inner().kobject_common.kern_inode.clone()
+    }
+
+    fn parent(&self) -> Option<Weak<dyn KObject>> {
+        self.inner().kobject_common.parent.clone()
+    }
+
+    fn set_parent(&self, parent: Option<Weak<dyn KObject>>) {
+        self.inner().kobject_common.parent = parent;
+    }
+
+    fn kset(&self) -> Option<Arc<KSet>> {
+        self.inner().kobject_common.kset.clone()
+    }
+
+    fn set_kset(&self, kset: Option<Arc<KSet>>) {
+        self.inner().kobject_common.kset = kset;
+    }
+
+    fn kobj_type(&self) -> Option<&'static dyn KObjType> {
+        self.inner().kobject_common.kobj_type
+    }
+
+    fn name(&self) -> String {
+        self.dev_name().to_string()
+    }
+
+    fn set_name(&self, _name: String) {}
+
+    fn kobj_state(&self) -> RwSemReadGuard<'_, KObjectState> {
+        self.locked_kobj_state.read()
+    }
+
+    fn kobj_state_mut(&self) -> RwSemWriteGuard<'_, KObjectState> {
+        self.locked_kobj_state.write()
+    }
+
+    fn set_kobj_state(&self, state: KObjectState) {
+        *self.locked_kobj_state.write() = state;
+    }
+
+    fn set_kobj_type(&self, ktype: Option<&'static dyn KObjType>) {
+        self.inner().kobject_common.kobj_type = ktype;
+    }
+}
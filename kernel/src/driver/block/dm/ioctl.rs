@@ -0,0 +1,379 @@
+//! device-mapper的用户态接口（`/dev/mapper/control`的ioctl）
+//!
+//! 所有命令的参数都是一块由用户态分配的缓冲区：开头是[`DmIoctl`]，之后是命令相关的数据，
+//! 数据的位置由`data_start`给出，缓冲区的总大小为`data_size`。内核把结果写回同一块缓冲区。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/include/uapi/linux/dm-ioctl.h
+
+use core::mem::size_of;
+
+use alloc::{string::String, vec::Vec};
+use bitflags::bitflags;
+use system_error::SystemError;
+
+use crate::{
+    filesystem::vfs::ioctl::IoctlCmd,
+    syscall::user_access::{UserBufferReader, UserBufferWriter},
+};
+
+/// 接口的主版本号，用户态的主版本号必须相同
+pub const DM_VERSION_MAJOR: u32 = 4;
+/// 支持的次版本号
+pub const DM_VERSION_MINOR: u32 = 48;
+pub const DM_VERSION_PATCHLEVEL: u32 = 0;
+
+/// ioctl的类型号
+const DM_IOCTL: u8 = 0xfd;
+
+/// 设备名的最大长度（含结尾的0）
+pub const DM_NAME_LEN: usize = 128;
+/// uuid的最大长度（含结尾的0）
+pub const DM_UUID_LEN: usize = 129;
+/// 目标类型名的最大长度（含结尾的0）
+pub const DM_MAX_TYPE_NAME: usize = 16;
+
+/// 一次ioctl的缓冲区的最大大小
+const DM_MAX_IOCTL_SIZE: usize = 1024 * 1024;
+
+/// ioctl命令，编号为命令号中的nr字段
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum DmCommand {
+    Version = 0,
+    RemoveAll = 1,
+    ListDevices = 2,
+    DevCreate = 3,
+    DevRemove = 4,
+    DevRename = 5,
+    DevSuspend = 6,
+    DevStatus = 7,
+    DevWait = 8,
+    TableLoad = 9,
+    TableClear = 10,
+    TableDeps = 11,
+    TableStatus = 12,
+    ListVersions = 13,
+    TargetMsg = 14,
+    DevSetGeometry = 15,
+}
+
+impl DmCommand {
+    /// 解析命令号，类型号不是device-mapper的命令时返回`None`
+    pub fn from_cmd(cmd: u32) -> Option<Self> {
+        let cmd = IoctlCmd::new(cmd);
+        if cmd.ty() != DM_IOCTL {
+            return None;
+        }
+        num_traits::FromPrimitive::from_u8(cmd.nr())
+    }
+}
+
+bitflags! {
+    /// [`DmIoctl`]的`flags`字段
+    pub struct DmFlags: u32 {
+        /// 表只读（TABLE_LOAD时输入，状态查询时输出）
+        const READONLY = 1 << 0;
+        /// 挂起设备（DEV_SUSPEND时输入），设备处于挂起状态（输出）
+        const SUSPEND = 1 << 1;
+        /// 使用`dev`中给出的设备号创建设备
+        const PERSISTENT_DEV = 1 << 3;
+        /// TABLE_STATUS返回表的参数，而不是目标的状态
+        const STATUS_TABLE = 1 << 4;
+        /// 设备有生效的表
+        const ACTIVE_PRESENT = 1 << 5;
+        /// 设备有已加载、尚未生效的表
+        const INACTIVE_PRESENT = 1 << 6;
+        /// 缓冲区不足以容纳结果
+        const BUFFER_FULL = 1 << 8;
+        /// 挂起时不把未完成的I/O写回
+        const NOFLUSH = 1 << 11;
+        /// 查询未生效的表
+        const QUERY_INACTIVE_TABLE = 1 << 12;
+        /// DEV_RENAME设置的是uuid而不是设备名
+        const UUID = 1 << 14;
+    }
+}
+
+/// 每条ioctl的头部，对应Linux的`struct dm_ioctl`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DmIoctl {
+    pub version: [u32; 3],
+    /// 整个缓冲区的大小，包括头部
+    pub data_size: u32,
+    /// 数据相对于缓冲区开头的偏移
+    pub data_start: u32,
+    /// 输入或者输出的目标数量
+    pub target_count: u32,
+    pub open_count: i32,
+    pub flags: u32,
+    pub event_nr: u32,
+    pub padding: u32,
+    /// 设备号，按Linux的`huge_encode_dev`编码
+    pub dev: u64,
+    pub name: [u8; DM_NAME_LEN],
+    pub uuid: [u8; DM_UUID_LEN],
+    pub data: [u8; 7],
+}
+
+const _: () = assert!(size_of::<DmIoctl>() == 312);
+
+impl DmIoctl {
+    pub fn flags(&self) -> DmFlags {
+        DmFlags::from_bits_truncate(self.flags)
+    }
+
+    pub fn name(&self) -> Result<&str, SystemError> {
+        c_str(&self.name)
+    }
+
+    pub fn uuid(&self) -> Result<&str, SystemError> {
+        c_str(&self.uuid)
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        set_c_str(&mut self.name, name);
+    }
+
+    pub fn set_uuid(&mut self, uuid: &str) {
+        set_c_str(&mut self.uuid, uuid);
+    }
+}
+
+/// 表中的一个目标，对应Linux的`struct dm_target_spec`，之后紧跟以0结尾的参数字符串
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DmTargetSpec {
+    pub sector_start: u64,
+    pub length: u64,
+    pub status: i32,
+    /// TABLE_LOAD时为下一个目标相对于本目标开头的偏移；输出时为相对于数据开头的偏移
+    pub next: u32,
+    pub target_type: [u8; DM_MAX_TYPE_NAME],
+}
+
+const _: () = assert!(size_of::<DmTargetSpec>() == 40);
+
+/// LIST_DEVICES输出的一项，对应Linux的`struct dm_name_list`：
+/// `u64 dev`、`u32 next`（下一项相对于本项开头的偏移，最后一项为0），之后紧跟以0结尾的设备名
+const DM_NAME_LIST_HEADER_LEN: usize = size_of::<u64>() + size_of::<u32>();
+
+/// 把`src`作为以0结尾的字符串解析
+fn c_str(src: &[u8]) -> Result<&str, SystemError> {
+    let len = src
+        .iter()
+        .position(|c| *c == 0)
+        .ok_or(SystemError::EINVAL)?;
+    core::str::from_utf8(&src[..len]).map_err(|_| SystemError::EINVAL)
+}
+
+fn set_c_str(dst: &mut [u8], src: &str) {
+    let len = src.len().min(dst.len() - 1);
+    dst.fill(0);
+    dst[..len].copy_from_slice(&src.as_bytes()[..len]);
+}
+
+fn as_bytes<T: Copy>(val: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(val as *const T as *const u8, size_of::<T>()) }
+}
+
+fn align8(x: usize) -> usize {
+    (x + 7) & !7
+}
+
+/// 一次ioctl的参数，保存了从用户态拷贝进来的整个缓冲区
+pub struct DmIoctlParam {
+    pub header: DmIoctl,
+    buf: Vec<u8>,
+    user_ptr: usize,
+}
+
+impl DmIoctlParam {
+    /// # 从用户态读取参数
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EINVAL)`: 主版本号不一致，或者缓冲区大小不合法
+    /// - `Err(SystemError::EFAULT)`: 缓冲区不可访问
+    pub fn read(user_ptr: usize) -> Result<Self, SystemError> {
+        if user_ptr == 0 {
+            return Err(SystemError::EFAULT);
+        }
+        let reader = UserBufferReader::new(user_ptr as *const u8, size_of::<DmIoctl>(), true)?;
+        let mut head = [0u8; size_of::<DmIoctl>()];
+        reader.copy_from_user_protected(&mut head, 0)?;
+        let header: DmIoctl = unsafe { core::ptr::read_unaligned(head.as_ptr() as *const DmIoctl) };
+
+        if header.version[0] != DM_VERSION_MAJOR {
+            log::warn!(
+                "device-mapper: ioctl interface mismatch: kernel({}.{}.{}), user({}.{}.{})",
+                DM_VERSION_MAJOR,
+                DM_VERSION_MINOR,
+                DM_VERSION_PATCHLEVEL,
+                header.version[0],
+                header.version[1],
+                header.version[2]
+            );
+            return Err(SystemError::EINVAL);
+        }
+        let size = header.data_size as usize;
+        if !(size_of::<DmIoctl>()..=DM_MAX_IOCTL_SIZE).contains(&size) {
+            return Err(SystemError::EINVAL);
+        }
+
+        let reader = UserBufferReader::new(user_ptr as *const u8, size, true)?;
+        let mut buf = alloc::vec![0u8; size];
+        reader.copy_from_user_protected(&mut buf, 0)?;
+        Ok(Self {
+            header,
+            buf,
+            user_ptr,
+        })
+    }
+
+    /// 输入的数据部分
+    pub fn input(&self) -> Result<&[u8], SystemError> {
+        let start = self.header.data_start as usize;
+        if start < size_of::<DmIoctl>() || start > self.buf.len() {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(&self.buf[start..])
+    }
+
+    /// 输入的数据部分作为以0结尾的字符串，用于DEV_RENAME
+    pub fn input_str(&self) -> Result<&str, SystemError> {
+        c_str(self.input()?)
+    }
+
+    /// # 解析TABLE_LOAD输入的目标
+    ///
+    /// ## 返回值
+    /// 每个目标的(起始扇区, 扇区数, 类型, 参数)
+    pub fn target_specs(&self) -> Result<Vec<(usize, usize, String, String)>, SystemError> {
+        let data = self.input()?;
+        let mut specs = Vec::new();
+        let mut offset = 0;
+        for i in 0..self.header.target_count {
+            let end = offset + size_of::<DmTargetSpec>();
+            if end > data.len() {
+                return Err(SystemError::EINVAL);
+            }
+            let spec: DmTargetSpec = unsafe {
+                core::ptr::read_unaligned(data[offset..].as_ptr() as *const DmTargetSpec)
+            };
+            let target_type = c_str(&spec.target_type)?;
+            let params = c_str(&data[end..])?;
+            specs.push((
+                spec.sector_start as usize,
+                spec.length as usize,
+                String::from(target_type),
+                String::from(params),
+            ));
+
+            if i + 1 < self.header.target_count {
+                if (spec.next as usize) < size_of::<DmTargetSpec>() {
+                    return Err(SystemError::EINVAL);
+                }
+                offset += spec.next as usize;
+            }
+        }
+        Ok(specs)
+    }
+
+    /// 输出数据的起始位置，紧跟在头部之后
+    fn output_start(&self) -> usize {
+        align8(size_of::<DmIoctl>())
+    }
+
+    /// # 输出目标的状态或者参数
+    ///
+    /// ## 参数
+    /// - `targets`: 每个目标的(起始扇区, 扇区数, 类型, 状态或参数)
+    pub fn write_target_status(&mut self, targets: &[(usize, usize, &str, String)]) {
+        let mut out = Vec::new();
+        for (sector_start, length, target_type, status) in targets {
+            let spec_off = out.len();
+            let next = align8(spec_off + size_of::<DmTargetSpec>() + status.len() + 1);
+            let mut spec = DmTargetSpec {
+                sector_start: *sector_start as u64,
+                length: *length as u64,
+                status: 0,
+                next: next as u32,
+                target_type: [0; DM_MAX_TYPE_NAME],
+            };
+            set_c_str(&mut spec.target_type, target_type);
+            out.extend_from_slice(as_bytes(&spec));
+            out.extend_from_slice(status.as_bytes());
+            out.resize(next, 0);
+        }
+        self.header.target_count = targets.len() as u32;
+        self.set_output(&out);
+    }
+
+    /// 输出设备列表，每项为(设备号, 设备名)
+    pub fn write_name_list(&mut self, devices: &[(u64, String)]) {
+        let mut out = Vec::new();
+        for (i, (dev, name)) in devices.iter().enumerate() {
+            let entry_off = out.len();
+            let next = align8(entry_off + DM_NAME_LIST_HEADER_LEN + name.len() + 1);
+            let rel_next = if i + 1 == devices.len() {
+                0
+            } else {
+                (next - entry_off) as u32
+            };
+            out.extend_from_slice(&dev.to_ne_bytes());
+            out.extend_from_slice(&rel_next.to_ne_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.resize(next, 0);
+        }
+        if out.is_empty() {
+            // 没有设备时输出一个dev为0的空项
+            out.resize(align8(DM_NAME_LIST_HEADER_LEN), 0);
+        }
+        self.set_output(&out);
+    }
+
+    /// 输出支持的目标类型，每项为(类型, 版本号)
+    pub fn write_target_versions(&mut self, versions: &[(&str, [u32; 3])]) {
+        // 对应Linux的`struct dm_target_versions`：`u32 next`、`u32 version[3]`，之后紧跟以0结尾的类型名
+        const HEADER_LEN: usize = size_of::<u32>() * 4;
+        let mut out = Vec::new();
+        for (i, (name, version)) in versions.iter().enumerate() {
+            let entry_off = out.len();
+            let next = align8(entry_off + HEADER_LEN + name.len() + 1);
+            let rel_next = if i + 1 == versions.len() {
+                0
+            } else {
+                (next - entry_off) as u32
+            };
+            out.extend_from_slice(&rel_next.to_ne_bytes());
+            for v in version {
+                out.extend_from_slice(&v.to_ne_bytes());
+            }
+            out.extend_from_slice(name.as_bytes());
+            out.resize(next, 0);
+        }
+        self.set_output(&out);
+    }
+
+    fn set_output(&mut self, out: &[u8]) {
+        let start = self.output_start();
+        self.header.data_start = start as u32;
+        if start + out.len() > self.buf.len() {
+            self.header.flags |= DmFlags::BUFFER_FULL.bits();
+            return;
+        }
+        self.buf[start..start + out.len()].copy_from_slice(out);
+        // 与Linux一致，data_size返回实际使用的大小
+        self.header.data_size = (start + out.len()) as u32;
+    }
+
+    /// 把结果写回用户态
+    pub fn write_back(mut self) -> Result<(), SystemError> {
+        let len = self.buf.len();
+        let header = self.header;
+        self.buf[..size_of::<DmIoctl>()].copy_from_slice(as_bytes(&header));
+        let mut writer = UserBufferWriter::new(self.user_ptr as *mut u8, len, true)?;
+        writer.copy_to_user_protected(&self.buf, 0)?;
+        Ok(())
+    }
+}
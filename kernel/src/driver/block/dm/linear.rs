@@ -0,0 +1,52 @@
+//! linear目标：把一段扇区线性映射到下层设备的一段扇区
+//!
+//! 参数为`<设备> <起始扇区>`，多个linear目标组成的映射表可以把几个设备拼接成一个设备。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/md/dm-linear.c
+
+use alloc::{string::String, sync::Arc};
+use system_error::SystemError;
+
+use crate::driver::base::block::block_device::BlockId;
+
+use super::table::{parse_sector, DmDev, DmTarget};
+
+#[derive(Debug)]
+pub struct LinearTarget {
+    dev: DmDev,
+    /// 在下层设备上的起始扇区
+    start: BlockId,
+}
+
+impl LinearTarget {
+    pub fn create(len: usize, args: &[&str]) -> Result<Arc<dyn DmTarget>, SystemError> {
+        let [path, start] = args else {
+            return Err(SystemError::EINVAL);
+        };
+        let start = parse_sector(start)?;
+        let dev = DmDev::open(path, start, len)?;
+        Ok(Arc::new(Self { dev, start }))
+    }
+}
+
+impl DmTarget for LinearTarget {
+    fn read(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError> {
+        self.dev.read(self.start + sector, buf)
+    }
+
+    fn write(&self, sector: BlockId, buf: &[u8]) -> Result<(), SystemError> {
+        self.dev.write(self.start + sector, buf)
+    }
+
+    fn discard(&self, sector: BlockId, count: usize) -> Result<(), SystemError> {
+        self.dev.discard(self.start + sector, count)
+    }
+
+    fn flush(&self) -> Result<(), SystemError> {
+        self.dev.flush()
+    }
+
+    fn table(&self) -> String {
+        format!("{} {}", self.dev.name(), self.start)
+    }
+}
@@ -0,0 +1,188 @@
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use ida::IdAllocator;
+use system_error::SystemError;
+
+use crate::{
+    driver::base::block::{block_device::BlockDevice, manager::block_dev_manager},
+    filesystem::devfs::{devfs_add_symlink, devfs_remove_symlink},
+    libs::mutex::{Mutex, MutexGuard},
+};
+
+use super::{
+    device::DmDevice,
+    ioctl::{DmIoctl, DM_NAME_LEN, DM_UUID_LEN},
+};
+
+/// device-mapper设备管理器
+pub struct DmManager {
+    inner: Mutex<DmManagerInner>,
+}
+
+struct DmManagerInner {
+    /// 按设备编号索引
+    devices: BTreeMap<usize, Arc<DmDevice>>,
+    id_alloc: IdAllocator,
+}
+
+impl DmManager {
+    /// 最大设备数量
+    const MAX_DEVICES: usize = 256;
+
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(DmManagerInner {
+                devices: BTreeMap::new(),
+                id_alloc: IdAllocator::new(0, Self::MAX_DEVICES)
+                    .expect("create IdAllocator failed"),
+            }),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, DmManagerInner> {
+        self.inner.lock()
+    }
+
+    fn check_name(name: &str) -> Result<(), SystemError> {
+        if name.is_empty()
+            || name.len() >= DM_NAME_LEN
+            || name.contains('/')
+            || name == "."
+            || name == ".."
+            || name == "control"
+        {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(())
+    }
+
+    /// /dev/mapper下指向设备节点的符号链接
+    fn mapper_link(name: &str) -> String {
+        format!("mapper/{}", name)
+    }
+
+    fn device_link_target(dev: &DmDevice) -> String {
+        format!("../{}", dev.dev_name())
+    }
+
+    /// # 创建设备
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EBUSY)`: 同名或者同uuid的设备已经存在
+    /// - `Err(SystemError::ENOSPC)`: 设备数量已达上限
+    pub fn create(&self, name: &str, uuid: &str) -> Result<Arc<DmDevice>, SystemError> {
+        Self::check_name(name)?;
+        if uuid.len() >= DM_UUID_LEN {
+            return Err(SystemError::EINVAL);
+        }
+        let mut inner = self.inner();
+        if inner
+            .devices
+            .values()
+            .any(|dev| dev.dm_name() == name || (!uuid.is_empty() && dev.uuid() == uuid))
+        {
+            return Err(SystemError::EBUSY);
+        }
+
+        let id = inner.id_alloc.alloc().ok_or(SystemError::ENOSPC)?;
+        let dev = DmDevice::new(id, name, uuid);
+        if let Err(e) = block_dev_manager().register(dev.clone() as Arc<dyn BlockDevice>) {
+            inner.id_alloc.free(id);
+            return Err(e);
+        }
+        if let Err(e) = devfs_add_symlink(&Self::mapper_link(name), &Self::device_link_target(&dev))
+        {
+            log::warn!(
+                "device-mapper: failed to create /dev/mapper/{}: {:?}",
+                name,
+                e
+            );
+        }
+        inner.devices.insert(id, dev.clone());
+        log::info!("device-mapper: created {} ({})", dev.dev_name(), name);
+        Ok(dev)
+    }
+
+    /// # 删除设备
+    ///
+    /// 先挂起设备并写回数据，再从系统中移除
+    pub fn remove(&self, dev: &Arc<DmDevice>) -> Result<(), SystemError> {
+        let mut inner = self.inner();
+        let id = dev.dev_name().id();
+        if !inner.devices.contains_key(&id) {
+            return Err(SystemError::ENXIO);
+        }
+        if let Err(e) = dev.suspend(true) {
+            log::warn!(
+                "device-mapper: {}: failed to flush before removal: {:?}",
+                dev.dev_name(),
+                e
+            );
+        }
+        if let Err(e) = block_dev_manager().unregister(&(dev.clone() as Arc<dyn BlockDevice>)) {
+            // 删除失败，设备继续可用
+            let _ = dev.resume();
+            return Err(e);
+        }
+        let _ = devfs_remove_symlink(&Self::mapper_link(&dev.dm_name()));
+        inner.devices.remove(&id);
+        inner.id_alloc.free(id);
+        log::info!(
+            "device-mapper: removed {} ({})",
+            dev.dev_name(),
+            dev.dm_name()
+        );
+        Ok(())
+    }
+
+    /// 删除所有设备，返回删除失败的设备数
+    pub fn remove_all(&self) -> usize {
+        let devices = self.list();
+        devices
+            .iter()
+            .filter(|dev| self.remove(dev).is_err())
+            .count()
+    }
+
+    /// # 重命名设备
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EBUSY)`: 同名的设备已经存在
+    pub fn rename(&self, dev: &Arc<DmDevice>, new_name: &str) -> Result<(), SystemError> {
+        Self::check_name(new_name)?;
+        let inner = self.inner();
+        if inner.devices.values().any(|d| d.dm_name() == new_name) {
+            return Err(SystemError::EBUSY);
+        }
+        let old_name = dev.dm_name();
+        let _ = devfs_remove_symlink(&Self::mapper_link(&old_name));
+        dev.set_dm_name(new_name);
+        devfs_add_symlink(&Self::mapper_link(new_name), &Self::device_link_target(dev))?;
+        log::info!("device-mapper: renamed {} to {}", old_name, new_name);
+        Ok(())
+    }
+
+    /// # 查找ioctl参数指定的设备
+    ///
+    /// 依次按uuid、设备名、设备号查找，与Linux一致，只使用第一个非空的字段
+    pub fn find(&self, header: &DmIoctl) -> Result<Arc<DmDevice>, SystemError> {
+        let uuid = header.uuid()?;
+        let name = header.name()?;
+        let inner = self.inner();
+        let found = if !uuid.is_empty() {
+            inner.devices.values().find(|dev| dev.uuid() == uuid)
+        } else if !name.is_empty() {
+            inner.devices.values().find(|dev| dev.dm_name() == name)
+        } else {
+            inner
+                .devices
+                .values()
+                .find(|dev| dev.device_number().new_encode_dev() as u64 == header.dev)
+        };
+        found.cloned().ok_or(SystemError::ENXIO)
+    }
+
+    /// 所有设备，按设备编号排序
+    pub fn list(&self) -> Vec<Arc<DmDevice>> {
+        self.inner().devices.values().cloned().collect()
+    }
+}
@@ -0,0 +1,56 @@
+//! device-mapper
+//!
+//! 把一个或多个下层块设备按映射表组合成新的块设备`/dev/dm-N`，并在`/dev/mapper/<name>`
+//! 创建指向它的符号链接。用户态通过`/dev/mapper/control`的ioctl管理设备，接口与Linux相同，
+//! 可以直接使用dmsetup：
+//! - DEV_CREATE创建一个没有映射表的设备；
+//! - TABLE_LOAD加载映射表，DEV_SUSPEND（不带挂起标志）恢复设备时映射表生效；
+//! - DEV_SUSPEND（带挂起标志）挂起设备，之后的I/O会等待，直到设备恢复；
+//! - DEV_REMOVE删除设备。
+//!
+//! 支持的目标：
+//! - `linear`: 线性映射，参见[`linear`]；
+//! - `crypt`: XTS-AES透明加密，参见[`crypt`]；
+//! - `snapshot`: 可写的非持久化快照，参见[`snapshot`]。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/md/dm-ioctl.c
+
+mod control;
+mod crypt;
+mod device;
+mod ioctl;
+mod linear;
+mod manager;
+mod snapshot;
+mod table;
+
+use alloc::sync::Arc;
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    driver::base::device::device_register, filesystem::devfs::devfs_register,
+    init::initcall::INITCALL_DEVICE,
+};
+
+pub use control::DmControlDevice;
+pub use device::DmDevice;
+pub use manager::DmManager;
+
+use control::DM_CONTROL_NODE;
+
+#[unified_init(INITCALL_DEVICE)]
+fn dm_init() -> Result<(), SystemError> {
+    let dm_mgr = Arc::new(DmManager::new());
+    let dm_ctl = DmControlDevice::new(dm_mgr);
+
+    device_register(dm_ctl.clone())?;
+    devfs_register(DM_CONTROL_NODE, dm_ctl)?;
+    log::info!(
+        "device-mapper: ioctl {}.{}.{} initialised",
+        ioctl::DM_VERSION_MAJOR,
+        ioctl::DM_VERSION_MINOR,
+        ioctl::DM_VERSION_PATCHLEVEL
+    );
+    Ok(())
+}
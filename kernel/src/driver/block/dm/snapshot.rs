@@ -0,0 +1,173 @@
+//! snapshot目标：在原设备之上提供一个可写的快照
+//!
+//! 参数为`<原设备> <COW设备> N <chunk大小>`。对快照的第一次写入会先把所在chunk的原始数据复制到
+//! COW设备上，之后对该chunk的读写都在COW设备上进行，原设备保持不变。删除快照或者重新加载映射表
+//! 就丢弃了所有的修改，可以用来回滚测试用的文件系统。
+//!
+//! 只支持非持久化（`N`）的快照：chunk的映射关系只保存在内存中。使用快照期间不能直接写原设备，
+//! 否则快照中还没有被复制的chunk也会跟着变化（没有实现snapshot-origin目标）。
+//! COW设备的空间用完后，快照变为无效，之后的所有I/O都返回`EIO`。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/md/dm-snap.c
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::block::block_device::{BlockId, LBA_SIZE},
+    libs::mutex::Mutex,
+};
+
+use super::table::{parse_sector, DmDev, DmTarget};
+
+#[derive(Debug)]
+struct SnapshotStore {
+    /// 原设备上的chunk号到COW设备上的chunk号的映射
+    exceptions: BTreeMap<usize, usize>,
+    /// COW设备上下一个可用的chunk
+    next_free: usize,
+    /// COW设备的空间用完后变为无效
+    valid: bool,
+}
+
+#[derive(Debug)]
+pub struct SnapshotTarget {
+    origin: DmDev,
+    cow: DmDev,
+    /// chunk的大小（扇区数）
+    chunk_size: usize,
+    /// COW设备能容纳的chunk数
+    cow_chunks: usize,
+    store: Mutex<SnapshotStore>,
+}
+
+impl SnapshotTarget {
+    pub fn create(len: usize, args: &[&str]) -> Result<Arc<dyn DmTarget>, SystemError> {
+        let [origin, cow, persistent, chunk_size] = args else {
+            return Err(SystemError::EINVAL);
+        };
+        if !persistent.eq_ignore_ascii_case("n") {
+            log::warn!("device-mapper: snapshot: only non-persistent (N) snapshots are supported");
+            return Err(SystemError::EINVAL);
+        }
+        let chunk_size = parse_sector(chunk_size)?;
+        if chunk_size == 0 || !chunk_size.is_power_of_two() {
+            return Err(SystemError::EINVAL);
+        }
+
+        let origin = DmDev::open(origin, 0, len)?;
+        let cow = DmDev::open(cow, 0, chunk_size)?;
+        let cow_chunks = cow.disk().nr_sectors() / chunk_size;
+        Ok(Arc::new(Self {
+            origin,
+            cow,
+            chunk_size,
+            cow_chunks,
+            store: Mutex::new(SnapshotStore {
+                exceptions: BTreeMap::new(),
+                next_free: 0,
+                valid: true,
+            }),
+        }))
+    }
+
+    /// 把一段扇区按chunk拆分，对每个chunk调用`f(chunk号, chunk内的扇区偏移, 扇区数, 在整段中的扇区偏移)`
+    fn for_each_chunk<F>(&self, sector: BlockId, count: usize, mut f: F) -> Result<(), SystemError>
+    where
+        F: FnMut(usize, usize, usize, usize) -> Result<(), SystemError>,
+    {
+        let mut done = 0;
+        while done < count {
+            let cur = sector + done;
+            let chunk = cur / self.chunk_size;
+            let offset = cur % self.chunk_size;
+            let n = (self.chunk_size - offset).min(count - done);
+            f(chunk, offset, n, done)?;
+            done += n;
+        }
+        Ok(())
+    }
+
+    /// 为原设备上的chunk在COW设备上分配空间，并复制原始数据
+    ///
+    /// `full`为true时调用者会覆盖整个chunk，不需要复制
+    fn copy_on_write(
+        &self,
+        store: &mut SnapshotStore,
+        chunk: usize,
+        full: bool,
+    ) -> Result<usize, SystemError> {
+        if store.next_free >= self.cow_chunks {
+            store.valid = false;
+            log::error!("device-mapper: snapshot: COW device is full, invalidating snapshot");
+            return Err(SystemError::EIO);
+        }
+        let cow_chunk = store.next_free;
+        if !full {
+            let mut buf = alloc::vec![0u8; self.chunk_size * LBA_SIZE];
+            self.origin.read(chunk * self.chunk_size, &mut buf)?;
+            self.cow.write(cow_chunk * self.chunk_size, &buf)?;
+        }
+        store.next_free += 1;
+        store.exceptions.insert(chunk, cow_chunk);
+        Ok(cow_chunk)
+    }
+}
+
+impl DmTarget for SnapshotTarget {
+    fn read(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError> {
+        let store = self.store.lock();
+        if !store.valid {
+            return Err(SystemError::EIO);
+        }
+        self.for_each_chunk(sector, buf.len() / LBA_SIZE, |chunk, offset, n, done| {
+            let dst = &mut buf[done * LBA_SIZE..(done + n) * LBA_SIZE];
+            match store.exceptions.get(&chunk) {
+                Some(cow_chunk) => self.cow.read(cow_chunk * self.chunk_size + offset, dst),
+                None => self.origin.read(chunk * self.chunk_size + offset, dst),
+            }
+        })
+    }
+
+    fn write(&self, sector: BlockId, buf: &[u8]) -> Result<(), SystemError> {
+        let mut store = self.store.lock();
+        if !store.valid {
+            return Err(SystemError::EIO);
+        }
+        self.for_each_chunk(sector, buf.len() / LBA_SIZE, |chunk, offset, n, done| {
+            let cow_chunk = match store.exceptions.get(&chunk) {
+                Some(cow_chunk) => *cow_chunk,
+                None => self.copy_on_write(&mut store, chunk, n == self.chunk_size)?,
+            };
+            self.cow.write(
+                cow_chunk * self.chunk_size + offset,
+                &buf[done * LBA_SIZE..(done + n) * LBA_SIZE],
+            )
+        })
+    }
+
+    fn flush(&self) -> Result<(), SystemError> {
+        self.cow.flush()
+    }
+
+    fn status(&self) -> String {
+        let store = self.store.lock();
+        if !store.valid {
+            return String::from("Invalid");
+        }
+        format!(
+            "{}/{} 0",
+            store.next_free * self.chunk_size,
+            self.cow_chunks * self.chunk_size
+        )
+    }
+
+    fn table(&self) -> String {
+        format!(
+            "{} {} N {}",
+            self.origin.name(),
+            self.cow.name(),
+            self.chunk_size
+        )
+    }
+}
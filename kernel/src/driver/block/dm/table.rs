@@ -0,0 +1,275 @@
+//! device-mapper的映射表
+//!
+//! 映射表由若干个首尾相接的目标（target）组成，每个目标负责映射设备上的一段连续扇区。
+//! 目标的类型决定了如何把对这段扇区的I/O转发到下层设备，参见[`DM_TARGET_TYPES`]。
+
+use core::fmt::Debug;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use crate::driver::base::{
+    block::{
+        block_device::{BlockId, LBA_SIZE},
+        gendisk::{GenDisk, GenDiskClaim},
+        manager::block_dev_manager,
+    },
+    device::device_number::{DeviceNumber, Major},
+};
+
+use super::{crypt::CryptTarget, linear::LinearTarget, snapshot::SnapshotTarget};
+
+/// 一个目标的实现
+///
+/// 扇区号都是相对于目标起始位置的偏移，缓冲区长度是[`LBA_SIZE`]的整数倍，
+/// 并且不会超出目标的范围
+pub trait DmTarget: Debug + Send + Sync {
+    fn read(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError>;
+
+    fn write(&self, sector: BlockId, buf: &[u8]) -> Result<(), SystemError>;
+
+    /// 丢弃一段扇区，默认不支持
+    fn discard(&self, _sector: BlockId, _count: usize) -> Result<(), SystemError> {
+        Err(SystemError::EOPNOTSUPP_OR_ENOTSUP)
+    }
+
+    /// 把写入的数据持久化到下层设备
+    fn flush(&self) -> Result<(), SystemError>;
+
+    /// 目标的运行状态，TABLE_STATUS时返回
+    fn status(&self) -> String {
+        String::new()
+    }
+
+    /// 目标的参数，带`DM_STATUS_TABLE_FLAG`的TABLE_STATUS时返回
+    fn table(&self) -> String;
+}
+
+/// 创建目标的函数，参数为目标的扇区数和参数列表
+type DmTargetCtr = fn(usize, &[&str]) -> Result<Arc<dyn DmTarget>, SystemError>;
+
+/// 支持的目标类型及其版本号
+pub const DM_TARGET_TYPES: &[(&str, [u32; 3], DmTargetCtr)] = &[
+    ("linear", [1, 4, 0], LinearTarget::create),
+    ("snapshot", [1, 16, 0], SnapshotTarget::create),
+    ("crypt", [1, 24, 0], CryptTarget::create),
+];
+
+/// 映射表中的一个目标
+#[derive(Debug)]
+pub struct DmTableEntry {
+    /// 在设备上的起始扇区
+    pub start: BlockId,
+    /// 扇区数
+    pub len: usize,
+    pub target_type: &'static str,
+    pub target: Arc<dyn DmTarget>,
+}
+
+/// 一张映射表
+#[derive(Debug)]
+pub struct DmTable {
+    entries: Vec<DmTableEntry>,
+    read_only: bool,
+}
+
+impl DmTable {
+    /// # 创建映射表
+    ///
+    /// ## 参数
+    /// - `specs`: 每个目标的(起始扇区, 扇区数, 类型, 参数)，必须从0开始首尾相接
+    /// - `read_only`: 映射表是否只读
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EINVAL)`: 目标不连续、类型未知或者参数错误
+    pub fn new(
+        specs: &[(usize, usize, String, String)],
+        read_only: bool,
+    ) -> Result<Arc<Self>, SystemError> {
+        let mut entries: Vec<DmTableEntry> = Vec::with_capacity(specs.len());
+        let mut next = 0;
+        for (start, len, target_type, params) in specs {
+            if *start != next || *len == 0 {
+                log::warn!(
+                    "device-mapper: target {} at sector {} is not contiguous",
+                    target_type,
+                    start
+                );
+                return Err(SystemError::EINVAL);
+            }
+            let (name, _, ctr) = DM_TARGET_TYPES
+                .iter()
+                .find(|(name, _, _)| *name == target_type.as_str())
+                .ok_or_else(|| {
+                    log::warn!("device-mapper: unknown target type {}", target_type);
+                    SystemError::EINVAL
+                })?;
+            let args: Vec<&str> = params.split_whitespace().collect();
+            let target = ctr(*len, &args).inspect_err(|e| {
+                log::warn!(
+                    "device-mapper: failed to create {} target ({}): {:?}",
+                    name,
+                    params,
+                    e
+                );
+            })?;
+            entries.push(DmTableEntry {
+                start: *start,
+                len: *len,
+                target_type: name,
+                target,
+            });
+            next = start + len;
+        }
+        Ok(Arc::new(Self { entries, read_only }))
+    }
+
+    pub fn entries(&self) -> &[DmTableEntry] {
+        &self.entries
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// 映射表的总扇区数
+    pub fn nr_sectors(&self) -> usize {
+        self.entries.last().map(|e| e.start + e.len).unwrap_or(0)
+    }
+
+    /// # 把一段扇区按目标拆分
+    ///
+    /// 对每个涉及的目标调用`f(目标, 目标内的起始扇区, 扇区数, 在整段中的扇区偏移)`
+    pub fn for_each_range<F>(
+        &self,
+        sector: BlockId,
+        count: usize,
+        mut f: F,
+    ) -> Result<(), SystemError>
+    where
+        F: FnMut(&Arc<dyn DmTarget>, BlockId, usize, usize) -> Result<(), SystemError>,
+    {
+        let end = sector.checked_add(count).ok_or(SystemError::EINVAL)?;
+        if end > self.nr_sectors() {
+            return Err(SystemError::EINVAL);
+        }
+        let first = self.entries.partition_point(|e| e.start + e.len <= sector);
+        let mut done = 0;
+        for entry in &self.entries[first..] {
+            if done == count {
+                break;
+            }
+            let cur = sector + done;
+            let offset = cur - entry.start;
+            let n = (entry.len - offset).min(count - done);
+            f(&entry.target, offset, n, done)?;
+            done += n;
+        }
+        Ok(())
+    }
+
+    pub fn read(&self, sector: BlockId, count: usize, buf: &mut [u8]) -> Result<(), SystemError> {
+        self.for_each_range(sector, count, |target, offset, n, done| {
+            target.read(offset, &mut buf[done * LBA_SIZE..(done + n) * LBA_SIZE])
+        })
+    }
+
+    pub fn write(&self, sector: BlockId, count: usize, buf: &[u8]) -> Result<(), SystemError> {
+        if self.read_only {
+            return Err(SystemError::EROFS);
+        }
+        self.for_each_range(sector, count, |target, offset, n, done| {
+            target.write(offset, &buf[done * LBA_SIZE..(done + n) * LBA_SIZE])
+        })
+    }
+
+    pub fn discard(&self, sector: BlockId, count: usize) -> Result<(), SystemError> {
+        if self.read_only {
+            return Err(SystemError::EROFS);
+        }
+        self.for_each_range(sector, count, |target, offset, n, _| {
+            target.discard(offset, n)
+        })
+    }
+
+    pub fn flush(&self) -> Result<(), SystemError> {
+        for entry in &self.entries {
+            entry.target.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// 目标使用的一个下层设备，存在期间下层设备处于打开状态
+#[derive(Debug)]
+pub struct DmDev {
+    claim: GenDiskClaim,
+}
+
+impl DmDev {
+    /// # 打开下层设备
+    ///
+    /// ## 参数
+    /// - `path`: `/dev/sda1`这样的路径，或者`8:1`这样的设备号
+    /// - `start`: 目标会访问的起始扇区
+    /// - `len`: 目标会访问的扇区数
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::ENODEV)`: 设备不存在
+    /// - `Err(SystemError::EINVAL)`: 访问范围超出了设备
+    pub fn open(path: &str, start: BlockId, len: usize) -> Result<Self, SystemError> {
+        let disk = Self::lookup(path).ok_or(SystemError::ENODEV)?;
+        let end = start.checked_add(len).ok_or(SystemError::EINVAL)?;
+        if end > disk.nr_sectors() {
+            log::warn!(
+                "device-mapper: {} is too small: {} sectors needed, {} available",
+                path,
+                end,
+                disk.nr_sectors()
+            );
+            return Err(SystemError::EINVAL);
+        }
+        Ok(Self {
+            claim: disk.claim(),
+        })
+    }
+
+    fn lookup(path: &str) -> Option<Arc<GenDisk>> {
+        let Some((major, minor)) = path.split_once(':') else {
+            return block_dev_manager().lookup_gendisk_by_path(path);
+        };
+        let major = major.parse::<u32>().ok()?;
+        let minor = minor.parse::<u32>().ok()?;
+        block_dev_manager().lookup_gendisk_by_devnum(DeviceNumber::new(Major::new(major), minor))
+    }
+
+    /// 设备号，`major:minor`的形式，用于输出映射表
+    pub fn name(&self) -> String {
+        self.disk().symlink_name()
+    }
+
+    pub fn disk(&self) -> &Arc<GenDisk> {
+        self.claim.disk()
+    }
+
+    pub fn read(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError> {
+        self.disk().read_at(buf, sector).map(|_| ())
+    }
+
+    pub fn write(&self, sector: BlockId, buf: &[u8]) -> Result<(), SystemError> {
+        self.disk().write_at(buf, sector).map(|_| ())
+    }
+
+    pub fn discard(&self, sector: BlockId, count: usize) -> Result<(), SystemError> {
+        self.disk().discard(sector, count)
+    }
+
+    pub fn flush(&self) -> Result<(), SystemError> {
+        self.disk().flush()
+    }
+}
+
+/// 解析十进制的扇区数或者扇区号
+pub fn parse_sector(s: &str) -> Result<usize, SystemError> {
+    s.parse::<usize>().map_err(|_| SystemError::EINVAL)
+}
@@ -44,6 +44,38 @@ pub enum LoopIoctl {
     LoopSetBlockSize = 0x4C09,
     /// 配置设备
     LoopConfigure = 0x4C0A,
+    /// 设置数据完整性模式，参数为[`LoopIntegrityMode`]（DragonOS私有）
+    LoopSetIntegrity = 0x4CF0,
+    /// 获取数据完整性状态，参数为`*mut LoopIntegrityInfo`（DragonOS私有）
+    LoopGetIntegrity = 0x4CF1,
+}
+
+/// `LOOP_SET_INTEGRITY`的参数
+#[repr(usize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum LoopIntegrityMode {
+    /// 关闭完整性校验
+    Disable = 0,
+    /// 开启完整性校验，后端文件中已有有效的校验数据时直接使用
+    Enable = 1,
+    /// 开启完整性校验，并根据当前数据重新生成校验数据
+    Reformat = 2,
+}
+
+/// `LOOP_GET_INTEGRITY`返回给用户态的信息
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoopIntegrityInfo {
+    /// 是否开启了完整性校验
+    pub enabled: u32,
+    /// 校验和计算是否使用了硬件加速
+    pub accelerated: u32,
+    /// 开启时设备可用的扇区数（不含校验数据占用的空间）
+    pub data_sectors: u64,
+    /// 校验通过的扇区数
+    pub verified: u64,
+    /// 校验失败的次数
+    pub mismatches: u64,
 }
 
 /// Loop-control 设备 ioctl 命令
@@ -64,9 +96,24 @@ bitflags! {
     pub struct LoopFlags: u32 {
         /// 只读模式
         const READ_ONLY = 1 << 0;
+        /// 最后一次关闭设备时自动解除绑定
+        const AUTOCLEAR = 1 << 2;
+        /// 绑定后扫描分区
+        const PARTSCAN = 1 << 3;
+        /// 使用直接I/O访问后端文件（暂不支持）
+        const DIRECT_IO = 1 << 4;
     }
 }
 
+impl LoopFlags {
+    /// `LOOP_SET_STATUS(64)`可以修改的标志位，其余的位保持不变
+    ///
+    /// 后端文件以只读方式打开时，`READ_ONLY`不能被清除
+    pub const SET_STATUS_SETTABLE: LoopFlags = LoopFlags::from_bits_truncate(
+        Self::READ_ONLY.bits() | Self::AUTOCLEAR.bits() | Self::PARTSCAN.bits(),
+    );
+}
+
 /// legacy loop_info 中 name 字段长度
 pub const LOOP_NAME_SIZE: usize = 64;
 
@@ -167,6 +214,18 @@ impl Default for LoopStatus64 {
     }
 }
 
+/// `LOOP_CONFIGURE`的参数，对应 Linux 的 `struct loop_config`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LoopConfig {
+    /// 后端文件的文件描述符
+    pub fd: u32,
+    /// 逻辑块大小，0 表示使用默认值
+    pub block_size: u32,
+    pub info: LoopStatus64,
+    pub reserved: [u64; 8],
+}
+
 /// Loop 设备状态
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LoopState {
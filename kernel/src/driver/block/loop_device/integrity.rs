@@ -0,0 +1,174 @@
+//! Loop 设备的数据完整性模式
+//!
+//! 开启后，后端文件（`[offset, offset + size)`窗口内）的末尾被划分出来保存校验数据：
+//!
+//! ```text
+//! | 数据区：N 个扇区 | tag 区：N 个 u32，按扇区对齐 | 超级块：1 个扇区 |
+//! ```
+//!
+//! 设备的容量随之变为 N 个扇区。tag 为每个扇区的 CRC32C，读取时校验，
+//! 不一致时读操作返回`EILSEQ`。
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::block::{
+        block_device::{BlockId, LBA_SIZE},
+        integrity::{BlkIntegrity, BlkIntegrityStore},
+    },
+    filesystem::vfs::kernel_file::KernelFile,
+};
+
+/// 超级块的魔数
+const LOOP_INTEGRITY_MAGIC: [u8; 8] = *b"DOSLOINT";
+/// 超级块格式的版本
+const LOOP_INTEGRITY_VERSION: u32 = 1;
+/// 每个tag的大小
+const TAG_SIZE: usize = core::mem::size_of::<u32>();
+/// 每个扇区能保存的tag数
+const TAGS_PER_LBA: usize = LBA_SIZE / TAG_SIZE;
+/// 格式化时每次处理的扇区数
+const FORMAT_BATCH_LBAS: usize = 256;
+
+/// 已开启的完整性校验
+#[derive(Debug, Clone)]
+pub struct LoopIntegrity {
+    pub blk: Arc<BlkIntegrity>,
+    pub store: Arc<LoopIntegrityStore>,
+}
+
+/// 保存在后端文件中的tag
+#[derive(Debug)]
+pub struct LoopIntegrityStore {
+    file: Arc<KernelFile>,
+    /// 窗口在后端文件中的起始偏移
+    base: usize,
+    /// 数据区的扇区数
+    data_blocks: usize,
+}
+
+impl LoopIntegrityStore {
+    /// ## 根据窗口大小计算布局
+    ///
+    /// ## 返回值
+    /// - `Some(usize)`: 数据区的扇区数
+    /// - `None`: 窗口太小，放不下校验数据
+    fn data_blocks_for(window_size: usize) -> Option<usize> {
+        let total = window_size / LBA_SIZE;
+        // 每128个数据扇区需要1个扇区保存tag，另外还需要1个扇区保存超级块
+        let mut data = total.checked_sub(1)? * TAGS_PER_LBA / (TAGS_PER_LBA + 1);
+        while data > 0 && data + data.div_ceil(TAGS_PER_LBA) + 1 > total {
+            data -= 1;
+        }
+        if data == 0 {
+            return None;
+        }
+        Some(data)
+    }
+
+    pub fn new(
+        file: Arc<KernelFile>,
+        base: usize,
+        window_size: usize,
+    ) -> Result<Self, SystemError> {
+        let data_blocks = Self::data_blocks_for(window_size).ok_or(SystemError::ENOSPC)?;
+        Ok(Self {
+            file,
+            base,
+            data_blocks,
+        })
+    }
+
+    pub fn data_blocks(&self) -> usize {
+        self.data_blocks
+    }
+
+    fn tag_area_offset(&self) -> usize {
+        self.base + self.data_blocks * LBA_SIZE
+    }
+
+    fn superblock_offset(&self) -> usize {
+        self.tag_area_offset() + self.data_blocks.div_ceil(TAGS_PER_LBA) * LBA_SIZE
+    }
+
+    fn read_file(&self, offset: usize, buf: &mut [u8]) -> Result<(), SystemError> {
+        let len = self.file.read_at(offset, buf)?;
+        if len != buf.len() {
+            return Err(SystemError::EIO);
+        }
+        Ok(())
+    }
+
+    fn write_file(&self, offset: usize, buf: &[u8]) -> Result<(), SystemError> {
+        let len = self.file.write_at(offset, buf)?;
+        if len != buf.len() {
+            return Err(SystemError::EIO);
+        }
+        Ok(())
+    }
+
+    fn check_range(&self, lba_start: BlockId, count: usize) -> Result<(), SystemError> {
+        match lba_start.checked_add(count) {
+            Some(end) if end <= self.data_blocks => Ok(()),
+            _ => Err(SystemError::ENOSPC),
+        }
+    }
+
+    fn encode_superblock(&self) -> [u8; LBA_SIZE] {
+        let mut sb = [0u8; LBA_SIZE];
+        sb[0..8].copy_from_slice(&LOOP_INTEGRITY_MAGIC);
+        sb[8..12].copy_from_slice(&LOOP_INTEGRITY_VERSION.to_le_bytes());
+        sb[12..16].copy_from_slice(&(TAG_SIZE as u32).to_le_bytes());
+        sb[16..24].copy_from_slice(&(self.data_blocks as u64).to_le_bytes());
+        let csum = BlkIntegrity::generate_tag(&sb[0..24]);
+        sb[24..28].copy_from_slice(&csum.to_le_bytes());
+        sb
+    }
+
+    /// ## 后端文件中是否已有与当前布局一致的校验数据
+    pub fn probe(&self) -> Result<bool, SystemError> {
+        let mut sb = [0u8; LBA_SIZE];
+        self.read_file(self.superblock_offset(), &mut sb)?;
+        Ok(sb == self.encode_superblock())
+    }
+
+    /// ## 根据数据区的当前内容生成全部tag，并写入超级块
+    pub fn format(&self) -> Result<(), SystemError> {
+        let mut buf = vec![0u8; FORMAT_BATCH_LBAS * LBA_SIZE];
+        let mut lba = 0;
+        while lba < self.data_blocks {
+            let count = (self.data_blocks - lba).min(FORMAT_BATCH_LBAS);
+            let chunk = &mut buf[..count * LBA_SIZE];
+            self.read_file(self.base + lba * LBA_SIZE, chunk)?;
+            self.store_tags(lba, &BlkIntegrity::generate_tags(chunk))?;
+            lba += count;
+        }
+        self.write_file(self.superblock_offset(), &self.encode_superblock())
+    }
+
+    /// ## 使超级块失效
+    ///
+    /// 关闭完整性校验后的写入不会更新tag，因此需要让下一次开启时重新生成
+    pub fn invalidate(&self) -> Result<(), SystemError> {
+        self.write_file(self.superblock_offset(), &[0u8; LBA_SIZE])
+    }
+}
+
+impl BlkIntegrityStore for LoopIntegrityStore {
+    fn load_tags(&self, lba_start: BlockId, tags: &mut [u32]) -> Result<(), SystemError> {
+        self.check_range(lba_start, tags.len())?;
+        let mut raw = vec![0u8; tags.len() * TAG_SIZE];
+        self.read_file(self.tag_area_offset() + lba_start * TAG_SIZE, &mut raw)?;
+        for (tag, bytes) in tags.iter_mut().zip(raw.chunks_exact(TAG_SIZE)) {
+            *tag = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        Ok(())
+    }
+
+    fn store_tags(&self, lba_start: BlockId, tags: &[u32]) -> Result<(), SystemError> {
+        self.check_range(lba_start, tags.len())?;
+        let raw: Vec<u8> = tags.iter().flat_map(|tag| tag.to_le_bytes()).collect();
+        self.write_file(self.tag_area_offset() + lba_start * TAG_SIZE, &raw)
+    }
+}
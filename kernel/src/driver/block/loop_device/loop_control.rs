@@ -11,7 +11,7 @@ use crate::{
         kset::KSet,
     },
     filesystem::{
-        devfs::{DevFS, DeviceINode, LockedDevFSInode},
+        devfs::{DevFS, DevNodeAttr, DeviceINode, LockedDevFSInode},
         kernfs::KernFSInode,
         vfs::{
             file::FileFlags, FilePrivateData, FileType, IndexNode, InodeFlags, InodeId, InodeMode,
@@ -53,6 +53,8 @@ pub struct LoopControlDevice {
     inner: SpinLock<LoopControlDeviceInner>,
     locked_kobj_state: LockedKObjectState,
     loop_mgr: Arc<LoopManager>,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
 }
 
 struct LoopControlDeviceInner {
@@ -76,6 +78,7 @@ impl LoopControlDevice {
             }),
             locked_kobj_state: LockedKObjectState::default(),
             loop_mgr,
+            attr: DevNodeAttr::new(InodeMode::from_bits_truncate(0o600)),
         })
     }
 
@@ -125,7 +128,7 @@ impl IndexNode for LoopControlDevice {
     /// - `Ok(Metadata)`: 成功获取设备元数据
     /// - 包含设备类型、权限、设备号等信息
     fn metadata(&self) -> Result<Metadata, SystemError> {
-        let metadata = Metadata {
+        let mut metadata = Metadata {
             dev_id: 0,
             inode_id: InodeId::new(0),
             size: 0,
@@ -143,9 +146,15 @@ impl IndexNode for LoopControlDevice {
             gid: 0,
             raw_dev: DeviceNumber::new(Major::LOOP_CONTROL_MAJOR, LOOP_CONTROL_MINOR),
         };
+        self.attr.apply(&mut metadata);
         Ok(metadata)
     }
 
+    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
+        self.attr.update(metadata);
+        Ok(())
+    }
+
     fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
         // loop-control 设备节点由 DevFS 注册；返回其所在的文件系统。
         if let Some(fs) = self
@@ -195,10 +204,10 @@ impl IndexNode for LoopControlDevice {
                 self.loop_mgr.loop_remove(minor_to_remove)?;
                 Ok(0)
             }
-            Some(LoopControlIoctl::GetFree) => match self.loop_mgr.find_free_minor() {
-                Some(minor) => Ok(minor as usize),
-                None => Err(SystemError::ENOSPC),
-            },
+            Some(LoopControlIoctl::GetFree) => {
+                let loop_dev = self.loop_mgr.loop_get_free()?;
+                Ok(loop_dev.minor() as usize)
+            }
             _ => Err(SystemError::ENOSYS),
         }
     }
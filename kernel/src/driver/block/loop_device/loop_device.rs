@@ -1,9 +1,12 @@
 use crate::{
     driver::base::{
         block::{
+            bio::{BioRequest, BioType},
             block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE},
+            cache::block_cache,
             disk_info::Partition,
-            manager::BlockDevMeta,
+            integrity::BlkIntegrity,
+            manager::{block_dev_manager, BlockDevMeta},
         },
         class::Class,
         device::{
@@ -22,16 +25,21 @@ use crate::{
         devfs::{DevFS, DeviceINode, LockedDevFSInode},
         kernfs::KernFSInode,
         sysfs::{AttributeGroup, SysFSOps},
-        vfs::{FilePrivateData, FileType, IndexNode, InodeFlags, InodeId, InodeMode, Metadata},
+        vfs::{
+            ioctl::IoctlArg, kernel_file::KernelFile, mount::MountDependent, FilePrivateData,
+            FileType, IndexNode, InodeFlags, InodeId, InodeMode, Metadata, MountFS,
+        },
     },
     libs::{
-        mutex::{Mutex, MutexGuard},
+        crc::crc32c_accelerated,
+        error_context::{ContextError, ErrorContext},
+        mutex::MutexGuard,
         rwlock::RwLock,
         rwsem::{RwSemReadGuard, RwSemWriteGuard},
         spinlock::{SpinLock, SpinLockGuard},
+        string::strscpy,
     },
     process::ProcessManager,
-    syscall::user_access::{UserBufferReader, UserBufferWriter},
     time::{sleep::nanosleep, PosixTimeSpec},
 };
 use alloc::{
@@ -48,9 +56,14 @@ use log::{error, info, warn};
 use num_traits::FromPrimitive;
 use system_error::SystemError;
 
-use super::constants::{
-    LoopFlags, LoopIoctl, LoopState, LoopStatus, LoopStatus64, LOOP_BASENAME,
-    LOOP_IO_DRAIN_CHECK_INTERVAL_US, LOOP_IO_DRAIN_TIMEOUT_MS,
+use super::{
+    constants::{
+        LoopConfig, LoopFlags, LoopIntegrityInfo, LoopIntegrityMode, LoopIoctl, LoopState,
+        LoopStatus, LoopStatus64, LOOP_BASENAME, LOOP_IO_DRAIN_CHECK_INTERVAL_US,
+        LOOP_IO_DRAIN_TIMEOUT_MS, LOOP_NAME_SIZE,
+    },
+    integrity::{LoopIntegrity, LoopIntegrityStore},
+    worker::LoopWorker,
 };
 
 /// Loop 设备 KObject 类型
@@ -105,6 +118,8 @@ pub struct LoopDevice {
     parent: RwLock<Weak<LockedDevFSInode>>,
     /// 活跃的 I/O 操作计数
     active_io_count: AtomicU32,
+    /// 设备被打开的次数
+    open_count: AtomicU32,
 }
 
 /// Loop 设备的私有数据（目前未使用）
@@ -115,18 +130,30 @@ pub struct LoopPrivateData;
 pub struct LoopDeviceInner {
     pub device_number: DeviceNumber,
     state: LoopState,
-    pub file_inode: Option<Arc<dyn IndexNode>>,
+    /// 后端文件
+    pub backing_file: Option<Arc<KernelFile>>,
     pub file_size: usize,
     pub offset: usize,
     pub size_limit: usize,
     pub flags: LoopFlags,
+    /// 后端文件是否以只读方式打开，此时设备始终是只读的
+    file_read_only: bool,
+    /// 数据完整性校验，开启时后端文件末尾的一部分用于保存校验数据
+    integrity: Option<LoopIntegrity>,
     pub kobject_common: KObjectCommonData,
     pub device_common: DeviceCommonData,
     /// drain_active_io 重试计数，用于限制无限重试
     drain_retry_count: u32,
+    /// 处理异步 BIO 的工作线程，第一次提交 BIO 时创建，解绑时停止
+    worker: Option<Arc<LoopWorker>>,
 }
 
 impl LoopDeviceInner {
+    /// 后端文件的inode
+    fn file_inode(&self) -> Option<Arc<dyn IndexNode>> {
+        self.backing_file.as_ref().map(|file| file.inode())
+    }
+
     /// 检查状态转换是否有效并执行转换
     ///
     /// 注意：调用者必须持有 LoopDeviceInner 的锁
@@ -160,11 +187,57 @@ impl LoopDeviceInner {
         self.flags.contains(LoopFlags::READ_ONLY)
     }
 
+    /// 应用`LOOP_SET_STATUS(64)`传入的标志位，后端文件只读时不能清除`READ_ONLY`
+    fn set_status_flags(&mut self, new_flags: LoopFlags) {
+        self.flags = (self.flags - LoopFlags::SET_STATUS_SETTABLE) | new_flags;
+        if self.file_read_only {
+            self.flags |= LoopFlags::READ_ONLY;
+        }
+    }
+
     /// 获取当前状态
     #[inline]
     pub(super) fn state(&self) -> LoopState {
         self.state
     }
+
+    /// 设备上可以读写的数据大小（开启完整性校验时不含校验数据）
+    #[inline]
+    fn data_size(&self) -> usize {
+        match &self.integrity {
+            Some(integrity) => integrity.store.data_blocks() * LBA_SIZE,
+            None => self.file_size,
+        }
+    }
+
+    /// 设备的容量（字节）
+    ///
+    /// 与Linux一致，映射窗口末尾不足一个扇区的部分不可访问
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.data_size() / LBA_SIZE * LBA_SIZE
+    }
+
+    /// ## 把设备上的扇区范围映射到后端文件
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(file_offset)`: 范围完全位于映射窗口内时，返回在后端文件中的起始偏移
+    /// - `Err(ENOSPC)`: 范围超出了映射窗口
+    fn map_range(&self, lba_id_start: BlockId, len: usize) -> Result<usize, SystemError> {
+        let block_offset = lba_id_start
+            .checked_mul(LBA_SIZE)
+            .ok_or(SystemError::EOVERFLOW)?;
+        let end = block_offset
+            .checked_add(len)
+            .ok_or(SystemError::EOVERFLOW)?;
+        if end > self.capacity() {
+            return Err(SystemError::ENOSPC);
+        }
+        self.offset
+            .checked_add(block_offset)
+            .ok_or(SystemError::EOVERFLOW)
+    }
 }
 
 impl Debug for LoopDevice {
@@ -197,12 +270,8 @@ impl LoopDevice {
         Ok(effective)
     }
 
-    fn set_file_locked(
-        inner: &mut LoopDeviceInner,
-        file_inode: Arc<dyn IndexNode>,
-        file_size: usize,
-    ) {
-        inner.file_inode = Some(file_inode);
+    fn set_file_locked(inner: &mut LoopDeviceInner, file: Arc<KernelFile>, file_size: usize) {
+        inner.backing_file = Some(file);
         inner.file_size = file_size;
         inner.offset = 0;
         inner.size_limit = 0;
@@ -210,17 +279,20 @@ impl LoopDevice {
 
     fn change_file_locked(
         inner: &mut LoopDeviceInner,
-        file_inode: Arc<dyn IndexNode>,
+        file: Arc<KernelFile>,
         total_size: usize,
         read_only: bool,
     ) -> Result<(), SystemError> {
         let effective = Self::calc_effective_size(total_size, inner.offset, inner.size_limit)?;
-        inner.file_inode = Some(file_inode);
+        inner.backing_file = Some(file);
+        // 校验数据保存在旧的后端文件中
+        inner.integrity = None;
         inner.flags = if read_only {
             LoopFlags::READ_ONLY
         } else {
             LoopFlags::empty()
         };
+        inner.file_read_only = read_only;
         inner.file_size = effective;
         Ok(())
     }
@@ -250,16 +322,19 @@ impl LoopDevice {
             id,
             minor,
             inner: SpinLock::new(LoopDeviceInner {
-                file_inode: None,
+                backing_file: None,
                 file_size: 0,
                 device_number: DeviceNumber::new(Major::LOOP_MAJOR, minor),
                 offset: 0,
                 size_limit: 0,
                 flags: LoopFlags::empty(),
+                file_read_only: false,
+                integrity: None,
                 kobject_common: KObjectCommonData::default(),
                 device_common: DeviceCommonData::default(),
                 state: LoopState::Unbound,
                 drain_retry_count: 0,
+                worker: None,
             }),
             block_dev_meta: BlockDevMeta::new(devname, Major::LOOP_MAJOR),
             locked_kobj_state: LockedKObjectState::default(),
@@ -267,6 +342,7 @@ impl LoopDevice {
             fs: RwLock::new(Weak::default()),
             parent: RwLock::new(Weak::default()),
             active_io_count: AtomicU32::new(0),
+            open_count: AtomicU32::new(0),
         });
 
         // 设置 KObjType
@@ -296,7 +372,7 @@ impl LoopDevice {
         for _ in 0..MAX_RETRY {
             let (file_inode, offset, size_limit) = {
                 let inner = self.inner();
-                (inner.file_inode.clone(), inner.offset, inner.size_limit)
+                (inner.file_inode(), inner.offset, inner.size_limit)
             };
 
             let inode = file_inode.ok_or(SystemError::ENODEV)?;
@@ -304,9 +380,8 @@ impl LoopDevice {
 
             let mut inner = self.inner();
             let still_same_inode = inner
-                .file_inode
-                .as_ref()
-                .map(|cur| Arc::ptr_eq(cur, &inode))
+                .file_inode()
+                .map(|cur| Arc::ptr_eq(&cur, &inode))
                 .unwrap_or(false);
             if still_same_inode && inner.offset == offset && inner.size_limit == size_limit {
                 inner.file_size = effective;
@@ -316,6 +391,19 @@ impl LoopDevice {
         Err(SystemError::EAGAIN_OR_EWOULDBLOCK)
     }
 
+    /// 向后端文件所在的挂载注册或取消注册，挂载被卸载之前需要先解除绑定
+    fn track_backing_mount(&self, file: &KernelFile, register: bool) {
+        let Some(mount_fs) = file.inode().fs().downcast_arc::<MountFS>() else {
+            return;
+        };
+        let dependent: Weak<dyn MountDependent> = self.self_ref.clone();
+        if register {
+            mount_fs.add_dependent(dependent);
+        } else {
+            mount_fs.remove_dependent(&dependent);
+        }
+    }
+
     pub fn is_bound(&self) -> bool {
         matches!(self.inner().state(), LoopState::Bound)
     }
@@ -326,26 +414,22 @@ impl LoopDevice {
     ///
     /// ## 参数
     ///
-    /// - `file_inode`: 需要绑定的文件节点。
+    /// - `file`: 需要绑定的后端文件。
     /// - `read_only`: 是否以只读方式绑定。
     ///
     /// ## 返回值
     /// - `Ok(())`: 成功绑定。
     /// - `Err(SystemError)`: 绑定失败的原因。
-    pub fn bind_file(
-        &self,
-        file_inode: Arc<dyn IndexNode>,
-        read_only: bool,
-    ) -> Result<(), SystemError> {
+    pub fn bind_file(&self, file: Arc<KernelFile>, read_only: bool) -> Result<(), SystemError> {
         // 先在锁外拿到 metadata，避免在持锁期间做可能阻塞的操作
-        let metadata = file_inode.metadata()?;
+        let metadata = file.inode().metadata()?;
         if metadata.size < 0 {
             return Err(SystemError::EINVAL);
         }
 
         let total_size = metadata.size as usize;
 
-        // 在同一个临界区里完成状态检查 + 状态转换 + 写入 file_inode，
+        // 在同一个临界区里完成状态检查 + 状态转换 + 写入 backing_file，
         // 避免 set_file() 先修改数据、再 set_state() 失败造成"半更新"。
         let mut inner = self.inner();
         match inner.state() {
@@ -357,13 +441,16 @@ impl LoopDevice {
         }
 
         inner.set_state(LoopState::Bound)?;
-        Self::set_file_locked(&mut inner, file_inode.clone(), total_size);
+        Self::set_file_locked(&mut inner, file.clone(), total_size);
         inner.flags = if read_only {
             LoopFlags::READ_ONLY
         } else {
             LoopFlags::empty()
         };
+        inner.file_read_only = read_only;
+        self.block_dev_meta.set_read_only(read_only);
         drop(inner);
+        self.track_backing_mount(&file, true);
 
         // recalc_effective_size 失败时回滚状态，
         // 避免调用者收到错误但设备实际已处于 Bound 状态的不一致情况。
@@ -373,19 +460,23 @@ impl LoopDevice {
             // 避免覆盖其他操作的结果
             let should_rollback = matches!(inner.state(), LoopState::Bound)
                 && inner
-                    .file_inode
+                    .backing_file
                     .as_ref()
-                    .map(|cur| Arc::ptr_eq(cur, &file_inode))
+                    .map(|cur| Arc::ptr_eq(cur, &file))
                     .unwrap_or(false);
 
             if should_rollback {
-                inner.file_inode = None;
+                inner.backing_file = None;
                 inner.file_size = 0;
                 inner.offset = 0;
                 inner.size_limit = 0;
                 inner.flags = LoopFlags::empty();
+                inner.file_read_only = false;
+                self.block_dev_meta.set_read_only(false);
                 // Bound -> Unbound 是有效转换
                 let _ = inner.set_state(LoopState::Unbound);
+                drop(inner);
+                self.track_backing_mount(&file, false);
             }
             return Err(e);
         }
@@ -404,6 +495,17 @@ impl LoopDevice {
     /// - `Ok(())`: 成功清除。
     /// - `Err(SystemError)`: 清除过程中的错误。
     pub fn clear_file(&self) -> Result<(), SystemError> {
+        if self.is_bound() {
+            // 解除绑定之前把块缓存中的脏数据写回后端文件
+            if let Some(dev) = self.self_ref.upgrade() {
+                if let Err(e) = block_cache().sync_disk(&(dev as Arc<dyn BlockDevice>)) {
+                    warn!(
+                        "{}: failed to write back cached data: {:?}",
+                        self.block_dev_meta.devname, e
+                    );
+                }
+            }
+        }
         let mut inner = self.inner();
         match inner.state() {
             LoopState::Bound | LoopState::Rundown => inner.set_state(LoopState::Unbound)?,
@@ -415,11 +517,25 @@ impl LoopDevice {
             }
         }
 
-        inner.file_inode = None;
+        let old_file = inner.backing_file.take();
         inner.file_size = 0;
         inner.offset = 0;
         inner.size_limit = 0;
         inner.flags = LoopFlags::empty();
+        inner.file_read_only = false;
+        self.block_dev_meta.set_read_only(false);
+        inner.integrity = None;
+        let worker = inner.worker.take();
+        drop(inner);
+
+        if let Some(file) = old_file {
+            self.track_backing_mount(&file, false);
+        }
+
+        // 已经入队的请求会在后端文件被清除之后以 ENODEV 完成
+        if let Some(worker) = worker {
+            worker.stop();
+        }
         Ok(())
     }
 
@@ -433,9 +549,7 @@ impl LoopDevice {
         if info.lo_sizelimit != 0 && !info.lo_sizelimit.is_multiple_of(LBA_SIZE as u64) {
             return Err(SystemError::EINVAL);
         }
-        if LoopFlags::from_bits(info.lo_flags).is_none() {
-            return Err(SystemError::EINVAL);
-        }
+        // 与 Linux 一致，lo_flags 中不可设置的位被忽略，不视为错误
         Ok(())
     }
 
@@ -447,15 +561,6 @@ impl LoopDevice {
         if !(info.lo_offset as u64).is_multiple_of(LBA_SIZE as u64) {
             return Err(SystemError::EINVAL);
         }
-
-        // legacy 的 lo_flags 是 int，这里只支持 LoopFlags 已实现的位
-        if info.lo_flags < 0 {
-            return Err(SystemError::EINVAL);
-        }
-        let flags_u32 = info.lo_flags as u32;
-        if LoopFlags::from_bits(flags_u32).is_none() {
-            return Err(SystemError::EINVAL);
-        }
         Ok(())
     }
 
@@ -463,24 +568,24 @@ impl LoopDevice {
     ///
     /// ## 参数
     ///
-    /// - `user_ptr`: 用户空间传入的 `LoopStatus64` 结构体指针。
+    /// - `arg`: ioctl参数，指向用户空间的 `LoopStatus64` 结构体。
     ///
     /// ## 返回值
     /// - `Ok(())`: 状态设置成功。
     /// - `Err(SystemError::EINVAL)`: 无效的参数或标志位。
     /// - `Err(SystemError::ENXIO)`: 设备未绑定或已卸载。
-    fn set_status64(&self, user_ptr: usize) -> Result<(), SystemError> {
-        if user_ptr == 0 {
+    fn set_status64(&self, arg: IoctlArg) -> Result<(), SystemError> {
+        if arg.value() == 0 {
             return Err(SystemError::EINVAL);
         }
 
-        let reader = UserBufferReader::new::<LoopStatus64>(
-            user_ptr as *const LoopStatus64,
-            core::mem::size_of::<LoopStatus64>(),
-            true,
-        )?;
-        let info: LoopStatus64 = reader.buffer_protected(0)?.read_one(0)?;
-        Self::validate_loop_status64_params(&info)?;
+        let info: LoopStatus64 = arg.read()?;
+        self.apply_status64(&info)
+    }
+
+    /// 把 `LoopStatus64` 中的 offset/sizelimit/flags 应用到设备上
+    fn apply_status64(&self, info: &LoopStatus64) -> Result<(), SystemError> {
+        Self::validate_loop_status64_params(info)?;
 
         let new_offset = info.lo_offset as usize;
         let new_limit = if info.lo_sizelimit == 0 {
@@ -500,7 +605,8 @@ impl LoopDevice {
         // （offset/size_limit/flags/file_size 同一临界区写入，保证对 I/O 原子可见）。
 
         const MAX_RETRY: usize = 16;
-        let new_flags = LoopFlags::from_bits_truncate(info.lo_flags);
+        let new_flags =
+            LoopFlags::from_bits_truncate(info.lo_flags) & LoopFlags::SET_STATUS_SETTABLE;
         let mut retry_count = 0;
         let mut last_inode_changed = false;
 
@@ -511,7 +617,7 @@ impl LoopDevice {
                 if !matches!(inner.state(), LoopState::Bound | LoopState::Rundown) {
                     return Err(SystemError::ENXIO);
                 }
-                inner.file_inode.clone().ok_or(SystemError::ENODEV)?
+                inner.file_inode().ok_or(SystemError::ENODEV)?
             };
 
             let effective = Self::compute_effective_size(&inode, new_offset, new_limit)?;
@@ -520,11 +626,18 @@ impl LoopDevice {
             if !matches!(inner.state(), LoopState::Bound | LoopState::Rundown) {
                 return Err(SystemError::ENXIO);
             }
-            match inner.file_inode.as_ref() {
-                Some(cur_inode) if Arc::ptr_eq(cur_inode, &inode) => {
+            match inner.file_inode() {
+                Some(cur_inode) if Arc::ptr_eq(&cur_inode, &inode) => {
+                    // 校验数据的位置依赖于窗口，开启完整性校验时不允许修改
+                    if inner.integrity.is_some()
+                        && (inner.offset != new_offset || inner.size_limit != new_limit)
+                    {
+                        return Err(SystemError::EBUSY);
+                    }
                     inner.offset = new_offset;
                     inner.size_limit = new_limit;
-                    inner.flags = new_flags;
+                    inner.set_status_flags(new_flags);
+                    self.block_dev_meta.set_read_only(inner.is_read_only());
                     inner.file_size = effective;
                     return Ok(());
                 }
@@ -559,56 +672,76 @@ impl LoopDevice {
     ///
     /// ## 参数
     ///
-    /// - `user_ptr`: 用户态缓冲区地址。
+    /// - `arg`: ioctl参数，指向用户态缓冲区。
     ///
     /// ## 返回值
     /// - `Ok(())`: 信息写回成功。
     /// - `Err(SystemError)`: 读取状态失败。
-    fn get_status64(&self, user_ptr: usize) -> Result<(), SystemError> {
-        if user_ptr == 0 {
+    fn get_status64(&self, arg: IoctlArg) -> Result<(), SystemError> {
+        if arg.value() == 0 {
             return Err(SystemError::EINVAL);
         }
 
-        let info = {
+        let (mut info, inode) = {
             let inner = self.inner();
             if !matches!(inner.state(), LoopState::Bound | LoopState::Rundown) {
                 return Err(SystemError::ENXIO);
             }
             // Linux ABI: 对应 uapi `struct loop_info64`（字段顺序/大小必须匹配）
-            // 目前 DragonOS 仅维护 offset/sizelimit/flags 等核心字段，其它字段置 0。
-            LoopStatus64 {
+            // 加密相关的字段已被 Linux 废弃，这里置 0。
+            let info = LoopStatus64 {
                 lo_offset: inner.offset as u64,
                 lo_sizelimit: inner.size_limit as u64,
                 lo_flags: inner.flags.bits(),
                 lo_number: self.minor,
                 ..LoopStatus64::default()
-            }
+            };
+            (info, inner.file_inode().ok_or(SystemError::ENODEV)?)
         };
 
-        let mut writer = UserBufferWriter::new::<LoopStatus64>(
-            user_ptr as *mut LoopStatus64,
-            core::mem::size_of::<LoopStatus64>(),
-            true,
-        )?;
-        writer.buffer_protected(0)?.write_one(0, &info)?;
+        // 后端文件的信息在锁外获取，避免持锁时阻塞
+        let (dev, ino) = Self::backing_file_ids(&inode);
+        info.lo_device = dev;
+        info.lo_inode = ino;
+        Self::fill_backing_file_name(&inode, &mut info.lo_file_name);
+
+        arg.write(&info)?;
         Ok(())
     }
 
-    fn set_status(&self, user_ptr: usize) -> Result<(), SystemError> {
-        if user_ptr == 0 {
+    /// 后端文件所在设备的设备号和 inode 号，获取失败时为 0
+    fn backing_file_ids(inode: &Arc<dyn IndexNode>) -> (u64, u64) {
+        inode
+            .metadata()
+            .map(|m| (m.dev_id as u64, m.inode_id.data() as u64))
+            .unwrap_or((0, 0))
+    }
+
+    /// 后端文件的路径，用于错误上下文
+    fn backing_file_path(inode: &Arc<dyn IndexNode>) -> String {
+        inode
+            .absolute_path()
+            .unwrap_or_else(|_| "<unknown>".to_string())
+    }
+
+    /// 把后端文件的路径写入 `name`，过长时截断，并保证以 0 结尾
+    fn fill_backing_file_name(inode: &Arc<dyn IndexNode>, name: &mut [u8; LOOP_NAME_SIZE]) {
+        if let Ok(path) = inode.absolute_path() {
+            let _ = strscpy(name, path.as_bytes());
+        }
+    }
+
+    fn set_status(&self, arg: IoctlArg) -> Result<(), SystemError> {
+        if arg.value() == 0 {
             return Err(SystemError::EINVAL);
         }
 
-        let reader = UserBufferReader::new::<LoopStatus>(
-            user_ptr as *const LoopStatus,
-            core::mem::size_of::<LoopStatus>(),
-            true,
-        )?;
-        let info: LoopStatus = reader.buffer_protected(0)?.read_one(0)?;
+        let info: LoopStatus = arg.read()?;
         Self::validate_loop_status_params(&info)?;
 
         let new_offset = info.lo_offset as usize;
-        let new_flags = LoopFlags::from_bits_truncate(info.lo_flags as u32);
+        let new_flags =
+            LoopFlags::from_bits_truncate(info.lo_flags as u32) & LoopFlags::SET_STATUS_SETTABLE;
 
         // legacy loop_info 不携带 sizelimit，这里保持现有的 size_limit 不变，只更新 offset/flags。
         // 同时复用 set_status64 的“快照 -> 计算 -> 原子提交”模式，避免 file_size 与 offset 不一致。
@@ -620,7 +753,7 @@ impl LoopDevice {
                     return Err(SystemError::ENXIO);
                 }
                 (
-                    inner.file_inode.clone().ok_or(SystemError::ENODEV)?,
+                    inner.file_inode().ok_or(SystemError::ENODEV)?,
                     inner.size_limit,
                 )
             };
@@ -631,10 +764,14 @@ impl LoopDevice {
             if !matches!(inner.state(), LoopState::Bound | LoopState::Rundown) {
                 return Err(SystemError::ENXIO);
             }
-            match inner.file_inode.as_ref() {
-                Some(cur_inode) if Arc::ptr_eq(cur_inode, &inode) => {
+            match inner.file_inode() {
+                Some(cur_inode) if Arc::ptr_eq(&cur_inode, &inode) => {
+                    if inner.integrity.is_some() && inner.offset != new_offset {
+                        return Err(SystemError::EBUSY);
+                    }
                     inner.offset = new_offset;
-                    inner.flags = new_flags;
+                    inner.set_status_flags(new_flags);
+                    self.block_dev_meta.set_read_only(inner.is_read_only());
                     inner.file_size = effective;
                     return Ok(());
                 }
@@ -645,33 +782,103 @@ impl LoopDevice {
         Err(SystemError::EBUSY)
     }
 
-    fn get_status(&self, user_ptr: usize) -> Result<(), SystemError> {
-        if user_ptr == 0 {
+    fn get_status(&self, arg: IoctlArg) -> Result<(), SystemError> {
+        if arg.value() == 0 {
             return Err(SystemError::EINVAL);
         }
 
-        let info = {
+        let (mut info, inode) = {
             let inner = self.inner();
             if !matches!(inner.state(), LoopState::Bound | LoopState::Rundown) {
                 return Err(SystemError::ENXIO);
             }
 
-            // legacy loop_info：只保证 offset/flags 等关键字段正确，其它字段目前置 0。
-            // 这仍然能满足绝大多数使用 LOOP_GET_STATUS 的用户态程序。
-            LoopStatus {
+            // legacy loop_info 的 offset 只有 32 位，超出范围时与 Linux 一样返回 EOVERFLOW
+            let offset = i32::try_from(inner.offset).map_err(|_| SystemError::EOVERFLOW)?;
+            let info = LoopStatus {
                 lo_number: self.minor as i32,
-                lo_offset: inner.offset as i32,
+                lo_offset: offset,
                 lo_flags: inner.flags.bits() as i32,
                 ..LoopStatus::default()
-            }
+            };
+            (info, inner.file_inode().ok_or(SystemError::ENODEV)?)
         };
 
-        let mut writer = UserBufferWriter::new::<LoopStatus>(
-            user_ptr as *mut LoopStatus,
-            core::mem::size_of::<LoopStatus>(),
-            true,
-        )?;
-        writer.buffer_protected(0)?.write_one(0, &info)?;
+        let (dev, ino) = Self::backing_file_ids(&inode);
+        info.lo_device = dev as u32;
+        info.lo_inode = ino;
+        Self::fill_backing_file_name(&inode, &mut info.lo_name);
+
+        arg.write(&info)?;
+        Ok(())
+    }
+
+    /// # 功能
+    ///
+    /// 把文件描述符对应的文件绑定到 loop 设备。
+    ///
+    /// ## 参数
+    ///
+    /// - `file_fd`: 后端文件的文件描述符。
+    /// - `read_only`: 是否强制以只读方式绑定。文件本身以只读方式打开时总是只读。
+    ///
+    /// ## 返回值
+    /// - `Ok(())`: 成功绑定。
+    /// - `Err(SystemError)`: 绑定失败的原因。
+    fn attach_fd(&self, file_fd: i32, read_only: bool) -> Result<(), SystemError> {
+        let fd_table = ProcessManager::current_pcb().fd_table();
+        let file = {
+            let guard = fd_table.read();
+            guard.get_file_by_fd(file_fd)
+        }
+        .ok_or(SystemError::EBADF)?;
+        let read_only = read_only || file.flags().is_read_only();
+        match file.file_type() {
+            FileType::File | FileType::BlockDevice => {}
+            _ => return Err(SystemError::EINVAL),
+        }
+
+        self.bind_file(Arc::new(KernelFile::from_file(file)), read_only)
+    }
+
+    /// 目前 loop 设备只支持 512 字节的逻辑块
+    fn validate_block_size(block_size: usize) -> Result<(), SystemError> {
+        if block_size != LBA_SIZE {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(())
+    }
+
+    /// # 功能
+    ///
+    /// 处理 `LOOP_CONFIGURE`：一次性完成绑定文件和设置状态，
+    /// 相当于 `LOOP_SET_FD` + `LOOP_SET_STATUS64`，但中途失败时不会留下已绑定的设备。
+    ///
+    /// ## 参数
+    ///
+    /// - `arg`: ioctl参数，指向用户空间的 `LoopConfig` 结构体。
+    ///
+    /// ## 返回值
+    /// - `Ok(())`: 配置成功。
+    /// - `Err(SystemError)`: 配置失败的原因。
+    fn configure(&self, arg: IoctlArg) -> Result<(), SystemError> {
+        if arg.value() == 0 {
+            return Err(SystemError::EINVAL);
+        }
+
+        let config: LoopConfig = arg.read()?;
+        if config.block_size != 0 {
+            Self::validate_block_size(config.block_size as usize)?;
+        }
+        Self::validate_loop_status64_params(&config.info)?;
+
+        let read_only = config.info.lo_flags & LoopFlags::READ_ONLY.bits() != 0;
+        self.attach_fd(config.fd as i32, read_only)?;
+
+        if let Err(e) = self.apply_status64(&config.info) {
+            let _ = self.clear_file();
+            return Err(e);
+        }
         Ok(())
     }
 
@@ -714,10 +921,16 @@ impl LoopDevice {
             LoopState::Bound => {}
             _ => return Err(SystemError::ENODEV),
         }
-        if inner.file_inode.is_none() {
+        let Some(old_file) = inner.backing_file.clone() else {
             return Err(SystemError::ENODEV);
-        }
-        Self::change_file_locked(&mut inner, inode, total_size, read_only)?;
+        };
+        let new_file = Arc::new(KernelFile::from_file(file));
+        Self::change_file_locked(&mut inner, new_file.clone(), total_size, read_only)?;
+        self.block_dev_meta.set_read_only(read_only);
+        drop(inner);
+
+        self.track_backing_mount(&old_file, false);
+        self.track_backing_mount(&new_file, true);
         Ok(())
     }
 
@@ -726,6 +939,191 @@ impl LoopDevice {
         Ok(())
     }
 
+    /// # 功能
+    ///
+    /// 开启或关闭数据完整性校验。
+    ///
+    /// 开启时需要读取整个设备来生成校验数据，应当在设备空闲时进行；
+    /// 格式化期间并发写入的数据可能在之后的读取中报告校验失败。
+    ///
+    /// ## 参数
+    ///
+    /// - `mode`: 完整性模式。
+    ///
+    /// ## 返回值
+    /// - `Ok(())`: 设置成功。
+    /// - `Err(SystemError::ENOSPC)`: 后端文件太小，放不下校验数据。
+    /// - `Err(SystemError::EROFS)`: 只读设备上没有可用的校验数据，且无法生成。
+    /// - `Err(SystemError::EBUSY)`: 设置期间后端文件或窗口被修改。
+    fn set_integrity(&self, mode: LoopIntegrityMode) -> Result<(), SystemError> {
+        let (file, offset, file_size, read_only, current) = {
+            let inner = self.inner();
+            if !matches!(inner.state(), LoopState::Bound) {
+                return Err(SystemError::ENXIO);
+            }
+            (
+                inner.backing_file.clone().ok_or(SystemError::ENODEV)?,
+                inner.offset,
+                inner.file_size,
+                inner.is_read_only(),
+                inner.integrity.clone(),
+            )
+        };
+
+        if mode == LoopIntegrityMode::Disable {
+            let current = match current {
+                Some(current) => current,
+                None => return Ok(()),
+            };
+            {
+                let mut inner = self.inner();
+                match &inner.integrity {
+                    Some(cur) if Arc::ptr_eq(&cur.blk, &current.blk) => inner.integrity = None,
+                    _ => return Err(SystemError::EBUSY),
+                }
+            }
+            if !read_only {
+                current.store.invalidate()?;
+            }
+            return Ok(());
+        }
+
+        if current.is_some() && mode == LoopIntegrityMode::Enable {
+            return Ok(());
+        }
+        if current.is_some() && read_only {
+            return Err(SystemError::EROFS);
+        }
+
+        // 已开启时重新格式化，沿用原有的布局
+        let store = match &current {
+            Some(current) => current.store.clone(),
+            None => Arc::new(LoopIntegrityStore::new(file.clone(), offset, file_size)?),
+        };
+        let reuse = mode == LoopIntegrityMode::Enable && store.probe()?;
+        if !reuse {
+            if read_only {
+                return Err(SystemError::EROFS);
+            }
+            store.format()?;
+        }
+        if current.is_some() {
+            return Ok(());
+        }
+
+        let mut inner = self.inner();
+        let unchanged = inner
+            .backing_file
+            .as_ref()
+            .map(|cur| Arc::ptr_eq(cur, &file))
+            .unwrap_or(false)
+            && inner.offset == offset
+            && inner.file_size == file_size
+            && inner.integrity.is_none();
+        if !unchanged {
+            return Err(SystemError::EBUSY);
+        }
+        inner.integrity = Some(LoopIntegrity {
+            blk: Arc::new(BlkIntegrity::new(store.clone())),
+            store,
+        });
+        drop(inner);
+
+        info!(
+            "{}: integrity enabled ({} sectors, crc32c{})",
+            self.block_dev_meta.devname,
+            self.disk_range().len(),
+            if crc32c_accelerated() { ", hw" } else { "" }
+        );
+        Ok(())
+    }
+
+    fn get_integrity(&self, arg: IoctlArg) -> Result<(), SystemError> {
+        if arg.value() == 0 {
+            return Err(SystemError::EINVAL);
+        }
+
+        let info = {
+            let inner = self.inner();
+            if !matches!(inner.state(), LoopState::Bound | LoopState::Rundown) {
+                return Err(SystemError::ENXIO);
+            }
+            match &inner.integrity {
+                Some(integrity) => LoopIntegrityInfo {
+                    enabled: 1,
+                    accelerated: crc32c_accelerated() as u32,
+                    data_sectors: integrity.store.data_blocks() as u64,
+                    verified: integrity.blk.verified(),
+                    mismatches: integrity.blk.mismatches(),
+                },
+                None => LoopIntegrityInfo {
+                    accelerated: crc32c_accelerated() as u32,
+                    ..LoopIntegrityInfo::default()
+                },
+            }
+        };
+
+        arg.write(&info)?;
+        Ok(())
+    }
+
+    /// 通知用户态后端文件发生了变化（绑定、解绑或更换），并更新分区
+    fn notify_media_change(&self) {
+        if let Some(dev) = self.self_ref.upgrade() {
+            block_dev_manager().notify_media_change(&(dev as Arc<dyn BlockDevice>));
+        }
+        self.update_partitions();
+    }
+
+    /// 设置了`PARTSCAN`时按后端文件当前的内容重新扫描分区表，否则删除已有的分区
+    fn update_partitions(&self) {
+        let Some(dev) = self.self_ref.upgrade() else {
+            return;
+        };
+        let scan = {
+            let inner = self.inner();
+            matches!(inner.state(), LoopState::Bound) && inner.flags.contains(LoopFlags::PARTSCAN)
+        };
+        let dev = dev as Arc<dyn BlockDevice>;
+        let result = if scan {
+            block_dev_manager().rescan_partitions(&dev)
+        } else {
+            block_dev_manager().drop_partitions(&dev)
+        };
+        if let Err(e) = result {
+            warn!(
+                "{}: failed to update partitions: {:?}",
+                self.block_dev_meta.devname, e
+            );
+        }
+    }
+
+    /// 如果设备容量相对于`old_sectors`发生了变化，通知用户态
+    fn notify_capacity_change(&self, old_sectors: usize) {
+        if let Some(dev) = self.self_ref.upgrade() {
+            block_dev_manager().notify_capacity_change(&(dev as Arc<dyn BlockDevice>), old_sectors);
+        }
+    }
+
+    /// 设备节点被打开时调用
+    pub(crate) fn on_open(&self) {
+        self.open_count.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// 设备节点被关闭时调用。最后一次关闭时，如果设置了 `AUTOCLEAR`，则解除绑定
+    pub(crate) fn on_release(&self) {
+        if self.open_count.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        let autoclear = {
+            let inner = self.inner();
+            matches!(inner.state(), LoopState::Bound) && inner.flags.contains(LoopFlags::AUTOCLEAR)
+        };
+        if autoclear && self.clear_file().is_ok() {
+            self.notify_media_change();
+        }
+    }
+
     /// # 功能
     ///
     /// I/O 操作开始时调用，增加活跃 I/O 计数
@@ -917,20 +1315,37 @@ impl LoopDevice {
             self.id()
         );
         let mut inner = self.inner();
-        if let Some(file_inode) = inner.file_inode.take() {
-            drop(file_inode);
+        let file = inner.backing_file.take();
+        inner.file_size = 0;
+        inner.offset = 0;
+        inner.size_limit = 0;
+        drop(inner);
+        if let Some(file) = file {
+            self.track_backing_mount(&file, false);
             warn!(
                 "File inode was still present during final cleanup for loop{}",
                 self.minor()
             );
         }
-        inner.file_size = 0;
-        inner.offset = 0;
-        inner.size_limit = 0;
         info!("Loop device loop{} cleanup complete", self.minor());
     }
 }
 
+impl MountDependent for LoopDevice {
+    fn detach_from_mount(&self) {
+        warn!(
+            "{}: backing file is being unmounted, detaching",
+            self.block_dev_meta.devname
+        );
+        if let Err(e) = self.clear_file() {
+            error!(
+                "{}: failed to detach from the backing file: {:?}",
+                self.block_dev_meta.devname, e
+            );
+        }
+    }
+}
+
 impl KObject for LoopDevice {
     fn as_any_ref(&self) -> &dyn Any {
         self
@@ -1019,6 +1434,12 @@ impl IndexNode for LoopDevice {
         if len > buf.len() {
             return Err(SystemError::ENOBUFS);
         }
+        // 读到设备末尾时返回0，跨过末尾时只读到末尾为止
+        let capacity = self.inner().capacity();
+        if offset >= capacity {
+            return Ok(0);
+        }
+        let len = len.min(capacity - offset);
         BlockDevice::read_at_bytes(self, offset, len, buf)
     }
 
@@ -1032,6 +1453,11 @@ impl IndexNode for LoopDevice {
         if len > buf.len() {
             return Err(SystemError::E2BIG);
         }
+        let capacity = self.inner().capacity();
+        if len > 0 && offset >= capacity {
+            return Err(SystemError::ENOSPC);
+        }
+        let len = len.min(capacity.saturating_sub(offset));
         BlockDevice::write_at_bytes(self, offset, len, &buf[..len])
     }
 
@@ -1042,7 +1468,7 @@ impl IndexNode for LoopDevice {
     fn metadata(&self) -> Result<crate::filesystem::vfs::Metadata, SystemError> {
         let (inode, file_size, devnum) = {
             let inner = self.inner();
-            let inode = inner.file_inode.clone().ok_or(SystemError::EPERM)?;
+            let inode = inner.file_inode().ok_or(SystemError::EPERM)?;
             (inode, inner.file_size, inner.device_number)
         };
 
@@ -1075,56 +1501,93 @@ impl IndexNode for LoopDevice {
         _private_data: MutexGuard<FilePrivateData>,
     ) -> Result<usize, SystemError> {
         let ioctl_cmd = LoopIoctl::from_u32(cmd).ok_or(SystemError::ENOSYS)?;
+        let arg = IoctlArg::new(cmd, data);
 
         match ioctl_cmd {
             LoopIoctl::LoopSetFd => {
-                let file_fd = data as i32;
-                let fd_table = ProcessManager::current_pcb().fd_table();
-                let file = {
-                    let guard = fd_table.read();
-                    guard.get_file_by_fd(file_fd)
-                }
-                .ok_or(SystemError::EBADF)?;
-                let read_only = file.flags().is_read_only();
-                let inode = file.inode();
-                let metadata = inode.metadata()?;
-                match metadata.file_type {
-                    FileType::File | FileType::BlockDevice => {}
-                    _ => return Err(SystemError::EINVAL),
-                }
-
-                self.bind_file(inode, read_only)?;
+                self.attach_fd(data as i32, false)?;
+                self.notify_media_change();
                 Ok(0)
             }
             LoopIoctl::LoopClrFd => {
+                if !self.is_bound() {
+                    return Err(SystemError::ENXIO);
+                }
+                // 设备仍被其他人打开时，与 Linux 一样推迟到最后一次关闭时再解除绑定
+                if self.open_count.load(Ordering::Acquire) > 1 {
+                    self.inner().flags.insert(LoopFlags::AUTOCLEAR);
+                    return Ok(0);
+                }
                 self.clear_file()?;
+                self.notify_media_change();
                 Ok(0)
             }
             LoopIoctl::LoopSetStatus => {
-                self.set_status(data)?;
+                let old_sectors = self.disk_range().len();
+                self.set_status(arg)?;
+                self.notify_capacity_change(old_sectors);
+                self.update_partitions();
                 Ok(0)
             }
             LoopIoctl::LoopGetStatus => {
-                self.get_status(data)?;
+                self.get_status(arg)?;
                 Ok(0)
             }
             LoopIoctl::LoopSetStatus64 => {
-                self.set_status64(data)?;
+                let old_sectors = self.disk_range().len();
+                self.set_status64(arg)?;
+                self.notify_capacity_change(old_sectors);
+                self.update_partitions();
                 Ok(0)
             }
             LoopIoctl::LoopGetStatus64 => {
-                self.get_status64(data)?;
+                self.get_status64(arg)?;
                 Ok(0)
             }
             LoopIoctl::LoopChangeFd => {
                 self.change_fd(data as i32)?;
+                self.notify_media_change();
                 Ok(0)
             }
             LoopIoctl::LoopSetCapacity => {
+                let old_sectors = self.disk_range().len();
                 self.set_capacity(data)?;
+                self.notify_capacity_change(old_sectors);
+                Ok(0)
+            }
+            LoopIoctl::LoopSetDirectIo => {
+                if !self.is_bound() {
+                    return Err(SystemError::ENXIO);
+                }
+                // 后端文件总是经过页缓存访问，只允许关闭直接I/O
+                if data != 0 {
+                    return Err(SystemError::EINVAL);
+                }
+                Ok(0)
+            }
+            LoopIoctl::LoopSetBlockSize => {
+                if !self.is_bound() {
+                    return Err(SystemError::ENXIO);
+                }
+                Self::validate_block_size(data)?;
+                Ok(0)
+            }
+            LoopIoctl::LoopConfigure => {
+                self.configure(arg)?;
+                self.notify_media_change();
+                Ok(0)
+            }
+            LoopIoctl::LoopSetIntegrity => {
+                let mode = LoopIntegrityMode::from_usize(data).ok_or(SystemError::EINVAL)?;
+                let old_sectors = self.disk_range().len();
+                self.set_integrity(mode)?;
+                self.notify_capacity_change(old_sectors);
+                Ok(0)
+            }
+            LoopIoctl::LoopGetIntegrity => {
+                self.get_integrity(arg)?;
                 Ok(0)
             }
-            _ => Err(SystemError::ENOSYS),
         }
     }
 }
@@ -1215,14 +1678,13 @@ impl BlockDevice for LoopDevice {
         &self.block_dev_meta
     }
 
+    fn devnode_mode(&self) -> InodeMode {
+        // loop 设备可以访问任意被绑定的文件，因此默认只允许 root 访问
+        InodeMode::from_bits_truncate(0o600)
+    }
+
     fn disk_range(&self) -> GeneralBlockRange {
-        let inner = self.inner();
-        let blocks = if inner.file_size == 0 {
-            0
-        } else {
-            inner.file_size.saturating_add(LBA_SIZE - 1) / LBA_SIZE
-        };
-        drop(inner);
+        let blocks = self.inner().capacity() / LBA_SIZE;
         GeneralBlockRange::new(0, blocks).unwrap_or(GeneralBlockRange {
             lba_start: 0,
             lba_end: 0,
@@ -1237,89 +1699,201 @@ impl BlockDevice for LoopDevice {
     ) -> Result<usize, SystemError> {
         // 使用 IoGuard 确保 I/O 计数正确管理
         let _io_guard = IoGuard::new(self)?;
+        Ok(self.do_read(lba_id_start, count, buf)?)
+    }
+
+    fn write_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        // 使用 IoGuard 确保 I/O 计数正确管理
+        let _io_guard = IoGuard::new(self)?;
+        Ok(self.do_write(lba_id_start, count, buf)?)
+    }
+
+    fn submit_bio(&self, bio: Arc<BioRequest>) -> Result<(), SystemError> {
+        if !self.is_bound() {
+            return Err(SystemError::ENODEV);
+        }
+
+        // 活跃 I/O 计数覆盖整个异步过程，直到 BIO 完成，删除设备时才能正确等待
+        self.io_start()?;
+        let dev = self.self_ref.clone();
+        bio.on_complete(move |_| {
+            if let Some(dev) = dev.upgrade() {
+                dev.io_end();
+            }
+        });
+
+        let result = self.worker().and_then(|worker| worker.submit(bio.clone()));
+        if let Err(e) = &result {
+            bio.complete(Err(e.clone()));
+        }
+        result
+    }
+
+    fn sync(&self) -> Result<(), SystemError> {
+        if !self.is_bound() {
+            return Ok(());
+        }
+        self.flush()
+    }
+
+    fn integrity(&self) -> Option<Arc<BlkIntegrity>> {
+        self.inner().integrity.as_ref().map(|i| i.blk.clone())
+    }
+
+    fn discard(&self, range: GeneralBlockRange) -> Result<(), SystemError> {
+        if self.blkdev_meta().is_read_only() {
+            return Err(SystemError::EROFS);
+        }
+        let _io_guard = IoGuard::new(self)?;
+        Ok(self.do_discard(range)?)
+    }
+
+    fn blk_size_log2(&self) -> u8 {
+        9
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn device(&self) -> Arc<dyn Device> {
+        self.self_ref.upgrade().unwrap()
+    }
 
+    fn block_size(&self) -> usize {
+        LBA_SIZE
+    }
+
+    fn partitions(&self) -> Vec<Arc<Partition>> {
+        self.blkdev_meta().partitions()
+    }
+}
+
+impl LoopDevice {
+    /// # 功能
+    ///
+    /// 从后端文件读取数据，调用者负责维护活跃 I/O 计数。
+    fn do_read(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, ContextError> {
         if count == 0 {
             return Ok(0);
         }
         let len = count.checked_mul(LBA_SIZE).ok_or(SystemError::EOVERFLOW)?;
         if len > buf.len() {
-            return Err(SystemError::EINVAL);
+            return Err(SystemError::EINVAL.into());
         }
 
-        let (file_inode, base_offset, limit_end) = {
+        let (file, file_offset) = {
             let inner = self.inner();
-            let inode = inner.file_inode.clone().ok_or(SystemError::ENODEV)?;
-            let limit = inner
-                .offset
-                .checked_add(inner.file_size)
-                .ok_or(SystemError::EOVERFLOW)?;
-            (inode, inner.offset, limit)
+            let file = inner.backing_file.clone().ok_or(SystemError::ENODEV)?;
+            (file, inner.map_range(lba_id_start, len)?)
         };
 
-        let block_offset = lba_id_start
+        let read = file
+            .read_at(file_offset, &mut buf[..len])
+            .with_context(|| {
+                format!(
+                    "read {} bytes from backing file {} at offset {}",
+                    len,
+                    Self::backing_file_path(&file.inode()),
+                    file_offset
+                )
+            })?;
+        // 后端文件在绑定之后可能被截断，与Linux一致，文件末尾之后的部分读出来全为0
+        buf[read..len].fill(0);
+        Ok(len)
+    }
+
+    /// # 功能
+    ///
+    /// 在后端文件上打洞，释放被丢弃的扇区占用的空间，调用者负责维护活跃 I/O 计数。
+    ///
+    /// 开启完整性校验时不支持discard：打洞之后数据与校验值不再一致
+    fn do_discard(&self, range: GeneralBlockRange) -> Result<(), ContextError> {
+        let len = range
+            .len()
             .checked_mul(LBA_SIZE)
             .ok_or(SystemError::EOVERFLOW)?;
-        let file_offset = base_offset
-            .checked_add(block_offset)
-            .ok_or(SystemError::EOVERFLOW)?;
-
-        let end = file_offset.checked_add(len).ok_or(SystemError::EOVERFLOW)?;
-        if end > limit_end {
-            return Err(SystemError::ENOSPC);
-        }
+        let (file_inode, file_offset) = {
+            let inner = self.inner();
+            if inner.integrity.is_some() {
+                return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP.into());
+            }
+            let inode = inner.file_inode().ok_or(SystemError::ENODEV)?;
+            (inode, inner.map_range(range.lba_start, len)?)
+        };
 
-        let data = Mutex::new(FilePrivateData::Unused);
-        let data_guard = data.lock();
+        file_inode.punch_hole(file_offset, len).with_context(|| {
+            format!(
+                "punch hole of {} bytes in backing file {} at offset {}",
+                len,
+                Self::backing_file_path(&file_inode),
+                file_offset
+            )
+        })
+    }
 
-        file_inode.read_at(file_offset, len, &mut buf[..len], data_guard)
+    /// # 功能
+    ///
+    /// 对后端文件执行 fsync，使之前完成的写入持久化，调用者负责维护活跃 I/O 计数。
+    fn do_flush(&self) -> Result<usize, ContextError> {
+        let file = self
+            .inner()
+            .backing_file
+            .clone()
+            .ok_or(SystemError::ENODEV)?;
+        file.fsync(false).with_context(|| {
+            format!(
+                "fsync backing file {}",
+                Self::backing_file_path(&file.inode())
+            )
+        })?;
+        Ok(0)
     }
 
-    fn write_at_sync(
+    /// # 功能
+    ///
+    /// 向后端文件写入数据，调用者负责维护活跃 I/O 计数。
+    fn do_write(
         &self,
         lba_id_start: BlockId,
         count: usize,
         buf: &[u8],
-    ) -> Result<usize, SystemError> {
-        // 使用 IoGuard 确保 I/O 计数正确管理
-        let _io_guard = IoGuard::new(self)?;
-
+    ) -> Result<usize, ContextError> {
         if count == 0 {
             return Ok(0);
         }
         let len = count.checked_mul(LBA_SIZE).ok_or(SystemError::EOVERFLOW)?;
         if len > buf.len() {
-            return Err(SystemError::EINVAL);
+            return Err(SystemError::EINVAL.into());
         }
 
-        let (file_inode, base_offset, limit_end) = {
+        let (file, file_offset) = {
             let inner = self.inner();
             if inner.is_read_only() {
-                return Err(SystemError::EROFS);
+                return Err(SystemError::EROFS.into());
             }
-            let inode = inner.file_inode.clone().ok_or(SystemError::ENODEV)?;
-            let limit = inner
-                .offset
-                .checked_add(inner.file_size)
-                .ok_or(SystemError::EOVERFLOW)?;
-            (inode, inner.offset, limit)
+            let file = inner.backing_file.clone().ok_or(SystemError::ENODEV)?;
+            (file, inner.map_range(lba_id_start, len)?)
         };
 
-        let block_offset = lba_id_start
-            .checked_mul(LBA_SIZE)
-            .ok_or(SystemError::EOVERFLOW)?;
-        let file_offset = base_offset
-            .checked_add(block_offset)
-            .ok_or(SystemError::EOVERFLOW)?;
-
-        let end = file_offset.checked_add(len).ok_or(SystemError::EOVERFLOW)?;
-        if end > limit_end {
-            return Err(SystemError::ENOSPC);
-        }
-
-        let data = Mutex::new(FilePrivateData::Unused);
-        let data_guard = data.lock();
-
-        let written = file_inode.write_at(file_offset, len, &buf[..len], data_guard)?;
+        let written = file.write_at(file_offset, &buf[..len]).with_context(|| {
+            format!(
+                "write {} bytes to backing file {} at offset {}",
+                len,
+                Self::backing_file_path(&file.inode()),
+                file_offset
+            )
+        })?;
 
         if written > 0 {
             let _ = self.recalc_effective_size();
@@ -1328,27 +1902,59 @@ impl BlockDevice for LoopDevice {
         Ok(written)
     }
 
-    fn sync(&self) -> Result<(), SystemError> {
-        Ok(())
-    }
-
-    fn blk_size_log2(&self) -> u8 {
-        9
-    }
-
-    fn as_any_ref(&self) -> &dyn Any {
-        self
-    }
-
-    fn device(&self) -> Arc<dyn Device> {
-        self.self_ref.upgrade().unwrap()
+    /// # 功能
+    ///
+    /// 在工作线程中处理一个 BIO，读写后端文件并完成该 BIO。
+    pub(super) fn handle_bio(&self, bio: &Arc<BioRequest>) {
+        let count = bio.count();
+        let len = count * LBA_SIZE;
+        let result = match bio.bio_type() {
+            BioType::Read => self.handle_bio_segments(bio, |lba, seg| {
+                // SAFETY: BIO 完成之前缓冲区只由工作线程访问
+                let buf = unsafe { &mut *seg };
+                self.do_read(lba, buf.len() / LBA_SIZE, buf)
+            }),
+            BioType::Write => self.handle_bio_segments(bio, |lba, seg| {
+                // SAFETY: 同上
+                let buf = unsafe { &*seg };
+                self.do_write(lba, buf.len() / LBA_SIZE, buf)
+            }),
+            BioType::Flush => self.do_flush(),
+            BioType::Discard => GeneralBlockRange::new(bio.lba_start(), bio.lba_start() + count)
+                .ok_or(SystemError::EINVAL.into())
+                .and_then(|range| self.do_discard(range))
+                .map(|_| len),
+        };
+        // 错误上下文随BIO交给等待者，在块设备层的边界打印
+        match result {
+            Ok(len) => bio.complete(Ok(len)),
+            Err(e) => bio.fail(e),
+        }
     }
 
-    fn block_size(&self) -> usize {
-        LBA_SIZE
+    /// 按顺序读写 BIO 的各个缓冲区段（直接读写用户页的 BIO 每一页是一段）
+    fn handle_bio_segments<F>(&self, bio: &Arc<BioRequest>, mut f: F) -> Result<usize, ContextError>
+    where
+        F: FnMut(BlockId, *mut [u8]) -> Result<usize, ContextError>,
+    {
+        let mut lba = bio.lba_start();
+        let mut done = 0;
+        for seg in bio.segments() {
+            done += f(lba, seg)?;
+            lba += seg.len() / LBA_SIZE;
+        }
+        Ok(done)
     }
 
-    fn partitions(&self) -> Vec<Arc<Partition>> {
-        Vec::new()
+    /// 获取工作线程，第一次调用时启动
+    fn worker(&self) -> Result<Arc<LoopWorker>, SystemError> {
+        if let Some(worker) = &self.inner().worker {
+            return Ok(worker.clone());
+        }
+        // 创建内核线程可能睡眠，不能持有自旋锁
+        let worker = LoopWorker::start(self.self_ref.clone(), self.minor)?;
+        let mut inner = self.inner();
+        // 并发提交时可能已经有其他线程创建了工作线程，多余的那个在 drop 时退出
+        Ok(inner.worker.get_or_insert(worker).clone())
     }
 }
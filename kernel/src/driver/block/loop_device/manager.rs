@@ -16,6 +16,8 @@ use super::{
     loop_device::LoopDevice,
 };
 
+kernel_cmdline_param_kv!(MAX_LOOP_PARAM, max_loop, "");
+
 /// Loop 设备管理器
 pub struct LoopManager {
     inner: SpinLock<LoopManagerInner>,
@@ -29,8 +31,8 @@ pub struct LoopManagerInner {
 impl LoopManager {
     /// 最大设备数量
     const MAX_DEVICES: usize = 256;
-    /// 初始化时创建的设备数量
-    const MAX_INIT_DEVICES: usize = 8;
+    /// 初始化时默认创建的设备数量，可以通过命令行参数`max_loop`修改
+    const DEFAULT_INIT_DEVICES: usize = 8;
 
     pub fn new() -> Self {
         Self {
@@ -281,22 +283,32 @@ impl LoopManager {
         Ok(())
     }
 
-    pub fn find_free_minor(&self) -> Option<u32> {
-        let inner = self.inner();
-        for minor in 0..Self::MAX_DEVICES as u32 {
-            match &inner.devices[minor as usize] {
-                // 只有 Unbound 才能视为可复用；删除流程中的设备不应返回给 loop_add
-                Some(dev) if Self::device_reusable(dev) => return Some(minor),
-                Some(_) => continue,
-                None => return Some(minor),
-            }
-        }
-        None
+    /// # 功能
+    ///
+    /// 获取一个空闲的 loop 设备（`LOOP_CTL_GET_FREE`）。
+    ///
+    /// 优先复用次设备号最小的未绑定设备，没有时按需创建新设备，
+    /// 保证返回的次设备号对应的 `/dev/loopN` 已经存在。
+    ///
+    /// ## 返回值
+    /// - `Ok(Arc<LoopDevice>)`: 空闲的设备。
+    /// - `Err(SystemError::ENOSPC)`: 设备数量已达上限。
+    pub fn loop_get_free(&self) -> Result<Arc<LoopDevice>, SystemError> {
+        self.loop_add(None)
+    }
+
+    /// 启动时创建的设备数量
+    fn init_device_count() -> usize {
+        MAX_LOOP_PARAM
+            .value_str()
+            .and_then(|s| s.trim().parse::<usize>().ok())
+            .unwrap_or(Self::DEFAULT_INIT_DEVICES)
+            .min(Self::MAX_DEVICES)
     }
 
     pub fn loop_init(&self, _driver: Arc<LoopDeviceDriver>) -> Result<(), SystemError> {
         let mut inner = self.inner();
-        for minor in 0..Self::MAX_INIT_DEVICES {
+        for minor in 0..Self::init_device_count() {
             let minor_u32 = minor as u32;
             if Self::find_device_by_minor_locked(&inner, minor_u32).is_some() {
                 continue;
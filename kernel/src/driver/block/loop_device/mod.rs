@@ -7,16 +7,20 @@
 //!
 //! - `constants`: 常量和枚举类型定义
 //! - `loop_device`: Loop 设备实现
+//! - `integrity`: Loop 设备的数据完整性模式
 //! - `loop_control`: Loop-control 控制设备实现
 //! - `manager`: Loop 设备管理器
 //! - `driver`: Loop 设备驱动
+//! - `worker`: Loop 设备的异步 I/O 工作线程
 
 mod constants;
 mod driver;
+mod integrity;
 mod loop_control;
 #[allow(clippy::module_inception)]
 mod loop_device;
 mod manager;
+mod worker;
 
 use alloc::sync::Arc;
 use system_error::SystemError;
@@ -0,0 +1,101 @@
+//! Loop 设备的 I/O 工作线程
+//!
+//! 与 Linux 的 loop worker 模型一致：提交到 loop 设备的 BIO 不在提交者的上下文中处理，
+//! 而是放入每个设备独立的队列，由内核线程 `loop{minor}` 依次读写后端文件，
+//! 完成后通过 BIO 的完成回调通知提交者。
+
+use alloc::{
+    format,
+    sync::{Arc, Weak},
+};
+use log::error;
+use system_error::SystemError;
+
+use crate::{
+    driver::base::block::{bio::BioRequest, request_queue::RequestQueue},
+    process::kthread::{KernelThreadClosure, KernelThreadMechanism},
+};
+
+use super::loop_device::LoopDevice;
+
+/// 每个 loop 设备一个的 I/O 工作线程
+#[derive(Debug)]
+pub(super) struct LoopWorker {
+    queue: Arc<RequestQueue>,
+}
+
+impl LoopWorker {
+    /// # 功能
+    ///
+    /// 创建请求队列并启动工作线程。
+    ///
+    /// ## 参数
+    /// - `dev`: 所属 loop 设备的弱引用，线程不持有设备本身。
+    /// - `minor`: 设备的次设备号，用于线程命名。
+    ///
+    /// ## 返回值
+    /// - `Ok(Arc<LoopWorker>)`: 工作线程已启动。
+    /// - `Err(SystemError::ENOMEM)`: 内核线程创建失败。
+    pub fn start(dev: Weak<LoopDevice>, minor: u32) -> Result<Arc<Self>, SystemError> {
+        let queue = RequestQueue::new();
+        let thread_queue = queue.clone();
+        let name = format!("loop{}", minor);
+        KernelThreadMechanism::create_and_run(
+            KernelThreadClosure::EmptyClosure((
+                alloc::boxed::Box::new(move || {
+                    loop_worker_thread(dev.clone(), thread_queue.clone())
+                }),
+                (),
+            )),
+            name.clone(),
+        )
+        .ok_or_else(|| {
+            error!("Failed to create loop worker thread {}", name);
+            SystemError::ENOMEM
+        })?;
+
+        Ok(Arc::new(Self { queue }))
+    }
+
+    /// 将 BIO 放入队列（可能与相邻的请求合并），由工作线程异步处理
+    pub fn submit(&self, bio: Arc<BioRequest>) -> Result<(), SystemError> {
+        self.queue.submit_bio(bio)
+    }
+
+    /// 停止接收新的请求。已入队的请求处理完之后线程退出
+    pub fn stop(&self) {
+        self.queue.close();
+    }
+}
+
+impl Drop for LoopWorker {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+fn loop_worker_thread(dev: Weak<LoopDevice>, queue: Arc<RequestQueue>) -> i32 {
+    loop {
+        if let Err(e) = queue.wait_for_work() {
+            error!("loop worker wait_for_work interrupted: {:?}", e);
+            continue;
+        }
+
+        let batch = queue.drain_batch();
+        if batch.is_empty() {
+            if queue.is_closed() {
+                break;
+            }
+            continue;
+        }
+
+        for rq in batch {
+            match dev.upgrade() {
+                Some(dev) => dev.handle_bio(&rq.into_bio()),
+                None => rq.complete(Err(SystemError::ENODEV)),
+            }
+        }
+    }
+
+    0
+}
@@ -0,0 +1,866 @@
+//! md设备（/dev/mdN）
+
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{any::Any, fmt::Debug};
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    driver::base::{
+        block::{
+            block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE},
+            cache::block_cache,
+            disk_info::Partition,
+            manager::{block_dev_manager, BlockDevMeta},
+        },
+        class::Class,
+        device::{
+            bus::Bus,
+            device_number::{DeviceNumber, Major},
+            driver::Driver,
+            DevName, Device, DeviceCommonData, DeviceType, IdTable,
+        },
+        kobject::{KObjType, KObject, KObjectCommonData, KObjectState, LockedKObjectState},
+        kset::KSet,
+    },
+    filesystem::{
+        devfs::{DevFS, DeviceINode, LockedDevFSInode},
+        kernfs::KernFSInode,
+        vfs::{
+            ioctl::IoctlArg, utils::DName, FilePrivateData, IndexNode, InodeFlags, InodeId,
+            InodeMode, Metadata,
+        },
+    },
+    libs::{
+        mutex::{Mutex, MutexGuard},
+        rwlock::RwLock,
+        rwsem::{RwSemReadGuard, RwSemWriteGuard},
+        spinlock::{SpinLock, SpinLockGuard},
+    },
+    mm::MemoryManagementArch,
+    time::PosixTimeSpec,
+};
+
+use super::{
+    ioctl::{
+        MduArrayInfo, MduDiskInfo, MduParam, MduVersion, ADD_NEW_DISK, CLEAR_ARRAY, GET_ARRAY_INFO,
+        GET_DISK_INFO, HOT_ADD_DISK, HOT_REMOVE_DISK, MD_DISK_FAULTY, MD_DISK_REMOVED,
+        MD_DISK_SYNC, MD_MAJOR_VERSION, MD_MINOR_VERSION, MD_PATCHLEVEL_VERSION, MD_SB_CLEAN,
+        MD_SB_DISKS, RAID_VERSION, RUN_ARRAY, SET_ARRAY_INFO, SET_DISK_FAULTY, STOP_ARRAY,
+        STOP_ARRAY_RO,
+    },
+    md_devices,
+    personality::{md_run_personality, MdConfig, MdPersonality, MD_PERSONALITIES},
+    rdev::MdRdev,
+};
+
+pub(super) const MD_BASENAME: &str = "md";
+
+#[derive(Debug)]
+struct InnerMdDevice {
+    device_common: DeviceCommonData,
+    kobject_common: KObjectCommonData,
+}
+
+/// 阵列的配置和成员盘
+#[derive(Debug, Default)]
+struct MdState {
+    /// SET_ARRAY_INFO设置的配置，为`None`时阵列没有配置
+    config: Option<MdConfig>,
+    /// 阵列的创建时间
+    ctime: u32,
+    /// 所有的成员盘，包括备用盘和故障盘
+    disks: Vec<Arc<MdRdev>>,
+}
+
+/// 一个md设备
+///
+/// 设备刚创建时没有配置，容量为0。与`mdadm --build`一样，依次通过SET_ARRAY_INFO、ADD_NEW_DISK
+/// 配置阵列，RUN_ARRAY启动阵列后设备才可以读写，STOP_ARRAY停止阵列并清除配置。
+#[cast_to([sync] Device)]
+pub struct MdDevice {
+    blkdev_meta: BlockDevMeta,
+    inner: SpinLock<InnerMdDevice>,
+    locked_kobj_state: LockedKObjectState,
+    self_ref: Weak<Self>,
+    parent: RwLock<Weak<LockedDevFSInode>>,
+    fs: RwLock<Weak<DevFS>>,
+    /// 串行化阵列的配置操作
+    state: Mutex<MdState>,
+    /// 运行中的阵列
+    pers: RwLock<Option<Arc<dyn MdPersonality>>>,
+}
+
+impl Debug for MdDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MdDevice")
+            .field("devname", &self.blkdev_meta.devname)
+            .finish()
+    }
+}
+
+impl MdDevice {
+    /// # 创建设备
+    ///
+    /// ## 参数
+    /// - `id`: 设备编号，设备节点为`/dev/md{id}`
+    pub fn new(id: usize) -> Arc<Self> {
+        let devname = DevName::new(format!("{MD_BASENAME}{id}"), id);
+        Arc::new_cyclic(|self_ref| Self {
+            blkdev_meta: BlockDevMeta::new(devname, Major::MD_MAJOR),
+            inner: SpinLock::new(InnerMdDevice {
+                device_common: DeviceCommonData::default(),
+                kobject_common: KObjectCommonData::default(),
+            }),
+            locked_kobj_state: LockedKObjectState::default(),
+            self_ref: self_ref.clone(),
+            parent: RwLock::new(Weak::default()),
+            fs: RwLock::new(Weak::default()),
+            state: Mutex::new(MdState::default()),
+            pers: RwLock::new(None),
+        })
+    }
+
+    fn inner(&self) -> SpinLockGuard<'_, InnerMdDevice> {
+        self.inner.lock_irqsave()
+    }
+
+    /// 整个设备的设备号
+    pub fn device_number(&self) -> DeviceNumber {
+        DeviceNumber::new(self.blkdev_meta.major, self.blkdev_meta.base_minor)
+    }
+
+    /// 运行中的阵列
+    pub fn personality(&self) -> Option<Arc<dyn MdPersonality>> {
+        self.pers.read().clone()
+    }
+
+    /// 阵列是否正在使用这个设备
+    fn uses_disk(&self, devnum: DeviceNumber) -> bool {
+        self.state
+            .lock()
+            .disks
+            .iter()
+            .any(|rdev| rdev.device_num() == devnum)
+    }
+
+    /// 读写`[sector, sector + count)`时使用的阵列
+    fn io_personality(
+        &self,
+        sector: BlockId,
+        count: usize,
+    ) -> Result<Arc<dyn MdPersonality>, SystemError> {
+        let pers = self.personality().ok_or(SystemError::ENXIO)?;
+        let end = sector.checked_add(count).ok_or(SystemError::EINVAL)?;
+        if end > pers.nr_sectors() {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(pers)
+    }
+
+    fn bdev(&self) -> Arc<dyn BlockDevice> {
+        self.self_ref.upgrade().unwrap() as Arc<dyn BlockDevice>
+    }
+
+    pub fn ioctl(&self, cmd: u32, data: usize) -> Result<usize, SystemError> {
+        let arg = IoctlArg::new(cmd, data);
+        match cmd {
+            RAID_VERSION => {
+                arg.write(&MduVersion {
+                    major: MD_MAJOR_VERSION,
+                    minor: MD_MINOR_VERSION,
+                    patchlevel: MD_PATCHLEVEL_VERSION,
+                })?;
+            }
+            GET_ARRAY_INFO => arg.write(&self.array_info()?)?,
+            GET_DISK_INFO => {
+                let info: MduDiskInfo = arg.read()?;
+                arg.write(&self.disk_info(info.number))?;
+            }
+            SET_ARRAY_INFO => {
+                if data == 0 {
+                    log::warn!(
+                        "md: {}: arrays with superblocks are not supported, use mdadm --build",
+                        self.dev_name()
+                    );
+                    return Err(SystemError::EINVAL);
+                }
+                self.set_array_info(&arg.read()?)?;
+            }
+            ADD_NEW_DISK => self.add_new_disk(&arg.read()?)?,
+            RUN_ARRAY => {
+                // 与Linux一样，参数中的内容不使用
+                if data != 0 {
+                    arg.read::<MduParam>()?;
+                }
+                self.run()?;
+            }
+            STOP_ARRAY => self.stop()?,
+            STOP_ARRAY_RO => self.set_readonly()?,
+            CLEAR_ARRAY => self.clear()?,
+            HOT_ADD_DISK => self.hot_add_disk(DeviceNumber::from_linux_dev_t(data as u32))?,
+            HOT_REMOVE_DISK => self.hot_remove_disk(DeviceNumber::from_linux_dev_t(data as u32))?,
+            SET_DISK_FAULTY => self.set_disk_faulty(DeviceNumber::from_linux_dev_t(data as u32))?,
+            _ => return Err(SystemError::ENOIOCTLCMD),
+        }
+        Ok(0)
+    }
+
+    /// GET_ARRAY_INFO返回的信息
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::ENODEV)`: 阵列没有配置
+    fn array_info(&self) -> Result<MduArrayInfo, SystemError> {
+        let state = self.state.lock();
+        let config = state.config.as_ref().ok_or(SystemError::ENODEV)?;
+        let pers = self.personality();
+
+        let mut info = MduArrayInfo {
+            major_version: MD_MAJOR_VERSION,
+            minor_version: MD_MINOR_VERSION,
+            ctime: state.ctime,
+            level: config.level,
+            size: (config.dev_sectors * LBA_SIZE / 1024) as i32,
+            nr_disks: state.disks.len() as i32,
+            raid_disks: config.raid_disks as i32,
+            md_minor: self.blkdev_meta.base_minor as i32,
+            not_persistent: 1,
+            utime: state.ctime,
+            chunk_size: (config.chunk_sectors * LBA_SIZE) as i32,
+            ..Default::default()
+        };
+        let syncing = pers.as_ref().is_some_and(|p| p.sync_progress().is_some());
+        if config.clean || (pers.is_some() && !syncing) {
+            info.state |= 1 << MD_SB_CLEAN;
+        }
+        if let Some(pers) = pers {
+            info.size = (pers.nr_sectors() * LBA_SIZE / 1024 / Self::data_disks(config)) as i32;
+        }
+        for rdev in &state.disks {
+            if rdev.is_faulty() {
+                info.failed_disks += 1;
+                continue;
+            }
+            info.working_disks += 1;
+            if rdev.raid_disk() < 0 {
+                info.spare_disks += 1;
+            } else if rdev.is_in_sync() {
+                info.active_disks += 1;
+            }
+        }
+        Ok(info)
+    }
+
+    /// 阵列中保存数据的成员盘数，用于由阵列的容量计算成员盘使用的容量
+    fn data_disks(config: &MdConfig) -> usize {
+        match config.level {
+            0 => config.raid_disks,
+            _ => 1,
+        }
+    }
+
+    /// GET_DISK_INFO返回的信息，`number`对应的成员盘不存在时返回`MD_DISK_REMOVED`
+    fn disk_info(&self, number: i32) -> MduDiskInfo {
+        self.state
+            .lock()
+            .disks
+            .iter()
+            .find(|rdev| rdev.number() == number)
+            .map(|rdev| rdev.disk_info())
+            .unwrap_or(MduDiskInfo {
+                number,
+                raid_disk: -1,
+                state: 1 << MD_DISK_REMOVED,
+                ..Default::default()
+            })
+    }
+
+    /// # 设置阵列的配置
+    ///
+    /// 只支持没有superblock的阵列（`not_persistent`为1），阵列的配置只保存在内存中
+    fn set_array_info(&self, info: &MduArrayInfo) -> Result<(), SystemError> {
+        let mut state = self.state.lock();
+        if state.config.is_some() {
+            return Err(SystemError::EBUSY);
+        }
+        if info.not_persistent == 0 {
+            log::warn!(
+                "md: {}: arrays with superblocks are not supported, use mdadm --build",
+                self.dev_name()
+            );
+            return Err(SystemError::EINVAL);
+        }
+        if !MD_PERSONALITIES
+            .iter()
+            .any(|(level, _)| *level == info.level)
+        {
+            log::warn!(
+                "md: {}: unsupported RAID level {}",
+                self.dev_name(),
+                info.level
+            );
+            return Err(SystemError::EINVAL);
+        }
+        if info.raid_disks <= 0 || info.raid_disks > MD_SB_DISKS || info.size < 0 {
+            return Err(SystemError::EINVAL);
+        }
+        // 与Linux一样，chunk的大小至少是一页，并且是2的幂
+        let chunk_size = info.chunk_size as usize;
+        if info.level == 0 && (chunk_size < MMArch::PAGE_SIZE || !chunk_size.is_power_of_two()) {
+            return Err(SystemError::EINVAL);
+        }
+
+        state.config = Some(MdConfig {
+            level: info.level,
+            raid_disks: info.raid_disks as usize,
+            chunk_sectors: chunk_size / LBA_SIZE,
+            dev_sectors: info.size as usize * 2,
+            clean: info.state & (1 << MD_SB_CLEAN) != 0,
+        });
+        state.ctime = PosixTimeSpec::now().tv_sec as u32;
+        Ok(())
+    }
+
+    /// 成员盘不能同时属于其他阵列
+    ///
+    /// 在持有自己的`state`之前检查，避免两个阵列互相等待
+    fn check_disk_unused(&self, devnum: DeviceNumber) -> Result<(), SystemError> {
+        if devnum == self.device_number() {
+            return Err(SystemError::EINVAL);
+        }
+        if md_devices()
+            .iter()
+            .filter(|md| !core::ptr::eq(md.as_ref(), self))
+            .any(|md| md.uses_disk(devnum))
+        {
+            return Err(SystemError::EBUSY);
+        }
+        Ok(())
+    }
+
+    /// 按设备号打开一个成员盘
+    fn open_rdev(
+        &self,
+        state: &MdState,
+        devnum: DeviceNumber,
+        number: i32,
+        raid_disk: i32,
+        in_sync: bool,
+    ) -> Result<Arc<MdRdev>, SystemError> {
+        if state.disks.iter().any(|rdev| rdev.device_num() == devnum) {
+            return Err(SystemError::EEXIST);
+        }
+        let disk = block_dev_manager()
+            .lookup_gendisk_by_devnum(devnum)
+            .ok_or(SystemError::ENODEV)?;
+        Ok(MdRdev::new(&disk, number, raid_disk, in_sync))
+    }
+
+    /// # 添加成员盘
+    ///
+    /// 阵列运行时等同于HOT_ADD_DISK
+    fn add_new_disk(&self, info: &MduDiskInfo) -> Result<(), SystemError> {
+        let devnum = DeviceNumber::new(Major::new(info.major as u32), info.minor as u32);
+        if self.personality().is_some() {
+            return self.hot_add_disk(devnum);
+        }
+
+        self.check_disk_unused(devnum)?;
+        let mut state = self.state.lock();
+        let config = state.config.as_ref().ok_or(SystemError::EINVAL)?;
+        if info.state & (1 << MD_DISK_FAULTY) != 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let raid_disk = if (0..config.raid_disks as i32).contains(&info.raid_disk)
+            && !state
+                .disks
+                .iter()
+                .any(|rdev| rdev.raid_disk() == info.raid_disk)
+        {
+            info.raid_disk
+        } else {
+            -1
+        };
+        let in_sync = info.state & (1 << MD_DISK_SYNC) != 0;
+        let rdev = self.open_rdev(&state, devnum, info.number, raid_disk, in_sync)?;
+        log::info!("md: {}: bind<{}>", self.dev_name(), rdev.name());
+        state.disks.push(rdev);
+        Ok(())
+    }
+
+    /// 启动阵列
+    fn run(&self) -> Result<(), SystemError> {
+        let state = self.state.lock();
+        if self.personality().is_some() {
+            return Err(SystemError::EBUSY);
+        }
+        let config = state.config.as_ref().ok_or(SystemError::EINVAL)?;
+        let mut slots = alloc::vec![None; config.raid_disks];
+        for rdev in &state.disks {
+            if let Ok(slot) = usize::try_from(rdev.raid_disk()) {
+                slots[slot] = Some(rdev.clone());
+            }
+        }
+        let pers = md_run_personality(&self.dev_name().to_string(), config, slots)?;
+        let level_name = MD_PERSONALITIES
+            .iter()
+            .find(|(level, _)| *level == config.level)
+            .map(|(_, name)| *name)
+            .unwrap_or("unknown");
+        log::info!(
+            "md/{}: {}: active with {} out of {} devices, {} sectors",
+            level_name,
+            self.dev_name(),
+            pers.slots().iter().flatten().count(),
+            config.raid_disks,
+            pers.nr_sectors()
+        );
+        *self.pers.write() = Some(pers);
+        self.blkdev_meta.set_read_only(false);
+        block_dev_manager().notify_media_change(&self.bdev());
+        Ok(())
+    }
+
+    /// 停止阵列并清除配置
+    fn stop(&self) -> Result<(), SystemError> {
+        let mut state = self.state.lock();
+        let Some(pers) = self.personality() else {
+            return Err(SystemError::ENXIO);
+        };
+        block_cache().sync_disk(&self.bdev())?;
+        pers.stop();
+        if let Err(e) = pers.flush() {
+            log::warn!("md: {}: flush failed: {:?}", self.dev_name(), e);
+        }
+        *self.pers.write() = None;
+        state.config = None;
+        state.disks.clear();
+        block_dev_manager().notify_media_change(&self.bdev());
+        log::info!("md: {} stopped.", self.dev_name());
+        Ok(())
+    }
+
+    /// 把运行中的阵列切换为只读
+    fn set_readonly(&self) -> Result<(), SystemError> {
+        let _state = self.state.lock();
+        let pers = self.personality().ok_or(SystemError::ENXIO)?;
+        block_cache().sync_disk(&self.bdev())?;
+        pers.flush()?;
+        self.blkdev_meta.set_read_only(true);
+        log::info!("md: {} switched to read-only mode.", self.dev_name());
+        Ok(())
+    }
+
+    /// 清除没有运行的阵列的配置
+    fn clear(&self) -> Result<(), SystemError> {
+        let mut state = self.state.lock();
+        if self.personality().is_some() {
+            return Err(SystemError::EBUSY);
+        }
+        state.config = None;
+        state.disks.clear();
+        Ok(())
+    }
+
+    fn find_rdev(state: &MdState, devnum: DeviceNumber) -> Result<Arc<MdRdev>, SystemError> {
+        state
+            .disks
+            .iter()
+            .find(|rdev| rdev.device_num() == devnum)
+            .cloned()
+            .ok_or(SystemError::ENXIO)
+    }
+
+    /// 向运行中的阵列添加成员盘，RAID1会在后台把数据同步到新的成员盘上
+    fn hot_add_disk(&self, devnum: DeviceNumber) -> Result<(), SystemError> {
+        self.check_disk_unused(devnum)?;
+        let mut state = self.state.lock();
+        let pers = self.personality().ok_or(SystemError::ENODEV)?;
+        let number = state
+            .disks
+            .iter()
+            .map(|rdev| rdev.number() + 1)
+            .max()
+            .unwrap_or(0);
+        let rdev = self.open_rdev(&state, devnum, number, -1, false)?;
+        pers.hot_add(&rdev)?;
+        log::info!("md: {}: bind<{}>", self.dev_name(), rdev.name());
+        state.disks.push(rdev);
+        Ok(())
+    }
+
+    /// 从运行中的阵列移除故障盘或备用盘
+    fn hot_remove_disk(&self, devnum: DeviceNumber) -> Result<(), SystemError> {
+        let mut state = self.state.lock();
+        let pers = self.personality().ok_or(SystemError::ENODEV)?;
+        let rdev = Self::find_rdev(&state, devnum)?;
+        if rdev.raid_disk() >= 0 {
+            pers.hot_remove(&rdev)?;
+        }
+        state.disks.retain(|r| !Arc::ptr_eq(r, &rdev));
+        log::info!("md: {}: unbind<{}>", self.dev_name(), rdev.name());
+        Ok(())
+    }
+
+    /// 把成员盘标记为故障，用于模拟磁盘出错
+    fn set_disk_faulty(&self, devnum: DeviceNumber) -> Result<(), SystemError> {
+        let state = self.state.lock();
+        let pers = self.personality().ok_or(SystemError::ENODEV)?;
+        let rdev = Self::find_rdev(&state, devnum)?;
+        // 备用盘不影响阵列
+        if rdev.raid_disk() < 0 {
+            rdev.set_faulty();
+            return Ok(());
+        }
+        if !pers.error(&rdev) {
+            return Err(SystemError::EBUSY);
+        }
+        Ok(())
+    }
+
+    /// /proc/mdstat中这个阵列的内容，阵列没有运行时返回`None`
+    pub fn mdstat(&self) -> Option<String> {
+        let state = self.state.lock();
+        let config = state.config.as_ref()?;
+        let pers = self.personality()?;
+        let level_name = MD_PERSONALITIES
+            .iter()
+            .find(|(level, _)| *level == config.level)
+            .map(|(_, name)| *name)?;
+
+        let mut s = format!("{} : active", self.dev_name());
+        if self.blkdev_meta.is_read_only() {
+            s.push_str(" (read-only)");
+        }
+        s.push(' ');
+        s.push_str(level_name);
+        for rdev in &state.disks {
+            s.push_str(&format!(" {}[{}]", rdev.name(), rdev.number()));
+            if rdev.is_faulty() {
+                s.push_str("(F)");
+            } else if rdev.raid_disk() < 0 {
+                s.push_str("(S)");
+            }
+        }
+        s.push_str(&format!(
+            "\n      {} blocks {}\n",
+            pers.nr_sectors() / 2,
+            pers.status()
+        ));
+        if let Some((done, total)) = pers.sync_progress() {
+            let permille = done * 1000 / total.max(1);
+            s.push_str(&format!(
+                "      resync = {}.{}% ({}/{})\n",
+                permille / 10,
+                permille % 10,
+                done / 2,
+                total / 2
+            ));
+        }
+        Some(s)
+    }
+}
+
+impl IndexNode for MdDevice {
+    fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
+        self.fs.read().upgrade().expect("MdDevice fs is not set")
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        let blocks = self.disk_range().len();
+        Ok(Metadata {
+            dev_id: 0,
+            inode_id: InodeId::new(0),
+            size: (blocks * LBA_SIZE) as i64,
+            blk_size: LBA_SIZE,
+            blocks,
+            atime: Default::default(),
+            mtime: Default::default(),
+            ctime: Default::default(),
+            btime: Default::default(),
+            file_type: crate::filesystem::vfs::FileType::BlockDevice,
+            mode: InodeMode::from_bits_truncate(0o660),
+            flags: InodeFlags::empty(),
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            raw_dev: self.device_number(),
+        })
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let parent = self.parent.read();
+        if let Some(parent) = parent.upgrade() {
+            return Ok(parent as Arc<dyn IndexNode>);
+        }
+        Err(SystemError::ENOENT)
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn dname(&self) -> Result<DName, SystemError> {
+        Ok(DName::from(self.blkdev_meta.devname.clone().as_ref()))
+    }
+
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _mode: &crate::filesystem::vfs::file::FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+}
+
+impl DeviceINode for MdDevice {
+    fn set_fs(&self, fs: Weak<DevFS>) {
+        *self.fs.write() = fs;
+    }
+
+    fn set_parent(&self, parent: Weak<LockedDevFSInode>) {
+        *self.parent.write() = parent;
+    }
+}
+
+impl BlockDevice for MdDevice {
+    fn dev_name(&self) -> &DevName {
+        &self.blkdev_meta.devname
+    }
+
+    fn blkdev_meta(&self) -> &BlockDevMeta {
+        &self.blkdev_meta
+    }
+
+    fn disk_range(&self) -> GeneralBlockRange {
+        let blocks = self.personality().map(|p| p.nr_sectors()).unwrap_or(0);
+        GeneralBlockRange::new(0, blocks).unwrap_or(GeneralBlockRange {
+            lba_start: 0,
+            lba_end: 0,
+        })
+    }
+
+    fn read_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let len = count * LBA_SIZE;
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        let pers = self.io_personality(lba_id_start, count)?;
+        pers.read(lba_id_start, &mut buf[..len])?;
+        Ok(len)
+    }
+
+    fn write_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let len = count * LBA_SIZE;
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        let pers = self.io_personality(lba_id_start, count)?;
+        pers.write(lba_id_start, &buf[..len])?;
+        Ok(len)
+    }
+
+    fn sync(&self) -> Result<(), SystemError> {
+        match self.personality() {
+            Some(pers) => pers.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn discard(&self, range: GeneralBlockRange) -> Result<(), SystemError> {
+        let pers = self.io_personality(range.lba_start, range.len())?;
+        pers.discard(range.lba_start, range.len())
+    }
+
+    fn blk_size_log2(&self) -> u8 {
+        9
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn device(&self) -> Arc<dyn Device> {
+        self.self_ref.upgrade().unwrap()
+    }
+
+    fn block_size(&self) -> usize {
+        LBA_SIZE
+    }
+
+    fn partitions(&self) -> Vec<Arc<Partition>> {
+        self.blkdev_meta().partitions()
+    }
+}
+
+impl Device for MdDevice {
+    fn dev_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id_table(&self) -> IdTable {
+        IdTable::new("md".to_string(), None)
+    }
+
+    fn bus(&self) -> Option<Weak<dyn Bus>> {
+        self.inner().device_common.bus.clone()
+    }
+
+    fn set_bus(&self, bus: Option<Weak<dyn Bus>>) {
+        self.inner().device_common.bus = bus;
+    }
+
+    fn class(&self) -> Option<Arc<dyn Class>> {
+        let mut guard = self.inner();
+        let class = guard.device_common.class.clone()?.upgrade();
+        if class.is_none() {
+            guard.device_common.class = None;
+        }
+        class
+    }
+
+    fn set_class(&self, class: Option<Weak<dyn Class>>) {
+        self.inner().device_common.class = class;
+    }
+
+    fn driver(&self) -> Option<Arc<dyn Driver>> {
+        let mut guard = self.inner();
+        let driver = guard.device_common.driver.clone()?.upgrade();
+        if driver.is_none() {
+            guard.device_common.driver = None;
+        }
+        driver
+    }
+
+    fn set_driver(&self, driver: Option<Weak<dyn Driver>>) {
+        self.inner().device_common.driver = driver;
+    }
+
+    fn is_dead(&self) -> bool {
+        false
+    }
+
+    fn can_match(&self) -> bool {
+        self.inner().device_common.can_match
+    }
+
+    fn set_can_match(&self, can_match: bool) {
+        self.inner().device_common.can_match = can_match;
+    }
+
+    fn state_synced(&self) -> bool {
+        true
+    }
+
+    fn dev_parent(&self) -> Option<Weak<dyn Device>> {
+        self.inner().device_common.get_parent_weak_or_clear()
+    }
+
+    fn set_dev_parent(&self, parent: Option<Weak<dyn Device>>) {
+        self.inner().device_common.parent = parent;
+    }
+}
+
+impl KObject for MdDevice {
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn set_inode(&self, inode: Option<Arc<KernFSInode>>) {
+        self.inner().kobject_common.kern_inode = inode;
+    }
+
+    fn inode(&self) -> Option<Arc<KernFSInode>> {
+        self.inner().kobject_common.kern_inode.clone()
+    }
+
+    fn parent(&self) -> Option<Weak<dyn KObject>> {
+        self.inner().kobject_common.parent.clone()
+    }
+
+    fn set_parent(&self, parent: Option<Weak<dyn KObject>>) {
+        self.inner().kobject_common.parent = parent;
+    }
+
+    fn kset(&self) -> Option<Arc<KSet>> {
+        self.inner().kobject_common.kset.clone()
+    }
+
+    fn set_kset(&self, kset: Option<Arc<KSet>>) {
+        self.inner().kobject_common.kset = kset;
+    }
+
+    fn kobj_type(&self) -> Option<&'static dyn KObjType> {
+        self.inner().kobject_common.kobj_type
+    }
+
+    fn name(&self) -> String {
+        self.dev_name().to_string()
+    }
+
+    fn set_name(&self, _name: String) {}
+
+    fn kobj_state(&self) -> RwSemReadGuard<'_, KObjectState> {
+        self.locked_kobj_state.read()
+    }
+
+    fn kobj_state_mut(&self) -> RwSemWriteGuard<'_, KObjectState> {
+        self.locked_kobj_state.write()
+    }
+
+    fn set_kobj_state(&self, state: KObjectState) {
+        *self.locked_kobj_state.write() = state;
+    }
+
+    fn set_kobj_type(&self, ktype: Option<&'static dyn KObjType>) {
+        self.inner().kobject_common.kobj_type = ktype;
+    }
+}
@@ -0,0 +1,94 @@
+//! md的ioctl接口，与Linux的include/uapi/linux/raid/md_u.h一致
+
+use crate::filesystem::vfs::ioctl::{io, ior, iow};
+
+/// ioctl命令号的类型字段，等于md的主设备号
+const MD_IOCTL_TYPE: u8 = 9;
+
+pub const RAID_VERSION: u32 = ior::<MduVersion>(MD_IOCTL_TYPE, 0x10);
+pub const GET_ARRAY_INFO: u32 = ior::<MduArrayInfo>(MD_IOCTL_TYPE, 0x11);
+pub const GET_DISK_INFO: u32 = ior::<MduDiskInfo>(MD_IOCTL_TYPE, 0x12);
+
+pub const CLEAR_ARRAY: u32 = io(MD_IOCTL_TYPE, 0x20);
+pub const ADD_NEW_DISK: u32 = iow::<MduDiskInfo>(MD_IOCTL_TYPE, 0x21);
+pub const HOT_REMOVE_DISK: u32 = io(MD_IOCTL_TYPE, 0x22);
+pub const SET_ARRAY_INFO: u32 = iow::<MduArrayInfo>(MD_IOCTL_TYPE, 0x23);
+pub const HOT_ADD_DISK: u32 = io(MD_IOCTL_TYPE, 0x28);
+pub const SET_DISK_FAULTY: u32 = io(MD_IOCTL_TYPE, 0x29);
+
+pub const RUN_ARRAY: u32 = iow::<MduParam>(MD_IOCTL_TYPE, 0x30);
+pub const STOP_ARRAY: u32 = io(MD_IOCTL_TYPE, 0x32);
+pub const STOP_ARRAY_RO: u32 = io(MD_IOCTL_TYPE, 0x33);
+
+/// RAID_VERSION返回的版本号
+pub const MD_MAJOR_VERSION: i32 = 0;
+pub const MD_MINOR_VERSION: i32 = 90;
+pub const MD_PATCHLEVEL_VERSION: i32 = 3;
+
+/// 阵列最多的成员盘数
+pub const MD_SB_DISKS: i32 = 27;
+
+/// `mdu_array_info_t.state`: 阵列的数据是一致的，不需要同步
+pub const MD_SB_CLEAN: i32 = 0;
+
+/// `mdu_disk_info_t.state`的各个位
+pub const MD_DISK_FAULTY: i32 = 0;
+pub const MD_DISK_ACTIVE: i32 = 1;
+pub const MD_DISK_SYNC: i32 = 2;
+pub const MD_DISK_REMOVED: i32 = 3;
+
+/// `mdu_version_t`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MduVersion {
+    pub major: i32,
+    pub minor: i32,
+    pub patchlevel: i32,
+}
+
+/// `mdu_array_info_t`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MduArrayInfo {
+    pub major_version: i32,
+    pub minor_version: i32,
+    pub patch_version: i32,
+    pub ctime: u32,
+    pub level: i32,
+    /// 每个成员盘使用的容量（KiB），为0时使用全部容量
+    pub size: i32,
+    pub nr_disks: i32,
+    pub raid_disks: i32,
+    pub md_minor: i32,
+    pub not_persistent: i32,
+    pub utime: u32,
+    pub state: i32,
+    pub active_disks: i32,
+    pub working_disks: i32,
+    pub failed_disks: i32,
+    pub spare_disks: i32,
+    pub layout: i32,
+    /// 条带的大小（字节）
+    pub chunk_size: i32,
+}
+
+/// `mdu_disk_info_t`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MduDiskInfo {
+    pub number: i32,
+    pub major: i32,
+    pub minor: i32,
+    /// 在阵列中的位置，-1表示备用盘
+    pub raid_disk: i32,
+    pub state: i32,
+}
+
+/// `mdu_param_t`，RUN_ARRAY的参数，与Linux一样不使用其中的内容
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MduParam {
+    pub personality: i32,
+    pub chunk_size: i32,
+    pub max_fault: i32,
+}
@@ -0,0 +1,71 @@
+//! 软件RAID（md）
+//!
+//! 启动时创建`/dev/md0`~`/dev/md{MD_NR_DEVICES-1}`，把多个块设备组合成一个阵列：
+//! - `raid0`: 条带化，参见[`raid0`]；
+//! - `raid1`: 镜像，支持读负载均衡、成员盘故障后继续运行，以及后台同步，参见[`raid1`]。
+//!
+//! 用户态通过md设备的ioctl管理阵列，接口与Linux相同，可以直接使用`mdadm --build`创建阵列，
+//! 用`mdadm --fail/--remove/--add`模拟磁盘故障和更换磁盘。阵列的状态可以在`/proc/mdstat`中查看。
+//!
+//! 只支持没有superblock的阵列：阵列的配置不会写到成员盘上，重启后需要重新`mdadm --build`。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/md/md.c
+
+mod device;
+mod ioctl;
+mod personality;
+mod raid0;
+mod raid1;
+mod rdev;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    driver::base::block::{block_device::BlockDevice, manager::block_dev_manager},
+    init::initcall::INITCALL_DEVICE,
+    libs::spinlock::SpinLock,
+};
+
+pub use device::MdDevice;
+
+use personality::MD_PERSONALITIES;
+
+/// 启动时创建的md设备数量
+const MD_NR_DEVICES: usize = 4;
+
+static MD_DEVICES: SpinLock<Vec<Arc<MdDevice>>> = SpinLock::new(Vec::new());
+
+/// 所有的md设备
+pub fn md_devices() -> Vec<Arc<MdDevice>> {
+    MD_DEVICES.lock().clone()
+}
+
+/// # /proc/mdstat的内容
+pub fn md_status() -> String {
+    let mut s = String::from("Personalities :");
+    for (_, name) in MD_PERSONALITIES {
+        s.push_str(&format!(" [{}]", name));
+    }
+    s.push('\n');
+    for md in md_devices() {
+        if let Some(status) = md.mdstat() {
+            s.push_str(&status);
+            s.push('\n');
+        }
+    }
+    s.push_str("unused devices: <none>\n");
+    s
+}
+
+#[unified_init(INITCALL_DEVICE)]
+fn md_init() -> Result<(), SystemError> {
+    for id in 0..MD_NR_DEVICES {
+        let dev = MdDevice::new(id);
+        block_dev_manager().register(dev.clone() as Arc<dyn BlockDevice>)?;
+        MD_DEVICES.lock().push(dev);
+    }
+    log::info!("md: {} devices, personalities: raid0 raid1", MD_NR_DEVICES);
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! RAID级别（personality）的接口
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::fmt::Debug;
+use system_error::SystemError;
+
+use crate::driver::base::block::block_device::BlockId;
+
+use super::{raid0::Raid0, raid1::Raid1, rdev::MdRdev};
+
+/// 一种RAID级别的实现
+///
+/// 扇区号都是阵列上的扇区号，缓冲区长度是`LBA_SIZE`的整数倍，并且不会超出阵列的范围
+pub trait MdPersonality: Debug + Send + Sync {
+    /// 阵列的容量（扇区数）
+    fn nr_sectors(&self) -> usize;
+
+    fn read(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError>;
+
+    fn write(&self, sector: BlockId, buf: &[u8]) -> Result<(), SystemError>;
+
+    fn discard(&self, sector: BlockId, count: usize) -> Result<(), SystemError>;
+
+    fn flush(&self) -> Result<(), SystemError>;
+
+    /// # 成员盘出错
+    ///
+    /// ## 返回值
+    /// - `true`: 成员盘被标记为故障
+    /// - `false`: 这是最后一个可用的成员盘，没有被标记为故障
+    fn error(&self, rdev: &Arc<MdRdev>) -> bool;
+
+    /// 向运行中的阵列添加成员盘，默认不支持
+    fn hot_add(&self, _rdev: &Arc<MdRdev>) -> Result<(), SystemError> {
+        Err(SystemError::EINVAL)
+    }
+
+    /// # 从运行中的阵列移除成员盘
+    ///
+    /// 只能移除故障盘和备用盘，默认不支持
+    fn hot_remove(&self, _rdev: &Arc<MdRdev>) -> Result<(), SystemError> {
+        Err(SystemError::EBUSY)
+    }
+
+    /// 阵列中各个位置上的成员盘
+    fn slots(&self) -> Vec<Option<Arc<MdRdev>>>;
+
+    /// 正在同步时返回(已同步的扇区数, 总扇区数)
+    fn sync_progress(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// 停止阵列前调用，等待后台的同步结束
+    fn stop(&self) {}
+
+    /// /proc/mdstat中阵列的状态，比如`[2/1] [U_]`
+    fn status(&self) -> String;
+}
+
+/// 阵列的配置，由SET_ARRAY_INFO和ADD_NEW_DISK设置
+#[derive(Debug)]
+pub struct MdConfig {
+    pub level: i32,
+    pub raid_disks: usize,
+    /// 条带的大小（扇区数）
+    pub chunk_sectors: usize,
+    /// 每个成员盘使用的扇区数，为0时使用全部容量
+    pub dev_sectors: usize,
+    /// 阵列的数据是否一致，不一致时RAID1启动后会先同步
+    pub clean: bool,
+}
+
+/// 支持的RAID级别，对应`mdu_array_info_t.level`
+pub const MD_PERSONALITIES: &[(i32, &str)] = &[(0, "raid0"), (1, "raid1")];
+
+/// # 按配置创建阵列
+///
+/// ## 参数
+/// - `name`: 阵列的设备名，用于输出日志
+/// - `config`: 阵列的配置
+/// - `rdevs`: 按位置排列的成员盘
+pub fn md_run_personality(
+    name: &str,
+    config: &MdConfig,
+    rdevs: Vec<Option<Arc<MdRdev>>>,
+) -> Result<Arc<dyn MdPersonality>, SystemError> {
+    match config.level {
+        0 => Ok(Raid0::new(name, config, rdevs)?),
+        1 => Ok(Raid1::new(name, config, rdevs)?),
+        _ => Err(SystemError::EINVAL),
+    }
+}
+
+/// 阵列中成员盘使用的扇区数：不超过最小的成员盘，并且按`align`对齐
+pub fn md_dev_sectors(
+    config: &MdConfig,
+    rdevs: &[Arc<MdRdev>],
+    align: usize,
+) -> Result<usize, SystemError> {
+    let min = rdevs
+        .iter()
+        .map(|rdev| rdev.nr_sectors())
+        .min()
+        .ok_or(SystemError::EINVAL)?;
+    let sectors = if config.dev_sectors != 0 {
+        if config.dev_sectors > min {
+            return Err(SystemError::EINVAL);
+        }
+        config.dev_sectors
+    } else {
+        min
+    };
+    let sectors = sectors - sectors % align;
+    if sectors == 0 {
+        return Err(SystemError::ENOSPC);
+    }
+    Ok(sectors)
+}
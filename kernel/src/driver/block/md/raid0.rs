@@ -0,0 +1,144 @@
+//! RAID0：条带化
+//!
+//! 阵列上的数据按`chunk_size`切分，依次轮流放到各个成员盘上。所有成员盘使用相同的容量
+//! （最小的成员盘的容量），没有实现Linux中容量不同的成员盘组成多个zone的布局。
+//! 任何一个成员盘出错，整个阵列都会失效。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/md/raid0.c
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+use system_error::SystemError;
+
+use crate::driver::base::block::block_device::{BlockId, LBA_SIZE};
+
+use super::{
+    personality::{md_dev_sectors, MdConfig, MdPersonality},
+    rdev::MdRdev,
+};
+
+#[derive(Debug)]
+pub struct Raid0 {
+    name: String,
+    disks: Vec<Arc<MdRdev>>,
+    chunk_sectors: usize,
+    /// 每个成员盘使用的扇区数
+    dev_sectors: usize,
+    /// 有成员盘出错后，所有的I/O都返回`EIO`
+    broken: AtomicBool,
+}
+
+impl Raid0 {
+    pub fn new(
+        name: &str,
+        config: &MdConfig,
+        rdevs: Vec<Option<Arc<MdRdev>>>,
+    ) -> Result<Arc<Self>, SystemError> {
+        if config.chunk_sectors == 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let disks = rdevs
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                log::warn!("md/raid0:{}: all raid disks must be present", name);
+                SystemError::EINVAL
+            })?;
+        let dev_sectors = md_dev_sectors(config, &disks, config.chunk_sectors)?;
+        Ok(Arc::new(Self {
+            name: String::from(name),
+            disks,
+            chunk_sectors: config.chunk_sectors,
+            dev_sectors,
+            broken: AtomicBool::new(false),
+        }))
+    }
+
+    /// 把一段扇区按chunk拆分，对每个chunk调用`f(成员盘, 成员盘上的扇区号, 扇区数, 在整段中的扇区偏移)`
+    fn for_each_chunk<F>(&self, sector: BlockId, count: usize, mut f: F) -> Result<(), SystemError>
+    where
+        F: FnMut(&Arc<MdRdev>, BlockId, usize, usize) -> Result<(), SystemError>,
+    {
+        if self.broken.load(Ordering::Relaxed) {
+            return Err(SystemError::EIO);
+        }
+        let nr_disks = self.disks.len();
+        let mut done = 0;
+        while done < count {
+            let cur = sector + done;
+            let chunk = cur / self.chunk_sectors;
+            let offset = cur % self.chunk_sectors;
+            let n = (self.chunk_sectors - offset).min(count - done);
+            let rdev = &self.disks[chunk % nr_disks];
+            let dev_sector = (chunk / nr_disks) * self.chunk_sectors + offset;
+            f(rdev, dev_sector, n, done).inspect_err(|_| {
+                self.error(rdev);
+            })?;
+            done += n;
+        }
+        Ok(())
+    }
+}
+
+impl MdPersonality for Raid0 {
+    fn nr_sectors(&self) -> usize {
+        self.dev_sectors * self.disks.len()
+    }
+
+    fn read(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError> {
+        self.for_each_chunk(sector, buf.len() / LBA_SIZE, |rdev, dev_sector, n, done| {
+            rdev.read(dev_sector, &mut buf[done * LBA_SIZE..(done + n) * LBA_SIZE])
+        })
+    }
+
+    fn write(&self, sector: BlockId, buf: &[u8]) -> Result<(), SystemError> {
+        self.for_each_chunk(sector, buf.len() / LBA_SIZE, |rdev, dev_sector, n, done| {
+            rdev.write(dev_sector, &buf[done * LBA_SIZE..(done + n) * LBA_SIZE])
+        })
+    }
+
+    fn discard(&self, sector: BlockId, count: usize) -> Result<(), SystemError> {
+        if self.broken.load(Ordering::Relaxed) {
+            return Err(SystemError::EIO);
+        }
+        let nr_disks = self.disks.len();
+        let mut done = 0;
+        while done < count {
+            let cur = sector + done;
+            let chunk = cur / self.chunk_sectors;
+            let offset = cur % self.chunk_sectors;
+            let n = (self.chunk_sectors - offset).min(count - done);
+            let dev_sector = (chunk / nr_disks) * self.chunk_sectors + offset;
+            self.disks[chunk % nr_disks].discard(dev_sector, n)?;
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), SystemError> {
+        for rdev in &self.disks {
+            rdev.flush()?;
+        }
+        Ok(())
+    }
+
+    fn error(&self, rdev: &Arc<MdRdev>) -> bool {
+        rdev.set_faulty();
+        if !self.broken.swap(true, Ordering::Relaxed) {
+            log::error!(
+                "md/raid0:{}: Disk failure on {} detected, failing array.",
+                self.name,
+                rdev.name()
+            );
+        }
+        true
+    }
+
+    fn slots(&self) -> Vec<Option<Arc<MdRdev>>> {
+        self.disks.iter().cloned().map(Some).collect()
+    }
+
+    fn status(&self) -> String {
+        format!("{}k chunks", self.chunk_sectors * LBA_SIZE / 1024)
+    }
+}
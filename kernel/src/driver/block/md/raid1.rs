@@ -0,0 +1,424 @@
+//! RAID1：镜像
+//!
+//! 写操作写到所有正常的成员盘上，读操作从一个数据已经同步的成员盘读取，优先选择磁头位置
+//! （上一次I/O结束的扇区）离请求最近的成员盘，出错时换一个成员盘重试。
+//! 成员盘出错后被标记为故障，只要还有一个数据完整的成员盘，阵列就可以继续使用。
+//!
+//! 阵列的数据不一致（创建时没有`--assume-clean`）或者加入了新的成员盘时，内核线程`<阵列名>_resync`
+//! 在后台按窗口把数据从完整的成员盘复制到其余成员盘。复制一个窗口期间写操作会等待，
+//! 避免复制的旧数据覆盖新写入的数据。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/md/raid1.c
+
+use alloc::{
+    boxed::Box,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::block::block_device::{BlockId, LBA_SIZE},
+    libs::{mutex::Mutex, spinlock::SpinLock, wait_queue::WaitQueue},
+    process::kthread::{KernelThreadClosure, KernelThreadMechanism},
+};
+
+use super::{
+    personality::{md_dev_sectors, MdConfig, MdPersonality},
+    rdev::MdRdev,
+};
+
+/// 同步时每次复制的扇区数
+const RESYNC_WINDOW_SECTORS: usize = 128;
+
+#[derive(Debug, Default)]
+struct Mirror {
+    rdev: Option<Arc<MdRdev>>,
+    /// 上一次读操作结束的扇区
+    head_position: usize,
+}
+
+#[derive(Debug, Default)]
+struct ResyncState {
+    running: bool,
+    stop: bool,
+    /// 当前同步到的扇区
+    pos: usize,
+}
+
+#[derive(Debug)]
+pub struct Raid1 {
+    name: String,
+    dev_sectors: usize,
+    mirrors: SpinLock<Vec<Mirror>>,
+    /// 写操作与同步互斥
+    barrier: Mutex<()>,
+    resync: SpinLock<ResyncState>,
+    /// 等待同步线程退出
+    resync_wq: WaitQueue,
+    self_ref: Weak<Self>,
+}
+
+impl Raid1 {
+    pub fn new(
+        name: &str,
+        config: &MdConfig,
+        rdevs: Vec<Option<Arc<MdRdev>>>,
+    ) -> Result<Arc<Self>, SystemError> {
+        let present: Vec<Arc<MdRdev>> = rdevs.iter().flatten().cloned().collect();
+        // 没有数据完整的成员盘时，把第一个成员盘作为同步的来源
+        let source = present
+            .iter()
+            .find(|rdev| rdev.is_in_sync())
+            .or(present.first())
+            .cloned()
+            .ok_or(SystemError::EINVAL)?;
+        let dev_sectors = md_dev_sectors(config, &present, 1)?;
+        for rdev in &present {
+            if Arc::ptr_eq(rdev, &source) {
+                rdev.set_recovery_offset(usize::MAX);
+            } else if !config.clean {
+                rdev.set_recovery_offset(0);
+            }
+        }
+
+        let mirrors = rdevs
+            .into_iter()
+            .map(|rdev| Mirror {
+                rdev,
+                head_position: 0,
+            })
+            .collect();
+        let raid1 = Arc::new_cyclic(|self_ref| Self {
+            name: String::from(name),
+            dev_sectors,
+            mirrors: SpinLock::new(mirrors),
+            barrier: Mutex::new(()),
+            resync: SpinLock::new(ResyncState::default()),
+            resync_wq: WaitQueue::default(),
+            self_ref: self_ref.clone(),
+        });
+        raid1.start_resync();
+        Ok(raid1)
+    }
+
+    /// 正常的成员盘
+    fn working(&self) -> Vec<Arc<MdRdev>> {
+        self.mirrors
+            .lock_irqsave()
+            .iter()
+            .filter_map(|m| m.rdev.clone())
+            .filter(|rdev| !rdev.is_faulty())
+            .collect()
+    }
+
+    /// 数据完整的成员盘数
+    fn nr_in_sync(&self) -> usize {
+        self.working()
+            .iter()
+            .filter(|rdev| rdev.is_in_sync())
+            .count()
+    }
+
+    /// 选择一个读取`[sector, sector + count)`的成员盘，优先选择磁头离`sector`最近的
+    fn read_balance(&self, sector: BlockId, count: usize) -> Option<Arc<MdRdev>> {
+        let end = sector + count;
+        let mut mirrors = self.mirrors.lock_irqsave();
+        let best = mirrors
+            .iter_mut()
+            .filter(|m| {
+                m.rdev
+                    .as_ref()
+                    .is_some_and(|rdev| !rdev.is_faulty() && rdev.is_in_sync_until(end))
+            })
+            .min_by_key(|m| m.head_position.abs_diff(sector))?;
+        best.head_position = end;
+        best.rdev.clone()
+    }
+
+    /// 从数据完整的成员盘读取，出错时换一个成员盘重试
+    fn read_from_mirrors(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError> {
+        loop {
+            let rdev = self
+                .read_balance(sector, buf.len() / LBA_SIZE)
+                .ok_or(SystemError::EIO)?;
+            match rdev.read(sector, buf) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    log::warn!(
+                        "md/raid1:{}: {}: read error at sector {}: {:?}",
+                        self.name,
+                        rdev.name(),
+                        sector,
+                        e
+                    );
+                    if !self.error(&rdev) {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 启动同步线程，已经在运行时新的成员盘会在这一轮同步中处理
+    fn start_resync(&self) {
+        {
+            let mut resync = self.resync.lock_irqsave();
+            if resync.running || resync.stop || self.next_resync().is_none() {
+                return;
+            }
+            resync.running = true;
+            resync.pos = 0;
+        }
+
+        let raid1 = self.self_ref.clone();
+        let name = format!("{}_resync", self.name);
+        let ret = KernelThreadMechanism::create_and_run(
+            KernelThreadClosure::EmptyClosure((
+                Box::new(move || {
+                    if let Some(raid1) = raid1.upgrade() {
+                        raid1.resync_thread();
+                    }
+                    0
+                }),
+                (),
+            )),
+            name,
+        );
+        if ret.is_none() {
+            log::error!("md/raid1:{}: failed to start resync thread", self.name);
+            self.resync.lock_irqsave().running = false;
+            self.resync_wq.wake_all();
+        }
+    }
+
+    /// 下一个需要同步的位置和需要同步的成员盘
+    fn next_resync(&self) -> Option<(usize, Vec<Arc<MdRdev>>)> {
+        let pending: Vec<Arc<MdRdev>> = self
+            .working()
+            .into_iter()
+            .filter(|rdev| !rdev.is_in_sync())
+            .collect();
+        let pos = pending.iter().map(|rdev| rdev.recovery_offset()).min()?;
+        let targets = pending
+            .into_iter()
+            .filter(|rdev| rdev.recovery_offset() == pos)
+            .collect();
+        Some((pos, targets))
+    }
+
+    fn resync_thread(&self) {
+        log::info!("md: resync of RAID array {}", self.name);
+        loop {
+            let next = {
+                let mut resync = self.resync.lock_irqsave();
+                if resync.stop {
+                    resync.running = false;
+                    break;
+                }
+                let next = self.next_resync();
+                match next {
+                    Some((pos, _)) => resync.pos = pos,
+                    None => resync.running = false,
+                }
+                next
+            };
+            let Some((pos, targets)) = next else {
+                log::info!("md: {}: resync done.", self.name);
+                break;
+            };
+            if let Err(e) = self.resync_window(pos, &targets) {
+                log::error!(
+                    "md/raid1:{}: resync interrupted at sector {}: {:?}",
+                    self.name,
+                    pos,
+                    e
+                );
+                self.resync.lock_irqsave().running = false;
+                break;
+            }
+        }
+        self.resync_wq.wake_all();
+    }
+
+    /// 把`[pos, pos + RESYNC_WINDOW_SECTORS)`复制到`targets`上
+    ///
+    /// 没有可以读取的成员盘时返回`EIO`
+    fn resync_window(&self, pos: usize, targets: &[Arc<MdRdev>]) -> Result<(), SystemError> {
+        let n = RESYNC_WINDOW_SECTORS.min(self.dev_sectors - pos);
+        let mut buf = alloc::vec![0u8; n * LBA_SIZE];
+        let _barrier = self.barrier.lock();
+        self.read_from_mirrors(pos, &mut buf)?;
+        let next = if pos + n >= self.dev_sectors {
+            usize::MAX
+        } else {
+            pos + n
+        };
+        for rdev in targets {
+            if rdev.is_faulty() {
+                continue;
+            }
+            match rdev.write(pos, &buf) {
+                Ok(()) => rdev.set_recovery_offset(next),
+                Err(_) => {
+                    self.error(rdev);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MdPersonality for Raid1 {
+    fn nr_sectors(&self) -> usize {
+        self.dev_sectors
+    }
+
+    fn read(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError> {
+        self.read_from_mirrors(sector, buf)
+    }
+
+    fn write(&self, sector: BlockId, buf: &[u8]) -> Result<(), SystemError> {
+        let end = sector + buf.len() / LBA_SIZE;
+        let _barrier = self.barrier.lock();
+        let mut written = false;
+        for rdev in self.working() {
+            // 还没有同步到的区域也要写，同步时会再复制一次
+            let in_sync = rdev.is_in_sync_until(end);
+            match rdev.write(sector, buf) {
+                Ok(()) => written |= in_sync,
+                Err(e) => {
+                    log::warn!(
+                        "md/raid1:{}: {}: write error at sector {}: {:?}",
+                        self.name,
+                        rdev.name(),
+                        sector,
+                        e
+                    );
+                    self.error(&rdev);
+                }
+            }
+        }
+        if !written {
+            return Err(SystemError::EIO);
+        }
+        Ok(())
+    }
+
+    fn discard(&self, sector: BlockId, count: usize) -> Result<(), SystemError> {
+        let _barrier = self.barrier.lock();
+        for rdev in self.working() {
+            rdev.discard(sector, count)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), SystemError> {
+        for rdev in self.working() {
+            rdev.flush()?;
+        }
+        Ok(())
+    }
+
+    fn error(&self, rdev: &Arc<MdRdev>) -> bool {
+        if rdev.is_faulty() {
+            return true;
+        }
+        // 与Linux一致，不把最后一个数据完整的成员盘标记为故障
+        if rdev.is_in_sync() && self.nr_in_sync() == 1 {
+            log::error!(
+                "md/raid1:{}: {}: cannot fail the last working device.",
+                self.name,
+                rdev.name()
+            );
+            return false;
+        }
+        rdev.set_faulty();
+        log::error!(
+            "md/raid1:{}: Disk failure on {}, disabling device.",
+            self.name,
+            rdev.name()
+        );
+        log::error!(
+            "md/raid1:{}: Operation continuing on {} devices.",
+            self.name,
+            self.nr_in_sync()
+        );
+        true
+    }
+
+    fn hot_add(&self, rdev: &Arc<MdRdev>) -> Result<(), SystemError> {
+        if rdev.nr_sectors() < self.dev_sectors {
+            return Err(SystemError::ENOSPC);
+        }
+        {
+            let mut mirrors = self.mirrors.lock_irqsave();
+            let (slot, mirror) = mirrors
+                .iter_mut()
+                .enumerate()
+                .find(|(_, m)| m.rdev.is_none())
+                .ok_or(SystemError::EBUSY)?;
+            rdev.set_raid_disk(slot as i32);
+            rdev.set_recovery_offset(0);
+            mirror.rdev = Some(rdev.clone());
+            mirror.head_position = 0;
+        }
+        log::info!(
+            "md/raid1:{}: {} added, starting recovery",
+            self.name,
+            rdev.name()
+        );
+        self.start_resync();
+        Ok(())
+    }
+
+    fn hot_remove(&self, rdev: &Arc<MdRdev>) -> Result<(), SystemError> {
+        if !rdev.is_faulty() && rdev.is_in_sync() {
+            return Err(SystemError::EBUSY);
+        }
+        // 正在同步的成员盘要等这个窗口复制完
+        let _barrier = self.barrier.lock();
+        let mut mirrors = self.mirrors.lock_irqsave();
+        let mirror = mirrors
+            .iter_mut()
+            .find(|m| m.rdev.as_ref().is_some_and(|r| Arc::ptr_eq(r, rdev)))
+            .ok_or(SystemError::ENXIO)?;
+        mirror.rdev = None;
+        rdev.set_raid_disk(-1);
+        Ok(())
+    }
+
+    fn slots(&self) -> Vec<Option<Arc<MdRdev>>> {
+        self.mirrors
+            .lock_irqsave()
+            .iter()
+            .map(|m| m.rdev.clone())
+            .collect()
+    }
+
+    fn sync_progress(&self) -> Option<(usize, usize)> {
+        let resync = self.resync.lock_irqsave();
+        resync.running.then_some((resync.pos, self.dev_sectors))
+    }
+
+    fn stop(&self) {
+        self.resync.lock_irqsave().stop = true;
+        self.resync_wq
+            .wait_until(|| (!self.resync.lock_irqsave().running).then_some(()));
+    }
+
+    fn status(&self) -> String {
+        let slots = self.slots();
+        let up = |rdev: &Option<Arc<MdRdev>>| {
+            rdev.as_ref()
+                .is_some_and(|r| !r.is_faulty() && r.is_in_sync())
+        };
+        let nr_up = slots.iter().filter(|r| up(r)).count();
+        let map: String = slots
+            .iter()
+            .map(|r| if up(r) { 'U' } else { '_' })
+            .collect();
+        format!("[{}/{}] [{}]", slots.len(), nr_up, map)
+    }
+}
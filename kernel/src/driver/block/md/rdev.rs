@@ -0,0 +1,140 @@
+//! 阵列的成员盘
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::{
+        block::{
+            block_device::BlockId,
+            gendisk::{GenDisk, GenDiskClaim},
+        },
+        device::device_number::DeviceNumber,
+    },
+    filesystem::vfs::IndexNode,
+};
+
+use super::ioctl::{MduDiskInfo, MD_DISK_ACTIVE, MD_DISK_FAULTY, MD_DISK_SYNC};
+
+/// 一个成员盘，存在期间下层设备处于打开状态
+#[derive(Debug)]
+pub struct MdRdev {
+    claim: GenDiskClaim,
+    /// mdadm使用的编号（`mdu_disk_info_t.number`）
+    number: i32,
+    /// 在阵列中的位置，-1表示备用盘
+    raid_disk: AtomicI32,
+    faulty: AtomicBool,
+    /// 这个扇区之前的数据已经与阵列一致，`usize::MAX`表示全部一致
+    recovery_offset: AtomicUsize,
+}
+
+impl MdRdev {
+    pub fn new(disk: &Arc<GenDisk>, number: i32, raid_disk: i32, in_sync: bool) -> Arc<Self> {
+        Arc::new(Self {
+            claim: disk.claim(),
+            number,
+            raid_disk: AtomicI32::new(raid_disk),
+            faulty: AtomicBool::new(false),
+            recovery_offset: AtomicUsize::new(if in_sync { usize::MAX } else { 0 }),
+        })
+    }
+
+    pub fn disk(&self) -> &Arc<GenDisk> {
+        self.claim.disk()
+    }
+
+    /// 设备名，比如`sda1`
+    pub fn name(&self) -> String {
+        self.disk()
+            .dname()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|_| self.disk().symlink_name())
+    }
+
+    pub fn device_num(&self) -> DeviceNumber {
+        self.disk().device_num()
+    }
+
+    pub fn nr_sectors(&self) -> usize {
+        self.disk().nr_sectors()
+    }
+
+    pub fn number(&self) -> i32 {
+        self.number
+    }
+
+    pub fn raid_disk(&self) -> i32 {
+        self.raid_disk.load(Ordering::Relaxed)
+    }
+
+    pub fn set_raid_disk(&self, raid_disk: i32) {
+        self.raid_disk.store(raid_disk, Ordering::Relaxed);
+    }
+
+    pub fn is_faulty(&self) -> bool {
+        self.faulty.load(Ordering::Relaxed)
+    }
+
+    pub fn set_faulty(&self) {
+        self.faulty.store(true, Ordering::Relaxed);
+    }
+
+    pub fn recovery_offset(&self) -> usize {
+        self.recovery_offset.load(Ordering::Acquire)
+    }
+
+    pub fn set_recovery_offset(&self, offset: usize) {
+        self.recovery_offset.store(offset, Ordering::Release);
+    }
+
+    pub fn is_in_sync(&self) -> bool {
+        self.recovery_offset() == usize::MAX
+    }
+
+    /// `[0, end)`范围内的数据是否已经与阵列一致
+    pub fn is_in_sync_until(&self, end: usize) -> bool {
+        self.recovery_offset() >= end
+    }
+
+    /// GET_DISK_INFO返回的信息
+    pub fn disk_info(&self) -> MduDiskInfo {
+        let mut state = 0;
+        if self.is_faulty() {
+            state |= 1 << MD_DISK_FAULTY;
+        } else if self.raid_disk() >= 0 {
+            state |= 1 << MD_DISK_ACTIVE;
+            if self.is_in_sync() {
+                state |= 1 << MD_DISK_SYNC;
+            }
+        }
+        let devnum = self.device_num();
+        MduDiskInfo {
+            number: self.number,
+            major: devnum.major().data() as i32,
+            minor: devnum.minor() as i32,
+            raid_disk: self.raid_disk(),
+            state,
+        }
+    }
+
+    pub fn read(&self, sector: BlockId, buf: &mut [u8]) -> Result<(), SystemError> {
+        self.disk().read_at(buf, sector).map(|_| ())
+    }
+
+    pub fn write(&self, sector: BlockId, buf: &[u8]) -> Result<(), SystemError> {
+        self.disk().write_at(buf, sector).map(|_| ())
+    }
+
+    pub fn discard(&self, sector: BlockId, count: usize) -> Result<(), SystemError> {
+        self.disk().discard(sector, count)
+    }
+
+    pub fn flush(&self) -> Result<(), SystemError> {
+        self.disk().flush()
+    }
+}
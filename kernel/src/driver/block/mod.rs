@@ -1,3 +1,8 @@
+pub mod brd;
+pub mod dm;
 pub mod loop_device;
+pub mod md;
+pub mod nbd;
 pub mod pmem;
 pub mod virtio_blk;
+pub mod zram;
@@ -0,0 +1,813 @@
+//! NBD块设备（/dev/nbdN）
+
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    fmt::Debug,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    driver::base::{
+        block::{
+            block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE},
+            cache::block_cache,
+            disk_info::Partition,
+            manager::{block_dev_manager, BlockDevMeta},
+        },
+        class::Class,
+        device::{
+            bus::Bus,
+            device_number::{DeviceNumber, Major},
+            driver::Driver,
+            DevName, Device, DeviceCommonData, DeviceType, IdTable,
+        },
+        kobject::{KObjType, KObject, KObjectCommonData, KObjectState, LockedKObjectState},
+        kset::KSet,
+    },
+    filesystem::{
+        devfs::{DevFS, DeviceINode, LockedDevFSInode},
+        kernfs::KernFSInode,
+        vfs::{
+            file::File, utils::DName, FilePrivateData, IndexNode, InodeFlags, InodeId, InodeMode,
+            Metadata,
+        },
+    },
+    libs::{
+        mutex::{Mutex, MutexGuard},
+        rwlock::RwLock,
+        rwsem::{RwSemReadGuard, RwSemWriteGuard},
+        spinlock::{SpinLock, SpinLockGuard},
+        wait_queue::WaitQueue,
+    },
+    mm::MemoryManagementArch,
+    net::socket::common::ShutdownBit,
+    process::{cred::CAPFlags, ProcessManager},
+    syscall::user_access::check_and_clone_cstr,
+};
+
+use super::{
+    ioctl::{
+        NBD_CLEAR_QUE, NBD_CLEAR_SOCK, NBD_DISCONNECT, NBD_DO_IT, NBD_NEGOTIATE, NBD_SET_BLKSIZE,
+        NBD_SET_FLAGS, NBD_SET_SIZE, NBD_SET_SIZE_BLOCKS, NBD_SET_SOCK, NBD_SET_TIMEOUT,
+    },
+    protocol::{
+        NbdCmd, NbdStream, NBD_FLAG_READ_ONLY, NBD_FLAG_SEND_FLUSH, NBD_FLAG_SEND_TRIM,
+        NBD_MAX_STRING,
+    },
+};
+
+pub(super) const NBD_BASENAME: &str = "nbd";
+
+/// 默认的逻辑块大小，与Linux相同
+const NBD_DEFAULT_BLKSIZE: usize = 1024;
+/// 一个读写请求的最大长度，更大的IO拆分为多个请求
+const NBD_MAX_REQUEST_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug)]
+struct InnerNbdDevice {
+    device_common: DeviceCommonData,
+    kobject_common: KObjectCommonData,
+}
+
+/// 连接的配置
+struct NbdConn {
+    /// 逻辑块大小，只用于NBD_SET_SIZE_BLOCKS的换算
+    blksize: usize,
+    /// 传输标志
+    flags: u16,
+    /// 上一个请求的标识
+    handle: u64,
+}
+
+/// 一个NBD设备
+///
+/// 与Linux的nbd一样，由用户态（例如nbd-client）建立TCP连接并通过NBD_SET_SOCK交给内核，
+/// 设置大小和传输标志之后调用NBD_DO_IT。NBD_DO_IT会一直阻塞到连接断开，在此期间设备可以读写。
+/// 也可以用NBD_NEGOTIATE让内核在socket上完成握手，得到导出的大小和传输标志。
+///
+/// 对服务器的请求是同步的，同一时刻只有一个请求在途。
+#[cast_to([sync] Device)]
+pub struct NbdDevice {
+    blkdev_meta: BlockDevMeta,
+    inner: SpinLock<InnerNbdDevice>,
+    locked_kobj_state: LockedKObjectState,
+    self_ref: Weak<Self>,
+    parent: RwLock<Weak<LockedDevFSInode>>,
+    fs: RwLock<Weak<DevFS>>,
+    /// NBD_SET_SOCK设置的已连接的TCP socket，持有文件引用，用户态关闭fd之后仍然可以使用
+    sock: RwLock<Option<Arc<File>>>,
+    /// 串行化配置操作和对服务器的请求
+    conn: Mutex<NbdConn>,
+    /// 设备容量（字节）
+    size: AtomicUsize,
+    /// NBD_DO_IT正在运行，设备可以读写
+    running: AtomicBool,
+    /// NBD_DO_IT在这里等待连接断开
+    wait_queue: WaitQueue,
+}
+
+impl Debug for NbdDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("NbdDevice")
+            .field("devname", &self.blkdev_meta.devname)
+            .field("size", &self.size())
+            .field("running", &self.is_running())
+            .finish()
+    }
+}
+
+impl NbdDevice {
+    /// # 创建设备
+    ///
+    /// ## 参数
+    /// - `id`: 设备编号，设备节点为`/dev/nbd{id}`
+    pub fn new(id: usize) -> Arc<Self> {
+        let devname = DevName::new(format!("{NBD_BASENAME}{id}"), id);
+        Arc::new_cyclic(|self_ref| Self {
+            blkdev_meta: BlockDevMeta::new(devname, Major::NBD_MAJOR),
+            inner: SpinLock::new(InnerNbdDevice {
+                device_common: DeviceCommonData::default(),
+                kobject_common: KObjectCommonData::default(),
+            }),
+            locked_kobj_state: LockedKObjectState::default(),
+            self_ref: self_ref.clone(),
+            parent: RwLock::new(Weak::default()),
+            fs: RwLock::new(Weak::default()),
+            sock: RwLock::new(None),
+            conn: Mutex::new(NbdConn {
+                blksize: NBD_DEFAULT_BLKSIZE,
+                flags: 0,
+                handle: 0,
+            }),
+            size: AtomicUsize::new(0),
+            running: AtomicBool::new(false),
+            wait_queue: WaitQueue::default(),
+        })
+    }
+
+    fn inner(&self) -> SpinLockGuard<'_, InnerNbdDevice> {
+        self.inner.lock_irqsave()
+    }
+
+    fn bdev(&self) -> Arc<dyn BlockDevice> {
+        self.self_ref.upgrade().unwrap() as Arc<dyn BlockDevice>
+    }
+
+    /// 设备容量（字节）
+    pub fn size(&self) -> usize {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    /// 设备是否已经连接到服务器
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// # 处理nbd的ioctl
+    ///
+    /// 所有命令都需要CAP_SYS_ADMIN
+    pub fn ioctl(&self, cmd: u32, data: usize) -> Result<usize, SystemError> {
+        if !ProcessManager::current_pcb()
+            .cred()
+            .has_capability(CAPFlags::CAP_SYS_ADMIN)
+        {
+            return Err(SystemError::EPERM);
+        }
+        match cmd {
+            NBD_SET_SOCK => self.set_sock(data as i32)?,
+            NBD_SET_BLKSIZE => {
+                let size = self.size();
+                self.set_size(size, data)?;
+            }
+            NBD_SET_SIZE => {
+                let blksize = self.conn.lock().blksize;
+                self.set_size(data, blksize)?;
+            }
+            NBD_SET_SIZE_BLOCKS => {
+                let blksize = self.conn.lock().blksize;
+                let size = data.checked_mul(blksize).ok_or(SystemError::EINVAL)?;
+                self.set_size(size, blksize)?;
+            }
+            NBD_SET_FLAGS => self.conn.lock().flags = data as u16,
+            NBD_SET_TIMEOUT => {
+                if data != 0 {
+                    log::warn!(
+                        "nbd: {}: request timeout is not supported, ignored",
+                        self.dev_name()
+                    );
+                }
+            }
+            NBD_NEGOTIATE => self.negotiate(data)?,
+            NBD_DO_IT => self.do_it()?,
+            NBD_DISCONNECT => self.disconnect()?,
+            NBD_CLEAR_SOCK => self.clear_sock(),
+            // 请求是同步的，没有排队的请求
+            NBD_CLEAR_QUE => {}
+            _ => return Err(SystemError::ENOIOCTLCMD),
+        }
+        Ok(0)
+    }
+
+    /// # 设置与服务器通信的socket
+    ///
+    /// ## 参数
+    /// - `fd`: 已经连接到服务器的TCP socket
+    fn set_sock(&self, fd: i32) -> Result<(), SystemError> {
+        let file = ProcessManager::current_pcb()
+            .fd_table()
+            .read()
+            .get_file_by_fd(fd)
+            .ok_or(SystemError::EBADF)?;
+        if file.inode().as_socket().is_none() {
+            return Err(SystemError::ENOTSOCK);
+        }
+        let _conn = self.conn.lock();
+        let mut sock = self.sock.write();
+        if sock.is_some() {
+            return Err(SystemError::EBUSY);
+        }
+        *sock = Some(file);
+        Ok(())
+    }
+
+    /// # 设置设备容量和逻辑块大小
+    ///
+    /// ## 参数
+    /// - `size`: 容量（字节），向下对齐到扇区大小
+    /// - `blksize`: 逻辑块大小，必须是512到页大小之间的2的幂
+    fn set_size(&self, size: usize, blksize: usize) -> Result<(), SystemError> {
+        if !blksize.is_power_of_two() || !(LBA_SIZE..=MMArch::PAGE_SIZE).contains(&blksize) {
+            return Err(SystemError::EINVAL);
+        }
+        let mut conn = self.conn.lock();
+        if self.is_running() {
+            return Err(SystemError::EBUSY);
+        }
+        conn.blksize = blksize;
+        self.size
+            .store(size / LBA_SIZE * LBA_SIZE, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// # 在socket上完成fixed newstyle握手
+    ///
+    /// 握手成功后按照导出的信息设置设备容量和传输标志
+    ///
+    /// ## 参数
+    /// - `name_ptr`: 用户态的导出名字符串，为NULL时使用服务器的默认导出
+    fn negotiate(&self, name_ptr: usize) -> Result<(), SystemError> {
+        let name = if name_ptr == 0 {
+            Vec::new()
+        } else {
+            check_and_clone_cstr(name_ptr as *const u8, Some(NBD_MAX_STRING))?.into_bytes()
+        };
+
+        let mut conn = self.conn.lock();
+        if self.is_running() {
+            return Err(SystemError::EBUSY);
+        }
+        let sock = self.sock.read().clone().ok_or(SystemError::EINVAL)?.inode();
+        let export = NbdStream::new(sock.as_socket().unwrap()).negotiate(&name)?;
+        let size = usize::try_from(export.size).map_err(|_| SystemError::EFBIG)?;
+        conn.flags = export.flags;
+        self.size
+            .store(size / LBA_SIZE * LBA_SIZE, Ordering::SeqCst);
+        log::info!(
+            "nbd: {}: negotiated export '{}', {} bytes, flags {:#x}",
+            self.dev_name(),
+            core::str::from_utf8(&name).unwrap_or("?"),
+            size,
+            export.flags
+        );
+        Ok(())
+    }
+
+    /// # 启动设备，阻塞到连接断开
+    ///
+    /// 返回之前清除socket和设备容量，用户态需要重新配置之后才能再次启动
+    fn do_it(&self) -> Result<(), SystemError> {
+        {
+            let conn = self.conn.lock();
+            if self.sock.read().is_none() || self.size() == 0 {
+                return Err(SystemError::EINVAL);
+            }
+            if self.is_running() {
+                return Err(SystemError::EBUSY);
+            }
+            self.blkdev_meta
+                .set_read_only(conn.flags & NBD_FLAG_READ_ONLY != 0);
+            self.running.store(true, Ordering::SeqCst);
+        }
+
+        let bdev = self.bdev();
+        block_dev_manager().notify_media_change(&bdev);
+        if let Err(e) = block_dev_manager().rescan_partitions(&bdev) {
+            log::warn!(
+                "nbd: {}: failed to scan partitions: {:?}",
+                self.dev_name(),
+                e
+            );
+        }
+        log::info!("nbd: {}: connected, {} bytes", self.dev_name(), self.size());
+
+        let r = self
+            .wait_queue
+            .wait_until_interruptible(|| (!self.is_running()).then_some(()));
+
+        // 被信号打断（例如nbd-client被杀死）时同样断开连接
+        self.abort();
+        {
+            let mut conn = self.conn.lock();
+            *self.sock.write() = None;
+            conn.flags = 0;
+            self.size.store(0, Ordering::SeqCst);
+        }
+        self.blkdev_meta.set_read_only(false);
+        if let Err(e) = block_dev_manager().drop_partitions(&bdev) {
+            log::warn!(
+                "nbd: {}: failed to drop partitions: {:?}",
+                self.dev_name(),
+                e
+            );
+        }
+        block_dev_manager().notify_media_change(&bdev);
+        log::info!("nbd: {}: disconnected", self.dev_name());
+        r
+    }
+
+    /// # 正常断开连接
+    ///
+    /// 先把块缓存中的脏数据写回并发送NBD_CMD_FLUSH，再通知服务器断开
+    fn disconnect(&self) -> Result<(), SystemError> {
+        if !self.is_running() {
+            let _conn = self.conn.lock();
+            *self.sock.write() = None;
+            return Ok(());
+        }
+        if let Err(e) = block_cache()
+            .sync_disk(&self.bdev())
+            .and_then(|_| self.sync())
+        {
+            log::warn!("nbd: {}: failed to flush: {:?}", self.dev_name(), e);
+        }
+
+        let mut conn = self.conn.lock();
+        let sock = self.sock.read().as_ref().map(|file| file.inode());
+        if let Some(sock) = sock {
+            conn.handle += 1;
+            let stream = NbdStream::new(sock.as_socket().unwrap());
+            // 服务器不回复NBD_CMD_DISC
+            if let Err(e) = stream.send_request(NbdCmd::Disc, conn.handle, 0, 0, None) {
+                log::warn!(
+                    "nbd: {}: failed to send disconnect: {:?}",
+                    self.dev_name(),
+                    e
+                );
+            }
+        }
+        drop(conn);
+        self.abort();
+        Ok(())
+    }
+
+    /// 没有运行时清除socket，运行时强制断开连接
+    fn clear_sock(&self) {
+        if self.is_running() {
+            self.abort();
+        } else {
+            let _conn = self.conn.lock();
+            *self.sock.write() = None;
+        }
+    }
+
+    /// 停止设备并关闭socket，唤醒NBD_DO_IT
+    fn abort(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        // 不获取conn锁，正在等待回复的请求会因为socket关闭而出错返回
+        let sock = self.sock.read().clone();
+        if let Some(sock) = sock {
+            let _ = sock
+                .inode()
+                .as_socket()
+                .unwrap()
+                .shutdown(ShutdownBit::SHUT_RDWR);
+        }
+        self.wait_queue.wakeup_all(None);
+    }
+
+    /// # 向服务器发送一个请求并等待回复
+    ///
+    /// 连接出错时设备停止，之后的请求都返回EIO
+    ///
+    /// ## 参数
+    /// - `cmd`: 请求类型
+    /// - `offset`, `len`: 请求的范围（字节）
+    /// - `wbuf`: 写请求的数据
+    /// - `rbuf`: 读请求的缓冲区
+    fn request(
+        &self,
+        cmd: NbdCmd,
+        offset: usize,
+        len: usize,
+        wbuf: Option<&[u8]>,
+        rbuf: Option<&mut [u8]>,
+    ) -> Result<(), SystemError> {
+        let mut conn = self.conn.lock();
+        if !self.is_running() {
+            return Err(SystemError::EIO);
+        }
+        let sock = self.sock.read().as_ref().ok_or(SystemError::EIO)?.inode();
+        conn.handle += 1;
+        let handle = conn.handle;
+
+        let stream = NbdStream::new(sock.as_socket().unwrap());
+        let result = stream
+            .send_request(cmd, handle, offset as u64, len as u32, wbuf)
+            .and_then(|_| stream.recv_reply(handle, rbuf));
+        drop(conn);
+
+        match result {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(error)) => {
+                log::warn!(
+                    "nbd: {}: {:?} of {} bytes at {:#x} failed with error {}",
+                    self.dev_name(),
+                    cmd,
+                    len,
+                    offset,
+                    error
+                );
+                Err(SystemError::EIO)
+            }
+            Err(e) => {
+                if self.is_running() {
+                    log::error!(
+                        "nbd: {}: connection to server lost: {:?}",
+                        self.dev_name(),
+                        e
+                    );
+                    self.abort();
+                }
+                Err(SystemError::EIO)
+            }
+        }
+    }
+
+    /// 检查IO范围，返回(字节偏移, 字节长度)
+    fn check_range(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf_len: usize,
+    ) -> Result<(usize, usize), SystemError> {
+        let offset = lba_id_start
+            .checked_mul(LBA_SIZE)
+            .ok_or(SystemError::EOVERFLOW)?;
+        let len = count.checked_mul(LBA_SIZE).ok_or(SystemError::EOVERFLOW)?;
+        if len > buf_len {
+            return Err(SystemError::EINVAL);
+        }
+        let end = offset.checked_add(len).ok_or(SystemError::EOVERFLOW)?;
+        if end > self.size() {
+            return Err(SystemError::ENOSPC);
+        }
+        Ok((offset, len))
+    }
+}
+
+impl IndexNode for NbdDevice {
+    fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
+        self.fs.read().upgrade().expect("NbdDevice fs is not set")
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        let blocks = self.disk_range().len();
+        Ok(Metadata {
+            dev_id: 0,
+            inode_id: InodeId::new(0),
+            size: (blocks * LBA_SIZE) as i64,
+            blk_size: LBA_SIZE,
+            blocks,
+            atime: Default::default(),
+            mtime: Default::default(),
+            ctime: Default::default(),
+            btime: Default::default(),
+            file_type: crate::filesystem::vfs::FileType::BlockDevice,
+            mode: InodeMode::from_bits_truncate(0o660),
+            flags: InodeFlags::empty(),
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            raw_dev: DeviceNumber::new(self.blkdev_meta.major, self.blkdev_meta.base_minor),
+        })
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let parent = self.parent.read();
+        if let Some(parent) = parent.upgrade() {
+            return Ok(parent as Arc<dyn IndexNode>);
+        }
+        Err(SystemError::ENOENT)
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn dname(&self) -> Result<DName, SystemError> {
+        Ok(DName::from(self.blkdev_meta.devname.clone().as_ref()))
+    }
+
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _mode: &crate::filesystem::vfs::file::FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+}
+
+impl DeviceINode for NbdDevice {
+    fn set_fs(&self, fs: Weak<DevFS>) {
+        *self.fs.write() = fs;
+    }
+
+    fn set_parent(&self, parent: Weak<LockedDevFSInode>) {
+        *self.parent.write() = parent;
+    }
+}
+
+impl BlockDevice for NbdDevice {
+    fn dev_name(&self) -> &DevName {
+        &self.blkdev_meta.devname
+    }
+
+    fn blkdev_meta(&self) -> &BlockDevMeta {
+        &self.blkdev_meta
+    }
+
+    fn disk_range(&self) -> GeneralBlockRange {
+        let blocks = self.size() / LBA_SIZE;
+        GeneralBlockRange::new(0, blocks).unwrap_or(GeneralBlockRange {
+            lba_start: 0,
+            lba_end: 0,
+        })
+    }
+
+    fn read_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let (offset, len) = self.check_range(lba_id_start, count, buf.len())?;
+        let mut done = 0;
+        while done < len {
+            let chunk = (len - done).min(NBD_MAX_REQUEST_BYTES);
+            self.request(
+                NbdCmd::Read,
+                offset + done,
+                chunk,
+                None,
+                Some(&mut buf[done..done + chunk]),
+            )?;
+            done += chunk;
+        }
+        Ok(len)
+    }
+
+    fn write_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let (offset, len) = self.check_range(lba_id_start, count, buf.len())?;
+        let mut done = 0;
+        while done < len {
+            let chunk = (len - done).min(NBD_MAX_REQUEST_BYTES);
+            self.request(
+                NbdCmd::Write,
+                offset + done,
+                chunk,
+                Some(&buf[done..done + chunk]),
+                None,
+            )?;
+            done += chunk;
+        }
+        Ok(len)
+    }
+
+    /// 服务器支持时发送NBD_CMD_FLUSH
+    fn sync(&self) -> Result<(), SystemError> {
+        if !self.is_running() || self.conn.lock().flags & NBD_FLAG_SEND_FLUSH == 0 {
+            return Ok(());
+        }
+        self.request(NbdCmd::Flush, 0, 0, None, None)
+    }
+
+    /// 服务器支持时发送NBD_CMD_TRIM
+    fn discard(&self, range: GeneralBlockRange) -> Result<(), SystemError> {
+        if self.conn.lock().flags & NBD_FLAG_SEND_TRIM == 0 {
+            return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+        }
+        let count = range.lba_end - range.lba_start;
+        if count == 0 {
+            return Ok(());
+        }
+        let (offset, len) = self.check_range(range.lba_start, count, usize::MAX)?;
+        let mut done = 0;
+        while done < len {
+            // 请求的长度字段只有32位
+            let chunk = (len - done).min(u32::MAX as usize / LBA_SIZE * LBA_SIZE);
+            self.request(NbdCmd::Trim, offset + done, chunk, None, None)?;
+            done += chunk;
+        }
+        Ok(())
+    }
+
+    fn blk_size_log2(&self) -> u8 {
+        9
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn device(&self) -> Arc<dyn Device> {
+        self.self_ref.upgrade().unwrap()
+    }
+
+    fn block_size(&self) -> usize {
+        LBA_SIZE
+    }
+
+    fn partitions(&self) -> Vec<Arc<Partition>> {
+        self.blkdev_meta().partitions()
+    }
+}
+
+impl Device for NbdDevice {
+    fn dev_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id_table(&self) -> IdTable {
+        IdTable::new(NBD_BASENAME.to_string(), None)
+    }
+
+    fn bus(&self) -> Option<Weak<dyn Bus>> {
+        self.inner().device_common.bus.clone()
+    }
+
+    fn set_bus(&self, bus: Option<Weak<dyn Bus>>) {
+        self.inner().device_common.bus = bus;
+    }
+
+    fn class(&self) -> Option<Arc<dyn Class>> {
+        let mut guard = self.inner();
+        let class = guard.device_common.class.clone()?.upgrade();
+        if class.is_none() {
+            guard.device_common.class = None;
+        }
+        class
+    }
+
+    fn set_class(&self, class: Option<Weak<dyn Class>>) {
+        self.inner().device_common.class = class;
+    }
+
+    fn driver(&self) -> Option<Arc<dyn Driver>> {
+        let mut guard = self.inner();
+        let driver = guard.device_common.driver.clone()?.upgrade();
+        if driver.is_none() {
+            guard.device_common.driver = None;
+        }
+        driver
+    }
+
+    fn set_driver(&self, driver: Option<Weak<dyn Driver>>) {
+        self.inner().device_common.driver = driver;
+    }
+
+    fn is_dead(&self) -> bool {
+        false
+    }
+
+    fn can_match(&self) -> bool {
+        self.inner().device_common.can_match
+    }
+
+    fn set_can_match(&self, can_match: bool) {
+        self.inner().device_common.can_match = can_match;
+    }
+
+    fn state_synced(&self) -> bool {
+        true
+    }
+
+    fn dev_parent(&self) -> Option<Weak<dyn Device>> {
+        self.inner().device_common.get_parent_weak_or_clear()
+    }
+
+    fn set_dev_parent(&self, parent: Option<Weak<dyn Device>>) {
+        self.inner().device_common.parent = parent;
+    }
+}
+
+impl KObject for NbdDevice {
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn set_inode(&self, inode: Option<Arc<KernFSInode>>) {
+        self.inner().kobject_common.kern_inode = inode;
+    }
+
+    fn inode(&self) -> Option<Arc<KernFSInode>> {
+        self.inner().kobject_common.kern_inode.clone()
+    }
+
+    fn parent(&self) -> Option<Weak<dyn KObject>> {
+        self.inner().kobject_common.parent.clone()
+    }
+
+    fn set_parent(&self, parent: Option<Weak<dyn KObject>>) {
+        self.inner().kobject_common.parent = parent;
+    }
+
+    fn kset(&self) -> Option<Arc<KSet>> {
+        self.inner().kobject_common.kset.clone()
+    }
+
+    fn set_kset(&self, kset: Option<Arc<KSet>>) {
+        self.inner().kobject_common.kset = kset;
+    }
+
+    fn kobj_type(&self) -> Option<&'static dyn KObjType> {
+        self.inner().kobject_common.kobj_type
+    }
+
+    fn name(&self) -> String {
+        self.dev_name().to_string()
+    }
+
+    fn set_name(&self, _name: String) {}
+
+    fn kobj_state(&self) -> RwSemReadGuard<'_, KObjectState> {
+        self.locked_kobj_state.read()
+    }
+
+    fn kobj_state_mut(&self) -> RwSemWriteGuard<'_, KObjectState> {
+        self.locked_kobj_state.write()
+    }
+
+    fn set_kobj_state(&self, state: KObjectState) {
+        *self.locked_kobj_state.write() = state;
+    }
+
+    fn set_kobj_type(&self, ktype: Option<&'static dyn KObjType>) {
+        self.inner().kobject_common.kobj_type = ktype;
+    }
+}
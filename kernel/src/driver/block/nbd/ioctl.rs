@@ -0,0 +1,21 @@
+//! nbd的ioctl接口，与Linux的include/uapi/linux/nbd.h一致
+
+use crate::filesystem::vfs::ioctl::io;
+
+/// ioctl命令号的类型字段
+const NBD_IOCTL_TYPE: u8 = 0xab;
+
+pub const NBD_SET_SOCK: u32 = io(NBD_IOCTL_TYPE, 0);
+pub const NBD_SET_BLKSIZE: u32 = io(NBD_IOCTL_TYPE, 1);
+pub const NBD_SET_SIZE: u32 = io(NBD_IOCTL_TYPE, 2);
+pub const NBD_DO_IT: u32 = io(NBD_IOCTL_TYPE, 3);
+pub const NBD_CLEAR_SOCK: u32 = io(NBD_IOCTL_TYPE, 4);
+pub const NBD_CLEAR_QUE: u32 = io(NBD_IOCTL_TYPE, 5);
+pub const NBD_SET_SIZE_BLOCKS: u32 = io(NBD_IOCTL_TYPE, 7);
+pub const NBD_DISCONNECT: u32 = io(NBD_IOCTL_TYPE, 8);
+pub const NBD_SET_TIMEOUT: u32 = io(NBD_IOCTL_TYPE, 9);
+pub const NBD_SET_FLAGS: u32 = io(NBD_IOCTL_TYPE, 10);
+
+/// DragonOS扩展：由内核在NBD_SET_SOCK设置的socket上完成fixed newstyle握手。
+/// 参数为导出名字符串的地址，为NULL时使用服务器的默认导出
+pub const NBD_NEGOTIATE: u32 = io(NBD_IOCTL_TYPE, 0x40);
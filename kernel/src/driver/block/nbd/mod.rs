@@ -0,0 +1,60 @@
+//! 网络块设备（NBD）客户端
+//!
+//! 启动时按照内核命令行参数`nbds_max`创建`/dev/nbd0`~`/dev/nbd{nbds_max-1}`，
+//! 通过已有的TCP协议栈把远端NBD服务器的导出作为本地块设备使用。
+//!
+//! 用户态的接口与Linux的ioctl接口相同，可以直接使用nbd-client：由用户态连接服务器并完成握手，
+//! 再把socket交给内核。也可以只建立TCP连接，用DragonOS扩展的NBD_NEGOTIATE让内核完成
+//! fixed newstyle握手（参见[`protocol`]）。
+//!
+//! 目前不支持请求超时、多连接和netlink接口，服务器无响应时IO会一直阻塞，
+//! 直到NBD_DISCONNECT、NBD_CLEAR_SOCK或者NBD_DO_IT被信号打断。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/block/nbd.c
+
+mod device;
+mod ioctl;
+mod protocol;
+
+use alloc::{string::ToString, sync::Arc};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    driver::base::{
+        block::{block_device::BlockDevice, manager::block_dev_manager},
+        device::device_number::Major,
+    },
+    init::initcall::INITCALL_DEVICE,
+};
+
+pub use device::NbdDevice;
+
+kernel_cmdline_param_kv!(NBDS_MAX_PARAM, nbds_max, "");
+
+/// 默认创建的设备数量，与Linux相同
+const DEFAULT_NBDS_MAX: usize = 16;
+/// 最多创建的设备数量
+const MAX_NBDS_MAX: usize = 256;
+
+#[unified_init(INITCALL_DEVICE)]
+fn nbd_init() -> Result<(), SystemError> {
+    let nr = NBDS_MAX_PARAM
+        .value_str()
+        .and_then(|s| s.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_NBDS_MAX)
+        .min(MAX_NBDS_MAX);
+
+    for id in 0..nr {
+        let dev = NbdDevice::new(id);
+        let dev_name = dev.dev_name().to_string();
+        block_dev_manager().register(dev as Arc<dyn BlockDevice>)?;
+        log::debug!("nbd: registered /dev/{}", dev_name);
+    }
+    log::info!(
+        "nbd: registered device at major {}",
+        Major::NBD_MAJOR.data()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,288 @@
+//! NBD协议：fixed newstyle握手和传输阶段的消息
+//!
+//! 所有整数都是大端序。传输阶段只使用simple reply，不协商structured reply。
+//!
+//! 参考: https://github.com/NetworkBlockDevice/nbd/blob/master/doc/proto.md
+
+use alloc::{vec, vec::Vec};
+use system_error::SystemError;
+
+use crate::net::socket::{Socket, PMSG};
+
+/// 握手开始时服务器发送的魔数
+const NBDMAGIC: u64 = 0x4e42_444d_4147_4943;
+/// newstyle握手的魔数，也是客户端发送选项时的魔数
+const IHAVEOPT: u64 = 0x4948_4156_454f_5054;
+/// 服务器回复选项时的魔数
+const OPT_REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+const NBD_REQUEST_MAGIC: u32 = 0x2560_9513;
+const NBD_SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+/// 服务器的握手标志
+const NBD_FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const NBD_FLAG_NO_ZEROES: u16 = 1 << 1;
+/// 客户端的握手标志
+const NBD_FLAG_C_FIXED_NEWSTYLE: u32 = 1 << 0;
+const NBD_FLAG_C_NO_ZEROES: u32 = 1 << 1;
+
+const NBD_OPT_EXPORT_NAME: u32 = 1;
+const NBD_OPT_GO: u32 = 7;
+
+const NBD_REP_ACK: u32 = 1;
+const NBD_REP_INFO: u32 = 3;
+const NBD_REP_FLAG_ERROR: u32 = 1 << 31;
+const NBD_REP_ERR_UNSUP: u32 = NBD_REP_FLAG_ERROR | 1;
+const NBD_REP_ERR_POLICY: u32 = NBD_REP_FLAG_ERROR | 2;
+const NBD_REP_ERR_UNKNOWN: u32 = NBD_REP_FLAG_ERROR | 6;
+
+const NBD_INFO_EXPORT: u16 = 0;
+
+/// 导出名的最大长度
+pub const NBD_MAX_STRING: usize = 4096;
+/// 选项回复的最大长度，超过时认为服务器有问题
+const NBD_MAX_OPT_REPLY: usize = 64 * 1024;
+/// NBD_OPT_EXPORT_NAME的回复末尾的填充字节数
+const NBD_EXPORT_NAME_PAD: usize = 124;
+
+/// 传输标志：服务器设置了标志
+pub const NBD_FLAG_HAS_FLAGS: u16 = 1 << 0;
+/// 传输标志：导出是只读的
+pub const NBD_FLAG_READ_ONLY: u16 = 1 << 1;
+/// 传输标志：服务器支持NBD_CMD_FLUSH
+pub const NBD_FLAG_SEND_FLUSH: u16 = 1 << 2;
+/// 传输标志：服务器支持NBD_CMD_TRIM
+pub const NBD_FLAG_SEND_TRIM: u16 = 1 << 5;
+
+/// 传输阶段的请求类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NbdCmd {
+    Read = 0,
+    Write = 1,
+    Disc = 2,
+    Flush = 3,
+    Trim = 4,
+}
+
+/// 握手得到的导出信息
+#[derive(Debug, Clone, Copy)]
+pub struct NbdExport {
+    /// 导出的大小（字节）
+    pub size: u64,
+    /// 传输标志
+    pub flags: u16,
+}
+
+/// 在已经连接的TCP socket上收发完整的消息
+pub struct NbdStream<'a> {
+    sock: &'a dyn Socket,
+}
+
+impl<'a> NbdStream<'a> {
+    pub fn new(sock: &'a dyn Socket) -> Self {
+        Self { sock }
+    }
+
+    fn send_all(&self, buf: &[u8]) -> Result<(), SystemError> {
+        let mut done = 0;
+        while done < buf.len() {
+            let n = self.sock.send(&buf[done..], PMSG::NOSIGNAL)?;
+            if n == 0 {
+                return Err(SystemError::EPIPE);
+            }
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn recv_all(&self, buf: &mut [u8]) -> Result<(), SystemError> {
+        let mut done = 0;
+        while done < buf.len() {
+            let n = self.sock.recv(&mut buf[done..], PMSG::empty())?;
+            if n == 0 {
+                // 服务器关闭了连接
+                return Err(SystemError::ECONNRESET);
+            }
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn recv_u16(&self) -> Result<u16, SystemError> {
+        let mut buf = [0u8; 2];
+        self.recv_all(&mut buf)?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    fn recv_u32(&self) -> Result<u32, SystemError> {
+        let mut buf = [0u8; 4];
+        self.recv_all(&mut buf)?;
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    fn recv_u64(&self) -> Result<u64, SystemError> {
+        let mut buf = [0u8; 8];
+        self.recv_all(&mut buf)?;
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    /// # 进行fixed newstyle握手，进入传输阶段
+    ///
+    /// 优先使用NBD_OPT_GO，服务器不支持时退回到NBD_OPT_EXPORT_NAME
+    ///
+    /// ## 参数
+    /// - `name`: 导出名，空串表示服务器的默认导出
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EPROTO)`: 服务器不是fixed newstyle，或者回复的格式不对
+    /// - `Err(SystemError::ENOENT)`: 导出不存在
+    /// - `Err(SystemError::EACCES)`: 服务器拒绝访问导出
+    pub fn negotiate(&self, name: &[u8]) -> Result<NbdExport, SystemError> {
+        if self.recv_u64()? != NBDMAGIC || self.recv_u64()? != IHAVEOPT {
+            return Err(SystemError::EPROTO);
+        }
+        let hs_flags = self.recv_u16()?;
+        if hs_flags & NBD_FLAG_FIXED_NEWSTYLE == 0 {
+            return Err(SystemError::EPROTO);
+        }
+        let no_zeroes = hs_flags & NBD_FLAG_NO_ZEROES != 0;
+        let mut client_flags = NBD_FLAG_C_FIXED_NEWSTYLE;
+        if no_zeroes {
+            client_flags |= NBD_FLAG_C_NO_ZEROES;
+        }
+        self.send_all(&client_flags.to_be_bytes())?;
+
+        match self.opt_go(name) {
+            Err(SystemError::EOPNOTSUPP_OR_ENOTSUP) => self.opt_export_name(name, no_zeroes),
+            r => r,
+        }
+    }
+
+    fn send_option(&self, opt: u32, data: &[u8]) -> Result<(), SystemError> {
+        let mut msg = Vec::with_capacity(16 + data.len());
+        msg.extend_from_slice(&IHAVEOPT.to_be_bytes());
+        msg.extend_from_slice(&opt.to_be_bytes());
+        msg.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        msg.extend_from_slice(data);
+        self.send_all(&msg)
+    }
+
+    fn opt_go(&self, name: &[u8]) -> Result<NbdExport, SystemError> {
+        // 导出名之后是请求的信息类型的数量，为0时服务器只回复NBD_INFO_EXPORT
+        let mut data = Vec::with_capacity(6 + name.len());
+        data.extend_from_slice(&(name.len() as u32).to_be_bytes());
+        data.extend_from_slice(name);
+        data.extend_from_slice(&0u16.to_be_bytes());
+        self.send_option(NBD_OPT_GO, &data)?;
+
+        let mut export = None;
+        loop {
+            if self.recv_u64()? != OPT_REPLY_MAGIC || self.recv_u32()? != NBD_OPT_GO {
+                return Err(SystemError::EPROTO);
+            }
+            let reply_type = self.recv_u32()?;
+            let len = self.recv_u32()? as usize;
+            if len > NBD_MAX_OPT_REPLY {
+                return Err(SystemError::EPROTO);
+            }
+            let mut payload = vec![0u8; len];
+            self.recv_all(&mut payload)?;
+
+            match reply_type {
+                NBD_REP_ACK => return export.ok_or(SystemError::EPROTO),
+                NBD_REP_INFO => {
+                    // 其他类型的信息不使用
+                    if len >= 12 && u16::from_be_bytes([payload[0], payload[1]]) == NBD_INFO_EXPORT
+                    {
+                        export = Some(NbdExport {
+                            size: u64::from_be_bytes(payload[2..10].try_into().unwrap()),
+                            flags: u16::from_be_bytes([payload[10], payload[11]]),
+                        });
+                    }
+                }
+                NBD_REP_ERR_UNSUP => return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP),
+                NBD_REP_ERR_UNKNOWN => return Err(SystemError::ENOENT),
+                NBD_REP_ERR_POLICY => return Err(SystemError::EACCES),
+                t if t & NBD_REP_FLAG_ERROR != 0 => {
+                    log::warn!(
+                        "nbd: server rejected NBD_OPT_GO with {:#x}: {}",
+                        t,
+                        core::str::from_utf8(&payload).unwrap_or("")
+                    );
+                    return Err(SystemError::EIO);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn opt_export_name(&self, name: &[u8], no_zeroes: bool) -> Result<NbdExport, SystemError> {
+        self.send_option(NBD_OPT_EXPORT_NAME, name)?;
+        // 导出不存在时服务器直接关闭连接
+        let export = NbdExport {
+            size: self.recv_u64()?,
+            flags: self.recv_u16()?,
+        };
+        if !no_zeroes {
+            self.recv_all(&mut [0u8; NBD_EXPORT_NAME_PAD])?;
+        }
+        Ok(export)
+    }
+
+    /// # 发送一个传输阶段的请求
+    ///
+    /// ## 参数
+    /// - `cmd`: 请求类型
+    /// - `handle`: 请求的标识，服务器在回复中原样返回
+    /// - `offset`, `len`: 请求的范围（字节）
+    /// - `data`: 写请求的数据，长度为`len`
+    pub fn send_request(
+        &self,
+        cmd: NbdCmd,
+        handle: u64,
+        offset: u64,
+        len: u32,
+        data: Option<&[u8]>,
+    ) -> Result<(), SystemError> {
+        let mut hdr = [0u8; 28];
+        hdr[0..4].copy_from_slice(&NBD_REQUEST_MAGIC.to_be_bytes());
+        hdr[6..8].copy_from_slice(&(cmd as u16).to_be_bytes());
+        hdr[8..16].copy_from_slice(&handle.to_be_bytes());
+        hdr[16..24].copy_from_slice(&offset.to_be_bytes());
+        hdr[24..28].copy_from_slice(&len.to_be_bytes());
+        self.send_all(&hdr)?;
+        if let Some(data) = data {
+            self.send_all(data)?;
+        }
+        Ok(())
+    }
+
+    /// # 接收一个请求的回复
+    ///
+    /// ## 参数
+    /// - `handle`: 请求的标识
+    /// - `data`: 读请求的数据缓冲区，服务器返回错误时不会有数据
+    ///
+    /// ## 返回值
+    /// - `Ok(Err(error))`: 服务器返回的错误码，连接仍然可以使用
+    /// - `Err(_)`: 连接出错，或者回复不属于这个请求，连接不能再使用
+    pub fn recv_reply(
+        &self,
+        handle: u64,
+        data: Option<&mut [u8]>,
+    ) -> Result<Result<(), u32>, SystemError> {
+        if self.recv_u32()? != NBD_SIMPLE_REPLY_MAGIC {
+            return Err(SystemError::EPROTO);
+        }
+        let error = self.recv_u32()?;
+        if self.recv_u64()? != handle {
+            return Err(SystemError::EPROTO);
+        }
+        if error != 0 {
+            return Ok(Err(error));
+        }
+        if let Some(data) = data {
+            self.recv_all(data)?;
+        }
+        Ok(Ok(()))
+    }
+}
@@ -324,7 +324,7 @@ impl BlockDevice for PmemBlockDevice {
     }
 
     fn partitions(&self) -> Vec<Arc<Partition>> {
-        Vec::new()
+        self.blkdev_meta().partitions()
     }
 }
 
@@ -1,10 +1,29 @@
+//! virtio块设备驱动
+//!
+//! 上层的BIO经过[`RequestQueue`]合并、调度之后，由提交BIO的CPU直接提交到virtqueue，
+//! 设备完成请求后触发中断，在中断下半部（tasklet）中取出完成的请求并完成对应的BIO。
+//!
+//! 设备的特性协商、virtqueue和请求头都由驱动自己处理（virtio-drivers的`VirtIOBlk`不支持DISCARD）。
+//!
+//! 设备提供了多个virtqueue（`VIRTIO_BLK_F_MQ`）时，每个CPU最多使用一个virtqueue。
+//! 请求队列按提交BIO的CPU把请求映射到硬件队列（[`Request::hw_queue`]），
+//! 派发时提交到对应的virtqueue。所有virtqueue共用设备的中断，每个virtqueue有自己的tasklet，
+//! 中断只调度有请求完成的virtqueue的tasklet。
+//!
+//! 设备支持`VIRTIO_BLK_F_DISCARD`时，[`BlockDevice::discard`]按设备的限制拆分成DISCARD请求提交。
+//!
+//! [`Request::hw_queue`]: crate::driver::base::block::request_queue::Request::hw_queue
+
 use core::{
     any::Any,
     fmt::{Debug, Formatter},
+    mem::size_of,
+    ptr,
 };
 
 use alloc::{
     boxed::Box,
+    collections::VecDeque,
     string::{String, ToString},
     sync::{Arc, Weak},
     vec::Vec,
@@ -15,20 +34,20 @@ use log::error;
 use system_error::SystemError;
 use unified_init::macros::unified_init;
 use virtio_drivers::{
-    device::blk::{BlkReq, BlkResp, VirtIOBlk, SECTOR_SIZE},
-    Error as VirtioError,
+    queue::VirtQueue,
+    transport::{DeviceStatus, Transport},
+    Error as VirtioError, PAGE_SIZE,
 };
 
 use crate::{
-    arch::CurrentIrqArch,
     driver::{
         base::{
             block::{
                 bio::{BioRequest, BioType},
-                bio_queue::BioQueue,
                 block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE},
                 disk_info::Partition,
                 manager::{block_dev_manager, BlockDevMeta},
+                request_queue::{Request, RequestQueue},
             },
             class::Class,
             device::{
@@ -43,6 +62,7 @@ use crate::{
         virtio::{
             sysfs::{virtio_bus, virtio_device_manager, virtio_driver_manager},
             transport::VirtIOTransport,
+            virtio_drivers_error_to_system_error,
             virtio_impl::HalImpl,
             VirtIODevice, VirtIODeviceIndex, VirtIODriver, VirtIODriverCommonData, VirtioDeviceId,
             VIRTIO_VENDOR_ID,
@@ -51,12 +71,11 @@ use crate::{
     exception::{
         irqdesc::IrqReturn,
         tasklet::{tasklet_schedule, Tasklet},
-        InterruptArch, IrqNumber,
+        IrqNumber,
     },
     filesystem::{
         devfs::{DevFS, DeviceINode, LockedDevFSInode},
         kernfs::KernFSInode,
-        mbr::MbrDiskPartionTable,
         vfs::{utils::DName, IndexNode, InodeMode, Metadata},
     },
     init::initcall::INITCALL_POSTCORE,
@@ -66,64 +85,309 @@ use crate::{
         rwsem::{RwSemReadGuard, RwSemWriteGuard},
         spinlock::{SpinLock, SpinLockGuard},
     },
-    process::{
-        kthread::{KernelThreadClosure, KernelThreadMechanism},
-        ProcessControlBlock, ProcessManager,
-    },
-    sched::prio::MAX_RT_PRIO,
-    time::{sleep::nanosleep, PosixTimeSpec},
+    smp::cpu::smp_cpu_manager,
 };
 
 const VIRTIO_BLK_BASENAME: &str = "virtio_blk";
 
-// IO线程的budget配置
-const IO_BUDGET: usize = 32; // 每次最多处理32个请求
-const SLEEP_MS: usize = 20; // 达到budget后睡眠20ms
+/// 设备是只读的
+const VIRTIO_BLK_F_RO: u64 = 1 << 5;
+/// 设备有易失性写缓存，支持FLUSH请求
+const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
+/// 设备支持多个virtqueue
+const VIRTIO_BLK_F_MQ: u64 = 1 << 12;
+/// 设备支持DISCARD请求
+const VIRTIO_BLK_F_DISCARD: u64 = 1 << 13;
+/// 设备遵循virtio 1.0及之后的规范
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+/// 驱动支持的特性
+const VIRTIO_BLK_SUPPORTED_FEATURES: u64 = VIRTIO_BLK_F_RO
+    | VIRTIO_BLK_F_FLUSH
+    | VIRTIO_BLK_F_MQ
+    | VIRTIO_BLK_F_DISCARD
+    | VIRTIO_F_VERSION_1;
+
+/// 配置空间中`num_queues`字段的偏移
+const VIRTIO_BLK_CONFIG_NUM_QUEUES_OFFSET: usize = 34;
+/// 配置空间中`max_discard_sectors`字段的偏移，之后依次是`max_discard_seg`、`discard_sector_alignment`
+const VIRTIO_BLK_CONFIG_DISCARD_OFFSET: usize = 36;
+/// 一个DISCARD请求最多包含的段数（与Linux的`MAX_DISCARD_SEGMENTS`相同）
+const VIRTIO_BLK_MAX_DISCARD_SEG: u32 = 256;
+
+/// virtio协议中扇区的大小，与设备的逻辑块大小无关
+const VIRTIO_BLK_SECTOR_SIZE: usize = 512;
+/// 每个virtqueue的描述符数
+const VIRTIO_BLK_QUEUE_SIZE: usize = 64;
+
+// 请求类型
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
+const VIRTIO_BLK_T_DISCARD: u32 = 11;
+
+// 设备写回的请求状态
+const VIRTIO_BLK_S_OK: u8 = 0;
+const VIRTIO_BLK_S_UNSUPP: u8 = 2;
+/// 提交时状态字节的初值，设备没有写回状态时按IO错误处理
+const VIRTIO_BLK_S_NONE: u8 = u8::MAX;
+
+/// 请求头（`struct virtio_blk_outhdr`），设备只读
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct VirtIOBlkReqHeader {
+    req_type: u32,
+    reserved: u32,
+    sector: u64,
+}
 
-/// Token映射表：virtqueue token -> BioRequest
-/// BIO请求的完整上下文，包含virtio需要的req和resp
+impl VirtIOBlkReqHeader {
+    fn new(req_type: u32, sector: u64) -> Self {
+        Self {
+            req_type: req_type.to_le(),
+            reserved: 0,
+            sector: sector.to_le(),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
+    }
+}
+
+/// DISCARD请求中的一段扇区（`struct virtio_blk_discard_write_zeroes`），设备只读
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct VirtIOBlkDiscardSegment {
+    sector: u64,
+    num_sectors: u32,
+    flags: u32,
+}
+
+impl VirtIOBlkDiscardSegment {
+    /// 把从`sector`开始的`count`个扇区拆分成每段最多`max_sectors`个扇区
+    fn split(sector: u64, count: u64, max_sectors: u32) -> Box<[Self]> {
+        let end = sector + count;
+        let mut segments = Vec::new();
+        let mut sector = sector;
+        while sector < end {
+            let n = (end - sector).min(max_sectors as u64);
+            segments.push(Self {
+                sector: sector.to_le(),
+                num_sectors: (n as u32).to_le(),
+                flags: 0,
+            });
+            sector += n;
+        }
+        segments.into_boxed_slice()
+    }
+}
+
+/// 设备对DISCARD请求的限制，单位为virtio扇区
+#[derive(Debug, Clone, Copy)]
+struct VirtIOBlkDiscardLimits {
+    /// 每一段的最大扇区数
+    max_sectors: u32,
+    /// 每个请求的最大段数
+    max_segments: u32,
+    /// 丢弃的起始扇区和扇区数需要对齐到的扇区数
+    alignment: u32,
+}
+
+/// BIO请求的完整上下文，包含设备访问的请求头、DISCARD的段和状态字节。
+/// 请求完成之前设备会访问它们，因此放在堆上，地址不随上下文移动
 struct BioContext {
     bio: Arc<BioRequest>,
-    req: Box<BlkReq>,
-    resp: Box<BlkResp>,
+    header: Box<VirtIOBlkReqHeader>,
+    /// DISCARD请求的段，其他请求为空
+    segments: Box<[VirtIOBlkDiscardSegment]>,
+    status: Box<u8>,
+}
+
+impl BioContext {
+    /// 以设备可读、设备可写两组缓冲区调用`f`。`VirtQueue::pop_used`要求传入与`add`时相同的缓冲区
+    fn with_buffers<R>(&mut self, f: impl FnOnce(&[&[u8]], &mut [&mut [u8]]) -> R) -> R {
+        let header = self.header.as_bytes();
+        let status = core::slice::from_mut(&mut *self.status);
+        match self.bio.bio_type() {
+            BioType::Read => {
+                // SAFETY: 在整个异步操作期间，bio被BioContext持有，缓冲区只由设备访问。
+                // 用户缓冲区的每一页是一段
+                let mut outputs: Vec<&mut [u8]> = self
+                    .bio
+                    .segments()
+                    .into_iter()
+                    .map(|seg| unsafe { &mut *seg })
+                    .collect();
+                outputs.push(status);
+                f(&[header], &mut outputs)
+            }
+            BioType::Write => {
+                let mut inputs: Vec<&[u8]> = vec![header];
+                inputs.extend(self.bio.segments().into_iter().map(|seg| unsafe { &*seg }));
+                f(&inputs, &mut [status])
+            }
+            BioType::Flush => f(&[header], &mut [status]),
+            BioType::Discard => {
+                let segments = unsafe {
+                    core::slice::from_raw_parts(
+                        self.segments.as_ptr() as *const u8,
+                        self.segments.len() * size_of::<VirtIOBlkDiscardSegment>(),
+                    )
+                };
+                f(&[header, segments], &mut [status])
+            }
+        }
+    }
 }
 
-struct BioTokenMap {
-    inner: SpinLock<HashMap<u16, BioContext>>,
+/// BIO及其完成结果
+type CompletedBio = (Arc<BioRequest>, Result<usize, SystemError>);
+
+/// 一个请求virtqueue及其上的请求
+struct VirtIOBlkQueue {
+    vq: VirtQueue<HalImpl, VIRTIO_BLK_QUEUE_SIZE>,
+    /// DISCARD请求每一段的最大扇区数
+    max_discard_sectors: u32,
+    /// 已经提交给设备的请求：virtqueue token -> 上下文
+    inflight: HashMap<u16, BioContext>,
+    /// virtqueue已满时等待提交的BIO，有请求完成之后按顺序提交
+    pending: VecDeque<Arc<BioRequest>>,
 }
 
-impl BioTokenMap {
-    pub fn new() -> Arc<Self> {
-        Arc::new(Self {
-            inner: SpinLock::new(HashMap::new()),
-        })
+impl VirtIOBlkQueue {
+    fn new(vq: VirtQueue<HalImpl, VIRTIO_BLK_QUEUE_SIZE>, max_discard_sectors: u32) -> Self {
+        Self {
+            vq,
+            max_discard_sectors,
+            inflight: HashMap::new(),
+            pending: VecDeque::new(),
+        }
     }
 
-    pub fn insert(&self, token: u16, ctx: BioContext) -> Result<(), BioContext> {
-        let mut inner = self.inner.lock_irqsave();
-        if inner.contains_key(&token) {
-            return Err(ctx);
+    /// # 功能
+    ///
+    /// 把BIO加入virtqueue。
+    ///
+    /// ## 返回值
+    /// - `Ok(true)`: 已加入
+    /// - `Ok(false)`: virtqueue已满
+    fn try_add(&mut self, bio: &Arc<BioRequest>) -> Result<bool, SystemError> {
+        let req_type = match bio.bio_type() {
+            BioType::Read => VIRTIO_BLK_T_IN,
+            BioType::Write => VIRTIO_BLK_T_OUT,
+            BioType::Flush => VIRTIO_BLK_T_FLUSH,
+            BioType::Discard => VIRTIO_BLK_T_DISCARD,
+        };
+        let ratio = (LBA_SIZE / VIRTIO_BLK_SECTOR_SIZE) as u64;
+        let start = bio.lba_start() as u64 * ratio;
+        // FLUSH和DISCARD不使用请求头中的扇区号，DISCARD的范围由段给出
+        let (sector, segments) = match bio.bio_type() {
+            BioType::Read | BioType::Write => (start, Box::default()),
+            BioType::Flush => (0, Box::default()),
+            BioType::Discard => (
+                0,
+                VirtIOBlkDiscardSegment::split(
+                    start,
+                    bio.count() as u64 * ratio,
+                    self.max_discard_sectors,
+                ),
+            ),
+        };
+        let mut ctx = BioContext {
+            bio: bio.clone(),
+            header: Box::new(VirtIOBlkReqHeader::new(req_type, sector)),
+            segments,
+            status: Box::new(VIRTIO_BLK_S_NONE),
+        };
+
+        let vq = &mut self.vq;
+        let token = match ctx.with_buffers(|inputs, outputs| unsafe { vq.add(inputs, outputs) }) {
+            Ok(token) => token,
+            Err(VirtioError::QueueFull) => return Ok(false),
+            Err(e) => {
+                error!("VirtIOBlk: add request failed: {:?}", e);
+                return Err(virtio_drivers_error_to_system_error(e));
+            }
+        };
+        // 描述符已经交给设备，无论如何都要等它完成之后才能释放上下文
+        if let Err(e) = bio.mark_submitted(token) {
+            error!("VirtIOBlk: mark_submitted failed: {:?}", e);
         }
-        inner.insert(token, ctx);
-        Ok(())
+        self.inflight.insert(token, ctx);
+        Ok(true)
+    }
+
+    /// 取出一个设备已经完成的请求
+    fn pop_completed(&mut self) -> Option<CompletedBio> {
+        let token = self.vq.peek_used()?;
+        let Some(mut ctx) = self.inflight.remove(&token) else {
+            error!("VirtIOBlk: token {} not found in flight", token);
+            return None;
+        };
+
+        let vq = &mut self.vq;
+        let popped =
+            ctx.with_buffers(|inputs, outputs| unsafe { vq.pop_used(token, inputs, outputs) });
+        let result = match popped {
+            Ok(_) => match *ctx.status {
+                VIRTIO_BLK_S_OK => Ok(ctx.bio.count() * LBA_SIZE),
+                VIRTIO_BLK_S_UNSUPP => Err(SystemError::EOPNOTSUPP_OR_ENOTSUP),
+                _ => Err(SystemError::EIO),
+            },
+            Err(e) => {
+                error!("VirtIOBlk: pop_used failed: {:?}", e);
+                Err(SystemError::EIO)
+            }
+        };
+        Some((ctx.bio, result))
+    }
+
+    /// # 功能
+    ///
+    /// 把等待中的BIO加入virtqueue，直到virtqueue再次变满。
+    ///
+    /// ## 参数
+    /// - `failed`: 提交失败的BIO，由调用者在释放锁之后完成
+    ///
+    /// ## 返回值
+    /// 是否加入了新的请求
+    fn submit_pending(&mut self, failed: &mut Vec<CompletedBio>) -> bool {
+        let mut added = false;
+        while let Some(bio) = self.pending.pop_front() {
+            match self.try_add(&bio) {
+                Ok(true) => added = true,
+                Ok(false) => {
+                    self.pending.push_front(bio);
+                    break;
+                }
+                Err(e) => failed.push((bio, Err(e))),
+            }
+        }
+        added
     }
 
-    pub fn remove(&self, token: u16) -> Option<BioContext> {
-        self.inner.lock_irqsave().remove(&token)
+    /// 取出所有还没有完成的BIO
+    fn drain(&mut self) -> Vec<Arc<BioRequest>> {
+        self.inflight
+            .drain()
+            .map(|(_, ctx)| ctx.bio)
+            .chain(self.pending.drain(..))
+            .collect()
     }
 }
 
 static mut VIRTIO_BLK_DRIVER: Option<Arc<VirtIOBlkDriver>> = None;
 
-/// 中断下半部：完成 BIO 请求
+/// 中断下半部：完成一个virtqueue上的BIO请求
 struct BioCompletionTasklet {
-    token_map: Arc<BioTokenMap>,
     device: Weak<VirtIOBlkDevice>,
+    /// virtqueue的下标
+    index: usize,
     tasklet: Arc<Tasklet>,
 }
 
 impl BioCompletionTasklet {
-    fn new(device: Weak<VirtIOBlkDevice>, token_map: Arc<BioTokenMap>) -> Arc<Self> {
+    fn new(device: Weak<VirtIOBlkDevice>, index: usize) -> Arc<Self> {
         Arc::new_cyclic(|weak: &alloc::sync::Weak<Self>| {
             let weak_for_cb = weak.clone();
             let tasklet = Tasklet::new(
@@ -136,8 +400,8 @@ impl BioCompletionTasklet {
                 None,
             );
             BioCompletionTasklet {
-                token_map,
                 device,
+                index,
                 tasklet,
             }
         })
@@ -148,82 +412,8 @@ impl BioCompletionTasklet {
     }
 
     fn run(&self) {
-        let device = match self.device.upgrade() {
-            Some(dev) => dev,
-            None => return,
-        };
-        loop {
-            let mut inner = device.inner();
-            let token = match inner.device_inner.peek_used() {
-                Some(token) => token,
-                None => break,
-            };
-
-            let ctx = match self.token_map.remove(token) {
-                Some(ctx) => ctx,
-                None => {
-                    error!("VirtIOBlk: token {} not found in token_map", token);
-                    continue;
-                }
-            };
-
-            let mut ctx = ctx;
-            let bio = ctx.bio.clone();
-            let count = bio.count();
-            let result = match bio.bio_type() {
-                BioType::Read => {
-                    let buf_ptr = bio.buffer_mut();
-                    let buf = unsafe { &mut *buf_ptr };
-                    match unsafe {
-                        inner.device_inner.complete_read_blocks(
-                            token,
-                            &ctx.req,
-                            &mut buf[..count * LBA_SIZE],
-                            &mut ctx.resp,
-                        )
-                    } {
-                        Ok(_) => Ok(count * LBA_SIZE),
-                        Err(VirtioError::NotReady) => {
-                            if self.token_map.insert(token, ctx).is_err() {
-                                error!("VirtIOBlk: token {} reinsert failed", token);
-                                bio.complete(Err(SystemError::EIO));
-                            }
-                            break;
-                        }
-                        Err(e) => {
-                            error!("VirtIOBlk complete_read_blocks failed: {:?}", e);
-                            Err(SystemError::EIO)
-                        }
-                    }
-                }
-                BioType::Write => {
-                    let buf_ptr = bio.buffer();
-                    let buf = unsafe { &*buf_ptr };
-                    match unsafe {
-                        inner.device_inner.complete_write_blocks(
-                            token,
-                            &ctx.req,
-                            &buf[..count * LBA_SIZE],
-                            &mut ctx.resp,
-                        )
-                    } {
-                        Ok(_) => Ok(count * LBA_SIZE),
-                        Err(VirtioError::NotReady) => {
-                            if self.token_map.insert(token, ctx).is_err() {
-                                error!("VirtIOBlk: token {} reinsert failed", token);
-                                bio.complete(Err(SystemError::EIO));
-                            }
-                            break;
-                        }
-                        Err(e) => {
-                            error!("VirtIOBlk complete_write_blocks failed: {:?}", e);
-                            Err(SystemError::EIO)
-                        }
-                    }
-                }
-            };
-            drop(inner);
-            bio.complete(result);
+        if let Some(device) = self.device.upgrade() {
+            device.complete_requests(self.index);
         }
     }
 }
@@ -337,6 +527,16 @@ pub struct VirtIOBlkDevice {
     parent: RwLock<Weak<LockedDevFSInode>>,
     fs: RwLock<Weak<DevFS>>,
     metadata: Metadata,
+    transport: SpinLock<VirtIOTransport>,
+    /// 请求virtqueue，第i个对应请求队列的第i个硬件队列
+    queues: Vec<SpinLock<VirtIOBlkQueue>>,
+    request_queue: Arc<RequestQueue>,
+    /// 协商后的特性
+    features: u64,
+    /// 协商了`VIRTIO_BLK_F_DISCARD`时设备对DISCARD请求的限制
+    discard_limits: Option<VirtIOBlkDiscardLimits>,
+    /// 设备容量（virtio扇区数）
+    capacity: u64,
 }
 
 impl Debug for VirtIOBlkDevice {
@@ -344,6 +544,7 @@ impl Debug for VirtIOBlkDevice {
         f.debug_struct("VirtIOBlkDevice")
             .field("devname", &self.blkdev_meta.devname)
             .field("dev_id", &self.dev_id.id())
+            .field("queues", &self.queues.len())
             .finish()
     }
 }
@@ -359,39 +560,56 @@ impl VirtIOBlkDevice {
             return None;
         }
 
-        let devname = virtioblk_manager().alloc_id()?;
-        let irq = Some(transport.irq());
-        let irq_is_msix = transport.irq_is_msix();
-        let device_inner = VirtIOBlk::<HalImpl, VirtIOTransport>::new(transport);
-        if let Err(e) = device_inner {
-            error!("VirtIOBlkDevice '{dev_id:?}' create failed: {:?}", e);
+        let mut transport = transport;
+        let (features, vqs) = match virtio_blk_init(&mut transport) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("VirtIOBlkDevice '{dev_id:?}' create failed: {:?}", e);
+                return None;
+            }
+        };
+        let Some(capacity) = virtio_blk_read_config::<8>(&transport).map(u64::from_le_bytes) else {
+            error!("VirtIOBlkDevice '{dev_id:?}' has no capacity in config space");
+            transport.set_status(DeviceStatus::FAILED);
             return None;
+        };
+
+        let discard_limits = if features & VIRTIO_BLK_F_DISCARD != 0 {
+            virtio_blk_discard_limits(&transport)
+        } else {
+            None
+        };
+
+        if vqs.len() > 1 {
+            log::info!("VirtIOBlkDevice '{dev_id:?}' uses {} virtqueues", vqs.len());
         }
 
-        let mut device_inner: VirtIOBlk<HalImpl, VirtIOTransport> = device_inner.unwrap();
-        device_inner.enable_interrupts();
+        let devname = virtioblk_manager().alloc_id()?;
+        let irq = Some(transport.irq());
+        let irq_is_msix = transport.irq_is_msix();
 
-        // 创建BIO队列和token映射表
-        let bio_queue = BioQueue::new();
-        let bio_token_map = BioTokenMap::new();
+        let nr_queues = vqs.len();
+        let queues = vqs
+            .into_iter()
+            .map(|vq| {
+                let max_discard_sectors = discard_limits.map_or(u32::MAX, |l| l.max_sectors);
+                SpinLock::new(VirtIOBlkQueue::new(vq, max_discard_sectors))
+            })
+            .collect();
 
-        let dev = Arc::new_cyclic(|self_ref| Self {
+        let dev = Arc::new_cyclic(|self_ref: &Weak<Self>| Self {
             blkdev_meta: BlockDevMeta::new(devname.clone(), Major::VIRTIO_BLK_MAJOR),
             self_ref: self_ref.clone(),
             dev_id,
             locked_kobj_state: LockedKObjectState::default(),
             inner: SpinLock::new(InnerVirtIOBlkDevice {
-                device_inner,
                 name: None,
                 virtio_index: None,
                 device_common: DeviceCommonData::default(),
                 kobject_common: KObjectCommonData::default(),
                 irq,
                 irq_is_msix,
-                bio_queue: Some(bio_queue.clone()),
-                bio_token_map: Some(bio_token_map.clone()),
-                io_thread_pcb: None, // 稍后初始化
-                completion_tasklet: None,
+                completion_tasklets: Vec::new(),
             }),
             parent: RwLock::new(Weak::default()),
             fs: RwLock::new(Weak::default()),
@@ -399,43 +617,108 @@ impl VirtIOBlkDevice {
                 crate::filesystem::vfs::FileType::BlockDevice,
                 InodeMode::from_bits_truncate(0o755),
             ),
+            transport: SpinLock::new(transport),
+            queues,
+            // 请求队列的每个硬件队列对应一个virtqueue，在提交BIO的CPU上直接派发
+            request_queue: {
+                let device = self_ref.clone();
+                RequestQueue::with_queue_rq(
+                    RequestQueue::DEFAULT_MAX_SECTORS,
+                    nr_queues,
+                    Box::new(move |rq| match device.upgrade() {
+                        Some(device) => device.queue_rq(rq),
+                        None => rq.complete(Err(SystemError::ENODEV)),
+                    }),
+                )
+            },
+            features,
+            discard_limits,
+            capacity,
         });
+        if features & VIRTIO_BLK_F_RO != 0 {
+            dev.blkdev_meta.set_read_only(true);
+        }
 
+        // 每个virtqueue一个完成BIO的tasklet
         let device_weak = Arc::downgrade(&dev);
+        dev.inner().completion_tasklets = (0..nr_queues)
+            .map(|index| BioCompletionTasklet::new(device_weak.clone(), index))
+            .collect();
 
-        // 创建BIO完成 tasklet
-        let completion_tasklet = BioCompletionTasklet::new(device_weak.clone(), bio_token_map);
-        dev.inner().completion_tasklet = Some(completion_tasklet);
-
-        // 创建IO线程
-        let thread_name = format!("virtio_blk_io_{}", devname.id());
-        let io_thread = KernelThreadMechanism::create_and_run(
-            KernelThreadClosure::EmptyClosure((
-                alloc::boxed::Box::new(move || bio_io_thread_loop(device_weak.clone())),
-                (),
-            )),
-            thread_name.clone(),
-        );
+        Some(dev)
+    }
 
-        if let Some(io_thread) = io_thread {
-            // 设置FIFO调度策略
-            if let Err(err) = ProcessManager::set_fifo_policy(&io_thread, MAX_RT_PRIO - 1) {
-                error!("Failed to set FIFO policy for {}: {:?}", thread_name, err);
+    fn inner(&self) -> SpinLockGuard<'_, InnerVirtIOBlkDevice> {
+        self.inner.lock_irqsave()
+    }
+
+    /// # 功能
+    ///
+    /// 把BIO提交到第`index`个virtqueue并通知设备。virtqueue已满时BIO进入等待队列，
+    /// 在这个virtqueue上有请求完成之后再提交。
+    fn queue_bio(&self, index: usize, bio: Arc<BioRequest>) -> Result<(), SystemError> {
+        let index = index % self.queues.len();
+        let notify = {
+            let mut queue = self.queues[index].lock_irqsave();
+            // 已经有BIO在等待时排在它们后面，保持提交顺序
+            if !queue.pending.is_empty() || !queue.try_add(&bio)? {
+                queue.pending.push_back(bio);
+                false
+            } else {
+                queue.vq.should_notify()
             }
+        };
+        if notify {
+            self.transport.lock_irqsave().notify(index as u16);
+        }
+        Ok(())
+    }
 
-            // 保存IO线程PCB
-            dev.inner().io_thread_pcb = Some(io_thread.clone());
-        } else {
-            error!("Failed to create IO thread for {}", thread_name);
-            virtioblk_manager().free_id(devname.id());
-            return None;
+    /// 请求队列派发出的请求：提交到它所属的硬件队列对应的virtqueue
+    fn queue_rq(&self, rq: Request) {
+        let hw_queue = rq.hw_queue();
+        // 合并过的请求在这里变成一个覆盖整个区间的BIO
+        let bio = rq.into_bio();
+        if let Err(e) = self.submit_bio_to_virtio(hw_queue, bio.clone()) {
+            error!(
+                "VirtIOBlk: submit to virtqueue {} failed: {:?}",
+                hw_queue, e
+            );
+            bio.complete(Err(e));
         }
+    }
 
-        Some(dev)
+    /// 将BIO请求提交到`hw_queue`对应的virtqueue（异步）
+    fn submit_bio_to_virtio(
+        &self,
+        hw_queue: usize,
+        bio: Arc<BioRequest>,
+    ) -> Result<(), SystemError> {
+        // 设备没有易失性写缓存时，写请求完成就已经持久化了
+        if bio.bio_type() == BioType::Flush && self.features & VIRTIO_BLK_F_FLUSH == 0 {
+            bio.complete(Ok(0));
+            return Ok(());
+        }
+        self.queue_bio(hw_queue, bio)
     }
 
-    fn inner(&self) -> SpinLockGuard<'_, InnerVirtIOBlkDevice> {
-        self.inner.lock_irqsave()
+    /// 取出第`index`个virtqueue上已经完成的请求，完成对应的BIO，并提交等待中的BIO
+    fn complete_requests(&self, index: usize) {
+        let mut completed = Vec::new();
+        let notify = {
+            let mut queue = self.queues[index].lock_irqsave();
+            while let Some(c) = queue.pop_completed() {
+                completed.push(c);
+            }
+            queue.submit_pending(&mut completed) && queue.vq.should_notify()
+        };
+        if notify {
+            self.transport.lock_irqsave().notify(index as u16);
+        }
+        // 完成回调可能再次提交BIO，不能持有virtqueue的锁
+        for (bio, result) in completed {
+            bio.complete(result);
+        }
     }
 }
 
@@ -523,9 +806,7 @@ impl BlockDevice for VirtIOBlkDevice {
     }
 
     fn disk_range(&self) -> GeneralBlockRange {
-        let inner = self.inner();
-        let blocks = inner.device_inner.capacity() as usize * SECTOR_SIZE / LBA_SIZE;
-        drop(inner);
+        let blocks = self.capacity as usize * VIRTIO_BLK_SECTOR_SIZE / LBA_SIZE;
         GeneralBlockRange::new(0, blocks).unwrap()
     }
 
@@ -557,7 +838,8 @@ impl BlockDevice for VirtIOBlkDevice {
     }
 
     fn sync(&self) -> Result<(), SystemError> {
-        Ok(())
+        // 经过请求队列下发，保证排在已经提交的写请求之后
+        self.flush()
     }
 
     fn blk_size_log2(&self) -> u8 {
@@ -577,36 +859,65 @@ impl BlockDevice for VirtIOBlkDevice {
     }
 
     fn partitions(&self) -> Vec<Arc<Partition>> {
-        let device = self.self_ref.upgrade().unwrap() as Arc<dyn BlockDevice>;
-        let mbr_table = MbrDiskPartionTable::from_disk(device.clone())
-            .expect("Failed to get MBR partition table");
-        mbr_table.partitions(Arc::downgrade(&device))
+        self.blkdev_meta().partitions()
     }
 
     /// 提交异步BIO请求
     fn submit_bio(&self, bio: Arc<BioRequest>) -> Result<(), SystemError> {
-        let inner = self.inner();
-        if let Some(bio_queue) = &inner.bio_queue {
-            bio_queue.submit(bio);
-            Ok(())
-        } else {
-            Err(SystemError::ENOSYS)
+        self.request_queue.submit_bio(bio)
+    }
+
+    /// 丢弃扇区
+    ///
+    /// 按设备的`max_discard_sectors`和`max_discard_seg`把区间拆成多个DISCARD请求，
+    /// 经过请求队列提交并等待它们全部完成。区间首尾没有对齐到`discard_sector_alignment`的部分不会被丢弃
+    fn discard(&self, range: GeneralBlockRange) -> Result<(), SystemError> {
+        let limits = self
+            .discard_limits
+            .ok_or(SystemError::EOPNOTSUPP_OR_ENOTSUP)?;
+        if self.blkdev_meta.is_read_only() {
+            return Err(SystemError::EROFS);
+        }
+
+        let ratio = LBA_SIZE / VIRTIO_BLK_SECTOR_SIZE;
+        let align = (limits.alignment as usize).div_ceil(ratio).max(1);
+        let start = range.lba_start.next_multiple_of(align);
+        let end = range.lba_end / align * align;
+        let max_count = (limits.max_sectors as usize / ratio).max(1) * limits.max_segments as usize;
+
+        let mut result = Ok(());
+        let mut bios = Vec::new();
+        let mut lba = start;
+        while lba < end {
+            let count = (end - lba).min(max_count);
+            let bio = BioRequest::new_discard(lba, count);
+            if let Err(e) = self.submit_bio(bio.clone()) {
+                result = Err(e);
+                break;
+            }
+            bios.push(bio);
+            lba += count;
         }
+        // 已经提交的请求引用着BIO，即使出错也要等它们完成
+        for bio in bios {
+            let r = bio.wait();
+            if result.is_ok() {
+                result = r.map(|_| ());
+            }
+        }
+        result
     }
 }
 
 struct InnerVirtIOBlkDevice {
-    device_inner: VirtIOBlk<HalImpl, VirtIOTransport>,
     name: Option<String>,
     virtio_index: Option<VirtIODeviceIndex>,
     device_common: DeviceCommonData,
     kobject_common: KObjectCommonData,
     irq: Option<IrqNumber>,
     irq_is_msix: bool,
-    bio_queue: Option<Arc<BioQueue>>,
-    bio_token_map: Option<Arc<BioTokenMap>>,
-    io_thread_pcb: Option<Arc<ProcessControlBlock>>,
-    completion_tasklet: Option<Arc<BioCompletionTasklet>>,
+    /// 第i个元素完成第i个virtqueue上的请求
+    completion_tasklets: Vec<Arc<BioCompletionTasklet>>,
 }
 
 impl Debug for InnerVirtIOBlkDevice {
@@ -624,8 +935,8 @@ impl VirtIODevice for VirtIOBlkDevice {
         &self,
         _irq: crate::exception::IrqNumber,
     ) -> Result<IrqReturn, system_error::SystemError> {
-        let mut inner = self.inner();
-        let acked = inner.device_inner.ack_interrupt();
+        let acked = self.transport.lock_irqsave().ack_interrupt();
+        let inner = self.inner();
         if !acked && !inner.irq_is_msix {
             log::debug!(
                 "VirtIOBlkDevice '{:?}' ack_interrupt not set",
@@ -633,9 +944,13 @@ impl VirtIODevice for VirtIOBlkDevice {
             );
             return Ok(crate::exception::irqdesc::IrqReturn::NotHandled);
         }
-        let tasklet = inner.completion_tasklet.clone();
-        if let Some(tasklet) = tasklet {
-            tasklet.schedule();
+        let tasklets = inner.completion_tasklets.clone();
+        drop(inner);
+        // 所有virtqueue共用一个中断，只调度有请求完成的virtqueue的tasklet
+        for (queue, tasklet) in self.queues.iter().zip(tasklets.iter()) {
+            if queue.lock_irqsave().vq.peek_used().is_some() {
+                tasklet.schedule();
+            }
         }
         Ok(crate::exception::irqdesc::IrqReturn::Handled)
     }
@@ -802,32 +1117,21 @@ impl KObject for VirtIOBlkDevice {
 
 impl Drop for VirtIOBlkDevice {
     fn drop(&mut self) {
-        let (bio_queue, bio_token_map) = {
-            let inner = self.inner.lock_irqsave();
-            (inner.bio_queue.clone(), inner.bio_token_map.clone())
-        };
-
-        if let Some(bio_queue) = bio_queue {
-            loop {
-                let batch = bio_queue.drain_batch();
-                if batch.is_empty() {
-                    break;
-                }
-                for bio in batch {
-                    bio.complete(Err(SystemError::ENODEV));
-                }
+        // 先完成已经提交给设备的请求，否则排在它们之后的FLUSH无法从队列中取出
+        for queue in self.queues.iter() {
+            let pending = queue.lock_irqsave().drain();
+            for bio in pending {
+                bio.complete(Err(SystemError::ENODEV));
             }
         }
 
-        if let Some(token_map) = bio_token_map {
-            let pending: Vec<BioContext> = token_map
-                .inner
-                .lock_irqsave()
-                .drain()
-                .map(|(_, v)| v)
-                .collect();
-            for ctx in pending {
-                ctx.bio.complete(Err(SystemError::ENODEV));
+        loop {
+            let batch = self.request_queue.drain_batch();
+            if batch.is_empty() {
+                break;
+            }
+            for rq in batch {
+                rq.complete(Err(SystemError::ENODEV));
             }
         }
     }
@@ -1020,150 +1324,97 @@ impl KObject for VirtIOBlkDriver {
     }
 }
 
-/// IO线程入口函数
-fn bio_io_thread_loop(device_weak: Weak<VirtIOBlkDevice>) -> i32 {
-    loop {
-        let device = match device_weak.upgrade() {
-            Some(dev) => dev,
-            None => {
-                // 设备已被销毁，线程退出
-                break;
-            }
-        };
-
-        let bio_queue: Option<Arc<BioQueue>> = {
-            let inner = device.inner();
-            inner.bio_queue.clone()
-        };
-
-        if let Some(bio_queue) = bio_queue {
-            // 等待队列中有请求
-            if let Err(e) = bio_queue.wait_for_work() {
-                log::error!("virtio bio wait_for_work interrupted: {:?}", e);
-                continue;
-            }
-
-            let mut processed = 0;
-
-            // 批量提交新请求，遵守budget限制
-            while processed < IO_BUDGET {
-                let batch = bio_queue.drain_batch();
-                if batch.is_empty() {
-                    break; // 队列空了，退出
-                }
-
-                for bio in batch {
-                    if let Err(e) = submit_bio_to_virtio(&device, bio.clone()) {
-                        log::error!("virtio submit_bio_to_virtio failed: {:?}", e);
-                        // 失败时立即完成BIO
-                        bio.complete(Err(e));
-                    }
-                    processed += 1;
-
-                    if processed >= IO_BUDGET {
-                        break; // 达到budget上限
-                    }
-                }
-            }
-
-            // 达到budget，主动睡眠20ms，避免独占CPU
-            if processed >= IO_BUDGET {
-                let sleep_time = PosixTimeSpec::new(0, (SLEEP_MS as i64) * 1_000_000); // 20ms
-                let _ = nanosleep(sleep_time);
+/// # 功能
+///
+/// 按virtio规范初始化设备：协商特性，并为每个CPU创建最多一个请求virtqueue。
+///
+/// ## 返回值
+/// - `Ok((features, vqs))`: 协商后的特性，以及创建好的virtqueue
+fn virtio_blk_init(
+    transport: &mut VirtIOTransport,
+) -> Result<(u64, Vec<VirtQueue<HalImpl, VIRTIO_BLK_QUEUE_SIZE>>), SystemError> {
+    transport.set_status(DeviceStatus::empty());
+    transport.set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER);
+    let features = transport.read_device_features() & VIRTIO_BLK_SUPPORTED_FEATURES;
+    transport.write_driver_features(features);
+    transport
+        .set_status(DeviceStatus::ACKNOWLEDGE | DeviceStatus::DRIVER | DeviceStatus::FEATURES_OK);
+    if !transport.get_status().contains(DeviceStatus::FEATURES_OK) {
+        transport.set_status(DeviceStatus::FAILED);
+        return Err(SystemError::ENODEV);
+    }
+    transport.set_guest_page_size(PAGE_SIZE as u32);
+
+    let device_queues = if features & VIRTIO_BLK_F_MQ != 0 {
+        virtio_blk_read_config::<{ VIRTIO_BLK_CONFIG_NUM_QUEUES_OFFSET + 2 }>(transport)
+            .map(|raw| {
+                u16::from_le_bytes([
+                    raw[VIRTIO_BLK_CONFIG_NUM_QUEUES_OFFSET],
+                    raw[VIRTIO_BLK_CONFIG_NUM_QUEUES_OFFSET + 1],
+                ])
+            })
+            .unwrap_or(1)
+    } else {
+        1
+    };
+    let nr_queues = (device_queues as usize)
+        .min(smp_cpu_manager().possible_cpus_count() as usize)
+        .max(1);
+
+    let mut vqs = Vec::with_capacity(nr_queues);
+    for idx in 0..nr_queues {
+        match VirtQueue::<HalImpl, VIRTIO_BLK_QUEUE_SIZE>::new(transport, idx as u16, false, false)
+        {
+            Ok(vq) => vqs.push(vq),
+            Err(e) => {
+                transport.set_status(DeviceStatus::FAILED);
+                return Err(virtio_drivers_error_to_system_error(e));
             }
-        } else {
-            break;
         }
     }
-
-    0
+    transport.finish_init();
+    Ok((features, vqs))
 }
 
-/// 将BIO请求提交到VirtIO设备（异步）
-fn submit_bio_to_virtio(
-    device: &Arc<VirtIOBlkDevice>,
-    bio: Arc<BioRequest>,
-) -> Result<(), SystemError> {
-    // 获取BIO信息
-    let lba_start = bio.lba_start();
-    let count = bio.count();
-    let bio_type = bio.bio_type();
-
-    // 获取token_map（clone以避免借用冲突）
-    let token_map = {
-        let inner = device.inner();
-        inner
-            .bio_token_map
-            .as_ref()
-            .ok_or(SystemError::EINVAL)?
-            .clone()
-    };
-
-    // 创建请求和响应结构
-    let mut req = Box::new(BlkReq::default());
-    let mut resp = Box::new(BlkResp::default());
-
-    // 获取buffer指针（在整个异步操作期间，bio会被BioContext持有，保证buffer有效）
-    let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
-
-    // 提交异步请求，获取token
-    let token = {
-        let mut inner = device.inner();
-        match bio_type {
-            BioType::Read => {
-                let buf_ptr = bio.buffer_mut();
-                let buf = unsafe { &mut *buf_ptr };
-                unsafe {
-                    inner.device_inner.read_blocks_nb(
-                        lba_start,
-                        &mut req,
-                        &mut buf[..count * LBA_SIZE],
-                        &mut resp,
-                    )
-                }
-                .map_err(|e| {
-                    error!("VirtIOBlk async read_blocks_nb failed: {:?}", e);
-                    SystemError::EIO
-                })?
-            }
-            BioType::Write => {
-                let buf_ptr = bio.buffer();
-                let buf = unsafe { &*buf_ptr };
-                unsafe {
-                    inner.device_inner.write_blocks_nb(
-                        lba_start,
-                        &mut req,
-                        &buf[..count * LBA_SIZE],
-                        &mut resp,
-                    )
-                }
-                .map_err(|e| {
-                    error!("VirtIOBlk async write_blocks_nb failed: {:?}", e);
-                    SystemError::EIO
-                })?
-            }
+/// 读出设备对DISCARD请求的限制，值为0的字段按Linux的virtio_blk的方式取默认值
+///
+/// ## 返回值
+/// - `None`: 配置空间中没有这些字段
+fn virtio_blk_discard_limits(transport: &VirtIOTransport) -> Option<VirtIOBlkDiscardLimits> {
+    let raw = virtio_blk_read_config::<{ VIRTIO_BLK_CONFIG_DISCARD_OFFSET + 12 }>(transport)?;
+    let field = |index: usize, default: u32| {
+        let off = VIRTIO_BLK_CONFIG_DISCARD_OFFSET + index * size_of::<u32>();
+        match u32::from_le_bytes([raw[off], raw[off + 1], raw[off + 2], raw[off + 3]]) {
+            0 => default,
+            v => v,
         }
     };
+    Some(VirtIOBlkDiscardLimits {
+        max_sectors: field(0, u32::MAX),
+        max_segments: field(1, 1).min(VIRTIO_BLK_MAX_DISCARD_SEG),
+        alignment: field(2, 1),
+    })
+}
 
-    // 保存上下文到token_map
-    let ctx = BioContext {
-        bio: bio.clone(),
-        req,
-        resp,
-    };
-    if token_map.insert(token, ctx).is_err() {
-        drop(irq_guard);
-        return Err(SystemError::EEXIST);
-    }
-
-    // 标记BIO为已提交
-    if let Err(e) = bio.mark_submitted(token) {
-        token_map.remove(token);
-        drop(irq_guard);
-        return Err(e);
-    }
-
-    drop(irq_guard);
+/// 配置空间开头的`N`个字节，只用于获取配置空间的地址
+#[allow(dead_code)]
+#[repr(C, packed)]
+struct VirtIOBlkConfigPrefix<const N: usize> {
+    bytes: [u8; N],
+}
 
-    Ok(())
+/// # 功能
+///
+/// 读出配置空间开头的`N`个字节。配置空间的长度与设备提供的特性有关，这里只访问需要的部分
+///
+/// ## 返回值
+/// - `None`: 配置空间不足`N`个字节
+fn virtio_blk_read_config<const N: usize>(transport: &VirtIOTransport) -> Option<[u8; N]> {
+    let cfg = transport.config_space::<VirtIOBlkConfigPrefix<N>>().ok()?;
+    let base = cfg.as_ptr() as *const u8;
+    let mut raw = [0u8; N];
+    for (i, b) in raw.iter_mut().enumerate() {
+        *b = unsafe { ptr::read_volatile(base.add(i)) };
+    }
+    Some(raw)
 }
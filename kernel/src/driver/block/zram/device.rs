@@ -0,0 +1,751 @@
+//! 压缩内存块设备
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    fmt::Debug,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+};
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    driver::base::{
+        block::{
+            block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE},
+            disk_info::Partition,
+            manager::{block_dev_manager, BlockDevMeta},
+        },
+        class::Class,
+        device::{
+            bus::Bus,
+            device_number::{DeviceNumber, Major},
+            driver::Driver,
+            DevName, Device, DeviceCommonData, DeviceType, IdTable,
+        },
+        kobject::{KObjType, KObject, KObjectCommonData, KObjectState, LockedKObjectState},
+        kset::KSet,
+    },
+    filesystem::{
+        devfs::{DevFS, DeviceINode, LockedDevFSInode},
+        kernfs::KernFSInode,
+        sysfs::AttributeGroup,
+        vfs::{utils::DName, FilePrivateData, IndexNode, InodeFlags, InodeId, InodeMode, Metadata},
+    },
+    libs::{
+        lz4,
+        mutex::MutexGuard,
+        rwlock::RwLock,
+        rwsem::{RwSemReadGuard, RwSemWriteGuard},
+        spinlock::{SpinLock, SpinLockGuard},
+    },
+    mm::MemoryManagementArch,
+};
+
+use super::{
+    sysfs::ZramAttrGroup,
+    zpool::{ZHandle, ZPool},
+};
+
+pub(super) const ZRAM_BASENAME: &str = "zram";
+
+/// 压缩后超过这个长度的页不再压缩，原样保存在一整页中（huge page）
+const ZRAM_HUGE_SIZE: usize = MMArch::PAGE_SIZE / 4 * 3;
+
+/// 一个数据页的保存方式
+#[derive(Debug, Clone, Copy)]
+enum ZramSlot {
+    /// 整页都是同一个`u64`的重复，只记录这个值
+    Same(u64),
+    /// 保存在内存池中的对象，`len`为`PAGE_SIZE`时是未压缩的原始数据
+    Object { handle: ZHandle, len: usize },
+}
+
+/// 内存使用统计，对应`/sys/block/zramN/mm_stat`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ZramMmStat {
+    /// 保存的数据页数量
+    pub pages_stored: usize,
+    /// 压缩后的数据总长度（字节）
+    pub compr_data_size: usize,
+    /// 内存池占用的页数量
+    pub mem_used_pages: usize,
+    /// 内存池占用页数量的历史最大值
+    pub mem_used_max_pages: usize,
+    /// 同值页的数量
+    pub same_pages: usize,
+    /// 当前不可压缩页的数量
+    pub huge_pages: usize,
+    /// 设备初始化以来写入过的不可压缩页的数量
+    pub huge_pages_since: usize,
+}
+
+/// I/O统计，对应`/sys/block/zramN/io_stat`
+#[derive(Debug, Default)]
+pub struct ZramIoStat {
+    pub failed_reads: AtomicU64,
+    pub failed_writes: AtomicU64,
+    pub invalid_io: AtomicU64,
+    /// 被discard释放的页数量
+    pub notify_free: AtomicU64,
+}
+
+/// 设备的数据，reset时整体丢弃
+struct ZramState {
+    /// 设备容量（字节），为0表示还没有设置`disksize`
+    disksize: usize,
+    /// 已写入的数据页，key为页号
+    table: BTreeMap<usize, ZramSlot>,
+    pool: ZPool,
+    stat: ZramMmStat,
+    /// 压缩用的缓冲区
+    cbuf: Vec<u8>,
+}
+
+impl ZramState {
+    fn new() -> Self {
+        Self {
+            disksize: 0,
+            table: BTreeMap::new(),
+            pool: ZPool::new(),
+            stat: ZramMmStat::default(),
+            cbuf: Vec::new(),
+        }
+    }
+
+    /// 释放一个数据页
+    fn free_page(&mut self, index: usize) -> bool {
+        match self.table.remove(&index) {
+            Some(ZramSlot::Same(_)) => self.stat.same_pages -= 1,
+            Some(ZramSlot::Object { handle, len }) => {
+                self.pool.free(handle);
+                self.stat.compr_data_size -= len;
+                if len == MMArch::PAGE_SIZE {
+                    self.stat.huge_pages -= 1;
+                }
+            }
+            None => return false,
+        }
+        self.stat.pages_stored -= 1;
+        true
+    }
+
+    /// 读出一个数据页，没有写入过的页全为0
+    fn load_page(&self, index: usize, out: &mut [u8]) -> Result<(), SystemError> {
+        match self.table.get(&index) {
+            None => out.fill(0),
+            Some(ZramSlot::Same(v)) => {
+                let pattern = v.to_ne_bytes();
+                out.chunks_exact_mut(pattern.len())
+                    .for_each(|c| c.copy_from_slice(&pattern));
+            }
+            Some(ZramSlot::Object { handle, len }) => {
+                let obj = &self.pool.object(*handle)[..*len];
+                if *len == MMArch::PAGE_SIZE {
+                    out.copy_from_slice(obj);
+                } else if lz4::decompress(obj, out) != Ok(MMArch::PAGE_SIZE) {
+                    return Err(SystemError::EIO);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 保存一个数据页，替换原有的内容
+    fn store_page(&mut self, index: usize, data: &[u8]) -> Result<(), SystemError> {
+        if let Some(v) = same_filled(data) {
+            self.free_page(index);
+            self.table.insert(index, ZramSlot::Same(v));
+            self.stat.same_pages += 1;
+            self.stat.pages_stored += 1;
+            return Ok(());
+        }
+
+        if self.cbuf.is_empty() {
+            self.cbuf = vec![0; ZRAM_HUGE_SIZE];
+        }
+        let len = lz4::compress(data, &mut self.cbuf).unwrap_or(MMArch::PAGE_SIZE);
+        // 先分配新对象，失败时保留原来的数据
+        let handle = self.pool.alloc(len)?;
+        let src = if len == MMArch::PAGE_SIZE {
+            data
+        } else {
+            &self.cbuf[..len]
+        };
+        self.pool.object_mut(handle)[..len].copy_from_slice(src);
+
+        self.free_page(index);
+        self.table.insert(index, ZramSlot::Object { handle, len });
+        self.stat.compr_data_size += len;
+        if len == MMArch::PAGE_SIZE {
+            self.stat.huge_pages += 1;
+            self.stat.huge_pages_since += 1;
+        }
+        self.stat.pages_stored += 1;
+        self.stat.mem_used_max_pages = self.stat.mem_used_max_pages.max(self.pool.pages());
+        Ok(())
+    }
+}
+
+/// 如果页的内容是同一个`u64`的重复，返回这个值
+fn same_filled(data: &[u8]) -> Option<u64> {
+    let mut words = data
+        .chunks_exact(8)
+        .map(|c| u64::from_ne_bytes(c.try_into().unwrap()));
+    let first = words.next()?;
+    words.all(|w| w == first).then_some(first)
+}
+
+#[derive(Debug)]
+struct InnerZramDevice {
+    device_common: DeviceCommonData,
+    kobject_common: KObjectCommonData,
+}
+
+#[cast_to([sync] Device)]
+pub struct ZramDevice {
+    blkdev_meta: BlockDevMeta,
+    inner: SpinLock<InnerZramDevice>,
+    locked_kobj_state: LockedKObjectState,
+    self_ref: Weak<Self>,
+    parent: RwLock<Weak<LockedDevFSInode>>,
+    fs: RwLock<Weak<DevFS>>,
+    raw_dev: DeviceNumber,
+    state: SpinLock<ZramState>,
+    io_stat: ZramIoStat,
+    /// 设备被打开的次数
+    open_count: AtomicU32,
+}
+
+impl Debug for ZramDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ZramDevice")
+            .field("devname", &self.blkdev_meta.devname)
+            .field("disksize", &self.disksize())
+            .finish()
+    }
+}
+
+impl ZramDevice {
+    /// 创建一个zram设备，创建时容量为0，需要通过`disksize`设置容量后才能使用
+    ///
+    /// ## 参数
+    ///
+    /// - `id`: 设备编号，设备名为`zram{id}`
+    pub fn new(id: usize) -> Arc<Self> {
+        let devname = DevName::new(format!("{ZRAM_BASENAME}{id}"), id);
+
+        Arc::new_cyclic(|self_ref| {
+            let blkdev_meta = BlockDevMeta::new(devname, Major::ZRAM_MAJOR);
+            let raw_dev = DeviceNumber::new(blkdev_meta.major, blkdev_meta.base_minor);
+
+            Self {
+                blkdev_meta,
+                inner: SpinLock::new(InnerZramDevice {
+                    device_common: DeviceCommonData::default(),
+                    kobject_common: KObjectCommonData::default(),
+                }),
+                locked_kobj_state: LockedKObjectState::default(),
+                self_ref: self_ref.clone(),
+                parent: RwLock::new(Weak::default()),
+                fs: RwLock::new(Weak::default()),
+                raw_dev,
+                state: SpinLock::new(ZramState::new()),
+                io_stat: ZramIoStat::default(),
+                open_count: AtomicU32::new(0),
+            }
+        })
+    }
+
+    /// 设备容量（字节），还没有初始化时为0
+    pub fn disksize(&self) -> usize {
+        self.state.lock().disksize
+    }
+
+    /// # 设置设备容量
+    ///
+    /// ## 参数
+    ///
+    /// - `size`: 容量（字节），向上对齐到页大小
+    ///
+    /// ## 返回值
+    ///
+    /// - `Err(SystemError::EBUSY)`: 设备已经初始化，需要先reset
+    /// - `Err(SystemError::EINVAL)`: 容量为0
+    pub fn set_disksize(&self, size: usize) -> Result<(), SystemError> {
+        let size = size
+            .checked_next_multiple_of(MMArch::PAGE_SIZE)
+            .ok_or(SystemError::EINVAL)?;
+        if size == 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let mut state = self.state.lock();
+        if state.disksize != 0 {
+            return Err(SystemError::EBUSY);
+        }
+        state.disksize = size;
+        drop(state);
+        log::info!(
+            "{}: detected capacity change from 0 to {}",
+            self.blkdev_meta.devname,
+            size / LBA_SIZE
+        );
+        Ok(())
+    }
+
+    /// # 重置设备
+    ///
+    /// 释放所有数据并把容量恢复为0
+    ///
+    /// ## 返回值
+    ///
+    /// - `Err(SystemError::EBUSY)`: 设备正在被使用
+    pub fn reset(&self) -> Result<(), SystemError> {
+        if self.open_count.load(Ordering::Acquire) != 0 {
+            return Err(SystemError::EBUSY);
+        }
+        let old = core::mem::replace(&mut *self.state.lock(), ZramState::new());
+        // 在锁外释放内存池
+        drop(old);
+        if let Some(dev) = self.self_ref.upgrade() {
+            block_dev_manager().notify_media_change(&(dev as Arc<dyn BlockDevice>));
+        }
+        Ok(())
+    }
+
+    /// 内存使用统计
+    pub fn mm_stat(&self) -> ZramMmStat {
+        let state = self.state.lock();
+        ZramMmStat {
+            mem_used_pages: state.pool.pages(),
+            ..state.stat
+        }
+    }
+
+    pub fn io_stat(&self) -> &ZramIoStat {
+        &self.io_stat
+    }
+
+    /// 设备节点被打开时调用
+    pub(crate) fn on_open(&self) {
+        self.open_count.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// 设备节点被关闭时调用
+    pub(crate) fn on_release(&self) {
+        self.open_count.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn inner(&self) -> SpinLockGuard<'_, InnerZramDevice> {
+        self.inner.lock_irqsave()
+    }
+
+    /// 检查IO范围，返回(字节偏移, 字节长度)
+    fn check_range(
+        disksize: usize,
+        lba_id_start: BlockId,
+        count: usize,
+        buf_len: usize,
+    ) -> Result<(usize, usize), SystemError> {
+        let offset = lba_id_start
+            .checked_mul(LBA_SIZE)
+            .ok_or(SystemError::EOVERFLOW)?;
+        let len = count.checked_mul(LBA_SIZE).ok_or(SystemError::EOVERFLOW)?;
+        if len > buf_len {
+            return Err(SystemError::EINVAL);
+        }
+
+        let end = offset.checked_add(len).ok_or(SystemError::EOVERFLOW)?;
+        if end > disksize {
+            return Err(SystemError::ENOSPC);
+        }
+        Ok((offset, len))
+    }
+
+    fn do_read(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        let state = self.state.lock();
+        let (mut offset, len) = Self::check_range(state.disksize, lba_id_start, count, buf.len())
+            .inspect_err(|_| {
+            self.io_stat.invalid_io.fetch_add(1, Ordering::Relaxed);
+        })?;
+
+        let mut page = Vec::new();
+        let mut done = 0;
+        while done < len {
+            let page_off = offset % MMArch::PAGE_SIZE;
+            let chunk = (MMArch::PAGE_SIZE - page_off).min(len - done);
+            let index = offset / MMArch::PAGE_SIZE;
+            if chunk == MMArch::PAGE_SIZE {
+                state.load_page(index, &mut buf[done..done + chunk])?;
+            } else {
+                page.resize(MMArch::PAGE_SIZE, 0);
+                state.load_page(index, &mut page)?;
+                buf[done..done + chunk].copy_from_slice(&page[page_off..page_off + chunk]);
+            }
+            offset += chunk;
+            done += chunk;
+        }
+
+        Ok(len)
+    }
+
+    fn do_write(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        let mut state = self.state.lock();
+        let (mut offset, len) = Self::check_range(state.disksize, lba_id_start, count, buf.len())
+            .inspect_err(|_| {
+            self.io_stat.invalid_io.fetch_add(1, Ordering::Relaxed);
+        })?;
+
+        let mut page = Vec::new();
+        let mut done = 0;
+        while done < len {
+            let page_off = offset % MMArch::PAGE_SIZE;
+            let chunk = (MMArch::PAGE_SIZE - page_off).min(len - done);
+            let index = offset / MMArch::PAGE_SIZE;
+            if chunk == MMArch::PAGE_SIZE {
+                state.store_page(index, &buf[done..done + chunk])?;
+            } else {
+                // 不足一页的写入需要先读出整页
+                page.resize(MMArch::PAGE_SIZE, 0);
+                state.load_page(index, &mut page)?;
+                page[page_off..page_off + chunk].copy_from_slice(&buf[done..done + chunk]);
+                state.store_page(index, &page)?;
+            }
+            offset += chunk;
+            done += chunk;
+        }
+
+        Ok(len)
+    }
+}
+
+impl IndexNode for ZramDevice {
+    fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
+        self.fs.read().upgrade().expect("ZramDevice fs is not set")
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        let size = self.disksize();
+        Ok(Metadata {
+            dev_id: 0,
+            inode_id: InodeId::new(0),
+            size: (size.min(i64::MAX as usize)) as i64,
+            blk_size: LBA_SIZE,
+            blocks: size / LBA_SIZE,
+            atime: Default::default(),
+            mtime: Default::default(),
+            ctime: Default::default(),
+            btime: Default::default(),
+            file_type: crate::filesystem::vfs::FileType::BlockDevice,
+            mode: InodeMode::from_bits_truncate(0o660),
+            flags: InodeFlags::empty(),
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            raw_dev: self.raw_dev,
+        })
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let parent = self.parent.read();
+        if let Some(parent) = parent.upgrade() {
+            return Ok(parent as Arc<dyn IndexNode>);
+        }
+        Err(SystemError::ENOENT)
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn dname(&self) -> Result<DName, SystemError> {
+        Ok(DName::from(self.blkdev_meta.devname.clone().as_ref()))
+    }
+
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _mode: &crate::filesystem::vfs::file::FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+}
+
+impl DeviceINode for ZramDevice {
+    fn set_fs(&self, fs: Weak<DevFS>) {
+        *self.fs.write() = fs;
+    }
+
+    fn set_parent(&self, parent: Weak<LockedDevFSInode>) {
+        *self.parent.write() = parent;
+    }
+}
+
+impl BlockDevice for ZramDevice {
+    fn dev_name(&self) -> &DevName {
+        &self.blkdev_meta.devname
+    }
+
+    fn blkdev_meta(&self) -> &BlockDevMeta {
+        &self.blkdev_meta
+    }
+
+    fn disk_range(&self) -> GeneralBlockRange {
+        let blocks = self.disksize() / LBA_SIZE;
+        GeneralBlockRange::new(0, blocks).unwrap_or(GeneralBlockRange {
+            lba_start: 0,
+            lba_end: 0,
+        })
+    }
+
+    fn read_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        self.do_read(lba_id_start, count, buf).inspect_err(|_| {
+            self.io_stat.failed_reads.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
+    fn write_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        self.do_write(lba_id_start, count, buf).inspect_err(|_| {
+            self.io_stat.failed_writes.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
+    fn sync(&self) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    /// 释放被完整覆盖的数据页。与Linux相同，不足一页的部分被忽略
+    fn discard(&self, range: GeneralBlockRange) -> Result<(), SystemError> {
+        let count = range.lba_end - range.lba_start;
+        if count == 0 {
+            return Ok(());
+        }
+        let mut state = self.state.lock();
+        let (offset, len) = Self::check_range(state.disksize, range.lba_start, count, usize::MAX)?;
+
+        let first_full = offset.div_ceil(MMArch::PAGE_SIZE);
+        let last_full = (offset + len) / MMArch::PAGE_SIZE;
+        if first_full >= last_full {
+            return Ok(());
+        }
+        let freed: Vec<usize> = state
+            .table
+            .range(first_full..last_full)
+            .map(|(idx, _)| *idx)
+            .collect();
+        for idx in freed {
+            if state.free_page(idx) {
+                self.io_stat.notify_free.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    fn blk_size_log2(&self) -> u8 {
+        9
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn device(&self) -> Arc<dyn Device> {
+        self.self_ref.upgrade().unwrap()
+    }
+
+    fn block_size(&self) -> usize {
+        LBA_SIZE
+    }
+
+    fn partitions(&self) -> Vec<Arc<Partition>> {
+        self.blkdev_meta().partitions()
+    }
+
+    fn sysfs_groups(&self) -> &'static [&'static dyn AttributeGroup] {
+        &[&ZramAttrGroup]
+    }
+}
+
+impl Device for ZramDevice {
+    fn dev_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id_table(&self) -> IdTable {
+        IdTable::new(ZRAM_BASENAME.to_string(), None)
+    }
+
+    fn bus(&self) -> Option<Weak<dyn Bus>> {
+        self.inner().device_common.bus.clone()
+    }
+
+    fn set_bus(&self, bus: Option<Weak<dyn Bus>>) {
+        self.inner().device_common.bus = bus;
+    }
+
+    fn class(&self) -> Option<Arc<dyn Class>> {
+        let mut guard = self.inner();
+        let class = guard.device_common.class.clone()?.upgrade();
+        if class.is_none() {
+            guard.device_common.class = None;
+        }
+        class
+    }
+
+    fn set_class(&self, class: Option<Weak<dyn Class>>) {
+        self.inner().device_common.class = class;
+    }
+
+    fn driver(&self) -> Option<Arc<dyn Driver>> {
+        let mut guard = self.inner();
+        let driver = guard.device_common.driver.clone()?.upgrade();
+        if driver.is_none() {
+            guard.device_common.driver = None;
+        }
+        driver
+    }
+
+    fn set_driver(&self, driver: Option<Weak<dyn Driver>>) {
+        self.inner().device_common.driver = driver;
+    }
+
+    fn is_dead(&self) -> bool {
+        false
+    }
+
+    fn can_match(&self) -> bool {
+        self.inner().device_common.can_match
+    }
+
+    fn set_can_match(&self, can_match: bool) {
+        self.inner().device_common.can_match = can_match;
+    }
+
+    fn state_synced(&self) -> bool {
+        true
+    }
+
+    fn dev_parent(&self) -> Option<Weak<dyn Device>> {
+        self.inner().device_common.get_parent_weak_or_clear()
+    }
+
+    fn set_dev_parent(&self, parent: Option<Weak<dyn Device>>) {
+        self.inner().device_common.parent = parent;
+    }
+}
+
+impl KObject for ZramDevice {
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn set_inode(&self, inode: Option<Arc<KernFSInode>>) {
+        self.inner().kobject_common.kern_inode = inode;
+    }
+
+    fn inode(&self) -> Option<Arc<KernFSInode>> {
+        self.inner().kobject_common.kern_inode.clone()
+    }
+
+    fn parent(&self) -> Option<Weak<dyn KObject>> {
+        self.inner().kobject_common.parent.clone()
+    }
+
+    fn set_parent(&self, parent: Option<Weak<dyn KObject>>) {
+        self.inner().kobject_common.parent = parent;
+    }
+
+    fn kset(&self) -> Option<Arc<KSet>> {
+        self.inner().kobject_common.kset.clone()
+    }
+
+    fn set_kset(&self, kset: Option<Arc<KSet>>) {
+        self.inner().kobject_common.kset = kset;
+    }
+
+    fn kobj_type(&self) -> Option<&'static dyn KObjType> {
+        self.inner().kobject_common.kobj_type
+    }
+
+    fn name(&self) -> String {
+        self.dev_name().to_string()
+    }
+
+    fn set_name(&self, _name: String) {}
+
+    fn kobj_state(&self) -> RwSemReadGuard<'_, KObjectState> {
+        self.locked_kobj_state.read()
+    }
+
+    fn kobj_state_mut(&self) -> RwSemWriteGuard<'_, KObjectState> {
+        self.locked_kobj_state.write()
+    }
+
+    fn set_kobj_state(&self, state: KObjectState) {
+        *self.locked_kobj_state.write() = state;
+    }
+
+    fn set_kobj_type(&self, ktype: Option<&'static dyn KObjType>) {
+        self.inner().kobject_common.kobj_type = ktype;
+    }
+}
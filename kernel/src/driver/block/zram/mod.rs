@@ -0,0 +1,56 @@
+//! 压缩内存块设备（zram）
+//!
+//! 启动时创建`/dev/zram0`~`/dev/zram{num_devices-1}`，数量由内核命令行参数`zram.num_devices`指定。
+//! 设备创建时容量为0，需要先向`/sys/block/zramN/disksize`写入容量，之后可以用作swap或者格式化成文件系统。
+//!
+//! 写入的每个页用LZ4压缩后保存在按大小分类的内存池中，参见[`zpool`]；整页是同一个值的页只记录这个值，
+//! 压缩效果不好的页原样保存。压缩统计可以在`/sys/block/zramN/mm_stat`中查看，参见[`sysfs`]。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/block/zram/zram_drv.c
+
+mod device;
+mod sysfs;
+mod zpool;
+
+use alloc::{sync::Arc, vec::Vec};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    driver::base::block::{block_device::BlockDevice, manager::block_dev_manager},
+    init::initcall::INITCALL_DEVICE,
+    libs::spinlock::SpinLock,
+};
+
+pub use device::ZramDevice;
+
+/// 默认创建的zram设备数量
+const DEFAULT_NUM_DEVICES: usize = 1;
+/// 最多创建的zram设备数量
+const MAX_NUM_DEVICES: usize = 32;
+
+kernel_cmdline_param_kv!(ZRAM_NUM_DEVICES_PARAM, "zram.num_devices", "");
+
+static ZRAM_DEVICES: SpinLock<Vec<Arc<ZramDevice>>> = SpinLock::new(Vec::new());
+
+/// 所有的zram设备
+pub fn zram_devices() -> Vec<Arc<ZramDevice>> {
+    ZRAM_DEVICES.lock().clone()
+}
+
+#[unified_init(INITCALL_DEVICE)]
+fn zram_init() -> Result<(), SystemError> {
+    let nr = ZRAM_NUM_DEVICES_PARAM
+        .value_str()
+        .and_then(|s| s.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_NUM_DEVICES)
+        .min(MAX_NUM_DEVICES);
+
+    for id in 0..nr {
+        let dev = ZramDevice::new(id);
+        block_dev_manager().register(dev.clone() as Arc<dyn BlockDevice>)?;
+        ZRAM_DEVICES.lock().push(dev);
+    }
+    log::info!("zram: created {} devices", nr);
+    Ok(())
+}
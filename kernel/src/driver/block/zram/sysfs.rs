@@ -0,0 +1,250 @@
+//! `/sys/block/zramN`下的zram属性
+//!
+//! - `disksize`: 设备容量，写入时可以带`K`/`M`/`G`后缀，只能在初始化之前写入
+//! - `reset`: 写入非0值释放所有数据并把容量恢复为0
+//! - `initstate`: 是否已经设置了容量
+//! - `comp_algorithm`: 压缩算法，目前只支持lz4
+//! - `mm_stat`: 内存使用统计，字段与Linux相同
+//! - `io_stat`: I/O失败统计，字段与Linux相同
+
+use alloc::sync::Arc;
+use core::sync::atomic::Ordering;
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    driver::base::kobject::KObject,
+    filesystem::{
+        sysfs::{
+            file::sysfs_emit_str, Attribute, AttributeGroup, SysFSOpsSupport, SYSFS_ATTR_MODE_RO,
+            SYSFS_ATTR_MODE_RW, SYSFS_ATTR_MODE_WO,
+        },
+        vfs::InodeMode,
+    },
+    mm::MemoryManagementArch,
+};
+
+use super::{device::ZramDevice, zram_devices};
+
+/// 根据`/sys/block/zramN`目录的kobject找到zram设备
+fn kobj_zram(kobj: &Arc<dyn KObject>) -> Result<Arc<ZramDevice>, SystemError> {
+    let name = kobj.name();
+    zram_devices()
+        .into_iter()
+        .find(|dev| dev.dev_name().as_str() == name)
+        .ok_or(SystemError::ENODEV)
+}
+
+fn parse_str(buf: &[u8]) -> Result<&str, SystemError> {
+    core::str::from_utf8(buf)
+        .map(|s| s.trim())
+        .map_err(|_| SystemError::EINVAL)
+}
+
+/// 解析容量，支持`K`/`M`/`G`后缀（大小写均可）
+fn parse_size(s: &str) -> Result<usize, SystemError> {
+    let (num, shift) = match s.as_bytes().last() {
+        Some(b'k' | b'K') => (&s[..s.len() - 1], 10),
+        Some(b'm' | b'M') => (&s[..s.len() - 1], 20),
+        Some(b'g' | b'G') => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    let num: usize = num.parse().map_err(|_| SystemError::EINVAL)?;
+    num.checked_mul(1 << shift).ok_or(SystemError::EINVAL)
+}
+
+#[derive(Debug)]
+pub(super) struct ZramAttrGroup;
+
+impl AttributeGroup for ZramAttrGroup {
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    fn attrs(&self) -> &[&'static dyn Attribute] {
+        &[
+            &AttrDisksize,
+            &AttrReset,
+            &AttrInitstate,
+            &AttrCompAlgorithm,
+            &AttrMmStat,
+            &AttrIoStat,
+        ]
+    }
+
+    fn is_visible(
+        &self,
+        _kobj: Arc<dyn KObject>,
+        attr: &'static dyn Attribute,
+    ) -> Option<InodeMode> {
+        Some(attr.mode())
+    }
+}
+
+#[derive(Debug)]
+struct AttrDisksize;
+
+impl Attribute for AttrDisksize {
+    fn name(&self) -> &str {
+        "disksize"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RW
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW | SysFSOpsSupport::ATTR_STORE
+    }
+
+    fn show(&self, kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let zram = kobj_zram(&kobj)?;
+        sysfs_emit_str(buf, &format!("{}\n", zram.disksize()))
+    }
+
+    fn store(&self, kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let size = parse_size(parse_str(buf)?)?;
+        kobj_zram(&kobj)?.set_disksize(size)?;
+        Ok(buf.len())
+    }
+}
+
+#[derive(Debug)]
+struct AttrReset;
+
+impl Attribute for AttrReset {
+    fn name(&self) -> &str {
+        "reset"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_WO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_STORE
+    }
+
+    fn store(&self, kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let v: u64 = parse_str(buf)?.parse().map_err(|_| SystemError::EINVAL)?;
+        if v == 0 {
+            return Err(SystemError::EINVAL);
+        }
+        kobj_zram(&kobj)?.reset()?;
+        Ok(buf.len())
+    }
+}
+
+#[derive(Debug)]
+struct AttrInitstate;
+
+impl Attribute for AttrInitstate {
+    fn name(&self) -> &str {
+        "initstate"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    fn show(&self, kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let initialized = kobj_zram(&kobj)?.disksize() != 0;
+        sysfs_emit_str(buf, if initialized { "1\n" } else { "0\n" })
+    }
+}
+
+#[derive(Debug)]
+struct AttrCompAlgorithm;
+
+impl Attribute for AttrCompAlgorithm {
+    fn name(&self) -> &str {
+        "comp_algorithm"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        sysfs_emit_str(buf, "[lz4]\n")
+    }
+}
+
+#[derive(Debug)]
+struct AttrMmStat;
+
+impl Attribute for AttrMmStat {
+    fn name(&self) -> &str {
+        "mm_stat"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    /// orig_data_size compr_data_size mem_used_total mem_limit mem_used_max
+    /// same_pages pages_compacted huge_pages huge_pages_since
+    fn show(&self, kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let s = kobj_zram(&kobj)?.mm_stat();
+        sysfs_emit_str(
+            buf,
+            &format!(
+                "{:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}\n",
+                s.pages_stored * MMArch::PAGE_SIZE,
+                s.compr_data_size,
+                s.mem_used_pages * MMArch::PAGE_SIZE,
+                0,
+                s.mem_used_max_pages * MMArch::PAGE_SIZE,
+                s.same_pages,
+                0,
+                s.huge_pages,
+                s.huge_pages_since
+            ),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct AttrIoStat;
+
+impl Attribute for AttrIoStat {
+    fn name(&self) -> &str {
+        "io_stat"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    /// failed_reads failed_writes invalid_io notify_free
+    fn show(&self, kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let zram = kobj_zram(&kobj)?;
+        let s = zram.io_stat();
+        sysfs_emit_str(
+            buf,
+            &format!(
+                "{:>8} {:>8} {:>8} {:>8}\n",
+                s.failed_reads.load(Ordering::Relaxed),
+                s.failed_writes.load(Ordering::Relaxed),
+                s.invalid_io.load(Ordering::Relaxed),
+                s.notify_free.load(Ordering::Relaxed)
+            ),
+        )
+    }
+}
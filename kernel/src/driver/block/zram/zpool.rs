@@ -0,0 +1,202 @@
+//! 保存压缩页的内存池
+//!
+//! 按对象大小把内存页划分成若干大小类别（size class），每个类别的页被切分成固定大小的槽位，
+//! 压缩后的数据放在不小于它的最小类别的一个槽位中。对象不跨页，所以一个类别的槽位大小
+//! 不能整除页大小时，页尾的空间会被浪费。
+//!
+//! 页在类别中第一次需要槽位时分配，页上的所有对象都释放后立即归还给伙伴系统。
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    mm::{
+        allocator::page_frame::{
+            allocate_page_frames, deallocate_page_frames, PageFrameCount, PhysPageFrame,
+        },
+        MemoryManagementArch, PhysAddr,
+    },
+};
+
+/// 大小类别的粒度（字节）
+const ZPOOL_CLASS_DELTA: usize = 64;
+/// 大小类别的数量，最大的类别的槽位大小为一页
+const ZPOOL_NR_CLASSES: usize = MMArch::PAGE_SIZE / ZPOOL_CLASS_DELTA;
+
+/// 池中一个对象的句柄
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZHandle {
+    class: u16,
+    page: u32,
+    slot: u16,
+}
+
+/// 池中的一个物理页
+struct ZPage {
+    paddr: PhysAddr,
+    /// 已使用的槽位，每个类别的槽位数量不超过`ZPOOL_NR_CLASSES`
+    used: u64,
+}
+
+impl ZPage {
+    fn new() -> Result<Self, SystemError> {
+        let (paddr, _) =
+            unsafe { allocate_page_frames(PageFrameCount::ONE) }.ok_or(SystemError::ENOMEM)?;
+        Ok(Self { paddr, used: 0 })
+    }
+
+    fn slot(&self, size: usize, slot: u16) -> *mut u8 {
+        let vaddr = unsafe { MMArch::phys_2_virt(self.paddr) }.unwrap();
+        (vaddr.data() + slot as usize * size) as *mut u8
+    }
+}
+
+impl Drop for ZPage {
+    fn drop(&mut self) {
+        unsafe { deallocate_page_frames(PhysPageFrame::new(self.paddr), PageFrameCount::ONE) };
+    }
+}
+
+/// 一个大小类别
+struct SizeClass {
+    /// 槽位大小
+    size: usize,
+    /// 每页的槽位数量
+    slots: usize,
+    pages: BTreeMap<u32, ZPage>,
+    /// 还有空闲槽位的页
+    partial: BTreeSet<u32>,
+    next_page_id: u32,
+}
+
+impl SizeClass {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            slots: MMArch::PAGE_SIZE / size,
+            pages: BTreeMap::new(),
+            partial: BTreeSet::new(),
+            next_page_id: 0,
+        }
+    }
+
+    fn full_mask(&self) -> u64 {
+        if self.slots >= 64 {
+            u64::MAX
+        } else {
+            (1u64 << self.slots) - 1
+        }
+    }
+
+    /// 分配一个槽位，返回(页号, 槽位号)
+    fn alloc(&mut self) -> Result<(u32, u16), SystemError> {
+        let page_id = match self.partial.first() {
+            Some(id) => *id,
+            None => {
+                let id = self.next_page_id;
+                self.next_page_id = self.next_page_id.wrapping_add(1);
+                self.pages.insert(id, ZPage::new()?);
+                self.partial.insert(id);
+                id
+            }
+        };
+        let full = self.full_mask();
+        let page = self.pages.get_mut(&page_id).unwrap();
+        let slot = (!page.used).trailing_zeros() as u16;
+        page.used |= 1 << slot;
+        if page.used == full {
+            self.partial.remove(&page_id);
+        }
+        Ok((page_id, slot))
+    }
+
+    /// 释放槽位，返回是否释放了页
+    fn free(&mut self, page_id: u32, slot: u16) -> bool {
+        let Some(page) = self.pages.get_mut(&page_id) else {
+            return false;
+        };
+        page.used &= !(1 << slot);
+        if page.used == 0 {
+            self.pages.remove(&page_id);
+            self.partial.remove(&page_id);
+            true
+        } else {
+            self.partial.insert(page_id);
+            false
+        }
+    }
+}
+
+/// 压缩页内存池
+pub struct ZPool {
+    classes: Vec<SizeClass>,
+    /// 池占用的物理页数量
+    pages: usize,
+}
+
+impl ZPool {
+    pub fn new() -> Self {
+        Self {
+            classes: (1..=ZPOOL_NR_CLASSES)
+                .map(|i| SizeClass::new(i * ZPOOL_CLASS_DELTA))
+                .collect(),
+            pages: 0,
+        }
+    }
+
+    /// # 分配一个对象
+    ///
+    /// ## 参数
+    ///
+    /// - `len`: 对象的长度，不能超过一页
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(handle)`: 对象的句柄，对象的内容未初始化
+    /// - `Err(SystemError::ENOMEM)`: 内存不足
+    pub fn alloc(&mut self, len: usize) -> Result<ZHandle, SystemError> {
+        if len == 0 || len > MMArch::PAGE_SIZE {
+            return Err(SystemError::EINVAL);
+        }
+        let class = len.div_ceil(ZPOOL_CLASS_DELTA) - 1;
+        let c = &mut self.classes[class];
+        let nr_pages = c.pages.len();
+        let (page, slot) = c.alloc()?;
+        self.pages += c.pages.len() - nr_pages;
+        Ok(ZHandle {
+            class: class as u16,
+            page,
+            slot,
+        })
+    }
+
+    /// 释放对象
+    pub fn free(&mut self, handle: ZHandle) {
+        if self.classes[handle.class as usize].free(handle.page, handle.slot) {
+            self.pages -= 1;
+        }
+    }
+
+    /// 对象所在的槽位，长度为所在类别的槽位大小
+    pub fn object(&self, handle: ZHandle) -> &[u8] {
+        let c = &self.classes[handle.class as usize];
+        let page = &c.pages[&handle.page];
+        unsafe { core::slice::from_raw_parts(page.slot(c.size, handle.slot), c.size) }
+    }
+
+    /// 对象所在的槽位，长度为所在类别的槽位大小
+    pub fn object_mut(&mut self, handle: ZHandle) -> &mut [u8] {
+        let c = &self.classes[handle.class as usize];
+        let page = &c.pages[&handle.page];
+        unsafe { core::slice::from_raw_parts_mut(page.slot(c.size, handle.slot), c.size) }
+    }
+
+    /// 池占用的物理页数量
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+}
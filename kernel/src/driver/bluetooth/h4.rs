@@ -0,0 +1,74 @@
+//! H4协议：每个HCI包前加一个字节的包类型，直接在串口上传输
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/bluetooth/hci_h4.c
+
+use alloc::vec::Vec;
+
+use crate::net::bluetooth::hci::{hci_frame_len, HCI_MAX_FRAME_SIZE};
+
+/// H4的接收状态机，把串口上收到的字节流重组为完整的HCI包
+#[derive(Debug, Default)]
+pub struct H4Recv {
+    /// 正在接收的包的类型，为`None`时下一个字节是包类型
+    pkt_type: Option<u8>,
+    buf: Vec<u8>,
+}
+
+impl H4Recv {
+    pub const fn new() -> Self {
+        Self {
+            pkt_type: None,
+            buf: Vec::new(),
+        }
+    }
+
+    /// 丢弃正在接收的包
+    pub fn reset(&mut self) {
+        self.pkt_type = None;
+        self.buf.clear();
+    }
+
+    /// # 处理收到的字节
+    ///
+    /// ## 参数
+    /// - `data`: 串口上收到的数据
+    /// - `f`: 每收到一个完整的包时调用，参数为包的类型和内容
+    pub fn recv<F>(&mut self, data: &[u8], mut f: F)
+    where
+        F: FnMut(u8, &[u8]),
+    {
+        for &byte in data {
+            let Some(pkt_type) = self.pkt_type else {
+                if hci_frame_len(byte, &[]).is_err() {
+                    log::warn!("hci_uart: unknown packet type {:#x}", byte);
+                    continue;
+                }
+                self.pkt_type = Some(byte);
+                continue;
+            };
+
+            self.buf.push(byte);
+            // 包类型已经检查过，这里不会出错
+            let Ok(Some(len)) = hci_frame_len(pkt_type, &self.buf) else {
+                continue;
+            };
+            if len > HCI_MAX_FRAME_SIZE {
+                log::warn!("hci_uart: frame too long ({} bytes), dropped", len);
+                self.reset();
+                continue;
+            }
+            if self.buf.len() == len {
+                f(pkt_type, &self.buf);
+                self.reset();
+            }
+        }
+    }
+}
+
+/// 把一个HCI包编码为H4格式
+pub fn h4_encode(pkt_type: u8, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(data.len() + 1);
+    frame.push(pkt_type);
+    frame.extend_from_slice(data);
+    frame
+}
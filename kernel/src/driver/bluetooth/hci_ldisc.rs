@@ -0,0 +1,265 @@
+//! 蓝牙HCI UART线路规程（N_HCI）
+//!
+//! 用法与Linux相同（比如`hciattach`/`btattach`）：
+//! 1. 打开串口并配置波特率；
+//! 2. `ioctl(fd, TIOCSETD, &N_HCI)`；
+//! 3. `ioctl(fd, HCIUARTSETPROTO, HCI_UART_H4)`，之后控制器注册为`hciN`；
+//! 4. 保持串口打开，关闭串口或者切换线路规程时注销控制器。
+//!
+//! 目前只支持H4协议。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/bluetooth/hci_ldisc.c
+
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    driver::tty::{
+        termios::Termios,
+        tty_core::TtyCore,
+        tty_ldisc::{LineDisciplineType, TtyLineDiscipline},
+    },
+    filesystem::vfs::{
+        file::FileFlags,
+        ioctl::{ior, iow},
+    },
+    libs::spinlock::SpinLock,
+    net::bluetooth::{
+        hci::HciBus,
+        hci_core::{hci_register_dev, hci_unregister_dev, HciDev, HciTransport},
+    },
+};
+
+use super::h4::{h4_encode, H4Recv};
+
+pub const HCIUARTSETPROTO: u32 = iow::<i32>(b'U', 200);
+pub const HCIUARTGETPROTO: u32 = ior::<i32>(b'U', 201);
+pub const HCIUARTGETDEVICE: u32 = ior::<i32>(b'U', 202);
+pub const HCIUARTSETFLAGS: u32 = iow::<i32>(b'U', 203);
+pub const HCIUARTGETFLAGS: u32 = ior::<i32>(b'U', 204);
+
+/// H4协议的编号
+pub const HCI_UART_H4: i32 = 0;
+
+/// 通过串口收发HCI包的传输层
+#[derive(Debug)]
+struct HciUartTransport {
+    tty: Weak<TtyCore>,
+}
+
+impl HciTransport for HciUartTransport {
+    fn open(&self) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn close(&self) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn send(&self, pkt_type: u8, data: &[u8]) -> Result<(), SystemError> {
+        let tty = self.tty.upgrade().ok_or(SystemError::ENODEV)?;
+        let frame = h4_encode(pkt_type, data);
+        let mut written = 0;
+        while written < frame.len() {
+            let n = tty.write_to_core(&frame[written..], frame.len() - written)?;
+            if n == 0 {
+                return Err(SystemError::EIO);
+            }
+            written += n;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct HciUartInner {
+    /// HCIUARTSETPROTO设置的协议，设置之前为`None`
+    proto: Option<i32>,
+    flags: u32,
+    hdev: Option<Arc<HciDev>>,
+    h4: H4Recv,
+}
+
+#[derive(Debug)]
+pub struct HciUartLdisc {
+    inner: SpinLock<HciUartInner>,
+}
+
+impl HciUartLdisc {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(HciUartInner {
+                proto: None,
+                flags: 0,
+                hdev: None,
+                h4: H4Recv::new(),
+            }),
+        }
+    }
+
+    fn set_proto(&self, tty: &Arc<TtyCore>, proto: i32) -> Result<usize, SystemError> {
+        if proto != HCI_UART_H4 {
+            log::warn!("hci_uart: protocol {} is not supported yet", proto);
+            return Err(SystemError::EPROTONOSUPPORT);
+        }
+        let mut inner = self.inner.lock_irqsave();
+        if inner.proto.is_some() {
+            return Err(SystemError::EBUSY);
+        }
+        let transport = Arc::new(HciUartTransport {
+            tty: Arc::downgrade(tty),
+        });
+        let hdev = hci_register_dev(HciBus::Uart, transport)?;
+        inner.proto = Some(proto);
+        inner.hdev = Some(hdev);
+        inner.h4.reset();
+        Ok(0)
+    }
+
+    /// 注销控制器，在关闭串口或者切换线路规程时调用
+    fn detach(&self) {
+        let hdev = {
+            let mut inner = self.inner.lock_irqsave();
+            inner.proto = None;
+            inner.h4.reset();
+            inner.hdev.take()
+        };
+        if let Some(hdev) = hdev {
+            hci_unregister_dev(&hdev);
+        }
+    }
+
+    fn recv(&self, buf: &[u8]) -> usize {
+        let mut frames: Vec<(u8, Vec<u8>)> = Vec::new();
+        let hdev = {
+            let mut inner = self.inner.lock_irqsave();
+            let Some(hdev) = inner.hdev.clone() else {
+                // 还没有设置协议，丢弃收到的数据
+                return buf.len();
+            };
+            inner
+                .h4
+                .recv(buf, |pkt_type, data| frames.push((pkt_type, data.to_vec())));
+            hdev
+        };
+        // 上交数据包时可能会通过串口发送排队的命令，不能持有锁
+        for (pkt_type, data) in frames {
+            hdev.recv_frame(pkt_type, &data);
+        }
+        buf.len()
+    }
+}
+
+impl Default for HciUartLdisc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TtyLineDiscipline for HciUartLdisc {
+    fn open(&self, _tty: Arc<TtyCore>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn close(&self, _tty: Arc<TtyCore>) -> Result<(), SystemError> {
+        self.detach();
+        Ok(())
+    }
+
+    fn flush_buffer(&self, _tty: Arc<TtyCore>) -> Result<(), SystemError> {
+        self.inner.lock_irqsave().h4.reset();
+        Ok(())
+    }
+
+    /// 数据都交给了HCI层，用户态不能直接读写串口
+    fn read(
+        &self,
+        _tty: Arc<TtyCore>,
+        _buf: &mut [u8],
+        _len: usize,
+        _cookie: &mut bool,
+        _offset: usize,
+        _flags: FileFlags,
+    ) -> Result<usize, SystemError> {
+        Ok(0)
+    }
+
+    fn write(
+        &self,
+        _tty: Arc<TtyCore>,
+        _buf: &[u8],
+        _len: usize,
+        _flags: FileFlags,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EINVAL)
+    }
+
+    fn ioctl(&self, tty: Arc<TtyCore>, cmd: u32, arg: usize) -> Result<usize, SystemError> {
+        match cmd {
+            HCIUARTSETPROTO => self.set_proto(&tty, arg as i32),
+            HCIUARTGETPROTO => self
+                .inner
+                .lock_irqsave()
+                .proto
+                .map(|proto| proto as usize)
+                .ok_or(SystemError::EUNATCH),
+            HCIUARTGETDEVICE => self
+                .inner
+                .lock_irqsave()
+                .hdev
+                .as_ref()
+                .map(|hdev| hdev.id() as usize)
+                .ok_or(SystemError::EUNATCH),
+            HCIUARTSETFLAGS => {
+                let mut inner = self.inner.lock_irqsave();
+                if inner.proto.is_some() {
+                    return Err(SystemError::EBUSY);
+                }
+                inner.flags = arg as u32;
+                Ok(0)
+            }
+            HCIUARTGETFLAGS => Ok(self.inner.lock_irqsave().flags as usize),
+            _ => Err(SystemError::ENOIOCTLCMD),
+        }
+    }
+
+    fn set_termios(&self, _tty: Arc<TtyCore>, _old: Option<Termios>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn poll(&self, _tty: Arc<TtyCore>) -> Result<usize, SystemError> {
+        Ok(0)
+    }
+
+    fn hangup(&self, _tty: Arc<TtyCore>) -> Result<(), SystemError> {
+        self.detach();
+        Ok(())
+    }
+
+    fn receive_buf(
+        &self,
+        _tty: Arc<TtyCore>,
+        buf: &[u8],
+        _flags: Option<&[u8]>,
+        count: usize,
+    ) -> Result<usize, SystemError> {
+        Ok(self.recv(&buf[..count.min(buf.len())]))
+    }
+
+    fn receive_buf2(
+        &self,
+        tty: Arc<TtyCore>,
+        buf: &[u8],
+        flags: Option<&[u8]>,
+        count: usize,
+    ) -> Result<usize, SystemError> {
+        self.receive_buf(tty, buf, flags, count)
+    }
+
+    fn disc_type(&self) -> LineDisciplineType {
+        LineDisciplineType::Hci
+    }
+}
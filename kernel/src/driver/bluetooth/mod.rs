@@ -0,0 +1,8 @@
+//! 蓝牙控制器的传输层驱动
+//!
+//! 目前只有UART（H4协议）传输层：用户态通过`TIOCSETD`把串口的线路规程设置为`N_HCI`，
+//! 再用`HCIUARTSETPROTO`选择协议后，串口上的控制器就会注册为`hciN`。
+//! 内核还没有USB主机控制器驱动，所以暂时没有USB（btusb）传输层。
+
+pub mod h4;
+pub mod hci_ldisc;
@@ -209,9 +209,9 @@ const ACPI_PM_READ_CHECKS: u32 = 10000;
 fn find_acpi_pm_clock() -> Result<(), SystemError> {
     let fadt = acpi_manager()
         .tables()
-        .unwrap()
+        .ok_or(SystemError::ENODEV)?
         .find_table::<Fadt>()
-        .expect("failed to find FADT table");
+        .map_err(|_| SystemError::ENODEV)?;
     let pm_timer_block = fadt.pm_timer_block().map_err(|_| SystemError::ENODEV)?;
     let pm_timer_block = pm_timer_block.ok_or(SystemError::ENODEV)?;
     let pmtmr_addr = pm_timer_block.address;
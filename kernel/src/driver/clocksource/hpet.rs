@@ -0,0 +1,124 @@
+use alloc::{
+    string::ToString,
+    sync::{Arc, Weak},
+};
+use log::{info, warn};
+use system_error::SystemError;
+
+use crate::{
+    arch::driver::hpet::{hpet_instance, is_hpet_enabled},
+    libs::spinlock::SpinLock,
+    time::clocksource::{
+        Clocksource, ClocksourceData, ClocksourceFlags, ClocksourceMask, CycleNum,
+    },
+};
+
+// 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/kernel/hpet.c#1117
+
+pub static mut CLOCKSOURCE_HPET: Option<Arc<HpetClocksource>> = None;
+
+#[allow(dead_code)]
+pub fn clocksource_hpet() -> Arc<HpetClocksource> {
+    unsafe { CLOCKSOURCE_HPET.as_ref().unwrap().clone() }
+}
+
+/// HPET主计数器作为时钟源
+///
+/// HPET的精度不如TSC，但是频率恒定，可以用来监视TSC是否稳定
+#[derive(Debug)]
+pub struct HpetClocksource(SpinLock<InnerHpetClocksource>);
+
+#[derive(Debug)]
+struct InnerHpetClocksource {
+    data: ClocksourceData,
+    self_ref: Weak<HpetClocksource>,
+}
+
+impl HpetClocksource {
+    pub fn new(mask: u64) -> Arc<Self> {
+        let data = ClocksourceData {
+            name: "hpet".to_string(),
+            rating: 250,
+            mask: ClocksourceMask::new(mask),
+            mult: 0,
+            shift: 0,
+            max_idle_ns: Default::default(),
+            flags: ClocksourceFlags::CLOCK_SOURCE_IS_CONTINUOUS,
+            watchdog_last: CycleNum::new(0),
+            cs_last: CycleNum::new(0),
+            uncertainty_margin: 0,
+            maxadj: 0,
+            cycle_last: CycleNum::new(0),
+        };
+        let hpet = Arc::new(HpetClocksource(SpinLock::new(InnerHpetClocksource {
+            data,
+            self_ref: Default::default(),
+        })));
+        hpet.0.lock().self_ref = Arc::downgrade(&hpet);
+
+        hpet
+    }
+}
+
+impl Clocksource for HpetClocksource {
+    fn read(&self) -> CycleNum {
+        CycleNum::new(hpet_instance().main_counter_value())
+    }
+
+    fn clocksource_data(&self) -> ClocksourceData {
+        let inner = self.0.lock_irqsave();
+        inner.data.clone()
+    }
+
+    fn clocksource(&self) -> Arc<dyn Clocksource> {
+        self.0.lock_irqsave().self_ref.upgrade().unwrap()
+    }
+
+    fn update_clocksource_data(&self, data: ClocksourceData) -> Result<(), SystemError> {
+        let d = &mut self.0.lock_irqsave().data;
+        d.set_name(data.name);
+        d.set_rating(data.rating);
+        d.set_mask(data.mask);
+        d.set_mult(data.mult);
+        d.set_shift(data.shift);
+        d.set_max_idle_ns(data.max_idle_ns);
+        d.set_flags(data.flags);
+        d.watchdog_last = data.watchdog_last;
+        d.cs_last = data.cs_last;
+        d.set_uncertainty_margin(data.uncertainty_margin);
+        d.set_maxadj(data.maxadj);
+        d.cycle_last = data.cycle_last;
+        Ok(())
+    }
+}
+
+/// 把HPET主计数器注册为时钟源
+///
+/// 必须在HPET使能之后调用
+pub fn init_hpet_clocksource() -> Result<(), SystemError> {
+    if !is_hpet_enabled() {
+        return Err(SystemError::ENODEV);
+    }
+
+    let freq = hpet_instance().frequency();
+    if freq == 0 || freq > u32::MAX as u64 {
+        warn!("HPET clocksource registration skipped: invalid frequency {freq}");
+        return Err(SystemError::EINVAL);
+    }
+
+    let mask = if hpet_instance().counter_is_64bit() {
+        u64::MAX
+    } else {
+        u32::MAX as u64
+    };
+    let hpet = HpetClocksource::new(mask);
+    unsafe {
+        CLOCKSOURCE_HPET = Some(hpet.clone());
+    }
+
+    let hpet_cs = hpet as Arc<dyn Clocksource>;
+    hpet_cs.register(1, freq as u32)?;
+    info!("HPET registered as clocksource, freq: {freq} Hz");
+
+    Ok(())
+}
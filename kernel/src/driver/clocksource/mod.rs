@@ -3,6 +3,8 @@ pub mod timer_riscv;
 
 pub mod acpi_pm;
 #[cfg(target_arch = "x86_64")]
+pub mod hpet;
+#[cfg(target_arch = "x86_64")]
 pub mod kvm_clock;
 #[cfg(target_arch = "x86_64")]
 pub mod tsc;
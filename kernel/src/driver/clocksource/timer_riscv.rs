@@ -1,7 +1,11 @@
 use core::sync::atomic::{compiler_fence, fence, Ordering};
 
-use alloc::{string::ToString, sync::Arc};
+use alloc::{
+    string::ToString,
+    sync::{Arc, Weak},
+};
 use bitmap::{static_bitmap, traits::BitMapOps, StaticBitmap};
+use log::{info, warn};
 use system_error::SystemError;
 
 use crate::{
@@ -22,7 +26,11 @@ use crate::{
     mm::percpu::PerCpu,
     smp::core::smp_get_processor_id,
     time::{
-        clocksource::HZ, tick_common::tick_handle_periodic, timer::try_raise_timer_softirq,
+        clockevents::{
+            clockevents_handle_event, clockevents_register_device, ClockEventDevice,
+            ClockEventFeatures,
+        },
+        clocksource::{Clocksource, ClocksourceData, ClocksourceFlags, ClocksourceMask, CycleNum},
         TimeArch,
     },
 };
@@ -32,8 +40,6 @@ pub struct RiscVSbiTimer;
 static SBI_TIMER_INIT_BMP: SpinLock<static_bitmap!(PerCpu::MAX_CPU_NUM as usize)> =
     SpinLock::new(StaticBitmap::new());
 
-static mut INTERVAL_CNT: usize = 0;
-
 impl RiscVSbiTimer {
     pub const TIMER_IRQ: HardwareIrqNumber = HardwareIrqNumber::new(5);
 
@@ -44,9 +50,8 @@ impl RiscVSbiTimer {
         //     smp_get_processor_id().data(),
         //     CurrentTimeArch::get_cycles() as u64
         // );
-        tick_handle_periodic(trap_frame);
         compiler_fence(Ordering::SeqCst);
-        sbi_rt::set_timer(CurrentTimeArch::get_cycles() as u64 + unsafe { INTERVAL_CNT } as u64);
+        clockevents_handle_event(trap_frame);
         Ok(())
     }
 
@@ -54,7 +59,6 @@ impl RiscVSbiTimer {
         unsafe { riscv::register::sie::set_stimer() };
     }
 
-    #[allow(dead_code)]
     fn disable() {
         unsafe { riscv::register::sie::clear_stimer() };
     }
@@ -65,14 +69,8 @@ impl RiscVSbiTimer {
 pub fn riscv_sbi_timer_init_local() {
     assert_eq!(CurrentIrqArch::is_irq_enabled(), false);
 
-    if unsafe { INTERVAL_CNT } == 0 {
-        let new = riscv_time_base_freq() / HZ as usize;
-        if new == 0 {
-            panic!("riscv_sbi_timer_init: failed to get timebase-frequency");
-        }
-        unsafe {
-            INTERVAL_CNT = new;
-        }
+    if riscv_time_base_freq() == 0 {
+        panic!("riscv_sbi_timer_init: failed to get timebase-frequency");
     }
 
     let mut guard = SBI_TIMER_INIT_BMP.lock();
@@ -94,15 +92,145 @@ pub fn riscv_sbi_timer_init_local() {
         )
         .expect("Apic timer init failed");
 
-    // 设置第一次中断
-    sbi_rt::set_timer(CurrentTimeArch::get_cycles() as u64);
-
-    RiscVSbiTimer::enable();
+    clockevents_register_device(Arc::new(RiscvSbiClockEvent))
+        .expect("riscv_sbi_timer_init: failed to register clock event device");
     guard
         .set(smp_get_processor_id().data() as usize, true)
         .unwrap();
 }
 
+/// SBI定时器作为时钟事件设备，只支持单次触发
+///
+/// 每个hart都有自己的定时器，SBI调用总是作用于当前hart，所以所有hart可以共用同一个类型
+#[derive(Debug)]
+struct RiscvSbiClockEvent;
+
+impl ClockEventDevice for RiscvSbiClockEvent {
+    fn name(&self) -> &str {
+        "riscv_sbi_timer"
+    }
+
+    fn rating(&self) -> u32 {
+        100
+    }
+
+    fn features(&self) -> ClockEventFeatures {
+        ClockEventFeatures::ONESHOT
+    }
+
+    fn freq(&self) -> u64 {
+        riscv_time_base_freq() as u64
+    }
+
+    fn max_delta_ticks(&self) -> u64 {
+        i64::MAX as u64
+    }
+
+    fn set_state_oneshot(&self) -> Result<(), SystemError> {
+        RiscVSbiTimer::enable();
+        Ok(())
+    }
+
+    fn set_state_shutdown(&self) -> Result<(), SystemError> {
+        sbi_rt::set_timer(u64::MAX);
+        RiscVSbiTimer::disable();
+        Ok(())
+    }
+
+    fn set_next_event(&self, delta: u64) -> Result<(), SystemError> {
+        let r = sbi_rt::set_timer(CurrentTimeArch::get_cycles() as u64 + delta);
+        if r.is_err() {
+            return Err(SystemError::EIO);
+        }
+        Ok(())
+    }
+}
+
+/// time寄存器作为时钟源
+#[derive(Debug)]
+pub struct RiscvTimeClocksource(SpinLock<InnerRiscvTimeClocksource>);
+
+#[derive(Debug)]
+struct InnerRiscvTimeClocksource {
+    data: ClocksourceData,
+    self_ref: Weak<RiscvTimeClocksource>,
+}
+
+impl RiscvTimeClocksource {
+    pub fn new() -> Arc<Self> {
+        let data = ClocksourceData {
+            name: "riscv_clocksource".to_string(),
+            rating: 300,
+            mask: ClocksourceMask::new(u64::MAX),
+            mult: 0,
+            shift: 0,
+            max_idle_ns: Default::default(),
+            flags: ClocksourceFlags::CLOCK_SOURCE_IS_CONTINUOUS,
+            watchdog_last: CycleNum::new(0),
+            cs_last: CycleNum::new(0),
+            uncertainty_margin: 0,
+            maxadj: 0,
+            cycle_last: CycleNum::new(0),
+        };
+        let cs = Arc::new(RiscvTimeClocksource(SpinLock::new(
+            InnerRiscvTimeClocksource {
+                data,
+                self_ref: Default::default(),
+            },
+        )));
+        cs.0.lock().self_ref = Arc::downgrade(&cs);
+
+        cs
+    }
+}
+
+impl Clocksource for RiscvTimeClocksource {
+    fn read(&self) -> CycleNum {
+        CycleNum::new(CurrentTimeArch::get_cycles() as u64)
+    }
+
+    fn clocksource_data(&self) -> ClocksourceData {
+        let inner = self.0.lock_irqsave();
+        inner.data.clone()
+    }
+
+    fn clocksource(&self) -> Arc<dyn Clocksource> {
+        self.0.lock_irqsave().self_ref.upgrade().unwrap()
+    }
+
+    fn update_clocksource_data(&self, data: ClocksourceData) -> Result<(), SystemError> {
+        let d = &mut self.0.lock_irqsave().data;
+        d.set_name(data.name);
+        d.set_rating(data.rating);
+        d.set_mask(data.mask);
+        d.set_mult(data.mult);
+        d.set_shift(data.shift);
+        d.set_max_idle_ns(data.max_idle_ns);
+        d.set_flags(data.flags);
+        d.watchdog_last = data.watchdog_last;
+        d.cs_last = data.cs_last;
+        d.set_uncertainty_margin(data.uncertainty_margin);
+        d.set_maxadj(data.maxadj);
+        d.cycle_last = data.cycle_last;
+        Ok(())
+    }
+}
+
+/// 把time寄存器注册为时钟源
+pub fn init_riscv_time_clocksource() -> Result<(), SystemError> {
+    let freq = riscv_time_base_freq();
+    if freq == 0 || freq > u32::MAX as usize {
+        warn!("riscv time clocksource registration skipped: invalid frequency {freq}");
+        return Err(SystemError::EINVAL);
+    }
+
+    let cs = RiscvTimeClocksource::new() as Arc<dyn Clocksource>;
+    cs.register(1, freq as u32)?;
+    info!("riscv time registered as clocksource, freq: {freq} Hz");
+
+    Ok(())
+}
+
 #[inline(never)]
 pub fn riscv_sbi_timer_irq_desc_init() {
     let virq = riscv_intc_assicate_irq(RiscVSbiTimer::TIMER_IRQ).unwrap();
@@ -0,0 +1,840 @@
+//! AHCI上的ATAPI光驱（/dev/srN）
+//!
+//! 通过ATA PACKET命令向光驱发送SCSI命令块（CDB）。对块设备层按照512字节扇区导出，
+//! 读取时再转换为光盘的2048字节数据块。光驱是只读的。
+//!
+//! 光驱没有介质变化中断，这里通过定时发送TEST UNIT READY检测光盘的插入与弹出，
+//! 变化时发送带有`DISK_MEDIA_CHANGE=1`的change uevent。
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    fmt::Debug,
+    mem::size_of,
+    ptr::write_bytes,
+    sync::atomic::{compiler_fence, AtomicUsize, Ordering},
+};
+use log::{error, info, warn};
+use system_error::SystemError;
+
+use super::{
+    _port,
+    hba::{
+        FisRegH2D, FisType, HbaCmdHeader, HbaCmdTable, ATA_CMD_PACKET, ATA_DEV_BUSY, ATA_DEV_DRQ,
+        HBA_CMD_HEADER_ATAPI,
+    },
+    HBA_PxIS_TFES,
+};
+use crate::{
+    arch::MMArch,
+    driver::base::{
+        block::{
+            block_device::{BlockDevice, BlockId, GeneralBlockRange, LBA_SIZE},
+            disk_info::Partition,
+            manager::{block_dev_manager, BlockDevMeta},
+        },
+        class::Class,
+        device::{
+            bus::Bus, device_number::Major, driver::Driver, DevName, Device, DeviceCommonData,
+            DeviceType, IdTable,
+        },
+        kobject::{KObjType, KObject, KObjectCommonData, KObjectState, LockedKObjectState},
+        kset::KSet,
+    },
+    exception::workqueue::{schedule_work, Work},
+    filesystem::{kernfs::KernFSInode, vfs::ioctl::IoctlArg},
+    libs::{
+        rwsem::{RwSemReadGuard, RwSemWriteGuard},
+        spinlock::{SpinLock, SpinLockGuard},
+    },
+    mm::{MemoryManagementArch, PhysAddr, VirtAddr},
+    time::timer::{next_n_ms_timer_jiffies, Timer, TimerFunction},
+};
+
+const SR_BASENAME: &str = "sr";
+
+/// 光盘数据块的大小
+const CD_FRAME_SIZE: usize = 2048;
+const SECTORS_PER_FRAME: usize = CD_FRAME_SIZE / LBA_SIZE;
+/// ATAPI命令块的长度
+const ATAPI_CDB_LEN: usize = 12;
+/// 每个PRDT项传输的字节数，与AhciDisk一致
+const ATAPI_PRDT_BYTES: usize = 8 * 1024;
+/// 一条命令最多传输的字节数（命令表中只预留了8个PRDT项）
+const ATAPI_MAX_TRANSFER: usize = ATAPI_PRDT_BYTES * 8;
+/// 检查光盘变化的间隔
+const MEDIA_POLL_MS: u64 = 2000;
+/// 等待端口空闲的最大轮询次数
+const SPIN_LIMIT: u32 = 10000;
+
+/// SCSI命令
+const SCSI_TEST_UNIT_READY: u8 = 0x00;
+const SCSI_REQUEST_SENSE: u8 = 0x03;
+const SCSI_READ_CAPACITY_10: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+const SCSI_READ_TOC: u8 = 0x43;
+
+/// Sense key
+const SENSE_NOT_READY: u8 = 0x02;
+const SENSE_UNIT_ATTENTION: u8 = 0x06;
+/// REQUEST SENSE返回的数据长度（固定格式）
+const SENSE_DATA_LEN: usize = 18;
+
+/// TOC中的导出区（lead-out）的轨道号
+const CDROM_LEADOUT: u8 = 0xAA;
+/// TOC头（4字节）之后最多100个轨道描述符（每个8字节）
+const TOC_MAX_LEN: usize = 4 + 100 * 8;
+
+/// 下一个光驱的编号
+static SR_NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// PACKET命令的错误
+#[derive(Debug, Clone, Copy)]
+enum PacketError {
+    /// 设备返回了CHECK CONDITION，参数为sense key
+    CheckCondition(u8),
+    /// 端口没有空闲的命令槽或者一直处于忙状态
+    Port,
+}
+
+impl From<PacketError> for SystemError {
+    fn from(e: PacketError) -> Self {
+        match e {
+            PacketError::CheckCondition(SENSE_NOT_READY) => SystemError::ENOMEDIUM,
+            _ => SystemError::EIO,
+        }
+    }
+}
+
+/// REQUEST SENSE的结果
+#[derive(Debug, Clone, Copy)]
+struct SenseData {
+    key: u8,
+    asc: u8,
+    ascq: u8,
+}
+
+/// TOC中的一个轨道
+#[derive(Debug, Clone, Copy)]
+pub struct CdromTrack {
+    pub track: u8,
+    /// 低4位为control，高4位为adr
+    pub adr_ctrl: u8,
+    /// 轨道的起始位置（2048字节块）
+    pub lba: u32,
+}
+
+/// 光盘的信息
+#[derive(Debug, Clone, Copy)]
+struct CdromMedia {
+    /// 光盘容量（2048字节块）
+    frames: usize,
+}
+
+/// 光驱的命令状态，访问端口时需要持有锁
+struct AhciCdrom {
+    ctrl_num: u8,
+    port_num: u8,
+    media: Option<CdromMedia>,
+}
+
+impl Debug for AhciCdrom {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AhciCdrom")
+            .field("ctrl_num", &self.ctrl_num)
+            .field("port_num", &self.port_num)
+            .field("media", &self.media)
+            .finish()
+    }
+}
+
+impl AhciCdrom {
+    /// ## 发送一条PACKET命令
+    ///
+    /// ## 参数
+    ///
+    /// - `cdb`: SCSI命令块
+    /// - `buf`: 从设备读取的数据，为空时表示没有数据阶段
+    fn packet(&self, cdb: &[u8; ATAPI_CDB_LEN], buf: &mut [u8]) -> Result<(), PacketError> {
+        let len = buf.len();
+        assert!(len <= ATAPI_MAX_TRANSFER && len % 2 == 0);
+        compiler_fence(Ordering::SeqCst);
+
+        let port = _port(self.ctrl_num, self.port_num);
+        volatile_write!(port.is, u32::MAX); // Clear pending interrupt bits
+
+        let slot = port.find_cmdslot().ok_or(PacketError::Port)?;
+
+        #[allow(unused_unsafe)]
+        let cmdheader: &mut HbaCmdHeader = unsafe {
+            (MMArch::phys_2_virt(PhysAddr::new(
+                volatile_read!(port.clb) as usize + slot as usize * size_of::<HbaCmdHeader>(),
+            ))
+            .unwrap()
+            .data() as *mut HbaCmdHeader)
+                .as_mut()
+                .unwrap()
+        };
+
+        // Command FIS size, ATAPI, Read from device
+        cmdheader.cfl = (size_of::<FisRegH2D>() / size_of::<u32>()) as u8 | HBA_CMD_HEADER_ATAPI;
+        let prdtl = len.div_ceil(ATAPI_PRDT_BYTES);
+        volatile_write!(cmdheader.prdtl, prdtl as u16);
+
+        // 用户空间的地址无法直接交给HBA，统一使用内核缓冲区
+        let mut kbuf: Vec<u8> = vec![0; len];
+
+        #[allow(unused_unsafe)]
+        let cmdtbl = unsafe {
+            (MMArch::phys_2_virt(PhysAddr::new(volatile_read!(cmdheader.ctba) as usize))
+                .unwrap()
+                .data() as *mut HbaCmdTable)
+                .as_mut()
+                .unwrap()
+        };
+        unsafe {
+            // 清空整个table的旧数据
+            write_bytes(cmdtbl, 0, 1);
+        }
+
+        for i in 0..prdtl {
+            let offset = i * ATAPI_PRDT_BYTES;
+            let chunk = ATAPI_PRDT_BYTES.min(len - offset);
+            let buf_ptr = kbuf.as_mut_ptr() as usize + offset;
+            volatile_write!(
+                cmdtbl.prdt_entry[i].dba,
+                MMArch::virt_2_phys(VirtAddr::new(buf_ptr)).unwrap().data() as u64
+            );
+            volatile_write!(cmdtbl.prdt_entry[i].dbc, (chunk - 1) as u32 | 1 << 31);
+        }
+
+        cmdtbl.acmd[..ATAPI_CDB_LEN].copy_from_slice(cdb);
+
+        // 设置命令
+        let cmdfis = unsafe {
+            ((&mut cmdtbl.cfis) as *mut [u8] as *mut usize as *mut FisRegH2D)
+                .as_mut()
+                .unwrap()
+        };
+        volatile_write!(cmdfis.fis_type, FisType::RegH2D as u8);
+        volatile_set_bit!(cmdfis.pm, 1 << 7, true); // command_bit set
+        volatile_write!(cmdfis.command, ATA_CMD_PACKET);
+        // 数据阶段使用DMA
+        volatile_write!(cmdfis.featurel, if len > 0 { 1 } else { 0 });
+        // Byte count limit，只在PIO方式下有意义
+        volatile_write!(cmdfis.lba1, (len & 0xFF) as u8);
+        volatile_write!(cmdfis.lba2, ((len >> 8) & 0xFF) as u8);
+
+        // 等待之前的操作完成
+        let mut spin_count = 0;
+        while (volatile_read!(port.tfd) as u8 & (ATA_DEV_BUSY | ATA_DEV_DRQ)) > 0
+            && spin_count < SPIN_LIMIT
+        {
+            spin_count += 1;
+        }
+        if spin_count == SPIN_LIMIT {
+            error!("ATAPI port is hung");
+            return Err(PacketError::Port);
+        }
+
+        volatile_set_bit!(port.ci, 1 << slot, true); // Issue command
+
+        // 等待操作完成
+        loop {
+            if (volatile_read!(port.ci) & (1 << slot)) == 0 {
+                break;
+            }
+            if (volatile_read!(port.is) & HBA_PxIS_TFES) > 0 {
+                // PxTFD的error寄存器的高4位为sense key
+                let sense_key = ((volatile_read!(port.tfd) >> 12) & 0xF) as u8;
+                port.recover();
+                return Err(PacketError::CheckCondition(sense_key));
+            }
+        }
+
+        buf.copy_from_slice(&kbuf);
+        compiler_fence(Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn test_unit_ready(&self) -> Result<(), PacketError> {
+        let mut cdb = [0u8; ATAPI_CDB_LEN];
+        cdb[0] = SCSI_TEST_UNIT_READY;
+        self.packet(&cdb, &mut [])
+    }
+
+    fn request_sense(&self) -> Result<SenseData, PacketError> {
+        let mut cdb = [0u8; ATAPI_CDB_LEN];
+        cdb[0] = SCSI_REQUEST_SENSE;
+        cdb[4] = SENSE_DATA_LEN as u8;
+
+        let mut data = [0u8; SENSE_DATA_LEN];
+        self.packet(&cdb, &mut data)?;
+        Ok(SenseData {
+            key: data[2] & 0xF,
+            asc: data[12],
+            ascq: data[13],
+        })
+    }
+
+    /// 读取光盘的容量，返回2048字节块的数量
+    fn read_capacity(&self) -> Result<usize, SystemError> {
+        let mut cdb = [0u8; ATAPI_CDB_LEN];
+        cdb[0] = SCSI_READ_CAPACITY_10;
+
+        let mut data = [0u8; 8];
+        self.packet(&cdb, &mut data)?;
+        let last_lba = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+        let block_len = u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize;
+        if block_len != CD_FRAME_SIZE {
+            warn!("ATAPI: unsupported medium block size {}", block_len);
+            return Err(SystemError::EMEDIUMTYPE);
+        }
+        Ok(last_lba + 1)
+    }
+
+    /// 使用READ(10)读取从`frame`开始的`count`个2048字节块
+    fn read_frames(&self, frame: usize, count: usize, buf: &mut [u8]) -> Result<(), SystemError> {
+        let mut cdb = [0u8; ATAPI_CDB_LEN];
+        cdb[0] = SCSI_READ_10;
+        cdb[2..6].copy_from_slice(&(frame as u32).to_be_bytes());
+        cdb[7..9].copy_from_slice(&(count as u16).to_be_bytes());
+
+        self.packet(&cdb, &mut buf[..count * CD_FRAME_SIZE])
+            .map_err(|e| {
+                error!("ATAPI read error at frame {}: {:?}", frame, e);
+                SystemError::from(e)
+            })
+    }
+
+    /// 读取光盘的TOC（格式0，地址使用LBA）
+    fn read_toc(&self) -> Result<Vec<CdromTrack>, SystemError> {
+        if self.media.is_none() {
+            return Err(SystemError::ENOMEDIUM);
+        }
+
+        let mut cdb = [0u8; ATAPI_CDB_LEN];
+        cdb[0] = SCSI_READ_TOC;
+        cdb[7..9].copy_from_slice(&(TOC_MAX_LEN as u16).to_be_bytes());
+
+        let mut data = vec![0u8; TOC_MAX_LEN];
+        self.packet(&cdb, &mut data)?;
+
+        // TOC data length不包含自身的2个字节
+        let toc_len = (u16::from_be_bytes([data[0], data[1]]) as usize + 2).min(TOC_MAX_LEN);
+        let tracks = data[4..toc_len]
+            .chunks_exact(8)
+            .map(|desc| CdromTrack {
+                adr_ctrl: desc[1],
+                track: desc[2],
+                lba: u32::from_be_bytes(desc[4..8].try_into().unwrap()),
+            })
+            .collect();
+        Ok(tracks)
+    }
+
+    /// ## 检查光盘是否发生了变化
+    ///
+    /// ## 返回值
+    ///
+    /// 光盘被插入、弹出或者更换时返回true
+    fn check_media(&mut self) -> bool {
+        let mut unit_attention = false;
+        let present = match self.test_unit_ready() {
+            Ok(()) => true,
+            Err(PacketError::CheckCondition(_)) => match self.request_sense() {
+                // 光盘可能被更换了，unit attention被读取之后，设备会恢复正常状态
+                Ok(sense) if sense.key == SENSE_UNIT_ATTENTION => {
+                    unit_attention = true;
+                    self.test_unit_ready().is_ok()
+                }
+                Ok(sense) => {
+                    if sense.key != SENSE_NOT_READY {
+                        warn!(
+                            "ATAPI: unexpected sense {:#x}/{:#x}/{:#x}",
+                            sense.key, sense.asc, sense.ascq
+                        );
+                    }
+                    false
+                }
+                Err(_) => false,
+            },
+            Err(PacketError::Port) => false,
+        };
+
+        let had_media = self.media.is_some();
+        if present && (unit_attention || !had_media) {
+            self.media = self
+                .read_capacity()
+                .ok()
+                .map(|frames| CdromMedia { frames });
+        } else if !present {
+            self.media = None;
+        }
+
+        unit_attention || had_media != self.media.is_some()
+    }
+
+    fn sectors(&self) -> usize {
+        self.media.map_or(0, |m| m.frames * SECTORS_PER_FRAME)
+    }
+
+    /// 以512字节扇区为单位读取
+    fn read_at(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        if self.media.is_none() {
+            return Err(SystemError::ENOMEDIUM);
+        }
+
+        let len = count.checked_mul(LBA_SIZE).ok_or(SystemError::EOVERFLOW)?;
+        if len > buf.len() {
+            return Err(SystemError::EINVAL);
+        }
+        let end_sector = lba_id_start
+            .checked_add(count)
+            .ok_or(SystemError::EOVERFLOW)?;
+        if end_sector > self.sectors() {
+            return Err(SystemError::ENOSPC);
+        }
+
+        let start = lba_id_start * LBA_SIZE;
+        let end = start + len;
+        let max_frames = ATAPI_MAX_TRANSFER / CD_FRAME_SIZE;
+        let mut frame_buf = vec![0u8; ATAPI_MAX_TRANSFER];
+
+        let mut frame = start / CD_FRAME_SIZE;
+        let last_frame = (end - 1) / CD_FRAME_SIZE;
+        while frame <= last_frame {
+            let nframes = (last_frame - frame + 1).min(max_frames);
+            self.read_frames(frame, nframes, &mut frame_buf)?;
+
+            // 只拷贝请求范围内的部分
+            let chunk_start = frame * CD_FRAME_SIZE;
+            let chunk_end = chunk_start + nframes * CD_FRAME_SIZE;
+            let copy_start = start.max(chunk_start);
+            let copy_end = end.min(chunk_end);
+            buf[copy_start - start..copy_end - start]
+                .copy_from_slice(&frame_buf[copy_start - chunk_start..copy_end - chunk_start]);
+
+            frame += nframes;
+        }
+
+        Ok(len)
+    }
+}
+
+/// 光驱的ioctl命令
+///
+/// 参考 Linux include/uapi/linux/cdrom.h
+pub struct CdromIoctlCmd;
+
+impl CdromIoctlCmd {
+    /// 读取TOC头，参数为`struct cdrom_tochdr *`
+    pub const CDROMREADTOCHDR: u32 = 0x5305;
+    /// 读取TOC中的一项，参数为`struct cdrom_tocentry *`
+    pub const CDROMREADTOCENTRY: u32 = 0x5306;
+    /// 获取光驱的状态，返回值为`CDS_*`
+    pub const CDROM_DRIVE_STATUS: u32 = 0x5326;
+}
+
+/// `CDROM_DRIVE_STATUS`的返回值
+const CDS_NO_DISC: usize = 1;
+const CDS_DISC_OK: usize = 4;
+
+/// `cdrom_tocentry.cdte_format`
+const CDROM_LBA: u8 = 0x01;
+const CDROM_MSF: u8 = 0x02;
+
+/// MSF地址从00:02:00开始
+const CD_MSF_OFFSET: u32 = 150;
+const CD_SECS: u32 = 60;
+const CD_FRAMES: u32 = 75;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct CdromTocHdr {
+    cdth_trk0: u8,
+    cdth_trk1: u8,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct CdromTocEntry {
+    cdte_track: u8,
+    /// 低4位为adr，高4位为ctrl
+    cdte_adr_ctrl: u8,
+    cdte_format: u8,
+    /// `union cdrom_addr`，LBA格式时为int，MSF格式时依次为minute、second、frame三个字节
+    cdte_addr: i32,
+    cdte_datamode: u8,
+}
+
+impl CdromTocEntry {
+    fn set_addr(&mut self, lba: u32) -> Result<(), SystemError> {
+        match self.cdte_format {
+            CDROM_LBA => self.cdte_addr = lba as i32,
+            CDROM_MSF => {
+                let lba = lba + CD_MSF_OFFSET;
+                let minute = lba / (CD_SECS * CD_FRAMES);
+                let second = lba / CD_FRAMES % CD_SECS;
+                let frame = lba % CD_FRAMES;
+                self.cdte_addr = i32::from_ne_bytes([minute as u8, second as u8, frame as u8, 0]);
+            }
+            _ => return Err(SystemError::EINVAL),
+        }
+        Ok(())
+    }
+}
+
+/// @brief: 带锁的AHCI光驱
+#[cast_to([sync] Device)]
+pub struct LockedAhciCdrom {
+    blkdev_meta: BlockDevMeta,
+    inner: SpinLock<AhciCdrom>,
+    dev_inner: SpinLock<InnerCdromDevice>,
+    locked_kobj_state: LockedKObjectState,
+    self_ref: Weak<Self>,
+}
+
+#[derive(Debug)]
+struct InnerCdromDevice {
+    device_common: DeviceCommonData,
+    kobject_common: KObjectCommonData,
+}
+
+impl Debug for LockedAhciCdrom {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("LockedAhciCdrom")
+            .field("devname", &self.blkdev_meta.devname)
+            .finish()
+    }
+}
+
+impl LockedAhciCdrom {
+    pub fn new(ctrl_num: u8, port_num: u8) -> Arc<Self> {
+        let id = SR_NEXT_ID.fetch_add(1, Ordering::SeqCst);
+        let devname = DevName::new(format!("{SR_BASENAME}{id}"), id);
+        let mut cdrom = AhciCdrom {
+            ctrl_num,
+            port_num,
+            media: None,
+        };
+        cdrom.check_media();
+
+        let blkdev_meta = BlockDevMeta::new(devname, Major::SCSI_CDROM_MAJOR);
+        // 光驱不支持写入
+        blkdev_meta.set_read_only(true);
+
+        Arc::new_cyclic(|self_ref| Self {
+            blkdev_meta,
+            inner: SpinLock::new(cdrom),
+            dev_inner: SpinLock::new(InnerCdromDevice {
+                device_common: DeviceCommonData::default(),
+                kobject_common: KObjectCommonData::default(),
+            }),
+            locked_kobj_state: LockedKObjectState::default(),
+            self_ref: self_ref.clone(),
+        })
+    }
+
+    fn inner(&self) -> SpinLockGuard<'_, AhciCdrom> {
+        self.inner.lock()
+    }
+
+    fn dev_inner(&self) -> SpinLockGuard<'_, InnerCdromDevice> {
+        self.dev_inner.lock_irqsave()
+    }
+
+    /// 光盘是否在光驱中
+    pub fn media_present(&self) -> bool {
+        self.inner().media.is_some()
+    }
+
+    /// 读取光盘的TOC，最后一项为导出区
+    pub fn read_toc(&self) -> Result<Vec<CdromTrack>, SystemError> {
+        self.inner().read_toc()
+    }
+
+    /// 开始定时检查光盘的变化
+    pub fn start_media_poll(&self) {
+        let timer = Timer::new(
+            Box::new(MediaPollTimer {
+                cdrom: self.self_ref.clone(),
+            }),
+            next_n_ms_timer_jiffies(MEDIA_POLL_MS),
+        );
+        timer.activate();
+    }
+
+    fn poll_media(&self) {
+        if !self.inner().check_media() {
+            return;
+        }
+
+        match self.inner().media {
+            Some(media) => info!(
+                "{}: media changed, {} blocks of {} bytes",
+                self.dev_name(),
+                media.frames,
+                CD_FRAME_SIZE
+            ),
+            None => info!("{}: media removed", self.dev_name()),
+        }
+        let dev = self.self_ref.upgrade().unwrap() as Arc<dyn BlockDevice>;
+        block_dev_manager().notify_media_change(&dev);
+    }
+
+    /// 处理光驱的ioctl
+    pub fn ioctl(&self, cmd: u32, data: usize) -> Result<usize, SystemError> {
+        let arg = IoctlArg::new(cmd, data);
+        match cmd {
+            CdromIoctlCmd::CDROMREADTOCHDR => {
+                let tracks = self.read_toc()?;
+                let mut numbered = tracks.iter().filter(|t| t.track != CDROM_LEADOUT);
+                let first = numbered.next().ok_or(SystemError::EIO)?;
+                let last = numbered.last().unwrap_or(first);
+                arg.write(&CdromTocHdr {
+                    cdth_trk0: first.track,
+                    cdth_trk1: last.track,
+                })?;
+                Ok(0)
+            }
+            CdromIoctlCmd::CDROMREADTOCENTRY => {
+                let mut entry: CdromTocEntry = arg.read()?;
+                let tracks = self.read_toc()?;
+                let track = tracks
+                    .iter()
+                    .find(|t| t.track == entry.cdte_track)
+                    .ok_or(SystemError::EINVAL)?;
+                // SCSI中adr在高4位，cdrom_tocentry中adr在低4位
+                entry.cdte_adr_ctrl = track.adr_ctrl.rotate_left(4);
+                entry.cdte_datamode = 0;
+                entry.set_addr(track.lba)?;
+                arg.write(&entry)?;
+                Ok(0)
+            }
+            CdromIoctlCmd::CDROM_DRIVE_STATUS => {
+                if self.media_present() {
+                    Ok(CDS_DISC_OK)
+                } else {
+                    Ok(CDS_NO_DISC)
+                }
+            }
+            _ => Err(SystemError::ENOSYS),
+        }
+    }
+}
+
+/// 定时检查光盘变化。检查需要发送ATAPI命令，放到工作队列中进行
+#[derive(Debug)]
+struct MediaPollTimer {
+    cdrom: Weak<LockedAhciCdrom>,
+}
+
+impl TimerFunction for MediaPollTimer {
+    fn run(&mut self) -> Result<(), SystemError> {
+        let cdrom = self.cdrom.clone();
+        schedule_work(Work::new(move || {
+            if let Some(cdrom) = cdrom.upgrade() {
+                cdrom.poll_media();
+                cdrom.start_media_poll();
+            }
+        }));
+        Ok(())
+    }
+}
+
+impl BlockDevice for LockedAhciCdrom {
+    fn dev_name(&self) -> &DevName {
+        &self.blkdev_meta.devname
+    }
+
+    fn blkdev_meta(&self) -> &BlockDevMeta {
+        &self.blkdev_meta
+    }
+
+    fn disk_range(&self) -> GeneralBlockRange {
+        GeneralBlockRange::new(0, self.inner().sectors()).unwrap_or(GeneralBlockRange {
+            lba_start: 0,
+            lba_end: 0,
+        })
+    }
+
+    fn read_at_sync(
+        &self,
+        lba_id_start: BlockId,
+        count: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        self.inner().read_at(lba_id_start, count, buf)
+    }
+
+    fn write_at_sync(
+        &self,
+        _lba_id_start: BlockId,
+        _count: usize,
+        _buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EROFS)
+    }
+
+    fn sync(&self) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn blk_size_log2(&self) -> u8 {
+        9
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn device(&self) -> Arc<dyn Device> {
+        self.self_ref.upgrade().unwrap()
+    }
+
+    fn block_size(&self) -> usize {
+        LBA_SIZE
+    }
+
+    fn partitions(&self) -> Vec<Arc<Partition>> {
+        Vec::new()
+    }
+}
+
+impl Device for LockedAhciCdrom {
+    fn dev_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn id_table(&self) -> IdTable {
+        IdTable::new(SR_BASENAME.to_string(), None)
+    }
+
+    fn bus(&self) -> Option<Weak<dyn Bus>> {
+        self.dev_inner().device_common.bus.clone()
+    }
+
+    fn set_bus(&self, bus: Option<Weak<dyn Bus>>) {
+        self.dev_inner().device_common.bus = bus;
+    }
+
+    fn class(&self) -> Option<Arc<dyn Class>> {
+        let mut guard = self.dev_inner();
+        let class = guard.device_common.class.clone()?.upgrade();
+        if class.is_none() {
+            guard.device_common.class = None;
+        }
+        class
+    }
+
+    fn set_class(&self, class: Option<Weak<dyn Class>>) {
+        self.dev_inner().device_common.class = class;
+    }
+
+    fn driver(&self) -> Option<Arc<dyn Driver>> {
+        let mut guard = self.dev_inner();
+        let driver = guard.device_common.driver.clone()?.upgrade();
+        if driver.is_none() {
+            guard.device_common.driver = None;
+        }
+        driver
+    }
+
+    fn set_driver(&self, driver: Option<Weak<dyn Driver>>) {
+        self.dev_inner().device_common.driver = driver;
+    }
+
+    fn is_dead(&self) -> bool {
+        false
+    }
+
+    fn can_match(&self) -> bool {
+        self.dev_inner().device_common.can_match
+    }
+
+    fn set_can_match(&self, can_match: bool) {
+        self.dev_inner().device_common.can_match = can_match;
+    }
+
+    fn state_synced(&self) -> bool {
+        true
+    }
+
+    fn dev_parent(&self) -> Option<Weak<dyn Device>> {
+        self.dev_inner().device_common.get_parent_weak_or_clear()
+    }
+
+    fn set_dev_parent(&self, parent: Option<Weak<dyn Device>>) {
+        self.dev_inner().device_common.parent = parent;
+    }
+}
+
+impl KObject for LockedAhciCdrom {
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn set_inode(&self, inode: Option<Arc<KernFSInode>>) {
+        self.dev_inner().kobject_common.kern_inode = inode;
+    }
+
+    fn inode(&self) -> Option<Arc<KernFSInode>> {
+        self.dev_inner().kobject_common.kern_inode.clone()
+    }
+
+    fn parent(&self) -> Option<Weak<dyn KObject>> {
+        self.dev_inner().kobject_common.parent.clone()
+    }
+
+    fn set_parent(&self, parent: Option<Weak<dyn KObject>>) {
+        self.dev_inner().kobject_common.parent = parent;
+    }
+
+    fn kset(&self) -> Option<Arc<KSet>> {
+        self.dev_inner().kobject_common.kset.clone()
+    }
+
+    fn set_kset(&self, kset: Option<Arc<KSet>>) {
+        self.dev_inner().kobject_common.kset = kset;
+    }
+
+    fn kobj_type(&self) -> Option<&'static dyn KObjType> {
+        self.dev_inner().kobject_common.kobj_type
+    }
+
+    fn name(&self) -> String {
+        self.dev_name().to_string()
+    }
+
+    fn set_name(&self, _name: String) {}
+
+    fn kobj_state(&self) -> RwSemReadGuard<'_, KObjectState> {
+        self.locked_kobj_state.read()
+    }
+
+    fn kobj_state_mut(&self) -> RwSemWriteGuard<'_, KObjectState> {
+        self.locked_kobj_state.write()
+    }
+
+    fn set_kobj_state(&self, state: KObjectState) {
+        *self.locked_kobj_state.write() = state;
+    }
+
+    fn set_kobj_type(&self, ktype: Option<&'static dyn KObjType>) {
+        self.dev_inner().kobject_common.kobj_type = ktype;
+    }
+}
@@ -9,27 +9,52 @@ use crate::mm::{MemoryManagementArch, PhysAddr};
 /// 根据 AHCI 写出 HBA 的 Command
 pub const ATA_CMD_READ_DMA_EXT: u8 = 0x25; // 读操作，并且退出
 pub const ATA_CMD_WRITE_DMA_EXT: u8 = 0x35; // 写操作，并且退出
-#[allow(dead_code)]
+pub const ATA_CMD_READ_FPDMA_QUEUED: u8 = 0x60; // NCQ读
+pub const ATA_CMD_WRITE_FPDMA_QUEUED: u8 = 0x61; // NCQ写
 pub const ATA_CMD_IDENTIFY: u8 = 0xEC;
 #[allow(dead_code)]
 pub const ATA_CMD_IDENTIFY_PACKET: u8 = 0xA1;
-#[allow(dead_code)]
 pub const ATA_CMD_PACKET: u8 = 0xA0;
 pub const ATA_DEV_BUSY: u8 = 0x80;
 pub const ATA_DEV_DRQ: u8 = 0x08;
 
+/// CAP.SNCQ: HBA支持NCQ
+pub const HBA_CAP_SNCQ: u32 = 1 << 30;
+/// GHC.IE: 打开HBA的中断
+pub const HBA_GHC_IE: u32 = 1 << 1;
+
 pub const HBA_PORT_CMD_CR: u32 = 1 << 15;
 pub const HBA_PORT_CMD_FR: u32 = 1 << 14;
 pub const HBA_PORT_CMD_FRE: u32 = 1 << 4;
 pub const HBA_PORT_CMD_ST: u32 = 1;
-#[allow(dead_code)]
+/// 端口上连接的是ATAPI设备
+pub const HBA_PORT_CMD_ATAPI: u32 = 1 << 24;
 pub const HBA_PORT_IS_ERR: u32 = 1 << 30 | 1 << 29 | 1 << 28 | 1 << 27;
+/// PxIS.DHRS: 收到Register D2H FIS
+pub const HBA_PORT_IS_DHRS: u32 = 1 << 0;
+/// PxIS.PSS: 收到PIO Setup FIS
+pub const HBA_PORT_IS_PSS: u32 = 1 << 1;
+/// PxIS.SDBS: 收到Set Device Bits FIS（NCQ命令完成）
+pub const HBA_PORT_IS_SDBS: u32 = 1 << 3;
+/// PxIS.PCS: 端口连接状态变化
+pub const HBA_PORT_IS_PCS: u32 = 1 << 6;
+/// PxIS.PRCS: PhyRdy状态变化
+pub const HBA_PORT_IS_PRCS: u32 = 1 << 22;
+/// PxSERR.DIAG.N: PhyRdy变化，清除它才能清除PxIS.PRCS
+pub const HBA_SERR_DIAG_N: u32 = 1 << 16;
+/// PxSERR.DIAG.X: 设备插入/拔出，清除它才能清除PxIS.PCS
+pub const HBA_SERR_DIAG_X: u32 = 1 << 26;
 pub const HBA_SSTS_PRESENT: u32 = 0x3;
+/// PxSSTS.DET为3: 检测到设备，并且已经建立通信
+pub const HBA_SSTS_DET_ESTABLISHED: u32 = 0x3;
 pub const HBA_SIG_ATA: u32 = 0x00000101;
 pub const HBA_SIG_ATAPI: u32 = 0xEB140101;
 pub const HBA_SIG_PM: u32 = 0x96690101;
 pub const HBA_SIG_SEMB: u32 = 0xC33C0101;
 
+/// Command Header 中的 ATAPI 位，置位时HBA会把命令表中的acmd作为PACKET命令的数据发送
+pub const HBA_CMD_HEADER_ATAPI: u8 = 1 << 5;
+
 /// 接入 Port 的 不同设备类型
 #[derive(Debug)]
 pub enum HbaPortType {
@@ -178,17 +203,74 @@ impl HbaPort {
         }
     }
 
+    /// 命令出错（PxIS.TFES）后恢复端口
+    ///
+    /// 出错后HBA会停止处理命令，需要清除错误状态并重启命令引擎
+    pub fn recover(&mut self) {
+        self.stop();
+        #[allow(unused_unsafe)]
+        {
+            volatile_write!(self.serr, volatile_read!(self.serr));
+            volatile_write!(self.is, u32::MAX);
+        }
+        self.start();
+    }
+
     /// @return: 返回一个空闲 cmd table 的 id; 如果没有，则返回 Option::None
     pub fn find_cmdslot(&self) -> Option<u32> {
         let slots = volatile_read!(self.sact) | volatile_read!(self.ci);
         (0..32).find(|&i| slots & 1 << i == 0)
     }
 
+    /// 端口上是否有已经建立通信的设备
+    pub fn link_up(&self) -> bool {
+        volatile_read!(self.ssts) & 0xf == HBA_SSTS_DET_ESTABLISHED
+    }
+
     /// 初始化,  把 CmdList 等变量的地址赋值到 HbaPort 上 - 这些空间由操作系统分配且固定
     /// 等价于原C版本的 port_rebase 函数
     pub fn init(&mut self, clb: u64, fb: u64, ctbas: &[u64]) {
         self.stop(); // 先暂停端口
+        self.rebase(clb, fb, ctbas);
+
+        #[allow(unused_unsafe)]
+        {
+            // 中断由上层按需打开
+            volatile_write!(self.ie, 0);
+
+            // 错误码
+            volatile_write!(self.serr, volatile_read!(self.serr));
+
+            // Disable power management
+            volatile_write!(self.sctl, volatile_read!(self.sctl) | 7 << 8);
+
+            // Power on and spin up device
+            volatile_write!(self.cmd, volatile_read!(self.cmd) | 1 << 2 | 1 << 1);
+        }
+        self.start(); // 重新开启端口
+    }
 
+    /// 初始化没有连接设备的端口
+    ///
+    /// 只打开FIS接收，不启动命令引擎，这样设备插入时能收到它的签名，并产生端口连接状态变化中断
+    pub fn init_empty(&mut self, clb: u64, fb: u64, ctbas: &[u64]) {
+        self.stop();
+        self.rebase(clb, fb, ctbas);
+
+        #[allow(unused_unsafe)]
+        {
+            volatile_write!(self.ie, 0);
+            volatile_write!(self.serr, volatile_read!(self.serr));
+            volatile_write!(self.is, u32::MAX);
+            volatile_write!(
+                self.cmd,
+                volatile_read!(self.cmd) | HBA_PORT_CMD_FRE | 1 << 2 | 1 << 1
+            );
+        }
+    }
+
+    /// 设置 CmdList、FIS 和命令表的地址，并清空它们
+    fn rebase(&mut self, clb: u64, fb: u64, ctbas: &[u64]) {
         // 赋值 command list base address
         // Command list offset: 1K*portno
         // Command list entry size = 32
@@ -201,7 +283,7 @@ impl HbaPort {
             ptr::write_bytes(
                 MMArch::phys_2_virt(PhysAddr::new(clb as usize))
                     .unwrap()
-                    .data() as *mut u64,
+                    .data() as *mut u8,
                 0,
                 1024,
             );
@@ -216,7 +298,7 @@ impl HbaPort {
             ptr::write_bytes(
                 MMArch::phys_2_virt(PhysAddr::new(fb as usize))
                     .unwrap()
-                    .data() as *mut u64,
+                    .data() as *mut u8,
                 0,
                 256,
             );
@@ -239,29 +321,13 @@ impl HbaPort {
                 ptr::write_bytes(
                     MMArch::phys_2_virt(PhysAddr::new(*ctbas_value as usize))
                         .unwrap()
-                        .data() as *mut u64,
+                        .data() as *mut u8,
                     0,
                     256,
                 );
             }
             cmdheaders = (cmdheaders as usize + size_of::<HbaCmdHeader>()) as *mut HbaCmdHeader;
         }
-
-        #[allow(unused_unsafe)]
-        {
-            // 启动中断
-            volatile_write!(self.ie, 0 /*TODO: Enable interrupts: 0b10111*/);
-
-            // 错误码
-            volatile_write!(self.serr, volatile_read!(self.serr));
-
-            // Disable power management
-            volatile_write!(self.sctl, volatile_read!(self.sctl) | 7 << 8);
-
-            // Power on and spin up device
-            volatile_write!(self.cmd, volatile_read!(self.cmd) | 1 << 2 | 1 << 1);
-        }
-        self.start(); // 重新开启端口
     }
 }
 
@@ -1,35 +1,145 @@
 // 导出 ahci 相关的 module
 pub mod ahcidisk;
+pub mod atapi;
 pub mod hba;
+pub mod port;
 use crate::arch::MMArch;
 use crate::driver::base::block::manager::block_dev_manager;
+use crate::driver::base::device::DeviceId;
 use crate::driver::disk::ahci::ahcidisk::LockedAhciDisk;
+use crate::driver::disk::ahci::atapi::LockedAhciCdrom;
+use crate::driver::disk::ahci::port::AhciPort;
 use crate::driver::pci::pci::{
-    get_pci_device_structure_mut, PciDeviceLinkedList, PciDeviceStructure, PCI_DEVICE_LINKEDLIST,
+    get_pci_device_structure_mut, PciDeviceLinkedList, PciDeviceStructure, PciError,
+    PCI_DEVICE_LINKEDLIST,
 };
+use crate::driver::pci::pci_irq::{
+    IrqCommonMsg, IrqSpecificMsg, PciInterrupt, PciIrqError, PciIrqMsg, IRQ,
+};
+use crate::exception::irqdata::IrqHandlerData;
+use crate::exception::irqdesc::{IrqHandler, IrqReturn};
+use crate::exception::IrqNumber;
+use alloc::string::ToString;
 use alloc::sync::Arc;
 
 use crate::driver::disk::ahci::{
     hba::HbaMem,
-    hba::{HbaPort, HbaPortType},
+    hba::{HbaPort, HbaPortType, HBA_GHC_IE, HBA_PORT_CMD_ATAPI},
 };
 use crate::libs::spinlock::{SpinLock, SpinLockGuard};
 use crate::mm::{MemoryManagementArch, VirtAddr};
 use alloc::{boxed::Box, vec::Vec};
 use core::sync::atomic::compiler_fence;
-use log::debug;
+use log::{debug, warn};
 use system_error::SystemError;
 
 // 仅module内可见 全局数据区  hbr_port, disks
 static LOCKED_HBA_MEM_LIST: SpinLock<Vec<&mut HbaMem>> = SpinLock::new(Vec::new());
 
+/// 所有的AHCI控制器
+static AHCI_HOSTS: SpinLock<Vec<Arc<AhciHost>>> = SpinLock::new(Vec::new());
+
 const AHCI_CLASS: u8 = 0x1;
 const AHCI_SUBCLASS: u8 = 0x6;
 
+/// AHCI控制器的MSI中断号
+///
+/// 目前缺少对PCI设备中断号的统一管理，所以这里需要指定一个中断号。不能与其他中断重复
+const AHCI_IRQ_VECTOR: IrqNumber = IrqNumber::new(58);
+
 /* TFES - Task File Error Status */
 #[allow(non_upper_case_globals)]
 pub const HBA_PxIS_TFES: u32 = 1 << 30;
 
+/// 一个AHCI控制器
+struct AhciHost {
+    dev_id: Arc<DeviceId>,
+    hba_mem: *mut HbaMem,
+    /// 实现了的端口
+    ports: Vec<Arc<AhciPort>>,
+}
+
+// HBA寄存器只通过volatile读写访问
+unsafe impl Send for AhciHost {}
+unsafe impl Sync for AhciHost {}
+
+impl AhciHost {
+    /// 处理控制器中断：先清除各个端口的中断状态，再清除控制器的中断状态
+    fn handle_irq(&self) -> IrqReturn {
+        let hba_mem = unsafe { &mut *self.hba_mem };
+        let is = volatile_read!(hba_mem.is);
+        if is == 0 {
+            return IrqReturn::NotHandled;
+        }
+        for port in self.ports.iter() {
+            if is & (1 << port.port_num()) != 0 {
+                port.handle_irq();
+            }
+        }
+        volatile_write!(hba_mem.is, is);
+        IrqReturn::Handled
+    }
+}
+
+/// AHCI控制器的中断处理函数，通过`DeviceId`找到对应的控制器
+#[derive(Debug)]
+struct AhciIrqHandler;
+
+impl IrqHandler for AhciIrqHandler {
+    fn handle(
+        &self,
+        _irq: IrqNumber,
+        _static_data: Option<&dyn IrqHandlerData>,
+        dev_id: Option<Arc<dyn IrqHandlerData>>,
+    ) -> Result<IrqReturn, SystemError> {
+        let dev_id = dev_id.ok_or(SystemError::EINVAL)?;
+        let dev_id = dev_id
+            .arc_any()
+            .downcast::<DeviceId>()
+            .map_err(|_| SystemError::EINVAL)?;
+
+        let host = AHCI_HOSTS
+            .lock_irqsave()
+            .iter()
+            .find(|host| host.dev_id == dev_id)
+            .cloned();
+        match host {
+            Some(host) => Ok(host.handle_irq()),
+            None => Ok(IrqReturn::NotHandled),
+        }
+    }
+}
+
+/// 为AHCI控制器安装MSI中断
+fn ahci_setup_irq(
+    device: &Arc<dyn PciDeviceStructure>,
+    dev_id: Arc<DeviceId>,
+) -> Result<(), PciError> {
+    let standard_device = device
+        .as_standard_device()
+        .ok_or(PciError::PciIrqError(PciIrqError::PciDeviceNotSupportIrq))?;
+    standard_device
+        .irq_vector_mut()
+        .ok_or(PciError::PciIrqError(PciIrqError::PciDeviceNotSupportIrq))?
+        .write()
+        .push(AHCI_IRQ_VECTOR);
+    standard_device
+        .irq_init(IRQ::PCI_IRQ_MSI)
+        .ok_or(PciError::PciIrqError(PciIrqError::IrqNotInited))?;
+    let msg = PciIrqMsg {
+        irq_common_message: IrqCommonMsg::init_from(
+            0,
+            "AHCI_IRQ".to_string(),
+            &AhciIrqHandler,
+            dev_id,
+        ),
+        irq_specific_message: IrqSpecificMsg::msi_default(),
+    };
+    standard_device.irq_install(msg)?;
+    standard_device.irq_enable(true)?;
+    Ok(())
+}
+
 /// @brief 寻找所有的ahci设备
 /// @param list 链表的写锁
 /// @return Result<Vec<&'a mut Box<dyn PciDeviceStructure>>, SystemError>   成功则返回包含所有ahci设备结构体的可变引用的链表，失败则返回err
@@ -46,6 +156,9 @@ fn ahci_device_search(
 }
 
 /// @brief: 初始化 ahci
+///
+/// 先初始化控制器的所有端口并打开中断，再为连接了设备的端口注册块设备。
+/// 没有连接设备的端口也会被初始化，之后插入的磁盘由端口的热插拔处理注册
 pub fn ahci_init() -> Result<(), SystemError> {
     let list = &*PCI_DEVICE_LINKEDLIST;
     let ahci_device = ahci_device_search(list)?;
@@ -73,59 +186,117 @@ pub fn ahci_init() -> Result<(), SystemError> {
         let hba_mem = unsafe { (virtaddr.data() as *mut HbaMem).as_mut().unwrap() };
         hba_mem_list.push(unsafe { (virtaddr.data() as *mut HbaMem).as_mut().unwrap() });
         let pi = volatile_read!(hba_mem.pi);
+        let cap = volatile_read!(hba_mem.cap);
         let hba_mem_index = hba_mem_list.len() - 1;
         drop(hba_mem_list);
+
+        let dev_id = DeviceId::new(None, Some(format!("ahci{}", hba_mem_index))).unwrap();
+        let irq_ok = match ahci_setup_irq(&device, dev_id.clone()) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(
+                    "ahci{}: failed to setup irq, fall back to polling: {:?}",
+                    hba_mem_index, e
+                );
+                false
+            }
+        };
+
         // 初始化所有的port
+        let mut ports = Vec::new();
+        let mut types = Vec::new();
         for j in 0..32 {
             if (pi >> j) & 1 > 0 {
-                let hba_mem_list = LOCKED_HBA_MEM_LIST.lock();
                 let hba_mem_port = &mut hba_mem.ports[j];
+                // 计算地址
+                let fb = unsafe {
+                    MMArch::virt_2_phys(VirtAddr::new(ahci_port_base_vaddr + (32 << 10) + (j << 8)))
+                }
+                .unwrap()
+                .data();
+                let clb = unsafe {
+                    MMArch::virt_2_phys(VirtAddr::new(ahci_port_base_vaddr + (j << 10)))
+                        .unwrap()
+                        .data()
+                };
+                let ctbas = (0..32)
+                    .map(|x| unsafe {
+                        MMArch::virt_2_phys(VirtAddr::new(
+                            ahci_port_base_vaddr + (40 << 10) + (j << 13) + (x << 8),
+                        ))
+                        .unwrap()
+                        .data() as u64
+                    })
+                    .collect::<Vec<_>>();
+                let port = AhciPort::new(
+                    hba_mem_index as u8,
+                    j as u8,
+                    hba_mem_port as *mut HbaPort,
+                    cap,
+                    clb as u64,
+                    fb as u64,
+                    ctbas,
+                );
+
                 let tp = hba_mem_port.check_type();
                 match tp {
                     HbaPortType::None => {
                         debug!("<ahci_rust_init> Find a None type Disk.");
+                        port.init_empty();
                     }
                     HbaPortType::Unknown(err) => {
                         debug!("<ahci_rust_init> Find a Unknown({:?}) type Disk.", err);
                     }
                     _ => {
                         debug!("<ahci_rust_init> Find a {:?} type Disk.", tp);
-
-                        // 计算地址
-                        let fb = unsafe {
-                            MMArch::virt_2_phys(VirtAddr::new(
-                                ahci_port_base_vaddr + (32 << 10) + (j << 8),
-                            ))
-                        }
-                        .unwrap()
-                        .data();
-                        let clb = unsafe {
-                            MMArch::virt_2_phys(VirtAddr::new(ahci_port_base_vaddr + (j << 10)))
-                                .unwrap()
-                                .data()
-                        };
-                        let ctbas = (0..32)
-                            .map(|x| unsafe {
-                                MMArch::virt_2_phys(VirtAddr::new(
-                                    ahci_port_base_vaddr + (40 << 10) + (j << 13) + (x << 8),
-                                ))
-                                .unwrap()
-                                .data() as u64
-                            })
-                            .collect::<Vec<_>>();
-
                         // 初始化 port
-                        hba_mem_port.init(clb as u64, fb as u64, &ctbas);
-                        drop(hba_mem_list);
+                        port.init();
                         compiler_fence(core::sync::atomic::Ordering::SeqCst);
-                        let ahci_disk = LockedAhciDisk::new(hba_mem_index as u8, j as u8)?;
-                        block_dev_manager()
-                            .register(ahci_disk)
-                            .expect("register ahci disk failed");
-
-                        debug!("start register ahci device");
                     }
                 }
+                ports.push(port);
+                types.push(tp);
+            }
+        }
+
+        let host = Arc::new(AhciHost {
+            dev_id,
+            hba_mem: hba_mem as *mut HbaMem,
+            ports,
+        });
+        AHCI_HOSTS.lock_irqsave().push(host.clone());
+
+        if irq_ok {
+            // ATAPI光驱的命令还是轮询完成的，不打开它的端口中断
+            for (port, tp) in host.ports.iter().zip(types.iter()) {
+                if !matches!(tp, HbaPortType::SATAPI | HbaPortType::Unknown(_)) {
+                    port.enable_irq();
+                }
+            }
+            volatile_write!(hba_mem.is, u32::MAX);
+            volatile_write!(hba_mem.ghc, volatile_read!(hba_mem.ghc) | HBA_GHC_IE);
+        }
+
+        for (port, tp) in host.ports.iter().zip(types.into_iter()) {
+            match tp {
+                HbaPortType::None | HbaPortType::Unknown(_) => {}
+                HbaPortType::SATAPI => {
+                    volatile_set_bit!(port.regs().cmd, HBA_PORT_CMD_ATAPI, true);
+                    let cdrom = LockedAhciCdrom::new(port.ctrl_num(), port.port_num());
+                    block_dev_manager()
+                        .register(cdrom.clone())
+                        .expect("register ahci cdrom failed");
+                    cdrom.start_media_poll();
+                }
+                _ => {
+                    let ahci_disk = LockedAhciDisk::new(port.clone())?;
+                    block_dev_manager()
+                        .register(ahci_disk.clone())
+                        .expect("register ahci disk failed");
+                    port.set_disk(ahci_disk);
+
+                    debug!("start register ahci device");
+                }
             }
         }
     }
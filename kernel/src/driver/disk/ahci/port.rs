@@ -0,0 +1,614 @@
+//! AHCI端口上的命令下发与完成
+//!
+//! 磁盘的读写命令通过[`AhciPort`]下发：先分配一个命令槽，填好命令表后写PxCI下发，再等待命令完成。
+//! HBA和设备都支持NCQ时使用READ/WRITE FPDMA QUEUED命令，同一个端口上最多同时下发32个命令
+//! （取决于设备报告的队列深度和HBA的命令槽数量）；否则一次只下发一个命令。
+//!
+//! 控制器的中断安装成功时，命令完成由[`AhciPort::handle_irq`]发现并唤醒等待者，否则等待者自己轮询PxCI/PxSACT。
+//! 端口的连接状态变化中断会被交给工作队列，注册新插入的磁盘或者移除被拔出的磁盘，参见[`AhciPort::hotplug`]。
+//!
+//! NCQ命令出错时HBA会停止处理这个端口上的命令。这里没有读取NCQ错误日志找出是哪一个命令出的错，
+//! 而是让端口上所有还没有完成的命令都以EIO失败，然后恢复端口。
+
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    mem::size_of,
+    ptr::write_bytes,
+    sync::atomic::{compiler_fence, AtomicBool, Ordering},
+};
+use log::{error, info, warn};
+use system_error::SystemError;
+
+use super::{
+    ahcidisk::LockedAhciDisk,
+    hba::{
+        FisRegH2D, FisType, HbaCmdHeader, HbaCmdTable, HbaPort, HbaPortType, ATA_CMD_IDENTIFY,
+        ATA_CMD_READ_DMA_EXT, ATA_CMD_READ_FPDMA_QUEUED, ATA_CMD_WRITE_DMA_EXT,
+        ATA_CMD_WRITE_FPDMA_QUEUED, ATA_DEV_BUSY, HBA_CAP_SNCQ, HBA_PORT_IS_DHRS, HBA_PORT_IS_ERR,
+        HBA_PORT_IS_PCS, HBA_PORT_IS_PRCS, HBA_PORT_IS_PSS, HBA_PORT_IS_SDBS, HBA_SERR_DIAG_N,
+        HBA_SERR_DIAG_X,
+    },
+};
+use crate::{
+    arch::MMArch,
+    driver::{
+        base::block::{block_device::BlockDevice, manager::block_dev_manager},
+        scsi::scsi_manager,
+    },
+    exception::workqueue::{schedule_work, Work},
+    libs::{mutex::Mutex, spinlock::SpinLock, wait_queue::WaitQueue},
+    mm::{MemoryManagementArch, PhysAddr, VirtAddr},
+    time::{sleep::nanosleep, PosixTimeSpec},
+};
+
+/// 打开的端口中断
+const AHCI_PORT_IRQ_MASK: u32 = HBA_PORT_IS_DHRS
+    | HBA_PORT_IS_PSS
+    | HBA_PORT_IS_SDBS
+    | HBA_PORT_IS_PCS
+    | HBA_PORT_IS_PRCS
+    | HBA_PORT_IS_ERR;
+
+/// 每个PRDT项传输的扇区数（8K）
+const AHCI_PRDT_SECTORS: usize = 16;
+/// 每个命令表预留的PRDT项数量
+const AHCI_MAX_PRDT: usize = 8;
+/// 一个命令最多传输的扇区数
+pub const AHCI_MAX_SECTORS: usize = AHCI_PRDT_SECTORS * AHCI_MAX_PRDT;
+
+/// 设备插入后等待它就绪的最长时间（毫秒）
+const AHCI_LINK_WAIT_MS: usize = 1000;
+
+/// 一个AHCI端口
+pub struct AhciPort {
+    ctrl_num: u8,
+    port_num: u8,
+    regs: *mut HbaPort,
+    /// 命令列表、FIS和命令表的物理地址，设备插入时重新初始化端口要用到
+    clb: u64,
+    fb: u64,
+    ctbas: Vec<u64>,
+    /// HBA支持NCQ
+    hba_ncq: bool,
+    /// HBA的命令槽数量
+    nr_slots: u32,
+    inner: SpinLock<InnerAhciPort>,
+    /// 等待命令完成或者空闲的命令槽
+    wait_queue: WaitQueue,
+    /// 端口中断已经打开，命令完成时由中断处理函数唤醒等待者
+    irq_enabled: AtomicBool,
+    /// 端口上的磁盘。同时用来串行化热插拔处理
+    disk: Mutex<Option<Arc<LockedAhciDisk>>>,
+}
+
+struct InnerAhciPort {
+    /// 读写命令使用NCQ
+    ncq: bool,
+    /// 同时下发的最大命令数
+    depth: u32,
+    /// 已经分配的命令槽
+    allocated: u32,
+    /// 已经下发、还没有完成的命令槽
+    issued: u32,
+    /// 已经完成的命令槽
+    done: u32,
+    /// 已经完成的命令槽中出错的那些
+    failed: u32,
+    /// 设备已经被拔出
+    removed: bool,
+}
+
+impl InnerAhciPort {
+    const fn new() -> Self {
+        Self {
+            ncq: false,
+            depth: 1,
+            allocated: 0,
+            issued: 0,
+            done: 0,
+            failed: 0,
+            removed: false,
+        }
+    }
+}
+
+impl core::fmt::Debug for AhciPort {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AhciPort")
+            .field("ctrl_num", &self.ctrl_num)
+            .field("port_num", &self.port_num)
+            .finish()
+    }
+}
+
+// 端口寄存器只通过volatile读写访问，命令槽的状态由锁保护
+unsafe impl Send for AhciPort {}
+unsafe impl Sync for AhciPort {}
+
+impl AhciPort {
+    /// # 创建端口
+    ///
+    /// ## 参数
+    /// - `regs`: 端口寄存器
+    /// - `cap`: HBA的CAP寄存器
+    /// - `clb`/`fb`/`ctbas`: 命令列表、FIS和32个命令表的物理地址
+    pub fn new(
+        ctrl_num: u8,
+        port_num: u8,
+        regs: *mut HbaPort,
+        cap: u32,
+        clb: u64,
+        fb: u64,
+        ctbas: Vec<u64>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            ctrl_num,
+            port_num,
+            regs,
+            clb,
+            fb,
+            ctbas,
+            hba_ncq: cap & HBA_CAP_SNCQ != 0,
+            nr_slots: ((cap >> 8) & 0x1f) + 1,
+            inner: SpinLock::new(InnerAhciPort::new()),
+            wait_queue: WaitQueue::default(),
+            irq_enabled: AtomicBool::new(false),
+            disk: Mutex::new(None),
+        })
+    }
+
+    pub fn ctrl_num(&self) -> u8 {
+        self.ctrl_num
+    }
+
+    pub fn port_num(&self) -> u8 {
+        self.port_num
+    }
+
+    /// 端口寄存器
+    #[allow(clippy::mut_from_ref)]
+    pub fn regs(&self) -> &mut HbaPort {
+        unsafe { &mut *self.regs }
+    }
+
+    /// 初始化端口并启动命令引擎，用于连接了设备的端口
+    pub fn init(&self) {
+        *self.inner.lock_irqsave() = InnerAhciPort::new();
+        self.regs().init(self.clb, self.fb, &self.ctbas);
+        if self.irq_enabled.load(Ordering::SeqCst) {
+            self.write_ie();
+        }
+    }
+
+    /// 初始化没有连接设备的端口，等待设备插入
+    pub fn init_empty(&self) {
+        self.regs().init_empty(self.clb, self.fb, &self.ctbas);
+        if self.irq_enabled.load(Ordering::SeqCst) {
+            self.write_ie();
+        }
+    }
+
+    /// 打开端口中断。调用前需要已经安装好控制器的中断处理函数
+    pub fn enable_irq(&self) {
+        self.irq_enabled.store(true, Ordering::SeqCst);
+        self.write_ie();
+    }
+
+    fn write_ie(&self) {
+        let regs = self.regs();
+        volatile_write!(regs.is, u32::MAX);
+        volatile_write!(regs.ie, AHCI_PORT_IRQ_MASK);
+    }
+
+    /// # 根据设备的队列深度决定是否使用NCQ
+    ///
+    /// ## 参数
+    /// - `device_depth`: 设备报告的队列深度，不支持NCQ时为0
+    ///
+    /// ## 返回值
+    /// - 同时下发的最大命令数
+    pub fn set_queue_depth(&self, device_depth: u32) -> u32 {
+        let mut inner = self.inner.lock_irqsave();
+        if self.hba_ncq && device_depth > 1 {
+            inner.ncq = true;
+            inner.depth = device_depth.min(self.nr_slots);
+        } else {
+            inner.ncq = false;
+            inner.depth = 1;
+        }
+        inner.depth
+    }
+
+    /// 分配一个命令槽，同时下发的命令数达到队列深度时等待
+    fn alloc_slot(&self) -> Result<u32, SystemError> {
+        self.wait_queue.wait_until_io(|| {
+            let mut inner = self.inner.lock_irqsave();
+            if inner.removed {
+                return Some(Err(SystemError::ENODEV));
+            }
+            if inner.allocated.count_ones() >= inner.depth {
+                return None;
+            }
+            let slot = (0..self.nr_slots).find(|&i| inner.allocated & (1 << i) == 0)?;
+            inner.allocated |= 1 << slot;
+            Some(Ok(slot))
+        })
+    }
+
+    fn free_slot(&self, slot: u32) {
+        let mut inner = self.inner.lock_irqsave();
+        let bit = !(1 << slot);
+        inner.allocated &= bit;
+        inner.done &= bit;
+        inner.failed &= bit;
+        drop(inner);
+        self.wait_queue.wakeup_all(None);
+    }
+
+    /// # 取出命令槽对应的命令头和命令表
+    ///
+    /// 命令表会被清零
+    #[allow(clippy::mut_from_ref)]
+    fn slot_tables(&self, slot: u32) -> (&mut HbaCmdHeader, &mut HbaCmdTable) {
+        let regs = self.regs();
+        let cmdheader = unsafe {
+            (MMArch::phys_2_virt(PhysAddr::new(
+                volatile_read!(regs.clb) as usize + slot as usize * size_of::<HbaCmdHeader>(),
+            ))
+            .unwrap()
+            .data() as *mut HbaCmdHeader)
+                .as_mut()
+                .unwrap()
+        };
+        let cmdtbl = unsafe {
+            (MMArch::phys_2_virt(PhysAddr::new(volatile_read!(cmdheader.ctba) as usize))
+                .unwrap()
+                .data() as *mut HbaCmdTable)
+                .as_mut()
+                .unwrap()
+        };
+        unsafe { write_bytes(cmdtbl as *mut HbaCmdTable, 0, 1) };
+        (cmdheader, cmdtbl)
+    }
+
+    /// 下发命令槽中的命令
+    fn issue(&self, slot: u32, queued: bool) -> Result<(), SystemError> {
+        let regs = self.regs();
+        let mut inner = self.inner.lock_irqsave();
+        if inner.removed {
+            return Err(SystemError::ENODEV);
+        }
+        inner.issued |= 1 << slot;
+        compiler_fence(Ordering::SeqCst);
+        // PxSACT和PxCI写0的位没有作用，只写要下发的这一位，不会影响其他正在执行的命令
+        if queued {
+            volatile_write!(regs.sact, 1 << slot);
+        }
+        volatile_write!(regs.ci, 1 << slot);
+        Ok(())
+    }
+
+    /// 等待命令槽中的命令完成
+    fn wait_slot(&self, slot: u32) -> Result<(), SystemError> {
+        let bit = 1 << slot;
+        let result = || {
+            let inner = self.inner.lock_irqsave();
+            if inner.done & bit != 0 {
+                if inner.failed & bit != 0 {
+                    return Some(Err(SystemError::EIO));
+                }
+                return Some(Ok(()));
+            }
+            if inner.removed {
+                return Some(Err(SystemError::ENODEV));
+            }
+            None
+        };
+
+        if self.irq_enabled.load(Ordering::SeqCst) {
+            return self.wait_queue.wait_until_io(result);
+        }
+        loop {
+            self.poll();
+            if let Some(r) = result() {
+                return r;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// 没有中断时，由等待者检查命令是否完成
+    fn poll(&self) {
+        let regs = self.regs();
+        let is = volatile_read!(regs.is) & HBA_PORT_IS_ERR;
+        if is != 0 {
+            volatile_write!(regs.is, is);
+        }
+        self.reap(is);
+    }
+
+    /// # 检查已经下发的命令是否完成
+    ///
+    /// ## 参数
+    /// - `is`: 读到的PxIS
+    fn reap(&self, is: u32) {
+        let regs = self.regs();
+        let mut inner = self.inner.lock_irqsave();
+        if inner.issued == 0 {
+            return;
+        }
+        if is & HBA_PORT_IS_ERR != 0 {
+            error!(
+                "ahci: port {}:{} command error, tfd={:#x}, serr={:#x}",
+                self.ctrl_num,
+                self.port_num,
+                volatile_read!(regs.tfd),
+                volatile_read!(regs.serr)
+            );
+            inner.failed |= inner.issued;
+            inner.done |= inner.issued;
+            inner.issued = 0;
+            regs.recover();
+        } else {
+            // NCQ命令完成时清除PxSACT中的位，普通命令完成时清除PxCI中的位
+            let active = volatile_read!(regs.sact) | volatile_read!(regs.ci);
+            let finished = inner.issued & !active;
+            if finished == 0 {
+                return;
+            }
+            inner.done |= finished;
+            inner.issued &= !finished;
+        }
+        drop(inner);
+        self.wait_queue.wakeup_all(None);
+    }
+
+    /// # 处理端口中断
+    ///
+    /// 在中断上下文中调用，由控制器的中断处理函数调用
+    pub fn handle_irq(self: &Arc<Self>) {
+        let regs = self.regs();
+        let is = volatile_read!(regs.is);
+        if is == 0 {
+            return;
+        }
+        volatile_write!(regs.is, is);
+
+        if is & (HBA_PORT_IS_PCS | HBA_PORT_IS_PRCS) != 0 {
+            // PxIS.PCS/PRCS反映的是PxSERR.DIAG.X/N，需要清除后者
+            volatile_write!(regs.serr, HBA_SERR_DIAG_X | HBA_SERR_DIAG_N);
+            let port = self.clone();
+            schedule_work(Work::new(move || port.hotplug()));
+        }
+        self.reap(is);
+    }
+
+    /// 设备被拔出，让所有还没有完成和之后下发的命令都以ENODEV失败
+    fn mark_removed(&self) {
+        let mut inner = self.inner.lock_irqsave();
+        inner.removed = true;
+        inner.issued = 0;
+        drop(inner);
+        self.wait_queue.wakeup_all(None);
+    }
+
+    /// # 下发一个命令并等待完成
+    ///
+    /// ## 参数
+    /// - `build`: 填写命令头和命令表，参数为命令槽、命令头、命令表
+    /// - `queued`: 是否为NCQ命令
+    fn exec<F>(&self, build: F, queued: bool) -> Result<(), SystemError>
+    where
+        F: FnOnce(u32, &mut HbaCmdHeader, &mut HbaCmdTable) -> Result<(), SystemError>,
+    {
+        let slot = self.alloc_slot()?;
+        let (cmdheader, cmdtbl) = self.slot_tables(slot);
+        let result = build(slot, cmdheader, cmdtbl)
+            .and_then(|_| self.issue(slot, queued))
+            .and_then(|_| self.wait_slot(slot));
+        self.free_slot(slot);
+        result
+    }
+
+    /// # 读写磁盘
+    ///
+    /// ## 参数
+    /// - `lba`: 起始扇区
+    /// - `count`: 扇区数，不能超过[`AHCI_MAX_SECTORS`]
+    /// - `buf`: 物理地址连续的内核缓冲区，长度不小于`count * 512`
+    /// - `write`: 是否为写
+    pub fn rw(&self, lba: usize, count: usize, buf: usize, write: bool) -> Result<(), SystemError> {
+        if count == 0 {
+            return Ok(());
+        }
+        let prdtl = count.div_ceil(AHCI_PRDT_SECTORS);
+        if prdtl > AHCI_MAX_PRDT {
+            error!("ahci rw: e2big");
+            return Err(SystemError::E2BIG);
+        }
+        let ncq = self.inner.lock_irqsave().ncq;
+
+        self.exec(
+            |slot, cmdheader, cmdtbl| {
+                // Command FIS长度（以DWORD为单位），写命令还要设置W位
+                let cfl = (size_of::<FisRegH2D>() / size_of::<u32>()) as u8;
+                volatile_write!(cmdheader.cfl, if write { cfl | 1 << 6 } else { cfl });
+                volatile_write!(cmdheader.prdtl, prdtl as u16);
+                volatile_write!(cmdheader._prdbc, 0);
+
+                // 8K bytes (16 sectors) per PRDT
+                let mut buf_ptr = buf;
+                let mut remain = count;
+                for entry in cmdtbl.prdt_entry.iter_mut().take(prdtl) {
+                    let sectors = remain.min(AHCI_PRDT_SECTORS);
+                    let dba = MMArch::virt_2_phys(VirtAddr::new(buf_ptr))
+                        .ok_or(SystemError::EFAULT)?
+                        .data() as u64;
+                    volatile_write!(entry.dba, dba);
+                    // 数据长度，并允许中断
+                    volatile_write!(entry.dbc, ((sectors << 9) - 1) as u32 | 1 << 31);
+                    buf_ptr += sectors << 9;
+                    remain -= sectors;
+                }
+
+                let cmdfis = unsafe {
+                    (cmdtbl.cfis.as_mut_ptr() as *mut FisRegH2D)
+                        .as_mut()
+                        .unwrap()
+                };
+                volatile_write!(cmdfis.fis_type, FisType::RegH2D as u8);
+                volatile_write!(cmdfis.pm, 1 << 7); // command_bit set
+                volatile_write!(cmdfis.lba0, (lba & 0xFF) as u8);
+                volatile_write!(cmdfis.lba1, ((lba >> 8) & 0xFF) as u8);
+                volatile_write!(cmdfis.lba2, ((lba >> 16) & 0xFF) as u8);
+                volatile_write!(cmdfis.lba3, ((lba >> 24) & 0xFF) as u8);
+                volatile_write!(cmdfis.lba4, ((lba >> 32) & 0xFF) as u8);
+                volatile_write!(cmdfis.lba5, ((lba >> 40) & 0xFF) as u8);
+                volatile_write!(cmdfis.device, 1 << 6); // LBA Mode
+
+                if ncq {
+                    // NCQ命令的扇区数放在Feature寄存器中，Count寄存器的7:3位是tag
+                    let command = if write {
+                        ATA_CMD_WRITE_FPDMA_QUEUED
+                    } else {
+                        ATA_CMD_READ_FPDMA_QUEUED
+                    };
+                    volatile_write!(cmdfis.command, command);
+                    volatile_write!(cmdfis.featurel, (count & 0xFF) as u8);
+                    volatile_write!(cmdfis.featureh, ((count >> 8) & 0xFF) as u8);
+                    volatile_write!(cmdfis.countl, (slot << 3) as u8);
+                } else {
+                    let command = if write {
+                        ATA_CMD_WRITE_DMA_EXT
+                    } else {
+                        ATA_CMD_READ_DMA_EXT
+                    };
+                    volatile_write!(cmdfis.command, command);
+                    volatile_write!(cmdfis.countl, (count & 0xFF) as u8);
+                    volatile_write!(cmdfis.counth, ((count >> 8) & 0xFF) as u8);
+                }
+                compiler_fence(Ordering::SeqCst);
+                Ok(())
+            },
+            ncq,
+        )
+    }
+
+    /// # 发送IDENTIFY DEVICE命令
+    ///
+    /// ## 返回值
+    /// - 设备返回的256个字
+    pub fn identify(&self) -> Result<Vec<u16>, SystemError> {
+        let mut data: Vec<u16> = vec![0; 256];
+        let buf = data.as_mut_ptr() as usize;
+
+        self.exec(
+            |_slot, cmdheader, cmdtbl| {
+                let cfl = (size_of::<FisRegH2D>() / size_of::<u32>()) as u8;
+                volatile_write!(cmdheader.cfl, cfl);
+                volatile_write!(cmdheader.prdtl, 1);
+                volatile_write!(cmdheader._prdbc, 0);
+
+                let dba = MMArch::virt_2_phys(VirtAddr::new(buf))
+                    .ok_or(SystemError::EFAULT)?
+                    .data() as u64;
+                volatile_write!(cmdtbl.prdt_entry[0].dba, dba);
+                volatile_write!(cmdtbl.prdt_entry[0].dbc, 511 | 1 << 31);
+
+                let cmdfis = unsafe {
+                    (cmdtbl.cfis.as_mut_ptr() as *mut FisRegH2D)
+                        .as_mut()
+                        .unwrap()
+                };
+                volatile_write!(cmdfis.fis_type, FisType::RegH2D as u8);
+                volatile_write!(cmdfis.pm, 1 << 7);
+                volatile_write!(cmdfis.command, ATA_CMD_IDENTIFY);
+                compiler_fence(Ordering::SeqCst);
+                Ok(())
+            },
+            false,
+        )?;
+        Ok(data)
+    }
+
+    /// 端口上的磁盘
+    pub fn set_disk(&self, disk: Arc<LockedAhciDisk>) {
+        *self.disk.lock() = Some(disk);
+    }
+
+    /// 等待插入的设备就绪
+    fn wait_link_ready(&self) -> bool {
+        let regs = self.regs();
+        for _ in 0..AHCI_LINK_WAIT_MS / 10 {
+            if regs.link_up() && volatile_read!(regs.tfd) as u8 & ATA_DEV_BUSY == 0 {
+                return true;
+            }
+            let _ = nanosleep(PosixTimeSpec::new(0, 10_000_000));
+        }
+        false
+    }
+
+    /// # 处理端口连接状态变化
+    ///
+    /// 在工作队列中调用：有设备插入而端口上还没有磁盘时注册新的磁盘，磁盘被拔出时把它从块设备层移除
+    fn hotplug(self: &Arc<Self>) {
+        let mut disk = self.disk.lock();
+        let link_up = self.regs().link_up();
+
+        if !link_up {
+            let Some(old) = disk.take() else {
+                return;
+            };
+            self.mark_removed();
+            let dev = old.clone() as Arc<dyn BlockDevice>;
+            if let Err(e) = block_dev_manager().unregister(&dev) {
+                warn!("ahci: failed to unregister {}: {:?}", dev.dev_name(), e);
+            }
+            scsi_manager().free_id(dev.dev_name().id());
+            self.init_empty();
+            info!(
+                "ahci: {} removed from port {}",
+                dev.dev_name(),
+                self.port_num
+            );
+            return;
+        }
+
+        if disk.is_some() || !self.wait_link_ready() {
+            return;
+        }
+        match self.regs().check_type() {
+            HbaPortType::SATA => {}
+            tp => {
+                info!(
+                    "ahci: {:?} device on port {} is not hot-pluggable",
+                    tp, self.port_num
+                );
+                return;
+            }
+        }
+
+        self.init();
+        let new_disk = match LockedAhciDisk::new(self.clone()) {
+            Ok(d) => d,
+            Err(e) => {
+                error!(
+                    "ahci: failed to probe disk on port {}: {:?}",
+                    self.port_num, e
+                );
+                return;
+            }
+        };
+        if let Err(e) = block_dev_manager().register(new_disk.clone()) {
+            error!("ahci: failed to register {}: {:?}", new_disk.dev_name(), e);
+            scsi_manager().free_id(new_disk.dev_name().id());
+            return;
+        }
+        info!(
+            "ahci: {} attached to port {}",
+            new_disk.dev_name(),
+            self.port_num
+        );
+        *disk = Some(new_disk);
+    }
+}
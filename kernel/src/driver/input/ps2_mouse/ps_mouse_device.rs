@@ -1,10 +1,6 @@
 use core::hint::spin_loop;
 
-use alloc::{
-    string::ToString,
-    sync::{Arc, Weak},
-    vec::Vec,
-};
+use alloc::{string::ToString, sync::Arc};
 use kdepends::ringbuffer::{AllocRingBuffer, RingBuffer};
 use log::{debug, error};
 use system_error::SystemError;
@@ -14,10 +10,11 @@ use crate::{
     arch::{io::PortIOArch, CurrentIrqArch, CurrentPortIOArch},
     driver::{
         base::{
+            char::{manager::char_dev_manager, CharDevice},
             class::Class,
             device::{
-                bus::Bus, device_manager, device_number::DeviceNumber, driver::Driver, Device,
-                DeviceCommonData, DeviceType, IdTable,
+                bus::Bus, device_manager, driver::Driver, Device, DeviceCommonData, DeviceType,
+                IdTable,
             },
             kobject::{KObjType, KObject, KObjectCommonData, KObjectState, LockedKObjectState},
             kset::KSet,
@@ -29,18 +26,13 @@ use crate::{
     },
     exception::InterruptArch,
     filesystem::{
-        devfs::{devfs_register, DevFS, DeviceINode, LockedDevFSInode},
         kernfs::KernFSInode,
-        vfs::{
-            utils::DName, vcore::generate_inode_id, FilePrivateData, FileSystem, FileType,
-            IndexNode, InodeFlags, InodeMode, Metadata,
-        },
+        vfs::{file::FileFlags, FilePrivateData, InodeMode},
     },
     libs::{
         rwsem::{RwSemReadGuard, RwSemWriteGuard},
         spinlock::{SpinLock, SpinLockGuard},
     },
-    time::PosixTimeSpec,
 };
 
 static mut PS2_MOUSE_DEVICE: Option<Arc<Ps2MouseDevice>> = None;
@@ -190,26 +182,6 @@ impl Ps2MouseDevice {
                 current_packet: 0,
                 current_state: MouseState::new(),
                 buf: AllocRingBuffer::new(MOUSE_BUFFER_CAPACITY),
-                devfs_metadata: Metadata {
-                    dev_id: 1,
-                    inode_id: generate_inode_id(),
-                    size: 4096,
-                    blk_size: 0,
-                    blocks: 0,
-                    atime: PosixTimeSpec::default(),
-                    mtime: PosixTimeSpec::default(),
-                    ctime: PosixTimeSpec::default(),
-                    btime: PosixTimeSpec::default(),
-                    file_type: FileType::CharDevice, // 文件夹，block设备，char设备
-                    mode: InodeMode::from_bits_truncate(0o644),
-                    flags: InodeFlags::empty(),
-                    nlinks: 1,
-                    uid: 0,
-                    gid: 0,
-                    raw_dev: DeviceNumber::default(), // 这里用来作为device number
-                },
-                device_inode_fs: None,
-                parent: Weak::new(),
             }),
             kobj_state: LockedKObjectState::new(None),
         };
@@ -424,11 +396,6 @@ struct InnerPs2MouseDevice {
     current_packet: u8,
     /// 鼠标数据环形缓冲区
     buf: AllocRingBuffer<u8>,
-
-    /// device inode要求的字段
-    device_inode_fs: Option<Weak<DevFS>>,
-    parent: Weak<LockedDevFSInode>,
-    devfs_metadata: Metadata,
 }
 
 impl Device for Ps2MouseDevice {
@@ -587,21 +554,15 @@ impl KObject for Ps2MouseDevice {
     }
 }
 
-impl DeviceINode for Ps2MouseDevice {
-    fn set_fs(&self, fs: Weak<DevFS>) {
-        self.inner.lock_irqsave().device_inode_fs = Some(fs);
+impl CharDevice for Ps2MouseDevice {
+    fn devnode_mode(&self) -> InodeMode {
+        InodeMode::from_bits_truncate(0o644)
     }
 
-    fn set_parent(&self, parent: Weak<crate::filesystem::devfs::LockedDevFSInode>) {
-        self.inner.lock_irqsave().parent = parent;
-    }
-}
-
-impl IndexNode for Ps2MouseDevice {
     fn open(
         &self,
         _data: MutexGuard<FilePrivateData>,
-        _mode: &crate::filesystem::vfs::file::FileFlags,
+        _flags: &FileFlags,
     ) -> Result<(), SystemError> {
         let mut guard = self.inner.lock_irqsave();
         guard.buf.clear();
@@ -614,7 +575,7 @@ impl IndexNode for Ps2MouseDevice {
         Ok(())
     }
 
-    fn read_at(
+    fn read(
         &self,
         _offset: usize,
         _len: usize,
@@ -633,7 +594,7 @@ impl IndexNode for Ps2MouseDevice {
         }
     }
 
-    fn write_at(
+    fn write(
         &self,
         _offset: usize,
         _len: usize,
@@ -642,44 +603,6 @@ impl IndexNode for Ps2MouseDevice {
     ) -> Result<usize, SystemError> {
         return Err(SystemError::ENOSYS);
     }
-
-    fn fs(&self) -> Arc<dyn FileSystem> {
-        self.inner
-            .lock_irqsave()
-            .device_inode_fs
-            .as_ref()
-            .unwrap()
-            .upgrade()
-            .unwrap()
-    }
-
-    fn as_any_ref(&self) -> &dyn core::any::Any {
-        self
-    }
-
-    fn list(&self) -> Result<Vec<alloc::string::String>, SystemError> {
-        todo!()
-    }
-
-    fn metadata(&self) -> Result<Metadata, SystemError> {
-        Ok(self.inner.lock_irqsave().devfs_metadata.clone())
-    }
-
-    fn resize(&self, _len: usize) -> Result<(), SystemError> {
-        Ok(())
-    }
-
-    fn dname(&self) -> Result<DName, SystemError> {
-        Ok(DName::from(self.name()))
-    }
-
-    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
-        let guard = self.inner.lock_irqsave();
-        if let Some(parent) = guard.parent.upgrade() {
-            return Ok(parent);
-        }
-        Err(SystemError::ENOENT)
-    }
 }
 
 impl Ps2Device for Ps2MouseDevice {}
@@ -692,9 +615,9 @@ pub fn rs_ps2_mouse_device_init(dev_parent: Arc<dyn Device>) -> Result<(), Syste
     psmouse.set_dev_parent(Some(Arc::downgrade(&dev_parent)));
     serio_device_manager().register_port(psmouse.clone() as Arc<dyn SerioDevice>)?;
 
-    devfs_register(&psmouse.name(), psmouse.clone()).map_err(|e| {
+    char_dev_manager().register(psmouse.clone()).map_err(|e| {
         error!(
-            "register psmouse device '{}' to devfs failed: {:?}",
+            "register psmouse char device '{}' failed: {:?}",
             psmouse.name(),
             e
         );
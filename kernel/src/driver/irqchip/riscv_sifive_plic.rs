@@ -24,7 +24,7 @@ use alloc::{
     sync::{Arc, Weak},
 };
 use bitmap::AllocBitmap;
-use fdt::node::FdtNode;
+use fdt::{node::FdtNode, Fdt};
 use log::{debug, warn};
 use system_error::SystemError;
 
@@ -217,16 +217,16 @@ impl PlicIrqChip {
      * Each hart context has a vector of interrupt enable bits associated with it.
      * There's one bit for each interrupt source.
      */
-    const CONTEXT_ENABLE_BASE: usize = 0x2080;
-    const CONTEXT_ENABLE_SIZE: usize = 0x100;
+    const CONTEXT_ENABLE_BASE: usize = 0x2000;
+    const CONTEXT_ENABLE_SIZE: usize = 0x80;
 
     /*
      * Each hart context has a set of control registers associated with it.  Right
      * now there's only two: a source priority threshold over which the hart will
      * take an interrupt, and a register to claim interrupts.
      */
-    const CONTEXT_BASE: usize = 0x201000;
-    const CONTEXT_SIZE: usize = 0x2000;
+    const CONTEXT_BASE: usize = 0x200000;
+    const CONTEXT_SIZE: usize = 0x1000;
     const CONTEXT_THRESHOLD: usize = 0x00;
     const CONTEXT_CLAIM: usize = 0x04;
 
@@ -234,6 +234,9 @@ impl PlicIrqChip {
     const PLIC_ENABLE_THRESHOLD: u32 = 0;
 
     const PLIC_QUIRK_EDGE_INTERRUPT: u32 = 0;
+
+    /// 上下文对应的hart本地中断号：S态外部中断
+    const IRQ_S_EXT: u32 = 9;
 }
 
 impl IrqChip for PlicIrqChip {
@@ -462,10 +465,12 @@ fn do_riscv_sifive_plic_init(fdt_node: &FdtNode) -> Result<(), SystemError> {
         "plic: node: {}, irq_num: {irq_num}, paddr: {paddr:?}, size: {size}",
         fdt_node.name
     );
-    let nr_contexts = fdt_node
-        .interrupts_extended()
+    // 每个上下文对应一对(hart的intc的phandle, hart本地中断号)，cpu intc的#interrupt-cells为1
+    let contexts = fdt_node
+        .property("interrupts-extended")
         .ok_or(SystemError::EINVAL)?
-        .count();
+        .value;
+    let nr_contexts = (contexts.len() / 8).min(PlicIrqChip::MAX_CONTEXTS as usize);
     debug!("plic: nr_contexts: {nr_contexts}");
 
     let irq_domain = irq_domain_manager()
@@ -499,15 +504,29 @@ fn do_riscv_sifive_plic_init(fdt_node: &FdtNode) -> Result<(), SystemError> {
         }
     };
 
-    // todo: 学习linux那样处理，获取到hartid，这里暂时糊代码
-    // linux: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/irqchip/irq-sifive-plic.c#458
-    for i in smp_cpu_manager().present_cpus().iter_cpu() {
-        let i = i.data() as usize;
+    // 参考 https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/irqchip/irq-sifive-plic.c#458
+    let fdt = open_firmware_fdt_driver().fdt_ref()?;
+    for (i, ctx) in contexts.chunks_exact(8).take(nr_contexts).enumerate() {
+        let intc_phandle = u32::from_be_bytes(ctx[0..4].try_into().unwrap());
+        let hwirq = u32::from_be_bytes(ctx[4..8].try_into().unwrap());
+        // 只使用S态外部中断的上下文，M态的上下文属于SBI固件
+        if hwirq != PlicIrqChip::IRQ_S_EXT {
+            continue;
+        }
+        let cpu = match plic_context_cpu(&fdt, intc_phandle) {
+            Some(cpu) => cpu,
+            None => {
+                warn!("plic: context {i}: can't find the hart of intc {intc_phandle}");
+                continue;
+            }
+        };
+        if smp_cpu_manager().present_cpus().get(cpu) != Some(true) {
+            continue;
+        }
 
-        let cpu = ProcessorId::new(i as u32);
         let handler = unsafe { plic_handlers().force_get(cpu) };
         if handler.present() {
-            warn!("plic: handler {i} already present.");
+            warn!("plic: handler {cpu:?} already present.");
             handler.set_threshold(PlicIrqChip::PLIC_ENABLE_THRESHOLD);
             loop_done_setup(handler);
             continue;
@@ -542,6 +561,24 @@ fn do_riscv_sifive_plic_init(fdt_node: &FdtNode) -> Result<(), SystemError> {
     Ok(())
 }
 
+/// # 查找PLIC上下文所属的CPU
+///
+/// ## 参数
+/// - `intc_phandle`: 上下文的中断父节点，也就是某个hart的`riscv,cpu-intc`节点
+///
+/// ## 返回值
+/// 这个hart对应的CPU，这里CPU的编号就是hart id
+fn plic_context_cpu(fdt: &Fdt, intc_phandle: u32) -> Option<ProcessorId> {
+    let cpu_node = fdt.all_nodes().find(|node| {
+        node.name.starts_with("cpu@")
+            && node.children().any(|child| {
+                child.property("phandle").and_then(|x| x.as_usize()) == Some(intc_phandle as usize)
+            })
+    })?;
+    let hartid = cpu_node.reg()?.next()?.starting_address as usize;
+    Some(ProcessorId::new(hartid as u32))
+}
+
 /// 把设备的中断与PLIC的关联起来
 fn associate_irq_with_plic_domain(
     irq_domain: &Arc<IrqDomain>,
@@ -21,7 +21,7 @@ use crate::{
         InterruptArch, IrqNumber,
     },
     filesystem::{
-        devfs::{devfs_register, DevFS, DeviceINode, LockedDevFSInode},
+        devfs::{devfs_register, DevFS, DevNodeAttr, DeviceINode, LockedDevFSInode},
         vfs::{
             file::FileFlags, vcore::generate_inode_id, FilePrivateData, FileSystem, FileType,
             IndexNode, InodeFlags, InodeMode, Metadata,
@@ -67,6 +67,8 @@ pub struct PS2KeyBoardInode {
     fs: Weak<DevFS>,
     /// INode 元数据
     metadata: Metadata,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
 }
 
 impl LockedPS2KeyBoardInode {
@@ -76,6 +78,7 @@ impl LockedPS2KeyBoardInode {
             self_ref: Weak::default(),
             parent: Weak::default(),
             fs: Weak::default(),
+            attr: DevNodeAttr::new(InodeMode::from_bits_truncate(0o666)),
             metadata: Metadata {
                 dev_id: 1,
                 inode_id: generate_inode_id(),
@@ -153,7 +156,10 @@ impl IndexNode for LockedPS2KeyBoardInode {
     }
 
     fn metadata(&self) -> Result<Metadata, SystemError> {
-        return Ok(self.0.read().metadata.clone());
+        let inode = self.0.read();
+        let mut metadata = inode.metadata.clone();
+        inode.attr.apply(&mut metadata);
+        Ok(metadata)
     }
 
     fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
@@ -162,9 +168,7 @@ impl IndexNode for LockedPS2KeyBoardInode {
         inode.metadata.mtime = metadata.mtime;
         inode.metadata.ctime = metadata.ctime;
         inode.metadata.btime = metadata.btime;
-        inode.metadata.mode = metadata.mode;
-        inode.metadata.uid = metadata.uid;
-        inode.metadata.gid = metadata.gid;
+        inode.attr.update(metadata);
 
         return Ok(());
     }
@@ -56,6 +56,8 @@ const E1000E_REG_SIZE: u8 = 4;
 
 // TxBuffer和RxBuffer的大小(DMA页)
 const E1000E_DMA_PAGES: usize = 1;
+/// 一个收发buffer的最大长度
+pub const E1000E_BUFFER_MAX_LEN: usize = PAGE_SIZE;
 
 // 中断相关
 const E1000E_RECV_VECTOR: IrqNumber = IrqNumber::new(57);
@@ -32,9 +32,9 @@ use core::{
 };
 use log::info;
 use smoltcp::{phy, wire::HardwareAddress};
-// use system_error::SystemError;
+use system_error::SystemError;
 
-use super::e1000e::{E1000EBuffer, E1000EDevice};
+use super::e1000e::{E1000EBuffer, E1000EDevice, E1000E_BUFFER_MAX_LEN};
 use super::irq::e1000e_irq_manager;
 use crate::driver::base::device::DeviceId;
 
@@ -394,6 +394,22 @@ impl Iface for E1000EInterface {
         self.common.poll_napi(self.driver.force_get_mut(), budget)
     }
 
+    fn poll_transmit(&self, frame: &[u8]) -> Result<(), SystemError> {
+        if frame.is_empty() || frame.len() > E1000E_BUFFER_MAX_LEN {
+            return Err(SystemError::EINVAL);
+        }
+        let mut device = self
+            .driver
+            .inner
+            .try_lock_irqsave()
+            .map_err(|_| SystemError::EAGAIN_OR_EWOULDBLOCK)?;
+        let mut buffer = E1000EBuffer::new(frame.len());
+        buffer.as_mut_slice().copy_from_slice(frame);
+        // 发送完成的buffer由发送描述符环在复用这个位置时回收
+        device.e1000e_transmit(buffer);
+        Ok(())
+    }
+
     fn addr_assign_type(&self) -> u8 {
         return self.inner().netdevice_common.addr_assign_type;
     }
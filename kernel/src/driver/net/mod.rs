@@ -24,9 +24,11 @@ mod dma;
 pub mod e1000e;
 pub mod loopback;
 pub mod napi;
+pub mod netconsole;
 pub mod sysfs;
 pub mod types;
 pub mod veth;
+pub mod virt_wifi;
 pub mod virtio_net;
 
 bitflags! {
@@ -115,6 +117,18 @@ pub trait Iface: crate::driver::base::device::Device {
         false
     }
 
+    /// # `poll_transmit`
+    /// 不经过协议栈，直接把一个完整的以太网帧交给网卡发送
+    ///
+    /// 供netconsole等在中断上下文、甚至panic时也要发送数据的场景使用，实现中不能睡眠，
+    /// 也不能等待锁：网卡正被占用时应返回`EAGAIN`，由调用者决定是否丢弃这一帧。
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::ENOSYS)`：网卡不支持（默认实现）
+    fn poll_transmit(&self, _frame: &[u8]) -> Result<(), SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
     /// # `update_ip_addrs`
     /// 用于更新接口的 IP 地址
     /// ## 参数
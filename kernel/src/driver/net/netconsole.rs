@@ -0,0 +1,369 @@
+//! 网络控制台（netconsole）
+//!
+//! 把内核日志以UDP数据报的形式发送到远端主机，用于在没有串口的机器上收集日志和panic信息。
+//! 通过内核命令行参数配置，格式与Linux相同：
+//!
+//! ```text
+//! netconsole=[src-port]@[src-ip]/[dev],[tgt-port]@<tgt-ip>/[tgt-macaddr]
+//! ```
+//!
+//! - `src-port`: 源端口，默认为6665
+//! - `src-ip`: 源地址，默认使用网卡的IPv4地址
+//! - `dev`: 网卡名，默认为第一个以太网卡
+//! - `tgt-port`: 目标端口，默认为6666
+//! - `tgt-ip`: 目标地址，必须指定
+//! - `tgt-macaddr`: 目标主机（或网关）的MAC地址，默认为广播地址
+//!
+//! 例如`netconsole=@/eth0,6666@10.0.2.2/`，然后在目标主机上用`nc -u -l 6666`接收。
+//!
+//! 发送时不经过协议栈，直接构造以太网帧并通过[`Iface::poll_transmit`]交给网卡。
+//! 整个过程不会睡眠，等待锁的次数也有上限，因此在中断上下文和panic时也能使用，
+//! 获取不到锁时丢弃这一部分日志。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/net/netconsole.c
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+use core::{
+    net::Ipv4Addr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use log::{info, warn};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    driver::net::{types::InterfaceType, Iface},
+    init::initcall::INITCALL_LATE,
+    libs::spinlock::{SpinLock, SpinLockGuard},
+    process::namespace::net_namespace::INIT_NET_NAMESPACE,
+};
+
+kernel_cmdline_param_kv!(NETCONSOLE_PARAM, netconsole, "");
+
+const DEFAULT_LOCAL_PORT: u16 = 6665;
+const DEFAULT_REMOTE_PORT: u16 = 6666;
+
+/// 一个数据报中最多携带的日志字节数，与Linux相同
+const MAX_PRINT_CHUNK: usize = 1000;
+
+const ETH_HLEN: usize = 14;
+const IPV4_HLEN: usize = 20;
+const UDP_HLEN: usize = 8;
+const HDR_LEN: usize = ETH_HLEN + IPV4_HLEN + UDP_HLEN;
+/// 以太网帧的最小长度（不含FCS），不足时补0
+const ETH_ZLEN: usize = 60;
+const FRAME_MAX: usize = HDR_LEN + MAX_PRINT_CHUNK;
+
+/// 等待锁或网卡时的最大重试次数
+const MAX_RETRIES: usize = 1000;
+
+/// 命令行中的配置
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NetconsoleConfig {
+    dev: Option<String>,
+    local_port: u16,
+    local_ip: Option<Ipv4Addr>,
+    remote_port: u16,
+    remote_ip: Ipv4Addr,
+    remote_mac: [u8; 6],
+}
+
+impl NetconsoleConfig {
+    /// 解析`[src-port]@[src-ip]/[dev],[tgt-port]@<tgt-ip>/[tgt-macaddr]`
+    fn parse(s: &str) -> Result<Self, SystemError> {
+        let (local, remote) = s.split_once(',').ok_or(SystemError::EINVAL)?;
+
+        let (local_port, rest) = local.split_once('@').ok_or(SystemError::EINVAL)?;
+        let (local_ip, dev) = rest.split_once('/').unwrap_or((rest, ""));
+
+        let (remote_port, rest) = remote.split_once('@').ok_or(SystemError::EINVAL)?;
+        let (remote_ip, remote_mac) = rest.split_once('/').unwrap_or((rest, ""));
+
+        Ok(Self {
+            dev: (!dev.is_empty()).then(|| dev.to_string()),
+            local_port: parse_port(local_port, DEFAULT_LOCAL_PORT)?,
+            local_ip: match local_ip {
+                "" => None,
+                ip => Some(ip.parse().map_err(|_| SystemError::EINVAL)?),
+            },
+            remote_port: parse_port(remote_port, DEFAULT_REMOTE_PORT)?,
+            remote_ip: remote_ip.parse().map_err(|_| SystemError::EINVAL)?,
+            remote_mac: match remote_mac {
+                "" => [0xff; 6],
+                mac => parse_mac(mac)?,
+            },
+        })
+    }
+}
+
+fn parse_port(s: &str, default: u16) -> Result<u16, SystemError> {
+    if s.is_empty() {
+        return Ok(default);
+    }
+    s.parse().map_err(|_| SystemError::EINVAL)
+}
+
+fn parse_mac(s: &str) -> Result<[u8; 6], SystemError> {
+    let mut mac = [0u8; 6];
+    let mut parts = s.split(':');
+    for byte in mac.iter_mut() {
+        let part = parts.next().ok_or(SystemError::EINVAL)?;
+        if part.len() != 2 {
+            return Err(SystemError::EINVAL);
+        }
+        *byte = u8::from_str_radix(part, 16).map_err(|_| SystemError::EINVAL)?;
+    }
+    if parts.next().is_some() {
+        return Err(SystemError::EINVAL);
+    }
+    Ok(mac)
+}
+
+/// 过滤控制台输出中的ANSI转义序列（颜色等）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeState {
+    Normal,
+    /// 刚读到ESC
+    Escape,
+    /// 在`ESC [`之后，直到读到结束字符
+    Csi,
+}
+
+struct Netconsole {
+    config: NetconsoleConfig,
+    iface: Arc<dyn Iface>,
+    local_mac: [u8; 6],
+    /// 未指定源地址，并且绑定时网卡还没有地址时为`UNSPECIFIED`，之后会再尝试获取
+    local_ip: Ipv4Addr,
+    ip_ident: u16,
+    escape: EscapeState,
+    /// 还没有发送的日志，遇到换行或者写满时发送
+    line: [u8; MAX_PRINT_CHUNK],
+    line_len: usize,
+    frame: [u8; FRAME_MAX],
+}
+
+impl Netconsole {
+    fn write(&mut self, s: &[u8]) -> Result<(), SystemError> {
+        for &b in s {
+            match self.escape {
+                EscapeState::Normal if b == 0x1b => {
+                    self.escape = EscapeState::Escape;
+                    continue;
+                }
+                EscapeState::Normal => {}
+                EscapeState::Escape => {
+                    self.escape = if b == b'[' {
+                        EscapeState::Csi
+                    } else {
+                        EscapeState::Normal
+                    };
+                    continue;
+                }
+                EscapeState::Csi => {
+                    if (0x40..=0x7e).contains(&b) {
+                        self.escape = EscapeState::Normal;
+                    }
+                    continue;
+                }
+            }
+
+            self.line[self.line_len] = b;
+            self.line_len += 1;
+            if b == b'\n' || self.line_len == MAX_PRINT_CHUNK {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 把缓冲的日志作为一个UDP数据报发送出去
+    fn flush(&mut self) -> Result<(), SystemError> {
+        let len = core::mem::take(&mut self.line_len);
+        if len == 0 {
+            return Ok(());
+        }
+        if self.local_ip.is_unspecified() {
+            // 网卡的地址可能是在绑定之后才配置的。这里不能等待锁，获取不到时仍然使用0.0.0.0
+            if let Some(ip) = self
+                .iface
+                .smol_iface()
+                .try_lock()
+                .ok()
+                .and_then(|iface| iface.ipv4_addr())
+            {
+                self.local_ip = ip;
+            }
+        }
+        self.ip_ident = self.ip_ident.wrapping_add(1);
+
+        let udp_len = UDP_HLEN + len;
+        let ip_len = IPV4_HLEN + udp_len;
+        let frame_len = (ETH_HLEN + ip_len).max(ETH_ZLEN);
+        let frame = &mut self.frame[..frame_len];
+        frame.fill(0);
+
+        let (eth, rest) = frame.split_at_mut(ETH_HLEN);
+        eth[0..6].copy_from_slice(&self.config.remote_mac);
+        eth[6..12].copy_from_slice(&self.local_mac);
+        eth[12..14].copy_from_slice(&0x0800u16.to_be_bytes());
+
+        let (ip, rest) = rest.split_at_mut(IPV4_HLEN);
+        ip[0] = 0x45;
+        ip[2..4].copy_from_slice(&(ip_len as u16).to_be_bytes());
+        ip[4..6].copy_from_slice(&self.ip_ident.to_be_bytes());
+        // Don't Fragment
+        ip[6] = 0x40;
+        ip[8] = 64;
+        ip[9] = 17;
+        ip[12..16].copy_from_slice(&self.local_ip.octets());
+        ip[16..20].copy_from_slice(&self.config.remote_ip.octets());
+        let ip_csum = csum_fold(csum_add(0, ip));
+        ip[10..12].copy_from_slice(&ip_csum.to_be_bytes());
+
+        let udp = &mut rest[..udp_len];
+        udp[0..2].copy_from_slice(&self.config.local_port.to_be_bytes());
+        udp[2..4].copy_from_slice(&self.config.remote_port.to_be_bytes());
+        udp[4..6].copy_from_slice(&(udp_len as u16).to_be_bytes());
+        udp[UDP_HLEN..].copy_from_slice(&self.line[..len]);
+        // 伪首部 + UDP首部 + 数据
+        let mut sum = csum_add(0, &self.local_ip.octets());
+        sum = csum_add(sum, &self.config.remote_ip.octets());
+        sum = csum_add(sum, &[0, 17]);
+        sum = csum_add(sum, &(udp_len as u16).to_be_bytes());
+        sum = csum_add(sum, udp);
+        let udp_csum = match csum_fold(sum) {
+            0 => 0xffff,
+            csum => csum,
+        };
+        udp[6..8].copy_from_slice(&udp_csum.to_be_bytes());
+
+        for _ in 0..MAX_RETRIES {
+            match self.iface.poll_transmit(&self.frame[..frame_len]) {
+                Err(SystemError::EAGAIN_OR_EWOULDBLOCK) => core::hint::spin_loop(),
+                r => return r,
+            }
+        }
+        Err(SystemError::EAGAIN_OR_EWOULDBLOCK)
+    }
+}
+
+fn csum_add(mut sum: u32, data: &[u8]) -> u32 {
+    for chunk in data.chunks(2) {
+        let word = match *chunk {
+            [hi, lo] => u16::from_be_bytes([hi, lo]),
+            [hi] => u16::from_be_bytes([hi, 0]),
+            _ => unreachable!(),
+        };
+        sum += word as u32;
+    }
+    sum
+}
+
+fn csum_fold(mut sum: u32) -> u16 {
+    while (sum >> 16) != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+static NETCONSOLE: SpinLock<Option<Netconsole>> = SpinLock::new(None);
+
+/// 为`false`时[`netconsole_write`]直接返回
+static NETCONSOLE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 获取锁，失败次数达到上限时放弃，避免在panic时死锁
+fn try_lock_bounded<T>(lock: &SpinLock<T>) -> Option<SpinLockGuard<'_, T>> {
+    for _ in 0..MAX_RETRIES {
+        if let Ok(guard) = lock.try_lock_irqsave() {
+            return Some(guard);
+        }
+        core::hint::spin_loop();
+    }
+    None
+}
+
+/// 控制台输出，发送到netconsole的目标主机
+#[inline]
+pub fn netconsole_write(s: &str) {
+    if !NETCONSOLE_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(mut guard) = try_lock_bounded(&NETCONSOLE) else {
+        return;
+    };
+    let Some(nc) = guard.as_mut() else {
+        return;
+    };
+    if let Err(SystemError::ENOSYS) = nc.write(s.as_bytes()) {
+        NETCONSOLE_ENABLED.store(false, Ordering::SeqCst);
+        let name = nc.iface.iface_name();
+        drop(guard);
+        warn!(
+            "netconsole: {} does not support polling transmit, disabled",
+            name
+        );
+    }
+}
+
+#[unified_init(INITCALL_LATE)]
+fn netconsole_init() -> Result<(), SystemError> {
+    let Some(param) = NETCONSOLE_PARAM.value_str().filter(|s| !s.is_empty()) else {
+        return Ok(());
+    };
+    let config = match NetconsoleConfig::parse(param) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("netconsole: invalid parameter '{}'", param);
+            return Err(e);
+        }
+    };
+
+    let iface = INIT_NET_NAMESPACE
+        .device_list()
+        .values()
+        .find(|iface| match &config.dev {
+            Some(dev) => iface.iface_name() == *dev,
+            None => iface.type_() == InterfaceType::EETHER,
+        })
+        .cloned();
+    let Some(iface) = iface else {
+        warn!(
+            "netconsole: network device {} not found",
+            config.dev.as_deref().unwrap_or("(ethernet)")
+        );
+        return Err(SystemError::ENODEV);
+    };
+
+    let local_ip = config
+        .local_ip
+        .or_else(|| iface.common().ipv4_addr())
+        .unwrap_or(Ipv4Addr::UNSPECIFIED);
+    info!(
+        "netconsole: {}:{} -> {}:{} via {}",
+        local_ip,
+        config.local_port,
+        config.remote_ip,
+        config.remote_port,
+        iface.iface_name()
+    );
+
+    *NETCONSOLE.lock_irqsave() = Some(Netconsole {
+        local_mac: iface.mac().0,
+        iface,
+        config,
+        local_ip,
+        ip_ident: 0,
+        escape: EscapeState::Normal,
+        line: [0; MAX_PRINT_CHUNK],
+        line_len: 0,
+        frame: [0; FRAME_MAX],
+    });
+    NETCONSOLE_ENABLED.store(true, Ordering::SeqCst);
+    info!("netconsole: enabled");
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! 模拟的无线网卡（virt_wifi）
+//!
+//! 不对应任何硬件，周围"存在"几个固定的接入点，扫描、连接和密钥操作都立即完成，
+//! 用于在没有无线硬件的环境中测试无线核心和nl80211。只模拟管理面，没有数据通路。
+//!
+//! 设备数量由内核命令行参数`virt_wifi`指定，默认为1，为0时不创建设备。
+
+use alloc::{sync::Arc, vec::Vec};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    init::initcall::INITCALL_DEVICE,
+    net::wireless::{
+        wireless_register, Bss, Cfg80211Ops, ConnectParams, KeyParams, MacAddr, ScanRequest,
+        WirelessDev, WLAN_CAPABILITY_ESS, WLAN_CAPABILITY_PRIVACY, WLAN_STATUS_SUCCESS,
+        WLAN_STATUS_UNSPECIFIED_FAILURE,
+    },
+};
+
+kernel_cmdline_param_kv!(VIRT_WIFI_PARAM, virt_wifi, "");
+
+const DEFAULT_VIRT_WIFI_NR: usize = 1;
+const MAX_VIRT_WIFI_NR: usize = 16;
+
+/// 模拟的接入点
+struct VirtAp {
+    ssid: &'static [u8],
+    bssid: MacAddr,
+    freq: u32,
+    signal_mbm: i32,
+    /// 是否为WPA2-PSK加密的网络
+    secured: bool,
+}
+
+const VIRT_APS: &[VirtAp] = &[
+    VirtAp {
+        ssid: b"VirtWifi",
+        bssid: [0x02, 0x00, 0x00, 0xaa, 0x00, 0x01],
+        freq: 2437,
+        signal_mbm: -5000,
+        secured: false,
+    },
+    VirtAp {
+        ssid: b"VirtWifi-WPA2",
+        bssid: [0x02, 0x00, 0x00, 0xaa, 0x00, 0x02],
+        freq: 5180,
+        signal_mbm: -6000,
+        secured: true,
+    },
+];
+
+impl VirtAp {
+    /// beacon中的信息元素：SSID，加密网络再加上RSN（WPA2-PSK，CCMP）
+    fn ies(&self) -> Vec<u8> {
+        let mut ies = Vec::with_capacity(2 + self.ssid.len() + 22);
+        ies.push(0);
+        ies.push(self.ssid.len() as u8);
+        ies.extend_from_slice(self.ssid);
+        if self.secured {
+            ies.extend_from_slice(&[
+                48, 20, // RSN
+                1, 0, // 版本
+                0x00, 0x0f, 0xac, 4, // 组播加密套件：CCMP
+                1, 0, 0x00, 0x0f, 0xac, 4, // 单播加密套件：CCMP
+                1, 0, 0x00, 0x0f, 0xac, 2, // 认证方式：PSK
+                0, 0, // RSN能力
+            ]);
+        }
+        ies
+    }
+
+    fn to_bss(&self) -> Bss {
+        let mut capability = WLAN_CAPABILITY_ESS;
+        if self.secured {
+            capability |= WLAN_CAPABILITY_PRIVACY;
+        }
+        Bss {
+            bssid: self.bssid,
+            ssid: self.ssid.to_vec(),
+            freq: self.freq,
+            signal_mbm: self.signal_mbm,
+            beacon_interval: 100,
+            capability,
+            ies: self.ies(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct VirtWifi;
+
+impl Cfg80211Ops for VirtWifi {
+    fn scan(&self, wdev: &Arc<WirelessDev>, req: &ScanRequest) -> Result<(), SystemError> {
+        // 接入点都不隐藏SSID，指定了SSID的主动扫描也能收到所有接入点的beacon，只按频率过滤
+        for ap in VIRT_APS {
+            if !req.freqs.is_empty() && !req.freqs.contains(&ap.freq) {
+                continue;
+            }
+            wdev.inform_bss(ap.to_bss());
+        }
+        wdev.scan_done(false);
+        Ok(())
+    }
+
+    fn connect(&self, wdev: &Arc<WirelessDev>, params: &ConnectParams) -> Result<(), SystemError> {
+        let ap = VIRT_APS.iter().find(|ap| {
+            ap.ssid == params.ssid.as_slice()
+                && params.bssid.is_none_or(|b| b == ap.bssid)
+                && params.freq.is_none_or(|f| f == ap.freq)
+        });
+        match ap {
+            Some(ap) if ap.secured == params.privacy => {
+                wdev.inform_bss(ap.to_bss());
+                wdev.connect_result(Some(ap.bssid), WLAN_STATUS_SUCCESS);
+            }
+            Some(ap) => wdev.connect_result(Some(ap.bssid), WLAN_STATUS_UNSPECIFIED_FAILURE),
+            None => wdev.connect_result(None, WLAN_STATUS_UNSPECIFIED_FAILURE),
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self, _wdev: &Arc<WirelessDev>, _reason: u16) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn add_key(&self, wdev: &Arc<WirelessDev>, key: &KeyParams) -> Result<(), SystemError> {
+        log::debug!(
+            "virt_wifi: {}: install key {} cipher {:#x}",
+            wdev.name(),
+            key.idx,
+            key.cipher
+        );
+        Ok(())
+    }
+
+    fn del_key(
+        &self,
+        _wdev: &Arc<WirelessDev>,
+        _idx: u8,
+        _mac: Option<MacAddr>,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+}
+
+#[unified_init(INITCALL_DEVICE)]
+fn virt_wifi_init() -> Result<(), SystemError> {
+    let nr = VIRT_WIFI_PARAM
+        .value_str()
+        .and_then(|s| s.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_VIRT_WIFI_NR)
+        .min(MAX_VIRT_WIFI_NR);
+
+    let ops = Arc::new(VirtWifi);
+    for i in 0..nr {
+        wireless_register([0x02, 0x00, 0x00, 0x00, i as u8, 0x00], ops.clone());
+    }
+    Ok(())
+}
@@ -784,6 +784,17 @@ impl Iface for VirtioInterface {
             .poll_napi(self.device_inner.force_get_mut(), budget)
     }
 
+    fn poll_transmit(&self, frame: &[u8]) -> Result<(), SystemError> {
+        let mut driver_net = self
+            .device_inner
+            .inner
+            .try_lock_irqsave()
+            .map_err(|_| SystemError::EAGAIN_OR_EWOULDBLOCK)?;
+        let mut tx_buf = driver_net.new_tx_buffer(frame.len());
+        tx_buf.packet_mut().copy_from_slice(frame);
+        driver_net.send(tx_buf).map_err(|_| SystemError::EIO)
+    }
+
     // fn as_any_ref(&'static self) -> &'static dyn core::any::Any {
     //     return self;
     // }
@@ -2,14 +2,15 @@ use alloc::{
     string::ToString,
     sync::{Arc, Weak},
 };
-use log::info;
+use log::{info, warn};
 use system_error::SystemError;
 use unified_init::macros::unified_init;
 
 use crate::{
     driver::base::{
+        char::{manager::char_dev_manager, CharDevOps},
         class::{class_manager, Class},
-        device::{device_manager, sys_dev_char_kobj},
+        device::{device_manager, device_number::DeviceNumber, sys_dev_char_kobj},
         kobject::KObject,
         subsys::SubSysPrivate,
     },
@@ -30,14 +31,29 @@ pub fn sys_class_rtc_instance() -> Option<&'static Arc<RtcClass>> {
     unsafe { CLASS_RTC_INSTANCE.as_ref() }
 }
 
+/// rtc字符设备最多支持的设备数量
+const RTC_DEV_MAX: u32 = 16;
+
+/// rtc字符设备号区间的起始设备号（次设备号为0）
+static mut RTC_DEVT: Option<DeviceNumber> = None;
+
+/// 获取rtc字符设备号区间的起始设备号
+#[inline(always)]
+pub(super) fn rtc_devt() -> Option<DeviceNumber> {
+    unsafe { RTC_DEVT }
+}
+
 /// 初始化帧缓冲区子系统
 #[unified_init(INITCALL_SUBSYS)]
 pub fn fbmem_init() -> Result<(), SystemError> {
     let rtc_class = RtcClass::new();
     class_manager().class_register(&(rtc_class.clone() as Arc<dyn Class>))?;
 
+    let devt = CharDevOps::alloc_chardev_region(0, RTC_DEV_MAX, "rtc")?;
+
     unsafe {
         CLASS_RTC_INSTANCE = Some(rtc_class);
+        RTC_DEVT = Some(devt);
     }
 
     return Ok(());
@@ -87,6 +103,14 @@ impl Class for RtcClass {
 /// 注册rtc通用设备
 pub(super) fn rtc_register_device(dev: &Arc<RtcGeneralDevice>) -> Result<(), SystemError> {
     device_manager().add_device(dev.clone())?;
+    if (dev.id() as u32) < RTC_DEV_MAX {
+        char_dev_manager().register(dev.clone())?;
+    } else {
+        warn!(
+            "{}: too many rtc devices, no char device created",
+            dev.name()
+        );
+    }
     register_default_rtc(dev.clone());
     // 把硬件时间同步到系统时间
     rtc_hctosys(dev);
@@ -0,0 +1,84 @@
+//! rtc的字符设备接口（/dev/rtcN）
+
+use system_error::SystemError;
+
+use crate::{
+    driver::base::char::CharDevice,
+    filesystem::vfs::{
+        ioctl::{ior, iow, IoctlArg},
+        FilePrivateData, InodeMode,
+    },
+    libs::mutex::MutexGuard,
+    process::{cred::CAPFlags, ProcessManager},
+};
+
+use super::{
+    interface::{rtc_read_time, rtc_set_time},
+    sysfs::RtcGeneralDevice,
+    RtcTime,
+};
+
+/// rtc设备的ioctl命令
+///
+/// 参考 Linux include/uapi/linux/rtc.h
+struct RtcIoctlCmd;
+
+impl RtcIoctlCmd {
+    /// 读取rtc时间
+    const RTC_RD_TIME: u32 = ior::<RtcTime>(b'p', 0x09);
+    /// 设置rtc时间
+    const RTC_SET_TIME: u32 = iow::<RtcTime>(b'p', 0x0a);
+}
+
+impl CharDevice for RtcGeneralDevice {
+    fn devnode_mode(&self) -> InodeMode {
+        InodeMode::from_bits_truncate(0o600)
+    }
+
+    fn read(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        // TODO: 支持通过read等待rtc中断
+        Err(SystemError::ENOSYS)
+    }
+
+    fn write(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EINVAL)
+    }
+
+    fn ioctl(
+        &self,
+        cmd: u32,
+        arg: usize,
+        data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        drop(data);
+        match cmd {
+            RtcIoctlCmd::RTC_RD_TIME => {
+                let time = rtc_read_time(self)?;
+                IoctlArg::new(cmd, arg).write(&time)?;
+                Ok(0)
+            }
+            RtcIoctlCmd::RTC_SET_TIME => {
+                let cred = ProcessManager::current_pcb().cred();
+                if !cred.has_capability(CAPFlags::CAP_SYS_TIME) {
+                    return Err(SystemError::EACCES);
+                }
+                let time: RtcTime = IoctlArg::new(cmd, arg).read()?;
+                rtc_set_time(self, &time)?;
+                Ok(0)
+            }
+            _ => Err(SystemError::ENOIOCTLCMD),
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use alloc::sync::Arc;
 use system_error::SystemError;
 
 use crate::driver::base::device::Device;
@@ -6,7 +5,7 @@ use crate::driver::base::device::Device;
 use super::{global_default_rtc, sysfs::RtcGeneralDevice, utils::kobj2rtc_device, RtcTime};
 
 /// 根据rtc general device, 读取真实时间
-pub fn rtc_read_time(general_dev: &Arc<RtcGeneralDevice>) -> Result<RtcTime, SystemError> {
+pub fn rtc_read_time(general_dev: &RtcGeneralDevice) -> Result<RtcTime, SystemError> {
     let class_ops = general_dev.class_ops().ok_or(SystemError::EINVAL)?;
 
     let real_dev = general_dev
@@ -25,6 +24,24 @@ pub fn rtc_read_time(general_dev: &Arc<RtcGeneralDevice>) -> Result<RtcTime, Sys
     return Ok(time);
 }
 
+/// 根据rtc general device, 设置硬件时间
+pub fn rtc_set_time(general_dev: &RtcGeneralDevice, time: &RtcTime) -> Result<(), SystemError> {
+    if !time.valid() {
+        return Err(SystemError::EINVAL);
+    }
+
+    let class_ops = general_dev.class_ops().ok_or(SystemError::EINVAL)?;
+
+    let real_dev = general_dev
+        .dev_parent()
+        .and_then(|p| p.upgrade())
+        .ok_or(SystemError::ENODEV)?;
+
+    let real_dev = kobj2rtc_device(real_dev).ok_or(SystemError::EINVAL)?;
+
+    return class_ops.set_time(&real_dev, time);
+}
+
 /// 从全局默认的rtc时钟那里读取时间
 pub fn rtc_read_time_default() -> Result<RtcTime, SystemError> {
     rtc_read_time(&global_default_rtc().ok_or(SystemError::ENODEV)?)
@@ -13,6 +13,7 @@ use self::sysfs::RtcGeneralDevice;
 use super::base::device::Device;
 
 pub mod class;
+mod dev;
 pub mod interface;
 pub mod rtc_cmos;
 mod sysfs;
@@ -53,6 +54,8 @@ pub trait RtcClassOps: Send + Sync + Debug {
     fn set_time(&self, dev: &Arc<dyn RtcDevice>, time: &RtcTime) -> Result<(), SystemError>;
 }
 
+/// 与Linux的`struct rtc_time`内存布局一致
+#[repr(C)]
 #[derive(Default, Debug, Clone, Copy)]
 pub struct RtcTime {
     /// `second`: 秒，范围从 0 到 59
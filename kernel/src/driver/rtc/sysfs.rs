@@ -9,7 +9,8 @@ use crate::{
     driver::base::{
         class::Class,
         device::{
-            bus::Bus, device_manager, driver::Driver, Device, DeviceCommonData, DeviceType, IdTable,
+            bus::Bus, device_manager, device_number::DeviceNumber, driver::Driver, Device,
+            DeviceCommonData, DeviceType, IdTable,
         },
         kobject::{KObjType, KObject, KObjectCommonData, KObjectState, LockedKObjectState},
         kset::KSet,
@@ -28,7 +29,7 @@ use crate::{
 };
 
 use super::{
-    class::sys_class_rtc_instance,
+    class::{rtc_devt, sys_class_rtc_instance},
     interface::rtc_read_time,
     utils::{kobj2rtc_device, kobj2rtc_general_device},
     GeneralRtcPriority, RtcClassOps, RtcDevice,
@@ -92,6 +93,10 @@ impl RtcGeneralDevice {
         self.inner().class_ops
     }
 
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
     pub fn priority(&self) -> GeneralRtcPriority {
         self.priority
     }
@@ -123,7 +128,8 @@ impl Device for RtcGeneralDevice {
     }
 
     fn id_table(&self) -> IdTable {
-        IdTable::new(self.name.clone(), None)
+        let devt = rtc_devt().map(|devt| DeviceNumber::new(devt.major(), self.id as u32));
+        IdTable::new(self.name.clone(), devt)
     }
 
     fn set_bus(&self, bus: Option<Weak<dyn Bus>>) {
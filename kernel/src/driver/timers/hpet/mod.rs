@@ -33,6 +33,13 @@ impl HpetRegisters {
         (cap >> 8) as usize & 0x1f
     }
 
+    /// HPET 主计数器是否为64位
+    pub fn counter_is_64bit(&self) -> bool {
+        let p = NonNull::new(self as *const HpetRegisters as *mut HpetRegisters).unwrap();
+        let cap = unsafe { volread!(p, capabilties) };
+        (cap & (1 << 13)) != 0
+    }
+
     /// 获取 HPET 计数器的周期
     pub fn counter_clock_period(&self) -> u64 {
         let p = NonNull::new(self as *const HpetRegisters as *mut HpetRegisters).unwrap();
@@ -17,7 +17,7 @@ use crate::{
         epoll::{event_poll::EventPoll, EPollEventType},
         vfs::{file::FileFlags, FilePrivateData, FileSystem, FileType, IndexNode, InodeMode},
     },
-    libs::{casting::DowncastArc, mutex::MutexGuard},
+    libs::mutex::MutexGuard,
     mm::VirtAddr,
     syscall::user_access::UserBufferWriter,
 };
@@ -366,13 +366,8 @@ pub fn ptmx_open(
         return Ok(());
     }
     // 根据当前节点所属的文件系统决定 devpts 根
-    let (pts_root_inode, fsinfo) =
-        if let Some(devpts) = this.fs().clone().downcast_arc::<DevPtsFs>() {
-            let root_inode = devpts.root_inode();
-            (root_inode, devpts)
-        } else {
-            return Err(SystemError::ENODEV);
-        };
+    let fsinfo = this.devpts().ok_or(SystemError::ENODEV)?;
+    let pts_root_inode = fsinfo.root_inode();
 
     let index = fsinfo.alloc_index()?;
 
@@ -11,7 +11,10 @@ use system_error::SystemError;
 
 use crate::{
     driver::{base::device::device_number::DeviceNumber, tty::pty::ptm_driver},
-    filesystem::epoll::{event_poll::LockedEPItemLinkedList, EPollEventType, EPollItem},
+    filesystem::{
+        epoll::{event_poll::LockedEPItemLinkedList, EPollEventType, EPollItem},
+        vfs::ioctl::IoctlArg,
+    },
     libs::{
         rwlock::{RwLock, RwLockReadGuard, RwLockUpgradableGuard, RwLockWriteGuard},
         spinlock::{SpinLock, SpinLockGuard},
@@ -19,17 +22,14 @@ use crate::{
     },
     mm::VirtAddr,
     process::{pid::Pid, ProcessControlBlock},
-    syscall::user_access::{UserBufferReader, UserBufferWriter},
+    syscall::user_access::UserBufferReader,
 };
 
 use super::{
     termios::{ControlMode, PosixTermios, Termios, TtySetTermiosOpt, WindowSize},
     tty_driver::{TtyCorePrivateField, TtyDriver, TtyDriverSubType, TtyDriverType, TtyOperation},
     tty_job_control::TtyJobCtrlManager,
-    tty_ldisc::{
-        ntty::{NTtyData, NTtyLinediscipline},
-        TtyLineDiscipline,
-    },
+    tty_ldisc::{ntty::NTtyLinediscipline, LineDisciplineType, TtyLineDiscipline},
     tty_port::TtyPort,
     virtual_terminal::{vc_manager, virtual_console::VirtualConsoleData, DrawRegion},
 };
@@ -37,8 +37,8 @@ use super::{
 #[derive(Debug)]
 pub struct TtyCore {
     core: TtyCoreData,
-    /// 线路规程函数集
-    line_discipline: Arc<dyn TtyLineDiscipline>,
+    /// 线路规程函数集，可以通过TIOCSETD切换
+    line_discipline: RwLock<Arc<dyn TtyLineDiscipline>>,
 }
 
 impl Drop for TtyCore {
@@ -79,9 +79,7 @@ impl TtyCore {
         };
         return Arc::new(Self {
             core,
-            line_discipline: Arc::new(NTtyLinediscipline {
-                data: SpinLock::new(NTtyData::new()),
-            }),
+            line_discipline: RwLock::new(Arc::new(NTtyLinediscipline::new())),
         });
     }
 
@@ -100,7 +98,28 @@ impl TtyCore {
 
     #[inline]
     pub fn ldisc(&self) -> Arc<dyn TtyLineDiscipline> {
-        self.line_discipline.clone()
+        self.line_discipline.read().clone()
+    }
+
+    /// # 切换线路规程
+    ///
+    /// 先关闭旧的线路规程再打开新的，新的打开失败时恢复旧的
+    pub fn set_ldisc(tty: Arc<TtyCore>, disc: LineDisciplineType) -> Result<(), SystemError> {
+        let old = tty.ldisc();
+        if old.disc_type() == disc {
+            return Ok(());
+        }
+
+        old.close(tty.clone())?;
+        let new = disc.create();
+        *tty.line_discipline.write() = new.clone();
+        if let Err(e) = new.open(tty.clone()) {
+            *tty.line_discipline.write() = old.clone();
+            let _ = old.open(tty);
+            return Err(e);
+        }
+        tty.core().termios_write().line = disc;
+        Ok(())
     }
 
     pub fn write_to_core(&self, buf: &[u8], nr: usize) -> Result<usize, SystemError> {
@@ -179,13 +198,7 @@ impl TtyCore {
         match cmd {
             TtyIoctlCmd::TCGETS => {
                 let termios = PosixTermios::from_kernel_termios(*real_tty.core.termios());
-                let mut user_writer = UserBufferWriter::new(
-                    VirtAddr::new(arg).as_ptr::<PosixTermios>(),
-                    core::mem::size_of::<PosixTermios>(),
-                    true,
-                )?;
-
-                user_writer.copy_one_to_user(&termios, 0)?;
+                IoctlArg::new(cmd, arg).write(&termios)?;
                 return Ok(0);
             }
             TtyIoctlCmd::TCSETS => {
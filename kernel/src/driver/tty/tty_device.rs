@@ -11,7 +11,7 @@ use crate::{
     arch::ipc::signal::Signal,
     driver::{
         base::{
-            char::CharDevice,
+            char::{manager::char_dev_manager, CharDevice},
             class::Class,
             device::{
                 bus::Bus,
@@ -26,20 +26,14 @@ use crate::{
         serial::serial_init,
     },
     filesystem::{
-        devfs::{devfs_register, DevFS, DeviceINode, LockedDevFSInode},
-        devpts::{DevPtsFs, LockedDevPtsFSInode},
+        devpts::DevPtsFs,
         epoll::EPollItem,
         kernfs::KernFSInode,
-        vfs::{
-            file::FileFlags, utils::DName, FilePrivateData, FileType, IndexNode, InodeMode,
-            Metadata, PollableInode,
-        },
+        vfs::{file::FileFlags, ioctl::IoctlArg, FilePrivateData, InodeMode},
     },
     init::initcall::INITCALL_DEVICE,
     libs::{mutex::MutexGuard, rwlock::RwLock},
-    mm::VirtAddr,
     process::ProcessManager,
-    syscall::user_access::{UserBufferReader, UserBufferWriter},
 };
 
 use super::{
@@ -50,6 +44,7 @@ use super::{
     tty_core::{TtyCore, TtyFlag, TtyIoctlCmd},
     tty_driver::{TtyDriverManager, TtyDriverSubType, TtyDriverType, TtyOperation},
     tty_job_control::TtyJobCtrlManager,
+    tty_ldisc::LineDisciplineType,
     virtual_terminal::vty_init,
 };
 
@@ -63,8 +58,6 @@ pub struct InnerTtyDevice {
     inode: Option<Arc<KernFSInode>>,
     driver: Option<Weak<dyn Driver>>,
     can_match: bool,
-
-    metadata: Metadata,
 }
 
 impl InnerTtyDevice {
@@ -76,14 +69,6 @@ impl InnerTtyDevice {
             inode: None,
             driver: None,
             can_match: false,
-            metadata: {
-                let mut md =
-                    Metadata::new(FileType::CharDevice, InodeMode::from_bits_truncate(0o755));
-                // 对于字符设备，选择与 Linux devpts/devfs 一致的首选 I/O 块大小。
-                // 1024 满足测试期望 (允许 1024 或 4096)。
-                md.blk_size = 1024;
-                md
-            },
         }
     }
 }
@@ -107,42 +92,30 @@ pub struct TtyDevice {
     tty_type: TtyType,
     inner: RwLock<InnerTtyDevice>,
     kobj_state: LockedKObjectState,
-    /// TTY所属的文件系统
-    fs: RwLock<TtyDeviceFs>,
-    parent: RwLock<TtyDeviceParent>,
-}
-
-pub enum TtyDeviceFs {
-    DevFs(Weak<DevFS>),
-    DevPtsFs(Weak<DevPtsFs>),
-    None,
-}
-pub enum TtyDeviceParent {
-    DevFS(Weak<LockedDevFSInode>),
-    DevPts(Weak<LockedDevPtsFSInode>),
-    None,
+    /// ptmx所在的devpts实例，打开ptmx时在其中分配pty
+    devpts: RwLock<Weak<DevPtsFs>>,
 }
 
 impl TtyDevice {
     pub fn new(name: String, id_table: IdTable, tty_type: TtyType) -> Arc<TtyDevice> {
-        let dev_num = id_table.device_number();
-        let dev = TtyDevice {
+        Arc::new(TtyDevice {
             name,
             id_table,
             inner: RwLock::new(InnerTtyDevice::new()),
             kobj_state: LockedKObjectState::new(None),
-            fs: RwLock::new(TtyDeviceFs::None),
-            parent: RwLock::new(TtyDeviceParent::None),
+            devpts: RwLock::new(Weak::new()),
             tty_type,
-        };
-
-        dev.inner.write().metadata.raw_dev = dev_num;
+        })
+    }
 
-        Arc::new(dev)
+    /// 设置ptmx所在的devpts实例
+    pub fn set_devpts(&self, devpts: Weak<DevPtsFs>) {
+        *self.devpts.write() = devpts;
     }
 
-    pub fn inner_write(&self) -> crate::libs::rwlock::RwLockWriteGuard<'_, InnerTtyDevice> {
-        self.inner.write()
+    /// ptmx所在的devpts实例
+    pub fn devpts(&self) -> Option<Arc<DevPtsFs>> {
+        self.devpts.read().upgrade()
     }
 
     pub fn name_ref(&self) -> &str {
@@ -190,44 +163,150 @@ impl Debug for TtyDevice {
     }
 }
 
-impl PollableInode for TtyDevice {
-    fn poll(&self, private_data: &FilePrivateData) -> Result<usize, SystemError> {
-        let tty = TtyDevice::tty_core(private_data)?;
-        tty.ldisc().poll(tty)
+impl KObject for TtyDevice {
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
     }
 
-    fn add_epitem(
+    fn set_inode(&self, inode: Option<Arc<crate::filesystem::kernfs::KernFSInode>>) {
+        self.inner.write().inode = inode;
+    }
+
+    fn inode(&self) -> Option<Arc<crate::filesystem::kernfs::KernFSInode>> {
+        self.inner.read().inode.clone()
+    }
+
+    fn parent(&self) -> Option<alloc::sync::Weak<dyn KObject>> {
+        self.inner.read().parent_kobj.clone()
+    }
+
+    fn set_parent(&self, parent: Option<alloc::sync::Weak<dyn KObject>>) {
+        self.inner.write().parent_kobj = parent
+    }
+
+    fn kset(&self) -> Option<Arc<crate::driver::base::kset::KSet>> {
+        self.inner.read().kset.clone()
+    }
+
+    fn set_kset(&self, kset: Option<Arc<crate::driver::base::kset::KSet>>) {
+        self.inner.write().kset = kset
+    }
+
+    fn kobj_type(&self) -> Option<&'static dyn crate::driver::base::kobject::KObjType> {
+        Some(&DeviceKObjType)
+    }
+
+    fn set_kobj_type(&self, _ktype: Option<&'static dyn crate::driver::base::kobject::KObjType>) {}
+
+    fn name(&self) -> alloc::string::String {
+        self.name.to_string()
+    }
+
+    fn set_name(&self, _name: alloc::string::String) {
+        // self.name = name
+    }
+
+    fn kobj_state(
         &self,
-        epitem: Arc<EPollItem>,
-        private_data: &FilePrivateData,
-    ) -> Result<(), SystemError> {
-        let tty = TtyDevice::tty_core(private_data)?;
-        let core = tty.core();
-        core.add_epitem(epitem);
-        Ok(())
+    ) -> crate::libs::rwsem::RwSemReadGuard<'_, crate::driver::base::kobject::KObjectState> {
+        self.kobj_state.read()
     }
 
-    fn remove_epitem(
+    fn kobj_state_mut(
         &self,
-        epitem: &Arc<EPollItem>,
-        private_data: &FilePrivateData,
-    ) -> Result<(), SystemError> {
-        let tty = TtyDevice::tty_core(private_data)?;
-        let core = tty.core();
-        core.remove_epitem(epitem)
+    ) -> crate::libs::rwsem::RwSemWriteGuard<'_, crate::driver::base::kobject::KObjectState> {
+        self.kobj_state.write()
+    }
+
+    fn set_kobj_state(&self, state: crate::driver::base::kobject::KObjectState) {
+        *self.kobj_state.write() = state
     }
 }
 
-impl IndexNode for TtyDevice {
+impl Device for TtyDevice {
+    fn dev_type(&self) -> crate::driver::base::device::DeviceType {
+        DeviceType::Char
+    }
+
+    fn id_table(&self) -> crate::driver::base::device::IdTable {
+        self.id_table.clone()
+    }
+
+    fn bus(&self) -> Option<Weak<dyn Bus>> {
+        self.inner.read().bus.clone()
+    }
+
+    fn set_bus(&self, bus: Option<alloc::sync::Weak<dyn crate::driver::base::device::bus::Bus>>) {
+        self.inner.write().bus = bus
+    }
+
+    fn set_class(&self, _class: Option<Weak<dyn crate::driver::base::class::Class>>) {
+        // do nothing
+    }
+
+    fn class(&self) -> Option<Arc<dyn Class>> {
+        sys_class_tty_instance()
+            .cloned()
+            .map(|x| x as Arc<dyn Class>)
+    }
+
+    fn driver(&self) -> Option<Arc<dyn crate::driver::base::device::driver::Driver>> {
+        self.inner.read().driver.clone()?.upgrade()
+    }
+
+    fn set_driver(
+        &self,
+        driver: Option<alloc::sync::Weak<dyn crate::driver::base::device::driver::Driver>>,
+    ) {
+        self.inner.write().driver = driver
+    }
+
+    fn is_dead(&self) -> bool {
+        false
+    }
+
+    fn can_match(&self) -> bool {
+        self.inner.read().can_match
+    }
+
+    fn set_can_match(&self, can_match: bool) {
+        self.inner.write().can_match = can_match
+    }
+
+    fn state_synced(&self) -> bool {
+        true
+    }
+
+    fn dev_parent(&self) -> Option<alloc::sync::Weak<dyn crate::driver::base::device::Device>> {
+        None
+    }
+
+    fn set_dev_parent(
+        &self,
+        _dev_parent: Option<alloc::sync::Weak<dyn crate::driver::base::device::Device>>,
+    ) {
+        todo!()
+    }
+}
+
+impl CharDevice for TtyDevice {
+    fn chrdev_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn devnode_mode(&self) -> InodeMode {
+        InodeMode::from_bits_truncate(0o666)
+    }
+
     fn open(
         &self,
         mut data: MutexGuard<FilePrivateData>,
-        mode: &crate::filesystem::vfs::file::FileFlags,
+        mode: &FileFlags,
     ) -> Result<(), SystemError> {
         if self.tty_type == TtyType::Pty(PtyType::Ptm) {
             return ptmx_open(self, data, mode);
         }
-        let dev_num = self.metadata()?.raw_dev;
+        let dev_num = self.id_table.device_number();
         // /dev/tty 仅在已有控制终端时才能打开；否则返回 ENXIO
         let mut tty = if dev_num == DeviceNumber::new(Major::TTYAUX_MAJOR, 0) {
             if let Some(current) = self.open_current_tty(dev_num, &mut data) {
@@ -296,13 +375,24 @@ impl IndexNode for TtyDevice {
         Ok(())
     }
 
-    fn read_at(
+    fn close(&self, data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        let (tty, _flags) = if let FilePrivateData::Tty(tty_priv) = &*data {
+            (tty_priv.tty(), tty_priv.flags)
+        } else {
+            return Err(SystemError::EIO);
+        };
+
+        drop(data);
+        tty.close(tty.clone())
+    }
+
+    fn read(
         &self,
         _offset: usize,
         len: usize,
         buf: &mut [u8],
         data: MutexGuard<FilePrivateData>,
-    ) -> Result<usize, system_error::SystemError> {
+    ) -> Result<usize, SystemError> {
         let (tty, flags) = if let FilePrivateData::Tty(tty_priv) = &*data {
             (tty_priv.tty(), tty_priv.flags)
         } else {
@@ -344,13 +434,13 @@ impl IndexNode for TtyDevice {
         return Ok(offset);
     }
 
-    fn write_at(
+    fn write(
         &self,
         _offset: usize,
         len: usize,
         buf: &[u8],
         data: MutexGuard<FilePrivateData>,
-    ) -> Result<usize, system_error::SystemError> {
+    ) -> Result<usize, SystemError> {
         let mut count = len;
         let (tty, flags) = if let FilePrivateData::Tty(tty_priv) = &*data {
             (tty_priv.tty(), tty_priv.flags)
@@ -395,59 +485,6 @@ impl IndexNode for TtyDevice {
         Ok(written)
     }
 
-    fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
-        match &*self.fs.read() {
-            TtyDeviceFs::DevFs(fs) => fs.upgrade().unwrap(),
-            TtyDeviceFs::DevPtsFs(fs) => fs.upgrade().unwrap(),
-            TtyDeviceFs::None => {
-                panic!("TtyDevice has no filesystem set");
-            }
-        }
-    }
-
-    fn dname(&self) -> Result<DName, SystemError> {
-        Ok(self.name.clone().into())
-    }
-
-    fn as_any_ref(&self) -> &dyn core::any::Any {
-        self
-    }
-
-    fn list(&self) -> Result<alloc::vec::Vec<alloc::string::String>, system_error::SystemError> {
-        todo!()
-    }
-
-    fn metadata(&self) -> Result<crate::filesystem::vfs::Metadata, SystemError> {
-        Ok(self.inner.read().metadata.clone())
-    }
-
-    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
-        let mut guard = self.inner_write();
-        guard.metadata = metadata.clone();
-
-        Ok(())
-    }
-
-    fn page_cache(&self) -> Option<Arc<crate::filesystem::page_cache::PageCache>> {
-        // TTY设备是字符设备，不需要页面缓存
-        None
-    }
-
-    fn close(&self, data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
-        let (tty, _flags) = if let FilePrivateData::Tty(tty_priv) = &*data {
-            (tty_priv.tty(), tty_priv.flags)
-        } else {
-            return Err(SystemError::EIO);
-        };
-
-        drop(data);
-        tty.close(tty.clone())
-    }
-
-    fn resize(&self, _len: usize) -> Result<(), SystemError> {
-        Ok(())
-    }
-
     fn ioctl(
         &self,
         cmd: u32,
@@ -470,7 +507,7 @@ impl IndexNode for TtyDevice {
             | TtyIoctlCmd::TCSBRK
             | TtyIoctlCmd::TCSBRKP => {
                 TtyJobCtrlManager::tty_check_change(tty.clone(), Signal::SIGTTOU)?;
-                if cmd != TtyIoctlCmd::TIOCCBRK {
+                if cmd != TtyIoctlCmd::TIOCCBRK && cmd != TtyIoctlCmd::TIOCSETD {
                     todo!()
                 }
             }
@@ -479,36 +516,30 @@ impl IndexNode for TtyDevice {
 
         match cmd {
             TtyIoctlCmd::TIOCGWINSZ => {
-                let core = tty.core();
-                let winsize = *core.window_size();
-
-                let mut user_writer = UserBufferWriter::new(
-                    VirtAddr::new(arg).as_ptr::<WindowSize>(),
-                    core::mem::size_of::<WindowSize>(),
-                    true,
-                )?;
-
-                let err = user_writer.copy_one_to_user(&winsize, 0);
-                if err.is_err() {
-                    return Err(SystemError::EFAULT);
-                }
+                let winsize = *tty.core().window_size();
+                IoctlArg::new(cmd, arg).write(&winsize)?;
+                return Ok(0);
+            }
+            TtyIoctlCmd::TIOCGETD => {
+                let disc = tty.ldisc().disc_type() as i32;
+                IoctlArg::new(cmd, arg).write(&disc)?;
+                return Ok(0);
+            }
+            TtyIoctlCmd::TIOCSETD => {
+                let disc: i32 = IoctlArg::new(cmd, arg).read()?;
+                let disc = LineDisciplineType::try_from_line(disc).ok_or(SystemError::EINVAL)?;
+                TtyCore::set_ldisc(tty, disc)?;
                 return Ok(0);
             }
             TtyIoctlCmd::TIOCSWINSZ => {
-                let reader = UserBufferReader::new(
-                    arg as *const (),
-                    core::mem::size_of::<WindowSize>(),
-                    true,
-                )?;
-
-                let user_winsize = reader.read_one_from_user::<WindowSize>(0)?;
+                let user_winsize: WindowSize = IoctlArg::new(cmd, arg).read()?;
 
-                let ret = tty.resize(tty.clone(), *user_winsize);
+                let ret = tty.resize(tty.clone(), user_winsize);
 
                 if ret != Err(SystemError::ENOSYS) {
                     return ret.map(|_| 0);
                 } else {
-                    return tty.tty_do_resize(*user_winsize).map(|_| 0);
+                    return tty.tty_do_resize(user_winsize).map(|_| 0);
                 }
             }
             _ => match TtyJobCtrlManager::job_ctrl_ioctl(tty.clone(), cmd, arg) {
@@ -538,178 +569,32 @@ impl IndexNode for TtyDevice {
         Ok(0)
     }
 
-    fn as_pollable_inode(&self) -> Result<&dyn PollableInode, SystemError> {
-        Ok(self)
-    }
-
-    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
-        let parent = self.parent.read();
-        match &*parent {
-            TtyDeviceParent::DevFS(devfs) => Ok(devfs.upgrade().unwrap()),
-            TtyDeviceParent::DevPts(devpts) => Ok(devpts.upgrade().unwrap()),
-            TtyDeviceParent::None => panic!("TtyDevice has no filesystem set"),
-        }
-    }
-}
-
-impl DeviceINode for TtyDevice {
-    fn set_fs(&self, fs: alloc::sync::Weak<crate::filesystem::devfs::DevFS>) {
-        *self.fs.write() = TtyDeviceFs::DevFs(fs);
-    }
-
-    fn set_parent(&self, parent: Weak<crate::filesystem::devfs::LockedDevFSInode>) {
-        *self.parent.write() = TtyDeviceParent::DevFS(parent);
-    }
-
-    fn set_devpts_fs(&self, devpts: Weak<crate::filesystem::devpts::DevPtsFs>) {
-        *self.fs.write() = TtyDeviceFs::DevPtsFs(devpts);
-    }
-
-    fn set_devpts_parent(&self, parent: Weak<LockedDevPtsFSInode>) {
-        *self.parent.write() = TtyDeviceParent::DevPts(parent);
-    }
-}
-
-impl KObject for TtyDevice {
-    fn as_any_ref(&self) -> &dyn core::any::Any {
-        self
-    }
-
-    fn set_inode(&self, inode: Option<Arc<crate::filesystem::kernfs::KernFSInode>>) {
-        self.inner.write().inode = inode;
-    }
-
-    fn inode(&self) -> Option<Arc<crate::filesystem::kernfs::KernFSInode>> {
-        self.inner.read().inode.clone()
-    }
-
-    fn parent(&self) -> Option<alloc::sync::Weak<dyn KObject>> {
-        self.inner.read().parent_kobj.clone()
-    }
-
-    fn set_parent(&self, parent: Option<alloc::sync::Weak<dyn KObject>>) {
-        self.inner.write().parent_kobj = parent
-    }
-
-    fn kset(&self) -> Option<Arc<crate::driver::base::kset::KSet>> {
-        self.inner.read().kset.clone()
-    }
-
-    fn set_kset(&self, kset: Option<Arc<crate::driver::base::kset::KSet>>) {
-        self.inner.write().kset = kset
-    }
-
-    fn kobj_type(&self) -> Option<&'static dyn crate::driver::base::kobject::KObjType> {
-        Some(&DeviceKObjType)
-    }
-
-    fn set_kobj_type(&self, _ktype: Option<&'static dyn crate::driver::base::kobject::KObjType>) {}
-
-    fn name(&self) -> alloc::string::String {
-        self.name.to_string()
-    }
-
-    fn set_name(&self, _name: alloc::string::String) {
-        // self.name = name
-    }
-
-    fn kobj_state(
-        &self,
-    ) -> crate::libs::rwsem::RwSemReadGuard<'_, crate::driver::base::kobject::KObjectState> {
-        self.kobj_state.read()
-    }
-
-    fn kobj_state_mut(
-        &self,
-    ) -> crate::libs::rwsem::RwSemWriteGuard<'_, crate::driver::base::kobject::KObjectState> {
-        self.kobj_state.write()
-    }
-
-    fn set_kobj_state(&self, state: crate::driver::base::kobject::KObjectState) {
-        *self.kobj_state.write() = state
-    }
-}
-
-impl Device for TtyDevice {
-    fn dev_type(&self) -> crate::driver::base::device::DeviceType {
-        DeviceType::Char
-    }
-
-    fn id_table(&self) -> crate::driver::base::device::IdTable {
-        self.id_table.clone()
-    }
-
-    fn bus(&self) -> Option<Weak<dyn Bus>> {
-        self.inner.read().bus.clone()
-    }
-
-    fn set_bus(&self, bus: Option<alloc::sync::Weak<dyn crate::driver::base::device::bus::Bus>>) {
-        self.inner.write().bus = bus
-    }
-
-    fn set_class(&self, _class: Option<Weak<dyn crate::driver::base::class::Class>>) {
-        // do nothing
-    }
-
-    fn class(&self) -> Option<Arc<dyn Class>> {
-        sys_class_tty_instance()
-            .cloned()
-            .map(|x| x as Arc<dyn Class>)
-    }
-
-    fn driver(&self) -> Option<Arc<dyn crate::driver::base::device::driver::Driver>> {
-        self.inner.read().driver.clone()?.upgrade()
+    fn poll(&self, data: &FilePrivateData) -> Result<usize, SystemError> {
+        let tty = TtyDevice::tty_core(data)?;
+        tty.ldisc().poll(tty)
     }
 
-    fn set_driver(
+    fn add_epitem(
         &self,
-        driver: Option<alloc::sync::Weak<dyn crate::driver::base::device::driver::Driver>>,
-    ) {
-        self.inner.write().driver = driver
-    }
-
-    fn is_dead(&self) -> bool {
-        false
-    }
-
-    fn can_match(&self) -> bool {
-        self.inner.read().can_match
-    }
-
-    fn set_can_match(&self, can_match: bool) {
-        self.inner.write().can_match = can_match
-    }
-
-    fn state_synced(&self) -> bool {
-        true
-    }
-
-    fn dev_parent(&self) -> Option<alloc::sync::Weak<dyn crate::driver::base::device::Device>> {
-        None
+        epitem: Arc<EPollItem>,
+        data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        let tty = TtyDevice::tty_core(data)?;
+        let core = tty.core();
+        core.add_epitem(epitem);
+        Ok(())
     }
 
-    fn set_dev_parent(
+    fn remove_epitem(
         &self,
-        _dev_parent: Option<alloc::sync::Weak<dyn crate::driver::base::device::Device>>,
-    ) {
-        todo!()
-    }
-}
-
-impl CharDevice for TtyDevice {
-    fn read(&self, _len: usize, _buf: &mut [u8]) -> Result<usize, SystemError> {
-        todo!()
-    }
-
-    fn write(&self, _len: usize, _buf: &[u8]) -> Result<usize, SystemError> {
-        todo!()
-    }
-
-    fn sync(&self) -> Result<(), SystemError> {
-        todo!()
+        epitem: &Arc<EPollItem>,
+        data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        let tty = TtyDevice::tty_core(data)?;
+        let core = tty.core();
+        core.remove_epitem(epitem)
     }
 }
-
 #[derive(Debug, Clone)]
 pub struct TtyFilePrivateData {
     pub tty: Arc<TtyCore>,
@@ -748,7 +633,7 @@ pub fn tty_init() -> Result<(), SystemError> {
     for dev in devs {
         // 将设备注册到devfs，TODO：这里console设备应该与tty在一个设备group里面
         device_register(dev.clone())?;
-        devfs_register(&dev.name.clone(), dev)?;
+        char_dev_manager().register(dev)?;
     }
 
     serial_init()?;
@@ -13,7 +13,7 @@ use system_error::SystemError;
 use crate::{
     driver::{
         base::{
-            char::CharDevOps,
+            char::{manager::char_dev_manager, CharDevOps},
             device::{
                 device_number::{DeviceNumber, Major},
                 device_register,
@@ -24,7 +24,6 @@ use crate::{
         },
         tty::tty_port::TtyPortState,
     },
-    filesystem::devfs::devfs_register,
     libs::{
         lazy_init::Lazy,
         rwlock::RwLock,
@@ -351,8 +350,8 @@ impl TtyDriver {
             IdTable::new(self.tty_line_name(idx), Some(*core.device_number())),
             super::tty_device::TtyType::Tty,
         );
-        // log::debug!("init_tty_device: to devfs_register");
-        devfs_register(device.name_ref(), device.clone())?;
+        // log::debug!("init_tty_device: to char_dev_manager register");
+        char_dev_manager().register(device.clone())?;
         // log::debug!("init_tty_device: to device_register");
         device_register(device)?;
         Ok(tty)
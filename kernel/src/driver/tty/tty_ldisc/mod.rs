@@ -3,7 +3,7 @@ use core::fmt::Debug;
 use alloc::sync::Arc;
 use system_error::SystemError;
 
-use crate::filesystem::vfs::file::FileFlags;
+use crate::{driver::bluetooth::hci_ldisc::HciUartLdisc, filesystem::vfs::file::FileFlags};
 
 use super::{
     termios::Termios,
@@ -12,6 +12,8 @@ use super::{
 
 pub mod ntty;
 
+use self::ntty::NTtyLinediscipline;
+
 pub trait TtyLineDiscipline: Sync + Send + Debug {
     fn open(&self, tty: Arc<TtyCore>) -> Result<(), SystemError>;
     fn close(&self, tty: Arc<TtyCore>) -> Result<(), SystemError>;
@@ -73,22 +75,45 @@ pub trait TtyLineDiscipline: Sync + Send + Debug {
     fn write_wakeup(&self, _tty: &TtyCoreData) -> Result<(), SystemError> {
         Err(SystemError::ENOSYS)
     }
+
+    /// 线路规程的编号，TIOCGETD时返回
+    fn disc_type(&self) -> LineDisciplineType;
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LineDisciplineType {
     NTty = 0,
+    /// 蓝牙HCI UART（N_HCI）
+    Hci = 15,
 }
 
 impl LineDisciplineType {
     pub fn from_line(line: u8) -> Self {
         match line {
             0 => Self::NTty,
+            15 => Self::Hci,
             _ => {
                 todo!()
             }
         }
     }
+
+    /// TIOCSETD传入的编号，不支持的返回`None`
+    pub fn try_from_line(line: i32) -> Option<Self> {
+        match line {
+            0 => Some(Self::NTty),
+            15 => Some(Self::Hci),
+            _ => None,
+        }
+    }
+
+    /// 创建这种线路规程的一个实例
+    pub fn create(self) -> Arc<dyn TtyLineDiscipline> {
+        match self {
+            Self::NTty => Arc::new(NTtyLinediscipline::new()),
+            Self::Hci => Arc::new(HciUartLdisc::new()),
+        }
+    }
 }
 
 pub struct TtyLdiscManager;
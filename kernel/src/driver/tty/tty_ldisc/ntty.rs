@@ -25,7 +25,7 @@ use crate::{
     syscall::user_access::UserBufferWriter,
 };
 
-use super::TtyLineDiscipline;
+use super::{LineDisciplineType, TtyLineDiscipline};
 pub const NTTY_BUFSIZE: usize = 4096;
 pub const ECHO_COMMIT_WATERMARK: usize = 256;
 pub const ECHO_BLOCK: usize = 256;
@@ -40,7 +40,19 @@ pub struct NTtyLinediscipline {
     pub data: SpinLock<NTtyData>,
 }
 
+impl Default for NTtyLinediscipline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NTtyLinediscipline {
+    pub fn new() -> Self {
+        Self {
+            data: SpinLock::new(NTtyData::new()),
+        }
+    }
+
     #[inline]
     pub fn disc_data(&self) -> SpinLockGuard<'_, NTtyData> {
         self.data.lock_irqsave()
@@ -1558,7 +1570,7 @@ impl TtyLineDiscipline for NTtyLinediscipline {
     }
 
     fn close(&self, _tty: Arc<TtyCore>) -> Result<(), system_error::SystemError> {
-        todo!()
+        Ok(())
     }
 
     /// ## 重置缓冲区的基本信息
@@ -2132,4 +2144,8 @@ impl TtyLineDiscipline for NTtyLinediscipline {
         let mut ldata = self.disc_data();
         ldata.receive_buf_common(tty, buf, flags, count, true)
     }
+
+    fn disc_type(&self) -> LineDisciplineType {
+        LineDisciplineType::NTty
+    }
 }
@@ -0,0 +1,250 @@
+//! 虚拟终端的响铃（BEL，0x07）
+//!
+//! 支持两种响铃方式，可以同时启用：
+//! - 可视响铃：整个屏幕反色闪烁一次
+//! - 声音响铃：通过PC喇叭发声（目前仅x86_64支持）
+//!
+//! 响铃方式通过[`BellIoctlCmd`]中的ioctl设置。音调和时长也可以通过
+//! `ESC [ 10 ; n ]`、`ESC [ 11 ; n ]`设置（与Linux的`setterm -bfreq/-blength`一致）。
+
+use alloc::{boxed::Box, sync::Arc};
+use system_error::SystemError;
+
+use crate::{
+    exception::workqueue::{schedule_work, Work},
+    filesystem::vfs::ioctl::IoctlArg,
+    libs::spinlock::SpinLock,
+    time::{
+        timer::{next_n_ms_timer_jiffies, Timer, TimerFunction},
+        PIT_TICK_RATE,
+    },
+};
+
+use super::{vc_manager, virtual_console::VirtualConsoleData};
+
+/// 可视响铃时屏幕保持反色的时长
+const VISUAL_BELL_MS: u64 = 100;
+
+/// 正在发声的PC喇叭的停止定时器
+static SOUND_STOP_TIMER: SpinLock<Option<Arc<Timer>>> = SpinLock::new(None);
+
+bitflags! {
+    /// 响铃方式
+    #[derive(Default)]
+    pub struct VcBellMode: u32 {
+        /// 可视响铃
+        const VISUAL = 1 << 0;
+        /// 声音响铃
+        const AUDIBLE = 1 << 1;
+    }
+}
+
+/// 虚拟终端的响铃设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VcBellConfig {
+    pub mode: VcBellMode,
+    /// 声音响铃的频率（Hz）
+    pub pitch: u32,
+    /// 声音响铃的时长（毫秒）
+    pub duration_ms: u32,
+}
+
+impl VcBellConfig {
+    pub const DEFAULT_PITCH: u32 = 750;
+    pub const DEFAULT_DURATION_MS: u32 = 125;
+    /// 声音响铃的最大时长
+    pub const MAX_DURATION_MS: u32 = 2000;
+}
+
+impl Default for VcBellConfig {
+    fn default() -> Self {
+        Self {
+            mode: VcBellMode::VISUAL,
+            pitch: Self::DEFAULT_PITCH,
+            duration_ms: Self::DEFAULT_DURATION_MS,
+        }
+    }
+}
+
+/// ## 终端收到BEL时响铃
+pub(super) fn vc_bell(vc_data: &mut VirtualConsoleData) {
+    let config = vc_data.bell_config();
+
+    if config.mode.contains(VcBellMode::VISUAL) && vc_data.should_update() {
+        // 上一次的闪烁还没有结束时，不再叠加
+        if !vc_data.bell_flashing {
+            vc_data.bell_flashing = true;
+            vc_data.flash_screen(true);
+            let timer = Timer::new(
+                Box::new(VisualBellTimer {
+                    vc_index: vc_data.vc_index,
+                }),
+                next_n_ms_timer_jiffies(VISUAL_BELL_MS),
+            );
+            timer.activate();
+        }
+    }
+
+    if config.mode.contains(VcBellMode::AUDIBLE) && vc_data.is_visible() {
+        kd_mksound(config.pitch, config.duration_ms);
+    }
+}
+
+/// ## 让PC喇叭以`hz`的频率发声`duration_ms`毫秒
+///
+/// `duration_ms`为0时持续发声，直到下一次调用`kd_mksound(0, 0)`
+pub fn kd_mksound(hz: u32, duration_ms: u32) {
+    // 取消和激活定时器都要获取定时器链表的锁，而定时器到期时会在持有它的情况下获取
+    // SOUND_STOP_TIMER，因此不能在持有SOUND_STOP_TIMER的时候操作定时器
+    let old_timer = SOUND_STOP_TIMER.lock_irqsave().take();
+    if let Some(timer) = old_timer {
+        timer.cancel();
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    crate::arch::driver::pcspkr::pcspkr_tone(hz);
+
+    if hz != 0 && duration_ms != 0 {
+        let timer = Timer::new(
+            Box::new(SoundStopTimer),
+            next_n_ms_timer_jiffies(duration_ms as u64),
+        );
+        timer.activate();
+        let old_timer = SOUND_STOP_TIMER.lock_irqsave().replace(timer);
+        // 并发调用时，另一个调用者设置的定时器被替换掉了，把它取消
+        if let Some(timer) = old_timer {
+            timer.cancel();
+        }
+    }
+}
+
+/// 可视响铃结束后恢复屏幕
+#[derive(Debug)]
+struct VisualBellTimer {
+    vc_index: usize,
+}
+
+impl TimerFunction for VisualBellTimer {
+    fn run(&mut self) -> Result<(), SystemError> {
+        let vc_index = self.vc_index;
+        // 重绘整个屏幕的开销较大，放到工作队列中进行
+        schedule_work(Work::new(move || {
+            let vc_data = match vc_manager().get(vc_index).and_then(|vc| vc.vc_data()) {
+                Some(vc_data) => vc_data,
+                None => return,
+            };
+            let mut vc_data = vc_data.lock_irqsave();
+            if vc_data.bell_flashing {
+                vc_data.bell_flashing = false;
+                if vc_data.should_update() {
+                    vc_data.flash_screen(false);
+                }
+            }
+        }));
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct SoundStopTimer;
+
+impl TimerFunction for SoundStopTimer {
+    fn run(&mut self) -> Result<(), SystemError> {
+        SOUND_STOP_TIMER.lock_irqsave().take();
+        #[cfg(target_arch = "x86_64")]
+        crate::arch::driver::pcspkr::pcspkr_tone(0);
+        Ok(())
+    }
+}
+
+/// 响铃相关的ioctl
+pub struct BellIoctlCmd;
+
+impl BellIoctlCmd {
+    /// 持续发声，参数为PIT的计数值（`PIT_TICK_RATE / 频率`），为0时停止
+    pub const KIOCSOUND: u32 = 0x4B2F;
+    /// 发声一段时间，参数的低16位为PIT的计数值，高16位为时长（毫秒）
+    pub const KDMKTONE: u32 = 0x4B30;
+    /// 获取当前终端的响铃设置，参数为`*mut VcBellArgs`（DragonOS私有）
+    pub const KDGETBELL: u32 = 0x4BF2;
+    /// 设置当前终端的响铃设置，参数为`*const VcBellArgs`（DragonOS私有）
+    pub const KDSETBELL: u32 = 0x4BF3;
+}
+
+/// 响铃ioctl的用户态参数。设置时，值为-1的字段保持不变
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VcBellArgs {
+    /// 响铃方式，为[`VcBellMode`]的组合
+    pub mode: i32,
+    /// 声音响铃的频率（Hz）
+    pub pitch: i32,
+    /// 声音响铃的时长（毫秒）
+    pub duration_ms: i32,
+}
+
+/// PIT计数值转换为频率
+fn pit_count_to_hz(count: usize) -> u32 {
+    if count == 0 {
+        return 0;
+    }
+    (PIT_TICK_RATE / count as u64) as u32
+}
+
+/// ## 处理响铃相关的ioctl
+pub fn bell_ioctl(
+    vc_data: &mut VirtualConsoleData,
+    cmd: u32,
+    arg: usize,
+) -> Result<(), SystemError> {
+    match cmd {
+        BellIoctlCmd::KIOCSOUND => {
+            kd_mksound(pit_count_to_hz(arg & 0xffff), 0);
+            Ok(())
+        }
+        BellIoctlCmd::KDMKTONE => {
+            let duration_ms = ((arg >> 16) & 0xffff) as u32;
+            let hz = if duration_ms != 0 {
+                pit_count_to_hz(arg & 0xffff)
+            } else {
+                0
+            };
+            kd_mksound(hz, duration_ms);
+            Ok(())
+        }
+        BellIoctlCmd::KDGETBELL => {
+            let config = vc_data.bell_config();
+            let args = VcBellArgs {
+                mode: config.mode.bits() as i32,
+                pitch: config.pitch as i32,
+                duration_ms: config.duration_ms as i32,
+            };
+            IoctlArg::new(cmd, arg).write(&args)?;
+            Ok(())
+        }
+        BellIoctlCmd::KDSETBELL => {
+            let args: VcBellArgs = IoctlArg::new(cmd, arg).read()?;
+
+            let mut config = vc_data.bell_config();
+            if args.mode != -1 {
+                config.mode = VcBellMode::from_bits(args.mode as u32).ok_or(SystemError::EINVAL)?;
+            }
+            if args.pitch != -1 {
+                if !(0..65536).contains(&args.pitch) {
+                    return Err(SystemError::EINVAL);
+                }
+                config.pitch = args.pitch as u32;
+            }
+            if args.duration_ms != -1 {
+                if !(0..=VcBellConfig::MAX_DURATION_MS as i32).contains(&args.duration_ms) {
+                    return Err(SystemError::EINVAL);
+                }
+                config.duration_ms = args.duration_ms as u32;
+            }
+
+            vc_data.set_bell_config(config);
+            Ok(())
+        }
+        _ => Err(SystemError::ENOIOCTLCMD),
+    }
+}
@@ -11,47 +11,40 @@ use unified_init::macros::unified_init;
 
 use crate::{
     driver::{
-        base::device::{
-            device_number::{DeviceNumber, Major},
-            device_register, IdTable,
+        base::{
+            char::{manager::char_dev_manager, CharDevice},
+            device::{
+                device_number::{DeviceNumber, Major},
+                device_register, IdTable,
+            },
         },
         serial::serial8250::send_to_default_serial8250_port,
     },
-    filesystem::devfs::{devfs_register, devfs_unregister},
     init::initcall::INITCALL_LATE,
     libs::{lazy_init::Lazy, rwlock::RwLock, spinlock::SpinLock},
 };
 
-use self::virtual_console::VirtualConsoleData;
+use self::{bell::BellIoctlCmd, theme::ThemeIoctlCmd, virtual_console::VirtualConsoleData};
 
 use super::{
     console::ConsoleSwitch,
     termios::{InputMode, TTY_STD_TERMIOS},
-    tty_core::{TtyCore, TtyCoreData},
+    tty_core::{TtyCore, TtyCoreData, TtyIoctlCmd},
     tty_device::{TtyDevice, TtyType},
     tty_driver::{TtyDriver, TtyDriverManager, TtyDriverType, TtyOperation},
     tty_port::{DefaultTtyPort, TtyPort},
 };
 
+pub mod bell;
 pub mod console_map;
+pub mod selection;
+pub mod theme;
 pub mod virtual_console;
 
 pub const MAX_NR_CONSOLES: u32 = 64;
 pub const VC_MAXCOL: usize = 32767;
 pub const VC_MAXROW: usize = 32767;
 
-pub const DEFAULT_RED: [u16; 16] = [
-    0x00, 0xaa, 0x00, 0xaa, 0x00, 0xaa, 0x00, 0xaa, 0x55, 0xff, 0x55, 0xff, 0x55, 0xff, 0x55, 0xff,
-];
-
-pub const DEFAULT_GREEN: [u16; 16] = [
-    0x00, 0x00, 0xaa, 0x55, 0x00, 0x00, 0xaa, 0xaa, 0x55, 0x55, 0xff, 0xff, 0x55, 0x55, 0xff, 0xff,
-];
-
-pub const DEFAULT_BLUE: [u16; 16] = [
-    0x00, 0x00, 0x00, 0x00, 0xaa, 0xaa, 0xaa, 0xaa, 0x55, 0x55, 0x55, 0x55, 0xff, 0xff, 0xff, 0xff,
-];
-
 pub const COLOR_TABLE: &[u8] = &[0, 4, 2, 6, 1, 5, 3, 7, 8, 12, 10, 14, 9, 13, 11, 15];
 
 lazy_static! {
@@ -117,7 +110,7 @@ impl VirtConsole {
         );
 
         device_register(vcdev.clone())?;
-        devfs_register(vcdev.name_ref(), vcdev.clone())?;
+        char_dev_manager().register(vcdev.clone())?;
         tty_core_data.set_vc_index(*self.index.get());
         self.inner.lock().vcdev = Some(vcdev);
 
@@ -127,9 +120,10 @@ impl VirtConsole {
     fn devfs_remove(&self) {
         let vcdev = self.inner.lock().vcdev.take();
         if let Some(vcdev) = vcdev {
-            devfs_unregister(vcdev.name_ref(), vcdev.clone())
+            char_dev_manager()
+                .unregister(&(vcdev as Arc<dyn CharDevice>))
                 .inspect_err(|e| {
-                    log::error!("virt console: devfs_unregister failed: {:?}", e);
+                    log::error!("virt console: unregister char device failed: {:?}", e);
                 })
                 .ok();
         }
@@ -396,9 +390,7 @@ impl TtyOperation for TtyConsoleDriverInner {
         //     loop {}
         // }
         send_to_default_serial8250_port(buf);
-        let ret = tty.do_write(buf, nr);
-        self.flush_chars(tty);
-        ret
+        vc_write_screen(tty, buf, nr)
     }
 
     #[inline(never)]
@@ -413,9 +405,25 @@ impl TtyOperation for TtyConsoleDriverInner {
         Ok(())
     }
 
-    fn ioctl(&self, _tty: Arc<TtyCore>, _cmd: u32, _arg: usize) -> Result<(), SystemError> {
-        // TODO
-        Err(SystemError::ENOIOCTLCMD)
+    fn ioctl(&self, tty: Arc<TtyCore>, cmd: u32, arg: usize) -> Result<(), SystemError> {
+        match cmd {
+            TtyIoctlCmd::TIOCLINUX => selection::tioclinux(tty, arg),
+            ThemeIoctlCmd::KDGETTHEME | ThemeIoctlCmd::KDSETTHEME => {
+                let vc_data = tty.core().vc_data().ok_or(SystemError::ENOTTY)?;
+                let mut vc_data = vc_data.lock_irqsave();
+                theme::theme_ioctl(&mut vc_data, cmd, arg)
+            }
+            BellIoctlCmd::KIOCSOUND
+            | BellIoctlCmd::KDMKTONE
+            | BellIoctlCmd::KDGETBELL
+            | BellIoctlCmd::KDSETBELL => {
+                let vc_data = tty.core().vc_data().ok_or(SystemError::ENOTTY)?;
+                let mut vc_data = vc_data.lock_irqsave();
+                bell::bell_ioctl(&mut vc_data, cmd, arg)
+            }
+            // TODO
+            _ => Err(SystemError::ENOIOCTLCMD),
+        }
     }
 
     fn close(&self, _tty: Arc<TtyCore>) -> Result<(), SystemError> {
@@ -431,6 +439,21 @@ impl TtyOperation for TtyConsoleDriverInner {
     }
 }
 
+/// 只把数据写到虚拟终端的屏幕上，不镜像到串口
+///
+/// 内核日志按输出端分别过滤，屏幕和串口的级别可能不同，因此需要能单独写屏幕
+pub fn vc_write_screen(tty: &TtyCoreData, buf: &[u8], nr: usize) -> Result<usize, SystemError> {
+    // 屏幕内容将要改变，清除该终端上的选区
+    if let Some(index) = tty.vc_index() {
+        selection::clear_selection(index);
+    }
+    let ret = tty.do_write(buf, nr);
+    if let Some(vc_data) = tty.vc_data() {
+        vc_data.lock_irqsave().set_cursor();
+    }
+    ret
+}
+
 #[derive(Debug, Clone)]
 pub struct VtModeData {
     mode: VtMode,
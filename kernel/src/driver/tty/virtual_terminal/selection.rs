@@ -0,0 +1,388 @@
+//! 虚拟控制台的文本选择与粘贴
+//!
+//! 参考： https://code.dragonos.org.cn/xref/linux-6.1.9/drivers/tty/vt/selection.c
+//!
+//! 用户态的鼠标守护进程（gpm风格）读取鼠标设备，根据鼠标的位置和按键，
+//! 通过`TIOCLINUX`的`TIOCL_SETSEL`设置选区（选区会被反色显示），
+//! 再通过`TIOCL_PASTESEL`把选中的文本作为输入送给tty。
+
+use alloc::{sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use crate::{
+    driver::tty::tty_core::TtyCore,
+    libs::spinlock::SpinLock,
+    process::{cred::CAPFlags, ProcessManager},
+    syscall::user_access::UserBufferReader,
+};
+
+use super::{vc_manager, virtual_console::VirtualConsoleData};
+
+/// TIOCLINUX的子命令
+pub struct TiocLCmd;
+
+impl TiocLCmd {
+    /// 设置选区
+    pub const TIOCL_SETSEL: u8 = 2;
+    /// 把选区的内容粘贴到tty
+    pub const TIOCL_PASTESEL: u8 = 3;
+}
+
+/// 选区的模式，与Linux的`TIOCL_SEL*`取值一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionMode {
+    /// 按字符选择
+    Char = 0,
+    /// 按单词选择
+    Word = 1,
+    /// 按行选择
+    Line = 2,
+    /// 只显示鼠标指针
+    Pointer = 3,
+    /// 清除选区
+    Clear = 4,
+    /// 矩形选择（DragonOS扩展）
+    Rect = 5,
+}
+
+impl SelectionMode {
+    fn from_raw(mode: u16) -> Option<Self> {
+        match mode {
+            0 => Some(Self::Char),
+            1 => Some(Self::Word),
+            2 => Some(Self::Line),
+            3 => Some(Self::Pointer),
+            4 => Some(Self::Clear),
+            5 => Some(Self::Rect),
+            _ => None,
+        }
+    }
+}
+
+/// 对应Linux的`struct tiocl_selection`，坐标从1开始
+#[derive(Debug, Clone, Copy)]
+pub struct TiocLSelection {
+    pub xs: u16,
+    pub ys: u16,
+    pub xe: u16,
+    pub ye: u16,
+    pub sel_mode: u16,
+}
+
+impl TiocLSelection {
+    /// `TIOCL_SETSEL`的参数：1字节的子命令，紧跟着（不对齐的）`struct tiocl_selection`
+    const USER_SIZE: usize = 1 + 5 * core::mem::size_of::<u16>();
+
+    fn from_user(arg: usize) -> Result<Self, SystemError> {
+        let reader = UserBufferReader::new(arg as *const u8, Self::USER_SIZE, true)?;
+        let mut raw = [0u8; Self::USER_SIZE];
+        reader.copy_from_user(&mut raw, 0)?;
+        let field = |idx: usize| u16::from_le_bytes([raw[1 + idx * 2], raw[2 + idx * 2]]);
+
+        Ok(Self {
+            xs: field(0),
+            ys: field(1),
+            xe: field(2),
+            ye: field(3),
+            sel_mode: field(4),
+        })
+    }
+}
+
+static SELECTION: SpinLock<Selection> = SpinLock::new(Selection::new());
+
+struct Selection {
+    /// 选区或鼠标指针所在的虚拟终端
+    vc_index: Option<usize>,
+    /// 是否存在被反色显示的选区
+    selected: bool,
+    /// 选区起点在screen_buf中的偏移
+    start: usize,
+    /// 选区终点在screen_buf中的偏移（包含）
+    end: usize,
+    /// 是否为矩形选区
+    rect: bool,
+    /// 被反色显示的鼠标指针位置
+    pointer: Option<usize>,
+    /// 选中的文本，行与行之间以'\r'分隔
+    buffer: Vec<u8>,
+}
+
+impl Selection {
+    const fn new() -> Self {
+        Self {
+            vc_index: None,
+            selected: false,
+            start: 0,
+            end: 0,
+            rect: false,
+            pointer: None,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// 反色显示选区，再次调用即取消
+    fn highlight(&self, vc_data: &mut VirtualConsoleData) {
+        if self.rect {
+            let cols = vc_data.cols;
+            let (xs, xe) = (self.start % cols, self.end % cols);
+            for y in (self.start / cols)..=(self.end / cols) {
+                vc_data.invert_screen(y * cols + xs, xe - xs + 1);
+            }
+        } else {
+            vc_data.invert_screen(self.start, self.end - self.start + 1);
+        }
+    }
+
+    /// 在vc_data已被锁住的情况下，取消鼠标指针的反色显示
+    fn unhighlight_pointer(&mut self, vc_data: &mut VirtualConsoleData) {
+        if let Some(pos) = self.pointer.take() {
+            vc_data.invert_screen(pos, 1);
+        }
+    }
+
+    /// 在vc_data已被锁住的情况下，取消选区的反色显示
+    fn unhighlight_selection(&mut self, vc_data: &mut VirtualConsoleData) {
+        if self.selected {
+            self.selected = false;
+            self.highlight(vc_data);
+        }
+    }
+
+    /// 清除选区（保留已复制的文本）
+    fn clear(&mut self) {
+        let Some(vc_index) = self.vc_index else {
+            return;
+        };
+        if let Some(vc_data) = vc_manager().get(vc_index).and_then(|vc| vc.vc_data()) {
+            let mut vc_data = vc_data.lock_irqsave();
+            self.unhighlight_pointer(&mut vc_data);
+            self.unhighlight_selection(&mut vc_data);
+        }
+        self.vc_index = None;
+        self.selected = false;
+        self.pointer = None;
+    }
+
+    /// 把选区中的文本复制到buffer
+    fn copy(&mut self, vc_data: &VirtualConsoleData) {
+        let cols = vc_data.cols;
+        self.buffer.clear();
+
+        let (xs, xe) = if self.rect {
+            (self.start % cols, self.end % cols)
+        } else {
+            (0, cols - 1)
+        };
+
+        let first_row = self.start / cols;
+        let last_row = self.end / cols;
+        for y in first_row..=last_row {
+            let row_start = if !self.rect && y == first_row {
+                self.start
+            } else {
+                y * cols + xs
+            };
+            let row_end = if !self.rect && y == last_row {
+                self.end
+            } else {
+                y * cols + xe
+            };
+
+            let line_start = self.buffer.len();
+            for cell in vc_data.screen_buf[row_start..=row_end].iter() {
+                self.buffer.push(cell_char(*cell));
+            }
+
+            // 选到了行尾，去掉行尾的空格并换行
+            if y != last_row || (row_end + 1) % cols == 0 {
+                while self.buffer.len() > line_start && self.buffer.last() == Some(&b' ') {
+                    self.buffer.pop();
+                }
+                if y != last_row {
+                    self.buffer.push(b'\r');
+                }
+            }
+        }
+    }
+}
+
+/// 取出屏幕缓冲区中一个字符单元的字符
+#[inline]
+fn cell_char(cell: u16) -> u8 {
+    let c = (cell & 0xff) as u8;
+    if c.is_ascii_control() {
+        b' '
+    } else {
+        c
+    }
+}
+
+/// 判断字符是否属于“单词”，与Linux默认的inword表一致
+#[inline]
+fn inword(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'-' || c == b'.' || c == b'/'
+}
+
+#[inline]
+fn is_space(vc_data: &VirtualConsoleData, pos: usize) -> bool {
+    cell_char(vc_data.screen_buf[pos]) == b' '
+}
+
+#[inline]
+fn same_class(vc_data: &VirtualConsoleData, pos: usize, spc: bool) -> bool {
+    let c = cell_char(vc_data.screen_buf[pos]);
+    if spc {
+        c == b' '
+    } else {
+        inword(c)
+    }
+}
+
+/// ## 设置选区
+///
+/// 选区总是作用在当前显示的虚拟终端上
+pub fn set_selection(sel: &TiocLSelection) -> Result<(), SystemError> {
+    let mode = SelectionMode::from_raw(sel.sel_mode).ok_or(SystemError::EINVAL)?;
+    let vc = vc_manager().current_vc().ok_or(SystemError::ENXIO)?;
+    let vc_index = vc.index().ok_or(SystemError::ENXIO)?;
+    let vc_data = vc.vc_data().ok_or(SystemError::ENXIO)?;
+
+    let mut selection = SELECTION.lock_irqsave();
+    if selection.vc_index.is_some_and(|idx| idx != vc_index) {
+        selection.clear();
+    }
+
+    let mut vc_data = vc_data.lock_irqsave();
+    let cols = vc_data.cols;
+    let rows = vc_data.rows;
+    if cols == 0 || rows == 0 {
+        return Err(SystemError::ENXIO);
+    }
+
+    let clamp = |v: u16, max: usize| (v as usize).saturating_sub(1).min(max - 1);
+    let (mut xs, mut ys) = (clamp(sel.xs, cols), clamp(sel.ys, rows));
+    let (mut xe, mut ye) = (clamp(sel.xe, cols), clamp(sel.ye, rows));
+
+    match mode {
+        SelectionMode::Clear => {
+            selection.unhighlight_pointer(&mut vc_data);
+            selection.unhighlight_selection(&mut vc_data);
+            selection.vc_index = None;
+            return Ok(());
+        }
+        SelectionMode::Pointer => {
+            // 移动指针不影响已有的选区
+            selection.unhighlight_pointer(&mut vc_data);
+            let pos = ys * cols + xs;
+            vc_data.invert_screen(pos, 1);
+            selection.vc_index = Some(vc_index);
+            selection.pointer = Some(pos);
+            return Ok(());
+        }
+        SelectionMode::Rect => {
+            if xs > xe {
+                core::mem::swap(&mut xs, &mut xe);
+            }
+            if ys > ye {
+                core::mem::swap(&mut ys, &mut ye);
+            }
+        }
+        _ => {}
+    }
+
+    let mut ps = ys * cols + xs;
+    let mut pe = ye * cols + xe;
+    if ps > pe {
+        core::mem::swap(&mut ps, &mut pe);
+    }
+
+    match mode {
+        SelectionMode::Word => {
+            // 起点/终点落在空白上时选中连续的空白，否则选中连续的单词字符
+            let spc = is_space(&vc_data, ps);
+            while ps % cols != 0 && same_class(&vc_data, ps - 1, spc) {
+                ps -= 1;
+            }
+
+            let spc = is_space(&vc_data, pe);
+            while (pe + 1) % cols != 0 && same_class(&vc_data, pe + 1, spc) {
+                pe += 1;
+            }
+        }
+        SelectionMode::Line => {
+            ps -= ps % cols;
+            pe += cols - 1 - pe % cols;
+        }
+        SelectionMode::Char => {
+            // 如果终点之后直到行尾都是空格，则把选区延伸到行尾
+            let row_end = pe + cols - 1 - pe % cols;
+            if ((pe + 1)..=row_end).all(|pos| is_space(&vc_data, pos)) {
+                pe = row_end;
+            }
+        }
+        _ => {}
+    }
+
+    selection.unhighlight_selection(&mut vc_data);
+    selection.vc_index = Some(vc_index);
+    selection.selected = true;
+    selection.start = ps;
+    selection.end = pe;
+    selection.rect = mode == SelectionMode::Rect;
+    selection.highlight(&mut vc_data);
+    selection.copy(&vc_data);
+
+    Ok(())
+}
+
+/// ## 清除指定虚拟终端上的选区
+///
+/// 在虚拟终端的内容发生变化之前调用，避免反色的区域与屏幕内容错位
+pub fn clear_selection(vc_index: usize) {
+    let mut selection = SELECTION.lock_irqsave();
+    if selection.vc_index == Some(vc_index) {
+        selection.clear();
+    }
+}
+
+/// ## 把选中的文本作为输入送给tty
+pub fn paste_selection(tty: Arc<TtyCore>) -> Result<(), SystemError> {
+    // 粘贴时回显会写vc，从而清除选区，因此要先释放锁
+    let buffer = SELECTION.lock_irqsave().buffer.clone();
+    if buffer.is_empty() {
+        return Ok(());
+    }
+
+    let port = tty.core().port().ok_or(SystemError::ENODEV)?;
+    let mut offset = 0;
+    while offset < buffer.len() {
+        let n = port.receive_buf(&buffer[offset..], &[], buffer.len() - offset)?;
+        if n == 0 {
+            break;
+        }
+        offset += n;
+    }
+
+    Ok(())
+}
+
+/// ## 处理TIOCLINUX中与选区相关的子命令
+pub fn tioclinux(tty: Arc<TtyCore>, arg: usize) -> Result<(), SystemError> {
+    let reader = UserBufferReader::new(arg as *const u8, 1, true)?;
+    let subcode = *reader.read_one_from_user::<u8>(0)?;
+
+    match subcode {
+        TiocLCmd::TIOCL_SETSEL => {
+            if !ProcessManager::current_pcb()
+                .cred()
+                .has_capability(CAPFlags::CAP_SYS_ADMIN)
+            {
+                return Err(SystemError::EPERM);
+            }
+            set_selection(&TiocLSelection::from_user(arg)?)
+        }
+        TiocLCmd::TIOCL_PASTESEL => paste_selection(tty),
+        _ => Err(SystemError::EINVAL),
+    }
+}
@@ -0,0 +1,215 @@
+//! 虚拟终端的配色主题
+//!
+//! 每个虚拟终端都有自己的主题（16色调色板）以及默认的前景色/背景色，
+//! 没有通过SGR指定颜色的字符都使用默认颜色显示。
+//!
+//! 默认值可以通过内核命令行设置：
+//! - `vt_theme=<name>`: 主题名称，见[`VC_THEMES`]
+//! - `vt_color=<fg>,<bg>`: 默认的前景色和背景色（ANSI颜色编号，0~15）
+//!
+//! 运行时可以通过[`ThemeIoctlCmd`]中的ioctl修改单个终端的设置。
+
+use log::warn;
+use system_error::SystemError;
+
+use crate::filesystem::vfs::ioctl::IoctlArg;
+
+use super::{virtual_console::VirtualConsoleData, Color};
+
+kernel_cmdline_param_kv!(VT_THEME_PARAM, vt_theme, "");
+kernel_cmdline_param_kv!(VT_COLOR_PARAM, vt_color, "");
+
+/// 内置的配色主题
+#[derive(Debug)]
+pub struct VcTheme {
+    pub name: &'static str,
+    /// 按ANSI颜色编号排列的调色板，格式为0xRRGGBB
+    pub palette: [u32; 16],
+    /// 默认前景色（ANSI颜色编号）
+    pub fg: u8,
+    /// 默认背景色（ANSI颜色编号）
+    pub bg: u8,
+}
+
+impl VcTheme {
+    /// 获取调色板中第`idx`个颜色
+    pub fn color(&self, idx: usize) -> Color {
+        let rgb = self.palette[idx];
+        Color {
+            red: ((rgb >> 16) & 0xff) as u16,
+            green: ((rgb >> 8) & 0xff) as u16,
+            blue: (rgb & 0xff) as u16,
+            transp: 0,
+        }
+    }
+}
+
+pub static VC_THEMES: &[VcTheme] = &[
+    VcTheme {
+        name: "linux",
+        palette: [
+            0x000000, 0xaa0000, 0x00aa00, 0xaa5500, 0x0000aa, 0xaa00aa, 0x00aaaa, 0xaaaaaa,
+            0x555555, 0xff5555, 0x55ff55, 0xffff55, 0x5555ff, 0xff55ff, 0x55ffff, 0xffffff,
+        ],
+        fg: 15,
+        bg: 0,
+    },
+    VcTheme {
+        name: "solarized-dark",
+        palette: [
+            0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5,
+            0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+        ],
+        fg: 12,
+        bg: 8,
+    },
+    VcTheme {
+        name: "solarized-light",
+        palette: [
+            0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5,
+            0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+        ],
+        fg: 11,
+        bg: 15,
+    },
+    VcTheme {
+        name: "gruvbox-dark",
+        palette: [
+            0x282828, 0xcc241d, 0x98971a, 0xd79921, 0x458588, 0xb16286, 0x689d6a, 0xa89984,
+            0x928374, 0xfb4934, 0xb8bb26, 0xfabd2f, 0x83a598, 0xd3869b, 0x8ec07c, 0xebdbb2,
+        ],
+        fg: 15,
+        bg: 0,
+    },
+];
+
+/// 根据名称查找主题，返回其在[`VC_THEMES`]中的下标
+pub fn lookup_theme(name: &str) -> Option<usize> {
+    VC_THEMES.iter().position(|t| t.name == name)
+}
+
+/// 虚拟终端的颜色设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VcColorConfig {
+    /// 主题在[`VC_THEMES`]中的下标
+    pub theme: usize,
+    /// 默认前景色（ANSI颜色编号）
+    pub fg: u8,
+    /// 默认背景色（ANSI颜色编号）
+    pub bg: u8,
+}
+
+impl VcColorConfig {
+    /// 根据内核命令行参数得到新建终端的默认颜色设置
+    pub fn from_cmdline() -> Self {
+        let mut theme = 0;
+        if let Some(name) = VT_THEME_PARAM.value_str().map(|s| s.trim()) {
+            if !name.is_empty() {
+                theme = lookup_theme(name).unwrap_or_else(|| {
+                    warn!("vt: unknown theme '{}', fallback to default", name);
+                    0
+                });
+            }
+        }
+
+        let mut config = Self {
+            theme,
+            fg: VC_THEMES[theme].fg,
+            bg: VC_THEMES[theme].bg,
+        };
+
+        if let Some(color) = VT_COLOR_PARAM.value_str().map(|s| s.trim()) {
+            if !color.is_empty() {
+                match Self::parse_color_pair(color) {
+                    Some((fg, bg)) => {
+                        config.fg = fg;
+                        config.bg = bg;
+                    }
+                    None => warn!("vt: invalid vt_color '{}', expect <fg>,<bg>", color),
+                }
+            }
+        }
+
+        config
+    }
+
+    fn parse_color_pair(s: &str) -> Option<(u8, u8)> {
+        let (fg, bg) = s.split_once(',')?;
+        let fg = fg.trim().parse::<u8>().ok().filter(|c| *c < 16)?;
+        let bg = bg.trim().parse::<u8>().ok().filter(|c| *c < 16)?;
+        Some((fg, bg))
+    }
+
+    #[inline]
+    pub fn theme(&self) -> &'static VcTheme {
+        &VC_THEMES[self.theme]
+    }
+}
+
+/// 主题相关的ioctl（DragonOS私有）
+pub struct ThemeIoctlCmd;
+
+impl ThemeIoctlCmd {
+    /// 获取当前终端的颜色设置，参数为`*mut VcThemeArgs`
+    pub const KDGETTHEME: u32 = 0x4BF0;
+    /// 设置当前终端的颜色设置，参数为`*const VcThemeArgs`
+    pub const KDSETTHEME: u32 = 0x4BF1;
+}
+
+/// 主题ioctl的用户态参数。设置时，值为-1的字段保持不变
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VcThemeArgs {
+    /// 主题在内置主题表中的下标
+    pub theme: i32,
+    /// 默认前景色（ANSI颜色编号，0~15）
+    pub fg: i32,
+    /// 默认背景色（ANSI颜色编号，0~15）
+    pub bg: i32,
+}
+
+/// ## 处理主题相关的ioctl
+pub fn theme_ioctl(
+    vc_data: &mut VirtualConsoleData,
+    cmd: u32,
+    arg: usize,
+) -> Result<(), SystemError> {
+    match cmd {
+        ThemeIoctlCmd::KDGETTHEME => {
+            let config = vc_data.color_config();
+            let args = VcThemeArgs {
+                theme: config.theme as i32,
+                fg: config.fg as i32,
+                bg: config.bg as i32,
+            };
+            IoctlArg::new(cmd, arg).write(&args)?;
+            Ok(())
+        }
+        ThemeIoctlCmd::KDSETTHEME => {
+            let args: VcThemeArgs = IoctlArg::new(cmd, arg).read()?;
+
+            let mut config = vc_data.color_config();
+            if args.theme != -1 {
+                if args.theme < 0 || args.theme as usize >= VC_THEMES.len() {
+                    return Err(SystemError::EINVAL);
+                }
+                // 切换主题时，默认颜色也随之切换，除非同时显式指定
+                config.theme = args.theme as usize;
+                config.fg = config.theme().fg;
+                config.bg = config.theme().bg;
+            }
+            for (val, target) in [(args.fg, &mut config.fg), (args.bg, &mut config.bg)] {
+                if val != -1 {
+                    if !(0..16).contains(&val) {
+                        return Err(SystemError::EINVAL);
+                    }
+                    *target = val as u8;
+                }
+            }
+
+            vc_data.set_color_config(config);
+            Ok(())
+        }
+        _ => Err(SystemError::ENOIOCTLCMD),
+    }
+}
@@ -17,9 +17,10 @@ use crate::{
 };
 
 use super::{
+    bell::{self, VcBellConfig},
     console_map::{TranslationMap, TranslationMapType},
-    vc_manager, Color, DrawRegion, VtMode, VtModeData, COLOR_TABLE, DEFAULT_BLUE, DEFAULT_GREEN,
-    DEFAULT_RED,
+    theme::VcColorConfig,
+    vc_manager, Color, DrawRegion, VtMode, VtModeData, COLOR_TABLE,
 };
 
 pub(super) const NPAR: usize = 16;
@@ -67,6 +68,12 @@ pub struct VirtualConsoleData {
     pub palette: [Color; 16],
     /// 默认颜色
     pub def_color: u8,
+    /// 配色主题以及默认的前景色/背景色
+    color_config: VcColorConfig,
+    /// 响铃设置
+    bell_config: VcBellConfig,
+    /// 可视响铃是否正在进行（屏幕处于反色状态）
+    pub(super) bell_flashing: bool,
     /// 下划线颜色
     pub underline_color: u32,
     /// 斜体颜色
@@ -161,6 +168,9 @@ impl VirtualConsoleData {
             bottom: Default::default(),
             palette: [Default::default(); 16],
             def_color: Default::default(),
+            color_config: VcColorConfig::from_cmdline(),
+            bell_config: VcBellConfig::default(),
+            bell_flashing: false,
             underline_color: Default::default(),
             italic_color: Default::default(),
             half_color: Default::default(),
@@ -217,7 +227,7 @@ impl VirtualConsoleData {
         self.pos = self.cols * self.state.y + self.state.x;
         // self.bytes_per_row = self.cols << 1;
 
-        self.def_color = 15; // white
+        self.def_color = self.config_def_color();
         self.italic_color = 2; // green
         self.underline_color = 3; // cyan
         self.half_color = 0x08; // grey
@@ -289,13 +299,53 @@ impl VirtualConsoleData {
     }
 
     fn reset_palette(&mut self) {
+        self.load_theme_palette();
+        self.set_palette();
+    }
+
+    fn load_theme_palette(&mut self) {
+        let theme = self.color_config.theme();
         for (idx, color) in self.palette.iter_mut().enumerate() {
-            color.red = DEFAULT_RED[idx];
-            color.green = DEFAULT_GREEN[idx];
-            color.blue = DEFAULT_BLUE[idx];
+            *color = theme.color(idx);
         }
+    }
 
-        self.set_palette();
+    /// 根据颜色设置计算默认的属性颜色（低4位为前景色，高4位为背景色）
+    fn config_def_color(&self) -> u8 {
+        let fg = COLOR_TABLE[self.color_config.fg as usize];
+        let bg = COLOR_TABLE[self.color_config.bg as usize];
+        (bg << 4) | fg
+    }
+
+    pub fn color_config(&self) -> VcColorConfig {
+        self.color_config
+    }
+
+    /// ## 修改终端的配色主题和默认颜色
+    ///
+    /// 之后没有通过SGR指定颜色的字符都会使用新的默认颜色
+    pub fn set_color_config(&mut self, config: VcColorConfig) {
+        let theme_changed = config.theme != self.color_config.theme;
+        self.color_config = config;
+        self.def_color = self.config_def_color();
+        if theme_changed {
+            self.load_theme_palette();
+            // 调色板是整个显示设备共用的，只在终端可见时才下发
+            if self.is_visible() {
+                self.set_palette();
+            }
+        }
+
+        self.default_attr();
+        self.update_attr();
+    }
+
+    pub fn bell_config(&self) -> VcBellConfig {
+        self.bell_config
+    }
+
+    pub fn set_bell_config(&mut self, config: VcBellConfig) {
+        self.bell_config = config;
     }
 
     fn set_palette(&self) {
@@ -525,6 +575,77 @@ impl VirtualConsoleData {
                 .con_putc(self, i as u16, self.state.y as u32, self.state.x as u32);
     }
 
+    /// ## 反色显示屏幕上的一段字符（再次调用即可恢复）
+    ///
+    /// 参考： https://code.dragonos.org.cn/xref/linux-6.1.9/drivers/tty/vt/vt.c#invert_screen
+    ///
+    /// ### 参数
+    /// - offset: 在screen_buf中的起始偏移
+    /// - count: 字符数量
+    pub(super) fn invert_screen(&mut self, offset: usize, count: usize) {
+        let end = (offset + count).min(self.screen_buf.len());
+        if offset >= end {
+            return;
+        }
+
+        let mut screen_buf = core::mem::take(&mut self.screen_buf);
+        for cell in screen_buf[offset..end].iter_mut() {
+            *cell = self.inverted_cell(*cell);
+        }
+        self.screen_buf = screen_buf;
+
+        if !self.should_update() {
+            return;
+        }
+
+        // 按行重绘
+        let mut pos = offset;
+        while pos < end {
+            let x = pos % self.cols;
+            let y = pos / self.cols;
+            let len = (self.cols - x).min(end - pos);
+            let _ = self.driver_funcs().con_putcs(
+                self,
+                &self.screen_buf[pos..pos + len],
+                len,
+                y as u32,
+                x as u32,
+            );
+            pos += len;
+        }
+    }
+
+    /// 交换字符单元的前景色与背景色
+    fn inverted_cell(&self, a: u16) -> u16 {
+        if !self.color_mode {
+            a ^ 0x0800
+        } else if self.hi_font_mask == 0x100 {
+            (a & 0x11ff) | ((a & 0xe000) >> 4) | ((a & 0x0e00) << 4)
+        } else {
+            (a & 0x88ff) | ((a & 0x7000) >> 4) | ((a & 0x0700) << 4)
+        }
+    }
+
+    /// ## 以反色或正常颜色重绘整个屏幕
+    ///
+    /// 与[`Self::invert_screen`]不同，这里不会修改screen_buf，用于可视响铃这样的临时效果
+    pub(super) fn flash_screen(&self, inverted: bool) {
+        let mut line = Vec::with_capacity(self.cols);
+        for y in 0..self.rows {
+            let row = &self.screen_buf[y * self.cols..(y + 1) * self.cols];
+            let cells = if inverted {
+                line.clear();
+                line.extend(row.iter().map(|a| self.inverted_cell(*a)));
+                &line[..]
+            } else {
+                row
+            };
+            let _ = self
+                .driver_funcs()
+                .con_putcs(self, cells, self.cols, y as u32, 0);
+        }
+    }
+
     pub fn hide_cursor(&mut self) {
         // TODO: 处理选择
 
@@ -969,10 +1090,41 @@ impl VirtualConsoleData {
                 todo!("csi_at todo");
             }
             ']' => {
-                todo!("set termial command todo");
+                self.setterm_command();
+                return;
+            }
+            _ => {}
+        }
+    }
+
+    /// ## 处理`ESC [ n ; m ]`形式的setterm命令
+    ///
+    /// 参考： https://code.dragonos.org.cn/xref/linux-6.1.9/drivers/tty/vt/vt.c#setterm_command
+    ///
+    /// 目前只支持设置响铃的音调（10）和时长（11），其余命令忽略
+    fn setterm_command(&mut self) {
+        let mut config = self.bell_config;
+        match self.par[0] {
+            10 => {
+                // 设置响铃音调，0或者超出范围时恢复默认值
+                config.pitch = if self.npar >= 1 && self.par[1] > 0 && self.par[1] < 65536 {
+                    self.par[1]
+                } else {
+                    VcBellConfig::DEFAULT_PITCH
+                };
+            }
+            11 => {
+                // 设置响铃时长
+                config.duration_ms =
+                    if self.npar >= 1 && self.par[1] < VcBellConfig::MAX_DURATION_MS {
+                        self.par[1]
+                    } else {
+                        VcBellConfig::DEFAULT_DURATION_MS
+                    };
             }
             _ => {}
         }
+        self.bell_config = config;
     }
 
     /// ##  处理Control Sequence Introducer（控制序列引导符） m字符
@@ -1271,8 +1423,9 @@ impl VirtualConsoleData {
                 // BEL
                 if self.vc_state.is_ansi_control_string() {
                     self.vc_state = VirtualConsoleState::ESnormal;
+                    return;
                 }
-                // TODO: 发出声音？
+                bell::vc_bell(self);
                 return;
             }
             8 => {
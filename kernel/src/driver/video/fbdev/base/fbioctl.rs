@@ -0,0 +1,178 @@
+//! 帧缓冲设备（/dev/fbN）的ioctl
+//!
+//! 参考 Linux include/uapi/linux/fb.h
+
+use alloc::sync::Arc;
+use system_error::SystemError;
+
+use crate::filesystem::vfs::ioctl::IoctlArg;
+
+use super::{
+    FbBitfield, FbColorMode, FbPixelFormat, FbVarScreenInfo, FixedScreenInfo, FrameBuffer,
+};
+
+/// 帧缓冲设备的ioctl命令
+struct FbIoctlCmd;
+
+impl FbIoctlCmd {
+    /// 获取可变屏幕信息，参数为`struct fb_var_screeninfo *`
+    const FBIOGET_VSCREENINFO: u32 = 0x4600;
+    /// 获取固定屏幕信息，参数为`struct fb_fix_screeninfo *`
+    const FBIOGET_FSCREENINFO: u32 = 0x4602;
+}
+
+/// 与Linux的`struct fb_bitfield`内存布局一致
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct UserFbBitfield {
+    offset: u32,
+    length: u32,
+    msb_right: u32,
+}
+
+impl From<FbBitfield> for UserFbBitfield {
+    fn from(bf: FbBitfield) -> Self {
+        Self {
+            offset: bf.offset,
+            length: bf.length,
+            msb_right: bf.msb_right as u32,
+        }
+    }
+}
+
+/// 与Linux的`struct fb_var_screeninfo`内存布局一致
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct UserFbVarScreenInfo {
+    xres: u32,
+    yres: u32,
+    xres_virtual: u32,
+    yres_virtual: u32,
+    xoffset: u32,
+    yoffset: u32,
+    bits_per_pixel: u32,
+    grayscale: u32,
+    red: UserFbBitfield,
+    green: UserFbBitfield,
+    blue: UserFbBitfield,
+    transp: UserFbBitfield,
+    nonstd: u32,
+    activate: u32,
+    height: u32,
+    width: u32,
+    accel_flags: u32,
+    pixclock: u32,
+    left_margin: u32,
+    right_margin: u32,
+    upper_margin: u32,
+    lower_margin: u32,
+    hsync_len: u32,
+    vsync_len: u32,
+    sync: u32,
+    vmode: u32,
+    rotate: u32,
+    colorspace: u32,
+    reserved: [u32; 4],
+}
+
+impl From<&FbVarScreenInfo> for UserFbVarScreenInfo {
+    fn from(var: &FbVarScreenInfo) -> Self {
+        Self {
+            xres: var.xres,
+            yres: var.yres,
+            xres_virtual: var.xres_virtual,
+            yres_virtual: var.yres_virtual,
+            xoffset: var.xoffset,
+            yoffset: var.yoffset,
+            bits_per_pixel: var.bits_per_pixel,
+            grayscale: (var.color_mode == FbColorMode::GrayScale) as u32,
+            red: var.red.into(),
+            green: var.green.into(),
+            blue: var.blue.into(),
+            transp: var.transp.into(),
+            nonstd: (var.pixel_format != FbPixelFormat::Standard) as u32,
+            activate: var.activate.bits(),
+            // 未知的物理尺寸用-1表示
+            height: var.height.unwrap_or(u32::MAX),
+            width: var.width.unwrap_or(u32::MAX),
+            accel_flags: 0,
+            pixclock: var.pixclock,
+            left_margin: var.left_margin,
+            right_margin: var.right_margin,
+            upper_margin: var.upper_margin,
+            lower_margin: var.lower_margin,
+            hsync_len: var.hsync_len,
+            vsync_len: var.vsync_len,
+            sync: var.sync.bits(),
+            vmode: var.vmode.bits(),
+            rotate: var.rotate_angle,
+            colorspace: var.colorspace as u32,
+            reserved: [0; 4],
+        }
+    }
+}
+
+/// 与Linux的`struct fb_fix_screeninfo`内存布局一致
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct UserFbFixScreenInfo {
+    id: [u8; 16],
+    smem_start: usize,
+    smem_len: u32,
+    fb_type: u32,
+    type_aux: u32,
+    visual: u32,
+    xpanstep: u16,
+    ypanstep: u16,
+    ywrapstep: u16,
+    line_length: u32,
+    mmio_start: usize,
+    mmio_len: u32,
+    accel: u32,
+    capabilities: u16,
+    reserved: [u16; 2],
+}
+
+impl From<&FixedScreenInfo> for UserFbFixScreenInfo {
+    fn from(fix: &FixedScreenInfo) -> Self {
+        let mut id = [0u8; 16];
+        for (dst, c) in id.iter_mut().zip(fix.id.iter()) {
+            *dst = *c as u8;
+        }
+        Self {
+            id,
+            smem_start: fix.smem_start.map(|a| a.data()).unwrap_or(0),
+            smem_len: fix.smem_len as u32,
+            fb_type: fix.fb_type as u32,
+            type_aux: fix.type_aux,
+            visual: fix.visual as u32,
+            xpanstep: fix.xpanstep,
+            ypanstep: fix.ypanstep,
+            ywrapstep: fix.ywrapstep,
+            line_length: fix.line_length,
+            mmio_start: fix.mmio_start.map(|a| a.data()).unwrap_or(0),
+            mmio_len: fix.mmio_len as u32,
+            accel: fix.accel as u32,
+            capabilities: fix.capabilities as u16,
+            reserved: [0; 2],
+        }
+    }
+}
+
+/// ## 处理帧缓冲设备的ioctl
+pub fn fb_ioctl(fb: &Arc<dyn FrameBuffer>, cmd: u32, arg: usize) -> Result<usize, SystemError> {
+    let arg = IoctlArg::new(cmd, arg);
+    match cmd {
+        FbIoctlCmd::FBIOGET_VSCREENINFO => {
+            let var = UserFbVarScreenInfo::from(&fb.current_fb_var());
+            arg.write(&var)?;
+            Ok(0)
+        }
+        FbIoctlCmd::FBIOGET_FSCREENINFO => {
+            let fix = UserFbFixScreenInfo::from(&fb.current_fb_fix());
+            arg.write(&fix)?;
+            Ok(0)
+        }
+        _ => Err(SystemError::ENOIOCTLCMD),
+    }
+}
@@ -26,7 +26,7 @@ use crate::{
         subsys::SubSysPrivate,
     },
     filesystem::{
-        devfs::{devfs_register, DevFS, DeviceINode, LockedDevFSInode},
+        devfs::{devfs_register, DevFS, DevNodeAttr, DeviceINode, LockedDevFSInode},
         kernfs::KernFSInode,
         sysfs::AttributeGroup,
         vfs::{
@@ -41,7 +41,9 @@ use crate::{
     },
 };
 
-use super::{fbcon::fb_console_init, fbsysfs::FbDeviceAttrGroup, FbId, FrameBuffer};
+use super::{
+    fbcon::fb_console_init, fbioctl::fb_ioctl, fbsysfs::FbDeviceAttrGroup, FbId, FrameBuffer,
+};
 
 /// `/sys/class/graphics` 的 class 实例
 static mut CLASS_GRAPHICS_INSTANCE: Option<Arc<GraphicsClass>> = None;
@@ -216,6 +218,8 @@ impl FrameBufferManager {
 pub struct FbDevice {
     inner: SpinLock<InnerFbDevice>,
     kobj_state: LockedKObjectState,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
 }
 
 impl FbDevice {
@@ -235,6 +239,7 @@ impl FbDevice {
                 ),
             }),
             kobj_state: LockedKObjectState::new(None),
+            attr: DevNodeAttr::new(InodeMode::from_bits_truncate(0o666)),
         });
 
         let mut inner_guard = r.inner.lock();
@@ -437,6 +442,16 @@ impl IndexNode for FbDevice {
         return fb.fb_write(&buf[0..len], offset);
     }
 
+    fn ioctl(
+        &self,
+        cmd: u32,
+        data: usize,
+        _private_data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let fb = self.inner.lock().fb.upgrade().ok_or(SystemError::ENODEV)?;
+        return fb_ioctl(&fb, cmd, data);
+    }
+
     fn fs(&self) -> Arc<dyn FileSystem> {
         self.inner
             .lock()
@@ -456,7 +471,14 @@ impl IndexNode for FbDevice {
     }
 
     fn metadata(&self) -> Result<Metadata, SystemError> {
-        Ok(self.inner.lock().devfs_metadata.clone())
+        let mut metadata = self.inner.lock().devfs_metadata.clone();
+        self.attr.apply(&mut metadata);
+        Ok(metadata)
+    }
+
+    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
+        self.attr.update(metadata);
+        Ok(())
     }
 
     fn resize(&self, _len: usize) -> Result<(), SystemError> {
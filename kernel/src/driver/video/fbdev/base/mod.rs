@@ -24,6 +24,7 @@ const COLOR_TABLE_16: &[u32] = &[0x00000000, 0xffff0000, 0x0000ffff, 0xffffffff]
 const COLOR_TABLE_32: &[u32] = &[0x00000000, 0xffffffff];
 
 pub mod fbcon;
+pub mod fbioctl;
 pub mod fbmem;
 pub mod fbsysfs;
 pub mod modedb;
@@ -38,6 +38,66 @@ pub struct VideoRefreshManager {
     device_buffer: RwLock<ScmBufferInfo>,
     refresh_target: RwLock<Option<Arc<SpinLock<Box<[u8]>>>>>,
     running: AtomicBool,
+    /// 8bpp调色板模式下，像素值到颜色的映射
+    palette: RwLock<VideoPalette>,
+}
+
+/// 8bpp调色板模式下使用的调色板
+///
+/// 帧缓冲区中的每个像素是调色板的下标，输出时需要把RGB颜色转换为最接近的下标。
+/// 默认只包含VGA的16种标准颜色，它们在VGA默认的DAC调色板中位于0~15，
+/// 因此在固件没有修改调色板的情况下也能正确显示。
+#[derive(Debug, Clone)]
+pub struct VideoPalette {
+    /// 格式为0xRRGGBB
+    colors: [u32; Self::MAX_COLORS],
+    len: usize,
+}
+
+impl VideoPalette {
+    pub const MAX_COLORS: usize = 256;
+
+    const VGA_COLORS: [u32; 16] = [
+        0x000000, 0x0000aa, 0x00aa00, 0x00aaaa, 0xaa0000, 0xaa00aa, 0xaa5500, 0xaaaaaa, 0x555555,
+        0x5555ff, 0x55ff55, 0x55ffff, 0xff5555, 0xff55ff, 0xffff55, 0xffffff,
+    ];
+
+    const fn new_vga() -> Self {
+        let mut colors = [0u32; Self::MAX_COLORS];
+        let mut i = 0;
+        while i < Self::VGA_COLORS.len() {
+            colors[i] = Self::VGA_COLORS[i];
+            i += 1;
+        }
+        Self {
+            colors,
+            len: Self::VGA_COLORS.len(),
+        }
+    }
+
+    /// 找到与`rgb`最接近的调色板下标
+    pub fn nearest(&self, rgb: u32) -> u8 {
+        let (r, g, b) = Self::split(rgb);
+        let mut best = 0;
+        let mut best_dist = u32::MAX;
+        for (idx, color) in self.colors[..self.len].iter().enumerate() {
+            let (pr, pg, pb) = Self::split(*color);
+            let dist = r.abs_diff(pr).pow(2) + g.abs_diff(pg).pow(2) + b.abs_diff(pb).pow(2);
+            if dist < best_dist {
+                best = idx;
+                best_dist = dist;
+                if dist == 0 {
+                    break;
+                }
+            }
+        }
+        best as u8
+    }
+
+    #[inline]
+    fn split(rgb: u32) -> (u32, u32, u32) {
+        ((rgb >> 16) & 0xff, (rgb >> 8) & 0xff, rgb & 0xff)
+    }
 }
 
 const REFRESH_INTERVAL: u64 = 30;
@@ -154,6 +214,33 @@ impl VideoRefreshManager {
         return self.device_buffer.read();
     }
 
+    /// ## 设置8bpp调色板模式下使用的调色板
+    ///
+    /// 应当与硬件实际使用的调色板（如VGA DAC）保持一致。
+    ///
+    /// ### 参数
+    ///
+    /// - `colors`: 调色板颜色，格式为0xRRGGBB，下标即像素值。长度为1~256
+    pub fn set_palette(&self, colors: &[u32]) -> Result<(), SystemError> {
+        if colors.is_empty() || colors.len() > VideoPalette::MAX_COLORS {
+            return Err(SystemError::EINVAL);
+        }
+        let mut palette = self.palette.write_irqsave();
+        palette.colors[..colors.len()].copy_from_slice(colors);
+        palette.len = colors.len();
+        return Ok(());
+    }
+
+    /// 将RGB颜色转换为帧缓冲区中的像素值
+    ///
+    /// 对于8bpp的帧缓冲区，返回调色板中最接近的颜色的下标；其他位深度直接返回RGB值
+    pub fn color_to_pixel(&self, rgb: u32) -> u32 {
+        if self.device_buffer().bit_depth() == 8 {
+            return self.palette.read_irqsave().nearest(rgb) as u32;
+        }
+        return rgb;
+    }
+
     /// 在riscv64平台下暂时不支持
     #[cfg(any(target_arch = "riscv64", target_arch = "loongarch64"))]
     pub unsafe fn video_init() -> Result<(), SystemError> {
@@ -211,6 +298,7 @@ impl VideoRefreshManager {
             device_buffer: RwLock::new(device_buffer),
             refresh_target: RwLock::new(None),
             running: AtomicBool::new(false),
+            palette: RwLock::new(VideoPalette::new_vga()),
         };
 
         __MAMAGER = Some(result);
@@ -1,14 +1,25 @@
+use alloc::vec::Vec;
 use fdt::node::FdtNode;
-use log::error;
+use log::{error, warn};
 use system_error::SystemError;
 
-use crate::driver::{
-    open_firmware::fdt::open_firmware_fdt_driver, virtio::transport_mmio::VirtIOMmioTransport,
+use crate::{
+    driver::{
+        open_firmware::fdt::open_firmware_fdt_driver, virtio::transport_mmio::VirtIOMmioTransport,
+    },
+    exception::HardwareIrqNumber,
+    init::boot_params,
 };
 
 use super::{transport::VirtIOTransport, virtio::virtio_device_init};
 
+/// 在命令行中描述virtio-mmio设备的参数名
+const VIRTIO_MMIO_CMDLINE_KEY: &str = "virtio_mmio.device=";
+
 pub(super) fn virtio_probe_mmio() {
+    // 没有设备树的平台（例如Firecracker和QEMU microvm）通过命令行描述设备
+    probe_virtio_mmio_cmdline();
+
     if let Err(e) = do_probe_virtio_mmio() {
         error!("virtio_probe_mmio failed: {:?}", e);
     }
@@ -25,7 +36,102 @@ fn do_probe_virtio_mmio() -> Result<(), SystemError> {
     };
 
     for node in open_firmware_fdt_driver().find_node_by_compatible(&fdt, "virtio,mmio") {
+        // QEMU virt等平台预留了很多没有插入设备的槽，探测它们会返回ENODEV
+        if !fdt_node_available(&node) {
+            continue;
+        }
         do_check(node).ok();
     }
     Ok(())
 }
+
+/// 设备树节点没有`status`属性，或者`status`为`okay`时表示可用
+fn fdt_node_available(node: &FdtNode) -> bool {
+    node.property("status")
+        .and_then(|p| p.as_str())
+        .is_none_or(|status| status == "okay" || status == "ok")
+}
+
+/// 命令行中描述的一个virtio-mmio设备
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VirtioMmioCmdlineDevice {
+    base: usize,
+    size: usize,
+    irq: u32,
+}
+
+/// 探测命令行中的`virtio_mmio.device=<size>@<baseaddr>:<irq>[:<id>]`，可以出现多次
+///
+/// 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/drivers/virtio/virtio_mmio.c#700
+fn probe_virtio_mmio_cmdline() {
+    let devices: Vec<VirtioMmioCmdlineDevice> = boot_params()
+        .read()
+        .boot_cmdline_str()
+        .split_ascii_whitespace()
+        .filter_map(|arg| arg.strip_prefix(VIRTIO_MMIO_CMDLINE_KEY))
+        .filter_map(|value| {
+            parse_cmdline_device(value)
+                .map_err(|_| warn!("virtio-mmio: invalid device description {:?}", value))
+                .ok()
+        })
+        .collect();
+
+    for dev in devices {
+        match VirtIOMmioTransport::with_resource(dev.base, dev.size, cmdline_irq(dev.irq)) {
+            Ok(mmio_transport) => {
+                let device_id = mmio_transport.device_id();
+                virtio_device_init(VirtIOTransport::Mmio(mmio_transport), device_id, None);
+            }
+            Err(e) => warn!(
+                "virtio-mmio: no device at {:#x} (irq {}): {:?}",
+                dev.base, dev.irq, e
+            ),
+        }
+    }
+}
+
+fn parse_cmdline_device(value: &str) -> Result<VirtioMmioCmdlineDevice, SystemError> {
+    let (size, rest) = value.split_once('@').ok_or(SystemError::EINVAL)?;
+    let mut fields = rest.split(':');
+    let base = fields.next().ok_or(SystemError::EINVAL)?;
+    let irq = fields.next().ok_or(SystemError::EINVAL)?;
+    // 第四个字段是平台设备的编号，这里不使用
+
+    let size = parse_number(size)?;
+    let base = parse_number(base)?;
+    let irq = u32::try_from(parse_number(irq)?).map_err(|_| SystemError::EINVAL)?;
+    if size == 0 {
+        return Err(SystemError::EINVAL);
+    }
+    Ok(VirtioMmioCmdlineDevice { base, size, irq })
+}
+
+/// 解析十进制或者`0x`开头的十六进制数，支持`K`/`M`/`G`后缀（大小写均可）
+fn parse_number(s: &str) -> Result<usize, SystemError> {
+    let (num, shift) = match s.as_bytes().last() {
+        Some(b'k' | b'K') => (&s[..s.len() - 1], 10),
+        Some(b'm' | b'M') => (&s[..s.len() - 1], 20),
+        Some(b'g' | b'G') => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    let num = match num.strip_prefix("0x").or_else(|| num.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => num.parse(),
+    }
+    .map_err(|_| SystemError::EINVAL)?;
+    num.checked_mul(1 << shift).ok_or(SystemError::EINVAL)
+}
+
+/// 命令行中的中断号在x86_64上是IO APIC的引脚号（GSI）
+fn cmdline_irq(irq: u32) -> HardwareIrqNumber {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use crate::arch::driver::apic::ioapic::IoApic;
+        HardwareIrqNumber::new(IoApic::VECTOR_BASE as u32 + irq)
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        HardwareIrqNumber::new(irq)
+    }
+}
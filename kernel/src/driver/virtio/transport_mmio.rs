@@ -2,10 +2,10 @@ use core::ptr::NonNull;
 
 use alloc::sync::Arc;
 use fdt::node::FdtNode;
-use log::info;
+use log::{info, warn};
 use system_error::SystemError;
 use virtio_drivers::transport::{
-    mmio::{MmioTransport, VirtIOHeader},
+    mmio::{MmioError, MmioTransport, VirtIOHeader},
     Transport,
 };
 
@@ -20,6 +20,9 @@ use crate::{
     },
 };
 
+/// 设备树中没有给出寄存器大小时使用的默认值，即virtio-mmio寄存器布局的大小
+const VIRTIO_MMIO_DEFAULT_SIZE: usize = 0x200;
+
 pub struct VirtIOMmioTransport {
     mmio_transport: MmioTransport,
     _mmio_guard: MMIOSpaceGuard,
@@ -34,19 +37,40 @@ impl VirtIOMmioTransport {
             .ok_or(SystemError::EINVAL)?
             .next()
             .ok_or(SystemError::EINVAL)?;
-        let paddr = reg.starting_address as usize;
-        let size = reg.size.unwrap_or(0);
-        let page_offset = paddr % MMArch::PAGE_SIZE;
-        let paddr = paddr - page_offset;
-        let size = page_align_up(size + page_offset);
         let irq = node
             .interrupts()
             .ok_or(SystemError::EINVAL)?
             .next()
             .ok_or(SystemError::EINVAL)?;
 
+        Self::with_resource(
+            reg.starting_address as usize,
+            reg.size.unwrap_or(VIRTIO_MMIO_DEFAULT_SIZE),
+            HardwareIrqNumber::new(irq as u32),
+        )
+    }
+
+    /// # 根据寄存器的地址和中断号创建
+    ///
+    /// ## 参数
+    /// - `paddr`, `size`: 寄存器的物理地址和大小
+    /// - `irq`: 设备的中断号
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::ENODEV)`: 这个位置没有设备（设备ID为0的占位槽）
+    /// - `Err(SystemError::EINVAL)`: 不是virtio-mmio设备，或者版本不支持
+    pub fn with_resource(
+        paddr: usize,
+        size: usize,
+        irq: HardwareIrqNumber,
+    ) -> Result<Self, SystemError> {
+        // 有的平台上多个设备共用一个页（例如间隔为0x200），设备ID需要使用完整的地址
         let device_id = DeviceId::new(None, Some(format!("virtio_mmio_{:#X}", paddr))).unwrap();
 
+        let page_offset = paddr % MMArch::PAGE_SIZE;
+        let paddr = paddr - page_offset;
+        let size = page_align_up(size + page_offset);
+
         let mmio_guard = mmio_pool().create_mmio(size)?;
         unsafe { mmio_guard.map_phys(PhysAddr::new(paddr), size) }?;
 
@@ -60,18 +84,23 @@ impl VirtIOMmioTransport {
                     mmio_transport.vendor_id(),
                     mmio_transport.device_type(),
                     mmio_transport.version(),
-                    irq as u32
+                    irq.data()
                 );
 
                 Ok(Self {
                     mmio_transport,
                     _mmio_guard: mmio_guard,
-                    irq: HardwareIrqNumber::new(irq as u32),
+                    irq,
                     device_id,
                 })
             }
-            Err(_) => {
-                // warn!("MmioTransport::new failed: {:?}", e);
+            Err(MmioError::ZeroDeviceId) => Err(SystemError::ENODEV),
+            Err(e) => {
+                warn!(
+                    "virtio-mmio: bad device at {:#x}: {:?}",
+                    paddr + page_offset,
+                    e
+                );
                 Err(SystemError::EINVAL)
             }
         }
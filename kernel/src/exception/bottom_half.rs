@@ -108,3 +108,14 @@ impl Drop for LocalBhDisableGuard {
         }
     }
 }
+
+/// 清零本 CPU 的 BH 禁用计数，返回清零前的值
+///
+/// 只用于oops：出错的进程不会再释放它持有的 [`LocalBhDisableGuard`]，
+/// 如果不清零，本 CPU 之后再也不会执行 softirq。
+///
+/// # Safety
+/// 调用者必须保证本 CPU 上已经没有其他执行流持有 [`LocalBhDisableGuard`]
+pub unsafe fn local_bh_reset() -> usize {
+    local_cnt().swap(0, Ordering::SeqCst)
+}
@@ -1,14 +1,15 @@
 use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use system_error::SystemError;
 
 #[cfg(target_arch = "x86_64")]
 use crate::arch::driver::apic::{CurrentApic, LocalAPIC};
 
 use crate::{
-    arch::MMArch,
-    mm::MemoryManagementArch,
+    arch::{interrupt::ipi::send_ipi, MMArch},
+    mm::{percpu::PerCpu, MemoryManagementArch},
     sched::{SchedMode, __schedule},
-    smp::cpu::ProcessorId,
+    smp::{core::smp_get_processor_id, cpu::ProcessorId},
 };
 
 use super::{
@@ -71,8 +72,74 @@ impl IrqHandler for FlushTLBIpiHandler {
         _static_data: Option<&dyn IrqHandlerData>,
         _dynamic_data: Option<Arc<dyn IrqHandlerData>>,
     ) -> Result<IrqReturn, SystemError> {
+        // 先读取代数再刷新，保证确认的代数之前的修改都已经生效
+        let gen = TLB_FLUSH_GEN.load(Ordering::SeqCst);
         unsafe { MMArch::invalidate_all() };
+        tlb_flush_ack(gen);
 
         Ok(IrqReturn::Handled)
     }
 }
+
+/// 同步TLB刷新的代数，每次[`flush_tlb_other_cpus`]递增
+static TLB_FLUSH_GEN: AtomicUsize = AtomicUsize::new(1);
+/// 每个CPU已经完成的同步TLB刷新的代数，为0表示这个CPU不在线，不需要等待它
+static TLB_FLUSH_DONE: [AtomicUsize; PerCpu::MAX_CPU_NUM as usize] =
+    [const { AtomicUsize::new(0) }; PerCpu::MAX_CPU_NUM as usize];
+
+fn tlb_flush_ack(gen: usize) {
+    let done = &TLB_FLUSH_DONE[smp_get_processor_id().data() as usize];
+    if done.load(Ordering::SeqCst) != 0 {
+        done.fetch_max(gen, Ordering::SeqCst);
+    }
+}
+
+/// # 标记当前CPU能否响应同步TLB刷新
+///
+/// CPU在能够处理IPI之后标记为在线，停机之前标记为离线，
+/// [`flush_tlb_other_cpus`]只等待在线的CPU
+pub fn tlb_flush_set_online(online: bool) {
+    let done = &TLB_FLUSH_DONE[smp_get_processor_id().data() as usize];
+    if online {
+        done.store(TLB_FLUSH_GEN.load(Ordering::SeqCst), Ordering::SeqCst);
+    } else {
+        done.store(0, Ordering::SeqCst);
+    }
+}
+
+/// # 刷新其他CPU的TLB，并等待它们完成
+///
+/// 降低页表项的权限（例如清除写权限）之后，如果地址空间可能正在其他CPU上使用，
+/// 必须等其他CPU都刷新了TLB才能返回，否则它们仍然可以通过旧的TLB项写入页面。
+///
+/// 等待期间会处理其他CPU发给自己的刷新请求，即使关闭了中断，两个CPU同时调用也不会死锁
+pub fn flush_tlb_other_cpus() {
+    // riscv的SBI远程刷新返回时已经完成
+    if !cfg!(target_arch = "x86_64") {
+        send_ipi(IpiKind::FlushTLB, IpiTarget::Other);
+        return;
+    }
+
+    let gen = TLB_FLUSH_GEN.fetch_add(1, Ordering::SeqCst) + 1;
+    send_ipi(IpiKind::FlushTLB, IpiTarget::Other);
+
+    let current = smp_get_processor_id().data() as usize;
+    for (cpu, done) in TLB_FLUSH_DONE.iter().enumerate() {
+        if cpu == current {
+            continue;
+        }
+        loop {
+            let acked = done.load(Ordering::SeqCst);
+            if acked == 0 || acked >= gen {
+                break;
+            }
+            // 替可能在等待自己的CPU完成本地的刷新
+            let pending = TLB_FLUSH_GEN.load(Ordering::SeqCst);
+            if TLB_FLUSH_DONE[current].load(Ordering::SeqCst) < pending {
+                unsafe { MMArch::invalidate_all() };
+                tlb_flush_ack(pending);
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
@@ -1,8 +1,15 @@
-use core::ops::Add;
+use core::{
+    ops::Add,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
+use alloc::vec::Vec;
 use system_error::SystemError;
 
-use crate::arch::CurrentIrqArch;
+use crate::{
+    arch::CurrentIrqArch,
+    mm::percpu::{PerCpu, PerCpuVar},
+};
 
 pub mod bottom_half;
 pub mod debug;
@@ -112,6 +119,49 @@ impl Drop for IrqFlagsGuard {
     }
 }
 
+lazy_static! {
+    /// 每个CPU上正在处理的硬中断的嵌套层数
+    static ref HARDIRQ_COUNT: PerCpuVar<AtomicUsize> = {
+        let mut v = Vec::with_capacity(PerCpu::MAX_CPU_NUM as usize);
+        v.resize_with(PerCpu::MAX_CPU_NUM as usize, || AtomicUsize::new(0));
+        PerCpuVar::new(v).expect("PerCpuVar length mismatch")
+    };
+}
+
+/// 硬中断处理期间持有的守卫，用于判断当前是否处于中断上下文
+///
+/// 由架构相关的中断入口在调用中断处理函数之前创建，处理完成（执行softirq之前）销毁
+pub struct HardirqGuard;
+
+impl HardirqGuard {
+    #[inline(always)]
+    pub fn enter() -> Self {
+        HARDIRQ_COUNT.get().fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for HardirqGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        HARDIRQ_COUNT.get().fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 当前CPU是否正在处理硬中断
+#[inline]
+pub fn in_hardirq() -> bool {
+    HARDIRQ_COUNT.get().load(Ordering::SeqCst) != 0
+}
+
+/// 当前CPU是否处于中断上下文（硬中断或softirq）
+///
+/// 此时的执行流不属于当前进程，不能睡眠，也不能通过结束当前进程来处理错误
+#[inline]
+pub fn in_interrupt() -> bool {
+    in_hardirq() || softirq::in_softirq()
+}
+
 // 定义中断号结构体
 // 用于表示软件逻辑视角的中断号，全局唯一
 int_like!(IrqNumber, u32);
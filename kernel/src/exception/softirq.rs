@@ -53,6 +53,14 @@ pub fn softirq_vectors() -> &'static mut Softirq {
     }
 }
 
+/// 当前CPU是否正在执行softirq
+pub fn in_softirq() -> bool {
+    match unsafe { __SORTIRQ_VECTORS.as_ref() } {
+        Some(softirq) => softirq.cpu_running_count().get().load(Ordering::SeqCst) > 0,
+        None => false,
+    }
+}
+
 #[inline(always)]
 fn cpu_pending(cpu_id: ProcessorId) -> &'static mut VecStatus {
     unsafe {
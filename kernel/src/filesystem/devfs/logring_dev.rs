@@ -0,0 +1,393 @@
+//! /dev/logring：用户态通过共享内存环形缓冲区向kmsg写日志
+//!
+//! 用户态把设备mmap（`MAP_SHARED`，从偏移0开始，长度为[`LOGRING_SIZE`]）之后，
+//! 直接把日志记录写进环形缓冲区，内核的`logring`线程定期把新记录合并到kmsg中，
+//! 写日志不需要每行一次系统调用。fsync(2)会立即取走所有已经提交的记录。
+//!
+//! 第一页是[`LogRingHeader`]，之后是数据区。数据区中的每条记录以[`LogRingRecord`]开头，
+//! 后面跟着日志文本，整条记录按8字节对齐，记录不会跨过数据区的末尾：
+//! 剩余空间放不下时，用户态写一条带有[`LOGRING_REC_PAD`]的填充记录，从数据区开头继续写。
+//!
+//! `head`和`tail`都是单调递增的字节偏移，取模数据区大小得到位置。用户态写完记录后
+//! 以release语义更新`head`；内核取走记录后更新`tail`，用户态据此判断剩余空间。
+//! 多个进程同时写入时需要自行同步。
+
+use core::{
+    mem::size_of,
+    ptr::{addr_of_mut, read_volatile},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::arch::mm::LockedFrameAllocator;
+use crate::arch::MMArch;
+use crate::debug::klog::loglevel::LogLevel;
+use crate::driver::base::device::device_number::DeviceNumber;
+use crate::filesystem::devfs::LockedDevFSInode;
+use crate::filesystem::page_cache::PageCache;
+use crate::filesystem::vfs::file::FileFlags;
+use crate::filesystem::vfs::InodeMode;
+use crate::filesystem::vfs::{
+    vcore::generate_inode_id, FilePrivateData, FileSystem, FileType, IndexNode, InodeFlags,
+    Metadata,
+};
+use crate::libs::align::page_align_up;
+use crate::libs::mutex::MutexGuard;
+use crate::libs::printk::Logger;
+use crate::mm::allocator::page_frame::PageFrameCount;
+use crate::mm::page::{page_manager_lock, PageFlags, PageType};
+use crate::mm::MemoryManagementArch;
+use crate::process::kthread::{KernelThreadClosure, KernelThreadMechanism};
+use crate::time::clocksource::HZ;
+use crate::time::timer::schedule_timeout;
+use crate::{libs::mutex::Mutex, time::PosixTimeSpec};
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use super::{DevFS, DevNodeAttr, DeviceINode};
+
+/// 头部的魔数，"LOGR"
+pub const LOGRING_MAGIC: u32 = 0x4c4f_4752;
+pub const LOGRING_VERSION: u32 = 1;
+/// 数据区大小
+pub const LOGRING_DATA_SIZE: usize = 64 * 1024;
+/// 可以映射的总大小：一页头部加上数据区
+pub const LOGRING_SIZE: usize = MMArch::PAGE_SIZE + LOGRING_DATA_SIZE;
+/// 记录的对齐
+pub const LOGRING_REC_ALIGN: usize = 8;
+/// 填充记录，跳到数据区开头
+pub const LOGRING_REC_PAD: u8 = 1;
+
+/// 没有fsync时，内核线程取记录的间隔(jiffies)，100ms
+const LOGRING_POLL_JIFFIES: i64 = (HZ / 10) as i64;
+
+/// 环形缓冲区的头部，位于映射的第一页
+#[repr(C)]
+#[derive(Debug)]
+pub struct LogRingHeader {
+    pub magic: u32,
+    pub version: u32,
+    /// 数据区相对映射起点的偏移
+    pub data_offset: u32,
+    pub data_size: u32,
+    /// 用户态写入的位置
+    pub head: AtomicU64,
+    /// 内核读取的位置
+    pub tail: AtomicU64,
+    /// 内核因为记录格式错误而丢弃的次数
+    pub dropped: AtomicU64,
+}
+
+/// 一条记录的头部
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LogRingRecord {
+    /// 日志文本的长度，不含头部和对齐
+    pub len: u16,
+    /// 日志级别0-7，其他值按默认级别处理
+    pub level: u8,
+    pub flags: u8,
+}
+
+static LOGRING: Mutex<Option<LogRing>> = Mutex::new(None);
+
+/// 已经分配的环形缓冲区
+#[derive(Debug)]
+struct LogRing {
+    /// 映射的起点在内核中的虚拟地址
+    vaddr: usize,
+    page_cache: Arc<PageCache>,
+}
+
+impl LogRing {
+    fn new() -> Result<Self, SystemError> {
+        let page_cache = PageCache::new(None, None);
+        let mut page_manager_guard = page_manager_lock();
+        let (phys, pages) = page_manager_guard.create_pages(
+            PageType::Normal,
+            PageFlags::PG_UNEVICTABLE,
+            &mut LockedFrameAllocator,
+            PageFrameCount::new(page_align_up(LOGRING_SIZE) / MMArch::PAGE_SIZE),
+        )?;
+        drop(page_manager_guard);
+        for (i, page) in pages.iter().enumerate() {
+            page.write().add_flags(PageFlags::PG_UPTODATE);
+            page_cache.insert_ready_page(i, page.clone())?;
+        }
+        let vaddr = unsafe { MMArch::phys_2_virt(phys) }
+            .ok_or(SystemError::EFAULT)?
+            .data();
+
+        unsafe {
+            core::ptr::write_bytes(vaddr as *mut u8, 0, LOGRING_SIZE);
+            let header = vaddr as *mut LogRingHeader;
+            addr_of_mut!((*header).magic).write(LOGRING_MAGIC);
+            addr_of_mut!((*header).version).write(LOGRING_VERSION);
+            addr_of_mut!((*header).data_offset).write(MMArch::PAGE_SIZE as u32);
+            addr_of_mut!((*header).data_size).write(LOGRING_DATA_SIZE as u32);
+        }
+        Ok(Self { vaddr, page_cache })
+    }
+
+    fn header(&self) -> &LogRingHeader {
+        unsafe { &*(self.vaddr as *const LogRingHeader) }
+    }
+
+    fn data(&self) -> *const u8 {
+        (self.vaddr + MMArch::PAGE_SIZE) as *const u8
+    }
+
+    /// 把已经提交的记录写到kmsg，返回取走的记录数
+    ///
+    /// 数据区与用户态共享，用户态随时可能改写，因此每个字段只读一次，文本先复制出来再使用
+    fn drain(&self) -> usize {
+        let header = self.header();
+        let head = header.head.load(Ordering::Acquire);
+        let mut tail = header.tail.load(Ordering::Relaxed);
+        let mut count = 0;
+
+        if head.wrapping_sub(tail) > LOGRING_DATA_SIZE as u64 {
+            // head不可能领先tail超过一圈，丢掉整个缓冲区
+            header.dropped.fetch_add(1, Ordering::Relaxed);
+            header.tail.store(head, Ordering::Release);
+            return 0;
+        }
+
+        while tail != head {
+            let off = (tail % LOGRING_DATA_SIZE as u64) as usize;
+            let avail = (head - tail) as usize;
+            if off % LOGRING_REC_ALIGN != 0 || avail < size_of::<LogRingRecord>() {
+                header.dropped.fetch_add(1, Ordering::Relaxed);
+                tail = head;
+                break;
+            }
+            let rec = unsafe { read_volatile(self.data().add(off) as *const LogRingRecord) };
+            if rec.flags & LOGRING_REC_PAD != 0 {
+                tail += (LOGRING_DATA_SIZE - off) as u64;
+                continue;
+            }
+
+            let total =
+                (size_of::<LogRingRecord>() + rec.len as usize).next_multiple_of(LOGRING_REC_ALIGN);
+            if total > avail || off + total > LOGRING_DATA_SIZE {
+                header.dropped.fetch_add(1, Ordering::Relaxed);
+                tail = head;
+                break;
+            }
+
+            let mut text = alloc::vec![0u8; rec.len as usize];
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    self.data().add(off + size_of::<LogRingRecord>()),
+                    text.as_mut_ptr(),
+                    text.len(),
+                );
+            }
+            let text = String::from_utf8_lossy(&text);
+            let text = text.trim_end_matches('\n');
+            let level = match rec.level {
+                0..=7 => LogLevel::from(rec.level as usize),
+                _ => LogLevel::DEFAULT,
+            };
+            Logger.log(level, format_args!("{}\n", text));
+
+            tail += total as u64;
+            count += 1;
+        }
+        header.tail.store(tail, Ordering::Release);
+        count
+    }
+}
+
+/// 取走环形缓冲区中所有已经提交的记录
+fn logring_drain() -> usize {
+    LOGRING
+        .lock()
+        .as_ref()
+        .map(|ring| ring.drain())
+        .unwrap_or(0)
+}
+
+fn logring_thread() -> i32 {
+    loop {
+        logring_drain();
+        let _ = schedule_timeout(LOGRING_POLL_JIFFIES);
+    }
+}
+
+/// 返回环形缓冲区的页缓存，第一次调用时分配缓冲区并启动`logring`线程
+fn logring_get_or_init() -> Result<Arc<PageCache>, SystemError> {
+    let mut guard = LOGRING.lock();
+    if let Some(ring) = guard.as_ref() {
+        return Ok(ring.page_cache.clone());
+    }
+    let ring = LogRing::new()?;
+    let page_cache = ring.page_cache.clone();
+    *guard = Some(ring);
+    drop(guard);
+
+    let closure = KernelThreadClosure::StaticEmptyClosure((&(logring_thread as fn() -> i32), ()));
+    KernelThreadMechanism::create_and_run(closure, "logring".to_string())
+        .ok_or(SystemError::ENOMEM)?;
+    Ok(page_cache)
+}
+
+#[derive(Debug)]
+pub struct LogRingInode {
+    /// 指向自身的弱引用
+    self_ref: Weak<LockedLogRingInode>,
+    /// 指向inode所在的文件系统对象的指针
+    fs: Weak<DevFS>,
+    parent: Weak<LockedDevFSInode>,
+    /// INode 元数据
+    metadata: Metadata,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
+}
+
+#[derive(Debug)]
+pub struct LockedLogRingInode(Mutex<LogRingInode>);
+
+impl LockedLogRingInode {
+    pub fn new() -> Arc<Self> {
+        let inode = LogRingInode {
+            self_ref: Weak::default(),
+            fs: Weak::default(),
+            parent: Weak::default(),
+            attr: DevNodeAttr::new(InodeMode::from_bits_truncate(0o600)),
+            metadata: Metadata {
+                dev_id: 1,
+                inode_id: generate_inode_id(),
+                // 缺页处理按文件大小判断映射是否越界
+                size: LOGRING_SIZE as i64,
+                blk_size: 0,
+                blocks: 0,
+                atime: PosixTimeSpec::default(),
+                mtime: PosixTimeSpec::default(),
+                ctime: PosixTimeSpec::default(),
+                btime: PosixTimeSpec::default(),
+                file_type: FileType::CharDevice,
+                mode: InodeMode::from_bits_truncate(0o600),
+                flags: InodeFlags::empty(),
+                nlinks: 1,
+                uid: 0,
+                gid: 0,
+                raw_dev: DeviceNumber::default(),
+            },
+        };
+
+        let result = Arc::new(LockedLogRingInode(Mutex::new(inode)));
+        result.0.lock().self_ref = Arc::downgrade(&result);
+
+        return result;
+    }
+}
+
+impl DeviceINode for LockedLogRingInode {
+    fn set_fs(&self, fs: Weak<DevFS>) {
+        self.0.lock().fs = fs;
+    }
+
+    fn set_parent(&self, parent: Weak<super::LockedDevFSInode>) {
+        self.0.lock().parent = parent;
+    }
+}
+
+impl IndexNode for LockedLogRingInode {
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _flags: &FileFlags,
+    ) -> Result<(), SystemError> {
+        return Ok(());
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        logring_drain();
+        return Ok(());
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        let inode = self.0.lock();
+        let mut metadata = inode.metadata.clone();
+        inode.attr.apply(&mut metadata);
+        Ok(metadata)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        return self.0.lock().fs.upgrade().unwrap();
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
+        let mut inode = self.0.lock();
+        inode.metadata.atime = metadata.atime;
+        inode.metadata.mtime = metadata.mtime;
+        inode.metadata.ctime = metadata.ctime;
+        inode.metadata.btime = metadata.btime;
+        inode.attr.update(metadata);
+
+        return Ok(());
+    }
+
+    /// 日志只能通过映射的环形缓冲区写入
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EINVAL)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EINVAL)
+    }
+
+    fn mmap(&self, _start: usize, len: usize, offset: usize) -> Result<(), SystemError> {
+        if offset.checked_add(len).is_none_or(|end| end > LOGRING_SIZE) {
+            return Err(SystemError::EINVAL);
+        }
+        logring_get_or_init()?;
+        Ok(())
+    }
+
+    fn page_cache(&self) -> Option<Arc<PageCache>> {
+        LOGRING.lock().as_ref().map(|ring| ring.page_cache.clone())
+    }
+
+    /// 立即把已经提交的记录写到kmsg
+    fn sync_file(
+        &self,
+        _datasync: bool,
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<(), SystemError> {
+        logring_drain();
+        Ok(())
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let parent = self.0.lock().parent.upgrade();
+        if let Some(parent) = parent {
+            return Ok(parent);
+        }
+        Err(SystemError::ENOENT)
+    }
+}
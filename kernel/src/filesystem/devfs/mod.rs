@@ -1,4 +1,5 @@
 /// 导出devfs的模块
+pub mod logring_dev;
 pub mod null_dev;
 pub mod random_dev;
 pub mod zero_dev;
@@ -20,6 +21,7 @@ use crate::{
         casting::DowncastArc,
         mutex::{Mutex, MutexGuard},
         once::Once,
+        spinlock::SpinLock,
     },
     process::ProcessManager,
     time::PosixTimeSpec,
@@ -84,7 +86,8 @@ impl FileSystem for DevFS {
 
     unsafe fn fault(&self, pfm: &mut PageFaultMessage) -> VmFaultReason {
         if !is_zero_inode(pfm) {
-            return VmFaultReason::VM_FAULT_SIGBUS;
+            // 由页缓存提供映射的设备（如/dev/logring）
+            return PageFaultHandler::filemap_fault(pfm);
         }
         PageFaultHandler::zero_fault(pfm)
     }
@@ -96,7 +99,7 @@ impl FileSystem for DevFS {
         end_pgoff: usize,
     ) -> VmFaultReason {
         if !is_zero_inode(pfm) {
-            return VmFaultReason::VM_FAULT_SIGBUS;
+            return PageFaultHandler::filemap_map_pages(pfm, start_pgoff, end_pgoff);
         }
         PageFaultHandler::zero_map_pages(pfm, start_pgoff, end_pgoff)
     }
@@ -149,6 +152,7 @@ impl DevFS {
     /// @brief 注册系统内部自带的设备
     fn register_bultinin_device(&self) {
         use crate::filesystem::fuse::dev::LockedFuseDevInode;
+        use logring_dev::LockedLogRingInode;
         use null_dev::LockedNullInode;
         use random_dev::LockedRandomInode;
         use zero_dev::LockedZeroInode;
@@ -165,6 +169,9 @@ impl DevFS {
         dev_root
             .add_dev("fuse", LockedFuseDevInode::new())
             .expect("DevFS: Failed to register /dev/fuse");
+        dev_root
+            .add_dev("logring", LockedLogRingInode::new())
+            .expect("DevFS: Failed to register /dev/logring");
     }
 
     /// @brief 在devfs内注册设备
@@ -176,6 +183,48 @@ impl DevFS {
         name: &str,
         device: Arc<T>,
     ) -> Result<(), SystemError> {
+        self.do_register_device(name, device, None)
+    }
+
+    /// @brief 在devfs内注册设备，并为设备节点设置默认的权限
+    ///
+    /// @param name 设备名称
+    /// @param device 设备节点的结构体
+    /// @param mode 设备节点的默认权限（只使用其中的权限位，文件类型位由设备决定）
+    ///
+    /// 设备节点不支持修改元数据（`set_metadata`返回`ENOSYS`）时，保留设备自身的权限并继续注册
+    pub fn register_device_with_mode<T: DeviceINode>(
+        &self,
+        name: &str,
+        device: Arc<T>,
+        mode: InodeMode,
+    ) -> Result<(), SystemError> {
+        self.do_register_device(name, device, Some(mode))
+    }
+
+    fn do_register_device<T: DeviceINode>(
+        &self,
+        name: &str,
+        device: Arc<T>,
+        mode: Option<InodeMode>,
+    ) -> Result<(), SystemError> {
+        if let Some(mode) = mode {
+            // 在节点对用户可见之前设置好权限，避免出现短暂的权限窗口
+            let mut metadata = device.metadata()?;
+            metadata.mode = DevNodeAttr::merge_mode(metadata.mode, mode);
+            match device.set_metadata(&metadata) {
+                Ok(()) => {}
+                Err(SystemError::ENOSYS) => {
+                    warn!(
+                        "devfs: device '{}' does not support setting its mode, keep {:?}",
+                        name,
+                        device.metadata()?.mode
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
         let dev_root_inode = self.root_inode.clone();
         let metadata = device.metadata()?;
         match metadata.file_type {
@@ -214,6 +263,14 @@ impl DevFS {
                 } else if name == "loop-control" {
                     // loop-control设备
                     dev_root_inode.add_dev(name, device.clone())?;
+                } else if name.starts_with("rtc") && name.len() > 3 {
+                    // rtc设备，挂载在 /dev 下
+                    dev_root_inode.add_dev(name, device.clone())?;
+                } else if let Some((dir, leaf)) = name.split_once('/') {
+                    // 带目录的设备名（例如mapper/control），挂载在 /dev/<dir> 下
+                    let dir_inode = dev_root_inode.get_or_add_dir(dir)?;
+                    dir_inode.add_dev(leaf, device.clone())?;
+                    device.set_parent(Arc::downgrade(&dir_inode));
                 } else {
                     // 在 /dev/char 下创建设备节点
                     dev_char_inode.add_dev(name, device.clone())?;
@@ -263,6 +320,9 @@ impl DevFS {
                 {
                     // PMEM 块设备 (pmem0, pmem1, ...) 挂载在 /dev 下
                     dev_root_inode.add_dev(name, device.clone())?;
+                } else if is_dm_block_device(name) {
+                    // device-mapper 块设备 (dm-0, dm-1, ...) 挂载在 /dev 下
+                    dev_root_inode.add_dev(name, device.clone())?;
                 } else {
                     dev_block_inode.add_dev(name, device.clone())?;
                     device.set_parent(Arc::downgrade(&dev_block_inode));
@@ -312,7 +372,7 @@ impl DevFS {
                     && name.len() > 4
                     && name[4..].chars().all(|c| c.is_ascii_digit());
 
-                if is_loop_block_device {
+                if is_loop_block_device || is_dm_block_device(name) {
                     dev_root_inode.remove(name)?;
                 } else {
                     if dev_root_inode.find("block").is_err() {
@@ -424,6 +484,17 @@ impl LockedDevFSInode {
         return Ok(());
     }
 
+    /// 获取名为`name`的子目录，不存在时创建
+    pub fn get_or_add_dir(&self, name: &str) -> Result<Arc<LockedDevFSInode>, SystemError> {
+        match self.add_dir(name) {
+            Ok(()) | Err(SystemError::EEXIST) => {}
+            Err(e) => return Err(e),
+        }
+        self.find(name)?
+            .downcast_arc::<LockedDevFSInode>()
+            .ok_or(SystemError::ENOTDIR)
+    }
+
     pub fn add_dev(&self, name: &str, dev: Arc<dyn IndexNode>) -> Result<(), SystemError> {
         let mut this = self.0.lock();
         let name = DName::from(name);
@@ -444,12 +515,10 @@ impl LockedDevFSInode {
         let new_inode =
             self.create_with_data(symlink_name, FileType::SymLink, InodeMode::S_IRWXUGO, 0)?;
 
-        let buf = path.as_bytes();
-        let len = buf.len();
         new_inode
             .downcast_ref::<LockedDevFSInode>()
             .unwrap()
-            .write_at(0, len, buf, Mutex::new(FilePrivateData::Unused).lock())?;
+            .kernel_write_at(0, path.as_bytes())?;
         Ok(())
     }
 
@@ -791,6 +860,59 @@ impl IndexNode for LockedDevFSInode {
     }
 }
 
+/// devfs设备节点的权限和属主信息
+///
+/// 多数设备驱动的inode会根据设备的状态动态地生成`Metadata`，
+/// 通过chmod/chown设置的mode/uid/gid无处保存，导致每次stat/open看到的都是驱动写死的值。
+/// 这类驱动可以内嵌一个`DevNodeAttr`：在`metadata()`中调用[`DevNodeAttr::apply`]，
+/// 在`set_metadata()`中调用[`DevNodeAttr::update`]，从而让权限在设备节点的生命周期内保持不变，
+/// 并由VFS在open时据此进行权限检查。
+#[derive(Debug)]
+pub struct DevNodeAttr(SpinLock<DevNodeAttrInner>);
+
+#[derive(Debug, Clone, Copy)]
+struct DevNodeAttrInner {
+    /// 权限位（不包含文件类型位）
+    perm: InodeMode,
+    uid: usize,
+    gid: usize,
+}
+
+impl DevNodeAttr {
+    /// 创建属于root的设备节点属性
+    ///
+    /// ## 参数
+    /// - `mode`: 默认权限，只使用其中的权限位
+    pub const fn new(mode: InodeMode) -> Self {
+        Self(SpinLock::new(DevNodeAttrInner {
+            perm: InodeMode::from_bits_truncate(mode.bits() & !InodeMode::S_IFMT.bits()),
+            uid: 0,
+            gid: 0,
+        }))
+    }
+
+    /// 把`new_mode`的权限位合并到`old_mode`中，保留`old_mode`的文件类型位
+    pub fn merge_mode(old_mode: InodeMode, new_mode: InodeMode) -> InodeMode {
+        (old_mode & InodeMode::S_IFMT) | (new_mode & !InodeMode::S_IFMT)
+    }
+
+    /// 用保存的权限和属主覆盖`metadata`中对应的字段
+    pub fn apply(&self, metadata: &mut Metadata) {
+        let inner = *self.0.lock();
+        metadata.mode = Self::merge_mode(metadata.mode, inner.perm);
+        metadata.uid = inner.uid;
+        metadata.gid = inner.gid;
+    }
+
+    /// 从chmod/chown等操作传入的`metadata`中更新权限和属主
+    pub fn update(&self, metadata: &Metadata) {
+        let mut inner = self.0.lock();
+        inner.perm = metadata.mode & !InodeMode::S_IFMT;
+        inner.uid = metadata.uid;
+        inner.gid = metadata.gid;
+    }
+}
+
 /// @brief 所有的设备INode都需要额外实现这个trait
 pub trait DeviceINode: IndexNode {
     fn set_fs(&self, fs: Weak<DevFS>);
@@ -833,9 +955,25 @@ pub fn devfs_register<T: DeviceINode>(name: &str, device: Arc<T>) -> Result<(),
     return devfs_exact_ref!().register_device(name, device);
 }
 
-/// 在 /dev 下创建符号链接
-#[allow(dead_code)]
-pub fn devfs_add_symlink(link_name: &str, target: &str) -> Result<(), SystemError> {
+/// @brief devfs的设备注册函数，同时为设备节点设置默认权限
+///
+/// 设备需要在`set_metadata`中保存权限（参见[`DevNodeAttr`]），否则会注册失败。
+pub fn devfs_register_with_mode<T: DeviceINode>(
+    name: &str,
+    device: Arc<T>,
+    mode: InodeMode,
+) -> Result<(), SystemError> {
+    return devfs_exact_ref!().register_device_with_mode(name, device, mode);
+}
+
+/// device-mapper 块设备 (dm-0, dm-1, ...)
+fn is_dm_block_device(name: &str) -> bool {
+    name.strip_prefix("dm-")
+        .is_some_and(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// 获取`link_name`所在的目录和链接的名称，例如`mapper/foo`位于/dev/mapper下
+fn devfs_link_parent(link_name: &str) -> Result<(Arc<LockedDevFSInode>, &str), SystemError> {
     let dev_inode = ProcessManager::current_mntns()
         .root_inode()
         .find("dev")
@@ -843,7 +981,22 @@ pub fn devfs_add_symlink(link_name: &str, target: &str) -> Result<(), SystemErro
     let dev_inode = dev_inode
         .downcast_arc::<LockedDevFSInode>()
         .ok_or(SystemError::ENOENT)?;
-    dev_inode.add_dev_symlink(target, link_name)
+    match link_name.split_once('/') {
+        Some((dir, leaf)) => Ok((dev_inode.get_or_add_dir(dir)?, leaf)),
+        None => Ok((dev_inode, link_name)),
+    }
+}
+
+/// 在 /dev 下创建符号链接，`link_name`可以带一级目录（例如`mapper/foo`）
+pub fn devfs_add_symlink(link_name: &str, target: &str) -> Result<(), SystemError> {
+    let (dir, name) = devfs_link_parent(link_name)?;
+    dir.add_dev_symlink(target, name)
+}
+
+/// 删除[`devfs_add_symlink`]创建的符号链接
+pub fn devfs_remove_symlink(link_name: &str) -> Result<(), SystemError> {
+    let (dir, name) = devfs_link_parent(link_name)?;
+    dir.remove(name)
 }
 
 /// @brief devfs的设备卸载函数
@@ -882,7 +1035,7 @@ pub fn devfs_init() -> Result<(), SystemError> {
                             }
                         }
                     }
-                    Err(e) => warn!("Create tmpfs for /dev/shm failed: {:?}", e),
+                    Err(e) => warn!("Create tmpfs for /dev/shm failed: {}", e),
                 }
             } else {
                 warn!("Create /dev/shm failed: {:?}", shm_inode.err());
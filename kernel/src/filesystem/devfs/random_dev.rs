@@ -16,7 +16,7 @@ use alloc::{
 use core::{cmp::min, mem::size_of};
 use system_error::SystemError;
 
-use super::DeviceINode;
+use super::{DevNodeAttr, DeviceINode};
 
 #[derive(Debug)]
 pub struct RandomInode {
@@ -24,6 +24,8 @@ pub struct RandomInode {
     fs: Weak<DevFS>,
     parent: Weak<LockedDevFSInode>,
     metadata: Metadata,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
 }
 
 #[derive(Debug)]
@@ -35,6 +37,7 @@ impl LockedRandomInode {
             self_ref: Weak::default(),
             fs: Weak::default(),
             parent: Weak::default(),
+            attr: DevNodeAttr::new(InodeMode::from_bits_truncate(0o666)),
             metadata: Metadata {
                 dev_id: 1,
                 inode_id: generate_inode_id(),
@@ -89,7 +92,10 @@ impl IndexNode for LockedRandomInode {
     }
 
     fn metadata(&self) -> Result<Metadata, SystemError> {
-        Ok(self.0.lock().metadata.clone())
+        let inode = self.0.lock();
+        let mut metadata = inode.metadata.clone();
+        inode.attr.apply(&mut metadata);
+        Ok(metadata)
     }
 
     fn fs(&self) -> Arc<dyn FileSystem> {
@@ -106,9 +112,7 @@ impl IndexNode for LockedRandomInode {
         inode.metadata.mtime = metadata.mtime;
         inode.metadata.ctime = metadata.ctime;
         inode.metadata.btime = metadata.btime;
-        inode.metadata.mode = metadata.mode;
-        inode.metadata.uid = metadata.uid;
-        inode.metadata.gid = metadata.gid;
+        inode.attr.update(metadata);
         Ok(())
     }
 
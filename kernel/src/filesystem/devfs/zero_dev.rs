@@ -15,7 +15,7 @@ use alloc::{
 };
 use system_error::SystemError;
 // use uuid::{uuid, Uuid};
-use super::{DevFS, DeviceINode};
+use super::{DevFS, DevNodeAttr, DeviceINode};
 
 #[derive(Debug)]
 pub struct ZeroInode {
@@ -28,6 +28,8 @@ pub struct ZeroInode {
     parent: Weak<LockedDevFSInode>,
     /// INode 元数据
     metadata: Metadata,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
 }
 
 #[derive(Debug)]
@@ -40,6 +42,7 @@ impl LockedZeroInode {
             self_ref: Weak::default(),
             parent: Weak::default(),
             fs: Weak::default(),
+            attr: DevNodeAttr::new(InodeMode::from_bits_truncate(0o666)),
             metadata: Metadata {
                 dev_id: 1,
                 inode_id: generate_inode_id(),
@@ -95,7 +98,10 @@ impl IndexNode for LockedZeroInode {
     }
 
     fn metadata(&self) -> Result<Metadata, SystemError> {
-        return Ok(self.0.lock().metadata.clone());
+        let inode = self.0.lock();
+        let mut metadata = inode.metadata.clone();
+        inode.attr.apply(&mut metadata);
+        Ok(metadata)
     }
 
     fn fs(&self) -> Arc<dyn FileSystem> {
@@ -112,9 +118,7 @@ impl IndexNode for LockedZeroInode {
         inode.metadata.mtime = metadata.mtime;
         inode.metadata.ctime = metadata.ctime;
         inode.metadata.btime = metadata.btime;
-        inode.metadata.mode = metadata.mode;
-        inode.metadata.uid = metadata.uid;
-        inode.metadata.gid = metadata.gid;
+        inode.attr.update(metadata);
 
         return Ok(());
     }
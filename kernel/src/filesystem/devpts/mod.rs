@@ -6,9 +6,12 @@ use core::{
 use crate::libs::mutex::MutexGuard;
 use crate::{
     driver::{
-        base::device::{
-            device_number::{DeviceNumber, Major},
-            IdTable,
+        base::{
+            char::char_inode::CharDevInode,
+            device::{
+                device_number::{DeviceNumber, Major},
+                IdTable,
+            },
         },
         tty::{
             pty::unix98pty::NR_UNIX98_PTY_MAX,
@@ -36,7 +39,13 @@ use super::{
     devfs::DeviceINode,
     vfs::{vcore::generate_inode_id, FilePrivateData, IndexNode, InodeFlags, Magic, Metadata},
 };
-use crate::{filesystem::vfs::FileSystemMakerData, register_mountable_fs};
+use crate::{
+    filesystem::vfs::{
+        mount_options::{MountOptionKind, MountOptionSpec, MountOptions},
+        FileSystemMakerData,
+    },
+    register_mountable_fs,
+};
 use linkme::distributed_slice;
 
 const DEV_PTYFS_MAX_NAMELEN: usize = 16;
@@ -120,19 +129,17 @@ impl DevPtsFs {
         }
 
         let dev_num = DeviceNumber::new(Major::TTYAUX_MAJOR, 2);
-        let ptmx_dev = TtyDevice::new(
+        let ptmx_tty = TtyDevice::new(
             "ptmx".to_string(),
             IdTable::new("ptmx".to_string(), Some(dev_num)),
             TtyType::Pty(PtyType::Ptm),
         );
+        ptmx_tty.set_devpts(guard.fs.clone());
+        let ptmx_dev = CharDevInode::new(ptmx_tty);
         let mut md = ptmx_dev.metadata().unwrap();
         md.mode = self.opts.ptmx_mode | InodeMode::S_IFCHR;
-        md.raw_dev = dev_num;
-        md.blk_size = 1024;
         let _ = ptmx_dev.set_metadata(&md);
-        if let Some(fs_arc) = guard.fs.upgrade() {
-            ptmx_dev.set_devpts_fs(Arc::downgrade(&fs_arc));
-        }
+        ptmx_dev.set_devpts_fs(guard.fs.clone());
         ptmx_dev.set_devpts_parent(guard.self_ref.clone());
 
         guard
@@ -177,7 +184,7 @@ impl MountableFileSystem for DevPtsFs {
         _source: &str,
     ) -> Result<Option<Arc<dyn FileSystemMakerData + 'static>>, SystemError> {
         // 对于 devpts，挂载数据只是解析选项，将结果以 Box<DevPtsOptions> 形式传递
-        let opts = parse_devpts_options(raw_data);
+        let opts = parse_devpts_options(raw_data)?;
         Ok(Some(Arc::new(opts)))
     }
 
@@ -237,18 +244,18 @@ impl LockedDevPtsFSInode {
 #[derive(Debug)]
 pub struct PtsDevInode {
     fs: Weak<DevPtsFs>,
-    children: Option<BTreeMap<String, Arc<TtyDevice>>>,
+    children: Option<BTreeMap<String, Arc<CharDevInode>>>,
     metadata: Metadata,
     parent: Weak<LockedDevPtsFSInode>,
     self_ref: Weak<LockedDevPtsFSInode>,
 }
 
 impl PtsDevInode {
-    pub fn children_unchecked(&self) -> &BTreeMap<String, Arc<TtyDevice>> {
+    pub fn children_unchecked(&self) -> &BTreeMap<String, Arc<CharDevInode>> {
         self.children.as_ref().unwrap()
     }
 
-    pub fn children_unchecked_mut(&mut self) -> &mut BTreeMap<String, Arc<TtyDevice>> {
+    pub fn children_unchecked_mut(&mut self) -> &mut BTreeMap<String, Arc<CharDevInode>> {
         self.children.as_mut().unwrap()
     }
 }
@@ -343,19 +350,16 @@ impl IndexNode for LockedDevPtsFSInode {
 
         let fs = guard.fs.upgrade().unwrap();
 
-        let result = TtyDevice::new(
+        let dev_num =
+            DeviceNumber::new(Major::UNIX98_PTY_SLAVE_MAJOR, name.parse::<u32>().unwrap());
+        let result = CharDevInode::new(TtyDevice::new(
             name.to_string(),
-            IdTable::new(name.to_string(), None),
+            IdTable::new(name.to_string(), Some(dev_num)),
             TtyType::Pty(PtyType::Pts),
-        );
+        ));
 
         let mut metadata = result.metadata()?;
-
         metadata.mode = fs.opts.pts_mode | InodeMode::S_IFCHR;
-        metadata.raw_dev =
-            DeviceNumber::new(Major::UNIX98_PTY_SLAVE_MAJOR, name.parse::<u32>().unwrap());
-        metadata.blk_size = 1024;
-
         result.set_metadata(&metadata)?;
 
         result.set_devpts_fs(Arc::downgrade(&fs));
@@ -397,24 +401,26 @@ pub fn devpts_init() -> Result<(), SystemError> {
     Ok(())
 }
 
-fn parse_devpts_options(raw: Option<&str>) -> DevPtsOptions {
+/// devpts认识的挂载选项
+const DEVPTS_MOUNT_OPTIONS: &[MountOptionSpec] = &[
+    MountOptionSpec::new("mode", MountOptionKind::Mode),
+    MountOptionSpec::new("ptmxmode", MountOptionKind::Mode),
+    MountOptionSpec::new("newinstance", MountOptionKind::Flag),
+];
+
+fn parse_devpts_options(raw: Option<&str>) -> Result<DevPtsOptions, SystemError> {
+    let parsed = MountOptions::parse("devpts", raw, DEVPTS_MOUNT_OPTIONS)?;
+    parsed.warn_unknown("devpts");
+
     let mut opts = DevPtsOptions::default();
-    if let Some(raw) = raw {
-        for item in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
-            if let Some(val) = item.strip_prefix("mode=") {
-                if let Ok(bits) = u32::from_str_radix(val, 8) {
-                    let mode = InodeMode::from_bits_truncate(bits);
-                    opts.pts_mode = mode;
-                    opts.root_mode = mode;
-                }
-            } else if let Some(val) = item.strip_prefix("ptmxmode=") {
-                if let Ok(bits) = u32::from_str_radix(val, 8) {
-                    opts.ptmx_mode = InodeMode::from_bits_truncate(bits);
-                }
-            } else if item == "newinstance" {
-                opts.new_instance = true;
-            }
-        }
+    if let Some(bits) = parsed.mode("mode") {
+        let mode = InodeMode::from_bits_truncate(bits);
+        opts.pts_mode = mode;
+        opts.root_mode = mode;
+    }
+    if let Some(bits) = parsed.mode("ptmxmode") {
+        opts.ptmx_mode = InodeMode::from_bits_truncate(bits);
     }
-    opts
+    opts.new_instance = parsed.flag("newinstance");
+    Ok(opts)
 }
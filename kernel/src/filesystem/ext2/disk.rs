@@ -0,0 +1,863 @@
+//! ext2的磁盘数据结构，以及只读挂载ext3/ext4时需要的扩展字段
+//!
+//! 所有结构都保留从磁盘读出的原始字节，只修改驱动关心的字段，
+//! 写回时不会丢失驱动不认识的字段。
+//!
+//! 参考: https://www.nongnu.org/ext2-doc/ext2.html
+//! 参考: https://www.kernel.org/doc/html/latest/filesystems/ext4/ondisk.html
+
+use alloc::{vec, vec::Vec};
+use system_error::SystemError;
+
+/// 超级块的魔数
+pub const EXT2_SUPER_MAGIC: u16 = 0xef53;
+/// 超级块在分区中的字节偏移
+pub const SUPERBLOCK_OFFSET: usize = 1024;
+/// 超级块的大小
+pub const SUPERBLOCK_SIZE: usize = 1024;
+/// 根目录的inode号
+pub const EXT2_ROOT_INO: u32 = 2;
+/// 修订版本0的inode大小
+pub const EXT2_GOOD_OLD_INODE_SIZE: usize = 128;
+/// 修订版本0的第一个非保留inode
+pub const EXT2_GOOD_OLD_FIRST_INO: u32 = 11;
+/// 直接块的数量
+pub const EXT2_NDIR_BLOCKS: usize = 12;
+/// 一级间接块在i_block中的下标
+pub const EXT2_IND_BLOCK: usize = 12;
+/// 二级间接块在i_block中的下标
+pub const EXT2_DIND_BLOCK: usize = 13;
+/// 三级间接块在i_block中的下标
+pub const EXT2_TIND_BLOCK: usize = 14;
+/// i_block的长度
+pub const EXT2_N_BLOCKS: usize = 15;
+/// 文件名的最大长度
+pub const EXT2_NAME_LEN: usize = 255;
+/// 块组描述符的大小
+pub const EXT2_GROUP_DESC_SIZE: usize = 32;
+/// 64BIT特性下块组描述符的最小大小
+pub const EXT4_MIN_DESC_SIZE_64BIT: usize = 64;
+/// 目录项头部（不含名字）的大小
+pub const EXT2_DIR_ENTRY_HEADER: usize = 8;
+/// 快速符号链接的最大长度，目标直接保存在i_block中
+pub const EXT2_FAST_SYMLINK_MAX: usize = EXT2_N_BLOCKS * 4;
+
+/// 文件系统处于干净状态
+pub const EXT2_VALID_FS: u16 = 1;
+
+/// compat特性：支持扩展属性
+pub const EXT2_FEATURE_COMPAT_EXT_ATTR: u32 = 0x0008;
+/// compat特性：目录使用htree索引
+pub const EXT2_FEATURE_COMPAT_DIR_INDEX: u32 = 0x0020;
+
+/// incompat特性：目录项中保存文件类型
+pub const EXT2_FEATURE_INCOMPAT_FILETYPE: u32 = 0x0002;
+/// incompat特性：日志需要回放
+pub const EXT3_FEATURE_INCOMPAT_RECOVER: u32 = 0x0004;
+/// incompat特性：inode使用extent树映射数据块
+pub const EXT4_FEATURE_INCOMPAT_EXTENTS: u32 = 0x0040;
+/// incompat特性：块号为64位
+pub const EXT4_FEATURE_INCOMPAT_64BIT: u32 = 0x0080;
+/// incompat特性：多挂载保护
+pub const EXT4_FEATURE_INCOMPAT_MMP: u32 = 0x0100;
+/// incompat特性：位图和inode表可以放在其他块组中
+pub const EXT4_FEATURE_INCOMPAT_FLEX_BG: u32 = 0x0200;
+/// incompat特性：扩展属性可以保存在单独的inode中
+pub const EXT4_FEATURE_INCOMPAT_EA_INODE: u32 = 0x0400;
+/// incompat特性：校验和种子保存在超级块中
+pub const EXT4_FEATURE_INCOMPAT_CSUM_SEED: u32 = 0x2000;
+/// incompat特性：htree可以有三层
+pub const EXT4_FEATURE_INCOMPAT_LARGEDIR: u32 = 0x4000;
+/// 驱动支持的incompat特性，包含其他incompat特性的文件系统不能读写挂载
+pub const EXT2_FEATURE_INCOMPAT_SUPP: u32 = EXT2_FEATURE_INCOMPAT_FILETYPE;
+/// 只读挂载时支持的incompat特性，包含其他incompat特性的文件系统不能挂载
+pub const EXT4_FEATURE_INCOMPAT_RO_SUPP: u32 = EXT2_FEATURE_INCOMPAT_SUPP
+    | EXT3_FEATURE_INCOMPAT_RECOVER
+    | EXT4_FEATURE_INCOMPAT_EXTENTS
+    | EXT4_FEATURE_INCOMPAT_64BIT
+    | EXT4_FEATURE_INCOMPAT_MMP
+    | EXT4_FEATURE_INCOMPAT_FLEX_BG
+    | EXT4_FEATURE_INCOMPAT_EA_INODE
+    | EXT4_FEATURE_INCOMPAT_CSUM_SEED
+    | EXT4_FEATURE_INCOMPAT_LARGEDIR;
+
+/// ro_compat特性：只在部分块组中保存超级块的备份
+pub const EXT2_FEATURE_RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+/// ro_compat特性：普通文件可以超过2GiB
+pub const EXT2_FEATURE_RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+/// ro_compat特性：i_blocks为48位，且可以以文件系统块为单位
+pub const EXT4_FEATURE_RO_COMPAT_HUGE_FILE: u32 = 0x0008;
+/// 驱动支持的ro_compat特性，包含其他ro_compat特性的文件系统只能只读挂载
+pub const EXT2_FEATURE_RO_COMPAT_SUPP: u32 =
+    EXT2_FEATURE_RO_COMPAT_SPARSE_SUPER | EXT2_FEATURE_RO_COMPAT_LARGE_FILE;
+
+/// 超级块s_flags：htree使用有符号char计算哈希
+pub const EXT2_FLAGS_SIGNED_HASH: u32 = 0x0001;
+/// 超级块s_flags：htree使用无符号char计算哈希
+pub const EXT2_FLAGS_UNSIGNED_HASH: u32 = 0x0002;
+
+/// inode标志：目录使用htree索引
+pub const EXT2_INDEX_FL: u32 = 0x0000_1000;
+/// inode标志：i_blocks以文件系统块为单位
+pub const EXT4_HUGE_FILE_FL: u32 = 0x0004_0000;
+/// inode标志：i_block中保存的是extent树
+pub const EXT4_EXTENTS_FL: u32 = 0x0008_0000;
+
+/// extent树节点的魔数
+pub const EXT4_EXT_MAGIC: u16 = 0xf30a;
+/// extent树的最大深度
+pub const EXT4_EXT_MAX_DEPTH: u16 = 5;
+/// extent树节点头部和每个条目的大小
+pub const EXT4_EXT_ENTRY_SIZE: usize = 12;
+/// 长度大于这个值的extent是未初始化的，读出来是0
+pub const EXT4_EXT_INIT_MAX_LEN: u16 = 1 << 15;
+
+/// 目录项中的文件类型（需要FILETYPE特性）
+pub const EXT2_FT_UNKNOWN: u8 = 0;
+pub const EXT2_FT_REG_FILE: u8 = 1;
+pub const EXT2_FT_DIR: u8 = 2;
+pub const EXT2_FT_CHRDEV: u8 = 3;
+pub const EXT2_FT_BLKDEV: u8 = 4;
+pub const EXT2_FT_FIFO: u8 = 5;
+pub const EXT2_FT_SOCK: u8 = 6;
+pub const EXT2_FT_SYMLINK: u8 = 7;
+
+/// i_mode中的文件类型
+pub const EXT2_S_IFMT: u16 = 0o170000;
+pub const EXT2_S_IFSOCK: u16 = 0o140000;
+pub const EXT2_S_IFLNK: u16 = 0o120000;
+pub const EXT2_S_IFREG: u16 = 0o100000;
+pub const EXT2_S_IFBLK: u16 = 0o060000;
+pub const EXT2_S_IFDIR: u16 = 0o040000;
+pub const EXT2_S_IFCHR: u16 = 0o020000;
+pub const EXT2_S_IFIFO: u16 = 0o010000;
+
+#[inline]
+pub(super) fn get_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+#[inline]
+pub(super) fn get_u32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([buf[off], buf[off + 1], buf[off + 2], buf[off + 3]])
+}
+
+#[inline]
+pub(super) fn put_u16(buf: &mut [u8], off: usize, val: u16) {
+    buf[off..off + 2].copy_from_slice(&val.to_le_bytes());
+}
+
+#[inline]
+pub(super) fn put_u32(buf: &mut [u8], off: usize, val: u32) {
+    buf[off..off + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+/// 把i_mode的文件类型转换为目录项中的文件类型
+pub fn mode_to_dirent_type(mode: u16) -> u8 {
+    match mode & EXT2_S_IFMT {
+        EXT2_S_IFREG => EXT2_FT_REG_FILE,
+        EXT2_S_IFDIR => EXT2_FT_DIR,
+        EXT2_S_IFCHR => EXT2_FT_CHRDEV,
+        EXT2_S_IFBLK => EXT2_FT_BLKDEV,
+        EXT2_S_IFIFO => EXT2_FT_FIFO,
+        EXT2_S_IFSOCK => EXT2_FT_SOCK,
+        EXT2_S_IFLNK => EXT2_FT_SYMLINK,
+        _ => EXT2_FT_UNKNOWN,
+    }
+}
+
+/// ext2超级块
+#[derive(Debug, Clone)]
+pub struct Ext2SuperBlock {
+    raw: Vec<u8>,
+}
+
+impl Ext2SuperBlock {
+    const S_INODES_COUNT: usize = 0;
+    const S_BLOCKS_COUNT: usize = 4;
+    const S_R_BLOCKS_COUNT: usize = 8;
+    const S_FREE_BLOCKS_COUNT: usize = 12;
+    const S_FREE_INODES_COUNT: usize = 16;
+    const S_FIRST_DATA_BLOCK: usize = 20;
+    const S_LOG_BLOCK_SIZE: usize = 24;
+    const S_BLOCKS_PER_GROUP: usize = 32;
+    const S_INODES_PER_GROUP: usize = 40;
+    const S_MTIME: usize = 44;
+    const S_WTIME: usize = 48;
+    const S_MNT_COUNT: usize = 52;
+    const S_MAGIC: usize = 56;
+    const S_STATE: usize = 58;
+    const S_REV_LEVEL: usize = 76;
+    const S_FIRST_INO: usize = 84;
+    const S_INODE_SIZE: usize = 88;
+    const S_FEATURE_COMPAT: usize = 92;
+    const S_FEATURE_INCOMPAT: usize = 96;
+    const S_FEATURE_RO_COMPAT: usize = 100;
+    const S_HASH_SEED: usize = 236;
+    const S_DEF_HASH_VERSION: usize = 252;
+    const S_DESC_SIZE: usize = 254;
+    const S_BLOCKS_COUNT_HI: usize = 336;
+    const S_R_BLOCKS_COUNT_HI: usize = 340;
+    const S_FREE_BLOCKS_COUNT_HI: usize = 344;
+    const S_FLAGS: usize = 352;
+
+    /// 从原始字节解析超级块，并检查各字段是否合法
+    pub fn parse(raw: Vec<u8>) -> Result<Self, SystemError> {
+        if raw.len() != SUPERBLOCK_SIZE {
+            return Err(SystemError::EINVAL);
+        }
+        let sb = Self { raw };
+        if sb.magic() != EXT2_SUPER_MAGIC {
+            return Err(SystemError::EINVAL);
+        }
+        if sb.log_block_size() > 6
+            || sb.blocks_per_group() == 0
+            || sb.inodes_per_group() == 0
+            || sb.blocks_count() <= sb.first_data_block() as u64
+            || sb.blocks_per_group() as usize > sb.block_size() * 8
+            || sb.inodes_per_group() as usize > sb.block_size() * 8
+        {
+            return Err(SystemError::EINVAL);
+        }
+        let inode_size = sb.inode_size();
+        if inode_size < EXT2_GOOD_OLD_INODE_SIZE
+            || inode_size > sb.block_size()
+            || !inode_size.is_power_of_two()
+        {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(sb)
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn magic(&self) -> u16 {
+        get_u16(&self.raw, Self::S_MAGIC)
+    }
+
+    pub fn inodes_count(&self) -> u32 {
+        get_u32(&self.raw, Self::S_INODES_COUNT)
+    }
+
+    fn is_64bit(&self) -> bool {
+        self.feature_incompat() & EXT4_FEATURE_INCOMPAT_64BIT != 0
+    }
+
+    /// 读取低32位和（64BIT特性下的）高32位组成的块数
+    fn get_blocks(&self, lo: usize, hi: usize) -> u64 {
+        let mut val = get_u32(&self.raw, lo) as u64;
+        if self.is_64bit() {
+            val |= (get_u32(&self.raw, hi) as u64) << 32;
+        }
+        val
+    }
+
+    pub fn blocks_count(&self) -> u64 {
+        self.get_blocks(Self::S_BLOCKS_COUNT, Self::S_BLOCKS_COUNT_HI)
+    }
+
+    pub fn r_blocks_count(&self) -> u64 {
+        self.get_blocks(Self::S_R_BLOCKS_COUNT, Self::S_R_BLOCKS_COUNT_HI)
+    }
+
+    pub fn free_blocks_count(&self) -> u64 {
+        self.get_blocks(Self::S_FREE_BLOCKS_COUNT, Self::S_FREE_BLOCKS_COUNT_HI)
+    }
+
+    pub fn set_free_blocks_count(&mut self, val: u64) {
+        put_u32(&mut self.raw, Self::S_FREE_BLOCKS_COUNT, val as u32);
+        if self.is_64bit() {
+            put_u32(
+                &mut self.raw,
+                Self::S_FREE_BLOCKS_COUNT_HI,
+                (val >> 32) as u32,
+            );
+        }
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        get_u32(&self.raw, Self::S_FREE_INODES_COUNT)
+    }
+
+    pub fn set_free_inodes_count(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::S_FREE_INODES_COUNT, val);
+    }
+
+    pub fn first_data_block(&self) -> u32 {
+        get_u32(&self.raw, Self::S_FIRST_DATA_BLOCK)
+    }
+
+    fn log_block_size(&self) -> u32 {
+        get_u32(&self.raw, Self::S_LOG_BLOCK_SIZE)
+    }
+
+    pub fn block_size(&self) -> usize {
+        1024 << self.log_block_size()
+    }
+
+    pub fn blocks_per_group(&self) -> u32 {
+        get_u32(&self.raw, Self::S_BLOCKS_PER_GROUP)
+    }
+
+    pub fn inodes_per_group(&self) -> u32 {
+        get_u32(&self.raw, Self::S_INODES_PER_GROUP)
+    }
+
+    pub fn set_mtime(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::S_MTIME, val);
+    }
+
+    pub fn set_wtime(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::S_WTIME, val);
+    }
+
+    pub fn mnt_count(&self) -> u16 {
+        get_u16(&self.raw, Self::S_MNT_COUNT)
+    }
+
+    pub fn set_mnt_count(&mut self, val: u16) {
+        put_u16(&mut self.raw, Self::S_MNT_COUNT, val);
+    }
+
+    pub fn state(&self) -> u16 {
+        get_u16(&self.raw, Self::S_STATE)
+    }
+
+    pub fn set_state(&mut self, val: u16) {
+        put_u16(&mut self.raw, Self::S_STATE, val);
+    }
+
+    pub fn rev_level(&self) -> u32 {
+        get_u32(&self.raw, Self::S_REV_LEVEL)
+    }
+
+    pub fn first_ino(&self) -> u32 {
+        if self.rev_level() == 0 {
+            EXT2_GOOD_OLD_FIRST_INO
+        } else {
+            get_u32(&self.raw, Self::S_FIRST_INO)
+        }
+    }
+
+    pub fn inode_size(&self) -> usize {
+        if self.rev_level() == 0 {
+            EXT2_GOOD_OLD_INODE_SIZE
+        } else {
+            get_u16(&self.raw, Self::S_INODE_SIZE) as usize
+        }
+    }
+
+    pub fn feature_compat(&self) -> u32 {
+        if self.rev_level() == 0 {
+            return 0;
+        }
+        get_u32(&self.raw, Self::S_FEATURE_COMPAT)
+    }
+
+    pub fn set_feature_compat(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::S_FEATURE_COMPAT, val);
+    }
+
+    pub fn feature_incompat(&self) -> u32 {
+        if self.rev_level() == 0 {
+            return 0;
+        }
+        get_u32(&self.raw, Self::S_FEATURE_INCOMPAT)
+    }
+
+    pub fn feature_ro_compat(&self) -> u32 {
+        if self.rev_level() == 0 {
+            return 0;
+        }
+        get_u32(&self.raw, Self::S_FEATURE_RO_COMPAT)
+    }
+
+    pub fn set_feature_ro_compat(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::S_FEATURE_RO_COMPAT, val);
+    }
+
+    /// 块组描述符的大小，只有64BIT特性下才可能大于32字节
+    pub fn desc_size(&self) -> usize {
+        if self.is_64bit() {
+            get_u16(&self.raw, Self::S_DESC_SIZE) as usize
+        } else {
+            EXT2_GROUP_DESC_SIZE
+        }
+    }
+
+    /// htree哈希的种子
+    pub fn hash_seed(&self) -> [u32; 4] {
+        core::array::from_fn(|i| get_u32(&self.raw, Self::S_HASH_SEED + i * 4))
+    }
+
+    /// 新建htree目录时使用的哈希算法
+    #[allow(dead_code)]
+    pub fn def_hash_version(&self) -> u8 {
+        self.raw[Self::S_DEF_HASH_VERSION]
+    }
+
+    pub fn flags(&self) -> u32 {
+        get_u32(&self.raw, Self::S_FLAGS)
+    }
+
+    /// 块组的数量
+    pub fn group_count(&self) -> usize {
+        let data_blocks = (self.blocks_count() - self.first_data_block() as u64) as usize;
+        data_blocks.div_ceil(self.blocks_per_group() as usize)
+    }
+}
+
+/// 块组描述符
+///
+/// 64BIT特性下描述符不小于64字节，块号的高32位保存在后半部分
+#[derive(Debug, Clone)]
+pub struct Ext2GroupDesc {
+    raw: Vec<u8>,
+}
+
+impl Ext2GroupDesc {
+    const BG_BLOCK_BITMAP: usize = 0;
+    const BG_INODE_BITMAP: usize = 4;
+    const BG_INODE_TABLE: usize = 8;
+    const BG_FREE_BLOCKS_COUNT: usize = 12;
+    const BG_FREE_INODES_COUNT: usize = 14;
+    const BG_USED_DIRS_COUNT: usize = 16;
+    const BG_BLOCK_BITMAP_HI: usize = 32;
+    const BG_INODE_BITMAP_HI: usize = 36;
+    const BG_INODE_TABLE_HI: usize = 40;
+
+    /// 从`buf`解析描述符，`buf`的长度就是描述符的大小
+    pub fn from_bytes(buf: &[u8]) -> Self {
+        Self { raw: buf.to_vec() }
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    fn get_block(&self, lo: usize, hi: usize) -> u64 {
+        let mut val = get_u32(&self.raw, lo) as u64;
+        if self.raw.len() >= EXT4_MIN_DESC_SIZE_64BIT {
+            val |= (get_u32(&self.raw, hi) as u64) << 32;
+        }
+        val
+    }
+
+    pub fn block_bitmap(&self) -> u64 {
+        self.get_block(Self::BG_BLOCK_BITMAP, Self::BG_BLOCK_BITMAP_HI)
+    }
+
+    pub fn inode_bitmap(&self) -> u64 {
+        self.get_block(Self::BG_INODE_BITMAP, Self::BG_INODE_BITMAP_HI)
+    }
+
+    pub fn inode_table(&self) -> u64 {
+        self.get_block(Self::BG_INODE_TABLE, Self::BG_INODE_TABLE_HI)
+    }
+
+    pub fn free_blocks_count(&self) -> u16 {
+        get_u16(&self.raw, Self::BG_FREE_BLOCKS_COUNT)
+    }
+
+    pub fn set_free_blocks_count(&mut self, val: u16) {
+        put_u16(&mut self.raw, Self::BG_FREE_BLOCKS_COUNT, val);
+    }
+
+    pub fn free_inodes_count(&self) -> u16 {
+        get_u16(&self.raw, Self::BG_FREE_INODES_COUNT)
+    }
+
+    pub fn set_free_inodes_count(&mut self, val: u16) {
+        put_u16(&mut self.raw, Self::BG_FREE_INODES_COUNT, val);
+    }
+
+    pub fn used_dirs_count(&self) -> u16 {
+        get_u16(&self.raw, Self::BG_USED_DIRS_COUNT)
+    }
+
+    pub fn set_used_dirs_count(&mut self, val: u16) {
+        put_u16(&mut self.raw, Self::BG_USED_DIRS_COUNT, val);
+    }
+}
+
+/// 磁盘上的inode
+#[derive(Debug, Clone)]
+pub struct Ext2DiskInode {
+    raw: Vec<u8>,
+}
+
+impl Ext2DiskInode {
+    const I_MODE: usize = 0;
+    const I_UID: usize = 2;
+    const I_SIZE: usize = 4;
+    const I_ATIME: usize = 8;
+    const I_CTIME: usize = 12;
+    const I_MTIME: usize = 16;
+    const I_DTIME: usize = 20;
+    const I_GID: usize = 24;
+    const I_LINKS_COUNT: usize = 26;
+    const I_BLOCKS: usize = 28;
+    const I_FLAGS: usize = 32;
+    const I_BLOCK: usize = 40;
+    const I_FILE_ACL: usize = 104;
+    const I_SIZE_HIGH: usize = 108;
+    const I_BLOCKS_HIGH: usize = 116;
+    const I_UID_HIGH: usize = 120;
+    const I_GID_HIGH: usize = 122;
+
+    pub fn from_bytes(raw: Vec<u8>) -> Self {
+        Self { raw }
+    }
+
+    /// 创建一个全零的inode
+    pub fn zeroed(inode_size: usize) -> Self {
+        Self {
+            raw: vec![0u8; inode_size],
+        }
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn mode(&self) -> u16 {
+        get_u16(&self.raw, Self::I_MODE)
+    }
+
+    pub fn set_mode(&mut self, val: u16) {
+        put_u16(&mut self.raw, Self::I_MODE, val);
+    }
+
+    pub fn file_type(&self) -> u16 {
+        self.mode() & EXT2_S_IFMT
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type() == EXT2_S_IFDIR
+    }
+
+    pub fn uid(&self) -> u32 {
+        get_u16(&self.raw, Self::I_UID) as u32
+            | ((get_u16(&self.raw, Self::I_UID_HIGH) as u32) << 16)
+    }
+
+    pub fn set_uid(&mut self, val: u32) {
+        put_u16(&mut self.raw, Self::I_UID, val as u16);
+        put_u16(&mut self.raw, Self::I_UID_HIGH, (val >> 16) as u16);
+    }
+
+    pub fn gid(&self) -> u32 {
+        get_u16(&self.raw, Self::I_GID) as u32
+            | ((get_u16(&self.raw, Self::I_GID_HIGH) as u32) << 16)
+    }
+
+    pub fn set_gid(&mut self, val: u32) {
+        put_u16(&mut self.raw, Self::I_GID, val as u16);
+        put_u16(&mut self.raw, Self::I_GID_HIGH, (val >> 16) as u16);
+    }
+
+    /// 文件大小。只有普通文件使用i_size_high，目录的这个字段是i_dir_acl
+    pub fn size(&self) -> u64 {
+        let low = get_u32(&self.raw, Self::I_SIZE) as u64;
+        if self.file_type() == EXT2_S_IFREG {
+            low | ((get_u32(&self.raw, Self::I_SIZE_HIGH) as u64) << 32)
+        } else {
+            low
+        }
+    }
+
+    pub fn set_size(&mut self, val: u64) {
+        put_u32(&mut self.raw, Self::I_SIZE, val as u32);
+        if self.file_type() == EXT2_S_IFREG {
+            put_u32(&mut self.raw, Self::I_SIZE_HIGH, (val >> 32) as u32);
+        }
+    }
+
+    pub fn atime(&self) -> u32 {
+        get_u32(&self.raw, Self::I_ATIME)
+    }
+
+    pub fn set_atime(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::I_ATIME, val);
+    }
+
+    pub fn ctime(&self) -> u32 {
+        get_u32(&self.raw, Self::I_CTIME)
+    }
+
+    pub fn set_ctime(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::I_CTIME, val);
+    }
+
+    pub fn mtime(&self) -> u32 {
+        get_u32(&self.raw, Self::I_MTIME)
+    }
+
+    pub fn set_mtime(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::I_MTIME, val);
+    }
+
+    pub fn dtime(&self) -> u32 {
+        get_u32(&self.raw, Self::I_DTIME)
+    }
+
+    pub fn set_dtime(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::I_DTIME, val);
+    }
+
+    pub fn links_count(&self) -> u16 {
+        get_u16(&self.raw, Self::I_LINKS_COUNT)
+    }
+
+    pub fn set_links_count(&mut self, val: u16) {
+        put_u16(&mut self.raw, Self::I_LINKS_COUNT, val);
+    }
+
+    /// 占用的512字节扇区数（包括间接块）
+    pub fn blocks(&self) -> u32 {
+        get_u32(&self.raw, Self::I_BLOCKS)
+    }
+
+    pub fn set_blocks(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::I_BLOCKS, val);
+    }
+
+    /// 占用的512字节扇区数
+    ///
+    /// ## 参数
+    /// - `huge_file`: 文件系统是否有HUGE_FILE特性，有的话i_blocks为48位
+    /// - `block_size`: 文件系统的块大小，带有EXT4_HUGE_FILE_FL的inode以块为单位
+    pub fn sectors(&self, huge_file: bool, block_size: usize) -> u64 {
+        if !huge_file {
+            return self.blocks() as u64;
+        }
+        let val = self.blocks() as u64 | ((get_u16(&self.raw, Self::I_BLOCKS_HIGH) as u64) << 32);
+        if self.flags() & EXT4_HUGE_FILE_FL != 0 {
+            val * (block_size >> 9) as u64
+        } else {
+            val
+        }
+    }
+
+    pub fn flags(&self) -> u32 {
+        get_u32(&self.raw, Self::I_FLAGS)
+    }
+
+    pub fn set_flags(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::I_FLAGS, val);
+    }
+
+    /// 数据块是否通过extent树映射
+    pub fn has_extents(&self) -> bool {
+        self.flags() & EXT4_EXTENTS_FL != 0
+    }
+
+    pub fn block(&self, idx: usize) -> u32 {
+        get_u32(&self.raw, Self::I_BLOCK + idx * 4)
+    }
+
+    pub fn set_block(&mut self, idx: usize, val: u32) {
+        put_u32(&mut self.raw, Self::I_BLOCK + idx * 4, val);
+    }
+
+    /// i_block的原始字节，快速符号链接和设备号保存在这里
+    pub fn block_bytes(&self) -> &[u8] {
+        &self.raw[Self::I_BLOCK..Self::I_BLOCK + EXT2_FAST_SYMLINK_MAX]
+    }
+
+    pub fn block_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[Self::I_BLOCK..Self::I_BLOCK + EXT2_FAST_SYMLINK_MAX]
+    }
+
+    /// 扩展属性块的块号，没有属性块时为0
+    pub fn file_acl(&self) -> u32 {
+        get_u32(&self.raw, Self::I_FILE_ACL)
+    }
+
+    pub fn set_file_acl(&mut self, val: u32) {
+        put_u32(&mut self.raw, Self::I_FILE_ACL, val);
+    }
+
+    /// 是否是目标保存在i_block中的快速符号链接
+    ///
+    /// ## 参数
+    /// - `block_size`: 文件系统的块大小，扩展属性块也计入i_blocks
+    pub fn is_fast_symlink(&self, block_size: usize) -> bool {
+        if self.file_type() != EXT2_S_IFLNK {
+            return false;
+        }
+        let ea_sectors = if self.file_acl() != 0 {
+            (block_size >> 9) as u32
+        } else {
+            0
+        };
+        self.blocks() == ea_sectors
+    }
+}
+
+/// 目录项
+#[derive(Debug, Clone)]
+pub struct Ext2DirEntry {
+    /// 目录项在块内的偏移
+    pub offset: usize,
+    pub inode: u32,
+    pub rec_len: usize,
+    pub file_type: u8,
+    pub name: Vec<u8>,
+}
+
+impl Ext2DirEntry {
+    /// 保存名字为`name_len`的目录项至少需要的长度
+    pub fn needed_len(name_len: usize) -> usize {
+        (EXT2_DIR_ENTRY_HEADER + name_len).next_multiple_of(4)
+    }
+
+    /// 解析一个目录块中的所有目录项（包括inode为0的空闲项）
+    pub fn parse_block(block: &[u8]) -> Result<Vec<Ext2DirEntry>, SystemError> {
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset < block.len() {
+            if offset + EXT2_DIR_ENTRY_HEADER > block.len() {
+                return Err(SystemError::EIO);
+            }
+            let inode = get_u32(block, offset);
+            let rec_len = get_u16(block, offset + 4) as usize;
+            let name_len = block[offset + 6] as usize;
+            let file_type = block[offset + 7];
+            if rec_len < EXT2_DIR_ENTRY_HEADER
+                || rec_len % 4 != 0
+                || offset + rec_len > block.len()
+                || EXT2_DIR_ENTRY_HEADER + name_len > rec_len
+            {
+                log::warn!("ext2: corrupted directory entry at offset {}", offset);
+                return Err(SystemError::EIO);
+            }
+            let name_start = offset + EXT2_DIR_ENTRY_HEADER;
+            entries.push(Ext2DirEntry {
+                offset,
+                inode,
+                rec_len,
+                file_type,
+                name: block[name_start..name_start + name_len].to_vec(),
+            });
+            offset += rec_len;
+        }
+        Ok(entries)
+    }
+
+    /// 把目录项写入块中
+    pub fn write_to(&self, block: &mut [u8]) {
+        let off = self.offset;
+        put_u32(block, off, self.inode);
+        put_u16(block, off + 4, self.rec_len as u16);
+        block[off + 6] = self.name.len() as u8;
+        block[off + 7] = self.file_type;
+        let name_start = off + EXT2_DIR_ENTRY_HEADER;
+        block[name_start..name_start + self.name.len()].copy_from_slice(&self.name);
+    }
+}
+
+/// extent树节点的头部
+#[derive(Debug, Clone)]
+pub struct Ext4ExtentHeader {
+    /// 有效条目的数量
+    pub entries: usize,
+    /// 节点在树中的高度，0表示叶子
+    pub depth: u16,
+}
+
+impl Ext4ExtentHeader {
+    /// 解析节点头部，并检查节点能装下所有条目
+    pub fn parse(node: &[u8]) -> Result<Self, SystemError> {
+        if node.len() < EXT4_EXT_ENTRY_SIZE || get_u16(node, 0) != EXT4_EXT_MAGIC {
+            log::warn!("ext4: bad extent header magic");
+            return Err(SystemError::EIO);
+        }
+        let entries = get_u16(node, 2) as usize;
+        let max = get_u16(node, 4) as usize;
+        let depth = get_u16(node, 6);
+        if entries > max
+            || (entries + 1) * EXT4_EXT_ENTRY_SIZE > node.len()
+            || depth > EXT4_EXT_MAX_DEPTH
+        {
+            log::warn!("ext4: corrupted extent header");
+            return Err(SystemError::EIO);
+        }
+        Ok(Self { entries, depth })
+    }
+
+    /// 查找覆盖逻辑块`lblk`的条目，即最后一个起始逻辑块号不大于`lblk`的条目
+    ///
+    /// 索引条目和extent条目的第一个字段都是起始逻辑块号，所以两者共用这个函数
+    pub fn search(&self, node: &[u8], lblk: u32) -> Option<usize> {
+        let first_block = |i: usize| get_u32(node, (i + 1) * EXT4_EXT_ENTRY_SIZE);
+        let n = (0..self.entries)
+            .take_while(|&i| first_block(i) <= lblk)
+            .count();
+        n.checked_sub(1)
+    }
+}
+
+/// extent树的叶子条目，描述一段连续的块
+#[derive(Debug, Clone)]
+pub struct Ext4Extent {
+    /// 第一个逻辑块号
+    pub block: u32,
+    /// 块数
+    pub len: u32,
+    /// 第一个物理块号
+    pub start: u64,
+    /// 未初始化的extent读出来是0
+    pub uninit: bool,
+}
+
+impl Ext4Extent {
+    /// 解析节点中的第`idx`个条目
+    pub fn at(node: &[u8], idx: usize) -> Self {
+        let off = (idx + 1) * EXT4_EXT_ENTRY_SIZE;
+        let raw_len = get_u16(node, off + 4);
+        let (len, uninit) = if raw_len > EXT4_EXT_INIT_MAX_LEN {
+            (raw_len - EXT4_EXT_INIT_MAX_LEN, true)
+        } else {
+            (raw_len, false)
+        };
+        Self {
+            block: get_u32(node, off),
+            len: len as u32,
+            start: get_u32(node, off + 8) as u64 | ((get_u16(node, off + 6) as u64) << 32),
+            uninit,
+        }
+    }
+
+    /// 逻辑块`lblk`对应的物理块，不在这个extent中或者未初始化时返回None
+    pub fn map(&self, lblk: u32) -> Option<u64> {
+        let rel = lblk.checked_sub(self.block)?;
+        if rel >= self.len || self.uninit {
+            return None;
+        }
+        Some(self.start + rel as u64)
+    }
+}
+
+/// extent树的索引条目，指向下一层节点
+#[derive(Debug, Clone)]
+pub struct Ext4ExtentIdx {
+    /// 下一层节点覆盖的第一个逻辑块号
+    #[allow(dead_code)]
+    pub block: u32,
+    /// 下一层节点所在的物理块号
+    pub leaf: u64,
+}
+
+impl Ext4ExtentIdx {
+    /// 解析节点中的第`idx`个条目
+    pub fn at(node: &[u8], idx: usize) -> Self {
+        let off = (idx + 1) * EXT4_EXT_ENTRY_SIZE;
+        Self {
+            block: get_u32(node, off),
+            leaf: get_u32(node, off + 4) as u64 | ((get_u16(node, off + 8) as u64) << 32),
+        }
+    }
+}
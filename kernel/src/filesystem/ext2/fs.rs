@@ -0,0 +1,1904 @@
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::{
+        block::gendisk::{GenDisk, GenDiskClaim},
+        device::device_number::DeviceNumber,
+    },
+    filesystem::{
+        ext4::filesystem::Ext4FileSystem,
+        vfs::{
+            self,
+            mount_options::MountOptions,
+            syscall::RenameFlags,
+            utils::DName,
+            vcore::{find_source_gendisk, generate_inode_id},
+            xattr::xattr_copy_value,
+            FileSystem, FileSystemMakerData, IndexNode, Magic, MountableFileSystem, FSMAKER,
+        },
+    },
+    libs::mutex::Mutex,
+    mm::{
+        fault::{PageFaultHandler, PageFaultMessage},
+        VmFaultReason,
+    },
+    process::ProcessManager,
+    register_mountable_fs,
+    time::PosixTimeSpec,
+};
+use linkme::distributed_slice;
+
+use super::{
+    disk::*,
+    htree::{dx_hash, dx_node_entries, dx_search, DxRoot, DX_HASH_TEA},
+    inode::{Ext2Inode, LockedExt2Inode},
+    xattr::{Ext2XattrBlock, EXT2_XATTR_H_REFCOUNT},
+};
+
+/// 受同一把锁保护的超级块和块组描述符
+///
+/// 分配、释放块和inode，以及修改inode、目录项的操作都在持有这把锁的情况下进行
+pub(super) struct Ext2Meta {
+    sb: Ext2SuperBlock,
+    groups: Vec<Ext2GroupDesc>,
+}
+
+pub struct Ext2FileSystem {
+    gendisk: Arc<GenDisk>,
+    /// 当前文件系统对应的设备号
+    pub(super) raw_dev: DeviceNumber,
+    /// 文件系统存在期间保持块设备处于打开状态
+    claim: GenDiskClaim,
+    pub(super) block_size: usize,
+    inode_size: usize,
+    /// 块组描述符的大小
+    desc_size: usize,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    first_data_block: u32,
+    first_ino: u32,
+    /// 目录项中是否保存文件类型
+    has_filetype: bool,
+    /// i_blocks是否为48位（HUGE_FILE特性）
+    pub(super) huge_file: bool,
+    /// 是否可以使用htree索引查找目录项（DIR_INDEX特性）
+    dir_index: bool,
+    /// htree哈希的种子
+    hash_seed: [u32; 4],
+    /// htree是否使用无符号char计算哈希
+    hash_unsigned: bool,
+    /// 存在只读支持的ext3/ext4特性或者不支持的ro_compat特性时只能只读
+    read_only: bool,
+    meta: Mutex<Ext2Meta>,
+    root_inode: Arc<LockedExt2Inode>,
+}
+
+impl FileSystem for Ext2FileSystem {
+    fn root_inode(&self) -> Arc<dyn IndexNode> {
+        self.root_inode.clone()
+    }
+
+    fn info(&self) -> vfs::FsInfo {
+        vfs::FsInfo {
+            blk_dev_id: self.raw_dev.data() as usize,
+            max_name_len: EXT2_NAME_LEN,
+        }
+    }
+
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "ext2"
+    }
+
+    fn dcache_enabled(&self) -> bool {
+        true
+    }
+
+    fn time_granularity(&self) -> u32 {
+        // 磁盘上的时间戳只保存到秒
+        1_000_000_000
+    }
+
+    fn super_block(&self) -> vfs::SuperBlock {
+        // ext2/3/4共用同一个魔数
+        vfs::SuperBlock::new(
+            Magic::EXT4_MAGIC,
+            self.block_size as u64,
+            EXT2_NAME_LEN as u64,
+        )
+    }
+
+    fn statfs(&self, _inode: &Arc<dyn IndexNode>) -> Result<vfs::SuperBlock, SystemError> {
+        let meta = self.meta.lock();
+        let mut sb = self.super_block();
+        let bfree = meta.sb.free_blocks_count();
+        sb.blocks = meta.sb.blocks_count() - meta.sb.first_data_block() as u64;
+        sb.bfree = bfree;
+        sb.bavail = bfree.saturating_sub(meta.sb.r_blocks_count());
+        sb.files = meta.sb.inodes_count() as u64;
+        sb.ffree = meta.sb.free_inodes_count() as u64;
+        sb.frsize = self.block_size as u64;
+        Ok(sb)
+    }
+
+    unsafe fn fault(&self, pfm: &mut PageFaultMessage) -> VmFaultReason {
+        PageFaultHandler::filemap_fault(pfm)
+    }
+
+    unsafe fn map_pages(
+        &self,
+        pfm: &mut PageFaultMessage,
+        start_pgoff: usize,
+        end_pgoff: usize,
+    ) -> VmFaultReason {
+        PageFaultHandler::filemap_map_pages(pfm, start_pgoff, end_pgoff)
+    }
+
+    fn on_umount(&self) {
+        if !self.read_only {
+            let mut meta = self.meta.lock();
+            let state = meta.sb.state() | EXT2_VALID_FS;
+            meta.sb.set_state(state);
+            meta.sb.set_wtime(now_secs());
+            if let Err(e) = self.write_super(&meta) {
+                log::warn!("ext2: failed to write the superblock on umount: {:?}", e);
+            }
+        }
+        // 把块缓存中的脏数据写回磁盘
+        if let Err(e) = self.claim.disk().flush() {
+            log::warn!("ext2: failed to flush the disk on umount: {:?}", e);
+        }
+    }
+}
+
+/// 当前时间（秒），ext2的时间戳是32位的
+fn now_secs() -> u32 {
+    PosixTimeSpec::now().tv_sec.clamp(0, u32::MAX as i64) as u32
+}
+
+impl Ext2FileSystem {
+    /// 探测 gendisk 上是否是ext2驱动能够挂载的文件系统
+    ///
+    /// ext4驱动能够挂载的文件系统由ext4驱动处理，这里不认为是ext2
+    pub fn probe(gendisk: &Arc<GenDisk>) -> bool {
+        if Ext4FileSystem::probe(gendisk).unwrap_or(false) {
+            return false;
+        }
+        let mut raw = vec![0u8; SUPERBLOCK_SIZE];
+        if gendisk.read_at_bytes(&mut raw, SUPERBLOCK_OFFSET).is_err() {
+            return false;
+        }
+        Ext2SuperBlock::parse(raw)
+            .map(|sb| sb.feature_incompat() & !EXT4_FEATURE_INCOMPAT_RO_SUPP == 0)
+            .unwrap_or(false)
+    }
+
+    pub fn from_gendisk(gendisk: Arc<GenDisk>) -> Result<Arc<dyn FileSystem>, SystemError> {
+        let mut raw = vec![0u8; SUPERBLOCK_SIZE];
+        gendisk.read_at_bytes(&mut raw, SUPERBLOCK_OFFSET)?;
+        let mut sb = Ext2SuperBlock::parse(raw)?;
+
+        let incompat = sb.feature_incompat();
+        let unsupported = incompat & !EXT4_FEATURE_INCOMPAT_RO_SUPP;
+        if unsupported != 0 {
+            log::warn!(
+                "ext2: unsupported incompat features {:#x}, refusing to mount",
+                unsupported
+            );
+            return Err(SystemError::EINVAL);
+        }
+        // ext3/ext4的这些特性只支持读取
+        let ro_incompat = incompat & !EXT2_FEATURE_INCOMPAT_SUPP;
+        if ro_incompat != 0 {
+            log::warn!(
+                "ext2: incompat features {:#x} are read-only, mounting read-only",
+                ro_incompat
+            );
+        }
+        if incompat & EXT3_FEATURE_INCOMPAT_RECOVER != 0 {
+            log::warn!("ext2: the journal needs recovery, recently written data may be missing");
+        }
+        let unsupported = sb.feature_ro_compat() & !EXT2_FEATURE_RO_COMPAT_SUPP;
+        if unsupported != 0 {
+            log::warn!(
+                "ext2: unsupported ro_compat features {:#x}, mounting read-only",
+                unsupported
+            );
+        }
+        let read_only = ro_incompat != 0 || unsupported != 0;
+        if sb.state() & EXT2_VALID_FS == 0 {
+            log::warn!("ext2: mounting unchecked fs, running fsck is recommended");
+        }
+
+        let block_size = sb.block_size();
+        let desc_size = sb.desc_size();
+        if desc_size < EXT2_GROUP_DESC_SIZE
+            || desc_size > block_size
+            || !desc_size.is_power_of_two()
+            || (incompat & EXT4_FEATURE_INCOMPAT_64BIT != 0 && desc_size < EXT4_MIN_DESC_SIZE_64BIT)
+        {
+            log::warn!("ext2: invalid group descriptor size {}", desc_size);
+            return Err(SystemError::EINVAL);
+        }
+        let group_count = sb.group_count();
+        let mut gd_buf = vec![0u8; group_count * desc_size];
+        gendisk.read_at_bytes(
+            &mut gd_buf,
+            (sb.first_data_block() as usize + 1) * block_size,
+        )?;
+        let groups: Vec<Ext2GroupDesc> = gd_buf
+            .chunks_exact(desc_size)
+            .map(Ext2GroupDesc::from_bytes)
+            .collect();
+        let blocks_count = sb.blocks_count();
+        if groups.iter().any(|gd| {
+            gd.block_bitmap() >= blocks_count
+                || gd.inode_bitmap() >= blocks_count
+                || gd.inode_table() >= blocks_count
+        }) {
+            log::warn!("ext2: corrupted group descriptors");
+            return Err(SystemError::EINVAL);
+        }
+
+        if !read_only {
+            // 挂载期间清除“干净”标记，正常卸载时再恢复
+            let state = sb.state() & !EXT2_VALID_FS;
+            sb.set_state(state);
+            let mnt_count = sb.mnt_count().wrapping_add(1);
+            sb.set_mnt_count(mnt_count);
+            sb.set_mtime(now_secs());
+            gendisk.write_at_bytes(sb.raw(), SUPERBLOCK_OFFSET)?;
+        }
+
+        let root_inode = Arc::new_cyclic(|self_ref: &Weak<LockedExt2Inode>| {
+            LockedExt2Inode(Mutex::new(Ext2Inode {
+                ino: EXT2_ROOT_INO,
+                fs_ptr: Weak::default(),
+                page_cache: None,
+                children: BTreeMap::new(),
+                dname: DName::from("/"),
+                vfs_inode_id: generate_inode_id(),
+                parent: self_ref.clone(),
+                self_ref: self_ref.clone(),
+                special_node: None,
+            }))
+        });
+
+        let fs = Arc::new(Ext2FileSystem {
+            raw_dev: gendisk.device_num(),
+            claim: gendisk.claim(),
+            gendisk,
+            block_size,
+            inode_size: sb.inode_size(),
+            blocks_per_group: sb.blocks_per_group(),
+            inodes_per_group: sb.inodes_per_group(),
+            first_data_block: sb.first_data_block(),
+            first_ino: sb.first_ino(),
+            desc_size,
+            has_filetype: incompat & EXT2_FEATURE_INCOMPAT_FILETYPE != 0,
+            huge_file: sb.feature_ro_compat() & EXT4_FEATURE_RO_COMPAT_HUGE_FILE != 0,
+            dir_index: sb.feature_compat() & EXT2_FEATURE_COMPAT_DIR_INDEX != 0,
+            hash_seed: sb.hash_seed(),
+            hash_unsigned: sb.flags() & EXT2_FLAGS_UNSIGNED_HASH != 0,
+            read_only,
+            meta: Mutex::new(Ext2Meta { sb, groups }),
+            root_inode,
+        });
+
+        if !fs.inode_attr(EXT2_ROOT_INO)?.is_dir() {
+            log::warn!("ext2: root inode is not a directory");
+            return Err(SystemError::EINVAL);
+        }
+
+        fs.root_inode.0.lock().fs_ptr = Arc::downgrade(&fs);
+
+        Ok(fs)
+    }
+
+    fn check_writable(&self) -> Result<(), SystemError> {
+        if self.read_only {
+            return Err(SystemError::EROFS);
+        }
+        Ok(())
+    }
+
+    fn sectors_per_block(&self) -> u32 {
+        (self.block_size / 512) as u32
+    }
+
+    // ---------------- 块读写 ----------------
+
+    fn check_block(&self, meta: &Ext2Meta, blk: u64) -> Result<u64, SystemError> {
+        if blk < self.first_data_block as u64 || blk >= meta.sb.blocks_count() {
+            log::warn!("ext2: block number {} out of range", blk);
+            return Err(SystemError::EIO);
+        }
+        Ok(blk)
+    }
+
+    fn read_block(&self, blk: u64, buf: &mut [u8]) -> Result<(), SystemError> {
+        self.read_block_at(blk, 0, buf)
+    }
+
+    fn write_block(&self, blk: u64, buf: &[u8]) -> Result<(), SystemError> {
+        self.write_block_at(blk, 0, buf)
+    }
+
+    /// 读取块内从`offset`开始的数据
+    fn read_block_at(&self, blk: u64, offset: usize, buf: &mut [u8]) -> Result<(), SystemError> {
+        self.gendisk
+            .read_at_bytes(buf, blk as usize * self.block_size + offset)?;
+        Ok(())
+    }
+
+    /// 写入块内从`offset`开始的数据
+    fn write_block_at(&self, blk: u64, offset: usize, buf: &[u8]) -> Result<(), SystemError> {
+        self.gendisk
+            .write_at_bytes(buf, blk as usize * self.block_size + offset)?;
+        Ok(())
+    }
+
+    fn zero_block(&self, blk: u64) -> Result<(), SystemError> {
+        self.write_block(blk, &vec![0u8; self.block_size])
+    }
+
+    fn write_super(&self, meta: &Ext2Meta) -> Result<(), SystemError> {
+        self.gendisk
+            .write_at_bytes(meta.sb.raw(), SUPERBLOCK_OFFSET)?;
+        Ok(())
+    }
+
+    fn write_group_desc(&self, meta: &Ext2Meta, group: usize) -> Result<(), SystemError> {
+        let table = (self.first_data_block as usize + 1) * self.block_size;
+        self.gendisk
+            .write_at_bytes(meta.groups[group].raw(), table + group * self.desc_size)?;
+        Ok(())
+    }
+
+    // ---------------- 块和inode的分配 ----------------
+
+    /// 块组中实际的块数，最后一个块组可能不满
+    fn blocks_in_group(&self, meta: &Ext2Meta, group: usize) -> usize {
+        let start = self.first_data_block as usize + group * self.blocks_per_group as usize;
+        core::cmp::min(
+            self.blocks_per_group as usize,
+            meta.sb.blocks_count() as usize - start,
+        )
+    }
+
+    fn inode_group(&self, ino: u32) -> usize {
+        ((ino - 1) / self.inodes_per_group) as usize
+    }
+
+    /// 在位图中查找第一个为0的位
+    fn find_zero_bit(bitmap: &[u8], start: usize, nbits: usize) -> Option<usize> {
+        (start..nbits).find(|&bit| bitmap[bit / 8] & (1 << (bit % 8)) == 0)
+    }
+
+    /// 分配一个块，并把它清零
+    ///
+    /// ## 参数
+    /// - `goal_group`: 优先从这个块组分配
+    fn alloc_block(&self, meta: &mut Ext2Meta, goal_group: usize) -> Result<u32, SystemError> {
+        let ngroups = meta.groups.len();
+        let mut bitmap = vec![0u8; self.block_size];
+        for i in 0..ngroups {
+            let group = (goal_group + i) % ngroups;
+            if meta.groups[group].free_blocks_count() == 0 {
+                continue;
+            }
+            let bitmap_blk = meta.groups[group].block_bitmap();
+            self.read_block(bitmap_blk, &mut bitmap)?;
+            let nbits = self.blocks_in_group(meta, group);
+            let Some(bit) = Self::find_zero_bit(&bitmap, 0, nbits) else {
+                log::warn!("ext2: group {} has no free block but counts some", group);
+                continue;
+            };
+            bitmap[bit / 8] |= 1 << (bit % 8);
+            self.write_block(bitmap_blk, &bitmap)?;
+
+            let gd = &mut meta.groups[group];
+            gd.set_free_blocks_count(gd.free_blocks_count() - 1);
+            self.write_group_desc(meta, group)?;
+            let free = meta.sb.free_blocks_count().saturating_sub(1);
+            meta.sb.set_free_blocks_count(free);
+            self.write_super(meta)?;
+
+            let blk = self.first_data_block + group as u32 * self.blocks_per_group + bit as u32;
+            self.zero_block(blk as u64)?;
+            return Ok(blk);
+        }
+        Err(SystemError::ENOSPC)
+    }
+
+    fn free_block(&self, meta: &mut Ext2Meta, blk: u32) -> Result<(), SystemError> {
+        self.check_block(meta, blk as u64)?;
+        let rel = blk - self.first_data_block;
+        let group = (rel / self.blocks_per_group) as usize;
+        let bit = (rel % self.blocks_per_group) as usize;
+
+        let mut bitmap = vec![0u8; self.block_size];
+        let bitmap_blk = meta.groups[group].block_bitmap();
+        self.read_block(bitmap_blk, &mut bitmap)?;
+        if bitmap[bit / 8] & (1 << (bit % 8)) == 0 {
+            log::warn!("ext2: freeing already free block {}", blk);
+            return Ok(());
+        }
+        bitmap[bit / 8] &= !(1 << (bit % 8));
+        self.write_block(bitmap_blk, &bitmap)?;
+
+        let gd = &mut meta.groups[group];
+        gd.set_free_blocks_count(gd.free_blocks_count() + 1);
+        self.write_group_desc(meta, group)?;
+        let free = meta.sb.free_blocks_count() + 1;
+        meta.sb.set_free_blocks_count(free);
+        self.write_super(meta)
+    }
+
+    fn alloc_inode(
+        &self,
+        meta: &mut Ext2Meta,
+        goal_group: usize,
+        is_dir: bool,
+    ) -> Result<u32, SystemError> {
+        let ngroups = meta.groups.len();
+        let mut bitmap = vec![0u8; self.block_size];
+        for i in 0..ngroups {
+            let group = (goal_group + i) % ngroups;
+            if meta.groups[group].free_inodes_count() == 0 {
+                continue;
+            }
+            let bitmap_blk = meta.groups[group].inode_bitmap();
+            self.read_block(bitmap_blk, &mut bitmap)?;
+            // 跳过保留的inode
+            let base = group as u32 * self.inodes_per_group;
+            let start = self.first_ino.saturating_sub(base + 1) as usize;
+            let Some(bit) = Self::find_zero_bit(&bitmap, start, self.inodes_per_group as usize)
+            else {
+                continue;
+            };
+            bitmap[bit / 8] |= 1 << (bit % 8);
+            self.write_block(bitmap_blk, &bitmap)?;
+
+            let gd = &mut meta.groups[group];
+            gd.set_free_inodes_count(gd.free_inodes_count() - 1);
+            if is_dir {
+                gd.set_used_dirs_count(gd.used_dirs_count() + 1);
+            }
+            self.write_group_desc(meta, group)?;
+            let free = meta.sb.free_inodes_count().saturating_sub(1);
+            meta.sb.set_free_inodes_count(free);
+            self.write_super(meta)?;
+
+            return Ok(base + bit as u32 + 1);
+        }
+        Err(SystemError::ENOSPC)
+    }
+
+    fn free_inode(&self, meta: &mut Ext2Meta, ino: u32, is_dir: bool) -> Result<(), SystemError> {
+        let group = self.inode_group(ino);
+        let bit = ((ino - 1) % self.inodes_per_group) as usize;
+
+        let mut bitmap = vec![0u8; self.block_size];
+        let bitmap_blk = meta.groups[group].inode_bitmap();
+        self.read_block(bitmap_blk, &mut bitmap)?;
+        if bitmap[bit / 8] & (1 << (bit % 8)) == 0 {
+            log::warn!("ext2: freeing already free inode {}", ino);
+            return Ok(());
+        }
+        bitmap[bit / 8] &= !(1 << (bit % 8));
+        self.write_block(bitmap_blk, &bitmap)?;
+
+        let gd = &mut meta.groups[group];
+        gd.set_free_inodes_count(gd.free_inodes_count() + 1);
+        if is_dir {
+            gd.set_used_dirs_count(gd.used_dirs_count().saturating_sub(1));
+        }
+        self.write_group_desc(meta, group)?;
+        let free = meta.sb.free_inodes_count() + 1;
+        meta.sb.set_free_inodes_count(free);
+        self.write_super(meta)
+    }
+
+    // ---------------- inode读写 ----------------
+
+    fn inode_offset(&self, meta: &Ext2Meta, ino: u32) -> Result<usize, SystemError> {
+        if ino == 0 || ino > meta.sb.inodes_count() {
+            log::warn!("ext2: inode number {} out of range", ino);
+            return Err(SystemError::EIO);
+        }
+        let table = meta.groups[self.inode_group(ino)].inode_table() as usize;
+        let index = ((ino - 1) % self.inodes_per_group) as usize;
+        Ok(table * self.block_size + index * self.inode_size)
+    }
+
+    fn read_inode(&self, meta: &Ext2Meta, ino: u32) -> Result<Ext2DiskInode, SystemError> {
+        let mut raw = vec![0u8; self.inode_size];
+        self.gendisk
+            .read_at_bytes(&mut raw, self.inode_offset(meta, ino)?)?;
+        Ok(Ext2DiskInode::from_bytes(raw))
+    }
+
+    fn write_inode(
+        &self,
+        meta: &Ext2Meta,
+        ino: u32,
+        inode: &Ext2DiskInode,
+    ) -> Result<(), SystemError> {
+        self.gendisk
+            .write_at_bytes(inode.raw(), self.inode_offset(meta, ino)?)?;
+        Ok(())
+    }
+
+    // ---------------- 块映射 ----------------
+
+    /// 计算逻辑块号在间接块树中的路径
+    ///
+    /// ## 返回值
+    /// - `(i_block中的下标, 各级间接块中的下标)`
+    fn block_path(&self, lblk: u64) -> Result<(usize, Vec<usize>), SystemError> {
+        let per = (self.block_size / 4) as u64;
+        let mut n = lblk;
+        if n < EXT2_NDIR_BLOCKS as u64 {
+            return Ok((n as usize, Vec::new()));
+        }
+        n -= EXT2_NDIR_BLOCKS as u64;
+        if n < per {
+            return Ok((EXT2_IND_BLOCK, vec![n as usize]));
+        }
+        n -= per;
+        if n < per * per {
+            return Ok((
+                EXT2_DIND_BLOCK,
+                vec![(n / per) as usize, (n % per) as usize],
+            ));
+        }
+        n -= per * per;
+        if n < per * per * per {
+            return Ok((
+                EXT2_TIND_BLOCK,
+                vec![
+                    (n / (per * per)) as usize,
+                    ((n / per) % per) as usize,
+                    (n % per) as usize,
+                ],
+            ));
+        }
+        Err(SystemError::EFBIG)
+    }
+
+    /// 文件能够达到的最大大小
+    fn max_file_size(&self) -> u64 {
+        let per = (self.block_size / 4) as u64;
+        let blocks = EXT2_NDIR_BLOCKS as u64 + per + per * per + per * per * per;
+        // i_blocks以512字节为单位，且只有32位
+        core::cmp::min(blocks * self.block_size as u64, (u32::MAX as u64) << 9)
+    }
+
+    /// 查找逻辑块对应的物理块，空洞返回None
+    fn lookup_block(
+        &self,
+        meta: &Ext2Meta,
+        inode: &Ext2DiskInode,
+        lblk: u64,
+    ) -> Result<Option<u64>, SystemError> {
+        if inode.has_extents() {
+            return self.lookup_extent(meta, inode, lblk);
+        }
+        let (slot, path) = self.block_path(lblk)?;
+        let mut blk = inode.block(slot) as u64;
+        for idx in path {
+            if blk == 0 {
+                return Ok(None);
+            }
+            let mut ptr = [0u8; 4];
+            self.read_block_at(self.check_block(meta, blk)?, idx * 4, &mut ptr)?;
+            blk = u32::from_le_bytes(ptr) as u64;
+        }
+        if blk == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.check_block(meta, blk)?))
+    }
+
+    /// 在extent树中查找逻辑块对应的物理块，空洞和未初始化的extent返回None
+    fn lookup_extent(
+        &self,
+        meta: &Ext2Meta,
+        inode: &Ext2DiskInode,
+        lblk: u64,
+    ) -> Result<Option<u64>, SystemError> {
+        // extent的逻辑块号只有32位
+        let Ok(lblk) = u32::try_from(lblk) else {
+            return Ok(None);
+        };
+        let mut node = inode.block_bytes().to_vec();
+        let mut expected_depth = None;
+        loop {
+            let header = Ext4ExtentHeader::parse(&node)?;
+            if expected_depth.is_some_and(|d| d != header.depth) {
+                log::warn!("ext4: extent tree depth mismatch");
+                return Err(SystemError::EIO);
+            }
+            let Some(idx) = header.search(&node, lblk) else {
+                return Ok(None);
+            };
+            if header.depth == 0 {
+                return match Ext4Extent::at(&node, idx).map(lblk) {
+                    Some(blk) => Ok(Some(self.check_block(meta, blk)?)),
+                    None => Ok(None),
+                };
+            }
+            let leaf = self.check_block(meta, Ext4ExtentIdx::at(&node, idx).leaf)?;
+            node.resize(self.block_size, 0);
+            self.read_block(leaf, &mut node)?;
+            expected_depth = Some(header.depth - 1);
+        }
+    }
+
+    /// 查找逻辑块对应的物理块，不存在时分配（包括途经的间接块）
+    ///
+    /// 会修改inode的i_block和i_blocks，由调用者写回inode
+    fn map_block(
+        &self,
+        meta: &mut Ext2Meta,
+        ino: u32,
+        inode: &mut Ext2DiskInode,
+        lblk: u64,
+    ) -> Result<u64, SystemError> {
+        let goal = self.inode_group(ino);
+        let (slot, path) = self.block_path(lblk)?;
+        let mut blk = inode.block(slot);
+        if blk == 0 {
+            blk = self.alloc_block(meta, goal)?;
+            inode.set_block(slot, blk);
+            inode.set_blocks(inode.blocks() + self.sectors_per_block());
+        }
+        for idx in path {
+            let mut ptr = [0u8; 4];
+            self.read_block_at(self.check_block(meta, blk as u64)?, idx * 4, &mut ptr)?;
+            let mut next = u32::from_le_bytes(ptr);
+            if next == 0 {
+                next = self.alloc_block(meta, goal)?;
+                self.write_block_at(blk as u64, idx * 4, &next.to_le_bytes())?;
+                inode.set_blocks(inode.blocks() + self.sectors_per_block());
+            }
+            blk = next;
+        }
+        self.check_block(meta, blk as u64)
+    }
+
+    /// 释放逻辑块号大于等于`keep`的所有块
+    fn truncate_blocks(
+        &self,
+        meta: &mut Ext2Meta,
+        inode: &mut Ext2DiskInode,
+        keep: u64,
+    ) -> Result<(), SystemError> {
+        for i in (keep as usize).min(EXT2_NDIR_BLOCKS)..EXT2_NDIR_BLOCKS {
+            let blk = inode.block(i);
+            if blk != 0 {
+                self.free_block(meta, blk)?;
+                inode.set_blocks(inode.blocks().saturating_sub(self.sectors_per_block()));
+                inode.set_block(i, 0);
+            }
+        }
+
+        let per = (self.block_size / 4) as u64;
+        let mut base = EXT2_NDIR_BLOCKS as u64;
+        let mut span = per;
+        for (slot, depth) in [
+            (EXT2_IND_BLOCK, 1),
+            (EXT2_DIND_BLOCK, 2),
+            (EXT2_TIND_BLOCK, 3),
+        ] {
+            let blk = inode.block(slot);
+            if blk != 0
+                && base + span > keep
+                && self.free_branch(meta, inode, blk, depth, base, keep)?
+            {
+                inode.set_block(slot, 0);
+            }
+            base += span;
+            span *= per;
+        }
+        Ok(())
+    }
+
+    /// 释放间接块`blk`管理的、逻辑块号大于等于`keep`的块
+    ///
+    /// ## 参数
+    /// - `depth`: 间接的级数
+    /// - `base`: `blk`管理的第一个逻辑块号
+    ///
+    /// ## 返回值
+    /// - 如果`blk`本身也被释放，返回true
+    fn free_branch(
+        &self,
+        meta: &mut Ext2Meta,
+        inode: &mut Ext2DiskInode,
+        blk: u32,
+        depth: u32,
+        base: u64,
+        keep: u64,
+    ) -> Result<bool, SystemError> {
+        let per = self.block_size / 4;
+        let child_span = (per as u64).pow(depth - 1);
+        let mut ptrs = vec![0u8; self.block_size];
+        self.read_block(self.check_block(meta, blk as u64)?, &mut ptrs)?;
+
+        let mut dirty = false;
+        for i in 0..per {
+            let child = get_u32(&ptrs, i * 4);
+            let child_base = base + i as u64 * child_span;
+            if child == 0 || child_base + child_span <= keep {
+                continue;
+            }
+            let freed = if depth == 1 {
+                self.free_block(meta, child)?;
+                inode.set_blocks(inode.blocks().saturating_sub(self.sectors_per_block()));
+                true
+            } else {
+                self.free_branch(meta, inode, child, depth - 1, child_base, keep)?
+            };
+            if freed {
+                put_u32(&mut ptrs, i * 4, 0);
+                dirty = true;
+            }
+        }
+
+        if base >= keep {
+            self.free_block(meta, blk)?;
+            inode.set_blocks(inode.blocks().saturating_sub(self.sectors_per_block()));
+            return Ok(true);
+        }
+        if dirty {
+            self.write_block(blk as u64, &ptrs)?;
+        }
+        Ok(false)
+    }
+
+    /// 数据块是否通过i_block映射（快速符号链接和设备文件的i_block保存的是别的内容）
+    fn has_data_blocks(&self, inode: &Ext2DiskInode) -> bool {
+        match inode.file_type() {
+            EXT2_S_IFREG | EXT2_S_IFDIR => true,
+            EXT2_S_IFLNK => !inode.is_fast_symlink(self.block_size),
+            _ => false,
+        }
+    }
+
+    // ---------------- 文件数据 ----------------
+
+    fn read_data(
+        &self,
+        meta: &Ext2Meta,
+        inode: &Ext2DiskInode,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        let size = inode.size() as usize;
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = core::cmp::min(buf.len(), size - offset);
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let in_block = pos % self.block_size;
+            let n = core::cmp::min(self.block_size - in_block, len - done);
+            let dst = &mut buf[done..done + n];
+            match self.lookup_block(meta, inode, (pos / self.block_size) as u64)? {
+                Some(blk) => self.read_block_at(blk, in_block, dst)?,
+                // 空洞读出来是0
+                None => dst.fill(0),
+            }
+            done += n;
+        }
+        Ok(len)
+    }
+
+    fn write_data(
+        &self,
+        meta: &mut Ext2Meta,
+        ino: u32,
+        inode: &mut Ext2DiskInode,
+        offset: usize,
+        buf: &[u8],
+    ) -> Result<usize, SystemError> {
+        let end = offset.checked_add(buf.len()).ok_or(SystemError::EFBIG)?;
+        if end as u64 > self.max_file_size() {
+            return Err(SystemError::EFBIG);
+        }
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let in_block = pos % self.block_size;
+            let n = core::cmp::min(self.block_size - in_block, buf.len() - done);
+            let blk = self.map_block(meta, ino, inode, (pos / self.block_size) as u64)?;
+            self.write_block_at(blk, in_block, &buf[done..done + n])?;
+            done += n;
+        }
+        if end as u64 > inode.size() {
+            self.set_inode_size(meta, inode, end as u64)?;
+        }
+        Ok(done)
+    }
+
+    /// 修改inode大小，超过2GiB的普通文件需要LARGE_FILE特性
+    fn set_inode_size(
+        &self,
+        meta: &mut Ext2Meta,
+        inode: &mut Ext2DiskInode,
+        size: u64,
+    ) -> Result<(), SystemError> {
+        if size > i32::MAX as u64
+            && meta.sb.feature_ro_compat() & EXT2_FEATURE_RO_COMPAT_LARGE_FILE == 0
+        {
+            // 修订版本0的超级块没有特性字段
+            if meta.sb.rev_level() == 0 {
+                return Err(SystemError::EFBIG);
+            }
+            let ro_compat = meta.sb.feature_ro_compat() | EXT2_FEATURE_RO_COMPAT_LARGE_FILE;
+            meta.sb.set_feature_ro_compat(ro_compat);
+            self.write_super(meta)?;
+        }
+        inode.set_size(size);
+        Ok(())
+    }
+
+    // ---------------- 目录项 ----------------
+
+    fn dir_block_count(&self, dir: &Ext2DiskInode) -> u64 {
+        dir.size() / self.block_size as u64
+    }
+
+    /// 遍历目录的每个数据块
+    ///
+    /// `f`返回`Some`时停止遍历并返回这个值
+    fn for_each_dir_block<T>(
+        &self,
+        meta: &Ext2Meta,
+        dir: &Ext2DiskInode,
+        mut f: impl FnMut(u64, &mut [u8], Vec<Ext2DirEntry>) -> Result<Option<T>, SystemError>,
+    ) -> Result<Option<T>, SystemError> {
+        let mut block = vec![0u8; self.block_size];
+        for lblk in 0..self.dir_block_count(dir) {
+            let Some(blk) = self.lookup_block(meta, dir, lblk)? else {
+                continue;
+            };
+            self.read_block(blk, &mut block)?;
+            let entries = Ext2DirEntry::parse_block(&block)?;
+            if let Some(r) = f(blk, &mut block, entries)? {
+                return Ok(Some(r));
+            }
+        }
+        Ok(None)
+    }
+
+    fn dir_find(
+        &self,
+        meta: &Ext2Meta,
+        dir: &Ext2DiskInode,
+        name: &str,
+    ) -> Result<Option<Ext2DirEntry>, SystemError> {
+        // "."和".."不在哈希索引中
+        if self.dir_index && dir.flags() & EXT2_INDEX_FL != 0 && name != "." && name != ".." {
+            match self.dx_find(meta, dir, name) {
+                Ok(r) => return Ok(r),
+                Err(e) => log::warn!("ext2: htree lookup failed ({:?}), scanning linearly", e),
+            }
+        }
+        self.for_each_dir_block(meta, dir, |_, _, entries| {
+            Ok(entries
+                .into_iter()
+                .find(|e| e.inode != 0 && e.name == name.as_bytes()))
+        })
+    }
+
+    /// 通过htree索引查找目录项
+    ///
+    /// 索引损坏或者哈希算法不支持时返回错误，由调用者退回到线性查找
+    fn dx_find(
+        &self,
+        meta: &Ext2Meta,
+        dir: &Ext2DiskInode,
+        name: &str,
+    ) -> Result<Option<Ext2DirEntry>, SystemError> {
+        let mut block = vec![0u8; self.block_size];
+        let read_dir_block = |lblk: u32, block: &mut [u8]| -> Result<(), SystemError> {
+            let blk = self
+                .lookup_block(meta, dir, lblk as u64)?
+                .ok_or(SystemError::EIO)?;
+            self.read_block(blk, block)
+        };
+
+        read_dir_block(0, &mut block)?;
+        let root = DxRoot::parse(&block)?;
+        let mut version = root.hash_version;
+        if version <= DX_HASH_TEA && self.hash_unsigned {
+            version += 3;
+        }
+        let hash =
+            dx_hash(name.as_bytes(), version, &self.hash_seed).ok_or(SystemError::EOPNOTSUPP)?;
+
+        let mut entries = root.entries;
+        for _ in 0..root.indirect_levels {
+            let i = dx_search(&entries, hash);
+            read_dir_block(entries[i].block, &mut block)?;
+            entries = dx_node_entries(&block)?;
+        }
+
+        let mut i = dx_search(&entries, hash);
+        loop {
+            read_dir_block(entries[i].block, &mut block)?;
+            let found = Ext2DirEntry::parse_block(&block)?
+                .into_iter()
+                .find(|e| e.inode != 0 && e.name == name.as_bytes());
+            if found.is_some() {
+                return Ok(found);
+            }
+            // 哈希冲突的目录项可能延续到下一个叶子块，此时下一个索引项的哈希与目标相同，最低位为1
+            i += 1;
+            if i >= entries.len() || entries[i].hash & !1 != hash {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn dir_is_empty(&self, meta: &Ext2Meta, dir: &Ext2DiskInode) -> Result<bool, SystemError> {
+        let non_empty = self.for_each_dir_block(meta, dir, |_, _, entries| {
+            Ok(entries
+                .iter()
+                .any(|e| e.inode != 0 && e.name != b"." && e.name != b"..")
+                .then_some(()))
+        })?;
+        Ok(non_empty.is_none())
+    }
+
+    fn dirent_type(&self, mode: u16) -> u8 {
+        if self.has_filetype {
+            mode_to_dirent_type(mode)
+        } else {
+            EXT2_FT_UNKNOWN
+        }
+    }
+
+    /// 在目录中添加目录项，空间不够时给目录追加一个块
+    ///
+    /// 可能修改目录inode的标志、大小和块映射，由调用者写回目录inode
+    fn dir_add(
+        &self,
+        meta: &mut Ext2Meta,
+        dir_ino: u32,
+        dir: &mut Ext2DiskInode,
+        name: &str,
+        ino: u32,
+        file_type: u8,
+    ) -> Result<(), SystemError> {
+        // 不维护htree索引，新目录项可能放在索引之外，所以让目录退化为线性目录
+        if dir.flags() & EXT2_INDEX_FL != 0 {
+            dir.set_flags(dir.flags() & !EXT2_INDEX_FL);
+        }
+        let needed = Ext2DirEntry::needed_len(name.len());
+        let mut new_entry = Ext2DirEntry {
+            offset: 0,
+            inode: ino,
+            rec_len: 0,
+            file_type,
+            name: name.as_bytes().to_vec(),
+        };
+
+        let inserted = self.for_each_dir_block(meta, dir, |blk, block, entries| {
+            for entry in entries {
+                let used = if entry.inode == 0 {
+                    0
+                } else {
+                    Ext2DirEntry::needed_len(entry.name.len())
+                };
+                if entry.rec_len - used < needed {
+                    continue;
+                }
+                if used == 0 {
+                    new_entry.offset = entry.offset;
+                    new_entry.rec_len = entry.rec_len;
+                } else {
+                    // 把已有目录项多余的空间分出来
+                    let mut shrunk = entry.clone();
+                    shrunk.rec_len = used;
+                    shrunk.write_to(block);
+                    new_entry.offset = entry.offset + used;
+                    new_entry.rec_len = entry.rec_len - used;
+                }
+                new_entry.write_to(block);
+                self.write_block(blk, block)?;
+                return Ok(Some(()));
+            }
+            Ok(None)
+        })?;
+        if inserted.is_some() {
+            return Ok(());
+        }
+
+        let lblk = self.dir_block_count(dir);
+        let blk = self.map_block(meta, dir_ino, dir, lblk)?;
+        let mut block = vec![0u8; self.block_size];
+        new_entry.offset = 0;
+        new_entry.rec_len = self.block_size;
+        new_entry.write_to(&mut block);
+        self.write_block(blk, &block)?;
+        dir.set_size((lblk + 1) * self.block_size as u64);
+        Ok(())
+    }
+
+    /// 删除目录项，返回被删除的目录项
+    fn dir_remove(
+        &self,
+        meta: &Ext2Meta,
+        dir: &Ext2DiskInode,
+        name: &str,
+    ) -> Result<Ext2DirEntry, SystemError> {
+        self.for_each_dir_block(meta, dir, |blk, block, entries| {
+            let Some(pos) = entries
+                .iter()
+                .position(|e| e.inode != 0 && e.name == name.as_bytes())
+            else {
+                return Ok(None);
+            };
+            let mut removed = entries[pos].clone();
+            if pos > 0 {
+                // 合并到前一个目录项
+                let mut prev = entries[pos - 1].clone();
+                prev.rec_len += removed.rec_len;
+                prev.write_to(block);
+            } else {
+                removed.inode = 0;
+                removed.write_to(block);
+                removed.inode = entries[pos].inode;
+            }
+            self.write_block(blk, block)?;
+            Ok(Some(removed))
+        })?
+        .ok_or(SystemError::ENOENT)
+    }
+
+    /// 让已有的目录项指向另一个inode
+    fn dir_set_entry(
+        &self,
+        meta: &Ext2Meta,
+        dir: &Ext2DiskInode,
+        name: &str,
+        ino: u32,
+        file_type: u8,
+    ) -> Result<(), SystemError> {
+        self.for_each_dir_block(meta, dir, |blk, block, entries| {
+            let Some(mut entry) = entries
+                .into_iter()
+                .find(|e| e.inode != 0 && e.name == name.as_bytes())
+            else {
+                return Ok(None);
+            };
+            entry.inode = ino;
+            entry.file_type = file_type;
+            entry.write_to(block);
+            self.write_block(blk, block)?;
+            Ok(Some(()))
+        })?
+        .ok_or(SystemError::ENOENT)
+    }
+
+    /// 读取目录inode，并检查它没有被删除
+    fn read_dir_inode(&self, meta: &Ext2Meta, ino: u32) -> Result<Ext2DiskInode, SystemError> {
+        let dir = self.read_inode(meta, ino)?;
+        if !dir.is_dir() {
+            return Err(SystemError::ENOTDIR);
+        }
+        if dir.links_count() == 0 {
+            return Err(SystemError::ENOENT);
+        }
+        Ok(dir)
+    }
+
+    fn check_name(name: &str) -> Result<(), SystemError> {
+        if name.is_empty() {
+            return Err(SystemError::ENOENT);
+        }
+        if name.len() > EXT2_NAME_LEN {
+            return Err(SystemError::ENAMETOOLONG);
+        }
+        if name == "." || name == ".." {
+            return Err(SystemError::EEXIST);
+        }
+        Ok(())
+    }
+
+    // ---------------- 扩展属性 ----------------
+
+    /// 读取inode的扩展属性块，没有属性块时返回空的属性表
+    fn read_xattrs(
+        &self,
+        meta: &Ext2Meta,
+        inode: &Ext2DiskInode,
+    ) -> Result<Ext2XattrBlock, SystemError> {
+        let blk = inode.file_acl();
+        if blk == 0 {
+            return Ok(Ext2XattrBlock::default());
+        }
+        let mut buf = vec![0u8; self.block_size];
+        self.read_block(self.check_block(meta, blk as u64)?, &mut buf)?;
+        Ext2XattrBlock::parse(&buf)
+    }
+
+    /// 把修改后的属性表写回磁盘
+    ///
+    /// 属性块被其他inode共享时写入新分配的块，属性表为空时释放属性块
+    fn write_xattrs(
+        &self,
+        meta: &mut Ext2Meta,
+        ino: u32,
+        inode: &mut Ext2DiskInode,
+        xattrs: &Ext2XattrBlock,
+    ) -> Result<(), SystemError> {
+        if xattrs.is_empty() {
+            return self.put_xattr_block(meta, inode);
+        }
+        let raw = xattrs.to_bytes(self.block_size)?;
+        let old = inode.file_acl();
+        if old != 0 && xattrs.refcount <= 1 {
+            return self.write_block(self.check_block(meta, old as u64)?, &raw);
+        }
+
+        let blk = self.alloc_block(meta, self.inode_group(ino))?;
+        self.write_block(blk as u64, &raw)?;
+        self.put_xattr_block(meta, inode)?;
+        inode.set_file_acl(blk);
+        inode.set_blocks(inode.blocks() + self.sectors_per_block());
+
+        let compat = meta.sb.feature_compat();
+        if meta.sb.rev_level() != 0 && compat & EXT2_FEATURE_COMPAT_EXT_ATTR == 0 {
+            meta.sb
+                .set_feature_compat(compat | EXT2_FEATURE_COMPAT_EXT_ATTR);
+            self.write_super(meta)?;
+        }
+        Ok(())
+    }
+
+    /// 解除inode和属性块的关联，没有其他inode使用属性块时释放它
+    fn put_xattr_block(
+        &self,
+        meta: &mut Ext2Meta,
+        inode: &mut Ext2DiskInode,
+    ) -> Result<(), SystemError> {
+        let blk = inode.file_acl();
+        if blk == 0 {
+            return Ok(());
+        }
+        let xattrs = self.read_xattrs(meta, inode)?;
+        if xattrs.refcount <= 1 {
+            self.free_block(meta, blk)?;
+        } else {
+            let refcount = xattrs.refcount - 1;
+            self.write_block_at(blk as u64, EXT2_XATTR_H_REFCOUNT, &refcount.to_le_bytes())?;
+        }
+        inode.set_file_acl(0);
+        inode.set_blocks(inode.blocks().saturating_sub(self.sectors_per_block()));
+        Ok(())
+    }
+
+    /// 修改inode的扩展属性，并更新ctime
+    fn update_xattrs(
+        &self,
+        ino: u32,
+        f: impl FnOnce(&mut Ext2XattrBlock) -> Result<(), SystemError>,
+    ) -> Result<(), SystemError> {
+        self.check_writable()?;
+        let mut meta = self.meta.lock();
+        let mut inode = self.read_inode(&meta, ino)?;
+        let mut xattrs = self.read_xattrs(&meta, &inode)?;
+        f(&mut xattrs)?;
+        self.write_xattrs(&mut meta, ino, &mut inode, &xattrs)?;
+        inode.set_ctime(now_secs());
+        self.write_inode(&meta, ino, &inode)
+    }
+
+    // ---------------- 提供给inode的操作 ----------------
+
+    pub(super) fn inode_attr(&self, ino: u32) -> Result<Ext2DiskInode, SystemError> {
+        let meta = self.meta.lock();
+        self.read_inode(&meta, ino)
+    }
+
+    pub(super) fn lookup(&self, dir_ino: u32, name: &str) -> Result<u32, SystemError> {
+        if name.len() > EXT2_NAME_LEN {
+            return Err(SystemError::ENAMETOOLONG);
+        }
+        let meta = self.meta.lock();
+        let dir = self.read_inode(&meta, dir_ino)?;
+        if !dir.is_dir() {
+            return Err(SystemError::ENOTDIR);
+        }
+        self.dir_find(&meta, &dir, name)?
+            .map(|e| e.inode)
+            .ok_or(SystemError::ENOENT)
+    }
+
+    pub(super) fn list(&self, dir_ino: u32) -> Result<Vec<String>, SystemError> {
+        let meta = self.meta.lock();
+        let dir = self.read_inode(&meta, dir_ino)?;
+        if !dir.is_dir() {
+            return Err(SystemError::ENOTDIR);
+        }
+        let mut names = Vec::new();
+        self.for_each_dir_block(&meta, &dir, |_, _, entries| {
+            names.extend(
+                entries
+                    .into_iter()
+                    .filter(|e| e.inode != 0)
+                    .map(|e| String::from_utf8_lossy(&e.name).into_owned()),
+            );
+            Ok(None::<()>)
+        })?;
+        Ok(names)
+    }
+
+    pub(super) fn read(
+        &self,
+        ino: u32,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        let meta = self.meta.lock();
+        let inode = self.read_inode(&meta, ino)?;
+        match inode.file_type() {
+            EXT2_S_IFDIR => Err(SystemError::EISDIR),
+            EXT2_S_IFREG => self.read_data(&meta, &inode, offset, buf),
+            EXT2_S_IFLNK if inode.is_fast_symlink(self.block_size) => {
+                let len = core::cmp::min(inode.size() as usize, EXT2_FAST_SYMLINK_MAX);
+                let target = &inode.block_bytes()[..len];
+                if offset >= target.len() {
+                    return Ok(0);
+                }
+                let len = core::cmp::min(buf.len(), target.len() - offset);
+                buf[..len].copy_from_slice(&target[offset..offset + len]);
+                Ok(len)
+            }
+            EXT2_S_IFLNK => self.read_data(&meta, &inode, offset, buf),
+            _ => Err(SystemError::EINVAL),
+        }
+    }
+
+    pub(super) fn write(&self, ino: u32, offset: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        self.check_writable()?;
+        let mut meta = self.meta.lock();
+        let mut inode = self.read_inode(&meta, ino)?;
+        match inode.file_type() {
+            EXT2_S_IFDIR => return Err(SystemError::EISDIR),
+            EXT2_S_IFREG => {}
+            _ => return Err(SystemError::EINVAL),
+        }
+        let r = self.write_data(&mut meta, ino, &mut inode, offset, buf);
+        // 即使写入失败，也要写回已经分配的块
+        self.write_inode(&meta, ino, &inode)?;
+        r
+    }
+
+    pub(super) fn getxattr(
+        &self,
+        ino: u32,
+        name: &str,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        let meta = self.meta.lock();
+        let inode = self.read_inode(&meta, ino)?;
+        let xattrs = self.read_xattrs(&meta, &inode)?;
+        xattr_copy_value(xattrs.get(name)?, buf)
+    }
+
+    pub(super) fn listxattr(&self, ino: u32) -> Result<Vec<String>, SystemError> {
+        let meta = self.meta.lock();
+        let inode = self.read_inode(&meta, ino)?;
+        Ok(self.read_xattrs(&meta, &inode)?.names())
+    }
+
+    pub(super) fn setxattr(&self, ino: u32, name: &str, value: &[u8]) -> Result<(), SystemError> {
+        self.update_xattrs(ino, |xattrs| xattrs.set(name, value))
+    }
+
+    pub(super) fn removexattr(&self, ino: u32, name: &str) -> Result<(), SystemError> {
+        self.update_xattrs(ino, |xattrs| xattrs.remove(name))
+    }
+
+    /// 修改inode的属性
+    pub(super) fn setattr(
+        &self,
+        ino: u32,
+        f: impl FnOnce(&mut Ext2DiskInode),
+    ) -> Result<(), SystemError> {
+        self.check_writable()?;
+        let meta = self.meta.lock();
+        let mut inode = self.read_inode(&meta, ino)?;
+        f(&mut inode);
+        self.write_inode(&meta, ino, &inode)
+    }
+
+    /// 通过页缓存写入之后更新文件大小和修改时间
+    ///
+    /// ## 参数
+    /// - `end`: 写入的结束位置，文件大小不会缩小
+    pub(super) fn write_extend(&self, ino: u32, end: u64) -> Result<(), SystemError> {
+        self.check_writable()?;
+        let mut meta = self.meta.lock();
+        let mut inode = self.read_inode(&meta, ino)?;
+        if end > inode.size() {
+            if end > self.max_file_size() {
+                return Err(SystemError::EFBIG);
+            }
+            self.set_inode_size(&mut meta, &mut inode, end)?;
+        }
+        let now = now_secs();
+        inode.set_mtime(now);
+        inode.set_ctime(now);
+        self.write_inode(&meta, ino, &inode)
+    }
+
+    /// 修改普通文件的大小，缩小时释放多余的块
+    pub(super) fn truncate(&self, ino: u32, len: usize) -> Result<(), SystemError> {
+        self.check_writable()?;
+        if len as u64 > self.max_file_size() {
+            return Err(SystemError::EFBIG);
+        }
+        let mut meta = self.meta.lock();
+        let mut inode = self.read_inode(&meta, ino)?;
+        match inode.file_type() {
+            EXT2_S_IFDIR => return Err(SystemError::EISDIR),
+            EXT2_S_IFREG => {}
+            _ => return Err(SystemError::EINVAL),
+        }
+
+        let len = len as u64;
+        if len < inode.size() {
+            let bs = self.block_size as u64;
+            self.truncate_blocks(&mut meta, &mut inode, len.div_ceil(bs))?;
+            // 最后一块中超出文件大小的部分需要清零，以后扩展文件时才能读出0
+            let tail = (len % bs) as usize;
+            if tail != 0 {
+                if let Some(blk) = self.lookup_block(&meta, &inode, len / bs)? {
+                    let zeros = vec![0u8; self.block_size - tail];
+                    self.write_block_at(blk, tail, &zeros)?;
+                }
+            }
+        }
+        self.set_inode_size(&mut meta, &mut inode, len)?;
+        let now = now_secs();
+        inode.set_mtime(now);
+        inode.set_ctime(now);
+        self.write_inode(&meta, ino, &inode)
+    }
+
+    fn create_locked(
+        &self,
+        meta: &mut Ext2Meta,
+        dir_ino: u32,
+        name: &str,
+        mode: u16,
+        rdev: DeviceNumber,
+    ) -> Result<(u32, Ext2DiskInode), SystemError> {
+        Self::check_name(name)?;
+        let mut dir = self.read_dir_inode(meta, dir_ino)?;
+        if self.dir_find(meta, &dir, name)?.is_some() {
+            return Err(SystemError::EEXIST);
+        }
+
+        let is_dir = mode & EXT2_S_IFMT == EXT2_S_IFDIR;
+        if is_dir && dir.links_count() == u16::MAX {
+            return Err(SystemError::EMLINK);
+        }
+        let ino = self.alloc_inode(meta, self.inode_group(dir_ino), is_dir)?;
+
+        let cred = ProcessManager::current_pcb().cred();
+        let now = now_secs();
+        let mut inode = Ext2DiskInode::zeroed(self.inode_size);
+        let mut mode = mode;
+        inode.set_uid(cred.fsuid.data() as u32);
+        // 设置了setgid的目录下新建的文件继承目录的组，子目录还继承setgid位
+        if dir.mode() & 0o2000 != 0 {
+            inode.set_gid(dir.gid());
+            if is_dir {
+                mode |= 0o2000;
+            }
+        } else {
+            inode.set_gid(cred.fsgid.data() as u32);
+        }
+        inode.set_mode(mode);
+        inode.set_atime(now);
+        inode.set_ctime(now);
+        inode.set_mtime(now);
+        inode.set_links_count(if is_dir { 2 } else { 1 });
+
+        match mode & EXT2_S_IFMT {
+            EXT2_S_IFCHR | EXT2_S_IFBLK => {
+                if rdev.old_valid_dev() {
+                    inode.set_block(0, (rdev.major().data() << 8) | rdev.minor());
+                } else {
+                    inode.set_block(1, rdev.new_encode_dev());
+                }
+            }
+            EXT2_S_IFDIR => {
+                if let Err(e) = self.init_dir_block(meta, ino, &mut inode, dir_ino) {
+                    self.free_inode(meta, ino, true)?;
+                    return Err(e);
+                }
+            }
+            _ => {}
+        }
+        self.write_inode(meta, ino, &inode)?;
+
+        let file_type = self.dirent_type(mode);
+        if let Err(e) = self.dir_add(meta, dir_ino, &mut dir, name, ino, file_type) {
+            self.release_inode(meta, ino, &mut inode)?;
+            return Err(e);
+        }
+        if is_dir {
+            dir.set_links_count(dir.links_count() + 1);
+        }
+        dir.set_mtime(now);
+        dir.set_ctime(now);
+        self.write_inode(meta, dir_ino, &dir)?;
+        Ok((ino, inode))
+    }
+
+    /// 给新目录分配第一个块，写入"."和".."
+    fn init_dir_block(
+        &self,
+        meta: &mut Ext2Meta,
+        ino: u32,
+        inode: &mut Ext2DiskInode,
+        parent: u32,
+    ) -> Result<(), SystemError> {
+        let blk = self.map_block(meta, ino, inode, 0)?;
+        let mut block = vec![0u8; self.block_size];
+        let dot_len = Ext2DirEntry::needed_len(1);
+        let file_type = self.dirent_type(EXT2_S_IFDIR);
+        Ext2DirEntry {
+            offset: 0,
+            inode: ino,
+            rec_len: dot_len,
+            file_type,
+            name: b".".to_vec(),
+        }
+        .write_to(&mut block);
+        Ext2DirEntry {
+            offset: dot_len,
+            inode: parent,
+            rec_len: self.block_size - dot_len,
+            file_type,
+            name: b"..".to_vec(),
+        }
+        .write_to(&mut block);
+        self.write_block(blk, &block)?;
+        inode.set_size(self.block_size as u64);
+        Ok(())
+    }
+
+    /// 创建文件、目录或设备文件
+    ///
+    /// ## 参数
+    /// - `mode`: 文件类型和权限
+    /// - `rdev`: 字符设备和块设备的设备号
+    ///
+    /// ## 返回值
+    /// - 新inode的编号
+    pub(super) fn create(
+        &self,
+        dir_ino: u32,
+        name: &str,
+        mode: u16,
+        rdev: DeviceNumber,
+    ) -> Result<u32, SystemError> {
+        self.check_writable()?;
+        let mut meta = self.meta.lock();
+        self.create_locked(&mut meta, dir_ino, name, mode, rdev)
+            .map(|(ino, _)| ino)
+    }
+
+    /// 创建符号链接，短于60字节的目标直接保存在inode中
+    pub(super) fn symlink(
+        &self,
+        dir_ino: u32,
+        name: &str,
+        target: &str,
+    ) -> Result<u32, SystemError> {
+        self.check_writable()?;
+        let target = target.as_bytes();
+        if target.is_empty() {
+            return Err(SystemError::ENOENT);
+        }
+        if target.len() >= self.block_size {
+            return Err(SystemError::ENAMETOOLONG);
+        }
+        let mut meta = self.meta.lock();
+        let (ino, mut inode) = self.create_locked(
+            &mut meta,
+            dir_ino,
+            name,
+            EXT2_S_IFLNK | 0o777,
+            DeviceNumber::default(),
+        )?;
+        if target.len() < EXT2_FAST_SYMLINK_MAX {
+            inode.block_bytes_mut()[..target.len()].copy_from_slice(target);
+            inode.set_size(target.len() as u64);
+        } else if let Err(e) = self.write_data(&mut meta, ino, &mut inode, 0, target) {
+            self.write_inode(&meta, ino, &inode)?;
+            return Err(e);
+        }
+        self.write_inode(&meta, ino, &inode)?;
+        Ok(ino)
+    }
+
+    /// 创建硬链接
+    pub(super) fn link(&self, dir_ino: u32, name: &str, ino: u32) -> Result<(), SystemError> {
+        self.check_writable()?;
+        Self::check_name(name)?;
+        let mut meta = self.meta.lock();
+        let mut dir = self.read_dir_inode(&meta, dir_ino)?;
+        let mut inode = self.read_inode(&meta, ino)?;
+        if inode.is_dir() {
+            return Err(SystemError::EPERM);
+        }
+        if inode.links_count() == 0 {
+            return Err(SystemError::ENOENT);
+        }
+        if inode.links_count() == u16::MAX {
+            return Err(SystemError::EMLINK);
+        }
+        if self.dir_find(&meta, &dir, name)?.is_some() {
+            return Err(SystemError::EEXIST);
+        }
+
+        let file_type = self.dirent_type(inode.mode());
+        self.dir_add(&mut meta, dir_ino, &mut dir, name, ino, file_type)?;
+        let now = now_secs();
+        dir.set_mtime(now);
+        dir.set_ctime(now);
+        self.write_inode(&meta, dir_ino, &dir)?;
+        inode.set_links_count(inode.links_count() + 1);
+        inode.set_ctime(now);
+        self.write_inode(&meta, ino, &inode)
+    }
+
+    /// 删除目录项
+    ///
+    /// 链接数减到0的inode不会立即释放，而是在没有人使用时由[`Self::evict`]释放
+    ///
+    /// ## 参数
+    /// - `rmdir`: 为true时只删除空目录，否则只删除非目录
+    ///
+    /// ## 返回值
+    /// - 被删除的目录项指向的inode
+    pub(super) fn unlink(&self, dir_ino: u32, name: &str, rmdir: bool) -> Result<u32, SystemError> {
+        self.check_writable()?;
+        if name == "." {
+            return Err(SystemError::EINVAL);
+        }
+        if name == ".." {
+            return Err(SystemError::ENOTEMPTY);
+        }
+        let meta = self.meta.lock();
+        let mut dir = self.read_inode(&meta, dir_ino)?;
+        if !dir.is_dir() {
+            return Err(SystemError::ENOTDIR);
+        }
+        let entry = self
+            .dir_find(&meta, &dir, name)?
+            .ok_or(SystemError::ENOENT)?;
+        let mut inode = self.read_inode(&meta, entry.inode)?;
+        if rmdir {
+            if !inode.is_dir() {
+                return Err(SystemError::ENOTDIR);
+            }
+            if !self.dir_is_empty(&meta, &inode)? {
+                return Err(SystemError::ENOTEMPTY);
+            }
+        } else if inode.is_dir() {
+            return Err(SystemError::EISDIR);
+        }
+
+        self.dir_remove(&meta, &dir, name)?;
+        let now = now_secs();
+        if rmdir {
+            inode.set_links_count(0);
+            dir.set_links_count(dir.links_count().saturating_sub(1));
+        } else {
+            inode.set_links_count(inode.links_count().saturating_sub(1));
+        }
+        inode.set_ctime(now);
+        self.write_inode(&meta, entry.inode, &inode)?;
+        dir.set_mtime(now);
+        dir.set_ctime(now);
+        self.write_inode(&meta, dir_ino, &dir)?;
+        Ok(entry.inode)
+    }
+
+    /// 重命名
+    ///
+    /// ## 返回值
+    /// - 被替换的目标inode（如果有）
+    pub(super) fn rename(
+        &self,
+        src_dir: u32,
+        old_name: &str,
+        dst_dir: u32,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> Result<Option<u32>, SystemError> {
+        self.check_writable()?;
+        if flags.contains(RenameFlags::WHITEOUT) {
+            return Err(SystemError::EINVAL);
+        }
+        if old_name == "." || old_name == ".." {
+            return Err(SystemError::EINVAL);
+        }
+        if new_name == "." || new_name == ".." {
+            return Err(SystemError::EINVAL);
+        }
+        Self::check_name(new_name)?;
+
+        let mut meta = self.meta.lock();
+        let sdir = self.read_dir_inode(&meta, src_dir)?;
+        let ddir = self.read_dir_inode(&meta, dst_dir)?;
+        let src = self
+            .dir_find(&meta, &sdir, old_name)?
+            .ok_or(SystemError::ENOENT)?;
+        let dst = self.dir_find(&meta, &ddir, new_name)?;
+
+        if flags.contains(RenameFlags::EXCHANGE) {
+            let dst = dst.ok_or(SystemError::ENOENT)?;
+            self.exchange_locked(&meta, src_dir, &src, dst_dir, &dst)?;
+            return Ok(None);
+        }
+        if dst.is_some() && flags.contains(RenameFlags::NOREPLACE) {
+            return Err(SystemError::EEXIST);
+        }
+        self.rename_locked(&mut meta, src_dir, &src, dst_dir, new_name, dst)
+    }
+
+    fn rename_locked(
+        &self,
+        meta: &mut Ext2Meta,
+        src_dir: u32,
+        src: &Ext2DirEntry,
+        dst_dir: u32,
+        new_name: &str,
+        dst: Option<Ext2DirEntry>,
+    ) -> Result<Option<u32>, SystemError> {
+        let now = now_secs();
+        let mut inode = self.read_inode(meta, src.inode)?;
+        let is_dir = inode.is_dir();
+        let file_type = self.dirent_type(inode.mode());
+        let old_name = String::from_utf8_lossy(&src.name).into_owned();
+
+        let mut replaced = None;
+        let mut ddir = self.read_inode(meta, dst_dir)?;
+        if let Some(dst) = dst {
+            // 同一个inode的两个硬链接之间重命名什么也不做
+            if dst.inode == src.inode {
+                return Ok(None);
+            }
+            let mut dst_inode = self.read_inode(meta, dst.inode)?;
+            if is_dir {
+                if !dst_inode.is_dir() {
+                    return Err(SystemError::ENOTDIR);
+                }
+                if !self.dir_is_empty(meta, &dst_inode)? {
+                    return Err(SystemError::ENOTEMPTY);
+                }
+            } else if dst_inode.is_dir() {
+                return Err(SystemError::EISDIR);
+            }
+
+            // 直接让目标目录项指向源inode，目标名字始终存在
+            self.dir_set_entry(meta, &ddir, new_name, src.inode, file_type)?;
+            if dst_inode.is_dir() {
+                dst_inode.set_links_count(0);
+                ddir.set_links_count(ddir.links_count().saturating_sub(1));
+            } else {
+                dst_inode.set_links_count(dst_inode.links_count().saturating_sub(1));
+            }
+            dst_inode.set_ctime(now);
+            self.write_inode(meta, dst.inode, &dst_inode)?;
+            replaced = Some(dst.inode);
+        } else {
+            if is_dir && src_dir != dst_dir && ddir.links_count() == u16::MAX {
+                return Err(SystemError::EMLINK);
+            }
+            self.dir_add(meta, dst_dir, &mut ddir, new_name, src.inode, file_type)?;
+        }
+        if is_dir && src_dir != dst_dir {
+            ddir.set_links_count(ddir.links_count() + 1);
+        }
+        ddir.set_mtime(now);
+        ddir.set_ctime(now);
+        self.write_inode(meta, dst_dir, &ddir)?;
+
+        let mut sdir = self.read_inode(meta, src_dir)?;
+        self.dir_remove(meta, &sdir, &old_name)?;
+        if is_dir && src_dir != dst_dir {
+            self.dir_set_entry(meta, &inode, "..", dst_dir, self.dirent_type(EXT2_S_IFDIR))?;
+            sdir.set_links_count(sdir.links_count().saturating_sub(1));
+        }
+        sdir.set_mtime(now);
+        sdir.set_ctime(now);
+        self.write_inode(meta, src_dir, &sdir)?;
+
+        inode.set_ctime(now);
+        self.write_inode(meta, src.inode, &inode)?;
+        Ok(replaced)
+    }
+
+    fn exchange_locked(
+        &self,
+        meta: &Ext2Meta,
+        src_dir: u32,
+        src: &Ext2DirEntry,
+        dst_dir: u32,
+        dst: &Ext2DirEntry,
+    ) -> Result<(), SystemError> {
+        if src.inode == dst.inode {
+            return Ok(());
+        }
+        let now = now_secs();
+        let mut src_inode = self.read_inode(meta, src.inode)?;
+        let mut dst_inode = self.read_inode(meta, dst.inode)?;
+        let old_name = String::from_utf8_lossy(&src.name).into_owned();
+        let new_name = String::from_utf8_lossy(&dst.name).into_owned();
+
+        let sdir = self.read_inode(meta, src_dir)?;
+        self.dir_set_entry(
+            meta,
+            &sdir,
+            &old_name,
+            dst.inode,
+            self.dirent_type(dst_inode.mode()),
+        )?;
+        let ddir = self.read_inode(meta, dst_dir)?;
+        self.dir_set_entry(
+            meta,
+            &ddir,
+            &new_name,
+            src.inode,
+            self.dirent_type(src_inode.mode()),
+        )?;
+
+        if src_dir != dst_dir {
+            let dir_type = self.dirent_type(EXT2_S_IFDIR);
+            if src_inode.is_dir() {
+                self.dir_set_entry(meta, &src_inode, "..", dst_dir, dir_type)?;
+            }
+            if dst_inode.is_dir() {
+                self.dir_set_entry(meta, &dst_inode, "..", src_dir, dir_type)?;
+            }
+        }
+
+        for (dir_ino, gained, lost) in [
+            (src_dir, dst_inode.is_dir(), src_inode.is_dir()),
+            (dst_dir, src_inode.is_dir(), dst_inode.is_dir()),
+        ] {
+            let mut dir = self.read_inode(meta, dir_ino)?;
+            if src_dir != dst_dir && gained != lost {
+                let links = if gained {
+                    dir.links_count() + 1
+                } else {
+                    dir.links_count().saturating_sub(1)
+                };
+                dir.set_links_count(links);
+            }
+            dir.set_mtime(now);
+            dir.set_ctime(now);
+            self.write_inode(meta, dir_ino, &dir)?;
+        }
+
+        src_inode.set_ctime(now);
+        self.write_inode(meta, src.inode, &src_inode)?;
+        dst_inode.set_ctime(now);
+        self.write_inode(meta, dst.inode, &dst_inode)
+    }
+
+    /// 释放inode及其占用的块
+    fn release_inode(
+        &self,
+        meta: &mut Ext2Meta,
+        ino: u32,
+        inode: &mut Ext2DiskInode,
+    ) -> Result<(), SystemError> {
+        if self.has_data_blocks(inode) {
+            self.truncate_blocks(meta, inode, 0)?;
+        }
+        self.put_xattr_block(meta, inode)?;
+        inode.set_links_count(0);
+        inode.set_size(0);
+        inode.set_dtime(now_secs());
+        self.write_inode(meta, ino, inode)?;
+        self.free_inode(meta, ino, inode.is_dir())
+    }
+
+    /// 没有人再使用这个inode时调用，如果链接数为0就释放它
+    pub(super) fn evict(&self, ino: u32) -> Result<(), SystemError> {
+        if self.read_only {
+            return Ok(());
+        }
+        let mut meta = self.meta.lock();
+        let mut inode = self.read_inode(&meta, ino)?;
+        // dtime不为0说明已经释放过了
+        if inode.links_count() != 0 || inode.dtime() != 0 {
+            return Ok(());
+        }
+        self.release_inode(&mut meta, ino, &mut inode)
+    }
+}
+
+impl MountableFileSystem for Ext2FileSystem {
+    fn make_fs(
+        data: Option<&dyn FileSystemMakerData>,
+    ) -> Result<Arc<dyn FileSystem + 'static>, SystemError> {
+        let mount_data = data
+            .and_then(|d| d.as_any().downcast_ref::<Ext2MountData>())
+            .ok_or(SystemError::EINVAL)?;
+
+        Self::from_gendisk(mount_data.gendisk.clone())
+    }
+
+    fn make_mount_data(
+        raw_data: Option<&str>,
+        source: &str,
+    ) -> Result<Option<Arc<dyn FileSystemMakerData + 'static>>, SystemError> {
+        // 暂不支持任何挂载选项
+        MountOptions::parse("ext2", raw_data, &[])?.warn_unknown("ext2");
+        let mount_data = Ext2MountData::from_source(source).map_err(|e| {
+            log::error!(
+                "Failed to create Ext2 mount data from source '{}': {:?}",
+                source,
+                e
+            );
+            e
+        })?;
+        Ok(Some(Arc::new(mount_data)))
+    }
+
+    fn probe(gendisk: &Arc<GenDisk>) -> bool {
+        Ext2FileSystem::probe(gendisk)
+    }
+}
+
+register_mountable_fs!(Ext2FileSystem, EXT2FSMAKER, "ext2");
+
+pub struct Ext2MountData {
+    gendisk: Arc<GenDisk>,
+}
+
+impl FileSystemMakerData for Ext2MountData {
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+impl Ext2MountData {
+    fn from_source(path: &str) -> Result<Self, SystemError> {
+        let gendisk = find_source_gendisk(path)?;
+        Ok(Self { gendisk })
+    }
+}
+
+impl core::fmt::Debug for Ext2FileSystem {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ext2")
+    }
+}
@@ -0,0 +1,272 @@
+//! htree目录索引（dir_index特性）
+//!
+//! htree目录的第一个块在"."和".."之后保存索引的根，其余的索引节点伪装成只有一个空闲目录项的块，
+//! 因此不认识htree的实现仍然可以线性遍历目录。这里只实现查找，不维护索引。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/fs/ext4/hash.c
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/fs/ext4/namei.c
+
+use alloc::vec::Vec;
+use system_error::SystemError;
+
+use super::disk::{get_u16, get_u32, EXT2_DIR_ENTRY_HEADER};
+
+/// 哈希算法
+pub const DX_HASH_LEGACY: u8 = 0;
+pub const DX_HASH_HALF_MD4: u8 = 1;
+pub const DX_HASH_TEA: u8 = 2;
+pub const DX_HASH_LEGACY_UNSIGNED: u8 = 3;
+pub const DX_HASH_HALF_MD4_UNSIGNED: u8 = 4;
+pub const DX_HASH_TEA_UNSIGNED: u8 = 5;
+
+/// 32位哈希中表示目录结束的值，不能被用作文件名的哈希
+const EXT4_HTREE_EOF_32BIT: u32 = 0x7fff_ffff;
+
+/// htree的最大层数（不含叶子），LARGEDIR特性下为3
+const DX_MAX_LEVELS: u8 = 3;
+
+/// 旧版哈希算法
+fn dx_hack_hash(name: &[u8], signed: bool) -> u32 {
+    let (mut hash0, mut hash1): (u32, u32) = (0x12a3fe2d, 0x37abe8f9);
+    for &c in name {
+        let c = if signed { c as i8 as i32 } else { c as i32 };
+        let mut hash = hash1.wrapping_add(hash0 ^ (c.wrapping_mul(7152373) as u32));
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7fff_ffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// 把文件名填充到哈希算法的输入中，不足的部分用长度填充
+fn str2hashbuf(msg: &[u8], out: &mut [u32], signed: bool) {
+    let len = msg.len() as u32;
+    let mut pad = len | (len << 8);
+    pad |= pad << 16;
+
+    let num = out.len();
+    let msg = &msg[..core::cmp::min(msg.len(), num * 4)];
+    let mut filled = 0;
+    let mut val = pad;
+    for (i, &c) in msg.iter().enumerate() {
+        let c = if signed {
+            c as i8 as i32 as u32
+        } else {
+            c as u32
+        };
+        val = c.wrapping_add(val << 8);
+        if i % 4 == 3 {
+            out[filled] = val;
+            filled += 1;
+            val = pad;
+        }
+    }
+    if filled < num {
+        out[filled] = val;
+        filled += 1;
+    }
+    out[filled..].fill(pad);
+}
+
+fn tea_transform(buf: &mut [u32; 4], input: &[u32]) {
+    const DELTA: u32 = 0x9e37_79b9;
+    let mut sum: u32 = 0;
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let (a, b, c, d) = (input[0], input[1], input[2], input[3]);
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            ((b1 << 4).wrapping_add(a)) ^ (b1.wrapping_add(sum)) ^ ((b1 >> 5).wrapping_add(b)),
+        );
+        b1 = b1.wrapping_add(
+            ((b0 << 4).wrapping_add(c)) ^ (b0.wrapping_add(sum)) ^ ((b0 >> 5).wrapping_add(d)),
+        );
+    }
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32]) {
+    const K1: u32 = 0;
+    const K2: u32 = 0o13240474631;
+    const K3: u32 = 0o15666365641;
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let round = |func: &dyn Fn(u32, u32, u32) -> u32,
+                 a: &mut u32,
+                 b: u32,
+                 c: u32,
+                 d: u32,
+                 x: u32,
+                 s: u32| {
+        *a = a.wrapping_add(func(b, c, d)).wrapping_add(x).rotate_left(s);
+    };
+
+    let [mut a, mut b, mut c, mut d] = *buf;
+
+    round(&f, &mut a, b, c, d, input[0].wrapping_add(K1), 3);
+    round(&f, &mut d, a, b, c, input[1].wrapping_add(K1), 7);
+    round(&f, &mut c, d, a, b, input[2].wrapping_add(K1), 11);
+    round(&f, &mut b, c, d, a, input[3].wrapping_add(K1), 19);
+    round(&f, &mut a, b, c, d, input[4].wrapping_add(K1), 3);
+    round(&f, &mut d, a, b, c, input[5].wrapping_add(K1), 7);
+    round(&f, &mut c, d, a, b, input[6].wrapping_add(K1), 11);
+    round(&f, &mut b, c, d, a, input[7].wrapping_add(K1), 19);
+
+    round(&g, &mut a, b, c, d, input[1].wrapping_add(K2), 3);
+    round(&g, &mut d, a, b, c, input[3].wrapping_add(K2), 5);
+    round(&g, &mut c, d, a, b, input[5].wrapping_add(K2), 9);
+    round(&g, &mut b, c, d, a, input[7].wrapping_add(K2), 13);
+    round(&g, &mut a, b, c, d, input[0].wrapping_add(K2), 3);
+    round(&g, &mut d, a, b, c, input[2].wrapping_add(K2), 5);
+    round(&g, &mut c, d, a, b, input[4].wrapping_add(K2), 9);
+    round(&g, &mut b, c, d, a, input[6].wrapping_add(K2), 13);
+
+    round(&h, &mut a, b, c, d, input[3].wrapping_add(K3), 3);
+    round(&h, &mut d, a, b, c, input[7].wrapping_add(K3), 9);
+    round(&h, &mut c, d, a, b, input[2].wrapping_add(K3), 11);
+    round(&h, &mut b, c, d, a, input[6].wrapping_add(K3), 15);
+    round(&h, &mut a, b, c, d, input[1].wrapping_add(K3), 3);
+    round(&h, &mut d, a, b, c, input[5].wrapping_add(K3), 9);
+    round(&h, &mut c, d, a, b, input[0].wrapping_add(K3), 11);
+    round(&h, &mut b, c, d, a, input[4].wrapping_add(K3), 15);
+
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+/// 计算文件名的htree哈希
+///
+/// ## 参数
+/// - `name`: 文件名
+/// - `version`: 哈希算法，已经根据超级块的标志换算成有符号或无符号的版本
+/// - `seed`: 超级块中的哈希种子，全零时使用默认种子
+///
+/// ## 返回值
+/// - `Some(hash)`: 最低位为0的哈希值
+/// - `None`: 不支持的哈希算法
+pub fn dx_hash(name: &[u8], version: u8, seed: &[u32; 4]) -> Option<u32> {
+    let mut buf: [u32; 4] = if seed.iter().any(|&s| s != 0) {
+        *seed
+    } else {
+        [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476]
+    };
+
+    let hash = match version {
+        DX_HASH_LEGACY | DX_HASH_LEGACY_UNSIGNED => dx_hack_hash(name, version == DX_HASH_LEGACY),
+        DX_HASH_HALF_MD4 | DX_HASH_HALF_MD4_UNSIGNED => {
+            let signed = version == DX_HASH_HALF_MD4;
+            let mut input = [0u32; 8];
+            let mut rest = name;
+            loop {
+                str2hashbuf(rest, &mut input, signed);
+                half_md4_transform(&mut buf, &input);
+                if rest.len() <= 32 {
+                    break;
+                }
+                rest = &rest[32..];
+            }
+            buf[1]
+        }
+        DX_HASH_TEA | DX_HASH_TEA_UNSIGNED => {
+            let signed = version == DX_HASH_TEA;
+            let mut input = [0u32; 4];
+            let mut rest = name;
+            loop {
+                str2hashbuf(rest, &mut input, signed);
+                tea_transform(&mut buf, &input);
+                if rest.len() <= 16 {
+                    break;
+                }
+                rest = &rest[16..];
+            }
+            buf[0]
+        }
+        _ => return None,
+    };
+
+    let hash = hash & !1;
+    if hash == EXT4_HTREE_EOF_32BIT << 1 {
+        return Some((EXT4_HTREE_EOF_32BIT - 1) << 1);
+    }
+    Some(hash)
+}
+
+/// 索引项：哈希值不小于`hash`的目录项保存在逻辑块`block`中
+#[derive(Debug, Clone, Copy)]
+pub struct DxEntry {
+    pub hash: u32,
+    pub block: u32,
+}
+
+/// 索引的根
+#[derive(Debug, Clone)]
+pub struct DxRoot {
+    pub hash_version: u8,
+    /// 根和叶子之间索引节点的层数
+    pub indirect_levels: u8,
+    pub entries: Vec<DxEntry>,
+}
+
+impl DxRoot {
+    /// "."和".."两个目录项之后是dx_root_info：
+    /// reserved_zero(4字节)、hash_version、info_length、indirect_levels、unused_flags
+    const INFO_OFFSET: usize = 24;
+
+    /// 从目录的第一个块解析索引的根
+    pub fn parse(block: &[u8]) -> Result<Self, SystemError> {
+        let off = Self::INFO_OFFSET;
+        if block.len() < off + 8
+            || get_u16(block, 4) != 12
+            || get_u32(block, off) != 0
+            || block[off + 6] >= DX_MAX_LEVELS
+        {
+            log::warn!("ext2: corrupted htree root");
+            return Err(SystemError::EIO);
+        }
+        Ok(Self {
+            hash_version: block[off + 4],
+            indirect_levels: block[off + 6],
+            // info_length从dx_root_info的开头算起
+            entries: parse_entries(block, off + block[off + 5] as usize)?,
+        })
+    }
+}
+
+/// 解析索引节点中的索引项，节点开头是一个覆盖整个块的空闲目录项
+pub fn dx_node_entries(block: &[u8]) -> Result<Vec<DxEntry>, SystemError> {
+    parse_entries(block, EXT2_DIR_ENTRY_HEADER)
+}
+
+/// 解析从`off`开始的索引项。第一项的哈希位置保存的是limit和count，它的哈希视为0
+fn parse_entries(block: &[u8], off: usize) -> Result<Vec<DxEntry>, SystemError> {
+    if off + 8 > block.len() {
+        return Err(SystemError::EIO);
+    }
+    let limit = get_u16(block, off) as usize;
+    let count = get_u16(block, off + 2) as usize;
+    if count == 0 || count > limit || off + count * 8 > block.len() {
+        log::warn!("ext2: corrupted htree node");
+        return Err(SystemError::EIO);
+    }
+    Ok((0..count)
+        .map(|i| {
+            let e = off + i * 8;
+            DxEntry {
+                hash: if i == 0 { 0 } else { get_u32(block, e) },
+                // 高4位保留
+                block: get_u32(block, e + 4) & 0x0fff_ffff,
+            }
+        })
+        .collect())
+}
+
+/// 查找哈希值`hash`应该位于的索引项：最后一个哈希不大于`hash`的项
+pub fn dx_search(entries: &[DxEntry], hash: u32) -> usize {
+    entries[1..].partition_point(|e| e.hash <= hash)
+}
@@ -0,0 +1,653 @@
+use crate::{
+    arch::MMArch,
+    driver::base::{block::block_device::LBA_SIZE, device::device_number::DeviceNumber},
+    filesystem::{
+        page_cache::{AsyncPageCacheBackend, PageCache},
+        vfs::{
+            self, syscall::RenameFlags, utils::DName, vcore::generate_inode_id, IndexNode,
+            InodeFlags, InodeId, InodeMode, SpecialNodeData,
+        },
+    },
+    ipc::pipe::LockedPipeInode,
+    libs::{
+        casting::DowncastArc,
+        mutex::{Mutex, MutexGuard},
+    },
+    mm::{truncate::truncate_inode_pages, MemoryManagementArch},
+    time::PosixTimeSpec,
+};
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::fmt::Debug;
+use system_error::SystemError;
+
+use super::{disk::*, fs::Ext2FileSystem};
+
+type PrivateData<'a> = MutexGuard<'a, vfs::FilePrivateData>;
+
+pub struct Ext2Inode {
+    /// ext2中的inode号
+    pub(super) ino: u32,
+    pub(super) fs_ptr: Weak<Ext2FileSystem>,
+    pub(super) page_cache: Option<Arc<PageCache>>,
+    pub(super) children: BTreeMap<DName, Arc<LockedExt2Inode>>,
+    pub(super) dname: DName,
+
+    // 对应vfs的inode id，用于标识系统中唯一的inode
+    pub(super) vfs_inode_id: InodeId,
+
+    // 指向父级IndexNode的Weak指针
+    pub(super) parent: Weak<LockedExt2Inode>,
+
+    // 指向自身的Weak指针，用于获取Arc<Self>
+    pub(super) self_ref: Weak<LockedExt2Inode>,
+
+    // 特殊节点数据（用于 FIFO 的 pipe inode）
+    pub(super) special_node: Option<SpecialNodeData>,
+}
+
+#[derive(Debug)]
+pub struct LockedExt2Inode(pub(super) Mutex<Ext2Inode>);
+
+impl IndexNode for LockedExt2Inode {
+    fn mmap(&self, _start: usize, _len: usize, _offset: usize) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn open(&self, _data: PrivateData, _mode: &vfs::file::FileFlags) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn close(&self, _data: PrivateData) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn create(
+        &self,
+        name: &str,
+        file_type: vfs::FileType,
+        mode: InodeMode,
+    ) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let mode = InodeMode::from(file_type).union(mode);
+        self.do_create(name, |fs, ino| {
+            fs.create(ino, name, mode.bits() as u16, DeviceNumber::default())
+        })
+    }
+
+    fn create_with_data(
+        &self,
+        name: &str,
+        file_type: vfs::FileType,
+        mode: InodeMode,
+        data: usize,
+    ) -> Result<Arc<dyn IndexNode>, SystemError> {
+        if data == 0 {
+            return self.create(name, file_type, mode);
+        }
+
+        Err(SystemError::ENOSYS)
+    }
+
+    fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
+        self.do_create(name, |fs, ino| fs.symlink(ino, name, target))
+    }
+
+    fn mknod(
+        &self,
+        filename: &str,
+        mode: InodeMode,
+        dev_t: DeviceNumber,
+    ) -> Result<Arc<dyn IndexNode>, SystemError> {
+        // 没有指定文件类型时创建普通文件
+        let mode = if (mode & InodeMode::S_IFMT).is_empty() {
+            mode | InodeMode::S_IFREG
+        } else {
+            mode
+        };
+        self.do_create(filename, |fs, ino| {
+            fs.create(ino, filename, mode.bits() as u16, dev_t)
+        })
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: PrivateData,
+    ) -> Result<usize, SystemError> {
+        let len = core::cmp::min(len, buf.len());
+        let buf = &mut buf[0..len];
+
+        // 不能在持有inode锁的时候调用PageCache::{read,write}，它们会调用metadata()
+        let (fs, ino, page_cache) = {
+            let guard = self.0.lock();
+            (guard.concret_fs(), guard.ino, guard.page_cache.clone())
+        };
+
+        if let Some(page_cache) = page_cache {
+            let now = PosixTimeSpec::now().tv_sec.clamp(0, u32::MAX as i64) as u32;
+            match fs.setattr(ino, |inode| inode.set_atime(now)) {
+                Ok(()) => {}
+                // 只读挂载下，atime 更新失败不应影响读取语义。
+                Err(SystemError::EROFS) => {}
+                Err(e) => return Err(e),
+            }
+            page_cache.read(offset, buf)
+        } else {
+            self.read_direct(offset, len, buf)
+        }
+    }
+
+    fn read_sync(&self, offset: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let (fs, ino) = {
+            let guard = self.0.lock();
+            (guard.concret_fs(), guard.ino)
+        };
+        fs.read(ino, offset, buf)
+    }
+
+    fn read_direct(&self, offset: usize, len: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let len = core::cmp::min(len, buf.len());
+        self.read_sync(offset, &mut buf[0..len])
+    }
+
+    fn write_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &[u8],
+        _data: PrivateData,
+    ) -> Result<usize, SystemError> {
+        let len = core::cmp::min(len, buf.len());
+        let buf = &buf[0..len];
+
+        let (fs, ino, page_cache) = {
+            let guard = self.0.lock();
+            (guard.concret_fs(), guard.ino, guard.page_cache.clone())
+        };
+
+        if let Some(page_cache) = page_cache {
+            let write_len = PageCache::write(&page_cache, offset, buf)?;
+            fs.write_extend(ino, (offset + write_len) as u64)?;
+            Ok(write_len)
+        } else {
+            self.write_direct(offset, len, buf)
+        }
+    }
+
+    fn write_sync(&self, offset: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        let (fs, ino) = {
+            let guard = self.0.lock();
+            (guard.concret_fs(), guard.ino)
+        };
+        fs.write(ino, offset, buf)
+    }
+
+    fn write_direct(&self, offset: usize, len: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        let len = core::cmp::min(len, buf.len());
+        self.write_sync(offset, &buf[0..len])
+    }
+
+    fn direct_io_alignment(&self) -> Option<usize> {
+        Some(LBA_SIZE)
+    }
+
+    fn fs(&self) -> Arc<dyn vfs::FileSystem> {
+        self.0.lock().concret_fs()
+    }
+
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let mut guard = self.0.lock();
+        match name {
+            "" | "." => {
+                return Ok(guard.self_ref.upgrade().ok_or(SystemError::ENOENT)?);
+            }
+            ".." => {
+                return Ok(guard.parent.upgrade().ok_or(SystemError::ENOENT)?);
+            }
+            _ => {}
+        }
+
+        let dname = DName::from(name);
+        if let Some(child) = guard.children.get(&dname) {
+            return Ok(child.clone() as Arc<dyn IndexNode>);
+        }
+        let ino = guard.concret_fs().lookup(guard.ino, name)?;
+        let self_arc = guard.self_ref.upgrade().ok_or(SystemError::ENOENT)?;
+        let inode = LockedExt2Inode::new(
+            ino,
+            guard.fs_ptr.clone(),
+            dname.clone(),
+            Some(Arc::downgrade(&self_arc)),
+        );
+        guard.children.insert(dname, inode.clone());
+        Ok(inode)
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let guard = self.0.lock();
+        if let Some(parent) = guard.parent.upgrade() {
+            return Ok(parent);
+        }
+        Err(SystemError::ENOENT)
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        let guard = self.0.lock();
+        guard.concret_fs().list(guard.ino)
+    }
+
+    fn link(&self, name: &str, other: &Arc<dyn IndexNode>) -> Result<(), SystemError> {
+        let other_arc = other
+            .clone()
+            .downcast_arc::<LockedExt2Inode>()
+            .ok_or(SystemError::EXDEV)?;
+        let other_ino = other_arc.0.lock().ino;
+
+        let mut guard = self.0.lock();
+        guard.concret_fs().link(guard.ino, name, other_ino)?;
+        guard.children.insert(DName::from(name), other_arc);
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), SystemError> {
+        self.do_remove(name, false)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<(), SystemError> {
+        self.do_remove(name, true)
+    }
+
+    fn metadata(&self) -> Result<vfs::Metadata, SystemError> {
+        let (fs, ino, vfs_inode_id) = {
+            let guard = self.0.lock();
+            (guard.concret_fs(), guard.ino, guard.vfs_inode_id)
+        };
+        let inode = fs.inode_attr(ino)?;
+
+        // raw_dev: device node's rdev (st_rdev), only for char/block devices
+        let raw_dev = match inode.file_type() {
+            EXT2_S_IFCHR | EXT2_S_IFBLK => {
+                // 旧格式的设备号保存在i_block[0]，新格式的保存在i_block[1]
+                let dev = if inode.block(0) != 0 {
+                    inode.block(0)
+                } else {
+                    inode.block(1)
+                };
+                DeviceNumber::from_linux_dev_t(dev)
+            }
+            _ => DeviceNumber::default(),
+        };
+
+        Ok(vfs::Metadata {
+            inode_id: vfs_inode_id,
+            size: inode.size() as i64,
+            blk_size: fs.block_size,
+            blocks: inode.sectors(fs.huge_file, fs.block_size) as usize,
+            atime: PosixTimeSpec::new(inode.atime().into(), 0),
+            // ext2没有创建时间
+            btime: PosixTimeSpec::new(inode.ctime().into(), 0),
+            mtime: PosixTimeSpec::new(inode.mtime().into(), 0),
+            ctime: PosixTimeSpec::new(inode.ctime().into(), 0),
+            file_type: Self::file_type(inode.file_type()),
+            mode: InodeMode::from_bits_truncate((inode.mode() & !EXT2_S_IFMT) as u32),
+            flags: InodeFlags::empty(),
+            nlinks: inode.links_count() as usize,
+            uid: inode.uid() as usize,
+            gid: inode.gid() as usize,
+            dev_id: fs.raw_dev.data() as usize,
+            raw_dev,
+        })
+    }
+
+    fn set_metadata(&self, metadata: &vfs::Metadata) -> Result<(), SystemError> {
+        let (fs, ino) = {
+            let guard = self.0.lock();
+            (guard.concret_fs(), guard.ino)
+        };
+        if metadata.file_type == vfs::FileType::File
+            && fs.inode_attr(ino)?.size() != metadata.size as u64
+        {
+            self.resize(metadata.size as usize)?;
+        }
+
+        let to_ext2_time =
+            |time: &PosixTimeSpec| -> u32 { time.tv_sec.clamp(0, u32::MAX as i64) as u32 };
+        fs.setattr(ino, |inode| {
+            let mode = (inode.mode() & EXT2_S_IFMT) | (metadata.mode.bits() as u16 & !EXT2_S_IFMT);
+            inode.set_mode(mode);
+            inode.set_uid(metadata.uid as u32);
+            inode.set_gid(metadata.gid as u32);
+            inode.set_atime(to_ext2_time(&metadata.atime));
+            inode.set_mtime(to_ext2_time(&metadata.mtime));
+            inode.set_ctime(to_ext2_time(&metadata.ctime));
+        })
+    }
+
+    fn resize(&self, len: usize) -> Result<(), SystemError> {
+        let (fs, ino, page_cache) = {
+            let guard = self.0.lock();
+            (guard.concret_fs(), guard.ino, guard.page_cache.clone())
+        };
+        // 先清除被截断区间的缓存页，避免脏页在截断之后被写回
+        if let Some(page_cache) = page_cache {
+            let start_page = (len + MMArch::PAGE_SIZE - 1) >> MMArch::PAGE_SHIFT;
+            truncate_inode_pages(page_cache.clone(), start_page);
+            page_cache.manager().resize(len)?;
+        }
+        fs.truncate(ino, len)
+    }
+
+    fn truncate(&self, len: usize) -> Result<(), SystemError> {
+        self.resize(len)
+    }
+
+    fn page_cache(&self) -> Option<Arc<PageCache>> {
+        self.0.lock().page_cache.clone()
+    }
+
+    fn dname(&self) -> Result<DName, SystemError> {
+        Ok(self.0.lock().dname.clone())
+    }
+
+    fn special_node(&self) -> Option<SpecialNodeData> {
+        self.0.lock().special_node.clone()
+    }
+
+    fn move_to(
+        &self,
+        old_name: &str,
+        target: &Arc<dyn IndexNode>,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> Result<(), SystemError> {
+        let target_locked = target
+            .clone()
+            .downcast_arc::<LockedExt2Inode>()
+            .ok_or(SystemError::EXDEV)?;
+
+        let (fs, src_ino) = {
+            let guard = self.0.lock();
+            (guard.concret_fs(), guard.ino)
+        };
+        let dst_ino = target_locked.0.lock().ino;
+
+        let old_dname = DName::from(old_name);
+        let new_dname = DName::from(new_name);
+        if src_ino == dst_ino && old_dname == new_dname {
+            return Ok(());
+        }
+
+        let replaced = fs.rename(src_ino, old_name, dst_ino, new_name, flags)?;
+        if flags.contains(RenameFlags::EXCHANGE) {
+            self.update_exchange_cache(&target_locked, src_ino, dst_ino, &old_dname, &new_dname);
+            return Ok(());
+        }
+
+        let replaced_child =
+            self.update_rename_cache(&target_locked, src_ino, dst_ino, &old_dname, &new_dname);
+        if let Some(replaced) = replaced {
+            Self::release_child(&fs, replaced, replaced_child);
+        }
+        Ok(())
+    }
+
+    fn getxattr(&self, name: &str, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let guard = self.0.lock();
+        guard.concret_fs().getxattr(guard.ino, name, buf)
+    }
+
+    fn setxattr(&self, name: &str, value: &[u8]) -> Result<usize, SystemError> {
+        let guard = self.0.lock();
+        guard.concret_fs().setxattr(guard.ino, name, value)?;
+        Ok(0)
+    }
+
+    fn listxattr(&self) -> Result<Vec<String>, SystemError> {
+        let guard = self.0.lock();
+        guard.concret_fs().listxattr(guard.ino)
+    }
+
+    fn removexattr(&self, name: &str) -> Result<(), SystemError> {
+        let guard = self.0.lock();
+        guard.concret_fs().removexattr(guard.ino, name)
+    }
+}
+
+impl LockedExt2Inode {
+    pub fn new(
+        ino: u32,
+        fs_ptr: Weak<Ext2FileSystem>,
+        dname: DName,
+        parent: Option<Weak<LockedExt2Inode>>,
+    ) -> Arc<Self> {
+        let inode = Arc::new(LockedExt2Inode(Mutex::new(Ext2Inode {
+            ino,
+            fs_ptr: fs_ptr.clone(),
+            page_cache: None,
+            children: BTreeMap::new(),
+            dname,
+            vfs_inode_id: generate_inode_id(),
+            parent: parent.unwrap_or_default(),
+            self_ref: Weak::new(),
+            special_node: None,
+        })));
+        let mut guard = inode.0.lock();
+        guard.self_ref = Arc::downgrade(&inode);
+
+        let backend = Arc::new(AsyncPageCacheBackend::new(
+            Arc::downgrade(&inode) as Weak<dyn IndexNode>
+        ));
+        guard.page_cache = Some(PageCache::new(
+            Some(Arc::downgrade(&inode) as Weak<dyn IndexNode>),
+            Some(backend),
+        ));
+
+        // 对于 FIFO，创建 pipe inode
+        if let Some(fs) = fs_ptr.upgrade() {
+            if let Ok(disk_inode) = fs.inode_attr(ino) {
+                if disk_inode.file_type() == EXT2_S_IFIFO {
+                    let pipe_inode = LockedPipeInode::new();
+                    pipe_inode.set_fifo();
+                    guard.special_node = Some(SpecialNodeData::Pipe(pipe_inode));
+                }
+            }
+        }
+
+        drop(guard);
+        inode
+    }
+
+    /// 在当前目录下创建inode，并加入children缓存
+    ///
+    /// ## 参数
+    /// - `f`: 在文件系统中创建inode，返回新inode的编号
+    fn do_create(
+        &self,
+        name: &str,
+        f: impl FnOnce(&Ext2FileSystem, u32) -> Result<u32, SystemError>,
+    ) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let mut guard = self.0.lock();
+        let ino = f(&guard.concret_fs(), guard.ino)?;
+        let dname = DName::from(name);
+        let self_arc = guard.self_ref.upgrade().ok_or(SystemError::ENOENT)?;
+        let inode = LockedExt2Inode::new(
+            ino,
+            guard.fs_ptr.clone(),
+            dname.clone(),
+            Some(Arc::downgrade(&self_arc)),
+        );
+        guard.children.insert(dname, inode.clone());
+        Ok(inode as Arc<dyn IndexNode>)
+    }
+
+    fn do_remove(&self, name: &str, rmdir: bool) -> Result<(), SystemError> {
+        let mut guard = self.0.lock();
+        let fs = guard.concret_fs();
+        let ino = fs.unlink(guard.ino, name, rmdir)?;
+        let child = guard.children.remove(&DName::from(name));
+        drop(guard);
+        Self::release_child(&fs, ino, child);
+        Ok(())
+    }
+
+    /// 目录项被删除之后，释放不再被引用的inode
+    ///
+    /// 如果inode还有缓存的IndexNode，就等到它被drop的时候（文件关闭之后）再释放
+    fn release_child(fs: &Arc<Ext2FileSystem>, ino: u32, child: Option<Arc<LockedExt2Inode>>) {
+        match child {
+            Some(child) => drop(child),
+            None => {
+                if let Err(e) = fs.evict(ino) {
+                    log::warn!("ext2: failed to evict inode {}: {:?}", ino, e);
+                }
+            }
+        }
+    }
+
+    /// 更新 rename 后的缓存
+    ///
+    /// ## 返回值
+    /// - 被替换的目标的缓存
+    fn update_rename_cache(
+        &self,
+        target: &Arc<LockedExt2Inode>,
+        src_dir: u32,
+        dst_dir: u32,
+        old_dname: &DName,
+        new_dname: &DName,
+    ) -> Option<Arc<LockedExt2Inode>> {
+        if src_dir == dst_dir {
+            let mut guard = self.0.lock();
+            let replaced = guard.children.remove(new_dname);
+            if let Some(child) = guard.children.remove(old_dname) {
+                child.0.lock().dname = new_dname.clone();
+                guard.children.insert(new_dname.clone(), child);
+            }
+            return replaced;
+        }
+
+        let (mut src_guard, mut dst_guard) = if src_dir < dst_dir {
+            (self.0.lock(), target.0.lock())
+        } else {
+            let d = target.0.lock();
+            let s = self.0.lock();
+            (s, d)
+        };
+        let replaced = dst_guard.children.remove(new_dname);
+        if let Some(child) = src_guard.children.remove(old_dname) {
+            dst_guard.children.insert(new_dname.clone(), child.clone());
+            drop(src_guard);
+            drop(dst_guard);
+            let mut child_guard = child.0.lock();
+            child_guard.dname = new_dname.clone();
+            child_guard.parent = Arc::downgrade(target);
+        }
+        replaced
+    }
+
+    /// 更新 exchange 后的缓存：交换两个条目
+    fn update_exchange_cache(
+        &self,
+        target: &Arc<LockedExt2Inode>,
+        src_dir: u32,
+        dst_dir: u32,
+        old_dname: &DName,
+        new_dname: &DName,
+    ) {
+        if src_dir == dst_dir {
+            let mut guard = self.0.lock();
+            let old_child = guard.children.remove(old_dname);
+            let new_child = guard.children.remove(new_dname);
+            if let Some(child) = old_child {
+                child.0.lock().dname = new_dname.clone();
+                guard.children.insert(new_dname.clone(), child);
+            }
+            if let Some(child) = new_child {
+                child.0.lock().dname = old_dname.clone();
+                guard.children.insert(old_dname.clone(), child);
+            }
+            return;
+        }
+
+        let (mut src_guard, mut dst_guard) = if src_dir < dst_dir {
+            (self.0.lock(), target.0.lock())
+        } else {
+            let d = target.0.lock();
+            let s = self.0.lock();
+            (s, d)
+        };
+        let old_child = src_guard.children.remove(old_dname);
+        let new_child = dst_guard.children.remove(new_dname);
+        if let Some(child) = &old_child {
+            dst_guard.children.insert(new_dname.clone(), child.clone());
+        }
+        if let Some(child) = &new_child {
+            src_guard.children.insert(old_dname.clone(), child.clone());
+        }
+        let self_ref = src_guard.self_ref.clone();
+        drop(src_guard);
+        drop(dst_guard);
+
+        if let Some(child) = old_child {
+            let mut child_guard = child.0.lock();
+            child_guard.dname = new_dname.clone();
+            child_guard.parent = Arc::downgrade(target);
+        }
+        if let Some(child) = new_child {
+            let mut child_guard = child.0.lock();
+            child_guard.dname = old_dname.clone();
+            child_guard.parent = self_ref;
+        }
+    }
+
+    fn file_type(ftype: u16) -> vfs::FileType {
+        match ftype {
+            EXT2_S_IFREG => vfs::FileType::File,
+            EXT2_S_IFDIR => vfs::FileType::Dir,
+            EXT2_S_IFCHR => vfs::FileType::CharDevice,
+            EXT2_S_IFBLK => vfs::FileType::BlockDevice,
+            EXT2_S_IFIFO => vfs::FileType::Pipe,
+            EXT2_S_IFSOCK => vfs::FileType::Socket,
+            EXT2_S_IFLNK => vfs::FileType::SymLink,
+            _ => {
+                log::warn!("Unknown file type, going to treat it as a file");
+                vfs::FileType::File
+            }
+        }
+    }
+}
+
+impl Ext2Inode {
+    fn concret_fs(&self) -> Arc<Ext2FileSystem> {
+        self.fs_ptr
+            .upgrade()
+            .expect("Ext2FileSystem should be alive")
+    }
+}
+
+impl Drop for Ext2Inode {
+    fn drop(&mut self) {
+        // 链接数为0的inode在最后一个引用消失时才真正释放
+        if let Some(fs) = self.fs_ptr.upgrade() {
+            if let Err(e) = fs.evict(self.ino) {
+                log::warn!("ext2: failed to evict inode {}: {:?}", self.ino, e);
+            }
+        }
+    }
+}
+
+impl Debug for Ext2Inode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Ext2Inode")
+    }
+}
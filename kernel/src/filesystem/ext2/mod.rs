@@ -0,0 +1,7 @@
+// ext2文件系统：块和inode位图、直接/间接块映射、线性目录项
+// 同一套代码可以只读挂载ext3/ext4：extent树、64位块号、flex_bg、htree索引
+pub mod disk;
+pub mod fs;
+pub mod htree;
+pub mod inode;
+pub mod xattr;
@@ -0,0 +1,233 @@
+//! 扩展属性块
+//!
+//! inode的i_file_acl指向一个保存扩展属性的块，内容相同的属性块可以被多个inode共享（h_refcount）。
+//! 块以32字节的头部开始，之后是按(命名空间编号, 名字长度, 名字)排序的属性项，以4个0字节结束；
+//! 属性值从块的末尾向前存放，每项都按4字节对齐。
+//!
+//! 这里不合并内容相同的属性块，修改共享的属性块时复制一份。属性值保存在单独inode中的
+//! 属性项（EA_INODE特性）会被忽略，带有这个特性的文件系统只能只读挂载。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/fs/ext2/xattr.c
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use super::disk::{get_u16, get_u32, put_u16, put_u32};
+
+/// 属性块的魔数
+pub const EXT2_XATTR_MAGIC: u32 = 0xea02_0000;
+/// 属性块头部的大小
+const XATTR_HEADER_SIZE: usize = 32;
+/// 属性项头部（不含名字）的大小
+const XATTR_ENTRY_HEADER: usize = 16;
+/// 头部中h_refcount的偏移
+pub const EXT2_XATTR_H_REFCOUNT: usize = 4;
+
+/// 命名空间在磁盘上的编号和对应的名字前缀
+const XATTR_PREFIXES: [(u8, &str); 3] = [(1, "user."), (4, "trusted."), (6, "security.")];
+
+#[inline]
+fn pad4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// 属性项
+#[derive(Debug, Clone)]
+pub struct Ext2XattrEntry {
+    /// 命名空间编号
+    pub index: u8,
+    /// 去掉命名空间前缀之后的名字
+    pub name: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+impl Ext2XattrEntry {
+    /// 带命名空间前缀的完整名字，不认识的命名空间返回None
+    fn full_name(&self) -> Option<String> {
+        let (_, prefix) = XATTR_PREFIXES.iter().find(|(i, _)| *i == self.index)?;
+        let name = core::str::from_utf8(&self.name).ok()?;
+        Some(prefix.to_string() + name)
+    }
+
+    fn hash(&self) -> u32 {
+        let mut hash: u32 = 0;
+        for &c in &self.name {
+            hash = (hash << 5) ^ (hash >> 27) ^ c as u32;
+        }
+        let mut value = self.value.clone();
+        value.resize(pad4(value.len()), 0);
+        for word in value.chunks_exact(4) {
+            hash = (hash << 16) ^ (hash >> 16) ^ get_u32(word, 0);
+        }
+        hash
+    }
+}
+
+/// 一个属性块中的全部属性
+#[derive(Debug, Clone, Default)]
+pub struct Ext2XattrBlock {
+    /// 共享这个属性块的inode数
+    pub refcount: u32,
+    entries: Vec<Ext2XattrEntry>,
+}
+
+impl Ext2XattrBlock {
+    /// 把完整的属性名拆分为命名空间编号和名字
+    fn split_name(name: &str) -> Result<(u8, &[u8]), SystemError> {
+        XATTR_PREFIXES
+            .iter()
+            .find(|(_, prefix)| name.starts_with(prefix))
+            .map(|(index, prefix)| (*index, name[prefix.len()..].as_bytes()))
+            .ok_or(SystemError::EOPNOTSUPP_OR_ENOTSUP)
+    }
+
+    /// 解析属性块
+    pub fn parse(block: &[u8]) -> Result<Self, SystemError> {
+        let magic = get_u32(block, 0);
+        // h_blocks必须为1
+        if magic != EXT2_XATTR_MAGIC || get_u32(block, 8) != 1 {
+            log::warn!("ext2: bad xattr block magic {:#x}", magic);
+            return Err(SystemError::EIO);
+        }
+        let refcount = get_u32(block, EXT2_XATTR_H_REFCOUNT);
+
+        let mut entries = Vec::new();
+        let mut off = XATTR_HEADER_SIZE;
+        loop {
+            if off + 4 > block.len() {
+                log::warn!("ext2: xattr entries run past the block");
+                return Err(SystemError::EIO);
+            }
+            if get_u32(block, off) == 0 {
+                break;
+            }
+            if off + XATTR_ENTRY_HEADER > block.len() {
+                log::warn!("ext2: xattr entries run past the block");
+                return Err(SystemError::EIO);
+            }
+            let name_len = block[off] as usize;
+            let index = block[off + 1];
+            let value_offs = get_u16(block, off + 2) as usize;
+            let value_inum = get_u32(block, off + 4);
+            let value_size = get_u32(block, off + 8) as usize;
+            let name_end = off + XATTR_ENTRY_HEADER + name_len;
+            if name_end > block.len() || value_offs + value_size > block.len() {
+                log::warn!("ext2: corrupted xattr entry at offset {}", off);
+                return Err(SystemError::EIO);
+            }
+            if value_inum == 0 {
+                entries.push(Ext2XattrEntry {
+                    index,
+                    name: block[off + XATTR_ENTRY_HEADER..name_end].to_vec(),
+                    value: block[value_offs..value_offs + value_size].to_vec(),
+                });
+            }
+            off += pad4(XATTR_ENTRY_HEADER + name_len);
+        }
+        Ok(Self { refcount, entries })
+    }
+
+    /// 生成属性块的内容，h_refcount为1
+    ///
+    /// ## 返回值
+    /// - `Err(ENOSPC)`: 属性放不下一个块
+    pub fn to_bytes(&self, block_size: usize) -> Result<Vec<u8>, SystemError> {
+        let entries_len: usize = self
+            .entries
+            .iter()
+            .map(|e| pad4(XATTR_ENTRY_HEADER + e.name.len()))
+            .sum();
+        let values_len: usize = self.entries.iter().map(|e| pad4(e.value.len())).sum();
+        if XATTR_HEADER_SIZE + entries_len + 4 + values_len > block_size {
+            return Err(SystemError::ENOSPC);
+        }
+
+        let mut block = vec![0u8; block_size];
+        put_u32(&mut block, 0, EXT2_XATTR_MAGIC);
+        put_u32(&mut block, EXT2_XATTR_H_REFCOUNT, 1);
+        put_u32(&mut block, 8, 1);
+
+        let mut off = XATTR_HEADER_SIZE;
+        let mut value_end = block_size;
+        let mut block_hash: u32 = 0;
+        for e in &self.entries {
+            let value_offs = if e.value.is_empty() {
+                0
+            } else {
+                value_end -= pad4(e.value.len());
+                block[value_end..value_end + e.value.len()].copy_from_slice(&e.value);
+                value_end
+            };
+            let hash = e.hash();
+            block[off] = e.name.len() as u8;
+            block[off + 1] = e.index;
+            put_u16(&mut block, off + 2, value_offs as u16);
+            put_u32(&mut block, off + 8, e.value.len() as u32);
+            put_u32(&mut block, off + 12, hash);
+            block[off + XATTR_ENTRY_HEADER..off + XATTR_ENTRY_HEADER + e.name.len()]
+                .copy_from_slice(&e.name);
+            off += pad4(XATTR_ENTRY_HEADER + e.name.len());
+            block_hash = (block_hash << 16) ^ (block_hash >> 16) ^ hash;
+        }
+        // 任何一项的哈希为0时，块的哈希也为0
+        if self.entries.iter().any(|e| e.hash() == 0) {
+            block_hash = 0;
+        }
+        put_u32(&mut block, 12, block_hash);
+        Ok(block)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&[u8], SystemError> {
+        let (index, name) = Self::split_name(name)?;
+        self.entries
+            .iter()
+            .find(|e| e.index == index && e.name == name)
+            .map(|e| e.value.as_slice())
+            .ok_or(SystemError::ENODATA)
+    }
+
+    pub fn set(&mut self, name: &str, value: &[u8]) -> Result<(), SystemError> {
+        let (index, name) = Self::split_name(name)?;
+        if let Some(e) = self
+            .entries
+            .iter_mut()
+            .find(|e| e.index == index && e.name == name)
+        {
+            e.value = value.to_vec();
+            return Ok(());
+        }
+        self.entries.push(Ext2XattrEntry {
+            index,
+            name: name.to_vec(),
+            value: value.to_vec(),
+        });
+        self.entries.sort_by(|a, b| {
+            (a.index, a.name.len(), &a.name).cmp(&(b.index, b.name.len(), &b.name))
+        });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), SystemError> {
+        let (index, name) = Self::split_name(name)?;
+        let pos = self
+            .entries
+            .iter()
+            .position(|e| e.index == index && e.name == name)
+            .ok_or(SystemError::ENODATA)?;
+        self.entries.remove(pos);
+        Ok(())
+    }
+
+    /// 所有命名空间可以识别的属性名
+    pub fn names(&self) -> Vec<String> {
+        self.entries.iter().filter_map(|e| e.full_name()).collect()
+    }
+}
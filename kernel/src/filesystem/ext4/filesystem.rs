@@ -1,17 +1,15 @@
-use crate::driver::base::block::gendisk::GenDisk;
+use crate::driver::base::block::gendisk::{GenDisk, GenDiskClaim};
 use crate::driver::base::device::device_number::DeviceNumber;
 use crate::filesystem::ext4::inode::Ext4Inode;
-use crate::filesystem::vfs::fcntl::AtFlags;
-use crate::filesystem::vfs::utils::{user_path_at, DName};
-use crate::filesystem::vfs::vcore::{generate_inode_id, try_find_gendisk};
+use crate::filesystem::vfs::mount_options::MountOptions;
+use crate::filesystem::vfs::utils::DName;
+use crate::filesystem::vfs::vcore::{find_source_gendisk, generate_inode_id};
 use crate::filesystem::vfs::{
     self, FileSystem, FileSystemMakerData, IndexNode, Magic, MountableFileSystem, FSMAKER,
-    VFS_MAX_FOLLOW_SYMLINK_TIMES,
 };
 use crate::libs::mutex::Mutex;
 use crate::mm::fault::{PageFaultHandler, PageFaultMessage};
 use crate::mm::VmFaultReason;
-use crate::process::ProcessManager;
 use crate::register_mountable_fs;
 use alloc::{
     collections::BTreeMap,
@@ -28,6 +26,8 @@ pub struct Ext4FileSystem {
     pub(super) fs: another_ext4::Ext4,
     /// 当前文件系统对应的设备号
     pub(super) raw_dev: DeviceNumber,
+    /// 文件系统存在期间保持块设备处于打开状态
+    claim: GenDiskClaim,
 
     /// 根 inode
     root_inode: Arc<LockedExt4Inode>,
@@ -50,6 +50,15 @@ impl FileSystem for Ext4FileSystem {
         "ext4"
     }
 
+    fn dcache_enabled(&self) -> bool {
+        true
+    }
+
+    fn time_granularity(&self) -> u32 {
+        // 磁盘上的时间戳只保存到秒
+        1_000_000_000
+    }
+
     fn super_block(&self) -> vfs::SuperBlock {
         vfs::SuperBlock::new(Magic::EXT4_MAGIC, another_ext4::BLOCK_SIZE as u64, 255)
     }
@@ -70,6 +79,13 @@ impl FileSystem for Ext4FileSystem {
     ) -> VmFaultReason {
         PageFaultHandler::filemap_map_pages(pfm, start_pgoff, end_pgoff)
     }
+
+    fn on_umount(&self) {
+        // 把块缓存中的脏数据写回磁盘
+        if let Err(e) = self.claim.disk().flush() {
+            log::warn!("ext4: failed to flush the disk on umount: {:?}", e);
+        }
+    }
 }
 
 impl Ext4FileSystem {
@@ -120,6 +136,7 @@ impl Ext4FileSystem {
         let fs = Arc::new(Ext4FileSystem {
             fs,
             raw_dev,
+            claim: mount_data.claim(),
             root_inode,
         });
 
@@ -142,9 +159,11 @@ impl MountableFileSystem for Ext4FileSystem {
         Self::from_gendisk(mount_data.gendisk.clone())
     }
     fn make_mount_data(
-        _raw_data: Option<&str>,
+        raw_data: Option<&str>,
         source: &str,
     ) -> Result<Option<Arc<dyn FileSystemMakerData + 'static>>, SystemError> {
+        // 暂不支持任何挂载选项
+        MountOptions::parse("ext4", raw_data, &[])?.warn_unknown("ext4");
         let mount_data = Ext4MountData::from_source(source).map_err(|e| {
             log::error!(
                 "Failed to create Ext4 mount data from source '{}': {:?}",
@@ -155,6 +174,10 @@ impl MountableFileSystem for Ext4FileSystem {
         })?;
         Ok(Some(Arc::new(mount_data)))
     }
+
+    fn probe(gendisk: &Arc<GenDisk>) -> bool {
+        Ext4FileSystem::probe(gendisk).unwrap_or(false)
+    }
 }
 
 register_mountable_fs!(Ext4FileSystem, EXT4FSMAKER, "ext4");
@@ -171,19 +194,8 @@ impl FileSystemMakerData for Ext4MountData {
 
 impl Ext4MountData {
     fn from_source(path: &str) -> Result<Self, SystemError> {
-        let pcb = ProcessManager::current_pcb();
-        let (current_node, rest_path) = user_path_at(&pcb, AtFlags::AT_FDCWD.bits(), path)?;
-        let inode = current_node.lookup_follow_symlink(&rest_path, VFS_MAX_FOLLOW_SYMLINK_TIMES)?;
-        if !inode.metadata()?.file_type.eq(&vfs::FileType::BlockDevice) {
-            return Err(SystemError::ENOTBLK);
-        }
-
-        let disk = inode.dname()?;
-
-        if let Some(gendisk) = try_find_gendisk(disk.0.as_str()) {
-            return Ok(Self { gendisk });
-        }
-        Err(SystemError::ENOENT)
+        let gendisk = find_source_gendisk(path)?;
+        Ok(Self { gendisk })
     }
 }
 
@@ -50,13 +50,11 @@ impl another_ext4::BlockDevice for GenDisk {
             .try_into()
             .expect("Failed to convert boxed slice to boxed array");
 
-        let (_, lba_id_start, block_count) = self.convert_from_ext4_blkid(block_id);
-        self.block_device()
-            .read_at(lba_id_start, block_count, &mut *buf)
-            .map_err(|e| {
-                log::error!("Ext4BlkDevice '{:?}' read_block failed: {:?}", block_id, e);
-                another_ext4::Ext4Error::new(Self::map_system_error_to_ext4(&e))
-            })?;
+        let (start_lba_offset, _, _) = self.convert_from_ext4_blkid(block_id);
+        self.read_at(&mut *buf, start_lba_offset).map_err(|e| {
+            log::error!("Ext4BlkDevice '{:?}' read_block failed: {:?}", block_id, e);
+            another_ext4::Ext4Error::new(Self::map_system_error_to_ext4(&e))
+        })?;
         Ok(another_ext4::Block::new(block_id, buf))
     }
 
@@ -64,22 +62,20 @@ impl another_ext4::BlockDevice for GenDisk {
         &self,
         block: &another_ext4::Block,
     ) -> core::result::Result<(), another_ext4::Ext4Error> {
-        let (_, lba_id_start, block_count) = self.convert_from_ext4_blkid(block.id);
-        self.block_device()
-            .write_at(lba_id_start, block_count, &*block.data)
-            .map_err(|e| {
-                let code = Self::map_system_error_to_ext4(&e);
-                if code == another_ext4::ErrCode::EROFS {
-                    log::trace!(
-                        "Ext4BlkDevice '{:?}' write_block on readonly media: {:?}",
-                        block.id,
-                        e
-                    );
-                } else {
-                    log::error!("Ext4BlkDevice '{:?}' write_block failed: {:?}", block.id, e);
-                }
-                another_ext4::Ext4Error::new(code)
-            })?;
+        let (start_lba_offset, _, _) = self.convert_from_ext4_blkid(block.id);
+        self.write_at(&*block.data, start_lba_offset).map_err(|e| {
+            let code = Self::map_system_error_to_ext4(&e);
+            if code == another_ext4::ErrCode::EROFS {
+                log::trace!(
+                    "Ext4BlkDevice '{:?}' write_block on readonly media: {:?}",
+                    block.id,
+                    e
+                );
+            } else {
+                log::error!("Ext4BlkDevice '{:?}' write_block failed: {:?}", block.id, e);
+            }
+            another_ext4::Ext4Error::new(code)
+        })?;
         Ok(())
     }
 }
@@ -1,17 +1,14 @@
 use crate::{
-    driver::base::device::device_number::DeviceNumber,
+    driver::base::{block::block_device::LBA_SIZE, device::device_number::DeviceNumber},
     filesystem::{
         page_cache::{AsyncPageCacheBackend, PageCache},
         vfs::{
-            self, syscall::RenameFlags, utils::DName, vcore::generate_inode_id, FilePrivateData,
-            IndexNode, InodeFlags, InodeId, InodeMode, SpecialNodeData,
+            self, syscall::RenameFlags, utils::DName, vcore::generate_inode_id, IndexNode,
+            InodeFlags, InodeId, InodeMode, SpecialNodeData,
         },
     },
     ipc::pipe::LockedPipeInode,
-    libs::{
-        casting::DowncastArc,
-        mutex::{Mutex, MutexGuard},
-    },
+    libs::{casting::DowncastArc, mutex::Mutex},
     mm::truncate::truncate_inode_pages,
     time::PosixTimeSpec,
 };
@@ -119,7 +116,7 @@ impl IndexNode for LockedExt4Inode {
         offset: usize,
         len: usize,
         buf: &mut [u8],
-        data: PrivateData,
+        _data: PrivateData,
     ) -> Result<usize, SystemError> {
         let len = core::cmp::min(len, buf.len());
         let buf = &mut buf[0..len];
@@ -128,45 +125,13 @@ impl IndexNode for LockedExt4Inode {
         // PageCache 读写路径内部会调用 inode.metadata() 获取文件大小：
         // - prepare_read(): inode.metadata()
         // 若此处持有 inode 锁，则会在 metadata() 再次尝试获取同一把锁而自旋死锁。
-        let (fs, inode_num, page_cache) = {
-            let guard = self.0.lock();
-            (
-                guard.concret_fs(),
-                guard.inner_inode_num,
-                guard.page_cache.clone(),
-            )
-        };
+        let page_cache = self.0.lock().page_cache.clone();
 
+        // atime由VFS按照挂载选项更新，参见vfs::timestamps
         if let Some(page_cache) = page_cache {
-            let time = PosixTimeSpec::now().tv_sec.to_u32().unwrap_or_else(|| {
-                log::warn!("Failed to get current time, using 0");
-                0
-            });
-            let atime_update = fs
-                .fs
-                .setattr(
-                    inode_num,
-                    another_ext4::SetAttr {
-                        mode: None,
-                        uid: None,
-                        gid: None,
-                        size: None,
-                        atime: Some(time),
-                        mtime: None,
-                        ctime: None,
-                        crtime: None,
-                    },
-                )
-                .map_err(SystemError::from);
-            match atime_update {
-                Ok(()) => {}
-                // 只读挂载下，atime 更新失败不应影响读取语义。
-                Err(SystemError::EROFS) => {}
-                Err(e) => return Err(e),
-            }
             page_cache.read(offset, buf)
         } else {
-            self.read_direct(offset, len, buf, data)
+            self.read_direct(offset, len, buf)
         }
     }
 
@@ -184,13 +149,7 @@ impl IndexNode for LockedExt4Inode {
         }
     }
 
-    fn read_direct(
-        &self,
-        offset: usize,
-        len: usize,
-        buf: &mut [u8],
-        _data: crate::libs::mutex::MutexGuard<vfs::FilePrivateData>,
-    ) -> Result<usize, SystemError> {
+    fn read_direct(&self, offset: usize, len: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
         let len = core::cmp::min(len, buf.len());
         self.read_sync(offset, &mut buf[0..len])
     }
@@ -200,7 +159,7 @@ impl IndexNode for LockedExt4Inode {
         offset: usize,
         len: usize,
         buf: &[u8],
-        data: PrivateData,
+        _data: PrivateData,
     ) -> Result<usize, SystemError> {
         let len = core::cmp::min(len, buf.len());
         let buf = &buf[0..len];
@@ -239,7 +198,7 @@ impl IndexNode for LockedExt4Inode {
                 .map_err(SystemError::from)?;
             Ok(write_len)
         } else {
-            self.write_direct(offset, len, buf, data)
+            self.write_direct(offset, len, buf)
         }
     }
 
@@ -256,17 +215,15 @@ impl IndexNode for LockedExt4Inode {
         }
     }
 
-    fn write_direct(
-        &self,
-        offset: usize,
-        len: usize,
-        buf: &[u8],
-        _data: MutexGuard<FilePrivateData>,
-    ) -> Result<usize, SystemError> {
+    fn write_direct(&self, offset: usize, len: usize, buf: &[u8]) -> Result<usize, SystemError> {
         let len = core::cmp::min(len, buf.len());
         self.write_sync(offset, &buf[0..len])
     }
 
+    fn direct_io_alignment(&self) -> Option<usize> {
+        Some(LBA_SIZE)
+    }
+
     fn fs(&self) -> Arc<dyn vfs::FileSystem> {
         self.0.lock().concret_fs()
     }
@@ -537,6 +494,24 @@ impl IndexNode for LockedExt4Inode {
         Ok(0)
     }
 
+    fn listxattr(&self) -> Result<Vec<String>, SystemError> {
+        let guard = self.0.lock();
+        let ext4 = &guard.concret_fs().fs;
+        Ok(ext4.listxattr(guard.inner_inode_num)?)
+    }
+
+    fn removexattr(&self, name: &str) -> Result<(), SystemError> {
+        let guard = self.0.lock();
+        let ext4 = &guard.concret_fs().fs;
+        let inode_num = guard.inner_inode_num;
+
+        if ext4.getattr(inode_num)?.ftype == FileType::SymLink {
+            return Err(SystemError::EPERM);
+        }
+
+        Ok(ext4.removexattr(inode_num, name)?)
+    }
+
     fn mknod(
         &self,
         filename: &str,
@@ -1,5 +1,5 @@
 #![allow(dead_code)]
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use log::error;
 use system_error::SystemError;
 
@@ -265,6 +265,11 @@ impl BiosParameterBlock {
         // 读取尾部的启动扇区标志
         bpb.trail_sig = cursor.read_u16()?;
 
+        // 不是FAT文件系统的设备上这些字段可能为0，先检查再用于计算簇的数量
+        if bpb.bytes_per_sector == 0 || !bpb.sector_per_cluster.is_power_of_two() {
+            return Err(SystemError::EINVAL);
+        }
+
         // 计算根目录项占用的空间（单位：字节）
         let root_sectors = (bpb.root_entries_cnt as u32 * 32).div_ceil(bpb.bytes_per_sector as u32);
 
@@ -283,15 +288,18 @@ impl BiosParameterBlock {
         };
 
         // 数据区扇区数
-        let data_sectors = total_sectors
-            - ((bpb.rsvd_sec_cnt as u32) + (bpb.num_fats as u32) * fat_size + root_sectors);
+        let data_sectors = (bpb.num_fats as u32)
+            .checked_mul(fat_size)
+            .and_then(|fats| fats.checked_add(bpb.rsvd_sec_cnt as u32 + root_sectors))
+            .and_then(|meta| total_sectors.checked_sub(meta))
+            .ok_or(SystemError::EINVAL)?;
         // 总的数据簇数量（向下对齐）
         let count_clusters = data_sectors / (bpb.sector_per_cluster as u32);
 
-        // 设置FAT类型
+        // FAT的类型只由簇的数量决定，与卷标中的"FAT12"/"FAT16"/"FAT32"字符串无关
         bpb.fat_type = if count_clusters < FATFileSystem::FAT12_MAX_CLUSTER {
             FATType::FAT12(BiosParameterBlockLegacy::default())
-        } else if count_clusters <= FATFileSystem::FAT16_MAX_CLUSTER {
+        } else if count_clusters < FATFileSystem::FAT16_MAX_CLUSTER {
             FATType::FAT16(BiosParameterBlockLegacy::default())
         } else if count_clusters < FATFileSystem::FAT32_MAX_CLUSTER {
             FATType::FAT32(bpb32)
@@ -380,3 +388,328 @@ impl BiosParameterBlock {
         }
     }
 }
+
+/// 格式化时要创建的FAT类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatFormatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+/// 格式化FAT文件系统的参数（对应mkfs.fat的常用选项）
+#[derive(Debug, Clone, Copy)]
+pub struct FatFormatOptions {
+    /// FAT类型，为`None`时根据分区大小自动选择
+    pub fat_type: Option<FatFormatType>,
+    /// 每簇扇区数，为`None`时自动选择
+    pub sectors_per_cluster: Option<u8>,
+    /// 卷号
+    pub volume_id: u32,
+    /// 卷标，不足11字节时以空格填充
+    pub volume_label: [u8; 11],
+}
+
+impl Default for FatFormatOptions {
+    fn default() -> Self {
+        Self {
+            fat_type: None,
+            sectors_per_cluster: None,
+            volume_id: 0x44524147,
+            volume_label: *b"NO NAME    ",
+        }
+    }
+}
+
+/// 格式化时计算出的卷布局（单位：扇区）
+#[derive(Debug, Clone, Copy)]
+struct FatFormatLayout {
+    fat_type: FatFormatType,
+    total_sectors: u32,
+    sectors_per_cluster: u8,
+    rsvd_sec_cnt: u16,
+    root_entries_cnt: u16,
+    fat_size: u32,
+    clusters: u32,
+}
+
+impl FatFormatLayout {
+    const NUM_FATS: u8 = 2;
+    const MEDIA: u8 = 0xF8;
+    /// FAT32的FSInfo扇区号
+    const FAT32_FS_INFO: u16 = 1;
+    /// FAT32备份引导扇区的扇区号
+    const FAT32_BACKUP_BOOT: u16 = 6;
+
+    fn root_sectors(&self) -> u32 {
+        (self.root_entries_cnt as u32 * 32).div_ceil(LBA_SIZE as u32)
+    }
+
+    fn first_fat_sector(&self) -> u32 {
+        self.rsvd_sec_cnt as u32
+    }
+
+    fn first_root_sector(&self) -> u32 {
+        self.first_fat_sector() + Self::NUM_FATS as u32 * self.fat_size
+    }
+
+    fn first_data_sector(&self) -> u32 {
+        self.first_root_sector() + self.root_sectors()
+    }
+
+    /// 给定FAT类型和每簇扇区数，计算FAT表大小和簇数量
+    fn compute(
+        fat_type: FatFormatType,
+        total_sectors: u32,
+        sectors_per_cluster: u8,
+    ) -> Option<FatFormatLayout> {
+        let (rsvd_sec_cnt, root_entries_cnt, bits) = match fat_type {
+            FatFormatType::Fat12 => (1u16, 224u16, 12u64),
+            FatFormatType::Fat16 => (1, 512, 16),
+            FatFormatType::Fat32 => (32, 0, 32),
+        };
+        let mut layout = FatFormatLayout {
+            fat_type,
+            total_sectors,
+            sectors_per_cluster,
+            rsvd_sec_cnt,
+            root_entries_cnt,
+            fat_size: 1,
+            clusters: 0,
+        };
+
+        // FAT表的大小依赖于簇数量，而簇数量又依赖于FAT表的大小，迭代到稳定为止
+        loop {
+            let data_sectors = total_sectors.checked_sub(layout.first_data_sector())?;
+            layout.clusters = data_sectors / sectors_per_cluster as u32;
+            let fat_bytes = (layout.clusters as u64 + 2) * bits;
+            let needed = fat_bytes.div_ceil(8).div_ceil(LBA_SIZE as u64) as u32;
+            if needed <= layout.fat_size {
+                break;
+            }
+            layout.fat_size = needed;
+        }
+
+        // 簇数量必须落在该FAT类型的范围内，否则挂载时会被识别为其他类型
+        let valid = match fat_type {
+            FatFormatType::Fat12 => layout.clusters >= 1 && layout.clusters < 0xFF5,
+            FatFormatType::Fat16 => layout.clusters >= 0xFF5 && layout.clusters < 0xFFF5,
+            FatFormatType::Fat32 => {
+                layout.clusters > 0xFFF5 && layout.clusters < FATFileSystem::FAT32_MAX_CLUSTER
+            }
+        };
+        if !valid {
+            return None;
+        }
+        Some(layout)
+    }
+
+    fn new(total_sectors: u32, opts: &FatFormatOptions) -> Result<FatFormatLayout, SystemError> {
+        let candidates: &[FatFormatType] = match opts.fat_type {
+            Some(FatFormatType::Fat12) => &[FatFormatType::Fat12],
+            Some(FatFormatType::Fat16) => &[FatFormatType::Fat16],
+            Some(FatFormatType::Fat32) => &[FatFormatType::Fat32],
+            // 与mkfs.fat类似：很小的分区使用FAT12，小于512MB的分区使用FAT16
+            None if total_sectors < 8400 => &[FatFormatType::Fat12, FatFormatType::Fat16],
+            None if total_sectors < 1024 * 1024 => &[
+                FatFormatType::Fat16,
+                FatFormatType::Fat32,
+                FatFormatType::Fat12,
+            ],
+            None => &[FatFormatType::Fat32, FatFormatType::Fat16],
+        };
+
+        if let Some(spc) = opts.sectors_per_cluster {
+            if spc == 0 || !spc.is_power_of_two() {
+                return Err(SystemError::EINVAL);
+            }
+        }
+
+        for &fat_type in candidates {
+            // 从较小的簇开始尝试，优先选择能满足簇数量范围的最小簇
+            let mut spc: u8 = opts.sectors_per_cluster.unwrap_or(match fat_type {
+                FatFormatType::Fat32 if total_sectors >= 16 * 1024 * 1024 => 16,
+                FatFormatType::Fat32 if total_sectors >= 512 * 1024 => 8,
+                _ => 1,
+            });
+            loop {
+                if let Some(layout) = Self::compute(fat_type, total_sectors, spc) {
+                    return Ok(layout);
+                }
+                if opts.sectors_per_cluster.is_some() || spc == 128 {
+                    break;
+                }
+                spc *= 2;
+            }
+        }
+
+        error!(
+            "mkfs.fat: cannot fit a FAT volume in {} sectors with the given options",
+            total_sectors
+        );
+        Err(SystemError::EINVAL)
+    }
+
+    fn boot_sector(&self, opts: &FatFormatOptions) -> Result<Vec<u8>, SystemError> {
+        let mut cursor = VecCursor::new(vec![0u8; LBA_SIZE]);
+        let fat32 = self.fat_type == FatFormatType::Fat32;
+
+        cursor.write_exact(if fat32 {
+            &[0xEB, 0x58, 0x90]
+        } else {
+            &[0xEB, 0x3C, 0x90]
+        })?;
+        cursor.write_exact(b"DRAGONOS")?;
+        cursor.write_u16(LBA_SIZE as u16)?;
+        cursor.write_u8(self.sectors_per_cluster)?;
+        cursor.write_u16(self.rsvd_sec_cnt)?;
+        cursor.write_u8(Self::NUM_FATS)?;
+        cursor.write_u16(self.root_entries_cnt)?;
+        // 总扇区数能用16位表示时，FAT12/16使用total_sectors_16
+        let (total_16, total_32) = if !fat32 && self.total_sectors <= u16::MAX as u32 {
+            (self.total_sectors as u16, 0)
+        } else {
+            (0, self.total_sectors)
+        };
+        cursor.write_u16(total_16)?;
+        cursor.write_u8(Self::MEDIA)?;
+        cursor.write_u16(if fat32 { 0 } else { self.fat_size as u16 })?;
+        // 每磁道扇区数、磁头数：对于非软盘介质没有实际意义
+        cursor.write_u16(32)?;
+        cursor.write_u16(64)?;
+        cursor.write_u32(0)?;
+        cursor.write_u32(total_32)?;
+
+        if fat32 {
+            cursor.write_u32(self.fat_size)?;
+            cursor.write_u16(0)?;
+            cursor.write_u16(0)?;
+            cursor.write_u32(2)?;
+            cursor.write_u16(Self::FAT32_FS_INFO)?;
+            cursor.write_u16(Self::FAT32_BACKUP_BOOT)?;
+            cursor.write_exact(&[0u8; 12])?;
+        }
+        cursor.write_u8(0x80)?;
+        cursor.write_u8(0)?;
+        cursor.write_u8(0x29)?;
+        cursor.write_u32(opts.volume_id)?;
+        cursor.write_exact(&opts.volume_label)?;
+        cursor.write_exact(match self.fat_type {
+            FatFormatType::Fat12 => b"FAT12   ",
+            FatFormatType::Fat16 => b"FAT16   ",
+            FatFormatType::Fat32 => b"FAT32   ",
+        })?;
+
+        cursor.seek(SeekFrom::SeekSet(510))?;
+        cursor.write_u16(0xAA55)?;
+        Ok(cursor.as_slice().to_vec())
+    }
+
+    fn fs_info_sector(&self) -> Result<Vec<u8>, SystemError> {
+        let mut cursor = VecCursor::new(vec![0u8; LBA_SIZE]);
+        cursor.write_u32(0x41615252)?;
+        cursor.seek(SeekFrom::SeekSet(484))?;
+        cursor.write_u32(0x61417272)?;
+        // 根目录占用了一个簇
+        cursor.write_u32(self.clusters - 1)?;
+        cursor.write_u32(3)?;
+        cursor.seek(SeekFrom::SeekSet(508))?;
+        cursor.write_u32(0xAA550000)?;
+        Ok(cursor.as_slice().to_vec())
+    }
+
+    /// FAT表的第一个扇区：保留的0、1号表项，以及FAT32根目录所在簇的表项
+    fn first_fat_sector_data(&self) -> Vec<u8> {
+        let mut sector = vec![0u8; LBA_SIZE];
+        let head: &[u8] = match self.fat_type {
+            FatFormatType::Fat12 => &[Self::MEDIA, 0xFF, 0xFF],
+            FatFormatType::Fat16 => &[Self::MEDIA, 0xFF, 0xFF, 0xFF],
+            FatFormatType::Fat32 => &[
+                Self::MEDIA,
+                0xFF,
+                0xFF,
+                0x0F,
+                0xFF,
+                0xFF,
+                0xFF,
+                0x0F,
+                0xFF,
+                0xFF,
+                0xFF,
+                0x0F,
+            ],
+        };
+        sector[..head.len()].copy_from_slice(head);
+        sector
+    }
+}
+
+/// 将`[start, start + count)`范围内的扇区清零
+fn zero_sectors(gendisk: &Arc<GenDisk>, start: u32, count: u32) -> Result<(), SystemError> {
+    const BATCH_SECTORS: u32 = 64;
+    let zeroes = vec![0u8; BATCH_SECTORS as usize * LBA_SIZE];
+    let mut done = 0;
+    while done < count {
+        let n = (count - done).min(BATCH_SECTORS);
+        gendisk.write_at(&zeroes[..n as usize * LBA_SIZE], (start + done) as usize)?;
+        done += n;
+    }
+    Ok(())
+}
+
+impl BiosParameterBlock {
+    /// # 在分区上创建FAT文件系统（mkfs.fat）
+    ///
+    /// 写入引导扇区、FAT表、空的根目录，FAT32还会写入FSInfo和备份引导扇区。
+    /// 数据区不会被清零。
+    ///
+    /// ## 参数
+    ///
+    /// - `gendisk`: 要格式化的分区
+    /// - `opts`: 格式化参数
+    ///
+    /// ## 返回值
+    /// - `Ok(FatFormatType)`: 实际创建的FAT类型
+    /// - `Err(SystemError::EINVAL)`: 分区大小与参数不匹配，无法创建
+    pub fn format(
+        gendisk: &Arc<GenDisk>,
+        opts: &FatFormatOptions,
+    ) -> Result<FatFormatType, SystemError> {
+        if gendisk.block_size_log2() as usize != LBA_SIZE.trailing_zeros() as usize {
+            return Err(SystemError::EINVAL);
+        }
+        let total_sectors = u32::try_from(gendisk.nr_sectors()).map_err(|_| SystemError::EFBIG)?;
+        let layout = FatFormatLayout::new(total_sectors, opts)?;
+
+        // 先清空保留区、FAT表和根目录区，最后再写引导扇区，
+        // 避免中途失败时留下一个看起来合法但内容错误的文件系统
+        zero_sectors(gendisk, 0, layout.first_data_sector())?;
+        if layout.fat_type == FatFormatType::Fat32 {
+            // FAT32的根目录位于2号簇
+            zero_sectors(
+                gendisk,
+                layout.first_data_sector(),
+                layout.sectors_per_cluster as u32,
+            )?;
+        }
+
+        let fat_head = layout.first_fat_sector_data();
+        for i in 0..FatFormatLayout::NUM_FATS as u32 {
+            let sector = layout.first_fat_sector() + i * layout.fat_size;
+            gendisk.write_at(&fat_head, sector as usize)?;
+        }
+
+        let boot = layout.boot_sector(opts)?;
+        if layout.fat_type == FatFormatType::Fat32 {
+            let fs_info = layout.fs_info_sector()?;
+            let backup = FatFormatLayout::FAT32_BACKUP_BOOT as usize;
+            gendisk.write_at(&fs_info, FatFormatLayout::FAT32_FS_INFO as usize)?;
+            gendisk.write_at(&boot, backup)?;
+            gendisk.write_at(&fs_info, backup + 1)?;
+        }
+        gendisk.write_at(&boot, 0)?;
+        gendisk.sync()?;
+
+        return Ok(layout.fat_type);
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 use crate::filesystem::fat::fs::LockedFATInode;
 use crate::filesystem::vfs::IndexNode;
-use crate::mm::truncate::truncate_inode_pages;
+use crate::mm::{gup::PinnedUserPages, truncate::truncate_inode_pages};
 use crate::{
     driver::base::block::{block_device::LBA_SIZE, SeekFrom},
     libs::vec_cursor::VecCursor,
@@ -85,6 +85,70 @@ impl FATFile {
         fs: &Arc<FATFileSystem>,
         buf: &mut [u8],
         offset: u64,
+    ) -> Result<usize, SystemError> {
+        let len = buf.len();
+        self.do_read(fs, len, offset, |start, len, disk_offset| {
+            fs.gendisk
+                .read_at_bytes(&mut buf[start..start + len], disk_offset)
+        })
+    }
+
+    /// @brief 绕过块缓存从文件读取数据，用于O_DIRECT
+    ///
+    /// @param buf 输出缓冲区，长度必须按LBA_SIZE对齐
+    /// @param offset 起始位置在文件中的偏移量，必须按LBA_SIZE对齐
+    ///
+    /// @return Ok(usize) 成功读取到的字节数，不会超过文件末尾
+    /// @return Err(SystemError) 读取时出现错误，返回错误码
+    pub fn read_direct(
+        &self,
+        fs: &Arc<FATFileSystem>,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> Result<usize, SystemError> {
+        let len = buf.len();
+        self.do_read(fs, len, offset, |start, len, disk_offset| {
+            // 文件末尾不足一个扇区的部分也整扇区读取，簇和缓冲区的大小都是扇区的整数倍
+            let io_len = len.next_multiple_of(LBA_SIZE);
+            fs.gendisk
+                .read_direct_bytes(&mut buf[start..start + io_len], disk_offset)?;
+            Ok(len)
+        })
+    }
+
+    /// @brief 绕过块缓存从文件读取数据到固定住的用户缓冲区，用于O_DIRECT。设备直接写入用户页
+    ///
+    /// @param pages 用户缓冲区，长度必须按LBA_SIZE对齐
+    /// @param offset 起始位置在文件中的偏移量，必须按LBA_SIZE对齐
+    ///
+    /// @return Ok(usize) 成功读取到的字节数，不会超过文件末尾
+    /// @return Err(SystemError) 读取时出现错误，返回错误码
+    pub fn read_direct_user(
+        &self,
+        fs: &Arc<FATFileSystem>,
+        pages: &PinnedUserPages,
+        offset: u64,
+    ) -> Result<usize, SystemError> {
+        self.do_read(fs, pages.len(), offset, |start, len, disk_offset| {
+            let io_len = len.next_multiple_of(LBA_SIZE);
+            fs.gendisk
+                .read_direct_user_bytes(&pages.slice(start, io_len), disk_offset)?;
+            Ok(len)
+        })
+    }
+
+    /// @brief 按簇把文件中的一段交给`read_extent`读取
+    ///
+    /// @param len 要读取的字节数
+    /// @param offset 起始位置在文件中的偏移量
+    /// @param read_extent 读取簇中连续的一段，参数为：在缓冲区中的偏移量、字节数、在分区上的字节偏移量。
+    /// 返回读取的字节数
+    fn do_read(
+        &self,
+        fs: &Arc<FATFileSystem>,
+        len: usize,
+        offset: u64,
+        mut read_extent: impl FnMut(usize, usize, usize) -> Result<usize, SystemError>,
     ) -> Result<usize, SystemError> {
         if offset >= self.size() {
             return Ok(0);
@@ -105,7 +169,7 @@ impl FATFile {
 
         // 计算簇内偏移量
         let mut in_cluster_offset: u64 = offset % fs.bytes_per_cluster();
-        let to_read_size: usize = min(buf.len(), bytes_remain as usize);
+        let to_read_size: usize = min(len, bytes_remain as usize);
 
         let mut start = 0;
         let mut read_ok = 0;
@@ -126,15 +190,13 @@ impl FATFile {
                 to_read_size - read_ok,
                 min(
                     (fs.bytes_per_cluster() - in_cluster_offset) as usize,
-                    buf.len() - read_ok,
+                    len - read_ok,
                 ),
             );
 
             //  从磁盘上读取数据
             let offset = fs.cluster_bytes_offset(current_cluster) + in_cluster_offset;
-            let r = fs
-                .gendisk
-                .read_at_bytes(&mut buf[start..start + end_len], offset as usize)?;
+            let r = read_extent(start, end_len, offset as usize)?;
 
             // 更新偏移量计数信息
             read_ok += r;
@@ -161,7 +223,64 @@ impl FATFile {
         buf: &[u8],
         offset: u64,
     ) -> Result<usize, SystemError> {
-        self.ensure_len(fs, offset, buf.len() as u64)?;
+        self.do_write(fs, buf.len(), offset, |start, len, disk_offset| {
+            fs.gendisk
+                .write_at_bytes(&buf[start..start + len], disk_offset)
+        })
+    }
+
+    /// @brief 绕过块缓存向文件写入数据，用于O_DIRECT。返回时数据已经写到设备上
+    ///
+    /// @param buf 输入缓冲区，长度必须按LBA_SIZE对齐
+    /// @param offset 起始位置在文件中的偏移量，必须按LBA_SIZE对齐
+    ///
+    /// @return Ok(usize) 成功写入的字节数
+    /// @return Err(SystemError) 写入时出现错误，返回错误码
+    pub fn write_direct(
+        &mut self,
+        fs: &Arc<FATFileSystem>,
+        buf: &[u8],
+        offset: u64,
+    ) -> Result<usize, SystemError> {
+        self.do_write(fs, buf.len(), offset, |start, len, disk_offset| {
+            fs.gendisk
+                .write_direct_bytes(&buf[start..start + len], disk_offset)
+        })
+    }
+
+    /// @brief 绕过块缓存把固定住的用户缓冲区写入文件，用于O_DIRECT。设备直接读取用户页，返回时数据已经写到设备上
+    ///
+    /// @param pages 用户缓冲区，长度必须按LBA_SIZE对齐
+    /// @param offset 起始位置在文件中的偏移量，必须按LBA_SIZE对齐
+    ///
+    /// @return Ok(usize) 成功写入的字节数
+    /// @return Err(SystemError) 写入时出现错误，返回错误码
+    pub fn write_direct_user(
+        &mut self,
+        fs: &Arc<FATFileSystem>,
+        pages: &PinnedUserPages,
+        offset: u64,
+    ) -> Result<usize, SystemError> {
+        self.do_write(fs, pages.len(), offset, |start, len, disk_offset| {
+            fs.gendisk
+                .write_direct_user_bytes(&pages.slice(start, len), disk_offset)
+        })
+    }
+
+    /// @brief 分配足够的簇之后，按簇把文件中的一段交给`write_extent`写入
+    ///
+    /// @param len 要写入的字节数
+    /// @param offset 起始位置在文件中的偏移量
+    /// @param write_extent 写入簇中连续的一段，参数为：在缓冲区中的偏移量、字节数、在分区上的字节偏移量。
+    /// 返回写入的字节数
+    fn do_write(
+        &mut self,
+        fs: &Arc<FATFileSystem>,
+        len: usize,
+        offset: u64,
+        mut write_extent: impl FnMut(usize, usize, usize) -> Result<usize, SystemError>,
+    ) -> Result<usize, SystemError> {
+        self.ensure_len(fs, offset, len as u64)?;
 
         // 要写入的第一个簇的簇号
         let start_cluster_num = offset / fs.bytes_per_cluster();
@@ -192,15 +311,13 @@ impl FATFile {
 
             let end_len = min(
                 (fs.bytes_per_cluster() - in_cluster_bytes_offset) as usize,
-                buf.len() - write_ok,
+                len - write_ok,
             );
 
             // 计算本次写入位置在分区上的偏移量
             let offset = fs.cluster_bytes_offset(current_cluster) + in_cluster_bytes_offset;
             // 写入磁盘（防御性检查：若设备返回0字节写入视为IO错误）
-            let w = fs
-                .gendisk
-                .write_at_bytes(&buf[start..start + end_len], offset as usize)?;
+            let w = write_extent(start, end_len, offset as usize)?;
             if w == 0 {
                 return Err(SystemError::EIO);
             }
@@ -210,7 +327,7 @@ impl FATFile {
             start += w;
             in_cluster_bytes_offset += w as u64;
 
-            if write_ok == buf.len() {
+            if write_ok == len {
                 break;
             }
         }
@@ -1,5 +1,6 @@
 use crate::arch::MMArch;
 use crate::filesystem::vfs::syscall::RenameFlags;
+use crate::mm::gup::PinnedUserPages;
 use crate::mm::truncate::truncate_inode_pages;
 use crate::mm::MemoryManagementArch;
 use alloc::string::ToString;
@@ -11,13 +12,14 @@ use alloc::{
 use core::cmp::Ordering;
 use core::intrinsics::unlikely;
 use core::num::NonZeroUsize;
+use core::sync::atomic::{self, AtomicBool};
 use core::{any::Any, fmt::Debug};
 use hashbrown::HashMap;
-use log::error;
+use log::{error, warn};
 use lru::LruCache;
 use system_error::SystemError;
 
-use crate::driver::base::block::gendisk::GenDisk;
+use crate::driver::base::block::gendisk::{GenDisk, GenDiskClaim};
 use crate::driver::base::device::device_number::DeviceNumber;
 use crate::filesystem::page_cache::{AsyncPageCacheBackend, PageCache};
 use crate::filesystem::vfs::utils::DName;
@@ -93,6 +95,8 @@ impl Eq for Cluster {}
 pub struct FATFileSystem {
     /// 当前文件系统所在的分区
     pub gendisk: Arc<GenDisk>,
+    /// 文件系统存在期间保持块设备处于打开状态
+    _claim: GenDiskClaim,
     /// 当前文件系统的BOPB
     pub bpb: BiosParameterBlock,
     /// 当前文件系统的第一个数据扇区（相对分区开始位置）
@@ -103,6 +107,8 @@ pub struct FATFileSystem {
     root_inode: Arc<LockedFATInode>,
     /// FAT表查询缓存（LRU）
     fat_cache: Mutex<LruCache<ClusterID, ClusterID>>,
+    /// 释放簇时是否通知块设备discard，设备不支持时关闭
+    discard_enabled: AtomicBool,
 }
 
 /// FAT文件系统的Inode
@@ -438,6 +444,11 @@ impl FileSystem for FATFileSystem {
         "fat"
     }
 
+    fn time_granularity(&self) -> u32 {
+        // FAT目录项中的修改时间以2秒为单位
+        2_000_000_000
+    }
+
     fn super_block(&self) -> SuperBlock {
         let mut sb = SuperBlock::new(
             Magic::FAT_MAGIC,
@@ -676,6 +687,7 @@ impl FATFileSystem {
         })));
 
         let result: Arc<FATFileSystem> = Arc::new(FATFileSystem {
+            _claim: gendisk.claim(),
             gendisk,
             bpb,
             first_data_sector,
@@ -684,6 +696,7 @@ impl FATFileSystem {
             fat_cache: Mutex::new(LruCache::new(
                 NonZeroUsize::new(FAT_LRU_CACHE_SIZE).unwrap(),
             )),
+            discard_enabled: AtomicBool::new(true),
         });
 
         // 对root inode加锁，并继续完成初始化工作
@@ -991,8 +1004,22 @@ impl FATFileSystem {
     /// @param start_cluster 簇链的第一个簇
     pub fn deallocate_cluster_chain(&self, start_cluster: Cluster) -> Result<(), SystemError> {
         let clusters: Vec<Cluster> = self.clusters(start_cluster);
-        for c in clusters {
-            self.deallocate_cluster(c)?;
+        for c in clusters.iter() {
+            self.free_cluster_entry(*c)?;
+        }
+
+        // 把簇号连续的簇合并成一次discard
+        let mut i = 0;
+        while i < clusters.len() {
+            let start = clusters[i];
+            let mut count = 1;
+            while i + count < clusters.len()
+                && clusters[i + count].cluster_num == start.cluster_num + count as u64
+            {
+                count += 1;
+            }
+            self.discard_clusters(start, count);
+            i += count;
         }
         return Ok(());
     }
@@ -1001,6 +1028,13 @@ impl FATFileSystem {
     ///
     /// @param 要释放的簇
     pub fn deallocate_cluster(&self, cluster: Cluster) -> Result<(), SystemError> {
+        self.free_cluster_entry(cluster)?;
+        self.discard_clusters(cluster, 1);
+        return Ok(());
+    }
+
+    /// @brief 在FAT表中把簇标记为空闲
+    fn free_cluster_entry(&self, cluster: Cluster) -> Result<(), SystemError> {
         let entry: FATEntry = self.get_fat_entry(cluster)?;
         // 如果不是坏簇
         if entry != FATEntry::Bad {
@@ -1017,6 +1051,31 @@ impl FATFileSystem {
         }
     }
 
+    /// 通知块设备从`start`开始的`count`个簇已经不再使用。
+    ///
+    /// 只是尽力而为：失败不影响簇的释放，设备不支持discard时之后不再尝试
+    fn discard_clusters(&self, start: Cluster, count: usize) {
+        // 安全选项要求被释放的簇读出来全为0，而discard之后的内容是未定义的
+        if cfg!(feature = "fatfs-secure") || !self.discard_enabled.load(atomic::Ordering::Relaxed) {
+            return;
+        }
+
+        let lba_start = self.cluster_bytes_offset(start) as usize / LBA_SIZE;
+        let lba_count = count * self.bpb.sector_per_cluster as usize * self.lba_per_sector();
+        match self.gendisk.discard(lba_start, lba_count) {
+            Ok(()) => {}
+            Err(SystemError::EOPNOTSUPP_OR_ENOTSUP) => {
+                self.discard_enabled.store(false, atomic::Ordering::Relaxed);
+            }
+            Err(e) => {
+                warn!(
+                    "FAT: discard {} clusters from {} failed: {:?}",
+                    count, start.cluster_num, e
+                );
+            }
+        }
+    }
+
     /// @brief 获取文件系统的根目录项
     pub fn root_dir(&self) -> FATDir {
         match self.bpb.fat_type {
@@ -1248,11 +1307,14 @@ impl FATFileSystem {
     pub fn umount(&mut self) -> Result<(), SystemError> {
         self.fs_info.0.lock().flush(&self.gendisk)?;
 
+        // 数据和FAT表都持久化之后，才能把卷标记为正常卸载
+        self.gendisk.flush()?;
+
         self.set_shut_bit_ok()?;
 
         self.set_hard_error_bit_ok()?;
 
-        self.gendisk.sync()?;
+        self.gendisk.flush()?;
 
         return Ok(());
     }
@@ -1827,30 +1889,87 @@ impl IndexNode for LockedFATInode {
         self.try_write_pagecache(offset, buf)
     }
 
-    fn read_direct(
+    fn read_direct(&self, offset: usize, len: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let len = core::cmp::min(len, buf.len());
+        let guard: MutexGuard<FATInode> = self.0.lock();
+        match &guard.inode_type {
+            FATDirEntry::File(f) | FATDirEntry::VolId(f) => f.read_direct(
+                &guard.fs.upgrade().unwrap(),
+                &mut buf[0..len],
+                offset as u64,
+            ),
+            FATDirEntry::Dir(_) => Err(SystemError::EISDIR),
+            FATDirEntry::UnInit => {
+                error!("FATFS: param: Inode_type uninitialized.");
+                Err(SystemError::EROFS)
+            }
+        }
+    }
+
+    fn write_direct(&self, offset: usize, len: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        let len = core::cmp::min(len, buf.len());
+        let mut guard: MutexGuard<FATInode> = self.0.lock();
+        let fs: &Arc<FATFileSystem> = &guard.fs.upgrade().unwrap();
+        let write_len = match &mut guard.inode_type {
+            FATDirEntry::File(f) | FATDirEntry::VolId(f) => {
+                f.write_direct(fs, &buf[0..len], offset as u64)?
+            }
+            FATDirEntry::Dir(_) => return Err(SystemError::EISDIR),
+            FATDirEntry::UnInit => {
+                error!("FATFS: param: Inode_type uninitialized.");
+                return Err(SystemError::EROFS);
+            }
+        };
+        let old_size = guard.metadata.size;
+        guard.update_metadata(Some(core::cmp::max(old_size, (offset + write_len) as i64)));
+        return Ok(write_len);
+    }
+
+    fn read_direct_user(
         &self,
         offset: usize,
-        len: usize,
-        buf: &mut [u8],
-        _data: MutexGuard<FilePrivateData>,
+        pages: &PinnedUserPages,
     ) -> Result<usize, SystemError> {
-        let len = core::cmp::min(len, buf.len());
-        let r = self.read_sync(offset, &mut buf[0..len]);
-        // self.0.lock_irqsave().update_metadata();
-        return r;
+        let guard: MutexGuard<FATInode> = self.0.lock();
+        match &guard.inode_type {
+            FATDirEntry::File(f) | FATDirEntry::VolId(f) => {
+                f.read_direct_user(&guard.fs.upgrade().unwrap(), pages, offset as u64)
+            }
+            FATDirEntry::Dir(_) => Err(SystemError::EISDIR),
+            FATDirEntry::UnInit => {
+                error!("FATFS: param: Inode_type uninitialized.");
+                Err(SystemError::EROFS)
+            }
+        }
     }
 
-    fn write_direct(
+    fn write_direct_user(
         &self,
         offset: usize,
-        len: usize,
-        buf: &[u8],
-        _data: MutexGuard<FilePrivateData>,
+        pages: &PinnedUserPages,
     ) -> Result<usize, SystemError> {
-        let len = core::cmp::min(len, buf.len());
-        let r = self.write_sync(offset, &buf[0..len]);
-        // self.0.lock_irqsave().update_metadata();
-        return r;
+        let mut guard: MutexGuard<FATInode> = self.0.lock();
+        let fs: &Arc<FATFileSystem> = &guard.fs.upgrade().unwrap();
+        let write_len = match &mut guard.inode_type {
+            FATDirEntry::File(f) | FATDirEntry::VolId(f) => {
+                f.write_direct_user(fs, pages, offset as u64)?
+            }
+            FATDirEntry::Dir(_) => return Err(SystemError::EISDIR),
+            FATDirEntry::UnInit => {
+                error!("FATFS: param: Inode_type uninitialized.");
+                return Err(SystemError::EROFS);
+            }
+        };
+        let old_size = guard.metadata.size;
+        guard.update_metadata(Some(core::cmp::max(old_size, (offset + write_len) as i64)));
+        return Ok(write_len);
+    }
+
+    fn direct_io_alignment(&self) -> Option<usize> {
+        match self.0.lock().inode_type {
+            FATDirEntry::File(_) | FATDirEntry::VolId(_) => Some(LBA_SIZE),
+            _ => None,
+        }
     }
 
     fn create(
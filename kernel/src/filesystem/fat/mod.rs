@@ -2,4 +2,6 @@ pub mod bpb;
 pub mod entry;
 pub mod fs;
 mod mount;
+#[cfg(feature = "fatfs-selftest")]
+mod selftest;
 pub mod utils;
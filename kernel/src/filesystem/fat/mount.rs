@@ -2,10 +2,9 @@ use crate::filesystem::vfs::FSMAKER;
 use crate::{
     driver::base::block::gendisk::GenDisk,
     filesystem::vfs::{
-        self, fcntl::AtFlags, utils::user_path_at, vcore::try_find_gendisk, FileSystem,
-        FileSystemMakerData, MountableFileSystem, VFS_MAX_FOLLOW_SYMLINK_TIMES,
+        mount_options::MountOptions, vcore::find_source_gendisk, FileSystem, FileSystemMakerData,
+        MountableFileSystem,
     },
-    process::ProcessManager,
     register_mountable_fs,
 };
 use alloc::sync::Arc;
@@ -27,19 +26,8 @@ impl FileSystemMakerData for FatMountData {
 
 impl FatMountData {
     fn from_source(path: &str) -> Result<Self, SystemError> {
-        let pcb = ProcessManager::current_pcb();
-        let (current_node, rest_path) = user_path_at(&pcb, AtFlags::AT_FDCWD.bits(), path)?;
-        let inode = current_node.lookup_follow_symlink(&rest_path, VFS_MAX_FOLLOW_SYMLINK_TIMES)?;
-        if !inode.metadata()?.file_type.eq(&vfs::FileType::BlockDevice) {
-            return Err(SystemError::ENOTBLK);
-        }
-
-        let disk = inode.dname()?;
-
-        if let Some(gendisk) = try_find_gendisk(disk.0.as_str()) {
-            return Ok(Self { gendisk });
-        }
-        Err(SystemError::ENOENT)
+        let gendisk = find_source_gendisk(path)?;
+        Ok(Self { gendisk })
     }
 }
 
@@ -55,9 +43,11 @@ impl MountableFileSystem for FATFileSystem {
         Ok(fs)
     }
     fn make_mount_data(
-        _raw_data: Option<&str>,
+        raw_data: Option<&str>,
         source: &str,
     ) -> Result<Option<Arc<dyn FileSystemMakerData + 'static>>, SystemError> {
+        // 暂不支持任何挂载选项
+        MountOptions::parse("vfat", raw_data, &[])?.warn_unknown("vfat");
         let mount_data = FatMountData::from_source(source).map_err(|e| {
             log::error!(
                 "Failed to create FAT mount data from source '{}': {:?}",
@@ -68,6 +58,10 @@ impl MountableFileSystem for FATFileSystem {
         })?;
         Ok(Some(Arc::new(mount_data)))
     }
+
+    fn probe(gendisk: &Arc<GenDisk>) -> bool {
+        FATFileSystem::probe(gendisk)
+    }
 }
 
 register_mountable_fs!(FATFileSystem, FATFSMAKER, "vfat");
@@ -0,0 +1,295 @@
+//! FAT文件系统的启动自检（需要开启`fatfs-selftest`特性）
+//!
+//! [`FatTestVolume`]在内存中创建镜像文件，绑定到一个空闲的loop设备上并格式化，
+//! 供其他文件系统测试在启动时得到一个全新的FAT卷，而不需要在宿主机上制作镜像。
+//!
+//! 自检会依次创建FAT12/16/32卷，测试目录的创建、遍历、删除以及文件读写。
+
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
+use log::{error, info};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    driver::{
+        base::block::{
+            block_device::{BlockDevice, LBA_SIZE},
+            gendisk::GenDisk,
+            manager::block_dev_manager,
+        },
+        block::loop_device::LoopDevice,
+    },
+    filesystem::{
+        ramfs::RamFS,
+        vfs::{
+            file::FileFlags, kernel_file::KernelFile, produce_fs, FileSystem, FileType, IndexNode,
+            InodeMode,
+        },
+    },
+    init::initcall::INITCALL_LATE,
+};
+
+use super::{
+    bpb::{BiosParameterBlock, FATType, FatFormatOptions, FatFormatType},
+    fs::FATFileSystem,
+};
+
+/// 查找空闲loop设备时尝试的最大编号
+const MAX_LOOP_PROBE: usize = 256;
+
+/// 格式化好的测试用FAT卷，drop时解除loop设备的绑定
+pub struct FatTestVolume {
+    loop_dev: Arc<dyn BlockDevice>,
+    gendisk: Arc<GenDisk>,
+    fat_type: FatFormatType,
+    /// loop设备在devfs中的路径
+    dev_path: String,
+    /// 镜像文件所在的内存文件系统
+    _backing_fs: Arc<RamFS>,
+}
+
+impl FatTestVolume {
+    /// ## 创建一个大小为`size`字节的FAT卷
+    ///
+    /// ## 参数
+    ///
+    /// - `size`: 卷的大小，向下对齐到扇区
+    /// - `opts`: 格式化参数
+    pub fn new(size: usize, opts: &FatFormatOptions) -> Result<Self, SystemError> {
+        let backing_fs = RamFS::new();
+        let image = backing_fs.root_inode().create(
+            "fat.img",
+            FileType::File,
+            InodeMode::from_bits_truncate(0o600),
+        )?;
+        image.resize(size / LBA_SIZE * LBA_SIZE)?;
+
+        let (loop_dev, gendisk, dev_path) = Self::bind_free_loop(image)?;
+        let fat_type = match BiosParameterBlock::format(&gendisk, opts) {
+            Ok(fat_type) => fat_type,
+            Err(e) => {
+                Self::unbind(&loop_dev);
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            loop_dev,
+            gendisk,
+            fat_type,
+            dev_path,
+            _backing_fs: backing_fs,
+        })
+    }
+
+    /// 把镜像文件绑定到第一个未使用的loop设备上
+    fn bind_free_loop(
+        image: Arc<dyn IndexNode>,
+    ) -> Result<(Arc<dyn BlockDevice>, Arc<GenDisk>, String), SystemError> {
+        let file = Arc::new(KernelFile::from_inode(image, FileFlags::O_RDWR)?);
+        for id in 0..MAX_LOOP_PROBE {
+            let gendisk = match block_dev_manager().lookup_gendisk_by_path(&format!("loop{}", id)) {
+                Some(gendisk) => gendisk,
+                None => continue,
+            };
+            let bdev = gendisk.block_device();
+            let loop_dev = match bdev.as_any_ref().downcast_ref::<LoopDevice>() {
+                Some(loop_dev) => loop_dev,
+                None => continue,
+            };
+            // 设备可能刚好被别人绑定，此时继续尝试下一个
+            if loop_dev.bind_file(file.clone(), false).is_ok() {
+                return Ok((bdev, gendisk, format!("/dev/loop{}", id)));
+            }
+        }
+        Err(SystemError::ENODEV)
+    }
+
+    fn unbind(bdev: &Arc<dyn BlockDevice>) {
+        if let Some(loop_dev) = bdev.as_any_ref().downcast_ref::<LoopDevice>() {
+            let _ = loop_dev.clear_file();
+        }
+    }
+
+    pub fn gendisk(&self) -> &Arc<GenDisk> {
+        &self.gendisk
+    }
+
+    pub fn fat_type(&self) -> FatFormatType {
+        self.fat_type
+    }
+
+    pub fn dev_path(&self) -> &str {
+        &self.dev_path
+    }
+
+    /// ## 挂载该卷
+    pub fn mount(&self) -> Result<Arc<FATFileSystem>, SystemError> {
+        FATFileSystem::new(self.gendisk.clone())
+    }
+}
+
+impl Drop for FatTestVolume {
+    fn drop(&mut self) {
+        Self::unbind(&self.loop_dev);
+    }
+}
+
+fn check(cond: bool, what: &str) -> Result<(), SystemError> {
+    if !cond {
+        error!("fat selftest: {}", what);
+        return Err(SystemError::EIO);
+    }
+    Ok(())
+}
+
+fn write_file(inode: &Arc<dyn IndexNode>, offset: usize, buf: &[u8]) -> Result<(), SystemError> {
+    let n = inode.kernel_write_at(offset, buf)?;
+    check(n == buf.len(), "short write")
+}
+
+fn read_file(
+    inode: &Arc<dyn IndexNode>,
+    offset: usize,
+    len: usize,
+) -> Result<Vec<u8>, SystemError> {
+    let mut buf = vec![0u8; len];
+    let n = inode.kernel_read_at(offset, &mut buf)?;
+    buf.truncate(n);
+    Ok(buf)
+}
+
+/// 在根目录下测试目录和文件操作
+fn exercise(fs: &Arc<FATFileSystem>) -> Result<(), SystemError> {
+    let root = fs.root_inode();
+    let mode = InodeMode::from_bits_truncate(0o755);
+
+    let dir = root.create("testdir", FileType::Dir, mode)?;
+    // 创建足够多的目录项，使目录跨越多个簇（FAT12/16的根目录则是固定大小的区域）
+    let names: Vec<String> = (0..64).map(|i| format!("file_{:02}.txt", i)).collect();
+    for name in &names {
+        dir.create(name, FileType::File, mode)?;
+    }
+    let long_name = "a directory entry with a rather long name";
+    dir.create(long_name, FileType::Dir, mode)?;
+
+    // 遍历目录
+    let entries = dir.list()?;
+    for name in names.iter().map(|s| s.as_str()).chain([long_name]) {
+        check(
+            entries.iter().any(|e| e == name),
+            &format!("readdir is missing '{}'", name),
+        )?;
+    }
+    check(
+        root.list()?.iter().any(|e| e == "testdir"),
+        "readdir of root is missing 'testdir'",
+    )?;
+
+    // 跨簇写入并读回
+    let file = dir.find(&names[0])?;
+    let pattern: Vec<u8> = (0..3 * fs.bytes_per_cluster() as usize + 123)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    write_file(&file, 0, &pattern)?;
+    check(
+        read_file(&file, 0, pattern.len())? == pattern,
+        "data read back does not match",
+    )?;
+    check(
+        dir.find(&names[0])?.metadata()?.size as usize == pattern.len(),
+        "file size mismatch",
+    )?;
+
+    // 删除
+    for name in &names[1..] {
+        dir.unlink(name)?;
+    }
+    dir.rmdir(long_name)?;
+    let entries = dir.list()?;
+    check(
+        !entries.iter().any(|e| e == &names[1] || e == long_name),
+        "deleted entries are still listed",
+    )?;
+    check(
+        root.rmdir("testdir") == Err(SystemError::ENOTEMPTY),
+        "rmdir of a non-empty directory succeeded",
+    )?;
+    dir.unlink(&names[0])?;
+    root.rmdir("testdir")?;
+    check(
+        root.find("testdir").err() == Some(SystemError::ENOENT),
+        "removed directory can still be found",
+    )?;
+
+    Ok(())
+}
+
+fn run_one(name: &str, size: usize, opts: FatFormatOptions) -> Result<(), SystemError> {
+    let volume = FatTestVolume::new(size, &opts)?;
+    if let Some(expected) = opts.fat_type {
+        check(volume.fat_type() == expected, "unexpected FAT type")?;
+    }
+
+    // FAT的类型由簇的数量识别，应当与格式化时选择的类型一致
+    let detected = match BiosParameterBlock::new(volume.gendisk())?.fat_type {
+        FATType::FAT12(_) => FatFormatType::Fat12,
+        FATType::FAT16(_) => FatFormatType::Fat16,
+        FATType::FAT32(_) => FatFormatType::Fat32,
+    };
+    check(detected == volume.fat_type(), "FAT type detected wrongly")?;
+    let fs = produce_fs("auto", None, volume.dev_path())?;
+    check(
+        fs.as_any_ref().is::<FATFileSystem>(),
+        "auto mount did not choose vfat",
+    )?;
+    drop(fs);
+
+    let fs = volume.mount()?;
+    exercise(&fs)?;
+    drop(fs);
+
+    // 重新挂载，确认修改已经写回到磁盘上
+    let fs = volume.mount()?;
+    check(
+        fs.root_inode().find("testdir").is_err(),
+        "changes were not persisted across remount",
+    )?;
+    info!(
+        "fat selftest: {} on {} ({} sectors) passed",
+        name,
+        volume.gendisk().symlink_name(),
+        volume.gendisk().nr_sectors()
+    );
+    Ok(())
+}
+
+#[unified_init(INITCALL_LATE)]
+fn fat_selftest() -> Result<(), SystemError> {
+    let cases = [
+        ("FAT12", 2 << 20, FatFormatType::Fat12, None),
+        ("FAT16", 16 << 20, FatFormatType::Fat16, None),
+        // FAT32至少需要65525个簇，使用最小的簇以减少镜像的大小
+        ("FAT32", 34 << 20, FatFormatType::Fat32, Some(1)),
+    ];
+    let mut failed = 0;
+    for (name, size, fat_type, sectors_per_cluster) in cases {
+        let opts = FatFormatOptions {
+            fat_type: Some(fat_type),
+            sectors_per_cluster,
+            volume_label: *b"SELFTEST   ",
+            ..Default::default()
+        };
+        if let Err(e) = run_one(name, size, opts) {
+            error!("fat selftest: {} failed: {:?}", name, e);
+            failed += 1;
+        }
+    }
+    if failed != 0 {
+        error!("fat selftest: {} case(s) failed", failed);
+    } else {
+        info!("fat selftest: all cases passed");
+    }
+    // 自检失败不应阻止系统启动
+    Ok(())
+}
@@ -2,6 +2,7 @@ use core::sync::atomic::{AtomicU32, Ordering};
 
 use alloc::sync::Arc;
 
+use crate::filesystem::vfs::mount::MountRef;
 use crate::filesystem::vfs::IndexNode;
 use crate::filesystem::vfs::InodeMode;
 use crate::libs::rwsem::RwSem;
@@ -10,13 +11,19 @@ use crate::process::ProcessManager;
 struct PathContext {
     root: Arc<dyn IndexNode>,
     pwd: Arc<dyn IndexNode>,
+    /// 当前目录和根目录所在的挂载在进程切换目录之前不能被卸载
+    root_mnt: MountRef,
+    pwd_mnt: MountRef,
 }
 
 impl PathContext {
     pub fn new() -> Self {
+        let root = ProcessManager::current_mntns().root_inode();
         Self {
-            root: ProcessManager::current_mntns().root_inode(),
-            pwd: ProcessManager::current_mntns().root_inode(),
+            root_mnt: MountRef::new(&root),
+            pwd_mnt: MountRef::new(&root),
+            pwd: root.clone(),
+            root,
         }
     }
 }
@@ -66,11 +73,17 @@ impl FsStruct {
     }
 
     pub fn set_root(&self, inode: Arc<dyn IndexNode>) {
-        self.path_context.write().root = inode;
+        let mnt = MountRef::new(&inode);
+        let mut ctx = self.path_context.write();
+        ctx.root = inode;
+        ctx.root_mnt = mnt;
     }
 
     pub fn set_pwd(&self, inode: Arc<dyn IndexNode>) {
-        self.path_context.write().pwd = inode;
+        let mnt = MountRef::new(&inode);
+        let mut ctx = self.path_context.write();
+        ctx.pwd = inode;
+        ctx.pwd_mnt = mnt;
     }
 
     pub fn pwd(&self) -> Arc<dyn IndexNode> {
@@ -24,11 +24,12 @@ use super::protocol::{
     FuseInterruptIn, FuseOutHeader, FuseWriteIn, FUSE_ABORT_ERROR, FUSE_ASYNC_DIO, FUSE_ASYNC_READ,
     FUSE_ATOMIC_O_TRUNC, FUSE_AUTO_INVAL_DATA, FUSE_BIG_WRITES, FUSE_DESTROY, FUSE_DONT_MASK,
     FUSE_DO_READDIRPLUS, FUSE_EXPLICIT_INVAL_DATA, FUSE_EXPORT_SUPPORT, FUSE_FLUSH, FUSE_FORGET,
-    FUSE_HANDLE_KILLPRIV, FUSE_INIT, FUSE_INIT_EXT, FUSE_INTERRUPT, FUSE_KERNEL_MINOR_VERSION,
-    FUSE_KERNEL_VERSION, FUSE_LOOKUP, FUSE_MAX_PAGES, FUSE_MIN_READ_BUFFER, FUSE_NOTIFY_DELETE,
-    FUSE_NOTIFY_INVAL_ENTRY, FUSE_NOTIFY_INVAL_INODE, FUSE_NOTIFY_POLL, FUSE_NOTIFY_RETRIEVE,
-    FUSE_NOTIFY_STORE, FUSE_NO_OPENDIR_SUPPORT, FUSE_NO_OPEN_SUPPORT, FUSE_PARALLEL_DIROPS,
-    FUSE_POSIX_ACL, FUSE_POSIX_LOCKS, FUSE_READDIRPLUS_AUTO, FUSE_WRITEBACK_CACHE,
+    FUSE_GETXATTR, FUSE_HANDLE_KILLPRIV, FUSE_INIT, FUSE_INIT_EXT, FUSE_INTERRUPT,
+    FUSE_KERNEL_MINOR_VERSION, FUSE_KERNEL_VERSION, FUSE_LISTXATTR, FUSE_LOOKUP, FUSE_MAX_PAGES,
+    FUSE_MIN_READ_BUFFER, FUSE_NOTIFY_DELETE, FUSE_NOTIFY_INVAL_ENTRY, FUSE_NOTIFY_INVAL_INODE,
+    FUSE_NOTIFY_POLL, FUSE_NOTIFY_RETRIEVE, FUSE_NOTIFY_STORE, FUSE_NO_OPENDIR_SUPPORT,
+    FUSE_NO_OPEN_SUPPORT, FUSE_PARALLEL_DIROPS, FUSE_POSIX_ACL, FUSE_POSIX_LOCKS,
+    FUSE_READDIRPLUS_AUTO, FUSE_REMOVEXATTR, FUSE_SETXATTR, FUSE_WRITEBACK_CACHE,
 };
 
 fn wait_with_recheck<T, F>(waitq: &WaitQueue, mut check: F) -> Result<T, SystemError>
@@ -147,6 +148,7 @@ struct FuseConnInner {
     no_open: bool,
     no_opendir: bool,
     no_readdirplus: bool,
+    no_xattr: [bool; 4],
     max_write_cap: usize,
     pending: VecDeque<Arc<FuseRequest>>,
     processing: BTreeMap<u64, Arc<FusePendingState>>,
@@ -195,6 +197,7 @@ impl FuseConn {
                 no_open: false,
                 no_opendir: false,
                 no_readdirplus: false,
+                no_xattr: [false; 4],
                 max_write_cap,
                 pending: VecDeque::new(),
                 processing: BTreeMap::new(),
@@ -293,6 +296,27 @@ impl FuseConn {
         g.no_readdirplus = true;
     }
 
+    fn xattr_slot(opcode: u32) -> Option<usize> {
+        match opcode {
+            FUSE_SETXATTR => Some(0),
+            FUSE_GETXATTR => Some(1),
+            FUSE_LISTXATTR => Some(2),
+            FUSE_REMOVEXATTR => Some(3),
+            _ => None,
+        }
+    }
+
+    /// 用户态文件系统对这个扩展属性操作返回过ENOSYS
+    pub fn xattr_unsupported(&self, opcode: u32) -> bool {
+        Self::xattr_slot(opcode).is_some_and(|i| self.inner.lock().no_xattr[i])
+    }
+
+    pub fn mark_xattr_unsupported(&self, opcode: u32) {
+        if let Some(i) = Self::xattr_slot(opcode) {
+            self.inner.lock().no_xattr[i] = true;
+        }
+    }
+
     fn alloc_unique(&self) -> u64 {
         self.next_unique.fetch_add(2, Ordering::Relaxed)
     }
@@ -865,6 +889,12 @@ impl FuseConn {
             (FUSE_LOOKUP, Some(SystemError::ENOENT))
                 | (FUSE_FLUSH, Some(SystemError::ENOSYS))
                 | (FUSE_INTERRUPT, Some(SystemError::EAGAIN_OR_EWOULDBLOCK))
+                | (FUSE_GETXATTR, Some(SystemError::ENODATA))
+                | (FUSE_REMOVEXATTR, Some(SystemError::ENODATA))
+                | (
+                    FUSE_SETXATTR | FUSE_GETXATTR | FUSE_LISTXATTR | FUSE_REMOVEXATTR,
+                    Some(SystemError::ENOSYS)
+                )
         )
     }
 
@@ -9,7 +9,7 @@ use system_error::SystemError;
 use crate::{
     driver::base::device::device_number::DeviceNumber,
     filesystem::{
-        devfs::{DevFS, DeviceINode, LockedDevFSInode},
+        devfs::{DevFS, DevNodeAttr, DeviceINode, LockedDevFSInode},
         epoll::EPollItem,
         vfs::{
             file::FileFlags, vcore::generate_inode_id, FilePrivateData, FileSystem, FileType,
@@ -34,6 +34,8 @@ pub struct FuseDevInode {
     fs: Weak<DevFS>,
     parent: Weak<LockedDevFSInode>,
     metadata: Metadata,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
 }
 
 #[derive(Debug)]
@@ -45,6 +47,7 @@ impl LockedFuseDevInode {
             self_ref: Weak::default(),
             fs: Weak::default(),
             parent: Weak::default(),
+            attr: DevNodeAttr::new(InodeMode::from_bits_truncate(0o666)),
             metadata: Metadata {
                 dev_id: 1,
                 inode_id: generate_inode_id(),
@@ -198,7 +201,10 @@ impl IndexNode for LockedFuseDevInode {
     }
 
     fn metadata(&self) -> Result<Metadata, SystemError> {
-        Ok(self.0.lock().metadata.clone())
+        let inode = self.0.lock();
+        let mut metadata = inode.metadata.clone();
+        inode.attr.apply(&mut metadata);
+        Ok(metadata)
     }
 
     fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
@@ -207,9 +213,7 @@ impl IndexNode for LockedFuseDevInode {
         inode.metadata.mtime = metadata.mtime;
         inode.metadata.ctime = metadata.ctime;
         inode.metadata.btime = metadata.btime;
-        inode.metadata.mode = metadata.mode;
-        inode.metadata.uid = metadata.uid;
-        inode.metadata.gid = metadata.gid;
+        inode.attr.update(metadata);
         Ok(())
     }
 
@@ -12,8 +12,8 @@ use crate::time::timekeep::ktime_get_real_ns;
 use crate::{
     driver::base::device::device_number::DeviceNumber,
     filesystem::vfs::{
-        file::FileFlags, permission::PermissionMask, syscall::RenameFlags, FilePrivateData,
-        FileSystem, FileType, IndexNode, InodeFlags, InodeId, InodeMode, Metadata,
+        file::FileFlags, permission::PermissionMask, syscall::RenameFlags, xattr::XATTR_LIST_MAX,
+        FilePrivateData, FileSystem, FileType, IndexNode, InodeFlags, InodeId, InodeMode, Metadata,
     },
     libs::mutex::{Mutex, MutexGuard},
     time::PosixTimeSpec,
@@ -26,14 +26,15 @@ use super::{
     protocol::{
         fuse_pack_struct, fuse_read_struct, FuseAccessIn, FuseAttr, FuseAttrOut, FuseCreateIn,
         FuseDirent, FuseDirentPlus, FuseEntryOut, FuseFlushIn, FuseFsyncIn, FuseGetattrIn,
-        FuseLinkIn, FuseMkdirIn, FuseMknodIn, FuseOpenIn, FuseOpenOut, FuseReadIn, FuseReleaseIn,
-        FuseRename2In, FuseRenameIn, FuseSetattrIn, FuseWriteIn, FuseWriteOut, FATTR_ATIME,
-        FATTR_CTIME, FATTR_GID, FATTR_MODE, FATTR_MTIME, FATTR_SIZE, FATTR_UID, FUSE_ACCESS,
-        FUSE_CREATE, FUSE_FLUSH, FUSE_FSYNC, FUSE_FSYNCDIR, FUSE_FSYNC_FDATASYNC, FUSE_GETATTR,
-        FUSE_LINK, FUSE_LOOKUP, FUSE_MKDIR, FUSE_MKNOD, FUSE_OPEN, FUSE_OPENDIR, FUSE_READ,
-        FUSE_READDIR, FUSE_READDIRPLUS, FUSE_READLINK, FUSE_RELEASE, FUSE_RELEASEDIR, FUSE_RENAME,
-        FUSE_RENAME2, FUSE_RMDIR, FUSE_ROOT_ID, FUSE_SETATTR, FUSE_SYMLINK, FUSE_UNLINK,
-        FUSE_WRITE,
+        FuseGetxattrIn, FuseGetxattrOut, FuseLinkIn, FuseMkdirIn, FuseMknodIn, FuseOpenIn,
+        FuseOpenOut, FuseReadIn, FuseReleaseIn, FuseRename2In, FuseRenameIn, FuseSetattrIn,
+        FuseSetxattrIn, FuseWriteIn, FuseWriteOut, FATTR_ATIME, FATTR_CTIME, FATTR_GID, FATTR_MODE,
+        FATTR_MTIME, FATTR_SIZE, FATTR_UID, FUSE_ACCESS, FUSE_CREATE, FUSE_FLUSH, FUSE_FSYNC,
+        FUSE_FSYNCDIR, FUSE_FSYNC_FDATASYNC, FUSE_GETATTR, FUSE_GETXATTR, FUSE_LINK,
+        FUSE_LISTXATTR, FUSE_LOOKUP, FUSE_MKDIR, FUSE_MKNOD, FUSE_OPEN, FUSE_OPENDIR, FUSE_READ,
+        FUSE_READDIR, FUSE_READDIRPLUS, FUSE_READLINK, FUSE_RELEASE, FUSE_RELEASEDIR,
+        FUSE_REMOVEXATTR, FUSE_RENAME, FUSE_RENAME2, FUSE_RMDIR, FUSE_ROOT_ID, FUSE_SETATTR,
+        FUSE_SETXATTR, FUSE_SYMLINK, FUSE_UNLINK, FUSE_WRITE,
     },
 };
 
@@ -377,6 +378,93 @@ impl FuseNode {
         Ok(())
     }
 
+    /// 通过已经打开的文件句柄`fh`读取数据
+    fn read_with_fh(&self, fh: u64, offset: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let read_in = FuseReadIn {
+            fh,
+            offset: offset as u64,
+            size: buf.len() as u32,
+            read_flags: 0,
+            lock_owner: 0,
+            flags: 0,
+            padding: 0,
+        };
+        let payload = self
+            .conn()
+            .request(FUSE_READ, self.nodeid, fuse_pack_struct(&read_in))?;
+        let n = core::cmp::min(payload.len(), buf.len());
+        buf[..n].copy_from_slice(&payload[..n]);
+        Ok(n)
+    }
+
+    /// 通过已经打开的文件句柄`fh`写入数据，按max_write分块发送
+    fn write_with_fh(&self, fh: u64, offset: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        let len = buf.len();
+        let max_write = self.conn().max_write();
+        let mut total_written = 0usize;
+
+        while total_written < len {
+            let chunk = core::cmp::min(max_write, len - total_written);
+            let chunk_offset = offset
+                .checked_add(total_written)
+                .ok_or(SystemError::EOVERFLOW)?;
+
+            let write_in = FuseWriteIn {
+                fh,
+                offset: chunk_offset as u64,
+                size: chunk as u32,
+                write_flags: 0,
+                lock_owner: 0,
+                flags: 0,
+                padding: 0,
+            };
+            let mut payload_in = Vec::with_capacity(size_of::<FuseWriteIn>() + chunk);
+            payload_in.extend_from_slice(fuse_pack_struct(&write_in));
+            payload_in.extend_from_slice(&buf[total_written..total_written + chunk]);
+            let payload = self.conn().request(FUSE_WRITE, self.nodeid, &payload_in)?;
+            let out: FuseWriteOut = fuse_read_struct(&payload)?;
+            let wrote = core::cmp::min(out.size as usize, chunk);
+            total_written += wrote;
+            if wrote < chunk {
+                break;
+            }
+        }
+
+        Ok(total_written)
+    }
+
+    /// 内核自身读写文件时没有用户打开的文件，临时打开一个文件句柄，用完之后释放
+    fn with_kernel_fh<R>(
+        &self,
+        flags: FileFlags,
+        f: impl FnOnce(u64) -> Result<R, SystemError>,
+    ) -> Result<R, SystemError> {
+        let mut data = FilePrivateData::Unused;
+        self.open_common(FUSE_OPEN, &mut data, &flags)?;
+        let FilePrivateData::Fuse(FuseFilePrivateData::File(p)) = &data else {
+            return Err(SystemError::EBADF);
+        };
+        let ret = f(p.fh);
+        if !p.no_open {
+            let _ = self.release_common(FUSE_RELEASE, p.fh, p.open_flags);
+        }
+        ret
+    }
+
+    /// 发送扩展属性请求，用户态文件系统不支持时返回EOPNOTSUPP
+    fn xattr_request(&self, opcode: u32, payload: &[u8]) -> Result<Vec<u8>, SystemError> {
+        if self.conn.xattr_unsupported(opcode) {
+            return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+        }
+        match self.conn().request(opcode, self.nodeid, payload) {
+            Err(SystemError::ENOSYS) => {
+                self.conn.mark_xattr_unsupported(opcode);
+                Err(SystemError::EOPNOTSUPP_OR_ENOTSUP)
+            }
+            r => r,
+        }
+    }
+
     fn ensure_dir(&self) -> Result<(), SystemError> {
         let md = self.cached_or_fetch_metadata()?;
         if md.file_type != FileType::Dir {
@@ -536,21 +624,7 @@ impl IndexNode for FuseNode {
         let FilePrivateData::Fuse(FuseFilePrivateData::File(p)) = &*data else {
             return Err(SystemError::EBADF);
         };
-        let read_in = FuseReadIn {
-            fh: p.fh,
-            offset: offset as u64,
-            size: len as u32,
-            read_flags: 0,
-            lock_owner: 0,
-            flags: 0,
-            padding: 0,
-        };
-        let payload = self
-            .conn()
-            .request(FUSE_READ, self.nodeid, fuse_pack_struct(&read_in))?;
-        let n = core::cmp::min(payload.len(), len);
-        buf[..n].copy_from_slice(&payload[..n]);
-        Ok(n)
+        self.read_with_fh(p.fh, offset, &mut buf[..len])
     }
 
     fn write_at(
@@ -567,37 +641,24 @@ impl IndexNode for FuseNode {
         let FilePrivateData::Fuse(FuseFilePrivateData::File(p)) = &*data else {
             return Err(SystemError::EBADF);
         };
-        let max_write = self.conn().max_write();
-        let mut total_written = 0usize;
-
-        while total_written < len {
-            let chunk = core::cmp::min(max_write, len - total_written);
-            let chunk_offset = offset
-                .checked_add(total_written)
-                .ok_or(SystemError::EOVERFLOW)?;
+        self.write_with_fh(p.fh, offset, &buf[..len])
+    }
 
-            let write_in = FuseWriteIn {
-                fh: p.fh,
-                offset: chunk_offset as u64,
-                size: chunk as u32,
-                write_flags: 0,
-                lock_owner: 0,
-                flags: 0,
-                padding: 0,
-            };
-            let mut payload_in = Vec::with_capacity(size_of::<FuseWriteIn>() + chunk);
-            payload_in.extend_from_slice(fuse_pack_struct(&write_in));
-            payload_in.extend_from_slice(&buf[total_written..total_written + chunk]);
-            let payload = self.conn().request(FUSE_WRITE, self.nodeid, &payload_in)?;
-            let out: FuseWriteOut = fuse_read_struct(&payload)?;
-            let wrote = core::cmp::min(out.size as usize, chunk);
-            total_written += wrote;
-            if wrote < chunk {
-                break;
-            }
+    fn kernel_read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let md = self.cached_or_fetch_metadata()?;
+        if md.file_type == FileType::SymLink {
+            let data = Mutex::new(FilePrivateData::Unused);
+            return self.read_at(offset, buf.len(), buf, data.lock());
         }
+        self.ensure_regular()?;
+        self.with_kernel_fh(FileFlags::O_RDONLY, |fh| self.read_with_fh(fh, offset, buf))
+    }
 
-        Ok(total_written)
+    fn kernel_write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        self.ensure_regular()?;
+        self.with_kernel_fh(FileFlags::O_WRONLY, |fh| {
+            self.write_with_fh(fh, offset, buf)
+        })
     }
 
     fn metadata(&self) -> Result<Metadata, SystemError> {
@@ -974,6 +1035,57 @@ impl IndexNode for FuseNode {
         Ok(())
     }
 
+    fn getxattr(&self, name: &str, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let inarg = FuseGetxattrIn {
+            size: buf.len() as u32,
+            padding: 0,
+        };
+        let payload = self.xattr_request(
+            FUSE_GETXATTR,
+            &Self::pack_struct_and_name_payload(&inarg, name),
+        )?;
+        // size为0时只返回属性值的长度
+        if buf.is_empty() {
+            let out: FuseGetxattrOut = fuse_read_struct(&payload)?;
+            return Ok(out.size as usize);
+        }
+        if payload.len() > buf.len() {
+            return Err(SystemError::ERANGE);
+        }
+        buf[..payload.len()].copy_from_slice(&payload);
+        Ok(payload.len())
+    }
+
+    fn setxattr(&self, name: &str, value: &[u8]) -> Result<usize, SystemError> {
+        let inarg = FuseSetxattrIn {
+            size: value.len() as u32,
+            flags: 0,
+        };
+        let mut payload = Self::pack_struct_and_name_payload(&inarg, name);
+        payload.extend_from_slice(value);
+        self.xattr_request(FUSE_SETXATTR, &payload)?;
+        Ok(0)
+    }
+
+    fn listxattr(&self) -> Result<Vec<String>, SystemError> {
+        let inarg = FuseGetxattrIn {
+            size: XATTR_LIST_MAX as u32,
+            padding: 0,
+        };
+        let payload = self.xattr_request(FUSE_LISTXATTR, fuse_pack_struct(&inarg))?;
+        // 属性名以'\0'分隔
+        Ok(payload
+            .split(|&c| c == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect())
+    }
+
+    fn removexattr(&self, name: &str) -> Result<(), SystemError> {
+        self.xattr_request(FUSE_REMOVEXATTR, &Self::pack_name_payload(name))?;
+        Ok(())
+    }
+
     fn absolute_path(&self) -> Result<String, SystemError> {
         Ok(format!("fuse:{}", self.nodeid))
     }
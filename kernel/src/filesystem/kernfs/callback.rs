@@ -2,7 +2,7 @@ use super::KernFSInode;
 use crate::filesystem::{sysfs::SysFSKernPrivateData, vfs::PollStatus};
 use crate::libs::mutex::MutexGuard;
 use crate::tracepoint::{TraceCmdLineCacheSnapshot, TracePipeSnapshot, TracePointInfo};
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc, vec::Vec};
 use core::fmt::Debug;
 use system_error::SystemError;
 
@@ -87,6 +87,13 @@ pub enum KernInodePrivateData {
     DebugFS(Arc<TracePointInfo>),
     TracePipe(TracePipeSnapshot),
     TraceSavedCmdlines(TraceCmdLineCacheSnapshot),
+    /// 打开时生成的页表转储，参见`crate::debug::page_tables`
+    PageTableDump(String),
+    /// 打开时生成的textui屏幕快照，参见`crate::debug::textui`
+    TextuiSnapshot(Vec<u8>),
+    /// 打开时生成的分配点统计，参见`crate::debug::allocinfo`
+    #[cfg(feature = "alloc_profiling")]
+    AllocInfo(String),
 }
 
 impl KernInodePrivateData {
@@ -2,17 +2,21 @@ pub mod devfs;
 pub mod devpts;
 pub mod epoll;
 pub mod eventfd;
+pub mod ext2;
 pub mod ext4;
 pub mod fat;
 pub mod fs;
 pub mod fuse;
 pub mod kernfs;
 pub mod mbr;
+pub mod notify;
 pub mod overlayfs;
 pub mod page_cache;
 pub mod poll;
 pub mod procfs;
+pub mod pstore;
 pub mod ramfs;
+pub mod smb;
 pub mod sysfs;
 pub mod tmpfs;
 pub mod vfs;
@@ -0,0 +1,532 @@
+//! inotify
+//!
+//! 每个inotify实例是一个伪文件，保存着一组监视和一个事件队列。
+//! 事件由[`super`]中的`fsnotify_*`函数产生，用户通过read(2)按Linux的`struct inotify_event`格式读出。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/fs/notify/inotify/
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    filesystem::{
+        epoll::{
+            event_poll::{EventPoll, LockedEPItemLinkedList},
+            EPollEventType, EPollItem,
+        },
+        vfs::{
+            file::FileFlags, FilePrivateData, FileSystem, FileType, FsInfo, IndexNode, InodeMode,
+            Magic, Metadata, PollableInode, SuperBlock,
+        },
+    },
+    libs::{mutex::MutexGuard, spinlock::SpinLock, wait_queue::WaitQueue},
+    mm::MemoryManagementArch,
+    process::ProcessManager,
+    syscall::user_access::UserBufferWriter,
+};
+
+use super::{register_watch, unregister_watch, WatchKey};
+
+/// 每个实例最多排队的事件数，超出后丢弃新事件，并在队尾放入一个`IN_Q_OVERFLOW`事件
+pub const INOTIFY_MAX_QUEUED_EVENTS: usize = 16384;
+/// 每个实例最多的监视数
+pub const INOTIFY_MAX_WATCHES: usize = 8192;
+
+const FIONREAD: u32 = 0x541B;
+
+bitflags! {
+    /// inotify事件和inotify_add_watch(2)的标志位
+    pub struct InotifyMask: u32 {
+        /// 文件被读取
+        const IN_ACCESS = 0x00000001;
+        /// 文件被修改
+        const IN_MODIFY = 0x00000002;
+        /// 元数据被修改
+        const IN_ATTRIB = 0x00000004;
+        /// 以可写方式打开的文件被关闭
+        const IN_CLOSE_WRITE = 0x00000008;
+        /// 以只读方式打开的文件被关闭
+        const IN_CLOSE_NOWRITE = 0x00000010;
+        /// 文件被打开
+        const IN_OPEN = 0x00000020;
+        /// 文件从被监视的目录中移出
+        const IN_MOVED_FROM = 0x00000040;
+        /// 文件被移入被监视的目录
+        const IN_MOVED_TO = 0x00000080;
+        /// 在被监视的目录中创建了文件
+        const IN_CREATE = 0x00000100;
+        /// 从被监视的目录中删除了文件
+        const IN_DELETE = 0x00000200;
+        /// 被监视的文件或目录自身被删除
+        const IN_DELETE_SELF = 0x00000400;
+        /// 被监视的文件或目录自身被移动
+        const IN_MOVE_SELF = 0x00000800;
+
+        /// 被监视的对象所在的文件系统被卸载
+        const IN_UNMOUNT = 0x00002000;
+        /// 事件队列溢出
+        const IN_Q_OVERFLOW = 0x00004000;
+        /// 监视已经被移除
+        const IN_IGNORED = 0x00008000;
+
+        /// 只在路径是目录时才添加监视
+        const IN_ONLYDIR = 0x01000000;
+        /// 不跟随路径中最后一个符号链接
+        const IN_DONT_FOLLOW = 0x02000000;
+        /// 不再报告已经被删除的子项的事件
+        const IN_EXCL_UNLINK = 0x04000000;
+        /// 只创建新的监视，监视已经存在时返回EEXIST
+        const IN_MASK_CREATE = 0x10000000;
+        /// 把新的掩码合并到已有的监视中，而不是替换
+        const IN_MASK_ADD = 0x20000000;
+        /// 事件的对象是目录
+        const IN_ISDIR = 0x40000000;
+        /// 只报告一次事件，之后移除监视
+        const IN_ONESHOT = 0x80000000;
+
+        const IN_CLOSE = Self::IN_CLOSE_WRITE.bits | Self::IN_CLOSE_NOWRITE.bits;
+        const IN_MOVE = Self::IN_MOVED_FROM.bits | Self::IN_MOVED_TO.bits;
+        /// 所有可以监视的事件
+        const IN_ALL_EVENTS = Self::IN_ACCESS.bits
+            | Self::IN_MODIFY.bits
+            | Self::IN_ATTRIB.bits
+            | Self::IN_CLOSE.bits
+            | Self::IN_OPEN.bits
+            | Self::IN_MOVE.bits
+            | Self::IN_CREATE.bits
+            | Self::IN_DELETE.bits
+            | Self::IN_DELETE_SELF.bits
+            | Self::IN_MOVE_SELF.bits;
+    }
+}
+
+bitflags! {
+    /// inotify_init1(2)的标志位
+    pub struct InotifyInitFlags: u32 {
+        const IN_NONBLOCK = FileFlags::O_NONBLOCK.bits();
+        const IN_CLOEXEC = FileFlags::O_CLOEXEC.bits();
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref INOTIFY_FS: Arc<InotifyFs> = Arc::new(InotifyFs);
+}
+
+/// inotify实例所在的伪文件系统
+#[derive(Debug)]
+pub struct InotifyFs;
+
+impl FileSystem for InotifyFs {
+    fn root_inode(&self) -> Arc<dyn IndexNode> {
+        // inotify实例不会被挂载，这里返回一个空的实例作为占位符
+        InotifyInode::new(false)
+    }
+
+    fn info(&self) -> FsInfo {
+        FsInfo {
+            blk_dev_id: 0,
+            max_name_len: 255,
+        }
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "inotifyfs"
+    }
+
+    fn super_block(&self) -> SuperBlock {
+        SuperBlock::new(Magic::ANON_INODE_MAGIC, MMArch::PAGE_SIZE as u64, 255)
+    }
+}
+
+/// 一个排队中的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InotifyEvent {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    name: Option<String>,
+}
+
+impl InotifyEvent {
+    /// `struct inotify_event`头部的大小
+    const HEADER_SIZE: usize = 16;
+
+    /// 名字所占的字节数：包含结尾的0，并补齐到头部大小的整数倍
+    fn name_len(&self) -> usize {
+        match &self.name {
+            Some(name) => (name.len() + 1).next_multiple_of(Self::HEADER_SIZE),
+            None => 0,
+        }
+    }
+
+    /// 读出时所占的字节数
+    fn size(&self) -> usize {
+        Self::HEADER_SIZE + self.name_len()
+    }
+
+    fn write_to(&self, buf: &mut [u8]) {
+        let name_len = self.name_len();
+        buf[0..4].copy_from_slice(&self.wd.to_ne_bytes());
+        buf[4..8].copy_from_slice(&self.mask.to_ne_bytes());
+        buf[8..12].copy_from_slice(&self.cookie.to_ne_bytes());
+        buf[12..16].copy_from_slice(&(name_len as u32).to_ne_bytes());
+        let name_buf = &mut buf[Self::HEADER_SIZE..Self::HEADER_SIZE + name_len];
+        name_buf.fill(0);
+        if let Some(name) = &self.name {
+            name_buf[..name.len()].copy_from_slice(name.as_bytes());
+        }
+    }
+}
+
+#[derive(Debug)]
+struct InotifyWatch {
+    key: WatchKey,
+    mask: InotifyMask,
+}
+
+#[derive(Debug)]
+struct InotifyInner {
+    events: VecDeque<InotifyEvent>,
+    watches: BTreeMap<i32, InotifyWatch>,
+    /// 下一个分配的监视描述符
+    next_wd: i32,
+}
+
+impl InotifyInner {
+    /// 把事件放入队列
+    ///
+    /// 与队尾的事件完全相同时合并；队列已满时丢弃事件，并在队尾放入一个`IN_Q_OVERFLOW`事件
+    fn queue_event(&mut self, event: InotifyEvent) {
+        if self.events.back() == Some(&event) {
+            return;
+        }
+        if self.events.len() >= INOTIFY_MAX_QUEUED_EVENTS {
+            let overflow = InotifyEvent {
+                wd: -1,
+                mask: InotifyMask::IN_Q_OVERFLOW.bits(),
+                cookie: 0,
+                name: None,
+            };
+            if self.events.back() != Some(&overflow) {
+                self.events.push_back(overflow);
+            }
+            return;
+        }
+        self.events.push_back(event);
+    }
+
+    fn queue_ignored(&mut self, wd: i32) {
+        self.queue_event(InotifyEvent {
+            wd,
+            mask: InotifyMask::IN_IGNORED.bits(),
+            cookie: 0,
+            name: None,
+        });
+    }
+
+    /// 队列中所有事件读出时的总字节数
+    fn queued_bytes(&self) -> usize {
+        self.events.iter().map(|e| e.size()).sum()
+    }
+}
+
+/// 一个inotify实例
+#[derive(Debug)]
+pub struct InotifyInode {
+    self_ref: Weak<InotifyInode>,
+    inner: SpinLock<InotifyInner>,
+    nonblock: AtomicBool,
+    wait_queue: WaitQueue,
+    epitems: LockedEPItemLinkedList,
+}
+
+impl InotifyInode {
+    pub fn new(nonblock: bool) -> Arc<Self> {
+        Arc::new_cyclic(|self_ref| InotifyInode {
+            self_ref: self_ref.clone(),
+            inner: SpinLock::new(InotifyInner {
+                events: VecDeque::new(),
+                watches: BTreeMap::new(),
+                next_wd: 1,
+            }),
+            nonblock: AtomicBool::new(nonblock),
+            wait_queue: WaitQueue::default(),
+            epitems: LockedEPItemLinkedList::default(),
+        })
+    }
+
+    fn readable(&self) -> bool {
+        !self.inner.lock_irqsave().events.is_empty()
+    }
+
+    /// 有新的事件时唤醒读者
+    fn notify_readers(&self) {
+        self.wait_queue.wakeup_all(None);
+        let pollflag = EPollEventType::EPOLLIN | EPollEventType::EPOLLRDNORM;
+        let _ = EventPoll::wakeup_epoll(&self.epitems, pollflag);
+    }
+
+    /// 添加或修改对`inode`的监视
+    ///
+    /// ## 返回值
+    /// - `Ok(i32)`: 监视描述符。已经监视了这个inode时，返回原有的监视描述符
+    pub fn add_watch(
+        &self,
+        inode: &Arc<dyn IndexNode>,
+        mask: InotifyMask,
+    ) -> Result<i32, SystemError> {
+        let key = WatchKey::of(inode)?;
+        let events = mask & InotifyMask::IN_ALL_EVENTS;
+        let flags = mask & (InotifyMask::IN_ONESHOT | InotifyMask::IN_EXCL_UNLINK);
+
+        let mut inner = self.inner.lock_irqsave();
+        if let Some((&wd, watch)) = inner.watches.iter_mut().find(|(_, w)| w.key == key) {
+            if mask.contains(InotifyMask::IN_MASK_CREATE) {
+                return Err(SystemError::EEXIST);
+            }
+            if mask.contains(InotifyMask::IN_MASK_ADD) {
+                watch.mask |= events | flags;
+            } else {
+                watch.mask = events | flags;
+            }
+            return Ok(wd);
+        }
+
+        if inner.watches.len() >= INOTIFY_MAX_WATCHES {
+            return Err(SystemError::ENOSPC);
+        }
+        let wd = inner.next_wd;
+        inner.next_wd = inner.next_wd.checked_add(1).ok_or(SystemError::ENOSPC)?;
+        inner.watches.insert(
+            wd,
+            InotifyWatch {
+                key,
+                mask: events | flags,
+            },
+        );
+        register_watch(key, self.self_ref.clone(), wd);
+        Ok(wd)
+    }
+
+    /// 移除一个监视，并发送`IN_IGNORED`事件
+    pub fn rm_watch(&self, wd: i32) -> Result<(), SystemError> {
+        let mut inner = self.inner.lock_irqsave();
+        let watch = inner.watches.remove(&wd).ok_or(SystemError::EINVAL)?;
+        unregister_watch(watch.key, &self.self_ref, wd);
+        inner.queue_ignored(wd);
+        drop(inner);
+        self.notify_readers();
+        Ok(())
+    }
+
+    /// 被监视的对象上发生了事件
+    pub(super) fn handle_event(&self, wd: i32, mask: InotifyMask, cookie: u32, name: Option<&str>) {
+        let mut inner = self.inner.lock_irqsave();
+        let Some(watch) = inner.watches.get(&wd) else {
+            return;
+        };
+        if !watch.mask.intersects(mask & InotifyMask::IN_ALL_EVENTS) {
+            return;
+        }
+        let oneshot = watch.mask.contains(InotifyMask::IN_ONESHOT);
+        let key = watch.key;
+        inner.queue_event(InotifyEvent {
+            wd,
+            mask: mask.bits(),
+            cookie,
+            name: name.map(|n| n.to_string()),
+        });
+        if oneshot {
+            inner.watches.remove(&wd);
+            unregister_watch(key, &self.self_ref, wd);
+            inner.queue_ignored(wd);
+        }
+        drop(inner);
+        self.notify_readers();
+    }
+
+    /// 被监视的对象已经被删除，它上面的监视已经从全局表中移除
+    pub(super) fn watch_removed(&self, wd: i32) {
+        let mut inner = self.inner.lock_irqsave();
+        if inner.watches.remove(&wd).is_none() {
+            return;
+        }
+        inner.queue_ignored(wd);
+        drop(inner);
+        self.notify_readers();
+    }
+}
+
+impl PollableInode for InotifyInode {
+    fn poll(&self, _private_data: &FilePrivateData) -> Result<usize, SystemError> {
+        let mut events = EPollEventType::empty();
+        if self.readable() {
+            events |= EPollEventType::EPOLLIN | EPollEventType::EPOLLRDNORM;
+        }
+        Ok(events.bits() as usize)
+    }
+
+    fn add_epitem(
+        &self,
+        epitem: Arc<EPollItem>,
+        _private_data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        self.epitems.lock().push_back(epitem);
+        Ok(())
+    }
+
+    fn remove_epitem(
+        &self,
+        epitem: &Arc<EPollItem>,
+        _private_data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        let mut guard = self.epitems.lock();
+        let len = guard.len();
+        guard.retain(|x| !Arc::ptr_eq(x, epitem));
+        if len != guard.len() {
+            return Ok(());
+        }
+        Err(SystemError::ENOENT)
+    }
+}
+
+impl IndexNode for InotifyInode {
+    fn is_stream(&self) -> bool {
+        true
+    }
+
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _flags: &FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    /// 实例被关闭时移除它所有的监视
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        let mut inner = self.inner.lock_irqsave();
+        for (wd, watch) in core::mem::take(&mut inner.watches) {
+            unregister_watch(watch.key, &self.self_ref, wd);
+        }
+        inner.events.clear();
+        Ok(())
+    }
+
+    /// 读出尽可能多的完整事件
+    ///
+    /// 缓冲区放不下第一个事件时返回EINVAL；队列为空时阻塞，设置了`IN_NONBLOCK`时返回EAGAIN
+    fn read_at(
+        &self,
+        _offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        drop(data);
+        loop {
+            let mut inner = self.inner.lock_irqsave();
+            if let Some(first) = inner.events.front() {
+                if len < first.size() {
+                    return Err(SystemError::EINVAL);
+                }
+                let mut written = 0;
+                while let Some(event) = inner.events.front() {
+                    let size = event.size();
+                    if written + size > len {
+                        break;
+                    }
+                    event.write_to(&mut buf[written..written + size]);
+                    written += size;
+                    inner.events.pop_front();
+                }
+                return Ok(written);
+            }
+            drop(inner);
+
+            if self.nonblock.load(Ordering::Relaxed) {
+                return Err(SystemError::EAGAIN_OR_EWOULDBLOCK);
+            }
+            if ProcessManager::current_pcb().has_pending_signal_fast() {
+                return Err(SystemError::ERESTARTSYS);
+            }
+            wq_wait_event_interruptible!(self.wait_queue, self.readable(), {})?;
+        }
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EINVAL)
+    }
+
+    fn ioctl(
+        &self,
+        cmd: u32,
+        data: usize,
+        _private_data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        match cmd {
+            FIONREAD => {
+                let bytes = self.inner.lock_irqsave().queued_bytes();
+                let mut writer =
+                    UserBufferWriter::new(data as *mut u8, core::mem::size_of::<i32>(), true)?;
+                let to_write = core::cmp::min(bytes, i32::MAX as usize) as i32;
+                writer.buffer_protected(0)?.write_one::<i32>(0, &to_write)?;
+                Ok(0)
+            }
+            _ => Err(SystemError::ENOTTY),
+        }
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        let meta = Metadata {
+            mode: InodeMode::from_bits_truncate(0o600),
+            file_type: FileType::File,
+            ..Default::default()
+        };
+        Ok(meta)
+    }
+
+    fn resize(&self, _len: usize) -> Result<(), SystemError> {
+        Err(SystemError::EINVAL)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        INOTIFY_FS.clone()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        Err(SystemError::ENOTDIR)
+    }
+
+    fn as_pollable_inode(&self) -> Result<&dyn PollableInode, SystemError> {
+        Ok(self)
+    }
+
+    fn absolute_path(&self) -> Result<String, SystemError> {
+        Ok(String::from("anon_inode:inotify"))
+    }
+}
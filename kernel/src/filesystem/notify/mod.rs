@@ -0,0 +1,254 @@
+//! 文件系统事件通知
+//!
+//! VFS在创建、删除、重命名、写入文件之后调用本模块中的`fsnotify_*`函数，
+//! 本模块把事件分发给监视了相关inode的inotify实例。
+//!
+//! 被监视的inode记录在一张全局表中，没有任何监视时，这些函数会直接返回。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/include/linux/fsnotify.h
+
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use system_error::SystemError;
+
+use crate::libs::{casting::DowncastArc, spinlock::SpinLock};
+
+use super::vfs::{mount::MountFSInode, FileType, IndexNode, InodeId};
+use inotify::{InotifyInode, InotifyMask};
+
+pub mod inotify;
+
+/// 被监视的inode的标识
+///
+/// 与flock相同，先剥离MountFSInode包装，使同一个底层inode无论从哪个挂载点访问都对应同一个标识
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct WatchKey {
+    dev_id: usize,
+    inode_id: InodeId,
+}
+
+impl WatchKey {
+    fn of(inode: &Arc<dyn IndexNode>) -> Result<Self, SystemError> {
+        let md = canonical_inode(inode.clone()).metadata()?;
+        Ok(Self {
+            dev_id: md.dev_id,
+            inode_id: md.inode_id,
+        })
+    }
+}
+
+fn canonical_inode(mut inode: Arc<dyn IndexNode>) -> Arc<dyn IndexNode> {
+    loop {
+        match inode.clone().downcast_arc::<MountFSInode>() {
+            Some(mnt_inode) => inode = mnt_inode.underlying_inode(),
+            None => return inode,
+        }
+    }
+}
+
+/// 某个inotify实例中的一个监视
+#[derive(Debug, Clone)]
+struct WatchRef {
+    instance: Weak<InotifyInode>,
+    wd: i32,
+}
+
+/// 被监视的inode -> 监视了它的inotify实例
+static INODE_WATCHES: SpinLock<BTreeMap<WatchKey, Vec<WatchRef>>> = SpinLock::new(BTreeMap::new());
+
+/// 全局的监视数量，为0时所有的通知函数都直接返回
+static NR_WATCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// 用于关联`IN_MOVED_FROM`和`IN_MOVED_TO`事件
+static RENAME_COOKIE: AtomicU32 = AtomicU32::new(0);
+
+fn register_watch(key: WatchKey, instance: Weak<InotifyInode>, wd: i32) {
+    INODE_WATCHES
+        .lock_irqsave()
+        .entry(key)
+        .or_default()
+        .push(WatchRef { instance, wd });
+    NR_WATCHES.fetch_add(1, Ordering::SeqCst);
+}
+
+fn unregister_watch(key: WatchKey, instance: &Weak<InotifyInode>, wd: i32) {
+    let mut guard = INODE_WATCHES.lock_irqsave();
+    let Some(watches) = guard.get_mut(&key) else {
+        return;
+    };
+    let len = watches.len();
+    watches.retain(|w| !(w.wd == wd && Weak::ptr_eq(&w.instance, instance)));
+    NR_WATCHES.fetch_sub(len - watches.len(), Ordering::SeqCst);
+    if watches.is_empty() {
+        guard.remove(&key);
+    }
+}
+
+#[inline(always)]
+fn has_watches() -> bool {
+    NR_WATCHES.load(Ordering::Relaxed) != 0
+}
+
+/// 把事件发送给监视了`key`的所有实例
+fn fsnotify_key(key: WatchKey, mask: InotifyMask, cookie: u32, name: Option<&str>) {
+    let watches = match INODE_WATCHES.lock_irqsave().get(&key) {
+        Some(watches) => watches.clone(),
+        None => return,
+    };
+    for watch in watches {
+        if let Some(instance) = watch.instance.upgrade() {
+            instance.handle_event(watch.wd, mask, cookie, name);
+        }
+    }
+}
+
+fn fsnotify(inode: &Arc<dyn IndexNode>, mask: InotifyMask, cookie: u32, name: Option<&str>) {
+    if let Ok(key) = WatchKey::of(inode) {
+        fsnotify_key(key, mask, cookie, name);
+    }
+}
+
+/// inode已经被删除：发送`mask`事件，然后移除它上面的所有监视，每个监视都会收到`IN_IGNORED`
+fn fsnotify_inode_gone(key: WatchKey, mask: InotifyMask) {
+    fsnotify_key(key, mask, 0, None);
+    let Some(watches) = INODE_WATCHES.lock_irqsave().remove(&key) else {
+        return;
+    };
+    NR_WATCHES.fetch_sub(watches.len(), Ordering::SeqCst);
+    for watch in watches {
+        if let Some(instance) = watch.instance.upgrade() {
+            instance.watch_removed(watch.wd);
+        }
+    }
+}
+
+fn dir_flag(is_dir: bool) -> InotifyMask {
+    if is_dir {
+        InotifyMask::IN_ISDIR
+    } else {
+        InotifyMask::empty()
+    }
+}
+
+/// 被删除的inode的标识
+///
+/// 删除之后，有的文件系统无法再获取inode的元数据，因此需要在删除之前获取
+#[derive(Debug, Clone, Copy)]
+pub struct FsnotifyVictim(Option<WatchKey>);
+
+/// 在删除或覆盖`inode`之前调用，获取它的标识
+pub fn fsnotify_victim(inode: &Arc<dyn IndexNode>) -> FsnotifyVictim {
+    if !has_watches() {
+        return FsnotifyVictim(None);
+    }
+    FsnotifyVictim(WatchKey::of(inode).ok())
+}
+
+/// 在目录`dir`中创建了`name`（包括mkdir、mknod、symlink和link）
+pub fn fsnotify_create(dir: &Arc<dyn IndexNode>, name: &str, is_dir: bool) {
+    if !has_watches() {
+        return;
+    }
+    fsnotify(
+        dir,
+        InotifyMask::IN_CREATE | dir_flag(is_dir),
+        0,
+        Some(name),
+    );
+}
+
+/// 从目录`dir`中删除了`name`
+///
+/// ## 参数
+/// - `victim`: 被删除的inode。目录被删除，或者文件的最后一个硬链接被删除时，
+///   它自身的监视者会收到`IN_DELETE_SELF`，之后这些监视被移除
+/// - `victim_key`: 删除之前取得的`victim`的标识，参见[`fsnotify_victim`]
+pub fn fsnotify_delete(
+    dir: &Arc<dyn IndexNode>,
+    name: &str,
+    victim: &Arc<dyn IndexNode>,
+    victim_key: FsnotifyVictim,
+    is_dir: bool,
+) {
+    if !has_watches() {
+        return;
+    }
+    fsnotify(
+        dir,
+        InotifyMask::IN_DELETE | dir_flag(is_dir),
+        0,
+        Some(name),
+    );
+    if let Some(key) = victim_key.0 {
+        let gone = is_dir || victim.metadata().map(|md| md.nlinks == 0).unwrap_or(true);
+        if gone {
+            fsnotify_inode_gone(key, InotifyMask::IN_DELETE_SELF);
+        }
+    }
+}
+
+/// 把`old_dir`中的`old_name`重命名为`new_dir`中的`new_name`
+///
+/// ## 参数
+/// - `moved`: 被移动的inode
+/// - `replaced`: 被覆盖的目标，为`None`表示目标原本不存在
+pub fn fsnotify_move(
+    old_dir: &Arc<dyn IndexNode>,
+    old_name: &str,
+    new_dir: &Arc<dyn IndexNode>,
+    new_name: &str,
+    moved: &Arc<dyn IndexNode>,
+    replaced: Option<(&Arc<dyn IndexNode>, FsnotifyVictim)>,
+) {
+    if !has_watches() {
+        return;
+    }
+    let is_dir = moved
+        .metadata()
+        .map(|md| md.file_type == FileType::Dir)
+        .unwrap_or(false);
+    let cookie = RENAME_COOKIE.fetch_add(1, Ordering::Relaxed);
+    fsnotify(
+        old_dir,
+        InotifyMask::IN_MOVED_FROM | dir_flag(is_dir),
+        cookie,
+        Some(old_name),
+    );
+    fsnotify(
+        new_dir,
+        InotifyMask::IN_MOVED_TO | dir_flag(is_dir),
+        cookie,
+        Some(new_name),
+    );
+    if let Some((replaced, victim_key)) = replaced {
+        if let Some(key) = victim_key.0 {
+            let gone = is_dir || replaced.metadata().map(|md| md.nlinks == 0).unwrap_or(true);
+            if gone {
+                fsnotify_inode_gone(key, InotifyMask::IN_DELETE_SELF);
+            }
+        }
+    }
+    fsnotify(moved, InotifyMask::IN_MOVE_SELF, 0, None);
+}
+
+/// 文件的内容被修改（write、truncate等）
+///
+/// 除了文件自身的监视者，如果文件系统能够提供它的父目录和文件名，监视了父目录的实例也会收到事件
+pub fn fsnotify_modify(inode: &Arc<dyn IndexNode>) {
+    if !has_watches() {
+        return;
+    }
+    fsnotify(inode, InotifyMask::IN_MODIFY, 0, None);
+
+    let inode = canonical_inode(inode.clone());
+    if let (Ok(parent), Ok(name)) = (inode.parent(), inode.dname()) {
+        if !Arc::ptr_eq(&parent, &inode) && !name.as_ref().is_empty() {
+            fsnotify(&parent, InotifyMask::IN_MODIFY, 0, Some(name.as_ref()));
+        }
+    }
+}
@@ -1,41 +1,104 @@
 use super::OvlInode;
-use crate::{
-    filesystem::vfs::{IndexNode, Metadata},
-    libs::mutex::Mutex,
-};
-use alloc::sync::Arc;
+use crate::filesystem::vfs::{syscall::RenameFlags, FileType, IndexNode, InodeMode, Metadata};
+use alloc::{string::String, sync::Arc};
 use system_error::SystemError;
 
+/// 复制文件内容时每次读写的大小
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
 impl OvlInode {
-    pub fn copy_up(&self) -> Result<(), SystemError> {
-        let mut upper_inode = self.upper_inode.lock();
-        if upper_inode.is_some() {
-            return Ok(());
+    /// 把只存在于下层的文件复制到上层，之后的修改都发生在上层的副本上
+    ///
+    /// 父目录会先被复制上去。副本先在workdir中建好，再整体移动到上层，
+    /// 因此上层里不会出现复制了一半的文件。
+    ///
+    /// ## 返回值
+    /// - `Ok(Arc<dyn IndexNode>)`: 上层的inode
+    /// - `Err(SystemError::EROFS)`: 没有上层，整个overlay是只读的
+    pub fn copy_up(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        if let Some(upper) = self.upper() {
+            return Ok(upper);
         }
 
-        let lower_inode = self.lower_inode.as_ref().ok_or(SystemError::ENOENT)?;
-
-        let metadata = lower_inode.metadata()?;
-        let new_upper_inode = self.create_upper_inode(metadata.clone())?;
-
-        let mut buffer = vec![0u8; metadata.size as usize];
-        let lock = Mutex::new(crate::filesystem::vfs::FilePrivateData::Unused);
-        lower_inode.read_at(0, metadata.size as usize, &mut buffer, lock.lock())?;
+        let fs = self.ovl_fs()?;
+        let workdir = fs.workdir.as_ref().ok_or(SystemError::EROFS)?;
+        // 根目录的上层就是upperdir，能走到这里说明没有上层
+        let parent = self.parent.as_ref().ok_or(SystemError::EROFS)?;
+        let upper_parent = parent.copy_up()?;
 
-        new_upper_inode.write_at(0, metadata.size as usize, &buffer, lock.lock())?;
+        let _guard = fs.copy_up_lock.lock();
+        let mut upper = self.upper.lock();
+        if let Some(upper) = upper.as_ref() {
+            return Ok(upper.clone());
+        }
 
-        *upper_inode = Some(new_upper_inode);
+        let lower = self.lowers.first().ok_or(SystemError::ENOENT)?;
+        let metadata = lower.metadata()?;
+        let tmp_name = fs.alloc_tmp_name();
+        if let Err(e) = copy_to_workdir(workdir, &tmp_name, lower, &metadata) {
+            if metadata.file_type == FileType::Dir {
+                workdir.rmdir(&tmp_name).ok();
+            } else {
+                workdir.unlink(&tmp_name).ok();
+            }
+            return Err(e);
+        }
+        workdir.move_to(
+            &tmp_name,
+            &upper_parent,
+            self.name.as_ref(),
+            RenameFlags::empty(),
+        )?;
 
-        Ok(())
+        let new_upper = upper_parent.find(self.name.as_ref())?;
+        *upper = Some(new_upper.clone());
+        Ok(new_upper)
     }
+}
+
+/// 在workdir中创建`lower`的副本，复制内容和属性
+fn copy_to_workdir(
+    workdir: &Arc<dyn IndexNode>,
+    name: &str,
+    lower: &Arc<dyn IndexNode>,
+    metadata: &Metadata,
+) -> Result<(), SystemError> {
+    let new_inode = match metadata.file_type {
+        FileType::Dir => workdir.create(name, FileType::Dir, metadata.mode)?,
+        FileType::File => {
+            let new_inode = workdir.create(name, FileType::File, metadata.mode)?;
+            let size = metadata.size as usize;
+            let mut buf = vec![0u8; core::cmp::min(size, COPY_CHUNK_SIZE)];
+            let mut offset = 0;
+            while offset < size {
+                let len = core::cmp::min(size - offset, buf.len());
+                let n = lower.kernel_read_at(offset, &mut buf[..len])?;
+                if n == 0 {
+                    break;
+                }
+                new_inode.kernel_write_at(offset, &buf[..n])?;
+                offset += n;
+            }
+            new_inode
+        }
+        FileType::SymLink => {
+            let mut buf = vec![0u8; metadata.size as usize];
+            let n = lower.kernel_read_at(0, &mut buf)?;
+            let target = String::from_utf8(buf[..n].to_vec()).map_err(|_| SystemError::EINVAL)?;
+            workdir.symlink(name, &target)?
+        }
+        t => workdir.mknod(name, metadata.mode | InodeMode::from(t), metadata.raw_dev)?,
+    };
 
-    fn create_upper_inode(&self, metadata: Metadata) -> Result<Arc<dyn IndexNode>, SystemError> {
-        let upper_inode = self.upper_inode.lock();
-        let upper_root_inode = upper_inode
-            .as_ref()
-            .ok_or(SystemError::ENOSYS)?
-            .fs()
-            .root_inode();
-        upper_root_inode.create_with_data(&self.dname()?.0, metadata.file_type, metadata.mode, 0)
+    let mut new_metadata = new_inode.metadata()?;
+    new_metadata.mode = metadata.mode;
+    new_metadata.uid = metadata.uid;
+    new_metadata.gid = metadata.gid;
+    new_metadata.atime = metadata.atime;
+    new_metadata.mtime = metadata.mtime;
+    new_metadata.ctime = metadata.ctime;
+    match new_inode.set_metadata(&new_metadata) {
+        Ok(_) | Err(SystemError::ENOSYS) => Ok(()),
+        Err(e) => Err(e),
     }
 }
@@ -1,32 +1,128 @@
-use alloc::sync::Arc;
+use alloc::{collections::BTreeSet, string::String, sync::Arc, vec::Vec};
+use system_error::SystemError;
 
-use alloc::vec::Vec;
+use crate::filesystem::vfs::{FileType, IndexNode, InodeMode};
 
-use crate::filesystem::vfs::IndexNode;
+use super::WHITEOUT_DEV;
 
-use super::{OvlInode, OvlSuperBlock};
+/// 一个路径在各层中对应的真实inode
 #[derive(Debug)]
 pub struct OvlEntry {
-    numlower: usize, // 下层数量
-    lowerstack: Vec<OvlPath>,
+    pub file_type: FileType,
+    pub upper: Option<Arc<dyn IndexNode>>,
+    /// 下层中的inode，从上到下排列。只有目录会有多于一个下层inode
+    pub lowers: Vec<Arc<dyn IndexNode>>,
 }
 
-impl OvlEntry {
-    pub fn new() -> Self {
-        Self {
-            numlower: 2,
-            lowerstack: Vec::new(),
-        }
+/// 判断真实inode是不是whiteout：设备号为0:0的字符设备
+pub fn is_whiteout(inode: &Arc<dyn IndexNode>) -> Result<bool, SystemError> {
+    let md = inode.metadata()?;
+    Ok(md.file_type == FileType::CharDevice && md.raw_dev == WHITEOUT_DEV)
+}
+
+/// 在上层目录`dir`中创建名为`name`的whiteout，遮住下层的同名文件
+pub fn create_whiteout(dir: &Arc<dyn IndexNode>, name: &str) -> Result<(), SystemError> {
+    dir.mknod(name, InodeMode::S_IFCHR, WHITEOUT_DEV)?;
+    Ok(())
+}
+
+/// 如果上层目录`dir`中的`name`是whiteout，就删除它，给新建的文件让出位置
+pub fn remove_whiteout(dir: &Arc<dyn IndexNode>, name: &str) -> Result<(), SystemError> {
+    match dir.find(name) {
+        Ok(inode) if is_whiteout(&inode)? => dir.unlink(name),
+        Ok(_) => Err(SystemError::EEXIST),
+        Err(SystemError::ENOENT) => Ok(()),
+        Err(e) => Err(e),
     }
 }
-#[derive(Debug)]
-pub struct OvlPath {
-    layer: Arc<OvlLayer>,
-    inode: Arc<dyn IndexNode>,
+
+/// 在一个目录的各层中查找`name`
+///
+/// 上层优先。目录会和下层的同名目录合并，直到遇到whiteout或者非目录为止；
+/// 其它类型的文件遮住下面的所有层。
+///
+/// ## 参数
+/// - `upper_dir`: 目录在上层中的inode
+/// - `lower_dirs`: 目录在下层中的inode，从上到下排列
+/// - `name`: 要查找的名字
+///
+/// ## 返回值
+/// - `Ok(OvlEntry)`: 找到的各层inode
+/// - `Err(SystemError::ENOENT)`: 不存在，或者被whiteout遮住
+pub fn ovl_lookup(
+    upper_dir: Option<&Arc<dyn IndexNode>>,
+    lower_dirs: &[Arc<dyn IndexNode>],
+    name: &str,
+) -> Result<OvlEntry, SystemError> {
+    let mut file_type = None;
+    let mut upper = None;
+    if let Some(dir) = upper_dir {
+        match dir.find(name) {
+            Ok(inode) => {
+                if is_whiteout(&inode)? {
+                    return Err(SystemError::ENOENT);
+                }
+                file_type = Some(inode.metadata()?.file_type);
+                upper = Some(inode);
+            }
+            Err(SystemError::ENOENT) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut lowers = Vec::new();
+    for dir in lower_dirs {
+        if file_type.is_some_and(|t| t != FileType::Dir) {
+            break;
+        }
+        let inode = match dir.find(name) {
+            Ok(inode) => inode,
+            Err(SystemError::ENOENT) => continue,
+            Err(e) => return Err(e),
+        };
+        if is_whiteout(&inode)? {
+            break;
+        }
+        let t = inode.metadata()?.file_type;
+        match file_type {
+            None => file_type = Some(t),
+            Some(FileType::Dir) if t != FileType::Dir => break,
+            _ => {}
+        }
+        lowers.push(inode);
+    }
+
+    Ok(OvlEntry {
+        file_type: file_type.ok_or(SystemError::ENOENT)?,
+        upper,
+        lowers,
+    })
 }
-#[derive(Debug)]
-pub struct OvlLayer {
-    pub mnt: Arc<OvlInode>, // 挂载点
-    pub index: u32,         // 0 是上层读写层，>0 是下层只读层
-    pub fsid: u32,          // 文件系统标识符
+
+/// 合并目录在各层中的目录项
+///
+/// 上层的目录项优先，whiteout遮住下层的同名目录项，它自己也不出现在结果中。
+/// 结果的前两项总是"."和".."
+pub fn ovl_merge_dir(
+    upper_dir: Option<&Arc<dyn IndexNode>>,
+    lower_dirs: &[Arc<dyn IndexNode>],
+) -> Result<Vec<String>, SystemError> {
+    let mut entries = vec![String::from("."), String::from("..")];
+    let mut seen = BTreeSet::new();
+    for dir in upper_dir.into_iter().chain(lower_dirs.iter()) {
+        for name in dir.list()? {
+            if name == "." || name == ".." || !seen.insert(name.clone()) {
+                continue;
+            }
+            let inode = match dir.find(&name) {
+                Ok(inode) => inode,
+                Err(SystemError::ENOENT) => continue,
+                Err(e) => return Err(e),
+            };
+            if !is_whiteout(&inode)? {
+                entries.push(name);
+            }
+        }
+    }
+    Ok(entries)
 }
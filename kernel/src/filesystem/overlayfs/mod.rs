@@ -1,29 +1,43 @@
-#![allow(dead_code, unused_variables, unused_imports)]
+//! overlayfs：把一个可写的上层目录和若干只读的下层目录合并成一棵目录树
+//!
+//! - 查找时上层优先，同名目录会和下层合并
+//! - 删除下层的文件时，在上层创建whiteout(设备号为0:0的字符设备)把它遮住
+//! - 第一次修改下层的文件时，先把它复制到上层(copy-up)
+//!
+//! 挂载参数：`lowerdir=<dir>[:<dir>...],upperdir=<dir>,workdir=<dir>`。
+//! lowerdir中越靠前的目录越靠上；省略upperdir和workdir时整个overlay只读。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/fs/overlayfs/
+
 pub mod copy_up;
 pub mod entry;
 
-use super::ramfs::{LockedRamFSInode, RamFSInode};
+use super::vfs::mount_options::{MountOptionKind, MountOptionSpec, MountOptions};
+use super::vfs::syscall::RenameFlags;
 use super::vfs::FSMAKER;
 use super::vfs::{
-    self, FileSystem, FileType, FsInfo, IndexNode, Metadata, MountableFileSystem, SuperBlock,
+    self, file::FileFlags, utils::DName, FilePrivateData, FileSystem, FileType, FsInfo, IndexNode,
+    InodeMode, Magic, Metadata, MountableFileSystem, SuperBlock,
 };
 use crate::driver::base::device::device_number::DeviceNumber;
 use crate::driver::base::device::device_number::Major;
-use crate::filesystem::vfs::{FileSystemMaker, FileSystemMakerData};
-use crate::libs::mutex::Mutex;
+use crate::filesystem::vfs::FileSystemMakerData;
+use crate::libs::mutex::{Mutex, MutexGuard};
 use crate::process::ProcessManager;
 use crate::register_mountable_fs;
+use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::sync::Weak;
 use alloc::vec::Vec;
-use entry::{OvlEntry, OvlLayer};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use entry::{create_whiteout, is_whiteout, ovl_lookup, ovl_merge_dir, remove_whiteout};
 use linkme::distributed_slice;
 use system_error::SystemError;
 
-const WHITEOUT_MODE: u64 = 0o020000 | 0o600; // whiteout字符设备文件模式与权限
 const WHITEOUT_DEV: DeviceNumber = DeviceNumber::new(Major::UNNAMED_MAJOR, 0); // Whiteout 文件设备号
-const WHITEOUT_FLAG: u64 = 0x1;
+const OVL_WORKDIR_NAME: &str = "work"; // workdir下用于copy-up的临时目录
 
 #[derive(Debug)]
 pub struct OverlayMountData {
@@ -32,29 +46,34 @@ pub struct OverlayMountData {
     work_dir: String,
 }
 
+/// overlayfs认识的挂载选项
+const OVL_MOUNT_OPTIONS: &[MountOptionSpec] = &[
+    MountOptionSpec::new("upperdir", MountOptionKind::Str),
+    MountOptionSpec::new("lowerdir", MountOptionKind::Str),
+    MountOptionSpec::new("workdir", MountOptionKind::Str),
+];
+
 impl OverlayMountData {
     pub fn from_raw(raw_data: Option<&str>) -> Result<Self, SystemError> {
-        if raw_data.is_none() {
-            return Err(SystemError::EINVAL);
-        }
-        let raw_str = raw_data.unwrap();
-        let mut data = OverlayMountData {
-            upper_dir: String::new(),
-            lower_dirs: Vec::new(),
-            work_dir: String::new(),
+        let raw_str = raw_data.ok_or(SystemError::EINVAL)?;
+        let opts = MountOptions::parse("overlay", Some(raw_str), OVL_MOUNT_OPTIONS)?;
+        opts.reject_unknown("overlay")?;
+
+        let data = OverlayMountData {
+            upper_dir: opts.str("upperdir").unwrap_or_default().into(),
+            lower_dirs: opts
+                .str("lowerdir")
+                .unwrap_or_default()
+                .split(':')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.into())
+                .collect(),
+            work_dir: opts.str("workdir").unwrap_or_default().into(),
         };
 
-        for pair in raw_str.split(',') {
-            let mut parts = pair.split('=');
-            let key = parts.next().ok_or(SystemError::EINVAL)?;
-            let value = parts.next().ok_or(SystemError::EINVAL)?;
-
-            match key {
-                "upperdir" => data.upper_dir = value.into(),
-                "lowerdir" => data.lower_dirs = value.split(':').map(|s| s.into()).collect(),
-                "workdir" => data.work_dir = value.into(),
-                _ => return Err(SystemError::EINVAL),
-            }
+        // 至少要有一个下层；upperdir和workdir必须同时给出
+        if data.lower_dirs.is_empty() || data.upper_dir.is_empty() != data.work_dir.is_empty() {
+            return Err(SystemError::EINVAL);
         }
         Ok(data)
     }
@@ -64,48 +83,26 @@ impl FileSystemMakerData for OverlayMountData {
         self
     }
 }
-#[derive(Debug)]
-pub struct OvlSuperBlock {
-    super_block: SuperBlock,
-    pseudo_dev: DeviceNumber, // 虚拟设备号
-    is_lower: bool,
-}
 
 #[derive(Debug)]
 struct OverlayFS {
-    numlayer: usize,
-    numfs: u32,
-    numdatalayer: usize,
-    layers: Vec<OvlLayer>, // 第0层为读写层，后面是只读层
-    workdir: Arc<OvlInode>,
     root_inode: Arc<OvlInode>,
+    /// workdir下的临时目录，copy-up时在这里准备副本。只读挂载时为None
+    workdir: Option<Arc<dyn IndexNode>>,
+    /// 串行化copy-up，避免同一个文件被复制两次
+    copy_up_lock: Mutex<()>,
+    /// 用于生成copy-up临时文件名
+    tmp_seq: AtomicUsize,
 }
 
-#[derive(Debug)]
-pub struct OvlInode {
-    redirect: String, // 重定向路径
-    file_type: FileType,
-    flags: Mutex<u64>,
-    upper_inode: Mutex<Option<Arc<dyn IndexNode>>>, // 读写层
-    lower_inode: Option<Arc<dyn IndexNode>>,        // 只读层
-    oe: Arc<OvlEntry>,
-    fs: Weak<OverlayFS>,
-}
-impl OvlInode {
-    pub fn new(
-        redirect: String,
-        upper: Option<Arc<dyn IndexNode>>,
-        lower_inode: Option<Arc<dyn IndexNode>>,
-    ) -> Self {
-        Self {
-            redirect,
-            file_type: FileType::Dir,
-            flags: Mutex::new(0),
-            upper_inode: Mutex::new(upper),
-            lower_inode,
-            oe: Arc::new(OvlEntry::new()),
-            fs: Weak::default(),
-        }
+impl OverlayFS {
+    fn alloc_tmp_name(&self) -> String {
+        format!("#{:x}", self.tmp_seq.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// 提供容量等信息的真实文件系统的根目录：有上层时是上层，否则是最上面的下层
+    fn real_root(&self) -> Arc<dyn IndexNode> {
+        self.root_inode.real()
     }
 }
 
@@ -130,14 +127,48 @@ impl FileSystem for OverlayFS {
     }
 
     fn super_block(&self) -> SuperBlock {
-        todo!()
+        let mut sb = self.real_root().fs().super_block();
+        sb.magic = Magic::OVERLAYFS_MAGIC;
+        sb
+    }
+
+    fn statfs(&self, _inode: &Arc<dyn IndexNode>) -> Result<SuperBlock, SystemError> {
+        let root = self.real_root();
+        let mut sb = root.fs().statfs(&root)?;
+        sb.magic = Magic::OVERLAYFS_MAGIC;
+        Ok(sb)
     }
 }
 
-impl OverlayFS {
-    pub fn ovl_upper_mnt(&self) -> Arc<dyn IndexNode> {
-        self.layers[0].mnt.clone()
+/// 在挂载命名空间的根目录下查找一个目录
+fn ovl_lookup_dir(path: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
+    let inode = ProcessManager::current_mntns().root_inode().lookup(path)?;
+    if inode.metadata()?.file_type != FileType::Dir {
+        return Err(SystemError::ENOTDIR);
     }
+    Ok(inode)
+}
+
+/// 准备workdir下的临时目录，并清理上次挂载遗留的临时文件
+fn ovl_setup_workdir(
+    upper: &Arc<dyn IndexNode>,
+    workdir: &Arc<dyn IndexNode>,
+) -> Result<Arc<dyn IndexNode>, SystemError> {
+    // copy-up要把副本从workdir移动到上层，所以它们必须在同一个文件系统中
+    if !Arc::ptr_eq(&upper.fs(), &workdir.fs()) {
+        log::error!("overlayfs: workdir and upperdir must reside under the same mount");
+        return Err(SystemError::EINVAL);
+    }
+    let work = workdir.mkdir(OVL_WORKDIR_NAME, InodeMode::S_IRWXU)?;
+    for name in work.list()? {
+        if name == "." || name == ".." {
+            continue;
+        }
+        if work.unlink(&name).is_err() && work.rmdir(&name).is_err() {
+            log::warn!("overlayfs: failed to clean up workdir entry '{}'", name);
+        }
+    }
+    Ok(work)
 }
 
 impl MountableFileSystem for OverlayFS {
@@ -147,60 +178,35 @@ impl MountableFileSystem for OverlayFS {
         let mount_data = data
             .and_then(|d| d.as_any().downcast_ref::<OverlayMountData>())
             .ok_or(SystemError::EINVAL)?;
-        let root_inode = ProcessManager::current_mntns().root_inode();
-        let upper_inode = root_inode
-            .lookup(&mount_data.upper_dir)
-            .map_err(|_| SystemError::EINVAL)?;
-        let upper_layer = OvlLayer {
-            mnt: Arc::new(OvlInode::new(
-                mount_data.upper_dir.clone(),
-                Some(upper_inode),
-                None,
-            )),
-            index: 0,
-            fsid: 0,
-        };
 
-        let lower_layers: Result<Vec<OvlLayer>, SystemError> = mount_data
+        let lowers = mount_data
             .lower_dirs
             .iter()
-            .enumerate()
-            .map(|(i, dir)| {
-                let lower_inode = ProcessManager::current_mntns()
-                    .root_inode()
-                    .lookup(dir)
-                    .map_err(|_| SystemError::EINVAL)?; // 处理错误
-                Ok(OvlLayer {
-                    mnt: Arc::new(OvlInode::new(dir.clone(), None, Some(lower_inode))),
-                    index: (i + 1) as u32,
-                    fsid: (i + 1) as u32,
-                })
-            })
-            .collect();
-
-        let lower_layers = lower_layers?;
-
-        let workdir = Arc::new(OvlInode::new(mount_data.work_dir.clone(), None, None));
-
-        if lower_layers.is_empty() {
-            return Err(SystemError::EINVAL);
-        }
+            .map(|dir| ovl_lookup_dir(dir))
+            .collect::<Result<Vec<_>, SystemError>>()?;
 
-        let mut layers = Vec::new();
-        layers.push(upper_layer);
-        layers.extend(lower_layers);
-
-        let root_inode = layers[0].mnt.clone();
+        let (upper, workdir) = if mount_data.upper_dir.is_empty() {
+            (None, None)
+        } else {
+            let upper = ovl_lookup_dir(&mount_data.upper_dir)?;
+            let workdir = ovl_setup_workdir(&upper, &ovl_lookup_dir(&mount_data.work_dir)?)?;
+            (Some(upper), Some(workdir))
+        };
 
-        let fs = OverlayFS {
-            numlayer: layers.len(),
-            numfs: 1,
-            numdatalayer: layers.len() - 1,
-            layers,
+        let fs = Arc::new_cyclic(|fs_ref: &Weak<OverlayFS>| OverlayFS {
+            root_inode: OvlInode::new(
+                DName::default(),
+                None,
+                fs_ref.clone(),
+                FileType::Dir,
+                upper,
+                lowers,
+            ),
             workdir,
-            root_inode,
-        };
-        Ok(Arc::new(fs))
+            copy_up_lock: Mutex::new(()),
+            tmp_seq: AtomicUsize::new(0),
+        });
+        Ok(fs)
     }
 
     fn make_mount_data(
@@ -217,69 +223,192 @@ impl MountableFileSystem for OverlayFS {
 
 register_mountable_fs!(OverlayFS, OVERLAYFSMAKER, "overlay");
 
+/// overlay中的一个路径
+///
+/// 同一个路径在被引用期间只对应一个OvlInode，它记录了路径在各层中的真实inode。
+/// 下层的inode在查找时确定，上层的inode在copy-up之后才会出现。
+#[derive(Debug)]
+pub struct OvlInode {
+    name: DName,
+    /// 父目录，根目录为None
+    parent: Option<Arc<OvlInode>>,
+    self_ref: Weak<OvlInode>,
+    fs: Weak<OverlayFS>,
+    file_type: FileType,
+    upper: Mutex<Option<Arc<dyn IndexNode>>>, // 读写层
+    lowers: Vec<Arc<dyn IndexNode>>,          // 只读层，从上到下排列
+    /// 已经查找过的子项，只保存弱引用
+    children: Mutex<BTreeMap<DName, Weak<OvlInode>>>,
+}
+
 impl OvlInode {
-    pub fn ovl_lower_redirect(&self) -> Option<&str> {
-        if self.file_type == FileType::File || self.file_type == FileType::Dir {
-            Some(&self.redirect)
-        } else {
-            None
+    fn new(
+        name: DName,
+        parent: Option<Arc<OvlInode>>,
+        fs: Weak<OverlayFS>,
+        file_type: FileType,
+        upper: Option<Arc<dyn IndexNode>>,
+        lowers: Vec<Arc<dyn IndexNode>>,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|self_ref| Self {
+            name,
+            parent,
+            self_ref: self_ref.clone(),
+            fs,
+            file_type,
+            upper: Mutex::new(upper),
+            lowers,
+            children: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    fn ovl_fs(&self) -> Result<Arc<OverlayFS>, SystemError> {
+        self.fs.upgrade().ok_or(SystemError::ENOENT)
+    }
+
+    fn upper(&self) -> Option<Arc<dyn IndexNode>> {
+        self.upper.lock().clone()
+    }
+
+    /// 当前提供内容的真实inode：有上层时是上层，否则是最上面的下层
+    fn real(&self) -> Arc<dyn IndexNode> {
+        self.upper()
+            .unwrap_or_else(|| self.lowers.first().unwrap().clone())
+    }
+
+    fn lookup_child(&self, name: &str) -> Result<Arc<OvlInode>, SystemError> {
+        if self.file_type != FileType::Dir {
+            return Err(SystemError::ENOTDIR);
+        }
+        let key = DName::from(name);
+        let mut children = self.children.lock();
+        if let Some(child) = children.get(&key).and_then(|c| c.upgrade()) {
+            return Ok(child);
         }
+
+        let entry = ovl_lookup(self.upper().as_ref(), &self.lowers, name)?;
+        let child = OvlInode::new(
+            key.clone(),
+            self.self_ref.upgrade(),
+            self.fs.clone(),
+            entry.file_type,
+            entry.upper,
+            entry.lowers,
+        );
+        children.insert(key, Arc::downgrade(&child));
+        Ok(child)
     }
 
-    pub fn create_whiteout(&self, name: &str) -> Result<(), SystemError> {
-        let whiteout_mode = vfs::InodeMode::S_IFCHR;
-        let mut upper_inode = self.upper_inode.lock();
-        if let Some(ref upper_inode) = *upper_inode {
-            upper_inode.mknod(name, whiteout_mode, WHITEOUT_DEV)?;
-        } else {
-            let new_inode = self
-                .fs
-                .upgrade()
-                .ok_or(SystemError::EROFS)?
-                .root_inode()
-                .create(name, FileType::CharDevice, whiteout_mode)?;
-            *upper_inode = Some(new_inode);
+    /// 目录项的组成发生了变化，下次查找时重新确定各层的inode
+    fn invalidate(&self, name: &str) {
+        self.children.lock().remove(&DName::from(name));
+    }
+
+    /// 下层中是否有可见的`name`，如果有，删除或者移走它之后需要留下whiteout
+    fn lower_has(&self, name: &str) -> Result<bool, SystemError> {
+        for dir in &self.lowers {
+            match dir.find(name) {
+                Ok(inode) => return Ok(!is_whiteout(&inode)?),
+                Err(SystemError::ENOENT) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(false)
+    }
+
+    /// 新建`name`之前的检查：名字不能已经存在，并且要让出上层中的whiteout
+    ///
+    /// ## 返回值
+    /// - `Ok(Arc<dyn IndexNode>)`: 本目录在上层中的inode
+    fn prepare_create(&self, name: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
+        match self.lookup_child(name) {
+            Ok(_) => return Err(SystemError::EEXIST),
+            Err(SystemError::ENOENT) => {}
+            Err(e) => return Err(e),
+        }
+        let upper_dir = self.copy_up()?;
+        remove_whiteout(&upper_dir, name)?;
+        Ok(upper_dir)
+    }
+
+    /// 在上层新建的目录`name`可能和下层的同名目录合并，
+    /// 为下层的每一个目录项创建whiteout，使新目录看起来是空的
+    fn make_opaque(&self, name: &str, upper_dir: &Arc<dyn IndexNode>) -> Result<(), SystemError> {
+        let lowers = match ovl_lookup(None, &self.lowers, name) {
+            Ok(entry) if entry.file_type == FileType::Dir => entry.lowers,
+            Ok(_) | Err(SystemError::ENOENT) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for child in ovl_merge_dir(None, &lowers)?.iter().skip(2) {
+            create_whiteout(upper_dir, child)?;
         }
-        let mut flags = self.flags.lock();
-        *flags |= WHITEOUT_FLAG; // 标记为 whiteout
         Ok(())
     }
 
-    fn is_whiteout(&self) -> bool {
-        let flags = self.flags.lock();
-        self.file_type == FileType::CharDevice && (*flags & WHITEOUT_FLAG) != 0
+    /// 在上层新建了`name`之后，返回它对应的OvlInode
+    fn finish_create(&self, name: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
+        self.invalidate(name);
+        Ok(self.lookup_child(name)?)
     }
 
-    fn has_whiteout(&self, name: &str) -> bool {
-        let upper_inode = self.upper_inode.lock();
-        if let Some(ref upper_inode) = *upper_inode {
-            if let Ok(inode) = upper_inode.find(name) {
-                if let Some(ovl_inode) = inode.as_any_ref().downcast_ref::<OvlInode>() {
-                    return ovl_inode.is_whiteout();
+    /// 删除子项`child`：删除它在上层的部分，如果下层中也有，就留下whiteout
+    fn remove_child(&self, child: &OvlInode) -> Result<(), SystemError> {
+        let name = child.name.as_ref();
+        let upper_dir = self.copy_up()?;
+        let need_whiteout = self.lower_has(name)?;
+        if let Some(upper) = child.upper() {
+            if child.file_type == FileType::Dir {
+                // 合并后的目录已经是空的，剩下的只有whiteout
+                for entry in upper.list()? {
+                    if entry != "." && entry != ".." {
+                        upper.unlink(&entry)?;
+                    }
                 }
+                upper_dir.rmdir(name)?;
+            } else {
+                upper_dir.unlink(name)?;
             }
         }
-        false
+        if need_whiteout {
+            create_whiteout(&upper_dir, name)?;
+        }
+        self.invalidate(name);
+        Ok(())
+    }
+
+    /// 合并目录是否为空
+    fn is_empty_dir(&self) -> Result<bool, SystemError> {
+        Ok(self.list()?.len() <= 2)
     }
 }
 
 impl IndexNode for OvlInode {
+    fn open(
+        &self,
+        data: MutexGuard<FilePrivateData>,
+        flags: &FileFlags,
+    ) -> Result<(), SystemError> {
+        // 以可写方式打开下层的普通文件时就进行copy-up
+        if self.file_type == FileType::File
+            && (flags.access_flags() != FileFlags::O_RDONLY || flags.contains(FileFlags::O_TRUNC))
+        {
+            self.copy_up()?;
+        }
+        self.real().open(data, flags)
+    }
+
+    fn close(&self, data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        self.real().close(data)
+    }
+
     fn read_at(
         &self,
         offset: usize,
         len: usize,
         buf: &mut [u8],
-        data: crate::libs::mutex::MutexGuard<vfs::FilePrivateData>,
-    ) -> Result<usize, system_error::SystemError> {
-        if let Some(ref upper_inode) = *self.upper_inode.lock() {
-            return upper_inode.read_at(offset, len, buf, data);
-        }
-
-        if let Some(lower_inode) = &self.lower_inode {
-            return lower_inode.read_at(offset, len, buf, data);
-        }
-
-        Err(SystemError::ENOENT)
+        data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        self.real().read_at(offset, len, buf, data)
     }
 
     fn write_at(
@@ -287,16 +416,9 @@ impl IndexNode for OvlInode {
         offset: usize,
         len: usize,
         buf: &[u8],
-        data: crate::libs::mutex::MutexGuard<vfs::FilePrivateData>,
+        data: MutexGuard<FilePrivateData>,
     ) -> Result<usize, SystemError> {
-        if (*self.upper_inode.lock()).is_none() {
-            self.copy_up()?;
-        }
-        if let Some(ref upper_inode) = *self.upper_inode.lock() {
-            return upper_inode.write_at(offset, len, buf, data);
-        }
-
-        Err(SystemError::EROFS)
+        self.copy_up()?.write_at(offset, len, buf, data)
     }
 
     fn fs(&self) -> Arc<dyn FileSystem> {
@@ -304,141 +426,168 @@ impl IndexNode for OvlInode {
     }
 
     fn metadata(&self) -> Result<Metadata, SystemError> {
-        if let Some(ref upper_inode) = *self.upper_inode.lock() {
-            return upper_inode.metadata();
-        }
+        self.real().metadata()
+    }
 
-        if let Some(ref lower_inode) = self.lower_inode {
-            return lower_inode.metadata();
-        }
-        Ok(Metadata::default())
+    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
+        self.copy_up()?.set_metadata(metadata)
+    }
+
+    fn resize(&self, len: usize) -> Result<(), SystemError> {
+        self.copy_up()?.resize(len)
+    }
+
+    fn truncate(&self, len: usize) -> Result<(), SystemError> {
+        self.copy_up()?.truncate(len)
     }
 
     fn as_any_ref(&self) -> &dyn core::any::Any {
         self
     }
 
-    fn list(&self) -> Result<Vec<String>, system_error::SystemError> {
-        let mut entries: Vec<String> = Vec::new();
-        let upper_inode = self.upper_inode.lock();
-        if let Some(ref upper_inode) = *upper_inode {
-            let upper_entries = upper_inode.list()?;
-            entries.extend(upper_entries);
-        }
-        if let Some(lower_inode) = &self.lower_inode {
-            let lower_entries = lower_inode.list()?;
-            for entry in lower_entries {
-                if !entries.contains(&entry) && !self.has_whiteout(&entry) {
-                    entries.push(entry);
-                }
-            }
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        if self.file_type != FileType::Dir {
+            return Err(SystemError::ENOTDIR);
         }
+        ovl_merge_dir(self.upper().as_ref(), &self.lowers)
+    }
 
-        Ok(entries)
+    fn find(&self, name: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
+        match name {
+            "" | "." => Ok(self.self_ref.upgrade().ok_or(SystemError::ENOENT)?),
+            ".." => self.parent(),
+            name => Ok(self.lookup_child(name)?),
+        }
     }
 
-    fn mkdir(
+    fn create_with_data(
         &self,
         name: &str,
-        mode: vfs::InodeMode,
-    ) -> Result<Arc<dyn IndexNode>, system_error::SystemError> {
-        if let Some(ref upper_inode) = *self.upper_inode.lock() {
-            upper_inode.mkdir(name, mode)
-        } else {
-            Err(SystemError::EROFS)
+        file_type: FileType,
+        mode: InodeMode,
+        data: usize,
+    ) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let upper_dir = self.prepare_create(name)?;
+        upper_dir.create_with_data(name, file_type, mode, data)?;
+        if file_type == FileType::Dir {
+            self.make_opaque(name, &upper_dir)?;
         }
+        self.finish_create(name)
     }
 
-    fn rmdir(&self, name: &str) -> Result<(), SystemError> {
-        let upper_inode = self.upper_inode.lock();
-        if let Some(ref upper_inode) = *upper_inode {
-            upper_inode.rmdir(name)?;
-        } else if let Some(lower_inode) = &self.lower_inode {
-            if lower_inode.find(name).is_ok() {
-                self.create_whiteout(name)?;
-            } else {
-                return Err(SystemError::ENOENT);
-            }
-        } else {
-            return Err(SystemError::ENOENT);
-        }
+    fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
+        self.prepare_create(name)?.symlink(name, target)?;
+        self.finish_create(name)
+    }
 
-        Ok(())
+    fn mknod(
+        &self,
+        filename: &str,
+        mode: InodeMode,
+        dev_t: DeviceNumber,
+    ) -> Result<Arc<dyn IndexNode>, SystemError> {
+        self.prepare_create(filename)?
+            .mknod(filename, mode, dev_t)?;
+        self.finish_create(filename)
     }
 
-    fn unlink(&self, name: &str) -> Result<(), SystemError> {
-        let upper_inode = self.upper_inode.lock();
-        if let Some(ref upper_inode) = *upper_inode {
-            upper_inode.unlink(name)?;
-        } else if let Some(lower_inode) = &self.lower_inode {
-            if lower_inode.find(name).is_ok() {
-                self.create_whiteout(name)?;
-            } else {
-                return Err(SystemError::ENOENT);
-            }
-        } else {
-            return Err(SystemError::ENOENT);
+    fn link(&self, name: &str, other: &Arc<dyn IndexNode>) -> Result<(), SystemError> {
+        let other = other.downcast_ref::<OvlInode>().ok_or(SystemError::EXDEV)?;
+        if other.file_type == FileType::Dir {
+            return Err(SystemError::EPERM);
         }
-
+        let other_upper = other.copy_up()?;
+        self.prepare_create(name)?.link(name, &other_upper)?;
+        self.invalidate(name);
         Ok(())
     }
 
-    fn link(
-        &self,
-        name: &str,
-        other: &Arc<dyn IndexNode>,
-    ) -> Result<(), system_error::SystemError> {
-        if let Some(ref upper_inode) = *self.upper_inode.lock() {
-            upper_inode.link(name, other)
-        } else {
-            Err(SystemError::EROFS)
+    fn unlink(&self, name: &str) -> Result<(), SystemError> {
+        let child = self.lookup_child(name)?;
+        if child.file_type == FileType::Dir {
+            return Err(SystemError::EISDIR);
         }
+        self.remove_child(&child)
     }
 
-    fn create(
-        &self,
-        name: &str,
-        file_type: vfs::FileType,
-        mode: vfs::InodeMode,
-    ) -> Result<Arc<dyn IndexNode>, system_error::SystemError> {
-        if let Some(ref upper_inode) = *self.upper_inode.lock() {
-            upper_inode.create(name, file_type, mode)
-        } else {
-            Err(SystemError::EROFS)
+    fn rmdir(&self, name: &str) -> Result<(), SystemError> {
+        let child = self.lookup_child(name)?;
+        if child.file_type != FileType::Dir {
+            return Err(SystemError::ENOTDIR);
+        }
+        if !child.is_empty_dir()? {
+            return Err(SystemError::ENOTEMPTY);
         }
+        self.remove_child(&child)
     }
 
-    fn find(&self, name: &str) -> Result<Arc<dyn IndexNode>, system_error::SystemError> {
-        let upper_inode = self.upper_inode.lock();
-        if let Some(ref upper) = *upper_inode {
-            if let Ok(inode) = upper.find(name) {
-                return Ok(inode);
-            }
+    fn move_to(
+        &self,
+        old_name: &str,
+        target: &Arc<dyn IndexNode>,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> Result<(), SystemError> {
+        if flags.intersects(RenameFlags::EXCHANGE | RenameFlags::WHITEOUT) {
+            return Err(SystemError::EINVAL);
         }
-        if self.has_whiteout(name) {
-            return Err(SystemError::ENOENT);
+        let target = target
+            .downcast_ref::<OvlInode>()
+            .ok_or(SystemError::EXDEV)?;
+        let src = self.lookup_child(old_name)?;
+        // 移动和下层合并的目录需要记录重定向，这里不支持，用户态会退回到逐个复制
+        if src.file_type == FileType::Dir && !src.lowers.is_empty() {
+            return Err(SystemError::EXDEV);
         }
 
-        if let Some(lower) = &self.lower_inode {
-            if let Ok(inode) = lower.find(name) {
-                return Ok(inode);
+        match target.lookup_child(new_name) {
+            Ok(dst) => {
+                if Arc::ptr_eq(&src, &dst) {
+                    return Ok(());
+                }
+                if flags.contains(RenameFlags::NOREPLACE) {
+                    return Err(SystemError::EEXIST);
+                }
+                match (
+                    src.file_type == FileType::Dir,
+                    dst.file_type == FileType::Dir,
+                ) {
+                    (true, false) => return Err(SystemError::ENOTDIR),
+                    (false, true) => return Err(SystemError::EISDIR),
+                    (true, true) if !dst.is_empty_dir()? => return Err(SystemError::ENOTEMPTY),
+                    _ => {}
+                }
+                target.remove_child(&dst)?;
             }
+            Err(SystemError::ENOENT) => {}
+            Err(e) => return Err(e),
         }
 
-        Err(SystemError::ENOENT)
+        let src_upper_dir = self.copy_up()?;
+        src.copy_up()?;
+        let dst_upper_dir = target.copy_up()?;
+        remove_whiteout(&dst_upper_dir, new_name)?;
+        let need_whiteout = self.lower_has(old_name)?;
+        src_upper_dir.move_to(old_name, &dst_upper_dir, new_name, flags)?;
+        if src.file_type == FileType::Dir {
+            target.make_opaque(new_name, &dst_upper_dir)?;
+        }
+        if need_whiteout {
+            create_whiteout(&src_upper_dir, old_name)?;
+        }
+        self.invalidate(old_name);
+        target.invalidate(new_name);
+        Ok(())
     }
 
-    fn mknod(
-        &self,
-        filename: &str,
-        mode: vfs::InodeMode,
-        dev_t: crate::driver::base::device::device_number::DeviceNumber,
-    ) -> Result<Arc<dyn IndexNode>, system_error::SystemError> {
-        let upper_inode = self.upper_inode.lock();
-        if let Some(ref inode) = *upper_inode {
-            inode.mknod(filename, mode, dev_t)
-        } else {
-            Err(SystemError::EROFS)
+    fn dname(&self) -> Result<DName, SystemError> {
+        Ok(self.name.clone())
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        match self.parent.as_ref() {
+            Some(parent) => Ok(parent.clone()),
+            None => Ok(self.self_ref.upgrade().ok_or(SystemError::ENOENT)?),
         }
     }
 }
@@ -2,14 +2,16 @@ use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
 
 use alloc::{
     collections::BTreeSet,
+    string::String,
     sync::{Arc, Weak},
     vec::Vec,
 };
 use hashbrown::HashMap;
 use system_error::SystemError;
 
-use super::vfs::{FilePrivateData, IndexNode};
+use super::vfs::IndexNode;
 use crate::exception::workqueue::{schedule_work, Work, WorkQueue};
+use crate::libs::error_context::{ContextError, ErrorContext};
 use crate::libs::mutex::MutexGuard;
 use crate::libs::spinlock::SpinLock;
 use crate::libs::wait_queue::WaitQueue;
@@ -55,6 +57,17 @@ fn register_page_cache(cache: &Arc<PageCache>) {
         .push(Arc::downgrade(cache));
 }
 
+/// 错误上下文中对文件的描述：文件的路径，拿不到路径时用inode号
+fn inode_description(inode: &Arc<dyn IndexNode>) -> String {
+    match inode.absolute_path() {
+        Ok(path) => path,
+        Err(_) => format!(
+            "inode {}",
+            inode.metadata().map(|m| m.inode_id.data()).unwrap_or(0)
+        ),
+    }
+}
+
 pub fn list_page_caches() -> Vec<Arc<PageCache>> {
     let mut guard = PAGECACHE_REGISTRY.lock_irqsave();
     let mut caches = Vec::new();
@@ -224,6 +237,15 @@ impl EvictPolicy {
         }
     }
 
+    /// 数据已经不再需要（例如打洞），脏页也可以直接丢弃
+    const fn discard() -> Self {
+        Self {
+            allow_dirty: true,
+            allow_mapped: false,
+            allow_writeback: false,
+        }
+    }
+
     fn can_evict(self, entry: &PageEntry) -> bool {
         let state = entry.state();
         if matches!(state, PageState::Loading) {
@@ -516,6 +538,9 @@ impl PageCacheManager {
                 let mut guard = page.write();
                 guard.remove_flags(PageFlags::PG_DIRTY);
             }
+            // 先清除脏标志再写保护映射：两者之间通过映射写入的数据会被本次回写带上，
+            // 写保护之后的写入会重新标记脏页
+            page.mkclean();
             let result = if let Some(backend) = backend {
                 let waiter = backend.write_page_async(page_index, &page, len);
                 waiter.wait().map(|_| len)
@@ -526,19 +551,20 @@ impl PageCacheManager {
                         len,
                     )
                 };
-                inode.write_direct(
-                    page_start,
-                    len,
-                    data,
-                    Mutex::new(FilePrivateData::Unused).lock(),
-                )
+                inode.write_direct(page_start, len, data)
             };
             if let Err(e) = result {
                 page.write().add_flags(PageFlags::PG_ERROR);
                 cache.account_state_transition(PageState::Writeback, PageState::Error);
                 entry.set_state(PageState::Error);
                 entry.wait_queue.wake_all();
-                return Err(e);
+                return Err(ContextError::from(e)
+                    .push(format!(
+                        "writeback page {} of {}",
+                        page_index,
+                        inode_description(&inode)
+                    ))
+                    .into_errno());
             }
         }
 
@@ -730,6 +756,13 @@ impl InnerPageCache {
         self.evict_pages_inner(Some((start_index, end_index)), EvictPolicy::clean_only())
     }
 
+    /// 丢弃指定范围的页（包括脏页），用于打洞
+    ///
+    /// 被映射、正在回写或者有外部引用的页不会被丢弃，返回丢弃的页数
+    pub fn discard_range(&mut self, start_index: usize, end_index: usize) -> usize {
+        self.evict_pages_inner(Some((start_index, end_index)), EvictPolicy::discard())
+    }
+
     fn evict_clean_pages(&mut self) -> usize {
         self.evict_pages_inner(None, EvictPolicy::clean_only())
     }
@@ -907,11 +940,19 @@ impl PageCache {
         &self,
         page_index: usize,
         page: &Arc<Page>,
-    ) -> Result<(), SystemError> {
+    ) -> Result<(), ContextError> {
         let backend = self.backend();
         if let Some(backend) = backend {
             let waiter = backend.read_page_async(page_index, page);
-            let read_len = waiter.wait()?;
+            let read_len = waiter.wait().with_context(|| {
+                let inode = self.inode().and_then(|inode| inode.upgrade());
+                match inode {
+                    Some(inode) => {
+                        format!("read page {} of {}", page_index, inode_description(&inode))
+                    }
+                    None => format!("read page {}", page_index),
+                }
+            })?;
             if read_len < MMArch::PAGE_SIZE {
                 let mut page_guard = page.write();
                 let dst = unsafe { page_guard.as_slice_mut() };
@@ -927,7 +968,11 @@ impl PageCache {
             .ok_or(SystemError::EIO)?;
         let mut page_guard = page.write();
         let dst = unsafe { page_guard.as_slice_mut() };
-        inode.read_sync(page_index * MMArch::PAGE_SIZE, dst)?;
+        inode
+            .read_sync(page_index * MMArch::PAGE_SIZE, dst)
+            .with_context(|| {
+                format!("read page {} of {}", page_index, inode_description(&inode))
+            })?;
         page_guard.add_flags(PageFlags::PG_UPTODATE);
         Ok(())
     }
@@ -1007,6 +1052,7 @@ impl PageCache {
             self.populate_page_from_backend(page_index, &entry.page)
         } else {
             self.populate_page_zero(&entry.page)
+                .map_err(ContextError::from)
         };
 
         match populate_result {
@@ -1019,7 +1065,7 @@ impl PageCache {
                 entry.set_state(PageState::Error);
                 entry.wait_queue.wake_all();
                 self.remove_failed_entry(page_index, &entry);
-                Err(e)
+                Err(e.into())
             }
         }
     }
@@ -0,0 +1,70 @@
+//! /proc/diskstats - 磁盘的I/O统计
+//!
+//! 每个磁盘一行：主设备号、次设备号、设备名，之后是与`/sys/block/<dev>/stat`相同的17个字段
+
+use crate::libs::mutex::MutexGuard;
+use crate::{
+    driver::base::block::{gendisk::GenDisk, manager::block_dev_manager},
+    filesystem::{
+        procfs::{
+            template::{Builder, FileOps, ProcFileBuilder},
+            utils::proc_read,
+        },
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+};
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+/// /proc/diskstats 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct DiskstatsFileOps;
+
+impl DiskstatsFileOps {
+    pub fn new_inode(parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self, InodeMode::S_IRUGO)
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+
+    fn generate_diskstats_content() -> Vec<u8> {
+        let mut content = String::new();
+        for dev in block_dev_manager().disks() {
+            let meta = dev.blkdev_meta();
+            let Some(devnum) = meta
+                .inner()
+                .gendisks
+                .get(&GenDisk::ENTIRE_DISK_IDX)
+                .map(|disk| disk.device_num())
+            else {
+                continue;
+            };
+            content.push_str(&format!(
+                "{:>4} {:>7} {} {}\n",
+                devnum.major().data(),
+                devnum.minor(),
+                dev.dev_name(),
+                meta.stats().format_stat(0)
+            ));
+        }
+        content.into_bytes()
+    }
+}
+
+impl FileOps for DiskstatsFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let content = Self::generate_diskstats_content();
+        proc_read(offset, len, buf, &content)
+    }
+}
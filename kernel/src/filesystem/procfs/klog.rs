@@ -2,7 +2,7 @@ use core::fmt::{Display, Formatter, Result};
 
 use alloc::string::String;
 
-use crate::{debug::klog::loglevel::LogLevel, time::PosixTimeSpec};
+use crate::{debug::klog::loglevel::LogLevel, libs::printk::PrintkTime, time::PosixTimeSpec};
 
 /// 日志消息
 #[derive(Default, Clone, Debug)]
@@ -33,7 +33,6 @@ impl LogMessage {
 
 impl Display for LogMessage {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        let timestamp = &self.timestamp;
         let level = match self.level {
             LogLevel::EMERG => "EMERG",
             LogLevel::ALERT => "ALERT",
@@ -46,12 +45,13 @@ impl Display for LogMessage {
             LogLevel::DEFAULT => "Default",
         };
 
-        let message = &self.message;
-
-        let res = format!(
-            "<{}>[{}.{}] : {}\n",
-            level, timestamp.tv_sec, timestamp.tv_nsec, message
+        // 不分配内存，panic时pstore也用它格式化日志
+        return write!(
+            f,
+            "<{}>{}{}",
+            level,
+            PrintkTime(self.timestamp),
+            self.message
         );
-        return write!(f, "{}", res);
     }
 }
@@ -56,6 +56,11 @@ impl Kmsg {
         self.is_changed = true;
     }
 
+    /// 按时间顺序遍历缓冲区中的日志消息
+    pub fn messages(&self) -> impl DoubleEndedIterator<Item = &LogMessage> + '_ {
+        self.buffer.iter()
+    }
+
     /// 读取缓冲区
     pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, SystemError> {
         self.tobytes();
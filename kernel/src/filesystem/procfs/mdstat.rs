@@ -0,0 +1,41 @@
+//! /proc/mdstat - 软件RAID阵列的状态
+
+use crate::libs::mutex::MutexGuard;
+use crate::{
+    driver::block::md::md_status,
+    filesystem::{
+        procfs::{
+            template::{Builder, FileOps, ProcFileBuilder},
+            utils::proc_read,
+        },
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+};
+use alloc::sync::{Arc, Weak};
+use system_error::SystemError;
+
+/// /proc/mdstat 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct MdstatFileOps;
+
+impl MdstatFileOps {
+    pub fn new_inode(parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self, InodeMode::S_IRUGO)
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for MdstatFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let content = md_status();
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+}
@@ -0,0 +1,43 @@
+//! /proc/memtest - 启动时内存测试的结果
+//!
+//! 展示测试的轮数、测试过的内存大小，以及被保留的坏内存区域
+
+use crate::libs::mutex::MutexGuard;
+use crate::{
+    filesystem::{
+        procfs::{
+            template::{Builder, FileOps, ProcFileBuilder},
+            utils::proc_read,
+        },
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+    mm::memtest::memtest_report,
+};
+use alloc::sync::{Arc, Weak};
+use system_error::SystemError;
+
+/// /proc/memtest 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct MemtestFileOps;
+
+impl MemtestFileOps {
+    pub fn new_inode(parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self, InodeMode::S_IRUGO)
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for MemtestFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let content = memtest_report();
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+}
@@ -5,21 +5,25 @@
 use alloc::{sync::Arc, vec::Vec};
 use system_error::SystemError;
 
-use crate::{libs::once::Once, process::ProcessManager};
+use crate::{libs::once::Once, process::ProcessManager, sched::psi::PsiTrigger};
 
 use super::vfs::mount::{MountFlags, MountPath};
 use super::vfs::InodeMode;
 
 mod cmdline;
 mod cpuinfo;
+mod diskstats;
 pub mod klog;
 pub mod kmsg;
 mod kmsg_file;
 mod loadavg;
+mod mdstat;
 mod meminfo;
+mod memtest;
 mod mounts;
 mod net;
 mod pid;
+mod pressure;
 pub mod root;
 mod self_;
 mod stat;
@@ -47,11 +51,16 @@ pub(super) use template::Builder;
 #[derive(Debug, Clone)]
 pub struct ProcfsFilePrivateData {
     pub data: Vec<u8>,
+    /// /proc/pressure/* 上通过写入创建的触发器
+    pub psi_trigger: Option<Arc<PsiTrigger>>,
 }
 
 impl ProcfsFilePrivateData {
     pub fn new() -> Self {
-        ProcfsFilePrivateData { data: Vec::new() }
+        ProcfsFilePrivateData {
+            data: Vec::new(),
+            psi_trigger: None,
+        }
     }
 }
 
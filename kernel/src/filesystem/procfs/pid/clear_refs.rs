@@ -0,0 +1,80 @@
+//! /proc/[pid]/clear_refs - 清除页面的访问位或者soft-dirty位
+//!
+//! 只写文件，写入一个数字：
+//!
+//! - 1: 清除所有页面的访问位
+//! - 2: 清除匿名页面的访问位
+//! - 3: 清除文件映射页面的访问位
+//! - 4: 清除所有页面的soft-dirty位，之后可以通过pagemap找出被写入过的页面
+//! - 5: 重置RSS的峰值
+
+use crate::libs::mutex::MutexGuard;
+use crate::{
+    filesystem::{
+        procfs::template::{Builder, FileOps, ProcFileBuilder},
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+    mm::pagemap::ClearRefsType,
+    process::{ptrace::ptrace_may_access, ProcessManager, RawPid},
+};
+use alloc::sync::{Arc, Weak};
+use system_error::SystemError;
+
+/// /proc/[pid]/clear_refs 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct ClearRefsFileOps {
+    pid: RawPid,
+}
+
+impl ClearRefsFileOps {
+    pub fn new_inode(pid: RawPid, parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        // 与Linux一致，仅属主可写
+        ProcFileBuilder::new(Self { pid }, InodeMode::S_IWUSR)
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for ClearRefsFileOps {
+    fn owner(&self) -> Option<(usize, usize)> {
+        let pcb = ProcessManager::find(self.pid)?;
+        if pcb.is_kthread() {
+            return Some((0, 0));
+        }
+        let cred = pcb.cred();
+        Some((cred.euid.data(), cred.egid.data()))
+    }
+
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EINVAL)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let input = core::str::from_utf8(buf).map_err(|_| SystemError::EINVAL)?;
+        let ty = input
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| SystemError::EINVAL)
+            .and_then(ClearRefsType::try_from)?;
+
+        let pcb = ProcessManager::find(self.pid).ok_or(SystemError::ESRCH)?;
+        ptrace_may_access(&pcb).map_err(|_| SystemError::EACCES)?;
+        if let Some(vm) = pcb.basic().user_vm() {
+            vm.write().clear_refs(ty)?;
+        }
+        Ok(buf.len())
+    }
+}
@@ -0,0 +1,71 @@
+//! /proc/[pid]/io - 进程I/O统计
+//!
+//! 格式与Linux一致，统计的是整个线程组的I/O
+
+use crate::libs::mutex::MutexGuard;
+use crate::{
+    filesystem::{
+        procfs::{
+            template::{Builder, FileOps, ProcFileBuilder},
+            utils::proc_read,
+        },
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+    process::{ProcessManager, RawPid},
+};
+use alloc::{
+    format,
+    sync::{Arc, Weak},
+};
+use system_error::SystemError;
+
+/// /proc/[pid]/io 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct IoFileOps {
+    pid: RawPid,
+}
+
+impl IoFileOps {
+    pub fn new_inode(pid: RawPid, parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        // 与Linux一致，仅属主可读
+        ProcFileBuilder::new(Self { pid }, InodeMode::S_IRUSR)
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for IoFileOps {
+    fn owner(&self) -> Option<(usize, usize)> {
+        let pcb = ProcessManager::find(self.pid)?;
+        if pcb.is_kthread() {
+            return Some((0, 0));
+        }
+        let cred = pcb.cred();
+        Some((cred.euid.data(), cred.egid.data()))
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let pcb = ProcessManager::find(self.pid).ok_or(SystemError::ESRCH)?;
+        let stats = pcb.process_io_stats();
+
+        let content = format!(
+            "rchar: {}\nwchar: {}\nsyscr: {}\nsyscw: {}\nread_bytes: {}\nwrite_bytes: {}\ncancelled_write_bytes: {}\n",
+            stats.rchar,
+            stats.wchar,
+            stats.syscr,
+            stats.syscw,
+            stats.read_bytes,
+            stats.write_bytes,
+            stats.cancelled_write_bytes,
+        );
+
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+}
@@ -12,7 +12,7 @@ use crate::{
         },
         vfs::{FilePrivateData, IndexNode, InodeMode},
     },
-    mm::{ucontext::LockedVMA, MemoryManagementArch, VmFlags},
+    mm::{ucontext::LockedVMA, MemoryManagementArch, VirtAddr, VmFlags},
     process::{ProcessManager, RawPid},
 };
 use alloc::{
@@ -102,6 +102,28 @@ fn format_dev_inode_and_path(
     (String::from("00:00 0"), String::new())
 }
 
+/// 路径列之前补齐到的宽度，与 Linux 在 64 位系统上的输出对齐
+const MAPS_PATH_COLUMN: usize = 25 + core::mem::size_of::<usize>() * 6 - 1;
+
+/// 匿名映射的名字：堆和栈分别标记为 [heap] 和 [stack]
+fn anon_vma_name(
+    start: VirtAddr,
+    end: VirtAddr,
+    heap: (VirtAddr, VirtAddr),
+    stack_bottom: Option<VirtAddr>,
+) -> Option<&'static str> {
+    // 半开区间相交：只是与堆相邻（比如结束于brk_start的bss）的映射不算堆
+    if heap.1 > heap.0 && start < heap.1 && end > heap.0 {
+        return Some("[heap]");
+    }
+    if let Some(bottom) = stack_bottom {
+        if start < bottom && end >= bottom {
+            return Some("[stack]");
+        }
+    }
+    None
+}
+
 /// 生成 /proc/[pid]/maps 内容
 fn generate_maps_content(pid: RawPid) -> Result<Vec<u8>, SystemError> {
     let target_pcb = ProcessManager::find(pid).ok_or(SystemError::ESRCH)?;
@@ -114,10 +136,11 @@ fn generate_maps_content(pid: RawPid) -> Result<Vec<u8>, SystemError> {
         .unwrap_or_default();
 
     let as_guard = vm.read();
+    let heap = (as_guard.brk_start, as_guard.brk);
+    let stack_bottom = as_guard.user_stack.as_ref().map(|s| s.stack_bottom());
 
-    // 收集并按地址排序
-    let mut vmas: Vec<Arc<LockedVMA>> = as_guard.mappings.iter_vmas().cloned().collect();
-    vmas.sort_by_key(|v| v.lock().region().start().data());
+    // VMA 按地址顺序存放，直接收集即可
+    let vmas: Vec<Arc<LockedVMA>> = as_guard.mappings.iter_vmas().cloned().collect();
 
     let mut out: Vec<u8> = Vec::new();
 
@@ -132,15 +155,20 @@ fn generate_maps_content(pid: RawPid) -> Result<Vec<u8>, SystemError> {
             .unwrap_or(0)
             .saturating_mul(MMArch::PAGE_SIZE);
 
-        let (dev_ino, path_tail) = if let Some(f) = g.vm_file() {
+        let (dev_ino, mut path_tail) = if let Some(f) = g.vm_file() {
             let inode = f.inode();
             format_dev_inode_and_path(Some(inode.as_ref()), &root_prefix)
         } else {
             format_dev_inode_and_path(None, &root_prefix)
         };
+        if g.vm_file().is_none() {
+            if let Some(name) = anon_vma_name(region.start(), region.end(), heap, stack_bottom) {
+                path_tail = format!(" {}", name);
+            }
+        }
 
-        let line = format!(
-            "{:016x}-{:016x} {}{}{}{} {:08x} {}{}\n",
+        let mut line = format!(
+            "{:08x}-{:08x} {}{}{}{} {:08x} {}",
             region.start().data(),
             region.end().data(),
             perms[0] as char,
@@ -149,8 +177,15 @@ fn generate_maps_content(pid: RawPid) -> Result<Vec<u8>, SystemError> {
             perms[3] as char,
             offset,
             dev_ino,
-            path_tail
         );
+        if !path_tail.is_empty() {
+            // path_tail 自带一个前导空格
+            while line.len() + 1 < MAPS_PATH_COLUMN {
+                line.push(' ');
+            }
+            line.push_str(&path_tail);
+        }
+        line.push('\n');
         out.extend_from_slice(line.as_bytes());
     }
 
@@ -14,31 +14,43 @@ use crate::{
 use alloc::sync::{Arc, Weak};
 use system_error::SystemError;
 
+mod clear_refs;
 mod cmdline;
 mod exe;
 mod fd;
 mod fdinfo;
+mod io;
 mod maps;
 mod mountinfo;
 mod mounts;
 mod ns;
+mod pagemap;
+mod schedstat;
 pub mod stat;
 mod statm;
 mod status;
 mod task;
+mod timens_offsets;
+mod timers;
 
+use clear_refs::ClearRefsFileOps;
 use cmdline::CmdlineFileOps;
 use exe::ExeSymOps;
 use fd::FdDirOps;
 use fdinfo::FdInfoDirOps;
+use io::IoFileOps;
 use maps::MapsFileOps;
 use mountinfo::MountInfoFileOps;
 use mounts::PidMountsFileOps;
 use ns::NsDirOps;
+use pagemap::PagemapFileOps;
+use schedstat::SchedstatFileOps;
 use stat::StatFileOps;
 use statm::StatmFileOps;
 use status::StatusFileOps;
 use task::TaskDirOps;
+use timens_offsets::TimensOffsetsFileOps;
+use timers::TimersFileOps;
 
 /// /proc/[pid] 目录的 DirOps 实现
 #[derive(Debug)]
@@ -68,9 +80,13 @@ impl PidDirOps {
         &'static str,
         fn(&PidDirOps, Weak<dyn IndexNode>) -> Arc<dyn IndexNode>,
     )] = &[
+        ("clear_refs", |ops, parent| {
+            ClearRefsFileOps::new_inode(ops.pid, parent)
+        }),
         ("cmdline", |ops, parent| {
             CmdlineFileOps::new_inode(ops.pid, parent)
         }),
+        ("io", |ops, parent| IoFileOps::new_inode(ops.pid, parent)),
         ("maps", |ops, parent| {
             MapsFileOps::new_inode(ops.pid, parent)
         }),
@@ -81,6 +97,12 @@ impl PidDirOps {
             PidMountsFileOps::new_inode(ops.pid, parent)
         }),
         ("ns", |ops, parent| NsDirOps::new_inode(ops.pid, parent)),
+        ("pagemap", |ops, parent| {
+            PagemapFileOps::new_inode(ops.pid, parent)
+        }),
+        ("schedstat", |ops, parent| {
+            SchedstatFileOps::new_inode(ops.pid, parent)
+        }),
         ("stat", |ops, parent| {
             StatFileOps::new_inode(ops.pid, parent)
         }),
@@ -91,6 +113,12 @@ impl PidDirOps {
             StatusFileOps::new_inode(ops.pid, parent)
         }),
         ("task", |ops, parent| TaskDirOps::new_inode(ops.pid, parent)),
+        ("timens_offsets", |ops, parent| {
+            TimensOffsetsFileOps::new_inode(ops.pid, parent)
+        }),
+        ("timers", |ops, parent| {
+            TimersFileOps::new_inode(ops.pid, parent)
+        }),
         ("exe", |ops, parent| ExeSymOps::new_inode(ops.pid, parent)),
         ("fd", |ops, parent| {
             // fd 目录仍然需要进程引用来列出文件描述符
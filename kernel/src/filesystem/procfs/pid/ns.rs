@@ -39,10 +39,8 @@ fn get_pid_ns_ino(pid: RawPid, ns_type: NsFileType) -> Result<usize, SystemError
         NsFileType::Net => nsproxy.net_ns.ns_common().nsid,
         NsFileType::Pid => pcb.active_pid_ns().ns_common().nsid,
         NsFileType::PidForChildren => nsproxy.pid_ns_for_children.ns_common().nsid,
-        NsFileType::Time | NsFileType::TimeForChildren => {
-            // Time namespace 尚未实现
-            NamespaceId::new(0)
-        }
+        NsFileType::Time => nsproxy.time_ns.ns_common().nsid,
+        NsFileType::TimeForChildren => nsproxy.time_ns_for_children.ns_common().nsid,
         NsFileType::User => pcb.cred().user_ns.ns_common().nsid,
         NsFileType::Cgroup => nsproxy.cgroup_ns.ns_common().nsid,
     };
@@ -166,9 +164,9 @@ impl SymOps for NsSymOps {
             NsFileType::PidForChildren => {
                 NamespaceFilePrivateData::PidForChildren(nsproxy.pid_ns_for_children.clone())
             }
-            NsFileType::Time | NsFileType::TimeForChildren => {
-                // Time namespace 尚未实现
-                return Err(SystemError::ENOSYS);
+            NsFileType::Time => NamespaceFilePrivateData::Time(nsproxy.time_ns.clone()),
+            NsFileType::TimeForChildren => {
+                NamespaceFilePrivateData::Time(nsproxy.time_ns_for_children.clone())
             }
             NsFileType::User => NamespaceFilePrivateData::User(pcb.cred().user_ns.clone()),
             NsFileType::Cgroup => NamespaceFilePrivateData::Cgroup(nsproxy.cgroup_ns.clone()),
@@ -0,0 +1,89 @@
+//! /proc/[pid]/pagemap - 虚拟页到物理页帧的映射
+//!
+//! 二进制文件，第n个u64对应第n个虚拟页，格式见[`crate::mm::pagemap`]。
+//! 读取的偏移量和长度都必须是8的倍数。
+//! 与Linux一致，只有拥有CAP_SYS_ADMIN时才会填写页帧号，否则为0。
+
+use crate::libs::mutex::MutexGuard;
+use crate::{
+    arch::MMArch,
+    filesystem::{
+        procfs::template::{Builder, FileOps, ProcFileBuilder},
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+    mm::{pagemap::PM_ENTRY_BYTES, MemoryManagementArch, VirtAddr},
+    process::{cred::CAPFlags, ptrace::ptrace_may_access, ProcessManager, RawPid},
+};
+use alloc::sync::{Arc, Weak};
+use core::cmp::min;
+use system_error::SystemError;
+
+/// 每次遍历的页数，避免一次分配过大的缓冲区
+const PAGEMAP_WALK_PAGES: usize = 512;
+
+/// /proc/[pid]/pagemap 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct PagemapFileOps {
+    pid: RawPid,
+}
+
+impl PagemapFileOps {
+    pub fn new_inode(pid: RawPid, parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        // 与Linux一致，仅属主可读
+        ProcFileBuilder::new(Self { pid }, InodeMode::S_IRUSR)
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for PagemapFileOps {
+    fn owner(&self) -> Option<(usize, usize)> {
+        let pcb = ProcessManager::find(self.pid)?;
+        if pcb.is_kthread() {
+            return Some((0, 0));
+        }
+        let cred = pcb.cred();
+        Some((cred.euid.data(), cred.egid.data()))
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        if offset % PM_ENTRY_BYTES != 0 || len % PM_ENTRY_BYTES != 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let pcb = ProcessManager::find(self.pid).ok_or(SystemError::ESRCH)?;
+        ptrace_may_access(&pcb).map_err(|_| SystemError::EACCES)?;
+        let Some(vm) = pcb.basic().user_vm() else {
+            return Ok(0);
+        };
+        let show_pfn = ProcessManager::current_pcb()
+            .cred()
+            .has_capability(CAPFlags::CAP_SYS_ADMIN);
+
+        let start_vpn = offset / PM_ENTRY_BYTES;
+        let end_vpn = MMArch::USER_END_VADDR.data() >> MMArch::PAGE_SHIFT;
+        if start_vpn >= end_vpn {
+            return Ok(0);
+        }
+        let count = min(min(len, buf.len()) / PM_ENTRY_BYTES, end_vpn - start_vpn);
+
+        let mut done = 0;
+        while done < count {
+            let n = min(count - done, PAGEMAP_WALK_PAGES);
+            let start = VirtAddr::new((start_vpn + done) << MMArch::PAGE_SHIFT);
+            let entries = vm.read().pagemap_read(start, n, show_pfn);
+            for (i, pme) in entries.iter().enumerate() {
+                let pos = (done + i) * PM_ENTRY_BYTES;
+                buf[pos..pos + PM_ENTRY_BYTES].copy_from_slice(&pme.to_ne_bytes());
+            }
+            done += n;
+        }
+        Ok(count * PM_ENTRY_BYTES)
+    }
+}
@@ -0,0 +1,56 @@
+//! /proc/[pid]/schedstat - 进程的调度统计
+//!
+//! 格式与Linux一致：`<在CPU上运行的时间ns> <在运行队列上等待的时间ns> <被调度运行的次数>`
+
+use crate::libs::mutex::MutexGuard;
+use crate::{
+    filesystem::{
+        procfs::{
+            template::{Builder, FileOps, ProcFileBuilder},
+            utils::proc_read,
+        },
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+    process::{ProcessManager, RawPid},
+};
+use alloc::{
+    format,
+    sync::{Arc, Weak},
+};
+use system_error::SystemError;
+
+/// /proc/[pid]/schedstat 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct SchedstatFileOps {
+    pid: RawPid,
+}
+
+impl SchedstatFileOps {
+    pub fn new_inode(pid: RawPid, parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self { pid }, InodeMode::S_IRUGO)
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for SchedstatFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let pcb = ProcessManager::find(self.pid).ok_or(SystemError::ESRCH)?;
+        let sched_info = pcb.sched_info();
+        let run_time = sched_info.sched_entity.sum_exec_runtime;
+        let (run_delay, pcount) = {
+            let stat = sched_info.sched_stat.read_irqsave();
+            (stat.run_delay, stat.pcount)
+        };
+
+        let content = format!("{} {} {}\n", run_time, run_delay, pcount);
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+}
@@ -132,10 +132,13 @@ fn generate_linux_proc_stat_line(
         .map(|cpu| cpu.data() as i32)
         .unwrap_or(0);
 
+    // delayacct_blkio_ticks: 等待块设备IO的累计时间
+    let blkio_ticks = ns_to_clock_t(pcb.delays().snapshot().blkio_delay);
+
     format!(
         "{pid} ({comm}) {state_ch} {ppid} {pgrp} {session} {tty_nr} {tpgid} {flags} \
 {minflt} {cminflt} {majflt} {cmajflt} {utime} {stime} {cutime} {cstime} {priority} {nice} \
-{num_threads} {itrealvalue} {starttime} {vsize_bytes} {rss_pages} 0 0 0 0 0 0 0 0 0 0 0 0 0 {processor} 0 0 0 0 0\n",
+{num_threads} {itrealvalue} {starttime} {vsize_bytes} {rss_pages} 0 0 0 0 0 0 0 0 0 0 0 0 0 {processor} 0 0 {blkio_ticks} 0 0\n",
         pid = pid.data(),
         ppid = ppid.data(),
     )
@@ -4,6 +4,7 @@
 
 use crate::libs::mutex::MutexGuard;
 use crate::{
+    arch::ipc::signal::{Signal, MAX_SIG_NUM},
     filesystem::{
         procfs::{
             template::{Builder, FileOps, ProcFileBuilder},
@@ -150,6 +151,38 @@ impl StatusFileOps {
             pdata.append(&mut format!("\nVmExe:\t{} kB", text).as_bytes().to_owned());
         }
 
+        // 信号状态，格式与Linux一致，第n位对应信号n+1
+        let (sig_pending, sig_blocked) = {
+            let sig_info = pcb.sig_info_irqsave();
+            (
+                sig_info.sig_pending().signal().bits(),
+                sig_info.sig_blocked().bits(),
+            )
+        };
+        let sighand = pcb.sighand();
+        let (mut sig_ignored, mut sig_caught) = (0u64, 0u64);
+        for sig in 1..=MAX_SIG_NUM {
+            let Some(sa) = sighand.handler(Signal::from(sig)) else {
+                continue;
+            };
+            if sa.is_ignore() {
+                sig_ignored |= 1 << (sig - 1);
+            } else if !sa.is_default() {
+                sig_caught |= 1 << (sig - 1);
+            }
+        }
+        pdata.append(
+            &mut format!(
+                "\nSigPnd:\t{:016x}\nShdPnd:\t{:016x}\nSigBlk:\t{:016x}\nSigIgn:\t{:016x}\nSigCgt:\t{:016x}",
+                sig_pending,
+                sighand.shared_pending_signal().bits(),
+                sig_blocked,
+                sig_ignored,
+                sig_caught
+            )
+            .into(),
+        );
+
         pdata.append(
             &mut format!("\nflags: {:?}\n", pcb.flags().clone())
                 .as_bytes()
@@ -0,0 +1,121 @@
+//! /proc/[pid]/timens_offsets - 子进程 time namespace 的时钟偏移量
+//!
+//! 每行一个时钟，格式与Linux一致：
+//!
+//! ```text
+//! monotonic           0         0
+//! boottime            0         0
+//! ```
+//!
+//! 写入时每行为`<时钟> <秒> <纳秒>`，时钟可以是名字（monotonic/boottime）或者clockid（1/7）。
+//! 只能在还没有进程进入这个命名空间之前写入，需要CAP_SYS_TIME权限。
+
+use crate::libs::mutex::MutexGuard;
+use crate::{
+    filesystem::{
+        procfs::{
+            template::{Builder, FileOps, ProcFileBuilder},
+            utils::proc_read,
+        },
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+    process::{cred::CAPFlags, ProcessManager, RawPid},
+    time::{syscall::PosixClockID, PosixTimeSpec},
+};
+use alloc::{
+    format,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+/// /proc/[pid]/timens_offsets 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct TimensOffsetsFileOps {
+    pid: RawPid,
+}
+
+impl TimensOffsetsFileOps {
+    pub fn new_inode(pid: RawPid, parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self { pid }, InodeMode::from_bits_truncate(0o644))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+
+    /// 解析一行`<时钟> <秒> <纳秒>`
+    fn parse_line(line: &str) -> Result<(PosixClockID, PosixTimeSpec), SystemError> {
+        let mut fields = line.split_whitespace();
+        let clock = match fields.next().ok_or(SystemError::EINVAL)? {
+            "monotonic" => PosixClockID::Monotonic,
+            "boottime" => PosixClockID::Boottime,
+            id => PosixClockID::try_from(id.parse::<i32>().map_err(|_| SystemError::EINVAL)?)?,
+        };
+        let sec = fields
+            .next()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or(SystemError::EINVAL)?;
+        let nsec = fields
+            .next()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or(SystemError::EINVAL)?;
+        if fields.next().is_some() {
+            return Err(SystemError::EINVAL);
+        }
+        Ok((clock, PosixTimeSpec::new(sec, nsec)))
+    }
+}
+
+impl FileOps for TimensOffsetsFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let pcb = ProcessManager::find(self.pid).ok_or(SystemError::ESRCH)?;
+        let offsets = pcb.nsproxy().time_ns_for_children.offsets();
+
+        let content = format!(
+            "{:<10} {:>10} {:>9}\n{:<10} {:>10} {:>9}\n",
+            "monotonic",
+            offsets.monotonic.tv_sec,
+            offsets.monotonic.tv_nsec,
+            "boottime",
+            offsets.boottime.tv_sec,
+            offsets.boottime.tv_nsec,
+        );
+
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        if !ProcessManager::current_pcb()
+            .cred()
+            .has_capability(CAPFlags::CAP_SYS_TIME)
+        {
+            return Err(SystemError::EPERM);
+        }
+        let input = core::str::from_utf8(buf).map_err(|_| SystemError::EINVAL)?;
+        let offsets = input
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Self::parse_line)
+            .collect::<Result<Vec<_>, _>>()?;
+        // 每个时钟最多一行
+        if offsets.is_empty() || offsets.len() > 2 {
+            return Err(SystemError::EINVAL);
+        }
+
+        let pcb = ProcessManager::find(self.pid).ok_or(SystemError::ESRCH)?;
+        pcb.nsproxy().time_ns_for_children.set_offsets(&offsets)?;
+        Ok(buf.len())
+    }
+}
@@ -0,0 +1,97 @@
+//! /proc/[pid]/timers - 进程的POSIX定时器
+//!
+//! 每个定时器输出4行，格式与Linux一致：
+//!
+//! ```text
+//! ID: 1
+//! signal: 14/0000000000000000
+//! notify: signal/pid.42
+//! ClockID: 1
+//! ```
+//!
+//! 检查点/恢复工具用它重建定时器（目前不支持ptrace，无法通过PTRACE_GETSIGMASK等请求获取这些信息）。
+
+use crate::libs::mutex::MutexGuard;
+use crate::{
+    filesystem::{
+        procfs::{
+            template::{Builder, FileOps, ProcFileBuilder},
+            utils::proc_read,
+        },
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+    process::{posix_timer::PosixTimerNotify, ptrace::ptrace_may_access, ProcessManager, RawPid},
+};
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::fmt::Write;
+use system_error::SystemError;
+
+/// /proc/[pid]/timers 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct TimersFileOps {
+    pid: RawPid,
+}
+
+impl TimersFileOps {
+    pub fn new_inode(pid: RawPid, parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self { pid }, InodeMode::S_IRUGO)
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for TimersFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let pcb = ProcessManager::find(self.pid).ok_or(SystemError::ESRCH)?;
+        ptrace_may_access(&pcb).map_err(|_| SystemError::EACCES)?;
+
+        let mut content = String::new();
+        let timers = pcb.posix_timers_irqsave();
+        let mut ids = timers.timer_ids().collect::<Vec<_>>();
+        ids.sort_unstable();
+        for id in ids {
+            let timer = timers.get_timer(id)?;
+            let (signo, sigval, notify, target, target_tid) = match timer.notify {
+                PosixTimerNotify::None => (0, 0, "none", "pid", self.pid),
+                PosixTimerNotify::Signal {
+                    signo,
+                    sigval,
+                    target_tid,
+                    thread_directed,
+                } => (
+                    signo as i32,
+                    unsafe { sigval.sival_ptr },
+                    "signal",
+                    if thread_directed { "tid" } else { "pid" },
+                    target_tid,
+                ),
+            };
+            write!(
+                content,
+                "ID: {}\nsignal: {}/{:016x}\nnotify: {}/{}.{}\nClockID: {}\n",
+                id,
+                signo,
+                sigval,
+                notify,
+                target,
+                target_tid.data(),
+                timer.clockid.raw_value()
+            )
+            .map_err(|_| SystemError::ENOMEM)?;
+        }
+        drop(timers);
+
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+}
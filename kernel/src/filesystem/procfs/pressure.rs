@@ -0,0 +1,217 @@
+//! /proc/pressure - 压力阻塞信息（PSI）
+//!
+//! 每个文件的格式与Linux一致：
+//! ```text
+//! some avg10=0.00 avg60=0.00 avg300=0.00 total=0
+//! full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+//! ```
+//!
+//! 向文件写入`<some|full> <阈值us> <窗口us>`可以创建触发器，之后通过poll/epoll等待`POLLPRI`事件。
+//! 每个打开的文件只能创建一个触发器，关闭文件时触发器随之销毁。
+
+use crate::{
+    filesystem::{
+        epoll::{EPollEventType, EPollItem},
+        procfs::{
+            template::{
+                lookup_child_from_table, populate_children_from_table, Builder, DirOps, FileOps,
+                ProcDir, ProcDirBuilder, ProcFileBuilder,
+            },
+            utils::proc_read,
+        },
+        vfs::{FilePrivateData, IndexNode, InodeMode},
+    },
+    libs::mutex::MutexGuard,
+    sched::{
+        loadavg::{FIXED_1, FSHIFT},
+        psi::{psi_pressure, psi_trigger_create, PsiPressure, PsiResource},
+    },
+};
+use alloc::{
+    format,
+    string::String,
+    sync::{Arc, Weak},
+};
+use system_error::SystemError;
+
+/// 触发器描述的最大长度
+const PSI_WRITE_MAX: usize = 128;
+
+/// /proc/pressure 目录的 DirOps 实现
+#[derive(Debug)]
+pub struct PressureDirOps;
+
+impl PressureDirOps {
+    pub fn new_inode(parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcDirBuilder::new(Self, InodeMode::from_bits_truncate(0o555))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+
+    #[expect(clippy::type_complexity)]
+    const STATIC_ENTRIES: &'static [(
+        &'static str,
+        fn(Weak<dyn IndexNode>) -> Arc<dyn IndexNode>,
+    )] = &[
+        ("cpu", |parent| {
+            PressureFileOps::new_inode(PsiResource::Cpu, parent)
+        }),
+        ("io", |parent| {
+            PressureFileOps::new_inode(PsiResource::Io, parent)
+        }),
+        ("memory", |parent| {
+            PressureFileOps::new_inode(PsiResource::Memory, parent)
+        }),
+    ];
+}
+
+impl DirOps for PressureDirOps {
+    fn lookup_child(
+        &self,
+        dir: &ProcDir<Self>,
+        name: &str,
+    ) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let mut cached_children = dir.cached_children().write();
+
+        if let Some(child) =
+            lookup_child_from_table(name, &mut cached_children, Self::STATIC_ENTRIES, |f| {
+                (f)(dir.self_ref_weak().clone())
+            })
+        {
+            return Ok(child);
+        }
+
+        Err(SystemError::ENOENT)
+    }
+
+    fn populate_children(&self, dir: &ProcDir<Self>) {
+        let mut cached_children = dir.cached_children().write();
+        populate_children_from_table(&mut cached_children, Self::STATIC_ENTRIES, |f| {
+            (f)(dir.self_ref_weak().clone())
+        });
+    }
+}
+
+/// /proc/pressure/{cpu,io,memory} 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct PressureFileOps {
+    resource: PsiResource,
+}
+
+impl PressureFileOps {
+    pub fn new_inode(resource: PsiResource, parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self { resource }, InodeMode::from_bits_truncate(0o666))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+
+    fn format_line(name: &str, pressure: &PsiPressure) -> String {
+        // 平均值是FIXED_1定点数表示的百分比，保留两位小数
+        let int = |x: u64| x >> FSHIFT;
+        let frac = |x: u64| int((x & (FIXED_1 - 1)) * 100);
+        let avg = &pressure.avg;
+        format!(
+            "{} avg10={}.{:02} avg60={}.{:02} avg300={}.{:02} total={}\n",
+            name,
+            int(avg[0]),
+            frac(avg[0]),
+            int(avg[1]),
+            frac(avg[1]),
+            int(avg[2]),
+            frac(avg[2]),
+            pressure.total_us
+        )
+    }
+}
+
+impl FileOps for PressureFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let (some, full) = psi_pressure(self.resource);
+        let mut content = Self::format_line("some", &some);
+        content.push_str(&Self::format_line("full", &full));
+
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        len: usize,
+        buf: &[u8],
+        mut data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        if len == 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let input = &buf[..len.min(buf.len()).min(PSI_WRITE_MAX)];
+        let input = core::str::from_utf8(input).map_err(|_| SystemError::EINVAL)?;
+        let input = input.trim_end_matches('\0');
+
+        let private = match &mut *data {
+            FilePrivateData::Procfs(private) => private,
+            _ => return Err(SystemError::EINVAL),
+        };
+        // 每个打开的文件只能有一个触发器
+        if private.psi_trigger.is_some() {
+            return Err(SystemError::EBUSY);
+        }
+
+        private.psi_trigger = Some(psi_trigger_create(self.resource, input)?);
+        Ok(len)
+    }
+
+    fn supports_poll(&self) -> bool {
+        true
+    }
+
+    fn poll(&self, data: &FilePrivateData) -> Result<usize, SystemError> {
+        match data {
+            FilePrivateData::Procfs(private) => match &private.psi_trigger {
+                Some(trigger) => Ok(trigger.poll().bits() as usize),
+                // 没有触发器时，与Linux一样返回错误
+                None => Ok((EPollEventType::EPOLLIN
+                    | EPollEventType::EPOLLOUT
+                    | EPollEventType::EPOLLRDNORM
+                    | EPollEventType::EPOLLWRNORM
+                    | EPollEventType::EPOLLERR
+                    | EPollEventType::EPOLLPRI)
+                    .bits() as usize),
+            },
+            _ => Err(SystemError::EINVAL),
+        }
+    }
+
+    fn add_epitem(
+        &self,
+        epitem: Arc<EPollItem>,
+        data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        if let FilePrivateData::Procfs(private) = data {
+            if let Some(trigger) = &private.psi_trigger {
+                trigger.epitems.lock().push_back(epitem);
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_epitem(
+        &self,
+        epitem: &Arc<EPollItem>,
+        data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        if let FilePrivateData::Procfs(private) = data {
+            if let Some(trigger) = &private.psi_trigger {
+                trigger.epitems.lock().retain(|x| !Arc::ptr_eq(x, epitem));
+            }
+        }
+        Ok(())
+    }
+}
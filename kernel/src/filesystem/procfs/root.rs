@@ -7,12 +7,16 @@ use crate::{
         procfs::{
             cmdline::CmdlineFileOps,
             cpuinfo::CpuInfoFileOps,
+            diskstats::DiskstatsFileOps,
             kmsg_file::KmsgFileOps,
             loadavg::LoadavgFileOps,
+            mdstat::MdstatFileOps,
             meminfo::MeminfoFileOps,
+            memtest::MemtestFileOps,
             mounts::MountsFileOps,
             net::NetDirOps,
             pid::PidDirOps,
+            pressure::PressureDirOps,
             self_::SelfSymOps,
             stat::StatFileOps,
             sys::SysDirOps,
@@ -62,11 +66,15 @@ impl RootDirOps {
     )] = &[
         ("cmdline", CmdlineFileOps::new_inode),
         ("cpuinfo", CpuInfoFileOps::new_inode),
+        ("diskstats", DiskstatsFileOps::new_inode),
         ("kmsg", KmsgFileOps::new_inode),
         ("loadavg", LoadavgFileOps::new_inode),
+        ("mdstat", MdstatFileOps::new_inode),
         ("meminfo", MeminfoFileOps::new_inode),
+        ("memtest", MemtestFileOps::new_inode),
         ("mounts", MountsFileOps::new_inode),
         ("net", NetDirOps::new_inode),
+        ("pressure", PressureDirOps::new_inode),
         ("self", SelfSymOps::new_inode),
         ("stat", StatFileOps::new_inode),
         ("sys", SysDirOps::new_inode),
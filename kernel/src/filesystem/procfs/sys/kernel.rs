@@ -4,7 +4,11 @@
 
 use crate::libs::mutex::MutexGuard;
 use crate::{
-    debug::klog::loglevel::KERNEL_LOG_LEVEL,
+    debug::{
+        klog::{loglevel::KERNEL_LOG_LEVEL, sink::LOG_SINKS},
+        oops::{panic_on_oops, set_panic_on_oops},
+        taint::{add_taint, tainted, TaintFlags},
+    },
     filesystem::{
         procfs::{
             template::{Builder, DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
@@ -12,6 +16,8 @@ use crate::{
         },
         vfs::{FilePrivateData, IndexNode, InodeMode},
     },
+    libs::error_context::{errctx_verbosity, set_errctx_verbosity},
+    process::{cred::CAPFlags, ProcessManager},
 };
 use alloc::{
     format,
@@ -40,18 +46,23 @@ impl DirOps for KernelDirOps {
         dir: &ProcDir<Self>,
         name: &str,
     ) -> Result<Arc<dyn IndexNode>, SystemError> {
-        if name == "printk" {
-            let mut cached_children = dir.cached_children().write();
-            if let Some(child) = cached_children.get(name) {
-                return Ok(child.clone());
-            }
-
-            let inode = PrintkFileOps::new_inode(dir.self_ref_weak().clone());
-            cached_children.insert(name.to_string(), inode.clone());
-            return Ok(inode);
+        let new_inode = match name {
+            "printk" => PrintkFileOps::new_inode,
+            "printk_sinks" => PrintkSinksFileOps::new_inode,
+            "errctx_verbosity" => ErrctxVerbosityFileOps::new_inode,
+            "panic_on_oops" => PanicOnOopsFileOps::new_inode,
+            "tainted" => TaintedFileOps::new_inode,
+            _ => return Err(SystemError::ENOENT),
+        };
+
+        let mut cached_children = dir.cached_children().write();
+        if let Some(child) = cached_children.get(name) {
+            return Ok(child.clone());
         }
 
-        Err(SystemError::ENOENT)
+        let inode = new_inode(dir.self_ref_weak().clone());
+        cached_children.insert(name.to_string(), inode.clone());
+        Ok(inode)
     }
 
     fn populate_children(&self, dir: &ProcDir<Self>) {
@@ -59,6 +70,18 @@ impl DirOps for KernelDirOps {
         cached_children
             .entry("printk".to_string())
             .or_insert_with(|| PrintkFileOps::new_inode(dir.self_ref_weak().clone()));
+        cached_children
+            .entry("printk_sinks".to_string())
+            .or_insert_with(|| PrintkSinksFileOps::new_inode(dir.self_ref_weak().clone()));
+        cached_children
+            .entry("errctx_verbosity".to_string())
+            .or_insert_with(|| ErrctxVerbosityFileOps::new_inode(dir.self_ref_weak().clone()));
+        cached_children
+            .entry("panic_on_oops".to_string())
+            .or_insert_with(|| PanicOnOopsFileOps::new_inode(dir.self_ref_weak().clone()));
+        cached_children
+            .entry("tainted".to_string())
+            .or_insert_with(|| TaintedFileOps::new_inode(dir.self_ref_weak().clone()));
     }
 }
 
@@ -129,3 +152,189 @@ impl FileOps for PrintkFileOps {
         Self::write_config(buf)
     }
 }
+
+/// /proc/sys/kernel/printk_sinks 文件的 FileOps 实现
+///
+/// 各个日志输出端的级别和格式选项，格式参见[`crate::debug::klog::sink`]。
+/// 写入时只修改提到的输出端，需要`CAP_SYS_ADMIN`
+#[derive(Debug)]
+pub struct PrintkSinksFileOps;
+
+impl PrintkSinksFileOps {
+    pub fn new_inode(parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self, InodeMode::from_bits_truncate(0o644))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for PrintkSinksFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let content = format!("{}\n", LOG_SINKS);
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        if !ProcessManager::current_pcb()
+            .cred()
+            .has_capability(CAPFlags::CAP_SYS_ADMIN)
+        {
+            return Err(SystemError::EPERM);
+        }
+        let input = core::str::from_utf8(buf).map_err(|_| SystemError::EINVAL)?;
+        LOG_SINKS.apply(input)?;
+        Ok(buf.len())
+    }
+}
+
+/// /proc/sys/kernel/errctx_verbosity 文件的 FileOps 实现
+///
+/// 错误上下文链打印到内核日志的详细程度，参见[`crate::libs::error_context`]
+#[derive(Debug)]
+pub struct ErrctxVerbosityFileOps;
+
+impl ErrctxVerbosityFileOps {
+    pub fn new_inode(parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self, InodeMode::from_bits_truncate(0o644))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for ErrctxVerbosityFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let content = format!("{}\n", errctx_verbosity());
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let input = core::str::from_utf8(buf).map_err(|_| SystemError::EINVAL)?;
+        let verbosity = input
+            .trim()
+            .parse::<u8>()
+            .map_err(|_| SystemError::EINVAL)?;
+        set_errctx_verbosity(verbosity)?;
+        Ok(buf.len())
+    }
+}
+
+/// /proc/sys/kernel/panic_on_oops 文件的 FileOps 实现
+///
+/// 为1时内核oops直接panic，参见[`crate::debug::oops`]
+#[derive(Debug)]
+pub struct PanicOnOopsFileOps;
+
+impl PanicOnOopsFileOps {
+    pub fn new_inode(parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self, InodeMode::from_bits_truncate(0o644))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for PanicOnOopsFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let content = format!("{}\n", panic_on_oops() as u8);
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let input = core::str::from_utf8(buf).map_err(|_| SystemError::EINVAL)?;
+        let value = input
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| SystemError::EINVAL)?;
+        set_panic_on_oops(value != 0);
+        Ok(buf.len())
+    }
+}
+
+/// /proc/sys/kernel/tainted 文件的 FileOps 实现
+///
+/// 与Linux相同，写入的值会与当前的taint位按位或，已经设置的位不能清除，未定义的位被忽略。
+/// 写入需要`CAP_SYS_ADMIN`
+#[derive(Debug)]
+pub struct TaintedFileOps;
+
+impl TaintedFileOps {
+    pub fn new_inode(parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self, InodeMode::from_bits_truncate(0o644))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for TaintedFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let content = format!("{}\n", tainted().bits());
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        if !ProcessManager::current_pcb()
+            .cred()
+            .has_capability(CAPFlags::CAP_SYS_ADMIN)
+        {
+            return Err(SystemError::EPERM);
+        }
+        let input = core::str::from_utf8(buf).map_err(|_| SystemError::EINVAL)?;
+        let mask = input
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| SystemError::EINVAL)?;
+        add_taint(TaintFlags::from_bits_truncate(mask));
+        Ok(buf.len())
+    }
+}
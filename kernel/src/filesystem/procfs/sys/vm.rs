@@ -3,12 +3,16 @@
 use crate::libs::mutex::MutexGuard;
 use crate::{
     filesystem::{
-        procfs::template::{Builder, DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        procfs::{
+            template::{Builder, DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+            utils::proc_read,
+        },
         vfs::{FilePrivateData, IndexNode, InodeMode},
     },
-    mm::{page::PageReclaimer, page_cache_stats},
+    mm::{page::PageReclaimer, page_cache_stats, writeback},
 };
 use alloc::{
+    format,
     string::ToString,
     sync::{Arc, Weak},
     vec::Vec,
@@ -37,18 +41,23 @@ impl DirOps for VmDirOps {
         dir: &ProcDir<Self>,
         name: &str,
     ) -> Result<Arc<dyn IndexNode>, SystemError> {
-        if name == "drop_caches" {
-            let mut cached_children = dir.cached_children().write();
-            if let Some(child) = cached_children.get(name) {
-                return Ok(child.clone());
-            }
+        let tunable = match name {
+            "drop_caches" => None,
+            _ => Some(DirtyTunable::from_name(name).ok_or(SystemError::ENOENT)?),
+        };
 
-            let inode = DropCachesFileOps::new_inode(dir.self_ref_weak().clone());
-            cached_children.insert(name.to_string(), inode.clone());
-            return Ok(inode);
+        let mut cached_children = dir.cached_children().write();
+        if let Some(child) = cached_children.get(name) {
+            return Ok(child.clone());
         }
 
-        Err(SystemError::ENOENT)
+        let parent = dir.self_ref_weak().clone();
+        let inode = match tunable {
+            Some(tunable) => DirtyTunableFileOps::new_inode(tunable, parent),
+            None => DropCachesFileOps::new_inode(parent),
+        };
+        cached_children.insert(name.to_string(), inode.clone());
+        Ok(inode)
     }
 
     fn populate_children(&self, dir: &ProcDir<Self>) {
@@ -56,6 +65,102 @@ impl DirOps for VmDirOps {
         cached_children
             .entry("drop_caches".to_string())
             .or_insert_with(|| DropCachesFileOps::new_inode(dir.self_ref_weak().clone()));
+        for tunable in DirtyTunable::ALL {
+            cached_children
+                .entry(tunable.name().to_string())
+                .or_insert_with(|| {
+                    DirtyTunableFileOps::new_inode(tunable, dir.self_ref_weak().clone())
+                });
+        }
+    }
+}
+
+/// /proc/sys/vm/dirty_* 脏页写回参数，参见[`crate::mm::writeback`]
+#[derive(Debug, Clone, Copy)]
+pub enum DirtyTunable {
+    Ratio,
+    BackgroundRatio,
+    WritebackCentisecs,
+}
+
+impl DirtyTunable {
+    const ALL: [DirtyTunable; 3] = [
+        DirtyTunable::Ratio,
+        DirtyTunable::BackgroundRatio,
+        DirtyTunable::WritebackCentisecs,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            DirtyTunable::Ratio => "dirty_ratio",
+            DirtyTunable::BackgroundRatio => "dirty_background_ratio",
+            DirtyTunable::WritebackCentisecs => "dirty_writeback_centisecs",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|tunable| tunable.name() == name)
+    }
+
+    fn get(self) -> u32 {
+        match self {
+            DirtyTunable::Ratio => writeback::dirty_ratio(),
+            DirtyTunable::BackgroundRatio => writeback::dirty_background_ratio(),
+            DirtyTunable::WritebackCentisecs => writeback::dirty_writeback_centisecs(),
+        }
+    }
+
+    fn set(self, value: u32) -> Result<(), SystemError> {
+        match self {
+            DirtyTunable::Ratio => writeback::set_dirty_ratio(value),
+            DirtyTunable::BackgroundRatio => writeback::set_dirty_background_ratio(value),
+            DirtyTunable::WritebackCentisecs => {
+                writeback::set_dirty_writeback_centisecs(value);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// /proc/sys/vm/dirty_* 文件的 FileOps 实现
+#[derive(Debug)]
+pub struct DirtyTunableFileOps(DirtyTunable);
+
+impl DirtyTunableFileOps {
+    pub fn new_inode(tunable: DirtyTunable, parent: Weak<dyn IndexNode>) -> Arc<dyn IndexNode> {
+        ProcFileBuilder::new(Self(tunable), InodeMode::from_bits_truncate(0o644))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for DirtyTunableFileOps {
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let content = format!("{}\n", self.0.get());
+        proc_read(offset, len, buf, content.as_bytes())
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let input = core::str::from_utf8(buf).map_err(|_| SystemError::EINVAL)?;
+        let value = input
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| SystemError::EINVAL)?;
+        self.0.set(value)?;
+        Ok(buf.len())
     }
 }
 
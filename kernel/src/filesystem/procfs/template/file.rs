@@ -2,10 +2,11 @@ use crate::libs::mutex::MutexGuard;
 use crate::{
     driver::base::device::device_number::DeviceNumber,
     filesystem::{
+        epoll::EPollItem,
         procfs::{template::Common, ProcfsFilePrivateData},
         vfs::{
             file::FileFlags, vcore::generate_inode_id, FilePrivateData, FileSystem, FileType,
-            IndexNode, InodeFlags, InodeMode, Metadata,
+            IndexNode, InodeFlags, InodeMode, Metadata, PollableInode,
         },
     },
     time::PosixTimeSpec,
@@ -110,6 +111,58 @@ pub trait FileOps: Sync + Send + Sized + Debug {
     fn owner(&self) -> Option<(usize, usize)> {
         None
     }
+
+    /// 是否支持 poll/epoll（可选，默认不支持）
+    ///
+    /// 返回 true 时，需要同时实现 `poll`、`add_epitem` 和 `remove_epitem`
+    fn supports_poll(&self) -> bool {
+        false
+    }
+
+    /// 获取文件当前的 poll 事件
+    fn poll(&self, _data: &FilePrivateData) -> Result<usize, SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    /// 添加 epoll 项
+    fn add_epitem(
+        &self,
+        _epitem: Arc<EPollItem>,
+        _data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    /// 移除 epoll 项
+    fn remove_epitem(
+        &self,
+        _epitem: &Arc<EPollItem>,
+        _data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+}
+
+impl<F: FileOps + 'static> PollableInode for ProcFile<F> {
+    fn poll(&self, private_data: &FilePrivateData) -> Result<usize, SystemError> {
+        self.inner.poll(private_data)
+    }
+
+    fn add_epitem(
+        &self,
+        epitem: Arc<EPollItem>,
+        private_data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        self.inner.add_epitem(epitem, private_data)
+    }
+
+    fn remove_epitem(
+        &self,
+        epitem: &Arc<EPollItem>,
+        private_data: &FilePrivateData,
+    ) -> Result<(), SystemError> {
+        self.inner.remove_epitem(epitem, private_data)
+    }
 }
 
 /// 为 ProcFile 实现 IndexNode trait
@@ -176,4 +229,12 @@ impl<F: FileOps + 'static> IndexNode for ProcFile<F> {
         // Linux procfs allows O_TRUNC; treat it as a no-op for pseudo files.
         Ok(())
     }
+
+    fn as_pollable_inode(&self) -> Result<&dyn PollableInode, SystemError> {
+        if self.inner.supports_poll() {
+            Ok(self)
+        } else {
+            Err(SystemError::ENOSYS)
+        }
+    }
 }
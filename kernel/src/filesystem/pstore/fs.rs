@@ -0,0 +1,286 @@
+//! pstore文件系统
+//!
+//! 挂载时从后端读出所有记录，每条记录对应根目录下的一个只读文件，
+//! 文件名为`<类型>-<后端>-<编号>`，修改时间为记录的写入时间。
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::any::Any;
+use linkme::distributed_slice;
+use system_error::SystemError;
+
+use crate::{
+    driver::base::device::device_number::DeviceNumber,
+    filesystem::vfs::{
+        file::FileFlags, vcore::generate_inode_id, FilePrivateData, FileSystem,
+        FileSystemMakerData, FileType, FsInfo, IndexNode, InodeFlags, InodeMode, Magic, Metadata,
+        MountableFileSystem, SuperBlock, FSMAKER,
+    },
+    libs::{mutex::MutexGuard, spinlock::SpinLock},
+    register_mountable_fs,
+    time::PosixTimeSpec,
+};
+
+use super::{pstore_backend, PstoreRecord};
+
+const PSTOREFS_MAX_NAMELEN: usize = 64;
+const PSTOREFS_BLOCK_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub struct PstoreFS {
+    root_inode: Arc<PstoreRootInode>,
+}
+
+impl PstoreFS {
+    fn new() -> Arc<Self> {
+        Arc::new_cyclic(|fs: &Weak<PstoreFS>| {
+            let mut children = BTreeMap::new();
+            if let Some(backend) = pstore_backend() {
+                for record in backend.read_records() {
+                    let name = format!("{}-{}-{}", record.ty.name(), backend.name(), record.id);
+                    let inode = Arc::new(PstoreFileInode {
+                        metadata: new_metadata(
+                            FileType::File,
+                            InodeMode::from_bits_truncate(0o444),
+                            record.data.len(),
+                            record.time,
+                        ),
+                        record,
+                        fs: fs.clone(),
+                    });
+                    children.insert(name, inode);
+                }
+            }
+            PstoreFS {
+                root_inode: Arc::new(PstoreRootInode {
+                    children: SpinLock::new(children),
+                    metadata: new_metadata(
+                        FileType::Dir,
+                        InodeMode::from_bits_truncate(0o750),
+                        0,
+                        PosixTimeSpec::now(),
+                    ),
+                    fs: fs.clone(),
+                }),
+            }
+        })
+    }
+}
+
+fn new_metadata(
+    file_type: FileType,
+    mode: InodeMode,
+    size: usize,
+    time: PosixTimeSpec,
+) -> Metadata {
+    Metadata {
+        dev_id: 0,
+        inode_id: generate_inode_id(),
+        size: size as i64,
+        blk_size: PSTOREFS_BLOCK_SIZE,
+        blocks: size.div_ceil(PSTOREFS_BLOCK_SIZE),
+        atime: time,
+        mtime: time,
+        ctime: time,
+        btime: time,
+        file_type,
+        mode,
+        flags: InodeFlags::empty(),
+        nlinks: if file_type == FileType::Dir { 2 } else { 1 },
+        uid: 0,
+        gid: 0,
+        raw_dev: DeviceNumber::default(),
+    }
+}
+
+impl FileSystem for PstoreFS {
+    fn root_inode(&self) -> Arc<dyn IndexNode> {
+        self.root_inode.clone()
+    }
+
+    fn info(&self) -> FsInfo {
+        FsInfo {
+            blk_dev_id: 0,
+            max_name_len: PSTOREFS_MAX_NAMELEN,
+        }
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "pstore"
+    }
+
+    fn super_block(&self) -> SuperBlock {
+        SuperBlock::new(
+            Magic::PSTOREFS_MAGIC,
+            PSTOREFS_BLOCK_SIZE as u64,
+            PSTOREFS_MAX_NAMELEN as u64,
+        )
+    }
+}
+
+impl MountableFileSystem for PstoreFS {
+    fn make_mount_data(
+        _raw_data: Option<&str>,
+        _source: &str,
+    ) -> Result<Option<Arc<dyn FileSystemMakerData + 'static>>, SystemError> {
+        Ok(None)
+    }
+
+    fn make_fs(
+        _data: Option<&dyn FileSystemMakerData>,
+    ) -> Result<Arc<dyn FileSystem + 'static>, SystemError> {
+        Ok(PstoreFS::new())
+    }
+}
+
+register_mountable_fs!(PstoreFS, PSTOREFS_MAKER, "pstore");
+
+/// pstore文件系统的根目录
+#[derive(Debug)]
+struct PstoreRootInode {
+    children: SpinLock<BTreeMap<String, Arc<PstoreFileInode>>>,
+    metadata: Metadata,
+    fs: Weak<PstoreFS>,
+}
+
+impl IndexNode for PstoreRootInode {
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _mode: &FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn read_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EISDIR)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EISDIR)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        let mut keys = vec![".".to_string(), "..".to_string()];
+        keys.extend(self.children.lock().keys().cloned());
+        Ok(keys)
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
+        match name {
+            "." | ".." => Ok(self.fs().root_inode()),
+            _ => self
+                .children
+                .lock()
+                .get(name)
+                .map(|inode| inode.clone() as Arc<dyn IndexNode>)
+                .ok_or(SystemError::ENOENT),
+        }
+    }
+
+    /// 删除文件时从后端擦除对应的记录
+    fn unlink(&self, name: &str) -> Result<(), SystemError> {
+        let mut children = self.children.lock();
+        let inode = children.get(name).ok_or(SystemError::ENOENT)?;
+        let backend = pstore_backend().ok_or(SystemError::ENODEV)?;
+        backend.erase(inode.record.ty, inode.record.id)?;
+        children.remove(name);
+        Ok(())
+    }
+}
+
+/// pstore中的一条记录
+#[derive(Debug)]
+struct PstoreFileInode {
+    record: PstoreRecord,
+    metadata: Metadata,
+    fs: Weak<PstoreFS>,
+}
+
+impl IndexNode for PstoreFileInode {
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _mode: &FileFlags,
+    ) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        let data = &self.record.data;
+        if offset >= data.len() {
+            return Ok(0);
+        }
+        let n = len.min(buf.len()).min(data.len() - offset);
+        buf[..n].copy_from_slice(&data[offset..offset + n]);
+        Ok(n)
+    }
+
+    fn write_at(
+        &self,
+        _offset: usize,
+        _len: usize,
+        _buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        Err(SystemError::EPERM)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
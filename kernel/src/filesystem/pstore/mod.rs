@@ -0,0 +1,236 @@
+//! 持久化存储（pstore）
+//!
+//! 内核panic时，把最后一段内核日志和panic时的控制台输出（位置、消息、调用栈）写入重启后仍然保留的
+//! 存储（后端），重启后挂载pstore文件系统读取：
+//!
+//! ```text
+//! mount -t pstore pstore /sys/fs/pstore
+//! ```
+//!
+//! 每条记录对应一个文件（如`dmesg-ramoops-0`），删除文件会同时从后端擦除这条记录。
+//! 目前只有保存在预留内存中的ramoops后端，参见[`ram`]。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/fs/pstore/
+
+mod fs;
+pub mod ram;
+
+use alloc::{string::ToString, sync::Arc, vec::Vec};
+use core::{
+    fmt::{self, Debug, Write},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    driver::base::kobject::{CommonKobj, DynamicKObjKType, KObject, KObjectManager},
+    filesystem::procfs::kmsg::KMSG,
+    init::initcall::INITCALL_FS,
+    libs::spinlock::SpinLock,
+    misc::ksysfs::sys_fs_kobj,
+    time::PosixTimeSpec,
+};
+
+/// panic时保存控制台输出的缓冲区大小
+const PSTORE_CAPTURE_SIZE: usize = 4096;
+/// 一条记录的最大长度
+const PSTORE_RECORD_MAX: usize = 64 * 1024;
+
+/// 记录的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PstoreType {
+    /// panic时的内核日志
+    Dmesg,
+}
+
+impl PstoreType {
+    /// pstore文件系统中文件名的前缀
+    pub fn name(&self) -> &'static str {
+        match self {
+            PstoreType::Dmesg => "dmesg",
+        }
+    }
+}
+
+/// 后端中保存的一条记录
+#[derive(Debug, Clone)]
+pub struct PstoreRecord {
+    pub ty: PstoreType,
+    /// 记录在后端中的编号
+    pub id: u64,
+    /// 写入记录的时间
+    pub time: PosixTimeSpec,
+    pub data: Vec<u8>,
+}
+
+/// pstore后端
+pub trait PstoreBackend: Send + Sync + Debug {
+    /// 后端的名字，用作文件名的一部分
+    fn name(&self) -> &str;
+
+    /// 单条记录的最大长度（字节）
+    fn record_size(&self) -> usize;
+
+    /// 读出后端中的所有记录
+    fn read_records(&self) -> Vec<PstoreRecord>;
+
+    /// # 写入一条记录
+    ///
+    /// 在panic时调用，实现中不能分配内存，也不能睡眠
+    fn write(&self, ty: PstoreType, time: PosixTimeSpec, data: &[u8]) -> Result<(), SystemError>;
+
+    /// 擦除一条记录
+    fn erase(&self, ty: PstoreType, id: u64) -> Result<(), SystemError>;
+}
+
+static PSTORE_BACKEND: SpinLock<Option<Arc<dyn PstoreBackend>>> = SpinLock::new(None);
+
+/// # 注册pstore后端
+///
+/// 同时只能有一个后端
+///
+/// ## 返回值
+///
+/// - `Err(SystemError::EBUSY)`: 已经注册了其他后端
+pub fn pstore_register(backend: Arc<dyn PstoreBackend>) -> Result<(), SystemError> {
+    let mut guard = PSTORE_BACKEND.lock_irqsave();
+    if guard.is_some() {
+        return Err(SystemError::EBUSY);
+    }
+    log::info!("pstore: registered backend {}", backend.name());
+    *guard = Some(backend);
+    Ok(())
+}
+
+pub(super) fn pstore_backend() -> Option<Arc<dyn PstoreBackend>> {
+    PSTORE_BACKEND.lock_irqsave().clone()
+}
+
+/// 定长缓冲区，写满之后丢弃多余的内容
+struct FixedBuf<const N: usize> {
+    data: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> FixedBuf<N> {
+    const fn new() -> Self {
+        Self {
+            data: [0; N],
+            len: 0,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        let n = bytes.len().min(N - self.len);
+        self.data[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+    }
+}
+
+impl<const N: usize> Write for FixedBuf<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.extend(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// 只统计长度的Writer
+struct CountWriter(usize);
+
+impl Write for CountWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+/// 是否正在保存控制台输出
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+static CAPTURE: SpinLock<FixedBuf<PSTORE_CAPTURE_SIZE>> = SpinLock::new(FixedBuf::new());
+/// panic时用于拼接记录的缓冲区，panic时不能分配内存
+static DUMP_BUF: SpinLock<FixedBuf<PSTORE_RECORD_MAX>> = SpinLock::new(FixedBuf::new());
+/// 本次启动以来dump的次数
+static DUMP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// # panic开始时调用
+///
+/// 之后的控制台输出会被保存下来，在[`pstore_panic_dump`]时一起写入后端
+pub fn pstore_panic_begin() {
+    if PSTORE_BACKEND.try_lock().is_ok_and(|b| b.is_none()) {
+        return;
+    }
+    if let Ok(mut capture) = CAPTURE.try_lock() {
+        capture.len = 0;
+        CAPTURING.store(true, Ordering::SeqCst);
+    }
+}
+
+/// 控制台输出，panic期间保存到缓冲区
+#[inline]
+pub fn pstore_console_write(s: &str) {
+    if !CAPTURING.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut capture) = CAPTURE.try_lock() {
+        let _ = capture.write_str(s);
+    }
+}
+
+/// # 把panic信息写入后端
+///
+/// 记录的内容依次为：`Panic#N Part1`、放得下的最近的内核日志、panic时的控制台输出。
+/// 与[`pstore_panic_begin`]成对调用，重复调用时只有第一次生效。
+/// 所有的锁都用try_lock获取，获取失败时放弃写入，避免panic时死锁
+pub fn pstore_panic_dump() {
+    if !CAPTURING.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let Some(backend) = PSTORE_BACKEND.try_lock().ok().and_then(|b| b.clone()) else {
+        return;
+    };
+    let (Ok(capture), Ok(mut out)) = (CAPTURE.try_lock(), DUMP_BUF.try_lock()) else {
+        return;
+    };
+
+    out.len = 0;
+    let limit = backend.record_size().min(PSTORE_RECORD_MAX);
+    let count = DUMP_COUNT.fetch_add(1, Ordering::SeqCst) + 1;
+    let _ = writeln!(out, "Panic#{} Part1", count);
+
+    // 为控制台输出预留空间，剩下的空间保存最近的内核日志
+    let budget = limit.saturating_sub(out.len + capture.len);
+    if let Some(kmsg) = unsafe { KMSG.as_ref() }.and_then(|k| k.try_lock().ok()) {
+        let total = kmsg.messages().count();
+        let mut first = total;
+        let mut used = 0;
+        for msg in kmsg.messages().rev() {
+            let mut counter = CountWriter(0);
+            let _ = write!(counter, "{}", msg);
+            if used + counter.0 > budget {
+                break;
+            }
+            used += counter.0;
+            first -= 1;
+        }
+        for msg in kmsg.messages().skip(first) {
+            let _ = write!(out, "{}", msg);
+        }
+    }
+    out.extend(capture.as_bytes());
+
+    let len = out.len.min(limit);
+    let _ = backend.write(PstoreType::Dmesg, PosixTimeSpec::now(), &out.data[..len]);
+}
+
+/// 创建`/sys/fs/pstore`，作为pstore文件系统的挂载点
+#[unified_init(INITCALL_FS)]
+fn pstore_init() -> Result<(), SystemError> {
+    let kobj = CommonKobj::new("pstore".to_string());
+    kobj.set_parent(Some(Arc::downgrade(&(sys_fs_kobj() as Arc<dyn KObject>))));
+    KObjectManager::init_and_add_kobj(kobj, Some(&DynamicKObjKType))
+}
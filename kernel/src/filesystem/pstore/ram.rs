@@ -0,0 +1,264 @@
+//! ramoops: 把pstore记录保存在重启后内容不丢失的一段预留内存中
+//!
+//! 通过内核命令行参数配置：
+//!
+//! - `ramoops.mem_address`: 预留内存的物理地址
+//! - `ramoops.mem_size`: 预留内存的大小
+//! - `ramoops.record_size`: 每条记录占用的空间，默认4K
+//!
+//! 例如`ramoops.mem_address=0x8000000 ramoops.mem_size=0x100000`。这段内存需要是热重启后内容不变的内存
+//! （例如QEMU的普通内存），并且不会被固件和bootloader改写。
+//!
+//! 预留内存被划分为若干个大小为`record_size`的区域，每个区域保存一条记录，区域写满之后从头覆盖。
+//! 每个区域以[`RamoopsZoneHeader`]开头，之后是数据。和Linux一样，数据的第一行是
+//! `====<秒>.<微秒>-D`格式的写入时间。
+//!
+//! EFI变量后端尚未实现。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/fs/pstore/ram.c
+
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    fmt::Write,
+    mem::size_of,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use log::{info, warn};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    init::{cmdline::KernelCmdlineEarlyKV, initcall::INITCALL_DEVICE},
+    libs::align::{page_align_down, page_align_up},
+    mm::{
+        memblock::mem_block_manager,
+        mmio_buddy::{mmio_pool, MMIOSpaceGuard},
+        PhysAddr, VirtAddr,
+    },
+    time::PosixTimeSpec,
+};
+
+use super::{pstore_register, FixedBuf, PstoreBackend, PstoreRecord, PstoreType};
+
+kernel_cmdline_param_early_kv!(RAMOOPS_MEM_ADDRESS_PARAM, "ramoops.mem_address", "");
+kernel_cmdline_param_early_kv!(RAMOOPS_MEM_SIZE_PARAM, "ramoops.mem_size", "");
+kernel_cmdline_param_early_kv!(RAMOOPS_RECORD_SIZE_PARAM, "ramoops.record_size", "4096");
+
+/// 区域头部的签名："DBGC"
+const RAMOOPS_SIG: u32 = 0x43474244;
+/// 最小的区域大小
+const RAMOOPS_MIN_RECORD_SIZE: usize = 512;
+/// 数据开头时间行的最大长度
+const RAMOOPS_TIME_LINE_MAX: usize = 48;
+
+/// 预留内存是否已经从memblock中保留
+static RAMOOPS_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// 每个区域开头的头部
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct RamoopsZoneHeader {
+    sig: u32,
+    /// 数据的长度
+    size: u32,
+}
+
+const RAMOOPS_HDR_SIZE: usize = size_of::<RamoopsZoneHeader>();
+
+/// 解析十进制或者`0x`开头的十六进制数，允许`K`/`M`/`G`后缀
+fn parse_num(s: &str) -> Option<usize> {
+    let s = s.trim();
+    let (s, shift) = match s.as_bytes().last()? {
+        b'k' | b'K' => (&s[..s.len() - 1], 10),
+        b'm' | b'M' => (&s[..s.len() - 1], 20),
+        b'g' | b'G' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    let num = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok()?,
+        None => s.parse().ok()?,
+    };
+    num.checked_mul(1 << shift)
+}
+
+/// 从命令行参数中读取(物理地址, 大小, 区域大小)
+fn ramoops_config() -> Option<(PhysAddr, usize, usize)> {
+    let addr = parse_num(RAMOOPS_MEM_ADDRESS_PARAM.value_str()?)?;
+    let size = parse_num(RAMOOPS_MEM_SIZE_PARAM.value_str()?)?;
+    let record_size = RAMOOPS_RECORD_SIZE_PARAM
+        .value_str()
+        .and_then(parse_num)
+        .unwrap_or(4096)
+        .max(RAMOOPS_MIN_RECORD_SIZE)
+        & !(RAMOOPS_HDR_SIZE - 1);
+    // 区域头部需要对齐访问
+    if addr % RAMOOPS_HDR_SIZE != 0 || size < record_size {
+        return None;
+    }
+    Some((PhysAddr::new(addr), size, record_size))
+}
+
+/// # 保留ramoops使用的内存
+///
+/// 在物理内存区域加入memblock之后、页帧分配器初始化之前调用，避免这段内存被分配出去
+pub fn ramoops_early_reserve() {
+    let Some((addr, size, _)) = ramoops_config() else {
+        return;
+    };
+    let base = PhysAddr::new(page_align_down(addr.data()));
+    let len = page_align_up(addr.data() + size) - base.data();
+    match mem_block_manager().reserve_block(base, len) {
+        Ok(()) => RAMOOPS_RESERVED.store(true, Ordering::SeqCst),
+        Err(e) => warn!(
+            "ramoops: failed to reserve memory: base={:?}, size={:#x}, error={:?}",
+            base, len, e
+        ),
+    }
+}
+
+/// ramoops后端
+#[derive(Debug)]
+pub struct RamoopsBackend {
+    _mmio_guard: MMIOSpaceGuard,
+    vaddr: VirtAddr,
+    record_size: usize,
+    nr_zones: usize,
+    /// 下一次写入的区域
+    next_zone: AtomicUsize,
+}
+
+impl RamoopsBackend {
+    fn new(addr: PhysAddr, size: usize, record_size: usize) -> Result<Self, SystemError> {
+        let offset = addr.data() - page_align_down(addr.data());
+        let map_size = page_align_up(size.checked_add(offset).ok_or(SystemError::EOVERFLOW)?);
+        let mmio_guard = mmio_pool().create_mmio(map_size)?;
+        let vaddr = unsafe { mmio_guard.map_any_phys(addr, size)? };
+
+        let backend = Self {
+            _mmio_guard: mmio_guard,
+            vaddr,
+            record_size,
+            nr_zones: size / record_size,
+            next_zone: AtomicUsize::new(0),
+        };
+        // 从第一个空闲区域开始写，都不空闲时覆盖第一个区域
+        let first_free = (0..backend.nr_zones)
+            .find(|&i| backend.header(i).sig != RAMOOPS_SIG)
+            .unwrap_or(0);
+        backend.next_zone.store(first_free, Ordering::SeqCst);
+        Ok(backend)
+    }
+
+    fn zone_ptr(&self, zone: usize) -> *mut u8 {
+        (self.vaddr.data() + zone * self.record_size) as *mut u8
+    }
+
+    fn header(&self, zone: usize) -> RamoopsZoneHeader {
+        unsafe { core::ptr::read_volatile(self.zone_ptr(zone) as *const RamoopsZoneHeader) }
+    }
+
+    fn set_header(&self, zone: usize, header: RamoopsZoneHeader) {
+        unsafe { core::ptr::write_volatile(self.zone_ptr(zone) as *mut RamoopsZoneHeader, header) }
+    }
+
+    fn zone_data(&self, zone: usize) -> &[u8] {
+        let size = (self.header(zone).size as usize).min(self.record_size - RAMOOPS_HDR_SIZE);
+        unsafe { core::slice::from_raw_parts(self.zone_ptr(zone).add(RAMOOPS_HDR_SIZE), size) }
+    }
+
+    /// 解析数据开头的时间行，返回(时间, 去掉时间行之后的数据)
+    fn parse_time_line(data: &[u8]) -> (PosixTimeSpec, &[u8]) {
+        let parse = || -> Option<(PosixTimeSpec, usize)> {
+            let line_end = data.iter().position(|&c| c == b'\n')?;
+            let line = core::str::from_utf8(&data[..line_end]).ok()?;
+            let (time, _) = line.strip_prefix("====")?.split_once('-')?;
+            let (sec, usec) = time.split_once('.')?;
+            let ts = PosixTimeSpec::new(sec.parse().ok()?, usec.parse::<i64>().ok()? * 1000);
+            Some((ts, line_end + 1))
+        };
+        match parse() {
+            Some((ts, skip)) => (ts, &data[skip..]),
+            None => (PosixTimeSpec::default(), data),
+        }
+    }
+}
+
+impl PstoreBackend for RamoopsBackend {
+    fn name(&self) -> &str {
+        "ramoops"
+    }
+
+    fn record_size(&self) -> usize {
+        self.record_size - RAMOOPS_HDR_SIZE - RAMOOPS_TIME_LINE_MAX
+    }
+
+    fn read_records(&self) -> Vec<PstoreRecord> {
+        (0..self.nr_zones)
+            .filter(|&i| self.header(i).sig == RAMOOPS_SIG)
+            .map(|i| {
+                let (time, data) = Self::parse_time_line(self.zone_data(i));
+                PstoreRecord {
+                    ty: PstoreType::Dmesg,
+                    id: i as u64,
+                    time,
+                    data: data.to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    fn write(&self, _ty: PstoreType, time: PosixTimeSpec, data: &[u8]) -> Result<(), SystemError> {
+        let zone = self
+            .next_zone
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |z| {
+                Some((z + 1) % self.nr_zones)
+            })
+            .unwrap();
+
+        let mut line = FixedBuf::<RAMOOPS_TIME_LINE_MAX>::new();
+        let _ = writeln!(line, "===={}.{:06}-D", time.tv_sec, time.tv_nsec / 1000);
+        let data_len = data.len().min(self.record_size());
+
+        // 先清除签名，写完数据后再写入头部，写入过程中重启不会留下不完整的记录
+        self.set_header(zone, RamoopsZoneHeader { sig: 0, size: 0 });
+        unsafe {
+            let dst = self.zone_ptr(zone).add(RAMOOPS_HDR_SIZE);
+            core::ptr::copy_nonoverlapping(line.as_bytes().as_ptr(), dst, line.len);
+            core::ptr::copy_nonoverlapping(data.as_ptr(), dst.add(line.len), data_len);
+        }
+        self.set_header(
+            zone,
+            RamoopsZoneHeader {
+                sig: RAMOOPS_SIG,
+                size: (line.len + data_len) as u32,
+            },
+        );
+        Ok(())
+    }
+
+    fn erase(&self, _ty: PstoreType, id: u64) -> Result<(), SystemError> {
+        let zone = id as usize;
+        if zone >= self.nr_zones {
+            return Err(SystemError::ENOENT);
+        }
+        self.set_header(zone, RamoopsZoneHeader { sig: 0, size: 0 });
+        Ok(())
+    }
+}
+
+#[unified_init(INITCALL_DEVICE)]
+fn ramoops_init() -> Result<(), SystemError> {
+    let Some((addr, size, record_size)) = ramoops_config() else {
+        return Ok(());
+    };
+    if !RAMOOPS_RESERVED.load(Ordering::SeqCst) {
+        warn!("ramoops: memory at {:?} is not reserved, disabled", addr);
+        return Ok(());
+    }
+    let backend = RamoopsBackend::new(addr, size, record_size)?;
+    info!(
+        "ramoops: using {:#x}@{:?}, {} records of {:#x} bytes",
+        size, addr, backend.nr_zones, record_size
+    );
+    pstore_register(Arc::new(backend))
+}
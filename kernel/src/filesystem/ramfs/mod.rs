@@ -126,6 +126,10 @@ impl FileSystem for RamFS {
         "ramfs"
     }
 
+    fn dcache_enabled(&self) -> bool {
+        true
+    }
+
     fn super_block(&self) -> SuperBlock {
         self.super_block.read().clone()
     }
@@ -0,0 +1,601 @@
+//! SMB2连接
+//!
+//! 一个挂载使用一条TCP连接、一个会话和一个树连接（共享）。请求是同步的：
+//! 同一时刻只有一个请求在连接上等待响应，因此每个请求只消耗一个credit，
+//! 读写请求的长度也限制在64KiB以内（不需要多credit的LargeMTU）。
+//!
+//! 服务器要求签名时，会话建立之后的请求和响应都用HMAC-SHA256签名（SMB 2.0.2/2.1）。
+//!
+//! 传输出错（包括等待响应时被信号打断）之后，连接上可能留有半条消息，
+//! 连接被标记为断开，之后的请求都返回EIO，需要重新挂载。目前不支持自动重连。
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use system_error::SystemError;
+
+use crate::{
+    libs::{
+        crypto::{hmac::Hmac, sha256::Sha256},
+        mutex::Mutex,
+        rand::rand_bytes,
+    },
+    net::socket::{
+        common::ShutdownBit,
+        create_socket,
+        endpoint::{Endpoint, IpEndpoint},
+        AddressFamily, Socket, PMSG, PSOCK,
+    },
+    time::PosixTimeSpec,
+};
+
+use super::{
+    ntlm::{self, NtlmCredentials},
+    proto::*,
+};
+
+/// 单个读写请求的最大长度
+const SMB2_MAX_IO_SIZE: u32 = 64 * 1024;
+/// 每个请求向服务器申请的credit数
+const SMB2_CREDIT_REQUEST: u16 = 16;
+/// 接收消息的最大长度，超过时认为服务器有问题
+const SMB2_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// 连接的会话状态，由请求锁保护
+struct SessionState {
+    message_id: u64,
+    session_id: u64,
+    tree_id: u32,
+    /// 签名使用的会话密钥，为None时不签名
+    signing_key: Option<[u8; 16]>,
+}
+
+pub struct SmbConn {
+    sock: Arc<dyn Socket>,
+    /// 请求锁，保证同一时刻只有一个请求在等待响应
+    state: Mutex<SessionState>,
+    broken: AtomicBool,
+    dialect: u16,
+    /// 读写请求的最大长度
+    max_read: u32,
+    max_write: u32,
+    /// QUERY_DIRECTORY、QUERY_INFO的最大输出长度
+    max_transact: u32,
+    /// 服务器名和共享名，用于日志
+    unc: String,
+}
+
+impl core::fmt::Debug for SmbConn {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SmbConn")
+            .field("unc", &self.unc)
+            .field("dialect", &self.dialect)
+            .field("broken", &self.broken.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// 当前时间（FILETIME）
+fn now_filetime() -> u64 {
+    timespec_to_filetime(&PosixTimeSpec::now())
+}
+
+fn sign(key: &[u8; 16], msg: &[u8]) -> [u8; 16] {
+    let mut mac = Hmac::<Sha256>::new(key);
+    mac.update(&msg[..48]);
+    mac.update(&[0u8; 16]);
+    mac.update(&msg[SMB2_HEADER_SIZE..]);
+    let mut sig = [0u8; 16];
+    sig.copy_from_slice(&mac.finalize()[..16]);
+    sig
+}
+
+impl SmbConn {
+    /// # 连接服务器并挂载共享
+    ///
+    /// 依次完成TCP连接、NEGOTIATE、NTLMv2认证和TREE_CONNECT
+    ///
+    /// ## 参数
+    /// - `endpoint`: 服务器地址
+    /// - `server`: 服务器名，用于TREE_CONNECT中的UNC路径
+    /// - `share`: 共享名
+    /// - `creds`: 认证使用的身份，用户名为空时匿名登录
+    pub fn connect(
+        endpoint: IpEndpoint,
+        server: &str,
+        share: &str,
+        creds: &NtlmCredentials,
+    ) -> Result<Arc<Self>, SystemError> {
+        let sock = create_socket(AddressFamily::INet, PSOCK::Stream, 0, false, false)?;
+        sock.connect(Endpoint::Ip(endpoint))?;
+
+        let mut conn = Self {
+            sock,
+            state: Mutex::new(SessionState {
+                message_id: 0,
+                session_id: 0,
+                tree_id: 0,
+                signing_key: None,
+            }),
+            broken: AtomicBool::new(false),
+            dialect: SMB2_DIALECT_202,
+            max_read: SMB2_MAX_IO_SIZE,
+            max_write: SMB2_MAX_IO_SIZE,
+            max_transact: SMB2_MAX_IO_SIZE,
+            unc: format!("\\\\{}\\{}", server, share),
+        };
+
+        let result = conn
+            .negotiate()
+            .and_then(|signing_required| conn.session_setup(creds, signing_required))
+            .and_then(|_| conn.tree_connect());
+        if let Err(e) = result {
+            conn.shutdown();
+            return Err(e);
+        }
+        log::info!(
+            "smb: connected to {} (dialect {:#x})",
+            conn.unc,
+            conn.dialect
+        );
+        Ok(Arc::new(conn))
+    }
+
+    fn send_all(&self, buf: &[u8]) -> Result<(), SystemError> {
+        let mut done = 0;
+        while done < buf.len() {
+            let n = self.sock.send(&buf[done..], PMSG::NOSIGNAL)?;
+            if n == 0 {
+                return Err(SystemError::EPIPE);
+            }
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn recv_all(&self, buf: &mut [u8]) -> Result<(), SystemError> {
+        let mut done = 0;
+        while done < buf.len() {
+            let n = self.sock.recv(&mut buf[done..], PMSG::empty())?;
+            if n == 0 {
+                return Err(SystemError::ECONNRESET);
+            }
+            done += n;
+        }
+        Ok(())
+    }
+
+    /// 接收一条消息（去掉4字节的长度前缀）
+    fn recv_message(&self) -> Result<Smb2Response, SystemError> {
+        let mut len = [0u8; 4];
+        self.recv_all(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize & 0x00ff_ffff;
+        if len > SMB2_MAX_MESSAGE_SIZE {
+            return Err(SystemError::EBADMSG);
+        }
+        let mut buf = vec![0u8; len];
+        self.recv_all(&mut buf)?;
+        Smb2Response::new(buf)
+    }
+
+    /// # 发送请求并等待响应
+    ///
+    /// 返回的响应可能带有错误状态，由调用者检查
+    fn transact(&self, req: Smb2Request) -> Result<Smb2Response, SystemError> {
+        if self.broken.load(Ordering::Acquire) {
+            return Err(SystemError::EIO);
+        }
+        let command = req.command;
+        let mut msg = req.into_bytes();
+
+        let mut state = self.state.lock();
+        let message_id = state.message_id;
+        state.message_id += 1;
+
+        // SMB 2.0.2中CreditCharge是保留字段
+        let credit_charge: u16 = if self.dialect == SMB2_DIALECT_202 {
+            0
+        } else {
+            1
+        };
+        msg[6..8].copy_from_slice(&credit_charge.to_le_bytes());
+        msg[14..16].copy_from_slice(&SMB2_CREDIT_REQUEST.to_le_bytes());
+        msg[24..32].copy_from_slice(&message_id.to_le_bytes());
+        msg[36..40].copy_from_slice(&state.tree_id.to_le_bytes());
+        msg[40..48].copy_from_slice(&state.session_id.to_le_bytes());
+        let signing_key = state.signing_key.filter(|_| command != SMB2_NEGOTIATE);
+        if let Some(key) = &signing_key {
+            msg[16..20].copy_from_slice(&SMB2_FLAGS_SIGNED.to_le_bytes());
+            let sig = sign(key, &msg);
+            msg[48..64].copy_from_slice(&sig);
+        }
+
+        let result = self.exchange(&msg, message_id, signing_key.as_ref());
+        if let Err(e) = &result {
+            self.broken.store(true, Ordering::Release);
+            log::warn!("smb: {}: connection lost: {:?}", self.unc, e);
+        }
+        drop(state);
+        result
+    }
+
+    fn exchange(
+        &self,
+        msg: &[u8],
+        message_id: u64,
+        signing_key: Option<&[u8; 16]>,
+    ) -> Result<Smb2Response, SystemError> {
+        let mut framed = Vec::with_capacity(4 + msg.len());
+        framed.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+        framed.extend_from_slice(msg);
+        self.send_all(&framed)?;
+
+        loop {
+            let resp = self.recv_message()?;
+            // 跳过服务器主动发送的通知（比如oplock break）
+            if resp.flags() & SMB2_FLAGS_SERVER_TO_REDIR == 0 || resp.message_id() != message_id {
+                continue;
+            }
+            // 异步处理的请求先收到STATUS_PENDING的临时响应，最终响应稍后到达
+            if resp.status() == STATUS_PENDING && resp.flags() & SMB2_FLAGS_ASYNC_COMMAND != 0 {
+                continue;
+            }
+            if let Some(key) = signing_key {
+                if resp.flags() & SMB2_FLAGS_SIGNED != 0 && sign(key, &resp.buf) != resp.buf[48..64]
+                {
+                    log::warn!("smb: {}: bad signature in response", self.unc);
+                    return Err(SystemError::EBADMSG);
+                }
+            }
+            return Ok(resp);
+        }
+    }
+
+    /// 发送请求，响应的状态不是STATUS_SUCCESS时返回对应的错误码
+    fn request(&self, req: Smb2Request) -> Result<Smb2Response, SystemError> {
+        let resp = self.transact(req)?;
+        match resp.status() {
+            STATUS_SUCCESS => Ok(resp),
+            status => Err(status_to_errno(status)),
+        }
+    }
+
+    /// # 协商协议版本
+    ///
+    /// ## 返回值
+    /// 服务器是否要求签名
+    fn negotiate(&mut self) -> Result<bool, SystemError> {
+        let mut req = Smb2Request::new(SMB2_NEGOTIATE, 36);
+        req.u16(2)
+            .u16(SMB2_NEGOTIATE_SIGNING_ENABLED)
+            .u16(0)
+            .u32(0)
+            .bytes(&rand_bytes::<16>())
+            .u64(0)
+            .u16(SMB2_DIALECT_202)
+            .u16(SMB2_DIALECT_21);
+        let resp = self.request(req)?;
+
+        let security_mode = resp.body_u16(2)?;
+        let dialect = resp.body_u16(4)?;
+        if dialect != SMB2_DIALECT_202 && dialect != SMB2_DIALECT_21 {
+            log::warn!("smb: {}: unsupported dialect {:#x}", self.unc, dialect);
+            return Err(SystemError::EPROTO);
+        }
+        self.dialect = dialect;
+        self.max_transact = resp.body_u32(28)?.min(SMB2_MAX_IO_SIZE);
+        self.max_read = resp.body_u32(32)?.min(SMB2_MAX_IO_SIZE);
+        self.max_write = resp.body_u32(36)?.min(SMB2_MAX_IO_SIZE);
+        if self.max_read == 0 || self.max_write == 0 || self.max_transact == 0 {
+            return Err(SystemError::EPROTO);
+        }
+        Ok(security_mode & SMB2_NEGOTIATE_SIGNING_REQUIRED != 0)
+    }
+
+    fn session_setup_request(&self, token: &[u8]) -> Result<Smb2Response, SystemError> {
+        let mut req = Smb2Request::new(SMB2_SESSION_SETUP, 25);
+        req.u8(0)
+            .u8(SMB2_NEGOTIATE_SIGNING_ENABLED as u8)
+            .u32(0)
+            .u32(0)
+            .u16((SMB2_HEADER_SIZE + 24) as u16)
+            .u16(token.len() as u16)
+            .u64(0)
+            .bytes(token);
+        self.transact(req)
+    }
+
+    /// NTLMv2认证，建立会话
+    fn session_setup(
+        &mut self,
+        creds: &NtlmCredentials,
+        signing_required: bool,
+    ) -> Result<(), SystemError> {
+        let resp = self.session_setup_request(&ntlm::negotiate_message(creds))?;
+        if resp.status() != STATUS_MORE_PROCESSING_REQUIRED {
+            return Err(status_to_errno(resp.status()));
+        }
+        self.state.lock().session_id = resp.session_id();
+        let token = resp.buffer(resp.body_u16(4)? as usize, resp.body_u16(6)? as usize)?;
+        let challenge = ntlm::parse_challenge(token)?;
+
+        let (token, session_key) =
+            ntlm::authenticate_message(creds, &challenge, &rand_bytes::<8>(), now_filetime());
+        let resp = self.session_setup_request(&token)?;
+        if resp.status() != STATUS_SUCCESS {
+            log::warn!(
+                "smb: {}: authentication failed: {:#x}",
+                self.unc,
+                resp.status()
+            );
+            return Err(status_to_errno(resp.status()));
+        }
+
+        let session_flags = resp.body_u16(2)?;
+        let anonymous =
+            session_flags & (SMB2_SESSION_FLAG_IS_GUEST | SMB2_SESSION_FLAG_IS_NULL) != 0;
+        if signing_required {
+            // 来宾和匿名会话没有会话密钥，无法签名
+            let key = session_key
+                .filter(|_| !anonymous)
+                .ok_or(SystemError::EACCES)?;
+            self.state.lock().signing_key = Some(key);
+        }
+        Ok(())
+    }
+
+    fn tree_connect(&mut self) -> Result<(), SystemError> {
+        let path = utf16le(&self.unc);
+        let mut req = Smb2Request::new(SMB2_TREE_CONNECT, 9);
+        req.u16(0)
+            .u16((SMB2_HEADER_SIZE + 8) as u16)
+            .u16(path.len() as u16)
+            .bytes(&path);
+        let resp = self.request(req)?;
+        self.state.lock().tree_id = resp.tree_id();
+        Ok(())
+    }
+
+    /// 断开树连接和会话，关闭socket，在卸载时调用
+    pub fn disconnect(&self) {
+        if !self.broken.load(Ordering::Acquire) {
+            let mut req = Smb2Request::new(SMB2_TREE_DISCONNECT, 4);
+            req.u16(0);
+            let _ = self.request(req);
+            let mut req = Smb2Request::new(SMB2_LOGOFF, 4);
+            req.u16(0);
+            let _ = self.request(req);
+        }
+        self.shutdown();
+    }
+
+    fn shutdown(&self) {
+        self.broken.store(true, Ordering::Release);
+        let _ = self.sock.shutdown(ShutdownBit::SHUT_RDWR);
+        let _ = self.sock.do_close();
+    }
+
+    /// # 打开或者创建文件
+    ///
+    /// ## 参数
+    /// - `path`: 相对于共享根目录、以`\`分隔的路径，根目录为空字符串
+    /// - `access`: DesiredAccess
+    /// - `disposition`: 文件存在或者不存在时的行为，如[`FILE_OPEN`]、[`FILE_CREATE`]
+    /// - `options`: CreateOptions，如[`FILE_DIRECTORY_FILE`]
+    /// - `attributes`: 创建文件时的属性
+    pub fn create(
+        &self,
+        path: &str,
+        access: u32,
+        disposition: u32,
+        options: u32,
+        attributes: u32,
+    ) -> Result<(FileId, FileAttrs), SystemError> {
+        let mut name = utf16le(path);
+        let name_len = name.len();
+        // Buffer至少有一个字节
+        if name.is_empty() {
+            name.push(0);
+        }
+        let mut req = Smb2Request::new(SMB2_CREATE, 57);
+        req.u8(0)
+            .u8(0)
+            .u32(2)
+            .u64(0)
+            .u64(0)
+            .u32(access | SYNCHRONIZE)
+            .u32(attributes)
+            .u32(FILE_SHARE_ALL)
+            .u32(disposition)
+            .u32(options)
+            .u16((SMB2_HEADER_SIZE + 56) as u16)
+            .u16(name_len as u16)
+            .u32(0)
+            .u32(0)
+            .bytes(&name);
+        let resp = self.request(req)?;
+        let attrs = FileAttrs::parse_create(&resp.buf, SMB2_HEADER_SIZE + 8)?;
+        Ok((resp.body_file_id(64)?, attrs))
+    }
+
+    pub fn close(&self, fid: &FileId) -> Result<(), SystemError> {
+        let mut req = Smb2Request::new(SMB2_CLOSE, 24);
+        req.u16(0).u32(0).file_id(fid);
+        self.request(req).map(|_| ())
+    }
+
+    /// 读取文件，返回读到的字节数，读到文件末尾时返回0
+    pub fn read(&self, fid: &FileId, offset: u64, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let mut done = 0;
+        while done < buf.len() {
+            let len = (buf.len() - done).min(self.max_read as usize);
+            let mut req = Smb2Request::new(SMB2_READ, 49);
+            req.u8(0x50)
+                .u8(0)
+                .u32(len as u32)
+                .u64(offset + done as u64)
+                .file_id(fid)
+                .u32(0)
+                .u32(0)
+                .u32(0)
+                .u16(0)
+                .u16(0)
+                .u8(0);
+            let resp = self.transact(req)?;
+            match resp.status() {
+                STATUS_SUCCESS => {}
+                STATUS_END_OF_FILE => break,
+                status => return Err(status_to_errno(status)),
+            }
+            let data = resp.buffer(resp.body_u8(2)? as usize, resp.body_u32(4)? as usize)?;
+            let n = data.len().min(len);
+            buf[done..done + n].copy_from_slice(&data[..n]);
+            done += n;
+            if n < len {
+                break;
+            }
+        }
+        Ok(done)
+    }
+
+    /// 写入文件，返回写入的字节数
+    pub fn write(&self, fid: &FileId, offset: u64, buf: &[u8]) -> Result<usize, SystemError> {
+        let mut done = 0;
+        while done < buf.len() {
+            let len = (buf.len() - done).min(self.max_write as usize);
+            let mut req = Smb2Request::new(SMB2_WRITE, 49);
+            req.u16((SMB2_HEADER_SIZE + 48) as u16)
+                .u32(len as u32)
+                .u64(offset + done as u64)
+                .file_id(fid)
+                .u32(0)
+                .u32(0)
+                .u16(0)
+                .u16(0)
+                .u32(0)
+                .bytes(&buf[done..done + len]);
+            let resp = self.request(req)?;
+            let n = (resp.body_u32(4)? as usize).min(len);
+            done += n;
+            if n < len {
+                break;
+            }
+        }
+        Ok(done)
+    }
+
+    pub fn flush(&self, fid: &FileId) -> Result<(), SystemError> {
+        let mut req = Smb2Request::new(SMB2_FLUSH, 24);
+        req.u16(0).u32(0).file_id(fid);
+        self.request(req).map(|_| ())
+    }
+
+    /// # 枚举目录
+    ///
+    /// ## 参数
+    /// - `restart`: 从头开始枚举
+    ///
+    /// ## 返回值
+    /// 下一批目录项，枚举结束时返回`None`
+    pub fn query_directory(
+        &self,
+        fid: &FileId,
+        restart: bool,
+    ) -> Result<Option<Vec<DirEntry>>, SystemError> {
+        let pattern = utf16le("*");
+        let mut req = Smb2Request::new(SMB2_QUERY_DIRECTORY, 33);
+        req.u8(FILE_DIRECTORY_INFORMATION)
+            .u8(if restart { SMB2_RESTART_SCANS } else { 0 })
+            .u32(0)
+            .file_id(fid)
+            .u16((SMB2_HEADER_SIZE + 32) as u16)
+            .u16(pattern.len() as u16)
+            .u32(self.max_transact)
+            .bytes(&pattern);
+        let resp = self.transact(req)?;
+        match resp.status() {
+            STATUS_SUCCESS => {}
+            STATUS_NO_MORE_FILES => return Ok(None),
+            status => return Err(status_to_errno(status)),
+        }
+        let data = resp.buffer(resp.body_u16(2)? as usize, resp.body_u32(4)? as usize)?;
+        if data.is_empty() {
+            return Ok(None);
+        }
+        parse_directory_info(data).map(Some)
+    }
+
+    /// 查询文件或者文件系统的信息
+    pub fn query_info(
+        &self,
+        fid: &FileId,
+        info_type: u8,
+        class: u8,
+    ) -> Result<Vec<u8>, SystemError> {
+        let mut req = Smb2Request::new(SMB2_QUERY_INFO, 41);
+        req.u8(info_type)
+            .u8(class)
+            .u32(self.max_transact)
+            .u16(0)
+            .u16(0)
+            .u32(0)
+            .u32(0)
+            .u32(0)
+            .file_id(fid)
+            .u8(0);
+        let resp = self.request(req)?;
+        let data = resp.buffer(resp.body_u16(2)? as usize, resp.body_u32(4)? as usize)?;
+        Ok(data.to_vec())
+    }
+
+    /// 设置文件的信息
+    pub fn set_info(&self, fid: &FileId, class: u8, data: &[u8]) -> Result<(), SystemError> {
+        let mut req = Smb2Request::new(SMB2_SET_INFO, 33);
+        req.u8(SMB2_0_INFO_FILE)
+            .u8(class)
+            .u32(data.len() as u32)
+            .u16((SMB2_HEADER_SIZE + 32) as u16)
+            .u16(0)
+            .u32(0)
+            .file_id(fid)
+            .bytes(data);
+        self.request(req).map(|_| ())
+    }
+
+    /// 服务器名和共享名
+    pub fn unc(&self) -> &str {
+        &self.unc
+    }
+}
+
+impl Drop for SmbConn {
+    fn drop(&mut self) {
+        if !self.broken.load(Ordering::Acquire) {
+            self.shutdown();
+        }
+    }
+}
+
+/// 解析`//server/share`形式的挂载源
+///
+/// ## 返回值
+/// `(server, share)`，共享名之后的子路径不支持
+pub fn parse_unc(source: &str) -> Result<(String, String), SystemError> {
+    let rest = source
+        .strip_prefix("//")
+        .or_else(|| source.strip_prefix("\\\\"))
+        .ok_or(SystemError::EINVAL)?;
+    let mut parts = rest.split(['/', '\\']).filter(|s| !s.is_empty());
+    let server = parts.next().ok_or(SystemError::EINVAL)?;
+    let share = parts.next().ok_or(SystemError::EINVAL)?;
+    if parts.next().is_some() {
+        log::warn!("smb: mounting a subdirectory of a share is not supported");
+        return Err(SystemError::EINVAL);
+    }
+    Ok((server.to_string(), share.to_string()))
+}
@@ -0,0 +1,301 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::net::Ipv4Addr;
+
+use linkme::distributed_slice;
+use smoltcp::wire::IpAddress;
+use system_error::SystemError;
+
+use crate::{
+    filesystem::vfs::{
+        mount_options::{MountOptionKind, MountOptionSpec, MountOptions},
+        FileSystem, FileSystemMakerData, FsInfo, IndexNode, Magic, MountableFileSystem, SuperBlock,
+        FSMAKER,
+    },
+    libs::mutex::Mutex,
+    net::socket::endpoint::IpEndpoint,
+    register_mountable_fs,
+};
+
+use super::{
+    conn::{parse_unc, SmbConn},
+    inode::SmbNode,
+    ntlm::NtlmCredentials,
+    proto::{
+        get_u32, get_u64, FileAttrs, FILE_DIRECTORY_FILE, FILE_FS_FULL_SIZE_INFORMATION, FILE_OPEN,
+        FILE_READ_ATTRIBUTES, SMB2_0_INFO_FILESYSTEM,
+    },
+};
+
+const SMB_PORT: u16 = 445;
+/// 与Linux cifs的默认值相同
+const DEFAULT_FILE_MODE: u32 = 0o755;
+const DEFAULT_DIR_MODE: u32 = 0o755;
+/// NTFS的时间精度为100纳秒
+const SMB_TIME_GRANULARITY: u32 = 100;
+
+const SMB_MOUNT_OPTIONS: &[MountOptionSpec] = &[
+    MountOptionSpec::new("server", MountOptionKind::Str),
+    MountOptionSpec::new("port", MountOptionKind::U32),
+    MountOptionSpec::new("user", MountOptionKind::Str),
+    MountOptionSpec::new("username", MountOptionKind::Str),
+    MountOptionSpec::new("pass", MountOptionKind::Str),
+    MountOptionSpec::new("password", MountOptionKind::Str),
+    MountOptionSpec::new("domain", MountOptionKind::Str),
+    MountOptionSpec::new("uid", MountOptionKind::U32),
+    MountOptionSpec::new("gid", MountOptionKind::U32),
+    MountOptionSpec::new("file_mode", MountOptionKind::Mode),
+    MountOptionSpec::new("dir_mode", MountOptionKind::Mode),
+];
+
+/// 服务器不保存Unix的属主和权限，所有文件都使用挂载时指定的值
+#[derive(Debug, Clone, Copy)]
+pub struct SmbFileOwner {
+    pub uid: usize,
+    pub gid: usize,
+    pub file_mode: u32,
+    pub dir_mode: u32,
+}
+
+pub struct SmbMountData {
+    endpoint: IpEndpoint,
+    server: String,
+    share: String,
+    creds: NtlmCredentials,
+    owner: SmbFileOwner,
+}
+
+impl FileSystemMakerData for SmbMountData {
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct SmbFS {
+    conn: Arc<SmbConn>,
+    root: Arc<SmbNode>,
+    /// 已经打开的inode，以路径为键，保证同一个文件只有一个inode
+    nodes: Mutex<BTreeMap<String, Weak<SmbNode>>>,
+    owner: SmbFileOwner,
+    self_ref: Weak<SmbFS>,
+}
+
+impl SmbFS {
+    pub fn conn(&self) -> &Arc<SmbConn> {
+        &self.conn
+    }
+
+    pub fn owner(&self) -> &SmbFileOwner {
+        &self.owner
+    }
+
+    pub fn root_node(&self) -> Arc<SmbNode> {
+        self.root.clone()
+    }
+
+    /// # 获取路径对应的inode
+    ///
+    /// ## 参数
+    /// - `attrs`: 刚从服务器得到的属性，用于更新缓存
+    pub fn get_or_create_node(&self, path: &str, attrs: Option<FileAttrs>) -> Arc<SmbNode> {
+        if path.is_empty() {
+            if let Some(attrs) = attrs {
+                self.root.update_attrs(attrs);
+            }
+            return self.root.clone();
+        }
+
+        let mut nodes = self.nodes.lock();
+        if let Some(node) = nodes.get(path).and_then(Weak::upgrade) {
+            if let Some(attrs) = attrs {
+                node.update_attrs(attrs);
+            }
+            return node;
+        }
+        // 顺便清理已经释放的inode
+        nodes.retain(|_, node| node.strong_count() > 0);
+        let node = SmbNode::new(self.self_ref.clone(), path.to_string(), attrs);
+        nodes.insert(path.to_string(), Arc::downgrade(&node));
+        node
+    }
+
+    /// 路径对应的、仍在使用的inode
+    pub fn cached_node(&self, path: &str) -> Option<Arc<SmbNode>> {
+        if path.is_empty() {
+            return Some(self.root.clone());
+        }
+        self.nodes.lock().get(path).and_then(Weak::upgrade)
+    }
+
+    /// 路径上的文件已经被删除
+    pub fn remove_node(&self, path: &str) {
+        self.nodes.lock().remove(path);
+    }
+
+    /// 重命名之后更新路径，包括目录下的所有inode
+    pub fn rename_nodes(&self, old: &str, new: &str) {
+        let mut nodes = self.nodes.lock();
+        // 目标原来的inode已经被覆盖
+        nodes.remove(new);
+        let old_prefix = format!("{}\\", old);
+        let moved: Vec<String> = nodes
+            .keys()
+            .filter(|path| *path == old || path.starts_with(&old_prefix))
+            .cloned()
+            .collect();
+        for path in moved {
+            let Some(node) = nodes.remove(&path) else {
+                continue;
+            };
+            let new_path = format!("{}{}", new, &path[old.len()..]);
+            if let Some(n) = node.upgrade() {
+                n.set_path(new_path.clone());
+            }
+            nodes.insert(new_path, node);
+        }
+    }
+
+    fn parse_endpoint(server: &str, port: u16) -> Result<IpEndpoint, SystemError> {
+        let addr: Ipv4Addr = server.parse().map_err(|_| {
+            // 内核中没有DNS解析
+            log::warn!(
+                "cifs: '{}' is not an IPv4 address, pass the address with server=",
+                server
+            );
+            SystemError::EINVAL
+        })?;
+        Ok(IpEndpoint::new(IpAddress::Ipv4(addr), port))
+    }
+}
+
+impl MountableFileSystem for SmbFS {
+    fn make_mount_data(
+        raw_data: Option<&str>,
+        source: &str,
+    ) -> Result<Option<Arc<dyn FileSystemMakerData + 'static>>, SystemError> {
+        let opts = MountOptions::parse("cifs", raw_data, SMB_MOUNT_OPTIONS)?;
+        opts.warn_unknown("cifs");
+
+        let (server, share) = parse_unc(source).inspect_err(|_| {
+            log::warn!("cifs: bad source '{}', expected //server/share", source);
+        })?;
+        let port = match opts.u32("port") {
+            Some(port) => u16::try_from(port).map_err(|_| SystemError::EINVAL)?,
+            None => SMB_PORT,
+        };
+        let endpoint = Self::parse_endpoint(opts.str("server").unwrap_or(&server), port)?;
+
+        let creds = NtlmCredentials {
+            user: opts
+                .str("user")
+                .or(opts.str("username"))
+                .unwrap_or("")
+                .to_string(),
+            password: opts
+                .str("pass")
+                .or(opts.str("password"))
+                .unwrap_or("")
+                .to_string(),
+            domain: opts.str("domain").unwrap_or("").to_string(),
+        };
+        let owner = SmbFileOwner {
+            uid: opts.u32("uid").unwrap_or(0) as usize,
+            gid: opts.u32("gid").unwrap_or(0) as usize,
+            file_mode: opts.mode("file_mode").unwrap_or(DEFAULT_FILE_MODE) & 0o7777,
+            dir_mode: opts.mode("dir_mode").unwrap_or(DEFAULT_DIR_MODE) & 0o7777,
+        };
+
+        Ok(Some(Arc::new(SmbMountData {
+            endpoint,
+            server,
+            share,
+            creds,
+            owner,
+        })))
+    }
+
+    fn make_fs(
+        data: Option<&dyn FileSystemMakerData>,
+    ) -> Result<Arc<dyn FileSystem + 'static>, SystemError> {
+        let data = data
+            .and_then(|d| d.as_any().downcast_ref::<SmbMountData>())
+            .ok_or(SystemError::EINVAL)?;
+        let conn = SmbConn::connect(data.endpoint, &data.server, &data.share, &data.creds)?;
+
+        let fs = Arc::new_cyclic(|weak_fs| SmbFS {
+            conn,
+            root: SmbNode::new(weak_fs.clone(), String::new(), None),
+            nodes: Mutex::new(BTreeMap::new()),
+            owner: data.owner,
+            self_ref: weak_fs.clone(),
+        });
+        // 确认共享的根目录可以访问
+        if let Err(e) = fs.root.metadata() {
+            fs.conn.disconnect();
+            return Err(e);
+        }
+        Ok(fs)
+    }
+}
+
+register_mountable_fs!(SmbFS, SMBFSMAKER, "cifs");
+
+impl FileSystem for SmbFS {
+    fn root_inode(&self) -> Arc<dyn IndexNode> {
+        self.root.clone()
+    }
+
+    fn info(&self) -> FsInfo {
+        FsInfo {
+            blk_dev_id: 0,
+            max_name_len: 255,
+        }
+    }
+
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "cifs"
+    }
+
+    fn super_block(&self) -> SuperBlock {
+        SuperBlock::new(Magic::SMB2_MAGIC, 4096, 255)
+    }
+
+    fn statfs(&self, _inode: &Arc<dyn IndexNode>) -> Result<SuperBlock, SystemError> {
+        let (fid, _) =
+            self.conn
+                .create("", FILE_READ_ATTRIBUTES, FILE_OPEN, FILE_DIRECTORY_FILE, 0)?;
+        let info =
+            self.conn
+                .query_info(&fid, SMB2_0_INFO_FILESYSTEM, FILE_FS_FULL_SIZE_INFORMATION);
+        let _ = self.conn.close(&fid);
+        let info = info?;
+
+        // FileFsFullSizeInformation: 总分配单元数、调用者可用的单元数、实际可用的单元数、
+        // 每个单元的扇区数、每个扇区的字节数
+        let unit = get_u32(&info, 24)? as u64 * get_u32(&info, 28)? as u64;
+        let mut sb = self.super_block();
+        sb.bsize = unit;
+        sb.frsize = unit;
+        sb.blocks = get_u64(&info, 0)?;
+        sb.bavail = get_u64(&info, 8)?;
+        sb.bfree = get_u64(&info, 16)?;
+        Ok(sb)
+    }
+
+    fn time_granularity(&self) -> u32 {
+        SMB_TIME_GRANULARITY
+    }
+
+    fn on_umount(&self) {
+        self.conn.disconnect();
+    }
+}
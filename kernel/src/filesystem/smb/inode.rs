@@ -0,0 +1,546 @@
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use system_error::SystemError;
+
+use crate::{
+    filesystem::vfs::{
+        file::FileFlags, syscall::RenameFlags, vcore::generate_inode_id, FilePrivateData,
+        FileSystem, FileType, IndexNode, InodeFlags, InodeId, InodeMode, Metadata,
+    },
+    libs::mutex::{Mutex, MutexGuard},
+    time::timekeep::ktime_get_real_ns,
+};
+
+use super::{
+    conn::SmbConn,
+    fs::SmbFS,
+    proto::{
+        filetime_to_timespec, timespec_to_filetime, utf16le, FileAttrs, FileId, DELETE,
+        FILE_ATTRIBUTE_NORMAL, FILE_ATTRIBUTE_READONLY, FILE_BASIC_INFORMATION, FILE_CREATE,
+        FILE_DIRECTORY_FILE, FILE_DISPOSITION_INFORMATION, FILE_END_OF_FILE_INFORMATION,
+        FILE_NON_DIRECTORY_FILE, FILE_OPEN, FILE_READ_ATTRIBUTES, FILE_READ_DATA,
+        FILE_RENAME_INFORMATION, FILE_WRITE_ATTRIBUTES, GENERIC_READ, GENERIC_WRITE,
+    },
+};
+
+/// 属性缓存的有效时间
+const ATTR_TIMEOUT_NS: i64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy)]
+struct CachedAttrs {
+    attrs: FileAttrs,
+    /// 从服务器得到属性的时间
+    fetched_ns: i64,
+}
+
+/// 打开文件时在服务器上打开的句柄，同一个inode的所有打开共享
+#[derive(Debug, Default)]
+struct OpenHandle {
+    fid: Option<FileId>,
+    /// 打开的次数，降为0时关闭句柄
+    opens: usize,
+}
+
+#[derive(Debug)]
+pub struct SmbNode {
+    fs: Weak<SmbFS>,
+    inode_id: InodeId,
+    /// 相对于共享根目录、以`\`分隔的路径，根目录为空字符串
+    path: Mutex<String>,
+    attrs: Mutex<Option<CachedAttrs>>,
+    handle: Mutex<OpenHandle>,
+}
+
+impl SmbNode {
+    pub fn new(fs: Weak<SmbFS>, path: String, attrs: Option<FileAttrs>) -> Arc<Self> {
+        Arc::new(Self {
+            fs,
+            inode_id: generate_inode_id(),
+            path: Mutex::new(path),
+            attrs: Mutex::new(attrs.map(|attrs| CachedAttrs {
+                attrs,
+                fetched_ns: ktime_get_real_ns(),
+            })),
+            handle: Mutex::new(OpenHandle::default()),
+        })
+    }
+
+    fn smb_fs(&self) -> Result<Arc<SmbFS>, SystemError> {
+        self.fs.upgrade().ok_or(SystemError::ENOENT)
+    }
+
+    fn path(&self) -> String {
+        self.path.lock().clone()
+    }
+
+    pub fn set_path(&self, path: String) {
+        *self.path.lock() = path;
+    }
+
+    fn child_path(&self, name: &str) -> Result<String, SystemError> {
+        if name.is_empty() || name.contains('\\') {
+            return Err(SystemError::EINVAL);
+        }
+        let path = self.path.lock();
+        if path.is_empty() {
+            Ok(name.to_string())
+        } else {
+            Ok(format!("{}\\{}", path, name))
+        }
+    }
+
+    pub fn update_attrs(&self, attrs: FileAttrs) {
+        *self.attrs.lock() = Some(CachedAttrs {
+            attrs,
+            fetched_ns: ktime_get_real_ns(),
+        });
+    }
+
+    /// 文件被修改之后，下次获取属性时重新向服务器查询
+    fn invalidate_attrs(&self) {
+        if let Some(cached) = self.attrs.lock().as_mut() {
+            cached.fetched_ns = 0;
+        }
+    }
+
+    fn attrs(&self) -> Result<FileAttrs, SystemError> {
+        if let Some(cached) = *self.attrs.lock() {
+            if ktime_get_real_ns() - cached.fetched_ns < ATTR_TIMEOUT_NS {
+                return Ok(cached.attrs);
+            }
+        }
+        let fs = self.smb_fs()?;
+        let (fid, attrs) = fs
+            .conn()
+            .create(&self.path(), FILE_READ_ATTRIBUTES, FILE_OPEN, 0, 0)?;
+        let _ = fs.conn().close(&fid);
+        self.update_attrs(attrs);
+        Ok(attrs)
+    }
+
+    fn ensure_dir(&self) -> Result<(), SystemError> {
+        if self.attrs()?.is_dir() {
+            Ok(())
+        } else {
+            Err(SystemError::ENOTDIR)
+        }
+    }
+
+    /// 以读写方式打开文件，没有写权限时以只读方式打开
+    fn open_file(&self, conn: &SmbConn) -> Result<FileId, SystemError> {
+        let path = self.path();
+        match conn.create(
+            &path,
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_OPEN,
+            FILE_NON_DIRECTORY_FILE,
+            0,
+        ) {
+            Err(SystemError::EACCES) => conn
+                .create(&path, GENERIC_READ, FILE_OPEN, FILE_NON_DIRECTORY_FILE, 0)
+                .map(|(fid, _)| fid),
+            result => result.map(|(fid, _)| fid),
+        }
+    }
+
+    /// 使用已经打开的句柄执行`f`，没有打开时临时打开一个句柄
+    fn with_handle<R>(
+        &self,
+        f: impl FnOnce(&SmbConn, &FileId) -> Result<R, SystemError>,
+    ) -> Result<R, SystemError> {
+        let fs = self.smb_fs()?;
+        let conn = fs.conn();
+        let handle = self.handle.lock();
+        if let Some(fid) = &handle.fid {
+            return f(conn, fid);
+        }
+        let fid = self.open_file(conn)?;
+        let result = f(conn, &fid);
+        let _ = conn.close(&fid);
+        result
+    }
+
+    /// 以`access`权限临时打开路径并执行`f`
+    fn with_path<R>(
+        conn: &SmbConn,
+        path: &str,
+        access: u32,
+        options: u32,
+        f: impl FnOnce(&FileId) -> Result<R, SystemError>,
+    ) -> Result<R, SystemError> {
+        let (fid, _) = conn.create(path, access, FILE_OPEN, options, 0)?;
+        let result = f(&fid);
+        let _ = conn.close(&fid);
+        result
+    }
+
+    fn delete(&self, name: &str, options: u32) -> Result<(), SystemError> {
+        self.ensure_dir()?;
+        let fs = self.smb_fs()?;
+        let path = self.child_path(name)?;
+        Self::with_path(fs.conn(), &path, DELETE, options, |fid| {
+            fs.conn().set_info(fid, FILE_DISPOSITION_INFORMATION, &[1])
+        })?;
+        fs.remove_node(&path);
+        self.invalidate_attrs();
+        Ok(())
+    }
+
+    fn attrs_to_metadata(&self, attrs: &FileAttrs) -> Result<Metadata, SystemError> {
+        let fs = self.smb_fs()?;
+        let owner = fs.owner();
+        let (file_type, mut mode, nlinks) = if attrs.is_dir() {
+            (FileType::Dir, InodeMode::S_IFDIR.bits() | owner.dir_mode, 2)
+        } else {
+            (
+                FileType::File,
+                InodeMode::S_IFREG.bits() | owner.file_mode,
+                1,
+            )
+        };
+        if attrs.attributes & FILE_ATTRIBUTE_READONLY != 0 {
+            mode &= !InodeMode::S_IWUGO.bits();
+        }
+        Ok(Metadata {
+            dev_id: 0,
+            inode_id: self.inode_id,
+            size: attrs.end_of_file as i64,
+            blk_size: 4096,
+            blocks: attrs.allocation_size.div_ceil(512) as usize,
+            atime: filetime_to_timespec(attrs.last_access_time),
+            mtime: filetime_to_timespec(attrs.last_write_time),
+            ctime: filetime_to_timespec(attrs.change_time),
+            btime: filetime_to_timespec(attrs.creation_time),
+            file_type,
+            mode: InodeMode::from_bits_truncate(mode),
+            flags: InodeFlags::empty(),
+            nlinks,
+            uid: owner.uid,
+            gid: owner.gid,
+            raw_dev: Default::default(),
+        })
+    }
+}
+
+impl IndexNode for SmbNode {
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn open(
+        &self,
+        _data: MutexGuard<FilePrivateData>,
+        _flags: &FileFlags,
+    ) -> Result<(), SystemError> {
+        if self.attrs()?.is_dir() {
+            return Ok(());
+        }
+        let fs = self.smb_fs()?;
+        let mut handle = self.handle.lock();
+        if handle.fid.is_none() {
+            handle.fid = Some(self.open_file(fs.conn())?);
+        }
+        handle.opens += 1;
+        Ok(())
+    }
+
+    fn close(&self, _data: MutexGuard<FilePrivateData>) -> Result<(), SystemError> {
+        let mut handle = self.handle.lock();
+        if handle.opens == 0 {
+            return Ok(());
+        }
+        handle.opens -= 1;
+        if handle.opens == 0 {
+            if let Some(fid) = handle.fid.take() {
+                self.smb_fs()?.conn().close(&fid)?;
+            }
+            self.invalidate_attrs();
+        }
+        Ok(())
+    }
+
+    fn read_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        if self.attrs()?.is_dir() {
+            return Err(SystemError::EISDIR);
+        }
+        self.with_handle(|conn, fid| conn.read(fid, offset as u64, &mut buf[..len]))
+    }
+
+    fn write_at(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: &[u8],
+        _data: MutexGuard<FilePrivateData>,
+    ) -> Result<usize, SystemError> {
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        if self.attrs()?.is_dir() {
+            return Err(SystemError::EISDIR);
+        }
+        let written = self.with_handle(|conn, fid| conn.write(fid, offset as u64, &buf[..len]))?;
+        self.invalidate_attrs();
+        Ok(written)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SystemError> {
+        let attrs = self.attrs()?;
+        self.attrs_to_metadata(&attrs)
+    }
+
+    /// 修改时间和只读属性，服务器不保存Unix的属主和权限，其余的修改被忽略
+    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
+        let attrs = self.attrs()?;
+        let old = self.attrs_to_metadata(&attrs)?;
+        if metadata.size != old.size {
+            self.resize(metadata.size as usize)?;
+        }
+
+        let mut basic = FileBasicInfo::default();
+        if metadata.atime != old.atime {
+            basic.last_access_time = timespec_to_filetime(&metadata.atime);
+        }
+        if metadata.mtime != old.mtime {
+            basic.last_write_time = timespec_to_filetime(&metadata.mtime);
+        }
+        let readonly = !metadata.mode.intersects(InodeMode::S_IWUGO);
+        if readonly != (attrs.attributes & FILE_ATTRIBUTE_READONLY != 0) {
+            let mut new_attrs = attrs.attributes & !FILE_ATTRIBUTE_READONLY;
+            if readonly {
+                new_attrs |= FILE_ATTRIBUTE_READONLY;
+            }
+            basic.attributes = if new_attrs == 0 {
+                FILE_ATTRIBUTE_NORMAL
+            } else {
+                new_attrs
+            };
+        }
+        if basic.is_empty() {
+            return Ok(());
+        }
+
+        let fs = self.smb_fs()?;
+        Self::with_path(fs.conn(), &self.path(), FILE_WRITE_ATTRIBUTES, 0, |fid| {
+            fs.conn()
+                .set_info(fid, FILE_BASIC_INFORMATION, &basic.to_bytes())
+        })?;
+        self.invalidate_attrs();
+        Ok(())
+    }
+
+    fn resize(&self, len: usize) -> Result<(), SystemError> {
+        if self.attrs()?.is_dir() {
+            return Err(SystemError::EISDIR);
+        }
+        self.with_handle(|conn, fid| {
+            conn.set_info(
+                fid,
+                FILE_END_OF_FILE_INFORMATION,
+                &(len as u64).to_le_bytes(),
+            )
+        })?;
+        self.invalidate_attrs();
+        Ok(())
+    }
+
+    fn truncate(&self, len: usize) -> Result<(), SystemError> {
+        self.resize(len)
+    }
+
+    fn sync(&self) -> Result<(), SystemError> {
+        let handle = self.handle.lock();
+        match &handle.fid {
+            Some(fid) => self.smb_fs()?.conn().flush(fid),
+            None => Ok(()),
+        }
+    }
+
+    fn datasync(&self) -> Result<(), SystemError> {
+        self.sync()
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn list(&self) -> Result<Vec<String>, SystemError> {
+        self.ensure_dir()?;
+        let fs = self.smb_fs()?;
+        let conn = fs.conn();
+        let path = self.path();
+
+        let mut names = alloc::vec![".".to_string(), "..".to_string()];
+        Self::with_path(
+            conn,
+            &path,
+            FILE_READ_DATA | FILE_READ_ATTRIBUTES,
+            FILE_DIRECTORY_FILE,
+            |fid| {
+                let mut restart = true;
+                while let Some(entries) = conn.query_directory(fid, restart)? {
+                    restart = false;
+                    for entry in entries {
+                        if entry.name == "." || entry.name == ".." {
+                            continue;
+                        }
+                        // 顺便更新已经打开的inode的属性
+                        if let Ok(child) = self.child_path(&entry.name) {
+                            if let Some(node) = fs.cached_node(&child) {
+                                node.update_attrs(entry.attrs);
+                            }
+                        }
+                        names.push(entry.name);
+                    }
+                }
+                Ok(())
+            },
+        )?;
+        Ok(names)
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
+        self.ensure_dir()?;
+        let fs = self.smb_fs()?;
+        match name {
+            "." => return Ok(fs.get_or_create_node(&self.path(), None)),
+            ".." => return self.parent(),
+            _ => {}
+        }
+        let path = self.child_path(name)?;
+        let (fid, attrs) = fs
+            .conn()
+            .create(&path, FILE_READ_ATTRIBUTES, FILE_OPEN, 0, 0)?;
+        let _ = fs.conn().close(&fid);
+        Ok(fs.get_or_create_node(&path, Some(attrs)))
+    }
+
+    fn parent(&self) -> Result<Arc<dyn IndexNode>, SystemError> {
+        let fs = self.smb_fs()?;
+        let path = self.path();
+        let parent = path.rsplit_once('\\').map(|(p, _)| p).unwrap_or("");
+        Ok(fs.get_or_create_node(parent, None))
+    }
+
+    fn create_with_data(
+        &self,
+        name: &str,
+        file_type: FileType,
+        _mode: InodeMode,
+        _data: usize,
+    ) -> Result<Arc<dyn IndexNode>, SystemError> {
+        self.ensure_dir()?;
+        let fs = self.smb_fs()?;
+        let path = self.child_path(name)?;
+        let (access, options, attributes) = match file_type {
+            FileType::File => (
+                GENERIC_READ | GENERIC_WRITE,
+                FILE_NON_DIRECTORY_FILE,
+                FILE_ATTRIBUTE_NORMAL,
+            ),
+            FileType::Dir => (FILE_READ_ATTRIBUTES, FILE_DIRECTORY_FILE, 0),
+            // 没有Unix扩展时无法保存符号链接和设备文件
+            _ => return Err(SystemError::EPERM),
+        };
+        let (fid, attrs) = fs
+            .conn()
+            .create(&path, access, FILE_CREATE, options, attributes)?;
+        let _ = fs.conn().close(&fid);
+        self.invalidate_attrs();
+        Ok(fs.get_or_create_node(&path, Some(attrs)))
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), SystemError> {
+        self.delete(name, FILE_NON_DIRECTORY_FILE)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<(), SystemError> {
+        self.delete(name, FILE_DIRECTORY_FILE)
+    }
+
+    fn move_to(
+        &self,
+        old_name: &str,
+        target: &Arc<dyn IndexNode>,
+        new_name: &str,
+        flag: RenameFlags,
+    ) -> Result<(), SystemError> {
+        if flag.intersects(RenameFlags::EXCHANGE | RenameFlags::WHITEOUT) {
+            return Err(SystemError::EINVAL);
+        }
+        let target = target
+            .as_any_ref()
+            .downcast_ref::<SmbNode>()
+            .ok_or(SystemError::EXDEV)?;
+        let fs = self.smb_fs()?;
+        if !Weak::ptr_eq(&self.fs, &target.fs) {
+            return Err(SystemError::EXDEV);
+        }
+        self.ensure_dir()?;
+        target.ensure_dir()?;
+
+        let old_path = self.child_path(old_name)?;
+        let new_path = target.child_path(new_name)?;
+        let new_name16 = utf16le(&new_path);
+        // FileRenameInformation: ReplaceIfExists、7字节保留、RootDirectory、名字的长度和名字
+        let mut info = Vec::with_capacity(20 + new_name16.len());
+        info.push(!flag.contains(RenameFlags::NOREPLACE) as u8);
+        info.extend_from_slice(&[0; 7]);
+        info.extend_from_slice(&0u64.to_le_bytes());
+        info.extend_from_slice(&(new_name16.len() as u32).to_le_bytes());
+        info.extend_from_slice(&new_name16);
+
+        Self::with_path(fs.conn(), &old_path, DELETE, 0, |fid| {
+            fs.conn().set_info(fid, FILE_RENAME_INFORMATION, &info)
+        })?;
+        fs.rename_nodes(&old_path, &new_path);
+        self.invalidate_attrs();
+        target.invalidate_attrs();
+        Ok(())
+    }
+}
+
+impl Drop for SmbNode {
+    fn drop(&mut self) {
+        let fid = self.handle.lock().fid.take();
+        if let (Some(fid), Some(fs)) = (fid, self.fs.upgrade()) {
+            let _ = fs.conn().close(&fid);
+        }
+    }
+}
+
+/// FileBasicInformation，值为0的字段表示不修改
+#[derive(Debug, Default)]
+struct FileBasicInfo {
+    last_access_time: u64,
+    last_write_time: u64,
+    attributes: u32,
+}
+
+impl FileBasicInfo {
+    fn is_empty(&self) -> bool {
+        self.last_access_time == 0 && self.last_write_time == 0 && self.attributes == 0
+    }
+
+    /// CreationTime、LastAccessTime、LastWriteTime、ChangeTime、FileAttributes和4字节保留
+    fn to_bytes(&self) -> [u8; 40] {
+        let mut buf = [0u8; 40];
+        buf[8..16].copy_from_slice(&self.last_access_time.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.last_write_time.to_le_bytes());
+        buf[32..36].copy_from_slice(&self.attributes.to_le_bytes());
+        buf
+    }
+}
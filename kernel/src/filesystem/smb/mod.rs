@@ -0,0 +1,23 @@
+//! SMB2客户端文件系统（cifs）
+//!
+//! 把Windows或者Samba服务器上的共享挂载到本地：
+//!
+//! ```text
+//! mount -t cifs //192.168.1.10/share /mnt -o user=alice,pass=secret,domain=WORKGROUP
+//! ```
+//!
+//! - 协商SMB 2.0.2或2.1，通过NTLMv2认证（[`ntlm`]），不提供用户名时匿名登录；
+//! - 服务器要求签名时对消息签名，不支持SMB3的加密，也不支持Kerberos；
+//! - 内核中没有DNS，服务器名不是IPv4地址时需要用`server=`指定地址；
+//! - 服务器不保存Unix的属主和权限，文件的uid/gid/权限来自挂载选项
+//!   `uid=`、`gid=`、`file_mode=`、`dir_mode=`，只读属性会去掉写权限；
+//! - 不缓存文件数据，属性缓存1秒（与Linux cifs的默认`actimeo=1`相同）；
+//! - 不支持符号链接、硬链接、设备文件和扩展属性。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/fs/smb/client/
+
+mod conn;
+pub mod fs;
+mod inode;
+mod ntlm;
+mod proto;
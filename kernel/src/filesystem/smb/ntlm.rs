@@ -0,0 +1,247 @@
+//! NTLMv2认证（NTLMSSP）
+//!
+//! SESSION_SETUP的安全缓冲区中直接携带NTLMSSP消息（与Linux cifs的`sec=ntlmssp`相同，
+//! 不使用SPNEGO封装），共三条消息：
+//! 1. 客户端发送NEGOTIATE_MESSAGE；
+//! 2. 服务器回复CHALLENGE_MESSAGE，其中包含8字节的挑战和服务器信息（TargetInfo）；
+//! 3. 客户端用由口令得到的密钥对挑战计算NTLMv2响应，放在AUTHENTICATE_MESSAGE中发送。
+//!
+//! 不协商密钥交换（KEY_EXCH），会话密钥就是NTLMv2的SessionBaseKey，用于SMB2签名。
+//! 用户名为空时进行匿名认证。
+//!
+//! 参考: [MS-NLMP] https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-nlmp
+
+use alloc::{string::String, vec::Vec};
+use system_error::SystemError;
+
+use crate::libs::crypto::{aes::zeroize, digest::Digest, hmac::Hmac, md4::Md4, md5::Md5};
+
+use super::proto::{get_u16, get_u32, get_u64, utf16le};
+
+const NTLMSSP_SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+const NTLMSSP_NEGOTIATE: u32 = 1;
+const NTLMSSP_CHALLENGE: u32 = 2;
+const NTLMSSP_AUTH: u32 = 3;
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_SIGN: u32 = 0x0000_0010;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ANONYMOUS: u32 = 0x0000_0800;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+
+const CLIENT_FLAGS: u32 = NEGOTIATE_UNICODE
+    | REQUEST_TARGET
+    | NEGOTIATE_SIGN
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_TARGET_INFO
+    | NEGOTIATE_128;
+
+/// TargetInfo中的AV_PAIR类型
+const MSV_AV_EOL: u16 = 0;
+const MSV_AV_TIMESTAMP: u16 = 7;
+
+/// 认证使用的身份
+#[derive(Debug, Clone, Default)]
+pub struct NtlmCredentials {
+    pub user: String,
+    pub password: String,
+    pub domain: String,
+}
+
+impl NtlmCredentials {
+    pub fn is_anonymous(&self) -> bool {
+        self.user.is_empty()
+    }
+}
+
+impl Drop for NtlmCredentials {
+    fn drop(&mut self) {
+        // 不让口令留在释放的内存中
+        zeroize(unsafe { self.password.as_bytes_mut() });
+    }
+}
+
+/// 服务器的CHALLENGE_MESSAGE
+#[derive(Debug)]
+pub struct NtlmChallenge {
+    server_challenge: [u8; 8],
+    flags: u32,
+    target_info: Vec<u8>,
+    /// TargetInfo中的MsvAvTimestamp，存在时NTLMv2响应必须使用这个时间
+    timestamp: Option<u64>,
+}
+
+/// NTLMSSP消息中的(长度, 最大长度, 偏移)字段
+fn security_buffer(msg: &[u8], off: usize) -> Result<&[u8], SystemError> {
+    let len = get_u16(msg, off)? as usize;
+    let offset = get_u32(msg, off + 4)? as usize;
+    msg.get(offset..offset + len).ok_or(SystemError::EBADMSG)
+}
+
+fn put_security_buffer(msg: &mut Vec<u8>, field: usize, payload: &[u8]) {
+    let offset = msg.len() as u32;
+    let len = payload.len() as u16;
+    msg[field..field + 2].copy_from_slice(&len.to_le_bytes());
+    msg[field + 2..field + 4].copy_from_slice(&len.to_le_bytes());
+    msg[field + 4..field + 8].copy_from_slice(&offset.to_le_bytes());
+    msg.extend_from_slice(payload);
+}
+
+/// 构造NEGOTIATE_MESSAGE
+pub fn negotiate_message(creds: &NtlmCredentials) -> Vec<u8> {
+    let mut flags = CLIENT_FLAGS;
+    if creds.is_anonymous() {
+        flags |= NEGOTIATE_ANONYMOUS;
+    }
+    let mut msg = Vec::with_capacity(32);
+    msg.extend_from_slice(NTLMSSP_SIGNATURE);
+    msg.extend_from_slice(&NTLMSSP_NEGOTIATE.to_le_bytes());
+    msg.extend_from_slice(&flags.to_le_bytes());
+    // DomainNameFields和WorkstationFields都为空
+    msg.resize(32, 0);
+    msg
+}
+
+/// 解析CHALLENGE_MESSAGE
+pub fn parse_challenge(msg: &[u8]) -> Result<NtlmChallenge, SystemError> {
+    if msg.len() < 48 || &msg[..8] != NTLMSSP_SIGNATURE || get_u32(msg, 8)? != NTLMSSP_CHALLENGE {
+        return Err(SystemError::EBADMSG);
+    }
+    let flags = get_u32(msg, 20)?;
+    let mut server_challenge = [0u8; 8];
+    server_challenge.copy_from_slice(&msg[24..32]);
+    let target_info = if flags & NEGOTIATE_TARGET_INFO != 0 {
+        security_buffer(msg, 40)?.to_vec()
+    } else {
+        Vec::new()
+    };
+
+    let mut timestamp = None;
+    let mut pos = 0;
+    while pos + 4 <= target_info.len() {
+        let id = get_u16(&target_info, pos)?;
+        let len = get_u16(&target_info, pos + 2)? as usize;
+        if id == MSV_AV_EOL {
+            break;
+        }
+        if id == MSV_AV_TIMESTAMP && len == 8 {
+            timestamp = Some(get_u64(&target_info, pos + 4)?);
+        }
+        pos += 4 + len;
+    }
+
+    Ok(NtlmChallenge {
+        server_challenge,
+        flags,
+        target_info,
+        timestamp,
+    })
+}
+
+type HmacMd5 = Hmac<Md5>;
+
+/// NTOWFv2：由口令、用户名和域名得到NTLMv2的密钥
+fn ntowf_v2(creds: &NtlmCredentials) -> [u8; 16] {
+    let mut password = utf16le(&creds.password);
+    let mut nt_hash = Md4::digest(&password);
+    zeroize(&mut password);
+
+    let mut identity = utf16le(&creds.user.to_uppercase());
+    identity.extend_from_slice(&utf16le(&creds.domain));
+    let key = HmacMd5::mac(&nt_hash, &identity);
+    zeroize(&mut nt_hash);
+    key
+}
+
+/// 计算NTLMv2响应
+///
+/// ## 参数
+/// - `client_challenge`: 客户端生成的8字节随机数
+/// - `now`: 当前时间（FILETIME），TargetInfo中没有时间戳时使用
+///
+/// ## 返回值
+/// `(LmChallengeResponse, NtChallengeResponse, SessionBaseKey)`
+fn ntlmv2_response(
+    creds: &NtlmCredentials,
+    challenge: &NtlmChallenge,
+    client_challenge: &[u8; 8],
+    now: u64,
+) -> (Vec<u8>, Vec<u8>, [u8; 16]) {
+    let mut key = ntowf_v2(creds);
+
+    // NTLMv2_CLIENT_CHALLENGE
+    let mut temp = Vec::with_capacity(32 + challenge.target_info.len());
+    temp.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0]);
+    temp.extend_from_slice(&challenge.timestamp.unwrap_or(now).to_le_bytes());
+    temp.extend_from_slice(client_challenge);
+    temp.extend_from_slice(&[0; 4]);
+    temp.extend_from_slice(&challenge.target_info);
+    temp.extend_from_slice(&[0; 4]);
+
+    let mut mac = HmacMd5::new(&key);
+    mac.update(&challenge.server_challenge);
+    mac.update(&temp);
+    let nt_proof = mac.finalize();
+
+    let mut nt_response = nt_proof.to_vec();
+    nt_response.extend_from_slice(&temp);
+
+    // 服务器提供了时间戳时，LMv2响应应当为全0
+    let lm_response = if challenge.timestamp.is_some() {
+        [0u8; 24].to_vec()
+    } else {
+        let mut mac = HmacMd5::new(&key);
+        mac.update(&challenge.server_challenge);
+        mac.update(client_challenge);
+        let mut lm = mac.finalize().to_vec();
+        lm.extend_from_slice(client_challenge);
+        lm
+    };
+
+    let session_key = HmacMd5::mac(&key, &nt_proof);
+    zeroize(&mut key);
+    (lm_response, nt_response, session_key)
+}
+
+/// 构造AUTHENTICATE_MESSAGE
+///
+/// ## 返回值
+/// `(消息, 会话密钥)`，匿名认证时没有会话密钥
+pub fn authenticate_message(
+    creds: &NtlmCredentials,
+    challenge: &NtlmChallenge,
+    client_challenge: &[u8; 8],
+    now: u64,
+) -> (Vec<u8>, Option<[u8; 16]>) {
+    let mut flags = CLIENT_FLAGS & challenge.flags;
+    // 只实现了Unicode编码的字符串
+    flags |= NEGOTIATE_UNICODE;
+
+    let (lm, nt, session_key) = if creds.is_anonymous() {
+        flags |= NEGOTIATE_ANONYMOUS;
+        // 匿名认证时LmChallengeResponse为一个0字节，NtChallengeResponse为空
+        ([0u8].to_vec(), Vec::new(), None)
+    } else {
+        let (lm, nt, key) = ntlmv2_response(creds, challenge, client_challenge, now);
+        (lm, nt, Some(key))
+    };
+
+    let mut msg = Vec::with_capacity(64 + lm.len() + nt.len());
+    msg.extend_from_slice(NTLMSSP_SIGNATURE);
+    msg.extend_from_slice(&NTLMSSP_AUTH.to_le_bytes());
+    msg.resize(64, 0);
+    msg[60..64].copy_from_slice(&flags.to_le_bytes());
+    put_security_buffer(&mut msg, 12, &lm);
+    put_security_buffer(&mut msg, 20, &nt);
+    put_security_buffer(&mut msg, 28, &utf16le(&creds.domain));
+    put_security_buffer(&mut msg, 36, &utf16le(&creds.user));
+    put_security_buffer(&mut msg, 44, &utf16le("DRAGONOS"));
+    put_security_buffer(&mut msg, 52, &[]);
+    (msg, session_key)
+}
@@ -0,0 +1,391 @@
+//! SMB2协议的消息格式
+//!
+//! 所有整数都是小端序。每条消息由64字节的SMB2头和命令相关的消息体组成，
+//! 消息体中变长数据的偏移都从SMB2头的开头算起。
+//! 通过TCP 445端口直接传输，每条消息前面是4字节的长度（高字节为0，低24位为大端序的长度）。
+//!
+//! 参考: [MS-SMB2] https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-smb2
+
+use alloc::{string::String, vec::Vec};
+use system_error::SystemError;
+
+use crate::time::PosixTimeSpec;
+
+/// SMB2头的长度
+pub const SMB2_HEADER_SIZE: usize = 64;
+const SMB2_PROTOCOL_ID: &[u8; 4] = b"\xfeSMB";
+
+pub const SMB2_NEGOTIATE: u16 = 0x0000;
+pub const SMB2_SESSION_SETUP: u16 = 0x0001;
+pub const SMB2_LOGOFF: u16 = 0x0002;
+pub const SMB2_TREE_CONNECT: u16 = 0x0003;
+pub const SMB2_TREE_DISCONNECT: u16 = 0x0004;
+pub const SMB2_CREATE: u16 = 0x0005;
+pub const SMB2_CLOSE: u16 = 0x0006;
+pub const SMB2_FLUSH: u16 = 0x0007;
+pub const SMB2_READ: u16 = 0x0008;
+pub const SMB2_WRITE: u16 = 0x0009;
+pub const SMB2_QUERY_DIRECTORY: u16 = 0x000e;
+pub const SMB2_QUERY_INFO: u16 = 0x0010;
+pub const SMB2_SET_INFO: u16 = 0x0011;
+
+/// 头部标志：这是一个响应
+pub const SMB2_FLAGS_SERVER_TO_REDIR: u32 = 0x0000_0001;
+/// 头部标志：异步响应，8字节的AsyncId代替了Reserved和TreeId
+pub const SMB2_FLAGS_ASYNC_COMMAND: u32 = 0x0000_0002;
+/// 头部标志：消息已签名
+pub const SMB2_FLAGS_SIGNED: u32 = 0x0000_0008;
+
+pub const SMB2_DIALECT_202: u16 = 0x0202;
+pub const SMB2_DIALECT_21: u16 = 0x0210;
+
+pub const SMB2_NEGOTIATE_SIGNING_ENABLED: u16 = 0x0001;
+pub const SMB2_NEGOTIATE_SIGNING_REQUIRED: u16 = 0x0002;
+
+pub const SMB2_SESSION_FLAG_IS_GUEST: u16 = 0x0001;
+pub const SMB2_SESSION_FLAG_IS_NULL: u16 = 0x0002;
+
+pub const STATUS_SUCCESS: u32 = 0x0000_0000;
+pub const STATUS_PENDING: u32 = 0x0000_0103;
+pub const STATUS_NO_MORE_FILES: u32 = 0x8000_0006;
+pub const STATUS_END_OF_FILE: u32 = 0xc000_0011;
+pub const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc000_0016;
+
+/// 把NTSTATUS转换为错误码
+pub fn status_to_errno(status: u32) -> SystemError {
+    match status {
+        // STATUS_NO_SUCH_FILE, STATUS_OBJECT_NAME_NOT_FOUND, STATUS_OBJECT_PATH_NOT_FOUND,
+        // STATUS_DELETE_PENDING, STATUS_BAD_NETWORK_NAME
+        0xc000_000f | 0xc000_0034 | 0xc000_003a | 0xc000_0056 | 0xc000_00cc => SystemError::ENOENT,
+        // STATUS_OBJECT_NAME_COLLISION
+        0xc000_0035 => SystemError::EEXIST,
+        // STATUS_ACCESS_DENIED, STATUS_LOGON_FAILURE, STATUS_CANNOT_DELETE,
+        // STATUS_ACCOUNT_DISABLED, STATUS_PASSWORD_EXPIRED
+        0xc000_0022 | 0xc000_006d | 0xc000_0121 | 0xc000_0072 | 0xc000_0071 => SystemError::EACCES,
+        // STATUS_SHARING_VIOLATION, STATUS_FILE_LOCK_CONFLICT
+        0xc000_0043 | 0xc000_0054 => SystemError::EBUSY,
+        // STATUS_DIRECTORY_NOT_EMPTY
+        0xc000_0101 => SystemError::ENOTEMPTY,
+        // STATUS_NOT_A_DIRECTORY
+        0xc000_0103 => SystemError::ENOTDIR,
+        // STATUS_FILE_IS_A_DIRECTORY
+        0xc000_00ba => SystemError::EISDIR,
+        // STATUS_DISK_FULL
+        0xc000_007f => SystemError::ENOSPC,
+        // STATUS_MEDIA_WRITE_PROTECTED
+        0xc000_00a2 => SystemError::EROFS,
+        // STATUS_INVALID_PARAMETER, STATUS_OBJECT_NAME_INVALID
+        0xc000_000d | 0xc000_0033 => SystemError::EINVAL,
+        // STATUS_NOT_SUPPORTED, STATUS_INVALID_INFO_CLASS
+        0xc000_00bb | 0xc000_0003 => SystemError::EOPNOTSUPP_OR_ENOTSUP,
+        // STATUS_NAME_TOO_LONG
+        0xc000_0106 => SystemError::ENAMETOOLONG,
+        _ => SystemError::EIO,
+    }
+}
+
+pub fn get_u16(buf: &[u8], off: usize) -> Result<u16, SystemError> {
+    buf.get(off..off + 2)
+        .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(SystemError::EBADMSG)
+}
+
+pub fn get_u32(buf: &[u8], off: usize) -> Result<u32, SystemError> {
+    buf.get(off..off + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(SystemError::EBADMSG)
+}
+
+pub fn get_u64(buf: &[u8], off: usize) -> Result<u64, SystemError> {
+    buf.get(off..off + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(SystemError::EBADMSG)
+}
+
+/// 编码为UTF-16LE（SMB2中的路径和NTLMSSP中的字符串都使用这种编码）
+pub fn utf16le(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+}
+
+/// 解码UTF-16LE
+pub fn from_utf16le(buf: &[u8]) -> Result<String, SystemError> {
+    let units = buf
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(|_| SystemError::EILSEQ)
+}
+
+/// 1601-01-01到1970-01-01之间的100纳秒数
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+/// FILETIME（自1601年起的100纳秒数）转换为时间
+pub fn filetime_to_timespec(ft: u64) -> PosixTimeSpec {
+    let ticks = ft as i64 - FILETIME_UNIX_EPOCH as i64;
+    PosixTimeSpec::new(
+        ticks.div_euclid(10_000_000),
+        ticks.rem_euclid(10_000_000) * 100,
+    )
+}
+
+/// 时间转换为FILETIME
+pub fn timespec_to_filetime(ts: &PosixTimeSpec) -> u64 {
+    (ts.tv_sec * 10_000_000 + ts.tv_nsec / 100 + FILETIME_UNIX_EPOCH as i64) as u64
+}
+
+/// 16字节的文件句柄，由CREATE返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileId(pub [u8; 16]);
+
+/// 正在构造的请求
+pub struct Smb2Request {
+    pub command: u16,
+    buf: Vec<u8>,
+}
+
+impl Smb2Request {
+    /// 创建请求，写入SMB2头和消息体的StructureSize
+    ///
+    /// 头中的MessageId、TreeId、SessionId和签名在发送时由连接填写
+    pub fn new(command: u16, structure_size: u16) -> Self {
+        let mut buf = Vec::with_capacity(SMB2_HEADER_SIZE + 64);
+        buf.extend_from_slice(SMB2_PROTOCOL_ID);
+        buf.extend_from_slice(&(SMB2_HEADER_SIZE as u16).to_le_bytes());
+        buf.resize(SMB2_HEADER_SIZE, 0);
+        buf[12..14].copy_from_slice(&command.to_le_bytes());
+        buf.extend_from_slice(&structure_size.to_le_bytes());
+        Self { command, buf }
+    }
+
+    pub fn u8(&mut self, v: u8) -> &mut Self {
+        self.buf.push(v);
+        self
+    }
+
+    pub fn u16(&mut self, v: u16) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn u32(&mut self, v: u32) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, v: u64) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn bytes(&mut self, v: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(v);
+        self
+    }
+
+    pub fn file_id(&mut self, id: &FileId) -> &mut Self {
+        self.bytes(&id.0)
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// 收到的响应
+pub struct Smb2Response {
+    pub buf: Vec<u8>,
+}
+
+impl Smb2Response {
+    pub fn new(buf: Vec<u8>) -> Result<Self, SystemError> {
+        if buf.len() < SMB2_HEADER_SIZE + 2 || &buf[..4] != SMB2_PROTOCOL_ID {
+            return Err(SystemError::EBADMSG);
+        }
+        Ok(Self { buf })
+    }
+
+    pub fn command(&self) -> u16 {
+        get_u16(&self.buf, 12).unwrap()
+    }
+
+    pub fn status(&self) -> u32 {
+        get_u32(&self.buf, 8).unwrap()
+    }
+
+    pub fn credits(&self) -> u16 {
+        get_u16(&self.buf, 14).unwrap()
+    }
+
+    pub fn flags(&self) -> u32 {
+        get_u32(&self.buf, 16).unwrap()
+    }
+
+    pub fn message_id(&self) -> u64 {
+        get_u64(&self.buf, 24).unwrap()
+    }
+
+    pub fn tree_id(&self) -> u32 {
+        get_u32(&self.buf, 36).unwrap()
+    }
+
+    pub fn session_id(&self) -> u64 {
+        get_u64(&self.buf, 40).unwrap()
+    }
+
+    /// 消息体中偏移`off`处的字段
+    pub fn body_u8(&self, off: usize) -> Result<u8, SystemError> {
+        self.buf
+            .get(SMB2_HEADER_SIZE + off)
+            .copied()
+            .ok_or(SystemError::EBADMSG)
+    }
+
+    pub fn body_u16(&self, off: usize) -> Result<u16, SystemError> {
+        get_u16(&self.buf, SMB2_HEADER_SIZE + off)
+    }
+
+    pub fn body_u32(&self, off: usize) -> Result<u32, SystemError> {
+        get_u32(&self.buf, SMB2_HEADER_SIZE + off)
+    }
+
+    pub fn body_u64(&self, off: usize) -> Result<u64, SystemError> {
+        get_u64(&self.buf, SMB2_HEADER_SIZE + off)
+    }
+
+    pub fn body_file_id(&self, off: usize) -> Result<FileId, SystemError> {
+        let start = SMB2_HEADER_SIZE + off;
+        let bytes = self
+            .buf
+            .get(start..start + 16)
+            .ok_or(SystemError::EBADMSG)?;
+        Ok(FileId(bytes.try_into().unwrap()))
+    }
+
+    /// 相对于SMB2头的(偏移, 长度)描述的变长数据
+    pub fn buffer(&self, offset: usize, len: usize) -> Result<&[u8], SystemError> {
+        if len == 0 {
+            return Ok(&[]);
+        }
+        self.buf
+            .get(offset..offset + len)
+            .ok_or(SystemError::EBADMSG)
+    }
+}
+
+/// CREATE请求的DesiredAccess
+pub const FILE_READ_DATA: u32 = 0x0000_0001;
+pub const FILE_WRITE_DATA: u32 = 0x0000_0002;
+pub const FILE_READ_ATTRIBUTES: u32 = 0x0000_0080;
+pub const FILE_WRITE_ATTRIBUTES: u32 = 0x0000_0100;
+pub const DELETE: u32 = 0x0001_0000;
+pub const SYNCHRONIZE: u32 = 0x0010_0000;
+pub const GENERIC_READ: u32 = 0x8000_0000;
+pub const GENERIC_WRITE: u32 = 0x4000_0000;
+
+/// CREATE请求的ShareAccess
+pub const FILE_SHARE_ALL: u32 = 0x0000_0007;
+
+/// CREATE请求的CreateDisposition
+pub const FILE_OPEN: u32 = 0x0000_0001;
+pub const FILE_CREATE: u32 = 0x0000_0002;
+
+/// CREATE请求的CreateOptions
+pub const FILE_DIRECTORY_FILE: u32 = 0x0000_0001;
+pub const FILE_NON_DIRECTORY_FILE: u32 = 0x0000_0040;
+
+/// 文件属性
+pub const FILE_ATTRIBUTE_READONLY: u32 = 0x0000_0001;
+pub const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x0000_0010;
+pub const FILE_ATTRIBUTE_NORMAL: u32 = 0x0000_0080;
+
+/// QUERY_INFO/SET_INFO的InfoType
+pub const SMB2_0_INFO_FILE: u8 = 0x01;
+pub const SMB2_0_INFO_FILESYSTEM: u8 = 0x02;
+
+/// 文件信息类
+pub const FILE_DIRECTORY_INFORMATION: u8 = 0x01;
+pub const FILE_BASIC_INFORMATION: u8 = 0x04;
+pub const FILE_RENAME_INFORMATION: u8 = 0x0a;
+pub const FILE_DISPOSITION_INFORMATION: u8 = 0x0d;
+pub const FILE_END_OF_FILE_INFORMATION: u8 = 0x14;
+/// 文件系统信息类
+pub const FILE_FS_FULL_SIZE_INFORMATION: u8 = 0x07;
+
+/// QUERY_DIRECTORY的标志：从头开始枚举
+pub const SMB2_RESTART_SCANS: u8 = 0x01;
+
+/// 文件的属性，来自CREATE响应或者目录枚举
+#[derive(Debug, Clone, Copy)]
+pub struct FileAttrs {
+    pub creation_time: u64,
+    pub last_access_time: u64,
+    pub last_write_time: u64,
+    pub change_time: u64,
+    pub allocation_size: u64,
+    pub end_of_file: u64,
+    pub attributes: u32,
+}
+
+impl FileAttrs {
+    /// 解析CREATE响应中从CreationTime开始的属性
+    pub fn parse_create(buf: &[u8], off: usize) -> Result<Self, SystemError> {
+        Ok(Self {
+            creation_time: get_u64(buf, off)?,
+            last_access_time: get_u64(buf, off + 8)?,
+            last_write_time: get_u64(buf, off + 16)?,
+            change_time: get_u64(buf, off + 24)?,
+            allocation_size: get_u64(buf, off + 32)?,
+            end_of_file: get_u64(buf, off + 40)?,
+            attributes: get_u32(buf, off + 48)?,
+        })
+    }
+
+    /// 解析FileDirectoryInformation中从CreationTime开始的属性
+    ///
+    /// 与CREATE响应不同，EndOfFile在AllocationSize之前
+    pub fn parse_dir_info(buf: &[u8], off: usize) -> Result<Self, SystemError> {
+        Ok(Self {
+            creation_time: get_u64(buf, off)?,
+            last_access_time: get_u64(buf, off + 8)?,
+            last_write_time: get_u64(buf, off + 16)?,
+            change_time: get_u64(buf, off + 24)?,
+            end_of_file: get_u64(buf, off + 32)?,
+            allocation_size: get_u64(buf, off + 40)?,
+            attributes: get_u32(buf, off + 48)?,
+        })
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attributes & FILE_ATTRIBUTE_DIRECTORY != 0
+    }
+}
+
+/// FileDirectoryInformation中的一项
+pub struct DirEntry {
+    pub name: String,
+    pub attrs: FileAttrs,
+}
+
+/// 解析QUERY_DIRECTORY返回的FileDirectoryInformation列表
+pub fn parse_directory_info(buf: &[u8]) -> Result<Vec<DirEntry>, SystemError> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    loop {
+        let next = get_u32(buf, pos)? as usize;
+        let attrs = FileAttrs::parse_dir_info(buf, pos + 8)?;
+        let name_len = get_u32(buf, pos + 60)? as usize;
+        let name = buf
+            .get(pos + 64..pos + 64 + name_len)
+            .ok_or(SystemError::EBADMSG)?;
+        entries.push(DirEntry {
+            name: from_utf16le(name)?,
+            attrs,
+        });
+        if next == 0 {
+            break;
+        }
+        pos += next;
+    }
+    Ok(entries)
+}
@@ -3,6 +3,9 @@ use core::intrinsics::unlikely;
 use core::sync::atomic::{AtomicU64, Ordering};
 
 use crate::filesystem::page_cache::{PageCache, PageCacheBackend};
+use crate::filesystem::vfs::mount_options::{
+    parse_size, MountOptionKind, MountOptionSpec, MountOptions,
+};
 use crate::filesystem::vfs::syscall::RenameFlags;
 use crate::filesystem::vfs::{FileSystemMakerData, FSMAKER};
 use crate::libs::rwsem::RwSem;
@@ -32,8 +35,11 @@ use alloc::{
 use system_error::SystemError;
 
 use super::vfs::{
-    file::FilePrivateData, utils::DName, FileSystem, FsInfo, IndexNode, InodeFlags, InodeId,
-    InodeMode, Metadata, SpecialNodeData,
+    dir_cookie::{DirCookie, DIR_COOKIE_DOT, DIR_COOKIE_DOTDOT, DIR_COOKIE_FIRST},
+    file::FilePrivateData,
+    utils::DName,
+    xattr::SimpleXattrs,
+    FileSystem, FsInfo, IndexNode, InodeFlags, InodeId, InodeMode, Metadata, SpecialNodeData,
 };
 
 use linkme::distributed_slice;
@@ -85,6 +91,23 @@ impl PageCacheBackend for TmpfsPageCacheBackend {
     }
 }
 
+/// 目录项被删除后减少inode的链接数，最后一个链接被删除时归还它占用的空间和inode计数
+fn tmpfs_drop_link(inode: &LockedTmpfsInode) {
+    let mut guard = inode.0.lock();
+    // 空目录被删除时，它自己的"."和父目录中的目录项同时消失
+    let links = if guard.metadata.file_type == FileType::Dir {
+        2
+    } else {
+        1
+    };
+    guard.metadata.nlinks = guard.metadata.nlinks.saturating_sub(links);
+    if guard.metadata.nlinks == 0 {
+        if let Some(fs) = guard.fs.upgrade() {
+            fs.free_inode(guard.metadata.size as usize);
+        }
+    }
+}
+
 fn tmpfs_move_entry_between_dirs(
     src_dir: &mut TmpfsInode,
     dst_dir: &mut TmpfsInode,
@@ -133,7 +156,7 @@ fn tmpfs_move_entry_between_dirs(
         if existing_id == to_move_id {
             // Destination already points to the same inode. For files this is
             // effectively removing the old entry.
-            src_dir.children.remove(old_key);
+            src_dir.remove_child(old_key);
             return Ok(());
         }
 
@@ -150,23 +173,23 @@ fn tmpfs_move_entry_between_dirs(
         }
 
         // Remove existing destination entry (replacement).
-        dst_dir.children.remove(new_key);
+        if let Some(replaced) = dst_dir.remove_child(new_key) {
+            tmpfs_drop_link(&replaced);
+        }
         if old_type == FileType::Dir {
             dst_dir.metadata.nlinks = dst_dir.metadata.nlinks.saturating_sub(1);
         }
     }
 
     // Remove from source directory.
-    src_dir.children.remove(old_key);
+    src_dir.remove_child(old_key);
     if old_type == FileType::Dir {
         src_dir.metadata.nlinks = src_dir.metadata.nlinks.saturating_sub(1);
         dst_dir.metadata.nlinks = dst_dir.metadata.nlinks.saturating_add(1);
     }
 
     // Insert into destination directory and update inode bookkeeping.
-    dst_dir
-        .children
-        .insert(new_key.clone(), inode_to_move.clone());
+    dst_dir.insert_child(new_key.clone(), inode_to_move.clone());
     let mut moved = inode_to_move.0.lock();
     moved.parent = Arc::downgrade(&dst_self);
     moved.name = new_key.clone();
@@ -181,8 +204,13 @@ pub struct LockedTmpfsInode(pub Mutex<TmpfsInode>);
 pub struct Tmpfs {
     root_inode: Arc<LockedTmpfsInode>,
     super_block: RwSem<SuperBlock>,
-    size_limit: Option<u64>,
+    /// 容量上限（字节），为0表示不限制
+    size_limit: AtomicU64,
     current_size: AtomicU64,
+    /// inode数量上限，为0表示不限制
+    inode_limit: AtomicU64,
+    /// 已经使用的inode数量（包括根目录）
+    nr_inodes: AtomicU64,
 }
 
 #[derive(Debug)]
@@ -190,11 +218,16 @@ pub struct TmpfsInode {
     parent: Weak<LockedTmpfsInode>,
     self_ref: Weak<LockedTmpfsInode>,
     children: BTreeMap<DName, Arc<LockedTmpfsInode>>,
+    /// 目录项的读取位置，按照加入目录的先后分配，目录被修改之后不会改变
+    dir_offsets: BTreeMap<DName, u64>,
+    /// 下一个加入目录的项使用的读取位置
+    next_dir_offset: u64,
     page_cache: Option<Arc<PageCache>>,
     metadata: Metadata,
     fs: Weak<Tmpfs>,
     special_node: Option<SpecialNodeData>,
     name: DName,
+    xattrs: SimpleXattrs,
 }
 
 impl TmpfsInode {
@@ -203,6 +236,8 @@ impl TmpfsInode {
             parent: Weak::default(),
             self_ref: Weak::default(),
             children: BTreeMap::new(),
+            dir_offsets: BTreeMap::new(),
+            next_dir_offset: DIR_COOKIE_FIRST,
             page_cache: None,
             metadata: Metadata {
                 dev_id: 0,
@@ -225,46 +260,67 @@ impl TmpfsInode {
             fs: Weak::default(),
             special_node: None,
             name: Default::default(),
+            xattrs: SimpleXattrs::new(),
         }
     }
+
+    /// 在目录中加入一项，并为它分配读取位置
+    fn insert_child(&mut self, name: DName, inode: Arc<LockedTmpfsInode>) {
+        self.dir_offsets.insert(name.clone(), self.next_dir_offset);
+        self.next_dir_offset += 1;
+        self.children.insert(name, inode);
+    }
+
+    fn remove_child(&mut self, name: &DName) -> Option<Arc<LockedTmpfsInode>> {
+        self.dir_offsets.remove(name);
+        self.children.remove(name)
+    }
 }
 
 #[derive(Debug)]
 pub struct TmpfsMountData {
     mode: InodeMode,
     size_bytes: Option<u64>,
+    nr_inodes: Option<u64>,
 }
 
+/// tmpfs认识的挂载选项
+const TMPFS_MOUNT_OPTIONS: &[MountOptionSpec] = &[
+    // mode 参数按八进制解析（mount 的习惯用法，如 755 = rwxr-xr-x）
+    MountOptionSpec::new("mode", MountOptionKind::Mode),
+    // size 除了k/m/g后缀之外还可以是物理内存的百分比，由tmpfs自己解析
+    MountOptionSpec::new("size", MountOptionKind::Str),
+    MountOptionSpec::new("nr_inodes", MountOptionKind::Size),
+];
+
 impl TmpfsMountData {
     fn parse(raw: Option<&str>) -> Result<Self, SystemError> {
-        let mut mode = InodeMode::S_IRWXUGO;
-        let mut size_bytes = None;
-
-        if let Some(raw) = raw {
-            for opt in raw.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
-                if let Some(v) = opt.strip_prefix("mode=").map(|s| s.trim()) {
-                    // mode 参数按八进制解析（mount 的习惯用法，如 755 = rwxr-xr-x）
-                    let parsed = u32::from_str_radix(v, 8).map_err(|_| SystemError::EINVAL)?;
-                    mode = InodeMode::from_bits_truncate(parsed);
-                } else if let Some(v) = opt.strip_prefix("size=").map(|s| s.trim()) {
-                    // 支持大小写后缀：g/G, m/M, k/K
-                    let v_lower = v.to_lowercase();
-                    let (num_str, mul) = if let Some(s) = v_lower.strip_suffix('g') {
-                        (s, 1u64 << 30)
-                    } else if let Some(s) = v_lower.strip_suffix('m') {
-                        (s, 1u64 << 20)
-                    } else if let Some(s) = v_lower.strip_suffix('k') {
-                        (s, 1u64 << 10)
-                    } else {
-                        (&v_lower[..], 1u64)
-                    };
-                    let base = num_str.parse::<u64>().map_err(|_| SystemError::EINVAL)?;
-                    size_bytes = Some(base.saturating_mul(mul));
-                }
-            }
-        }
+        let opts = MountOptions::parse("tmpfs", raw, TMPFS_MOUNT_OPTIONS)?;
+        opts.warn_unknown("tmpfs");
+
+        let mode = opts
+            .mode("mode")
+            .map(InodeMode::from_bits_truncate)
+            .unwrap_or(InodeMode::S_IRWXUGO);
+        let size_bytes = opts.str("size").map(Self::parse_size).transpose()?;
+
+        Ok(Self {
+            mode,
+            size_bytes,
+            nr_inodes: opts.size("nr_inodes"),
+        })
+    }
 
-        Ok(Self { mode, size_bytes })
+    /// 解析size选项，size=N% 表示物理内存的百分比
+    fn parse_size(v: &str) -> Result<u64, SystemError> {
+        let size = if let Some(pct) = v.strip_suffix('%') {
+            pct.parse::<u64>()
+                .map(|pct| Tmpfs::total_ram_bytes().saturating_mul(pct) / 100)
+                .map_err(|_| SystemError::EINVAL)
+        } else {
+            parse_size(v)
+        };
+        size.inspect_err(|_| log::warn!("tmpfs: bad value '{}' for mount option 'size'", v))
     }
 }
 
@@ -310,6 +366,10 @@ impl FileSystem for Tmpfs {
         "tmpfs"
     }
 
+    fn dcache_enabled(&self) -> bool {
+        true
+    }
+
     fn super_block(&self) -> SuperBlock {
         self.super_block.read().clone()
     }
@@ -318,15 +378,51 @@ impl FileSystem for Tmpfs {
         // tmpfs 是内存文件系统，数据已经在 page_cache 中，不需要 readahead
         false
     }
+
+    fn remount(&self, data: Option<&str>) -> Result<(), SystemError> {
+        let d = TmpfsMountData::parse(data)?;
+        // 与Linux一致，新的上限不能小于已经使用的量；0表示不限制
+        if let Some(size) = d.size_bytes {
+            if size != 0 && size < self.current_size.load(Ordering::Acquire) {
+                return Err(SystemError::EINVAL);
+            }
+        }
+        if let Some(inodes) = d.nr_inodes {
+            if inodes != 0 && inodes < self.nr_inodes.load(Ordering::Acquire) {
+                return Err(SystemError::EINVAL);
+            }
+        }
+        if let Some(size) = d.size_bytes {
+            self.size_limit.store(size, Ordering::Release);
+        }
+        if let Some(inodes) = d.nr_inodes {
+            self.inode_limit.store(inodes, Ordering::Release);
+        }
+        self.update_superblock();
+        Ok(())
+    }
 }
 
 impl Tmpfs {
     #[inline]
-    fn default_size_bytes() -> usize {
+    fn total_ram_bytes() -> u64 {
         // 与 /proc/meminfo 一致：从帧分配器获取物理内存总量。
-        let total = unsafe { LockedFrameAllocator.usage() }.total().bytes();
-        let half = total / 2;
-        half.clamp(TMPFS_DEFAULT_MIN_SIZE_BYTES, TMPFS_DEFAULT_MAX_SIZE_BYTES)
+        unsafe { LockedFrameAllocator.usage() }.total().bytes() as u64
+    }
+
+    #[inline]
+    fn default_size_bytes() -> u64 {
+        let half = Self::total_ram_bytes() / 2;
+        half.clamp(
+            TMPFS_DEFAULT_MIN_SIZE_BYTES as u64,
+            TMPFS_DEFAULT_MAX_SIZE_BYTES as u64,
+        )
+    }
+
+    /// 默认的inode数量上限：物理内存页数的一半，与Linux一致
+    #[inline]
+    fn default_nr_inodes() -> u64 {
+        Self::total_ram_bytes() / MMArch::PAGE_SIZE as u64 / 2
     }
 
     #[inline]
@@ -334,19 +430,21 @@ impl Tmpfs {
         bytes.div_ceil(TMPFS_BLOCK_SIZE)
     }
 
-    fn update_superblock_free(&self, current_bytes: u64) {
-        // 只在启用 size_limit 时输出可用容量；否则保持现有行为（0-sized）。
-        let Some(limit) = self.size_limit else {
-            return;
-        };
-        let total_blocks = limit / TMPFS_BLOCK_SIZE;
-        let used_blocks = Self::bytes_to_blocks_ceil(current_bytes);
-        let free_blocks = total_blocks.saturating_sub(used_blocks);
+    /// 根据当前的上限和使用量更新 superblock 中的统计，供 statfs/df 使用
+    fn update_superblock(&self) {
+        let size_limit = self.size_limit.load(Ordering::Acquire);
+        let inode_limit = self.inode_limit.load(Ordering::Acquire);
         let mut sb = self.super_block.write();
+        sb.frsize = TMPFS_BLOCK_SIZE;
+        // 不限制容量时保持 0-sized，不限制inode数量时同理
+        let total_blocks = size_limit / TMPFS_BLOCK_SIZE;
+        let used_blocks = Self::bytes_to_blocks_ceil(self.current_size.load(Ordering::Acquire));
+        let free_blocks = total_blocks.saturating_sub(used_blocks);
         sb.blocks = total_blocks;
         sb.bfree = free_blocks;
         sb.bavail = free_blocks;
-        sb.frsize = TMPFS_BLOCK_SIZE;
+        sb.files = inode_limit;
+        sb.ffree = inode_limit.saturating_sub(self.nr_inodes.load(Ordering::Acquire));
     }
 
     pub fn new(mount_data: &TmpfsMountData) -> Arc<Self> {
@@ -354,29 +452,26 @@ impl Tmpfs {
         // 这样 busybox df -h（默认过滤 f_blocks==0）就能显示 /tmp。
         let size_limit = mount_data
             .size_bytes
-            .or_else(|| Some(Self::default_size_bytes() as u64));
+            .unwrap_or_else(Self::default_size_bytes);
+        let inode_limit = mount_data.nr_inodes.unwrap_or_else(Self::default_nr_inodes);
 
-        let mut sb = SuperBlock::new(
+        let sb = SuperBlock::new(
             Magic::TMPFS_MAGIC,
             TMPFS_BLOCK_SIZE,
             TMPFS_MAX_NAMELEN as u64,
         );
-        sb.frsize = TMPFS_BLOCK_SIZE;
-        if let Some(size) = size_limit {
-            let blocks = size / TMPFS_BLOCK_SIZE;
-            sb.blocks = blocks;
-            sb.bfree = blocks;
-            sb.bavail = blocks;
-        }
 
         let root: Arc<LockedTmpfsInode> = Arc::new(LockedTmpfsInode(Mutex::new(TmpfsInode::new())));
 
         let result: Arc<Tmpfs> = Arc::new(Tmpfs {
             root_inode: root,
             super_block: RwSem::new(sb),
-            size_limit,
+            size_limit: AtomicU64::new(size_limit),
             current_size: AtomicU64::new(0),
+            inode_limit: AtomicU64::new(inode_limit),
+            nr_inodes: AtomicU64::new(1),
         });
+        result.update_superblock();
 
         let mut root_guard: MutexGuard<TmpfsInode> = result.root_inode.0.lock();
         root_guard.parent = Arc::downgrade(&result.root_inode);
@@ -392,46 +487,62 @@ impl Tmpfs {
     /// 返回Ok(())如果更新成功，Err(SystemError::ENOSPC)如果超过限制
     /// 使用compare_exchange_weak循环确保并发安全
     fn increase_size(&self, size_diff: u64) -> Result<(), SystemError> {
-        if let Some(limit) = self.size_limit {
-            // 使用compare_exchange_weak循环确保原子性
-            loop {
-                let current = self.current_size.load(Ordering::Acquire);
-                let new_total = current.saturating_add(size_diff);
-
-                if new_total > limit {
-                    return Err(SystemError::ENOSPC);
-                }
-
-                // 原子地更新，如果current没有被其他线程修改，则更新成功
-                match self.current_size.compare_exchange_weak(
-                    current,
-                    new_total,
-                    Ordering::Release,
-                    Ordering::Acquire,
-                ) {
-                    Ok(_) => {
-                        // 同步更新 superblock 的 free 统计，供 statfs/df 使用
-                        self.update_superblock_free(new_total);
-                        break;
-                    } // 更新成功
-                    Err(_) => continue, // 被其他线程修改，重试
-                }
-            }
-        }
+        Self::charge(&self.current_size, &self.size_limit, size_diff)?;
+        // 同步更新 superblock 的 free 统计，供 statfs/df 使用
+        self.update_superblock();
         Ok(())
     }
 
     /// 原子地减少文件系统当前使用的大小（用于文件删除或缩小）
     /// 使用fetch_sub确保并发安全
     fn decrease_size(&self, size: usize) {
-        if self.size_limit.is_some() {
-            let size_to_decrease = size as u64;
-            // 使用fetch_sub原子地减少大小
-            let prev = self
-                .current_size
-                .fetch_sub(size_to_decrease, Ordering::Release);
-            let new = prev.saturating_sub(size_to_decrease);
-            self.update_superblock_free(new);
+        let size_to_decrease = size as u64;
+        // 使用fetch_update原子地减少大小，避免下溢
+        let _ = self
+            .current_size
+            .fetch_update(Ordering::Release, Ordering::Acquire, |cur| {
+                Some(cur.saturating_sub(size_to_decrease))
+            });
+        self.update_superblock();
+    }
+
+    /// 为新的inode记账，超过 nr_inodes= 的上限时返回ENOSPC
+    fn alloc_inode(&self) -> Result<(), SystemError> {
+        Self::charge(&self.nr_inodes, &self.inode_limit, 1)?;
+        self.update_superblock();
+        Ok(())
+    }
+
+    /// 释放最后一个链接已经被删除的inode：归还它占用的空间和inode计数
+    fn free_inode(&self, size: usize) {
+        let _ = self
+            .nr_inodes
+            .fetch_update(Ordering::Release, Ordering::Acquire, |cur| {
+                Some(cur.saturating_sub(1))
+            });
+        self.decrease_size(size);
+    }
+
+    /// 在`limit`（为0时不限制）之内原子地把`counter`增加`diff`
+    ///
+    /// 使用compare_exchange_weak循环确保并发安全
+    fn charge(counter: &AtomicU64, limit: &AtomicU64, diff: u64) -> Result<(), SystemError> {
+        loop {
+            let current = counter.load(Ordering::Acquire);
+            let new_total = current.saturating_add(diff);
+            let limit = limit.load(Ordering::Acquire);
+
+            if limit != 0 && new_total > limit {
+                return Err(SystemError::ENOSPC);
+            }
+
+            // 原子地更新，如果current没有被其他线程修改，则更新成功
+            if counter
+                .compare_exchange_weak(current, new_total, Ordering::Release, Ordering::Acquire)
+                .is_ok()
+            {
+                return Ok(());
+            }
         }
     }
 }
@@ -733,6 +844,43 @@ impl IndexNode for LockedTmpfsInode {
         }
     }
 
+    fn punch_hole(&self, offset: usize, len: usize) -> Result<(), SystemError> {
+        let inode = self.0.lock();
+        if inode.metadata.file_type != FileType::File {
+            return Err(SystemError::ENODEV);
+        }
+        let end = offset.saturating_add(len).min(inode.metadata.size as usize);
+        if offset >= end {
+            return Ok(());
+        }
+        let page_cache = match inode.page_cache.clone() {
+            Some(pc) => pc,
+            None => return Ok(()),
+        };
+
+        // 范围内的整页直接从页缓存中丢弃，之后读到的是新的零页
+        let first_full = offset.div_ceil(MMArch::PAGE_SIZE);
+        let last_full = end / MMArch::PAGE_SIZE;
+        if first_full < last_full {
+            page_cache.lock().discard_range(first_full, last_full - 1);
+        }
+
+        // 首尾不完整的页，以及因为被映射等原因无法丢弃的页，把对应的部分清零
+        let mut pos = offset;
+        while pos < end {
+            let page_index = pos / MMArch::PAGE_SIZE;
+            let page_start = page_index * MMArch::PAGE_SIZE;
+            let page_end = (page_start + MMArch::PAGE_SIZE).min(end);
+            if let Some(page) = page_cache.manager().get_page_any(page_index) {
+                let mut guard = page.write();
+                unsafe { guard.as_slice_mut()[pos - page_start..page_end - page_start].fill(0) };
+            }
+            pos = page_end;
+        }
+
+        Ok(())
+    }
+
     fn create_with_data(
         &self,
         name: &str,
@@ -748,11 +896,15 @@ impl IndexNode for LockedTmpfsInode {
         if inode.children.contains_key(&name) {
             return Err(SystemError::EEXIST);
         }
+        let fs = inode.fs.upgrade().ok_or(SystemError::EIO)?;
+        fs.alloc_inode()?;
 
         let result: Arc<LockedTmpfsInode> = Arc::new(LockedTmpfsInode(Mutex::new(TmpfsInode {
             parent: inode.self_ref.clone(),
             self_ref: Weak::default(),
             children: BTreeMap::new(),
+            dir_offsets: BTreeMap::new(),
+            next_dir_offset: DIR_COOKIE_FIRST,
             page_cache: None,
             metadata: Metadata {
                 dev_id: 0,
@@ -775,6 +927,7 @@ impl IndexNode for LockedTmpfsInode {
             fs: inode.fs.clone(),
             special_node: None,
             name: name.clone(),
+            xattrs: SimpleXattrs::new(),
         })));
 
         result.0.lock().self_ref = Arc::downgrade(&result);
@@ -795,7 +948,7 @@ impl IndexNode for LockedTmpfsInode {
             result.0.lock().page_cache = Some(pc);
         }
 
-        inode.children.insert(name, result.clone());
+        inode.insert_child(name, result.clone());
         if file_type == FileType::Dir {
             inode.metadata.nlinks += 1;
         }
@@ -821,9 +974,7 @@ impl IndexNode for LockedTmpfsInode {
             return Err(SystemError::EEXIST);
         }
 
-        inode
-            .children
-            .insert(name, other_locked.self_ref.upgrade().unwrap());
+        inode.insert_child(name, other_locked.self_ref.upgrade().unwrap());
         other_locked.metadata.nlinks += 1;
         Ok(())
     }
@@ -864,10 +1015,10 @@ impl IndexNode for LockedTmpfsInode {
         let should_free = deleted_guard.metadata.nlinks == 0;
         drop(deleted_guard);
 
-        inode.children.remove(&name);
+        inode.remove_child(&name);
 
         if should_free {
-            tmpfs.decrease_size(file_size);
+            tmpfs.free_inode(file_size);
         }
 
         Ok(())
@@ -908,11 +1059,11 @@ impl IndexNode for LockedTmpfsInode {
 
         drop(deleted_inode);
         to_delete.0.lock().metadata.nlinks -= 1;
-        inode.children.remove(&name);
+        inode.remove_child(&name);
         inode.metadata.nlinks -= 1;
 
-        // 减少文件系统使用的大小（目录通常大小为0）
-        tmpfs.decrease_size(dir_size);
+        // 归还inode计数和占用的大小（目录通常大小为0）
+        tmpfs.free_inode(dir_size);
 
         Ok(())
     }
@@ -984,12 +1135,17 @@ impl IndexNode for LockedTmpfsInode {
                 }
 
                 // Remove existing destination entry (replacement).
-                dir.children.remove(&new_key);
+                if let Some(replaced) = dir.remove_child(&new_key) {
+                    tmpfs_drop_link(&replaced);
+                    if old_type == FileType::Dir {
+                        dir.metadata.nlinks = dir.metadata.nlinks.saturating_sub(1);
+                    }
+                }
             }
 
             // Move entry within the same directory.
-            dir.children.remove(&old_key);
-            dir.children.insert(new_key.clone(), inode_to_move.clone());
+            dir.remove_child(&old_key);
+            dir.insert_child(new_key.clone(), inode_to_move.clone());
             inode_to_move.0.lock().name = new_key;
             return Ok(());
         }
@@ -1093,6 +1249,27 @@ impl IndexNode for LockedTmpfsInode {
         Ok(keys)
     }
 
+    fn list_cookies(&self) -> Result<Vec<DirCookie>, SystemError> {
+        let inode = self.0.lock();
+        if inode.metadata.file_type != FileType::Dir {
+            return Err(SystemError::ENOTDIR);
+        }
+
+        let mut entries = vec![
+            DirCookie::new(DIR_COOKIE_DOT, "."),
+            DirCookie::new(DIR_COOKIE_DOTDOT, ".."),
+        ];
+        let mut children: Vec<DirCookie> = inode
+            .dir_offsets
+            .iter()
+            .map(|(name, offset)| DirCookie::new(*offset, name.as_ref()))
+            .collect();
+        children.sort_unstable_by_key(|entry| entry.cookie);
+        entries.append(&mut children);
+
+        Ok(entries)
+    }
+
     fn mknod(
         &self,
         filename: &str,
@@ -1112,6 +1289,11 @@ impl IndexNode for LockedTmpfsInode {
         }
 
         let filename = DName::from(filename);
+        if inode.children.contains_key(&filename) {
+            return Err(SystemError::EEXIST);
+        }
+        let fs = inode.fs.upgrade().ok_or(SystemError::EIO)?;
+        fs.alloc_inode()?;
 
         // 确定文件类型
         let file_type = if mode.contains(InodeMode::S_IFIFO) {
@@ -1128,6 +1310,8 @@ impl IndexNode for LockedTmpfsInode {
             parent: inode.self_ref.clone(),
             self_ref: Weak::default(),
             children: BTreeMap::new(),
+            dir_offsets: BTreeMap::new(),
+            next_dir_offset: DIR_COOKIE_FIRST,
             page_cache: None,
             metadata: Metadata {
                 dev_id: 0,
@@ -1150,6 +1334,7 @@ impl IndexNode for LockedTmpfsInode {
             fs: inode.fs.clone(),
             special_node: None,
             name: filename.clone(),
+            xattrs: SimpleXattrs::new(),
         })));
 
         nod.0.lock().self_ref = Arc::downgrade(&nod);
@@ -1161,7 +1346,7 @@ impl IndexNode for LockedTmpfsInode {
             nod.0.lock().special_node = Some(SpecialNodeData::Pipe(pipe_inode));
         }
 
-        inode.children.insert(filename, nod.clone());
+        inode.insert_child(filename, nod.clone());
         Ok(nod)
     }
 
@@ -1185,4 +1370,21 @@ impl IndexNode for LockedTmpfsInode {
     fn page_cache(&self) -> Option<Arc<PageCache>> {
         self.0.lock().page_cache.clone()
     }
+
+    fn getxattr(&self, name: &str, buf: &mut [u8]) -> Result<usize, SystemError> {
+        self.0.lock().xattrs.get(name, buf)
+    }
+
+    fn setxattr(&self, name: &str, value: &[u8]) -> Result<usize, SystemError> {
+        self.0.lock().xattrs.set(name, value);
+        Ok(0)
+    }
+
+    fn listxattr(&self) -> Result<Vec<String>, SystemError> {
+        Ok(self.0.lock().xattrs.list())
+    }
+
+    fn removexattr(&self, name: &str) -> Result<(), SystemError> {
+        self.0.lock().xattrs.remove(name)
+    }
 }
@@ -0,0 +1,223 @@
+//! 文件之间的数据拷贝（copy_file_range）
+//!
+//! 数据在内核中拷贝，不经过用户态缓冲区，按下面的顺序选择拷贝方式：
+//! 1. 交给源文件所在的文件系统（[`IndexNode::copy_file_range`](super::IndexNode::copy_file_range)），支持reflink或者
+//!    服务器端拷贝的文件系统可以共享数据块，完全不复制数据；
+//! 2. 源文件有页缓存时，直接把页缓存中的页写入目标文件，每个字节只复制一次；
+//! 3. 其它情况（没有页缓存、O_DIRECT、源文件和目标文件是同一个inode）通过内核缓冲区读写。
+
+use alloc::{sync::Arc, vec};
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    filesystem::page_cache::PageCache,
+    mm::MemoryManagementArch,
+    process::{io_accounting::task_io_add_rchar, ProcessManager},
+};
+
+use super::{
+    file::{File, FileFlags, FileMode},
+    timestamps::file_accessed,
+    FileType, InodeFlags,
+};
+
+/// 一次调用最多拷贝的字节数，与Linux的MAX_RW_COUNT相同
+fn max_rw_count() -> usize {
+    (i32::MAX as usize) & !(MMArch::PAGE_SIZE - 1)
+}
+
+/// 每拷贝这么多字节检查一次信号，也是预读和内核缓冲区的大小
+const COPY_CHUNK_SIZE: usize = 128 * 1024;
+
+/// 检查两个文件能否进行copy_file_range（参考Linux generic_copy_file_checks）
+pub fn generic_copy_file_checks(in_file: &File, out_file: &File) -> Result<(), SystemError> {
+    let md_in = in_file.metadata()?;
+    let md_out = out_file.metadata()?;
+
+    if md_in.file_type == FileType::Dir || md_out.file_type == FileType::Dir {
+        return Err(SystemError::EISDIR);
+    }
+    // 只支持普通文件
+    if md_in.file_type != FileType::File || md_out.file_type != FileType::File {
+        return Err(SystemError::EINVAL);
+    }
+
+    if !in_file.mode().contains(FileMode::FMODE_READ)
+        || !out_file.mode().contains(FileMode::FMODE_WRITE)
+    {
+        return Err(SystemError::EBADF);
+    }
+    // 目标文件不能以O_APPEND打开
+    if out_file.flags().contains(FileFlags::O_APPEND) {
+        return Err(SystemError::EBADF);
+    }
+
+    let out_flags = out_file.get_inode_flags()?;
+    if out_flags.contains(InodeFlags::S_IMMUTABLE) {
+        return Err(SystemError::EPERM);
+    }
+    if out_flags.contains(InodeFlags::S_SWAPFILE) {
+        return Err(SystemError::ETXTBSY);
+    }
+    Ok(())
+}
+
+/// # 把`in_file`从`pos_in`开始的`len`字节拷贝到`out_file`的`pos_out`处
+///
+/// 不修改两个文件的文件偏移，调用者需要先用[`generic_copy_file_checks`]检查两个文件。
+///
+/// ## 返回值
+///
+/// - `Ok(usize)`: 拷贝的字节数。源文件在`pos_in`之后没有数据时为0；
+///   已经拷贝了部分数据之后出错或者收到信号时，返回已经拷贝的字节数
+/// - `Err(SystemError::EINVAL)`: 源文件和目标文件是同一个文件，并且两个范围重叠
+/// - `Err(SystemError::EOVERFLOW)`: 范围的结尾超出了文件偏移的表示范围
+pub fn vfs_copy_file_range(
+    in_file: &File,
+    pos_in: usize,
+    out_file: &File,
+    pos_out: usize,
+    len: usize,
+) -> Result<usize, SystemError> {
+    if pos_in
+        .checked_add(len)
+        .is_none_or(|end| end > i64::MAX as usize)
+        || pos_out
+            .checked_add(len)
+            .is_none_or(|end| end > i64::MAX as usize)
+    {
+        return Err(SystemError::EOVERFLOW);
+    }
+
+    let md_in = in_file.metadata()?;
+    let size_in = md_in.size.max(0) as usize;
+    if pos_in >= size_in {
+        return Ok(0);
+    }
+    let len = len.min(size_in - pos_in).min(max_rw_count());
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let md_out = out_file.metadata()?;
+    let same_inode = md_in.inode_id == md_out.inode_id && md_in.dev_id == md_out.dev_id;
+    if same_inode && pos_in < pos_out + len && pos_out < pos_in + len {
+        return Err(SystemError::EINVAL);
+    }
+
+    let in_inode = in_file.inode();
+    match in_inode.copy_file_range(pos_in, &out_file.inode(), pos_out, len) {
+        Ok(copied) => {
+            task_io_add_rchar(copied);
+            file_accessed(&in_inode, in_file.flags());
+            out_file.account_write(copied)?;
+            out_file.maybe_sync_after_write(out_file.flags(), out_file.get_inode_flags()?)?;
+            return Ok(copied);
+        }
+        Err(SystemError::EOPNOTSUPP_OR_ENOTSUP) | Err(SystemError::EXDEV) => {}
+        Err(e) => return Err(e),
+    }
+
+    // 同一个inode时，写入目标范围需要锁住的页可能正是读出来的那一页
+    let page_cache = in_inode
+        .page_cache()
+        .filter(|_| !same_inode && !in_file.flags().contains(FileFlags::O_DIRECT));
+    match page_cache {
+        Some(page_cache) => {
+            let copied =
+                copy_from_page_cache(in_file, &page_cache, pos_in, out_file, pos_out, len)?;
+            if copied > 0 {
+                task_io_add_rchar(copied);
+                file_accessed(&in_inode, in_file.flags());
+            }
+            Ok(copied)
+        }
+        None => copy_through_buffer(in_file, pos_in, out_file, pos_out, len),
+    }
+}
+
+/// 直接把源文件页缓存中的页写入目标文件
+fn copy_from_page_cache(
+    in_file: &File,
+    page_cache: &Arc<PageCache>,
+    pos_in: usize,
+    out_file: &File,
+    pos_out: usize,
+    len: usize,
+) -> Result<usize, SystemError> {
+    copy_in_chunks(len, |done, chunk, copied| {
+        let start = pos_in + done;
+        let end = start + chunk;
+        in_file.file_readahead(start, chunk)?;
+
+        let mut pos = start;
+        while pos < end {
+            let page_offset = pos & (MMArch::PAGE_SIZE - 1);
+            let n = (MMArch::PAGE_SIZE - page_offset).min(end - pos);
+            let page = page_cache
+                .manager()
+                .commit_page(pos >> MMArch::PAGE_SHIFT)?;
+            let written = {
+                let guard = page.read();
+                let data = unsafe { &guard.as_slice()[page_offset..page_offset + n] };
+                out_file.do_write(pos_out + (pos - pos_in), n, data, false, false)?
+            };
+            *copied += written;
+            if written < n {
+                return Ok(false);
+            }
+            pos += n;
+        }
+        Ok(true)
+    })
+}
+
+/// 通过内核缓冲区读写
+fn copy_through_buffer(
+    in_file: &File,
+    pos_in: usize,
+    out_file: &File,
+    pos_out: usize,
+    len: usize,
+) -> Result<usize, SystemError> {
+    let mut buf = vec![0u8; len.min(COPY_CHUNK_SIZE)];
+    copy_in_chunks(len, |done, chunk, copied| {
+        let read = in_file.do_read(pos_in + done, chunk, &mut buf[..chunk], false)?;
+        if read == 0 {
+            return Ok(false);
+        }
+        let written = out_file.do_write(pos_out + done, read, &buf[..read], false, false)?;
+        *copied += written;
+        Ok(written == chunk)
+    })
+}
+
+/// 按[`COPY_CHUNK_SIZE`]分块拷贝，每块之间检查信号
+///
+/// `copy_chunk(done, chunk, copied)`拷贝第`done`字节开始的`chunk`字节，边拷贝边累加`copied`，
+/// 返回`false`表示遇到了文件结尾或者短写。拷贝了部分数据之后出错，返回已经拷贝的字节数
+fn copy_in_chunks(
+    len: usize,
+    mut copy_chunk: impl FnMut(usize, usize, &mut usize) -> Result<bool, SystemError>,
+) -> Result<usize, SystemError> {
+    let mut copied = 0;
+    while copied < len {
+        if copied > 0 && signal_pending() {
+            break;
+        }
+        let chunk = (len - copied).min(COPY_CHUNK_SIZE);
+        match copy_chunk(copied, chunk, &mut copied) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) if copied == 0 => return Err(e),
+            Err(_) => break,
+        }
+    }
+    Ok(copied)
+}
+
+fn signal_pending() -> bool {
+    let pcb = ProcessManager::current_pcb();
+    pcb.has_pending_signal_fast() && pcb.has_pending_not_masked_signal()
+}
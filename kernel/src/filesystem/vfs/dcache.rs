@@ -0,0 +1,181 @@
+//! 目录项缓存（dcache）
+//!
+//! 路径查找的每一级都要调用文件系统的`find`，对于磁盘文件系统意味着重新读取并扫描目录。
+//! dcache以(文件系统, 父目录inode号, 名字)为键缓存查找的结果：
+//! - 正向项保存找到的inode；
+//! - 负向项记录名字不存在，避免反复查找不存在的文件（比如shell逐个搜索PATH）。
+//!
+//! 只有目录内容只会经过VFS改变的文件系统才使用dcache（[`FileSystem::dcache_enabled`]），
+//! 创建、删除、重命名由[`MountFSInode`](super::mount::MountFSInode)负责使对应的项失效。
+//! 缓存按LRU淘汰，总数不超过[`DCACHE_CAPACITY`]，内存紧张时由页面回收线程收缩。
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use lru::LruCache;
+use system_error::SystemError;
+
+use crate::libs::mutex::Mutex;
+
+use super::{FileSystem, IndexNode, InodeId};
+
+/// 缓存的目录项数量上限
+pub const DCACHE_CAPACITY: usize = 8192;
+
+lazy_static! {
+    static ref DCACHE: DentryCache = DentryCache::new();
+}
+
+/// 获取全局的目录项缓存
+pub fn dcache() -> &'static DentryCache {
+    &DCACHE
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct DentryKey {
+    /// 文件系统对象的地址，同一个文件系统的多个挂载共享缓存
+    fs: usize,
+    parent: InodeId,
+    name: String,
+}
+
+impl DentryKey {
+    fn new(fs: &Arc<dyn FileSystem>, parent: InodeId, name: &str) -> Self {
+        Self {
+            fs: fs_key(fs),
+            parent,
+            name: name.to_string(),
+        }
+    }
+}
+
+fn fs_key(fs: &Arc<dyn FileSystem>) -> usize {
+    Arc::as_ptr(fs) as *const () as usize
+}
+
+/// 缓存的查找结果，`None`表示负向项
+type Dentry = Option<Arc<dyn IndexNode>>;
+
+pub struct DentryCache {
+    inner: Mutex<LruCache<DentryKey, Dentry>>,
+    /// 每次失效时递增。向文件系统查找期间如果有项失效，查找的结果可能已经过时，不能放入缓存
+    generation: AtomicU64,
+}
+
+impl DentryCache {
+    fn new() -> Self {
+        Self {
+            inner: Mutex::new(LruCache::unbounded()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// 向文件系统查找之前获取，作为[`insert`](Self::insert)的参数
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// 查找缓存
+    ///
+    /// ## 返回值
+    /// - `None`: 没有缓存，需要向文件系统查找
+    /// - `Some(Ok(inode))`: 正向项
+    /// - `Some(Err(ENOENT))`: 负向项
+    pub fn lookup(
+        &self,
+        fs: &Arc<dyn FileSystem>,
+        parent: InodeId,
+        name: &str,
+    ) -> Option<Result<Arc<dyn IndexNode>, SystemError>> {
+        let key = DentryKey::new(fs, parent, name);
+        let mut inner = self.inner.lock();
+        let dentry = inner.get(&key)?;
+        Some(dentry.clone().ok_or(SystemError::ENOENT))
+    }
+
+    /// 记录一次查找的结果
+    ///
+    /// ## 参数
+    /// - `inode`: 找到的inode，`None`表示名字不存在
+    /// - `generation`: 查找之前调用[`generation`](Self::generation)得到的值
+    pub fn insert(
+        &self,
+        fs: &Arc<dyn FileSystem>,
+        parent: InodeId,
+        name: &str,
+        inode: Option<Arc<dyn IndexNode>>,
+        generation: u64,
+    ) {
+        let key = DentryKey::new(fs, parent, name);
+        let evicted = {
+            let mut inner = self.inner.lock();
+            if self.generation() != generation {
+                return;
+            }
+            let mut evicted: Vec<Dentry> = inner.put(key, inode).into_iter().collect();
+            while inner.len() > DCACHE_CAPACITY {
+                match inner.pop_lru() {
+                    Some((_, dentry)) => evicted.push(dentry),
+                    None => break,
+                }
+            }
+            evicted
+        };
+        // 释放inode可能触发文件系统的回收逻辑，不能持有缓存的锁
+        drop(evicted);
+    }
+
+    /// 使一个目录项失效，在目录中创建、删除或者重命名`name`之后调用
+    pub fn invalidate(&self, fs: &Arc<dyn FileSystem>, parent: InodeId, name: &str) {
+        let key = DentryKey::new(fs, parent, name);
+        let old = {
+            let mut inner = self.inner.lock();
+            self.generation.fetch_add(1, Ordering::AcqRel);
+            inner.pop(&key)
+        };
+        drop(old);
+    }
+
+    /// 使一个目录下的所有目录项失效，在目录被删除之后调用，避免inode号被重用后命中旧的项
+    pub fn invalidate_dir(&self, fs: &Arc<dyn FileSystem>, dir: InodeId) {
+        let fs = fs_key(fs);
+        self.remove_if(|key| key.fs == fs && key.parent == dir);
+    }
+
+    /// 使一个文件系统的所有目录项失效，在文件系统卸载时调用
+    pub fn invalidate_fs(&self, fs: &Arc<dyn FileSystem>) {
+        let fs = fs_key(fs);
+        self.remove_if(|key| key.fs == fs);
+    }
+
+    fn remove_if(&self, pred: impl Fn(&DentryKey) -> bool) {
+        let removed: Vec<Dentry> = {
+            let mut inner = self.inner.lock();
+            self.generation.fetch_add(1, Ordering::AcqRel);
+            let keys: Vec<DentryKey> = inner
+                .iter()
+                .filter(|(key, _)| pred(key))
+                .map(|(key, _)| key.clone())
+                .collect();
+            keys.iter().filter_map(|key| inner.pop(key)).collect()
+        };
+        drop(removed);
+    }
+
+    /// 淘汰最久未使用的`count`个目录项，返回实际淘汰的数量
+    ///
+    /// 内存紧张时由页面回收线程调用
+    pub fn shrink(&self, count: usize) -> usize {
+        let evicted: Vec<Dentry> = {
+            let mut inner = self.inner.lock();
+            (0..count)
+                .map_while(|_| inner.pop_lru().map(|(_, d)| d))
+                .collect()
+        };
+        evicted.len()
+    }
+}
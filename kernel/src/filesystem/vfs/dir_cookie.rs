@@ -0,0 +1,77 @@
+//! 目录读取位置（cookie）
+//!
+//! getdents返回的d_off、telldir/seekdir，以及通过NFS、9p导出目录时使用的位置都是cookie。
+//! 与目录项在列表中的下标不同，cookie在目录被修改之后仍然指向原来的位置：
+//! 从某个cookie继续读取，不会重复或者跳过期间没有被修改的目录项。
+//!
+//! - 0表示目录的开头，"."和".."固定为1和2
+//! - 文件系统可以通过[`IndexNode::list_cookies`](super::IndexNode::list_cookies)提供自己的cookie
+//!   （比如tmpfs按照目录项加入的先后分配），默认使用目录项名字的哈希
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// "."的cookie
+pub const DIR_COOKIE_DOT: u64 = 1;
+/// ".."的cookie
+pub const DIR_COOKIE_DOTDOT: u64 = 2;
+/// 普通目录项的cookie从这里开始
+pub const DIR_COOKIE_FIRST: u64 = 3;
+/// cookie的上限（不含）。读取完一项之后文件位置变为cookie+1，必须能放进off_t
+pub const DIR_COOKIE_END: u64 = i64::MAX as u64;
+
+/// 一个目录项及其cookie
+#[derive(Debug, Clone)]
+pub struct DirCookie {
+    pub cookie: u64,
+    pub name: String,
+}
+
+impl DirCookie {
+    pub fn new(cookie: u64, name: &str) -> Self {
+        Self {
+            cookie,
+            name: name.to_string(),
+        }
+    }
+}
+
+/// 根据名字计算cookie（FNV-1a），结果落在`[DIR_COOKIE_FIRST, DIR_COOKIE_END)`中
+pub fn name_hash_cookie(name: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in name.bytes() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    DIR_COOKIE_FIRST + hash % (DIR_COOKIE_END - DIR_COOKIE_FIRST)
+}
+
+/// 为`list()`返回的名字分配基于哈希的cookie，按cookie从小到大排序
+///
+/// 哈希冲突的项按名字排序后依次使用下一个cookie，因此只有在冲突的项之间插入或删除时，
+/// 位置才可能移动
+pub fn hash_cookies(names: Vec<String>) -> Vec<DirCookie> {
+    let mut entries: Vec<DirCookie> = names
+        .into_iter()
+        .map(|name| {
+            let cookie = match name.as_str() {
+                "." => DIR_COOKIE_DOT,
+                ".." => DIR_COOKIE_DOTDOT,
+                _ => name_hash_cookie(&name),
+            };
+            DirCookie { cookie, name }
+        })
+        .collect();
+    entries.sort_unstable_by(|a, b| a.cookie.cmp(&b.cookie).then_with(|| a.name.cmp(&b.name)));
+
+    let mut last = 0;
+    for entry in entries.iter_mut() {
+        if entry.cookie <= last {
+            entry.cookie = last + 1;
+        }
+        last = entry.cookie;
+    }
+    entries
+}
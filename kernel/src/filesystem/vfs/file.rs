@@ -8,8 +8,11 @@ use log::error;
 use system_error::SystemError;
 
 use super::{
-    append_lock::with_inode_append_lock, mount::MountFSInode, utils::should_remove_sgid, FileType,
-    IndexNode, InodeId, Metadata, SpecialNodeData,
+    append_lock::with_inode_append_lock,
+    mount::{MountFSInode, MountRef},
+    timestamps::file_accessed,
+    utils::should_remove_sgid,
+    FileType, IndexNode, InodeId, Metadata, SpecialNodeData,
 };
 use crate::{arch::ipc::signal::Signal, filesystem::vfs::InodeFlags, process::pid::PidPrivateData};
 use crate::{
@@ -28,22 +31,27 @@ use crate::{
     ipc::{kill::send_signal_to_pid, pipe::PipeFsPrivateData},
     libs::{casting::DowncastArc, mutex::Mutex, rwsem::RwSem},
     mm::{
+        gup::PinnedUserPages,
         page::PageFlags,
         readahead::{page_cache_async_readahead, page_cache_sync_readahead, FileReadaheadState},
+        writeback::balance_dirty_pages,
         MemoryManagementArch,
     },
     process::{
         cred::{CAPFlags, Cred},
+        io_accounting::{task_io_add_rchar, task_io_add_wchar},
         namespace::{
             cgroup_namespace::CgroupNamespace, ipc_namespace::IpcNamespace, mnt::MntNamespace,
             net_namespace::NetNamespace, pid_namespace::PidNamespace,
-            user_namespace::UserNamespace, uts_namespace::UtsNamespace,
+            time_namespace::TimeNamespace, user_namespace::UserNamespace,
+            uts_namespace::UtsNamespace,
         },
         resource::RLimitID,
         ProcessControlBlock, ProcessManager, RawPid,
     },
 };
 
+use crate::filesystem::notify::fsnotify_modify;
 use crate::filesystem::vfs::InodeMode;
 
 const MAX_LFS_FILESIZE: i64 = i64::MAX;
@@ -77,6 +85,30 @@ enum OffsetUpdate {
     StoreEnd,
 }
 
+/// 读操作的目标缓冲区
+enum ReadBuf<'a> {
+    Kernel(&'a mut [u8]),
+    /// 固定住的用户缓冲区，O_DIRECT时设备直接写入
+    User(&'a PinnedUserPages),
+}
+
+/// 写操作的数据来源
+#[derive(Clone, Copy)]
+enum WriteBuf<'a> {
+    Kernel(&'a [u8]),
+    /// 固定住的用户缓冲区，O_DIRECT时设备直接读取
+    User(&'a PinnedUserPages),
+}
+
+impl WriteBuf<'_> {
+    fn len(&self) -> usize {
+        match self {
+            WriteBuf::Kernel(buf) => buf.len(),
+            WriteBuf::User(pages) => pages.len(),
+        }
+    }
+}
+
 /// 写操作的配置参数
 #[derive(Clone, Copy)]
 struct WriteConfig {
@@ -104,7 +136,8 @@ pub enum NamespaceFilePrivateData {
     User(Arc<UserNamespace>),
     /// Cgroup namespace.
     Cgroup(Arc<CgroupNamespace>),
-    // Time namespace is not implemented yet.
+    /// Time namespace (both `time` and `time_for_children`).
+    Time(Arc<TimeNamespace>),
 }
 
 impl fmt::Debug for NamespaceFilePrivateData {
@@ -122,6 +155,7 @@ impl fmt::Debug for NamespaceFilePrivateData {
             NamespaceFilePrivateData::Cgroup(_) => {
                 f.write_str("NamespaceFilePrivateData::Cgroup(..)")
             }
+            NamespaceFilePrivateData::Time(_) => f.write_str("NamespaceFilePrivateData::Time(..)"),
         }
     }
 }
@@ -416,8 +450,6 @@ pub struct File {
     mode: RwSem<FileMode>,
     /// 文件类型
     file_type: FileType,
-    /// readdir时候用的，暂存的本次循环中，所有子目录项的名字的数组
-    readdir_subdirs_name: Mutex<Vec<String>>,
     pub private_data: Mutex<FilePrivateData>,
     /// 文件的凭证
     cred: Arc<Cred>,
@@ -428,6 +460,8 @@ pub struct File {
     posix_lock_key: (usize, InodeId),
     /// 预读状态
     ra_state: Mutex<FileReadaheadState>,
+    /// 固定文件所在的挂载，放在最后，保证先关闭文件再释放对挂载的引用
+    mount_ref: MountRef,
 }
 
 impl File {
@@ -491,7 +525,7 @@ impl File {
         Ok(len.min(limit.saturating_sub(offset)))
     }
 
-    fn maybe_sync_after_write(
+    pub(super) fn maybe_sync_after_write(
         &self,
         flags: FileFlags,
         inode_flags: InodeFlags,
@@ -521,16 +555,42 @@ impl File {
         &self,
         actual_offset: usize,
         actual_len: usize,
-        buf: &[u8],
+        buf: WriteBuf,
         config: WriteConfig,
     ) -> Result<usize, SystemError> {
-        let written_len =
-            self.inode
-                .write_at(actual_offset, actual_len, buf, self.private_data.lock())?;
+        let direct = self.check_direct_io(config.flags, actual_offset, actual_len)?;
+        let written_len = if direct {
+            // 先写回并丢弃范围内的页，写入之后再丢弃一次，避免之后的缓冲读读到旧数据
+            self.sync_page_cache_range(actual_offset, actual_len, true)?;
+            let r = match buf {
+                WriteBuf::Kernel(buf) => self.inode.write_direct(actual_offset, actual_len, buf),
+                WriteBuf::User(pages) => self
+                    .inode
+                    .write_direct_user(actual_offset, &pages.slice(0, actual_len)),
+            }?;
+            self.sync_page_cache_range(actual_offset, actual_len, false)?;
+            r
+        } else {
+            match buf {
+                WriteBuf::Kernel(buf) => {
+                    self.inode
+                        .write_at(actual_offset, actual_len, buf, self.private_data.lock())?
+                }
+                // 固定用户页之后O_DIRECT被清除了，经过内核缓冲区写入页缓存
+                WriteBuf::User(pages) => {
+                    let mut data = vec![0u8; actual_len];
+                    pages.copy_to_slice(&mut data);
+                    self.inode.write_at(
+                        actual_offset,
+                        actual_len,
+                        &data,
+                        self.private_data.lock(),
+                    )?
+                }
+            }
+        };
 
-        if written_len > 0 {
-            self.maybe_kill_suid_sgid_after_write()?;
-        }
+        self.account_write(written_len)?;
 
         if config.update_offset {
             match config.offset_update {
@@ -548,8 +608,114 @@ impl File {
         }
 
         self.maybe_sync_after_write(config.flags, config.inode_flags)?;
+        if !direct && written_len > 0 {
+            balance_dirty_pages();
+        }
         Ok(written_len)
     }
+
+    /// # 数据写入inode之后的记账
+    ///
+    /// 统计写入的字节数，清除setuid/setgid位，并发出修改通知。
+    /// 绕过[`do_write`](Self::do_write)直接修改文件数据的路径（比如copy_file_range）也要调用
+    pub(super) fn account_write(&self, written_len: usize) -> Result<(), SystemError> {
+        if written_len > 0 {
+            task_io_add_wchar(written_len);
+            self.maybe_kill_suid_sgid_after_write()?;
+            fsnotify_modify(&self.inode);
+        }
+        Ok(())
+    }
+
+    /// 本次读写是否按O_DIRECT处理，返回对齐要求
+    fn direct_io_alignment(&self, flags: FileFlags) -> Option<usize> {
+        if !flags.contains(FileFlags::O_DIRECT)
+            || !self.mode().contains(FileMode::FMODE_CAN_ODIRECT)
+        {
+            return None;
+        }
+        self.inode.direct_io_alignment()
+    }
+
+    /// # 判断本次读写是否使用直接I/O
+    ///
+    /// 用户缓冲区的地址由系统调用层通过[`File::check_direct_io_buffer`]检查，
+    /// 之后固定住用户页交给这里；内核自身发起的读写使用内核缓冲区
+    ///
+    /// ## 参数
+    /// - `flags`: 文件标志
+    /// - `offset`: 文件内的偏移量
+    /// - `len`: 读写的长度
+    ///
+    /// ## 返回值
+    /// - `Ok(true)`: 使用直接I/O
+    /// - `Ok(false)`: 没有设置O_DIRECT，或者inode不支持直接I/O，使用普通的读写
+    /// - `Err(SystemError::EINVAL)`: 偏移量或者长度不满足inode的对齐要求
+    fn check_direct_io(
+        &self,
+        flags: FileFlags,
+        offset: usize,
+        len: usize,
+    ) -> Result<bool, SystemError> {
+        let Some(align) = self.direct_io_alignment(flags) else {
+            return Ok(false);
+        };
+        if (offset | len) & (align - 1) != 0 {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(true)
+    }
+
+    /// # 本次读写是否使用直接I/O
+    ///
+    /// 为true时，系统调用层固定住用户缓冲区，通过[`read_pinned`](Self::read_pinned)等接口
+    /// 让设备直接读写用户页
+    pub fn is_direct_io(&self) -> bool {
+        self.direct_io_alignment(self.flags()).is_some()
+    }
+
+    /// # 检查O_DIRECT读写的用户缓冲区地址是否对齐
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EINVAL)`: 文件使用直接I/O，但是地址不满足inode的对齐要求
+    pub fn check_direct_io_buffer(&self, addr: usize) -> Result<(), SystemError> {
+        match self.direct_io_alignment(self.flags()) {
+            Some(align) if addr & (align - 1) != 0 => Err(SystemError::EINVAL),
+            _ => Ok(()),
+        }
+    }
+
+    /// # 让页缓存与直接I/O保持一致
+    ///
+    /// 把`[offset, offset + len)`范围内的脏页写回，再丢弃其中干净的页。
+    ///
+    /// ## 参数
+    /// - `writeback`: 是否先写回脏页。直接写入之后调用时为false，此时范围内的页已经写回过
+    fn sync_page_cache_range(
+        &self,
+        offset: usize,
+        len: usize,
+        writeback: bool,
+    ) -> Result<(), SystemError> {
+        let Some(page_cache) = self.inode.page_cache() else {
+            return Ok(());
+        };
+        if len == 0 {
+            return Ok(());
+        }
+        let start_index = offset >> MMArch::PAGE_SHIFT;
+        let end_index = (offset + len - 1) >> MMArch::PAGE_SHIFT;
+        if writeback {
+            page_cache
+                .manager()
+                .writeback_range(start_index, end_index)?;
+        }
+        page_cache
+            .manager()
+            .invalidate_range(start_index, end_index)?;
+        Ok(())
+    }
+
     /// @brief 创建一个新的文件对象
     ///
     /// @param inode 文件对象对应的inode
@@ -567,6 +733,7 @@ impl File {
         mut flags: FileFlags,
         private_data_init: FilePrivateData,
     ) -> Result<Self, SystemError> {
+        let mount_ref = MountRef::new(&inode);
         let mut inode = inode;
         let file_type = inode.metadata()?.file_type;
         // 检查是否为命名管道（FIFO）
@@ -621,6 +788,9 @@ impl File {
         if inode.supports_pwrite() {
             mode.insert(FileMode::FMODE_PWRITE);
         }
+        if inode.direct_io_alignment().is_some() {
+            mode.insert(FileMode::FMODE_CAN_ODIRECT);
+        }
 
         // TODO: 检查inode是否有read/write方法,设置FMODE_CAN_READ/WRITE
         // 这需要在IndexNode trait中添加相应的检查方法
@@ -646,12 +816,12 @@ impl File {
             flags: RwSem::new(flags),
             mode: RwSem::new(mode),
             file_type,
-            readdir_subdirs_name: Mutex::new(Vec::default()),
             private_data,
             cred: ProcessManager::current_pcb().cred(),
             pid: Mutex::new(None),
             posix_lock_key,
             ra_state: Mutex::new(FileReadaheadState::new()),
+            mount_ref,
         };
 
         return Ok(f);
@@ -693,6 +863,22 @@ impl File {
         )
     }
 
+    /// ## 从当前偏移量读取数据到固定住的用户缓冲区（O_DIRECT），读取`pages.len()`个字节
+    ///
+    /// 文件使用直接I/O时设备直接写入用户页，否则经过内核缓冲区读取
+    ///
+    /// ### 返回值
+    /// - `Ok(usize)`: 成功读取的字节数
+    /// - `Err(SystemError)`: 错误码
+    pub fn read_pinned(&self, pages: &PinnedUserPages) -> Result<usize, SystemError> {
+        self.do_read_buf(
+            self.offset.load(core::sync::atomic::Ordering::SeqCst),
+            pages.len(),
+            ReadBuf::User(pages),
+            true,
+        )
+    }
+
     /// ## 从buffer向文件写入指定的字节数的数据
     ///
     /// ### 参数
@@ -713,6 +899,23 @@ impl File {
         )
     }
 
+    /// ## 把固定住的用户缓冲区写入文件的当前偏移量处（O_DIRECT），写入`pages.len()`个字节
+    ///
+    /// 文件使用直接I/O时设备直接读取用户页，否则经过内核缓冲区写入
+    ///
+    /// ### 返回值
+    /// - `Ok(usize)`: 成功写入的字节数
+    /// - `Err(SystemError)`: 错误码
+    pub fn write_pinned(&self, pages: &PinnedUserPages) -> Result<usize, SystemError> {
+        self.do_write_buf(
+            self.offset.load(core::sync::atomic::Ordering::SeqCst),
+            pages.len(),
+            WriteBuf::User(pages),
+            true,
+            false,
+        )
+    }
+
     /// ## 从文件中指定的偏移处读取指定的字节数到buf中
     ///
     /// ### 参数
@@ -723,6 +926,25 @@ impl File {
     /// ### 返回值
     /// - `Ok(usize)`: 成功读取的字节数
     pub fn pread(&self, offset: usize, len: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        self.check_pread()?;
+        self.do_read(offset, len, buf, false)
+    }
+
+    /// ## 从文件中指定的偏移处读取数据到固定住的用户缓冲区（O_DIRECT），读取`pages.len()`个字节
+    ///
+    /// ### 返回值
+    /// - `Ok(usize)`: 成功读取的字节数
+    pub fn pread_pinned(
+        &self,
+        offset: usize,
+        pages: &PinnedUserPages,
+    ) -> Result<usize, SystemError> {
+        self.check_pread()?;
+        self.do_read_buf(offset, pages.len(), ReadBuf::User(pages), false)
+    }
+
+    /// pread的模式检查
+    fn check_pread(&self) -> Result<(), SystemError> {
         // Linux 语义：O_PATH fd 任何 I/O 都应返回 EBADF（优先于 ESPIPE）。
         let mode = *self.mode.read();
         if mode.contains(FileMode::FMODE_PATH) {
@@ -750,8 +972,7 @@ impl File {
         if !mode.can_read() {
             return Err(SystemError::EINVAL);
         }
-
-        self.do_read(offset, len, buf, false)
+        Ok(())
     }
 
     /// ## 从buf向文件中指定的偏移处写入指定的字节数的数据
@@ -764,6 +985,25 @@ impl File {
     /// ### 返回值
     /// - `Ok(usize)`: 成功写入的字节数
     pub fn pwrite(&self, offset: usize, len: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        self.check_pwrite()?;
+        self.do_write(offset, len, buf, false, false)
+    }
+
+    /// ## 把固定住的用户缓冲区写入文件中指定的偏移处（O_DIRECT），写入`pages.len()`个字节
+    ///
+    /// ### 返回值
+    /// - `Ok(usize)`: 成功写入的字节数
+    pub fn pwrite_pinned(
+        &self,
+        offset: usize,
+        pages: &PinnedUserPages,
+    ) -> Result<usize, SystemError> {
+        self.check_pwrite()?;
+        self.do_write_buf(offset, pages.len(), WriteBuf::User(pages), false, false)
+    }
+
+    /// pwrite的模式检查
+    fn check_pwrite(&self) -> Result<(), SystemError> {
         // Linux 语义：O_PATH fd 任何 I/O 都应返回 EBADF（优先于 ESPIPE）。
         let mode = *self.mode.read();
         if mode.contains(FileMode::FMODE_PATH) {
@@ -784,8 +1024,7 @@ impl File {
         if !mode.contains(FileMode::FMODE_WRITE) {
             return Err(SystemError::EBADF);
         }
-
-        self.do_write(offset, len, buf, false, false)
+        Ok(())
     }
 
     /// 强制追加写（Linux `RWF_APPEND`/`IOCB_APPEND` 语义）：
@@ -817,7 +1056,7 @@ impl File {
         self.do_write(offset, len, buf, false, true)
     }
 
-    fn file_readahead(&self, offset: usize, len: usize) -> Result<(), SystemError> {
+    pub(super) fn file_readahead(&self, offset: usize, len: usize) -> Result<(), SystemError> {
         if self.mode().contains(FileMode::FMODE_RANDOM) {
             return Ok(());
         }
@@ -878,32 +1117,71 @@ impl File {
         len: usize,
         buf: &mut [u8],
         update_offset: bool,
+    ) -> Result<usize, SystemError> {
+        self.do_read_buf(offset, len, ReadBuf::Kernel(buf), update_offset)
+    }
+
+    fn do_read_buf(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: ReadBuf,
+        update_offset: bool,
     ) -> Result<usize, SystemError> {
         self.readable()?;
         // Linux/POSIX: count==0 must not touch the buffer and must not block.
         if len == 0 {
             return Ok(0);
         }
-        if buf.len() < len {
+        let buf_len = match &buf {
+            ReadBuf::Kernel(buf) => buf.len(),
+            ReadBuf::User(pages) => pages.len(),
+        };
+        if buf_len < len {
             return Err(SystemError::ENOBUFS);
         }
 
-        if self.file_type == FileType::File && !self.flags().contains(FileFlags::O_DIRECT) {
+        let direct = self.check_direct_io(self.flags(), offset, len)?;
+        if self.file_type == FileType::File && !direct {
             self.file_readahead(offset, len)?;
         }
 
-        let len = if self.flags().contains(FileFlags::O_DIRECT) {
-            self.inode
-                .read_direct(offset, len, buf, self.private_data.lock())
-        } else {
-            self.inode
-                .read_at(offset, len, buf, self.private_data.lock())
-        }?;
+        let len = match buf {
+            ReadBuf::Kernel(buf) if direct => {
+                // 页缓存中的脏数据还没有写到设备上
+                self.sync_page_cache_range(offset, len, true)?;
+                self.inode.read_direct(offset, len, buf)?
+            }
+            ReadBuf::Kernel(buf) => {
+                self.inode
+                    .read_at(offset, len, buf, self.private_data.lock())?
+            }
+            ReadBuf::User(pages) => {
+                let pages = pages.slice(0, len);
+                let len = if direct {
+                    self.sync_page_cache_range(offset, len, true)?;
+                    self.inode.read_direct_user(offset, &pages)?
+                } else {
+                    // 固定用户页之后O_DIRECT被清除了，经过内核缓冲区从页缓存读取
+                    let mut data = vec![0u8; len];
+                    let len =
+                        self.inode
+                            .read_at(offset, len, &mut data, self.private_data.lock())?;
+                    pages.copy_from_slice(&data[..len]);
+                    len
+                };
+                // 写入用户页时没有经过页表，文件映射的页需要标记为脏页
+                pages.slice(0, len).mark_dirty();
+                len
+            }
+        };
 
         if len > 0 {
+            task_io_add_rchar(len);
             let last_page_readed = (offset + len - 1) >> MMArch::PAGE_SHIFT;
             self.ra_state.lock().prev_index = last_page_readed as i64;
         }
+        file_accessed(&self.inode, self.flags());
 
         if update_offset {
             self.offset
@@ -919,6 +1197,23 @@ impl File {
         buf: &[u8],
         update_offset: bool,
         force_append: bool,
+    ) -> Result<usize, SystemError> {
+        self.do_write_buf(
+            offset,
+            len,
+            WriteBuf::Kernel(buf),
+            update_offset,
+            force_append,
+        )
+    }
+
+    fn do_write_buf(
+        &self,
+        offset: usize,
+        len: usize,
+        buf: WriteBuf,
+        update_offset: bool,
+        force_append: bool,
     ) -> Result<usize, SystemError> {
         self.writeable()?;
 
@@ -1107,32 +1402,21 @@ impl File {
         }
 
         let inode: &Arc<dyn IndexNode> = &self.inode;
-        let mut current_pos = self.offset.load(Ordering::SeqCst);
-
-        // POSIX 标准要求readdir应该返回. 和 ..
-        // 但是观察到在现有的子目录中已经包含，不做处理也能正常返回. 和 .. 这里先不做处理
-
-        // 迭代读取目录项
-        // 为了保证在目录内容动态变化（例如 /proc/self/fd）时不会因为重新
-        // 创建列表而丢失尚未读取的目录项，这里缓存第一次生成的列表，在
-        // 文件偏移被 seek 到 0 之前复用该缓存。
-        let mut cached_names = self.readdir_subdirs_name.lock();
-        if current_pos == 0 || cached_names.is_empty() {
-            *cached_names = inode.list()?;
-        }
-        let readdir_subdirs_name = cached_names.clone();
-        drop(cached_names);
-
-        let subdirs_name_len = readdir_subdirs_name.len();
-        while current_pos < subdirs_name_len {
-            let name = &readdir_subdirs_name[current_pos];
-            let sub_inode: Arc<dyn IndexNode> = match inode.find(name) {
+
+        // 文件偏移是下一个要读取的目录项的cookie，目录在两次读取之间被修改时，
+        // 从这里继续不会重复或者跳过没有被修改的目录项。参见dir_cookie
+        let entries = inode.list_cookies()?;
+        let pos = self.offset.load(Ordering::SeqCst) as u64;
+        let start = entries.partition_point(|entry| entry.cookie < pos);
+
+        for entry in &entries[start..] {
+            let next_pos = (entry.cookie + 1) as usize;
+            let sub_inode: Arc<dyn IndexNode> = match inode.find(&entry.name) {
                 Ok(i) => i,
                 Err(e) => {
                     if e == SystemError::ENOENT {
                         // 目录项在本次读取过程中被移除，跳过它，继续读取后续条目
-                        self.offset.fetch_add(1, Ordering::SeqCst);
-                        current_pos += 1;
+                        self.offset.store(next_pos, Ordering::SeqCst);
                         continue;
                     }
                     error!("Readdir error: Failed to find sub inode");
@@ -1143,12 +1427,13 @@ impl File {
             let inode_metadata = sub_inode.metadata().unwrap();
             let entry_ino = inode_metadata.inode_id.into() as u64;
             let entry_d_type = inode_metadata.file_type.get_file_type_num() as u8;
-            match ctx.fill_dir(name, current_pos, entry_ino, entry_d_type) {
+            // d_off是读取完这一项之后的位置
+            match ctx.fill_dir(&entry.name, next_pos, entry_ino, entry_d_type) {
                 Ok(_) => {
-                    self.offset.fetch_add(1, Ordering::SeqCst);
-                    current_pos += 1;
+                    self.offset.store(next_pos, Ordering::SeqCst);
                 }
                 Err(SystemError::EINVAL) => {
+                    file_accessed(inode, self.flags());
                     return Ok(());
                 }
                 Err(e) => {
@@ -1157,6 +1442,7 @@ impl File {
                 }
             }
         }
+        file_accessed(inode, self.flags());
         return Ok(());
     }
 
@@ -1175,12 +1461,12 @@ impl File {
             flags: RwSem::new(self.flags()),
             mode: RwSem::new(self.mode()),
             file_type: self.file_type,
-            readdir_subdirs_name: Mutex::new(self.readdir_subdirs_name.lock().clone()),
             private_data: Mutex::new(self.private_data.lock().clone()),
             cred: self.cred.clone(),
             pid: Mutex::new(None),
             posix_lock_key: self.posix_lock_key,
             ra_state: Mutex::new(self.ra_state.lock().clone()),
+            mount_ref: self.mount_ref.clone(),
         };
         // 调用inode的open方法，让inode知道有新的文件打开了这个inode
         // TODO: reopen is not a good idea for some inodes, need a better design
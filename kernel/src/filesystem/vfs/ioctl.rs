@@ -0,0 +1,169 @@
+//! ioctl命令号的编解码，以及ioctl参数在用户态与内核态之间的拷贝
+//!
+//! 命令号的布局参考 Linux include/uapi/asm-generic/ioctl.h：
+//!
+//! ```text
+//!  31    30 29            16 15       8 7        0
+//! +--------+----------------+----------+----------+
+//! |  dir   |      size      |   type   |    nr    |
+//! +--------+----------------+----------+----------+
+//! ```
+//!
+//! 驱动应当通过[`IoctlArg`]读写ioctl的参数结构体，而不是各自构造`UserBufferReader`/
+//! `UserBufferWriter`：前者会检查空指针，并在命令号编码了参数大小时校验其与结构体大小一致。
+
+use core::mem::size_of;
+
+use system_error::SystemError;
+
+use crate::syscall::user_access::{UserBufferReader, UserBufferWriter};
+
+pub const IOC_NRBITS: u32 = 8;
+pub const IOC_TYPEBITS: u32 = 8;
+pub const IOC_SIZEBITS: u32 = 14;
+pub const IOC_DIRBITS: u32 = 2;
+
+pub const IOC_NRMASK: u32 = (1 << IOC_NRBITS) - 1;
+pub const IOC_TYPEMASK: u32 = (1 << IOC_TYPEBITS) - 1;
+pub const IOC_SIZEMASK: u32 = (1 << IOC_SIZEBITS) - 1;
+pub const IOC_DIRMASK: u32 = (1 << IOC_DIRBITS) - 1;
+
+pub const IOC_NRSHIFT: u32 = 0;
+pub const IOC_TYPESHIFT: u32 = IOC_NRSHIFT + IOC_NRBITS;
+pub const IOC_SIZESHIFT: u32 = IOC_TYPESHIFT + IOC_TYPEBITS;
+pub const IOC_DIRSHIFT: u32 = IOC_SIZESHIFT + IOC_SIZEBITS;
+
+/// 没有参数
+pub const IOC_NONE: u32 = 0;
+/// 用户态向内核写入参数
+pub const IOC_WRITE: u32 = 1;
+/// 内核向用户态写回参数
+pub const IOC_READ: u32 = 2;
+
+/// 构造ioctl命令号，对应Linux的`_IOC`
+pub const fn ioc(dir: u32, ty: u8, nr: u8, size: usize) -> u32 {
+    (dir << IOC_DIRSHIFT)
+        | ((ty as u32) << IOC_TYPESHIFT)
+        | ((nr as u32) << IOC_NRSHIFT)
+        | (((size as u32) & IOC_SIZEMASK) << IOC_SIZESHIFT)
+}
+
+/// 对应Linux的`_IO`
+pub const fn io(ty: u8, nr: u8) -> u32 {
+    ioc(IOC_NONE, ty, nr, 0)
+}
+
+/// 对应Linux的`_IOR`
+pub const fn ior<T>(ty: u8, nr: u8) -> u32 {
+    ioc(IOC_READ, ty, nr, size_of::<T>())
+}
+
+/// 对应Linux的`_IOW`
+pub const fn iow<T>(ty: u8, nr: u8) -> u32 {
+    ioc(IOC_WRITE, ty, nr, size_of::<T>())
+}
+
+/// 对应Linux的`_IOWR`
+pub const fn iowr<T>(ty: u8, nr: u8) -> u32 {
+    ioc(IOC_READ | IOC_WRITE, ty, nr, size_of::<T>())
+}
+
+/// 解码后的ioctl命令号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoctlCmd(u32);
+
+impl IoctlCmd {
+    pub const fn new(cmd: u32) -> Self {
+        Self(cmd)
+    }
+
+    #[inline]
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+
+    /// 参数的传输方向，为`IOC_NONE`/`IOC_READ`/`IOC_WRITE`的组合
+    #[inline]
+    pub const fn dir(&self) -> u32 {
+        (self.0 >> IOC_DIRSHIFT) & IOC_DIRMASK
+    }
+
+    #[inline]
+    pub const fn ty(&self) -> u8 {
+        ((self.0 >> IOC_TYPESHIFT) & IOC_TYPEMASK) as u8
+    }
+
+    #[inline]
+    pub const fn nr(&self) -> u8 {
+        ((self.0 >> IOC_NRSHIFT) & IOC_NRMASK) as u8
+    }
+
+    /// 命令号中编码的参数大小。旧式的命令号（如`0x5401`）没有编码参数信息，此时返回0
+    #[inline]
+    pub const fn size(&self) -> usize {
+        ((self.0 >> IOC_SIZESHIFT) & IOC_SIZEMASK) as usize
+    }
+}
+
+impl From<u32> for IoctlCmd {
+    fn from(cmd: u32) -> Self {
+        Self::new(cmd)
+    }
+}
+
+/// ioctl的命令号及其参数
+#[derive(Debug, Clone, Copy)]
+pub struct IoctlArg {
+    cmd: IoctlCmd,
+    arg: usize,
+}
+
+impl IoctlArg {
+    pub const fn new(cmd: u32, arg: usize) -> Self {
+        Self {
+            cmd: IoctlCmd::new(cmd),
+            arg,
+        }
+    }
+
+    #[inline]
+    pub const fn cmd(&self) -> IoctlCmd {
+        self.cmd
+    }
+
+    /// 原始的参数值（对于按值传递参数的命令）
+    #[inline]
+    pub const fn value(&self) -> usize {
+        self.arg
+    }
+
+    /// 从用户态读取参数结构体
+    ///
+    /// ## 错误
+    ///
+    /// - `EFAULT`: 参数指针为空或不可访问
+    /// - `EINVAL`: 命令号中编码的参数大小与`T`不一致
+    pub fn read<T: Copy>(&self) -> Result<T, SystemError> {
+        self.check::<T>()?;
+        let reader = UserBufferReader::new(self.arg as *const T, size_of::<T>(), true)?;
+        reader.buffer_protected(0)?.read_one::<T>(0)
+    }
+
+    /// 将参数结构体写回用户态，错误同[`IoctlArg::read`]
+    pub fn write<T: Copy>(&self, val: &T) -> Result<(), SystemError> {
+        self.check::<T>()?;
+        let mut writer = UserBufferWriter::new(self.arg as *mut T, size_of::<T>(), true)?;
+        writer.buffer_protected(0)?.write_one(0, val)
+    }
+
+    fn check<T>(&self) -> Result<(), SystemError> {
+        if self.arg == 0 {
+            return Err(SystemError::EFAULT);
+        }
+        let size = self.cmd.size();
+        if size != 0 && size != size_of::<T>() {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+//! 内核使用的文件接口
+//!
+//! 回环设备、固件加载等内核子系统需要按路径打开并读写文件，但不应当占用调用者进程的
+//! 文件描述符。[`KernelFile`]包装了一个打开的[`File`]，提供与read(2)/write(2)/lseek(2)/
+//! fsync(2)相同语义的读写接口，文件在`KernelFile`被释放时关闭。
+//!
+//! 内核打开的文件总是带有`O_LARGEFILE`，不受32位文件偏移的限制。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/fs/open.c#filp_open
+
+use alloc::{sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use crate::{
+    driver::base::block::SeekFrom,
+    process::{cred::Cred, ProcessManager},
+};
+
+use super::{
+    fcntl::AtFlags,
+    file::{File, FileFlags},
+    open::do_filp_open,
+    syscall::{OpenHow, OpenHowResolve},
+    FileType, IndexNode, InodeMode,
+};
+
+/// 内核打开的文件
+#[derive(Debug)]
+pub struct KernelFile {
+    file: Arc<File>,
+}
+
+impl KernelFile {
+    /// 按路径打开文件
+    ///
+    /// 相对路径从当前进程的工作目录开始解析，权限按当前进程的凭据检查。
+    ///
+    /// ## 参数
+    /// - `path`: 文件路径
+    /// - `flags`: 打开方式，与open(2)相同
+    /// - `mode`: 带有`O_CREAT`时新建文件的权限
+    pub fn open(path: &str, flags: FileFlags, mode: InodeMode) -> Result<Self, SystemError> {
+        let how = OpenHow::new(
+            flags | FileFlags::O_LARGEFILE,
+            mode,
+            OpenHowResolve::empty(),
+        );
+        let file = do_filp_open(AtFlags::AT_FDCWD.bits(), path, &how)?;
+        Ok(Self {
+            file: Arc::new(file),
+        })
+    }
+
+    /// 以指定的凭据打开文件
+    ///
+    /// 路径解析、权限检查和新建文件的属主都使用`cred`，打开之后的读写也按`cred`进行。
+    #[allow(dead_code)]
+    pub fn open_with_cred(
+        path: &str,
+        flags: FileFlags,
+        mode: InodeMode,
+        cred: &Arc<Cred>,
+    ) -> Result<Self, SystemError> {
+        with_cred(cred, || Self::open(path, flags, mode))
+    }
+
+    /// 打开已经找到的inode，不做权限检查
+    pub fn from_inode(inode: Arc<dyn IndexNode>, flags: FileFlags) -> Result<Self, SystemError> {
+        let file = File::new(inode, flags | FileFlags::O_LARGEFILE)?;
+        Ok(Self {
+            file: Arc::new(file),
+        })
+    }
+
+    /// 使用用户进程已经打开的文件，比如通过文件描述符传入的回环设备后端文件
+    pub fn from_file(file: Arc<File>) -> Self {
+        Self { file }
+    }
+
+    #[allow(dead_code)]
+    pub fn file(&self) -> &Arc<File> {
+        &self.file
+    }
+
+    pub fn inode(&self) -> Arc<dyn IndexNode> {
+        self.file.inode()
+    }
+
+    #[allow(dead_code)]
+    pub fn flags(&self) -> FileFlags {
+        self.file.flags()
+    }
+
+    /// 文件的大小
+    pub fn size(&self) -> Result<usize, SystemError> {
+        let md = self.file.metadata()?;
+        if md.size < 0 {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(md.size as usize)
+    }
+
+    /// 从当前的文件偏移处读取，并推进文件偏移
+    #[allow(dead_code)]
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, SystemError> {
+        self.file.read(buf.len(), buf)
+    }
+
+    /// 在当前的文件偏移处写入，并推进文件偏移
+    #[allow(dead_code)]
+    pub fn write(&self, buf: &[u8]) -> Result<usize, SystemError> {
+        self.file.write(buf.len(), buf)
+    }
+
+    /// 从`offset`处读取，不改变文件偏移
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        self.file.pread(offset, buf.len(), buf)
+    }
+
+    /// 在`offset`处写入，不改变文件偏移
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        self.file.pwrite(offset, buf.len(), buf)
+    }
+
+    /// 读取整个普通文件
+    ///
+    /// ## 参数
+    /// - `max_size`: 允许的最大文件大小
+    ///
+    /// ## 返回值
+    /// - `Err(EINVAL)`: 不是普通文件
+    /// - `Err(EFBIG)`: 文件超过了`max_size`
+    pub fn read_to_end(&self, max_size: usize) -> Result<Vec<u8>, SystemError> {
+        if self.file.file_type() != FileType::File {
+            return Err(SystemError::EINVAL);
+        }
+        let size = self.size()?;
+        if size > max_size {
+            return Err(SystemError::EFBIG);
+        }
+        let mut data = alloc::vec![0u8; size];
+        let mut pos = 0;
+        while pos < size {
+            let n = self.read_at(pos, &mut data[pos..])?;
+            if n == 0 {
+                break;
+            }
+            pos += n;
+        }
+        data.truncate(pos);
+        Ok(data)
+    }
+
+    /// 把文件的数据同步到存储设备
+    ///
+    /// ## 参数
+    /// - `datasync`: 为true时只同步数据，与fdatasync(2)相同
+    pub fn fsync(&self, datasync: bool) -> Result<(), SystemError> {
+        self.file
+            .inode()
+            .sync_file(datasync, self.file.private_data.lock())
+    }
+
+    /// 调整文件偏移，返回新的文件偏移
+    #[allow(dead_code)]
+    pub fn llseek(&self, origin: SeekFrom) -> Result<usize, SystemError> {
+        self.file.lseek(origin)
+    }
+}
+
+/// 临时以`cred`的身份执行`f`，结束后恢复当前进程原来的凭据
+pub fn with_cred<R>(cred: &Arc<Cred>, f: impl FnOnce() -> R) -> R {
+    let pcb = ProcessManager::current_pcb();
+    let old = pcb.cred();
+    pcb.set_cred(cred.clone()).ok();
+    let ret = f();
+    pcb.set_cred(old).ok();
+    ret
+}
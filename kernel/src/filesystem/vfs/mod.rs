@@ -1,17 +1,25 @@
 pub mod append_lock;
+pub mod copy_range;
+pub mod dcache;
+pub mod dir_cookie;
 pub mod fasync;
 pub mod fcntl;
 pub mod file;
 pub mod flock;
+pub mod ioctl;
 pub mod iov;
+pub mod kernel_file;
 pub mod mount;
+pub mod mount_options;
 pub mod open;
 pub mod permission;
 pub mod posix_lock;
 pub mod stat;
 pub mod syscall;
+pub mod timestamps;
 pub mod utils;
 pub mod vcore;
+pub mod xattr;
 
 use alloc::{string::String, sync::Arc, vec::Vec};
 use core::{any::Any, fmt::Debug, fmt::Display, sync::atomic::AtomicUsize};
@@ -22,7 +30,9 @@ use system_error::SystemError;
 
 use crate::{
     driver::base::{
-        block::block_device::BlockDevice, char::CharDevice, device::device_number::DeviceNumber,
+        block::{block_device::BlockDevice, gendisk::GenDisk},
+        char::CharDevice,
+        device::device_number::DeviceNumber,
     },
     filesystem::{
         epoll::EPollItem,
@@ -31,16 +41,22 @@ use crate::{
     ipc::pipe::LockedPipeInode,
     libs::{
         casting::DowncastArc,
+        error_context::{ContextError, ErrorContext},
         mutex::{Mutex, MutexGuard},
     },
-    mm::{fault::PageFaultMessage, VmFaultReason},
+    mm::{fault::PageFaultMessage, gup::PinnedUserPages, VmFaultReason},
     net::socket::Socket,
     process::ProcessManager,
     syscall::user_buffer::UserBuffer,
     time::PosixTimeSpec,
 };
 
-use self::{file::FileFlags, utils::DName, vcore::generate_inode_id};
+use self::{
+    dir_cookie::{hash_cookies, DirCookie},
+    file::FileFlags,
+    utils::DName,
+    vcore::generate_inode_id,
+};
 pub use self::{file::FilePrivateData, mount::MountFS};
 
 use super::page_cache::PageCache;
@@ -438,7 +454,6 @@ pub trait IndexNode: Any + Sync + Send + Debug + CastFromSync {
     /// - `offset`: 起始位置在Inode中的偏移量
     /// - `len`: 要读取的字节数
     /// - `buf`: 缓冲区
-    /// - `data`: 各文件系统系统所需私有信息
     ///
     /// ## 返回值
     ///
@@ -449,7 +464,6 @@ pub trait IndexNode: Any + Sync + Send + Debug + CastFromSync {
         _offset: usize,
         _len: usize,
         _buf: &mut [u8],
-        _data: MutexGuard<FilePrivateData>,
     ) -> Result<usize, SystemError> {
         return Err(SystemError::ENOSYS);
     }
@@ -461,20 +475,112 @@ pub trait IndexNode: Any + Sync + Send + Debug + CastFromSync {
     /// - `offset`: 起始位置在Inode中的偏移量
     /// - `len`: 要读取的字节数
     /// - `buf`: 缓冲区
-    /// - `data`: 各文件系统系统所需私有信息
     ///
     /// ## 返回值
     ///
     /// - `Ok(usize)``: Ok(读取的字节数)
     /// - `Err(SystemError)``: Err(Posix错误码)
-    fn write_direct(
+    fn write_direct(&self, _offset: usize, _len: usize, _buf: &[u8]) -> Result<usize, SystemError> {
+        return Err(SystemError::ENOSYS);
+    }
+
+    /// # 在inode的指定偏移量开始，读取数据到固定住的用户缓冲区，忽略PageCache
+    ///
+    /// 块设备和FAT用用户页构造BIO，设备直接写入用户缓冲区。
+    /// 默认经过内核缓冲区调用[`read_direct`](Self::read_direct)，再拷贝到用户缓冲区
+    ///
+    /// ## 参数
+    ///
+    /// - `offset`: 起始位置在Inode中的偏移量
+    /// - `pages`: 用户缓冲区，读取`pages.len()`个字节
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(usize)`: Ok(读取的字节数)
+    /// - `Err(SystemError)`: Err(Posix错误码)
+    fn read_direct_user(
         &self,
-        _offset: usize,
-        _len: usize,
-        _buf: &[u8],
-        _data: MutexGuard<FilePrivateData>,
+        offset: usize,
+        pages: &PinnedUserPages,
     ) -> Result<usize, SystemError> {
-        return Err(SystemError::ENOSYS);
+        let mut buf = vec![0u8; pages.len()];
+        let len = self.read_direct(offset, buf.len(), &mut buf)?;
+        pages.copy_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    /// # 在inode的指定偏移量开始，写入固定住的用户缓冲区中的数据，忽略PageCache
+    ///
+    /// 块设备和FAT用用户页构造BIO，设备直接读取用户缓冲区。
+    /// 默认把用户缓冲区拷贝到内核缓冲区之后调用[`write_direct`](Self::write_direct)
+    ///
+    /// ## 参数
+    ///
+    /// - `offset`: 起始位置在Inode中的偏移量
+    /// - `pages`: 用户缓冲区，写入`pages.len()`个字节
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(usize)`: Ok(写入的字节数)
+    /// - `Err(SystemError)`: Err(Posix错误码)
+    fn write_direct_user(
+        &self,
+        offset: usize,
+        pages: &PinnedUserPages,
+    ) -> Result<usize, SystemError> {
+        let mut buf = vec![0u8; pages.len()];
+        pages.copy_to_slice(&mut buf);
+        self.write_direct(offset, buf.len(), &buf)
+    }
+
+    /// # 内核自身发起的读取
+    ///
+    /// 不经过打开的文件读取inode的内容，例如loop设备读取后端文件、加载固件、读取符号链接的目标。
+    /// 内核中的调用者应该使用这个接口，而不是自己构造文件私有数据去调用[`read_at`](Self::read_at)。
+    ///
+    /// 默认以`FilePrivateData::Unused`调用`read_at`。读写依赖打开时建立的私有数据的inode
+    /// （例如fuse文件）需要重写这个方法，否则会返回错误。
+    ///
+    /// ## 参数
+    ///
+    /// - `offset`: 起始位置在Inode中的偏移量
+    /// - `buf`: 缓冲区，读取`buf.len()`字节
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(usize)`: 读取的字节数
+    /// - `Err(SystemError)`: Posix错误码
+    fn kernel_read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let data = Mutex::new(FilePrivateData::Unused);
+        self.read_at(offset, buf.len(), buf, data.lock())
+    }
+
+    /// # 内核自身发起的写入
+    ///
+    /// 与[`kernel_read_at`](Self::kernel_read_at)相对应，写入`buf`的全部内容。
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(usize)`: 写入的字节数
+    /// - `Err(SystemError)`: Posix错误码
+    fn kernel_write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        let data = Mutex::new(FilePrivateData::Unused);
+        self.write_at(offset, buf.len(), buf, data.lock())
+    }
+
+    /// # O_DIRECT直接I/O的对齐要求
+    ///
+    /// 使用O_DIRECT时，文件偏移量、长度和用户缓冲区的地址都必须按返回值对齐。
+    /// 来自用户态的读写固定住用户页之后通过[`read_direct_user`](Self::read_direct_user)和
+    /// [`write_direct_user`](Self::write_direct_user)完成，内核缓冲区通过
+    /// [`read_direct`](Self::read_direct)和[`write_direct`](Self::write_direct)完成。
+    ///
+    /// ## 返回值
+    ///
+    /// - `Some(align)`: 支持直接I/O，对齐要求为`align`字节（2的幂）
+    /// - `None`: 不支持直接I/O，O_DIRECT的读写退回到普通的读写
+    fn direct_io_alignment(&self) -> Option<usize> {
+        None
     }
 
     /// @brief 获取inode的元数据
@@ -507,6 +613,41 @@ pub trait IndexNode: Any + Sync + Send + Debug + CastFromSync {
         return Err(SystemError::ENOSYS);
     }
 
+    /// @brief 在文件的[offset, offset+len)范围内打洞
+    ///
+    /// 释放这部分数据占用的存储空间，之后读出来全为0，文件大小不变
+    ///
+    /// @return 成功：Ok()
+    ///         失败：Err(错误码)
+    fn punch_hole(&self, _offset: usize, _len: usize) -> Result<(), SystemError> {
+        // 若文件系统没有实现此方法，则返回"不支持"
+        return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+    }
+
+    /// # 在文件系统内部拷贝文件数据（copy_file_range）
+    ///
+    /// 把本文件`[pos_in, pos_in + len)`的数据拷贝到`dest`的`pos_out`处。支持reflink、
+    /// 服务器端拷贝的文件系统可以实现这个方法，共享数据块而不是复制数据。
+    /// 文件系统自己负责两个文件页缓存的一致性，以及更新目标文件的大小和时间戳。
+    ///
+    /// ## 参数
+    ///
+    /// - `dest`: 目标文件，已经去掉了挂载包装，可能属于其它文件系统
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(usize)`: 拷贝的字节数，可以少于`len`
+    /// - `Err(EOPNOTSUPP_OR_ENOTSUP | EXDEV)`: 不支持这次拷贝，VFS会退回到经过页缓存的通用拷贝
+    fn copy_file_range(
+        &self,
+        _pos_in: usize,
+        _dest: &Arc<dyn IndexNode>,
+        _pos_out: usize,
+        _len: usize,
+    ) -> Result<usize, SystemError> {
+        return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+    }
+
     /// @brief 在当前目录下创建一个新的inode
     ///
     /// @param name 目录项的名字
@@ -549,8 +690,7 @@ pub trait IndexNode: Any + Sync + Send + Debug + CastFromSync {
     fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
         let inode = self.create_with_data(name, FileType::SymLink, InodeMode::S_IRWXUGO, 0)?;
         let bytes = target.as_bytes();
-        let len = bytes.len();
-        inode.write_at(0, len, bytes, Mutex::new(FilePrivateData::Unused).lock())?;
+        inode.kernel_write_at(0, bytes)?;
         Ok(inode)
     }
 
@@ -671,6 +811,14 @@ pub trait IndexNode: Any + Sync + Send + Debug + CastFromSync {
         Err(SystemError::ENOTDIR)
     }
 
+    /// 列出当前目录下的所有目录项及其cookie，按cookie从小到大排序
+    ///
+    /// cookie是readdir的位置，目录被修改之后必须仍然指向同一个目录项，参见[`dir_cookie`]。
+    /// 默认使用名字的哈希，能够按照目录项的存储位置分配cookie的文件系统应当重写本方法
+    fn list_cookies(&self) -> Result<Vec<DirCookie>, SystemError> {
+        Ok(hash_cookies(self.list()?))
+    }
+
     /// # mount - 挂载文件系统
     ///
     /// 将给定的文件系统挂载到当前的文件系统节点上。
@@ -901,33 +1049,41 @@ pub trait IndexNode: Any + Sync + Send + Debug + CastFromSync {
 
     /// @brief 按文件名获取扩展属性
     ///
+    /// 属性名是包含命名空间前缀的完整名字，命名空间和权限已经由VFS检查过，
+    /// 参见[`xattr`]
+    ///
     /// @param name 属性名称
-    /// @param buf 用于存储扩展属性值的缓冲区
+    /// @param buf 用于存储扩展属性值的缓冲区，为空时只返回属性值的长度
     ///
     /// @return 成功：Ok(属性值的实际长度)
-    ///         失败：Err(错误码)
+    ///         失败：Err(错误码)，属性不存在时为ENODATA，缓冲区不够大时为ERANGE
     fn getxattr(&self, _name: &str, _buf: &mut [u8]) -> Result<usize, SystemError> {
-        log::warn!(
-            "getxattr not implemented for {}",
-            crate::libs::name::get_type_name(&self)
-        );
-        return Err(SystemError::ENOSYS);
+        return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
     }
 
     /// @brief 按文件名设置扩展属性
     ///
     /// @param name 属性名称
-    /// @param buf 用于存储扩展属性值的缓冲区
     /// @param value 要设置的扩展属性值
     ///
     /// @return 成功：Ok(0)
     ///         失败：Err(错误码)
     fn setxattr(&self, _name: &str, _value: &[u8]) -> Result<usize, SystemError> {
-        log::warn!(
-            "setxattr not implemented for {}",
-            crate::libs::name::get_type_name(&self)
-        );
-        return Err(SystemError::ENOSYS);
+        return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+    }
+
+    /// @brief 列出所有扩展属性的名字
+    ///
+    /// 不支持扩展属性的文件系统返回空列表
+    fn listxattr(&self) -> Result<Vec<String>, SystemError> {
+        return Ok(Vec::new());
+    }
+
+    /// @brief 删除扩展属性
+    ///
+    /// @return 失败：Err(错误码)，属性不存在时为ENODATA
+    fn removexattr(&self, _name: &str) -> Result<(), SystemError> {
+        return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
     }
 
     /// # 将当前Inode转换为 Socket 引用
@@ -1135,13 +1291,7 @@ impl dyn IndexNode {
 
                 let mut content = [0u8; 256];
                 // 读取符号链接
-                // TODO:We need to clarify which interfaces require private data and which do not
-                let len = inode.read_at(
-                    0,
-                    256,
-                    &mut content,
-                    Mutex::new(FilePrivateData::Unused).lock(),
-                )?;
+                let len = inode.kernel_read_at(0, &mut content)?;
 
                 // 将读到的数据转换为utf8字符串（先转为str，再转为String）
                 let link_path = String::from(
@@ -1318,6 +1468,10 @@ bitflags! {
         const MOUNT_MAGIC = 61267;
         const PIPEFS_MAGIC = 0x50495045;
         const EVENTFD_MAGIC = 0x45564446; // "EVDF" in ASCII
+        const PSTOREFS_MAGIC = 0x6165676c;
+        const OVERLAYFS_MAGIC = 0x794c7630;
+        const ANON_INODE_MAGIC = 0x09041934;
+        const SMB2_MAGIC = 0xfe534d42;
     }
 }
 
@@ -1349,6 +1503,22 @@ pub trait FileSystem: Any + Sync + Send + Debug {
         true // 默认支持 readahead
     }
 
+    /// 路径查找的结果能否缓存在dcache中
+    ///
+    /// 只有目录内容只会经过VFS改变、并且名字区分大小写的文件系统才能返回true。
+    /// procfs、sysfs这类内容自行变化的文件系统，以及FUSE等远程文件系统应当保持默认的false
+    fn dcache_enabled(&self) -> bool {
+        false
+    }
+
+    /// 文件系统能够保存的时间戳精度，单位为纳秒
+    ///
+    /// 比如只保存秒的文件系统返回1_000_000_000。VFS生成的时间戳会先截断到这个精度，
+    /// 避免内存中的时间戳和写回之后读到的不一致
+    fn time_granularity(&self) -> u32 {
+        1
+    }
+
     /// @brief 本函数用于实现动态转换。
     /// 具体的文件系统在实现本函数时，最简单的方式就是：直接返回self
     fn as_any_ref(&self) -> &dyn Any;
@@ -1375,6 +1545,16 @@ pub trait FileSystem: Any + Sync + Send + Debug {
     /// Default is no-op.
     fn on_umount(&self) {}
 
+    /// 重新挂载时，根据新的挂载选项调整文件系统的参数
+    ///
+    /// ## 参数
+    /// - `data`: mount(2)传入的挂载选项字符串
+    ///
+    /// 默认忽略挂载选项，只由VFS更新挂载标志
+    fn remount(&self, _data: Option<&str>) -> Result<(), SystemError> {
+        Ok(())
+    }
+
     unsafe fn fault(&self, _pfm: &mut PageFaultMessage) -> VmFaultReason {
         VmFaultReason::VM_FAULT_SIGBUS
     }
@@ -1414,6 +1594,14 @@ pub trait MountableFileSystem: FileSystem {
         log::error!("This filesystem does not support make_fs");
         Err(SystemError::ENOSYS)
     }
+
+    /// 根据磁盘上的魔数等信息，探测`gendisk`上是否是这个文件系统
+    ///
+    /// 用于挂载类型为"auto"或者没有指定类型时选择文件系统，以及在sysfs中报告分区的文件系统类型。
+    /// 不基于块设备的文件系统不需要实现，默认探测不到。
+    fn probe(_gendisk: &Arc<GenDisk>) -> bool {
+        false
+    }
 }
 
 /// # 注册一个可以被挂载文件系统
@@ -1440,6 +1628,10 @@ macro_rules! register_mountable_fs {
             ) -> Result<Option<Arc<dyn FileSystemMakerData + 'static>>, SystemError> {
                 <$fs as MountableFileSystem>::make_mount_data(raw_data, source)
             }
+
+            fn probe_bridge(gendisk: &Arc<$crate::driver::base::block::gendisk::GenDisk>) -> bool {
+                <$fs as MountableFileSystem>::probe(gendisk)
+            }
         }
 
         #[distributed_slice(FSMAKER)]
@@ -1456,6 +1648,8 @@ macro_rules! register_mountable_fs {
                         &str,
                     )
                         -> Result<Option<Arc<dyn FileSystemMakerData + 'static>>, SystemError>),
+                &($fs::probe_bridge
+                    as fn(&Arc<$crate::driver::base::block::gendisk::GenDisk>) -> bool),
             );
     };
 }
@@ -1497,6 +1691,8 @@ pub struct FileSystemMaker {
     name: &'static str,
     /// 用于创建挂载数据的函数
     builder: &'static MountDataBuilder,
+    /// 探测块设备上的文件系统的函数
+    prober: &'static FSProbeFunction,
 }
 
 impl FileSystemMaker {
@@ -1504,11 +1700,13 @@ impl FileSystemMaker {
         name: &'static str,
         maker: &'static FSMakerFunction,
         builder: &'static MountDataBuilder,
+        prober: &'static FSProbeFunction,
     ) -> FileSystemMaker {
         FileSystemMaker {
             maker,
             name,
             builder,
+            prober,
         }
     }
 
@@ -1518,6 +1716,29 @@ impl FileSystemMaker {
     ) -> Result<Arc<dyn FileSystem>, SystemError> {
         (self.maker)(data)
     }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// 探测`gendisk`上是否是这个文件系统
+    pub fn probe(&self, gendisk: &Arc<GenDisk>) -> bool {
+        (self.prober)(gendisk)
+    }
+
+    /// 解析挂载选项并创建文件系统实例
+    fn produce(
+        &self,
+        filesystem: &str,
+        data: Option<&str>,
+        source: &str,
+    ) -> Result<Arc<dyn FileSystem>, ContextError> {
+        let mount_data = (self.builder)(data, source)
+            .with_context(|| format!("{}: invalid mount options {:?}", filesystem, data))?;
+        let mount_data_ref = mount_data.as_ref().map(|arc| arc.as_ref());
+        self.build(mount_data_ref)
+            .with_context(|| format!("{}: failed to create filesystem", filesystem))
+    }
 }
 
 pub trait FileSystemMakerData: Send + Sync {
@@ -1531,6 +1752,7 @@ pub type MountDataBuilder =
         raw_data: Option<&str>,
         source: &str,
     ) -> Result<Option<Arc<dyn FileSystemMakerData + 'static>>, SystemError>;
+pub type FSProbeFunction = fn(gendisk: &Arc<GenDisk>) -> bool;
 
 #[macro_export]
 macro_rules! define_filesystem_maker_slice {
@@ -1552,31 +1774,70 @@ macro_rules! define_filesystem_maker_slice {
 ///
 /// ## 返回值
 /// - `Ok(Arc<dyn FileSystem>)`: 成功时返回文件系统的共享引用
-/// - `Err(SystemError)`: 如果找不到对应的文件系统或创建失败，则返回错误
+/// - `Err(ContextError)`: 如果找不到对应的文件系统或创建失败，则返回带上下文的错误
+///
+/// 文件系统类型为"auto"时，依次用各个文件系统的`probe`探测挂载源所在的块设备，
+/// 使用第一个能够成功创建的文件系统。"fat"和"msdos"是"vfat"的别名，
+/// FAT12/16/32由FAT驱动根据簇的数量自动识别。
 ///
 /// 这个是之前的`produce_fs!`的函数版本，改成了函数之后ext4的挂载会慢一点，仅作记录
 pub fn produce_fs(
     filesystem: &str,
     data: Option<&str>,
     source: &str,
-) -> Result<Arc<dyn FileSystem>, SystemError> {
+) -> Result<Arc<dyn FileSystem>, ContextError> {
+    if filesystem == "auto" {
+        return produce_fs_auto(data, source);
+    }
+
     let canonical_filesystem = if filesystem.starts_with("fuse.") {
         "fuse"
+    } else if filesystem == "fat" || filesystem == "msdos" {
+        "vfat"
     } else {
         filesystem
     };
 
     match FSMAKER.iter().find(|&m| m.name == canonical_filesystem) {
-        Some(maker) => {
-            let mount_data = (maker.builder)(data, source)?;
-            let mount_data_ref = mount_data.as_ref().map(|arc| arc.as_ref());
-            maker.build(mount_data_ref)
-        }
-        None => {
-            log::error!("mismatch filesystem type : {}", filesystem);
-            Err(SystemError::EINVAL)
+        Some(maker) => maker.produce(filesystem, data, source),
+        None => Err(SystemError::EINVAL)
+            .with_context(|| format!("unknown filesystem type {}", filesystem)),
+    }
+}
+
+/// 探测`gendisk`上的文件系统
+///
+/// ## 返回值
+/// - `Some(maker)`: 第一个探测成功的文件系统
+/// - `None`: 没有可以识别的文件系统
+pub fn probe_fs(gendisk: &Arc<GenDisk>) -> Option<&'static FileSystemMaker> {
+    FSMAKER.iter().find(|m| m.probe(gendisk))
+}
+
+/// 探测挂载源上的文件系统并创建文件系统实例
+fn produce_fs_auto(data: Option<&str>, source: &str) -> Result<Arc<dyn FileSystem>, ContextError> {
+    let gendisk = vcore::find_source_gendisk(source)
+        .with_context(|| format!("auto: {} is not a block device", source))?;
+    let mut last_err = None;
+    for maker in FSMAKER.iter().filter(|m| m.probe(&gendisk)) {
+        match maker.produce(maker.name, data, source) {
+            Ok(fs) => return Ok(fs),
+            Err(e) => {
+                log::warn!(
+                    "auto mount: {} detected on {} but failed: {}",
+                    maker.name,
+                    source,
+                    e
+                );
+                last_err = Some(e);
+            }
         }
     }
+    match last_err {
+        Some(e) => Err(e),
+        None => Err(SystemError::EINVAL)
+            .with_context(|| format!("auto: no known filesystem found on {}", source)),
+    }
 }
 
 define_filesystem_maker_slice!(FSMAKER);
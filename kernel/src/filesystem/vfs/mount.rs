@@ -2,7 +2,7 @@ use core::{
     any::Any,
     fmt::Debug,
     hash::Hash,
-    sync::atomic::{compiler_fence, Ordering},
+    sync::atomic::{compiler_fence, AtomicBool, AtomicUsize, Ordering},
 };
 
 use alloc::{
@@ -23,7 +23,7 @@ use crate::{
         vfs::{fcntl::AtFlags, syscall::RenameFlags, vcore::do_mkdir_at},
     },
     libs::{casting::DowncastArc, lazy_init::Lazy, rwsem::RwSem},
-    mm::{fault::PageFaultMessage, VmFaultReason},
+    mm::{fault::PageFaultMessage, gup::PinnedUserPages, VmFaultReason},
     process::{
         namespace::{
             mnt::MntNamespace,
@@ -33,11 +33,17 @@ use crate::{
         },
         ProcessManager,
     },
+    time::PosixTimeSpec,
 };
 
 use super::{
-    file::FileFlags, utils::DName, FilePrivateData, FileSystem, FileType, IndexNode, InodeId,
-    InodeMode, PollableInode, SuperBlock,
+    dcache::dcache,
+    dir_cookie::DirCookie,
+    file::FileFlags,
+    timestamps::{atime_need_update, lazy_times, timestamp_truncate, LazyTimeKey},
+    utils::DName,
+    FilePrivateData, FileSystem, FileType, IndexNode, InodeId, InodeMode, PollableInode,
+    SuperBlock,
 };
 
 bitflags! {
@@ -220,6 +226,15 @@ impl MountId {
     }
 }
 
+/// 依赖于某个挂载的内核对象，比如后端文件在该挂载上的loop设备
+///
+/// 内核对象长期持有挂载上的文件时，向挂载注册自己。普通的卸载在挂载仍有依赖者时返回EBUSY；
+/// `MNT_FORCE`或`MNT_DETACH`卸载时，先通知依赖者放弃它持有的文件，再卸载文件系统。
+pub trait MountDependent: Send + Sync {
+    /// 挂载即将被强制卸载，释放持有的该挂载上的文件
+    fn detach_from_mount(&self);
+}
+
 /// @brief 挂载文件系统
 /// 挂载文件系统的时候，套了MountFS这一层，以实现文件系统的递归挂载
 pub struct MountFS {
@@ -240,6 +255,12 @@ pub struct MountFS {
 
     mount_flags: RwSem<MountFlags>,
     mount_source: RwSem<Option<String>>,
+    /// 依赖于这个挂载的内核对象
+    dependents: Mutex<Vec<Weak<dyn MountDependent>>>,
+    /// 打开的文件、进程的当前目录和根目录对这个挂载的引用数，见[`MountRef`]
+    users: AtomicUsize,
+    /// 已经通过`MNT_DETACH`从挂载树上摘下，最后一个引用释放时通知文件系统
+    detached: AtomicBool,
 }
 
 impl Debug for MountFS {
@@ -298,6 +319,9 @@ impl MountFS {
             mount_id: MountId::alloc(),
             mount_flags: RwSem::new(mount_flags),
             mount_source: RwSem::new(mount_source),
+            dependents: Mutex::new(Vec::new()),
+            users: AtomicUsize::new(0),
+            detached: AtomicBool::new(false),
         });
 
         if let Some(mnt_ns) = mnt_ns {
@@ -323,6 +347,9 @@ impl MountFS {
             mount_id: MountId::alloc(),
             mount_flags: RwSem::new(self.mount_flags()),
             mount_source: RwSem::new(mount_source),
+            dependents: Mutex::new(Vec::new()),
+            users: AtomicUsize::new(0),
+            detached: AtomicBool::new(false),
         });
 
         return mountfs;
@@ -341,6 +368,65 @@ impl MountFS {
         update(&mut mount_flags);
     }
 
+    /// 注册依赖于这个挂载的内核对象
+    pub fn add_dependent(&self, dependent: Weak<dyn MountDependent>) {
+        let mut dependents = self.dependents.lock();
+        dependents.retain(|d| d.strong_count() != 0);
+        dependents.push(dependent);
+    }
+
+    /// 取消注册，与[`add_dependent`](Self::add_dependent)相对应
+    pub fn remove_dependent(&self, dependent: &Weak<dyn MountDependent>) {
+        let mut dependents = self.dependents.lock();
+        if let Some(pos) = dependents.iter().position(|d| Weak::ptr_eq(d, dependent)) {
+            dependents.remove(pos);
+        }
+    }
+
+    /// 卸载之前处理依赖于这个挂载的内核对象
+    ///
+    /// ## 参数
+    /// - `force`: 为true时通知所有依赖者放弃对挂载的引用
+    ///
+    /// ## 返回值
+    /// - `Err(EBUSY)`: 不是强制卸载，并且挂载仍有依赖者
+    pub fn release_dependents(&self, force: bool) -> Result<(), SystemError> {
+        let dependents: Vec<Arc<dyn MountDependent>> = {
+            let mut dependents = self.dependents.lock();
+            dependents.retain(|d| d.strong_count() != 0);
+            dependents.iter().filter_map(|d| d.upgrade()).collect()
+        };
+        if dependents.is_empty() {
+            return Ok(());
+        }
+        if !force {
+            return Err(SystemError::EBUSY);
+        }
+        // 依赖者会在detach_from_mount中取消注册，不能持有锁
+        for dependent in dependents {
+            dependent.detach_from_mount();
+        }
+        Ok(())
+    }
+
+    /// 打开的文件、进程的当前目录和根目录对这个挂载的引用数
+    pub fn users(&self) -> usize {
+        self.users.load(Ordering::Acquire)
+    }
+
+    /// 挂载是否正在被使用：有引用，或者上面还有子挂载
+    pub fn is_busy(&self) -> bool {
+        self.users() != 0 || !self.mountpoints.lock().is_empty()
+    }
+
+    fn put_user(&self) {
+        if self.users.fetch_sub(1, Ordering::AcqRel) == 1 && self.detached.load(Ordering::Acquire) {
+            dcache().invalidate_fs(&self.inner_filesystem);
+            lazy_times().flush_fs(&self.inner_filesystem);
+            self.inner_filesystem.on_umount();
+        }
+    }
+
     pub fn add_mount(&self, inode_id: InodeId, mount_fs: Arc<MountFS>) -> Result<(), SystemError> {
         // 检查是否已经存在同名的挂载点
         if self.mountpoints.lock().contains_key(&inode_id) {
@@ -440,11 +526,83 @@ impl MountFS {
         // Only clear mountpoint state and notify filesystem after successful detach.
         if result.is_ok() {
             self.self_mountpoint.write().take();
+            dcache().invalidate_fs(&self.inner_filesystem);
+            lazy_times().flush_fs(&self.inner_filesystem);
             self.inner_filesystem.on_umount();
         }
 
         return result;
     }
+
+    /// 把挂载从挂载树上摘下（`MNT_DETACH`）
+    ///
+    /// 挂载立即从挂载树上消失，已经打开的文件和位于其中的当前目录仍然可以使用；
+    /// 最后一个引用释放时才通知文件系统卸载。没有引用时与[`umount`](Self::umount)相同。
+    ///
+    /// # Errors
+    /// 如果当前文件系统是根文件系统，那么将会返回`EINVAL`
+    pub fn detach(&self) -> Result<Arc<MountFS>, SystemError> {
+        let result = self
+            .self_mountpoint()
+            .ok_or(SystemError::EINVAL)?
+            .do_umount()?;
+        self.self_mountpoint.write().take();
+
+        // 先标记再检查引用数，与put_user配合保证on_umount恰好调用一次
+        self.users.fetch_add(1, Ordering::AcqRel);
+        self.detached.store(true, Ordering::Release);
+        self.put_user();
+        return Ok(result);
+    }
+}
+
+/// 对挂载的引用
+///
+/// 打开的文件、进程的当前目录和根目录通过它固定所在的挂载：挂载有引用时普通的卸载返回`EBUSY`，
+/// `MNT_DETACH`卸载的挂载在最后一个引用释放后才通知文件系统。
+/// 不是通过挂载访问到的inode（比如管道、socket）不引用任何挂载。
+#[derive(Debug, Default)]
+pub struct MountRef(Option<Arc<MountFS>>);
+
+impl MountRef {
+    pub fn new(inode: &Arc<dyn IndexNode>) -> Self {
+        let mount_fs = inode
+            .clone()
+            .downcast_arc::<MountFSInode>()
+            .map(|inode| inode.mount_fs.clone());
+        if let Some(mount_fs) = &mount_fs {
+            mount_fs.users.fetch_add(1, Ordering::AcqRel);
+        }
+        Self(mount_fs)
+    }
+}
+
+impl Clone for MountRef {
+    fn clone(&self) -> Self {
+        if let Some(mount_fs) = &self.0 {
+            mount_fs.users.fetch_add(1, Ordering::AcqRel);
+        }
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for MountRef {
+    fn drop(&mut self) {
+        if let Some(mount_fs) = self.0.take() {
+            mount_fs.put_user();
+        }
+    }
+}
+
+/// 获取inode所在挂载的挂载标志
+///
+/// 不是通过挂载访问到的inode（比如文件系统内部使用的inode）没有挂载标志
+pub fn inode_mount_flags(inode: &Arc<dyn IndexNode>) -> MountFlags {
+    inode
+        .fs()
+        .downcast_arc::<MountFS>()
+        .map(|mfs| mfs.mount_flags())
+        .unwrap_or(MountFlags::empty())
 }
 
 impl Drop for MountFS {
@@ -465,11 +623,18 @@ impl MountFSInode {
         Ok(())
     }
 
+    /// 在当前inode上挂载`inner_fs`中以`root_inner_inode`为根的子树
+    ///
+    /// ## 参数
+    /// - `clone_from`: 绑定挂载时被复制的挂载。新挂载与它的传播关系相同：
+    ///   共享挂载的副本加入同一个对等组，从属挂载的副本从属于同一个主挂载。
+    ///   为None或者被复制的挂载是私有挂载时，父挂载是共享挂载则新挂载也是共享挂载。
     pub(crate) fn mount_subtree(
         &self,
         inner_fs: Arc<dyn FileSystem>,
         root_inner_inode: Arc<dyn IndexNode>,
         mount_flags: MountFlags,
+        clone_from: Option<&Arc<MountFS>>,
     ) -> Result<Arc<MountFS>, SystemError> {
         let metadata = self.inner_inode.metadata()?;
         if metadata.file_type != FileType::Dir {
@@ -477,10 +642,18 @@ impl MountFSInode {
         }
 
         let parent_propagation = self.mount_fs.propagation();
-        let new_propagation = if parent_propagation.is_shared() {
-            MountPropagation::new_shared()
-        } else {
-            MountPropagation::new_private()
+        let source_propagation = clone_from.map(|mnt| mnt.propagation());
+        let master = source_propagation
+            .as_ref()
+            .filter(|prop| prop.is_slave())
+            .and_then(|prop| prop.master());
+        let new_propagation = match (&source_propagation, &master) {
+            (Some(prop), _) if prop.is_shared() => {
+                MountPropagation::new_shared_with_group(prop.peer_group_id())
+            }
+            (_, Some(master)) => MountPropagation::new_slave(Arc::downgrade(master)),
+            _ if parent_propagation.is_shared() => MountPropagation::new_shared(),
+            _ => MountPropagation::new_private(),
         };
 
         let new_mount_fs = MountFS::new(
@@ -506,6 +679,10 @@ impl MountFSInode {
         if new_mount_fs.propagation().is_shared() {
             let group_id = new_mount_fs.propagation().peer_group_id();
             register_peer(group_id, &new_mount_fs);
+        } else if let Some(master) = master {
+            master
+                .propagation()
+                .add_slave(Arc::downgrade(&new_mount_fs));
         }
 
         if parent_propagation.is_shared() {
@@ -581,7 +758,26 @@ impl MountFSInode {
     fn do_find(&self, name: &str) -> Result<Arc<MountFSInode>, SystemError> {
         // 直接调用当前inode所在的文件系统的find方法进行查找
         // 由于向下查找可能会跨越文件系统的边界，因此需要尝试替换inode
-        let inner_inode = self.inner_inode.find(name)?;
+        let inner_inode = match self.dcache_dir() {
+            Some((fs, dir)) => match dcache().lookup(&fs, dir, name) {
+                Some(result) => result?,
+                None => {
+                    let generation = dcache().generation();
+                    let result = self.inner_inode.find(name);
+                    match &result {
+                        Ok(inode) => {
+                            dcache().insert(&fs, dir, name, Some(inode.clone()), generation)
+                        }
+                        Err(SystemError::ENOENT) => {
+                            dcache().insert(&fs, dir, name, None, generation)
+                        }
+                        Err(_) => {}
+                    }
+                    result?
+                }
+            },
+            None => self.inner_inode.find(name)?,
+        };
         return Ok(Arc::new_cyclic(|self_ref| MountFSInode {
             inner_inode,
             mount_fs: self.mount_fs.clone(),
@@ -590,6 +786,63 @@ impl MountFSInode {
         .overlaid_inode());
     }
 
+    fn lazy_time_key(&self, inode_id: InodeId) -> LazyTimeKey {
+        LazyTimeKey::new(&self.mount_fs.inner_filesystem, inode_id)
+    }
+
+    fn flush_lazy_times(&self) {
+        if let Ok(md) = self.inner_inode.metadata() {
+            lazy_times().flush(&self.lazy_time_key(md.inode_id));
+        }
+    }
+
+    /// 文件或者目录被读取之后，按照挂载选项更新atime，参见[`super::timestamps`]
+    pub(super) fn touch_atime(&self) {
+        let mount_flags = self.mount_fs.mount_flags();
+        if mount_flags.intersects(MountFlags::NOATIME | MountFlags::RDONLY) {
+            return;
+        }
+        let Ok(mut md) = self.inner_inode.metadata() else {
+            return;
+        };
+        let key = self.lazy_time_key(md.inode_id);
+        lazy_times().apply(&key, &mut md);
+
+        let now = timestamp_truncate(
+            PosixTimeSpec::now(),
+            self.mount_fs.inner_filesystem.time_granularity(),
+        );
+        if !atime_need_update(mount_flags, &md, &now) {
+            return;
+        }
+        if mount_flags.contains(MountFlags::LAZYTIME)
+            && lazy_times().set_atime(key, &self.inner_inode, now)
+        {
+            return;
+        }
+        md.atime = now;
+        if self.inner_inode.set_metadata(&md).is_ok() {
+            lazy_times().forget(&key);
+        }
+    }
+
+    /// 当前目录在dcache中的(文件系统, inode号)，文件系统不使用dcache时返回None
+    fn dcache_dir(&self) -> Option<(Arc<dyn FileSystem>, InodeId)> {
+        let fs = self.mount_fs.inner_filesystem.clone();
+        if !fs.dcache_enabled() {
+            return None;
+        }
+        let dir = self.inner_inode.metadata().ok()?.inode_id;
+        Some((fs, dir))
+    }
+
+    /// 当前目录中的`name`被创建、删除或者重命名
+    fn dcache_invalidate(&self, name: &str) {
+        if let Some((fs, dir)) = self.dcache_dir() {
+            dcache().invalidate(&fs, dir, name);
+        }
+    }
+
     pub(super) fn do_parent(&self) -> Result<Arc<MountFSInode>, SystemError> {
         if self.is_mountpoint_root()? {
             // 当前inode是它所在的文件系统的root inode
@@ -739,6 +992,7 @@ impl IndexNode for MountFSInode {
     }
 
     fn sync(&self) -> Result<(), SystemError> {
+        self.flush_lazy_times();
         return self.inner_inode.sync();
     }
 
@@ -747,6 +1001,10 @@ impl IndexNode for MountFSInode {
         datasync: bool,
         data: MutexGuard<FilePrivateData>,
     ) -> Result<(), SystemError> {
+        // fdatasync不要求写回只有时间戳的修改
+        if !datasync {
+            self.flush_lazy_times();
+        }
         self.inner_inode.sync_file(datasync, data)
     }
 
@@ -774,7 +1032,9 @@ impl IndexNode for MountFSInode {
         self.ensure_mount_writable()?;
         let inner_inode = self
             .inner_inode
-            .create_with_data(name, file_type, mode, data)?;
+            .create_with_data(name, file_type, mode, data);
+        self.dcache_invalidate(name);
+        let inner_inode = inner_inode?;
         return Ok(Arc::new_cyclic(|self_ref| MountFSInode {
             inner_inode,
             mount_fs: self.mount_fs.clone(),
@@ -808,25 +1068,62 @@ impl IndexNode for MountFSInode {
         return self.inner_inode.write_at(offset, len, buf, data);
     }
 
-    fn read_direct(
+    fn read_direct(&self, offset: usize, len: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        self.inner_inode.read_direct(offset, len, buf)
+    }
+
+    fn write_direct(&self, offset: usize, len: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        self.ensure_mount_writable()?;
+        self.inner_inode.write_direct(offset, len, buf)
+    }
+
+    fn copy_file_range(
         &self,
-        offset: usize,
+        pos_in: usize,
+        dest: &Arc<dyn IndexNode>,
+        pos_out: usize,
         len: usize,
-        buf: &mut [u8],
-        data: MutexGuard<FilePrivateData>,
     ) -> Result<usize, SystemError> {
-        self.inner_inode.read_direct(offset, len, buf, data)
+        // 与link相同，文件系统期望拿到自己的inode，需要先解包目标文件的挂载包装
+        let dest_inner: Arc<dyn IndexNode> = match dest.clone().downcast_arc::<MountFSInode>() {
+            Some(mnt) => {
+                mnt.ensure_mount_writable()?;
+                mnt.inner_inode.clone()
+            }
+            None => dest.clone(),
+        };
+        self.inner_inode
+            .copy_file_range(pos_in, &dest_inner, pos_out, len)
+    }
+
+    fn kernel_read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, SystemError> {
+        self.inner_inode.kernel_read_at(offset, buf)
     }
 
-    fn write_direct(
+    fn kernel_write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, SystemError> {
+        self.ensure_mount_writable()?;
+        self.inner_inode.kernel_write_at(offset, buf)
+    }
+
+    fn read_direct_user(
         &self,
         offset: usize,
-        len: usize,
-        buf: &[u8],
-        data: MutexGuard<FilePrivateData>,
+        pages: &PinnedUserPages,
+    ) -> Result<usize, SystemError> {
+        self.inner_inode.read_direct_user(offset, pages)
+    }
+
+    fn write_direct_user(
+        &self,
+        offset: usize,
+        pages: &PinnedUserPages,
     ) -> Result<usize, SystemError> {
         self.ensure_mount_writable()?;
-        self.inner_inode.write_direct(offset, len, buf, data)
+        self.inner_inode.write_direct_user(offset, pages)
+    }
+
+    fn direct_io_alignment(&self) -> Option<usize> {
+        self.inner_inode.direct_io_alignment()
     }
 
     #[inline]
@@ -850,6 +1147,7 @@ impl IndexNode for MountFSInode {
             let minor = (mnt_id as u32) & DeviceNumber::MINOR_MASK;
             md.dev_id = DeviceNumber::new(Major::UNNAMED_MAJOR, minor).data() as usize;
         }
+        lazy_times().apply(&self.lazy_time_key(md.inode_id), &mut md);
 
         Ok(md)
     }
@@ -857,7 +1155,10 @@ impl IndexNode for MountFSInode {
     #[inline]
     fn set_metadata(&self, metadata: &super::Metadata) -> Result<(), SystemError> {
         self.ensure_mount_writable()?;
-        return self.inner_inode.set_metadata(metadata);
+        self.inner_inode.set_metadata(metadata)?;
+        // 元数据已经整体写回，其中包含了内存中的时间戳
+        lazy_times().forget(&self.lazy_time_key(metadata.inode_id));
+        Ok(())
     }
 
     #[inline]
@@ -866,6 +1167,12 @@ impl IndexNode for MountFSInode {
         return self.inner_inode.resize(len);
     }
 
+    #[inline]
+    fn punch_hole(&self, offset: usize, len: usize) -> Result<(), SystemError> {
+        self.ensure_mount_writable()?;
+        return self.inner_inode.punch_hole(offset, len);
+    }
+
     #[inline]
     fn create(
         &self,
@@ -874,7 +1181,9 @@ impl IndexNode for MountFSInode {
         mode: InodeMode,
     ) -> Result<Arc<dyn IndexNode>, SystemError> {
         self.ensure_mount_writable()?;
-        let inner_inode = self.inner_inode.create(name, file_type, mode)?;
+        let inner_inode = self.inner_inode.create(name, file_type, mode);
+        self.dcache_invalidate(name);
+        let inner_inode = inner_inode?;
         return Ok(Arc::new_cyclic(|self_ref| MountFSInode {
             inner_inode,
             mount_fs: self.mount_fs.clone(),
@@ -894,12 +1203,16 @@ impl IndexNode for MountFSInode {
             .map(|mnt| mnt.inner_inode.clone())
             .unwrap_or_else(|| other.clone());
 
-        return self.inner_inode.link(name, &other_inner);
+        let r = self.inner_inode.link(name, &other_inner);
+        self.dcache_invalidate(name);
+        return r;
     }
 
     fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
         self.ensure_mount_writable()?;
-        let inner_inode = self.inner_inode.symlink(name, target)?;
+        let inner_inode = self.inner_inode.symlink(name, target);
+        self.dcache_invalidate(name);
+        let inner_inode = inner_inode?;
         Ok(Arc::new_cyclic(|self_ref| MountFSInode {
             inner_inode,
             mount_fs: self.mount_fs.clone(),
@@ -918,7 +1231,9 @@ impl IndexNode for MountFSInode {
             return Err(SystemError::EBUSY);
         }
         // 调用内层的inode的方法来删除这个inode
-        return self.inner_inode.unlink(name);
+        let r = self.inner_inode.unlink(name);
+        self.dcache_invalidate(name);
+        return r;
     }
 
     #[inline]
@@ -932,6 +1247,13 @@ impl IndexNode for MountFSInode {
         }
         // 调用内层的rmdir的方法来删除这个inode
         let r = self.inner_inode.rmdir(name);
+        self.dcache_invalidate(name);
+        if r.is_ok() {
+            // 目录的inode号可能被重用，它下面的项不能再命中
+            if let Some((fs, _)) = self.dcache_dir() {
+                dcache().invalidate_dir(&fs, inode_id);
+            }
+        }
 
         return r;
     }
@@ -951,15 +1273,29 @@ impl IndexNode for MountFSInode {
         // would make FS-level downcasts fail and incorrectly return EINVAL.
         //
         // So we unwrap the mount wrapper before delegating.
-        let target_inner: Arc<dyn IndexNode> = target
-            .clone()
-            .downcast_arc::<MountFSInode>()
+        let target_mnt = target.clone().downcast_arc::<MountFSInode>();
+        let target_inner: Arc<dyn IndexNode> = target_mnt
+            .as_ref()
             .map(|mnt| mnt.inner_inode.clone())
             .unwrap_or_else(|| target.clone());
 
-        return self
+        // 被覆盖的目录会被删除，它下面的项不能再命中
+        let replaced_dir = self.dcache_dir().and_then(|_| {
+            let md = target_inner.find(new_name).ok()?.metadata().ok()?;
+            (md.file_type == FileType::Dir).then_some(md.inode_id)
+        });
+
+        let r = self
             .inner_inode
             .move_to(old_name, &target_inner, new_name, flags);
+        self.dcache_invalidate(old_name);
+        if let Some(target_mnt) = &target_mnt {
+            target_mnt.dcache_invalidate(new_name);
+        }
+        if let (Ok(()), Some((fs, _)), Some(dir)) = (&r, self.dcache_dir(), replaced_dir) {
+            dcache().invalidate_dir(&fs, dir);
+        }
+        return r;
     }
 
     fn check_access(
@@ -1014,6 +1350,10 @@ impl IndexNode for MountFSInode {
         return self.inner_inode.list();
     }
 
+    fn list_cookies(&self) -> Result<Vec<DirCookie>, SystemError> {
+        self.inner_inode.list_cookies()
+    }
+
     fn mount(
         &self,
         fs: Arc<dyn FileSystem>,
@@ -1028,7 +1368,7 @@ impl IndexNode for MountFSInode {
                 (fs, root_inner_inode)
             });
 
-        self.mount_subtree(to_mount_fs, root_inner_inode, mount_flags)
+        self.mount_subtree(to_mount_fs, root_inner_inode, mount_flags, None)
     }
 
     fn mount_from(&self, from: Arc<dyn IndexNode>) -> Result<Arc<MountFS>, SystemError> {
@@ -1086,7 +1426,9 @@ impl IndexNode for MountFSInode {
         dev_t: DeviceNumber,
     ) -> Result<Arc<dyn IndexNode>, SystemError> {
         self.ensure_mount_writable()?;
-        let inner_inode = self.inner_inode.mknod(filename, mode, dev_t)?;
+        let inner_inode = self.inner_inode.mknod(filename, mode, dev_t);
+        self.dcache_invalidate(filename);
+        let inner_inode = inner_inode?;
         return Ok(Arc::new_cyclic(|self_ref| MountFSInode {
             inner_inode,
             mount_fs: self.mount_fs.clone(),
@@ -1141,12 +1483,25 @@ impl IndexNode for MountFSInode {
         self.ensure_mount_writable()?;
         self.inner_inode.setxattr(name, value)
     }
+
+    fn listxattr(&self) -> Result<Vec<String>, SystemError> {
+        self.inner_inode.listxattr()
+    }
+
+    fn removexattr(&self, name: &str) -> Result<(), SystemError> {
+        self.ensure_mount_writable()?;
+        self.inner_inode.removexattr(name)
+    }
 }
 
 impl FileSystem for MountFS {
     fn support_readahead(&self) -> bool {
         self.inner_filesystem.support_readahead()
     }
+
+    fn time_granularity(&self) -> u32 {
+        self.inner_filesystem.time_granularity()
+    }
     fn root_inode(&self) -> Arc<dyn IndexNode> {
         match self.self_mountpoint() {
             Some(inode) => return inode.mount_fs.root_inode(),
@@ -1360,6 +1715,17 @@ impl MountList {
             .next()
     }
 
+    /// 获取挂载在`path`上的最上层的文件系统
+    pub fn get<T: Into<MountPath>>(&self, path: T) -> Option<Arc<MountFS>> {
+        let path: MountPath = path.into();
+        self.inner
+            .read()
+            .mounts
+            .get(&path)
+            .and_then(|stack| stack.last())
+            .map(|rec| rec.fs.clone())
+    }
+
     /// # remove - 移除挂载点
     ///
     /// 从挂载点管理器中移除一个挂载点。
@@ -0,0 +1,204 @@
+//! 挂载选项解析
+//!
+//! mount(2)的`data`参数是以逗号分隔的`key=value`或者`key`形式的选项。
+//! 文件系统用[`MountOptionSpec`]声明自己认识的选项及其值的类型，
+//! [`MountOptions::parse`]按声明解析出类型化的选项表，并把不认识的选项原样留下，
+//! 由文件系统决定拒绝（[`MountOptions::reject_unknown`]）还是忽略（[`MountOptions::warn_unknown`]）。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/fs/fs_parser.c
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+use system_error::SystemError;
+
+/// 选项值的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MountOptionKind {
+    /// 不带值的开关，如`newinstance`
+    Flag,
+    /// 十进制的u32，如`uid=1000`
+    U32,
+    /// 八进制的权限位，如`mode=755`
+    Mode,
+    /// 带有k/m/g后缀（不区分大小写）的大小，如`nr_inodes=1k`
+    Size,
+    /// 原样保存的字符串，如`lowerdir=/a:/b`
+    Str,
+}
+
+/// 文件系统认识的一个挂载选项
+#[derive(Debug, Clone, Copy)]
+pub struct MountOptionSpec {
+    pub name: &'static str,
+    pub kind: MountOptionKind,
+}
+
+impl MountOptionSpec {
+    pub const fn new(name: &'static str, kind: MountOptionKind) -> Self {
+        Self { name, kind }
+    }
+}
+
+/// 解析后的选项值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MountOptionValue {
+    Flag,
+    U32(u32),
+    Mode(u32),
+    Size(u64),
+    Str(String),
+}
+
+/// 解析后的挂载选项
+#[derive(Debug, Default)]
+pub struct MountOptions {
+    /// 认识的选项，同一个选项出现多次时以最后一次为准
+    values: BTreeMap<&'static str, MountOptionValue>,
+    /// 不认识的选项，以逗号连接
+    unknown: String,
+}
+
+impl MountOptions {
+    /// 按`specs`解析挂载选项
+    ///
+    /// ## 参数
+    /// - `fs_name`: 文件系统的名字，用于错误信息
+    /// - `raw`: mount(2)传入的选项字符串
+    /// - `specs`: 文件系统认识的选项
+    ///
+    /// ## 返回值
+    /// - `Err(EINVAL)`: 认识的选项缺少值、不应带值却带了值，或者值的格式不对
+    pub fn parse(
+        fs_name: &str,
+        raw: Option<&str>,
+        specs: &[MountOptionSpec],
+    ) -> Result<Self, SystemError> {
+        let mut opts = Self::default();
+        let Some(raw) = raw else {
+            return Ok(opts);
+        };
+
+        for item in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = match item.split_once('=') {
+                Some((k, v)) => (k.trim(), Some(v.trim())),
+                None => (item, None),
+            };
+            let Some(spec) = specs.iter().find(|spec| spec.name == key) else {
+                if !opts.unknown.is_empty() {
+                    opts.unknown.push(',');
+                }
+                opts.unknown.push_str(item);
+                continue;
+            };
+            let parsed = Self::parse_value(spec, value).inspect_err(|_| match value {
+                Some(v) => log::warn!("{}: bad value '{}' for mount option '{}'", fs_name, v, key),
+                None => log::warn!("{}: mount option '{}' requires a value", fs_name, key),
+            })?;
+            opts.values.insert(spec.name, parsed);
+        }
+        Ok(opts)
+    }
+
+    fn parse_value(
+        spec: &MountOptionSpec,
+        value: Option<&str>,
+    ) -> Result<MountOptionValue, SystemError> {
+        if spec.kind == MountOptionKind::Flag {
+            return match value {
+                None => Ok(MountOptionValue::Flag),
+                Some(_) => Err(SystemError::EINVAL),
+            };
+        }
+        let value = value.ok_or(SystemError::EINVAL)?;
+        match spec.kind {
+            MountOptionKind::U32 => value
+                .parse::<u32>()
+                .map(MountOptionValue::U32)
+                .map_err(|_| SystemError::EINVAL),
+            MountOptionKind::Mode => u32::from_str_radix(value, 8)
+                .map(MountOptionValue::Mode)
+                .map_err(|_| SystemError::EINVAL),
+            MountOptionKind::Size => parse_size(value).map(MountOptionValue::Size),
+            MountOptionKind::Str => Ok(MountOptionValue::Str(value.to_string())),
+            MountOptionKind::Flag => unreachable!(),
+        }
+    }
+
+    /// 选项是否出现过
+    #[allow(dead_code)]
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        matches!(self.values.get(name), Some(MountOptionValue::Flag))
+    }
+
+    pub fn u32(&self, name: &str) -> Option<u32> {
+        match self.values.get(name) {
+            Some(MountOptionValue::U32(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn mode(&self, name: &str) -> Option<u32> {
+        match self.values.get(name) {
+            Some(MountOptionValue::Mode(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn size(&self, name: &str) -> Option<u64> {
+        match self.values.get(name) {
+            Some(MountOptionValue::Size(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn str(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(MountOptionValue::Str(v)) => Some(v.as_str()),
+            _ => None,
+        }
+    }
+
+    /// 不认识的选项，以逗号连接
+    #[allow(dead_code)]
+    pub fn unknown(&self) -> &str {
+        &self.unknown
+    }
+
+    /// 有不认识的选项时返回`EINVAL`
+    pub fn reject_unknown(&self, fs_name: &str) -> Result<(), SystemError> {
+        if self.unknown.is_empty() {
+            return Ok(());
+        }
+        log::warn!("{}: unknown mount options '{}'", fs_name, self.unknown);
+        Err(SystemError::EINVAL)
+    }
+
+    /// 忽略不认识的选项，只打印警告
+    pub fn warn_unknown(&self, fs_name: &str) {
+        if !self.unknown.is_empty() {
+            log::warn!(
+                "{}: ignoring unknown mount options '{}'",
+                fs_name,
+                self.unknown
+            );
+        }
+    }
+}
+
+/// 解析带有k/m/g后缀（不区分大小写）的大小
+pub fn parse_size(value: &str) -> Result<u64, SystemError> {
+    let (num, shift) = match value.as_bytes().last() {
+        Some(b'k' | b'K') => (&value[..value.len() - 1], 10),
+        Some(b'm' | b'M') => (&value[..value.len() - 1], 20),
+        Some(b'g' | b'G') => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    let base = num.parse::<u64>().map_err(|_| SystemError::EINVAL)?;
+    Ok(base.saturating_mul(1u64 << shift))
+}
@@ -4,12 +4,15 @@ use system_error::SystemError;
 use super::{
     fcntl::AtFlags,
     file::{File, FileFlags},
+    mount::{inode_mount_flags, MountFlags},
     permission::PermissionMask,
     syscall::{OpenHow, OpenHowResolve},
+    timestamps::current_time,
     utils::{rsplit_path, should_remove_sgid_on_chown, user_path_at},
     vcore::{check_parent_dir_permission_inode, resolve_parent_inode},
     FileType, FsPermissionPolicy, IndexNode, InodeMode, MAX_PATHLEN, VFS_MAX_FOLLOW_SYMLINK_TIMES,
 };
+use crate::filesystem::notify::{fsnotify_create, fsnotify_modify};
 use crate::{filesystem::vfs::syscall::UtimensFlags, process::cred::Kgid};
 use crate::{
     process::cred::CAPFlags,
@@ -84,6 +87,23 @@ pub(super) fn do_faccessat(
     }
 
     let metadata = inode.metadata()?;
+    let mnt_flags = inode_mount_flags(&inode);
+    // 与Linux相同，只读挂载上的文件、目录不可写，noexec挂载上的普通文件不可执行
+    if mask.contains(PermissionMask::MAY_WRITE)
+        && mnt_flags.contains(MountFlags::RDONLY)
+        && matches!(
+            metadata.file_type,
+            FileType::File | FileType::Dir | FileType::SymLink
+        )
+    {
+        return Err(SystemError::EROFS);
+    }
+    if mask.contains(PermissionMask::MAY_EXEC)
+        && mnt_flags.contains(MountFlags::NOEXEC)
+        && metadata.file_type == FileType::File
+    {
+        return Err(SystemError::EACCES);
+    }
     match inode.fs().permission_policy() {
         FsPermissionPolicy::Dac => {
             super::permission::check_inode_permission(&inode, &metadata, mask)?;
@@ -249,6 +269,28 @@ pub fn do_sys_open(
 
 fn do_sys_openat2(dirfd: i32, path: &str, how: OpenHow) -> Result<usize, SystemError> {
     // log::debug!("openat2: dirfd: {}, path: {}, how: {:?}",dirfd, path, how);
+    let file = do_filp_open(dirfd, path, &how)?;
+    let cloexec = how.o_flags.contains(FileFlags::O_CLOEXEC);
+
+    // 把文件对象存入pcb
+    let r = ProcessManager::current_pcb()
+        .fd_table()
+        .write()
+        .alloc_fd(file, None, cloexec)
+        .map(|fd| fd as usize);
+
+    return r;
+}
+
+/// 按路径打开文件，返回文件对象但不分配文件描述符
+///
+/// 包含open(2)的路径解析、O_CREAT创建、权限检查和O_TRUNC截断。
+///
+/// ## 参数
+/// - `dirfd`: 相对路径的起点目录
+/// - `path`: 文件路径
+/// - `how`: 打开方式
+pub fn do_filp_open(dirfd: i32, path: &str, how: &OpenHow) -> Result<File, SystemError> {
     let path = path.trim();
     let follow_symlink = !how.o_flags.contains(FileFlags::O_NOFOLLOW);
     // 检查空字符串路径
@@ -299,6 +341,7 @@ fn do_sys_openat2(dirfd: i32, path: &str, how: OpenHow) -> Result<usize, SystemE
                 // 创建文件
                 let inode: Arc<dyn IndexNode> =
                     parent_inode.create(filename, FileType::File, create_mode)?;
+                fsnotify_create(&parent_inode, filename, false);
                 created = true;
                 inode
             } else {
@@ -353,6 +396,27 @@ fn do_sys_openat2(dirfd: i32, path: &str, how: OpenHow) -> Result<usize, SystemE
             return Err(SystemError::EINVAL);
         }
     }
+    // 挂载标志检查，O_PATH只引用路径，不受限制
+    if !how.o_flags.contains(FileFlags::O_PATH) {
+        let mnt_flags = inode_mount_flags(&inode);
+        // nodev挂载上的设备文件不能打开
+        if matches!(file_type, FileType::CharDevice | FileType::BlockDevice)
+            && mnt_flags.contains(MountFlags::NODEV)
+        {
+            return Err(SystemError::EACCES);
+        }
+        // 只读挂载上的文件不能以写方式打开。设备文件、管道的写入不修改文件系统，不受限制
+        let acc_mode = how.o_flags.access_flags();
+        let wants_write = acc_mode == FileFlags::O_WRONLY
+            || acc_mode == FileFlags::O_RDWR
+            || how.o_flags.contains(FileFlags::O_TRUNC);
+        if wants_write
+            && mnt_flags.contains(MountFlags::RDONLY)
+            && matches!(file_type, FileType::File | FileType::Dir)
+        {
+            return Err(SystemError::EROFS);
+        }
+    }
     // 非 O_PATH 需要检查访问权限（read/write/truncate）
     // Linux 语义：若本次 open() 触发了创建，则不应因“新 inode 的 mode”而拒绝
     // 当前这次 open() 的访问模式；权限在“后续 reopen()”时生效。
@@ -384,18 +448,9 @@ fn do_sys_openat2(dirfd: i32, path: &str, how: OpenHow) -> Result<usize, SystemE
     // 例如：open(file, O_RDONLY | O_TRUNC) 是合法的，只要用户对文件有写权限
     if how.o_flags.contains(FileFlags::O_TRUNC) && file_type == FileType::File {
         inode.resize(0)?;
+        fsnotify_modify(&inode);
     }
-    let file: File = File::new(inode, how.o_flags)?;
-    let cloexec = how.o_flags.contains(FileFlags::O_CLOEXEC);
-
-    // 把文件对象存入pcb
-    let r = ProcessManager::current_pcb()
-        .fd_table()
-        .write()
-        .alloc_fd(file, None, cloexec)
-        .map(|fd| fd as usize);
-
-    return r;
+    File::new(inode, how.o_flags)
 }
 
 /// 为exec打开可执行文件
@@ -426,6 +481,10 @@ pub fn do_open_execat(dirfd: i32, path: &str) -> Result<Arc<File>, SystemError>
     if file_type != FileType::File {
         return Err(SystemError::EACCES);
     }
+    // noexec挂载上的文件不能执行
+    if inode_mount_flags(&inode).contains(MountFlags::NOEXEC) {
+        return Err(SystemError::EACCES);
+    }
 
     super::permission::check_inode_permission(&inode, &metadata, PermissionMask::MAY_EXEC)?;
     super::permission::check_inode_permission(&inode, &metadata, PermissionMask::MAY_READ)?;
@@ -496,7 +555,7 @@ pub fn do_utimensat(
             file.inode()
         }
     };
-    let now = PosixTimeSpec::now();
+    let now = current_time(&inode);
     let mut meta = inode.metadata()?;
 
     if let Some([atime, mtime]) = times {
@@ -534,7 +593,7 @@ pub fn do_utimes(path: &str, times: Option<[PosixTimeval; 2]>) -> Result<usize,
         meta.mtime = PosixTimeSpec::from(mtime);
         inode.set_metadata(&meta)?;
     } else {
-        let now = PosixTimeSpec::now();
+        let now = current_time(&inode);
         meta.atime = now;
         meta.mtime = now;
         inode.set_metadata(&meta)?;
@@ -1,3 +1,4 @@
+use crate::filesystem::notify::fsnotify_create;
 use crate::filesystem::vfs::permission::PermissionMask;
 use crate::filesystem::vfs::syscall::AtFlags;
 use crate::filesystem::vfs::utils::rsplit_path;
@@ -96,7 +97,9 @@ pub fn do_linkat(
         return Err(SystemError::EXDEV);
     }
 
-    return new_parent.link(new_name, &old_inode).map(|_| 0);
+    new_parent.link(new_name, &old_inode)?;
+    fsnotify_create(&new_parent, new_name, false);
+    return Ok(0);
 }
 
 /// 检查是否允许创建硬链接（对应Linux的may_linkat）
@@ -31,6 +31,9 @@ mod sys_fstatfs;
 mod sys_ftruncate;
 mod sys_getcwd;
 mod sys_getdents;
+mod sys_inotify_add_watch;
+pub mod sys_inotify_init1;
+mod sys_inotify_rm_watch;
 mod sys_ioctl;
 mod sys_linkat;
 mod sys_lseek;
@@ -91,6 +94,8 @@ mod sys_dup2;
 mod sys_epoll_create;
 #[cfg(target_arch = "x86_64")]
 mod sys_epoll_wait;
+#[cfg(target_arch = "x86_64")]
+mod sys_inotify_init;
 
 #[cfg(target_arch = "x86_64")]
 mod sys_creat;
@@ -128,10 +133,16 @@ mod sys_unlink;
 mod sys_utimes;
 
 mod sys_fgetxattr;
+mod sys_flistxattr;
+mod sys_fremovexattr;
 mod sys_fsetxattr;
 mod sys_getxattr;
 mod sys_lgetxattr;
+mod sys_listxattr;
+mod sys_llistxattr;
+mod sys_lremovexattr;
 mod sys_lsetxattr;
+mod sys_removexattr;
 mod sys_setxattr;
 mod xattr_utils;
 
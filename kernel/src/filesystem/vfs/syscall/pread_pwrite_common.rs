@@ -3,7 +3,10 @@ use system_error::SystemError;
 
 use crate::{
     filesystem::vfs::file::File,
-    mm::VirtAddr,
+    mm::{
+        gup::{pin_user_pages, PinnedUserPages},
+        VirtAddr,
+    },
     syscall::user_access::{copy_from_user_protected, copy_to_user_protected, user_accessible_len},
 };
 
@@ -12,6 +15,47 @@ pub(super) enum PreadPwriteDir {
     Write,
 }
 
+/// O_DIRECT每次固定的用户缓冲区大小，是各种对齐要求的整数倍
+const DIRECT_IO_PIN_CHUNK: usize = 1024 * 1024;
+
+/// # O_DIRECT读写用户缓冲区
+///
+/// 分段固定住用户缓冲区，交给`io`让设备直接读写用户页，不经过内核缓冲区。
+/// 与缓冲读写一样，已经传输了一部分数据之后出错时返回已经传输的字节数
+///
+/// ## 参数
+/// - `dir`: 读文件时设备写入用户页，固定时按写访问处理缺页
+/// - `io`: 参数为这一段在整个缓冲区中的偏移量和固定住的这一段，返回传输的字节数
+pub(super) fn do_direct_user_io<F>(
+    user_ptr: usize,
+    len: usize,
+    dir: &PreadPwriteDir,
+    mut io: F,
+) -> Result<usize, SystemError>
+where
+    F: FnMut(usize, &PinnedUserPages) -> Result<usize, SystemError>,
+{
+    let mut total: usize = 0;
+    while total < len {
+        let want = core::cmp::min(DIRECT_IO_PIN_CHUNK, len - total);
+        let write = matches!(dir, PreadPwriteDir::Read);
+        let r = pin_user_pages(VirtAddr::new(user_ptr + total), want, write)
+            .and_then(|pages| io(total, &pages));
+        match r {
+            Ok(n) => {
+                total += n;
+                // 文件末尾或者短写
+                if n < want {
+                    break;
+                }
+            }
+            Err(e) if total == 0 => return Err(e),
+            Err(_) => break,
+        }
+    }
+    Ok(total)
+}
+
 /// Common implementation for pread64/pwrite64 with Linux-compatible "partial bad buffer" semantics.
 ///
 /// Key rules:
@@ -41,6 +85,15 @@ pub(super) fn do_pread_pwrite_at(
     if from_user && user_ptr == 0 {
         return Err(SystemError::EFAULT);
     }
+    if from_user {
+        file.check_direct_io_buffer(user_ptr)?;
+        if file.is_direct_io() {
+            return do_direct_user_io(user_ptr, len, &dir, |done, pages| match dir {
+                PreadPwriteDir::Read => file.pread_pinned(offset + done, pages),
+                PreadPwriteDir::Write => file.pwrite_pinned(offset + done, pages),
+            });
+        }
+    }
 
     const CHUNK: usize = 64 * 1024;
     let mut total: usize = 0;
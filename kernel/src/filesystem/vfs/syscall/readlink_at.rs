@@ -1,10 +1,7 @@
 use system_error::SystemError;
 
 use crate::{
-    filesystem::vfs::{
-        utils::user_path_at, FilePrivateData, FileType, MAX_PATHLEN, VFS_MAX_FOLLOW_SYMLINK_TIMES,
-    },
-    libs::mutex::Mutex,
+    filesystem::vfs::{utils::user_path_at, FileType, MAX_PATHLEN, VFS_MAX_FOLLOW_SYMLINK_TIMES},
     process::ProcessManager,
     syscall::user_access::{check_and_clone_cstr, UserBufferWriter},
 };
@@ -30,12 +27,7 @@ pub fn do_readlink_at(
 
     let ubuf = user_buf.buffer::<u8>(0).unwrap();
 
-    let len = inode.read_at(
-        0,
-        buf_size,
-        ubuf,
-        Mutex::new(FilePrivateData::Unused).lock(),
-    )?;
+    let len = inode.kernel_read_at(0, ubuf)?;
 
     return Ok(len);
 }
@@ -1,3 +1,4 @@
+use crate::filesystem::notify::{fsnotify_move, fsnotify_victim};
 use crate::filesystem::vfs::permission::PermissionMask;
 use crate::filesystem::vfs::syscall::RenameFlags;
 use crate::filesystem::vfs::utils::is_ancestor;
@@ -112,6 +113,39 @@ pub fn do_renameat2(
         PermissionMask::MAY_WRITE | PermissionMask::MAY_EXEC,
     )?;
 
+    let replaced = new_parent_inode.find(new_filename).ok();
+    let victim = replaced.as_ref().map(fsnotify_victim);
+
     old_parent_inode.move_to(old_filename, &new_parent_inode, new_filename, flags)?;
+
+    if flags.contains(RenameFlags::EXCHANGE) {
+        fsnotify_move(
+            &old_parent_inode,
+            old_filename,
+            &new_parent_inode,
+            new_filename,
+            &old_inode,
+            None,
+        );
+        if let Some(replaced) = &replaced {
+            fsnotify_move(
+                &new_parent_inode,
+                new_filename,
+                &old_parent_inode,
+                old_filename,
+                replaced,
+                None,
+            );
+        }
+    } else {
+        fsnotify_move(
+            &old_parent_inode,
+            old_filename,
+            &new_parent_inode,
+            new_filename,
+            &old_inode,
+            replaced.as_ref().zip(victim),
+        );
+    }
     return Ok(0);
 }
@@ -1,6 +1,7 @@
 use system_error::SystemError;
 
 use crate::{
+    filesystem::notify::fsnotify_create,
     filesystem::vfs::{
         fcntl::AtFlags,
         utils::{rsplit_path, user_path_at},
@@ -50,6 +51,7 @@ pub fn do_symlinkat(from: &str, newdfd: Option<i32>, to: &str) -> Result<usize,
     }
 
     new_parent.symlink(new_name, from)?;
+    fsnotify_create(&new_parent, new_name, false);
 
     return Ok(0);
 }
@@ -0,0 +1,106 @@
+//! System call handler for inotify_add_watch.
+
+use crate::arch::interrupt::TrapFrame;
+use crate::arch::syscall::nr::SYS_INOTIFY_ADD_WATCH;
+use crate::filesystem::notify::inotify::{InotifyInode, InotifyMask};
+use crate::filesystem::vfs::fcntl::AtFlags;
+use crate::filesystem::vfs::permission::PermissionMask;
+use crate::filesystem::vfs::utils::user_path_at;
+use crate::filesystem::vfs::{FileType, MAX_PATHLEN, VFS_MAX_FOLLOW_SYMLINK_TIMES};
+use crate::libs::casting::DowncastArc;
+use crate::process::ProcessManager;
+use crate::syscall::table::{FormattedSyscallParam, Syscall};
+use crate::syscall::user_access::vfs_check_and_clone_cstr;
+use alloc::vec::Vec;
+use system_error::SystemError;
+
+pub struct SysInotifyAddWatchHandle;
+
+impl SysInotifyAddWatchHandle {
+    fn fd(args: &[usize]) -> i32 {
+        args[0] as i32
+    }
+
+    fn path(args: &[usize]) -> *const u8 {
+        args[1] as *const u8
+    }
+
+    fn mask(args: &[usize]) -> u32 {
+        args[2] as u32
+    }
+}
+
+impl Syscall for SysInotifyAddWatchHandle {
+    fn num_args(&self) -> usize {
+        3
+    }
+
+    /// 为inotify实例添加或修改对某个路径的监视
+    ///
+    /// ## 参数
+    /// - `fd`: inotify实例的文件描述符
+    /// - `pathname`: 要监视的路径
+    /// - `mask`: 要监视的事件以及`IN_ONLYDIR`、`IN_DONT_FOLLOW`、`IN_MASK_ADD`等标志
+    ///
+    /// ## 返回值
+    /// - 成功：监视描述符
+    fn handle(&self, args: &[usize], _frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        let mask = InotifyMask::from_bits_truncate(Self::mask(args));
+        if !mask.intersects(InotifyMask::IN_ALL_EVENTS) {
+            return Err(SystemError::EINVAL);
+        }
+        if mask.contains(InotifyMask::IN_MASK_ADD | InotifyMask::IN_MASK_CREATE) {
+            return Err(SystemError::EINVAL);
+        }
+
+        let file = ProcessManager::current_pcb()
+            .fd_table()
+            .read()
+            .get_file_by_fd(Self::fd(args))
+            .ok_or(SystemError::EBADF)?;
+        let instance = file
+            .inode()
+            .downcast_arc::<InotifyInode>()
+            .ok_or(SystemError::EINVAL)?;
+
+        let path = vfs_check_and_clone_cstr(Self::path(args), Some(MAX_PATHLEN))?
+            .into_string()
+            .map_err(|_| SystemError::EINVAL)?;
+        if path.is_empty() {
+            return Err(SystemError::ENOENT);
+        }
+        let (inode_begin, rest) = user_path_at(
+            &ProcessManager::current_pcb(),
+            AtFlags::AT_FDCWD.bits(),
+            &path,
+        )?;
+        let inode = inode_begin.lookup_follow_symlink2(
+            &rest,
+            VFS_MAX_FOLLOW_SYMLINK_TIMES,
+            !mask.contains(InotifyMask::IN_DONT_FOLLOW),
+        )?;
+
+        let md = inode.metadata()?;
+        if mask.contains(InotifyMask::IN_ONLYDIR) && md.file_type != FileType::Dir {
+            return Err(SystemError::ENOTDIR);
+        }
+        // Linux 语义：监视需要对目标拥有读权限
+        crate::filesystem::vfs::permission::check_inode_permission(
+            &inode,
+            &md,
+            PermissionMask::MAY_READ,
+        )?;
+
+        instance.add_watch(&inode, mask).map(|wd| wd as usize)
+    }
+
+    fn entry_format(&self, args: &[usize]) -> Vec<FormattedSyscallParam> {
+        vec![
+            FormattedSyscallParam::new("fd", format!("{}", Self::fd(args))),
+            FormattedSyscallParam::new("pathname", format!("{:#x}", Self::path(args) as usize)),
+            FormattedSyscallParam::new("mask", format!("{:#x}", Self::mask(args))),
+        ]
+    }
+}
+
+syscall_table_macros::declare_syscall!(SYS_INOTIFY_ADD_WATCH, SysInotifyAddWatchHandle);
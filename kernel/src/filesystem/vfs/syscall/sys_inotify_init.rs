@@ -0,0 +1,27 @@
+//! System call handler for inotify_init.
+
+use crate::arch::interrupt::TrapFrame;
+use crate::arch::syscall::nr::SYS_INOTIFY_INIT;
+use crate::filesystem::vfs::syscall::sys_inotify_init1::do_inotify_init;
+use crate::syscall::table::{FormattedSyscallParam, Syscall};
+use alloc::vec::Vec;
+use system_error::SystemError;
+
+pub struct SysInotifyInitHandle;
+
+impl Syscall for SysInotifyInitHandle {
+    fn num_args(&self) -> usize {
+        0
+    }
+
+    /// 创建一个inotify实例，等价于`inotify_init1(0)`
+    fn handle(&self, _args: &[usize], _frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        do_inotify_init(0)
+    }
+
+    fn entry_format(&self, _args: &[usize]) -> Vec<FormattedSyscallParam> {
+        vec![]
+    }
+}
+
+syscall_table_macros::declare_syscall!(SYS_INOTIFY_INIT, SysInotifyInitHandle);
@@ -0,0 +1,67 @@
+//! System call handler for inotify_init1.
+
+use crate::arch::interrupt::TrapFrame;
+use crate::arch::syscall::nr::SYS_INOTIFY_INIT1;
+use crate::filesystem::notify::inotify::{InotifyInitFlags, InotifyInode};
+use crate::filesystem::vfs::file::{File, FileFlags};
+use crate::process::ProcessManager;
+use crate::syscall::table::{FormattedSyscallParam, Syscall};
+use alloc::vec::Vec;
+use system_error::SystemError;
+
+pub struct SysInotifyInit1Handle;
+
+impl SysInotifyInit1Handle {
+    fn flags(args: &[usize]) -> u32 {
+        args[0] as u32
+    }
+}
+
+impl Syscall for SysInotifyInit1Handle {
+    fn num_args(&self) -> usize {
+        1
+    }
+
+    /// 创建一个inotify实例
+    ///
+    /// ## 参数
+    /// - `flags`: `IN_NONBLOCK`、`IN_CLOEXEC`
+    ///
+    /// ## 返回值
+    /// - 成功：inotify实例的文件描述符
+    fn handle(&self, args: &[usize], _frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        do_inotify_init(Self::flags(args))
+    }
+
+    fn entry_format(&self, args: &[usize]) -> Vec<FormattedSyscallParam> {
+        vec![FormattedSyscallParam::new(
+            "flags",
+            format!("{:#x}", Self::flags(args)),
+        )]
+    }
+}
+
+syscall_table_macros::declare_syscall!(SYS_INOTIFY_INIT1, SysInotifyInit1Handle);
+
+/// 创建一个inotify实例，并为它分配文件描述符
+///
+/// See: https://man7.org/linux/man-pages/man2/inotify_init1.2.html
+pub fn do_inotify_init(flags: u32) -> Result<usize, SystemError> {
+    let flags = InotifyInitFlags::from_bits(flags).ok_or(SystemError::EINVAL)?;
+    let nonblock = flags.contains(InotifyInitFlags::IN_NONBLOCK);
+    let cloexec = flags.contains(InotifyInitFlags::IN_CLOEXEC);
+
+    let mut file_flags = FileFlags::O_RDONLY;
+    if nonblock {
+        file_flags |= FileFlags::O_NONBLOCK;
+    }
+    if cloexec {
+        file_flags |= FileFlags::O_CLOEXEC;
+    }
+    let file = File::new(InotifyInode::new(nonblock), file_flags)?;
+    let binding = ProcessManager::current_pcb().fd_table();
+    let mut fd_table_guard = binding.write();
+    fd_table_guard
+        .alloc_fd(file, None, cloexec)
+        .map(|fd| fd as usize)
+}
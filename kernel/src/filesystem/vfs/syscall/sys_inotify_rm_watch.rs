@@ -0,0 +1,55 @@
+//! System call handler for inotify_rm_watch.
+
+use crate::arch::interrupt::TrapFrame;
+use crate::arch::syscall::nr::SYS_INOTIFY_RM_WATCH;
+use crate::filesystem::notify::inotify::InotifyInode;
+use crate::libs::casting::DowncastArc;
+use crate::process::ProcessManager;
+use crate::syscall::table::{FormattedSyscallParam, Syscall};
+use alloc::vec::Vec;
+use system_error::SystemError;
+
+pub struct SysInotifyRmWatchHandle;
+
+impl SysInotifyRmWatchHandle {
+    fn fd(args: &[usize]) -> i32 {
+        args[0] as i32
+    }
+
+    fn wd(args: &[usize]) -> i32 {
+        args[1] as i32
+    }
+}
+
+impl Syscall for SysInotifyRmWatchHandle {
+    fn num_args(&self) -> usize {
+        2
+    }
+
+    /// 移除inotify实例中的一个监视，实例会收到一个`IN_IGNORED`事件
+    ///
+    /// ## 参数
+    /// - `fd`: inotify实例的文件描述符
+    /// - `wd`: inotify_add_watch(2)返回的监视描述符
+    fn handle(&self, args: &[usize], _frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        let file = ProcessManager::current_pcb()
+            .fd_table()
+            .read()
+            .get_file_by_fd(Self::fd(args))
+            .ok_or(SystemError::EBADF)?;
+        let instance = file
+            .inode()
+            .downcast_arc::<InotifyInode>()
+            .ok_or(SystemError::EINVAL)?;
+        instance.rm_watch(Self::wd(args)).map(|_| 0)
+    }
+
+    fn entry_format(&self, args: &[usize]) -> Vec<FormattedSyscallParam> {
+        vec![
+            FormattedSyscallParam::new("fd", format!("{}", Self::fd(args))),
+            FormattedSyscallParam::new("wd", format!("{}", Self::wd(args))),
+        ]
+    }
+}
+
+syscall_table_macros::declare_syscall!(SYS_INOTIFY_RM_WATCH, SysInotifyRmWatchHandle);
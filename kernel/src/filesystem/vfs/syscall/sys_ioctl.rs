@@ -5,6 +5,7 @@ use crate::arch::syscall::nr::SYS_IOCTL;
 use crate::filesystem::vfs::fasync::FAsyncItem;
 use crate::filesystem::vfs::file::File;
 use crate::filesystem::vfs::file::FileFlags;
+use crate::filesystem::vfs::ioctl::IoctlArg;
 use crate::syscall::table::FormattedSyscallParam;
 use crate::syscall::table::Syscall;
 use system_error::SystemError;
@@ -14,7 +15,6 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 use crate::process::{ProcessManager, RawPid};
-use crate::syscall::user_access::{UserBufferReader, UserBufferWriter};
 
 // 通用文件描述符ioctl命令常量
 const FIONBIO: u32 = 0x5421; // Set/clear non-blocking i/o
@@ -133,15 +133,8 @@ impl SysIoctlHandle {
 
     /// Handle FIONBIO command: set/clear non-blocking I/O
     fn handle_fionbio(file: &File, data: usize) -> Result<usize, SystemError> {
-        // 检查data指针是否为null
-        if data == 0 {
-            return Err(SystemError::EFAULT);
-        }
-
         // 从用户空间读取int值
-        let user_reader =
-            UserBufferReader::new(data as *const i32, core::mem::size_of::<i32>(), true)?;
-        let value = user_reader.buffer_protected(0)?.read_one::<i32>(0)?;
+        let value: i32 = IoctlArg::new(FIONBIO, data).read()?;
 
         // 获取当前文件标志
         let mut flags = file.flags();
@@ -158,15 +151,8 @@ impl SysIoctlHandle {
 
     /// Handle FIOASYNC command: set/clear asynchronous I/O
     fn handle_fioasync(file: &Arc<File>, data: usize) -> Result<usize, SystemError> {
-        // 检查data指针是否为null
-        if data == 0 {
-            return Err(SystemError::EFAULT);
-        }
-
         // 从用户空间读取int值
-        let user_reader =
-            UserBufferReader::new(data as *const i32, core::mem::size_of::<i32>(), true)?;
-        let value = user_reader.buffer_protected(0)?.read_one::<i32>(0)?;
+        let value: i32 = IoctlArg::new(FIOASYNC, data).read()?;
 
         // 获取当前文件模式
         let mut flags = file.flags();
@@ -196,15 +182,8 @@ impl SysIoctlHandle {
 
     /// Handle FIOSETOWN command: set file owner for SIGIO/SIGURG signals
     fn handle_fiosetown(file: &File, data: usize) -> Result<usize, SystemError> {
-        // 检查data指针是否为null
-        if data == 0 {
-            return Err(SystemError::EFAULT);
-        }
-
         // 从用户空间读取pid值
-        let user_reader =
-            UserBufferReader::new(data as *const i32, core::mem::size_of::<i32>(), true)?;
-        let pid_value = user_reader.buffer_protected(0)?.read_one::<i32>(0)?;
+        let pid_value: i32 = IoctlArg::new(FIOSETOWN, data).read()?;
 
         // 处理pid值，逻辑与fcntl F_SETOWN相同
         let pid = pid_value.unsigned_abs();
@@ -227,12 +206,7 @@ impl SysIoctlHandle {
     }
 
     /// Handle FIOGETOWN and SIOCGPGRP commands: get file owner/process group
-    fn handle_ownership_get(file: &File, data: usize, _cmd: u32) -> Result<usize, SystemError> {
-        // 检查data指针是否为null
-        if data == 0 {
-            return Err(SystemError::EFAULT);
-        }
-
+    fn handle_ownership_get(file: &File, data: usize, cmd: u32) -> Result<usize, SystemError> {
         // 获取所有者pid（如果没有所有者则返回0）
         let owner = file.owner().unwrap_or(RawPid::from(0));
         let pid_value: i32 = owner.data() as i32;
@@ -242,11 +216,7 @@ impl SysIoctlHandle {
         let value_to_write = pid_value;
 
         // 将pid值写入用户空间
-        let mut user_writer =
-            UserBufferWriter::new(data as *mut i32, core::mem::size_of::<i32>(), true)?;
-        user_writer
-            .buffer_protected(0)?
-            .write_one(0, &value_to_write)?;
+        IoctlArg::new(cmd, data).write(&value_to_write)?;
 
         Ok(0)
     }
@@ -0,0 +1,49 @@
+//! System call handler for sys_llistxattr.
+
+use super::xattr_utils::path_listxattr;
+use crate::{
+    arch::{interrupt::TrapFrame, syscall::nr::SYS_LLISTXATTR},
+    syscall::table::{FormattedSyscallParam, Syscall},
+};
+use alloc::{string::ToString, vec::Vec};
+use system_error::SystemError;
+
+pub struct SysLlistxattrHandle;
+
+impl Syscall for SysLlistxattrHandle {
+    fn num_args(&self) -> usize {
+        3
+    }
+
+    fn handle(&self, args: &[usize], _frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        let path_ptr = Self::path(args);
+        let list_ptr = Self::list(args);
+        let size = Self::size(args);
+
+        path_listxattr(path_ptr, list_ptr, size, 0)
+    }
+
+    fn entry_format(&self, args: &[usize]) -> Vec<FormattedSyscallParam> {
+        vec![
+            FormattedSyscallParam::new("path", format!("{:#x}", Self::path(args) as usize)),
+            FormattedSyscallParam::new("list", format!("{:#x}", Self::list(args) as usize)),
+            FormattedSyscallParam::new("size", Self::size(args).to_string()),
+        ]
+    }
+}
+
+impl SysLlistxattrHandle {
+    fn path(args: &[usize]) -> *const u8 {
+        args[0] as *const u8
+    }
+
+    fn list(args: &[usize]) -> *mut u8 {
+        args[1] as *mut u8
+    }
+
+    fn size(args: &[usize]) -> usize {
+        args[2]
+    }
+}
+
+syscall_table_macros::declare_syscall!(SYS_LLISTXATTR, SysLlistxattrHandle);
@@ -2,6 +2,7 @@ use super::InodeMode;
 use crate::arch::interrupt::TrapFrame;
 use crate::arch::syscall::nr::SYS_MKNODAT;
 use crate::driver::base::device::device_number::DeviceNumber;
+use crate::filesystem::notify::fsnotify_create;
 use crate::filesystem::vfs::syscall::AtFlags;
 use crate::filesystem::vfs::utils::rsplit_path;
 use crate::filesystem::vfs::utils::user_path_at;
@@ -75,6 +76,7 @@ impl Syscall for SysMknodatHandle {
 
         // 在解析出的父目录上进行 mknod
         current_inode.mknod(name, mode, dev)?;
+        fsnotify_create(&current_inode, name, false);
 
         Ok(0)
     }
@@ -6,10 +6,15 @@ use crate::{
         fcntl::AtFlags,
         mount::{is_mountpoint_root, MountFlags},
         produce_fs,
+        timestamps::lazy_times,
         utils::user_path_at,
+        vcore::find_source_gendisk,
         FileType, IndexNode, InodeId, MountFS, MAX_PATHLEN, VFS_MAX_FOLLOW_SYMLINK_TIMES,
     },
-    libs::casting::DowncastArc,
+    libs::{
+        casting::DowncastArc,
+        error_context::{ContextError, ErrorContext},
+    },
     process::{
         namespace::propagation::{
             change_mnt_propagation_recursive, flags_to_propagation_type, is_propagation_change,
@@ -229,10 +234,12 @@ fn path_mount(
     if flags.contains(MountFlags::NOSYMFOLLOW) {
         mnt_flags.insert(MountFlags::NOSYMFOLLOW);
     }
+    if flags.contains(MountFlags::LAZYTIME) {
+        mnt_flags.insert(MountFlags::LAZYTIME);
+    }
 
     if flags.contains(MountFlags::REMOUNT) {
-        log::warn!("todo: remount");
-        return Err(SystemError::ENOSYS);
+        return do_remount(target_inode, flags, data);
     }
 
     if flags.contains(MountFlags::BIND) {
@@ -248,8 +255,9 @@ fn path_mount(
         return Err(SystemError::ENOSYS);
     }
 
-    // 创建新的挂载
-    return do_new_mount(source, target_inode, filesystemtype, data, mnt_flags).map(|_| ());
+    // 创建新的挂载，这里是挂载流程的边界，出错时打印上下文链
+    do_new_mount(source, target_inode, filesystemtype, data, mnt_flags)?;
+    Ok(())
 }
 
 fn bind_remount_requested_flags(flags: MountFlags) -> MountFlags {
@@ -280,6 +288,14 @@ fn do_reconfigure_bind_mount(
         SystemError::EINVAL
     })?;
 
+    if !requested_flags.contains(MountFlags::RDONLY)
+        && target_mfs
+            .mount_source()
+            .is_some_and(|source| is_read_only_bdev(&source))
+    {
+        return Err(SystemError::EACCES);
+    }
+
     let reconfigurable_flags = MountFlags::RDONLY
         | MountFlags::NOSUID
         | MountFlags::NODEV
@@ -307,18 +323,57 @@ fn do_reconfigure_bind_mount(
     Ok(())
 }
 
+/// 重新挂载：先让文件系统应用新的挂载选项，再更新挂载标志
+fn do_remount(
+    target_inode: Arc<dyn IndexNode>,
+    flags: MountFlags,
+    data: Option<String>,
+) -> Result<(), SystemError> {
+    if !is_mountpoint_root(&target_inode) {
+        return Err(SystemError::EINVAL);
+    }
+    let target_mfs = target_inode
+        .fs()
+        .downcast_arc::<MountFS>()
+        .ok_or(SystemError::EINVAL)?;
+    target_mfs.inner_filesystem().remount(data.as_deref())?;
+    do_reconfigure_bind_mount(target_inode, bind_remount_requested_flags(flags))?;
+
+    // lazytime是文件系统级的选项，只有不带MS_BIND的重新挂载才能改变
+    let lazytime = flags.contains(MountFlags::LAZYTIME);
+    target_mfs.update_mount_flags(|mount_flags| mount_flags.set(MountFlags::LAZYTIME, lazytime));
+    if !lazytime {
+        lazy_times().flush_fs(&target_mfs.inner_filesystem());
+    }
+    Ok(())
+}
+
 fn do_new_mount(
     source: Option<String>,
     mut target_inode: Arc<dyn IndexNode>,
     filesystemtype: Option<String>,
     data: Option<String>,
     mount_flags: MountFlags,
-) -> Result<Arc<MountFS>, SystemError> {
-    let fs_type_str = filesystemtype.ok_or(SystemError::EINVAL)?;
+) -> Result<Arc<MountFS>, ContextError> {
     let source = source.ok_or(SystemError::EINVAL)?;
-    let fs = produce_fs(&fs_type_str, data.as_deref(), &source).inspect_err(|e| {
-        log::warn!("Failed to produce filesystem: {:?}", e);
-    })?;
+    // 没有指定文件系统类型时，探测挂载源所在块设备上的文件系统
+    let fs_type_str = filesystemtype
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| String::from("auto"));
+    // 与Linux一致，只读块设备只能以只读方式挂载
+    if !mount_flags.contains(MountFlags::RDONLY) && is_read_only_bdev(&source) {
+        return Err(SystemError::EACCES.into());
+    }
+    let mount_desc = |target_inode: &Arc<dyn IndexNode>| {
+        format!(
+            "mount {} ({}) on {}",
+            source,
+            fs_type_str,
+            target_inode.absolute_path().unwrap_or_default()
+        )
+    };
+    let fs = produce_fs(&fs_type_str, data.as_deref(), &source)
+        .with_context(|| mount_desc(&target_inode))?;
 
     // 若目标是挂载点根，则尝试在其父目录挂载，避免 EBUSY 并与 Linux 叠加语义接近
     if is_mountpoint_root(&target_inode) {
@@ -331,10 +386,19 @@ fn do_new_mount(
 
     // 允许在已有挂载点上再次挂载（符合 Linux 允许叠加挂载的语义）
     // MountList::insert 会替换同一路径的记录，无需提前返回 EBUSY。
-    let new_mount = target_inode.mount(fs, mount_flags)?;
+    let new_mount = target_inode
+        .mount(fs, mount_flags)
+        .with_context(|| mount_desc(&target_inode))?;
     new_mount.set_mount_source(Some(source));
     Ok(new_mount)
 }
+
+/// 挂载源是否是只读的块设备
+fn is_read_only_bdev(source: &str) -> bool {
+    find_source_gendisk(source)
+        .is_ok_and(|gendisk| gendisk.block_device().blkdev_meta().is_read_only())
+}
+
 #[inline(never)]
 fn copy_mount_string(raw: Option<*const u8>) -> Result<Option<String>, SystemError> {
     if let Some(raw) = raw {
@@ -440,7 +504,12 @@ fn do_bind_mount(
         .clone()
         .downcast_arc::<crate::filesystem::vfs::mount::MountFSInode>()
         .ok_or(SystemError::EINVAL)?
-        .mount_subtree(inner_fs, root_inner_inode, MountFlags::empty())?;
+        .mount_subtree(
+            inner_fs,
+            root_inner_inode,
+            MountFlags::empty(),
+            source_mfs_for_recursive.as_ref(),
+        )?;
     target_mfs.set_mount_source(Some(source_path.clone()));
 
     // If MS_REC is set, recursively bind all submounts from source to target
@@ -539,6 +608,11 @@ fn do_recursive_bind_mount(
 
     // Process all submounts
     while let Some(info) = queue.pop() {
+        // Unbindable submounts (and everything below them) are not copied by rbind
+        if info.source_mfs.propagation().is_unbindable() {
+            continue;
+        }
+
         // Get the mount path of this submount
         let child_mount_path = match mount_list.get_mount_path_by_ino(info.source_mp_ino) {
             Some(path) => path,
@@ -621,8 +695,12 @@ fn do_recursive_bind_mount(
             .clone()
             .downcast_arc::<crate::filesystem::vfs::mount::MountFSInode>()
             .ok_or(SystemError::EINVAL)?
-            .mount_subtree(child_inner_fs, child_root_inner_inode, MountFlags::empty())
-        {
+            .mount_subtree(
+                child_inner_fs,
+                child_root_inner_inode,
+                MountFlags::empty(),
+                Some(&info.source_mfs),
+            ) {
             Ok(new_child_mnt) => {
                 let source = info
                     .source_mfs
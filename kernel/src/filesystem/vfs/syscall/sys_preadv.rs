@@ -5,6 +5,7 @@ use system_error::SystemError;
 
 use crate::arch::syscall::nr::SYS_PREADV;
 use crate::filesystem::vfs::iov::{IoVec, IoVecs};
+use crate::process::io_accounting::task_io_inc_syscr;
 use crate::process::ProcessManager;
 use crate::syscall::table::{FormattedSyscallParam, Syscall};
 
@@ -20,6 +21,8 @@ impl Syscall for SysPreadVHandle {
         args: &[usize],
         _frame: &mut crate::arch::interrupt::TrapFrame,
     ) -> Result<usize, SystemError> {
+        task_io_inc_syscr();
+
         let fd = Self::fd(args);
         let iov = Self::iov(args);
         let iov_count = Self::iov_count(args);
@@ -4,6 +4,7 @@ use system_error::SystemError;
 
 use crate::arch::interrupt::TrapFrame;
 use crate::arch::syscall::nr::SYS_PWRITE64;
+use crate::process::io_accounting::task_io_inc_syscw;
 use crate::process::ProcessManager;
 use crate::syscall::table::FormattedSyscallParam;
 use crate::syscall::table::Syscall;
@@ -48,6 +49,8 @@ impl Syscall for SysPwrite64Handle {
     /// - `len`: 要写入的字节数
     /// - `offset`: 文件偏移量
     fn handle(&self, args: &[usize], frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        task_io_inc_syscw();
+
         let fd = Self::fd(args);
         let buf_vaddr = Self::buf(args);
         let len = Self::len(args);
@@ -8,6 +8,7 @@ use crate::filesystem::vfs::file::File;
 use crate::filesystem::vfs::iov::{IoVec, IoVecs};
 use crate::filesystem::vfs::syscall::sys_pwrite64::validate_pwrite_range;
 use crate::filesystem::vfs::syscall::sys_pwritev2::{do_pwritev2, RwfFlags};
+use crate::process::io_accounting::task_io_inc_syscw;
 use crate::process::ProcessManager;
 use crate::syscall::table::{FormattedSyscallParam, Syscall};
 use alloc::sync::Arc;
@@ -36,6 +37,8 @@ impl Syscall for SysPwriteVHandle {
         args: &[usize],
         _frame: &mut crate::arch::interrupt::TrapFrame,
     ) -> Result<usize, SystemError> {
+        task_io_inc_syscw();
+
         // 从 args buffer 中获取想要的参数
         let fd = Self::fd(args);
         let iov = Self::iov(args);
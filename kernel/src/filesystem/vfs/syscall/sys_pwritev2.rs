@@ -6,6 +6,7 @@ use crate::arch::interrupt::TrapFrame;
 use crate::arch::syscall::nr::SYS_PWRITEV2;
 use crate::filesystem::vfs::file::File;
 use crate::filesystem::vfs::iov::{IoVec, IoVecs};
+use crate::process::io_accounting::task_io_inc_syscw;
 use crate::process::ProcessManager;
 use crate::syscall::table::{FormattedSyscallParam, Syscall};
 
@@ -37,6 +38,8 @@ impl Syscall for SysPwriteV2Handle {
     }
 
     fn handle(&self, args: &[usize], _frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        task_io_inc_syscw();
+
         let fd = Self::fd(args);
         let iov = Self::iov(args);
         let iov_count = Self::iov_count(args);
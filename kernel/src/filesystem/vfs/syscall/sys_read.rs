@@ -5,6 +5,7 @@ use crate::arch::syscall::nr::SYS_READ;
 use crate::filesystem::vfs::file::{File, FileFlags};
 use crate::filesystem::vfs::FileType;
 use crate::mm::VirtAddr;
+use crate::process::io_accounting::task_io_inc_syscr;
 use crate::process::ProcessManager;
 use crate::syscall::table::FormattedSyscallParam;
 use crate::syscall::table::Syscall;
@@ -13,6 +14,8 @@ use alloc::string::ToString;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 
+use super::pread_pwrite_common::{do_direct_user_io, PreadPwriteDir};
+
 /// System call handler for the `read` syscall
 ///
 /// This handler implements the `Syscall` trait to provide functionality for reading data from a file descriptor.
@@ -39,6 +42,8 @@ impl Syscall for SysReadHandle {
     /// * `Ok(usize)` - Number of bytes successfully read
     /// * `Err(SystemError)` - Error code if operation fails
     fn handle(&self, args: &[usize], frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        task_io_inc_syscr();
+
         let fd = Self::fd(args);
         let buf_vaddr = Self::buf(args);
         let len = Self::len(args);
@@ -146,6 +151,12 @@ fn read_into_user_buffer(fd: i32, user_ptr: *mut u8, len: usize) -> Result<usize
     if file.file_type() == FileType::Socket {
         return read_socket_into_user_buffer(file.as_ref(), user_ptr, accessible);
     }
+    file.check_direct_io_buffer(user_ptr as usize)?;
+    if file.is_direct_io() {
+        return do_direct_user_io(user_ptr as usize, len, &PreadPwriteDir::Read, |_, pages| {
+            file.read_pinned(pages)
+        });
+    }
 
     // Keep the kernel-side buffer modest to avoid huge allocations/long critical sections.
     const CHUNK: usize = 64 * 1024;
@@ -7,6 +7,7 @@ use crate::filesystem::vfs::iov::IoVec;
 use crate::filesystem::vfs::iov::IoVecs;
 use crate::mm::MemoryManagementArch;
 use crate::mm::VirtAddr;
+use crate::process::io_accounting::task_io_inc_syscr;
 use crate::syscall::table::FormattedSyscallParam;
 use crate::syscall::table::Syscall;
 use crate::syscall::user_access::{copy_to_user_protected, user_accessible_len};
@@ -27,6 +28,8 @@ impl Syscall for SysReadVHandle {
     }
 
     fn handle(&self, args: &[usize], _frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        task_io_inc_syscr();
+
         let fd = Self::fd(args);
         let iov = Self::iov(args);
         let count = Self::count(args);
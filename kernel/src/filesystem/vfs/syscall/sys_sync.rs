@@ -7,8 +7,8 @@ use crate::{
         interrupt::TrapFrame,
         syscall::nr::{SYS_SYNC, SYS_SYNCFS},
     },
-    filesystem::vfs::file::FileFlags,
-    mm::page::page_reclaimer_lock,
+    filesystem::vfs::{file::FileFlags, timestamps::lazy_times},
+    mm::writeback::writeback_all,
     process::ProcessManager,
     syscall::table::{FormattedSyscallParam, Syscall},
 };
@@ -31,7 +31,8 @@ impl Syscall for SysSyncHandle {
         _args: &[usize],
         _frame: &mut TrapFrame,
     ) -> Result<usize, system_error::SystemError> {
-        page_reclaimer_lock().flush_dirty_pages();
+        lazy_times().flush_all();
+        writeback_all();
         Ok(0)
     }
 
@@ -80,7 +81,8 @@ impl Syscall for SysSyncFsHandle {
 
         // TODO: now, we ignore the fd and sync all filesystems.
         // In the future, we should sync only the filesystem of the given fd.
-        page_reclaimer_lock().flush_dirty_pages();
+        lazy_times().flush_all();
+        writeback_all();
         Ok(0)
     }
 
@@ -3,7 +3,7 @@
 use crate::{
     arch::{interrupt::TrapFrame, syscall::nr::SYS_UMOUNT2},
     filesystem::vfs::{fcntl::AtFlags, utils::user_path_at, MountFS, MAX_PATHLEN},
-    process::ProcessManager,
+    process::{namespace::mnt::MntNamespace, ProcessManager},
     syscall::{
         table::{FormattedSyscallParam, Syscall},
         user_access,
@@ -65,7 +65,11 @@ syscall_table_macros::declare_syscall!(SYS_UMOUNT2, SysUmount2Handle);
 ///
 /// - dirfd: i32 - 目录文件描述符，用于指定要卸载的文件系统的根目录。
 /// - target: &str - 要卸载的文件系统的目标路径。
-/// - _flag: UmountFlag - 卸载标志，目前未使用。
+/// - flag: UmountFlag - 卸载标志。挂载仍被内核对象（比如loop设备）使用时，
+///   只有`MNT_FORCE`或`MNT_DETACH`能够卸载，此时先让这些对象放弃对挂载的引用。
+///   `MNT_DETACH`把挂载和它的子挂载立即从挂载树上摘下，等最后一个引用释放后再卸载。
+///
+/// 挂载上有打开的文件、进程的当前目录或根目录，或者还有子挂载时，不带`MNT_DETACH`的卸载返回`EBUSY`。
 ///
 /// ## 返回值
 ///
@@ -75,11 +79,7 @@ syscall_table_macros::declare_syscall!(SYS_UMOUNT2, SysUmount2Handle);
 /// ## 错误处理
 ///
 /// 如果指定的路径没有对应的文件系统，或者在尝试卸载时发生错误，将返回错误。
-pub fn do_umount2(
-    dirfd: i32,
-    target: &str,
-    _flag: UmountFlag,
-) -> Result<Arc<MountFS>, SystemError> {
+pub fn do_umount2(dirfd: i32, target: &str, flag: UmountFlag) -> Result<Arc<MountFS>, SystemError> {
     let target = target.trim();
     if target.is_empty() {
         return Err(SystemError::ENOENT);
@@ -103,13 +103,55 @@ pub fn do_umount2(
         base
     };
 
-    let result = ProcessManager::current_mntns().remove_mount(&path);
-    if let Some(fs) = result {
-        // Todo: 占用检测
-        fs.umount()?;
+    let mntns = ProcessManager::current_mntns();
+    let fs = mntns
+        .mount_list()
+        .get(path.as_str())
+        .ok_or(SystemError::EINVAL)?;
+    fs.release_dependents(flag.intersects(UmountFlag::MNT_FORCE | UmountFlag::MNT_DETACH))?;
+
+    if flag.contains(UmountFlag::MNT_DETACH) {
+        if fs.self_mountpoint().is_none() {
+            return Err(SystemError::EINVAL);
+        }
+        // 子挂载随着一起摘下，挂载立即从挂载树上消失，最后一个引用释放时才真正卸载
+        let children: Vec<_> = fs.mountpoints().values().cloned().collect();
+        for child in children {
+            detach_subtree(&mntns, &child)?;
+        }
+        fs.detach()?;
+        mntns.remove_mount(&path);
         return Ok(fs);
     }
-    return Err(SystemError::EINVAL);
+
+    // 挂载上有打开的文件、进程的当前目录，或者还有子挂载
+    if fs.is_busy() {
+        return Err(SystemError::EBUSY);
+    }
+    fs.umount()?;
+    mntns.remove_mount(&path);
+    return Ok(fs);
+}
+
+/// 从挂载树上摘下`fs`和它下面的所有挂载
+fn detach_subtree(mntns: &Arc<MntNamespace>, fs: &Arc<MountFS>) -> Result<(), SystemError> {
+    let children: Vec<_> = fs.mountpoints().values().cloned().collect();
+    for child in children {
+        detach_subtree(mntns, &child)?;
+    }
+    let mount_list = mntns.mount_list();
+    let path = mount_list.get_mount_path_by_mountfs(fs);
+    fs.detach()?;
+    if let Some(path) = path {
+        // 同一路径上可能叠加了多个挂载，只有最上层的是fs时才从列表中移除
+        if mount_list
+            .get(path.as_str())
+            .is_some_and(|top| Arc::ptr_eq(&top, fs))
+        {
+            mount_list.remove(path.as_str());
+        }
+    }
+    Ok(())
 }
 
 bitflags! {
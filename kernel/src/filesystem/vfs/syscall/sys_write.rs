@@ -2,14 +2,19 @@ use system_error::SystemError;
 
 use crate::arch::interrupt::TrapFrame;
 use crate::arch::syscall::nr::SYS_WRITE;
+use crate::filesystem::vfs::file::File;
 use crate::mm::VirtAddr;
+use crate::process::io_accounting::task_io_inc_syscw;
 use crate::process::ProcessManager;
 use crate::syscall::table::FormattedSyscallParam;
 use crate::syscall::table::Syscall;
 use crate::syscall::user_access::{user_accessible_len, UserBufferReader};
 use alloc::string::ToString;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 
+use super::pread_pwrite_common::{do_direct_user_io, PreadPwriteDir};
+
 /// System call handler for the `write` syscall
 ///
 /// This handler implements the `Syscall` trait to provide functionality for writing data to a file descriptor.
@@ -36,6 +41,8 @@ impl Syscall for SysWriteHandle {
     /// * `Ok(usize)` - Number of bytes successfully written
     /// * `Err(SystemError)` - Error code if operation fails
     fn handle(&self, args: &[usize], frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        task_io_inc_syscw();
+
         let fd = Self::fd(args);
         let buf_vaddr = Self::buf(args);
         let len = Self::len(args);
@@ -49,6 +56,16 @@ impl Syscall for SysWriteHandle {
 
         // 用户态：先检查可访问长度，避免直接触碰无效页；内核态直接使用
         let (user_buffer_reader, write_len) = if frame.is_from_user() {
+            let file = get_write_file(fd)?;
+            file.check_direct_io_buffer(buf_vaddr as usize)?;
+            if file.is_direct_io() {
+                return do_direct_user_io(
+                    buf_vaddr as usize,
+                    len,
+                    &PreadPwriteDir::Write,
+                    |_, pages| file.write_pinned(pages),
+                );
+            }
             let accessible = user_accessible_len(
                 VirtAddr::new(buf_vaddr as usize),
                 len,
@@ -115,6 +132,11 @@ syscall_table_macros::declare_syscall!(SYS_WRITE, SysWriteHandle);
 /// * `Ok(usize)` - Number of bytes successfully written
 /// * `Err(SystemError)` - Error code if operation fails
 pub(super) fn do_write(fd: i32, buf: &[u8]) -> Result<usize, SystemError> {
+    let file = get_write_file(fd)?;
+    return file.write(buf.len(), buf);
+}
+
+fn get_write_file(fd: i32) -> Result<Arc<File>, SystemError> {
     let binding = ProcessManager::current_pcb().fd_table();
     let fd_table_guard = binding.read();
 
@@ -125,5 +147,5 @@ pub(super) fn do_write(fd: i32, buf: &[u8]) -> Result<usize, SystemError> {
     // drop guard 以避免无法调度的问题
     drop(fd_table_guard);
 
-    return file.write(buf.len(), buf);
+    Ok(file)
 }
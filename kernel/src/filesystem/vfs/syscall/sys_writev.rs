@@ -3,6 +3,7 @@ use system_error::SystemError;
 use crate::arch::syscall::nr::SYS_WRITEV;
 use crate::filesystem::vfs::iov::IoVec;
 use crate::filesystem::vfs::iov::IoVecs;
+use crate::process::io_accounting::task_io_inc_syscw;
 use crate::syscall::table::FormattedSyscallParam;
 use crate::syscall::table::Syscall;
 
@@ -39,6 +40,8 @@ impl Syscall for SysWriteVHandle {
     /// # Safety
     /// The caller must ensure the `iov` pointer is valid and points to properly initialized memory.
     fn handle(&self, args: &[usize], _frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        task_io_inc_syscw();
+
         let fd = Self::fd(args);
         let iov = Self::iov(args);
         let count = Self::count(args);
@@ -1,39 +1,37 @@
 use super::{XATTR_CREATE, XATTR_REPLACE};
 use crate::{
-    filesystem::vfs::{syscall::AtFlags, utils::user_path_at, IndexNode, MAX_PATHLEN},
+    filesystem::vfs::{
+        permission::PermissionMask,
+        syscall::AtFlags,
+        utils::user_path_at,
+        xattr::{
+            xattr_list_filter, xattr_permission, XattrNamespace, XATTR_LIST_MAX, XATTR_NAME_MAX,
+            XATTR_SIZE_MAX,
+        },
+        IndexNode, MAX_PATHLEN,
+    },
     process::ProcessManager,
     syscall::user_access::{
         check_and_clone_cstr, vfs_check_and_clone_cstr, UserBufferReader, UserBufferWriter,
     },
 };
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{string::String, sync::Arc, vec::Vec};
 use system_error::SystemError;
 
-/// Extended attribute GET operations
-pub(super) fn path_getxattr(
+fn path_lookup(
     path_ptr: *const u8,
-    name_ptr: *const u8,
-    buf_ptr: *mut u8,
-    size: usize,
     lookup_flags: usize,
-) -> Result<usize, SystemError> {
+) -> Result<Arc<dyn IndexNode>, SystemError> {
     let path = vfs_check_and_clone_cstr(path_ptr, Some(MAX_PATHLEN))?
         .into_string()
         .map_err(|_| SystemError::EINVAL)?;
 
     let pcb = ProcessManager::current_pcb();
     let (current_node, rest_path) = user_path_at(&pcb, AtFlags::AT_FDCWD.bits(), &path)?;
-    let inode = current_node.lookup_follow_symlink(&rest_path, lookup_flags)?;
-
-    do_getxattr(inode, name_ptr, buf_ptr, size)
+    current_node.lookup_follow_symlink(&rest_path, lookup_flags)
 }
 
-pub(super) fn fd_getxattr(
-    fd: i32,
-    name_ptr: *const u8,
-    buf_ptr: *mut u8,
-    size: usize,
-) -> Result<usize, SystemError> {
+fn fd_lookup(fd: i32) -> Result<Arc<dyn IndexNode>, SystemError> {
     // 获取文件描述符对应的文件节点
     let binding = ProcessManager::current_pcb().fd_table();
     let fd_table_guard = binding.read();
@@ -41,8 +39,38 @@ pub(super) fn fd_getxattr(
     let file = fd_table_guard
         .get_file_by_fd(fd)
         .ok_or(SystemError::EBADF)?;
-    let inode = file.inode();
+    Ok(file.inode())
+}
 
+/// 从用户空间复制属性名，并解析它的命名空间
+fn xattr_name(name_ptr: *const u8) -> Result<(String, XattrNamespace), SystemError> {
+    // 多读一个字节，超长的名字由parse返回ERANGE
+    let name = check_and_clone_cstr(name_ptr, Some(XATTR_NAME_MAX + 1))?
+        .into_string()
+        .map_err(|_| SystemError::EINVAL)?;
+    let (ns, _) = XattrNamespace::parse(&name)?;
+    Ok((name, ns))
+}
+
+/// Extended attribute GET operations
+pub(super) fn path_getxattr(
+    path_ptr: *const u8,
+    name_ptr: *const u8,
+    buf_ptr: *mut u8,
+    size: usize,
+    lookup_flags: usize,
+) -> Result<usize, SystemError> {
+    let inode = path_lookup(path_ptr, lookup_flags)?;
+    do_getxattr(inode, name_ptr, buf_ptr, size)
+}
+
+pub(super) fn fd_getxattr(
+    fd: i32,
+    name_ptr: *const u8,
+    buf_ptr: *mut u8,
+    size: usize,
+) -> Result<usize, SystemError> {
+    let inode = fd_lookup(fd)?;
     // 调用VFS接口获取扩展属性
     do_getxattr(inode, name_ptr, buf_ptr, size)
 }
@@ -53,9 +81,8 @@ fn do_getxattr(
     buf_ptr: *mut u8,
     size: usize,
 ) -> Result<usize, SystemError> {
-    let name = check_and_clone_cstr(name_ptr, None)?
-        .into_string()
-        .map_err(|_| SystemError::EINVAL)?;
+    let (name, ns) = xattr_name(name_ptr)?;
+    xattr_permission(&inode, ns, PermissionMask::MAY_READ)?;
 
     if size == 0 {
         // 只返回需要的缓冲区大小
@@ -63,6 +90,7 @@ fn do_getxattr(
         let result_size = inode.getxattr(&name, &mut temp_buf)?;
         Ok(result_size)
     } else {
+        let size = size.min(XATTR_SIZE_MAX);
         let mut user_buffer_writer = UserBufferWriter::new(buf_ptr, size, true)?;
         let user_buf = user_buffer_writer.buffer(0)?;
 
@@ -81,14 +109,7 @@ pub(super) fn path_setxattr(
     lookup_flags: usize,
     flags: i32,
 ) -> Result<usize, SystemError> {
-    let path = vfs_check_and_clone_cstr(path_ptr, Some(MAX_PATHLEN))?
-        .into_string()
-        .map_err(|_| SystemError::EINVAL)?;
-
-    let pcb = ProcessManager::current_pcb();
-    let (current_node, rest_path) = user_path_at(&pcb, AtFlags::AT_FDCWD.bits(), &path)?;
-    let inode = current_node.lookup_follow_symlink(&rest_path, lookup_flags)?;
-
+    let inode = path_lookup(path_ptr, lookup_flags)?;
     do_setxattr(inode, name_ptr, value_ptr, size, flags)
 }
 
@@ -99,14 +120,7 @@ pub(super) fn fd_setxattr(
     size: usize,
     flags: i32,
 ) -> Result<usize, SystemError> {
-    let binding = ProcessManager::current_pcb().fd_table();
-    let fd_table_guard = binding.read();
-
-    let file = fd_table_guard
-        .get_file_by_fd(fd)
-        .ok_or(SystemError::EBADF)?;
-    let inode = file.inode();
-
+    let inode = fd_lookup(fd)?;
     do_setxattr(inode, name_ptr, value_ptr, size, flags)
 }
 
@@ -117,9 +131,14 @@ fn do_setxattr(
     size: usize,
     flags: i32,
 ) -> Result<usize, SystemError> {
-    let name = check_and_clone_cstr(name_ptr, None)?
-        .into_string()
-        .map_err(|_| SystemError::EINVAL)?;
+    if flags & !(XATTR_CREATE | XATTR_REPLACE) != 0 {
+        return Err(SystemError::EINVAL);
+    }
+    let (name, ns) = xattr_name(name_ptr)?;
+    if size > XATTR_SIZE_MAX {
+        return Err(SystemError::E2BIG);
+    }
+    xattr_permission(&inode, ns, PermissionMask::MAY_WRITE)?;
 
     if (flags & XATTR_CREATE != 0) && inode.getxattr(&name, &mut Vec::new()).is_ok() {
         return Err(SystemError::EEXIST);
@@ -128,8 +147,77 @@ fn do_setxattr(
         return Err(SystemError::ENODATA);
     }
 
-    let user_buffer_reader = UserBufferReader::new(value_ptr, size, true)?;
-    let value_buf = user_buffer_reader.buffer(0)?;
+    let value = if size == 0 {
+        Vec::new()
+    } else {
+        let user_buffer_reader = UserBufferReader::new(value_ptr, size, true)?;
+        user_buffer_reader.buffer::<u8>(0)?.to_vec()
+    };
+
+    inode.setxattr(&name, &value)
+}
+
+/// Extended attribute LIST operations
+pub(super) fn path_listxattr(
+    path_ptr: *const u8,
+    list_ptr: *mut u8,
+    size: usize,
+    lookup_flags: usize,
+) -> Result<usize, SystemError> {
+    let inode = path_lookup(path_ptr, lookup_flags)?;
+    do_listxattr(inode, list_ptr, size)
+}
+
+pub(super) fn fd_listxattr(fd: i32, list_ptr: *mut u8, size: usize) -> Result<usize, SystemError> {
+    let inode = fd_lookup(fd)?;
+    do_listxattr(inode, list_ptr, size)
+}
+
+fn do_listxattr(
+    inode: Arc<dyn IndexNode>,
+    list_ptr: *mut u8,
+    size: usize,
+) -> Result<usize, SystemError> {
+    // 属性名以'\0'分隔
+    let mut list = Vec::new();
+    for name in xattr_list_filter(inode.listxattr()?) {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+    }
+
+    if size == 0 {
+        return Ok(list.len());
+    }
+    if list.len() > size.min(XATTR_LIST_MAX) {
+        return Err(if size >= XATTR_LIST_MAX {
+            SystemError::E2BIG
+        } else {
+            SystemError::ERANGE
+        });
+    }
+    let mut user_buffer_writer = UserBufferWriter::new(list_ptr, list.len(), true)?;
+    user_buffer_writer.copy_to_user(&list, 0)?;
+    Ok(list.len())
+}
+
+/// Extended attribute REMOVE operations
+pub(super) fn path_removexattr(
+    path_ptr: *const u8,
+    name_ptr: *const u8,
+    lookup_flags: usize,
+) -> Result<usize, SystemError> {
+    let inode = path_lookup(path_ptr, lookup_flags)?;
+    do_removexattr(inode, name_ptr)
+}
+
+pub(super) fn fd_removexattr(fd: i32, name_ptr: *const u8) -> Result<usize, SystemError> {
+    let inode = fd_lookup(fd)?;
+    do_removexattr(inode, name_ptr)
+}
 
-    inode.setxattr(&name, value_buf)
+fn do_removexattr(inode: Arc<dyn IndexNode>, name_ptr: *const u8) -> Result<usize, SystemError> {
+    let (name, ns) = xattr_name(name_ptr)?;
+    xattr_permission(&inode, ns, PermissionMask::MAY_WRITE)?;
+    inode.removexattr(&name)?;
+    Ok(0)
 }
@@ -0,0 +1,238 @@
+//! inode时间戳的精度和访问时间（atime）的更新
+//!
+//! 读文件、读目录之后由[`file_accessed`]按照挂载选项决定是否更新atime：
+//! - `noatime`: 不更新；`nodiratime`: 不更新目录的atime
+//! - `relatime`（默认）: 只有atime不晚于mtime/ctime，或者距离上次更新超过一天时才更新
+//! - `strictatime`: 每次访问都更新
+//!
+//! `lazytime`挂载下，atime的更新只记录在内存中（stat能立即看到），等到fsync、sync、
+//! 卸载、inode的元数据被修改，或者记录超过[`LAZYTIME_EXPIRE_SECS`]时才写回文件系统，
+//! 避免每次读都产生一次元数据写。
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{libs::spinlock::SpinLock, time::PosixTimeSpec};
+
+use super::{
+    file::FileFlags,
+    mount::{MountFSInode, MountFlags},
+    FileSystem, IndexNode, InodeId, Metadata,
+};
+
+/// relatime下，atime距离现在超过这个时间就会更新
+const RELATIME_INTERVAL_SECS: i64 = 24 * 60 * 60;
+/// lazytime下，内存中的时间戳最多保留这么久就写回
+pub const LAZYTIME_EXPIRE_SECS: i64 = 12 * 60 * 60;
+/// 内存中最多保留的时间戳数量，超出时直接写回
+const LAZYTIME_MAX_PENDING: usize = 4096;
+
+const NSEC_PER_SEC: i64 = 1_000_000_000;
+
+lazy_static! {
+    static ref LAZY_TIMES: LazyTimes = LazyTimes::new();
+}
+
+/// 获取lazytime挂载下还没有写回的时间戳
+pub fn lazy_times() -> &'static LazyTimes {
+    &LAZY_TIMES
+}
+
+/// 把时间戳截断到文件系统能够保存的精度
+///
+/// ## 参数
+/// - `gran`: 精度，单位为纳秒，参见[`FileSystem::time_granularity`]
+pub fn timestamp_truncate(ts: PosixTimeSpec, gran: u32) -> PosixTimeSpec {
+    let gran = gran.max(1) as i64;
+    if gran == 1 {
+        return ts;
+    }
+    if gran < NSEC_PER_SEC {
+        return PosixTimeSpec::new(ts.tv_sec, ts.tv_nsec - ts.tv_nsec % gran);
+    }
+    let sec_gran = gran / NSEC_PER_SEC;
+    PosixTimeSpec::new(ts.tv_sec - ts.tv_sec.rem_euclid(sec_gran), 0)
+}
+
+/// 获取当前时间，截断到inode所在文件系统的精度
+pub fn current_time(inode: &Arc<dyn IndexNode>) -> PosixTimeSpec {
+    timestamp_truncate(PosixTimeSpec::now(), inode.fs().time_granularity())
+}
+
+/// 读取文件或者目录之后调用，按照挂载选项更新atime
+///
+/// 不是通过挂载访问到的inode（管道、socket等）不更新
+pub fn file_accessed(inode: &Arc<dyn IndexNode>, flags: FileFlags) {
+    if flags.contains(FileFlags::O_NOATIME) {
+        return;
+    }
+    if let Some(mnt) = inode.as_any_ref().downcast_ref::<MountFSInode>() {
+        mnt.touch_atime();
+    }
+}
+
+fn ts_key(ts: &PosixTimeSpec) -> (i64, i64) {
+    (ts.tv_sec, ts.tv_nsec)
+}
+
+/// 判断一次访问是否需要更新atime
+///
+/// ## 参数
+/// - `mount_flags`: inode所在挂载的标志，调用者已经排除了`noatime`
+/// - `md`: inode当前的元数据
+/// - `now`: 已经截断到文件系统精度的当前时间
+pub fn atime_need_update(mount_flags: MountFlags, md: &Metadata, now: &PosixTimeSpec) -> bool {
+    if md.file_type == super::FileType::Dir && mount_flags.contains(MountFlags::NODIRATIME) {
+        return false;
+    }
+    if ts_key(&md.atime) == ts_key(now) {
+        return false;
+    }
+    if !mount_flags.contains(MountFlags::RELATIME) {
+        return true;
+    }
+    let atime = ts_key(&md.atime);
+    ts_key(&md.mtime) >= atime
+        || ts_key(&md.ctime) >= atime
+        || now.tv_sec - md.atime.tv_sec >= RELATIME_INTERVAL_SECS
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LazyTimeKey {
+    /// 文件系统对象的地址，同一个文件系统的多个挂载共享
+    fs: usize,
+    inode_id: InodeId,
+}
+
+impl LazyTimeKey {
+    pub fn new(fs: &Arc<dyn FileSystem>, inode_id: InodeId) -> Self {
+        Self {
+            fs: Arc::as_ptr(fs) as *const () as usize,
+            inode_id,
+        }
+    }
+}
+
+struct LazyTime {
+    /// 文件系统内部的inode，写回时直接调用它的`set_metadata`
+    inode: Arc<dyn IndexNode>,
+    atime: PosixTimeSpec,
+    /// 第一次记录的时间，用于判断是否过期
+    since: i64,
+}
+
+pub struct LazyTimes {
+    pending: SpinLock<BTreeMap<LazyTimeKey, LazyTime>>,
+    /// 记录的数量，为0时查询不需要加锁
+    count: AtomicUsize,
+}
+
+impl LazyTimes {
+    fn new() -> Self {
+        Self {
+            pending: SpinLock::new(BTreeMap::new()),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// 在内存中记录新的atime
+    ///
+    /// ## 返回值
+    /// - `true`: 已经记录
+    /// - `false`: 记录已满，调用者应当直接写回
+    pub fn set_atime(
+        &self,
+        key: LazyTimeKey,
+        inode: &Arc<dyn IndexNode>,
+        atime: PosixTimeSpec,
+    ) -> bool {
+        let mut pending = self.pending.lock_irqsave();
+        if let Some(entry) = pending.get_mut(&key) {
+            entry.atime = atime;
+            return true;
+        }
+        if pending.len() >= LAZYTIME_MAX_PENDING {
+            return false;
+        }
+        pending.insert(
+            key,
+            LazyTime {
+                inode: inode.clone(),
+                atime,
+                since: PosixTimeSpec::now().tv_sec,
+            },
+        );
+        self.count.store(pending.len(), Ordering::Release);
+        true
+    }
+
+    /// 用内存中的时间戳覆盖文件系统返回的元数据
+    pub fn apply(&self, key: &LazyTimeKey, md: &mut Metadata) {
+        if self.count.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        if let Some(entry) = self.pending.lock_irqsave().get(key) {
+            md.atime = entry.atime;
+        }
+    }
+
+    /// 丢弃内存中的时间戳，在inode的元数据被整体写回之后调用
+    pub fn forget(&self, key: &LazyTimeKey) {
+        if self.count.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        let removed = {
+            let mut pending = self.pending.lock_irqsave();
+            let removed = pending.remove(key);
+            self.count.store(pending.len(), Ordering::Release);
+            removed
+        };
+        drop(removed);
+    }
+
+    /// 写回一个inode的时间戳，在fsync之前调用
+    pub fn flush(&self, key: &LazyTimeKey) {
+        self.flush_if(|k, _| k == key);
+    }
+
+    /// 写回一个文件系统的所有时间戳，在卸载或者关闭lazytime时调用
+    pub fn flush_fs(&self, fs: &Arc<dyn FileSystem>) {
+        let fs = Arc::as_ptr(fs) as *const () as usize;
+        self.flush_if(|k, _| k.fs == fs);
+    }
+
+    /// 写回所有时间戳，sync时调用
+    pub fn flush_all(&self) {
+        self.flush_if(|_, _| true);
+    }
+
+    /// 写回超过[`LAZYTIME_EXPIRE_SECS`]的时间戳，由回写线程定期调用
+    pub fn flush_expired(&self) {
+        let now = PosixTimeSpec::now().tv_sec;
+        self.flush_if(|_, since| now - since >= LAZYTIME_EXPIRE_SECS);
+    }
+
+    fn flush_if(&self, pred: impl Fn(&LazyTimeKey, i64) -> bool) {
+        if self.count.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        let flushed: Vec<LazyTime> = {
+            let mut pending = self.pending.lock_irqsave();
+            let keys: Vec<LazyTimeKey> = pending
+                .iter()
+                .filter(|(k, entry)| pred(k, entry.since))
+                .map(|(k, _)| *k)
+                .collect();
+            let flushed = keys.iter().filter_map(|k| pending.remove(k)).collect();
+            self.count.store(pending.len(), Ordering::Release);
+            flushed
+        };
+        // 写回可能会睡眠，不能持有锁
+        for entry in flushed {
+            if let Ok(mut md) = entry.inode.metadata() {
+                md.atime = entry.atime;
+                let _ = entry.inode.set_metadata(&md);
+            }
+        }
+    }
+}
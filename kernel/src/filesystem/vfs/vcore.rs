@@ -4,13 +4,16 @@ use alloc::{string::ToString, sync::Arc};
 use log::{error, info, warn};
 use system_error::SystemError;
 
-use crate::libs::casting::DowncastArc;
+use crate::filesystem::notify::{
+    fsnotify_create, fsnotify_delete, fsnotify_modify, fsnotify_victim,
+};
 use crate::{
     define_event_trace,
     driver::base::block::{gendisk::GenDisk, manager::block_dev_manager},
     filesystem::{
         devfs::devfs_init,
         devpts::devpts_init,
+        ext2::fs::Ext2FileSystem,
         ext4::filesystem::Ext4FileSystem,
         fat::fs::FATFileSystem,
         procfs::procfs_init,
@@ -25,6 +28,8 @@ use crate::{
 };
 
 use super::{
+    fcntl::AtFlags,
+    mount::{inode_mount_flags, MountFlags},
     stat::LookUpFlags,
     utils::{rsplit_path, should_remove_sgid, user_path_at},
     IndexNode, InodeId, VFS_MAX_FOLLOW_SYMLINK_TIMES,
@@ -145,6 +150,30 @@ pub(crate) fn try_find_gendisk(path: &str) -> Option<Arc<GenDisk>> {
     return None;
 }
 
+/// # 查找挂载源对应的块设备
+///
+/// 挂载源可以是devfs中的整盘或分区（例如`/dev/sda1`、`/dev/loop0p1`），
+/// 也可以是指向它们的符号链接，或者用mknod在其他文件系统中创建的块设备文件。
+/// 优先按照设备号查找，设备号找不到时再按照文件名查找
+///
+/// ## 返回值
+/// - `Err(SystemError::ENOTBLK)`: 挂载源不是块设备
+/// - `Err(SystemError::ENXIO)`: 没有对应的块设备
+pub(crate) fn find_source_gendisk(path: &str) -> Result<Arc<GenDisk>, SystemError> {
+    let pcb = ProcessManager::current_pcb();
+    let (current_node, rest_path) = user_path_at(&pcb, AtFlags::AT_FDCWD.bits(), path)?;
+    let inode = current_node.lookup_follow_symlink(&rest_path, VFS_MAX_FOLLOW_SYMLINK_TIMES)?;
+    let metadata = inode.metadata()?;
+    if metadata.file_type != FileType::BlockDevice {
+        return Err(SystemError::ENOTBLK);
+    }
+
+    if let Some(gendisk) = block_dev_manager().lookup_gendisk_by_devnum(metadata.raw_dev) {
+        return Ok(gendisk);
+    }
+    try_find_gendisk(inode.dname()?.0.as_str()).ok_or(SystemError::ENXIO)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum RootFsKind {
     Ext4,
@@ -187,11 +216,14 @@ pub fn mount_root_fs() -> Result<(), SystemError> {
         Some(RootFsKind::Ext4) => Ext4FileSystem::from_gendisk(gendisk.clone()),
         Some(RootFsKind::Fat) => Ok(FATFileSystem::new(gendisk.clone())?),
         None => {
-            // 兜底：按常见顺序尝试初始化（ext4 -> fat），便于未来扩展 probe 或处理特殊镜像。
-            Ext4FileSystem::from_gendisk(gendisk.clone()).or_else(|_| {
-                let fat: Arc<FATFileSystem> = FATFileSystem::new(gendisk.clone())?;
-                Ok(fat)
-            })
+            // 兜底：按常见顺序尝试初始化（ext4 -> ext2 -> fat），便于未来扩展 probe 或处理特殊镜像。
+            // ext4驱动不支持的ext2/3/4镜像（例如块大小不是4K）由ext2驱动挂载，ext3/ext4特性只读
+            Ext4FileSystem::from_gendisk(gendisk.clone())
+                .or_else(|_| Ext2FileSystem::from_gendisk(gendisk.clone()))
+                .or_else(|_| {
+                    let fat: Arc<FATFileSystem> = FATFileSystem::new(gendisk.clone())?;
+                    Ok(fat)
+                })
         }
     };
 
@@ -330,7 +362,9 @@ pub fn do_mkdir_at(
     let final_mode = InodeMode::from_bits_truncate(final_mode_bits) & !umask;
 
     // 执行创建
-    return current_inode.mkdir(name, final_mode);
+    let inode = current_inode.mkdir(name, final_mode)?;
+    fsnotify_create(&current_inode, name, true);
+    return Ok(inode);
 }
 
 /// 解析父目录inode
@@ -407,7 +441,9 @@ pub fn do_remove_dir(dirfd: i32, path: &str) -> Result<u64, SystemError> {
     }
 
     // 删除文件夹
+    let victim = fsnotify_victim(&target_inode);
     parent_inode.rmdir(filename)?;
+    fsnotify_delete(&parent_inode, filename, &target_inode, victim, true);
 
     return Ok(0);
 }
@@ -453,7 +489,9 @@ pub fn do_unlink_at(dirfd: i32, path: &str) -> Result<u64, SystemError> {
     }
 
     // 在父目录上执行 unlink 操作
+    let victim = fsnotify_victim(&target_inode);
     parent_inode.unlink(filename)?;
+    fsnotify_delete(&parent_inode, filename, &target_inode, victim, false);
 
     return Ok(0);
 }
@@ -504,16 +542,15 @@ pub fn vfs_truncate(inode: Arc<dyn IndexNode>, len: usize) -> Result<(), SystemE
         return Err(SystemError::ETXTBSY);
     }
 
-    // 只读挂载检查：若当前 fs 是 MountFS 且带 RDONLY 标志，拒绝写
-    let fs = inode.fs();
-    if let Some(mfs) = fs.clone().downcast_arc::<MountFS>() {
-        let mount_flags = mfs.mount_flags();
-        if mount_flags.contains(crate::filesystem::vfs::mount::MountFlags::RDONLY) {
-            return Err(SystemError::EROFS);
-        }
+    // 只读挂载上的文件不能截断
+    if inode_mount_flags(&inode).contains(MountFlags::RDONLY) {
+        return Err(SystemError::EROFS);
     }
 
     let result = inode.resize(len);
+    if result.is_ok() {
+        fsnotify_modify(&inode);
+    }
 
     // Linux 语义：对普通文件进行截断（且确实改变 size）后，若无 CAP_FSETID，清理 suid/sgid。
     if result.is_ok() && old_size != len as i64 {
@@ -0,0 +1,201 @@
+//! 扩展属性（xattr）
+//!
+//! 属性名以命名空间前缀开头：
+//! - `user.`：普通用户数据，只能设置在普通文件和目录上，访问时检查文件的读写权限
+//! - `trusted.`：只有具有`CAP_SYS_ADMIN`的进程能够读写和列出
+//! - `security.`：安全模块使用，修改需要`CAP_SYS_ADMIN`
+//! - `system.`：POSIX ACL等，暂不支持
+//!
+//! 这里实现命名空间和权限的检查，以及内存文件系统使用的属性表。
+//! 具体文件系统只需要按完整的属性名保存属性值。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/fs/xattr.c
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::process::{cred::CAPFlags, ProcessManager};
+
+use super::{
+    permission::{check_inode_permission, PermissionMask},
+    FileType, IndexNode, InodeFlags, InodeMode,
+};
+
+/// 属性名的最大长度
+pub const XATTR_NAME_MAX: usize = 255;
+/// 属性值的最大长度
+pub const XATTR_SIZE_MAX: usize = 65536;
+/// 属性名列表的最大长度
+pub const XATTR_LIST_MAX: usize = 65536;
+
+/// 扩展属性的命名空间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XattrNamespace {
+    User,
+    Trusted,
+    Security,
+    System,
+}
+
+impl XattrNamespace {
+    const ALL: [XattrNamespace; 4] = [
+        XattrNamespace::User,
+        XattrNamespace::Trusted,
+        XattrNamespace::Security,
+        XattrNamespace::System,
+    ];
+
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            XattrNamespace::User => "user.",
+            XattrNamespace::Trusted => "trusted.",
+            XattrNamespace::Security => "security.",
+            XattrNamespace::System => "system.",
+        }
+    }
+
+    /// 解析属性名
+    ///
+    /// ## 返回值
+    /// - `Ok((命名空间, 去掉前缀之后的名字))`
+    /// - `Err(ERANGE)`: 名字为空或者太长
+    /// - `Err(EOPNOTSUPP_OR_ENOTSUP)`: 不认识的命名空间
+    /// - `Err(EINVAL)`: 只有前缀
+    pub fn parse(name: &str) -> Result<(Self, &str), SystemError> {
+        if name.is_empty() || name.len() > XATTR_NAME_MAX {
+            return Err(SystemError::ERANGE);
+        }
+        let ns = Self::ALL
+            .into_iter()
+            .find(|ns| name.starts_with(ns.prefix()))
+            .ok_or(SystemError::EOPNOTSUPP_OR_ENOTSUP)?;
+        let suffix = &name[ns.prefix().len()..];
+        if suffix.is_empty() {
+            return Err(SystemError::EINVAL);
+        }
+        Ok((ns, suffix))
+    }
+}
+
+fn capable(cap: CAPFlags) -> bool {
+    ProcessManager::current_pcb().cred().has_capability(cap)
+}
+
+/// 检查当前进程能否读取或修改`inode`在命名空间`ns`中的属性
+///
+/// ## 参数
+/// - `mask`: `MAY_READ`或者`MAY_WRITE`
+pub fn xattr_permission(
+    inode: &Arc<dyn IndexNode>,
+    ns: XattrNamespace,
+    mask: PermissionMask,
+) -> Result<(), SystemError> {
+    let write = mask.contains(PermissionMask::MAY_WRITE);
+    let md = inode.metadata()?;
+    if write
+        && md
+            .flags
+            .intersects(InodeFlags::S_IMMUTABLE | InodeFlags::S_APPEND)
+    {
+        return Err(SystemError::EPERM);
+    }
+
+    match ns {
+        XattrNamespace::Trusted => {
+            if !capable(CAPFlags::CAP_SYS_ADMIN) {
+                return Err(if write {
+                    SystemError::EPERM
+                } else {
+                    SystemError::ENODATA
+                });
+            }
+            Ok(())
+        }
+        XattrNamespace::Security => {
+            if write && !capable(CAPFlags::CAP_SYS_ADMIN) {
+                return Err(SystemError::EPERM);
+            }
+            Ok(())
+        }
+        XattrNamespace::System => Err(SystemError::EOPNOTSUPP_OR_ENOTSUP),
+        XattrNamespace::User => {
+            // 其他类型的文件的权限位有别的含义，不允许设置user属性
+            if md.file_type != FileType::File && md.file_type != FileType::Dir {
+                return Err(if write {
+                    SystemError::EPERM
+                } else {
+                    SystemError::ENODATA
+                });
+            }
+            if write
+                && md.file_type == FileType::Dir
+                && md.mode.contains(InodeMode::S_ISVTX)
+                && !ProcessManager::current_pcb().cred().is_owner(&md)
+                && !capable(CAPFlags::CAP_FOWNER)
+            {
+                return Err(SystemError::EPERM);
+            }
+            check_inode_permission(inode, &md, mask)
+        }
+    }
+}
+
+/// 过滤掉当前进程不能看到的属性名
+pub fn xattr_list_filter(names: Vec<String>) -> Vec<String> {
+    let trusted = capable(CAPFlags::CAP_SYS_ADMIN);
+    names
+        .into_iter()
+        .filter(|name| trusted || !name.starts_with(XattrNamespace::Trusted.prefix()))
+        .collect()
+}
+
+/// 把属性值复制到`buf`中，`buf`为空时只返回属性值的长度
+pub fn xattr_copy_value(value: &[u8], buf: &mut [u8]) -> Result<usize, SystemError> {
+    if buf.is_empty() {
+        return Ok(value.len());
+    }
+    if buf.len() < value.len() {
+        return Err(SystemError::ERANGE);
+    }
+    buf[..value.len()].copy_from_slice(value);
+    Ok(value.len())
+}
+
+/// 保存在内存中的扩展属性表，供tmpfs等内存文件系统使用
+#[derive(Debug, Default)]
+pub struct SimpleXattrs {
+    attrs: BTreeMap<String, Vec<u8>>,
+}
+
+impl SimpleXattrs {
+    pub const fn new() -> Self {
+        Self {
+            attrs: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let value = self.attrs.get(name).ok_or(SystemError::ENODATA)?;
+        xattr_copy_value(value, buf)
+    }
+
+    pub fn set(&mut self, name: &str, value: &[u8]) {
+        self.attrs.insert(name.to_string(), value.to_vec());
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), SystemError> {
+        self.attrs
+            .remove(name)
+            .map(|_| ())
+            .ok_or(SystemError::ENODATA)
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.attrs.keys().cloned().collect()
+    }
+}
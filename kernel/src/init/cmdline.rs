@@ -385,6 +385,14 @@ impl KernelCmdlineManager {
         self.default_initialize();
         // 处理loglevel参数
         crate::debug::klog::loglevel::handle_loglevel_param();
+        // 处理klog.sinks参数
+        crate::debug::klog::sink::handle_klog_sinks_param();
+        // 处理printk.time参数
+        crate::libs::printk::handle_printk_time_param();
+        // 处理errctx.verbosity参数
+        crate::libs::error_context::handle_errctx_verbosity_param();
+        // 处理oops参数
+        crate::debug::oops::handle_oops_param();
         fence(Ordering::SeqCst);
     }
 
@@ -4,6 +4,7 @@ use crate::{
         time::time_init,
         CurrentIrqArch, CurrentSMPArch, CurrentSchedArch,
     },
+    debug::stack_protector::boot_init_stack_canary,
     driver::{
         acpi::acpi_init, base::init::driver_init, serial::serial_early_init,
         video::VideoRefreshManager,
@@ -43,6 +44,9 @@ pub fn start_kernel() -> ! {
     // 进入内核后，中断应该是关闭的
     assert!(!CurrentIrqArch::is_irq_enabled());
 
+    // 本函数不会返回，在这里更换canary是安全的
+    unsafe { boot_init_stack_canary() };
+
     do_start_kernel();
 
     CurrentSchedArch::initial_setup_sched_local();
@@ -79,7 +83,10 @@ fn do_start_kernel() {
     vfs_init().expect("vfs init failed");
     driver_init().expect("driver init failed");
 
-    acpi_init().expect("acpi init failed");
+    // 微虚拟机可能没有ACPI，此时平台信息从MP表等其他来源获取
+    if let Err(e) = acpi_init() {
+        log::warn!("acpi init failed: {:?}, continuing without ACPI", e);
+    }
     crate::sched::sched_init();
     process_init();
     early_smp_init().expect("early smp init failed");
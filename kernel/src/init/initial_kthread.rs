@@ -10,9 +10,10 @@ use system_error::SystemError;
 use crate::filesystem::vfs::vcore::change_root_fs;
 
 use crate::{
-    arch::{interrupt::TrapFrame, process::arch_switch_to_user},
+    arch::{interrupt::TrapFrame, process::arch_switch_to_user, MMArch},
     driver::net::e1000e::e1000e::e1000e_init,
     filesystem::vfs::vcore::mount_root_fs,
+    mm::MemoryManagementArch,
     net::net_core::net_init,
     process::{
         exec::ProcInitInfo, execve::do_execve, kthread::KernelThreadMechanism, stdio::stdio_init,
@@ -70,6 +71,10 @@ fn kernel_init() -> Result<(), SystemError> {
     KernelThreadMechanism::init_stage2();
     kenrel_init_freeable()?;
     set_system_state(SystemState::FreeingInitMem);
+    unsafe {
+        MMArch::free_initmem();
+        MMArch::mark_rodata_ro();
+    }
     #[cfg(target_arch = "x86_64")]
     crate::driver::disk::ahci::ahci_init()
         .inspect_err(|e| log::error!("ahci_init failed: {:?}", e))
@@ -5,13 +5,11 @@ use alloc::vec::Vec;
 
 use crate::filesystem::ramfs::RamFS;
 use crate::filesystem::vfs::mount::MountFlags;
-use crate::filesystem::vfs::FilePrivateData;
 use crate::filesystem::vfs::FileSystem;
 use crate::filesystem::vfs::MountFS;
 use crate::init::boot::boot_callbacks;
 use crate::init::initcall::INITCALL_ROOTFS;
 use crate::libs::decompress::xz_decompress;
-use crate::libs::mutex::Mutex;
 use crate::process::namespace::propagation::MountPropagation;
 use cpio_reader::Mode;
 use system_error::SystemError;
@@ -175,12 +173,7 @@ pub fn initramfs_init() -> Result<(), SystemError> {
             FileType::File => {
                 // 插入, 随后写入文件数据
                 let inode = parent_inode.create(filename, file_type, mode)?;
-                inode.write_at(
-                    0,
-                    entry.file.len(),
-                    &entry.file,
-                    Mutex::new(crate::filesystem::vfs::FilePrivateData::Unused).lock(),
-                )?;
+                inode.kernel_write_at(0, &entry.file)?;
             }
             FileType::CharDevice => {
                 // 不处理, 如果使用 initramfs 那么直接从已经初始化好的根文件系统迁移到此文件系统
@@ -210,9 +203,7 @@ pub fn initramfs_init() -> Result<(), SystemError> {
         })?;
         let new_inode =
             parent_inode.create_with_data(filename, FileType::SymLink, InodeMode::S_IRWXUGO, 0)?;
-        let buf = other_name.as_bytes();
-        let len = buf.len();
-        new_inode.write_at(0, len, buf, Mutex::new(FilePrivateData::Unused).lock())?;
+        new_inode.kernel_write_at(0, other_name.as_bytes())?;
     }
 
     // 下面的方式是查看外置 initramfs, 例如使用 qemu 的 -initrd 参数加载的
@@ -20,8 +20,10 @@ use crate::{
         pid::PidType, ProcessControlBlock, ProcessFlags, ProcessManager, ProcessSignalInfo, RawPid,
     },
     time::{
-        sleep::nanosleep, syscall::PosixClockID, timekeeping::getnstimeofday, Instant,
-        PosixTimeSpec,
+        sleep::nanosleep,
+        syscall::{posix_clock_now, PosixClockID},
+        timekeeping::getnstimeofday,
+        Instant, PosixTimeSpec,
     },
 };
 
@@ -949,7 +951,8 @@ pub struct PollRestartBlockData {
 fn ktime_now(clockid: PosixClockID) -> PosixTimeSpec {
     match clockid {
         PosixClockID::Realtime => getnstimeofday(),
-        PosixClockID::Monotonic | PosixClockID::Boottime => getnstimeofday(),
+        // 与clock_nanosleep一致，deadline是time namespace中的时间
+        PosixClockID::Monotonic | PosixClockID::Boottime => posix_clock_now(clockid),
         PosixClockID::ProcessCPUTimeID => {
             let pcb = ProcessManager::current_pcb();
             PosixTimeSpec::from_ns(pcb.process_cputime_ns())
@@ -0,0 +1,96 @@
+//! CRC校验和
+//!
+//! 对`kdepends::crc`中通用实现的封装：在CPU支持时使用硬件指令加速，否则回退到查表实现。
+//! 目前x86_64上的CRC32C会在支持SSE4.2时使用`crc32`指令。
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use kdepends::crc::crc32c::crc32c_le_generic;
+
+const ACCEL_UNKNOWN: u8 = 0;
+const ACCEL_NONE: u8 = 1;
+const ACCEL_HW: u8 = 2;
+
+/// CRC32C是否可以使用硬件加速（首次查询时检测）
+static CRC32C_ACCEL: AtomicU8 = AtomicU8::new(ACCEL_UNKNOWN);
+
+/// ## 当前CPU是否支持硬件加速的CRC32C
+pub fn crc32c_accelerated() -> bool {
+    match CRC32C_ACCEL.load(Ordering::Relaxed) {
+        ACCEL_HW => true,
+        ACCEL_NONE => false,
+        _ => {
+            let hw = arch::crc32c_hw_supported();
+            CRC32C_ACCEL.store(if hw { ACCEL_HW } else { ACCEL_NONE }, Ordering::Relaxed);
+            hw
+        }
+    }
+}
+
+/// ## 计算CRC32C（Castagnoli）
+///
+/// 与Linux的`crc32c()`不同，这里与`crc32c_le_generic`一致，不对输入和输出取反。
+///
+/// ## 参数
+///
+/// - `crc`: 初始值。新计算时一般为`!0`，增量计算时为上一次的返回值
+/// - `buf`: 要计算的数据
+pub fn crc32c(crc: u32, buf: &[u8]) -> u32 {
+    if crc32c_accelerated() {
+        // SAFETY: 已经确认CPU支持该指令
+        return unsafe { arch::crc32c_hw(crc, buf) };
+    }
+    crc32c_le_generic(crc, buf)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    pub(super) fn crc32c_hw_supported() -> bool {
+        raw_cpuid::CpuId::new()
+            .get_feature_info()
+            .map(|f| f.has_sse42())
+            .unwrap_or(false)
+    }
+
+    /// 使用SSE4.2的`crc32`指令计算CRC32C
+    ///
+    /// 这里直接使用内联汇编，而不是`#[target_feature]`，避免编译器在内核中生成使用xmm寄存器的代码。
+    ///
+    /// ## Safety
+    ///
+    /// 调用者需要保证CPU支持SSE4.2
+    pub(super) unsafe fn crc32c_hw(crc: u32, buf: &[u8]) -> u32 {
+        let mut crc = crc as u64;
+        let mut chunks = buf.chunks_exact(8);
+        for chunk in &mut chunks {
+            let v = u64::from_le_bytes(chunk.try_into().unwrap());
+            core::arch::asm!(
+                "crc32 {crc}, {v}",
+                crc = inout(reg) crc,
+                v = in(reg) v,
+                options(pure, nomem, nostack)
+            );
+        }
+        let mut crc = crc as u32;
+        for &b in chunks.remainder() {
+            core::arch::asm!(
+                "crc32 {crc:e}, {b}",
+                crc = inout(reg) crc,
+                b = in(reg_byte) b,
+                options(pure, nomem, nostack)
+            );
+        }
+        crc
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+mod arch {
+    pub(super) fn crc32c_hw_supported() -> bool {
+        false
+    }
+
+    pub(super) unsafe fn crc32c_hw(crc: u32, buf: &[u8]) -> u32 {
+        kdepends::crc::crc32c::crc32c_le_generic(crc, buf)
+    }
+}
@@ -0,0 +1,219 @@
+//! AES分组密码（FIPS-197）
+//!
+//! 按字节实现，S盒在编译期由GF(2^8)上的求逆和仿射变换生成。
+//!
+//! 参考: https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.197-upd1.pdf
+
+use system_error::SystemError;
+
+/// 分组长度（字节）
+pub const AES_BLOCK_SIZE: usize = 16;
+/// 最多的轮数（AES-256）
+const AES_MAX_ROUNDS: usize = 14;
+
+/// GF(2^8)上的乘法，模多项式为x^8 + x^4 + x^3 + x + 1
+const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut p = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            p ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    p
+}
+
+/// 生成S盒和逆S盒
+const fn build_sboxes() -> ([u8; 256], [u8; 256]) {
+    // 以3为生成元的指数表和对数表，用于求乘法逆元
+    let mut exp = [0u8; 256];
+    let mut log = [0u8; 256];
+    let mut x = 1u8;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x;
+        log[x as usize] = i as u8;
+        x = gf_mul(x, 3);
+        i += 1;
+    }
+
+    let mut sbox = [0u8; 256];
+    let mut inv_sbox = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let inv = if i == 0 {
+            0
+        } else {
+            exp[(255 - log[i] as usize) % 255]
+        };
+        let s = inv
+            ^ inv.rotate_left(1)
+            ^ inv.rotate_left(2)
+            ^ inv.rotate_left(3)
+            ^ inv.rotate_left(4)
+            ^ 0x63;
+        sbox[i] = s;
+        inv_sbox[s as usize] = i as u8;
+        i += 1;
+    }
+    (sbox, inv_sbox)
+}
+
+const SBOXES: ([u8; 256], [u8; 256]) = build_sboxes();
+static SBOX: [u8; 256] = SBOXES.0;
+static INV_SBOX: [u8; 256] = SBOXES.1;
+
+/// 密钥扩展使用的轮常量
+const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// 展开后的AES密钥
+pub struct Aes {
+    rounds: usize,
+    round_keys: [[u8; AES_BLOCK_SIZE]; AES_MAX_ROUNDS + 1],
+}
+
+impl Aes {
+    /// # 展开密钥
+    ///
+    /// ## 参数
+    /// - `key`: 16字节（AES-128）或32字节（AES-256）的密钥
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EINVAL)`: 密钥长度不支持
+    pub fn new(key: &[u8]) -> Result<Self, SystemError> {
+        let (nk, rounds) = match key.len() {
+            16 => (4, 10),
+            32 => (8, 14),
+            _ => return Err(SystemError::EINVAL),
+        };
+
+        let nwords = 4 * (rounds + 1);
+        let mut w = [[0u8; 4]; 4 * (AES_MAX_ROUNDS + 1)];
+        for (i, word) in w.iter_mut().enumerate().take(nk) {
+            word.copy_from_slice(&key[i * 4..i * 4 + 4]);
+        }
+        for i in nk..nwords {
+            let mut temp = w[i - 1];
+            if i % nk == 0 {
+                temp.rotate_left(1);
+                temp = temp.map(|b| SBOX[b as usize]);
+                temp[0] ^= RCON[i / nk - 1];
+            } else if nk > 6 && i % nk == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            let prev = w[i - nk];
+            for (j, b) in w[i].iter_mut().enumerate() {
+                *b = prev[j] ^ temp[j];
+            }
+        }
+
+        let mut round_keys = [[0u8; AES_BLOCK_SIZE]; AES_MAX_ROUNDS + 1];
+        for (r, rk) in round_keys.iter_mut().enumerate().take(rounds + 1) {
+            for c in 0..4 {
+                rk[c * 4..c * 4 + 4].copy_from_slice(&w[r * 4 + c]);
+            }
+        }
+        w.iter_mut().for_each(|word| zeroize(word));
+        Ok(Self { rounds, round_keys })
+    }
+
+    /// 原地加密一个分组
+    pub fn encrypt_block(&self, block: &mut [u8; AES_BLOCK_SIZE]) {
+        add_round_key(block, &self.round_keys[0]);
+        for r in 1..self.rounds {
+            sub_bytes(block, &SBOX);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, &self.round_keys[r]);
+        }
+        sub_bytes(block, &SBOX);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[self.rounds]);
+    }
+
+    /// 原地解密一个分组
+    pub fn decrypt_block(&self, block: &mut [u8; AES_BLOCK_SIZE]) {
+        add_round_key(block, &self.round_keys[self.rounds]);
+        for r in (1..self.rounds).rev() {
+            inv_shift_rows(block);
+            sub_bytes(block, &INV_SBOX);
+            add_round_key(block, &self.round_keys[r]);
+            inv_mix_columns(block);
+        }
+        inv_shift_rows(block);
+        sub_bytes(block, &INV_SBOX);
+        add_round_key(block, &self.round_keys[0]);
+    }
+}
+
+impl Drop for Aes {
+    fn drop(&mut self) {
+        self.round_keys.iter_mut().for_each(|rk| zeroize(rk));
+    }
+}
+
+/// 清除内存中的密钥，不会被编译器优化掉
+pub fn zeroize(buf: &mut [u8]) {
+    for b in buf.iter_mut() {
+        unsafe { core::ptr::write_volatile(b, 0) };
+    }
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+// 状态按列存放：第c列第r行的字节为state[c * 4 + r]
+
+fn add_round_key(state: &mut [u8; AES_BLOCK_SIZE], rk: &[u8; AES_BLOCK_SIZE]) {
+    for (s, k) in state.iter_mut().zip(rk) {
+        *s ^= k;
+    }
+}
+
+fn sub_bytes(state: &mut [u8; AES_BLOCK_SIZE], sbox: &[u8; 256]) {
+    for s in state.iter_mut() {
+        *s = sbox[*s as usize];
+    }
+}
+
+/// 第r行循环左移r个字节
+fn shift_rows(state: &mut [u8; AES_BLOCK_SIZE]) {
+    let old = *state;
+    for c in 0..4 {
+        for r in 1..4 {
+            state[c * 4 + r] = old[((c + r) % 4) * 4 + r];
+        }
+    }
+}
+
+fn inv_shift_rows(state: &mut [u8; AES_BLOCK_SIZE]) {
+    let old = *state;
+    for c in 0..4 {
+        for r in 1..4 {
+            state[((c + r) % 4) * 4 + r] = old[c * 4 + r];
+        }
+    }
+}
+
+fn mix_columns(state: &mut [u8; AES_BLOCK_SIZE]) {
+    for col in state.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [col[0], col[1], col[2], col[3]];
+        col[0] = gf_mul(a0, 2) ^ gf_mul(a1, 3) ^ a2 ^ a3;
+        col[1] = a0 ^ gf_mul(a1, 2) ^ gf_mul(a2, 3) ^ a3;
+        col[2] = a0 ^ a1 ^ gf_mul(a2, 2) ^ gf_mul(a3, 3);
+        col[3] = gf_mul(a0, 3) ^ a1 ^ a2 ^ gf_mul(a3, 2);
+    }
+}
+
+fn inv_mix_columns(state: &mut [u8; AES_BLOCK_SIZE]) {
+    for col in state.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [col[0], col[1], col[2], col[3]];
+        col[0] = gf_mul(a0, 14) ^ gf_mul(a1, 11) ^ gf_mul(a2, 13) ^ gf_mul(a3, 9);
+        col[1] = gf_mul(a0, 9) ^ gf_mul(a1, 14) ^ gf_mul(a2, 11) ^ gf_mul(a3, 13);
+        col[2] = gf_mul(a0, 13) ^ gf_mul(a1, 9) ^ gf_mul(a2, 14) ^ gf_mul(a3, 11);
+        col[3] = gf_mul(a0, 11) ^ gf_mul(a1, 13) ^ gf_mul(a2, 9) ^ gf_mul(a3, 14);
+    }
+}
@@ -0,0 +1,89 @@
+//! 哈希函数的公共接口
+//!
+//! MD4、MD5和SHA-256都是Merkle–Damgård结构，分组长度为64字节，
+//! 末尾填充一个0x80、若干个0和消息的比特长度。[`BlockBuffer`]负责分组和填充，
+//! 各算法只需要实现压缩函数。
+
+/// 哈希函数
+pub trait Digest: Sized {
+    /// 分组长度（字节），HMAC按这个长度处理密钥
+    const BLOCK_SIZE: usize;
+    /// 摘要
+    type Output: AsRef<[u8]> + Copy;
+
+    fn new() -> Self;
+
+    fn update(&mut self, data: &[u8]);
+
+    fn finalize(self) -> Self::Output;
+
+    /// 一次计算`data`的摘要
+    fn digest(data: &[u8]) -> Self::Output {
+        let mut d = Self::new();
+        d.update(data);
+        d.finalize()
+    }
+}
+
+/// 64字节分组的缓冲区
+pub(super) struct BlockBuffer {
+    buf: [u8; 64],
+    len: usize,
+    /// 已经输入的字节数
+    total: u64,
+}
+
+impl BlockBuffer {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; 64],
+            len: 0,
+            total: 0,
+        }
+    }
+
+    /// 输入数据，每凑满一个分组调用一次`compress`
+    pub fn update(&mut self, mut data: &[u8], mut compress: impl FnMut(&[u8; 64])) {
+        self.total = self.total.wrapping_add(data.len() as u64);
+        if self.len > 0 {
+            let n = (64 - self.len).min(data.len());
+            self.buf[self.len..self.len + n].copy_from_slice(&data[..n]);
+            self.len += n;
+            data = &data[n..];
+            if self.len < 64 {
+                return;
+            }
+            compress(&self.buf);
+            self.len = 0;
+        }
+        let mut chunks = data.chunks_exact(64);
+        for block in &mut chunks {
+            compress(block.try_into().unwrap());
+        }
+        let rest = chunks.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.len = rest.len();
+    }
+
+    /// 填充并处理最后的分组
+    ///
+    /// ## 参数
+    /// - `big_endian`: 比特长度是否按大端序写入（SHA-256为大端序，MD4/MD5为小端序）
+    pub fn finish(&mut self, big_endian: bool, mut compress: impl FnMut(&[u8; 64])) {
+        let bits = self.total.wrapping_mul(8);
+        self.buf[self.len] = 0x80;
+        self.buf[self.len + 1..].fill(0);
+        if self.len >= 56 {
+            compress(&self.buf);
+            self.buf.fill(0);
+        }
+        let len_bytes = if big_endian {
+            bits.to_be_bytes()
+        } else {
+            bits.to_le_bytes()
+        };
+        self.buf[56..].copy_from_slice(&len_bytes);
+        compress(&self.buf);
+        self.len = 0;
+    }
+}
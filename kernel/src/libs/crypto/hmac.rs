@@ -0,0 +1,50 @@
+//! HMAC（RFC 2104）
+
+use alloc::{vec, vec::Vec};
+
+use super::{aes::zeroize, digest::Digest};
+
+pub struct Hmac<D: Digest> {
+    inner: D,
+    /// 与opad异或之后的密钥
+    outer_key: Vec<u8>,
+}
+
+impl<D: Digest> Hmac<D> {
+    pub fn new(key: &[u8]) -> Self {
+        let mut block = vec![0u8; D::BLOCK_SIZE];
+        if key.len() > D::BLOCK_SIZE {
+            let hashed = D::digest(key);
+            block[..hashed.as_ref().len()].copy_from_slice(hashed.as_ref());
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = D::new();
+        let ipad: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+        inner.update(&ipad);
+        let outer_key = block.iter().map(|b| b ^ 0x5c).collect();
+        zeroize(&mut block);
+        Self { inner, outer_key }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(mut self) -> D::Output {
+        let inner = self.inner.finalize();
+        let mut outer = D::new();
+        outer.update(&self.outer_key);
+        outer.update(inner.as_ref());
+        zeroize(&mut self.outer_key);
+        outer.finalize()
+    }
+
+    /// 一次计算`data`的HMAC
+    pub fn mac(key: &[u8], data: &[u8]) -> D::Output {
+        let mut h = Self::new(key);
+        h.update(data);
+        h.finalize()
+    }
+}
@@ -0,0 +1,114 @@
+//! MD4（RFC 1320）
+//!
+//! MD4早已不安全，这里只用于计算NTLM的NT哈希。
+
+use super::digest::{BlockBuffer, Digest};
+
+pub struct Md4 {
+    state: [u32; 4],
+    buffer: BlockBuffer,
+}
+
+fn compress(state: &mut [u32; 4], block: &[u8; 64]) {
+    let mut x = [0u32; 16];
+    for (i, w) in x.iter_mut().enumerate() {
+        *w = u32::from_le_bytes(block[i * 4..i * 4 + 4].try_into().unwrap());
+    }
+    let [mut a, mut b, mut c, mut d] = *state;
+
+    let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+    let g = |x: u32, y: u32, z: u32| (x & y) | (x & z) | (y & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+
+    for &i in &[0, 4, 8, 12] {
+        a = a.wrapping_add(f(b, c, d)).wrapping_add(x[i]).rotate_left(3);
+        d = d
+            .wrapping_add(f(a, b, c))
+            .wrapping_add(x[i + 1])
+            .rotate_left(7);
+        c = c
+            .wrapping_add(f(d, a, b))
+            .wrapping_add(x[i + 2])
+            .rotate_left(11);
+        b = b
+            .wrapping_add(f(c, d, a))
+            .wrapping_add(x[i + 3])
+            .rotate_left(19);
+    }
+    for &i in &[0, 1, 2, 3] {
+        a = a
+            .wrapping_add(g(b, c, d))
+            .wrapping_add(x[i])
+            .wrapping_add(0x5a82_7999)
+            .rotate_left(3);
+        d = d
+            .wrapping_add(g(a, b, c))
+            .wrapping_add(x[i + 4])
+            .wrapping_add(0x5a82_7999)
+            .rotate_left(5);
+        c = c
+            .wrapping_add(g(d, a, b))
+            .wrapping_add(x[i + 8])
+            .wrapping_add(0x5a82_7999)
+            .rotate_left(9);
+        b = b
+            .wrapping_add(g(c, d, a))
+            .wrapping_add(x[i + 12])
+            .wrapping_add(0x5a82_7999)
+            .rotate_left(13);
+    }
+    for &i in &[0, 2, 1, 3] {
+        a = a
+            .wrapping_add(h(b, c, d))
+            .wrapping_add(x[i])
+            .wrapping_add(0x6ed9_eba1)
+            .rotate_left(3);
+        d = d
+            .wrapping_add(h(a, b, c))
+            .wrapping_add(x[i + 8])
+            .wrapping_add(0x6ed9_eba1)
+            .rotate_left(9);
+        c = c
+            .wrapping_add(h(d, a, b))
+            .wrapping_add(x[i + 4])
+            .wrapping_add(0x6ed9_eba1)
+            .rotate_left(11);
+        b = b
+            .wrapping_add(h(c, d, a))
+            .wrapping_add(x[i + 12])
+            .wrapping_add(0x6ed9_eba1)
+            .rotate_left(15);
+    }
+
+    state[0] = state[0].wrapping_add(a);
+    state[1] = state[1].wrapping_add(b);
+    state[2] = state[2].wrapping_add(c);
+    state[3] = state[3].wrapping_add(d);
+}
+
+impl Digest for Md4 {
+    const BLOCK_SIZE: usize = 64;
+    type Output = [u8; 16];
+
+    fn new() -> Self {
+        Self {
+            state: [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+            buffer: BlockBuffer::new(),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.buffer.update(data, |block| compress(state, block));
+    }
+
+    fn finalize(mut self) -> [u8; 16] {
+        let state = &mut self.state;
+        self.buffer.finish(false, |block| compress(state, block));
+        let mut out = [0u8; 16];
+        for (i, w) in self.state.iter().enumerate() {
+            out[i * 4..i * 4 + 4].copy_from_slice(&w.to_le_bytes());
+        }
+        out
+    }
+}
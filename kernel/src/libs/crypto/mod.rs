@@ -0,0 +1,16 @@
+//! 软件实现的密码算法
+//!
+//! - [`aes`]: AES分组密码（FIPS-197），支持128位和256位密钥；
+//! - [`xts`]: XTS-AES（IEEE 1619），用于磁盘加密，每个扇区使用由扇区号得到的tweak；
+//! - [`md4`]、[`md5`]、[`sha256`]: 哈希函数，实现[`digest::Digest`]；
+//! - [`hmac`]: 基于任意[`digest::Digest`]的HMAC。
+//!
+//! 没有使用AES-NI等硬件加速，查表实现也不是常数时间的，只适合测试使用。
+
+pub mod aes;
+pub mod digest;
+pub mod hmac;
+pub mod md4;
+pub mod md5;
+pub mod sha256;
+pub mod xts;
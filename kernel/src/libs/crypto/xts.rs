@@ -0,0 +1,95 @@
+//! XTS-AES（IEEE 1619）
+//!
+//! 每个数据单元（磁盘加密中为一个扇区）用第二个密钥加密tweak得到初始的T，
+//! 第j个分组的T为初始T乘以GF(2^128)中的α^j。数据单元的长度必须是分组长度的整数倍，
+//! 没有实现密文挪用（ciphertext stealing）。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/crypto/xts.c
+
+use system_error::SystemError;
+
+use super::aes::{zeroize, Aes, AES_BLOCK_SIZE};
+
+/// XTS-AES的密钥
+pub struct XtsAes {
+    /// 加密数据的密钥
+    data: Aes,
+    /// 加密tweak的密钥
+    tweak: Aes,
+}
+
+impl XtsAes {
+    /// # 创建XTS-AES
+    ///
+    /// ## 参数
+    /// - `key`: 32字节（XTS-AES-128）或64字节（XTS-AES-256）的密钥，前一半加密数据，后一半加密tweak
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EINVAL)`: 密钥长度不支持，或者两半密钥相同
+    pub fn new(key: &[u8]) -> Result<Self, SystemError> {
+        if key.len() != 32 && key.len() != 64 {
+            return Err(SystemError::EINVAL);
+        }
+        let (k1, k2) = key.split_at(key.len() / 2);
+        // 与Linux的xts_verify_key一样拒绝两半相同的弱密钥
+        if k1 == k2 {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(Self {
+            data: Aes::new(k1)?,
+            tweak: Aes::new(k2)?,
+        })
+    }
+
+    /// # 原地加密一个数据单元
+    ///
+    /// ## 参数
+    /// - `tweak`: 数据单元的tweak，磁盘加密中通常由扇区号得到
+    /// - `buf`: 数据单元，长度必须是16的整数倍
+    pub fn encrypt(&self, tweak: &[u8; AES_BLOCK_SIZE], buf: &mut [u8]) {
+        self.crypt(tweak, buf, |block| self.data.encrypt_block(block));
+    }
+
+    /// # 原地解密一个数据单元
+    ///
+    /// 参数与[`XtsAes::encrypt`]相同
+    pub fn decrypt(&self, tweak: &[u8; AES_BLOCK_SIZE], buf: &mut [u8]) {
+        self.crypt(tweak, buf, |block| self.data.decrypt_block(block));
+    }
+
+    fn crypt<F>(&self, tweak: &[u8; AES_BLOCK_SIZE], buf: &mut [u8], f: F)
+    where
+        F: Fn(&mut [u8; AES_BLOCK_SIZE]),
+    {
+        debug_assert!(buf.len() % AES_BLOCK_SIZE == 0);
+        let mut t = *tweak;
+        self.tweak.encrypt_block(&mut t);
+        for chunk in buf.chunks_exact_mut(AES_BLOCK_SIZE) {
+            let block: &mut [u8; AES_BLOCK_SIZE] = chunk.try_into().unwrap();
+            xor_block(block, &t);
+            f(block);
+            xor_block(block, &t);
+            mul_alpha(&mut t);
+        }
+        zeroize(&mut t);
+    }
+}
+
+fn xor_block(block: &mut [u8; AES_BLOCK_SIZE], t: &[u8; AES_BLOCK_SIZE]) {
+    for (b, t) in block.iter_mut().zip(t) {
+        *b ^= t;
+    }
+}
+
+/// T乘以α，T按小端序表示GF(2^128)中的元素，模多项式为x^128 + x^7 + x^2 + x + 1
+fn mul_alpha(t: &mut [u8; AES_BLOCK_SIZE]) {
+    let mut carry = 0;
+    for b in t.iter_mut() {
+        let next = *b >> 7;
+        *b = (*b << 1) | carry;
+        carry = next;
+    }
+    if carry != 0 {
+        t[0] ^= 0x87;
+    }
+}
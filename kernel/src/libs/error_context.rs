@@ -0,0 +1,222 @@
+//! 带上下文的错误
+//!
+//! [`SystemError`]只有一个错误码，深层的EIO传到上层时已经看不出是在哪里、因为什么出的错。
+//! 子系统内部可以用[`ErrorContext`]给错误逐层加上说明，得到[`ContextError`]：
+//!
+//! ```ignore
+//! fn do_read(&self, offset: usize) -> Result<usize, ContextError> {
+//!     let inode = self.backing_inode()?;
+//!     inode
+//!         .read_at(offset, ...)
+//!         .with_context(|| format!("read backing file at offset {}", offset))
+//! }
+//! ```
+//!
+//! 错误离开子系统时（转换回`SystemError`）把上下文链打印到内核日志，错误码原样返回给用户态。
+//! 打印的详细程度由内核命令行参数`errctx.verbosity`或者`/proc/sys/kernel/errctx_verbosity`控制：
+//!
+//! - 0: 不打印
+//! - 1: 只打印最外层的上下文和错误码（默认）
+//! - 2: 打印完整的上下文链
+//! - 3: 打印完整的上下文链，以及每一层上下文的源码位置
+//!
+//! EAGAIN、EINTR等表示需要重试的错误码属于正常流程，不会打印。
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{
+    fmt::{self, Display},
+    panic::Location,
+    sync::atomic::{AtomicU8, Ordering},
+};
+use log::warn;
+use system_error::SystemError;
+
+/// 最大的打印详细程度
+pub const ERRCTX_VERBOSITY_MAX: u8 = 3;
+
+static ERRCTX_VERBOSITY: AtomicU8 = AtomicU8::new(1);
+
+kernel_cmdline_param_kv!(ERRCTX_VERBOSITY_PARAM, "errctx.verbosity", "");
+
+/// 处理`errctx.verbosity`命令行参数
+pub fn handle_errctx_verbosity_param() {
+    let Some(value) = ERRCTX_VERBOSITY_PARAM.value_str() else {
+        return;
+    };
+    if value.is_empty() {
+        return;
+    }
+    match value.parse::<u8>() {
+        Ok(v) if v <= ERRCTX_VERBOSITY_MAX => ERRCTX_VERBOSITY.store(v, Ordering::SeqCst),
+        _ => warn!(
+            "errctx.verbosity: invalid value '{}', must be 0~{}",
+            value, ERRCTX_VERBOSITY_MAX
+        ),
+    }
+}
+
+/// 当前的打印详细程度
+pub fn errctx_verbosity() -> u8 {
+    ERRCTX_VERBOSITY.load(Ordering::Relaxed)
+}
+
+/// # 设置打印详细程度
+///
+/// ## 返回值
+///
+/// - `Err(SystemError::EINVAL)`: `verbosity`大于[`ERRCTX_VERBOSITY_MAX`]
+pub fn set_errctx_verbosity(verbosity: u8) -> Result<(), SystemError> {
+    if verbosity > ERRCTX_VERBOSITY_MAX {
+        return Err(SystemError::EINVAL);
+    }
+    ERRCTX_VERBOSITY.store(verbosity, Ordering::SeqCst);
+    Ok(())
+}
+
+/// 上下文链中的一层
+#[derive(Debug)]
+struct ContextFrame {
+    msg: Cow<'static, str>,
+    location: &'static Location<'static>,
+}
+
+/// 带上下文链的错误
+#[derive(Debug)]
+pub struct ContextError {
+    errno: SystemError,
+    /// 从内到外排列
+    frames: Vec<ContextFrame>,
+}
+
+impl ContextError {
+    pub fn new(errno: SystemError) -> Self {
+        Self {
+            errno,
+            frames: Vec::new(),
+        }
+    }
+
+    /// 返回给用户态的错误码
+    pub fn errno(&self) -> SystemError {
+        self.errno.clone()
+    }
+
+    /// 在最外层加上一层上下文
+    #[track_caller]
+    pub fn push(mut self, msg: impl Into<Cow<'static, str>>) -> Self {
+        self.frames.push(ContextFrame {
+            msg: msg.into(),
+            location: Location::caller(),
+        });
+        self
+    }
+
+    /// 是否是表示需要重试的错误，这些错误属于正常流程
+    fn is_transient(&self) -> bool {
+        matches!(
+            self.errno,
+            SystemError::EAGAIN_OR_EWOULDBLOCK
+                | SystemError::EINTR
+                | SystemError::ERESTARTSYS
+                | SystemError::ERESTARTNOINTR
+                | SystemError::ERESTARTNOHAND
+                | SystemError::ERESTART_RESTARTBLOCK
+        )
+    }
+
+    /// 按照当前的详细程度把上下文链打印到内核日志
+    pub fn report(&self) {
+        if self.is_transient() || self.frames.is_empty() {
+            return;
+        }
+        match errctx_verbosity() {
+            0 => {}
+            1 => warn!("{}: {}", self.frames.last().unwrap().msg, self.errno),
+            2 => warn!("{}", self),
+            _ => {
+                warn!("{}", self.errno);
+                for frame in self.frames.iter().rev() {
+                    warn!(
+                        "    {} ({}:{})",
+                        frame.msg,
+                        frame.location.file(),
+                        frame.location.line()
+                    );
+                }
+            }
+        }
+    }
+
+    /// 打印上下文链，返回错误码
+    pub fn into_errno(self) -> SystemError {
+        self.report();
+        self.errno
+    }
+}
+
+/// 格式为`外层上下文: 内层上下文: EIO (Input/output error)`
+impl Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for frame in self.frames.iter().rev() {
+            write!(f, "{}: ", frame.msg)?;
+        }
+        write!(f, "{}", self.errno)
+    }
+}
+
+impl From<SystemError> for ContextError {
+    fn from(errno: SystemError) -> Self {
+        Self::new(errno)
+    }
+}
+
+/// 错误离开子系统的边界，打印上下文链
+impl From<ContextError> for SystemError {
+    fn from(err: ContextError) -> Self {
+        err.into_errno()
+    }
+}
+
+/// 给`Result`的错误加上上下文
+pub trait ErrorContext<T> {
+    /// 加上一层固定的上下文
+    fn context(self, msg: &'static str) -> Result<T, ContextError>;
+
+    /// 加上一层上下文，只有出错时才会调用`f`生成上下文
+    fn with_context<C, F>(self, f: F) -> Result<T, ContextError>
+    where
+        C: Into<Cow<'static, str>>,
+        F: FnOnce() -> C;
+}
+
+impl<T, E: Into<ContextError>> ErrorContext<T> for Result<T, E> {
+    #[track_caller]
+    fn context(self, msg: &'static str) -> Result<T, ContextError> {
+        let location = Location::caller();
+        self.map_err(|e| {
+            let mut err = e.into();
+            err.frames.push(ContextFrame {
+                msg: Cow::Borrowed(msg),
+                location,
+            });
+            err
+        })
+    }
+
+    #[track_caller]
+    fn with_context<C, F>(self, f: F) -> Result<T, ContextError>
+    where
+        C: Into<Cow<'static, str>>,
+        F: FnOnce() -> C,
+    {
+        let location = Location::caller();
+        self.map_err(|e| {
+            let mut err = e.into();
+            err.frames.push(ContextFrame {
+                msg: f().into(),
+                location,
+            });
+            err
+        })
+    }
+}
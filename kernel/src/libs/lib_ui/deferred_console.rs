@@ -0,0 +1,318 @@
+//! 屏幕输出的延迟刷新
+//!
+//! SMP上多个CPU同时打印时，如果各自直接写屏幕，会在窗口锁和帧缓冲上串行化，
+//! 缓存行在CPU之间来回迁移，启动明显变慢。这里让每个CPU先把要写到屏幕的内容
+//! 放进自己的队列，由`console_flush`内核线程按照入队的先后顺序统一写到屏幕上。
+//! 串口、netconsole等其他输出不经过这里。
+//!
+//! 刷新线程启动之前，以及panic之后，屏幕输出是同步的。
+
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+
+use alloc::{string::ToString, sync::Arc, vec, vec::Vec};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    arch::CurrentIrqArch,
+    driver::tty::virtual_terminal::{vc_manager, vc_write_screen},
+    exception::{
+        tasklet::{tasklet_schedule, Tasklet, TaskletData},
+        InterruptArch,
+    },
+    init::initcall::INITCALL_LATE,
+    libs::spinlock::{SpinLock, SpinLockGuard},
+    mm::percpu::{PerCpu, PerCpuVar},
+    process::{
+        kthread::{KernelThreadClosure, KernelThreadMechanism},
+        ProcessControlBlock, ProcessManager,
+    },
+    sched::{schedule, SchedMode},
+    smp::cpu::smp_cpu_manager,
+};
+
+use super::textui::{textui_putstr_no_serial, FontColor};
+
+/// 每个CPU的队列大小
+const QUEUE_SIZE: usize = 8192;
+/// 一条记录最多保存的字节数，超出的部分被截断
+const RECORD_MAX: usize = 1024;
+/// 记录头：序号(u64) + 长度(u16)
+const HEADER_SIZE: usize = 10;
+
+/// 下一条记录的序号，刷新线程按序号从小到大输出
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+/// 队列满而丢弃的记录数
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+/// 刷新线程是否已经在运行
+static FLUSHER_RUNNING: AtomicBool = AtomicBool::new(false);
+/// panic之后屏幕输出改为同步
+static SYNC_MODE: AtomicBool = AtomicBool::new(false);
+/// 是否有还没有刷新的记录
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+static mut CONSOLE_FLUSH_THREAD: Option<Arc<ProcessControlBlock>> = None;
+
+lazy_static! {
+    static ref CONSOLE_QUEUES: PerCpuVar<SpinLock<ConsoleQueue>> = {
+        let mut v = Vec::with_capacity(PerCpu::MAX_CPU_NUM as usize);
+        v.resize_with(PerCpu::MAX_CPU_NUM as usize, || SpinLock::new(ConsoleQueue::new()));
+        PerCpuVar::new(v).expect("PerCpuVar length mismatch")
+    };
+
+    /// 入队之后通过tasklet唤醒刷新线程，避免在任意上下文中直接触碰调度器
+    static ref CONSOLE_FLUSH_TASKLET: Arc<Tasklet> = Tasklet::new(console_flush_tasklet_fn, 0, None);
+}
+
+/// 一个CPU的待刷新记录，保存在环形缓冲区中
+struct ConsoleQueue {
+    /// 第一次使用时才分配
+    buf: Vec<u8>,
+    /// 第一条记录的位置，单调递增，取模得到下标
+    head: usize,
+    /// 下一条记录写入的位置
+    tail: usize,
+}
+
+impl ConsoleQueue {
+    const fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    fn put(&mut self, pos: usize, data: &[u8]) {
+        for (i, b) in data.iter().enumerate() {
+            self.buf[(pos + i) % QUEUE_SIZE] = *b;
+        }
+    }
+
+    fn get(&self, pos: usize, out: &mut [u8]) {
+        for (i, b) in out.iter_mut().enumerate() {
+            *b = self.buf[(pos + i) % QUEUE_SIZE];
+        }
+    }
+
+    /// 放入一条记录，空间不够时返回false
+    fn push(&mut self, data: &[u8]) -> bool {
+        if self.buf.is_empty() {
+            self.buf = vec![0; QUEUE_SIZE];
+        }
+        if QUEUE_SIZE - (self.tail - self.head) < HEADER_SIZE + data.len() {
+            return false;
+        }
+        let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+        let mut header = [0u8; HEADER_SIZE];
+        header[..8].copy_from_slice(&seq.to_le_bytes());
+        header[8..].copy_from_slice(&(data.len() as u16).to_le_bytes());
+        self.put(self.tail, &header);
+        self.put(self.tail + HEADER_SIZE, data);
+        self.tail += HEADER_SIZE + data.len();
+        true
+    }
+
+    /// 第一条记录的序号
+    fn peek_seq(&self) -> Option<u64> {
+        if self.head == self.tail {
+            return None;
+        }
+        let mut seq = [0u8; 8];
+        self.get(self.head, &mut seq);
+        Some(u64::from_le_bytes(seq))
+    }
+
+    /// 取出第一条记录，返回它的长度
+    fn pop(&mut self, out: &mut [u8; RECORD_MAX]) -> Option<usize> {
+        if self.head == self.tail {
+            return None;
+        }
+        let mut len = [0u8; 2];
+        self.get(self.head + 8, &mut len);
+        let len = u16::from_le_bytes(len) as usize;
+        self.get(self.head + HEADER_SIZE, &mut out[..len]);
+        self.head += HEADER_SIZE + len;
+        Some(len)
+    }
+}
+
+/// 在栈上格式化一条记录，超出`RECORD_MAX`的部分被截断
+struct RecordBuf {
+    buf: [u8; RECORD_MAX],
+    len: usize,
+    truncated: bool,
+}
+
+impl RecordBuf {
+    const fn new() -> Self {
+        Self {
+            buf: [0; RECORD_MAX],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    fn as_bytes(&mut self) -> &[u8] {
+        // 被截断的行仍然以换行结束，不影响下一条记录
+        if self.truncated && self.len > 0 && self.buf[self.len - 1] != b'\n' {
+            let mut n = self.len - 1;
+            // 回退到最后一个字符的起始位置
+            while n > 0 && (self.buf[n] & 0xc0) == 0x80 {
+                n -= 1;
+            }
+            self.buf[n] = b'\n';
+            self.len = n + 1;
+        }
+        &self.buf[..self.len]
+    }
+}
+
+impl fmt::Write for RecordBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut n = s.len().min(RECORD_MAX - self.len);
+        if n < s.len() {
+            self.truncated = true;
+            // 在字符边界上截断
+            while !s.is_char_boundary(n) {
+                n -= 1;
+            }
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// 直接写到屏幕，不经过队列，也不输出到串口
+pub struct ScreenWriter;
+
+impl fmt::Write for ScreenWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let tty = vc_manager()
+            .current_vc()
+            .and_then(|vc| vc.port().port_data().internal_tty());
+        if let Some(tty) = tty {
+            let _ = vc_write_screen(tty.core(), s.as_bytes(), s.len());
+        } else {
+            let _ = textui_putstr_no_serial(s, FontColor::WHITE, FontColor::BLACK);
+        }
+        Ok(())
+    }
+}
+
+/// 把一段输出写到屏幕
+///
+/// 刷新线程在运行时放入当前CPU的队列，由刷新线程稍后写到屏幕；
+/// 否则直接写到屏幕。
+///
+/// ## 参数
+/// - `f`: 把要输出的内容格式化到给定的writer中。队列中的一条记录最多`RECORD_MAX`字节
+pub fn screen_write(f: impl FnOnce(&mut dyn Write) -> fmt::Result) {
+    if !FLUSHER_RUNNING.load(Ordering::Acquire) || SYNC_MODE.load(Ordering::Acquire) {
+        let _ = f(&mut ScreenWriter);
+        return;
+    }
+
+    // 在加锁之前格式化，格式化过程中再次打印不会死锁
+    let mut record = RecordBuf::new();
+    let _ = f(&mut record);
+    let data = record.as_bytes();
+    if data.is_empty() {
+        return;
+    }
+    if !CONSOLE_QUEUES.get().lock_irqsave().push(data) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+    PENDING.store(true, Ordering::Release);
+    tasklet_schedule(&CONSOLE_FLUSH_TASKLET);
+}
+
+/// 屏幕输出改为同步，并把队列中剩下的记录写到屏幕上
+///
+/// panic时调用。其他CPU可能已经停在持有队列锁的位置，因此只尝试加锁
+pub fn console_enter_sync_mode() {
+    if SYNC_MODE.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    console_drain(true);
+}
+
+/// 按序号从小到大把所有CPU队列中的记录写到屏幕上
+///
+/// ## 参数
+/// - `try_lock`: 为true时只尝试获取队列锁，获取不到的队列被跳过
+fn console_drain(try_lock: bool) {
+    let lock = |cpu| -> Option<SpinLockGuard<'static, ConsoleQueue>> {
+        let queue = unsafe { CONSOLE_QUEUES.force_get(cpu) };
+        if try_lock {
+            queue.try_lock_irqsave().ok()
+        } else {
+            Some(queue.lock_irqsave())
+        }
+    };
+
+    let mut record = [0u8; RECORD_MAX];
+    loop {
+        let dropped = DROPPED.swap(0, Ordering::Relaxed);
+        if dropped != 0 {
+            let _ = writeln!(ScreenWriter, "** {} console messages dropped **", dropped);
+        }
+
+        // 找出第一条记录序号最小的CPU
+        let mut oldest = None;
+        for cpu in smp_cpu_manager().possible_cpus().iter_cpu() {
+            let Some(seq) = lock(cpu).and_then(|q| q.peek_seq()) else {
+                continue;
+            };
+            if oldest.is_none_or(|(_, oldest_seq)| seq < oldest_seq) {
+                oldest = Some((cpu, seq));
+            }
+        }
+        let Some((cpu, _)) = oldest else {
+            break;
+        };
+        let Some(len) = lock(cpu).and_then(|mut q| q.pop(&mut record)) else {
+            continue;
+        };
+        if let Ok(s) = core::str::from_utf8(&record[..len]) {
+            let _ = ScreenWriter.write_str(s);
+        }
+    }
+}
+
+fn console_flush_tasklet_fn(_data: usize, _data_obj: Option<Arc<dyn TaskletData>>) {
+    let Some(thread) = (unsafe { CONSOLE_FLUSH_THREAD.as_ref() }) else {
+        return;
+    };
+    let _ = ProcessManager::wakeup(thread);
+}
+
+fn console_flush_thread() -> i32 {
+    loop {
+        PENDING.store(false, Ordering::Release);
+        console_drain(false);
+
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        if !PENDING.load(Ordering::Acquire) {
+            ProcessManager::mark_sleep(true).expect("console_flush can not mark sleep");
+            schedule(SchedMode::SM_NONE);
+        }
+    }
+}
+
+#[unified_init(INITCALL_LATE)]
+fn console_flush_thread_init() -> Result<(), SystemError> {
+    let closure =
+        KernelThreadClosure::StaticEmptyClosure((&(console_flush_thread as fn() -> i32), ()));
+    let pcb = KernelThreadMechanism::create_and_run(closure, "console_flush".to_string())
+        .ok_or(SystemError::ENOMEM)?;
+    unsafe {
+        CONSOLE_FLUSH_THREAD = Some(pcb);
+    }
+    FLUSHER_RUNNING.store(true, Ordering::Release);
+    Ok(())
+}
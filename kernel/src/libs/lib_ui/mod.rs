@@ -1,3 +1,4 @@
+pub mod deferred_console;
 pub mod font;
 pub mod screen_manager;
 pub mod textui;
@@ -6,7 +6,12 @@ use crate::{
         spinlock::{SpinLock, SpinLockGuard},
     },
 };
-use alloc::{boxed::Box, collections::LinkedList, string::ToString};
+use alloc::{
+    boxed::Box,
+    collections::LinkedList,
+    format,
+    string::{String, ToString},
+};
 use alloc::{sync::Arc, vec::Vec};
 use core::{
     fmt::Debug,
@@ -23,7 +28,7 @@ use super::{
         scm_register, ScmBuffer, ScmBufferInfo, ScmFramworkType, ScmUiFramework,
         ScmUiFrameworkMetadata,
     },
-    textui_no_alloc::no_init_textui_putchar_window,
+    textui_no_alloc::{no_init_textui_putchar_window, textui_drain_early_staging},
 };
 
 /// 声明全局的TEXTUI_FRAMEWORK
@@ -104,12 +109,39 @@ fn textui_framwork_init() {
         scm_register(textui_framework()).expect("register textui framework failed");
         debug!("textui framework init success");
 
+        // 将textui初始化之前输出的信息回放到第一个窗口中，避免这些信息在屏幕上丢失。
+        // 回放期间暂存区仍然记录新输出的字符
+        textui_replay_early_staging(false);
+
         send_to_default_serial8250_port("\ntext ui initialized\n\0".as_bytes());
         unsafe { TEXTUI_IS_INIT = true };
+        // 回放之后、TEXTUI_IS_INIT生效之前输出的字符还留在暂存区中，再回放一次
+        textui_replay_early_staging(true);
     } else {
         panic!("Try to init TEXTUI_FRAMEWORK twice!");
     }
 }
+/// 把暂存区中textui初始化之前输出的字符回放到当前窗口
+///
+/// ## 参数
+/// - finish 是否是最后一次回放，参见[`textui_drain_early_staging`]
+fn textui_replay_early_staging(finish: bool) {
+    let replay_to_window = textui_is_enable_put_to_window();
+    let window = textui_framework().current_window.clone();
+    let result = textui_drain_early_staging(finish, |c, frcolor, bkcolor| {
+        if replay_to_window {
+            window
+                .lock_irqsave()
+                .textui_render_char_window(c, frcolor, bkcolor)
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(e) = result {
+        debug!("textui replay early messages failed: {:?}", e);
+    }
+}
+
 // window标志位
 bitflags! {
     pub struct WindowFlag: u8 {
@@ -359,6 +391,13 @@ impl TextuiBuf<'_> {
                     );
                 };
             }
+            8 => {
+                // 调色板模式，color为调色板下标，见`VideoRefreshManager::color_to_pixel`
+                let buf = self.buf_mut().as_mut_ptr();
+                unsafe {
+                    *buf.offset(index) = color as u8;
+                }
+            }
             _ => {
                 panic!("bidepth unsupported!")
             }
@@ -430,16 +469,19 @@ impl TextuiCharChromatic {
 
         let mut buf = TextuiBuf::new(&mut _binding);
 
+        let frcolor = video_refresh_manager().color_to_pixel(self.frcolor.into());
+        let bkcolor = video_refresh_manager().color_to_pixel(self.bkcolor.into());
+
         // 在缓冲区画出一个字体，每个字体有TEXTUI_CHAR_HEIGHT行，TEXTUI_CHAR_WIDTH列个像素点
         for i in 0..TEXTUI_CHAR_HEIGHT {
             let start = count;
             for j in 0..TEXTUI_CHAR_WIDTH {
                 if font.is_frcolor(i as usize, j as usize) {
                     // 字，显示前景色
-                    buf.put_color_in_pixel(self.frcolor.into(), count);
+                    buf.put_color_in_pixel(frcolor, count);
                 } else {
                     // 背景色
-                    buf.put_color_in_pixel(self.bkcolor.into(), count);
+                    buf.put_color_in_pixel(bkcolor, count);
                 }
                 count += 1;
             }
@@ -472,6 +514,9 @@ impl TextuiCharChromatic {
                 panic!("device buffer is not init");
             };
 
+        let frcolor = video_refresh_manager().color_to_pixel(self.frcolor.into());
+        let bkcolor = video_refresh_manager().color_to_pixel(self.bkcolor.into());
+
         let mut testbit: u32; // 用来测试特定行的某列是背景还是字体本身
 
         // 在缓冲区画出一个字体，每个字体有TEXTUI_CHAR_HEIGHT行，TEXTUI_CHAR_WIDTH列个像素点
@@ -490,19 +535,17 @@ impl TextuiCharChromatic {
                 //从左往右逐个测试相应位
                 testbit >>= 1;
                 if (font.0[i as usize] & testbit as u8) != 0 {
-                    let color: u32 = self.frcolor.into();
                     unsafe {
                         copy_nonoverlapping(
-                            &color as *const u32 as *const u8,
+                            &frcolor as *const u32 as *const u8,
                             addr,
                             byte_num_of_depth,
                         )
                     }; // 字，显示前景色
                 } else {
-                    let color: u32 = self.bkcolor.into();
                     unsafe {
                         copy_nonoverlapping(
-                            &color as *const u32 as *const u8,
+                            &bkcolor as *const u32 as *const u8,
                             addr,
                             byte_num_of_depth,
                         )
@@ -558,6 +601,29 @@ pub enum TextuiVline {
 #[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
 pub struct WindowId(u32);
 
+/// 转义序列的最大长度，超过之后按原样显示
+const TEXTUI_ESC_SEQ_MAX: usize = 32;
+
+/// 窗口的转义序列解析状态
+///
+/// 目前只识别`ESC [ ? 7 h/l`（DECAWM），其余的转义序列按原样显示到窗口上
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum TextuiEscState {
+    #[default]
+    Normal,
+    /// 收到了ESC
+    Esc,
+    /// 正在解析CSI序列
+    Csi {
+        /// 是否为`ESC [ ?`开头的私有序列
+        private: bool,
+        /// 当前的参数
+        param: u32,
+        /// 之前的参数中是否出现过7
+        awm: bool,
+    },
+}
+
 impl WindowId {
     pub fn new() -> Self {
         static MAX_ID: AtomicU32 = AtomicU32::new(0);
@@ -583,6 +649,12 @@ pub struct TextuiWindow {
     chars_per_line: i32,
     // 窗口flag
     flags: WindowFlag,
+    // 是否自动换行（DECAWM）。关闭时光标停在行尾，之后的字符覆盖最后一列
+    autowrap: bool,
+    // 转义序列解析状态
+    esc_state: TextuiEscState,
+    // 正在解析的转义序列中已经收到的字符，不能识别时按原样显示
+    esc_seq: Vec<char>,
 }
 
 impl TextuiWindow {
@@ -609,6 +681,9 @@ impl TextuiWindow {
             vlines: initial_vlines,
             vline_operating: LineId::new(0),
             chars_per_line: chars_num,
+            autowrap: true,
+            esc_state: TextuiEscState::Normal,
+            esc_seq: Vec::new(),
         }
     }
 
@@ -754,12 +829,18 @@ impl TextuiWindow {
         // 启用彩色字符
         if self.flags.contains(WindowFlag::TEXTUI_CHROMATIC) {
             let mut line_index = LineIndex::new(0); //操作的列号
+            let last_column = LineIndex::new(self.chars_per_line - 1);
             if let TextuiVline::Chromatic(vline) =
                 &mut (self.vlines[<LineId as Into<usize>>::into(self.vline_operating)])
             {
-                let index = <LineIndex as Into<usize>>::into(vline.index);
-
-                if let Some(v_char) = vline.chars.get_mut(index) {
+                // 不自动换行时光标停在最后一列，之后的字符覆盖最后一个单元格（与VT的行为相同）
+                if vline.index > last_column {
+                    vline.index = last_column;
+                }
+                if let Some(v_char) = vline
+                    .chars
+                    .get_mut(<LineIndex as Into<usize>>::into(vline.index))
+                {
                     v_char.c = Some(character);
                     v_char.frcolor = frcolor;
                     v_char.bkcolor = bkcolor;
@@ -771,7 +852,7 @@ impl TextuiWindow {
             self.textui_refresh_characters(self.vline_operating, line_index, 1)?;
 
             // 加入光标后，因为会识别光标，所以需超过该行最大字符数才能创建新行
-            if !line_index.check(self.chars_per_line - 1) {
+            if self.autowrap && !line_index.check(self.chars_per_line - 1) {
                 self.textui_new_line()?;
             }
         } else {
@@ -792,9 +873,8 @@ impl TextuiWindow {
         frcolor: FontColor,
         bkcolor: FontColor,
         is_enable_window: bool,
+        to_serial: bool,
     ) -> Result<(), SystemError> {
-        let actual_line_sum = textui_framework().actual_line.load(Ordering::SeqCst);
-
         //字符'\0'代表ASCII码表中的空字符,表示字符串的结尾
         if unlikely(character == '\0') {
             return Ok(());
@@ -808,102 +888,242 @@ impl TextuiWindow {
         if !self.flags.contains(WindowFlag::TEXTUI_CHROMATIC) {
             return Ok(());
         }
-        send_to_default_serial8250_port(&[character as u8]);
+        if to_serial {
+            send_to_default_serial8250_port(&[character as u8]);
+            if character == '\n' {
+                // 换行时还需要输出\r
+                send_to_default_serial8250_port(b"\r");
+            }
+        }
+
+        if is_enable_window {
+            return self.textui_render_char_window(character, frcolor, bkcolor);
+        }
+
+        return Ok(());
+    }
+
+    /// 只将字符渲染到窗口上，不向串口输出
+    ///
+    /// 用于回放textui初始化之前已经输出到串口的字符
+    /// ## 参数
+    /// - character 字符
+    /// - FRcolor 前景色（RGB）
+    /// - BKcolor 背景色（RGB）
+    fn textui_render_char_window(
+        &mut self,
+        character: char,
+        frcolor: FontColor,
+        bkcolor: FontColor,
+    ) -> Result<(), SystemError> {
+        if unlikely(character == '\0' || character == '\r') {
+            return Ok(());
+        }
+
+        if !self.flags.contains(WindowFlag::TEXTUI_CHROMATIC) {
+            return Ok(());
+        }
+
+        if self.esc_state == TextuiEscState::Normal && character != '\x1b' {
+            return self.textui_render_plain_char(character, frcolor, bkcolor);
+        }
+        for c in self.textui_handle_escape(character) {
+            self.textui_render_plain_char(c, frcolor, bkcolor)?;
+        }
+        return Ok(());
+    }
+
+    /// 把不属于转义序列的字符渲染到窗口上
+    fn textui_render_plain_char(
+        &mut self,
+        character: char,
+        frcolor: FontColor,
+        bkcolor: FontColor,
+    ) -> Result<(), SystemError> {
+        let actual_line_sum = textui_framework().actual_line.load(Ordering::SeqCst);
 
         //进行换行操作
         if character == '\n' {
-            // 换行时还需要输出\r
-            send_to_default_serial8250_port(b"\r");
-            if is_enable_window {
-                self.textui_new_line()?;
-            }
-            return Ok(());
+            self.textui_new_line()?;
         }
         // 输出制表符
         else if character == '\t' {
-            if is_enable_window {
-                if let TextuiVline::Chromatic(vline) =
-                    &self.vlines[<LineId as Into<usize>>::into(self.vline_operating)]
-                {
-                    //打印的空格数（注意将每行分成一个个表格，每个表格为8个字符）
-                    let mut space_to_print = 8 - <LineIndex as Into<usize>>::into(vline.index) % 8;
-                    while space_to_print > 0 {
-                        self.true_textui_putchar_window(' ', frcolor, bkcolor)?;
-                        space_to_print -= 1;
-                    }
+            if let TextuiVline::Chromatic(vline) =
+                &self.vlines[<LineId as Into<usize>>::into(self.vline_operating)]
+            {
+                //打印的空格数（注意将每行分成一个个表格，每个表格为8个字符）
+                let mut space_to_print = 8 - <LineIndex as Into<usize>>::into(vline.index) % 8;
+                while space_to_print > 0 {
+                    self.true_textui_putchar_window(' ', frcolor, bkcolor)?;
+                    space_to_print -= 1;
                 }
             }
         }
         // 字符 '\x08' 代表 ASCII 码中的退格字符。它在输出中的作用是将光标向左移动一个位置，并在该位置上输出后续的字符，从而实现字符的删除或替换。
         else if character == '\x08' {
-            if is_enable_window {
-                let mut tmp = LineIndex(0);
+            let mut tmp = LineIndex(0);
+            if let TextuiVline::Chromatic(vline) =
+                &mut self.vlines[<LineId as Into<usize>>::into(self.vline_operating)]
+            {
+                vline.index = vline.index - 1;
+                tmp = vline.index;
+            }
+            if <LineIndex as Into<i32>>::into(tmp) >= 0 {
                 if let TextuiVline::Chromatic(vline) =
                     &mut self.vlines[<LineId as Into<usize>>::into(self.vline_operating)]
                 {
-                    vline.index = vline.index - 1;
-                    tmp = vline.index;
-                }
-                if <LineIndex as Into<i32>>::into(tmp) >= 0 {
-                    if let TextuiVline::Chromatic(vline) =
-                        &mut self.vlines[<LineId as Into<usize>>::into(self.vline_operating)]
+                    if let Some(v_char) = vline.chars.get_mut(<LineIndex as Into<usize>>::into(tmp))
                     {
-                        if let Some(v_char) =
-                            vline.chars.get_mut(<LineIndex as Into<usize>>::into(tmp))
-                        {
-                            v_char.c = Some(' ');
+                        v_char.c = Some(' ');
 
-                            v_char.bkcolor = bkcolor;
-                        }
+                        v_char.bkcolor = bkcolor;
                     }
-                    return self.textui_refresh_characters(self.vline_operating, tmp, 1);
                 }
-                // 需要向上缩一行
-                if <LineIndex as Into<i32>>::into(tmp) < 0 {
-                    // 当前行为空,需要重新刷新
-                    if let TextuiVline::Chromatic(vline) =
-                        &mut self.vlines[<LineId as Into<usize>>::into(self.vline_operating)]
-                    {
-                        vline.index = LineIndex::new(0);
-                        for i in 0..self.chars_per_line {
-                            if let Some(v_char) = vline.chars.get_mut(i as usize) {
-                                v_char.c = None;
-                                v_char.frcolor = FontColor::BLACK;
-                                v_char.bkcolor = FontColor::BLACK;
-                            }
+                return self.textui_refresh_characters(self.vline_operating, tmp, 1);
+            }
+            // 需要向上缩一行
+            if <LineIndex as Into<i32>>::into(tmp) < 0 {
+                // 当前行为空,需要重新刷新
+                if let TextuiVline::Chromatic(vline) =
+                    &mut self.vlines[<LineId as Into<usize>>::into(self.vline_operating)]
+                {
+                    vline.index = LineIndex::new(0);
+                    for i in 0..self.chars_per_line {
+                        if let Some(v_char) = vline.chars.get_mut(i as usize) {
+                            v_char.c = None;
+                            v_char.frcolor = FontColor::BLACK;
+                            v_char.bkcolor = FontColor::BLACK;
                         }
                     }
-                    // 上缩一行
-                    self.vline_operating = self.vline_operating - 1;
-                    if self.vline_operating.data() < 0 {
-                        self.vline_operating = LineId(self.vline_sum - 1);
-                    }
+                }
+                // 上缩一行
+                self.vline_operating = self.vline_operating - 1;
+                if self.vline_operating.data() < 0 {
+                    self.vline_operating = LineId(self.vline_sum - 1);
+                }
 
-                    // 考虑是否向上滚动（在top_vline上退格）
-                    if self.vlines_used > actual_line_sum {
-                        self.top_vline = self.top_vline - 1;
-                        if <LineId as Into<i32>>::into(self.top_vline) < 0 {
-                            self.top_vline = LineId(self.vline_sum - 1);
-                        }
+                // 考虑是否向上滚动（在top_vline上退格）
+                if self.vlines_used > actual_line_sum {
+                    self.top_vline = self.top_vline - 1;
+                    if <LineId as Into<i32>>::into(self.top_vline) < 0 {
+                        self.top_vline = LineId(self.vline_sum - 1);
                     }
-                    //因为上缩一行所以显示在屏幕中的虚拟行少一
-                    self.vlines_used -= 1;
-                    self.textui_refresh_vlines(self.top_vline, actual_line_sum)?;
                 }
+                //因为上缩一行所以显示在屏幕中的虚拟行少一
+                self.vlines_used -= 1;
+                self.textui_refresh_vlines(self.top_vline, actual_line_sum)?;
             }
-        } else if is_enable_window {
-            if let TextuiVline::Chromatic(vline) =
-                &self.vlines[<LineId as Into<usize>>::into(self.vline_operating)]
-            {
-                if !vline.index.check(self.chars_per_line) {
-                    self.textui_new_line()?;
+        } else if let TextuiVline::Chromatic(vline) =
+            &self.vlines[<LineId as Into<usize>>::into(self.vline_operating)]
+        {
+            if self.autowrap && !vline.index.check(self.chars_per_line) {
+                self.textui_new_line()?;
+            }
+
+            return self.true_textui_putchar_window(character, frcolor, bkcolor);
+        }
+
+        return Ok(());
+    }
+
+    /// 处理转义序列中的一个字符
+    ///
+    /// ## 返回值
+    /// - 需要按原样显示的字符。序列还没有结束或者已经被识别时为空；
+    ///   不能识别的序列结束时为整个序列
+    fn textui_handle_escape(&mut self, character: char) -> Vec<char> {
+        // 序列中间又出现了ESC：之前的部分按原样显示，从这里开始新的序列
+        if character == '\x1b' {
+            let pending = core::mem::take(&mut self.esc_seq);
+            self.esc_seq.push(character);
+            self.esc_state = TextuiEscState::Esc;
+            return pending;
+        }
+        self.esc_seq.push(character);
+
+        let next = match self.esc_state {
+            TextuiEscState::Normal => None,
+            TextuiEscState::Esc if character == '[' => Some(TextuiEscState::Csi {
+                private: false,
+                param: 0,
+                awm: false,
+            }),
+            TextuiEscState::Esc => None,
+            TextuiEscState::Csi {
+                private,
+                param,
+                awm,
+            } => match character {
+                '?' if self.esc_seq.len() == 3 => Some(TextuiEscState::Csi {
+                    private: true,
+                    param,
+                    awm,
+                }),
+                '0'..='9' => Some(TextuiEscState::Csi {
+                    private,
+                    param: param
+                        .saturating_mul(10)
+                        .saturating_add(character.to_digit(10).unwrap()),
+                    awm,
+                }),
+                ';' => Some(TextuiEscState::Csi {
+                    private,
+                    param: 0,
+                    awm: awm || param == 7,
+                }),
+                'h' | 'l' if private && (awm || param == 7) => {
+                    self.textui_set_autowrap(character == 'h');
+                    self.esc_state = TextuiEscState::Normal;
+                    self.esc_seq.clear();
+                    return Vec::new();
                 }
+                _ => None,
+            },
+        };
 
-                return self.true_textui_putchar_window(character, frcolor, bkcolor);
+        match next {
+            Some(state) if self.esc_seq.len() < TEXTUI_ESC_SEQ_MAX => {
+                self.esc_state = state;
+                Vec::new()
+            }
+            // 不能识别或者过长的序列
+            _ => {
+                self.esc_state = TextuiEscState::Normal;
+                core::mem::take(&mut self.esc_seq)
             }
         }
+    }
 
-        return Ok(());
+    /// 设置是否自动换行
+    fn textui_set_autowrap(&mut self, enable: bool) {
+        self.autowrap = enable;
+    }
+
+    /// 获取窗口当前显示在屏幕上的内容
+    ///
+    /// ## 参数
+    /// - rows 屏幕上的真实行数
+    pub fn textui_snapshot(&self, rows: i32) -> TextuiSnapshot {
+        let rows = rows.clamp(0, self.vline_sum) as usize;
+        let cols = self.chars_per_line.max(0) as usize;
+        let blank = TextuiCharChromatic::new(None, FontColor::BLACK, FontColor::BLACK);
+
+        let mut cells = Vec::with_capacity(rows * cols);
+        for row in 0..rows {
+            // 虚拟行是循环表，屏幕的第一行是top_vline
+            let vline_id =
+                (<LineId as Into<usize>>::into(self.top_vline) + row) % self.vline_sum as usize;
+            match &self.vlines[vline_id] {
+                TextuiVline::Chromatic(vline) => {
+                    for column in 0..cols {
+                        cells.push(*vline.chars.get(column).unwrap_or(&blank));
+                    }
+                }
+                TextuiVline::_Normal(_) => cells.extend(core::iter::repeat(blank).take(cols)),
+            }
+        }
+
+        TextuiSnapshot { rows, cols, cells }
     }
 }
 impl Default for TextuiWindow {
@@ -917,6 +1137,9 @@ impl Default for TextuiWindow {
             vlines: Vec::new(),
             vline_operating: LineId::new(0),
             chars_per_line: 0,
+            autowrap: true,
+            esc_state: TextuiEscState::Normal,
+            esc_seq: Vec::new(),
         }
     }
 }
@@ -1014,11 +1237,29 @@ where
     }
 }
 
-/// 向默认窗口输出一个字符串
+/// 向默认窗口输出一个字符串，同时输出到串口
 pub fn textui_putstr(
     string: &str,
     fr_color: FontColor,
     bk_color: FontColor,
+) -> Result<(), SystemError> {
+    do_textui_putstr(string, fr_color, bk_color, true)
+}
+
+/// 只向默认窗口输出一个字符串，不输出到串口
+pub fn textui_putstr_no_serial(
+    string: &str,
+    fr_color: FontColor,
+    bk_color: FontColor,
+) -> Result<(), SystemError> {
+    do_textui_putstr(string, fr_color, bk_color, false)
+}
+
+fn do_textui_putstr(
+    string: &str,
+    fr_color: FontColor,
+    bk_color: FontColor,
+    to_serial: bool,
 ) -> Result<(), SystemError> {
     let window = if unsafe { TEXTUI_IS_INIT } {
         let fw = textui_framework();
@@ -1037,6 +1278,7 @@ pub fn textui_putstr(
                 fr_color,
                 bk_color,
                 textui_is_enable_put_to_window(),
+                to_serial,
             )?;
         } else {
             no_init_textui_putchar_window(
@@ -1044,6 +1286,7 @@ pub fn textui_putstr(
                 fr_color,
                 bk_color,
                 textui_is_enable_put_to_window(),
+                to_serial,
             )?;
         }
     }
@@ -1051,6 +1294,107 @@ pub fn textui_putstr(
     return Ok(());
 }
 
+/// 窗口内容的快照：屏幕上每个字符单元格的字符和颜色，按行优先排列
+#[derive(Debug, Clone)]
+pub struct TextuiSnapshot {
+    rows: usize,
+    cols: usize,
+    cells: Vec<TextuiCharChromatic>,
+}
+
+impl TextuiSnapshot {
+    /// `cells`格式的魔数
+    pub const CELLS_MAGIC: [u8; 4] = *b"TXUI";
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// 获取第`row`行第`col`列的单元格
+    pub fn cell(&self, row: usize, col: usize) -> Option<&TextuiCharChromatic> {
+        if col >= self.cols {
+            return None;
+        }
+        self.cells.get(row * self.cols + col)
+    }
+
+    /// 纯文本格式：每行一个换行符，去掉行尾的空白
+    pub fn to_text(&self) -> String {
+        let mut s = String::with_capacity(self.rows * (self.cols + 1));
+        for line in self.cells.chunks(self.cols.max(1)) {
+            let start = s.len();
+            for cell in line {
+                s.push(cell.c.unwrap_or(' '));
+            }
+            s.truncate(start + s[start..].trim_end().len());
+            s.push('\n');
+        }
+        s
+    }
+
+    /// 二进制格式，所有整数均为小端序：
+    ///
+    /// - 头部：魔数`TXUI`、行数（u32）、列数（u32）
+    /// - 之后按行优先排列每个单元格：字符的码点（u32，没有字符时为0）、前景色（u32，0x00RRGGBB）、背景色（u32，0x00RRGGBB）
+    pub fn to_cells(&self) -> Vec<u8> {
+        let mut v = Vec::with_capacity(12 + self.cells.len() * 12);
+        v.extend_from_slice(&Self::CELLS_MAGIC);
+        v.extend_from_slice(&(self.rows as u32).to_le_bytes());
+        v.extend_from_slice(&(self.cols as u32).to_le_bytes());
+        for cell in &self.cells {
+            v.extend_from_slice(&cell.c.map(|c| c as u32).unwrap_or(0).to_le_bytes());
+            v.extend_from_slice(&u32::from(cell.frcolor).to_le_bytes());
+            v.extend_from_slice(&u32::from(cell.bkcolor).to_le_bytes());
+        }
+        v
+    }
+
+    /// 按照textui的字体把快照渲染成二进制PPM（P6）图像，与屏幕上显示的像素一致
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let width = self.cols * TEXTUI_CHAR_WIDTH as usize;
+        let height = self.rows * TEXTUI_CHAR_HEIGHT as usize;
+        let header = format!("P6\n{} {}\n255\n", width, height);
+
+        let mut v = Vec::with_capacity(header.len() + width * height * 3);
+        v.extend_from_slice(header.as_bytes());
+        for line in self.cells.chunks(self.cols.max(1)) {
+            let fonts: Vec<Font> = line
+                .iter()
+                .map(|cell| Font::get_font(cell.c.unwrap_or(' ')))
+                .collect();
+            for y in 0..TEXTUI_CHAR_HEIGHT as usize {
+                for (cell, font) in line.iter().zip(fonts.iter()) {
+                    for x in 0..TEXTUI_CHAR_WIDTH as usize {
+                        let color = if font.is_frcolor(y, x) {
+                            cell.frcolor
+                        } else {
+                            cell.bkcolor
+                        };
+                        let rgb = u32::from(color);
+                        v.extend_from_slice(&[(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]);
+                    }
+                }
+            }
+        }
+        v
+    }
+}
+
+/// 获取当前窗口显示在屏幕上的内容，参见[`TextuiWindow::textui_snapshot`]
+pub fn textui_snapshot() -> Result<TextuiSnapshot, SystemError> {
+    if unsafe { !TEXTUI_IS_INIT } {
+        return Err(SystemError::ENODEV);
+    }
+    let fw = textui_framework();
+    let rows = fw.actual_line.load(Ordering::SeqCst);
+    let snapshot = fw.current_window.lock_irqsave().textui_snapshot(rows);
+    Ok(snapshot)
+}
+
 /// 初始化text ui框架
 #[inline(never)]
 pub fn textui_init() -> Result<i32, SystemError> {
@@ -3,10 +3,12 @@ use core::{
     sync::atomic::{AtomicI32, Ordering},
 };
 
+use alloc::vec::Vec;
 use system_error::SystemError;
 
-use crate::driver::{
-    serial::serial8250::send_to_default_serial8250_port, video::video_refresh_manager,
+use crate::{
+    driver::{serial::serial8250::send_to_default_serial8250_port, video::video_refresh_manager},
+    libs::spinlock::SpinLock,
 };
 
 use super::textui::{
@@ -19,6 +21,98 @@ pub static CHAR_PER_LINE: AtomicI32 = AtomicI32::new(0);
 pub static NO_ALLOC_OPERATIONS_LINE: AtomicI32 = AtomicI32::new(0);
 pub static NO_ALLOC_OPERATIONS_INDEX: AtomicI32 = AtomicI32::new(0);
 
+/// textui初始化之前输出的字符的暂存区容量（字符数）
+const EARLY_STAGING_CAPACITY: usize = 8192;
+
+/// textui初始化之前输出的字符的暂存区
+///
+/// 在textui框架初始化之前，字符只会被输出到串口（以及直接写到帧缓冲区）。
+/// 为了让这些信息在textui初始化之后依然能够出现在窗口上，先把它们暂存在这里，
+/// 等到第一个窗口创建之后再回放进去。暂存区满了之后，会覆盖最旧的字符。
+static EARLY_STAGING_BUF: SpinLock<EarlyStagingBuf> = SpinLock::new(EarlyStagingBuf::new());
+
+#[derive(Debug, Clone, Copy)]
+struct StagedChar {
+    c: char,
+    frcolor: FontColor,
+    bkcolor: FontColor,
+}
+
+impl StagedChar {
+    const EMPTY: StagedChar = StagedChar {
+        c: '\0',
+        frcolor: FontColor::BLACK,
+        bkcolor: FontColor::BLACK,
+    };
+}
+
+#[derive(Debug)]
+struct EarlyStagingBuf {
+    chars: [StagedChar; EARLY_STAGING_CAPACITY],
+    /// 最旧的字符所在的位置
+    head: usize,
+    /// 暂存区中的字符数
+    len: usize,
+    /// textui已经接管输出，不再需要暂存
+    drained: bool,
+}
+
+impl EarlyStagingBuf {
+    const fn new() -> Self {
+        Self {
+            chars: [StagedChar::EMPTY; EARLY_STAGING_CAPACITY],
+            head: 0,
+            len: 0,
+            drained: false,
+        }
+    }
+
+    fn push(&mut self, staged: StagedChar) {
+        if self.drained {
+            return;
+        }
+        let tail = (self.head + self.len) % EARLY_STAGING_CAPACITY;
+        self.chars[tail] = staged;
+        if self.len == EARLY_STAGING_CAPACITY {
+            self.head = (self.head + 1) % EARLY_STAGING_CAPACITY;
+        } else {
+            self.len += 1;
+        }
+    }
+}
+
+/// 取出textui初始化之前暂存的所有字符，并按照输出顺序逐个交给`f`处理
+///
+/// 字符在锁内被拷贝出来，调用`f`时不持有暂存区的锁，`f`中可以再次输出字符。
+/// 这个函数只在textui框架初始化时调用，此时已经可以分配内存。
+///
+/// ## 参数
+/// - finish 为true时，之后暂存区将不再记录新的字符
+/// - f 处理每个字符的回调函数，参数依次为：字符、前景色、背景色
+pub fn textui_drain_early_staging<F>(finish: bool, mut f: F) -> Result<(), SystemError>
+where
+    F: FnMut(char, FontColor, FontColor) -> Result<(), SystemError>,
+{
+    let staged: Vec<StagedChar> = {
+        let mut staging = EARLY_STAGING_BUF.lock_irqsave();
+        if finish {
+            staging.drained = true;
+        }
+        let (head, len) = (staging.head, staging.len);
+        staging.head = 0;
+        staging.len = 0;
+        (0..len)
+            .map(|i| staging.chars[(head + i) % EARLY_STAGING_CAPACITY])
+            .collect()
+    };
+
+    for staged in staged {
+        f(staged.c, staged.frcolor, staged.bkcolor)?;
+    }
+
+    return Ok(());
+}
+
 /// 当系统刚启动的时候，由于内存管理未初始化，而texiui需要动态内存分配。因此只能暂时暴力往屏幕（video_frame_buffer_info）输出信息
 pub fn textui_init_no_alloc(video_enabled: bool) {
     if video_enabled {
@@ -43,18 +137,28 @@ pub fn no_init_textui_putchar_window(
     frcolor: FontColor,
     bkcolor: FontColor,
     is_put_to_window: bool,
+    to_serial: bool,
 ) -> Result<(), SystemError> {
     //字符'\0'代表ASCII码表中的空字符,表示字符串的结尾
     if unlikely(character == '\0') {
         return Ok(());
     }
-    send_to_default_serial8250_port(&[character as u8]);
+    if to_serial {
+        send_to_default_serial8250_port(&[character as u8]);
+    }
+    EARLY_STAGING_BUF.lock_irqsave().push(StagedChar {
+        c: character,
+        frcolor,
+        bkcolor,
+    });
 
     if is_put_to_window {
         match character {
             // 进行换行操作
             '\n' => {
-                send_to_default_serial8250_port(b"\r");
+                if to_serial {
+                    send_to_default_serial8250_port(b"\r");
+                }
                 if is_put_to_window {
                     next_line();
                 }
@@ -0,0 +1,211 @@
+//! LZ4块格式的压缩与解压
+//!
+//! 只实现LZ4 block format（不含frame头），与Linux `lib/lz4`的`LZ4_compress_default`/
+//! `LZ4_decompress_safe`产生、接受的数据格式相同。压缩器使用单个哈希表的快速模式，
+//! 适合压缩内存页这类小块数据。
+//!
+//! 参考: https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md
+
+use system_error::SystemError;
+
+/// 最短匹配长度
+const MIN_MATCH: usize = 4;
+/// 最后一个匹配必须在块结束前至少这么多字节开始
+const MFLIMIT: usize = 12;
+/// 块的最后这么多字节必须是字面量
+const LAST_LITERALS: usize = 5;
+/// 匹配的最大回溯距离（偏移量用16位表示）
+const MAX_DISTANCE: usize = 65535;
+const HASH_LOG: u32 = 12;
+/// token中长度字段的最大值，超过时后面跟随扩展长度字节
+const RUN_MASK: usize = 15;
+
+/// 输出缓冲区，空间不足时返回`None`
+struct Output<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Output<'_> {
+    fn push(&mut self, b: u8) -> Option<()> {
+        *self.buf.get_mut(self.pos)? = b;
+        self.pos += 1;
+        Some(())
+    }
+
+    fn extend(&mut self, data: &[u8]) -> Option<()> {
+        let end = self.pos.checked_add(data.len())?;
+        self.buf.get_mut(self.pos..end)?.copy_from_slice(data);
+        self.pos = end;
+        Some(())
+    }
+
+    /// 写入token之后的扩展长度：若干个255，再加上余数
+    fn push_len(&mut self, mut len: usize) -> Option<()> {
+        while len >= 255 {
+            self.push(255)?;
+            len -= 255;
+        }
+        self.push(len as u8)
+    }
+
+    /// 写入一个序列：字面量，以及可选的匹配（偏移量，匹配长度）
+    fn sequence(&mut self, literals: &[u8], matched: Option<(usize, usize)>) -> Option<()> {
+        let lit_len = literals.len();
+        let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+        let token = ((lit_len.min(RUN_MASK) as u8) << 4) | match_len.min(RUN_MASK) as u8;
+        self.push(token)?;
+        if lit_len >= RUN_MASK {
+            self.push_len(lit_len - RUN_MASK)?;
+        }
+        self.extend(literals)?;
+        if let Some((offset, _)) = matched {
+            self.extend(&(offset as u16).to_le_bytes())?;
+            if match_len >= RUN_MASK {
+                self.push_len(match_len - RUN_MASK)?;
+            }
+        }
+        Some(())
+    }
+}
+
+#[inline]
+fn read_u32(src: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([src[pos], src[pos + 1], src[pos + 2], src[pos + 3]])
+}
+
+#[inline]
+fn hash(v: u32) -> usize {
+    (v.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+/// # 压缩
+///
+/// ## 参数
+///
+/// - `src`: 待压缩的数据
+/// - `dst`: 输出缓冲区
+///
+/// ## 返回值
+///
+/// - `Some(len)`: 压缩后的长度
+/// - `None`: `dst`放不下压缩后的数据。调用者可以借此限制压缩后的最大长度
+pub fn compress(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut out = Output { buf: dst, pos: 0 };
+    let mut anchor = 0;
+
+    if src.len() > MFLIMIT {
+        // 哈希表保存的是位置，0既表示未使用也表示位置0，匹配前会比较实际内容
+        let mut table = [0u32; 1 << HASH_LOG];
+        let match_limit = src.len() - MFLIMIT;
+        let end_limit = src.len() - LAST_LITERALS;
+        let mut pos = 0;
+        while pos < match_limit {
+            let seq = read_u32(src, pos);
+            let h = hash(seq);
+            let mut cand = table[h] as usize;
+            table[h] = pos as u32;
+            if cand >= pos || pos - cand > MAX_DISTANCE || read_u32(src, cand) != seq {
+                pos += 1;
+                continue;
+            }
+
+            let mut start = pos;
+            let mut len = MIN_MATCH;
+            while start + len < end_limit && src[cand + len] == src[start + len] {
+                len += 1;
+            }
+            // 向前扩展匹配
+            while start > anchor && cand > 0 && src[start - 1] == src[cand - 1] {
+                start -= 1;
+                cand -= 1;
+                len += 1;
+            }
+
+            out.sequence(&src[anchor..start], Some((start - cand, len)))?;
+            pos = start + len;
+            anchor = pos;
+        }
+    }
+
+    out.sequence(&src[anchor..], None)?;
+    Some(out.pos)
+}
+
+/// 读取token之后的扩展长度
+fn read_len(src: &[u8], ip: &mut usize) -> Result<usize, SystemError> {
+    let mut len = 0usize;
+    loop {
+        let b = *src.get(*ip).ok_or(SystemError::EINVAL)?;
+        *ip += 1;
+        len = len.checked_add(b as usize).ok_or(SystemError::EINVAL)?;
+        if b != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+/// # 解压
+///
+/// 会检查输入的合法性，损坏的数据不会导致越界访问。
+///
+/// ## 参数
+///
+/// - `src`: 压缩的数据，必须是一个完整的块
+/// - `dst`: 输出缓冲区
+///
+/// ## 返回值
+///
+/// - `Ok(len)`: 解压后的长度
+/// - `Err(SystemError::EINVAL)`: 数据损坏，或者`dst`放不下解压后的数据
+pub fn decompress(src: &[u8], dst: &mut [u8]) -> Result<usize, SystemError> {
+    let mut ip = 0;
+    let mut op = 0usize;
+    loop {
+        let token = *src.get(ip).ok_or(SystemError::EINVAL)? as usize;
+        ip += 1;
+
+        let mut lit_len = token >> 4;
+        if lit_len == RUN_MASK {
+            lit_len += read_len(src, &mut ip)?;
+        }
+        let lit_end = ip.checked_add(lit_len).ok_or(SystemError::EINVAL)?;
+        let out_end = op.checked_add(lit_len).ok_or(SystemError::EINVAL)?;
+        if lit_end > src.len() || out_end > dst.len() {
+            return Err(SystemError::EINVAL);
+        }
+        dst[op..out_end].copy_from_slice(&src[ip..lit_end]);
+        ip = lit_end;
+        op = out_end;
+
+        // 最后一个序列只有字面量
+        if ip == src.len() {
+            return Ok(op);
+        }
+
+        if ip + 2 > src.len() {
+            return Err(SystemError::EINVAL);
+        }
+        let offset = u16::from_le_bytes([src[ip], src[ip + 1]]) as usize;
+        ip += 2;
+        if offset == 0 || offset > op {
+            return Err(SystemError::EINVAL);
+        }
+
+        let mut match_len = token & RUN_MASK;
+        if match_len == RUN_MASK {
+            match_len += read_len(src, &mut ip)?;
+        }
+        match_len += MIN_MATCH;
+        let out_end = op.checked_add(match_len).ok_or(SystemError::EINVAL)?;
+        if out_end > dst.len() {
+            return Err(SystemError::EINVAL);
+        }
+        // 匹配可能与输出重叠（offset < match_len），需要逐字节复制
+        let from = op - offset;
+        for i in 0..match_len {
+            dst[op + i] = dst[from + i];
+        }
+        op = out_end;
+    }
+}
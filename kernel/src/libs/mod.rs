@@ -2,13 +2,17 @@ pub mod align;
 pub mod byte_parser;
 pub mod casting;
 pub mod cpumask;
+pub mod crc;
+pub mod crypto;
 pub mod elf;
+pub mod error_context;
 #[macro_use]
 pub mod int_like;
 pub mod keyboard_parser;
 pub mod lazy_init;
 pub mod lib_ui;
 pub mod lock_free_flags;
+pub mod lz4;
 pub mod mutex;
 pub mod notifier;
 pub mod once;
@@ -20,6 +24,7 @@ pub mod rwlock;
 pub mod rwsem;
 pub mod semaphore;
 pub mod spinlock;
+pub mod string;
 pub mod vec_cursor;
 #[macro_use]
 pub mod volatile;
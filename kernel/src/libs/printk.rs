@@ -1,17 +1,94 @@
-use core::fmt::{self, Write};
+use core::{
+    fmt::{self, Display, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use alloc::string::ToString;
 use log::{info, Level, Log};
 
-use super::lib_ui::textui::{textui_putstr, FontColor};
+use super::lib_ui::deferred_console::screen_write;
 
 use crate::{
-    debug::klog::loglevel::{LogLevel, KERNEL_LOG_LEVEL},
-    driver::tty::{tty_driver::TtyOperation, virtual_terminal::vc_manager},
-    filesystem::procfs::{klog::LogMessage, kmsg::KMSG},
+    debug::klog::{
+        loglevel::LogLevel,
+        sink::{LogSink, LOG_SINKS},
+    },
+    driver::{
+        net::netconsole::netconsole_write, serial::serial8250::send_to_default_serial8250_port,
+    },
+    filesystem::{
+        procfs::{klog::LogMessage, kmsg::KMSG},
+        pstore::pstore_console_write,
+    },
     time::PosixTimeSpec,
 };
 
+/// 是否在日志前输出时间戳（printk.time）
+///
+/// 命令行参数在内存管理初始化之后才会被解析，因此默认开启，保证早期日志也带有时间戳
+static PRINTK_TIME: AtomicBool = AtomicBool::new(true);
+
+/// printk.time命令行参数
+///
+/// 支持格式：printk.time=0/1（也接受 n/y、off/on）
+kernel_cmdline_param_kv!(PRINTK_TIME_PARAM, "printk.time", "1");
+
+/// 处理printk.time参数
+///
+/// 在cmdline参数解析完成后调用
+pub fn handle_printk_time_param() {
+    let Some(value) = PRINTK_TIME_PARAM.value_str() else {
+        return;
+    };
+    match value {
+        "1" | "y" | "Y" | "on" => PRINTK_TIME.store(true, Ordering::SeqCst),
+        "0" | "n" | "N" | "off" => PRINTK_TIME.store(false, Ordering::SeqCst),
+        _ => log::warn!("printk.time: invalid value '{}', must be 0 or 1", value),
+    }
+}
+
+/// 日志是否带时间戳
+#[inline]
+pub fn printk_time_enabled() -> bool {
+    PRINTK_TIME.load(Ordering::Relaxed)
+}
+
+/// 日志时间戳，格式与Linux一致：`[    1.234567] `
+///
+/// printk.time=0 时不输出任何内容
+pub struct PrintkTime(pub PosixTimeSpec);
+
+impl Display for PrintkTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !printk_time_enabled() {
+            return Ok(());
+        }
+        write!(f, "[{:>5}.{:06}] ", self.0.tv_sec, self.0.tv_nsec / 1000)
+    }
+}
+
+/// 日志的子系统前缀：`<target>: (<file>:<line>)\t `
+///
+/// target默认为模块路径，这里去掉内核crate名，只保留子系统路径（如`driver::block::md`）
+struct RecordPrefix<'a, 'b>(&'a log::Record<'b>);
+
+impl Display for RecordPrefix<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let record = self.0;
+        let target = record.target();
+        let target = target
+            .strip_prefix(concat!(env!("CARGO_CRATE_NAME"), "::"))
+            .unwrap_or(target);
+        write!(
+            f,
+            "{}: ({}:{})\t ",
+            target,
+            record.file().unwrap_or(""),
+            record.line().unwrap_or(0)
+        )
+    }
+}
+
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => ($crate::libs::printk::__printk(format_args!($($arg)*)));
@@ -33,21 +110,13 @@ impl PrintkWriter {
         self.write_fmt(args).ok();
     }
 
-    /// 并输出白底黑字
+    /// 写到串口、pstore和netconsole，屏幕输出交给[`screen_write`]
     /// @param str: 要写入的字符
     pub fn __write_string(&mut self, s: &str) {
-        if let Some(current_vc) = vc_manager().current_vc() {
-            // tty已经初始化了之后才输出到屏幕
-            let port = current_vc.port();
-            let tty = port.port_data().internal_tty();
-            if let Some(tty) = tty {
-                let _ = tty.write(tty.core(), s.as_bytes(), s.len());
-            } else {
-                let _ = textui_putstr(s, FontColor::WHITE, FontColor::BLACK);
-            }
-        } else {
-            let _ = textui_putstr(s, FontColor::WHITE, FontColor::BLACK);
-        }
+        pstore_console_write(s);
+        netconsole_write(s);
+        send_to_default_serial8250_port(s.as_bytes());
+        screen_write(|w| w.write_str(s));
     }
 }
 
@@ -59,6 +128,34 @@ impl fmt::Write for PrintkWriter {
     }
 }
 
+/// 只写到串口的日志输出，panic期间同时保存到pstore
+struct SerialSinkWriter;
+
+impl fmt::Write for SerialSinkWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        pstore_console_write(s);
+        // 串口终端需要\r\n换行
+        let mut lines = s.split('\n');
+        if let Some(first) = lines.next() {
+            send_to_default_serial8250_port(first.as_bytes());
+        }
+        for line in lines {
+            send_to_default_serial8250_port(b"\r\n");
+            send_to_default_serial8250_port(line.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+struct NetconsoleSinkWriter;
+
+impl fmt::Write for NetconsoleSinkWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        netconsole_write(s);
+        Ok(())
+    }
+}
+
 #[doc(hidden)]
 pub fn __printk(args: fmt::Arguments) {
     PrintkWriter.write_fmt(args).unwrap();
@@ -68,9 +165,12 @@ pub struct Logger;
 
 impl Logger {
     pub fn log(&self, log_level: LogLevel, message: fmt::Arguments) {
-        if unsafe { KMSG.is_some() } {
-            let timestamp: PosixTimeSpec = PosixTimeSpec::now_cpu_time();
+        self.log_at(PosixTimeSpec::now_cpu_time(), log_level, message);
+    }
 
+    /// 以给定的时间戳写入kmsg缓冲区
+    pub fn log_at(&self, timestamp: PosixTimeSpec, log_level: LogLevel, message: fmt::Arguments) {
+        if unsafe { KMSG.is_some() } {
             let log_message = LogMessage::new(timestamp, log_level, message.to_string());
 
             unsafe { KMSG.as_ref().unwrap().lock_irqsave().push(log_message) };
@@ -85,16 +185,24 @@ struct KernelLogger;
 
 impl Log for KernelLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        // 根据全局日志级别过滤日志
-        KERNEL_LOG_LEVEL.should_print(metadata.level().into())
+        // 只要有一个输出端需要就记录
+        LOG_SINKS.any_enabled(&metadata.level().into())
     }
 
     fn log(&self, record: &log::Record) {
-        if self.enabled(record.metadata()) {
-            // 记录到 kmsg 缓冲区
-            Self::kernel_log(record);
-            // 输出到控制台
-            Self::iodisplay(record)
+        let level: LogLevel = record.level().into();
+        if !LOG_SINKS.any_enabled(&level) {
+            return;
+        }
+        // 各个输出端使用同一个时间戳
+        let timestamp = PosixTimeSpec::now_cpu_time();
+        if LOG_SINKS.should_emit(LogSink::Kmsg, &level) {
+            Self::kernel_log(timestamp, record);
+        }
+        for sink in LogSink::CONSOLES {
+            if LOG_SINKS.should_emit(sink, &level) {
+                Self::console_log(sink, timestamp, record);
+            }
         }
     }
 
@@ -104,68 +212,51 @@ impl Log for KernelLogger {
 }
 
 impl KernelLogger {
-    fn iodisplay(record: &log::Record) {
-        match record.level() {
-            Level::Debug | Level::Info | Level::Trace => {
-                write!(PrintkWriter, "[ {} ] ", record.level(),)
-            }
-            Level::Error => {
-                write!(PrintkWriter, "\x1B[41m[ ERROR ] \x1B[0m",)
+    /// 把日志写到一个控制台输出端
+    ///
+    /// 日志系统初始化得很早，这里直接格式化到输出端，不分配内存
+    fn console_log(sink: LogSink, timestamp: PosixTimeSpec, record: &log::Record) {
+        let color = LOG_SINKS.color(sink);
+        let _ = match sink {
+            LogSink::Serial => Self::format_record(&mut SerialSinkWriter, timestamp, record, color),
+            LogSink::Screen => {
+                screen_write(|w| Self::format_record(w, timestamp, record, color));
+                Ok(())
             }
-            Level::Warn => {
-                write!(PrintkWriter, "\x1B[1;33m[ WARN ] \x1B[0m",)
+            LogSink::Netconsole => {
+                Self::format_record(&mut NetconsoleSinkWriter, timestamp, record, color)
             }
+            LogSink::Kmsg => Ok(()),
+        };
+    }
+
+    fn format_record(
+        w: &mut (impl Write + ?Sized),
+        timestamp: PosixTimeSpec,
+        record: &log::Record,
+        color: bool,
+    ) -> fmt::Result {
+        write!(w, "{}", PrintkTime(timestamp))?;
+        match (record.level(), color) {
+            (Level::Error, true) => w.write_str("\x1B[41m[ ERROR ] \x1B[0m")?,
+            (Level::Warn, true) => w.write_str("\x1B[1;33m[ WARN ] \x1B[0m")?,
+            (level, _) => write!(w, "[ {} ] ", level)?,
         }
-        .unwrap();
-        writeln!(
-            PrintkWriter,
-            "({}:{})\t {}",
-            record.file().unwrap_or(""),
-            record.line().unwrap_or(0),
-            record.args()
-        )
-        .unwrap();
-    }
-
-    fn kernel_log(record: &log::Record) {
-        match record.level() {
-            Level::Debug | Level::Trace => Logger.log(
-                LogLevel::DEBUG,
-                format_args!(
-                    "({}:{})\t {}\n",
-                    record.file().unwrap_or(""),
-                    record.line().unwrap_or(0),
-                    record.args()
-                ),
-            ),
-            Level::Error => Logger.log(
-                LogLevel::ERR,
-                format_args!(
-                    "({}:{})\t {}\n",
-                    record.file().unwrap_or(""),
-                    record.line().unwrap_or(0),
-                    record.args()
-                ),
-            ),
-            Level::Info => Logger.log(
-                LogLevel::INFO,
-                format_args!(
-                    "({}:{})\t {}\n",
-                    record.file().unwrap_or(""),
-                    record.line().unwrap_or(0),
-                    record.args()
-                ),
-            ),
-            Level::Warn => Logger.log(
-                LogLevel::WARN,
-                format_args!(
-                    "({}:{})\t {}\n",
-                    record.file().unwrap_or(""),
-                    record.line().unwrap_or(0),
-                    record.args()
-                ),
-            ),
-        }
+        writeln!(w, "{}{}", RecordPrefix(record), record.args())
+    }
+
+    fn kernel_log(timestamp: PosixTimeSpec, record: &log::Record) {
+        let level = match record.level() {
+            Level::Debug | Level::Trace => LogLevel::DEBUG,
+            Level::Error => LogLevel::ERR,
+            Level::Info => LogLevel::INFO,
+            Level::Warn => LogLevel::WARN,
+        };
+        Logger.log_at(
+            timestamp,
+            level,
+            format_args!("{}{}\n", RecordPrefix(record), record.args()),
+        );
     }
 }
 
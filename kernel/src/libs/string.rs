@@ -0,0 +1,126 @@
+//! 带边界检查的内存/字符串拷贝
+//!
+//! 类似Linux的FORTIFY_SOURCE：按目标缓冲区的实际大小检查拷贝长度。
+//! debug构建下发现越界直接panic，便于尽早暴露问题；release构建下截断到目标缓冲区的大小，
+//! 保证不会越界写入。
+//!
+//! 用于把字符串填入用户态ABI中固定长度的数组（例如`char name[64]`）等场景。
+
+use system_error::SystemError;
+
+#[inline(always)]
+#[track_caller]
+fn fortify_check(func: &str, len: usize, size: usize) {
+    if cfg!(debug_assertions) && len > size {
+        panic!(
+            "{}: buffer overflow detected ({} bytes into {} bytes)",
+            func, len, size
+        );
+    }
+}
+
+/// # 功能
+///
+/// 把`src`拷贝到`dst`的开头
+///
+/// ## 返回值
+/// 拷贝的字节数。`src`比`dst`长时，debug构建下panic，release构建下截断
+#[track_caller]
+pub fn memcpy(dst: &mut [u8], src: &[u8]) -> usize {
+    fortify_check("memcpy", src.len(), dst.len());
+    let len = src.len().min(dst.len());
+    dst[..len].copy_from_slice(&src[..len]);
+    len
+}
+
+/// 返回`s`中第一个0之前的长度，没有0时返回`s.len()`
+pub fn strnlen(s: &[u8]) -> usize {
+    s.iter().position(|&c| c == 0).unwrap_or(s.len())
+}
+
+/// # 功能
+///
+/// 把字符串`src`（到第一个0或者切片末尾为止）拷贝到`dst`，并以0结尾
+///
+/// ## 返回值
+/// 拷贝的字节数（不含结尾的0）。放不下时，debug构建下panic，release构建下截断
+#[track_caller]
+pub fn strcpy(dst: &mut [u8], src: &[u8]) -> usize {
+    let len = strnlen(src);
+    fortify_check("strcpy", len + 1, dst.len());
+    strscpy(dst, src).unwrap_or_else(|_| dst.len().saturating_sub(1))
+}
+
+/// # 功能
+///
+/// 把字符串`src`（到第一个0或者切片末尾为止）拷贝到`dst`，并以0结尾。
+/// 与[`strcpy`]不同，截断是预期内的情况，不会panic
+///
+/// ## 返回值
+/// - `Ok(len)`: 拷贝的字节数（不含结尾的0）
+/// - `Err(SystemError::E2BIG)`: 字符串被截断（`dst`不为空时仍然以0结尾）
+pub fn strscpy(dst: &mut [u8], src: &[u8]) -> Result<usize, SystemError> {
+    if dst.is_empty() {
+        return Err(SystemError::E2BIG);
+    }
+    let len = strnlen(src);
+    let copy_len = len.min(dst.len() - 1);
+    dst[..copy_len].copy_from_slice(&src[..copy_len]);
+    dst[copy_len] = 0;
+    if copy_len < len {
+        return Err(SystemError::E2BIG);
+    }
+    Ok(copy_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strscpy_fits() {
+        let mut buf = [0xffu8; 8];
+        assert_eq!(strscpy(&mut buf, b"abc"), Ok(3));
+        assert_eq!(&buf[..4], b"abc\0");
+    }
+
+    #[test]
+    fn test_strscpy_stops_at_nul() {
+        let mut buf = [0xffu8; 8];
+        assert_eq!(strscpy(&mut buf, b"ab\0cd"), Ok(2));
+        assert_eq!(&buf[..3], b"ab\0");
+    }
+
+    #[test]
+    fn test_strscpy_truncates() {
+        let mut buf = [0xffu8; 4];
+        assert_eq!(strscpy(&mut buf, b"abcdef"), Err(SystemError::E2BIG));
+        assert_eq!(&buf, b"abc\0");
+
+        let mut empty = [0u8; 0];
+        assert_eq!(strscpy(&mut empty, b"a"), Err(SystemError::E2BIG));
+    }
+
+    #[test]
+    fn test_memcpy_fits() {
+        let mut buf = [0u8; 4];
+        assert_eq!(memcpy(&mut buf, b"xy"), 2);
+        assert_eq!(&buf, b"xy\0\0");
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer overflow detected")]
+    #[cfg(debug_assertions)]
+    fn test_memcpy_overflow_panics() {
+        let mut buf = [0u8; 2];
+        memcpy(&mut buf, b"xyz");
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer overflow detected")]
+    #[cfg(debug_assertions)]
+    fn test_strcpy_overflow_panics() {
+        let mut buf = [0u8; 3];
+        strcpy(&mut buf, b"abc");
+    }
+}
@@ -20,6 +20,14 @@ pub fn sys_kernel_kobj() -> Arc<CommonKobj> {
     unsafe { KERNEL_KOBJECT_INSTANCE.clone().unwrap() }
 }
 
+/// `/sys/fs`的kobject，各个文件系统在其下创建自己的目录
+static mut FS_KOBJECT_INSTANCE: Option<Arc<CommonKobj>> = None;
+
+#[inline(always)]
+pub fn sys_fs_kobj() -> Arc<CommonKobj> {
+    unsafe { FS_KOBJECT_INSTANCE.clone().unwrap() }
+}
+
 #[unified_init(INITCALL_CORE)]
 fn ksysfs_init() -> Result<(), SystemError> {
     // let kernel_kset = KSet::new("kernel".to_string());
@@ -42,6 +50,12 @@ fn ksysfs_init() -> Result<(), SystemError> {
         KERNEL_KOBJECT_INSTANCE = Some(kernel_kobj);
     }
 
+    let fs_kobj = CommonKobj::new("fs".to_string());
+    KObjectManager::init_and_add_kobj(fs_kobj.clone(), Some(&DynamicKObjKType))?;
+    unsafe {
+        FS_KOBJECT_INSTANCE = Some(fs_kobj);
+    }
+
     return Ok(());
 }
 
@@ -1,5 +1,4 @@
 use alloc::{string::String, sync::Arc};
-use core::hint::spin_loop;
 use system_error::SystemError;
 
 use crate::{
@@ -237,9 +236,14 @@ pub fn kernel_power_off() -> ! {
     do_kernel_power_off_prepare();
 
     log::warn!("Power down");
-    log::warn!("Currently, the system cannot be powered off, so we halt here.");
-    loop {
-        spin_loop();
+    #[cfg(target_arch = "riscv64")]
+    crate::arch::reboot::machine_power_off();
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        log::warn!("Currently, the system cannot be powered off, so we halt here.");
+        loop {
+            core::hint::spin_loop();
+        }
     }
 }
 
@@ -0,0 +1,206 @@
+//! 内核堆分配的分配点统计（类似Linux的`/proc/allocinfo`）
+//!
+//! 这个模块只在开启`alloc_profiling`特性时编译。被[`alloc_hooks!`](crate::alloc_hooks)包住的表达式中
+//! 发生的堆分配，都会记在这个分配点（模块、文件、行号）上；其它的分配记在“未标记”分配点上。
+//!
+//! 每个堆对象前面都有一个头部，保存它所属的分配点，释放时据此扣减统计。
+//! 统计结果通过`/sys/kernel/debug/allocinfo`导出，参见[`crate::debug::allocinfo`]。
+//!
+//! 当前的分配点保存在进程的PCB中，因此被包住的代码可以睡眠、被抢占。
+
+use core::{
+    alloc::Layout,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::process::ProcessManager;
+
+/// 一个分配点上的统计
+#[derive(Debug)]
+pub struct AllocTag {
+    pub module: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    /// 存活对象的字节数
+    bytes: AtomicUsize,
+    /// 存活对象的数量
+    calls: AtomicUsize,
+    /// 上一次快照时的存活字节数
+    baseline: AtomicUsize,
+}
+
+impl AllocTag {
+    pub const fn new(module: &'static str, file: &'static str, line: u32) -> Self {
+        Self {
+            module,
+            file,
+            line,
+            bytes: AtomicUsize::new(0),
+            calls: AtomicUsize::new(0),
+            baseline: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// 自上一次快照以来增长的存活字节数
+    pub fn growth(&self) -> isize {
+        self.bytes() as isize - self.baseline.load(Ordering::Relaxed) as isize
+    }
+}
+
+/// 所有由[`alloc_hooks!`](crate::alloc_hooks)定义的分配点
+#[::linkme::distributed_slice]
+pub static ALLOC_TAGS: [AllocTag] = [..];
+
+/// 没有被标记的分配
+static UNTAGGED: AllocTag = AllocTag::new("untagged", "", 0);
+
+/// 遍历所有分配点，包括“未标记”分配点
+fn all_tags() -> impl Iterator<Item = &'static AllocTag> {
+    core::iter::once(&UNTAGGED).chain(ALLOC_TAGS.iter())
+}
+
+/// 在作用域内把当前进程的分配记到指定的分配点上，离开作用域时恢复原来的分配点
+pub struct AllocTagGuard {
+    prev: Option<*const AllocTag>,
+}
+
+impl AllocTagGuard {
+    pub fn enter(tag: &'static AllocTag) -> Self {
+        if !ProcessManager::initialized() {
+            return Self { prev: None };
+        }
+        let prev = ProcessManager::current_pcb().swap_alloc_tag(tag);
+        Self { prev: Some(prev) }
+    }
+}
+
+impl Drop for AllocTagGuard {
+    fn drop(&mut self) {
+        if let Some(prev) = self.prev {
+            ProcessManager::current_pcb().swap_alloc_tag(prev);
+        }
+    }
+}
+
+/// 获取当前的分配应当记在哪个分配点上
+fn current_tag() -> &'static AllocTag {
+    if !ProcessManager::initialized() {
+        return &UNTAGGED;
+    }
+    let tag = ProcessManager::current_pcb().alloc_tag();
+    if tag.is_null() {
+        &UNTAGGED
+    } else {
+        // 分配点都是静态变量
+        unsafe { &*tag }
+    }
+}
+
+/// 头部的大小：至少能放下一个指针，并且保持原有的对齐
+fn header_size(layout: Layout) -> usize {
+    layout.align().max(core::mem::size_of::<usize>())
+}
+
+/// 向底层分配器申请的布局：在原有的大小前面加上头部
+pub(super) fn tagged_layout(layout: Layout) -> Layout {
+    Layout::from_size_align(layout.size() + header_size(layout), layout.align()).unwrap()
+}
+
+/// 记录一次分配，返回交给调用者的地址
+///
+/// ## 参数
+/// - `base`: 底层分配器按[`tagged_layout`]分配到的地址
+/// - `layout`: 调用者申请的布局
+///
+/// ## Safety
+/// `base`必须为空，或者是按`tagged_layout(layout)`分配的内存
+pub(super) unsafe fn on_alloc(base: *mut u8, layout: Layout) -> *mut u8 {
+    if base.is_null() {
+        return base;
+    }
+    let tag = current_tag();
+    tag.bytes.fetch_add(layout.size(), Ordering::Relaxed);
+    tag.calls.fetch_add(1, Ordering::Relaxed);
+
+    let ptr = base.add(header_size(layout));
+    (ptr as *mut *const AllocTag).sub(1).write(tag);
+    ptr
+}
+
+/// 记录一次释放，返回要交还给底层分配器的地址和布局
+///
+/// ## Safety
+/// `ptr`必须是[`on_alloc`]返回的地址，`layout`与分配时相同
+pub(super) unsafe fn on_dealloc(ptr: *mut u8, layout: Layout) -> (*mut u8, Layout) {
+    let tag = &*(ptr as *mut *const AllocTag).sub(1).read();
+    tag.bytes.fetch_sub(layout.size(), Ordering::Relaxed);
+    tag.calls.fetch_sub(1, Ordering::Relaxed);
+    (ptr.sub(header_size(layout)), tagged_layout(layout))
+}
+
+fn format_tag(tag: &AllocTag, bytes: String) -> String {
+    if tag.line == 0 {
+        format!("{} {} [{}]\n", bytes, tag.calls(), tag.module)
+    } else {
+        format!(
+            "{} {} {}:{} [{}]\n",
+            bytes,
+            tag.calls(),
+            tag.file,
+            tag.line,
+            tag.module
+        )
+    }
+}
+
+/// 生成所有分配点的统计，格式与Linux的`/proc/allocinfo`相同：字节数 对象数 位置 [模块]
+pub fn allocinfo_report() -> String {
+    let mut s = String::from("# <size> <calls> <tag info>\n");
+    for tag in all_tags() {
+        s.push_str(&format_tag(tag, format!("{:>12}", tag.bytes())));
+    }
+    s
+}
+
+/// 生成泄漏报告：自上一次快照以来存活字节数增长的分配点，按增长量从大到小排列
+pub fn alloc_leak_report() -> String {
+    let mut tags: Vec<&AllocTag> = all_tags().filter(|t| t.growth() > 0).collect();
+    tags.sort_by_key(|t| core::cmp::Reverse(t.growth()));
+
+    let mut s = String::from("# <growth> <calls> <tag info>\n");
+    for tag in tags {
+        s.push_str(&format_tag(tag, format!("{:>+12}", tag.growth())));
+    }
+    s
+}
+
+/// 记录所有分配点当前的存活字节数，作为之后泄漏报告的基准
+pub fn alloc_tag_snapshot() {
+    for tag in all_tags() {
+        tag.baseline.store(tag.bytes(), Ordering::Relaxed);
+    }
+}
+
+/// 把表达式中发生的堆分配记到当前位置的分配点上
+///
+/// 没有开启`alloc_profiling`特性时直接求值表达式，参见`crate::mm`中的同名宏。
+#[macro_export]
+macro_rules! alloc_hooks {
+    ($e:expr) => {{
+        #[::linkme::distributed_slice($crate::mm::alloc_tag::ALLOC_TAGS)]
+        static __ALLOC_TAG: $crate::mm::alloc_tag::AllocTag =
+            $crate::mm::alloc_tag::AllocTag::new(module_path!(), file!(), line!());
+        let __alloc_tag_guard = $crate::mm::alloc_tag::AllocTagGuard::enter(&__ALLOC_TAG);
+        $e
+    }};
+}
@@ -18,6 +18,9 @@ use super::{
     slab::SLABALLOCATOR,
 };
 
+#[cfg(feature = "alloc_profiling")]
+use crate::mm::alloc_tag;
+
 /// 类kmalloc的分配器应当实现的trait
 pub trait LocalAlloc {
     #[allow(dead_code)]
@@ -116,26 +119,36 @@ impl LocalAlloc for KernelAllocator {
 /// 为内核slab分配器实现GlobalAlloc特性
 unsafe impl GlobalAlloc for KernelAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        #[cfg(feature = "alloc_profiling")]
+        let (user_layout, layout) = (layout, alloc_tag::tagged_layout(layout));
         let r = self.local_alloc_zeroed(layout);
         if allocator_select_condition(layout) {
             alloc_debug_log(klog_types::LogSource::Buddy, layout, r);
         } else {
             alloc_debug_log(klog_types::LogSource::Slab, layout, r);
         }
+        #[cfg(feature = "alloc_profiling")]
+        let r = alloc_tag::on_alloc(r, user_layout);
         return r;
     }
 
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        #[cfg(feature = "alloc_profiling")]
+        let (user_layout, layout) = (layout, alloc_tag::tagged_layout(layout));
         let r = self.local_alloc_zeroed(layout);
         if allocator_select_condition(layout) {
             alloc_debug_log(klog_types::LogSource::Buddy, layout, r);
         } else {
             alloc_debug_log(klog_types::LogSource::Slab, layout, r);
         }
+        #[cfg(feature = "alloc_profiling")]
+        let r = alloc_tag::on_alloc(r, user_layout);
         return r;
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        #[cfg(feature = "alloc_profiling")]
+        let (ptr, layout) = alloc_tag::on_dealloc(ptr, layout);
         if allocator_select_condition(layout) || (ptr as usize).is_multiple_of(4096) {
             dealloc_debug_log(klog_types::LogSource::Buddy, layout, ptr);
         } else {
@@ -2,6 +2,7 @@ use core::{
     alloc::Layout,
     cmp::{max, min},
     intrinsics::unlikely,
+    ops::Range,
     panic,
 };
 
@@ -9,8 +10,10 @@ use alloc::sync::Arc;
 
 use crate::{
     arch::{mm::PageMapper, MMArch},
+    filesystem::page_cache::PageCache,
     libs::align::align_down,
     mm::{
+        huge_memory::{self, thp_vma_allowable, HPAGE_PMD_SIZE},
         page::{page_manager_lock, EntryFlags},
         ucontext::LockedVMA,
         VirtAddr, VmFaultReason, VmFlags,
@@ -22,6 +25,9 @@ use crate::mm::MemoryManagementArch;
 
 use super::page::{Page, PageFlags, PageType};
 
+/// 缺页预读（fault-around）的页数
+const FAULT_AROUND_PAGES: usize = 16;
+
 bitflags! {
     pub struct FaultFlags: u64{
         const FAULT_FLAG_WRITE = 1 << 0;
@@ -58,6 +64,8 @@ pub struct PageFaultMessage<'a> {
     page: Option<Arc<Page>>,
     /// 写时拷贝需要的页面
     cow_page: Option<Arc<Page>>,
+    /// 返回`VM_FAULT_RETRY`时，需要在释放地址空间锁之后完成的I/O
+    retry_io: Option<FaultRetryIo>,
 }
 
 impl<'a> PageFaultMessage<'a> {
@@ -80,6 +88,7 @@ impl<'a> PageFaultMessage<'a> {
             page: None,
             mapper,
             cow_page: None,
+            retry_io: None,
         }
     }
 
@@ -106,6 +115,48 @@ impl<'a> PageFaultMessage<'a> {
     pub fn flags(&self) -> FaultFlags {
         self.flags
     }
+
+    /// 取出缺页处理返回`VM_FAULT_RETRY`时留下的I/O
+    #[inline(always)]
+    pub fn take_retry_io(&mut self) -> Option<FaultRetryIo> {
+        self.retry_io.take()
+    }
+}
+
+/// 缺页处理需要在释放地址空间锁之后完成的文件页读入
+///
+/// 文件页不在PageCache中时，如果允许重试，`filemap_fault`不会在持有地址空间锁的情况下
+/// 等待块设备I/O，而是记录需要读入的页并返回`VM_FAULT_RETRY`。
+/// 调用者释放锁之后调用[`FaultRetryIo::run`]，再带上`FAULT_FLAG_TRIED`重新处理缺页。
+#[derive(Debug)]
+pub struct FaultRetryIo {
+    vma: Arc<LockedVMA>,
+    page_cache: Arc<PageCache>,
+    /// 读入的后备对象页号范围，同时作为VMA范围锁的范围
+    range: Range<usize>,
+    /// 发生缺页的后备对象页号
+    pgoff: usize,
+}
+
+impl FaultRetryIo {
+    /// 读入缺页所在的页，并对范围内的其余页发起预读
+    ///
+    /// 读入失败时不做处理，重试的缺页处理会再次读取并返回相应的错误
+    pub fn run(self) {
+        let _guard = self.vma.lock_fault_range(self.range.clone());
+        // 等待范围锁期间，其他线程可能已经读入了这一页
+        if self.page_cache.is_page_ready(self.pgoff) {
+            return;
+        }
+
+        let manager = self.page_cache.manager();
+        for pgoff in self.range.clone() {
+            if pgoff != self.pgoff {
+                let _ = manager.prefetch_page(pgoff);
+            }
+        }
+        let _ = manager.commit_page(self.pgoff);
+    }
 }
 
 /// 缺页中断处理结构体
@@ -121,7 +172,7 @@ impl PageFaultHandler {
     /// ## 返回值
     /// - VmFaultReason: 页面错误处理信息标志
     #[inline(never)]
-    pub unsafe fn handle_mm_fault(mut pfm: PageFaultMessage) -> VmFaultReason {
+    pub unsafe fn handle_mm_fault(pfm: &mut PageFaultMessage) -> VmFaultReason {
         let flags = pfm.flags();
         let vma = pfm.vma();
         let current_pcb = ProcessManager::current_pcb();
@@ -145,7 +196,7 @@ impl PageFaultHandler {
         if unlikely(vm_flags.contains(VmFlags::VM_HUGETLB)) {
             //TODO: 添加handle_hugetlb_fault处理大页缺页异常
         } else {
-            return Self::handle_normal_fault(&mut pfm);
+            return Self::handle_normal_fault(pfm);
         }
 
         VmFaultReason::VM_FAULT_COMPLETED
@@ -162,30 +213,75 @@ impl PageFaultHandler {
     pub unsafe fn handle_normal_fault(pfm: &mut PageFaultMessage) -> VmFaultReason {
         let address = pfm.address_aligned_down();
         let vma = pfm.vma();
-        let mapper = &mut pfm.mapper;
-        if mapper.get_entry(address, 3).is_none() {
-            mapper
+        if pfm.mapper.get_entry(address, 3).is_none() {
+            pfm.mapper
                 .allocate_table(address, 2)
                 .expect("failed to allocate PUD table");
         }
-        let page_flags = vma.lock().flags();
 
         for level in 2..=3 {
             let level = MMArch::PAGE_LEVELS - level;
-            if mapper.get_entry(address, level).is_none() {
-                if vma.is_hugepage() {
-                    if vma.is_anonymous() {
-                        mapper.map_huge_page(address, page_flags);
+            if pfm.mapper.get_entry(address, level).is_none() {
+                if level == 1 && thp_vma_allowable(&vma, address) {
+                    let ret = Self::do_huge_pmd_anonymous_page(pfm);
+                    if !ret.contains(VmFaultReason::VM_FAULT_FALLBACK) {
+                        return ret;
                     }
-                } else if mapper.allocate_table(address, level - 1).is_none() {
+                }
+                if pfm.mapper.allocate_table(address, level - 1).is_none() {
                     return VmFaultReason::VM_FAULT_OOM;
                 }
             }
         }
 
+        // PMD映射了透明大页。只有对写保护的大页（例如fork之后）写入时需要处理：
+        // 把大页拆分为4K页表项，再由handle_pte_fault逐页进行写时复制
+        if let Some(entry) = pfm.mapper.get_huge_entry(address) {
+            if !pfm
+                .flags
+                .intersects(FaultFlags::FAULT_FLAG_WRITE | FaultFlags::FAULT_FLAG_UNSHARE)
+                || entry.write()
+            {
+                return VmFaultReason::VM_FAULT_COMPLETED;
+            }
+            match pfm.mapper.split_huge_page(address) {
+                Some(flush) => flush.flush(),
+                None => return VmFaultReason::VM_FAULT_OOM,
+            }
+        }
+
         Self::handle_pte_fault(pfm)
     }
 
+    /// 为私有匿名映射分配并映射一个透明大页
+    /// ## 参数
+    ///
+    /// - `pfm`: 缺页异常信息
+    ///
+    /// ## 返回值
+    /// - VmFaultReason: 页面错误处理信息标志，无法使用大页时返回`VM_FAULT_FALLBACK`
+    pub unsafe fn do_huge_pmd_anonymous_page(pfm: &mut PageFaultMessage) -> VmFaultReason {
+        let haddr = VirtAddr::new(align_down(pfm.address.data(), HPAGE_PMD_SIZE));
+        let vma = pfm.vma.clone();
+        let flags = vma.lock().flags();
+
+        let mut page_manager_guard = page_manager_lock();
+        let Some((paddr, pages)) = huge_memory::alloc_huge_page(&mut page_manager_guard) else {
+            return VmFaultReason::VM_FAULT_FALLBACK;
+        };
+        let Some(flush) = pfm.mapper.map_huge_phys(haddr, paddr, flags) else {
+            huge_memory::free_huge_page(&mut page_manager_guard, pages);
+            return VmFaultReason::VM_FAULT_FALLBACK;
+        };
+        drop(page_manager_guard);
+        flush.flush();
+        for page in pages {
+            page.write().insert_vma(vma.clone());
+        }
+        vma.lock().set_mapped(true);
+        VmFaultReason::VM_FAULT_COMPLETED
+    }
+
     /// 处理页表项异常
     /// ## 参数
     ///
@@ -378,8 +474,8 @@ impl PageFaultHandler {
             return ret;
         }
 
-        // 出现错误类返回时，不再继续 finish_fault 以避免 unwrap/panic
-        if ret.intersects(VmFaultReason::VM_FAULT_ERROR) {
+        // 出现错误类返回或需要释放锁重试时，不再继续 finish_fault 以避免 unwrap/panic
+        if ret.intersects(VmFaultReason::VM_FAULT_ERROR | VmFaultReason::VM_FAULT_RETRY) {
             return ret;
         }
 
@@ -400,7 +496,7 @@ impl PageFaultHandler {
     pub unsafe fn do_shared_fault(pfm: &mut PageFaultMessage) -> VmFaultReason {
         let mut ret = Self::filemap_fault(pfm);
 
-        if ret.intersects(VmFaultReason::VM_FAULT_ERROR) {
+        if ret.intersects(VmFaultReason::VM_FAULT_ERROR | VmFaultReason::VM_FAULT_RETRY) {
             return ret;
         }
 
@@ -476,10 +572,17 @@ impl PageFaultHandler {
         let mut page_manager = page_manager_lock();
         let old_page = page_manager.get_unwrap(&old_paddr);
         let map_count = old_page.read().map_count();
+        let is_private_copy = matches!(old_page.read().page_type(), PageType::Normal);
         drop(page_manager);
 
         let mut entry = mapper.get_entry(address, 0).unwrap();
-        let new_flags = entry.flags().set_write(true).set_dirty(true);
+        // 影子栈页面保持W=0、D=1，其余页面恢复可写
+        let new_flags = if vma.lock().vm_flags().contains(VmFlags::VM_SHADOW_STACK) {
+            entry.flags().set_write(false).set_dirty(true)
+        } else {
+            entry.flags().set_write(true).set_dirty(true)
+        }
+        .set_soft_dirty(true);
 
         if vma.lock().vm_flags().contains(VmFlags::VM_SHARED) {
             // 共享映射，直接修改页表项保护位，标记为脏页
@@ -525,6 +628,13 @@ impl PageFaultHandler {
             } else {
                 VmFaultReason::VM_FAULT_OOM
             }
+        } else if is_private_copy && map_count == 1 {
+            // 私有文件映射中已经拷贝过的页面（clear_refs清除soft-dirty时被写保护），直接恢复可写
+            let table = mapper.get_table(address, 0).unwrap();
+            let i = table.index_of(address).unwrap();
+            entry.set_flags(new_flags);
+            table.set_entry(i, entry);
+            VmFaultReason::VM_FAULT_COMPLETED
         } else {
             // 私有文件映射，必须拷贝页面
             if let Some(flush) = mapper.map(address, new_flags) {
@@ -584,7 +694,7 @@ impl PageFaultHandler {
 
         let vma_pages_count = (vma_region.end() - vma_region.start()) >> MMArch::PAGE_SHIFT;
 
-        let fault_around_page_number = 16;
+        let fault_around_page_number = FAULT_AROUND_PAGES;
 
         // 开始位置不能超出当前pte和vma头部
         let from_pte = max(
@@ -646,6 +756,12 @@ impl PageFaultHandler {
             }
         };
         let mapper = &mut pfm.mapper;
+        // 预读的页面以只读方式映射，共享映射第一次写入时由do_wp_page标记脏页
+        let entry_flags = if vma_guard.vm_flags().contains(VmFlags::VM_SHARED) {
+            vma_guard.flags().set_write(false)
+        } else {
+            vma_guard.flags()
+        };
 
         // 起始页地址
         let addr = vma_guard.region().start
@@ -664,10 +780,7 @@ impl PageFaultHandler {
                     let address =
                         VirtAddr::new(addr.data() + ((pgoff - start_pgoff) << MMArch::PAGE_SHIFT));
                     if mapper.get_entry(address, 0).is_none() {
-                        mapper
-                            .map_phys(address, phys, vma_guard.flags())
-                            .unwrap()
-                            .flush();
+                        mapper.map_phys(address, phys, entry_flags).unwrap().flush();
                     }
                     page_guard.upgrade().insert_vma(vma.clone());
                 }
@@ -701,16 +814,37 @@ impl PageFaultHandler {
         };
         let backing_pgoff = pfm.backing_pgoff.expect("no backing_pgoff");
         let mut ret = VmFaultReason::empty();
+        let mut file_pages = usize::MAX;
 
         if let Ok(md) = file.inode().metadata() {
             let size = md.size.max(0) as usize;
             if size == 0 || backing_pgoff.saturating_mul(MMArch::PAGE_SIZE) >= size {
                 return VmFaultReason::VM_FAULT_SIGBUS;
             }
+            file_pages = size.div_ceil(MMArch::PAGE_SIZE);
         }
 
         if !page_cache.is_page_ready(backing_pgoff) {
             ret = VmFaultReason::VM_FAULT_MAJOR;
+
+            // 需要读块设备，释放地址空间锁之后再读，避免阻塞同一进程中其他线程的缺页
+            if pfm.flags.contains(FaultFlags::FAULT_FLAG_ALLOW_RETRY)
+                && !pfm.flags.contains(FaultFlags::FAULT_FLAG_TRIED)
+            {
+                let vma_start = vma_guard
+                    .backing_page_offset()
+                    .expect("backing_page_offset is none");
+                let vma_end = vma_start + (vma_guard.region().size() >> MMArch::PAGE_SHIFT);
+                let start = max(align_down(backing_pgoff, FAULT_AROUND_PAGES), vma_start);
+                let end = min(start + FAULT_AROUND_PAGES, min(vma_end, file_pages));
+                pfm.retry_io = Some(FaultRetryIo {
+                    vma: vma.clone(),
+                    page_cache,
+                    range: start..end,
+                    pgoff: backing_pgoff,
+                });
+                return ret | VmFaultReason::VM_FAULT_RETRY;
+            }
         }
 
         match page_cache.manager().commit_page(backing_pgoff) {
@@ -794,7 +928,14 @@ impl PageFaultHandler {
 
         let page_phys = page_to_map.phys_address();
 
-        mapper.map_phys(address, page_phys, vma_guard.flags());
+        let mut entry_flags = vma_guard.flags();
+        if !flags.contains(FaultFlags::FAULT_FLAG_WRITE)
+            && vma_guard.vm_flags().contains(VmFlags::VM_SHARED)
+        {
+            // 共享映射的页面在第一次写入时才变为可写，由do_wp_page标记脏页
+            entry_flags = entry_flags.set_write(false);
+        }
+        mapper.map_phys(address, page_phys, entry_flags);
         page_to_map.write().insert_vma(pfm.vma());
         VmFaultReason::VM_FAULT_COMPLETED
     }
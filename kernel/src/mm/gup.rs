@@ -0,0 +1,212 @@
+//! 固定用户页
+//!
+//! 设备直接访问用户缓冲区（例如O_DIRECT）之前，先把缓冲区所在的页调入内存、完成写时拷贝，
+//! 并持有这些页的引用。I/O期间进程即使解除了映射，物理页也要等到引用释放之后才会回收。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/mm/gup.c
+
+use alloc::{sync::Arc, vec::Vec};
+use system_error::SystemError;
+
+use crate::{arch::MMArch, libs::align::page_align_down, process::ProcessManager};
+
+use super::{
+    fault::{FaultFlags, PageFaultHandler, PageFaultMessage},
+    page::{page_manager_lock, Page, PageFlags, PageType},
+    ucontext::AddressSpace,
+    MemoryManagementArch, VirtAddr, VmFaultReason, VmFlags,
+};
+
+/// 固定一页时最多处理的缺页次数
+const PIN_FAULT_ATTEMPTS: usize = 4;
+
+/// 固定住的一段用户缓冲区
+///
+/// 持有缓冲区所在的每一页的引用，设备访问缓冲区期间不能释放。
+/// [`slice`](Self::slice)得到的子缓冲区与原来的对象共享这些引用
+pub struct PinnedUserPages {
+    pages: Arc<Vec<Arc<Page>>>,
+    /// 缓冲区的起始位置相对于`pages[0]`开头的偏移
+    start: usize,
+    len: usize,
+}
+
+impl PinnedUserPages {
+    /// 缓冲区的字节数
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// # 取出缓冲区中的一段
+    ///
+    /// ## 参数
+    /// - `offset`: 子缓冲区在缓冲区中的偏移
+    /// - `len`: 子缓冲区的长度，`offset + len`不能超过缓冲区的长度
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        assert!(offset + len <= self.len, "slice out of pinned buffer");
+        Self {
+            pages: self.pages.clone(),
+            start: self.start + offset,
+            len,
+        }
+    }
+
+    /// 按顺序返回缓冲区在每一页中的部分，地址位于内核的直接映射区，每一段在物理上连续
+    pub fn segments(&self) -> impl Iterator<Item = *mut [u8]> + '_ {
+        let mut pos = self.start;
+        let end = self.start + self.len;
+        core::iter::from_fn(move || {
+            if pos >= end {
+                return None;
+            }
+            let page = &self.pages[pos / MMArch::PAGE_SIZE];
+            let in_page = pos % MMArch::PAGE_SIZE;
+            let len = (MMArch::PAGE_SIZE - in_page).min(end - pos);
+            pos += len;
+            let vaddr = unsafe { MMArch::phys_2_virt(page.phys_address()) }.unwrap();
+            Some(core::ptr::slice_from_raw_parts_mut(
+                (vaddr.data() + in_page) as *mut u8,
+                len,
+            ))
+        })
+    }
+
+    /// 把`src`拷贝到缓冲区的开头，最多拷贝缓冲区的长度
+    pub fn copy_from_slice(&self, src: &[u8]) {
+        let mut done = 0;
+        for seg in self.segments() {
+            if done == src.len() {
+                break;
+            }
+            let seg = unsafe { &mut *seg };
+            let n = seg.len().min(src.len() - done);
+            seg[..n].copy_from_slice(&src[done..done + n]);
+            done += n;
+        }
+    }
+
+    /// 把缓冲区开头的数据拷贝到`dst`，最多拷贝缓冲区的长度
+    pub fn copy_to_slice(&self, dst: &mut [u8]) {
+        let mut done = 0;
+        for seg in self.segments() {
+            if done == dst.len() {
+                break;
+            }
+            let seg = unsafe { &*seg };
+            let n = seg.len().min(dst.len() - done);
+            dst[done..done + n].copy_from_slice(&seg[..n]);
+            done += n;
+        }
+    }
+
+    /// 设备写入缓冲区之后调用。设备写入不会设置页表项的脏位，文件映射的页需要标记为脏页才会被写回
+    pub fn mark_dirty(&self) {
+        if self.len == 0 {
+            return;
+        }
+        let first = self.start / MMArch::PAGE_SIZE;
+        let last = (self.start + self.len - 1) / MMArch::PAGE_SIZE;
+        for page in &self.pages[first..=last] {
+            page.write().add_flags(PageFlags::PG_DIRTY);
+            if let PageType::File(info) = page.read().page_type().clone() {
+                if let Some(page_cache) = info.page_cache.upgrade() {
+                    page_cache.mark_page_dirty(info.index);
+                }
+            }
+        }
+    }
+}
+
+/// # 固定当前进程的一段用户缓冲区
+///
+/// 缓冲区中还没有调入的页、需要写时拷贝的页先按缺页处理，然后持有每一页的引用
+///
+/// ## 参数
+/// - `addr`: 缓冲区的起始地址
+/// - `len`: 缓冲区的长度
+/// - `write`: 设备是否会写入缓冲区（例如从文件读取数据）
+///
+/// ## 返回值
+/// - `Err(SystemError::EFAULT)`: 缓冲区中有没有映射或者权限不足的页，或者映射的不是普通内存（`VM_IO`、`VM_PFNMAP`）
+/// - `Err(SystemError::ENOMEM)`: 缺页处理时内存不足
+pub fn pin_user_pages(
+    addr: VirtAddr,
+    len: usize,
+    write: bool,
+) -> Result<PinnedUserPages, SystemError> {
+    let vm = ProcessManager::current_pcb()
+        .basic()
+        .user_vm()
+        .ok_or(SystemError::EFAULT)?;
+    let end = addr.data().checked_add(len).ok_or(SystemError::EFAULT)?;
+    let first = page_align_down(addr.data());
+
+    let mut pages = Vec::with_capacity((end - first).div_ceil(MMArch::PAGE_SIZE));
+    let mut page_addr = first;
+    while page_addr < end {
+        pages.push(pin_one_page(&vm, VirtAddr::new(page_addr), write)?);
+        page_addr += MMArch::PAGE_SIZE;
+    }
+
+    Ok(PinnedUserPages {
+        pages: Arc::new(pages),
+        start: addr.data() - first,
+        len,
+    })
+}
+
+/// 固定`addr`所在的页，页不在内存中或者权限不满足时按缺页处理之后再查找
+fn pin_one_page(
+    vm: &Arc<AddressSpace>,
+    addr: VirtAddr,
+    write: bool,
+) -> Result<Arc<Page>, SystemError> {
+    let mut flags = FaultFlags::FAULT_FLAG_ALLOW_RETRY | FaultFlags::FAULT_FLAG_KILLABLE;
+    if write {
+        flags |= FaultFlags::FAULT_FLAG_WRITE;
+    }
+    let required = if write {
+        VmFlags::VM_WRITE
+    } else {
+        VmFlags::VM_READ
+    };
+
+    for _ in 0..PIN_FAULT_ATTEMPTS {
+        let mut guard = vm.write();
+        let vma = guard.mappings.contains(addr).ok_or(SystemError::EFAULT)?;
+        let vm_flags = *vma.lock().vm_flags();
+        if vm_flags.intersects(VmFlags::VM_IO | VmFlags::VM_PFNMAP) || !vm_flags.contains(required)
+        {
+            return Err(SystemError::EFAULT);
+        }
+
+        let mapper = &mut guard.user_mapper.utable;
+        if let Some((paddr, entry_flags)) = mapper.translate(addr) {
+            if !write || entry_flags.has_write() {
+                // 持有地址空间的锁，页不会在这期间被解除映射
+                return page_manager_lock().get(&paddr).ok_or(SystemError::EFAULT);
+            }
+        }
+
+        let mut message = PageFaultMessage::new(vma, addr, flags, mapper);
+        let fault = unsafe { PageFaultHandler::handle_mm_fault(&mut message) };
+        let retry_io = message.take_retry_io();
+        if fault.contains(VmFaultReason::VM_FAULT_RETRY) {
+            flags |= FaultFlags::FAULT_FLAG_TRIED;
+            if let Some(retry_io) = retry_io {
+                // 读入文件页期间不持有地址空间锁
+                drop(guard);
+                retry_io.run();
+            }
+        } else if fault.contains(VmFaultReason::VM_FAULT_OOM) {
+            return Err(SystemError::ENOMEM);
+        } else if fault.intersects(VmFaultReason::VM_FAULT_ERROR) {
+            return Err(SystemError::EFAULT);
+        }
+    }
+    Err(SystemError::EFAULT)
+}
@@ -0,0 +1,683 @@
+//! 匿名内存的透明大页（THP）
+//!
+//! 参考 Linux mm/huge_memory.c 和 mm/khugepaged.c。
+//!
+//! - 私有匿名映射缺页时，如果缺页地址所在的2M区间完全落在VMA内，直接分配2M的物理页，用PMD映射
+//! - 部分解除映射、修改权限或者写时复制时，页表映射器把大页拆分为512个4K页表项
+//! - 后台线程khugepaged扫描地址空间，把4K页足够密集的2M区间合并为大页
+//!
+//! 大页的每个4K子页仍然对应一个[`Page`]，反向映射和释放都按4K进行，
+//! 因此拆分大页只需要修改页表。
+//!
+//! 控制接口位于`/sys/kernel/mm/transparent_hugepage`。
+
+use alloc::{string::ToString, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    arch::{mm::LockedFrameAllocator, MMArch},
+    driver::base::kobject::{CommonKobj, DynamicKObjKType, KObject, KObjectManager},
+    filesystem::{
+        sysfs::{
+            file::sysfs_emit_str, sysfs_instance, Attribute, AttributeGroup, SysFSOpsSupport,
+            SYSFS_ATTR_MODE_RO, SYSFS_ATTR_MODE_RW,
+        },
+        vfs::InodeMode,
+    },
+    init::initcall::INITCALL_CORE,
+    libs::align::{align_down, align_up},
+    process::ProcessManager,
+    time::{sleep::nanosleep, PosixTimeSpec},
+};
+
+use super::{
+    allocator::page_frame::{deallocate_page_frames, PageFrameCount, PhysPageFrame},
+    page::{
+        page_manager_lock, InactiveFlusher, Page, PageEntry, PageFlags, PageFlushAll, PageManager,
+        PageType, PAGE_2M_SIZE,
+    },
+    ucontext::{AddressSpace, InnerAddressSpace, LockedVMA},
+    MemoryManagementArch, PhysAddr, VirtAddr, VmFlags,
+};
+
+/// PMD大页的大小
+pub const HPAGE_PMD_SIZE: usize = PAGE_2M_SIZE;
+/// 一个PMD大页包含的4K页数
+pub const HPAGE_PMD_NR: usize = HPAGE_PMD_SIZE / MMArch::PAGE_SIZE;
+
+/// 透明大页的启用模式，对应`/sys/kernel/mm/transparent_hugepage/enabled`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ThpMode {
+    /// 所有私有匿名映射都使用大页
+    Always = 0,
+    /// 只有通过madvise(MADV_HUGEPAGE)标记的区域使用大页
+    Madvise = 1,
+    /// 禁用透明大页
+    Never = 2,
+}
+
+impl ThpMode {
+    const ALL: [ThpMode; 3] = [ThpMode::Always, ThpMode::Madvise, ThpMode::Never];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ThpMode::Always => "always",
+            ThpMode::Madvise => "madvise",
+            ThpMode::Never => "never",
+        }
+    }
+
+    fn from_u8(v: u8) -> Self {
+        match v {
+            0 => ThpMode::Always,
+            1 => ThpMode::Madvise,
+            _ => ThpMode::Never,
+        }
+    }
+}
+
+static THP_MODE: AtomicU8 = AtomicU8::new(ThpMode::Always as u8);
+
+/// khugepaged每轮扫描的4K页数
+static KHUGEPAGED_PAGES_TO_SCAN: AtomicUsize = AtomicUsize::new(HPAGE_PMD_NR * 8);
+/// khugepaged两轮扫描之间的休眠时间（毫秒）
+static KHUGEPAGED_SCAN_SLEEP_MS: AtomicUsize = AtomicUsize::new(10000);
+/// 合并时，2M区间内最多允许的空页表项数
+static KHUGEPAGED_MAX_PTES_NONE: AtomicUsize = AtomicUsize::new(HPAGE_PMD_NR - 1);
+/// 完整扫描所有地址空间的次数
+static KHUGEPAGED_FULL_SCANS: AtomicUsize = AtomicUsize::new(0);
+/// khugepaged合并出的大页数
+static KHUGEPAGED_PAGES_COLLAPSED: AtomicUsize = AtomicUsize::new(0);
+
+/// 获取当前的透明大页模式
+#[inline]
+pub fn thp_mode() -> ThpMode {
+    ThpMode::from_u8(THP_MODE.load(Ordering::Relaxed))
+}
+
+/// 当前架构是否支持透明大页
+///
+/// riscv64和loongarch64的页表项没有独立的大页标志位，暂不支持
+#[inline(always)]
+fn thp_supported() -> bool {
+    cfg!(target_arch = "x86_64")
+}
+
+/// # 判断VMA中包含`address`的2M区间能否使用透明大页
+///
+/// ## 参数
+///
+/// - `vma`: 地址所在的VMA
+/// - `address`: 虚拟地址
+///
+/// ## 返回值
+///
+/// 只有私有匿名映射，并且对齐后的2M区间完全落在VMA内时，才返回true
+pub fn thp_vma_allowable(vma: &LockedVMA, address: VirtAddr) -> bool {
+    if !thp_supported() {
+        return false;
+    }
+    let mode = thp_mode();
+    if mode == ThpMode::Never {
+        return false;
+    }
+
+    let guard = vma.lock();
+    if guard.vm_file().is_some() || guard.shared_anon.is_some() {
+        return false;
+    }
+    let vm_flags = *guard.vm_flags();
+    if vm_flags.intersects(
+        VmFlags::VM_SHARED
+            | VmFlags::VM_MAYSHARE
+            | VmFlags::VM_NOHUGEPAGE
+            | VmFlags::VM_HUGETLB
+            | VmFlags::VM_SHADOW_STACK
+            | VmFlags::VM_IO
+            | VmFlags::VM_PFNMAP,
+    ) {
+        return false;
+    }
+    if mode == ThpMode::Madvise && !vm_flags.contains(VmFlags::VM_HUGEPAGE) {
+        return false;
+    }
+
+    let haddr = align_down(address.data(), HPAGE_PMD_SIZE);
+    let region = guard.region();
+    haddr >= region.start().data() && haddr + HPAGE_PMD_SIZE <= region.end().data()
+}
+
+/// # 分配一个2M的匿名大页
+///
+/// 大页的每个4K子页都作为普通匿名页加入页面管理器，内容已清零
+///
+/// ## 返回值
+///
+/// - `Some((PhysAddr, Vec<Arc<Page>>))`: 大页的起始物理地址，512个子页
+/// - `None`: 没有足够的连续物理内存
+pub fn alloc_huge_page(page_manager: &mut PageManager) -> Option<(PhysAddr, Vec<Arc<Page>>)> {
+    let (paddr, pages) = page_manager
+        .create_pages(
+            PageType::Normal,
+            PageFlags::empty(),
+            &mut LockedFrameAllocator,
+            PageFrameCount::new(HPAGE_PMD_NR),
+        )
+        .ok()?;
+    if !paddr.check_aligned(HPAGE_PMD_SIZE) {
+        free_huge_page(page_manager, pages);
+        return None;
+    }
+    Some((paddr, pages))
+}
+
+/// 释放一个还没有被映射的大页
+pub fn free_huge_page(page_manager: &mut PageManager, pages: Vec<Arc<Page>>) {
+    for page in pages {
+        page_manager.remove_page(&page.phys_address());
+    }
+}
+
+/// 刷新所有CPU上的TLB
+fn flush_tlb_all() {
+    PageFlushAll::<MMArch>::new().flush();
+    drop(InactiveFlusher::new());
+}
+
+/// # 把`haddr`开始的2M区间中的4K页合并为一个大页
+///
+/// 区间内的页表项必须全部是只映射到当前VMA的匿名页或者空页表项，
+/// 并且空页表项的数量不超过`max_ptes_none`。调用者需要持有地址空间的写锁。
+///
+/// ## 返回值
+///
+/// 合并成功时返回true
+fn collapse_huge_page(
+    inner: &mut InnerAddressSpace,
+    vma: &Arc<LockedVMA>,
+    haddr: VirtAddr,
+) -> bool {
+    let mapper = &mut inner.user_mapper.utable;
+    // PMD为空或者已经是大页时没有最后一级页表
+    let (Some(pmd), Some(pte_table)) = (mapper.get_table(haddr, 1), mapper.get_table(haddr, 0))
+    else {
+        return false;
+    };
+    let Some(pmd_index) = pmd.index_of(haddr) else {
+        return false;
+    };
+
+    // 锁顺序：VMA锁 -> page_manager
+    let flags = vma.lock().flags();
+    let max_ptes_none = KHUGEPAGED_MAX_PTES_NONE.load(Ordering::Relaxed);
+    let mut page_manager_guard = page_manager_lock();
+    let mut old_pages: Vec<Option<Arc<Page>>> = Vec::with_capacity(HPAGE_PMD_NR);
+    let mut none = 0;
+    for i in 0..HPAGE_PMD_NR {
+        let entry = unsafe { pte_table.entry(i).unwrap() };
+        if entry.empty() {
+            none += 1;
+            if none > max_ptes_none {
+                return false;
+            }
+            old_pages.push(None);
+            continue;
+        }
+        let Ok(paddr) = entry.address() else {
+            return false;
+        };
+        let Some(page) = page_manager_guard.get(&paddr) else {
+            return false;
+        };
+        {
+            let page_guard = page.read();
+            if !matches!(page_guard.page_type(), PageType::Normal)
+                || page_guard.map_count() != 1
+                || !page_guard.vma_set().contains(vma)
+            {
+                return false;
+            }
+        }
+        old_pages.push(Some(page));
+    }
+    if none == HPAGE_PMD_NR {
+        return false;
+    }
+
+    let Some((hpaddr, new_pages)) = alloc_huge_page(&mut page_manager_guard) else {
+        return false;
+    };
+
+    // 先清除PMD并刷新TLB，防止其他CPU在拷贝过程中继续写入旧页
+    let old_pmd = unsafe { pmd.entry(pmd_index).unwrap() };
+    unsafe { pmd.set_entry(pmd_index, PageEntry::from_usize(0)) };
+    flush_tlb_all();
+
+    for (i, page) in old_pages.iter().enumerate() {
+        if let Some(page) = page {
+            unsafe {
+                let src = MMArch::phys_2_virt(page.phys_address()).unwrap();
+                let dst = MMArch::phys_2_virt(hpaddr + i * MMArch::PAGE_SIZE).unwrap();
+                (dst.data() as *mut u8)
+                    .copy_from_nonoverlapping(src.data() as *const u8, MMArch::PAGE_SIZE);
+            }
+        }
+    }
+
+    match unsafe { mapper.map_huge_phys(haddr, hpaddr, flags) } {
+        Some(flush) => flush.flush(),
+        None => {
+            unsafe { pmd.set_entry(pmd_index, old_pmd) };
+            free_huge_page(&mut page_manager_guard, new_pages);
+            return false;
+        }
+    }
+
+    for page in new_pages {
+        page.write().insert_vma(vma.clone());
+    }
+    for page in old_pages.into_iter().flatten() {
+        let mut page_guard = page.write();
+        page_guard.remove_vma(vma);
+        if page_guard.can_deallocate() {
+            page_manager_guard.remove_page(&page.phys_address());
+        }
+    }
+    unsafe { deallocate_page_frames(PhysPageFrame::new(pte_table.phys()), PageFrameCount::ONE) };
+
+    KHUGEPAGED_PAGES_COLLAPSED.fetch_add(1, Ordering::Relaxed);
+    true
+}
+
+/// khugepaged的扫描位置，下一轮从这里继续
+#[derive(Debug, Default)]
+struct KhugepagedCursor {
+    /// 地址空间ID
+    space_id: u64,
+    /// 地址空间内的虚拟地址
+    address: usize,
+}
+
+/// 收集所有用户地址空间，按ID排序。同一进程的多个线程共享地址空间，只保留一个
+fn collect_address_spaces() -> Vec<Arc<AddressSpace>> {
+    let mut spaces: Vec<Arc<AddressSpace>> = ProcessManager::get_all_processes()
+        .into_iter()
+        .filter_map(ProcessManager::find)
+        .filter_map(|pcb| pcb.basic().user_vm())
+        .collect();
+    spaces.sort_by_key(|space| space.id());
+    spaces.dedup_by_key(|space| space.id());
+    spaces
+}
+
+/// 扫描一个地址空间
+///
+/// ## 返回值
+///
+/// 地址空间扫描完成时返回true，扫描额度用完时返回false，游标记录了下一次开始的位置
+fn khugepaged_scan_space(
+    space: &Arc<AddressSpace>,
+    cursor: &mut KhugepagedCursor,
+    budget: &mut usize,
+) -> bool {
+    let mut guard = space.write();
+    let vmas: Vec<Arc<LockedVMA>> = guard.mappings.iter_vmas().cloned().collect();
+    for vma in vmas {
+        let region = *vma.lock().region();
+        let mut haddr = align_up(region.start().data().max(cursor.address), HPAGE_PMD_SIZE);
+        while haddr + HPAGE_PMD_SIZE <= region.end().data() {
+            if *budget == 0 {
+                cursor.address = haddr;
+                return false;
+            }
+            *budget = budget.saturating_sub(HPAGE_PMD_NR);
+
+            let addr = VirtAddr::new(haddr);
+            if thp_vma_allowable(&vma, addr) {
+                collapse_huge_page(&mut guard, &vma, addr);
+            }
+            haddr += HPAGE_PMD_SIZE;
+        }
+    }
+    true
+}
+
+/// 进行一轮扫描，扫描的4K页数不超过`pages_to_scan`
+fn khugepaged_do_scan(cursor: &mut KhugepagedCursor) {
+    let mut budget = KHUGEPAGED_PAGES_TO_SCAN.load(Ordering::Relaxed);
+    for space in collect_address_spaces() {
+        if space.id() < cursor.space_id {
+            continue;
+        }
+        if space.id() != cursor.space_id {
+            cursor.space_id = space.id();
+            cursor.address = 0;
+        }
+        if !khugepaged_scan_space(&space, cursor, &mut budget) {
+            return;
+        }
+        cursor.space_id = space.id() + 1;
+        cursor.address = 0;
+    }
+
+    // 所有地址空间都扫描了一遍，下一轮从头开始
+    *cursor = KhugepagedCursor::default();
+    KHUGEPAGED_FULL_SCANS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// khugepaged线程执行的函数
+fn khugepaged_thread() -> i32 {
+    let mut cursor = KhugepagedCursor::default();
+    loop {
+        if thp_supported() && thp_mode() != ThpMode::Never {
+            khugepaged_do_scan(&mut cursor);
+        }
+        let sleep_ms = KHUGEPAGED_SCAN_SLEEP_MS.load(Ordering::Relaxed);
+        let _ = nanosleep(PosixTimeSpec::new(
+            (sleep_ms / 1000) as i64,
+            ((sleep_ms % 1000) * 1_000_000) as i64,
+        ));
+    }
+}
+
+/// khugepaged线程初始化函数
+#[unified_init(INITCALL_CORE)]
+fn khugepaged_init() -> Result<(), SystemError> {
+    let closure = crate::process::kthread::KernelThreadClosure::StaticEmptyClosure((
+        &(khugepaged_thread as fn() -> i32),
+        (),
+    ));
+    crate::process::kthread::KernelThreadMechanism::create_and_run(
+        closure,
+        "khugepaged".to_string(),
+    )
+    .ok_or(SystemError::ENOMEM)?;
+    Ok(())
+}
+
+/// 创建`/sys/kernel/mm/transparent_hugepage`
+///
+/// ## 参数
+///
+/// - `mm_kobj`: `/sys/kernel/mm`的kobject
+pub(super) fn thp_sysfs_init(mm_kobj: &Arc<CommonKobj>) -> Result<(), SystemError> {
+    let thp_kobj = CommonKobj::new("transparent_hugepage".to_string());
+    thp_kobj.set_parent(Some(Arc::downgrade(&(mm_kobj.clone() as Arc<dyn KObject>))));
+    KObjectManager::init_and_add_kobj(thp_kobj.clone(), Some(&DynamicKObjKType))?;
+
+    sysfs_instance()
+        .create_groups(
+            &(thp_kobj as Arc<dyn KObject>),
+            &[&ThpAttrGroup, &KhugepagedAttrGroup],
+        )
+        .map_err(|e| {
+            log::warn!(
+                "Failed to create transparent_hugepage sysfs groups: {:?}",
+                e
+            );
+            SystemError::ENOMEM
+        })
+}
+
+/// 解析写入sysfs属性的字符串
+fn parse_sysfs_str(buf: &[u8]) -> Result<&str, SystemError> {
+    core::str::from_utf8(buf)
+        .map(|s| s.trim_matches(|c: char| c == '\0' || c.is_whitespace()))
+        .map_err(|_| SystemError::EINVAL)
+}
+
+/// 解析写入sysfs属性的整数
+fn parse_sysfs_usize(buf: &[u8]) -> Result<usize, SystemError> {
+    parse_sysfs_str(buf)?
+        .parse::<usize>()
+        .map_err(|_| SystemError::EINVAL)
+}
+
+#[derive(Debug)]
+struct ThpAttrGroup;
+
+impl AttributeGroup for ThpAttrGroup {
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    fn attrs(&self) -> &[&'static dyn Attribute] {
+        &[&AttrThpEnabled, &AttrHpagePmdSize]
+    }
+
+    fn is_visible(
+        &self,
+        _kobj: Arc<dyn KObject>,
+        attr: &'static dyn Attribute,
+    ) -> Option<InodeMode> {
+        Some(attr.mode())
+    }
+}
+
+#[derive(Debug)]
+struct KhugepagedAttrGroup;
+
+impl AttributeGroup for KhugepagedAttrGroup {
+    fn name(&self) -> Option<&str> {
+        Some("khugepaged")
+    }
+
+    fn attrs(&self) -> &[&'static dyn Attribute] {
+        &[
+            &AttrPagesToScan,
+            &AttrScanSleepMillisecs,
+            &AttrMaxPtesNone,
+            &AttrFullScans,
+            &AttrPagesCollapsed,
+        ]
+    }
+
+    fn is_visible(
+        &self,
+        _kobj: Arc<dyn KObject>,
+        attr: &'static dyn Attribute,
+    ) -> Option<InodeMode> {
+        Some(attr.mode())
+    }
+}
+
+/// `enabled`：读取时用方括号标出当前模式，例如`[always] madvise never`
+#[derive(Debug)]
+struct AttrThpEnabled;
+
+impl Attribute for AttrThpEnabled {
+    fn name(&self) -> &str {
+        "enabled"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RW
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW | SysFSOpsSupport::ATTR_STORE
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let current = thp_mode();
+        let modes: Vec<_> = ThpMode::ALL
+            .iter()
+            .map(|m| {
+                if *m == current {
+                    format!("[{}]", m.name())
+                } else {
+                    m.name().to_string()
+                }
+            })
+            .collect();
+        sysfs_emit_str(buf, &format!("{}\n", modes.join(" ")))
+    }
+
+    fn store(&self, _kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let s = parse_sysfs_str(buf)?;
+        let mode = ThpMode::ALL
+            .iter()
+            .find(|m| m.name() == s)
+            .ok_or(SystemError::EINVAL)?;
+        THP_MODE.store(*mode as u8, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+}
+
+#[derive(Debug)]
+struct AttrHpagePmdSize;
+
+impl Attribute for AttrHpagePmdSize {
+    fn name(&self) -> &str {
+        "hpage_pmd_size"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        sysfs_emit_str(buf, &format!("{}\n", HPAGE_PMD_SIZE))
+    }
+}
+
+#[derive(Debug)]
+struct AttrPagesToScan;
+
+impl Attribute for AttrPagesToScan {
+    fn name(&self) -> &str {
+        "pages_to_scan"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RW
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW | SysFSOpsSupport::ATTR_STORE
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let v = KHUGEPAGED_PAGES_TO_SCAN.load(Ordering::Relaxed);
+        sysfs_emit_str(buf, &format!("{v}\n"))
+    }
+
+    fn store(&self, _kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let v = parse_sysfs_usize(buf)?;
+        if v == 0 {
+            return Err(SystemError::EINVAL);
+        }
+        KHUGEPAGED_PAGES_TO_SCAN.store(v, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+}
+
+#[derive(Debug)]
+struct AttrScanSleepMillisecs;
+
+impl Attribute for AttrScanSleepMillisecs {
+    fn name(&self) -> &str {
+        "scan_sleep_millisecs"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RW
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW | SysFSOpsSupport::ATTR_STORE
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let v = KHUGEPAGED_SCAN_SLEEP_MS.load(Ordering::Relaxed);
+        sysfs_emit_str(buf, &format!("{v}\n"))
+    }
+
+    fn store(&self, _kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let v = parse_sysfs_usize(buf)?;
+        KHUGEPAGED_SCAN_SLEEP_MS.store(v, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+}
+
+#[derive(Debug)]
+struct AttrMaxPtesNone;
+
+impl Attribute for AttrMaxPtesNone {
+    fn name(&self) -> &str {
+        "max_ptes_none"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RW
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW | SysFSOpsSupport::ATTR_STORE
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let v = KHUGEPAGED_MAX_PTES_NONE.load(Ordering::Relaxed);
+        sysfs_emit_str(buf, &format!("{v}\n"))
+    }
+
+    fn store(&self, _kobj: Arc<dyn KObject>, buf: &[u8]) -> Result<usize, SystemError> {
+        let v = parse_sysfs_usize(buf)?;
+        if v >= HPAGE_PMD_NR {
+            return Err(SystemError::EINVAL);
+        }
+        KHUGEPAGED_MAX_PTES_NONE.store(v, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+}
+
+#[derive(Debug)]
+struct AttrFullScans;
+
+impl Attribute for AttrFullScans {
+    fn name(&self) -> &str {
+        "full_scans"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let v = KHUGEPAGED_FULL_SCANS.load(Ordering::Relaxed);
+        sysfs_emit_str(buf, &format!("{v}\n"))
+    }
+}
+
+#[derive(Debug)]
+struct AttrPagesCollapsed;
+
+impl Attribute for AttrPagesCollapsed {
+    fn name(&self) -> &str {
+        "pages_collapsed"
+    }
+
+    fn mode(&self) -> InodeMode {
+        SYSFS_ATTR_MODE_RO
+    }
+
+    fn support(&self) -> SysFSOpsSupport {
+        SysFSOpsSupport::ATTR_SHOW
+    }
+
+    fn show(&self, _kobj: Arc<dyn KObject>, buf: &mut [u8]) -> Result<usize, SystemError> {
+        let v = KHUGEPAGED_PAGES_COLLAPSED.load(Ordering::Relaxed);
+        sysfs_emit_str(buf, &format!("{v}\n"))
+    }
+}
@@ -105,7 +105,12 @@ impl LockedVMA {
 
             MadvFlags::MADV_MERGEABLE | MadvFlags::MADV_UNMERGEABLE => {}
 
-            MadvFlags::MADV_HUGEPAGE | MadvFlags::MADV_NOHUGEPAGE => {}
+            MadvFlags::MADV_HUGEPAGE => {
+                new_flags = (new_flags & !VmFlags::VM_NOHUGEPAGE) | VmFlags::VM_HUGEPAGE
+            }
+            MadvFlags::MADV_NOHUGEPAGE => {
+                new_flags = (new_flags & !VmFlags::VM_HUGEPAGE) | VmFlags::VM_NOHUGEPAGE
+            }
 
             MadvFlags::MADV_COLLAPSE => {}
             _ => {}
@@ -0,0 +1,250 @@
+//! 启动时的内存测试
+//!
+//! 在内核命令行中指定`memtest=N`后，会在buddy分配器接管物理内存之前，
+//! 对每个空闲的物理页帧依次写入N种测试模式并读回校验。出错的页帧会被记录下来，
+//! 并在memblock中标记为保留区域，因此buddy分配器永远不会把它们分配出去。
+//!
+//! 测试前会保存页帧原有的内容，测试完成后恢复，不会破坏引导阶段留在空闲内存中的数据。
+//! 测试结果会输出到内核日志，并可以通过`/proc/memtest`查看。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/mm/memtest.c
+
+use alloc::{format, string::String};
+use log::{info, warn};
+
+use crate::{
+    arch::MMArch,
+    init::cmdline::KernelCmdlineEarlyKV,
+    libs::{align::page_align_up, spinlock::SpinLock},
+    mm::{memblock::mem_block_manager, MemoryManagementArch, PhysAddr, PhysMemoryArea},
+};
+
+use super::memblock::INITIAL_MEMORY_REGIONS_NUM;
+
+kernel_cmdline_param_early_kv!(MEMTEST_PARAM, memtest, "");
+
+/// 测试模式。最后一轮总是使用第一个模式
+const PATTERNS: [u64; 17] = [
+    0,
+    0xffffffffffffffff,
+    0x5555555555555555,
+    0xaaaaaaaaaaaaaaaa,
+    0x1111111111111111,
+    0x2222222222222222,
+    0x4444444444444444,
+    0x8888888888888888,
+    0x3333333333333333,
+    0x6666666666666666,
+    0x9999999999999999,
+    0xcccccccccccccccc,
+    0x7777777777777777,
+    0xbbbbbbbbbbbbbbbb,
+    0xdddddddddddddddd,
+    0xeeeeeeeeeeeeeeee,
+    0x7a6c7258554e494c,
+];
+
+/// 最多记录的坏内存区域数量，超出的部分仍然会被保留，但不会出现在结果中
+const MAX_BAD_RANGES: usize = 64;
+
+const WORDS_PER_PAGE: usize = MMArch::PAGE_SIZE / core::mem::size_of::<u64>();
+
+/// 测试时用来保存页帧原有内容的缓冲区，位于内核的bss段中，不会被测试到
+static mut SAVED_PAGE: [u64; WORDS_PER_PAGE] = [0; WORDS_PER_PAGE];
+
+static MEMTEST_RESULT: SpinLock<MemtestResult> = SpinLock::new(MemtestResult::new());
+
+/// 一段连续的坏内存
+#[derive(Debug, Clone, Copy)]
+struct BadMemRange {
+    base: PhysAddr,
+    size: usize,
+}
+
+#[derive(Debug)]
+struct MemtestResult {
+    /// 实际执行的测试轮数，为0表示没有进行测试
+    passes: usize,
+    /// 测试过的内存大小（字节）
+    tested: usize,
+    /// 坏内存的总大小（字节）
+    bad_bytes: usize,
+    ranges: [BadMemRange; MAX_BAD_RANGES],
+    nr_ranges: usize,
+    /// 坏内存区域的数量超出了`MAX_BAD_RANGES`
+    overflow: bool,
+}
+
+impl MemtestResult {
+    const fn new() -> Self {
+        Self {
+            passes: 0,
+            tested: 0,
+            bad_bytes: 0,
+            ranges: [BadMemRange {
+                base: PhysAddr::new(0),
+                size: 0,
+            }; MAX_BAD_RANGES],
+            nr_ranges: 0,
+            overflow: false,
+        }
+    }
+
+    /// 记录一个坏页帧，和上一个坏区域相邻时合并
+    ///
+    /// ## 返回值
+    /// - `false`: 记录的区域已满，调用者需要自己保留这个页帧
+    fn add_bad_page(&mut self, paddr: PhysAddr) -> bool {
+        self.bad_bytes += MMArch::PAGE_SIZE;
+        if self.nr_ranges > 0 {
+            let last = &mut self.ranges[self.nr_ranges - 1];
+            if last.base + last.size == paddr {
+                last.size += MMArch::PAGE_SIZE;
+                return true;
+            }
+        }
+        if self.nr_ranges == MAX_BAD_RANGES {
+            self.overflow = true;
+            return false;
+        }
+        self.ranges[self.nr_ranges] = BadMemRange {
+            base: paddr,
+            size: MMArch::PAGE_SIZE,
+        };
+        self.nr_ranges += 1;
+        true
+    }
+}
+
+/// 获取命令行指定的测试轮数，为0表示不进行测试
+pub fn memtest_passes() -> usize {
+    MEMTEST_PARAM
+        .value_str()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(0)
+}
+
+/// 对一个页帧执行所有轮次的测试
+///
+/// ## 返回值
+/// - `true`: 页帧中有读回的值与写入的不一致
+unsafe fn test_one_page(paddr: PhysAddr, passes: usize) -> bool {
+    let page = MMArch::phys_2_virt(paddr).unwrap().data() as *mut u64;
+    let saved = core::ptr::addr_of_mut!(SAVED_PAGE) as *mut u64;
+    core::ptr::copy_nonoverlapping(page, saved, WORDS_PER_PAGE);
+
+    let mut bad = false;
+    for i in (0..passes).rev() {
+        let pattern = PATTERNS[i % PATTERNS.len()];
+        for w in 0..WORDS_PER_PAGE {
+            core::ptr::write_volatile(page.add(w), pattern);
+        }
+        for w in 0..WORDS_PER_PAGE {
+            if core::ptr::read_volatile(page.add(w)) != pattern {
+                bad = true;
+            }
+        }
+    }
+
+    core::ptr::copy_nonoverlapping(saved, page, WORDS_PER_PAGE);
+    bad
+}
+
+/// 启动时的内存测试
+///
+/// 测试所有可用的物理内存中，不低于`start`的部分，并把出错的页帧标记为保留区域
+///
+/// ## 参数
+/// - `start`: 早期分配器已经分配到的位置，更低的物理内存已经被使用，不会被测试
+///
+/// ## Safety
+/// 必须在buddy分配器初始化之前调用，并且当前页表中已经建立了所有物理内存的线性映射
+pub unsafe fn early_memtest(start: PhysAddr) {
+    let passes = memtest_passes();
+    if passes == 0 {
+        return;
+    }
+
+    // 先把要测试的区域复制出来，遍历memblock时会持有它的锁
+    let mut areas = [PhysMemoryArea::DEFAULT; INITIAL_MEMORY_REGIONS_NUM];
+    let mut nr_areas = 0;
+    for area in mem_block_manager().to_iter_available() {
+        areas[nr_areas] = area;
+        nr_areas += 1;
+    }
+
+    info!("memtest: {passes} pass(es), starting at {:?}", start);
+    let mut result = MEMTEST_RESULT.lock();
+    result.passes = passes;
+    for area in &areas[..nr_areas] {
+        let begin = core::cmp::max(
+            area.area_base_aligned(),
+            PhysAddr::new(page_align_up(start.data())),
+        );
+        let end = area.area_end_aligned();
+        if begin >= end {
+            continue;
+        }
+        info!("memtest: testing {:?} - {:?}", begin, end);
+        let mut paddr = begin;
+        while paddr < end {
+            if test_one_page(paddr, passes) {
+                warn!("memtest: bad page frame at {:?}", paddr);
+                if !result.add_bad_page(paddr) {
+                    reserve_bad_memory(paddr, MMArch::PAGE_SIZE);
+                }
+            }
+            result.tested += MMArch::PAGE_SIZE;
+            paddr += MMArch::PAGE_SIZE;
+        }
+    }
+
+    for range in &result.ranges[..result.nr_ranges] {
+        reserve_bad_memory(range.base, range.size);
+    }
+    if result.overflow {
+        warn!(
+            "memtest: more than {} bad memory ranges, the rest are not listed",
+            MAX_BAD_RANGES
+        );
+    }
+    info!(
+        "memtest: tested {} KB, {} KB bad",
+        result.tested / 1024,
+        result.bad_bytes / 1024
+    );
+}
+
+/// 把坏内存标记为memblock中的保留区域，buddy分配器不会接管它们
+fn reserve_bad_memory(base: PhysAddr, size: usize) {
+    warn!(
+        "memtest: reserving bad memory {:?} - {:?}",
+        base,
+        base + size
+    );
+    if let Err(e) = mem_block_manager().reserve_block(base, size) {
+        warn!("memtest: failed to reserve bad memory: {:?}", e);
+    }
+}
+
+/// 生成`/proc/memtest`的内容
+pub fn memtest_report() -> String {
+    let result = MEMTEST_RESULT.lock();
+    let mut s = format!(
+        "Passes:   {}\nTested:   {} kB\nBad:      {} kB\n",
+        result.passes,
+        result.tested / 1024,
+        result.bad_bytes / 1024
+    );
+    for range in &result.ranges[..result.nr_ranges] {
+        s.push_str(&format!(
+            "{:#018x}-{:#018x}\n",
+            range.base.data(),
+            range.base.data() + range.size
+        ));
+    }
+    if result.overflow {
+        s.push_str("(truncated)\n");
+    }
+    s
+}
@@ -14,32 +14,54 @@ use core::{
 };
 
 use self::{
-    allocator::page_frame::{VirtPageFrame, VirtPageFrameIter},
+    allocator::page_frame::{
+        deallocate_page_frames, PageFrameCount, PhysPageFrame, VirtPageFrame, VirtPageFrameIter,
+    },
     memblock::MemoryAreaAttr,
     page::round_up_to_page_size,
     ucontext::{AddressSpace, LockedVMA, UserMapper},
 };
 
+#[cfg(feature = "alloc_profiling")]
+pub mod alloc_tag;
 pub mod allocator;
 pub mod dma;
 pub mod early_ioremap;
 pub mod fault;
+pub mod gup;
+pub mod huge_memory;
 pub mod ident_map;
 pub mod init;
 pub mod kernel_mapper;
 pub mod madvise;
 pub mod memblock;
+pub mod memtest;
 pub mod mincore;
 pub mod mmio_buddy;
 pub mod no_init;
 pub mod page;
 pub mod page_cache_stats;
+pub mod pagemap;
 pub mod percpu;
+pub mod ptdump;
 pub mod readahead;
 pub mod syscall;
 pub mod sysfs;
 pub mod truncate;
 pub mod ucontext;
+pub mod vma_tree;
+pub mod writeback;
+
+/// 把表达式中发生的堆分配记到当前位置的分配点上
+///
+/// 没有开启`alloc_profiling`特性，直接求值表达式，参见[`alloc_tag`](crate::mm::alloc_tag)。
+#[cfg(not(feature = "alloc_profiling"))]
+#[macro_export]
+macro_rules! alloc_hooks {
+    ($e:expr) => {
+        $e
+    };
+}
 
 /// 内核INIT进程的用户地址空间结构体（仅在process_init中初始化）
 static mut __IDLE_PROCESS_ADDRESS_SPACE: Option<Arc<AddressSpace>> = None;
@@ -81,6 +103,14 @@ bitflags! {
         const VM_ARCH_1 = 0x01000000;
         const VM_WIPEONFORK = 0x02000000;
         const VM_DONTDUMP = 0x04000000;
+        /// 上次清除soft-dirty之后新建的VMA，其中所有的页面在pagemap中都是soft-dirty的
+        const VM_SOFTDIRTY = 0x08000000;
+
+        const VM_HUGEPAGE = 0x20000000;
+        const VM_NOHUGEPAGE = 0x40000000;
+
+        /// 用户态影子栈（x86 CET），页表项为 W=0、D=1
+        const VM_SHADOW_STACK = 1 << 37;
     }
 
     /// 描述页面错误处理过程中发生的不同情况或结果
@@ -486,6 +516,8 @@ pub trait MemoryManagementArch: Clone + Copy + Debug {
     const ENTRY_FLAG_HUGE_PAGE: usize;
     /// 当该位为1时，代表该页表项是全局的
     const ENTRY_FLAG_GLOBAL: usize;
+    /// 软件脏位（soft-dirty），由软件清除，页面被写入后置位。为0表示该架构不支持
+    const ENTRY_FLAG_SOFT_DIRTY: usize;
 
     /// 虚拟地址与物理地址的偏移量
     const PHYS_OFFSET: usize;
@@ -555,6 +587,30 @@ pub trait MemoryManagementArch: Clone + Copy + Debug {
     /// 内存管理初始化完成后，调用该函数
     unsafe fn arch_post_init() {}
 
+    /// 内核初始化（initcall）完成后调用，释放只在启动阶段使用的代码
+    unsafe fn free_initmem() {}
+
+    /// 内核初始化（initcall）完成后调用，把内核代码和只读数据设置为只读，其他内核映射设置为不可执行
+    unsafe fn mark_rodata_ro() {}
+
+    /// # 修改内核代码
+    ///
+    /// 用于kprobe等合法的代码修改。内核代码只读时，架构需要通过临时的可写别名映射完成写入
+    ///
+    /// ## 参数
+    /// - `addr`: 被修改的代码的地址
+    /// - `data`: 写入的内容，长度不能超过一页
+    unsafe fn text_poke(addr: VirtAddr, data: &[u8]) -> Result<(), SystemError> {
+        ptr::copy(data.as_ptr(), addr.data() as *mut u8, data.len());
+        Ok(())
+    }
+
+    /// 在当前CPU上临时允许写入只读的内核代码，执行`f`。
+    /// 仅用于在原地修改代码的第三方库（例如static keys）
+    fn with_kernel_text_writable<R>(f: impl FnOnce() -> R) -> R {
+        f()
+    }
+
     /// @brief 读取指定虚拟地址的值，并假设它是类型T的指针
     #[inline(always)]
     unsafe fn read<T: Sized>(address: VirtAddr) -> T {
@@ -625,6 +681,19 @@ pub trait MemoryManagementArch: Clone + Copy + Debug {
     /// 初始化新的usermapper，为用户进程创建页表
     fn setup_new_usermapper() -> Result<UserMapper, SystemError>;
 
+    /// 释放由[`Self::setup_new_usermapper`]创建的顶级页表
+    ///
+    /// 调用前，用户空间的下级页表应当已经被全部释放
+    unsafe fn free_usermapper_table(table: PhysAddr) {
+        deallocate_page_frames(PhysPageFrame::new(table), PageFrameCount::new(1));
+    }
+
+    /// 顶级页表的第`index`项被修改之后调用
+    ///
+    /// 架构可以在这里同步影子页表，例如x86_64开启KPTI时，需要把用户空间的顶级页表项复制到用户态页表中
+    #[inline(always)]
+    unsafe fn top_level_entry_updated(_table: PhysAddr, _index: usize, _entry: usize) {}
+
     /// 创建页表项
     ///
     /// 这是一个低阶api，用于根据物理地址以及指定好的EntryFlags，创建页表项
@@ -698,6 +767,14 @@ pub trait MemoryManagementArch: Clone + Copy + Debug {
             if crate::arch::mm::X86_64MMArch::is_xd_reserved() {
                 ret = ret.set_execute(true);
             }
+            // 影子栈页面在CET下以“只读且脏”的页表项表示，普通写入会触发缺页
+            if vm_flags.contains(VmFlags::VM_SHADOW_STACK) {
+                ret = ret.set_write(false).set_dirty(true);
+            }
+        }
+        // 新建立的可写映射视为已被写入，clear_refs之后才开始跟踪
+        if vm_flags.contains(VmFlags::VM_WRITE) {
+            ret = ret.set_soft_dirty(true);
         }
         ret
     }
@@ -16,10 +16,10 @@ use lru::LruCache;
 
 use crate::{
     arch::{interrupt::ipi::send_ipi, mm::LockedFrameAllocator, MMArch},
-    exception::ipi::{IpiKind, IpiTarget},
+    exception::ipi::{flush_tlb_other_cpus, IpiKind, IpiTarget},
     filesystem::{
         page_cache::{list_page_caches, PageCache},
-        vfs::FilePrivateData,
+        vfs::dcache::{dcache, DCACHE_CAPACITY},
     },
     init::initcall::INITCALL_CORE,
     libs::{
@@ -28,6 +28,10 @@ use crate::{
     },
     mm::page_cache_stats as pc_stats,
     process::{ProcessControlBlock, ProcessManager},
+    sched::{
+        delayacct::{delayacct_freepages_end, delayacct_freepages_start},
+        psi::{psi_memstall_enter, psi_memstall_leave},
+    },
     time::{sleep::nanosleep, PosixTimeSpec},
 };
 
@@ -264,12 +268,15 @@ fn page_reclaim_thread() -> i32 {
             let page_to_free = 4096;
             // 分离选择和回收阶段，避免长时间持有页面回收器锁导致与
             // page_manager/page_cache 的锁顺序反转。
+            let memstall = psi_memstall_enter();
+            delayacct_freepages_start();
+            // 目录项缓存持有inode，inode又持有页缓存，先释放一部分目录项
+            dcache().shrink(DCACHE_CAPACITY / 2);
             PageReclaimer::shrink_list(PageFrameCount::new(page_to_free));
+            delayacct_freepages_end();
+            psi_memstall_leave(memstall);
         } else {
-            //TODO 暂时让页面回收线程负责脏页回写任务，后续需要分离
-            page_reclaimer_lock().flush_dirty_pages();
-            // 休眠5秒
-            // log::info!("sleep");
+            // 脏页由writeback线程写回，参见mm::writeback
             let _ = nanosleep(PosixTimeSpec::new(0, 500_000_000));
         }
     }
@@ -472,12 +479,7 @@ impl PageReclaimer {
             let r = if let Some(backend) = backend {
                 backend.write_page(page_index, data)
             } else {
-                inode.write_direct(
-                    page_start,
-                    len,
-                    data,
-                    Mutex::new(FilePrivateData::Unused).lock(),
-                )
+                inode.write_direct(page_start, len, data)
             };
             if let Err(e) = r {
                 log::error!(
@@ -615,6 +617,55 @@ impl Page {
     pub fn write(&self) -> RwSemWriteGuard<'_, InnerPage> {
         self.inner.write()
     }
+
+    /// # 写保护共享文件映射中映射到当前页面的页表项
+    ///
+    /// 在回写文件页之前调用。之后通过映射写入页面时会触发写保护异常，
+    /// 由`do_wp_page`重新把页面标记为脏页，回写之后写入的数据因此不会丢失。
+    ///
+    /// 调用者不能持有当前页面的锁，也不能持有任何地址空间的锁
+    pub fn mkclean(&self) {
+        let (page_index, vmas) = {
+            let guard = self.read();
+            let PageType::File(info) = guard.page_type() else {
+                return;
+            };
+            (
+                info.index,
+                guard.vma_set().iter().cloned().collect::<Vec<_>>(),
+            )
+        };
+
+        for vma in vmas {
+            let (address_space, virt) = {
+                let vma_guard = vma.lock();
+                if !vma_guard.vm_flags().contains(super::VmFlags::VM_SHARED) {
+                    continue;
+                }
+                let Some(address_space) = vma_guard.address_space().and_then(|x| x.upgrade())
+                else {
+                    continue;
+                };
+                let Ok(virt) = vma_guard.page_address(page_index) else {
+                    continue;
+                };
+                (address_space, virt)
+            };
+
+            let mut guard = address_space.write();
+            let mapper = &mut guard.user_mapper.utable;
+            let Some((paddr, flags)) = mapper.translate(virt) else {
+                continue;
+            };
+            if paddr != self.phys_addr || !flags.has_write() {
+                continue;
+            }
+            if let Some(flush) = unsafe { mapper.remap(virt, flags.set_write(false)) } {
+                let mut flusher = SyncFlusher::new();
+                flusher.consume(flush);
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -889,6 +940,9 @@ impl<Arch: MemoryManagementArch> PageTable<Arch> {
     pub unsafe fn set_entry(&self, i: usize, entry: PageEntry<Arch>) -> Option<()> {
         let entry_virt = self.entry_virt(i)?;
         Arch::write::<usize>(entry_virt, entry.data());
+        if self.level == Arch::PAGE_LEVELS - 1 {
+            Arch::top_level_entry_updated(self.phys, i, entry.data());
+        }
         return Some(());
     }
 
@@ -929,10 +983,15 @@ impl<Arch: MemoryManagementArch> PageTable<Arch> {
     }
 
     /// 获取第i个页表项指向的下一级页表
+    ///
+    /// 页表项映射的是大页时，没有下一级页表，返回None
     pub unsafe fn next_level_table(&self, index: usize) -> Option<Self> {
         if self.level == 0 {
             return None;
         }
+        if self.entry(index)?.huge() {
+            return None;
+        }
 
         // 返回下一级页表
         return Some(PageTable::new(
@@ -962,11 +1021,11 @@ impl<Arch: MemoryManagementArch> PageTable<Arch> {
                 if let Some(mut entry) = self.entry(i) {
                     if entry.present() {
                         if copy_on_write {
-                            let mut new_flags = entry.flags().set_write(false);
+                            // 父子进程都要清除脏位：开启CET后，W=0、D=1的页表项会被当作影子栈页面，
+                            // 影子栈写入将不再触发写时复制
+                            let new_flags = entry.flags().set_write(false).set_dirty(false);
                             entry.set_flags(new_flags);
                             self.set_entry(i, entry);
-                            new_flags = new_flags.set_dirty(false);
-                            entry.set_flags(new_flags);
                             new_table.set_entry(i, entry);
                         } else {
                             let phys = allocator.allocate_one()?;
@@ -981,6 +1040,15 @@ impl<Arch: MemoryManagementArch> PageTable<Arch> {
         } else {
             // 非一级页表拷贝时，对每个页表项对应的页表都进行拷贝
             for i in 0..MMArch::PAGE_ENTRY_NUM {
+                let mut entry = self.entry(i)?;
+                if entry.huge() {
+                    // 大页总是以写时复制的方式共享，写入时由缺页处理拆分
+                    let new_flags = entry.flags().set_write(false).set_dirty(false);
+                    entry.set_flags(new_flags);
+                    self.set_entry(i, entry);
+                    new_table.set_entry(i, entry);
+                    continue;
+                }
                 if let Some(next_table) = self.next_level_table(i) {
                     let table = next_table.clone(allocator, copy_on_write)?;
                     let old_entry = self.entry(i).unwrap();
@@ -1091,6 +1159,14 @@ impl<Arch: MemoryManagementArch> PageEntry<Arch> {
     pub fn write(&self) -> bool {
         return self.data & Arch::ENTRY_FLAG_READWRITE != 0;
     }
+
+    /// 判断当前页表项是否映射了大页（只对非最后一级页表的页表项有意义）
+    #[inline(always)]
+    pub fn huge(&self) -> bool {
+        Arch::ENTRY_FLAG_HUGE_PAGE != 0
+            && self.present()
+            && self.data & Arch::ENTRY_FLAG_HUGE_PAGE == Arch::ENTRY_FLAG_HUGE_PAGE
+    }
 }
 
 /// 页表项的标志位
@@ -1388,6 +1464,22 @@ impl<Arch: MemoryManagementArch> EntryFlags<Arch> {
         return self.update_flags(Arch::ENTRY_FLAG_ACCESSED, value);
     }
 
+    /// 设置当前页表项的soft-dirty位
+    ///
+    /// ## 参数
+    ///
+    /// - value: 如果为true，那么标记页面在上次清除soft-dirty之后被写入过
+    #[inline(always)]
+    pub fn set_soft_dirty(self, value: bool) -> Self {
+        return self.update_flags(Arch::ENTRY_FLAG_SOFT_DIRTY, value);
+    }
+
+    /// 页面在上次清除soft-dirty之后是否被写入过
+    #[inline(always)]
+    pub fn has_soft_dirty(&self) -> bool {
+        return Arch::ENTRY_FLAG_SOFT_DIRTY != 0 && self.has_flag(Arch::ENTRY_FLAG_SOFT_DIRTY);
+    }
+
     /// 设置指向的页是否为大页
     ///
     /// ## 参数
@@ -1575,6 +1667,9 @@ impl<Arch: MemoryManagementArch, F: FrameAllocator> PageMapper<Arch, F> {
                 compiler_fence(Ordering::SeqCst);
                 return Some(PageFlush::new(virt));
             } else {
+                if table.entry(i)?.huge() {
+                    split_huge_entry(&table, i, &mut self.frame_allocator)?;
+                }
                 let next_table = table.next_level_table(i);
                 if let Some(next_table) = next_table {
                     table = next_table;
@@ -1599,52 +1694,130 @@ impl<Arch: MemoryManagementArch, F: FrameAllocator> PageMapper<Arch, F> {
         }
     }
 
-    /// 进行大页映射
-    pub unsafe fn map_huge_page(
+    /// 把2M的物理页映射到指定的虚拟地址，在PMD中填写大页页表项
+    ///
+    /// ## 参数
+    ///
+    /// - `virt`: 虚拟地址，按2M对齐
+    /// - `phys`: 物理地址，按2M对齐
+    /// - `flags`: 页表项的flags
+    ///
+    /// ## 返回值
+    ///
+    /// 如果映射成功，返回页表项刷新器。虚拟地址所在的PMD已经被使用时返回None
+    pub unsafe fn map_huge_phys(
         &mut self,
         virt: VirtAddr,
+        phys: PhysAddr,
         flags: EntryFlags<Arch>,
     ) -> Option<PageFlush<Arch>> {
-        // 验证虚拟地址是否对齐
-        if !(virt.check_aligned(Arch::PAGE_SIZE)) {
-            error!("Try to map unaligned page: virt={:?}", virt);
+        let huge_size = Arch::PAGE_SIZE << Arch::PAGE_ENTRY_SHIFT;
+        if !(virt.check_aligned(huge_size) && phys.check_aligned(huge_size)) {
+            error!(
+                "Try to map unaligned huge page: virt={:?}, phys={:?}",
+                virt, phys
+            );
             return None;
         }
 
         let virt = VirtAddr::new(virt.data() & (!Arch::PAGE_NEGATIVE_MASK));
-
         let mut table = self.table();
         loop {
             let i = table.index_of(virt)?;
-            assert!(i < Arch::PAGE_ENTRY_NUM);
-            let next_table = table.next_level_table(i);
-            if let Some(next_table) = next_table {
+            if table.level() == 1 {
+                if table.entry_mapped(i)? {
+                    return None;
+                }
+                compiler_fence(Ordering::SeqCst);
+                table.set_entry(i, PageEntry::new(phys, flags.set_huge_page(true)));
+                compiler_fence(Ordering::SeqCst);
+                return Some(PageFlush::new(virt));
+            }
+
+            if let Some(next_table) = table.next_level_table(i) {
                 table = next_table;
-            } else {
-                break;
+                continue;
             }
+            if table.entry_mapped(i)? {
+                // 上一级已经是大页映射
+                return None;
+            }
+            let frame = self.frame_allocator.allocate_one()?;
+            MMArch::write_bytes(MMArch::phys_2_virt(frame).unwrap(), 0, MMArch::PAGE_SIZE);
+            let flags: EntryFlags<Arch> =
+                EntryFlags::new_page_table(virt.kind() == PageTableKind::User);
+            table.set_entry(i, PageEntry::new(frame, flags));
+            table = table.next_level_table(i)?;
         }
+    }
 
-        // 支持2M、1G大页，即页表层级为1、2级的页表可以映射大页
-        if table.level == 0 || table.level > 2 {
-            return None;
-        }
+    /// 取消虚拟地址所在的2M大页的映射，不释放物理页
+    ///
+    /// ## 返回值
+    ///
+    /// 如果虚拟地址由大页映射，返回大页的物理地址、页表项的flags和刷新器，否则返回None
+    pub unsafe fn unmap_huge_phys(
+        &mut self,
+        virt: VirtAddr,
+    ) -> Option<(PhysAddr, EntryFlags<Arch>, PageFlush<Arch>)> {
+        let (table, i) = self.huge_pmd(virt)?;
+        let entry = table.entry(i)?;
+        table.set_entry(i, PageEntry::from_usize(0));
+        Some((
+            entry.address().ok()?,
+            entry.flags(),
+            PageFlush::new(table.entry_base(i)?),
+        ))
+    }
+
+    /// 修改虚拟地址所在的2M大页的页表项的flags
+    ///
+    /// ## 返回值
+    ///
+    /// 如果虚拟地址由大页映射，返回刷新器，否则返回None
+    pub unsafe fn remap_huge(
+        &mut self,
+        virt: VirtAddr,
+        flags: EntryFlags<Arch>,
+    ) -> Option<PageFlush<Arch>> {
+        let (table, i) = self.huge_pmd(virt)?;
+        let mut entry = table.entry(i)?;
+        entry.set_flags(flags.set_huge_page(true));
+        table.set_entry(i, entry);
+        Some(PageFlush::new(table.entry_base(i)?))
+    }
 
-        let (phys, count) = self.frame_allocator.allocate(PageFrameCount::new(
-            Arch::PAGE_ENTRY_NUM.pow(table.level as u32),
-        ))?;
+    /// 获取映射了虚拟地址的2M大页页表项
+    ///
+    /// ## 返回值
+    ///
+    /// 如果虚拟地址由大页映射，返回PMD中的页表项，否则返回None
+    pub fn get_huge_entry(&self, virt: VirtAddr) -> Option<PageEntry<Arch>> {
+        let (table, i) = self.huge_pmd(virt)?;
+        unsafe { table.entry(i) }
+    }
 
-        MMArch::write_bytes(
-            MMArch::phys_2_virt(phys).unwrap(),
-            0,
-            MMArch::PAGE_SIZE * count.data(),
-        );
+    /// 把映射了虚拟地址的2M大页拆分为512个4K页表项，物理页和权限保持不变
+    ///
+    /// ## 返回值
+    ///
+    /// 如果拆分成功，返回刷新器。虚拟地址没有由大页映射或者分配页表失败时返回None
+    pub unsafe fn split_huge_page(&mut self, virt: VirtAddr) -> Option<PageFlush<Arch>> {
+        let (table, i) = self.huge_pmd(virt)?;
+        split_huge_entry(&table, i, &mut self.frame_allocator)?;
+        Some(PageFlush::new(table.entry_base(i)?))
+    }
 
-        table.set_entry(
-            table.index_of(virt)?,
-            PageEntry::new(phys, flags.set_huge_page(true)),
-        )?;
-        Some(PageFlush::new(virt))
+    /// 查找映射了虚拟地址的大页所在的PMD，返回PMD和页表项的下标
+    fn huge_pmd(&self, virt: VirtAddr) -> Option<(PageTable<Arch>, usize)> {
+        let virt = VirtAddr::new(virt.data() & (!Arch::PAGE_NEGATIVE_MASK));
+        let table = self.get_table(virt, 1)?;
+        let i = table.index_of(virt)?;
+        if unsafe { table.entry(i)? }.huge() {
+            Some((table, i))
+        } else {
+            None
+        }
     }
 
     /// 为虚拟地址分配指定层级的页表
@@ -1780,7 +1953,8 @@ impl<Arch: MemoryManagementArch, F: FrameAllocator> PageMapper<Arch, F> {
 
     /// 修改虚拟地址的页表项的flags，并返回页表项刷新器
     ///
-    /// 请注意，需要在修改完flags后，调用刷新器的flush方法，才能使修改生效
+    /// 请注意，需要在修改完flags后，调用刷新器的flush方法，才能使修改生效。
+    /// 虚拟地址由大页映射时，先把大页拆分为4K页表项
     ///
     /// ## 参数
     /// - virt 虚拟地址
@@ -1794,6 +1968,9 @@ impl<Arch: MemoryManagementArch, F: FrameAllocator> PageMapper<Arch, F> {
         virt: VirtAddr,
         flags: EntryFlags<Arch>,
     ) -> Option<PageFlush<Arch>> {
+        if let Some((table, i)) = self.huge_pmd(virt) {
+            split_huge_entry(&table, i, &mut self.frame_allocator)?;
+        }
         return self
             .visit(virt, |p1, i| {
                 let mut entry = p1.entry(i)?;
@@ -1815,6 +1992,13 @@ impl<Arch: MemoryManagementArch, F: FrameAllocator> PageMapper<Arch, F> {
     ///
     /// 如果查找成功，返回物理地址和页表项的flags，否则返回None
     pub fn translate(&self, virt: VirtAddr) -> Option<(PhysAddr, EntryFlags<Arch>)> {
+        if let Some(entry) = self.get_huge_entry(virt) {
+            // 返回大页中对应4K页的物理地址，以及等价的4K页表项flags
+            let huge_size = Arch::PAGE_SIZE << Arch::PAGE_ENTRY_SHIFT;
+            let offset = virt.data() & (huge_size - 1) & !(Arch::PAGE_SIZE - 1);
+            let paddr = entry.address().ok()? + offset;
+            return Some((paddr, huge_to_pte_flags(entry.flags())));
+        }
         let entry: PageEntry<Arch> = self.visit(virt, |p1, i| unsafe { p1.entry(i) })??;
         let paddr = entry.address().ok()?;
         let flags = entry.flags();
@@ -1841,6 +2025,8 @@ impl<Arch: MemoryManagementArch, F: FrameAllocator> PageMapper<Arch, F> {
 
     /// 取消虚拟地址的映射，并返回物理地址和页表项的flags
     ///
+    /// 虚拟地址由大页映射时，先把大页拆分为4K页表项，只取消这一个4K页的映射
+    ///
     /// ## 参数
     ///
     /// - vaddr 虚拟地址
@@ -1912,6 +2098,9 @@ unsafe fn unmap_phys_inner<Arch: MemoryManagementArch>(
         return Some((entry.address().ok()?, entry.flags()));
     }
 
+    if table.entry(i)?.huge() {
+        split_huge_entry(table, i, allocator)?;
+    }
     let subtable = table.next_level_table(i)?;
     // 递归地取消映射
     let result = unmap_phys_inner(vaddr, &subtable, unmap_parents, allocator)?;
@@ -1936,6 +2125,49 @@ unsafe fn unmap_phys_inner<Arch: MemoryManagementArch>(
     return Some(result);
 }
 
+/// 把PMD中的大页页表项拆分为一个完整的4K页表，物理页和权限保持不变
+///
+/// 拆分前后映射的物理地址相同，残留在TLB中的大页表项不会造成错误的访问，
+/// 因此这里不需要刷新TLB
+unsafe fn split_huge_entry<Arch: MemoryManagementArch>(
+    table: &PageTable<Arch>,
+    i: usize,
+    allocator: &mut impl FrameAllocator,
+) -> Option<()> {
+    if table.level() != 1 {
+        return None;
+    }
+    let entry = table.entry(i)?;
+    let phys = entry.address().ok()?;
+    let flags = huge_to_pte_flags(entry.flags());
+
+    let frame = allocator.allocate_one()?;
+    let pte_table = PageTable::<Arch>::new(table.entry_base(i)?, frame, 0);
+    for k in 0..Arch::PAGE_ENTRY_NUM {
+        pte_table.set_entry(k, PageEntry::new(phys + k * Arch::PAGE_SIZE, flags));
+    }
+
+    compiler_fence(Ordering::SeqCst);
+    table.set_entry(
+        i,
+        PageEntry::new(frame, EntryFlags::new_page_table(entry.flags().has_user())),
+    );
+    compiler_fence(Ordering::SeqCst);
+    Some(())
+}
+
+/// 大页页表项的flags转换为等价的4K页表项的flags
+///
+/// x86_64使用PS位标记大页，需要去掉；riscv64的叶子页表项不需要额外的标志位
+#[inline(always)]
+fn huge_to_pte_flags<Arch: MemoryManagementArch>(flags: EntryFlags<Arch>) -> EntryFlags<Arch> {
+    if cfg!(target_arch = "x86_64") {
+        flags.set_huge_page(false)
+    } else {
+        flags
+    }
+}
+
 impl<Arch, F: Debug> Debug for PageMapper<Arch, F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("PageMapper")
@@ -2065,6 +2297,35 @@ impl Drop for InactiveFlusher {
     }
 }
 
+/// 刷新所有CPU的TLB的刷新器
+///
+/// 与[`InactiveFlusher`]不同，drop时会等待其他CPU都完成刷新。
+/// 用于撤销权限之后必须立即生效的场景（例如清除soft-dirty时的写保护），
+/// 地址空间是否在当前CPU上激活都可以使用
+#[derive(Debug)]
+pub struct SyncFlusher;
+
+impl SyncFlusher {
+    pub fn new() -> Self {
+        return Self {};
+    }
+}
+
+impl Flusher<MMArch> for SyncFlusher {
+    fn consume(&mut self, flush: PageFlush<MMArch>) {
+        unsafe {
+            flush.ignore();
+        }
+    }
+}
+
+impl Drop for SyncFlusher {
+    fn drop(&mut self) {
+        unsafe { MMArch::invalidate_all() };
+        flush_tlb_other_cpus();
+    }
+}
+
 /// # 把一个地址向下对齐到页大小
 pub fn round_down_to_page_size(addr: usize) -> usize {
     addr & !(MMArch::PAGE_SIZE - 1)
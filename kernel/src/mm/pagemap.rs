@@ -0,0 +1,189 @@
+//! /proc/<pid>/pagemap 和 /proc/<pid>/clear_refs 使用的页表遍历
+//!
+//! pagemap中每个虚拟页对应一个u64，格式与Linux一致：
+//!
+//! - bit 0-54: 页帧号（PFN），没有CAP_SYS_ADMIN时为0
+//! - bit 55: soft-dirty，上次向clear_refs写入4之后页面被写入过，或者所在的VMA是之后新建的
+//! - bit 56: 页面只被映射了一次
+//! - bit 61: 文件页或者共享内存页
+//! - bit 63: 页面在内存中
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/fs/proc/task_mmu.c
+
+use alloc::vec::Vec;
+use system_error::SystemError;
+
+use crate::arch::MMArch;
+use crate::mm::{
+    page::{page_manager_lock, Flusher, PageType, SyncFlusher},
+    ucontext::InnerAddressSpace,
+    MemoryManagementArch, VirtAddr, VirtRegion, VmFlags,
+};
+
+/// pagemap中每一项的字节数
+pub const PM_ENTRY_BYTES: usize = core::mem::size_of::<u64>();
+
+const PM_PFRAME_MASK: u64 = (1 << 55) - 1;
+const PM_SOFT_DIRTY: u64 = 1 << 55;
+const PM_MMAP_EXCLUSIVE: u64 = 1 << 56;
+const PM_FILE: u64 = 1 << 61;
+const PM_PRESENT: u64 = 1 << 63;
+
+/// 向clear_refs写入的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClearRefsType {
+    /// 清除所有页面的访问位
+    All = 1,
+    /// 清除匿名页面的访问位
+    Anon = 2,
+    /// 清除文件映射页面的访问位
+    Mapped = 3,
+    /// 清除所有页面的soft-dirty位
+    SoftDirty = 4,
+    /// 重置RSS的峰值
+    MmHiwaterRss = 5,
+}
+
+impl TryFrom<u32> for ClearRefsType {
+    type Error = SystemError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::All),
+            2 => Ok(Self::Anon),
+            3 => Ok(Self::Mapped),
+            4 => Ok(Self::SoftDirty),
+            5 => Ok(Self::MmHiwaterRss),
+            _ => Err(SystemError::EINVAL),
+        }
+    }
+}
+
+impl InnerAddressSpace {
+    /// # 读取一段虚拟地址的pagemap
+    ///
+    /// ## 参数
+    /// - `start`: 起始虚拟地址，按页对齐
+    /// - `count`: 页数
+    /// - `show_pfn`: 是否填写页帧号
+    ///
+    /// ## 返回值
+    /// 每个虚拟页对应的pagemap项，没有映射的页为0
+    pub fn pagemap_read(&self, start: VirtAddr, count: usize, show_pfn: bool) -> Vec<u64> {
+        let mut entries = vec![0u64; count];
+        let region = VirtRegion::new(start, count * MMArch::PAGE_SIZE);
+        let mapper = &self.user_mapper.utable;
+        let mut page_manager = page_manager_lock();
+
+        for vma in self.mappings.conflicts(region) {
+            let guard = vma.lock();
+            let Some(range) = guard.region().intersect(&region) else {
+                continue;
+            };
+            let file_backed = guard.vm_file().is_some();
+            let vma_soft_dirty = guard.vm_flags().contains(VmFlags::VM_SOFTDIRTY);
+            drop(guard);
+
+            let mut vaddr = range.start();
+            while vaddr < range.end() {
+                let idx = (vaddr - start) >> MMArch::PAGE_SHIFT;
+                if vma_soft_dirty {
+                    entries[idx] = PM_SOFT_DIRTY;
+                }
+                if let Some((paddr, flags)) = mapper.translate(vaddr) {
+                    if flags.present() {
+                        let mut pme = entries[idx] | PM_PRESENT;
+                        if show_pfn {
+                            pme |= (paddr.data() >> MMArch::PAGE_SHIFT) as u64 & PM_PFRAME_MASK;
+                        }
+                        if flags.has_soft_dirty() {
+                            pme |= PM_SOFT_DIRTY;
+                        }
+                        // 大页和设备内存没有对应的Page，按照VMA的类型判断
+                        match page_manager.get(&paddr) {
+                            Some(page) => {
+                                let page = page.read();
+                                if !matches!(page.page_type(), PageType::Normal) {
+                                    pme |= PM_FILE;
+                                }
+                                if page.map_count() == 1 {
+                                    pme |= PM_MMAP_EXCLUSIVE;
+                                }
+                            }
+                            None if file_backed => pme |= PM_FILE,
+                            None => {}
+                        }
+                        entries[idx] = pme;
+                    }
+                }
+                vaddr += MMArch::PAGE_SIZE;
+            }
+        }
+        entries
+    }
+
+    /// # 清除页面的访问位或者soft-dirty位
+    ///
+    /// 清除soft-dirty时同时把页面写保护，下次写入时在写保护异常中重新置位。
+    /// 返回之前所有CPU上的TLB都已经刷新，之后的写入一定会被记录。
+    /// 大页会先被拆分为4K页。
+    ///
+    /// ## 参数
+    /// - `ty`: 要清除的内容
+    pub fn clear_refs(&mut self, ty: ClearRefsType) -> Result<(), SystemError> {
+        // 目前没有统计RSS的峰值，不需要重置
+        if ty == ClearRefsType::MmHiwaterRss {
+            return Ok(());
+        }
+
+        // 进程的其他线程可能正在别的CPU上运行
+        let mut flusher = SyncFlusher::new();
+        let mapper = &mut self.user_mapper.utable;
+
+        for vma in self.mappings.iter_vmas() {
+            match ty {
+                ClearRefsType::Anon if !vma.is_anonymous() => continue,
+                ClearRefsType::Mapped if vma.is_anonymous() => continue,
+                _ => {}
+            }
+            let mut guard = vma.lock();
+            let region = *guard.region();
+            let shadow_stack = guard.vm_flags().contains(VmFlags::VM_SHADOW_STACK);
+            if ty == ClearRefsType::SoftDirty {
+                let vm_flags = *guard.vm_flags() - VmFlags::VM_SOFTDIRTY;
+                guard.set_vm_flags(vm_flags);
+            }
+            drop(guard);
+
+            let mut vaddr = region.start();
+            while vaddr < region.end() {
+                let Some((_, flags)) = mapper.translate(vaddr) else {
+                    vaddr += MMArch::PAGE_SIZE;
+                    continue;
+                };
+                let new_flags = match ty {
+                    ClearRefsType::SoftDirty if flags.has_soft_dirty() || flags.has_write() => {
+                        // 影子栈页面本来就是只读的，写入由硬件完成，只清除soft-dirty
+                        if shadow_stack {
+                            Some(flags.set_soft_dirty(false))
+                        } else {
+                            Some(flags.set_soft_dirty(false).set_write(false))
+                        }
+                    }
+                    ClearRefsType::SoftDirty => None,
+                    _ if flags.has_flag(MMArch::ENTRY_FLAG_ACCESSED) => {
+                        Some(flags.set_access(false))
+                    }
+                    _ => None,
+                };
+                if let Some(new_flags) = new_flags.filter(|_| flags.present()) {
+                    if let Some(flush) = unsafe { mapper.remap(vaddr, new_flags) } {
+                        flusher.consume(flush);
+                    }
+                }
+                vaddr += MMArch::PAGE_SIZE;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! 页表转储与一致性检查
+//!
+//! 遍历一个地址空间的页表，把属性相同的连续映射合并成区间输出（类似Linux的`kernel_page_tables`），
+//! 并检查以下不变式：
+//! - 内核空间中没有同时可写、可执行的映射（W^X）；
+//! - 内核空间中没有用户态可以访问的映射；
+//! - 用户空间中没有只有内核才能访问的映射。
+//!
+//! 通过`/sys/kernel/debug/page_tables`访问，参见[`crate::debug::page_tables`]。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/arch/x86/mm/dump_pagetables.c
+
+use core::{fmt::Write, ops::Range};
+
+use alloc::{string::String, vec::Vec};
+use system_error::SystemError;
+
+use crate::{
+    arch::MMArch,
+    process::{ProcessManager, RawPid},
+};
+
+use super::{kernel_mapper::KernelMapper, page::PageTable, MemoryManagementArch, PageTableKind};
+
+/// 检查报告中最多列出的违规映射数
+const PTDUMP_MAX_REPORTED: usize = 32;
+
+/// 逐级合并之后的有效权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PtePerm {
+    user: bool,
+    write: bool,
+    execute: bool,
+    uncached: bool,
+}
+
+impl PtePerm {
+    const ALL: Self = Self {
+        user: true,
+        write: true,
+        execute: true,
+        uncached: false,
+    };
+}
+
+/// 一个（大）页的映射
+#[derive(Debug, Clone, Copy)]
+struct Leaf {
+    start: usize,
+    size: usize,
+    level: usize,
+    perm: PtePerm,
+}
+
+/// 把页表索引得到的地址做符号扩展，得到规范地址
+fn canonical(addr: usize) -> usize {
+    if addr & (MMArch::PAGE_ADDRESS_SIZE >> 1) != 0 {
+        addr | MMArch::PAGE_NEGATIVE_MASK
+    } else {
+        addr
+    }
+}
+
+fn level_name(level: usize) -> &'static str {
+    if level == MMArch::PAGE_LEVELS - 1 {
+        return "pgd";
+    }
+    ["pte", "pmd", "pud", "p4d"].get(level).unwrap_or(&"???")
+}
+
+/// 遍历页表中`indices`范围内的页表项，对每个映射调用`f`
+unsafe fn walk(
+    table: &PageTable<MMArch>,
+    indices: Range<usize>,
+    parent: PtePerm,
+    f: &mut impl FnMut(Leaf),
+) {
+    for i in indices {
+        let entry = match table.entry(i) {
+            Some(entry) if entry.present() => entry,
+            _ => continue,
+        };
+        let flags = entry.flags();
+        let perm = PtePerm {
+            user: parent.user && flags.has_user(),
+            write: parent.write && flags.has_write(),
+            execute: parent.execute && flags.has_execute(),
+            uncached: flags.has_page_cache_disable(),
+        };
+        if table.level() == 0 || entry.huge() {
+            f(Leaf {
+                start: canonical(table.entry_base(i).unwrap().data()),
+                size: 1 << (table.level() * MMArch::PAGE_ENTRY_SHIFT + MMArch::PAGE_SHIFT),
+                level: table.level(),
+                perm,
+            });
+        } else if let Some(next) = table.next_level_table(i) {
+            // x86_64上中间级页表项的权限会限制下级的映射，其他架构的中间级页表项不带权限位
+            let inherited = if cfg!(target_arch = "x86_64") {
+                perm
+            } else {
+                PtePerm::ALL
+            };
+            walk(&next, 0..MMArch::PAGE_ENTRY_NUM, inherited, f);
+        }
+    }
+}
+
+/// 遍历内核空间
+fn walk_kernel(f: &mut impl FnMut(Leaf)) {
+    // 持有锁，避免遍历期间页表被修改
+    let _guard = KernelMapper::lock();
+    unsafe {
+        let table = PageTable::<MMArch>::top_level_table(PageTableKind::Kernel);
+        walk(
+            &table,
+            MMArch::PAGE_KERNEL_INDEX..MMArch::PAGE_ENTRY_NUM,
+            PtePerm::ALL,
+            f,
+        );
+    }
+}
+
+/// 遍历进程`pid`（为0时是当前进程）的用户空间
+fn walk_user(pid: RawPid, f: &mut impl FnMut(Leaf)) -> Result<(), SystemError> {
+    let pcb = if pid.data() == 0 {
+        ProcessManager::current_pcb()
+    } else {
+        ProcessManager::find_task_by_vpid(pid).ok_or(SystemError::ESRCH)?
+    };
+    let vm = pcb.basic().user_vm().ok_or(SystemError::EINVAL)?;
+    let guard = vm.read();
+    unsafe {
+        walk(
+            &guard.user_mapper.utable.table(),
+            0..MMArch::PAGE_KERNEL_INDEX,
+            PtePerm::ALL,
+            f,
+        );
+    }
+    Ok(())
+}
+
+/// 把属性相同的连续映射合并成区间并格式化
+struct Dumper<'a> {
+    out: String,
+    /// 按地址排序的区域起始地址和名称
+    markers: &'a [(usize, &'static str)],
+    next_marker: usize,
+    cur: Option<Leaf>,
+}
+
+impl<'a> Dumper<'a> {
+    fn new(markers: &'a [(usize, &'static str)]) -> Self {
+        Self {
+            out: String::new(),
+            markers,
+            next_marker: 0,
+            cur: None,
+        }
+    }
+
+    fn flush(&mut self) {
+        let Some(leaf) = self.cur.take() else {
+            return;
+        };
+        let end = leaf.start.wrapping_add(leaf.size);
+        let size = leaf.size >> 10;
+        let (size, unit) = if size >= (1 << 20) && size.is_multiple_of(1 << 20) {
+            (size >> 20, 'G')
+        } else if size >= (1 << 10) && size.is_multiple_of(1 << 10) {
+            (size >> 10, 'M')
+        } else {
+            (size, 'K')
+        };
+        let perm = leaf.perm;
+        let _ = writeln!(
+            self.out,
+            "{:#018x}-{:#018x} {:>8}{} {} {} {} {} {}",
+            leaf.start,
+            end,
+            size,
+            unit,
+            if perm.user { "USR" } else { "   " },
+            if perm.write { "RW" } else { "ro" },
+            if perm.execute { "x " } else { "NX" },
+            if perm.uncached { "UC" } else { "  " },
+            level_name(leaf.level)
+        );
+    }
+
+    fn push(&mut self, leaf: Leaf) {
+        let mut crossed = false;
+        while self.next_marker < self.markers.len()
+            && self.markers[self.next_marker].0 <= leaf.start
+        {
+            if !crossed {
+                self.flush();
+                crossed = true;
+            }
+            let _ = writeln!(self.out, "---[ {} ]---", self.markers[self.next_marker].1);
+            self.next_marker += 1;
+        }
+        if let Some(cur) = self.cur.as_mut() {
+            if cur.start.wrapping_add(cur.size) == leaf.start
+                && cur.level == leaf.level
+                && cur.perm == leaf.perm
+            {
+                cur.size += leaf.size;
+                return;
+            }
+        }
+        self.flush();
+        self.cur = Some(leaf);
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        self.out
+    }
+}
+
+/// 内核空间中的区域
+fn kernel_markers() -> Vec<(usize, &'static str)> {
+    let mut markers = alloc::vec![
+        (MMArch::PHYS_OFFSET, "Direct Map"),
+        (MMArch::MMIO_BASE.data(), "MMIO"),
+        (MMArch::MMIO_TOP.data(), "MMIO End"),
+        (MMArch::FIXMAP_START_VADDR.data(), "Fixmap"),
+        (MMArch::FIXMAP_END_VADDR.data(), "Fixmap End"),
+    ];
+    markers.sort_by_key(|m| m.0);
+    markers
+}
+
+/// # 转储内核空间的页表
+pub fn ptdump_kernel() -> String {
+    let markers = kernel_markers();
+    let mut dumper = Dumper::new(&markers);
+    walk_kernel(&mut |leaf| dumper.push(leaf));
+    dumper.finish()
+}
+
+/// # 转储进程的用户空间的页表
+///
+/// ## 参数
+/// - `pid`: 进程号，为0时是当前进程
+///
+/// ## 返回值
+/// - `Err(SystemError::ESRCH)`: 进程不存在
+/// - `Err(SystemError::EINVAL)`: 进程没有用户地址空间（内核线程）
+pub fn ptdump_user(pid: RawPid) -> Result<String, SystemError> {
+    let markers = [(0, "User Space")];
+    let mut dumper = Dumper::new(&markers);
+    walk_user(pid, &mut |leaf| dumper.push(leaf))?;
+    Ok(dumper.finish())
+}
+
+/// 一致性检查的结果
+#[derive(Debug, Default)]
+struct CheckResult {
+    pages: usize,
+    violations: usize,
+    report: String,
+}
+
+impl CheckResult {
+    fn check(&mut self, leaf: &Leaf, what: &str, violated: bool) {
+        if !violated {
+            return;
+        }
+        self.violations += 1;
+        if self.violations <= PTDUMP_MAX_REPORTED {
+            let _ = writeln!(
+                self.report,
+                "  {}: {:#018x}-{:#018x} {}",
+                what,
+                leaf.start,
+                leaf.start.wrapping_add(leaf.size),
+                level_name(leaf.level)
+            );
+        }
+    }
+}
+
+/// # 检查页表的不变式
+///
+/// 检查内核空间的W^X与用户位，以及进程`pid`（为0时是当前进程）的用户空间中的用户位
+///
+/// ## 返回值
+/// (违规的映射数, 可读的报告)
+pub fn ptdump_check(pid: RawPid) -> (usize, String) {
+    let mut kernel = CheckResult::default();
+    walk_kernel(&mut |leaf| {
+        kernel.pages += leaf.size >> MMArch::PAGE_SHIFT;
+        kernel.check(&leaf, "W+X", leaf.perm.write && leaf.perm.execute);
+        kernel.check(&leaf, "user accessible", leaf.perm.user);
+    });
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "kernel: checked {} pages, {} violations",
+        kernel.pages, kernel.violations
+    );
+    out.push_str(&kernel.report);
+
+    let mut user = CheckResult::default();
+    match walk_user(pid, &mut |leaf| {
+        user.pages += leaf.size >> MMArch::PAGE_SHIFT;
+        user.check(&leaf, "kernel only", !leaf.perm.user);
+    }) {
+        Ok(()) => {
+            let _ = writeln!(
+                out,
+                "user: checked {} pages, {} violations",
+                user.pages, user.violations
+            );
+            out.push_str(&user.report);
+        }
+        Err(e) => {
+            let _ = writeln!(out, "user: skipped ({:?})", e);
+        }
+    }
+    if kernel.violations > PTDUMP_MAX_REPORTED || user.violations > PTDUMP_MAX_REPORTED {
+        let _ = writeln!(
+            out,
+            "  ... (only the first {} of each are listed)",
+            PTDUMP_MAX_REPORTED
+        );
+    }
+    (kernel.violations + user.violations, out)
+}
@@ -1,6 +1,7 @@
 //! System call handler for the msync system call.
 
 use crate::arch::{interrupt::TrapFrame, syscall::nr::SYS_MSYNC, MMArch};
+use crate::filesystem::vfs::file::File;
 
 use crate::mm::{
     syscall::{MsFlags, VmFlags},
@@ -11,7 +12,7 @@ use crate::mm::{
 use crate::syscall::table::{FormattedSyscallParam, Syscall};
 use system_error::SystemError;
 
-use alloc::vec::Vec;
+use alloc::{sync::Arc, vec::Vec};
 
 /// Handles the msync system call.
 pub struct SysMsyncHandle;
@@ -68,13 +69,14 @@ impl Syscall for SysMsyncHandle {
         loop {
             if let Some(vma) = next_vma.clone() {
                 // 读取VMA信息，确保在调用find_nearest前释放锁
-                let (vm_start, vm_end, vm_flags, file);
+                let (vm_start, vm_end, vm_flags, file, backing_pgoff);
                 {
                     let guard = vma.lock();
                     vm_start = guard.region().start().data();
                     vm_end = guard.region().end().data();
                     vm_flags = *guard.vm_flags();
                     file = guard.vm_file();
+                    backing_pgoff = guard.backing_page_offset().unwrap_or(0);
 
                     if start < vm_start {
                         if flags == MsFlags::MS_ASYNC {
@@ -95,14 +97,20 @@ impl Syscall for SysMsyncHandle {
                     }
                 }
 
+                let sync_start = start;
+                let sync_end = core::cmp::min(end, vm_end);
                 start = vm_end;
 
                 if flags.contains(MsFlags::MS_SYNC) && vm_flags.contains(VmFlags::VM_SHARED) {
                     if let Some(file) = file {
-                        // 对于文件映射的 msync，我们只需要触发文件系统的同步操作
-                        // 实际的脏页回写由页面管理系统和文件系统处理
-                        // 这里调用 sync 来确保文件系统的缓存被刷新到磁盘
-                        let _ = file.inode().sync();
+                        let start_index =
+                            backing_pgoff + ((sync_start - vm_start) >> MMArch::PAGE_SHIFT);
+                        let end_index =
+                            backing_pgoff + ((sync_end - vm_start - 1) >> MMArch::PAGE_SHIFT);
+                        if let Err(e) = Self::sync_file_range(&file, start_index, end_index) {
+                            err = Err(e);
+                            break;
+                        }
                     }
                 }
 
@@ -132,6 +140,21 @@ impl Syscall for SysMsyncHandle {
 }
 
 impl SysMsyncHandle {
+    /// 把文件中页号为`[start_index, end_index]`的脏页写回
+    ///
+    /// 没有页缓存的文件退化为同步整个inode
+    fn sync_file_range(
+        file: &Arc<File>,
+        start_index: usize,
+        end_index: usize,
+    ) -> Result<(), SystemError> {
+        let inode = file.inode();
+        match inode.page_cache() {
+            Some(page_cache) => page_cache.manager().writeback_range(start_index, end_index),
+            None => inode.sync(),
+        }
+    }
+
     /// Extracts the start_vaddr argument from syscall parameters.
     fn start_vaddr(args: &[usize]) -> usize {
         args[0]
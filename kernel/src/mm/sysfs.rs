@@ -18,7 +18,7 @@ use crate::{
     init::initcall::INITCALL_POSTCORE,
     libs::casting::DowncastArc,
     misc::ksysfs::sys_kernel_kobj,
-    mm::{page_cache_stats, MemoryManagementArch},
+    mm::{huge_memory, page_cache_stats, MemoryManagementArch},
 };
 
 use crate::driver::base::kobject::CommonKobj;
@@ -497,6 +497,10 @@ fn pagecache_sysfs_init() -> Result<(), SystemError> {
         },
     );
 
+    huge_memory::thp_sysfs_init(&mm_kobj).unwrap_or_else(|e| {
+        log::warn!("Failed to add transparent_hugepage to sysfs: {:?}", e);
+    });
+
     let pagecache_kobj = CommonKobj::new("pagecache".to_string());
     pagecache_kobj.set_parent(Some(Arc::downgrade(&(mm_kobj as Arc<dyn KObject>))));
     KObjectManager::init_and_add_kobj(pagecache_kobj.clone(), Some(&DynamicKObjKType))
@@ -1,5 +1,8 @@
 use super::page::{Page, PageFlags};
-use crate::filesystem::page_cache::PageCache;
+use crate::{
+    arch::MMArch, filesystem::page_cache::PageCache, mm::MemoryManagementArch,
+    process::io_accounting::task_io_account_cancelled_write,
+};
 use alloc::sync::Arc;
 
 /// # 功能
@@ -27,6 +30,10 @@ pub fn truncate_inode_pages(page_cache: Arc<PageCache>, start: usize) {
 
 fn truncate_complete_page(page_cache: Arc<PageCache>, page_index: usize, page: Arc<Page>) {
     let mut guard = page.write();
+    if guard.flags().contains(PageFlags::PG_DIRTY) {
+        // 脏页被丢弃，原本需要的写回也随之取消
+        task_io_account_cancelled_write(MMArch::PAGE_SIZE);
+    }
     guard.remove_flags(PageFlags::PG_DIRTY);
     drop(guard);
     page_cache.mark_page_uptodate(page_index);
@@ -4,18 +4,16 @@ use core::{
     cmp,
     hash::Hasher,
     intrinsics::unlikely,
-    ops::Add,
+    ops::{Add, Range},
     sync::atomic::{compiler_fence, AtomicU64, Ordering},
 };
 
 use alloc::{
-    collections::BTreeMap,
     sync::{Arc, Weak},
     vec::Vec,
 };
 use defer::defer;
 use hashbrown::HashMap;
-use hashbrown::HashSet;
 use ida::IdAllocator;
 use log::warn;
 use system_error::SystemError;
@@ -25,6 +23,7 @@ use crate::{
     exception::InterruptArch,
     filesystem::vfs::{
         file::{File, FileMode},
+        mount::{inode_mount_flags, MountFlags},
         FileType, InodeId,
     },
     ipc::shm::{ShmFlags, ShmId},
@@ -33,17 +32,18 @@ use crate::{
         mutex::{Mutex, MutexGuard},
         rwsem::RwSem,
         spinlock::SpinLock,
+        wait_queue::WaitQueue,
     },
     mm::{page::page_manager_lock, PhysAddr},
     process::{resource::RLimitID, ProcessManager},
 };
 
 use super::{
-    allocator::page_frame::{
-        deallocate_page_frames, PageFrameCount, PhysPageFrame, VirtPageFrame, VirtPageFrameIter,
-    },
+    allocator::page_frame::{PageFrameCount, PhysPageFrame, VirtPageFrame, VirtPageFrameIter},
+    huge_memory::{HPAGE_PMD_NR, HPAGE_PMD_SIZE},
     page::{EntryFlags, Flusher, InactiveFlusher, Page, PageFlags, PageFlushAll, PageType},
     syscall::{MadvFlags, MapFlags, MremapFlags, ProtFlags},
+    vma_tree::{VmGapTree, VmaTree},
     MemoryManagementArch, PageTableKind, VirtAddr, VirtRegion, VmFlags,
 };
 use crate::arch::mm::LockedFrameAllocator;
@@ -240,7 +240,7 @@ impl InnerAddressSpace {
 
         // 遍历父进程的每个VMA，根据VMA属性进行适当的复制
         // 参考 Linux: https://code.dragonos.org.cn/xref/linux-6.6.21/mm/memory.c#copy_page_range
-        for vma in self.mappings.vmas.iter() {
+        for vma in self.mappings.iter_vmas() {
             // 锁顺序：VMA 锁 -> page_manager -> shm_manager，避免交叉获取导致死锁。
             let vma_guard = vma.lock();
 
@@ -551,6 +551,12 @@ impl InnerAddressSpace {
         if prot_flags.contains(ProtFlags::PROT_EXEC) && !file_mode.contains(FileMode::FMODE_READ) {
             return Err(SystemError::EACCES);
         }
+        // noexec挂载上的文件不能映射为可执行
+        if prot_flags.contains(ProtFlags::PROT_EXEC)
+            && inode_mount_flags(&file.inode()).contains(MountFlags::NOEXEC)
+        {
+            return Err(SystemError::EPERM);
+        }
         if prot_flags.contains(ProtFlags::PROT_WRITE) {
             if map_flags.contains(MapFlags::MAP_SHARED) {
                 if !file_mode.contains(FileMode::FMODE_WRITE) {
@@ -730,7 +736,8 @@ impl InnerAddressSpace {
             | VmFlags::from(map_flags)
             | VmFlags::VM_MAYREAD
             | VmFlags::VM_MAYWRITE
-            | VmFlags::VM_MAYEXEC;
+            | VmFlags::VM_MAYEXEC
+            | VmFlags::VM_SOFTDIRTY;
 
         // debug!("mmap: page: {:?}, region={region:?}", page.virt_address());
 
@@ -1079,6 +1086,14 @@ impl InnerAddressSpace {
         let regions = self.mappings.conflicts(region).collect::<Vec<_>>();
         // debug!("mprotect: regions: {:?}", regions);
 
+        // 影子栈的保护位由内核管理，不允许用户修改
+        if regions
+            .iter()
+            .any(|r| r.lock().vm_flags().contains(VmFlags::VM_SHADOW_STACK))
+        {
+            return Err(SystemError::EINVAL);
+        }
+
         for r in regions {
             // debug!("mprotect: r: {:?}", r);
             let r = *r.lock().region();
@@ -1103,7 +1118,8 @@ impl InnerAddressSpace {
                 self.mappings.insert_vma(r.clone());
                 return Err(SystemError::EACCES);
             }
-            r_guard.set_vm_flags(VmFlags::from(prot_flags));
+            let soft_dirty = *r_guard.vm_flags() & VmFlags::VM_SOFTDIRTY;
+            r_guard.set_vm_flags(VmFlags::from(prot_flags) | soft_dirty);
 
             let new_flags: EntryFlags<MMArch> = r_guard
                 .flags()
@@ -1410,30 +1426,26 @@ impl Drop for UserMapper {
         }
         // 释放用户空间顶层页表占用的页帧
         // 请注意，在释放这个页帧之前，用户页表应该已经被完全释放，否则会产生内存泄露
-        unsafe {
-            deallocate_page_frames(
-                PhysPageFrame::new(self.utable.table().phys()),
-                PageFrameCount::new(1),
-            )
-        };
+        unsafe { MMArch::free_usermapper_table(self.utable.table().phys()) };
     }
 }
 
 /// 用户空间映射信息
 #[derive(Debug)]
 pub struct UserMappings {
-    /// 当前用户空间的虚拟内存区域
-    vmas: HashSet<Arc<LockedVMA>>,
+    /// 当前用户空间的虚拟内存区域，按起始地址排序
+    vmas: VmaTree,
     /// 当前用户空间的VMA空洞
-    vm_holes: BTreeMap<VirtAddr, usize>,
+    vm_holes: VmGapTree,
 }
 
 impl UserMappings {
     pub fn new() -> Self {
+        let mut vm_holes = VmGapTree::new();
+        vm_holes.insert(VirtAddr::new(0), MMArch::USER_END_VADDR.data());
         return Self {
-            vmas: HashSet::new(),
-            vm_holes: core::iter::once((VirtAddr::new(0), MMArch::USER_END_VADDR.data()))
-                .collect::<BTreeMap<_, _>>(),
+            vmas: VmaTree::new(),
+            vm_holes,
         };
     }
 
@@ -1442,13 +1454,7 @@ impl UserMappings {
     /// 如果有，返回包含指定虚拟地址的VMA的Arc指针，否则返回None。
     #[allow(dead_code)]
     pub fn contains(&self, vaddr: VirtAddr) -> Option<Arc<LockedVMA>> {
-        for v in self.vmas.iter() {
-            let guard = v.lock();
-            if guard.region.contains(vaddr) {
-                return Some(v.clone());
-            }
-        }
-        return None;
+        return self.vmas.find(vaddr);
     }
 
     /// 向下寻找距离虚拟地址最近的VMA
@@ -1461,34 +1467,12 @@ impl UserMappings {
     /// - None: 未找到VMA
     #[allow(dead_code)]
     pub fn find_nearest(&self, vaddr: VirtAddr) -> Option<Arc<LockedVMA>> {
-        let mut nearest: Option<Arc<LockedVMA>> = None;
-        for v in self.vmas.iter() {
-            let guard = v.lock();
-            if guard.region.contains(vaddr) {
-                return Some(v.clone());
-            }
-            // 向下寻找：选择起始地址大于 vaddr 的 VMA 中，起始地址最小的一个（最近的下一个VMA）
-            if guard.region.start > vaddr
-                && if let Some(ref current) = nearest {
-                    guard.region.start < current.lock().region.start
-                } else {
-                    true
-                }
-            {
-                nearest = Some(v.clone());
-            }
-        }
-        return nearest;
+        return self.vmas.find_nearest(vaddr);
     }
 
-    /// 获取当前进程的地址空间中，与给定虚拟地址范围有重叠的VMA的迭代器。
+    /// 获取当前进程的地址空间中，与给定虚拟地址范围有重叠的VMA的迭代器（按地址顺序）。
     pub fn conflicts(&self, request: VirtRegion) -> impl Iterator<Item = Arc<LockedVMA>> + '_ {
-        let r = self
-            .vmas
-            .iter()
-            .filter(move |v| v.lock().region.intersect(&request).is_some())
-            .cloned();
-        return r;
+        return self.vmas.overlapping(request).cloned();
     }
 
     /// 在当前进程的地址空间中，寻找第一个符合条件的空闲的虚拟内存范围。
@@ -1498,27 +1482,17 @@ impl UserMappings {
     ///
     /// @return 如果找到了，返回虚拟内存范围，否则返回None
     pub fn find_free(&self, min_vaddr: VirtAddr, req_size: usize) -> Option<VirtRegion> {
-        let mut iter = self
-            .vm_holes
-            .iter()
-            .skip_while(|(hole_vaddr, hole_size)| hole_vaddr.add(**hole_size) <= min_vaddr);
-
-        let (hole_vaddr, _hole_size) = iter.find(|(hole_vaddr, hole_size)| {
-            // 计算当前空洞的可用大小
-            let available_size: usize =
-                if hole_vaddr <= &&min_vaddr && min_vaddr <= hole_vaddr.add(**hole_size) {
-                    **hole_size - (min_vaddr - **hole_vaddr)
-                } else {
-                    **hole_size
-                };
-
-            req_size <= available_size
-        })?;
-
-        // 返回恰好等于请求大小的区域，起始地址取空洞与下限的较大值。
-        let region = VirtRegion::new(cmp::max(*hole_vaddr, min_vaddr), req_size);
+        // 包含min_vaddr的空洞只有min_vaddr之后的部分可用
+        if let Some((hole_vaddr, hole_size)) = self.vm_holes.floor(min_vaddr) {
+            let hole_end = hole_vaddr.add(hole_size);
+            if hole_end > min_vaddr && req_size <= hole_end - min_vaddr {
+                return Some(VirtRegion::new(min_vaddr, req_size));
+            }
+        }
 
-        return Some(region);
+        // 其余的空洞都在min_vaddr之后，取最低的一个足够大的空洞
+        let (hole_vaddr, _hole_size) = self.vm_holes.first_fit(min_vaddr, req_size)?;
+        return Some(VirtRegion::new(hole_vaddr, req_size));
     }
 
     /// 在当前进程的地址空间中，保留一个指定大小的区域，使得该区域不在空洞中。
@@ -1528,15 +1502,19 @@ impl UserMappings {
     ///
     /// 请注意，在调用本函数之前，必须先确定region所在范围内没有VMA。
     fn reserve_hole(&mut self, region: &VirtRegion) {
-        let prev_hole: Option<(&VirtAddr, &mut usize)> =
-            self.vm_holes.range_mut(..=region.start()).next_back();
+        let prev_hole: Option<(VirtAddr, usize)> = self.vm_holes.floor(region.start());
 
         if let Some((prev_hole_vaddr, prev_hole_size)) = prev_hole {
-            let prev_hole_end = prev_hole_vaddr.add(*prev_hole_size);
+            let prev_hole_end = prev_hole_vaddr.add(prev_hole_size);
 
             if prev_hole_end > region.start() {
                 // 如果前一个空洞的结束地址大于当前空洞的起始地址，那么就需要调整前一个空洞的大小。
-                *prev_hole_size = region.start().data() - prev_hole_vaddr.data();
+                let new_size = region.start().data() - prev_hole_vaddr.data();
+                if new_size == 0 {
+                    self.vm_holes.remove(prev_hole_vaddr);
+                } else {
+                    self.vm_holes.insert(prev_hole_vaddr, new_size);
+                }
             }
 
             if prev_hole_end > region.end() {
@@ -1551,18 +1529,23 @@ impl UserMappings {
     /// 该函数会修改vm_holes中的空洞信息。
     fn unreserve_hole(&mut self, region: &VirtRegion) {
         // 如果将要插入的空洞与后一个空洞相邻，那么就需要合并。
-        let next_hole_size: Option<usize> = self.vm_holes.remove(&region.end());
+        let next_hole_size: usize = self.vm_holes.remove(region.end()).unwrap_or(0);
 
-        if let Some((_prev_hole_vaddr, prev_hole_size)) = self
+        match self
             .vm_holes
-            .range_mut(..region.start())
-            .next_back()
-            .filter(|(offset, size)| offset.data() + **size == region.start().data())
+            .lower(region.start())
+            .filter(|(offset, size)| offset.data() + *size == region.start().data())
         {
-            *prev_hole_size += region.size() + next_hole_size.unwrap_or(0);
-        } else {
-            self.vm_holes
-                .insert(region.start(), region.size() + next_hole_size.unwrap_or(0));
+            Some((prev_hole_vaddr, prev_hole_size)) => {
+                self.vm_holes.insert(
+                    prev_hole_vaddr,
+                    prev_hole_size + region.size() + next_hole_size,
+                );
+            }
+            None => {
+                self.vm_holes
+                    .insert(region.start(), region.size() + next_hole_size);
+            }
         }
     }
 
@@ -1591,18 +1574,14 @@ impl UserMappings {
     /// - 会修改vm_holes中的空洞信息
     ///
     pub fn remove_vma(&mut self, region: &VirtRegion) -> Option<Arc<LockedVMA>> {
-        // 请注意，由于这里会对每个VMA加锁，因此性能很低
-        let vma: Arc<LockedVMA> = self
-            .vmas
-            .drain_filter(|vma| vma.lock().region == *region)
-            .next()?;
+        let vma: Arc<LockedVMA> = self.vmas.remove(region)?;
         self.unreserve_hole(region);
 
         return Some(vma);
     }
 
-    /// @brief Get the iterator of all VMAs in this process.
-    pub fn iter_vmas(&self) -> hashbrown::hash_set::Iter<'_, Arc<LockedVMA>> {
+    /// @brief Get the iterator of all VMAs in this process, ordered by address.
+    pub fn iter_vmas(&self) -> impl Iterator<Item = &Arc<LockedVMA>> + '_ {
         return self.vmas.iter();
     }
 }
@@ -1621,6 +1600,8 @@ pub struct LockedVMA {
     /// 用于计算哈希值，避免总是获取vma锁来计算哈希值
     id: usize,
     vma: Mutex<VMA>,
+    /// 缺页时读入后备对象页使用的范围锁，不需要持有地址空间锁
+    fault_range: VmaRangeLock,
 }
 
 impl core::hash::Hash for LockedVMA {
@@ -1643,6 +1624,7 @@ impl LockedVMA {
         let r = Arc::new(Self {
             id: LOCKEDVMA_ID_ALLOCATOR.lock().alloc().unwrap(),
             vma: Mutex::new(vma),
+            fault_range: VmaRangeLock::new(),
         });
         r.vma.lock().self_ref = Arc::downgrade(&r);
         return r;
@@ -1656,6 +1638,11 @@ impl LockedVMA {
         return self.vma.lock();
     }
 
+    /// 锁定VMA中后备对象页号在`range`内的部分，用于在不持有地址空间锁的情况下读入文件页
+    pub fn lock_fault_range(&self, range: Range<usize>) -> VmaRangeGuard<'_> {
+        self.fault_range.lock(range)
+    }
+
     /// 调整当前VMA的页面的标志位
     ///
     /// TODO：增加调整虚拟页映射的物理地址的功能
@@ -1671,15 +1658,26 @@ impl LockedVMA {
         mut flusher: impl Flusher<MMArch>,
     ) -> Result<(), SystemError> {
         let mut guard = self.lock();
-        for page in guard.region.pages() {
+        let end = guard.region.end();
+        let mut virt = guard.region.start();
+        while virt < end {
+            // 完全落在VMA内的透明大页整体修改，其余的大页由mapper拆分后逐页修改
+            if virt.check_aligned(HPAGE_PMD_SIZE) && virt + HPAGE_PMD_SIZE <= end {
+                if let Some(r) = unsafe { mapper.remap_huge(virt, flags) } {
+                    flusher.consume(r);
+                    virt += HPAGE_PMD_SIZE;
+                    continue;
+                }
+            }
             // 暂时要求所有的页帧都已经映射到页表
             // TODO: 引入Lazy Mapping, 通过缺页中断来映射页帧，这里就不必要求所有的页帧都已经映射到页表了
             let r = unsafe {
                 mapper
-                    .remap(page.virt_address(), flags)
+                    .remap(virt, flags)
                     .expect("Failed to remap, beacuse of some page is not mapped")
             };
             flusher.consume(r);
+            virt += MMArch::PAGE_SIZE;
         }
         guard.flags = flags;
         return Ok(());
@@ -1716,13 +1714,7 @@ impl LockedVMA {
             }
         }
 
-        for page in self_guard.region.pages() {
-            if mapper.translate(page.virt_address()).is_none() {
-                continue;
-            }
-            let (paddr, _, flush) = unsafe { mapper.unmap_phys(page.virt_address(), true) }
-                .expect("Failed to unmap, beacuse of some page is not mapped");
-
+        let mut release_page = |paddr: PhysAddr| {
             // 从anon_vma中删除当前VMA
             let page = page_manager_guard.get_unwrap(&paddr);
             let mut page_guard = page.write();
@@ -1733,8 +1725,30 @@ impl LockedVMA {
             if page_guard.can_deallocate() {
                 page_manager_guard.remove_page(&paddr);
             }
+        };
+
+        let end = self_guard.region.end();
+        let mut virt = self_guard.region.start();
+        while virt < end {
+            // 完全落在VMA内的透明大页整体解除映射，其余的大页由mapper拆分后逐页解除映射
+            if virt.check_aligned(HPAGE_PMD_SIZE) && virt + HPAGE_PMD_SIZE <= end {
+                if let Some((paddr, _, flush)) = unsafe { mapper.unmap_huge_phys(virt) } {
+                    for i in 0..HPAGE_PMD_NR {
+                        release_page(paddr + i * MMArch::PAGE_SIZE);
+                    }
+                    flusher.consume(flush);
+                    virt += HPAGE_PMD_SIZE;
+                    continue;
+                }
+            }
 
-            flusher.consume(flush);
+            if mapper.translate(virt).is_some() {
+                let (paddr, _, flush) = unsafe { mapper.unmap_phys(virt, true) }
+                    .expect("Failed to unmap, beacuse of some page is not mapped");
+                release_page(paddr);
+                flusher.consume(flush);
+            }
+            virt += MMArch::PAGE_SIZE;
         }
         self_guard.mapped = false;
 
@@ -1924,6 +1938,65 @@ impl Drop for LockedVMA {
     }
 }
 
+/// VMA内按后备对象页号划分的范围锁
+///
+/// 缺页处理释放地址空间锁之后读入文件页时持有对应的范围，
+/// 落在同一范围内的其他缺页会等待这次读入完成，而不是重复发起I/O。
+/// 不相交的范围互不影响，因此同一VMA的不同区域可以并发读入。
+#[derive(Debug)]
+pub struct VmaRangeLock {
+    ranges: SpinLock<Vec<Range<usize>>>,
+    wait_queue: WaitQueue,
+}
+
+impl VmaRangeLock {
+    pub fn new() -> Self {
+        Self {
+            ranges: SpinLock::new(Vec::new()),
+            wait_queue: WaitQueue::default(),
+        }
+    }
+
+    /// 等待与`range`相交的范围全部释放后锁定`range`
+    pub fn lock(&self, range: Range<usize>) -> VmaRangeGuard<'_> {
+        self.wait_queue.wait_until(|| {
+            let mut ranges = self.ranges.lock_irqsave();
+            if ranges
+                .iter()
+                .any(|r| r.start < range.end && range.start < r.end)
+            {
+                return None;
+            }
+            ranges.push(range.clone());
+            Some(())
+        });
+        VmaRangeGuard { lock: self, range }
+    }
+}
+
+impl Default for VmaRangeLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// [`VmaRangeLock`]的守卫，drop时释放范围并唤醒等待者
+pub struct VmaRangeGuard<'a> {
+    lock: &'a VmaRangeLock,
+    range: Range<usize>,
+}
+
+impl Drop for VmaRangeGuard<'_> {
+    fn drop(&mut self) {
+        let mut ranges = self.lock.ranges.lock_irqsave();
+        if let Some(pos) = ranges.iter().position(|r| *r == self.range) {
+            ranges.swap_remove(pos);
+        }
+        drop(ranges);
+        self.lock.wait_queue.wakeup_all(None);
+    }
+}
+
 /// VMA切分结果
 #[allow(dead_code)]
 pub struct VMASplitResult {
@@ -2192,12 +2265,19 @@ impl VMA {
         mapper: &mut PageMapper,
         mut flusher: impl Flusher<MMArch>,
     ) -> Result<(), SystemError> {
+        let writenotify = self.vm_flags.contains(VmFlags::VM_SHARED) && self.vm_file.is_some();
         for page in self.region.pages() {
             // debug!("remap page {:?}", page.virt_address());
-            if mapper.translate(page.virt_address()).is_some() {
+            if let Some((_, old_flags)) = mapper.translate(page.virt_address()) {
+                // 共享文件映射中还没有被写入的页面保持只读，第一次写入时由do_wp_page标记脏页
+                let new_flags = if writenotify && !old_flags.has_write() {
+                    flags.set_write(false)
+                } else {
+                    flags
+                };
                 let r = unsafe {
                     mapper
-                        .remap(page.virt_address(), flags)
+                        .remap(page.virt_address(), new_flags)
                         .expect("Failed to remap")
                 };
                 flusher.consume(r);
@@ -2490,6 +2570,11 @@ impl UserStack {
         };
     }
 
+    /// 获取栈底地址（栈的最高地址）
+    pub fn stack_bottom(&self) -> VirtAddr {
+        return self.stack_bottom;
+    }
+
     /// 获取当前用户栈的大小（不包括保护页）
     pub fn stack_size(&self) -> usize {
         return self.mapped_size;
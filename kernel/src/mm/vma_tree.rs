@@ -0,0 +1,307 @@
+//! 用户地址空间的VMA索引
+//!
+//! - [`VmaTree`]：以起始地址为键的有序VMA集合，查找、插入、删除都是O(log n)，
+//!   并且可以按地址顺序遍历（/proc/<pid>/maps 依赖这一点）
+//! - [`VmGapTree`]：记录地址空间空洞的AVL树，每个节点额外维护子树中最大的空洞大小，
+//!   mmap选择地址时可以在O(log n)内找到满足大小要求的最低空洞
+
+use core::{cmp::Ordering, ops::Bound};
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
+
+use super::{ucontext::LockedVMA, VirtAddr, VirtRegion};
+
+/// 按起始地址排序的VMA集合
+///
+/// 以VMA的起始地址作为键，因此VMA的region在树中时不能修改，
+/// 需要修改时先[`VmaTree::remove`]，修改后再重新插入。
+#[derive(Debug, Default)]
+pub struct VmaTree {
+    vmas: BTreeMap<VirtAddr, Arc<LockedVMA>>,
+}
+
+impl VmaTree {
+    pub const fn new() -> Self {
+        Self {
+            vmas: BTreeMap::new(),
+        }
+    }
+
+    /// 插入一个VMA，调用者需要保证它与已有的VMA不重叠
+    pub fn insert(&mut self, vma: Arc<LockedVMA>) {
+        let start = vma.lock().region().start();
+        self.vmas.insert(start, vma);
+    }
+
+    /// 删除region与给定范围完全相同的VMA
+    pub fn remove(&mut self, region: &VirtRegion) -> Option<Arc<LockedVMA>> {
+        let vma = self.vmas.get(&region.start())?;
+        if *vma.lock().region() != *region {
+            return None;
+        }
+        self.vmas.remove(&region.start())
+    }
+
+    /// 查找包含`vaddr`的VMA
+    pub fn find(&self, vaddr: VirtAddr) -> Option<Arc<LockedVMA>> {
+        let (_, vma) = self.vmas.range(..=vaddr).next_back()?;
+        if vma.lock().region().contains(vaddr) {
+            return Some(vma.clone());
+        }
+        None
+    }
+
+    /// 查找包含`vaddr`的VMA，如果不存在，则返回`vaddr`之后的第一个VMA
+    pub fn find_nearest(&self, vaddr: VirtAddr) -> Option<Arc<LockedVMA>> {
+        if let Some(vma) = self.find(vaddr) {
+            return Some(vma);
+        }
+        self.vmas
+            .range((Bound::Excluded(vaddr), Bound::Unbounded))
+            .next()
+            .map(|(_, vma)| vma.clone())
+    }
+
+    /// 按地址顺序遍历与`region`重叠的VMA
+    pub fn overlapping(&self, region: VirtRegion) -> impl Iterator<Item = &Arc<LockedVMA>> + '_ {
+        // 起始地址在region之前的VMA中，只有最后一个可能与region重叠
+        let first = self
+            .vmas
+            .range(..region.start())
+            .next_back()
+            .map(|(_, vma)| vma)
+            .filter(move |vma| vma.lock().region().end() > region.start());
+
+        first.into_iter().chain(
+            self.vmas
+                .range(region.start()..region.end())
+                .map(|(_, vma)| vma),
+        )
+    }
+
+    /// 按地址顺序遍历所有VMA
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<LockedVMA>> + '_ {
+        self.vmas.values()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct GapNode {
+    start: VirtAddr,
+    size: usize,
+    /// 以该节点为根的子树中最大的空洞大小
+    max_size: usize,
+    height: u8,
+    left: Option<Box<GapNode>>,
+    right: Option<Box<GapNode>>,
+}
+
+impl GapNode {
+    fn new(start: VirtAddr, size: usize) -> Box<Self> {
+        Box::new(Self {
+            start,
+            size,
+            max_size: size,
+            height: 1,
+            left: None,
+            right: None,
+        })
+    }
+
+    fn update(&mut self) {
+        let (lh, lm) = self
+            .left
+            .as_ref()
+            .map_or((0, 0), |n| (n.height, n.max_size));
+        let (rh, rm) = self
+            .right
+            .as_ref()
+            .map_or((0, 0), |n| (n.height, n.max_size));
+        self.height = lh.max(rh) + 1;
+        self.max_size = self.size.max(lm).max(rm);
+    }
+
+    fn balance_factor(&self) -> i16 {
+        let lh = self.left.as_ref().map_or(0, |n| n.height) as i16;
+        let rh = self.right.as_ref().map_or(0, |n| n.height) as i16;
+        lh - rh
+    }
+
+    fn rotate_right(mut self: Box<Self>) -> Box<Self> {
+        let mut left = self.left.take().expect("rotate_right without left child");
+        self.left = left.right.take();
+        self.update();
+        left.right = Some(self);
+        left.update();
+        left
+    }
+
+    fn rotate_left(mut self: Box<Self>) -> Box<Self> {
+        let mut right = self.right.take().expect("rotate_left without right child");
+        self.right = right.left.take();
+        self.update();
+        right.left = Some(self);
+        right.update();
+        right
+    }
+
+    fn rebalance(mut self: Box<Self>) -> Box<Self> {
+        self.update();
+        let bf = self.balance_factor();
+        if bf > 1 {
+            if self.left.as_ref().unwrap().balance_factor() < 0 {
+                self.left = Some(self.left.take().unwrap().rotate_left());
+            }
+            return self.rotate_right();
+        }
+        if bf < -1 {
+            if self.right.as_ref().unwrap().balance_factor() > 0 {
+                self.right = Some(self.right.take().unwrap().rotate_right());
+            }
+            return self.rotate_left();
+        }
+        self
+    }
+
+    fn insert(node: Option<Box<Self>>, start: VirtAddr, size: usize) -> Box<Self> {
+        let mut node = match node {
+            Some(node) => node,
+            None => return Self::new(start, size),
+        };
+        match start.cmp(&node.start) {
+            Ordering::Less => node.left = Some(Self::insert(node.left.take(), start, size)),
+            Ordering::Greater => node.right = Some(Self::insert(node.right.take(), start, size)),
+            Ordering::Equal => node.size = size,
+        }
+        node.rebalance()
+    }
+
+    /// 删除子树中最小的节点，返回剩余的子树和被删除的节点
+    fn remove_min(mut self: Box<Self>) -> (Option<Box<Self>>, Box<Self>) {
+        match self.left.take() {
+            None => {
+                let right = self.right.take();
+                (right, self)
+            }
+            Some(left) => {
+                let (left, min) = left.remove_min();
+                self.left = left;
+                (Some(self.rebalance()), min)
+            }
+        }
+    }
+
+    fn remove(node: Option<Box<Self>>, start: VirtAddr) -> (Option<Box<Self>>, Option<usize>) {
+        let mut node = match node {
+            Some(node) => node,
+            None => return (None, None),
+        };
+        let removed;
+        match start.cmp(&node.start) {
+            Ordering::Less => {
+                let (left, r) = Self::remove(node.left.take(), start);
+                node.left = left;
+                removed = r;
+            }
+            Ordering::Greater => {
+                let (right, r) = Self::remove(node.right.take(), start);
+                node.right = right;
+                removed = r;
+            }
+            Ordering::Equal => {
+                let size = node.size;
+                let left = node.left.take();
+                let right = node.right.take();
+                let root = match (left, right) {
+                    (None, None) => return (None, Some(size)),
+                    (Some(child), None) | (None, Some(child)) => return (Some(child), Some(size)),
+                    (Some(left), Some(right)) => {
+                        let (right, mut succ) = right.remove_min();
+                        succ.left = Some(left);
+                        succ.right = right;
+                        succ
+                    }
+                };
+                return (Some(root.rebalance()), Some(size));
+            }
+        }
+        (Some(node.rebalance()), removed)
+    }
+
+    /// 在子树中寻找起始地址不小于`min`、大小不小于`size`的最低空洞
+    fn first_fit(node: &Option<Box<Self>>, min: VirtAddr, size: usize) -> Option<&Self> {
+        let node = node.as_ref().filter(|n| n.max_size >= size)?;
+        if node.start < min {
+            return Self::first_fit(&node.right, min, size);
+        }
+        if let Some(found) = Self::first_fit(&node.left, min, size) {
+            return Some(found);
+        }
+        if node.size >= size {
+            return Some(node);
+        }
+        Self::first_fit(&node.right, min, size)
+    }
+}
+
+/// 地址空间中的空洞集合
+///
+/// 以空洞的起始地址为键，并在每个节点上记录子树中最大的空洞大小，
+/// 寻找空闲地址时可以跳过整棵放不下请求的子树。
+#[derive(Debug, Clone, Default)]
+pub struct VmGapTree {
+    root: Option<Box<GapNode>>,
+}
+
+impl VmGapTree {
+    pub const fn new() -> Self {
+        Self { root: None }
+    }
+
+    /// 插入一个空洞，起始地址已经存在时更新它的大小
+    pub fn insert(&mut self, start: VirtAddr, size: usize) {
+        self.root = Some(GapNode::insert(self.root.take(), start, size));
+    }
+
+    /// 删除起始地址为`start`的空洞，返回它的大小
+    pub fn remove(&mut self, start: VirtAddr) -> Option<usize> {
+        let (root, removed) = GapNode::remove(self.root.take(), start);
+        self.root = root;
+        removed
+    }
+
+    /// 查找起始地址不大于`addr`的最后一个空洞
+    pub fn floor(&self, addr: VirtAddr) -> Option<(VirtAddr, usize)> {
+        let mut cur = self.root.as_ref();
+        let mut found = None;
+        while let Some(node) = cur {
+            if node.start <= addr {
+                found = Some((node.start, node.size));
+                cur = node.right.as_ref();
+            } else {
+                cur = node.left.as_ref();
+            }
+        }
+        found
+    }
+
+    /// 查找起始地址小于`addr`的最后一个空洞
+    pub fn lower(&self, addr: VirtAddr) -> Option<(VirtAddr, usize)> {
+        let mut cur = self.root.as_ref();
+        let mut found = None;
+        while let Some(node) = cur {
+            if node.start < addr {
+                found = Some((node.start, node.size));
+                cur = node.right.as_ref();
+            } else {
+                cur = node.left.as_ref();
+            }
+        }
+        found
+    }
+
+    /// 寻找起始地址不小于`min`、大小不小于`size`的最低空洞
+    pub fn first_fit(&self, min: VirtAddr, size: usize) -> Option<(VirtAddr, usize)> {
+        GapNode::first_fit(&self.root, min, size).map(|n| (n.start, n.size))
+    }
+}
@@ -0,0 +1,170 @@
+//! 脏页写回
+//!
+//! `writeback`内核线程每隔[`dirty_writeback_centisecs`]把页缓存和块缓存中的脏数据写回设备。
+//! 写入者在产生脏页之后调用[`balance_dirty_pages`]：
+//! - 脏页超过可写回内存的`dirty_background_ratio`时，立即唤醒写回线程；
+//! - 超过`dirty_ratio`时，写入者暂停，等待写回线程把脏页降下来，避免脏页占满内存。
+//!
+//! 阈值可以通过`/proc/sys/vm/dirty_*`调整，含义与Linux相同。
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use alloc::{string::ToString, sync::Arc};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    arch::{mm::LockedFrameAllocator, MMArch},
+    driver::base::block::{block_device::LBA_SIZE, cache::block_cache},
+    filesystem::vfs::timestamps::lazy_times,
+    init::initcall::INITCALL_CORE,
+    mm::{
+        allocator::page_frame::FrameAllocator, page::page_reclaimer_lock, page_cache_stats,
+        MemoryManagementArch,
+    },
+    process::{
+        kthread::{KernelThreadClosure, KernelThreadMechanism},
+        ProcessControlBlock, ProcessManager,
+    },
+    time::{clocksource::HZ, timer::schedule_timeout},
+};
+
+/// 脏页占可写回内存的百分比超过这个值时，写入者被暂停
+static DIRTY_RATIO: AtomicU32 = AtomicU32::new(20);
+/// 脏页占可写回内存的百分比超过这个值时，开始后台写回
+static DIRTY_BACKGROUND_RATIO: AtomicU32 = AtomicU32::new(10);
+/// 定期写回的间隔，单位为百分之一秒
+static DIRTY_WRITEBACK_CENTISECS: AtomicU32 = AtomicU32::new(500);
+
+/// 写入者每次最多暂停的时间（jiffies）
+const MAX_PAUSE_JIFFIES: u64 = HZ / 5;
+
+/// 有写入者要求立即写回
+static WAKEUP_PENDING: AtomicBool = AtomicBool::new(false);
+static mut WRITEBACK_THREAD: Option<Arc<ProcessControlBlock>> = None;
+
+pub fn dirty_ratio() -> u32 {
+    DIRTY_RATIO.load(Ordering::Relaxed)
+}
+
+/// 设置`dirty_ratio`，取值范围为0~100
+pub fn set_dirty_ratio(ratio: u32) -> Result<(), SystemError> {
+    if ratio > 100 {
+        return Err(SystemError::EINVAL);
+    }
+    DIRTY_RATIO.store(ratio, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn dirty_background_ratio() -> u32 {
+    DIRTY_BACKGROUND_RATIO.load(Ordering::Relaxed)
+}
+
+/// 设置`dirty_background_ratio`，取值范围为0~100
+pub fn set_dirty_background_ratio(ratio: u32) -> Result<(), SystemError> {
+    if ratio > 100 {
+        return Err(SystemError::EINVAL);
+    }
+    DIRTY_BACKGROUND_RATIO.store(ratio, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn dirty_writeback_centisecs() -> u32 {
+    DIRTY_WRITEBACK_CENTISECS.load(Ordering::Relaxed)
+}
+
+/// 设置定期写回的间隔，为0时只在脏页超过阈值或者sync时写回
+pub fn set_dirty_writeback_centisecs(centisecs: u32) {
+    DIRTY_WRITEBACK_CENTISECS.store(centisecs, Ordering::Relaxed);
+    wakeup_flusher();
+}
+
+/// 当前的脏页数，包括块缓存中的脏扇区
+fn dirty_pages() -> u64 {
+    let stats = page_cache_stats::snapshot();
+    let sectors = block_cache().dirty_sectors() as u64;
+    stats.file_dirty + stats.file_writeback + sectors * LBA_SIZE as u64 / MMArch::PAGE_SIZE as u64
+}
+
+/// 计算后台写回和暂停写入者的阈值（页数）
+///
+/// 与Linux一样以空闲内存加上页缓存作为可写回内存
+fn dirty_thresholds() -> (u64, u64) {
+    let free = unsafe { LockedFrameAllocator.usage() }.free().data() as u64;
+    let dirtyable = free + page_cache_stats::snapshot().file_pages;
+    let limit = dirtyable * dirty_ratio() as u64 / 100;
+    let mut background = dirtyable * dirty_background_ratio() as u64 / 100;
+    // 后台阈值必须低于上限，否则写入者会在后台写回开始之前就被暂停
+    if background >= limit {
+        background = limit / 2;
+    }
+    (background, limit)
+}
+
+/// 唤醒写回线程
+pub fn wakeup_flusher() {
+    WAKEUP_PENDING.store(true, Ordering::Release);
+    if let Some(thread) = unsafe { WRITEBACK_THREAD.as_ref() } {
+        let _ = ProcessManager::wakeup(thread);
+    }
+}
+
+/// 产生脏页之后调用，必要时唤醒写回线程或者暂停当前进程
+///
+/// 调用者不能持有写回时需要的锁（inode、页缓存等）
+pub fn balance_dirty_pages() {
+    let (background, limit) = dirty_thresholds();
+    let dirty = dirty_pages();
+    if dirty <= background {
+        return;
+    }
+    wakeup_flusher();
+    if dirty <= limit {
+        return;
+    }
+
+    // 每次暂停一个tick，直到脏页降到上限以下，最多暂停MAX_PAUSE_JIFFIES
+    for _ in 0..MAX_PAUSE_JIFFIES {
+        let _ = schedule_timeout(1);
+        if dirty_pages() <= limit {
+            break;
+        }
+    }
+}
+
+/// 把页缓存和块缓存中的所有脏数据写回
+///
+/// 页缓存写回时会写到块缓存中，因此先写页缓存
+pub fn writeback_all() {
+    page_reclaimer_lock().flush_dirty_pages();
+    block_cache().writeback_all();
+}
+
+fn writeback_thread() -> i32 {
+    loop {
+        if !WAKEUP_PENDING.swap(false, Ordering::AcqRel) {
+            let centisecs = dirty_writeback_centisecs();
+            let timeout = if centisecs == 0 {
+                // 一直睡眠，直到被唤醒
+                i64::MAX
+            } else {
+                (centisecs as u64 * HZ / 100).max(1) as i64
+            };
+            let _ = schedule_timeout(timeout);
+            WAKEUP_PENDING.store(false, Ordering::Release);
+        }
+        writeback_all();
+        lazy_times().flush_expired();
+    }
+}
+
+#[unified_init(INITCALL_CORE)]
+fn writeback_thread_init() -> Result<(), SystemError> {
+    let closure = KernelThreadClosure::StaticEmptyClosure((&(writeback_thread as fn() -> i32), ()));
+    let pcb = KernelThreadMechanism::create_and_run(closure, "writeback".to_string())
+        .ok_or(SystemError::ENOMEM)?;
+    unsafe {
+        WRITEBACK_THREAD = Some(pcb);
+    }
+    Ok(())
+}
@@ -0,0 +1,138 @@
+//! HCI协议的常量和包格式
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/include/net/bluetooth/hci.h
+
+use system_error::SystemError;
+
+use crate::filesystem::vfs::ioctl::{ior, iow};
+
+/// HCI包的类型，H4传输层和raw套接字中位于包的第一个字节
+pub const HCI_COMMAND_PKT: u8 = 0x01;
+pub const HCI_ACLDATA_PKT: u8 = 0x02;
+pub const HCI_SCODATA_PKT: u8 = 0x03;
+pub const HCI_EVENT_PKT: u8 = 0x04;
+pub const HCI_ISODATA_PKT: u8 = 0x05;
+pub const HCI_VENDOR_PKT: u8 = 0xff;
+
+pub const HCI_COMMAND_HDR_SIZE: usize = 3;
+pub const HCI_ACL_HDR_SIZE: usize = 4;
+pub const HCI_SCO_HDR_SIZE: usize = 3;
+pub const HCI_EVENT_HDR_SIZE: usize = 2;
+pub const HCI_ISO_HDR_SIZE: usize = 4;
+
+/// 一个HCI包的最大长度（不含包类型）
+pub const HCI_MAX_FRAME_SIZE: usize = 1024 + HCI_ACL_HDR_SIZE;
+
+pub const HCI_EV_CMD_COMPLETE: u8 = 0x0e;
+pub const HCI_EV_CMD_STATUS: u8 = 0x0f;
+
+pub const HCI_OP_RESET: u16 = 0x0c03;
+pub const HCI_OP_READ_BD_ADDR: u16 = 0x1009;
+
+/// 厂商自定义命令的OGF
+pub const HCI_OGF_VENDOR: u16 = 0x3f;
+
+/// 设备号，表示不绑定任何设备
+pub const HCI_DEV_NONE: u16 = 0xffff;
+
+/// raw套接字的通道
+pub const HCI_CHANNEL_RAW: u16 = 0;
+
+/// 控制器所在的总线
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum HciBus {
+    Virtual = 0,
+    Usb = 1,
+    Uart = 3,
+}
+
+pub const HCIDEVUP: u32 = iow::<i32>(b'H', 201);
+pub const HCIDEVDOWN: u32 = iow::<i32>(b'H', 202);
+pub const HCIDEVRESET: u32 = iow::<i32>(b'H', 203);
+pub const HCIDEVRESTAT: u32 = iow::<i32>(b'H', 204);
+pub const HCIGETDEVLIST: u32 = ior::<i32>(b'H', 210);
+pub const HCIGETDEVINFO: u32 = ior::<i32>(b'H', 211);
+
+/// `struct hci_dev_stats`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HciDevStats {
+    pub err_rx: u32,
+    pub err_tx: u32,
+    pub cmd_tx: u32,
+    pub evt_rx: u32,
+    pub acl_tx: u32,
+    pub acl_rx: u32,
+    pub sco_tx: u32,
+    pub sco_rx: u32,
+    pub byte_rx: u32,
+    pub byte_tx: u32,
+}
+
+/// `struct hci_dev_info`，HCIGETDEVINFO的参数
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HciDevInfo {
+    pub dev_id: u16,
+    pub name: [u8; 8],
+    pub bdaddr: [u8; 6],
+    pub flags: u32,
+    pub type_: u8,
+    pub features: [u8; 8],
+    pub pkt_type: u32,
+    pub link_policy: u32,
+    pub link_mode: u32,
+    pub acl_mtu: u16,
+    pub acl_pkts: u16,
+    pub sco_mtu: u16,
+    pub sco_pkts: u16,
+    pub stat: HciDevStats,
+}
+
+/// `struct hci_dev_req`，HCIGETDEVLIST返回的数组元素
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HciDevReq {
+    pub dev_id: u16,
+    pub dev_opt: u32,
+}
+
+/// 命令的操作码
+pub fn hci_opcode(ogf: u16, ocf: u16) -> u16 {
+    (ogf << 10) | (ocf & 0x03ff)
+}
+
+/// 操作码中的OGF
+pub fn hci_opcode_ogf(opcode: u16) -> u16 {
+    opcode >> 10
+}
+
+/// # 计算一个HCI包的完整长度
+///
+/// ## 参数
+/// - `pkt_type`: 包的类型
+/// - `buf`: 包的内容（不含包类型），可以只有一部分
+///
+/// ## 返回值
+/// - `Ok(Some(len))`: 完整的包长度（不含包类型）
+/// - `Ok(None)`: 包头还不完整
+/// - `Err(SystemError::EILSEQ)`: 未知的包类型
+pub fn hci_frame_len(pkt_type: u8, buf: &[u8]) -> Result<Option<usize>, SystemError> {
+    let (hdr_len, data_len): (usize, fn(&[u8]) -> usize) = match pkt_type {
+        HCI_COMMAND_PKT => (HCI_COMMAND_HDR_SIZE, |h| h[2] as usize),
+        HCI_ACLDATA_PKT => (HCI_ACL_HDR_SIZE, |h| {
+            u16::from_le_bytes([h[2], h[3]]) as usize
+        }),
+        HCI_SCODATA_PKT => (HCI_SCO_HDR_SIZE, |h| h[2] as usize),
+        HCI_EVENT_PKT => (HCI_EVENT_HDR_SIZE, |h| h[1] as usize),
+        HCI_ISODATA_PKT => (HCI_ISO_HDR_SIZE, |h| {
+            (u16::from_le_bytes([h[2], h[3]]) & 0x3fff) as usize
+        }),
+        _ => return Err(SystemError::EILSEQ),
+    };
+    if buf.len() < hdr_len {
+        return Ok(None);
+    }
+    Ok(Some(hdr_len + data_len(buf)))
+}
@@ -0,0 +1,340 @@
+//! HCI核心：管理蓝牙控制器
+//!
+//! 传输层驱动实现[`HciTransport`]并调用[`hci_register_dev`]注册控制器，收到的包通过
+//! [`HciDev::recv_frame`]交给核心，核心再分发给绑定到这个控制器的raw套接字。
+//!
+//! 发往控制器的命令先进入队列，按照Command Complete/Command Status事件中的
+//! `Num_HCI_Command_Packets`做流控，同一时刻最多只有一个命令在控制器上执行。
+//! ACL/SCO数据直接交给传输层，没有按照控制器的缓冲区数量做流控。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/net/bluetooth/hci_core.c
+
+use alloc::{collections::VecDeque, format, string::String, sync::Arc, vec::Vec};
+use core::fmt::Debug;
+use system_error::SystemError;
+
+use crate::{libs::spinlock::SpinLock, net::socket::bluetooth::hci_send_to_sock};
+
+use super::hci::{
+    hci_frame_len, HciBus, HciDevInfo, HciDevStats, HCI_ACLDATA_PKT, HCI_COMMAND_PKT,
+    HCI_EVENT_PKT, HCI_EV_CMD_COMPLETE, HCI_EV_CMD_STATUS, HCI_ISODATA_PKT, HCI_OP_READ_BD_ADDR,
+    HCI_OP_RESET, HCI_SCODATA_PKT,
+};
+
+/// 最多注册的控制器数量
+const HCI_MAX_DEV: u16 = 64;
+
+bitflags! {
+    /// 控制器的状态，与Linux的`HCI_UP`等标志位一致
+    pub struct HciDevFlags: u32 {
+        const UP = 1 << 0;
+        const INIT = 1 << 1;
+        const RUNNING = 1 << 2;
+    }
+}
+
+/// 传输层驱动需要实现的操作
+pub trait HciTransport: Debug + Send + Sync {
+    /// 打开控制器，在HCIDEVUP时调用
+    fn open(&self) -> Result<(), SystemError>;
+
+    /// 关闭控制器，在HCIDEVDOWN和注销时调用
+    fn close(&self) -> Result<(), SystemError>;
+
+    /// # 发送一个完整的HCI包
+    ///
+    /// ## 参数
+    /// - `pkt_type`: 包的类型，比如[`HCI_COMMAND_PKT`]
+    /// - `data`: 包的内容，不含包类型
+    fn send(&self, pkt_type: u8, data: &[u8]) -> Result<(), SystemError>;
+}
+
+#[derive(Debug)]
+struct HciDevInner {
+    flags: HciDevFlags,
+    bdaddr: [u8; 6],
+    /// 控制器还能接收的命令数
+    cmd_cnt: u8,
+    cmd_q: VecDeque<Vec<u8>>,
+    stat: HciDevStats,
+}
+
+/// 一个蓝牙控制器
+#[derive(Debug)]
+pub struct HciDev {
+    id: u16,
+    name: String,
+    bus: HciBus,
+    transport: Arc<dyn HciTransport>,
+    inner: SpinLock<HciDevInner>,
+}
+
+impl HciDev {
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// 控制器的名字，`hci<N>`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn flags(&self) -> HciDevFlags {
+        self.inner.lock_irqsave().flags
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.flags().contains(HciDevFlags::RUNNING)
+    }
+
+    /// # 打开控制器
+    ///
+    /// 打开后会发送HCI_Reset和Read_BD_ADDR，控制器的地址在命令完成后更新
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EALREADY)`: 控制器已经打开
+    pub fn open(&self) -> Result<(), SystemError> {
+        {
+            let mut inner = self.inner.lock_irqsave();
+            if inner.flags.contains(HciDevFlags::UP) {
+                return Err(SystemError::EALREADY);
+            }
+            inner.flags.insert(HciDevFlags::INIT);
+        }
+        let ret = self.transport.open();
+        let mut inner = self.inner.lock_irqsave();
+        inner.flags.remove(HciDevFlags::INIT);
+        ret?;
+        inner.flags.insert(HciDevFlags::UP | HciDevFlags::RUNNING);
+        inner.cmd_cnt = 1;
+        inner.cmd_q.clear();
+        drop(inner);
+
+        for opcode in [HCI_OP_RESET, HCI_OP_READ_BD_ADDR] {
+            let mut cmd = Vec::with_capacity(3);
+            cmd.extend_from_slice(&opcode.to_le_bytes());
+            cmd.push(0);
+            self.send_frame(HCI_COMMAND_PKT, &cmd)?;
+        }
+        Ok(())
+    }
+
+    /// # 关闭控制器
+    ///
+    /// 丢弃还没有发送的命令
+    pub fn close(&self) -> Result<(), SystemError> {
+        {
+            let mut inner = self.inner.lock_irqsave();
+            if !inner.flags.contains(HciDevFlags::UP) {
+                return Ok(());
+            }
+            inner.flags.remove(HciDevFlags::UP | HciDevFlags::RUNNING);
+            inner.cmd_q.clear();
+        }
+        self.transport.close()
+    }
+
+    /// 丢弃还没有发送的命令并恢复命令流控，控制器保持打开
+    pub fn reset(&self) -> Result<(), SystemError> {
+        let mut inner = self.inner.lock_irqsave();
+        if !inner.flags.contains(HciDevFlags::RUNNING) {
+            return Err(SystemError::ENETDOWN);
+        }
+        inner.cmd_q.clear();
+        inner.cmd_cnt = 1;
+        Ok(())
+    }
+
+    pub fn reset_stat(&self) {
+        self.inner.lock_irqsave().stat = HciDevStats::default();
+    }
+
+    /// HCIGETDEVINFO返回的信息
+    pub fn info(&self) -> HciDevInfo {
+        let inner = self.inner.lock_irqsave();
+        let mut info = HciDevInfo {
+            dev_id: self.id,
+            bdaddr: inner.bdaddr,
+            flags: inner.flags.bits(),
+            type_: self.bus as u8,
+            stat: inner.stat,
+            ..Default::default()
+        };
+        let len = self.name.len().min(info.name.len() - 1);
+        info.name[..len].copy_from_slice(&self.name.as_bytes()[..len]);
+        info
+    }
+
+    /// # 向控制器发送一个包
+    ///
+    /// ## 参数
+    /// - `pkt_type`: 包的类型
+    /// - `data`: 完整的包，不含包类型
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::ENETDOWN)`: 控制器没有打开
+    /// - `Err(SystemError::EINVAL)`: 包的长度与包头不一致
+    pub fn send_frame(&self, pkt_type: u8, data: &[u8]) -> Result<(), SystemError> {
+        if !self.is_running() {
+            return Err(SystemError::ENETDOWN);
+        }
+        match hci_frame_len(pkt_type, data) {
+            Ok(Some(len)) if len == data.len() => {}
+            _ => return Err(SystemError::EINVAL),
+        }
+
+        if pkt_type == HCI_COMMAND_PKT {
+            self.inner.lock_irqsave().cmd_q.push_back(data.to_vec());
+            self.run_cmd_queue();
+            return Ok(());
+        }
+        self.transport.send(pkt_type, data).inspect_err(|_| {
+            self.inner.lock_irqsave().stat.err_tx += 1;
+        })?;
+        let mut inner = self.inner.lock_irqsave();
+        match pkt_type {
+            HCI_ACLDATA_PKT => inner.stat.acl_tx += 1,
+            HCI_SCODATA_PKT => inner.stat.sco_tx += 1,
+            _ => {}
+        }
+        inner.stat.byte_tx += data.len() as u32;
+        Ok(())
+    }
+
+    /// 控制器可以接收命令时，从队列中取出命令发送
+    fn run_cmd_queue(&self) {
+        loop {
+            let cmd = {
+                let mut inner = self.inner.lock_irqsave();
+                if inner.cmd_cnt == 0 {
+                    return;
+                }
+                let Some(cmd) = inner.cmd_q.pop_front() else {
+                    return;
+                };
+                inner.cmd_cnt -= 1;
+                cmd
+            };
+            let ret = self.transport.send(HCI_COMMAND_PKT, &cmd);
+            let mut inner = self.inner.lock_irqsave();
+            match ret {
+                Ok(()) => {
+                    inner.stat.cmd_tx += 1;
+                    inner.stat.byte_tx += cmd.len() as u32;
+                }
+                Err(e) => {
+                    // 控制器不会回应这个命令，把流控恢复
+                    log::warn!("{}: failed to send command: {:?}", self.name, e);
+                    inner.stat.err_tx += 1;
+                    inner.cmd_cnt = 1;
+                }
+            }
+        }
+    }
+
+    /// # 传输层收到一个包
+    ///
+    /// 可以在中断上下文中调用
+    ///
+    /// ## 参数
+    /// - `pkt_type`: 包的类型
+    /// - `data`: 完整的包，不含包类型
+    pub fn recv_frame(&self, pkt_type: u8, data: &[u8]) {
+        if !self.is_running() {
+            return;
+        }
+        let valid = matches!(hci_frame_len(pkt_type, data), Ok(Some(len)) if len == data.len());
+        {
+            let mut inner = self.inner.lock_irqsave();
+            if !valid {
+                inner.stat.err_rx += 1;
+                return;
+            }
+            match pkt_type {
+                HCI_EVENT_PKT => inner.stat.evt_rx += 1,
+                HCI_ACLDATA_PKT | HCI_ISODATA_PKT => inner.stat.acl_rx += 1,
+                HCI_SCODATA_PKT => inner.stat.sco_rx += 1,
+                _ => {}
+            }
+            inner.stat.byte_rx += data.len() as u32;
+        }
+
+        if pkt_type == HCI_EVENT_PKT {
+            self.handle_event(data);
+        }
+        hci_send_to_sock(self.id, pkt_type, data);
+    }
+
+    /// 处理命令完成事件，更新命令流控
+    fn handle_event(&self, data: &[u8]) {
+        let params = &data[2..];
+        let ncmd = match data[0] {
+            HCI_EV_CMD_COMPLETE if params.len() >= 3 => {
+                let opcode = u16::from_le_bytes([params[1], params[2]]);
+                // 返回参数为Status(1) + BD_ADDR(6)
+                if opcode == HCI_OP_READ_BD_ADDR && params.len() >= 10 && params[3] == 0 {
+                    self.inner
+                        .lock_irqsave()
+                        .bdaddr
+                        .copy_from_slice(&params[4..10]);
+                }
+                params[0]
+            }
+            HCI_EV_CMD_STATUS if params.len() >= 4 => params[1],
+            _ => return,
+        };
+        if ncmd > 0 {
+            self.inner.lock_irqsave().cmd_cnt = 1;
+            self.run_cmd_queue();
+        }
+    }
+}
+
+static HCI_DEVS: SpinLock<Vec<Arc<HciDev>>> = SpinLock::new(Vec::new());
+
+/// # 注册一个控制器
+///
+/// 控制器注册后处于关闭状态，由用户态通过HCIDEVUP打开
+///
+/// ## 返回值
+/// - `Err(SystemError::ENFILE)`: 控制器的编号已经用完
+pub fn hci_register_dev(
+    bus: HciBus,
+    transport: Arc<dyn HciTransport>,
+) -> Result<Arc<HciDev>, SystemError> {
+    let mut devs = HCI_DEVS.lock_irqsave();
+    let id = (0..HCI_MAX_DEV)
+        .find(|id| devs.iter().all(|d| d.id != *id))
+        .ok_or(SystemError::ENFILE)?;
+    let hdev = Arc::new(HciDev {
+        id,
+        name: format!("hci{}", id),
+        bus,
+        transport,
+        inner: SpinLock::new(HciDevInner {
+            flags: HciDevFlags::empty(),
+            bdaddr: [0; 6],
+            cmd_cnt: 1,
+            cmd_q: VecDeque::new(),
+            stat: HciDevStats::default(),
+        }),
+    });
+    devs.push(hdev.clone());
+    log::info!("bluetooth: registered {} ({:?})", hdev.name, bus);
+    Ok(hdev)
+}
+
+/// 注销控制器，控制器会先被关闭
+pub fn hci_unregister_dev(hdev: &Arc<HciDev>) {
+    let _ = hdev.close();
+    HCI_DEVS.lock_irqsave().retain(|d| !Arc::ptr_eq(d, hdev));
+    log::info!("bluetooth: unregistered {}", hdev.name);
+}
+
+pub fn hci_dev_get(id: u16) -> Option<Arc<HciDev>> {
+    HCI_DEVS.lock_irqsave().iter().find(|d| d.id == id).cloned()
+}
+
+pub fn hci_devices() -> Vec<Arc<HciDev>> {
+    HCI_DEVS.lock_irqsave().clone()
+}
@@ -0,0 +1,8 @@
+//! 蓝牙协议栈
+//!
+//! 目前只实现了HCI层：[`hci_core`]管理控制器并负责HCI包的收发，传输层驱动位于
+//! `driver/bluetooth`，用户态通过AF_BLUETOOTH的HCI raw套接字直接收发HCI包。
+//! L2CAP以及更上层的协议还没有在内核中实现，需要由用户态在HCI之上完成。
+
+pub mod hci;
+pub mod hci_core;
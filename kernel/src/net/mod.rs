@@ -6,6 +6,7 @@ use core::sync::atomic::AtomicUsize;
 
 use crate::driver::net::Iface;
 
+pub mod bluetooth;
 pub mod neighbor;
 pub mod net_core;
 pub mod posix;
@@ -14,6 +15,7 @@ pub mod socket;
 pub mod syscall;
 pub mod tcp_close_defer;
 pub mod tcp_listener_backlog;
+pub mod wireless;
 
 /// 生成网络接口的id (全局自增)
 pub fn generate_iface_id() -> usize {
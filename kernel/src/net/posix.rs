@@ -47,6 +47,7 @@ use super::socket::{
     endpoint::{Endpoint, LinkLayerEndpoint},
     AddressFamily,
 };
+use crate::net::socket::bluetooth::addr::HciEndpoint;
 use crate::net::socket::netlink::addr::{multicast::GroupIdSet, NetlinkSocketAddr};
 use crate::net::socket::unix::UnixEndpoint;
 use crate::net::socket::vsock::addr::VsockEndpoint;
@@ -128,6 +129,15 @@ pub struct SockAddrVm {
     pub svm_zero: [u8; 3],
 }
 
+/// struct sockaddr_hci for AF_BLUETOOTH/BTPROTO_HCI.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SockAddrHci {
+    pub hci_family: u16,
+    pub hci_dev: u16,
+    pub hci_channel: u16,
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct SockAddrPlaceholder {
@@ -145,6 +155,7 @@ pub union SockAddr {
     pub addr_ll: SockAddrLl,
     pub addr_nl: SockAddrNl,
     pub addr_vm: SockAddrVm,
+    pub addr_hci: SockAddrHci,
     pub addr_ph: SockAddrPlaceholder,
 }
 
@@ -235,6 +246,18 @@ impl From<VsockEndpoint> for SockAddr {
     }
 }
 
+impl From<HciEndpoint> for SockAddr {
+    fn from(value: HciEndpoint) -> Self {
+        SockAddr {
+            addr_hci: SockAddrHci {
+                hci_family: AddressFamily::Bluetooth as u16,
+                hci_dev: value.dev,
+                hci_channel: value.channel,
+            },
+        }
+    }
+}
+
 impl From<LinkLayerEndpoint> for SockAddr {
     fn from(value: LinkLayerEndpoint) -> Self {
         SockAddr {
@@ -265,6 +288,7 @@ impl From<Endpoint> for SockAddr {
             Endpoint::Unix(unix_endpoint) => Self::from(unix_endpoint),
             Endpoint::Netlink(netlink_addr) => Self::from(netlink_addr),
             Endpoint::Vsock(vsock_endpoint) => Self::from(vsock_endpoint),
+            Endpoint::Hci(hci_endpoint) => Self::from(hci_endpoint),
         }
     }
 }
@@ -475,6 +499,22 @@ impl SockAddr {
                     port: addr_vm.svm_port,
                 }))
             }
+            AddressFamily::Bluetooth => {
+                if len < size_of::<SockAddrHci>() as u32 {
+                    log::error!(
+                        "len {} < sizeof(sockaddr_hci) {}",
+                        len,
+                        size_of::<SockAddrHci>()
+                    );
+                    return Err(SystemError::EINVAL);
+                }
+
+                let addr_hci = reader.buffer_protected(0)?.read_one::<SockAddrHci>(0)?;
+                Ok(Endpoint::Hci(HciEndpoint::new(
+                    addr_hci.hci_dev,
+                    addr_hci.hci_channel,
+                )))
+            }
             _ => {
                 log::warn!("not support address family {:?}", family);
                 return Err(SystemError::EINVAL);
@@ -491,6 +531,7 @@ impl SockAddr {
             AddressFamily::Netlink => Ok(core::mem::size_of::<SockAddrNl>()),
             AddressFamily::Unix => Ok(core::mem::size_of::<SockAddrUn>()),
             AddressFamily::Vsock => Ok(core::mem::size_of::<SockAddrVm>()),
+            AddressFamily::Bluetooth => Ok(core::mem::size_of::<SockAddrHci>()),
             _ => Err(SystemError::EINVAL),
         }
         .map(|x| x as u32)
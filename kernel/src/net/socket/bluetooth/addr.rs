@@ -0,0 +1,13 @@
+/// HCI套接字的地址（`struct sockaddr_hci`），由`(设备号, 通道)`组成
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HciEndpoint {
+    /// 控制器编号，`HCI_DEV_NONE`表示不绑定控制器
+    pub dev: u16,
+    pub channel: u16,
+}
+
+impl HciEndpoint {
+    pub const fn new(dev: u16, channel: u16) -> Self {
+        Self { dev, channel }
+    }
+}
@@ -0,0 +1,474 @@
+//! HCI raw套接字
+//!
+//! 发送的数据第一个字节为包类型，后面是完整的HCI包；收到的数据格式相同。
+//! 控制器收到的包按照套接字的过滤器（HCI_FILTER）分发，默认的过滤器不接收任何包。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/net/bluetooth/hci_sock.c
+
+use alloc::{
+    collections::VecDeque,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use system_error::SystemError;
+
+use crate::{
+    filesystem::{
+        epoll::EPollEventType,
+        vfs::{
+            fasync::FAsyncItems, iov::IoVecs, vcore::generate_inode_id, FilePrivateData, InodeId,
+        },
+    },
+    libs::{spinlock::SpinLock, wait_queue::WaitQueue},
+    net::{
+        bluetooth::{
+            hci::{
+                HciDevInfo, HciDevReq, HCIDEVDOWN, HCIDEVRESET, HCIDEVRESTAT, HCIDEVUP,
+                HCIGETDEVINFO, HCIGETDEVLIST, HCI_ACLDATA_PKT, HCI_CHANNEL_RAW, HCI_COMMAND_PKT,
+                HCI_DEV_NONE, HCI_EVENT_PKT, HCI_EV_CMD_COMPLETE, HCI_EV_CMD_STATUS,
+                HCI_ISODATA_PKT, HCI_MAX_FRAME_SIZE, HCI_SCODATA_PKT,
+            },
+            hci_core::{hci_dev_get, hci_devices, HciDev},
+        },
+        posix::{MsgHdr, SockAddr},
+        socket::{common::EPollItems, endpoint::Endpoint, Socket, PMSG, PSOCK, PSOL},
+    },
+    process::{cred::CAPFlags, ProcessManager},
+    syscall::user_access::{UserBufferReader, UserBufferWriter},
+};
+
+use super::addr::HciEndpoint;
+
+/// 接收队列最多缓存的包数
+const HCI_RX_QUEUE_LEN: usize = 256;
+
+/// SOL_HCI与SOL_IP的值都为0
+const HCI_DATA_DIR: usize = 1;
+const HCI_FILTER: usize = 2;
+const HCI_TIME_STAMP: usize = 3;
+
+/// `struct hci_ufilter`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct HciFilter {
+    /// 按包类型过滤，第n位对应包类型n，厂商包对应第31位
+    type_mask: u32,
+    /// 按事件码过滤
+    event_mask: [u32; 2],
+    /// 不为0时只接收这个命令的Command Complete/Command Status事件
+    opcode: u16,
+}
+
+impl HciFilter {
+    fn is_filtered(&self, pkt_type: u8, data: &[u8]) -> bool {
+        let flt_type = (pkt_type & 31) as u32;
+        if self.type_mask & (1 << flt_type) == 0 {
+            return true;
+        }
+        if pkt_type != HCI_EVENT_PKT {
+            return false;
+        }
+        let event = (data[0] & 63) as usize;
+        if self.event_mask[event / 32] & (1 << (event % 32)) == 0 {
+            return true;
+        }
+        if self.opcode == 0 {
+            return false;
+        }
+        let opcode = match data[0] {
+            HCI_EV_CMD_COMPLETE if data.len() >= 5 => u16::from_le_bytes([data[3], data[4]]),
+            HCI_EV_CMD_STATUS if data.len() >= 6 => u16::from_le_bytes([data[4], data[5]]),
+            _ => return false,
+        };
+        opcode != self.opcode
+    }
+}
+
+/// 绑定了控制器的套接字
+static HCI_SOCKETS: SpinLock<Vec<Weak<HciRawSocket>>> = SpinLock::new(Vec::new());
+
+/// 把控制器收到的包分发给绑定到这个控制器的raw套接字
+pub fn hci_send_to_sock(dev_id: u16, pkt_type: u8, data: &[u8]) {
+    let sockets = HCI_SOCKETS.lock_irqsave();
+    for sock in sockets.iter().filter_map(Weak::upgrade) {
+        if sock.bound_dev() == Some(dev_id) {
+            sock.deliver(pkt_type, data);
+        }
+    }
+}
+
+#[cast_to([sync] Socket)]
+#[derive(Debug)]
+pub struct HciRawSocket {
+    bound: SpinLock<Option<HciEndpoint>>,
+    filter: SpinLock<HciFilter>,
+    rx_queue: SpinLock<VecDeque<Vec<u8>>>,
+    nonblock: AtomicBool,
+    wait_queue: WaitQueue,
+    inode_id: InodeId,
+    open_files: AtomicUsize,
+    self_ref: Weak<Self>,
+    epoll_items: EPollItems,
+    fasync_items: FAsyncItems,
+}
+
+impl HciRawSocket {
+    pub fn new(socket_type: PSOCK, nonblock: bool) -> Result<Arc<Self>, SystemError> {
+        if socket_type != PSOCK::Raw {
+            return Err(SystemError::ESOCKTNOSUPPORT);
+        }
+        Ok(Arc::new_cyclic(|me| Self {
+            bound: SpinLock::new(None),
+            filter: SpinLock::new(HciFilter::default()),
+            rx_queue: SpinLock::new(VecDeque::new()),
+            nonblock: AtomicBool::new(nonblock),
+            wait_queue: WaitQueue::default(),
+            inode_id: generate_inode_id(),
+            open_files: AtomicUsize::new(0),
+            self_ref: me.clone(),
+            epoll_items: EPollItems::default(),
+            fasync_items: FAsyncItems::default(),
+        }))
+    }
+
+    fn is_nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
+    /// 绑定的控制器编号
+    fn bound_dev(&self) -> Option<u16> {
+        self.bound
+            .lock_irqsave()
+            .map(|ep| ep.dev)
+            .filter(|dev| *dev != HCI_DEV_NONE)
+    }
+
+    fn bound_hdev(&self) -> Result<Arc<HciDev>, SystemError> {
+        let dev = self.bound_dev().ok_or(SystemError::EBADFD)?;
+        hci_dev_get(dev).ok_or(SystemError::ENODEV)
+    }
+
+    fn deliver(&self, pkt_type: u8, data: &[u8]) {
+        if self.filter.lock_irqsave().is_filtered(pkt_type, data) {
+            return;
+        }
+        let mut rx_queue = self.rx_queue.lock_irqsave();
+        if rx_queue.len() >= HCI_RX_QUEUE_LEN {
+            return;
+        }
+        let mut pkt = Vec::with_capacity(data.len() + 1);
+        pkt.push(pkt_type);
+        pkt.extend_from_slice(data);
+        rx_queue.push_back(pkt);
+        drop(rx_queue);
+        self.wait_queue.wakeup(None);
+    }
+
+    fn can_recv(&self) -> bool {
+        !self.rx_queue.lock_irqsave().is_empty()
+    }
+
+    /// 返回(复制的长度, 包的原始长度)
+    fn try_recv(&self, buf: &mut [u8], flags: PMSG) -> Result<(usize, usize), SystemError> {
+        let mut rx_queue = self.rx_queue.lock_irqsave();
+        let Some(pkt) = rx_queue.front() else {
+            return Err(SystemError::EAGAIN_OR_EWOULDBLOCK);
+        };
+        let len = pkt.len();
+        let copied = buf.len().min(len);
+        buf[..copied].copy_from_slice(&pkt[..copied]);
+        if !flags.contains(PMSG::PEEK) {
+            rx_queue.pop_front();
+        }
+        Ok((copied, len))
+    }
+
+    fn recv_inner(&self, buf: &mut [u8], flags: PMSG) -> Result<(usize, usize), SystemError> {
+        if self.is_nonblock() || flags.contains(PMSG::DONTWAIT) {
+            return self.try_recv(buf, flags);
+        }
+        loop {
+            match self.try_recv(buf, flags) {
+                Err(SystemError::EAGAIN_OR_EWOULDBLOCK) => {
+                    wq_wait_event_interruptible!(self.wait_queue, self.can_recv(), {})?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn do_send(&self, buf: &[u8]) -> Result<usize, SystemError> {
+        if buf.len() < 4 || buf.len() > HCI_MAX_FRAME_SIZE + 1 {
+            return Err(SystemError::EINVAL);
+        }
+        let hdev = self.bound_hdev()?;
+        if !ProcessManager::current_pcb()
+            .cred()
+            .has_capability(CAPFlags::CAP_NET_RAW)
+        {
+            return Err(SystemError::EPERM);
+        }
+        match buf[0] {
+            HCI_COMMAND_PKT | HCI_ACLDATA_PKT | HCI_SCODATA_PKT | HCI_ISODATA_PKT => {}
+            _ => return Err(SystemError::EINVAL),
+        }
+        hdev.send_frame(buf[0], &buf[1..])?;
+        Ok(buf.len())
+    }
+
+    fn get_dev_list(arg: usize) -> Result<usize, SystemError> {
+        let reader = UserBufferReader::new(arg as *const u16, 2, true)?;
+        let dev_num = reader.buffer_protected(0)?.read_one::<u16>(0)? as usize;
+        if dev_num == 0 {
+            return Err(SystemError::EINVAL);
+        }
+
+        // struct hci_dev_list_req的dev_req数组从第4个字节开始
+        let devs = hci_devices();
+        let n = devs.len().min(dev_num);
+        let entry_size = core::mem::size_of::<HciDevReq>();
+        let mut writer = UserBufferWriter::new(arg as *mut u8, 4 + n * entry_size, true)?;
+        let mut buffer = writer.buffer_protected(0)?;
+        buffer.write_one::<u16>(0, &(n as u16))?;
+        for (i, hdev) in devs.iter().take(n).enumerate() {
+            let req = HciDevReq {
+                dev_id: hdev.id(),
+                dev_opt: hdev.flags().bits(),
+            };
+            buffer.write_one::<HciDevReq>(4 + i * entry_size, &req)?;
+        }
+        Ok(0)
+    }
+
+    fn get_dev_info(arg: usize) -> Result<usize, SystemError> {
+        let size = core::mem::size_of::<HciDevInfo>();
+        let reader = UserBufferReader::new(arg as *const u16, 2, true)?;
+        let dev_id = reader.buffer_protected(0)?.read_one::<u16>(0)?;
+        let hdev = hci_dev_get(dev_id).ok_or(SystemError::ENODEV)?;
+        let mut writer = UserBufferWriter::new(arg as *mut HciDevInfo, size, true)?;
+        writer.buffer_protected(0)?.write_one(0, &hdev.info())?;
+        Ok(0)
+    }
+}
+
+impl Socket for HciRawSocket {
+    fn open_file_counter(&self) -> &AtomicUsize {
+        &self.open_files
+    }
+
+    fn wait_queue(&self) -> &WaitQueue {
+        &self.wait_queue
+    }
+
+    fn bind(&self, endpoint: Endpoint) -> Result<(), SystemError> {
+        let Endpoint::Hci(ep) = endpoint else {
+            return Err(SystemError::EINVAL);
+        };
+        if ep.channel != HCI_CHANNEL_RAW {
+            log::warn!("hci socket: channel {} is not supported yet", ep.channel);
+            return Err(SystemError::EINVAL);
+        }
+        if ep.dev != HCI_DEV_NONE && hci_dev_get(ep.dev).is_none() {
+            return Err(SystemError::ENODEV);
+        }
+
+        let mut bound = self.bound.lock_irqsave();
+        if bound.is_some() {
+            return Err(SystemError::EALREADY);
+        }
+        *bound = Some(ep);
+        drop(bound);
+        HCI_SOCKETS.lock_irqsave().push(self.self_ref.clone());
+        Ok(())
+    }
+
+    fn send_buffer_size(&self) -> usize {
+        HCI_MAX_FRAME_SIZE + 1
+    }
+
+    fn recv_buffer_size(&self) -> usize {
+        HCI_RX_QUEUE_LEN * (HCI_MAX_FRAME_SIZE + 1)
+    }
+
+    fn connect(&self, _endpoint: Endpoint) -> Result<(), SystemError> {
+        Err(SystemError::EOPNOTSUPP_OR_ENOTSUP)
+    }
+
+    fn send(&self, buffer: &[u8], _flags: PMSG) -> Result<usize, SystemError> {
+        self.do_send(buffer)
+    }
+
+    fn send_to(
+        &self,
+        buffer: &[u8],
+        _flags: PMSG,
+        _address: Endpoint,
+    ) -> Result<usize, SystemError> {
+        self.do_send(buffer)
+    }
+
+    fn send_msg(&self, msg: &MsgHdr, flags: PMSG) -> Result<usize, SystemError> {
+        let iovs = unsafe { IoVecs::from_user(msg.msg_iov, msg.msg_iovlen, false)? };
+        let data = iovs.gather()?;
+        if msg.msg_name.is_null() || msg.msg_namelen == 0 {
+            self.send(&data, flags)
+        } else {
+            let endpoint = SockAddr::to_endpoint(msg.msg_name as *const SockAddr, msg.msg_namelen)?;
+            self.send_to(&data, flags, endpoint)
+        }
+    }
+
+    fn recv(&self, buffer: &mut [u8], flags: PMSG) -> Result<usize, SystemError> {
+        self.recv_inner(buffer, flags).map(|(len, _)| len)
+    }
+
+    fn read_to_user_buffer(
+        &self,
+        user_buffer: &mut crate::syscall::user_buffer::UserBuffer<'_>,
+    ) -> Result<usize, SystemError> {
+        crate::net::socket::base::read_to_user_buffer_via_kernel_buf(
+            self,
+            user_buffer,
+            HCI_MAX_FRAME_SIZE + 1,
+        )
+    }
+
+    fn recv_from(
+        &self,
+        buffer: &mut [u8],
+        flags: PMSG,
+        _address: Option<Endpoint>,
+    ) -> Result<(usize, Endpoint), SystemError> {
+        let (len, _) = self.recv_inner(buffer, flags)?;
+        Ok((len, self.local_endpoint()?))
+    }
+
+    fn recv_msg(&self, msg: &mut MsgHdr, flags: PMSG) -> Result<usize, SystemError> {
+        let iovs = unsafe { IoVecs::from_user(msg.msg_iov, msg.msg_iovlen, true)? };
+        let mut buf = iovs.new_buf(true);
+        let (copied, len) = self.recv_inner(&mut buf, flags)?;
+        iovs.scatter(&buf[..copied])?;
+
+        if !msg.msg_name.is_null() {
+            let endpoint = self.local_endpoint()?;
+            msg.msg_namelen = endpoint.write_to_user_msghdr(msg.msg_name, msg.msg_namelen)?;
+        } else {
+            msg.msg_namelen = 0;
+        }
+        msg.msg_controllen = 0;
+        msg.msg_flags = 0;
+        if len > copied {
+            msg.msg_flags |= PMSG::TRUNC.bits() as i32;
+        }
+        Ok(copied)
+    }
+
+    fn do_close(&self) -> Result<(), SystemError> {
+        HCI_SOCKETS
+            .lock_irqsave()
+            .retain(|s| s.strong_count() > 0 && !Weak::ptr_eq(s, &self.self_ref));
+        Ok(())
+    }
+
+    fn local_endpoint(&self) -> Result<Endpoint, SystemError> {
+        let ep = self
+            .bound
+            .lock_irqsave()
+            .unwrap_or(HciEndpoint::new(HCI_DEV_NONE, HCI_CHANNEL_RAW));
+        Ok(Endpoint::Hci(ep))
+    }
+
+    fn remote_endpoint(&self) -> Result<Endpoint, SystemError> {
+        Err(SystemError::ENOTCONN)
+    }
+
+    fn epoll_items(&self) -> &EPollItems {
+        &self.epoll_items
+    }
+
+    fn fasync_items(&self) -> &FAsyncItems {
+        &self.fasync_items
+    }
+
+    fn check_io_event(&self) -> EPollEventType {
+        let mut event = EPollEventType::EPOLLOUT | EPollEventType::EPOLLWRNORM;
+        if self.can_recv() {
+            event |= EPollEventType::EPOLLIN | EPollEventType::EPOLLRDNORM;
+        }
+        event
+    }
+
+    fn socket_inode_id(&self) -> InodeId {
+        self.inode_id
+    }
+
+    fn option(&self, level: PSOL, name: usize, value: &mut [u8]) -> Result<usize, SystemError> {
+        if level != PSOL::IP || name != HCI_FILTER {
+            return Err(SystemError::ENOPROTOOPT);
+        }
+        let filter = *self.filter.lock_irqsave();
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                &filter as *const HciFilter as *const u8,
+                core::mem::size_of::<HciFilter>(),
+            )
+        };
+        let len = value.len().min(bytes.len());
+        value[..len].copy_from_slice(&bytes[..len]);
+        Ok(len)
+    }
+
+    fn set_option(&self, level: PSOL, name: usize, val: &[u8]) -> Result<(), SystemError> {
+        if level != PSOL::IP {
+            return Err(SystemError::ENOPROTOOPT);
+        }
+        match name {
+            HCI_FILTER => {
+                let mut filter = HciFilter::default();
+                let bytes = unsafe {
+                    core::slice::from_raw_parts_mut(
+                        &mut filter as *mut HciFilter as *mut u8,
+                        core::mem::size_of::<HciFilter>(),
+                    )
+                };
+                let len = val.len().min(bytes.len());
+                bytes[..len].copy_from_slice(&val[..len]);
+                *self.filter.lock_irqsave() = filter;
+                Ok(())
+            }
+            // 收到的包不附带方向和时间戳的控制信息，接受这两个选项只是为了兼容
+            HCI_DATA_DIR | HCI_TIME_STAMP => Ok(()),
+            _ => Err(SystemError::ENOPROTOOPT),
+        }
+    }
+
+    fn ioctl(
+        &self,
+        cmd: u32,
+        arg: usize,
+        _private_data: &FilePrivateData,
+    ) -> Result<usize, SystemError> {
+        match cmd {
+            HCIGETDEVLIST => Self::get_dev_list(arg),
+            HCIGETDEVINFO => Self::get_dev_info(arg),
+            HCIDEVUP | HCIDEVDOWN | HCIDEVRESET | HCIDEVRESTAT => {
+                if !ProcessManager::current_pcb()
+                    .cred()
+                    .has_capability(CAPFlags::CAP_NET_ADMIN)
+                {
+                    return Err(SystemError::EPERM);
+                }
+                let hdev = hci_dev_get(arg as u16).ok_or(SystemError::ENODEV)?;
+                match cmd {
+                    HCIDEVUP => hdev.open()?,
+                    HCIDEVDOWN => hdev.close()?,
+                    HCIDEVRESET => hdev.reset()?,
+                    _ => hdev.reset_stat(),
+                }
+                Ok(0)
+            }
+            _ => Err(SystemError::ENOIOCTLCMD),
+        }
+    }
+}
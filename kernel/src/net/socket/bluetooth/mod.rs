@@ -0,0 +1,32 @@
+//! AF_BLUETOOTH套接字
+//!
+//! 目前只支持`BTPROTO_HCI`的raw通道，用户态可以通过它直接收发HCI包，
+//! 并通过ioctl打开、关闭和查询控制器。
+
+pub mod addr;
+mod hci;
+
+use alloc::sync::Arc;
+use system_error::SystemError;
+
+use crate::net::socket::{Socket, PSOCK};
+
+pub use hci::hci_send_to_sock;
+
+use self::hci::HciRawSocket;
+
+const BTPROTO_HCI: u32 = 1;
+
+pub fn create_bluetooth_socket(
+    socket_type: PSOCK,
+    protocol: u32,
+    is_nonblock: bool,
+) -> Result<Arc<dyn Socket>, SystemError> {
+    match protocol {
+        BTPROTO_HCI => Ok(HciRawSocket::new(socket_type, is_nonblock)?),
+        _ => {
+            log::warn!("bluetooth protocol {} is not supported yet", protocol);
+            Err(SystemError::EPROTONOSUPPORT)
+        }
+    }
+}
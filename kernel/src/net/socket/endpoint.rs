@@ -2,7 +2,8 @@ use crate::{
     net::{
         posix::SockAddr,
         socket::{
-            netlink::addr::NetlinkSocketAddr, unix::UnixEndpoint, vsock::addr::VsockEndpoint,
+            bluetooth::addr::HciEndpoint, netlink::addr::NetlinkSocketAddr, unix::UnixEndpoint,
+            vsock::addr::VsockEndpoint,
         },
     },
     syscall::user_access::{UserBufferReader, UserBufferWriter},
@@ -24,6 +25,8 @@ pub enum Endpoint {
     Netlink(NetlinkSocketAddr),
     /// Vsock端点
     Vsock(VsockEndpoint),
+    /// 蓝牙HCI端点
+    Hci(HciEndpoint),
 }
 
 /// @brief 链路层端点 (对应 sockaddr_ll)
@@ -106,6 +109,7 @@ impl Endpoint {
             Endpoint::Ip(_) => Ok(SockAddr::from(self.clone()).len()?),
             Endpoint::Netlink(_) => Ok(SockAddr::from(self.clone()).len()?),
             Endpoint::Vsock(_) => Ok(SockAddr::from(self.clone()).len()?),
+            Endpoint::Hci(_) => Ok(SockAddr::from(self.clone()).len()?),
             Endpoint::Unix(unix) => {
                 // Linux AF_UNIX getsockname/getpeername length semantics depend on the
                 // effective address length, not always sizeof(sockaddr_un).
@@ -1,4 +1,5 @@
 mod base;
+pub mod bluetooth;
 pub mod common;
 pub mod endpoint;
 mod family;
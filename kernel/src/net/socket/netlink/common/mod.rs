@@ -155,6 +155,27 @@ where
     pub fn netns(&self) -> Arc<NetNamespace> {
         self.netns.clone()
     }
+
+    /// SOL_NETLINK层的选项，目前只支持加入和退出多播组
+    fn set_netlink_option(&self, name: usize, val: &[u8]) -> Result<(), SystemError> {
+        const NETLINK_ADD_MEMBERSHIP: usize = 1;
+        const NETLINK_DROP_MEMBERSHIP: usize = 2;
+
+        let group = val
+            .get(..4)
+            .map(|v| u32::from_ne_bytes(v.try_into().unwrap()))
+            .ok_or(SystemError::EINVAL)?;
+        if group == 0 || group > 32 {
+            return Err(SystemError::EINVAL);
+        }
+        let groups = GroupIdSet::new(1 << (group - 1));
+        match name {
+            NETLINK_ADD_MEMBERSHIP => self.inner.write().add_groups(groups),
+            NETLINK_DROP_MEMBERSHIP => self.inner.write().drop_groups(groups),
+            _ => return Err(SystemError::ENOPROTOOPT),
+        }
+        Ok(())
+    }
 }
 
 impl<P: SupportedNetlinkProtocol + 'static> Socket for NetlinkSocket<P>
@@ -333,6 +354,9 @@ where
     }
 
     fn set_option(&self, level: PSOL, name: usize, val: &[u8]) -> Result<(), SystemError> {
+        if matches!(level, PSOL::NETLINK) {
+            return self.set_netlink_option(name, val);
+        }
         if !matches!(level, PSOL::SOCKET) {
             return Err(SystemError::ENOPROTOOPT);
         }
@@ -403,7 +427,6 @@ impl<P: SupportedNetlinkProtocol> NetlinkSocket<P> {
 
 // 多播消息的时候会用到，比如uevent
 impl<P: SupportedNetlinkProtocol> Inner<UnboundNetlink<P>, BoundNetlink<P::Message>> {
-    #[allow(unused)]
     fn add_groups(&mut self, groups: GroupIdSet) {
         match self {
             Inner::Bound(bound) => bound.add_groups(groups),
@@ -411,7 +434,6 @@ impl<P: SupportedNetlinkProtocol> Inner<UnboundNetlink<P>, BoundNetlink<P::Messa
         }
     }
 
-    #[allow(unused)]
     fn drop_groups(&mut self, groups: GroupIdSet) {
         match self {
             Inner::Unbound(unbound) => unbound.drop_groups(groups),
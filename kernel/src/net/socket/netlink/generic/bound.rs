@@ -0,0 +1,86 @@
+use crate::{
+    filesystem::epoll::EPollEventType,
+    net::socket::{
+        netlink::{
+            addr::NetlinkSocketAddr,
+            common::bound::BoundNetlink,
+            generic::{genl_rcv, message::GenericNlMessage},
+            table::{NetlinkGenericProtocol, SupportedNetlinkProtocol},
+        },
+        utils::datagram_common,
+        PMSG,
+    },
+};
+use system_error::SystemError;
+
+impl datagram_common::Bound for BoundNetlink<GenericNlMessage> {
+    type Endpoint = NetlinkSocketAddr;
+
+    fn bind(&mut self, endpoint: &Self::Endpoint) -> Result<(), SystemError> {
+        self.bind_common(endpoint)
+    }
+
+    fn local_endpoint(&self) -> Self::Endpoint {
+        self.handle.addr()
+    }
+
+    fn remote_endpoint(&self) -> Option<Self::Endpoint> {
+        Some(self.remote_addr)
+    }
+
+    fn set_remote_endpoint(&mut self, endpoint: &Self::Endpoint) {
+        self.remote_addr = *endpoint;
+    }
+
+    fn try_send(
+        &self,
+        buf: &[u8],
+        to: &Self::Endpoint,
+        _flags: PMSG,
+    ) -> Result<usize, SystemError> {
+        let sent_len = buf.len();
+
+        // 目前只支持发往内核，回复按顺序放入发送者的接收队列
+        if to.port() != 0 {
+            log::warn!("netlink generic: sending to user port is not supported yet");
+            return Err(SystemError::ECONNREFUSED);
+        }
+
+        for reply in genl_rcv(buf, self.handle.port()) {
+            <NetlinkGenericProtocol as SupportedNetlinkProtocol>::unicast(
+                self.handle.port(),
+                GenericNlMessage::new(reply),
+                self.netns(),
+            )?;
+        }
+
+        Ok(sent_len)
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut [u8],
+        flags: PMSG,
+    ) -> Result<(usize, usize, Self::Endpoint), SystemError> {
+        let mut receive_queue = self.receive_queue.0.lock();
+        let Some(message) = receive_queue.front() else {
+            return Err(SystemError::EAGAIN_OR_EWOULDBLOCK);
+        };
+
+        let orig_len = message.as_bytes().len();
+        let copied = writer.len().min(orig_len);
+        if copied > 0 {
+            writer[..copied].copy_from_slice(&message.as_bytes()[..copied]);
+        }
+
+        if !flags.contains(PMSG::PEEK) {
+            receive_queue.pop_front();
+        }
+
+        Ok((copied, orig_len, NetlinkSocketAddr::new_unspecified()))
+    }
+
+    fn check_io_events(&self) -> EPollEventType {
+        self.check_io_events_common()
+    }
+}
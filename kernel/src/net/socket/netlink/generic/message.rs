@@ -0,0 +1,250 @@
+//! Generic Netlink消息的编码与解析
+//!
+//! 消息的格式为`nlmsghdr | genlmsghdr | 属性...`，属性是TLV格式并按4字节对齐，
+//! 嵌套属性的负载是另一组属性。
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use system_error::SystemError;
+
+use crate::net::socket::netlink::table::MulticastMessage;
+
+pub const NLMSG_HDRLEN: usize = 16;
+pub const GENL_HDRLEN: usize = 4;
+const NLA_HDRLEN: usize = 4;
+
+pub const NLMSG_ERROR: u16 = 2;
+pub const NLMSG_DONE: u16 = 3;
+
+pub const NLM_F_REQUEST: u16 = 0x1;
+pub const NLM_F_MULTI: u16 = 0x2;
+pub const NLM_F_ACK: u16 = 0x4;
+pub const NLM_F_DUMP: u16 = 0x300;
+
+const NLA_F_NESTED: u16 = 1 << 15;
+const NLA_F_NET_BYTEORDER: u16 = 1 << 14;
+const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+
+const fn nla_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// 放入接收队列的一条（或者一批）Generic Netlink消息
+#[derive(Debug, Clone)]
+pub struct GenericNlMessage {
+    bytes: Vec<u8>,
+}
+
+impl GenericNlMessage {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl MulticastMessage for GenericNlMessage {}
+
+/// 解析后的一组属性
+#[derive(Debug, Clone, Default)]
+pub struct NlAttrs<'a> {
+    attrs: BTreeMap<u16, &'a [u8]>,
+}
+
+impl<'a> NlAttrs<'a> {
+    /// # 解析一组属性
+    ///
+    /// 同一类型的属性出现多次时，以最后一次为准
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EINVAL)`: 属性的长度不合法
+    pub fn parse(mut buf: &'a [u8]) -> Result<Self, SystemError> {
+        let mut attrs = BTreeMap::new();
+        while buf.len() >= NLA_HDRLEN {
+            let len = u16::from_ne_bytes([buf[0], buf[1]]) as usize;
+            let ty = u16::from_ne_bytes([buf[2], buf[3]]) & NLA_TYPE_MASK;
+            if len < NLA_HDRLEN || len > buf.len() {
+                return Err(SystemError::EINVAL);
+            }
+            attrs.insert(ty, &buf[NLA_HDRLEN..len]);
+            buf = &buf[nla_align(len).min(buf.len())..];
+        }
+        Ok(Self { attrs })
+    }
+
+    pub fn get(&self, ty: u16) -> Option<&'a [u8]> {
+        self.attrs.get(&ty).copied()
+    }
+
+    pub fn has(&self, ty: u16) -> bool {
+        self.attrs.contains_key(&ty)
+    }
+
+    pub fn get_u8(&self, ty: u16) -> Option<u8> {
+        self.get(ty).and_then(|v| v.first().copied())
+    }
+
+    pub fn get_u16(&self, ty: u16) -> Option<u16> {
+        let v = self.get(ty)?;
+        Some(u16::from_ne_bytes(v.get(..2)?.try_into().ok()?))
+    }
+
+    pub fn get_u32(&self, ty: u16) -> Option<u32> {
+        let v = self.get(ty)?;
+        Some(u32::from_ne_bytes(v.get(..4)?.try_into().ok()?))
+    }
+
+    /// 字符串属性，去掉结尾的`\0`
+    pub fn get_str(&self, ty: u16) -> Option<&'a str> {
+        let v = self.get(ty)?;
+        let end = v.iter().position(|&b| b == 0).unwrap_or(v.len());
+        core::str::from_utf8(&v[..end]).ok()
+    }
+
+    pub fn get_nested(&self, ty: u16) -> Result<Option<NlAttrs<'a>>, SystemError> {
+        self.get(ty).map(NlAttrs::parse).transpose()
+    }
+
+    /// 按类型从小到大遍历所有属性，用于解析嵌套的属性数组
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &'a [u8])> + '_ {
+        self.attrs.iter().map(|(ty, v)| (*ty, *v))
+    }
+}
+
+/// 用户发来的一个Generic Netlink请求
+#[derive(Debug, Clone)]
+pub struct GenlRequest<'a> {
+    /// 协议族id，即`nlmsg_type`
+    pub family: u16,
+    pub flags: u16,
+    pub seq: u32,
+    /// 发送者的端口号，回复发往这个端口
+    pub port: u32,
+    pub cmd: u8,
+    pub version: u8,
+    pub attrs: NlAttrs<'a>,
+}
+
+impl GenlRequest<'_> {
+    pub fn is_dump(&self) -> bool {
+        self.flags & NLM_F_DUMP == NLM_F_DUMP
+    }
+
+    /// 为这个请求构造一条回复，dump请求的回复带`NLM_F_MULTI`
+    pub fn reply(&self, cmd: u8, version: u8) -> GenlMsgBuilder {
+        let flags = if self.is_dump() { NLM_F_MULTI } else { 0 };
+        GenlMsgBuilder::new(self.family, flags, self.seq, self.port, cmd, version)
+    }
+}
+
+/// 构造一条Generic Netlink消息
+#[derive(Debug)]
+pub struct GenlMsgBuilder {
+    buf: Vec<u8>,
+}
+
+impl GenlMsgBuilder {
+    pub fn new(family: u16, flags: u16, seq: u32, port: u32, cmd: u8, version: u8) -> Self {
+        let mut buf = Vec::with_capacity(128);
+        buf.extend_from_slice(&0u32.to_ne_bytes());
+        buf.extend_from_slice(&family.to_ne_bytes());
+        buf.extend_from_slice(&flags.to_ne_bytes());
+        buf.extend_from_slice(&seq.to_ne_bytes());
+        buf.extend_from_slice(&port.to_ne_bytes());
+        buf.extend_from_slice(&[cmd, version, 0, 0]);
+        Self { buf }
+    }
+
+    pub fn put(&mut self, ty: u16, data: &[u8]) {
+        let len = (NLA_HDRLEN + data.len()) as u16;
+        self.buf.extend_from_slice(&len.to_ne_bytes());
+        self.buf.extend_from_slice(&ty.to_ne_bytes());
+        self.buf.extend_from_slice(data);
+        self.pad();
+    }
+
+    pub fn put_u8(&mut self, ty: u16, v: u8) {
+        self.put(ty, &[v]);
+    }
+
+    pub fn put_u16(&mut self, ty: u16, v: u16) {
+        self.put(ty, &v.to_ne_bytes());
+    }
+
+    pub fn put_u32(&mut self, ty: u16, v: u32) {
+        self.put(ty, &v.to_ne_bytes());
+    }
+
+    pub fn put_u64(&mut self, ty: u16, v: u64) {
+        self.put(ty, &v.to_ne_bytes());
+    }
+
+    /// 以`\0`结尾的字符串
+    pub fn put_str(&mut self, ty: u16, s: &str) {
+        let mut data = Vec::with_capacity(s.len() + 1);
+        data.extend_from_slice(s.as_bytes());
+        data.push(0);
+        self.put(ty, &data);
+    }
+
+    /// 没有负载的标志属性
+    pub fn put_flag(&mut self, ty: u16) {
+        self.put(ty, &[]);
+    }
+
+    /// 开始一个嵌套属性，返回值交给[`Self::nest_end`]
+    pub fn nest_start(&mut self, ty: u16) -> usize {
+        let start = self.buf.len();
+        self.buf.extend_from_slice(&0u16.to_ne_bytes());
+        self.buf
+            .extend_from_slice(&(ty | NLA_F_NESTED).to_ne_bytes());
+        start
+    }
+
+    pub fn nest_end(&mut self, start: usize) {
+        let len = (self.buf.len() - start) as u16;
+        self.buf[start..start + 2].copy_from_slice(&len.to_ne_bytes());
+    }
+
+    fn pad(&mut self) {
+        let aligned = nla_align(self.buf.len());
+        self.buf.resize(aligned, 0);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let len = self.buf.len() as u32;
+        self.buf[..4].copy_from_slice(&len.to_ne_bytes());
+        self.buf
+    }
+}
+
+/// 构造只有`nlmsghdr`的消息，用于NLMSG_DONE和NLMSG_ERROR
+fn nlmsg_new(ty: u16, flags: u16, seq: u32, port: u32, payload: &[u8]) -> Vec<u8> {
+    let len = (NLMSG_HDRLEN + payload.len()) as u32;
+    let mut buf = Vec::with_capacity(len as usize);
+    buf.extend_from_slice(&len.to_ne_bytes());
+    buf.extend_from_slice(&ty.to_ne_bytes());
+    buf.extend_from_slice(&flags.to_ne_bytes());
+    buf.extend_from_slice(&seq.to_ne_bytes());
+    buf.extend_from_slice(&port.to_ne_bytes());
+    buf.extend_from_slice(payload);
+    buf
+}
+
+/// # 构造NLMSG_ERROR消息
+///
+/// ## 参数
+/// - `req_hdr`: 请求的`nlmsghdr`，会被原样附在错误码之后
+/// - `error`: 0表示确认（ACK），否则为负的错误码
+pub fn nlmsg_error(req_hdr: &[u8], seq: u32, port: u32, error: i32) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(4 + NLMSG_HDRLEN);
+    payload.extend_from_slice(&error.to_ne_bytes());
+    payload.extend_from_slice(&req_hdr[..NLMSG_HDRLEN]);
+    nlmsg_new(NLMSG_ERROR, 0, seq, port, &payload)
+}
+
+/// 构造结束dump的NLMSG_DONE消息
+pub fn nlmsg_done(seq: u32, port: u32) -> Vec<u8> {
+    nlmsg_new(NLMSG_DONE, NLM_F_MULTI, seq, port, &0i32.to_ne_bytes())
+}
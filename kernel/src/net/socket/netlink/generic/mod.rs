@@ -0,0 +1,322 @@
+//! NETLINK_GENERIC协议
+//!
+//! Generic Netlink在同一个netlink协议号上复用多个协议族（family）。协议族在注册时分配id和多播组号，
+//! 用户先通过id为[`GENL_ID_CTRL`]的控制器（`nlctrl`）按名字查询，再向查到的id发送请求。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/net/netlink/genetlink.c
+
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt::Debug;
+use system_error::SystemError;
+
+use crate::{
+    libs::spinlock::SpinLock,
+    net::socket::netlink::{
+        addr::multicast::GroupIdSet,
+        common::NetlinkSocket,
+        table::{NetlinkGenericProtocol, SupportedNetlinkProtocol},
+    },
+    process::namespace::net_namespace::INIT_NET_NAMESPACE,
+};
+
+use self::message::{
+    nlmsg_done, nlmsg_error, GenericNlMessage, GenlRequest, NlAttrs, GENL_HDRLEN, NLMSG_HDRLEN,
+    NLM_F_ACK, NLM_F_REQUEST,
+};
+
+mod bound;
+pub mod message;
+
+pub(super) type NetlinkGenericSocket = NetlinkSocket<NetlinkGenericProtocol>;
+
+/// 控制器的协议族id
+pub const GENL_ID_CTRL: u16 = 0x10;
+/// 多播组号的上限，与netlink套接字表的多播组数一致
+const GENL_MAX_GROUPS: u32 = 32;
+
+/// 一个Generic Netlink协议族
+pub trait GenlFamily: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn version(&self) -> u8;
+
+    /// 支持的最大属性类型
+    fn max_attr(&self) -> u16;
+
+    /// 多播组的名字，组号在注册时分配
+    fn mcast_groups(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// 支持的命令，控制器查询协议族时返回
+    fn commands(&self) -> &'static [u8];
+
+    /// # 处理一个请求
+    ///
+    /// ## 参数
+    /// - `req`: 请求，`req.family`是注册时分配的id
+    /// - `replies`: 要发回给用户的消息，dump请求结尾的NLMSG_DONE和确认消息由调用者追加
+    ///
+    /// ## 返回值
+    /// 出错时用户会收到带有对应错误码的NLMSG_ERROR
+    fn doit(&self, req: &GenlRequest, replies: &mut Vec<Vec<u8>>) -> Result<(), SystemError>;
+}
+
+#[derive(Debug)]
+struct GenlFamilyEntry {
+    id: u16,
+    /// 第一个多播组的组号，其余的组号依次递增
+    first_group: u32,
+    family: Arc<dyn GenlFamily>,
+}
+
+#[derive(Debug)]
+struct GenlRegistry {
+    families: Vec<GenlFamilyEntry>,
+    next_id: u16,
+    next_group: u32,
+}
+
+lazy_static! {
+    static ref GENL_REGISTRY: SpinLock<GenlRegistry> = SpinLock::new(GenlRegistry {
+        families: alloc::vec![GenlFamilyEntry {
+            id: GENL_ID_CTRL,
+            first_group: 1,
+            family: Arc::new(GenlCtrl),
+        }],
+        next_id: GENL_ID_CTRL + 1,
+        next_group: 2,
+    });
+}
+
+/// # 注册一个协议族
+///
+/// ## 返回值
+/// - `Ok(id)`: 分配的协议族id
+/// - `Err(SystemError::EEXIST)`: 同名的协议族已经注册
+/// - `Err(SystemError::ENOSPC)`: 多播组号已经用完
+pub fn genl_register_family(family: Arc<dyn GenlFamily>) -> Result<u16, SystemError> {
+    let mut registry = GENL_REGISTRY.lock();
+    if registry
+        .families
+        .iter()
+        .any(|e| e.family.name() == family.name())
+    {
+        return Err(SystemError::EEXIST);
+    }
+    let nr_groups = family.mcast_groups().len() as u32;
+    if registry.next_group + nr_groups > GENL_MAX_GROUPS + 1 {
+        return Err(SystemError::ENOSPC);
+    }
+
+    let id = registry.next_id;
+    let first_group = registry.next_group;
+    registry.next_id += 1;
+    registry.next_group += nr_groups;
+    registry.families.push(GenlFamilyEntry {
+        id,
+        first_group,
+        family,
+    });
+    Ok(id)
+}
+
+/// # 向协议族的一个多播组广播消息
+///
+/// ## 参数
+/// - `family_id`: 协议族id
+/// - `group`: 多播组的名字
+/// - `msg`: 完整的消息
+pub fn genl_multicast(family_id: u16, group: &str, msg: Vec<u8>) -> Result<(), SystemError> {
+    let group_id = {
+        let registry = GENL_REGISTRY.lock();
+        let entry = registry
+            .families
+            .iter()
+            .find(|e| e.id == family_id)
+            .ok_or(SystemError::ENOENT)?;
+        let index = entry
+            .family
+            .mcast_groups()
+            .iter()
+            .position(|g| *g == group)
+            .ok_or(SystemError::EINVAL)? as u32;
+        entry.first_group + index
+    };
+    <NetlinkGenericProtocol as SupportedNetlinkProtocol>::multicast(
+        GroupIdSet::new(1 << (group_id - 1)),
+        GenericNlMessage::new(msg),
+        INIT_NET_NAMESPACE.clone(),
+    )
+}
+
+/// # 处理用户发往内核的消息
+///
+/// ## 参数
+/// - `buf`: 一个或多个首尾相接的netlink消息
+/// - `port`: 发送者的端口号
+///
+/// ## 返回值
+/// 要发回给发送者的消息
+fn genl_rcv(mut buf: &[u8], port: u32) -> Vec<Vec<u8>> {
+    let mut replies = Vec::new();
+    while buf.len() >= NLMSG_HDRLEN {
+        let len = u32::from_ne_bytes(buf[0..4].try_into().unwrap()) as usize;
+        if len < NLMSG_HDRLEN || len > buf.len() {
+            break;
+        }
+        let hdr = &buf[..NLMSG_HDRLEN];
+        let family = u16::from_ne_bytes(buf[4..6].try_into().unwrap());
+        let flags = u16::from_ne_bytes(buf[6..8].try_into().unwrap());
+        let seq = u32::from_ne_bytes(buf[8..12].try_into().unwrap());
+        let body = &buf[NLMSG_HDRLEN..len];
+        buf = &buf[((len + 3) & !3).min(buf.len())..];
+
+        if flags & NLM_F_REQUEST == 0 {
+            continue;
+        }
+        let result = genl_rcv_msg(family, flags, seq, port, body, &mut replies);
+        match result {
+            Ok(()) if flags & NLM_F_ACK != 0 => replies.push(nlmsg_error(hdr, seq, port, 0)),
+            Ok(()) => {}
+            Err(e) => replies.push(nlmsg_error(hdr, seq, port, e.to_posix_errno())),
+        }
+    }
+    replies
+}
+
+fn genl_rcv_msg(
+    family: u16,
+    flags: u16,
+    seq: u32,
+    port: u32,
+    body: &[u8],
+    replies: &mut Vec<Vec<u8>>,
+) -> Result<(), SystemError> {
+    if body.len() < GENL_HDRLEN {
+        return Err(SystemError::EINVAL);
+    }
+    let ops = GENL_REGISTRY
+        .lock()
+        .families
+        .iter()
+        .find(|e| e.id == family)
+        .map(|e| e.family.clone())
+        .ok_or(SystemError::ENOENT)?;
+    let req = GenlRequest {
+        family,
+        flags,
+        seq,
+        port,
+        cmd: body[0],
+        version: body[1],
+        attrs: NlAttrs::parse(&body[GENL_HDRLEN..])?,
+    };
+    if !ops.commands().contains(&req.cmd) {
+        return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+    }
+    ops.doit(&req, replies)?;
+    if req.is_dump() {
+        replies.push(nlmsg_done(seq, port));
+    }
+    Ok(())
+}
+
+const CTRL_CMD_NEWFAMILY: u8 = 1;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+const CTRL_ATTR_VERSION: u16 = 3;
+const CTRL_ATTR_HDRSIZE: u16 = 4;
+const CTRL_ATTR_MAXATTR: u16 = 5;
+const CTRL_ATTR_OPS: u16 = 6;
+const CTRL_ATTR_MCAST_GROUPS: u16 = 7;
+
+const CTRL_ATTR_OP_ID: u16 = 1;
+const CTRL_ATTR_OP_FLAGS: u16 = 2;
+
+const CTRL_ATTR_MCAST_GRP_NAME: u16 = 1;
+const CTRL_ATTR_MCAST_GRP_ID: u16 = 2;
+
+/// 控制器，用于按名字或者id查询协议族
+#[derive(Debug)]
+struct GenlCtrl;
+
+impl GenlCtrl {
+    fn fill_family(req: &GenlRequest, entry: &GenlFamilyEntry) -> Vec<u8> {
+        let family = &entry.family;
+        let mut msg = req.reply(CTRL_CMD_NEWFAMILY, 2);
+        msg.put_u16(CTRL_ATTR_FAMILY_ID, entry.id);
+        msg.put_str(CTRL_ATTR_FAMILY_NAME, family.name());
+        msg.put_u32(CTRL_ATTR_VERSION, family.version() as u32);
+        msg.put_u32(CTRL_ATTR_HDRSIZE, 0);
+        msg.put_u32(CTRL_ATTR_MAXATTR, family.max_attr() as u32);
+
+        let ops = msg.nest_start(CTRL_ATTR_OPS);
+        for (i, cmd) in family.commands().iter().enumerate() {
+            let op = msg.nest_start(i as u16 + 1);
+            msg.put_u32(CTRL_ATTR_OP_ID, *cmd as u32);
+            msg.put_u32(CTRL_ATTR_OP_FLAGS, 0);
+            msg.nest_end(op);
+        }
+        msg.nest_end(ops);
+
+        if !family.mcast_groups().is_empty() {
+            let groups = msg.nest_start(CTRL_ATTR_MCAST_GROUPS);
+            for (i, name) in family.mcast_groups().iter().enumerate() {
+                let group = msg.nest_start(i as u16 + 1);
+                msg.put_str(CTRL_ATTR_MCAST_GRP_NAME, name);
+                msg.put_u32(CTRL_ATTR_MCAST_GRP_ID, entry.first_group + i as u32);
+                msg.nest_end(group);
+            }
+            msg.nest_end(groups);
+        }
+        msg.finish()
+    }
+}
+
+impl GenlFamily for GenlCtrl {
+    fn name(&self) -> &'static str {
+        "nlctrl"
+    }
+
+    fn version(&self) -> u8 {
+        2
+    }
+
+    fn max_attr(&self) -> u16 {
+        CTRL_ATTR_MCAST_GROUPS
+    }
+
+    fn mcast_groups(&self) -> &'static [&'static str] {
+        &["notify"]
+    }
+
+    fn commands(&self) -> &'static [u8] {
+        &[CTRL_CMD_GETFAMILY]
+    }
+
+    fn doit(&self, req: &GenlRequest, replies: &mut Vec<Vec<u8>>) -> Result<(), SystemError> {
+        let registry = GENL_REGISTRY.lock();
+        if req.is_dump() {
+            for entry in &registry.families {
+                replies.push(Self::fill_family(req, entry));
+            }
+            return Ok(());
+        }
+
+        let id = req.attrs.get_u16(CTRL_ATTR_FAMILY_ID);
+        let name = req.attrs.get_str(CTRL_ATTR_FAMILY_NAME);
+        if id.is_none() && name.is_none() {
+            return Err(SystemError::EINVAL);
+        }
+        let entry = registry
+            .families
+            .iter()
+            .find(|e| id.is_none_or(|id| e.id == id) && name.is_none_or(|n| e.family.name() == n))
+            .ok_or(SystemError::ENOENT)?;
+        replies.push(Self::fill_family(req, entry));
+        Ok(())
+    }
+}
@@ -1,6 +1,28 @@
-use crate::net::socket::netlink::{common::NetlinkSocket, table::NetlinkKobjectUeventProtocol};
+use system_error::SystemError;
+
+use crate::{
+    net::socket::netlink::{
+        addr::multicast::GroupIdSet,
+        common::NetlinkSocket,
+        kobject::message::KobjectUeventMessage,
+        table::{NetlinkKobjectUeventProtocol, SupportedNetlinkProtocol},
+    },
+    process::namespace::net_namespace::INIT_NET_NAMESPACE,
+};
 
 mod bound;
 pub mod message;
 
 pub(super) type NetlinkKobjectUeventSocket = NetlinkSocket<NetlinkKobjectUeventProtocol>;
+
+/// 内核uevent使用的多播组
+const UEVENT_GROUPS: GroupIdSet = GroupIdSet::new(1);
+
+/// 将内核产生的uevent广播给所有订阅了NETLINK_KOBJECT_UEVENT的套接字
+pub fn netlink_broadcast_uevent(payload: &[u8]) -> Result<(), SystemError> {
+    <NetlinkKobjectUeventProtocol as SupportedNetlinkProtocol>::multicast(
+        UEVENT_GROUPS,
+        KobjectUeventMessage::new(payload),
+        INIT_NET_NAMESPACE.clone(),
+    )
+}
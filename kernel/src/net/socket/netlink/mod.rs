@@ -1,5 +1,6 @@
 use crate::net::socket::{
     netlink::{
+        generic::NetlinkGenericSocket,
         kobject::NetlinkKobjectUeventSocket,
         route::NetlinkRouteSocket,
         table::{is_valid_protocol, StandardNetlinkProtocol},
@@ -11,7 +12,8 @@ use system_error::SystemError;
 
 pub mod addr;
 mod common;
-mod kobject;
+pub mod generic;
+pub mod kobject;
 mod message;
 mod receiver;
 mod route;
@@ -34,6 +36,9 @@ pub fn create_netlink_socket(
         Ok(StandardNetlinkProtocol::KOBJECT_UEVENT) => {
             NetlinkKobjectUeventSocket::new(is_nonblock, socket_type, protocol)
         }
+        Ok(StandardNetlinkProtocol::GENERIC) => {
+            NetlinkGenericSocket::new(is_nonblock, socket_type, protocol)
+        }
         Ok(_) => {
             log::warn!(
                 "standard netlink families {} is not supported yet",
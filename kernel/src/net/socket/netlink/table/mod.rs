@@ -2,6 +2,7 @@ mod multicast;
 pub use multicast::MulticastMessage;
 
 use crate::net::socket::netlink::addr::multicast::GroupIdSet;
+use crate::net::socket::netlink::generic::message::GenericNlMessage;
 use crate::net::socket::netlink::kobject::message::KobjectUeventMessage;
 use crate::net::socket::netlink::route::kern::NetlinkRouteKernelSocket;
 use crate::net::socket::netlink::route::message::RouteNlMessage;
@@ -27,6 +28,7 @@ const MAX_GROUPS: u32 = 32;
 pub struct NetlinkSocketTable {
     route: Arc<RwSem<ProtocolSocketTable<RouteNlMessage>>>,
     kobject_uevent: Arc<RwSem<ProtocolSocketTable<KobjectUeventMessage>>>,
+    generic: Arc<RwSem<ProtocolSocketTable<GenericNlMessage>>>,
 }
 
 impl Default for NetlinkSocketTable {
@@ -34,6 +36,7 @@ impl Default for NetlinkSocketTable {
         Self {
             route: Arc::new(RwSem::new(ProtocolSocketTable::new())),
             kobject_uevent: Arc::new(RwSem::new(ProtocolSocketTable::new())),
+            generic: Arc::new(RwSem::new(ProtocolSocketTable::new())),
         }
     }
 }
@@ -46,6 +49,10 @@ impl NetlinkSocketTable {
     pub fn kobject_uevent(&self) -> Arc<RwSem<ProtocolSocketTable<KobjectUeventMessage>>> {
         self.kobject_uevent.clone()
     }
+
+    pub fn generic(&self) -> Arc<RwSem<ProtocolSocketTable<GenericNlMessage>>> {
+        self.generic.clone()
+    }
 }
 
 #[derive(Debug)]
@@ -259,6 +266,17 @@ impl SupportedNetlinkProtocol for NetlinkKobjectUeventProtocol {
     }
 }
 
+#[derive(Debug)]
+pub struct NetlinkGenericProtocol;
+
+impl SupportedNetlinkProtocol for NetlinkGenericProtocol {
+    type Message = GenericNlMessage;
+
+    fn socket_table(netns: Arc<NetNamespace>) -> Arc<RwSem<ProtocolSocketTable<Self::Message>>> {
+        netns.netlink_socket_table().generic()
+    }
+}
+
 pub fn is_valid_protocol(protocol: u32) -> bool {
     protocol < MAX_ALLOWED_PROTOCOL_ID
 }
@@ -1,8 +1,9 @@
 pub(super) mod datagram_common;
 
 use crate::net::socket::{
-    self, inet::syscall::create_inet_socket, netlink::create_netlink_socket, packet::PacketSocket,
-    unix::create_unix_socket, vsock::create_vsock_socket, Socket,
+    self, bluetooth::create_bluetooth_socket, inet::syscall::create_inet_socket,
+    netlink::create_netlink_socket, packet::PacketSocket, unix::create_unix_socket,
+    vsock::create_vsock_socket, Socket,
 };
 use alloc::sync::Arc;
 use smoltcp::wire::{IpAddress, IpVersion};
@@ -129,6 +130,7 @@ pub fn create_socket(
             PacketSocket::new(socket_type, eth_protocol, is_nonblock)?
         }
         AF::Vsock => create_vsock_socket(socket_type, protocol, is_nonblock)?,
+        AF::Bluetooth => create_bluetooth_socket(socket_type, protocol, is_nonblock)?,
         _ => {
             log::warn!("unsupport address family");
             return Err(SystemError::EAFNOSUPPORT);
@@ -0,0 +1,387 @@
+//! 无线网络核心（精简版cfg80211）
+//!
+//! 负责管理无线设备的扫描结果、连接状态和密钥，并通过[`nl80211`]向用户态提供接口。
+//! 驱动实现[`Cfg80211Ops`]并调用[`wireless_register`]注册设备，扫描和连接的结果由驱动
+//! 通过[`WirelessDev::inform_bss`]、[`WirelessDev::scan_done`]、[`WirelessDev::connect_result`]
+//! 和[`WirelessDev::disconnected`]上报。
+//!
+//! 目前只支持station模式，连接状态机为`Idle -> Connecting -> Connected -> Idle`，
+//! 认证和密钥协商（WPA）由用户态完成，内核只负责保存和下发密钥。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/net/wireless/
+
+pub mod nl80211;
+
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt::Debug,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use system_error::SystemError;
+
+use crate::{libs::spinlock::SpinLock, net::generate_iface_id};
+
+pub const WLAN_STATUS_SUCCESS: u16 = 0;
+pub const WLAN_STATUS_UNSPECIFIED_FAILURE: u16 = 1;
+
+pub const WLAN_REASON_UNSPECIFIED: u16 = 1;
+pub const WLAN_REASON_DEAUTH_LEAVING: u16 = 3;
+
+pub const WLAN_CAPABILITY_ESS: u16 = 1 << 0;
+pub const WLAN_CAPABILITY_PRIVACY: u16 = 1 << 4;
+
+pub const WLAN_CIPHER_SUITE_WEP40: u32 = 0x000f_ac01;
+pub const WLAN_CIPHER_SUITE_TKIP: u32 = 0x000f_ac02;
+pub const WLAN_CIPHER_SUITE_CCMP: u32 = 0x000f_ac04;
+pub const WLAN_CIPHER_SUITE_WEP104: u32 = 0x000f_ac05;
+
+/// 支持的加密套件及其密钥长度
+pub const SUPPORTED_CIPHERS: &[(u32, usize)] = &[
+    (WLAN_CIPHER_SUITE_WEP40, 5),
+    (WLAN_CIPHER_SUITE_TKIP, 32),
+    (WLAN_CIPHER_SUITE_CCMP, 16),
+    (WLAN_CIPHER_SUITE_WEP104, 13),
+];
+
+pub const IEEE80211_MAX_SSID_LEN: usize = 32;
+
+pub type MacAddr = [u8; 6];
+
+/// 扫描到的一个BSS（接入点）
+#[derive(Debug, Clone)]
+pub struct Bss {
+    pub bssid: MacAddr,
+    pub ssid: Vec<u8>,
+    /// 信道的中心频率（MHz）
+    pub freq: u32,
+    /// 信号强度（mBm，即dBm * 100）
+    pub signal_mbm: i32,
+    pub beacon_interval: u16,
+    pub capability: u16,
+    /// beacon中的信息元素
+    pub ies: Vec<u8>,
+}
+
+impl Bss {
+    pub fn privacy(&self) -> bool {
+        self.capability & WLAN_CAPABILITY_PRIVACY != 0
+    }
+}
+
+/// 扫描请求，字段为空表示不限制
+#[derive(Debug, Clone, Default)]
+pub struct ScanRequest {
+    pub ssids: Vec<Vec<u8>>,
+    pub freqs: Vec<u32>,
+}
+
+/// 连接请求
+#[derive(Debug, Clone)]
+pub struct ConnectParams {
+    pub ssid: Vec<u8>,
+    pub bssid: Option<MacAddr>,
+    pub freq: Option<u32>,
+    /// 是否使用加密连接
+    pub privacy: bool,
+}
+
+/// 一个密钥
+#[derive(Debug, Clone)]
+pub struct KeyParams {
+    pub idx: u8,
+    pub cipher: u32,
+    pub data: Vec<u8>,
+    pub seq: Vec<u8>,
+    /// 对端的地址，`None`表示组播密钥
+    pub mac: Option<MacAddr>,
+}
+
+/// 连接状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnState {
+    Idle,
+    Connecting {
+        ssid: Vec<u8>,
+    },
+    Connected {
+        ssid: Vec<u8>,
+        bssid: MacAddr,
+        freq: u32,
+    },
+}
+
+/// 无线设备驱动需要实现的操作
+///
+/// 所有操作都在没有持有[`WirelessDev`]内部锁的情况下调用，驱动可以在其中直接上报结果
+pub trait Cfg80211Ops: Debug + Send + Sync {
+    /// 开始扫描，驱动用[`WirelessDev::inform_bss`]上报扫描到的BSS，最后调用[`WirelessDev::scan_done`]
+    fn scan(&self, wdev: &Arc<WirelessDev>, req: &ScanRequest) -> Result<(), SystemError>;
+
+    /// 开始连接，驱动在完成后调用[`WirelessDev::connect_result`]
+    fn connect(&self, wdev: &Arc<WirelessDev>, params: &ConnectParams) -> Result<(), SystemError>;
+
+    /// 断开当前连接，返回成功后核心会把状态切换为`Idle`
+    fn disconnect(&self, wdev: &Arc<WirelessDev>, reason: u16) -> Result<(), SystemError>;
+
+    /// 把密钥下发到硬件
+    fn add_key(&self, wdev: &Arc<WirelessDev>, key: &KeyParams) -> Result<(), SystemError>;
+
+    fn del_key(
+        &self,
+        wdev: &Arc<WirelessDev>,
+        idx: u8,
+        mac: Option<MacAddr>,
+    ) -> Result<(), SystemError>;
+}
+
+#[derive(Debug)]
+struct WirelessDevInner {
+    state: ConnState,
+    scanning: bool,
+    bss_list: Vec<Bss>,
+    /// 以(密钥索引, 对端地址)为键
+    keys: BTreeMap<(u8, Option<MacAddr>), KeyParams>,
+    /// 扫描结果每次变化时递增，用户态用它判断dump期间结果是否变化
+    generation: u32,
+}
+
+/// 一个station模式的无线设备
+#[derive(Debug)]
+pub struct WirelessDev {
+    wiphy_idx: u32,
+    ifindex: usize,
+    name: String,
+    mac: MacAddr,
+    ops: Arc<dyn Cfg80211Ops>,
+    inner: SpinLock<WirelessDevInner>,
+}
+
+impl WirelessDev {
+    pub fn wiphy_idx(&self) -> u32 {
+        self.wiphy_idx
+    }
+
+    /// 接口的索引，与网卡的索引在同一个空间中分配
+    pub fn ifindex(&self) -> usize {
+        self.ifindex
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn wiphy_name(&self) -> String {
+        format!("phy{}", self.wiphy_idx)
+    }
+
+    pub fn mac(&self) -> MacAddr {
+        self.mac
+    }
+
+    pub fn state(&self) -> ConnState {
+        self.inner.lock().state.clone()
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.inner.lock().generation
+    }
+
+    pub fn bss_list(&self) -> Vec<Bss> {
+        self.inner.lock().bss_list.clone()
+    }
+
+    /// # 开始扫描
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EBUSY)`: 已经有扫描正在进行
+    pub fn scan(self: &Arc<Self>, req: &ScanRequest) -> Result<(), SystemError> {
+        {
+            let mut inner = self.inner.lock();
+            if inner.scanning {
+                return Err(SystemError::EBUSY);
+            }
+            inner.scanning = true;
+        }
+        nl80211::send_scan_start(self);
+        self.ops.scan(self, req).inspect_err(|_| {
+            self.inner.lock().scanning = false;
+        })
+    }
+
+    /// 驱动上报扫描到的BSS，同一个BSSID的旧结果会被替换
+    pub fn inform_bss(&self, bss: Bss) {
+        let mut inner = self.inner.lock();
+        inner.bss_list.retain(|b| b.bssid != bss.bssid);
+        inner.bss_list.push(bss);
+        inner.generation = inner.generation.wrapping_add(1);
+    }
+
+    /// 驱动上报扫描结束
+    pub fn scan_done(&self, aborted: bool) {
+        let was_scanning = core::mem::replace(&mut self.inner.lock().scanning, false);
+        if was_scanning {
+            nl80211::send_scan_done(self, aborted);
+        }
+    }
+
+    /// # 连接到一个网络
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EALREADY)`: 已经连接或者正在连接
+    pub fn connect(self: &Arc<Self>, params: &ConnectParams) -> Result<(), SystemError> {
+        if params.ssid.is_empty() || params.ssid.len() > IEEE80211_MAX_SSID_LEN {
+            return Err(SystemError::EINVAL);
+        }
+        {
+            let mut inner = self.inner.lock();
+            if inner.state != ConnState::Idle {
+                return Err(SystemError::EALREADY);
+            }
+            inner.state = ConnState::Connecting {
+                ssid: params.ssid.clone(),
+            };
+        }
+        self.ops.connect(self, params).inspect_err(|_| {
+            self.inner.lock().state = ConnState::Idle;
+        })
+    }
+
+    /// # 驱动上报连接结果
+    ///
+    /// ## 参数
+    /// - `bssid`: 连接上的BSS，失败时可以为`None`
+    /// - `status`: IEEE 802.11的状态码，[`WLAN_STATUS_SUCCESS`]表示成功
+    pub fn connect_result(&self, bssid: Option<MacAddr>, status: u16) {
+        {
+            let mut inner = self.inner.lock();
+            let ConnState::Connecting { ssid } = &inner.state else {
+                return;
+            };
+            let state = match bssid {
+                Some(bssid) if status == WLAN_STATUS_SUCCESS => {
+                    let freq = inner
+                        .bss_list
+                        .iter()
+                        .find(|b| b.bssid == bssid)
+                        .map(|b| b.freq)
+                        .unwrap_or(0);
+                    ConnState::Connected {
+                        ssid: ssid.clone(),
+                        bssid,
+                        freq,
+                    }
+                }
+                _ => ConnState::Idle,
+            };
+            if state == ConnState::Idle {
+                inner.keys.clear();
+            }
+            inner.state = state;
+        }
+        nl80211::send_connect_result(self, bssid, status);
+    }
+
+    /// # 断开连接
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::ENOTCONN)`: 当前没有连接
+    pub fn disconnect(self: &Arc<Self>, reason: u16) -> Result<(), SystemError> {
+        if self.inner.lock().state == ConnState::Idle {
+            return Err(SystemError::ENOTCONN);
+        }
+        self.ops.disconnect(self, reason)?;
+        self.disconnected(reason, false);
+        Ok(())
+    }
+
+    /// 连接已经断开，`by_ap`表示由接入点发起，驱动在收到deauth/disassoc时调用
+    pub fn disconnected(&self, reason: u16, by_ap: bool) {
+        {
+            let mut inner = self.inner.lock();
+            if inner.state == ConnState::Idle {
+                return;
+            }
+            inner.state = ConnState::Idle;
+            inner.keys.clear();
+        }
+        nl80211::send_disconnected(self, reason, by_ap);
+    }
+
+    /// # 安装密钥
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EINVAL)`: 加密套件不支持或者密钥长度不匹配
+    /// - `Err(SystemError::ENOLINK)`: 安装单播密钥时还没有连接
+    pub fn add_key(self: &Arc<Self>, key: KeyParams) -> Result<(), SystemError> {
+        let expected_len = SUPPORTED_CIPHERS
+            .iter()
+            .find(|(cipher, _)| *cipher == key.cipher)
+            .map(|(_, len)| *len)
+            .ok_or(SystemError::EINVAL)?;
+        if key.data.len() != expected_len || key.idx > 3 || key.seq.len() > 16 {
+            return Err(SystemError::EINVAL);
+        }
+        if key.mac.is_some() && !matches!(self.state(), ConnState::Connected { .. }) {
+            return Err(SystemError::ENOLINK);
+        }
+        self.ops.add_key(self, &key)?;
+        self.inner.lock().keys.insert((key.idx, key.mac), key);
+        Ok(())
+    }
+
+    /// # 删除密钥
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::ENOENT)`: 密钥不存在
+    pub fn del_key(self: &Arc<Self>, idx: u8, mac: Option<MacAddr>) -> Result<(), SystemError> {
+        if !self.inner.lock().keys.contains_key(&(idx, mac)) {
+            return Err(SystemError::ENOENT);
+        }
+        self.ops.del_key(self, idx, mac)?;
+        self.inner.lock().keys.remove(&(idx, mac));
+        Ok(())
+    }
+}
+
+static WIRELESS_DEVS: SpinLock<Vec<Arc<WirelessDev>>> = SpinLock::new(Vec::new());
+
+/// # 注册一个无线设备
+///
+/// 设备的名字为`wlan<N>`，其中N与wiphy的编号相同
+///
+/// ## 参数
+/// - `mac`: 设备的MAC地址
+/// - `ops`: 驱动的操作
+pub fn wireless_register(mac: MacAddr, ops: Arc<dyn Cfg80211Ops>) -> Arc<WirelessDev> {
+    static WIPHY_IDX: AtomicU32 = AtomicU32::new(0);
+
+    let wiphy_idx = WIPHY_IDX.fetch_add(1, Ordering::SeqCst);
+    let wdev = Arc::new(WirelessDev {
+        wiphy_idx,
+        ifindex: generate_iface_id(),
+        name: format!("wlan{}", wiphy_idx),
+        mac,
+        ops,
+        inner: SpinLock::new(WirelessDevInner {
+            state: ConnState::Idle,
+            scanning: false,
+            bss_list: Vec::new(),
+            keys: BTreeMap::new(),
+            generation: 0,
+        }),
+    });
+    WIRELESS_DEVS.lock().push(wdev.clone());
+    nl80211::send_new_interface(&wdev);
+    log::info!("wireless: registered {} ({})", wdev.name, wdev.wiphy_name());
+    wdev
+}
+
+/// 注销无线设备，当前的连接会被断开
+pub fn wireless_unregister(wdev: &Arc<WirelessDev>) {
+    wdev.disconnected(WLAN_REASON_DEAUTH_LEAVING, false);
+    WIRELESS_DEVS.lock().retain(|w| !Arc::ptr_eq(w, wdev));
+}
+
+pub fn wireless_devices() -> Vec<Arc<WirelessDev>> {
+    WIRELESS_DEVS.lock().clone()
+}
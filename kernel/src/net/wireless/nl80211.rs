@@ -0,0 +1,422 @@
+//! nl80211：无线设备的Generic Netlink接口（精简版）
+//!
+//! 支持查询设备、触发扫描并读取扫描结果、连接和断开以及安装密钥，命令和属性的编号与Linux一致，
+//! 因此`iw`这类工具可以直接使用。扫描和连接的结果通过`scan`和`mlme`多播组通知。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/include/uapi/linux/nl80211.h
+
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU16, Ordering};
+use system_error::SystemError;
+use unified_init::macros::unified_init;
+
+use crate::{
+    init::initcall::INITCALL_SUBSYS,
+    net::socket::netlink::generic::{
+        genl_multicast, genl_register_family,
+        message::{GenlMsgBuilder, GenlRequest, NlAttrs},
+        GenlFamily,
+    },
+};
+
+use super::{
+    wireless_devices, ConnState, ConnectParams, KeyParams, MacAddr, ScanRequest, WirelessDev,
+    SUPPORTED_CIPHERS, WLAN_REASON_DEAUTH_LEAVING,
+};
+
+const NL80211_GENL_NAME: &str = "nl80211";
+const NL80211_GENL_VERSION: u8 = 1;
+
+const NL80211_CMD_GET_WIPHY: u8 = 1;
+const NL80211_CMD_NEW_WIPHY: u8 = 3;
+const NL80211_CMD_GET_INTERFACE: u8 = 5;
+const NL80211_CMD_SET_INTERFACE: u8 = 6;
+const NL80211_CMD_NEW_INTERFACE: u8 = 7;
+const NL80211_CMD_NEW_KEY: u8 = 11;
+const NL80211_CMD_DEL_KEY: u8 = 12;
+const NL80211_CMD_GET_SCAN: u8 = 32;
+const NL80211_CMD_TRIGGER_SCAN: u8 = 33;
+const NL80211_CMD_NEW_SCAN_RESULTS: u8 = 34;
+const NL80211_CMD_SCAN_ABORTED: u8 = 35;
+const NL80211_CMD_CONNECT: u8 = 46;
+const NL80211_CMD_DISCONNECT: u8 = 48;
+
+const NL80211_ATTR_WIPHY: u16 = 1;
+const NL80211_ATTR_WIPHY_NAME: u16 = 2;
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_IFNAME: u16 = 4;
+const NL80211_ATTR_IFTYPE: u16 = 5;
+const NL80211_ATTR_MAC: u16 = 6;
+const NL80211_ATTR_KEY_DATA: u16 = 7;
+const NL80211_ATTR_KEY_IDX: u16 = 8;
+const NL80211_ATTR_KEY_CIPHER: u16 = 9;
+const NL80211_ATTR_KEY_SEQ: u16 = 10;
+const NL80211_ATTR_SUPPORTED_IFTYPES: u16 = 32;
+const NL80211_ATTR_WIPHY_FREQ: u16 = 38;
+const NL80211_ATTR_MAX_NUM_SCAN_SSIDS: u16 = 43;
+const NL80211_ATTR_SCAN_FREQUENCIES: u16 = 44;
+const NL80211_ATTR_SCAN_SSIDS: u16 = 45;
+const NL80211_ATTR_GENERATION: u16 = 46;
+const NL80211_ATTR_BSS: u16 = 47;
+const NL80211_ATTR_SUPPORTED_COMMANDS: u16 = 50;
+const NL80211_ATTR_SSID: u16 = 52;
+const NL80211_ATTR_REASON_CODE: u16 = 54;
+const NL80211_ATTR_CIPHER_SUITES: u16 = 57;
+const NL80211_ATTR_PRIVACY: u16 = 70;
+const NL80211_ATTR_DISCONNECTED_BY_AP: u16 = 71;
+const NL80211_ATTR_STATUS_CODE: u16 = 72;
+const NL80211_ATTR_KEY: u16 = 80;
+const NL80211_ATTR_MAX: u16 = NL80211_ATTR_KEY;
+
+const NL80211_KEY_DATA: u16 = 1;
+const NL80211_KEY_IDX: u16 = 2;
+const NL80211_KEY_CIPHER: u16 = 3;
+const NL80211_KEY_SEQ: u16 = 4;
+
+const NL80211_BSS_BSSID: u16 = 1;
+const NL80211_BSS_FREQUENCY: u16 = 2;
+const NL80211_BSS_BEACON_INTERVAL: u16 = 4;
+const NL80211_BSS_CAPABILITY: u16 = 5;
+const NL80211_BSS_INFORMATION_ELEMENTS: u16 = 6;
+const NL80211_BSS_SIGNAL_MBM: u16 = 7;
+const NL80211_BSS_STATUS: u16 = 9;
+const NL80211_BSS_SEEN_MS_AGO: u16 = 10;
+
+const NL80211_BSS_STATUS_ASSOCIATED: u32 = 1;
+
+const NL80211_IFTYPE_STATION: u32 = 2;
+
+/// 一次扫描最多指定的SSID数
+const MAX_SCAN_SSIDS: u8 = 4;
+
+const NL80211_COMMANDS: &[u8] = &[
+    NL80211_CMD_GET_WIPHY,
+    NL80211_CMD_GET_INTERFACE,
+    NL80211_CMD_SET_INTERFACE,
+    NL80211_CMD_NEW_KEY,
+    NL80211_CMD_DEL_KEY,
+    NL80211_CMD_GET_SCAN,
+    NL80211_CMD_TRIGGER_SCAN,
+    NL80211_CMD_CONNECT,
+    NL80211_CMD_DISCONNECT,
+];
+
+/// 注册时分配的协议族id，0表示还没有注册
+static NL80211_FAMILY_ID: AtomicU16 = AtomicU16::new(0);
+
+#[derive(Debug)]
+struct Nl80211;
+
+impl Nl80211 {
+    /// 按IFINDEX或者WIPHY属性查找设备
+    fn lookup(attrs: &NlAttrs) -> Result<Arc<WirelessDev>, SystemError> {
+        let ifindex = attrs.get_u32(NL80211_ATTR_IFINDEX);
+        let wiphy = attrs.get_u32(NL80211_ATTR_WIPHY);
+        if ifindex.is_none() && wiphy.is_none() {
+            return Err(SystemError::EINVAL);
+        }
+        wireless_devices()
+            .into_iter()
+            .find(|w| {
+                ifindex.is_none_or(|i| w.ifindex() == i as usize)
+                    && wiphy.is_none_or(|p| w.wiphy_idx() == p)
+            })
+            .ok_or(SystemError::ENODEV)
+    }
+
+    /// dump请求可以不指定设备，此时返回所有设备
+    fn lookup_or_all(req: &GenlRequest) -> Result<Vec<Arc<WirelessDev>>, SystemError> {
+        if req.is_dump()
+            && !req.attrs.has(NL80211_ATTR_IFINDEX)
+            && !req.attrs.has(NL80211_ATTR_WIPHY)
+        {
+            return Ok(wireless_devices());
+        }
+        Self::lookup(&req.attrs).map(|w| alloc::vec![w])
+    }
+
+    fn fill_wiphy(msg: &mut GenlMsgBuilder, wdev: &WirelessDev) {
+        msg.put_u32(NL80211_ATTR_WIPHY, wdev.wiphy_idx());
+        msg.put_str(NL80211_ATTR_WIPHY_NAME, &wdev.wiphy_name());
+        msg.put_u32(NL80211_ATTR_GENERATION, wdev.generation());
+        msg.put_u8(NL80211_ATTR_MAX_NUM_SCAN_SSIDS, MAX_SCAN_SSIDS);
+
+        let iftypes = msg.nest_start(NL80211_ATTR_SUPPORTED_IFTYPES);
+        msg.put_flag(NL80211_IFTYPE_STATION as u16);
+        msg.nest_end(iftypes);
+
+        let ciphers: Vec<u8> = SUPPORTED_CIPHERS
+            .iter()
+            .flat_map(|(cipher, _)| cipher.to_ne_bytes())
+            .collect();
+        msg.put(NL80211_ATTR_CIPHER_SUITES, &ciphers);
+
+        let cmds = msg.nest_start(NL80211_ATTR_SUPPORTED_COMMANDS);
+        for (i, cmd) in NL80211_COMMANDS.iter().enumerate() {
+            msg.put_u32(i as u16 + 1, *cmd as u32);
+        }
+        msg.nest_end(cmds);
+    }
+
+    fn fill_interface(msg: &mut GenlMsgBuilder, wdev: &WirelessDev) {
+        msg.put_u32(NL80211_ATTR_IFINDEX, wdev.ifindex() as u32);
+        msg.put_str(NL80211_ATTR_IFNAME, wdev.name());
+        msg.put_u32(NL80211_ATTR_WIPHY, wdev.wiphy_idx());
+        msg.put_u32(NL80211_ATTR_IFTYPE, NL80211_IFTYPE_STATION);
+        msg.put(NL80211_ATTR_MAC, &wdev.mac());
+        msg.put_u32(NL80211_ATTR_GENERATION, wdev.generation());
+        if let ConnState::Connected { ssid, freq, .. } = wdev.state() {
+            msg.put(NL80211_ATTR_SSID, &ssid);
+            msg.put_u32(NL80211_ATTR_WIPHY_FREQ, freq);
+        }
+    }
+
+    fn get_scan(req: &GenlRequest, wdev: &WirelessDev, replies: &mut Vec<Vec<u8>>) {
+        let associated = match wdev.state() {
+            ConnState::Connected { bssid, .. } => Some(bssid),
+            _ => None,
+        };
+        let generation = wdev.generation();
+        for bss in wdev.bss_list() {
+            let mut msg = req.reply(NL80211_CMD_NEW_SCAN_RESULTS, NL80211_GENL_VERSION);
+            msg.put_u32(NL80211_ATTR_GENERATION, generation);
+            msg.put_u32(NL80211_ATTR_IFINDEX, wdev.ifindex() as u32);
+
+            let nest = msg.nest_start(NL80211_ATTR_BSS);
+            msg.put(NL80211_BSS_BSSID, &bss.bssid);
+            msg.put_u32(NL80211_BSS_FREQUENCY, bss.freq);
+            msg.put_u16(NL80211_BSS_BEACON_INTERVAL, bss.beacon_interval);
+            msg.put_u16(NL80211_BSS_CAPABILITY, bss.capability);
+            msg.put(NL80211_BSS_INFORMATION_ELEMENTS, &bss.ies);
+            msg.put_u32(NL80211_BSS_SIGNAL_MBM, bss.signal_mbm as u32);
+            msg.put_u32(NL80211_BSS_SEEN_MS_AGO, 0);
+            if associated == Some(bss.bssid) {
+                msg.put_u32(NL80211_BSS_STATUS, NL80211_BSS_STATUS_ASSOCIATED);
+            }
+            msg.nest_end(nest);
+            replies.push(msg.finish());
+        }
+    }
+
+    fn trigger_scan(req: &GenlRequest) -> Result<(), SystemError> {
+        let wdev = Self::lookup(&req.attrs)?;
+        let mut scan = ScanRequest::default();
+        if let Some(ssids) = req.attrs.get_nested(NL80211_ATTR_SCAN_SSIDS)? {
+            for (_, ssid) in ssids.iter() {
+                if ssid.len() > super::IEEE80211_MAX_SSID_LEN {
+                    return Err(SystemError::EINVAL);
+                }
+                scan.ssids.push(ssid.to_vec());
+            }
+            if scan.ssids.len() > MAX_SCAN_SSIDS as usize {
+                return Err(SystemError::EINVAL);
+            }
+        }
+        if let Some(freqs) = req.attrs.get_nested(NL80211_ATTR_SCAN_FREQUENCIES)? {
+            for (_, freq) in freqs.iter() {
+                let freq = freq.get(..4).ok_or(SystemError::EINVAL)?;
+                scan.freqs
+                    .push(u32::from_ne_bytes(freq.try_into().unwrap()));
+            }
+        }
+        wdev.scan(&scan)
+    }
+
+    fn connect(req: &GenlRequest) -> Result<(), SystemError> {
+        let wdev = Self::lookup(&req.attrs)?;
+        let ssid = req
+            .attrs
+            .get(NL80211_ATTR_SSID)
+            .ok_or(SystemError::EINVAL)?;
+        let bssid = req.attrs.get(NL80211_ATTR_MAC).map(parse_mac).transpose()?;
+        wdev.connect(&ConnectParams {
+            ssid: ssid.to_vec(),
+            bssid,
+            freq: req.attrs.get_u32(NL80211_ATTR_WIPHY_FREQ),
+            privacy: req.attrs.has(NL80211_ATTR_PRIVACY),
+        })
+    }
+
+    /// 密钥可以放在NL80211_ATTR_KEY嵌套属性中，也可以直接放在顶层（旧的格式）
+    fn parse_key(attrs: &NlAttrs) -> Result<KeyParams, SystemError> {
+        let mac = attrs.get(NL80211_ATTR_MAC).map(parse_mac).transpose()?;
+        let (data, idx, cipher, seq) = match attrs.get_nested(NL80211_ATTR_KEY)? {
+            Some(key) => (
+                key.get(NL80211_KEY_DATA),
+                key.get_u8(NL80211_KEY_IDX),
+                key.get_u32(NL80211_KEY_CIPHER),
+                key.get(NL80211_KEY_SEQ),
+            ),
+            None => (
+                attrs.get(NL80211_ATTR_KEY_DATA),
+                attrs.get_u8(NL80211_ATTR_KEY_IDX),
+                attrs.get_u32(NL80211_ATTR_KEY_CIPHER),
+                attrs.get(NL80211_ATTR_KEY_SEQ),
+            ),
+        };
+        Ok(KeyParams {
+            idx: idx.unwrap_or(0),
+            cipher: cipher.ok_or(SystemError::EINVAL)?,
+            data: data.ok_or(SystemError::EINVAL)?.to_vec(),
+            seq: seq.unwrap_or_default().to_vec(),
+            mac,
+        })
+    }
+}
+
+impl GenlFamily for Nl80211 {
+    fn name(&self) -> &'static str {
+        NL80211_GENL_NAME
+    }
+
+    fn version(&self) -> u8 {
+        NL80211_GENL_VERSION
+    }
+
+    fn max_attr(&self) -> u16 {
+        NL80211_ATTR_MAX
+    }
+
+    fn mcast_groups(&self) -> &'static [&'static str] {
+        &["config", "scan", "regulatory", "mlme"]
+    }
+
+    fn commands(&self) -> &'static [u8] {
+        NL80211_COMMANDS
+    }
+
+    fn doit(&self, req: &GenlRequest, replies: &mut Vec<Vec<u8>>) -> Result<(), SystemError> {
+        match req.cmd {
+            NL80211_CMD_GET_WIPHY => {
+                for wdev in Self::lookup_or_all(req)? {
+                    let mut msg = req.reply(NL80211_CMD_NEW_WIPHY, NL80211_GENL_VERSION);
+                    Self::fill_wiphy(&mut msg, &wdev);
+                    replies.push(msg.finish());
+                }
+            }
+            NL80211_CMD_GET_INTERFACE => {
+                for wdev in Self::lookup_or_all(req)? {
+                    let mut msg = req.reply(NL80211_CMD_NEW_INTERFACE, NL80211_GENL_VERSION);
+                    Self::fill_interface(&mut msg, &wdev);
+                    replies.push(msg.finish());
+                }
+            }
+            NL80211_CMD_SET_INTERFACE => {
+                Self::lookup(&req.attrs)?;
+                // 只支持station模式
+                if req
+                    .attrs
+                    .get_u32(NL80211_ATTR_IFTYPE)
+                    .is_some_and(|t| t != NL80211_IFTYPE_STATION)
+                {
+                    return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+                }
+            }
+            NL80211_CMD_GET_SCAN => {
+                let wdev = Self::lookup(&req.attrs)?;
+                Self::get_scan(req, &wdev, replies);
+            }
+            NL80211_CMD_TRIGGER_SCAN => Self::trigger_scan(req)?,
+            NL80211_CMD_CONNECT => Self::connect(req)?,
+            NL80211_CMD_DISCONNECT => {
+                let reason = req
+                    .attrs
+                    .get_u16(NL80211_ATTR_REASON_CODE)
+                    .unwrap_or(WLAN_REASON_DEAUTH_LEAVING);
+                Self::lookup(&req.attrs)?.disconnect(reason)?;
+            }
+            NL80211_CMD_NEW_KEY => {
+                let wdev = Self::lookup(&req.attrs)?;
+                wdev.add_key(Self::parse_key(&req.attrs)?)?;
+            }
+            NL80211_CMD_DEL_KEY => {
+                let wdev = Self::lookup(&req.attrs)?;
+                let idx = req
+                    .attrs
+                    .get_nested(NL80211_ATTR_KEY)?
+                    .and_then(|key| key.get_u8(NL80211_KEY_IDX))
+                    .or_else(|| req.attrs.get_u8(NL80211_ATTR_KEY_IDX))
+                    .unwrap_or(0);
+                let mac = req.attrs.get(NL80211_ATTR_MAC).map(parse_mac).transpose()?;
+                wdev.del_key(idx, mac)?;
+            }
+            _ => return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP),
+        }
+        Ok(())
+    }
+}
+
+fn parse_mac(v: &[u8]) -> Result<MacAddr, SystemError> {
+    v.try_into().map_err(|_| SystemError::EINVAL)
+}
+
+/// 构造一条事件消息，事件的序号和端口号都为0
+fn event(cmd: u8, wdev: &WirelessDev) -> Option<(u16, GenlMsgBuilder)> {
+    let family = NL80211_FAMILY_ID.load(Ordering::Acquire);
+    if family == 0 {
+        return None;
+    }
+    let mut msg = GenlMsgBuilder::new(family, 0, 0, 0, cmd, NL80211_GENL_VERSION);
+    msg.put_u32(NL80211_ATTR_WIPHY, wdev.wiphy_idx());
+    msg.put_u32(NL80211_ATTR_IFINDEX, wdev.ifindex() as u32);
+    Some((family, msg))
+}
+
+fn send_event(family: u16, group: &str, msg: GenlMsgBuilder) {
+    if let Err(e) = genl_multicast(family, group, msg.finish()) {
+        log::warn!("nl80211: failed to send {} event: {:?}", group, e);
+    }
+}
+
+pub(super) fn send_new_interface(wdev: &WirelessDev) {
+    if let Some((family, mut msg)) = event(NL80211_CMD_NEW_INTERFACE, wdev) {
+        msg.put_str(NL80211_ATTR_IFNAME, wdev.name());
+        msg.put_u32(NL80211_ATTR_IFTYPE, NL80211_IFTYPE_STATION);
+        msg.put(NL80211_ATTR_MAC, &wdev.mac());
+        send_event(family, "config", msg);
+    }
+}
+
+pub(super) fn send_scan_start(wdev: &WirelessDev) {
+    if let Some((family, msg)) = event(NL80211_CMD_TRIGGER_SCAN, wdev) {
+        send_event(family, "scan", msg);
+    }
+}
+
+pub(super) fn send_scan_done(wdev: &WirelessDev, aborted: bool) {
+    let cmd = if aborted {
+        NL80211_CMD_SCAN_ABORTED
+    } else {
+        NL80211_CMD_NEW_SCAN_RESULTS
+    };
+    if let Some((family, msg)) = event(cmd, wdev) {
+        send_event(family, "scan", msg);
+    }
+}
+
+pub(super) fn send_connect_result(wdev: &WirelessDev, bssid: Option<MacAddr>, status: u16) {
+    if let Some((family, mut msg)) = event(NL80211_CMD_CONNECT, wdev) {
+        if let Some(bssid) = bssid {
+            msg.put(NL80211_ATTR_MAC, &bssid);
+        }
+        msg.put_u16(NL80211_ATTR_STATUS_CODE, status);
+        send_event(family, "mlme", msg);
+    }
+}
+
+pub(super) fn send_disconnected(wdev: &WirelessDev, reason: u16, by_ap: bool) {
+    if let Some((family, mut msg)) = event(NL80211_CMD_DISCONNECT, wdev) {
+        msg.put_u16(NL80211_ATTR_REASON_CODE, reason);
+        if by_ap {
+            msg.put_flag(NL80211_ATTR_DISCONNECTED_BY_AP);
+        }
+        send_event(family, "mlme", msg);
+    }
+}
+
+#[unified_init(INITCALL_SUBSYS)]
+fn nl80211_init() -> Result<(), SystemError> {
+    let id = genl_register_family(Arc::new(Nl80211))?;
+    NL80211_FAMILY_ID.store(id, Ordering::Release);
+    Ok(())
+}
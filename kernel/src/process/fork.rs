@@ -513,8 +513,10 @@ impl ProcessManager {
 
         // 如果新进程将处于不同的time namespace，
         // 则不能让它共享vm或线程组。
-        if !((clone_flags & (CloneFlags::CLONE_THREAD | CloneFlags::CLONE_VM)).is_empty()) {
-            // TODO: 判断time namespace，不同则返回错误
+        if !((clone_flags & (CloneFlags::CLONE_THREAD | CloneFlags::CLONE_VM)).is_empty())
+            && current_pcb.nsproxy().time_ns_pending()
+        {
+            return Err(SystemError::EINVAL);
         }
 
         if clone_flags.contains(CloneFlags::CLONE_PIDFD)
@@ -0,0 +1,154 @@
+//! 进程的I/O统计，导出到 /proc/[pid]/io
+//!
+//! 语义参考 Linux include/linux/task_io_accounting.h：
+//! - `rchar`/`wchar`: 通过read/write类系统调用读写的字节数（不论是否命中页缓存）
+//! - `syscr`/`syscw`: read/write类系统调用的次数
+//! - `read_bytes`/`write_bytes`: 提交到块设备的读写字节数
+//! - `cancelled_write_bytes`: 因截断文件而被丢弃、不再需要写回的脏页字节数
+//!
+//! 注意：由内核线程完成的写回，统计在该内核线程上。
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::{ProcessControlBlock, ProcessManager};
+
+/// 单个线程的I/O计数器
+#[derive(Debug, Default)]
+pub struct TaskIoAccounting {
+    rchar: AtomicU64,
+    wchar: AtomicU64,
+    syscr: AtomicU64,
+    syscw: AtomicU64,
+    read_bytes: AtomicU64,
+    write_bytes: AtomicU64,
+    cancelled_write_bytes: AtomicU64,
+}
+
+/// I/O计数器的快照
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TaskIoStats {
+    pub rchar: u64,
+    pub wchar: u64,
+    pub syscr: u64,
+    pub syscw: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub cancelled_write_bytes: u64,
+}
+
+impl TaskIoStats {
+    fn accumulate(&mut self, other: &TaskIoStats) {
+        self.rchar = self.rchar.saturating_add(other.rchar);
+        self.wchar = self.wchar.saturating_add(other.wchar);
+        self.syscr = self.syscr.saturating_add(other.syscr);
+        self.syscw = self.syscw.saturating_add(other.syscw);
+        self.read_bytes = self.read_bytes.saturating_add(other.read_bytes);
+        self.write_bytes = self.write_bytes.saturating_add(other.write_bytes);
+        self.cancelled_write_bytes = self
+            .cancelled_write_bytes
+            .saturating_add(other.cancelled_write_bytes);
+    }
+}
+
+impl TaskIoAccounting {
+    pub fn snapshot(&self) -> TaskIoStats {
+        // 各计数器相互独立，只需要一个近似的快照，因此使用Relaxed
+        TaskIoStats {
+            rchar: self.rchar.load(Ordering::Relaxed),
+            wchar: self.wchar.load(Ordering::Relaxed),
+            syscr: self.syscr.load(Ordering::Relaxed),
+            syscw: self.syscw.load(Ordering::Relaxed),
+            read_bytes: self.read_bytes.load(Ordering::Relaxed),
+            write_bytes: self.write_bytes.load(Ordering::Relaxed),
+            cancelled_write_bytes: self.cancelled_write_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl ProcessControlBlock {
+    #[inline(always)]
+    pub fn io_accounting(&self) -> &TaskIoAccounting {
+        &self.io_accounting
+    }
+
+    /// 整个线程组的I/O统计，即线程组内所有线程的计数之和
+    pub fn process_io_stats(&self) -> TaskIoStats {
+        let leader = if self.is_thread_group_leader() {
+            self.self_ref.upgrade()
+        } else {
+            self.threads_read_irqsave().group_leader()
+        };
+        let leader = match leader {
+            Some(leader) => leader,
+            None => return self.io_accounting.snapshot(),
+        };
+
+        let mut stats = leader.io_accounting.snapshot();
+        let ti = leader.threads_read_irqsave();
+        for t in &ti.group_tasks {
+            if let Some(p) = t.upgrade() {
+                stats.accumulate(&p.io_accounting.snapshot());
+            }
+        }
+        stats
+    }
+}
+
+/// 对当前线程的计数器执行`f`。进程管理初始化完成之前的I/O不做统计
+#[inline]
+fn with_current(f: impl FnOnce(&TaskIoAccounting)) {
+    if !ProcessManager::initialized() {
+        return;
+    }
+    f(ProcessManager::current_pcb().io_accounting());
+}
+
+/// 记录一次read类系统调用
+pub fn task_io_inc_syscr() {
+    with_current(|acct| {
+        acct.syscr.fetch_add(1, Ordering::Relaxed);
+    });
+}
+
+/// 记录一次write类系统调用
+pub fn task_io_inc_syscw() {
+    with_current(|acct| {
+        acct.syscw.fetch_add(1, Ordering::Relaxed);
+    });
+}
+
+/// 记录通过系统调用读取的字节数
+pub fn task_io_add_rchar(bytes: usize) {
+    with_current(|acct| {
+        acct.rchar.fetch_add(bytes as u64, Ordering::Relaxed);
+    });
+}
+
+/// 记录通过系统调用写入的字节数
+pub fn task_io_add_wchar(bytes: usize) {
+    with_current(|acct| {
+        acct.wchar.fetch_add(bytes as u64, Ordering::Relaxed);
+    });
+}
+
+/// 记录提交到块设备的读请求字节数
+pub fn task_io_account_read(bytes: usize) {
+    with_current(|acct| {
+        acct.read_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    });
+}
+
+/// 记录提交到块设备的写请求字节数
+pub fn task_io_account_write(bytes: usize) {
+    with_current(|acct| {
+        acct.write_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    });
+}
+
+/// 记录因截断而取消写回的脏数据字节数
+pub fn task_io_account_cancelled_write(bytes: usize) {
+    with_current(|acct| {
+        acct.cancelled_write_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    });
+}
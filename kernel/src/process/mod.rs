@@ -59,8 +59,8 @@ use crate::{
     },
     process::resource::{RLimit64, RLimitID},
     sched::{
-        DequeueFlag, EnqueueFlag, OnRq, SchedMode, WakeupFlags, __schedule, completion::Completion,
-        cpu_rq, fair::FairSchedEntity, prio::MAX_PRIO,
+        __schedule, completion::Completion, cpu_rq, fair::FairSchedEntity, prio::MAX_PRIO,
+        DequeueFlag, EnqueueFlag, OnRq, SchedMode, WakeupFlags,
     },
     smp::{
         core::smp_get_processor_id,
@@ -83,12 +83,15 @@ pub mod exit;
 pub mod fork;
 pub mod geteuid;
 pub mod idle;
+pub mod io_accounting;
 pub mod kthread;
 pub mod namespace;
 pub mod pid;
 pub mod posix_timer;
 pub mod preempt;
 pub mod process_group;
+pub mod ptrace;
+pub mod ptrauth;
 pub mod resource;
 pub mod rseq;
 pub mod session;
@@ -1175,6 +1178,12 @@ pub struct ProcessControlBlock {
     /// CPU时间片
     cpu_time: Arc<ProcessCpuTime>,
 
+    /// I/O统计
+    io_accounting: io_accounting::TaskIoAccounting,
+
+    /// 延迟统计
+    delays: crate::sched::delayacct::TaskDelayInfo,
+
     /// 进程的robust lock列表
     robust_list: RwLock<Option<RobustListHead>>,
 
@@ -1193,6 +1202,9 @@ pub struct ProcessControlBlock {
     cmdline: RwLock<Vec<u8>>,
     /// 资源限制（rlimit）数组
     rlimits: RwLock<[RLimit64; RLimitID::Nlimits as usize]>,
+    /// 当前的堆分配记在哪个分配点上，参见[`crate::mm::alloc_tag`]
+    #[cfg(feature = "alloc_profiling")]
+    alloc_tag: core::sync::atomic::AtomicPtr<crate::mm::alloc_tag::AllocTag>,
 }
 
 impl ProcessControlBlock {
@@ -1314,6 +1326,8 @@ impl ProcessControlBlock {
                 itimers: SpinLock::new(ProcessItimers::default()),
                 posix_timers: SpinLock::new(posix_timer::ProcessPosixTimers::default()),
                 cpu_time: Arc::new(ProcessCpuTime::default()),
+                io_accounting: io_accounting::TaskIoAccounting::default(),
+                delays: crate::sched::delayacct::TaskDelayInfo::default(),
                 robust_list: RwLock::new(None),
                 rseq_state: RwLock::new(rseq::RseqState::new()),
                 cred: SpinLock::new(cred),
@@ -1322,6 +1336,8 @@ impl ProcessControlBlock {
                 executable_path: RwLock::new(name),
                 cmdline: RwLock::new(Vec::new()),
                 rlimits: RwLock::new(Self::default_rlimits()),
+                #[cfg(feature = "alloc_profiling")]
+                alloc_tag: core::sync::atomic::AtomicPtr::new(core::ptr::null_mut()),
             };
 
             pcb.sig_info.write().set_tty(tty);
@@ -1551,6 +1567,23 @@ impl ProcessControlBlock {
         }
     }
 
+    /// 获取当前的分配点，为空表示没有被标记
+    #[cfg(feature = "alloc_profiling")]
+    #[inline(always)]
+    pub fn alloc_tag(&self) -> *const crate::mm::alloc_tag::AllocTag {
+        self.alloc_tag.load(Ordering::Relaxed)
+    }
+
+    /// 设置当前的分配点，返回原来的分配点
+    #[cfg(feature = "alloc_profiling")]
+    #[inline(always)]
+    pub fn swap_alloc_tag(
+        &self,
+        tag: *const crate::mm::alloc_tag::AllocTag,
+    ) -> *const crate::mm::alloc_tag::AllocTag {
+        self.alloc_tag.swap(tag as *mut _, Ordering::Relaxed)
+    }
+
     #[inline(always)]
     pub fn keepcaps(&self) -> bool {
         self.keepcaps.load(Ordering::SeqCst)
@@ -2329,6 +2362,9 @@ pub struct ProcessSchedulerInfo {
     pub on_rq: SpinLock<OnRq>,
 
     pub prio_data: RwLock<PrioData>,
+
+    /// 在PSI中被统计的状态
+    pub psi: SpinLock<crate::sched::psi::PsiTaskState>,
 }
 
 #[derive(Debug, Default)]
@@ -2411,6 +2447,7 @@ impl ProcessSchedulerInfo {
             sched_entity: FairSchedEntity::new(),
             on_rq: SpinLock::new(OnRq::None),
             prio_data: RwLock::new(PrioData::default()),
+            psi: SpinLock::new(crate::sched::psi::PsiTaskState::default()),
         };
     }
 
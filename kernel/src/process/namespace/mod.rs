@@ -6,6 +6,7 @@ pub mod nsproxy;
 pub mod pid_namespace;
 pub mod propagation;
 pub mod setns;
+pub mod time_namespace;
 pub mod unshare;
 pub mod user_namespace;
 pub mod uts_namespace;
@@ -8,6 +8,7 @@ use crate::process::{
         cgroup_namespace::{CgroupNamespace, INIT_CGROUP_NAMESPACE},
         mnt::{root_mnt_namespace, MntNamespace},
         net_namespace::{NetNamespace, INIT_NET_NAMESPACE},
+        time_namespace::{TimeNamespace, INIT_TIME_NAMESPACE},
         uts_namespace::{UtsNamespace, INIT_UTS_NAMESPACE},
     },
     ProcessControlBlock, ProcessManager,
@@ -55,10 +56,11 @@ pub struct NsProxy {
     pub net_ns: Arc<NetNamespace>,
     /// cgroup 命名空间
     pub cgroup_ns: Arc<CgroupNamespace>,
+    /// time 命名空间
+    pub time_ns: Arc<TimeNamespace>,
+    /// 子进程的 time 命名空间，与 time_ns 不同时，fork出的子进程和execve之后的进程进入这个命名空间
+    pub time_ns_for_children: Arc<TimeNamespace>,
     // 注意，user_ns 存储在cred,不存储在nsproxy
-
-    // 其他namespace（为未来扩展预留）
-    // pub time_ns: Option<Arc<TimeNamespace>>,
 }
 
 impl Debug for NsProxy {
@@ -76,6 +78,7 @@ impl NsProxy {
         let root_uts_ns = INIT_UTS_NAMESPACE.clone();
         let root_ipc_ns = INIT_IPC_NAMESPACE.clone();
         let root_cgroup_ns = INIT_CGROUP_NAMESPACE.clone();
+        let root_time_ns = INIT_TIME_NAMESPACE.clone();
         Arc::new(Self {
             pid_ns_for_children: root_pid_ns,
             mnt_ns: root_mnt_ns,
@@ -83,6 +86,8 @@ impl NsProxy {
             uts_ns: root_uts_ns,
             ipc_ns: root_ipc_ns,
             cgroup_ns: root_cgroup_ns,
+            time_ns: root_time_ns.clone(),
+            time_ns_for_children: root_time_ns,
         })
     }
 
@@ -109,8 +114,15 @@ impl NsProxy {
             uts_ns: self.uts_ns.clone(),
             ipc_ns: self.ipc_ns.clone(),
             cgroup_ns: self.cgroup_ns.clone(),
+            time_ns: self.time_ns.clone(),
+            time_ns_for_children: self.time_ns_for_children.clone(),
         }
     }
+
+    /// 进程的 time 命名空间和子进程的不同
+    pub fn time_ns_pending(&self) -> bool {
+        !Arc::ptr_eq(&self.time_ns, &self.time_ns_for_children)
+    }
 }
 
 impl ProcessManager {
@@ -120,7 +132,7 @@ impl ProcessManager {
     #[inline(never)]
     pub fn copy_namespaces(
         clone_flags: &CloneFlags,
-        _parent_pcb: &Arc<ProcessControlBlock>,
+        parent_pcb: &Arc<ProcessControlBlock>,
         child_pcb: &Arc<ProcessControlBlock>,
     ) -> Result<(), SystemError> {
         // log::debug!(
@@ -138,8 +150,8 @@ impl ProcessManager {
                 | CloneFlags::CLONE_NEWNET
                 | CloneFlags::CLONE_NEWCGROUP
                 | CloneFlags::CLONE_NEWTIME,
-        )) && clone_flags.contains(CloneFlags::CLONE_VM)
-        // || likely(parent_nsproxy.time_ns_for_children() == parent_nsproxy.time_ns())
+        )) && (clone_flags.contains(CloneFlags::CLONE_VM)
+            || likely(!parent_pcb.nsproxy().time_ns_pending()))
         {
             // 由于在创建pcb的时候已经默认继承了parent的nsproxy，所以这里不需要做任何操作
             return Ok(());
@@ -162,7 +174,10 @@ impl ProcessManager {
             return Err(SystemError::EINVAL);
         }
         let user_ns = child_pcb.cred().user_ns.clone();
-        let new_ns = create_new_namespaces(clone_flags, child_pcb, user_ns)?;
+        let mut new_ns = create_new_namespaces(clone_flags, child_pcb, user_ns)?;
+        if !clone_flags.contains(CloneFlags::CLONE_VM) {
+            new_ns = timens_on_fork(new_ns);
+        }
         // 设置新的nsproxy
 
         child_pcb.set_nsproxy(new_ns);
@@ -194,6 +209,9 @@ pub(super) fn create_new_namespaces(
     let cgroup_ns = nsproxy
         .cgroup_ns
         .copy_cgroup_ns(clone_flags, user_ns.clone())?;
+    let time_ns_for_children = nsproxy
+        .time_ns_for_children
+        .copy_time_ns(clone_flags, user_ns.clone())?;
 
     let result = NsProxy {
         pid_ns_for_children,
@@ -202,6 +220,8 @@ pub(super) fn create_new_namespaces(
         uts_ns,
         ipc_ns,
         cgroup_ns,
+        time_ns: nsproxy.time_ns.clone(),
+        time_ns_for_children,
     };
 
     let result = Arc::new(result);
@@ -252,14 +272,31 @@ impl Default for NsCommon {
 /// https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/nsproxy.c?fi=exec_task_namespaces#259
 pub fn exec_task_namespaces() -> Result<(), SystemError> {
     let tsk = ProcessManager::current_pcb();
+    if !tsk.nsproxy().time_ns_pending() {
+        return Ok(());
+    }
     let user_ns = tsk.cred().user_ns.clone();
     let new_nsproxy = create_new_namespaces(&CloneFlags::empty(), &tsk, user_ns)?;
-    // todo: time_ns的逻辑
-    switch_task_namespaces(&tsk, new_nsproxy)?;
+    switch_task_namespaces(&tsk, timens_on_fork(new_nsproxy))?;
 
     return Ok(());
 }
 
+/// # 让进程进入子进程的 time 命名空间
+///
+/// 在fork出不共享地址空间的子进程和execve时调用。进入之后命名空间的偏移量被冻结
+///
+/// https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/time/namespace.c#399
+fn timens_on_fork(nsproxy: Arc<NsProxy>) -> Arc<NsProxy> {
+    if !nsproxy.time_ns_pending() {
+        return nsproxy;
+    }
+    let mut new_inner = nsproxy.clone_inner();
+    new_inner.time_ns = new_inner.time_ns_for_children.clone();
+    new_inner.time_ns.freeze_offsets();
+    Arc::new(new_inner)
+}
+
 pub fn switch_task_namespaces(
     tsk: &Arc<ProcessControlBlock>,
     new_nsproxy: Arc<NsProxy>,
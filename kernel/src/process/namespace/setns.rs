@@ -7,19 +7,23 @@ use crate::{
     process::{fork::CloneFlags, ProcessManager, RawPid},
 };
 
-use super::nsproxy::{switch_task_namespaces, NsProxy};
+use super::{
+    nsproxy::{switch_task_namespaces, NsProxy},
+    time_namespace::TimeNamespace,
+};
 
 /// 内核态 setns 实现（当前仅支持 pidfd + namespace flag 形式）
 ///
 /// - `fd`：必须是通过 `pidfd_open` 或 `clone(CLONE_PIDFD)` 获得的 pidfd
 /// - `nstype`：命名空间 flag 组合，仅允许 CLONE_NEWNS/CLONE_NEWUTS/CLONE_NEWIPC/
-///   CLONE_NEWNET/CLONE_NEWPID/CLONE_NEWCGROUP，且不能为空
+///   CLONE_NEWNET/CLONE_NEWPID/CLONE_NEWCGROUP/CLONE_NEWTIME，且不能为空
 ///
 /// 语义（与 Linux setns(pidfd, flags) 对齐的子集）：
 /// - 针对指定 flag，从目标任务的 `NsProxy` 中拷贝对应 namespace 引用，
 ///   在当前任务上构造新的 `NsProxy` 并通过 `switch_task_namespaces` 原子替换
 /// - CLONE_NEWPID 仅影响 `pid_ns_for_children`（与 DragonOS/ Linux 一致）
-/// - 不支持 USER/TIME namespace 以及 `/proc/<pid>/ns/*` 路径
+/// - CLONE_NEWTIME 立即切换当前任务的 time namespace，要求当前任务是单线程的
+/// - 不支持 USER namespace
 #[inline(never)]
 pub fn ksys_setns(fd: i32, nstype: i32) -> Result<(), SystemError> {
     // 1. 解析并校验 flag
@@ -31,7 +35,8 @@ pub fn ksys_setns(fd: i32, nstype: i32) -> Result<(), SystemError> {
             | CloneFlags::CLONE_NEWIPC.bits()
             | CloneFlags::CLONE_NEWNET.bits()
             | CloneFlags::CLONE_NEWPID.bits()
-            | CloneFlags::CLONE_NEWCGROUP.bits(),
+            | CloneFlags::CLONE_NEWCGROUP.bits()
+            | CloneFlags::CLONE_NEWTIME.bits(),
     );
 
     // 不能包含未支持的位；对 pidfd 路径，后续会额外要求非空
@@ -96,6 +101,9 @@ pub fn ksys_setns(fd: i32, nstype: i32) -> Result<(), SystemError> {
         if flags.contains(CloneFlags::CLONE_NEWCGROUP) {
             new_inner.cgroup_ns = target_nsproxy.cgroup_ns.clone();
         }
+        if flags.contains(CloneFlags::CLONE_NEWTIME) {
+            timens_install(&mut new_inner, target_nsproxy.time_ns.clone())?;
+        }
 
         let new_nsproxy = Arc::new(new_inner);
         switch_task_namespaces(&current, new_nsproxy)?;
@@ -153,6 +161,12 @@ pub fn ksys_setns(fd: i32, nstype: i32) -> Result<(), SystemError> {
             }
             new_inner.cgroup_ns = ns;
         }
+        NamespaceFilePrivateData::Time(ns) => {
+            if !flags.is_empty() && !flags.contains(CloneFlags::CLONE_NEWTIME) {
+                return Err(SystemError::EINVAL);
+            }
+            timens_install(&mut new_inner, ns)?;
+        }
     }
 
     let new_nsproxy = Arc::new(new_inner);
@@ -162,3 +176,23 @@ pub fn ksys_setns(fd: i32, nstype: i32) -> Result<(), SystemError> {
 
     Ok(())
 }
+
+/// # 切换到指定的 time namespace
+///
+/// 与其他命名空间不同，time namespace 会立即生效：当前任务和之后的子进程都进入这个命名空间，
+/// 命名空间的偏移量随之冻结。同一个线程组的其他线程共享地址空间，必须处于同一个 time namespace，
+/// 所以只允许单线程的任务切换
+///
+/// https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/time/namespace.c#336
+fn timens_install(nsproxy: &mut NsProxy, ns: Arc<TimeNamespace>) -> Result<(), SystemError> {
+    if !ProcessManager::current_pcb()
+        .threads_read_irqsave()
+        .thread_group_empty()
+    {
+        return Err(SystemError::EUSERS);
+    }
+    ns.freeze_offsets();
+    nsproxy.time_ns = ns.clone();
+    nsproxy.time_ns_for_children = ns;
+    Ok(())
+}
@@ -0,0 +1,187 @@
+//! Time namespace
+//!
+//! 每个time namespace保存CLOCK_MONOTONIC和CLOCK_BOOTTIME相对于宿主的偏移量，命名空间内的进程
+//! 读到的这两组时钟（包括对应的RAW/COARSE/ALARM时钟）都会加上偏移量，CLOCK_REALTIME不受影响。
+//! 检查点/恢复（CRIU）用它让恢复后的进程看到连续的单调时间。
+//!
+//! 与Linux一致，`clone(CLONE_NEWTIME)`和`unshare(CLONE_NEWTIME)`只会创建新的`time_ns_for_children`，
+//! 调用者自己仍然留在原来的命名空间。之后fork出的（不共享地址空间的）子进程，或者调用者自己execve时，
+//! 才会进入新的命名空间。在此之前可以通过`/proc/<pid>/timens_offsets`设置偏移量，
+//! 第一个进程进入之后偏移量就被冻结，不能再修改。
+//!
+//! vDSO实现之后，需要把当前命名空间的偏移量（[`TimeNamespace::offsets`]）提供给vDSO的时钟读取路径，
+//! 否则通过vDSO读到的是宿主的时间。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/time/namespace.c
+
+use alloc::sync::{Arc, Weak};
+use core::sync::atomic::{AtomicBool, Ordering};
+use system_error::SystemError;
+
+use crate::libs::spinlock::SpinLock;
+use crate::process::fork::CloneFlags;
+use crate::process::namespace::nsproxy::NsCommon;
+use crate::process::namespace::user_namespace::{UserNamespace, INIT_USER_NAMESPACE};
+use crate::process::namespace::{NamespaceOps, NamespaceType};
+use crate::process::ProcessManager;
+use crate::time::syscall::{posix_clock_host_now, PosixClockID};
+use crate::time::{PosixTimeSpec, NSEC_PER_SEC};
+
+lazy_static! {
+    pub static ref INIT_TIME_NAMESPACE: Arc<TimeNamespace> = TimeNamespace::new_root();
+}
+
+/// 偏移量允许的最大秒数（与Linux的KTIME_SEC_MAX相同）
+const KTIME_SEC_MAX: i64 = i64::MAX / NSEC_PER_SEC as i64;
+
+/// 命名空间中各个时钟的偏移量
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeNsOffsets {
+    pub monotonic: PosixTimeSpec,
+    pub boottime: PosixTimeSpec,
+}
+
+pub struct TimeNamespace {
+    ns_common: NsCommon,
+    self_ref: Weak<TimeNamespace>,
+    /// 关联的 user namespace (权限判断使用)
+    _user_ns: Arc<UserNamespace>,
+    offsets: SpinLock<TimeNsOffsets>,
+    /// 已经有进程进入了这个命名空间，偏移量不能再修改
+    frozen_offsets: AtomicBool,
+}
+
+impl NamespaceOps for TimeNamespace {
+    fn ns_common(&self) -> &NsCommon {
+        &self.ns_common
+    }
+}
+
+impl TimeNamespace {
+    fn new_root() -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| Self {
+            ns_common: NsCommon::new(0, NamespaceType::Time),
+            self_ref: weak_self.clone(),
+            _user_ns: INIT_USER_NAMESPACE.clone(),
+            offsets: SpinLock::new(TimeNsOffsets::default()),
+            frozen_offsets: AtomicBool::new(true),
+        })
+    }
+
+    /// # 根据clone flags复制time namespace
+    ///
+    /// 设置了`CLONE_NEWTIME`时创建新的命名空间，继承当前命名空间的偏移量，否则返回当前命名空间
+    pub fn copy_time_ns(
+        &self,
+        clone_flags: &CloneFlags,
+        user_ns: Arc<UserNamespace>,
+    ) -> Result<Arc<TimeNamespace>, SystemError> {
+        if !clone_flags.contains(CloneFlags::CLONE_NEWTIME) {
+            return Ok(self.self_ref.upgrade().unwrap());
+        }
+
+        let new_time_ns = Arc::new_cyclic(|weak_self| TimeNamespace {
+            ns_common: NsCommon::new(self.ns_common.level + 1, NamespaceType::Time),
+            self_ref: weak_self.clone(),
+            _user_ns: user_ns,
+            offsets: SpinLock::new(self.offsets()),
+            frozen_offsets: AtomicBool::new(false),
+        });
+
+        Ok(new_time_ns)
+    }
+
+    /// 命名空间中各个时钟的偏移量
+    pub fn offsets(&self) -> TimeNsOffsets {
+        *self.offsets.lock_irqsave()
+    }
+
+    /// 有进程进入了这个命名空间，冻结偏移量
+    pub fn freeze_offsets(&self) {
+        self.frozen_offsets.store(true, Ordering::SeqCst);
+    }
+
+    /// # 设置偏移量
+    ///
+    /// 要么全部设置成功，要么一个都不设置
+    ///
+    /// ## 参数
+    /// - `new_offsets`: (时钟, 偏移量)，时钟只能是CLOCK_MONOTONIC或者CLOCK_BOOTTIME
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EINVAL)`: 时钟不支持偏移，或者纳秒部分不在[0, 1s)内
+    /// - `Err(SystemError::ERANGE)`: 加上偏移量之后的时间为负数或者过大
+    /// - `Err(SystemError::EACCES)`: 已经有进程进入了这个命名空间
+    pub fn set_offsets(
+        &self,
+        new_offsets: &[(PosixClockID, PosixTimeSpec)],
+    ) -> Result<(), SystemError> {
+        for (clock, offset) in new_offsets {
+            if !matches!(clock, PosixClockID::Monotonic | PosixClockID::Boottime) {
+                return Err(SystemError::EINVAL);
+            }
+            if offset.tv_nsec < 0 || offset.tv_nsec >= NSEC_PER_SEC as i64 {
+                return Err(SystemError::EINVAL);
+            }
+            if offset.tv_sec > KTIME_SEC_MAX || offset.tv_sec < -KTIME_SEC_MAX {
+                return Err(SystemError::ERANGE);
+            }
+            // 保证KTIME_MAX离得足够远，所以上限取一半
+            let now = timespec_add(posix_clock_host_now(*clock), *offset);
+            if now.tv_sec < 0 || now.tv_sec > KTIME_SEC_MAX / 2 {
+                return Err(SystemError::ERANGE);
+            }
+        }
+
+        let mut offsets = self.offsets.lock_irqsave();
+        if self.frozen_offsets.load(Ordering::SeqCst) {
+            return Err(SystemError::EACCES);
+        }
+        for (clock, offset) in new_offsets {
+            match clock {
+                PosixClockID::Monotonic => offsets.monotonic = *offset,
+                PosixClockID::Boottime => offsets.boottime = *offset,
+                _ => unreachable!(),
+            }
+        }
+        Ok(())
+    }
+
+    /// # 把宿主的时间转换为命名空间中的时间
+    ///
+    /// ## 参数
+    /// - `clock_id`: 时钟，只有单调时钟和启动时钟会加上偏移量
+    /// - `ts`: 宿主的时间
+    pub fn to_ns_time(&self, clock_id: PosixClockID, ts: PosixTimeSpec) -> PosixTimeSpec {
+        let offset = match clock_id {
+            PosixClockID::Monotonic
+            | PosixClockID::MonotonicRaw
+            | PosixClockID::MonotonicCoarse => self.offsets.lock_irqsave().monotonic,
+            PosixClockID::Boottime | PosixClockID::BoottimeAlarm => {
+                self.offsets.lock_irqsave().boottime
+            }
+            _ => return ts,
+        };
+        timespec_add(ts, offset)
+    }
+}
+
+/// 两个时间相加，结果的纳秒部分在[0, 1s)内
+fn timespec_add(a: PosixTimeSpec, b: PosixTimeSpec) -> PosixTimeSpec {
+    let nsec = a.tv_nsec + b.tv_nsec;
+    let sec = a
+        .tv_sec
+        .saturating_add(b.tv_sec)
+        .saturating_add(nsec.div_euclid(NSEC_PER_SEC as i64));
+    PosixTimeSpec::new(sec, nsec.rem_euclid(NSEC_PER_SEC as i64))
+}
+
+impl ProcessManager {
+    pub fn current_timens() -> Arc<TimeNamespace> {
+        if Self::initialized() {
+            ProcessManager::current_pcb().nsproxy.read().time_ns.clone()
+        } else {
+            INIT_TIME_NAMESPACE.clone()
+        }
+    }
+}
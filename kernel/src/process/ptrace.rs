@@ -0,0 +1,50 @@
+//! ptrace相关的权限检查
+//!
+//! 目前还不支持ptrace系统调用本身，这里只提供访问其他进程时需要的权限检查，
+//! 供process_vm_readv/writev、kcmp、/proc/<pid>/pagemap等使用。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/ptrace.c
+
+use alloc::sync::Arc;
+use system_error::SystemError;
+
+use crate::process::{cred::CAPFlags, ProcessControlBlock, ProcessManager};
+
+/// # 检查当前进程是否可以访问目标进程（读取内存、比较内核对象等）
+///
+/// Linux中`__ptrace_may_access()`的简化版本，满足以下任意一个条件即可访问：
+/// 1. 目标进程就是当前进程
+/// 2. 当前进程拥有CAP_SYS_PTRACE
+/// 3. 当前进程的uid/gid与目标进程的uid/euid/suid和gid/egid/sgid都相同
+///
+/// ## 返回值
+/// - `Err(SystemError::EPERM)`: 没有权限
+pub fn ptrace_may_access(target_pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+    let current_pcb = ProcessManager::current_pcb();
+
+    if Arc::ptr_eq(&current_pcb, target_pcb) {
+        return Ok(());
+    }
+
+    let current_cred = current_pcb.cred();
+    let target_cred = target_pcb.cred();
+
+    if current_cred.has_capability(CAPFlags::CAP_SYS_PTRACE) {
+        return Ok(());
+    }
+
+    // 与PTRACE_MODE_REALCREDS一致，使用当前进程的真实uid/gid
+    let uid_match = current_cred.uid == target_cred.euid
+        && current_cred.uid == target_cred.suid
+        && current_cred.uid == target_cred.uid;
+
+    let gid_match = current_cred.gid == target_cred.egid
+        && current_cred.gid == target_cred.sgid
+        && current_cred.gid == target_cred.gid;
+
+    if uid_match && gid_match {
+        return Ok(());
+    }
+
+    Err(SystemError::EPERM)
+}
@@ -0,0 +1,70 @@
+//! 指针认证（ARM PAC）相关的prctl接口
+//!
+//! 目前没有架构实现指针认证，这里只负责参数检查，并在不支持时返回`EINVAL`（与Linux在非arm64上的行为一致）。
+//! 架构支持PAC之后，在参数检查之后接入密钥的生成/启用即可。
+
+use system_error::SystemError;
+
+bitflags! {
+    /// 指针认证密钥
+    pub struct PtrAuthKeys: usize {
+        /// 指令地址密钥A
+        const APIA = 1 << 0;
+        /// 指令地址密钥B
+        const APIB = 1 << 1;
+        /// 数据地址密钥A
+        const APDA = 1 << 2;
+        /// 数据地址密钥B
+        const APDB = 1 << 3;
+        /// 通用密钥
+        const APGA = 1 << 4;
+    }
+}
+
+impl PtrAuthKeys {
+    /// 可以通过`PR_PAC_SET_ENABLED_KEYS`单独开关的地址密钥
+    pub const ADDRESS_KEYS: Self = Self::from_bits_truncate(
+        Self::APIA.bits() | Self::APIB.bits() | Self::APDA.bits() | Self::APDB.bits(),
+    );
+}
+
+/// # 功能
+///
+/// 处理`PR_PAC_RESET_KEYS`，为当前进程重新生成指定的密钥。
+///
+/// ## 参数
+/// - `keys`: 要重置的密钥，为0表示全部重置
+///
+/// ## 返回值
+/// - `Err(SystemError::EINVAL)`: 参数非法，或者当前架构不支持指针认证
+pub fn ptrauth_prctl_reset_keys(keys: usize) -> Result<usize, SystemError> {
+    PtrAuthKeys::from_bits(keys).ok_or(SystemError::EINVAL)?;
+    // 还没有架构实现指针认证
+    Err(SystemError::EINVAL)
+}
+
+/// # 功能
+///
+/// 处理`PR_PAC_SET_ENABLED_KEYS`，开启或关闭当前线程的地址密钥。
+///
+/// ## 参数
+/// - `keys`: 要修改的密钥
+/// - `enabled`: `keys`中需要开启的密钥，必须是`keys`的子集
+///
+/// ## 返回值
+/// - `Err(SystemError::EINVAL)`: 参数非法，或者当前架构不支持指针认证
+pub fn ptrauth_set_enabled_keys(keys: usize, enabled: usize) -> Result<usize, SystemError> {
+    let keys = PtrAuthKeys::from_bits(keys).ok_or(SystemError::EINVAL)?;
+    let enabled = PtrAuthKeys::from_bits(enabled).ok_or(SystemError::EINVAL)?;
+    if !PtrAuthKeys::ADDRESS_KEYS.contains(keys) || !keys.contains(enabled) {
+        return Err(SystemError::EINVAL);
+    }
+    // 还没有架构实现指针认证
+    Err(SystemError::EINVAL)
+}
+
+/// 处理`PR_PAC_GET_ENABLED_KEYS`，返回当前线程已开启的地址密钥
+pub fn ptrauth_get_enabled_keys() -> Result<usize, SystemError> {
+    // 还没有架构实现指针认证
+    Err(SystemError::EINVAL)
+}
@@ -0,0 +1,160 @@
+//! kcmp系统调用
+//!
+//! 比较两个进程是否共享同一个内核对象（文件、地址空间、文件描述符表等），
+//! 检查点/恢复工具用它判断哪些资源在进程之间是共享的。
+//!
+//! 为了不泄露内核地址，对象地址会先用每种类型各自的随机数混淆，再比较大小。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/kcmp.c
+
+use crate::arch::interrupt::TrapFrame;
+use crate::arch::rand::rand;
+use crate::arch::syscall::nr::SYS_KCMP;
+use crate::process::ptrace::ptrace_may_access;
+use crate::process::{ProcessControlBlock, ProcessManager, RawPid};
+use crate::syscall::table::{FormattedSyscallParam, Syscall};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use system_error::SystemError;
+
+const KCMP_FILE: usize = 0;
+const KCMP_VM: usize = 1;
+const KCMP_FILES: usize = 2;
+const KCMP_FS: usize = 3;
+const KCMP_SIGHAND: usize = 4;
+const KCMP_IO: usize = 5;
+const KCMP_SYSVSEM: usize = 6;
+const KCMP_EPOLL_TFD: usize = 7;
+const KCMP_TYPES: usize = 8;
+
+lazy_static! {
+    /// 每种类型的混淆参数，乘数必须是奇数
+    static ref KCMP_COOKIES: [(usize, usize); KCMP_TYPES] =
+        core::array::from_fn(|_| (rand(), rand() | 1));
+}
+
+pub struct SysKcmp;
+
+impl SysKcmp {
+    fn pid1(args: &[usize]) -> RawPid {
+        RawPid::new(args[0])
+    }
+
+    fn pid2(args: &[usize]) -> RawPid {
+        RawPid::new(args[1])
+    }
+
+    fn ty(args: &[usize]) -> usize {
+        args[2]
+    }
+
+    fn idx1(args: &[usize]) -> usize {
+        args[3]
+    }
+
+    fn idx2(args: &[usize]) -> usize {
+        args[4]
+    }
+
+    /// 混淆对象地址
+    fn obfuscate(ptr: usize, ty: usize) -> usize {
+        let (xor, mul) = KCMP_COOKIES[ty];
+        (ptr ^ xor).wrapping_mul(mul)
+    }
+
+    /// # 比较两个对象
+    ///
+    /// ## 返回值
+    /// - 0: 是同一个对象
+    /// - 1: 第一个对象小于第二个对象
+    /// - 2: 第一个对象大于第二个对象
+    fn kcmp_ptr(ptr1: usize, ptr2: usize, ty: usize) -> usize {
+        match Self::obfuscate(ptr1, ty).cmp(&Self::obfuscate(ptr2, ty)) {
+            Ordering::Equal => 0,
+            Ordering::Less => 1,
+            Ordering::Greater => 2,
+        }
+    }
+
+    /// 获取进程中文件描述符对应的文件的地址
+    fn file_ptr(pcb: &Arc<ProcessControlBlock>, fd: usize) -> Result<usize, SystemError> {
+        let fd = i32::try_from(fd).map_err(|_| SystemError::EBADF)?;
+        let file = pcb
+            .fd_table()
+            .read()
+            .get_file_by_fd(fd)
+            .ok_or(SystemError::EBADF)?;
+        Ok(Arc::as_ptr(&file) as usize)
+    }
+}
+
+impl Syscall for SysKcmp {
+    fn num_args(&self) -> usize {
+        5
+    }
+
+    /// # 比较两个进程的内核对象
+    ///
+    /// ## 参数
+    /// - pid1, pid2: 两个进程的pid
+    /// - type: 比较的对象类型
+    /// - idx1, idx2: KCMP_FILE时为两个进程中的文件描述符
+    ///
+    /// ## 返回值
+    /// 见[`SysKcmp::kcmp_ptr`]
+    fn handle(&self, args: &[usize], _frame: &mut TrapFrame) -> Result<usize, SystemError> {
+        let ty = Self::ty(args);
+        let pcb1 = ProcessManager::find_task_by_vpid(Self::pid1(args)).ok_or(SystemError::ESRCH)?;
+        let pcb2 = ProcessManager::find_task_by_vpid(Self::pid2(args)).ok_or(SystemError::ESRCH)?;
+
+        ptrace_may_access(&pcb1)?;
+        ptrace_may_access(&pcb2)?;
+
+        let (ptr1, ptr2) = match ty {
+            KCMP_FILE => (
+                Self::file_ptr(&pcb1, Self::idx1(args))?,
+                Self::file_ptr(&pcb2, Self::idx2(args))?,
+            ),
+            KCMP_VM => {
+                let vm_ptr = |pcb: &Arc<ProcessControlBlock>| {
+                    pcb.basic()
+                        .user_vm()
+                        .map_or(0, |vm| Arc::as_ptr(&vm) as usize)
+                };
+                (vm_ptr(&pcb1), vm_ptr(&pcb2))
+            }
+            KCMP_FILES => (
+                Arc::as_ptr(&pcb1.fd_table()) as usize,
+                Arc::as_ptr(&pcb2.fd_table()) as usize,
+            ),
+            KCMP_FS => (
+                Arc::as_ptr(&pcb1.fs_struct()) as usize,
+                Arc::as_ptr(&pcb2.fs_struct()) as usize,
+            ),
+            KCMP_SIGHAND => (
+                Arc::as_ptr(&pcb1.sighand()) as usize,
+                Arc::as_ptr(&pcb2.sighand()) as usize,
+            ),
+            // 还没有io_context，与Linux中两个进程都没有io_context时的结果相同
+            KCMP_IO => (0, 0),
+            // 还不支持System V信号量和epoll
+            KCMP_SYSVSEM | KCMP_EPOLL_TFD => return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP),
+            _ => return Err(SystemError::EINVAL),
+        };
+
+        Ok(Self::kcmp_ptr(ptr1, ptr2, ty))
+    }
+
+    fn entry_format(&self, args: &[usize]) -> Vec<FormattedSyscallParam> {
+        vec![
+            FormattedSyscallParam::new("pid1", format!("{}", Self::pid1(args).data())),
+            FormattedSyscallParam::new("pid2", format!("{}", Self::pid2(args).data())),
+            FormattedSyscallParam::new("type", format!("{}", Self::ty(args))),
+            FormattedSyscallParam::new("idx1", format!("{:#x}", Self::idx1(args))),
+            FormattedSyscallParam::new("idx2", format!("{:#x}", Self::idx2(args))),
+        ]
+    }
+}
+
+syscall_table_macros::declare_syscall!(SYS_KCMP, SysKcmp);
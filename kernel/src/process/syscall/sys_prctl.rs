@@ -9,7 +9,10 @@ use crate::process::cred::{CAPFlags, Cred};
 
 use crate::{
     arch::{interrupt::TrapFrame, ipc::signal::Signal, syscall::nr::SYS_PRCTL},
-    process::ProcessManager,
+    process::{
+        ptrauth::{ptrauth_get_enabled_keys, ptrauth_prctl_reset_keys, ptrauth_set_enabled_keys},
+        ProcessManager,
+    },
     syscall::{
         table::{FormattedSyscallParam, Syscall},
         user_access::{UserBufferReader, UserBufferWriter},
@@ -58,6 +61,10 @@ enum PrctlOption {
 
     SetNoNewPrivs = 38,
     GetNoNewPrivs = 39,
+
+    PacResetKeys = 54,
+    PacSetEnabledKeys = 60,
+    PacGetEnabledKeys = 61,
 }
 
 impl TryFrom<usize> for PrctlOption {
@@ -241,6 +248,25 @@ impl Syscall for SysPrctl {
                 Ok(0)
             }
             PrctlOption::GetNoNewPrivs => Ok(current.no_new_privs()),
+
+            PrctlOption::PacResetKeys => {
+                if args[2] != 0 || args[3] != 0 || args[4] != 0 {
+                    return Err(SystemError::EINVAL);
+                }
+                ptrauth_prctl_reset_keys(arg2)
+            }
+            PrctlOption::PacSetEnabledKeys => {
+                if args[3] != 0 || args[4] != 0 {
+                    return Err(SystemError::EINVAL);
+                }
+                ptrauth_set_enabled_keys(arg2, args[2])
+            }
+            PrctlOption::PacGetEnabledKeys => {
+                if arg2 != 0 || args[2] != 0 || args[3] != 0 || args[4] != 0 {
+                    return Err(SystemError::EINVAL);
+                }
+                ptrauth_get_enabled_keys()
+            }
         }
     }
 
@@ -0,0 +1,112 @@
+//! 任务的延迟统计（delay accounting）
+//!
+//! 记录任务由于资源不足而无法运行的时间：
+//! - CPU：可运行但在运行队列上等待的时间，即[`SchedInfo`](crate::process::SchedInfo)的`run_delay`
+//! - 块设备IO：在`io_schedule()`中睡眠等待IO完成的时间
+//! - 内存回收：回收内存花费的时间
+//!
+//! 参考 Linux kernel/delayacct.c
+
+use crate::{
+    libs::spinlock::SpinLock,
+    process::{ProcessControlBlock, ProcessManager},
+    smp::core::smp_get_processor_id,
+};
+
+use super::clock::SchedClock;
+
+/// 单个任务的延迟统计
+#[derive(Debug)]
+pub struct TaskDelayInfo {
+    inner: SpinLock<TaskDelayInner>,
+}
+
+impl Default for TaskDelayInfo {
+    fn default() -> Self {
+        Self {
+            inner: SpinLock::new(TaskDelayInner::default()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TaskDelayInner {
+    blkio_start: u64,
+    freepages_start: u64,
+    stats: TaskDelayStats,
+}
+
+/// 延迟统计的快照，时间单位为ns
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TaskDelayStats {
+    pub blkio_delay: u64,
+    pub blkio_count: u64,
+    pub freepages_delay: u64,
+    pub freepages_count: u64,
+}
+
+#[inline]
+fn delayacct_clock() -> u64 {
+    SchedClock::sched_clock_cpu(smp_get_processor_id())
+}
+
+impl TaskDelayInfo {
+    pub fn snapshot(&self) -> TaskDelayStats {
+        self.inner.lock_irqsave().stats
+    }
+
+    /// 任务开始等待块设备IO
+    pub fn blkio_start(&self) {
+        self.inner.lock_irqsave().blkio_start = delayacct_clock();
+    }
+
+    /// 等待块设备IO的任务被唤醒
+    pub fn blkio_end(&self) {
+        let now = delayacct_clock();
+        let mut inner = self.inner.lock_irqsave();
+        if inner.blkio_start == 0 {
+            return;
+        }
+        inner.stats.blkio_delay += now.saturating_sub(inner.blkio_start);
+        inner.stats.blkio_count += 1;
+        inner.blkio_start = 0;
+    }
+
+    fn freepages_start(&self) {
+        self.inner.lock_irqsave().freepages_start = delayacct_clock();
+    }
+
+    fn freepages_end(&self) {
+        let now = delayacct_clock();
+        let mut inner = self.inner.lock_irqsave();
+        if inner.freepages_start == 0 {
+            return;
+        }
+        inner.stats.freepages_delay += now.saturating_sub(inner.freepages_start);
+        inner.stats.freepages_count += 1;
+        inner.freepages_start = 0;
+    }
+}
+
+impl ProcessControlBlock {
+    #[inline(always)]
+    pub fn delays(&self) -> &TaskDelayInfo {
+        &self.delays
+    }
+}
+
+/// 当前任务开始回收内存
+pub fn delayacct_freepages_start() {
+    if !ProcessManager::initialized() {
+        return;
+    }
+    ProcessManager::current_pcb().delays().freepages_start();
+}
+
+/// 当前任务结束回收内存
+pub fn delayacct_freepages_end() {
+    if !ProcessManager::initialized() {
+        return;
+    }
+    ProcessManager::current_pcb().delays().freepages_end();
+}
@@ -18,7 +18,7 @@ static AVENRUN: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU6
 static NR_RUNNING: AtomicUsize = AtomicUsize::new(0);
 static NR_UNINTERRUPTIBLE: AtomicUsize = AtomicUsize::new(0);
 
-fn calc_load(load: u64, exp: u64, active: u64) -> u64 {
+pub(super) fn calc_load(load: u64, exp: u64, active: u64) -> u64 {
     let mut newload: u128 =
         (load as u128) * (exp as u128) + (active as u128) * ((FIXED_1 - exp) as u128);
     if active >= load {
@@ -50,7 +50,7 @@ fn fixed_power_int(mut x: u64, frac_bits: u32, mut n: u64) -> u64 {
     result as u64
 }
 
-fn calc_load_n(load: u64, exp: u64, active: u64, n: u64) -> u64 {
+pub(super) fn calc_load_n(load: u64, exp: u64, active: u64, n: u64) -> u64 {
     calc_load(load, fixed_power_int(exp, FSHIFT, n), active)
 }
 
@@ -1,6 +1,7 @@
 pub mod clock;
 pub mod completion;
 pub mod cputime;
+pub mod delayacct;
 pub mod fair;
 pub mod fifo;
 #[cfg(feature = "fifo_demo")]
@@ -9,6 +10,7 @@ pub mod idle;
 pub mod loadavg;
 pub mod pelt;
 pub mod prio;
+pub mod psi;
 pub mod syscall;
 
 use core::{
@@ -458,11 +460,38 @@ impl CpuRunQueue {
         }
     }
 
+    /// 任务开始在CPU上运行，统计其在运行队列上等待的时间
+    fn sched_info_arrive(&mut self, pcb: &Arc<ProcessControlBlock>) {
+        let mut sched_info = pcb.sched_info().sched_stat.write_irqsave();
+        if sched_info.last_queued == 0 {
+            return;
+        }
+        let delta = self.clock.saturating_sub(sched_info.last_queued);
+        sched_info.last_queued = 0;
+        sched_info.run_delay += delta as usize;
+        sched_info.last_arrival = self.clock;
+        sched_info.pcount += 1;
+
+        self.sched_info.run_delay += delta as usize;
+        self.sched_info.pcount += 1;
+    }
+
+    /// 任务离开CPU。如果它仍然可运行，则重新开始计算等待时间
+    fn sched_info_depart(&mut self, pcb: &Arc<ProcessControlBlock>) {
+        if *pcb.sched_info().on_rq.lock_irqsave() == OnRq::Queued {
+            let mut sched_info = pcb.sched_info().sched_stat.write_irqsave();
+            if sched_info.last_queued == 0 {
+                sched_info.last_queued = self.clock;
+            }
+        }
+    }
+
     /// 启用一个任务，将加入队列
     pub fn activate_task(&mut self, pcb: &Arc<ProcessControlBlock>, mut flags: EnqueueFlag) {
         // 如果进程之前因 IO 等待而睡眠，现在被唤醒，减少 nr_iowait 计数
         if pcb.flags().contains(ProcessFlags::IN_IOWAIT) {
             self.nr_iowait.fetch_sub(1, Ordering::Relaxed);
+            pcb.delays().blkio_end();
         }
 
         if *pcb.sched_info().on_rq.lock_irqsave() == OnRq::Migrating {
@@ -474,6 +503,7 @@ impl CpuRunQueue {
         }
 
         self.enqueue_task(pcb.clone(), flags);
+        psi::psi_enqueue(pcb, self.cpu);
 
         *pcb.sched_info().on_rq.lock_irqsave() = OnRq::Queued;
         pcb.sched_info().set_on_cpu(Some(self.cpu));
@@ -517,18 +547,19 @@ impl CpuRunQueue {
         }
 
         // 如果进程因 IO 等待而睡眠，增加 nr_iowait 计数
-        if flags.contains(DequeueFlag::DEQUEUE_SLEEP)
-            && pcb.flags().contains(ProcessFlags::IN_IOWAIT)
-        {
+        let iowait = flags.contains(DequeueFlag::DEQUEUE_SLEEP)
+            && pcb.flags().contains(ProcessFlags::IN_IOWAIT);
+        if iowait {
             self.nr_iowait.fetch_add(1, Ordering::Relaxed);
+            pcb.delays().blkio_start();
         }
 
-        *pcb.sched_info().on_rq.lock_irqsave() =
-            if flags.intersects(DequeueFlag::DEQUEUE_SLEEP | DequeueFlag::DEQUEUE_STOPPED) {
-                OnRq::None
-            } else {
-                OnRq::Migrating
-            };
+        let sleep = flags.intersects(DequeueFlag::DEQUEUE_SLEEP | DequeueFlag::DEQUEUE_STOPPED);
+        if sleep {
+            psi::psi_dequeue(&pcb, self.cpu, iowait);
+        }
+
+        *pcb.sched_info().on_rq.lock_irqsave() = if sleep { OnRq::None } else { OnRq::Migrating };
 
         self.dequeue_task(pcb, flags);
     }
@@ -957,6 +988,12 @@ pub fn __schedule(sched_mod: SchedMode) {
         }
 
         rq.set_current(Arc::downgrade(&next));
+
+        if prev.sched_info().policy() != SchedPolicy::IDLE {
+            rq.sched_info_depart(&prev);
+        }
+        rq.sched_info_arrive(&next);
+        psi::psi_task_switch(&prev, &next, rq.cpu);
         // warn!(
         //     "switch_process prev {:?} next {:?} sched_mode {sched_mod:?}",
         //     prev.pid(),
@@ -0,0 +1,704 @@
+//! 压力阻塞信息（Pressure Stall Information，PSI）
+//!
+//! 统计任务因为CPU、IO、内存资源不足而无法推进的时间占比，导出到
+//! /proc/pressure/{cpu,io,memory}。
+//!
+//! 每个CPU根据其上任务的状态（可运行、正在运行、等待IO、内存阻塞）得到当前处于哪些
+//! 压力状态，并累计各状态持续的时间：
+//! - `some`: 至少有一个任务因该资源而阻塞
+//! - `full`: 所有非空闲的任务都因该资源而阻塞
+//!
+//! 汇总时各CPU的时间按照其非空闲时间加权。汇总结果用于：
+//! - 每2秒计算一次10s/60s/300s的指数移动平均；
+//! - 存在触发器时，以触发器窗口的1/10为周期检查阻塞时间的增长，超过阈值则通过
+//!   `POLLPRI`通知用户态。
+//!
+//! 参考 Linux kernel/sched/psi.c
+//!
+//! 目前cgroup尚未实现，只有系统级的[`PsiGroup`]。
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use system_error::SystemError;
+
+use crate::{
+    exception::workqueue::{schedule_work, Work},
+    filesystem::epoll::{
+        event_poll::{EventPoll, LockedEPItemLinkedList},
+        EPollEventType,
+    },
+    libs::spinlock::SpinLock,
+    mm::percpu::PerCpu,
+    process::{ProcessControlBlock, ProcessManager},
+    smp::{
+        core::smp_get_processor_id,
+        cpu::{smp_cpu_manager, ProcessorId},
+    },
+    time::{NSEC_PER_SEC, NSEC_PER_USEC},
+};
+
+use super::{
+    clock::SchedClock,
+    loadavg::{calc_load, calc_load_n, FIXED_1},
+    SchedPolicy,
+};
+
+/// 计算移动平均的周期
+const PSI_PERIOD: u64 = 2 * NSEC_PER_SEC as u64;
+
+/// 10s/60s/300s移动平均每个周期的衰减系数，即`FIXED_1 / exp(2s / 窗口)`
+const EXP_10S: u64 = 1677;
+const EXP_60S: u64 = 1981;
+const EXP_300S: u64 = 2034;
+
+/// 触发器窗口的取值范围（微秒）
+const WINDOW_MIN_US: u64 = 500_000;
+const WINDOW_MAX_US: u64 = 10_000_000;
+/// 每个触发器窗口内检查的次数
+const UPDATES_PER_WINDOW: u64 = 10;
+
+bitflags! {
+    /// 任务在PSI中被统计的状态
+    #[derive(Default)]
+    pub struct PsiTaskFlags: u32 {
+        /// 在`io_schedule()`中睡眠，等待IO完成
+        const IOWAIT = 1 << 0;
+        /// 因内存不足而阻塞（例如正在回收内存）
+        const MEMSTALL = 1 << 1;
+        /// 可运行（在运行队列上）
+        const RUNNING = 1 << 2;
+        /// 正在CPU上运行
+        const ONCPU = 1 << 3;
+        /// 同时处于MEMSTALL和RUNNING
+        const MEMSTALL_RUNNING = 1 << 4;
+    }
+}
+
+const NR_PSI_TASK_COUNTS: usize = 5;
+
+/// 压力状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PsiState {
+    IoSome = 0,
+    IoFull,
+    MemSome,
+    MemFull,
+    CpuSome,
+    CpuFull,
+    /// 只用于加权，不对外导出
+    NonIdle,
+}
+
+const NR_PSI_STATES: usize = 7;
+/// 对外导出的状态数量（不含`NonIdle`）
+const NR_PSI_EXPORTED: usize = NR_PSI_STATES - 1;
+
+/// 汇总CPU时间的使用者，各自记录上一次汇总到的位置
+#[derive(Debug, Clone, Copy)]
+enum PsiAggregator {
+    Avgs = 0,
+    Poll,
+}
+
+const NR_PSI_AGGREGATORS: usize = 2;
+
+/// 资源类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsiResource {
+    Io,
+    Memory,
+    Cpu,
+}
+
+impl PsiResource {
+    fn state(&self, full: bool) -> PsiState {
+        match (self, full) {
+            (PsiResource::Io, false) => PsiState::IoSome,
+            (PsiResource::Io, true) => PsiState::IoFull,
+            (PsiResource::Memory, false) => PsiState::MemSome,
+            (PsiResource::Memory, true) => PsiState::MemFull,
+            (PsiResource::Cpu, false) => PsiState::CpuSome,
+            (PsiResource::Cpu, true) => PsiState::CpuFull,
+        }
+    }
+}
+
+/// 任务的PSI状态，保存在[`ProcessSchedulerInfo`](crate::process::ProcessSchedulerInfo)中
+#[derive(Debug, Default)]
+pub struct PsiTaskState {
+    flags: PsiTaskFlags,
+    /// `flags`被统计在哪个CPU上
+    cpu: usize,
+}
+
+/// 单个CPU上的压力统计
+#[derive(Debug)]
+struct PsiGroupCpu {
+    /// 处于各状态的任务数量，下标为[`PsiTaskFlags`]的位序号
+    tasks: [u32; NR_PSI_TASK_COUNTS],
+    /// 当前处于哪些压力状态，第i位对应`PsiState`的第i个状态
+    state_mask: u32,
+    /// `state_mask`开始生效的时间
+    state_start: u64,
+    /// 各状态的累计时间（ns）
+    times: [u64; NR_PSI_STATES],
+    /// 各汇总者上一次读取到的`times`
+    times_prev: [[u64; NR_PSI_STATES]; NR_PSI_AGGREGATORS],
+}
+
+impl PsiGroupCpu {
+    const fn new() -> Self {
+        Self {
+            tasks: [0; NR_PSI_TASK_COUNTS],
+            state_mask: 0,
+            state_start: 0,
+            times: [0; NR_PSI_STATES],
+            times_prev: [[0; NR_PSI_STATES]; NR_PSI_AGGREGATORS],
+        }
+    }
+
+    /// 把当前状态从`state_start`到`now`的时间累加到各状态上
+    fn record_times(&mut self, now: u64) {
+        let delta = now.saturating_sub(self.state_start);
+        self.state_start = now;
+        for (s, time) in self.times.iter_mut().enumerate() {
+            if self.state_mask & (1 << s) != 0 {
+                *time += delta;
+            }
+        }
+    }
+
+    fn change(&mut self, now: u64, clear: PsiTaskFlags, set: PsiTaskFlags) {
+        self.record_times(now);
+
+        for (i, count) in self.tasks.iter_mut().enumerate() {
+            let flag = PsiTaskFlags::from_bits_truncate(1 << i);
+            if clear.contains(flag) {
+                *count = count.saturating_sub(1);
+            }
+            if set.contains(flag) {
+                *count += 1;
+            }
+        }
+
+        self.state_mask = Self::test_states(&self.tasks);
+    }
+
+    fn test_states(tasks: &[u32; NR_PSI_TASK_COUNTS]) -> u32 {
+        let iowait = tasks[0];
+        let memstall = tasks[1];
+        let running = tasks[2];
+        let oncpu = tasks[3];
+        let memstall_running = tasks[4];
+
+        let mut mask = 0;
+        if iowait > 0 {
+            mask |= 1 << PsiState::IoSome as u32;
+            if running == 0 {
+                mask |= 1 << PsiState::IoFull as u32;
+            }
+        }
+        if memstall > 0 {
+            mask |= 1 << PsiState::MemSome as u32;
+            if running == memstall_running {
+                mask |= 1 << PsiState::MemFull as u32;
+            }
+        }
+        if running > oncpu {
+            mask |= 1 << PsiState::CpuSome as u32;
+        }
+        // 系统级别的CPU full没有意义（总有任务在运行），与Linux一样不统计
+        if iowait > 0 || memstall > 0 || running > 0 {
+            mask |= 1 << PsiState::NonIdle as u32;
+        }
+        mask
+    }
+
+    /// 获取自汇总者`aggregator`上一次读取以来，各状态增加的时间
+    fn recent_times(&mut self, aggregator: PsiAggregator, now: u64) -> [u64; NR_PSI_STATES] {
+        let mut times = self.times;
+        // 当前状态持续的时间还没有累加到times中
+        let delta = now.saturating_sub(self.state_start);
+        for (s, time) in times.iter_mut().enumerate() {
+            if self.state_mask & (1 << s) != 0 {
+                *time += delta;
+            }
+        }
+
+        let mut deltas = [0; NR_PSI_STATES];
+        let prev = &mut self.times_prev[aggregator as usize];
+        for ((d, time), prev) in deltas.iter_mut().zip(times.iter()).zip(prev.iter_mut()) {
+            *d = time.saturating_sub(*prev);
+            *prev = (*prev).max(*time);
+        }
+        deltas
+    }
+}
+
+/// 移动平均的状态
+#[derive(Debug)]
+struct PsiAvgs {
+    total: [u64; NR_PSI_EXPORTED],
+    avg_total: [u64; NR_PSI_EXPORTED],
+    avg_last_update: u64,
+    avg_next_update: u64,
+    /// 10s/60s/300s的移动平均，为`FIXED_1`定点数表示的百分比
+    avg: [[u64; 3]; NR_PSI_EXPORTED],
+}
+
+/// 触发器轮询的状态
+#[derive(Debug)]
+struct PsiPoll {
+    total: [u64; NR_PSI_EXPORTED],
+    /// 触发器由打开的文件持有，文件关闭后自动失效
+    triggers: Vec<Weak<PsiTrigger>>,
+    next_update: u64,
+}
+
+/// 一组任务的压力统计
+#[derive(Debug)]
+pub struct PsiGroup {
+    pcpu: Vec<SpinLock<PsiGroupCpu>>,
+    avgs: SpinLock<PsiAvgs>,
+    poll: SpinLock<PsiPoll>,
+}
+
+/// 某个状态的压力
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PsiPressure {
+    /// 10s/60s/300s的平均阻塞时间占比，为`FIXED_1`定点数表示的百分比
+    pub avg: [u64; 3],
+    /// 累计阻塞时间（微秒）
+    pub total_us: u64,
+}
+
+lazy_static! {
+    static ref PSI_SYSTEM: PsiGroup = PsiGroup::new();
+}
+
+#[inline]
+fn psi_system() -> &'static PsiGroup {
+    &PSI_SYSTEM
+}
+
+#[inline]
+fn psi_clock() -> u64 {
+    SchedClock::sched_clock_cpu(smp_get_processor_id())
+}
+
+impl PsiGroup {
+    fn new() -> Self {
+        let mut pcpu = Vec::with_capacity(PerCpu::MAX_CPU_NUM as usize);
+        pcpu.resize_with(PerCpu::MAX_CPU_NUM as usize, || {
+            SpinLock::new(PsiGroupCpu::new())
+        });
+        Self {
+            pcpu,
+            avgs: SpinLock::new(PsiAvgs {
+                total: [0; NR_PSI_EXPORTED],
+                avg_total: [0; NR_PSI_EXPORTED],
+                avg_last_update: 0,
+                avg_next_update: 0,
+                avg: [[0; 3]; NR_PSI_EXPORTED],
+            }),
+            poll: SpinLock::new(PsiPoll {
+                total: [0; NR_PSI_EXPORTED],
+                triggers: Vec::new(),
+                next_update: u64::MAX,
+            }),
+        }
+    }
+
+    fn change_cpu(&self, cpu: usize, now: u64, clear: PsiTaskFlags, set: PsiTaskFlags) {
+        self.pcpu[cpu].lock_irqsave().change(now, clear, set);
+    }
+
+    /// 汇总各CPU自上一次汇总以来各状态增加的时间，按各CPU的非空闲时间加权
+    fn collect_percpu_times(&self, aggregator: PsiAggregator, now: u64) -> [u64; NR_PSI_EXPORTED] {
+        let mut deltas = [0u128; NR_PSI_EXPORTED];
+        let mut nonidle_total: u128 = 0;
+
+        for cpu in smp_cpu_manager().present_cpus().iter_cpu() {
+            let times = self.pcpu[cpu.data() as usize]
+                .lock_irqsave()
+                .recent_times(aggregator, now);
+            let nonidle = times[PsiState::NonIdle as usize] as u128;
+            nonidle_total += nonidle;
+            for (d, time) in deltas.iter_mut().zip(times.iter()) {
+                *d += *time as u128 * nonidle;
+            }
+        }
+
+        let nonidle_total = nonidle_total.max(1);
+        let mut result = [0; NR_PSI_EXPORTED];
+        for (r, d) in result.iter_mut().zip(deltas.iter()) {
+            *r = (*d / nonidle_total) as u64;
+        }
+        result
+    }
+
+    fn update_averages(&self, avgs: &mut PsiAvgs, now: u64) {
+        let deltas = self.collect_percpu_times(PsiAggregator::Avgs, now);
+        for (total, d) in avgs.total.iter_mut().zip(deltas.iter()) {
+            *total += *d;
+        }
+
+        if now < avgs.avg_next_update {
+            return;
+        }
+
+        // 错过的周期按阻塞时间为0衰减
+        let expires = avgs.avg_next_update;
+        let missed_periods = (now - expires) / PSI_PERIOD;
+        avgs.avg_next_update = expires + (missed_periods + 1) * PSI_PERIOD;
+        let period = now
+            .saturating_sub(avgs.avg_last_update + missed_periods * PSI_PERIOD)
+            .max(1);
+        avgs.avg_last_update = now;
+
+        for s in 0..NR_PSI_EXPORTED {
+            let sample = (avgs.total[s] - avgs.avg_total[s]).min(period);
+            avgs.avg_total[s] += sample;
+
+            let avg = &mut avgs.avg[s];
+            if missed_periods > 0 {
+                avg[0] = calc_load_n(avg[0], EXP_10S, 0, missed_periods);
+                avg[1] = calc_load_n(avg[1], EXP_60S, 0, missed_periods);
+                avg[2] = calc_load_n(avg[2], EXP_300S, 0, missed_periods);
+            }
+            let pct = sample * 100 / period * FIXED_1;
+            avg[0] = calc_load(avg[0], EXP_10S, pct);
+            avg[1] = calc_load(avg[1], EXP_60S, pct);
+            avg[2] = calc_load(avg[2], EXP_300S, pct);
+        }
+    }
+
+    /// 获取某种资源的`some`和`full`压力
+    pub fn pressure(&self, resource: PsiResource) -> (PsiPressure, PsiPressure) {
+        let now = psi_clock();
+        let mut avgs = self.avgs.lock_irqsave();
+        self.update_averages(&mut avgs, now);
+
+        let get = |full: bool| {
+            // 系统级别不统计CPU full
+            if resource == PsiResource::Cpu && full {
+                return PsiPressure::default();
+            }
+            let state = resource.state(full) as usize;
+            PsiPressure {
+                avg: avgs.avg[state],
+                total_us: avgs.total[state] / NSEC_PER_USEC as u64,
+            }
+        };
+        (get(false), get(true))
+    }
+
+    fn update_triggers(&self, now: u64) {
+        let mut poll = self.poll.lock_irqsave();
+        if now < poll.next_update {
+            return;
+        }
+
+        poll.triggers.retain(|t| t.strong_count() > 0);
+        let triggers = poll
+            .triggers
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect::<Vec<_>>();
+        if triggers.is_empty() {
+            poll.next_update = u64::MAX;
+            return;
+        }
+
+        let deltas = self.collect_percpu_times(PsiAggregator::Poll, now);
+        for (total, d) in poll.total.iter_mut().zip(deltas.iter()) {
+            *total += *d;
+        }
+
+        let mut min_period = u64::MAX;
+        let mut fired = Vec::new();
+        for t in triggers {
+            min_period = min_period.min(t.win_size / UPDATES_PER_WINDOW);
+            let state = t.state as usize;
+            if t.update(now, poll.total[state], deltas[state] > 0) {
+                fired.push(t);
+            }
+        }
+        poll.next_update = now + min_period;
+        drop(poll);
+
+        if !fired.is_empty() {
+            // 唤醒epoll需要获取互斥锁，不能在中断上下文中进行
+            schedule_work(Work::new(move || {
+                for t in fired {
+                    let _ = EventPoll::wakeup_epoll(&t.epitems, EPollEventType::EPOLLPRI);
+                }
+            }));
+        }
+    }
+
+    /// ## 创建触发器
+    ///
+    /// ## 参数
+    ///
+    /// - `resource`: 资源类型
+    /// - `buf`: 用户写入的触发器描述，格式为`<some|full> <阈值us> <窗口us>`
+    pub fn create_trigger(
+        &self,
+        resource: PsiResource,
+        buf: &str,
+    ) -> Result<Arc<PsiTrigger>, SystemError> {
+        let mut args = buf.split_whitespace();
+        let full = match args.next() {
+            Some("some") => false,
+            Some("full") => true,
+            _ => return Err(SystemError::EINVAL),
+        };
+        let threshold_us = args
+            .next()
+            .and_then(|s| s.parse::<u64>().ok())
+            .ok_or(SystemError::EINVAL)?;
+        let window_us = args
+            .next()
+            .and_then(|s| s.parse::<u64>().ok())
+            .ok_or(SystemError::EINVAL)?;
+        if args.next().is_some() {
+            return Err(SystemError::EINVAL);
+        }
+
+        if !(WINDOW_MIN_US..=WINDOW_MAX_US).contains(&window_us) {
+            return Err(SystemError::EINVAL);
+        }
+        if threshold_us == 0 || threshold_us > window_us {
+            return Err(SystemError::EINVAL);
+        }
+
+        let state = resource.state(full);
+        let win_size = window_us * NSEC_PER_USEC as u64;
+        let now = psi_clock();
+
+        let mut poll = self.poll.lock_irqsave();
+        // 先汇总一次，使窗口从当前的累计值开始
+        let deltas = self.collect_percpu_times(PsiAggregator::Poll, now);
+        for (total, d) in poll.total.iter_mut().zip(deltas.iter()) {
+            *total += *d;
+        }
+
+        let trigger = Arc::new(PsiTrigger {
+            state,
+            threshold: threshold_us * NSEC_PER_USEC as u64,
+            win_size,
+            inner: SpinLock::new(PsiTriggerInner {
+                win_start_time: now,
+                win_start_value: poll.total[state as usize],
+                prev_growth: 0,
+                last_event_time: 0,
+                pending_event: false,
+            }),
+            event: AtomicBool::new(false),
+            epitems: LockedEPItemLinkedList::default(),
+        });
+        poll.triggers.push(Arc::downgrade(&trigger));
+        poll.next_update = poll.next_update.min(now + win_size / UPDATES_PER_WINDOW);
+
+        Ok(trigger)
+    }
+}
+
+/// 阻塞时间的触发器
+///
+/// 在`win_size`的滑动窗口内，阻塞时间的增长超过`threshold`时产生一个`POLLPRI`事件。
+/// 每个窗口内最多产生一个事件。
+#[derive(Debug)]
+pub struct PsiTrigger {
+    state: PsiState,
+    threshold: u64,
+    win_size: u64,
+    inner: SpinLock<PsiTriggerInner>,
+    /// 是否有尚未被poll取走的事件
+    event: AtomicBool,
+    pub epitems: LockedEPItemLinkedList,
+}
+
+#[derive(Debug)]
+struct PsiTriggerInner {
+    win_start_time: u64,
+    win_start_value: u64,
+    /// 上一个窗口内的增长，用于估计滑动窗口内的增长
+    prev_growth: u64,
+    last_event_time: u64,
+    /// 已超过阈值，但由于每个窗口只能产生一个事件而尚未通知
+    pending_event: bool,
+}
+
+impl PsiTrigger {
+    /// 更新触发器，返回是否产生了新的事件
+    fn update(&self, now: u64, total: u64, new_stall: bool) -> bool {
+        let mut inner = self.inner.lock_irqsave();
+        if !new_stall && !inner.pending_event {
+            return false;
+        }
+
+        let elapsed = now.saturating_sub(inner.win_start_time);
+        let mut growth = total - inner.win_start_value;
+        if elapsed > self.win_size {
+            inner.win_start_time = now;
+            inner.win_start_value = total;
+            inner.prev_growth = growth;
+        } else {
+            // 按上一个窗口的增长，估计滑动窗口中落在上一个窗口内那部分的增长
+            let remaining = self.win_size - elapsed;
+            growth += inner.prev_growth * remaining / self.win_size;
+        }
+
+        if !inner.pending_event {
+            if growth < self.threshold {
+                return false;
+            }
+            inner.pending_event = true;
+        }
+
+        if inner.last_event_time != 0 && now < inner.last_event_time + self.win_size {
+            return false;
+        }
+
+        inner.last_event_time = now;
+        inner.pending_event = false;
+        !self.event.swap(true, Ordering::SeqCst)
+    }
+
+    /// poll触发器，取走尚未处理的事件
+    pub fn poll(&self) -> EPollEventType {
+        if self.event.swap(false, Ordering::SeqCst) {
+            EPollEventType::EPOLLPRI
+        } else {
+            EPollEventType::empty()
+        }
+    }
+}
+
+/// ## 获取系统级的压力
+///
+/// 返回`(some, full)`
+pub fn psi_pressure(resource: PsiResource) -> (PsiPressure, PsiPressure) {
+    psi_system().pressure(resource)
+}
+
+/// ## 在系统级的统计上创建触发器
+pub fn psi_trigger_create(
+    resource: PsiResource,
+    buf: &str,
+) -> Result<Arc<PsiTrigger>, SystemError> {
+    psi_system().create_trigger(resource, buf)
+}
+
+/// 由时钟中断调用，更新移动平均并检查触发器
+pub fn psi_tick() {
+    let now = psi_clock();
+    if now == 0 {
+        // 时钟还没有校准
+        return;
+    }
+
+    let group = psi_system();
+    {
+        let mut avgs = group.avgs.lock_irqsave();
+        if now >= avgs.avg_next_update {
+            group.update_averages(&mut avgs, now);
+        }
+    }
+    group.update_triggers(now);
+}
+
+/// ## 更新任务的PSI状态
+///
+/// ## 参数
+///
+/// - `pcb`: 任务
+/// - `cpu`: 任务当前所在的CPU
+/// - `f`: 修改任务状态的函数
+fn psi_task_update(pcb: &ProcessControlBlock, cpu: ProcessorId, f: impl FnOnce(&mut PsiTaskFlags)) {
+    // idle进程不参与统计
+    if pcb.sched_info().policy() == SchedPolicy::IDLE {
+        return;
+    }
+
+    let now = psi_clock();
+    let group = psi_system();
+    let cpu = cpu.data() as usize;
+    let mut state = pcb.sched_info().psi.lock_irqsave();
+
+    if state.cpu != cpu && !state.flags.is_empty() {
+        // 任务换了CPU，把它的状态一起迁移过去
+        group.change_cpu(state.cpu, now, state.flags, PsiTaskFlags::empty());
+        group.change_cpu(cpu, now, PsiTaskFlags::empty(), state.flags);
+    }
+    state.cpu = cpu;
+
+    let old = state.flags;
+    f(&mut state.flags);
+    let running_memstall = state
+        .flags
+        .contains(PsiTaskFlags::MEMSTALL | PsiTaskFlags::RUNNING);
+    state
+        .flags
+        .set(PsiTaskFlags::MEMSTALL_RUNNING, running_memstall);
+    let new = state.flags;
+
+    if old != new {
+        group.change_cpu(cpu, now, old - new, new - old);
+    }
+}
+
+/// 任务被加入运行队列
+pub fn psi_enqueue(pcb: &ProcessControlBlock, cpu: ProcessorId) {
+    psi_task_update(pcb, cpu, |flags| {
+        flags.remove(PsiTaskFlags::IOWAIT);
+        flags.insert(PsiTaskFlags::RUNNING);
+    });
+}
+
+/// 任务睡眠，离开运行队列
+pub fn psi_dequeue(pcb: &ProcessControlBlock, cpu: ProcessorId, iowait: bool) {
+    psi_task_update(pcb, cpu, |flags| {
+        flags.remove(PsiTaskFlags::RUNNING);
+        flags.set(PsiTaskFlags::IOWAIT, iowait);
+    });
+}
+
+/// CPU上运行的任务从`prev`切换到`next`
+pub fn psi_task_switch(prev: &ProcessControlBlock, next: &ProcessControlBlock, cpu: ProcessorId) {
+    psi_task_update(prev, cpu, |flags| flags.remove(PsiTaskFlags::ONCPU));
+    psi_task_update(next, cpu, |flags| flags.insert(PsiTaskFlags::ONCPU));
+}
+
+/// ## 标记当前任务开始因内存不足而阻塞
+///
+/// 返回当前任务之前是否已经处于该状态，需要传给[`psi_memstall_leave`]
+pub fn psi_memstall_enter() -> bool {
+    if !ProcessManager::initialized() {
+        return true;
+    }
+    let pcb = ProcessManager::current_pcb();
+    let mut nested = false;
+    psi_task_update(&pcb, smp_get_processor_id(), |flags| {
+        nested = flags.contains(PsiTaskFlags::MEMSTALL);
+        flags.insert(PsiTaskFlags::MEMSTALL);
+    });
+    nested
+}
+
+/// 标记当前任务结束内存阻塞
+pub fn psi_memstall_leave(nested: bool) {
+    if nested {
+        return;
+    }
+    let pcb = ProcessManager::current_pcb();
+    psi_task_update(&pcb, smp_get_processor_id(), |flags| {
+        flags.remove(PsiTaskFlags::MEMSTALL);
+    });
+}
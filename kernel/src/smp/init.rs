@@ -2,7 +2,7 @@ use log::info;
 
 use crate::{
     arch::{syscall::arch_syscall_init, CurrentIrqArch, CurrentSchedArch},
-    exception::InterruptArch,
+    exception::{ipi::tlb_flush_set_online, InterruptArch},
     process::ProcessManager,
     sched::SchedArch,
     smp::{core::smp_get_processor_id, cpu::smp_cpu_manager},
@@ -15,6 +15,7 @@ pub fn smp_ap_start_stage2() -> ! {
     smp_cpu_manager().complete_ap_thread(true);
 
     do_ap_start_stage2();
+    tlb_flush_set_online(true);
 
     CurrentSchedArch::initial_setup_sched_local();
 
@@ -2,7 +2,7 @@ use system_error::SystemError;
 
 use crate::{
     arch::{interrupt::ipi::send_ipi, CurrentSMPArch},
-    exception::ipi::{IpiKind, IpiTarget},
+    exception::ipi::{tlb_flush_set_online, IpiKind, IpiTarget},
 };
 
 use self::{
@@ -51,6 +51,7 @@ pub fn early_smp_init() -> Result<(), SystemError> {
 
 #[inline(never)]
 pub fn smp_init() {
+    tlb_flush_set_online(true);
     smp_cpu_manager().bringup_nonboot_cpus();
 
     CurrentSMPArch::post_init().expect("SMP post init failed");
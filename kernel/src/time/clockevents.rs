@@ -0,0 +1,240 @@
+//! 时钟事件设备(clockevent)
+//!
+//! 时钟事件设备是可以在将来某个时刻产生中断的定时器，例如APIC定时器、riscv的SBI定时器。
+//! 每个CPU上可以注册多个时钟事件设备，框架选择rating最高的一个作为本CPU的滴答设备，
+//! 当它编程失败的时候，回退到剩下的设备中rating最高的一个。
+//!
+//! 只支持单次触发模式的设备，通过在每次中断中重新编程来模拟周期滴答。
+//!
+//! 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/time/clockevents.c
+
+use core::fmt::Debug;
+
+use alloc::{sync::Arc, vec::Vec};
+use log::{info, warn};
+use system_error::SystemError;
+
+use crate::{
+    arch::interrupt::TrapFrame, libs::spinlock::SpinLock, mm::percpu::PerCpu,
+    smp::core::smp_get_processor_id,
+};
+
+use super::{clocksource::HZ, tick_common::tick_handle_periodic, NSEC_PER_SEC};
+
+/// 一个滴答的纳秒数
+const TICK_NSEC: u64 = NSEC_PER_SEC as u64 / HZ;
+
+bitflags! {
+    /// 时钟事件设备支持的工作模式
+    pub struct ClockEventFeatures: u32 {
+        /// 周期触发
+        const PERIODIC = 1 << 0;
+        /// 单次触发
+        const ONESHOT = 1 << 1;
+    }
+}
+
+/// 时钟事件设备当前的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEventState {
+    /// 没有被用作滴答设备
+    Detached,
+    /// 已经关闭
+    Shutdown,
+    /// 周期触发
+    Periodic,
+    /// 单次触发
+    Oneshot,
+}
+
+/// 时钟事件设备
+///
+/// 设备的回调只会在它所属的CPU上、关中断的情况下被调用
+pub trait ClockEventDevice: Send + Sync + Debug {
+    /// 设备的名称
+    fn name(&self) -> &str;
+
+    /// 设备的质量，越高越优先被选用
+    fn rating(&self) -> u32;
+
+    /// 设备支持的工作模式
+    fn features(&self) -> ClockEventFeatures;
+
+    /// 设备的计数频率(Hz)
+    ///
+    /// 只有在进入单次触发模式之后才会被调用
+    fn freq(&self) -> u64;
+
+    /// 单次触发时允许的最小间隔(设备计数)
+    fn min_delta_ticks(&self) -> u64 {
+        1
+    }
+
+    /// 单次触发时允许的最大间隔(设备计数)
+    fn max_delta_ticks(&self) -> u64 {
+        u32::MAX as u64
+    }
+
+    /// 切换到周期触发模式，周期为一个滴答
+    fn set_state_periodic(&self) -> Result<(), SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    /// 切换到单次触发模式
+    fn set_state_oneshot(&self) -> Result<(), SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+
+    /// 关闭设备，不再产生中断
+    fn set_state_shutdown(&self) -> Result<(), SystemError>;
+
+    /// 单次触发模式下，设置在`delta`个设备计数之后产生中断
+    fn set_next_event(&self, _delta: u64) -> Result<(), SystemError> {
+        Err(SystemError::ENOSYS)
+    }
+}
+
+/// 每个CPU的滴答设备
+#[derive(Debug)]
+struct TickDevice {
+    /// 当前使用的设备
+    current: Option<Arc<dyn ClockEventDevice>>,
+    /// 当前设备的状态
+    state: ClockEventState,
+    /// 本CPU上注册过的所有设备
+    devices: Vec<Arc<dyn ClockEventDevice>>,
+}
+
+impl TickDevice {
+    const fn new() -> Self {
+        Self {
+            current: None,
+            state: ClockEventState::Detached,
+            devices: Vec::new(),
+        }
+    }
+
+    /// 让`dev`开始产生滴答
+    fn setup(&mut self, dev: &Arc<dyn ClockEventDevice>) -> Result<(), SystemError> {
+        let features = dev.features();
+        if features.contains(ClockEventFeatures::PERIODIC) {
+            dev.set_state_periodic()?;
+            self.state = ClockEventState::Periodic;
+        } else if features.contains(ClockEventFeatures::ONESHOT) {
+            dev.set_state_oneshot()?;
+            program_event(dev, TICK_NSEC)?;
+            self.state = ClockEventState::Oneshot;
+        } else {
+            return Err(SystemError::EINVAL);
+        }
+        self.current = Some(dev.clone());
+        Ok(())
+    }
+
+    /// 关闭当前设备，然后按rating从高到低启用剩下的设备，直到成功为止
+    fn fallback(&mut self) {
+        if let Some(old) = self.current.take() {
+            old.set_state_shutdown().ok();
+            self.devices.retain(|d| !Arc::ptr_eq(d, &old));
+            warn!("clockevents: device '{}' failed, falling back", old.name());
+        }
+        self.state = ClockEventState::Detached;
+
+        let mut candidates = self.devices.clone();
+        candidates.sort_by_key(|d| core::cmp::Reverse(d.rating()));
+        for dev in candidates {
+            match self.setup(&dev) {
+                Ok(_) => {
+                    info!("clockevents: switched to '{}'", dev.name());
+                    return;
+                }
+                Err(e) => {
+                    dev.set_state_shutdown().ok();
+                    self.devices.retain(|d| !Arc::ptr_eq(d, &dev));
+                    warn!("clockevents: failed to setup '{}': {:?}", dev.name(), e);
+                }
+            }
+        }
+        self.state = ClockEventState::Shutdown;
+        warn!("clockevents: no usable clock event device on this cpu");
+    }
+}
+
+static TICK_DEVICES: [SpinLock<TickDevice>; PerCpu::MAX_CPU_NUM as usize] =
+    [const { SpinLock::new(TickDevice::new()) }; PerCpu::MAX_CPU_NUM as usize];
+
+/// 把纳秒转换为设备计数，并编程下一次中断
+fn program_event(dev: &Arc<dyn ClockEventDevice>, delta_ns: u64) -> Result<(), SystemError> {
+    let ticks = (delta_ns as u128 * dev.freq() as u128 / NSEC_PER_SEC as u128) as u64;
+    let ticks = ticks.clamp(dev.min_delta_ticks(), dev.max_delta_ticks());
+    dev.set_next_event(ticks)
+}
+
+/// 在当前CPU上注册时钟事件设备
+///
+/// 如果它的rating比当前使用的设备高，就关闭原来的设备，改用它产生滴答
+///
+/// ## 参数
+/// - `dev`: 要注册的设备，必须属于当前CPU
+///
+/// ## 返回值
+/// - `Ok(())`: 注册成功(不一定被选用)
+/// - `Err(SystemError)`: 设备被选用，但是启动失败
+pub fn clockevents_register_device(dev: Arc<dyn ClockEventDevice>) -> Result<(), SystemError> {
+    let cpu = smp_get_processor_id().data() as usize;
+    let mut td = TICK_DEVICES[cpu].lock_irqsave();
+    td.devices.push(dev.clone());
+
+    if let Some(cur) = td.current.as_ref() {
+        if cur.rating() >= dev.rating() {
+            return Ok(());
+        }
+    }
+
+    let old = td.current.take();
+    td.state = ClockEventState::Detached;
+    if let Some(old) = old.as_ref() {
+        old.set_state_shutdown().ok();
+    }
+
+    if let Err(e) = td.setup(&dev) {
+        warn!(
+            "clockevents: failed to setup '{}' on cpu {}: {:?}",
+            dev.name(),
+            cpu,
+            e
+        );
+        dev.set_state_shutdown().ok();
+        td.devices.retain(|d| !Arc::ptr_eq(d, &dev));
+        if old.is_some() {
+            td.fallback();
+        }
+        return Err(e);
+    }
+    info!("clockevents: cpu {} uses '{}'", cpu, dev.name());
+    Ok(())
+}
+
+/// 当前CPU正在使用的滴答设备的状态
+#[allow(dead_code)]
+pub fn clockevents_current_state() -> ClockEventState {
+    let cpu = smp_get_processor_id().data() as usize;
+    TICK_DEVICES[cpu].lock_irqsave().state
+}
+
+/// 时钟事件设备的中断处理函数，由设备的驱动在中断上下文中调用
+///
+/// 处理一个滴答，如果设备工作在单次触发模式，就重新编程下一次中断
+pub fn clockevents_handle_event(trap_frame: &TrapFrame) {
+    tick_handle_periodic(trap_frame);
+
+    let cpu = smp_get_processor_id().data() as usize;
+    let mut td = TICK_DEVICES[cpu].lock_irqsave();
+    if td.state != ClockEventState::Oneshot {
+        return;
+    }
+    let dev = td.current.clone().unwrap();
+    if program_event(&dev, TICK_NSEC).is_err() {
+        td.fallback();
+    }
+}
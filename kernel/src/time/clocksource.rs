@@ -11,7 +11,7 @@ use alloc::{
     vec::Vec,
 };
 use lazy_static::__Deref;
-use log::{debug, info};
+use log::{debug, info, warn};
 use system_error::SystemError;
 use unified_init::macros::unified_init;
 
@@ -165,7 +165,7 @@ impl ClocksourceWatchdog {
         }
         // 生成一个定时器
         let wd_timer_func: Box<WatchdogTimerFunc> = Box::new(WatchdogTimerFunc {});
-        self.timer_expires += clock() + WATCHDOG_INTERVAL;
+        self.timer_expires = clock() + WATCHDOG_INTERVAL;
         let mut wd_data = self.watchdog.as_ref().unwrap().clone().clocksource_data();
         wd_data.watchdog_last = self.watchdog.as_ref().unwrap().clone().read();
         self.watchdog
@@ -447,6 +447,12 @@ impl dyn Clocksource {
                 .remove(ClocksourceFlags::CLOCK_SOURCE_WATCHDOG);
             cs.update_clocksource_data(cs_data)?;
             list_guard.push_back(cs);
+            drop(list_guard);
+
+            // 如果监视器已经就绪，开始检查这个时钟源
+            CLOCKSOURCE_WATCHDOG
+                .lock_irqsave()
+                .clocksource_start_watchdog();
         } else {
             // cs是监视器
             if cs_data
@@ -460,13 +466,15 @@ impl dyn Clocksource {
                 cs.update_clocksource_data(cs_data.clone())?;
             }
 
-            // 将时钟源加入到监控队列中
-            let mut list_guard = WATCHDOG_LIST.lock_irqsave();
-            list_guard.push_back(cs.clone());
-            drop(list_guard);
-
             // 对比当前注册的时间源的精度和监视器的精度
+            // jiffies这类依赖时钟中断计数的时钟源不是连续的，不能作为监视器
             let mut cs_watchdog = CLOCKSOURCE_WATCHDOG.lock_irqsave();
+            if !cs_data
+                .flags
+                .contains(ClocksourceFlags::CLOCK_SOURCE_IS_CONTINUOUS)
+            {
+                return Ok(0);
+            }
             if cs_watchdog.watchdog.is_none()
                 || cs_data.rating
                     > cs_watchdog
@@ -553,9 +561,11 @@ impl dyn Clocksource {
 
             // 代替了clocksource_reset_watchdog()的功能，将所有时钟源的watchdog标记清除
             for ele in list.iter() {
-                ele.clocksource_data()
+                let mut ele_data = ele.clocksource_data();
+                ele_data
                     .flags
                     .remove(ClocksourceFlags::CLOCK_SOURCE_WATCHDOG);
+                ele.update_clocksource_data(ele_data).ok();
             }
 
             // 遍历所有时间源，寻找新的监视器
@@ -607,7 +617,7 @@ impl dyn Clocksource {
             .expect("clocksource_dequeue_watchdog: failed to update clocksource data");
         size = list.len();
         // 停止当前的watchdog
-        locked_watchdog.clocksource_stop_watchdog(size - 1);
+        locked_watchdog.clocksource_stop_watchdog(size);
     }
 
     /// # 将时钟源从时钟源链表中弹出
@@ -791,6 +801,41 @@ pub fn clocksource_suspend() {
     }
 }
 
+/// 读取被监视的时钟源时，前后两次读取监视器的最大允许间隔
+const WATCHDOG_MAX_READ_SKEW: u64 = WATCHDOG_MAX_SKEW as u64;
+/// 读取时间间隔过大时的最大重试次数
+const WATCHDOG_MAX_READ_RETRIES: usize = 3;
+
+/// # 在两次读取监视器之间读取被监视的时钟源
+///
+/// 如果两次读取监视器的间隔太大（例如读取过程中被NMI或者SMI打断），
+/// 两个时钟源的读数就不是同一时刻的，需要重新读取
+///
+/// ## 返回值
+///
+/// * `Some((cs_now, wd_now))` - 被监视的时钟源和监视器的读数
+/// * `None` - 多次重试后读取间隔仍然过大，本轮跳过检查
+fn cs_watchdog_read(
+    cs: &Arc<dyn Clocksource>,
+    wd: &Arc<dyn Clocksource>,
+    wd_data: &ClocksourceData,
+) -> Option<(CycleNum, CycleNum)> {
+    for _ in 0..WATCHDOG_MAX_READ_RETRIES {
+        let wd_begin = wd.read();
+        let cs_now = cs.read();
+        let wd_end = wd.read();
+        let wd_delay = clocksource_cyc2ns(
+            CycleNum::new(wd_end.data().wrapping_sub(wd_begin.data()) & wd_data.mask.bits()),
+            wd_data.mult,
+            wd_data.shift,
+        );
+        if wd_delay <= WATCHDOG_MAX_READ_SKEW {
+            return Some((cs_now, wd_end));
+        }
+    }
+    None
+}
+
 /// # 根据watchdog的精度，来检查被监视的时钟源的误差
 ///
 /// ## 返回值
@@ -799,16 +844,20 @@ pub fn clocksource_suspend() {
 /// * `Err(SystemError)` - 错误码
 pub fn clocksource_watchdog() -> Result<(), SystemError> {
     let cs_watchdog = CLOCKSOURCE_WATCHDOG.lock_irqsave();
-    // debug!("clocksource_watchdog start");
-
     // watchdog没有在运行的话直接退出
     if !cs_watchdog.is_running || cs_watchdog.watchdog.is_none() {
-        // debug!("is_running = {:?},watchdog = {:?}", cs_watchdog.is_running, cs_watchdog.watchdog);
         return Ok(());
     }
-
+    let wd = cs_watchdog.watchdog.clone().unwrap();
     drop(cs_watchdog);
-    let watchdog_list = WATCHDOG_LIST.lock_irqsave();
+    let wd_data = wd.clocksource_data();
+
+    // 复制一份监视链表，检查过程中不持有锁，避免和注册、注销时钟源的路径互相等待
+    let watchdog_list: Vec<Arc<dyn Clocksource>> =
+        WATCHDOG_LIST.lock_irqsave().iter().cloned().collect();
+    // 两次检查之间理论上经过的时间
+    let interval_nsec = WATCHDOG_INTERVAL * NSEC_PER_SEC as u64 / HZ;
+
     for cs in watchdog_list.iter() {
         let mut cs_data = cs.clocksource_data();
         // 判断时钟源是否已经被标记为不稳定
@@ -816,7 +865,6 @@ pub fn clocksource_watchdog() -> Result<(), SystemError> {
             .flags
             .contains(ClocksourceFlags::CLOCK_SOURCE_UNSTABLE)
         {
-            // debug!("clocksource_watchdog unstable");
             // 启动watchdog_kthread
             if FINISHED_BOOTING.load(Ordering::Relaxed) {
                 // TODO 在实现了工作队列后，将启动线程换成schedule work
@@ -825,60 +873,71 @@ pub fn clocksource_watchdog() -> Result<(), SystemError> {
             continue;
         }
 
-        // 读取时钟源现在的时间
-        let cs_now_clock = cs.read();
-        // 读取watchdog现在的时间
-        let wd = CLOCKSOURCE_WATCHDOG.lock_irqsave();
-        let wd_now = wd.watchdog.as_ref().unwrap().clone();
-        let wd_now_data = wd_now.as_ref().clocksource_data();
-        let wd_now_clock = wd_now.as_ref().read().data();
-
-        // info!("cs_name = {:?}", cs_data.name);
-        // info!("cs_last = {:?}", cs_data.cs_last);
-        // info!("cs_now_clock = {:?}", cs_now_clock);
-        // info!("wd_name");
-        // info!("wd_last = {:?}", cs_data.watchdog_last);
-        // info!("wd_now_clock = {:?}", wd_now_clock);
-
-        // 如果时钟源没有被监视，则开始监视他
+        let (cs_now_clock, wd_now_clock) = match cs_watchdog_read(cs, &wd, &wd_data) {
+            Some(v) => v,
+            None => {
+                warn!(
+                    "clocksource watchdog: read of '{}' vs '{}' took too long, skip",
+                    cs_data.name, wd_data.name
+                );
+                continue;
+            }
+        };
+
+        // 如果时钟源没有被监视，或者上一次检查的结果不可信，则重新开始监视他
         if !cs_data
             .flags
             .contains(ClocksourceFlags::CLOCK_SOURCE_WATCHDOG)
         {
-            // debug!("clocksource_watchdog start watch");
             cs_data
                 .flags
                 .insert(ClocksourceFlags::CLOCK_SOURCE_WATCHDOG);
             // 记录此次检查的时刻
-            cs_data.watchdog_last = CycleNum::new(wd_now_clock);
+            cs_data.watchdog_last = wd_now_clock;
             cs_data.cs_last = cs_now_clock;
             cs.update_clocksource_data(cs_data.clone())?;
             continue;
         }
 
         let wd_dev_nsec = clocksource_cyc2ns(
-            CycleNum((wd_now_clock - cs_data.watchdog_last.data()) & wd_now_data.mask.bits),
-            wd_now_data.mult,
-            wd_now_data.shift,
+            CycleNum::new(
+                wd_now_clock
+                    .data()
+                    .wrapping_sub(cs_data.watchdog_last.data())
+                    & wd_data.mask.bits(),
+            ),
+            wd_data.mult,
+            wd_data.shift,
         );
-
         let cs_dev_nsec = clocksource_cyc2ns(
-            CycleNum(cs_now_clock.div(cs_data.cs_last).data() & cs_data.mask.bits),
-            cs_data.mult,  // 2343484437
-            cs_data.shift, // 23
+            CycleNum::new(
+                cs_now_clock.data().wrapping_sub(cs_data.cs_last.data()) & cs_data.mask.bits(),
+            ),
+            cs_data.mult,
+            cs_data.shift,
         );
         // 记录此次检查的时刻
-        cs_data.watchdog_last = CycleNum::new(wd_now_clock);
+        cs_data.watchdog_last = wd_now_clock;
         cs_data.cs_last = cs_now_clock;
         cs.update_clocksource_data(cs_data.clone())?;
 
+        // 定时器被推迟太久时监视器可能已经回绕，间隔太短时误差会被放大，这两种情况都不做判断
+        if wd_dev_nsec < (interval_nsec >> 2) || wd_dev_nsec > (interval_nsec << 2) {
+            continue;
+        }
+
         // 判断是否有误差
-        if cs_dev_nsec.abs_diff(wd_dev_nsec) > WATCHDOG_THRESHOLD.into() {
-            // debug!("set_unstable");
+        let threshold = WATCHDOG_THRESHOLD as u64
+            + cs_data.uncertainty_margin as u64
+            + wd_data.uncertainty_margin as u64;
+        let skew = cs_dev_nsec.abs_diff(wd_dev_nsec);
+        if skew > threshold {
             // 误差过大，标记为unstable
-            info!("cs_dev_nsec = {}", cs_dev_nsec);
-            info!("wd_dev_nsec = {}", wd_dev_nsec);
-            cs.set_unstable(cs_dev_nsec.abs_diff(wd_dev_nsec).try_into().unwrap())?;
+            warn!(
+                "clocksource watchdog: '{}' measured {} ns while '{}' measured {} ns, marking unstable",
+                cs_data.name, cs_dev_nsec, wd_data.name, wd_dev_nsec
+            );
+            cs.set_unstable(skew.min(i64::MAX as u64) as i64)?;
             continue;
         }
 
@@ -889,7 +948,7 @@ pub fn clocksource_watchdog() -> Result<(), SystemError> {
             && cs_data
                 .flags
                 .contains(ClocksourceFlags::CLOCK_SOURCE_IS_CONTINUOUS)
-            && wd_now_data
+            && wd_data
                 .flags
                 .contains(ClocksourceFlags::CLOCK_SOURCE_IS_CONTINUOUS)
         {
@@ -915,29 +974,26 @@ fn create_new_watchdog_timer_function() {
 }
 
 fn __clocksource_watchdog_kthread() {
-    let mut del_vec: Vec<usize> = Vec::new();
     let mut del_clocks: Vec<Arc<dyn Clocksource>> = Vec::new();
     let mut wd_list = WATCHDOG_LIST.lock_irqsave();
 
     // 将不稳定的时钟源弹出监视链表
-    for (pos, ele) in wd_list.iter().enumerate() {
+    let old_list = core::mem::take(&mut *wd_list);
+    for ele in old_list {
         let data = ele.clocksource_data();
         if data.flags.contains(ClocksourceFlags::CLOCK_SOURCE_UNSTABLE) {
-            del_vec.push(pos);
-            del_clocks.push(ele.clone());
+            del_clocks.push(ele);
+        } else {
+            wd_list.push_back(ele);
         }
     }
-    for pos in del_vec {
-        let mut temp_list = wd_list.split_off(pos);
-        temp_list.pop_front();
-        wd_list.append(&mut temp_list);
-    }
 
     // 检查是否需要停止watchdog
+    let list_len = wd_list.len();
+    drop(wd_list);
     CLOCKSOURCE_WATCHDOG
         .lock_irqsave()
-        .clocksource_stop_watchdog(wd_list.len());
-    drop(wd_list);
+        .clocksource_stop_watchdog(list_len);
     // 将不稳定的时钟源精度都设置为最低，然后删除unstable标记
     for clock in del_clocks.iter() {
         clock.clocksource_change_rating(0);
@@ -965,9 +1021,9 @@ pub fn clocksource_watchdog_kthread() -> i32 {
 pub fn clocksource_reset_watchdog() {
     let list_guard = WATCHDOG_LIST.lock_irqsave();
     for ele in list_guard.iter() {
-        ele.clocksource_data()
-            .flags
-            .remove(ClocksourceFlags::CLOCK_SOURCE_WATCHDOG);
+        let mut data = ele.clocksource_data();
+        data.flags.remove(ClocksourceFlags::CLOCK_SOURCE_WATCHDOG);
+        ele.update_clocksource_data(data).ok();
     }
 }
 
@@ -986,7 +1042,12 @@ pub fn clocksource_select() {
     let override_name = OVERRIDE_NAME.lock();
     // 判断是否有用户空间指定的时间源
     for ele in list_guard.iter() {
-        if ele.clocksource_data().name.eq(override_name.deref()) {
+        let ele_data = ele.clocksource_data();
+        if ele_data.name.eq(override_name.deref())
+            && !ele_data
+                .flags
+                .contains(ClocksourceFlags::CLOCK_SOURCE_UNSTABLE)
+        {
             // TODO 判断是否是高精度模式
             // 暂时不支持高精度模式
             // 如果是高精度模式，但是时钟源不支持高精度模式的话，就要退出循环
@@ -1014,7 +1075,7 @@ pub fn clocksource_select() {
         }
     }
     if should_update_timekeeper && timekeeping::timekeeping_is_initialized() {
-        timekeeping::timekeeper().timekeeper_setup_internals(best.clone());
+        timekeeping::timekeeper().timekeeping_change_clocksource(best.clone());
     }
     debug!("clocksource_select finish, CUR_CLOCKSOURCE = {best:?}");
 }
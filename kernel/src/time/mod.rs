@@ -9,6 +9,7 @@ use crate::time::syscall::PosixTimeval;
 
 use self::timekeeping::getnstimeofday;
 
+pub mod clockevents;
 pub mod clocksource;
 pub mod jiffies;
 pub mod sleep;
@@ -84,9 +85,9 @@ impl PosixTimeSpec {
             if unlikely(khz == 0) {
                 return PosixTimeSpec::default();
             } else {
-                return Self::from(Duration::from_millis(
-                    CurrentTimeArch::get_cycles() as u64 / khz,
-                ));
+                // 以微秒精度换算，避免日志时间戳只有毫秒分辨率
+                let cycles = CurrentTimeArch::get_cycles() as u128;
+                return Self::from(Duration::from_micros((cycles * 1000 / khz as u128) as u64));
             }
         }
 
@@ -21,7 +21,7 @@ mod sys_timer_getoverrun;
 mod sys_timer_gettime;
 mod sys_timer_settime;
 
-pub(crate) use posix_clock::{posix_clock_now, posix_clock_res};
+pub(crate) use posix_clock::{posix_clock_host_now, posix_clock_now, posix_clock_res};
 
 pub type PosixTimeT = c_longlong;
 pub type PosixSusecondsT = c_int;
@@ -4,7 +4,13 @@ use crate::time::PosixTimeSpec;
 
 use super::{PosixClockID, CPUCLOCK_PERTHREAD_MASK};
 
+/// 当前进程看到的时间，单调时钟和启动时钟会加上time namespace的偏移量
 pub(crate) fn posix_clock_now(clock_id: PosixClockID) -> PosixTimeSpec {
+    ProcessManager::current_timens().to_ns_time(clock_id, posix_clock_host_now(clock_id))
+}
+
+/// 宿主的时间，不考虑time namespace
+pub(crate) fn posix_clock_host_now(clock_id: PosixClockID) -> PosixTimeSpec {
     match clock_id {
         PosixClockID::Realtime => getnstimeofday(),
         // 单调/boottime/raw/coarse/alarm 等目前仍复用 realtime（后续可补齐真正语义）。
@@ -9,7 +9,7 @@ use crate::time::{sleep::nanosleep, PosixTimeSpec};
 use alloc::vec::Vec;
 use system_error::SystemError;
 
-use super::{PosixClockID, PosixClockID::*};
+use super::{posix_clock_now, PosixClockID, PosixClockID::*};
 
 pub struct SysClockNanosleep;
 
@@ -31,10 +31,11 @@ impl SysClockNanosleep {
     fn ktime_now(clockid: PosixClockID) -> PosixTimeSpec {
         // 暂时使用 realtime 近似；后续区分 monotonic/boottime
         // - Realtime：使用 getnstimeofday()
-        // - Monotonic/Boottime：暂与 Realtime 等价（后续引入真正单调/启动时钟）
+        // - Monotonic/Boottime：暂与 Realtime 等价（后续引入真正单调/启动时钟），
+        //   并加上time namespace的偏移量，TIMER_ABSTIME的deadline是命名空间中的时间
         match clockid {
             Realtime => getnstimeofday(),
-            Monotonic | Boottime => getnstimeofday(),
+            Monotonic | Boottime => posix_clock_now(clockid),
             ProcessCPUTimeID => {
                 let pcb = ProcessManager::current_pcb();
                 PosixTimeSpec::from_ns(pcb.process_cputime_ns())
@@ -1,7 +1,7 @@
 use crate::{
     arch::interrupt::TrapFrame,
     process::ProcessManager,
-    sched::loadavg,
+    sched::{loadavg, psi},
     smp::{core::smp_get_processor_id, cpu::ProcessorId},
     time::timer::run_local_timer,
 };
@@ -20,6 +20,7 @@ fn tick_periodic(cpu_id: ProcessorId, trap_frame: &TrapFrame) {
     if cpu_id.data() == 0 {
         update_timer_jiffies(1);
         loadavg::calc_global_load(clock());
+        psi::psi_tick();
         run_local_timer();
     }
 
@@ -114,32 +114,19 @@ impl Timekeeper {
     /// * 'clock' - 指定的时钟实际类型。初始为ClocksourceJiffies
     pub fn timekeeper_setup_internals(&self, clock: Arc<dyn Clocksource>) {
         let mut timekeeper = self.inner.write_irqsave();
-        // 更新clock
-        let mut clock_data = clock.clocksource_data();
-        clock_data.cycle_last = clock.read();
-        if clock.update_clocksource_data(clock_data).is_err() {
-            debug!("timekeeper_setup_internals:update_clocksource_data run failed");
-        }
-        timekeeper.clock.replace(clock.clone());
+        tk_setup_internals(&mut timekeeper, clock);
+    }
 
-        let clock_data = clock.clocksource_data();
-        let mut temp = NTP_INTERVAL_LENGTH << clock_data.shift;
-        let ntpinterval = temp;
-        temp += (clock_data.mult / 2) as u64;
-        // do div
-
-        timekeeper.cycle_interval = CycleNum::new(temp);
-        timekeeper.xtime_interval = temp * clock_data.mult as u64;
-        // 这里可能存在下界溢出问题，debug模式下会报错panic
-        timekeeper.xtime_remainder = (ntpinterval - timekeeper.xtime_interval) as i64;
-        timekeeper.raw_interval = (timekeeper.xtime_interval >> clock_data.shift) as i64;
-        timekeeper.xtime_nsec = 0;
-        timekeeper.shift = clock_data.shift as i32;
-
-        timekeeper.ntp_error = 0;
-        timekeeper.ntp_error_shift = (NTP_SCALE_SHIFT - clock_data.shift) as i32;
-
-        timekeeper.mult = clock_data.mult;
+    /// # 切换timekeeper使用的时钟源
+    ///
+    /// 先用旧的时钟源把xtime推进到当前时刻，再换上新的时钟源，
+    /// 这样切换前后的时间是连续的，墙上时间和CLOCK_MONOTONIC都不会回退
+    ///
+    /// 参考：https://code.dragonos.org.cn/xref/linux-6.6.21/kernel/time/timekeeping.c#1547
+    pub fn timekeeping_change_clocksource(&self, clock: Arc<dyn Clocksource>) {
+        let mut timekeeper = self.inner.write_irqsave();
+        timekeeping_forward_now(&mut timekeeper);
+        tk_setup_internals(&mut timekeeper, clock);
     }
 
     pub fn timekeeping_get_ns(&self) -> i64 {
@@ -450,6 +437,61 @@ pub fn update_wall_time() {
 }
 // TODO wall_to_monotic
 
+/// # 根据时钟源设置timekeeper的参数
+fn tk_setup_internals(timekeeper: &mut TimekeeperData, clock: Arc<dyn Clocksource>) {
+    // 更新clock
+    let mut clock_data = clock.clocksource_data();
+    clock_data.cycle_last = clock.read();
+    if clock.update_clocksource_data(clock_data).is_err() {
+        debug!("timekeeper_setup_internals:update_clocksource_data run failed");
+    }
+    timekeeper.clock.replace(clock.clone());
+
+    let clock_data = clock.clocksource_data();
+    let mut temp = NTP_INTERVAL_LENGTH << clock_data.shift;
+    let ntpinterval = temp;
+    temp += (clock_data.mult / 2) as u64;
+    // do div
+
+    timekeeper.cycle_interval = CycleNum::new(temp);
+    timekeeper.xtime_interval = temp * clock_data.mult as u64;
+    // 这里可能存在下界溢出问题，debug模式下会报错panic
+    timekeeper.xtime_remainder = (ntpinterval - timekeeper.xtime_interval) as i64;
+    timekeeper.raw_interval = (timekeeper.xtime_interval >> clock_data.shift) as i64;
+    timekeeper.xtime_nsec = 0;
+    timekeeper.shift = clock_data.shift as i32;
+
+    timekeeper.ntp_error = 0;
+    timekeeper.ntp_error_shift = (NTP_SCALE_SHIFT - clock_data.shift) as i32;
+
+    timekeeper.mult = clock_data.mult;
+}
+
+/// # 把当前时钟源自上次更新以来经过的时间累加到xtime上
+fn timekeeping_forward_now(timekeeper: &mut TimekeeperData) {
+    let clock = match timekeeper.clock.clone() {
+        Some(clock) => clock,
+        None => return,
+    };
+    let mut clock_data = clock.clocksource_data();
+    let cycle_now = clock.read();
+    let cycle_delta =
+        cycle_now.data().wrapping_sub(clock_data.cycle_last.data()) & clock_data.mask.bits();
+    clock_data.cycle_last = cycle_now;
+    if clock.update_clocksource_data(clock_data).is_err() {
+        debug!("timekeeping_forward_now:update_clocksource_data run failed");
+    }
+
+    let nsec = clocksource_cyc2ns(
+        CycleNum::new(cycle_delta),
+        timekeeper.mult,
+        timekeeper.shift as u32,
+    ) as i64;
+    timekeeper.xtime.tv_nsec += nsec;
+    timekeeper.xtime.tv_sec += timekeeper.xtime.tv_nsec / NSEC_PER_SEC as i64;
+    timekeeper.xtime.tv_nsec %= NSEC_PER_SEC as i64;
+}
+
 /// 参考：https://code.dragonos.org.cn/xref/linux-3.4.99/kernel/time/timekeeping.c#190
 pub fn timekeeping_update(timekeeper: &mut TimekeeperData) {
     // TODO：如果clearntp为true，则会清除NTP错误并调用ntp_clear()
@@ -1,4 +1,6 @@
+use crate::arch::MMArch;
 use crate::libs::spinlock::SpinLock;
+use crate::mm::MemoryManagementArch;
 use alloc::{boxed::Box, collections::BTreeMap, format, string::String};
 use core::{any::Any, fmt::Debug, sync::atomic::AtomicU32};
 use static_keys::StaticFalseKey;
@@ -183,16 +185,17 @@ impl TracePoint {
 
     /// Enable the tracepoint
     pub fn enable(&self) {
-        unsafe {
+        // static keys原地修改代码，内核代码只读时需要临时允许写入
+        MMArch::with_kernel_text_writable(|| unsafe {
             self.key.enable();
-        }
+        });
     }
 
     /// Disable the tracepoint
     pub fn disable(&self) {
-        unsafe {
+        MMArch::with_kernel_text_writable(|| unsafe {
             self.key.disable();
-        }
+        });
     }
 
     /// Check if the tracepoint is enabled
@@ -11,22 +11,33 @@ use crate::{
     },
     driver::base::device::device_number::DeviceNumber,
     filesystem::{
-        devfs::{devfs_register, DevFS, DeviceINode},
+        devfs::{devfs_register, DevFS, DevNodeAttr, DeviceINode},
+        page_cache::PageCache,
         vfs::{
             file::{File, FileFlags},
             vcore::generate_inode_id,
-            FileType, IndexNode, InodeFlags, InodeMode, Metadata,
+            FileSystem, FileType, FsInfo, IndexNode, InodeFlags, InodeMode, Magic, Metadata,
+            SuperBlock,
         },
     },
     libs::{mutex::MutexGuard, spinlock::SpinLock},
-    mm::MemoryManagementArch,
+    mm::{
+        fault::{PageFaultHandler, PageFaultMessage},
+        MemoryManagementArch, VmFaultReason,
+    },
     process::ProcessManager,
     syscall::user_access::{UserBufferReader, UserBufferWriter},
     time::PosixTimeSpec,
-    virt::vm::user_api::{KvmUserspaceMemoryRegion, PosixKvmUserspaceMemoryRegion},
+    virt::vm::user_api::{
+        KvmUserspaceMemoryRegion, PosixKvmUserspaceMemoryRegion, PosixKvmVirtioBlkConfig,
+    },
 };
 
-use super::kvm_host::{vcpu::LockedVirtCpu, LockedVm};
+use super::kvm_host::{
+    vcpu::{LockedVirtCpu, KVM_VCPU_MMAP_PAGES},
+    virtio_blk::KvmVirtioBlk,
+    LockedVm,
+};
 
 #[derive(Debug)]
 pub struct KvmInode {
@@ -42,10 +53,19 @@ pub struct KvmInode {
 #[derive(Debug)]
 pub struct LockedKvmInode {
     inner: SpinLock<KvmInode>,
+    /// 设备节点的权限和属主
+    attr: DevNodeAttr,
 }
 
+/// `KVM_GET_API_VERSION`返回的版本号，自Linux 2.6.22起固定为12
+const KVM_API_VERSION: usize = 12;
+/// 支持`KVM_SET_USER_MEMORY_REGION`
+const KVM_CAP_USER_MEMORY: usize = 3;
+
 impl LockedKvmInode {
+    const KVM_GET_API_VERSION: u32 = 0xAE00;
     const KVM_CREATE_VM: u32 = 0xAE01;
+    const KVM_CHECK_EXTENSION: u32 = 0xAE03;
     const KVM_GET_VCPU_MMAP_SIZE: u32 = 0xAE04;
 
     pub fn new() -> Arc<Self> {
@@ -75,6 +95,7 @@ impl LockedKvmInode {
 
         let result = Arc::new(LockedKvmInode {
             inner: SpinLock::new(inode),
+            attr: DevNodeAttr::new(InodeMode::S_IALLUGO),
         });
         result.inner.lock().self_ref = Arc::downgrade(&result);
 
@@ -145,7 +166,14 @@ impl IndexNode for LockedKvmInode {
     }
 
     fn metadata(&self) -> Result<Metadata, system_error::SystemError> {
-        Ok(self.inner.lock().metadata.clone())
+        let mut metadata = self.inner.lock().metadata.clone();
+        self.attr.apply(&mut metadata);
+        Ok(metadata)
+    }
+
+    fn set_metadata(&self, metadata: &Metadata) -> Result<(), SystemError> {
+        self.attr.update(metadata);
+        Ok(())
     }
 
     fn ioctl(
@@ -155,6 +183,13 @@ impl IndexNode for LockedKvmInode {
         _private_data: MutexGuard<crate::filesystem::vfs::FilePrivateData>,
     ) -> Result<usize, SystemError> {
         match cmd {
+            Self::KVM_GET_API_VERSION => {
+                if arg != 0 {
+                    return Err(SystemError::EINVAL);
+                }
+                return Ok(KVM_API_VERSION);
+            }
+
             Self::KVM_CREATE_VM => {
                 let ret = self.create_vm(arg);
                 warn!("[KVM]: KVM_CREATE_VM {ret:?}");
@@ -162,21 +197,26 @@ impl IndexNode for LockedKvmInode {
                 return ret;
             }
 
+            Self::KVM_CHECK_EXTENSION => {
+                // 返回0表示不支持，用户态会退回到不依赖该扩展的路径
+                return Ok((arg == KVM_CAP_USER_MEMORY) as usize);
+            }
+
             Self::KVM_GET_VCPU_MMAP_SIZE => {
                 if arg != 0 {
                     return Err(SystemError::EINVAL);
                 }
                 debug!("[KVM] KVM_GET_VCPU_MMAP_SIZE");
-                return Ok(MMArch::PAGE_SIZE);
+                return Ok(KVM_VCPU_MMAP_PAGES * MMArch::PAGE_SIZE);
             }
 
             _ => {
                 // TODO: arch_ioctl
-                warn!("[KVM]: unknown iooctl cmd {cmd:x}");
+                debug!("[KVM]: unknown ioctl cmd {cmd:x}");
             }
         }
 
-        Ok(0)
+        Err(SystemError::EINVAL)
     }
 
     fn close(
@@ -204,6 +244,8 @@ pub struct KvmInstance {
 impl KvmInstance {
     const KVM_CREATE_VCPU: u32 = 0xAE41;
     const KVM_SET_USER_MEMORY_REGION: u32 = 0x4020AE46;
+    /// DragonOS扩展：_IOW(KVMIO, 0xf0, struct PosixKvmVirtioBlkConfig)
+    const KVM_CREATE_VIRTIO_BLK: u32 = 0x4010AEF0;
 
     pub fn new(vm: Arc<LockedVm>) -> Arc<Self> {
         Arc::new(Self {
@@ -271,12 +313,27 @@ impl IndexNode for KvmInstance {
                 return Ok(0);
             }
 
+            Self::KVM_CREATE_VIRTIO_BLK => {
+                let user_reader = UserBufferReader::new(
+                    arg as *const PosixKvmVirtioBlkConfig,
+                    core::mem::size_of::<PosixKvmVirtioBlkConfig>(),
+                    true,
+                )?;
+                let config = user_reader.read_one_from_user::<PosixKvmVirtioBlkConfig>(0)?;
+
+                // 不需要VM的锁，vCPU运行时也可以添加设备
+                KvmVirtioBlk::create(&self.kvm, config)?;
+
+                return Ok(0);
+            }
+
             _ => {
                 // arch_ioctl
+                debug!("[KVM-INSTANCE] unknown ioctl cmd {cmd:x}");
             }
         }
 
-        todo!()
+        Err(SystemError::EINVAL)
     }
 
     fn read_at(
@@ -286,7 +343,7 @@ impl IndexNode for KvmInstance {
         _buf: &mut [u8],
         _data: MutexGuard<crate::filesystem::vfs::FilePrivateData>,
     ) -> Result<usize, SystemError> {
-        todo!()
+        Err(SystemError::EINVAL)
     }
 
     fn write_at(
@@ -296,7 +353,7 @@ impl IndexNode for KvmInstance {
         _buf: &[u8],
         _data: MutexGuard<crate::filesystem::vfs::FilePrivateData>,
     ) -> Result<usize, SystemError> {
-        todo!()
+        Err(SystemError::EINVAL)
     }
 
     fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
@@ -304,11 +361,11 @@ impl IndexNode for KvmInstance {
     }
 
     fn as_any_ref(&self) -> &dyn core::any::Any {
-        todo!()
+        self
     }
 
     fn list(&self) -> Result<alloc::vec::Vec<alloc::string::String>, SystemError> {
-        todo!()
+        Err(SystemError::ENOTDIR)
     }
 
     fn metadata(&self) -> Result<Metadata, SystemError> {
@@ -326,8 +383,11 @@ impl IndexNode for KvmInstance {
 #[derive(Debug)]
 pub struct KvmVcpuDev {
     vcpu: Arc<LockedVirtCpu>,
+    /// kvm_run所在的页。KVM_RUN期间一直持有vCPU的锁，缺页时不能再获取它
+    run_page_cache: Arc<PageCache>,
     /// INode 元数据
     metadata: Metadata,
+    self_ref: Weak<KvmVcpuDev>,
 }
 
 impl KvmVcpuDev {
@@ -338,12 +398,15 @@ impl KvmVcpuDev {
     const KVM_SET_SREGS: u32 = 0x4138AE84;
 
     pub fn new(vcpu: Arc<LockedVirtCpu>) -> Arc<Self> {
-        Arc::new(Self {
+        let run_page_cache = vcpu.lock().run.as_ref().unwrap().page_cache().clone();
+        Arc::new_cyclic(|self_ref| Self {
             vcpu,
+            run_page_cache,
             metadata: Metadata {
                 dev_id: 1,
                 inode_id: generate_inode_id(),
-                size: 0,
+                // 缺页时按文件大小判断越界
+                size: (KVM_VCPU_MMAP_PAGES * MMArch::PAGE_SIZE) as i64,
                 blk_size: 0,
                 blocks: 0,
                 atime: PosixTimeSpec::default(),
@@ -358,11 +421,27 @@ impl KvmVcpuDev {
                 raw_dev: DeviceNumber::default(), // 这里用来作为device number
                 flags: InodeFlags::empty(),
             },
+            self_ref: self_ref.clone(),
         })
     }
 }
 
 impl IndexNode for KvmVcpuDev {
+    /// 映射kvm_run和PIO数据页，大小由`KVM_GET_VCPU_MMAP_SIZE`获取
+    fn mmap(&self, _start: usize, len: usize, offset: usize) -> Result<(), SystemError> {
+        if offset
+            .checked_add(len)
+            .is_none_or(|end| end > KVM_VCPU_MMAP_PAGES * MMArch::PAGE_SIZE)
+        {
+            return Err(SystemError::EINVAL);
+        }
+        Ok(())
+    }
+
+    fn page_cache(&self) -> Option<Arc<PageCache>> {
+        Some(self.run_page_cache.clone())
+    }
+
     fn open(
         &self,
         _data: MutexGuard<crate::filesystem::vfs::FilePrivateData>,
@@ -457,11 +536,11 @@ impl IndexNode for KvmVcpuDev {
 
             _ => {
                 // arch ioctl
-                warn!("[KVM-VCPU] unknown ioctl cmd {cmd:x}");
+                debug!("[KVM-VCPU] unknown ioctl cmd {cmd:x}");
             }
         }
 
-        Ok(0)
+        Err(SystemError::EINVAL)
     }
 
     fn metadata(&self) -> Result<Metadata, SystemError> {
@@ -475,7 +554,7 @@ impl IndexNode for KvmVcpuDev {
         _buf: &mut [u8],
         _data: crate::libs::mutex::MutexGuard<crate::filesystem::vfs::FilePrivateData>,
     ) -> Result<usize, SystemError> {
-        todo!()
+        Err(SystemError::EINVAL)
     }
 
     fn write_at(
@@ -485,19 +564,67 @@ impl IndexNode for KvmVcpuDev {
         _buf: &[u8],
         _data: crate::libs::mutex::MutexGuard<crate::filesystem::vfs::FilePrivateData>,
     ) -> Result<usize, SystemError> {
-        todo!()
+        Err(SystemError::EINVAL)
     }
 
     fn fs(&self) -> Arc<dyn crate::filesystem::vfs::FileSystem> {
-        todo!()
+        Arc::new(KvmVcpuFakeFs {
+            vcpu_dev: self.self_ref.upgrade().unwrap(),
+        })
     }
 
     fn as_any_ref(&self) -> &dyn core::any::Any {
-        todo!()
+        self
     }
 
     fn list(&self) -> Result<alloc::vec::Vec<alloc::string::String>, SystemError> {
-        todo!()
+        Err(SystemError::ENOTDIR)
+    }
+}
+
+/// vcpu文件所属的伪文件系统（anon_inode风格），处理`kvm_run`映射的缺页，
+/// 并为fstatfs提供文件系统信息
+#[derive(Debug)]
+struct KvmVcpuFakeFs {
+    vcpu_dev: Arc<KvmVcpuDev>,
+}
+
+impl FileSystem for KvmVcpuFakeFs {
+    fn root_inode(&self) -> Arc<dyn IndexNode> {
+        // vcpu文件不会被挂载，根inode就是这个vcpu文件本身
+        self.vcpu_dev.clone()
+    }
+
+    fn info(&self) -> FsInfo {
+        FsInfo {
+            blk_dev_id: 0,
+            max_name_len: 255,
+        }
+    }
+
+    fn as_any_ref(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "kvm-vcpu"
+    }
+
+    fn super_block(&self) -> SuperBlock {
+        SuperBlock::new(Magic::ANON_INODE_MAGIC, MMArch::PAGE_SIZE as u64, 255)
+    }
+
+    unsafe fn fault(&self, pfm: &mut PageFaultMessage) -> VmFaultReason {
+        PageFaultHandler::filemap_fault(pfm)
+    }
+
+    unsafe fn map_pages(
+        &self,
+        pfm: &mut PageFaultMessage,
+        start_pgoff: usize,
+        end_pgoff: usize,
+    ) -> VmFaultReason {
+        PageFaultHandler::filemap_map_pages(pfm, start_pgoff, end_pgoff)
     }
 }
 
@@ -0,0 +1,96 @@
+//! 内核中模拟的I/O设备
+//!
+//! 设备注册到VM的PIO总线上之后，客户机对这段端口的访问直接在VM exit的处理中完成，
+//! 不需要返回用户态的设备模型。没有注册的端口仍然交给用户态处理。
+//!
+//! 参考: https://code.dragonos.org.cn/xref/linux-6.6.21/virt/kvm/kvm_main.c#5640
+
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt::Debug;
+use system_error::SystemError;
+
+use crate::libs::rwlock::RwLock;
+
+use super::{vcpu::VirtCpu, Vm};
+
+/// 内核中模拟的I/O设备
+pub trait KvmIoDevice: Debug + Send + Sync {
+    /// # 读设备的寄存器
+    ///
+    /// ## 参数
+    /// - `vcpu`: 发起访问的vCPU
+    /// - `vm`: vCPU所属的VM
+    /// - `offset`: 相对于设备起始地址的偏移
+    /// - `data`: 读到的数据，长度为访问的宽度
+    fn read(
+        &self,
+        vcpu: &mut VirtCpu,
+        vm: &Vm,
+        offset: u64,
+        data: &mut [u8],
+    ) -> Result<(), SystemError>;
+
+    /// # 写设备的寄存器
+    ///
+    /// 参数与[`KvmIoDevice::read`]相同，`data`为写入的数据
+    fn write(
+        &self,
+        vcpu: &mut VirtCpu,
+        vm: &Vm,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), SystemError>;
+}
+
+#[derive(Debug)]
+struct KvmIoRange {
+    addr: u64,
+    len: u64,
+    dev: Arc<dyn KvmIoDevice>,
+}
+
+/// 一条I/O总线上注册的设备
+#[derive(Debug, Default)]
+pub struct KvmIoBus {
+    ranges: RwLock<Vec<KvmIoRange>>,
+}
+
+impl KvmIoBus {
+    /// # 注册设备
+    ///
+    /// ## 参数
+    /// - `addr`, `len`: 设备占用的地址范围
+    /// - `dev`: 设备
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EEXIST)`: 与已经注册的设备重叠
+    pub fn register(
+        &self,
+        addr: u64,
+        len: u64,
+        dev: Arc<dyn KvmIoDevice>,
+    ) -> Result<(), SystemError> {
+        let end = addr.checked_add(len).ok_or(SystemError::EINVAL)?;
+        if len == 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let mut ranges = self.ranges.write();
+        if ranges.iter().any(|r| r.addr < end && addr < r.addr + r.len) {
+            return Err(SystemError::EEXIST);
+        }
+        ranges.push(KvmIoRange { addr, len, dev });
+        Ok(())
+    }
+
+    /// # 查找处理一次访问的设备
+    ///
+    /// ## 返回值
+    /// (设备, 访问地址相对于设备起始地址的偏移)，访问必须完全落在设备的范围内
+    pub fn find(&self, addr: u64, len: u64) -> Option<(Arc<dyn KvmIoDevice>, u64)> {
+        self.ranges
+            .read()
+            .iter()
+            .find(|r| addr >= r.addr && addr + len <= r.addr + r.len)
+            .map(|r| (r.dev.clone(), addr - r.addr))
+    }
+}
@@ -18,10 +18,11 @@ use crate::{
         spinlock::{SpinLock, SpinLockGuard},
     },
     mm::{kernel_mapper::KernelMapper, page::EntryFlags, MemoryManagementArch, VirtAddr},
+    syscall::user_access::{copy_from_user_protected, copy_to_user_protected},
     virt::vm::{kvm_host::KVM_ADDRESS_SPACE_NUM, user_api::KvmUserspaceMemoryRegion},
 };
 
-use super::{LockedVm, Vm};
+use super::{search_memslots, LockedVm, Vm};
 
 pub const KVM_USER_MEM_SLOTS: u16 = u16::MAX;
 pub const KVM_INTERNAL_MEM_SLOTS: u16 = 3;
@@ -268,6 +269,48 @@ pub enum KvmMemoryChangeMode {
 }
 
 impl Vm {
+    /// # 把一段客户机物理地址转换为VMM进程中的虚拟地址
+    ///
+    /// ## 参数
+    /// - `gpa`: 客户机物理地址
+    /// - `len`: 长度，整段地址必须在同一个内存槽中
+    /// - `write`: 是否要写入，只读的内存槽不能写入
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EFAULT)`: 地址不在任何内存槽中，跨越了内存槽，或者内存槽只读
+    pub fn gpa_to_hva(&self, gpa: u64, len: usize, write: bool) -> Result<VirtAddr, SystemError> {
+        let gfn = gpa >> PAGE_SHIFT;
+        let slot = search_memslots(self.memslots[0].clone(), gfn).ok_or(SystemError::EFAULT)?;
+        let slot = slot.read();
+        if !slot.is_visible()
+            || (write && slot.flags.contains(UserMemRegionFlag::READONLY))
+            || gpa.checked_add(len as u64).ok_or(SystemError::EFAULT)?
+                > (slot.base_gfn + slot.npages as u64) << PAGE_SHIFT
+        {
+            return Err(SystemError::EFAULT);
+        }
+        Ok(slot.userspace_addr + (gpa - (slot.base_gfn << PAGE_SHIFT)) as usize)
+    }
+
+    /// # 读客户机物理内存
+    ///
+    /// 整段地址必须在同一个内存槽中
+    pub fn read_guest(&self, gpa: u64, buf: &mut [u8]) -> Result<(), SystemError> {
+        let hva = self.gpa_to_hva(gpa, buf.len(), false)?;
+        // vCPU线程属于创建VM的进程，内存槽中的地址在当前地址空间中
+        unsafe { copy_from_user_protected(buf, hva) }?;
+        Ok(())
+    }
+
+    /// # 写客户机物理内存
+    ///
+    /// 整段地址必须在同一个可写的内存槽中
+    pub fn write_guest(&self, gpa: u64, buf: &[u8]) -> Result<(), SystemError> {
+        let hva = self.gpa_to_hva(gpa, buf.len(), true)?;
+        unsafe { copy_to_user_protected(hva, buf) }?;
+        Ok(())
+    }
+
     #[inline(never)]
     pub fn set_memory_region(&mut self, mem: KvmUserspaceMemoryRegion) -> Result<(), SystemError> {
         if mem.slot >= u16::MAX as u32 {
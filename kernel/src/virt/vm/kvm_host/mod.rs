@@ -4,7 +4,6 @@ use core::{
 };
 
 use alloc::{
-    boxed::Box,
     sync::{Arc, Weak},
     vec::Vec,
 };
@@ -25,17 +24,20 @@ use crate::{
     smp::cpu::ProcessorId,
     virt::vm::{
         kvm_dev::KvmVcpuDev,
-        kvm_host::vcpu::{LockedVirtCpu, VirtCpu},
+        kvm_host::vcpu::{KvmRunPages, LockedVirtCpu, VirtCpu},
     },
 };
 
 use self::{
+    io_bus::KvmIoBus,
     mem::{GfnToHvaCache, KvmMemSlotSet, LockedVmMemSlotSet, PfnCacheUsage},
     vcpu::{GuestDebug, VcpuMode},
 };
 
+pub mod io_bus;
 pub mod mem;
 pub mod vcpu;
+pub mod virtio_blk;
 
 const KVM_ADDRESS_SPACE_NUM: usize = 1;
 pub const KVM_USERSAPCE_IRQ_SOURCE_ID: usize = 0;
@@ -44,6 +46,8 @@ pub const KVM_IRQFD_RESAMPLE_IRQ_SOURCE_ID: usize = 1;
 #[derive(Debug)]
 pub struct LockedVm {
     inner: SpinLock<Vm>,
+    /// 与[`Vm::pio_bus`]是同一条总线。vCPU运行时一直持有VM的锁，注册设备不能等待这把锁
+    pio_bus: Arc<KvmIoBus>,
 }
 
 static KVM_USAGE_COUNT: AtomicUsize = AtomicUsize::new(0);
@@ -53,6 +57,11 @@ impl LockedVm {
         self.inner.lock()
     }
 
+    /// VM的PIO总线
+    pub fn pio_bus(&self) -> &Arc<KvmIoBus> {
+        &self.pio_bus
+    }
+
     pub fn create(vm_type: usize) -> Result<Arc<Self>, SystemError> {
         let mut memslots_set = vec![];
         let mut memslots = vec![];
@@ -68,6 +77,7 @@ impl LockedVm {
             memslots_set.push(tmp);
             memslots.push(memslots_set[i][0].clone());
         }
+        let pio_bus = Arc::new(KvmIoBus::default());
 
         let kvm = Vm {
             mm: ProcessManager::current_pcb()
@@ -91,10 +101,12 @@ impl LockedVm {
             kvm_vmx: KvmVmx::default(),
             nr_memslots_dirty_logging: 0,
             mmu_invalidate_seq: 0,
+            pio_bus: pio_bus.clone(),
         };
 
         let ret = Arc::new(Self {
             inner: SpinLock::new(kvm),
+            pio_bus,
         });
 
         Self::hardware_enable_all()?;
@@ -145,9 +157,17 @@ pub struct Vm {
     pub kvm_vmx: KvmVmx,
 
     pub mmu_invalidate_seq: u64, //用于表示内存管理单元（MMU）无效化序列号
+
+    /// 内核中模拟的PIO设备
+    pio_bus: Arc<KvmIoBus>,
 }
 
 impl Vm {
+    /// VM的PIO总线
+    pub fn pio_bus(&self) -> &Arc<KvmIoBus> {
+        &self.pio_bus
+    }
+
     #[inline(never)]
     pub fn create_vcpu(&mut self, id: usize) -> Result<usize, SystemError> {
         if id >= self.max_vcpus {
@@ -173,7 +193,7 @@ impl Vm {
 
         let vcpu_inode = KvmVcpuDev::new(vcpu);
 
-        let file = File::new(vcpu_inode, FileFlags::from_bits_truncate(0x777))?;
+        let file = File::new(vcpu_inode, FileFlags::O_RDWR)?;
 
         let fd = ProcessManager::current_pcb()
             .fd_table()
@@ -186,7 +206,7 @@ impl Vm {
     /// ### 创建一个vcpu，并且初始化部分数据
     #[inline(never)]
     pub fn _create_vcpu(&mut self, id: usize) -> Result<Arc<LockedVirtCpu>, SystemError> {
-        let mut vcpu = self.new_vcpu(id);
+        let mut vcpu = self.new_vcpu(id)?;
 
         vcpu.init_arch(self, id)?;
 
@@ -194,8 +214,8 @@ impl Vm {
     }
 
     #[inline(never)]
-    pub fn new_vcpu(&self, id: usize) -> VirtCpu {
-        return VirtCpu {
+    pub fn new_vcpu(&self, id: usize) -> Result<VirtCpu, SystemError> {
+        return Ok(VirtCpu {
             cpu: ProcessorId::INVALID,
             kvm: Some(self.lock_vm_ref.clone()),
             vcpu_id: id,
@@ -209,11 +229,11 @@ impl Vm {
             private: None,
             request: VirtCpuRequest::empty(),
             guest_debug: GuestDebug::empty(),
-            run: unsafe { Some(Box::new_zeroed().assume_init()) },
+            run: Some(KvmRunPages::new()?),
             _vcpu_idx: 0,
             mode: VcpuMode::OutsideGuestMode,
             stat: Default::default(),
-        };
+        });
     }
 
     #[cfg(target_arch = "x86_64")]
@@ -1,18 +1,27 @@
+use core::ops::{Deref, DerefMut};
+
 use alloc::{
-    boxed::Box,
     string::String,
     sync::{Arc, Weak},
 };
+use system_error::SystemError;
 
 use crate::{
     arch::{
+        mm::LockedFrameAllocator,
         vm::{
             kvm_host::{vcpu::VirtCpuRequest, KvmReg},
             vmx::VmxVCpuPriv,
         },
-        VirtCpuArch, VirtCpuStat,
+        MMArch, VirtCpuArch, VirtCpuStat,
     },
+    filesystem::page_cache::PageCache,
     libs::spinlock::{SpinLock, SpinLockGuard},
+    mm::{
+        allocator::page_frame::PageFrameCount,
+        page::{page_manager_lock, PageFlags, PageType},
+        MemoryManagementArch,
+    },
     process::RawPid,
     smp::cpu::ProcessorId,
     virt::vm::user_api::UapiKvmRun,
@@ -75,7 +84,77 @@ pub struct VirtCpu {
 
     /// 记录请求
     pub request: VirtCpuRequest,
-    pub run: Option<Box<UapiKvmRun>>,
+    pub run: Option<KvmRunPages>,
+}
+
+/// vCPU的fd可以mmap的页数：kvm_run所在的页和PIO数据页
+pub const KVM_VCPU_MMAP_PAGES: usize = 2;
+/// PIO数据页在映射中的页号，与Linux的KVM_PIO_PAGE_OFFSET相同
+pub const KVM_PIO_PAGE_OFFSET: usize = 1;
+
+const _: () = assert!(core::mem::size_of::<UapiKvmRun>() <= MMArch::PAGE_SIZE);
+
+/// # 与用户态共享的kvm_run
+///
+/// 用户态mmap vCPU的fd之后，通过第一页读取VM exit的原因和参数，
+/// 交给用户态处理的PIO的数据放在第二页（`kvm_run.io.data_offset`指向这里）
+#[derive(Debug)]
+pub struct KvmRunPages {
+    /// 第一页在内核中的虚拟地址
+    vaddr: usize,
+    page_cache: Arc<PageCache>,
+}
+
+impl KvmRunPages {
+    pub fn new() -> Result<Self, SystemError> {
+        let page_cache = PageCache::new(None, None);
+        let mut page_manager_guard = page_manager_lock();
+        let (phys, pages) = page_manager_guard.create_pages(
+            PageType::Normal,
+            PageFlags::PG_UNEVICTABLE,
+            &mut LockedFrameAllocator,
+            PageFrameCount::new(KVM_VCPU_MMAP_PAGES),
+        )?;
+        drop(page_manager_guard);
+        for (i, page) in pages.iter().enumerate() {
+            page.write().add_flags(PageFlags::PG_UPTODATE);
+            page_cache.insert_ready_page(i, page.clone())?;
+        }
+        // create_pages已经把页清零
+        let vaddr = unsafe { MMArch::phys_2_virt(phys) }
+            .ok_or(SystemError::EFAULT)?
+            .data();
+        Ok(Self { vaddr, page_cache })
+    }
+
+    /// 用于mmap的页缓存
+    pub fn page_cache(&self) -> &Arc<PageCache> {
+        &self.page_cache
+    }
+
+    /// PIO数据页
+    pub fn pio_data(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(
+                (self.vaddr + KVM_PIO_PAGE_OFFSET * MMArch::PAGE_SIZE) as *mut u8,
+                MMArch::PAGE_SIZE,
+            )
+        }
+    }
+}
+
+impl Deref for KvmRunPages {
+    type Target = UapiKvmRun;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*(self.vaddr as *const UapiKvmRun) }
+    }
+}
+
+impl DerefMut for KvmRunPages {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *(self.vaddr as *mut UapiKvmRun) }
+    }
 }
 
 impl VirtCpu {
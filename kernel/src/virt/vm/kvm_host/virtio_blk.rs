@@ -0,0 +1,459 @@
+//! 内核中的virtio-blk后端
+//!
+//! 把主机上的一个文件或者块设备作为virtio-blk磁盘提供给客户机。
+//! 设备实现的是legacy virtio-pci的I/O寄存器块，注册在VM的PIO总线上。
+//! 客户机通知队列时，在VM exit的处理中直接遍历共享内存中的virtqueue并读写后端文件，
+//! 磁盘I/O不需要经过用户态的设备模型。
+//!
+//! 目前的限制：
+//! - PCI配置空间仍然由用户态模拟，用户态把I/O BAR分配到创建设备时指定的端口；
+//! - 只有一个virtqueue，不支持MSI-X、间接描述符和VIRTIO_RING_F_EVENT_IDX；
+//! - 没有中断控制器的模拟，完成请求时直接向发起通知的vCPU注入创建设备时指定的向量。
+//!
+//! 参考: https://docs.oasis-open.org/virtio/virtio/v1.2/virtio-v1.2.html 4.1.4.8, 5.2
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use system_error::SystemError;
+
+use crate::{
+    arch::vm::kvm_host::vcpu::VirtCpuRequest,
+    filesystem::vfs::file::File,
+    libs::spinlock::SpinLock,
+    process::ProcessManager,
+    virt::vm::user_api::{PosixKvmVirtioBlkConfig, KVM_VIRTIO_BLK_READONLY},
+};
+
+use super::{io_bus::KvmIoDevice, vcpu::VirtCpu, LockedVm, Vm};
+
+/// 寄存器块的大小
+pub const VIRTIO_BLK_PIO_LEN: u64 = 0x40;
+
+// legacy virtio-pci的寄存器偏移
+const VIRTIO_PCI_HOST_FEATURES: u64 = 0;
+const VIRTIO_PCI_GUEST_FEATURES: u64 = 4;
+const VIRTIO_PCI_QUEUE_PFN: u64 = 8;
+const VIRTIO_PCI_QUEUE_NUM: u64 = 12;
+const VIRTIO_PCI_QUEUE_SEL: u64 = 14;
+const VIRTIO_PCI_QUEUE_NOTIFY: u64 = 16;
+const VIRTIO_PCI_STATUS: u64 = 18;
+const VIRTIO_PCI_ISR: u64 = 19;
+/// 没有MSI-X时设备配置的起始偏移
+const VIRTIO_PCI_CONFIG: u64 = 20;
+
+const VIRTIO_PCI_QUEUE_ADDR_SHIFT: u32 = 12;
+const VIRTIO_PCI_VRING_ALIGN: u64 = 4096;
+
+const VIRTIO_CONFIG_S_NEEDS_RESET: u8 = 0x40;
+const VIRTIO_PCI_ISR_QUEUE: u8 = 1;
+
+const VIRTIO_BLK_F_SEG_MAX: u32 = 1 << 2;
+const VIRTIO_BLK_F_RO: u32 = 1 << 5;
+const VIRTIO_BLK_F_BLK_SIZE: u32 = 1 << 6;
+const VIRTIO_BLK_F_FLUSH: u32 = 1 << 9;
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
+const VIRTIO_BLK_T_GET_ID: u32 = 8;
+
+const VIRTIO_BLK_S_OK: u8 = 0;
+const VIRTIO_BLK_S_IOERR: u8 = 1;
+const VIRTIO_BLK_S_UNSUPP: u8 = 2;
+
+const VIRTIO_BLK_ID_BYTES: usize = 20;
+
+const VRING_DESC_F_NEXT: u16 = 1;
+const VRING_DESC_F_WRITE: u16 = 2;
+const VRING_AVAIL_F_NO_INTERRUPT: u16 = 1;
+
+/// virtqueue的大小
+const QUEUE_SIZE: u16 = 128;
+const SECTOR_SIZE: u64 = 512;
+/// 读写后端文件时的缓冲区大小
+const BOUNCE_SIZE: usize = 64 * 1024;
+
+/// virtio_blk_outhdr的大小
+const OUTHDR_LEN: u32 = 16;
+
+#[derive(Debug, Clone, Copy)]
+struct VringDesc {
+    addr: u64,
+    len: u32,
+    flags: u16,
+}
+
+#[derive(Debug, Default)]
+struct VirtioBlkState {
+    guest_features: u32,
+    queue_pfn: u32,
+    queue_sel: u16,
+    status: u8,
+    isr: u8,
+    /// 下一个要处理的avail ring项
+    last_avail_idx: u16,
+}
+
+impl VirtioBlkState {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// 内核中的virtio-blk设备
+#[derive(Debug)]
+pub struct KvmVirtioBlk {
+    file: Arc<File>,
+    /// 容量（扇区数）
+    capacity: u64,
+    readonly: bool,
+    vector: u8,
+    /// 寄存器和队列的状态。vCPU访问寄存器时已经持有VM的锁，请求的处理是串行的
+    state: SpinLock<VirtioBlkState>,
+}
+
+impl KvmVirtioBlk {
+    /// # 处理KVM_CREATE_VIRTIO_BLK
+    ///
+    /// ## 参数
+    /// - `vm`: 设备所属的VM
+    /// - `config`: 用户态传入的参数
+    ///
+    /// ## 返回值
+    /// - `Err(SystemError::EBADF)`: 文件描述符无效，或者打开方式不允许读写
+    /// - `Err(SystemError::EEXIST)`: 端口与已有的设备重叠
+    pub fn create(vm: &LockedVm, config: &PosixKvmVirtioBlkConfig) -> Result<(), SystemError> {
+        if config.flags & !KVM_VIRTIO_BLK_READONLY != 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let readonly = config.flags & KVM_VIRTIO_BLK_READONLY != 0;
+        let file = ProcessManager::current_pcb()
+            .fd_table()
+            .read()
+            .get_file_by_fd(config.fd)
+            .ok_or(SystemError::EBADF)?;
+        file.readable()?;
+        if !readonly {
+            file.writeable()?;
+        }
+        let metadata = file.inode().metadata()?;
+        if metadata.size < 0 {
+            return Err(SystemError::EINVAL);
+        }
+
+        let dev = Arc::new(Self {
+            file,
+            capacity: metadata.size as u64 / SECTOR_SIZE,
+            readonly,
+            vector: config.vector,
+            state: SpinLock::new(VirtioBlkState::default()),
+        });
+        vm.pio_bus()
+            .register(config.port as u64, VIRTIO_BLK_PIO_LEN, dev)
+    }
+
+    fn host_features(&self) -> u32 {
+        let mut features = VIRTIO_BLK_F_SEG_MAX | VIRTIO_BLK_F_BLK_SIZE | VIRTIO_BLK_F_FLUSH;
+        if self.readonly {
+            features |= VIRTIO_BLK_F_RO;
+        }
+        features
+    }
+
+    /// 寄存器块的内容，读操作从中截取
+    fn regs(&self, state: &VirtioBlkState) -> [u8; VIRTIO_BLK_PIO_LEN as usize] {
+        let mut regs = [0u8; VIRTIO_BLK_PIO_LEN as usize];
+        let mut put = |offset: u64, bytes: &[u8]| {
+            regs[offset as usize..offset as usize + bytes.len()].copy_from_slice(bytes);
+        };
+        put(
+            VIRTIO_PCI_HOST_FEATURES,
+            &self.host_features().to_le_bytes(),
+        );
+        put(
+            VIRTIO_PCI_GUEST_FEATURES,
+            &state.guest_features.to_le_bytes(),
+        );
+        put(VIRTIO_PCI_QUEUE_PFN, &state.queue_pfn.to_le_bytes());
+        let num = if state.queue_sel == 0 { QUEUE_SIZE } else { 0 };
+        put(VIRTIO_PCI_QUEUE_NUM, &num.to_le_bytes());
+        put(VIRTIO_PCI_QUEUE_SEL, &state.queue_sel.to_le_bytes());
+        put(VIRTIO_PCI_STATUS, &[state.status]);
+        put(VIRTIO_PCI_ISR, &[state.isr]);
+
+        // struct virtio_blk_config：capacity, size_max, seg_max, geometry, blk_size
+        let config = VIRTIO_PCI_CONFIG;
+        put(config, &self.capacity.to_le_bytes());
+        // 每个请求除了数据还需要头部和状态两个描述符
+        put(config + 12, &(QUEUE_SIZE as u32 - 2).to_le_bytes());
+        put(config + 20, &(SECTOR_SIZE as u32).to_le_bytes());
+        regs
+    }
+
+    /// # 处理队列中所有可用的请求
+    ///
+    /// ## 返回值
+    /// 是否需要向客户机发送中断
+    fn process_queue(&self, vm: &Vm, state: &mut VirtioBlkState) -> Result<bool, SystemError> {
+        if state.queue_pfn == 0 || state.status & VIRTIO_CONFIG_S_NEEDS_RESET != 0 {
+            return Ok(false);
+        }
+        let n = QUEUE_SIZE as u64;
+        let desc_table = (state.queue_pfn as u64) << VIRTIO_PCI_QUEUE_ADDR_SHIFT;
+        let avail = desc_table + 16 * n;
+        let used = (avail + 4 + 2 * n + 2).next_multiple_of(VIRTIO_PCI_VRING_ALIGN);
+
+        let mut completed = false;
+        loop {
+            let avail_idx = read_u16(vm, avail + 2)?;
+            if avail_idx == state.last_avail_idx {
+                break;
+            }
+            if avail_idx.wrapping_sub(state.last_avail_idx) > QUEUE_SIZE {
+                return Err(SystemError::EINVAL);
+            }
+            let slot = (state.last_avail_idx % QUEUE_SIZE) as u64;
+            let head = read_u16(vm, avail + 4 + 2 * slot)?;
+            let written = self.process_request(vm, desc_table, head)?;
+
+            // 先写入used ring的项，再更新idx，客户机看到新的idx时项一定已经写好
+            let used_idx = read_u16(vm, used + 2)?;
+            let elem = used + 4 + 8 * (used_idx % QUEUE_SIZE) as u64;
+            vm.write_guest(elem, &(head as u32).to_le_bytes())?;
+            vm.write_guest(elem + 4, &written.to_le_bytes())?;
+            core::sync::atomic::fence(core::sync::atomic::Ordering::Release);
+            vm.write_guest(used + 2, &used_idx.wrapping_add(1).to_le_bytes())?;
+
+            state.last_avail_idx = state.last_avail_idx.wrapping_add(1);
+            completed = true;
+        }
+
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+        let avail_flags = read_u16(vm, avail)?;
+        Ok(completed && avail_flags & VRING_AVAIL_F_NO_INTERRUPT == 0)
+    }
+
+    /// 读出一个请求的描述符链
+    fn read_chain(vm: &Vm, desc_table: u64, head: u16) -> Result<Vec<VringDesc>, SystemError> {
+        let mut chain = Vec::new();
+        let mut idx = head;
+        loop {
+            // 链的长度超过队列大小说明有环
+            if idx >= QUEUE_SIZE || chain.len() >= QUEUE_SIZE as usize {
+                return Err(SystemError::EINVAL);
+            }
+            let mut raw = [0u8; 16];
+            vm.read_guest(desc_table + 16 * idx as u64, &mut raw)?;
+            let desc = VringDesc {
+                addr: u64::from_le_bytes(raw[0..8].try_into().unwrap()),
+                len: u32::from_le_bytes(raw[8..12].try_into().unwrap()),
+                flags: u16::from_le_bytes([raw[12], raw[13]]),
+            };
+            chain.push(desc);
+            if desc.flags & VRING_DESC_F_NEXT == 0 {
+                return Ok(chain);
+            }
+            idx = u16::from_le_bytes([raw[14], raw[15]]);
+        }
+    }
+
+    /// # 处理一个请求
+    ///
+    /// 描述符链的格式为：virtio_blk_outhdr，数据，1字节的状态
+    ///
+    /// ## 返回值
+    /// 写入客户机内存的字节数。描述符链的格式不对时返回错误，设备进入NEEDS_RESET状态
+    fn process_request(&self, vm: &Vm, desc_table: u64, head: u16) -> Result<u32, SystemError> {
+        let chain = Self::read_chain(vm, desc_table, head)?;
+        if chain.len() < 2 {
+            return Err(SystemError::EINVAL);
+        }
+        let hdr = chain[0];
+        let status = chain[chain.len() - 1];
+        if hdr.flags & VRING_DESC_F_WRITE != 0
+            || hdr.len < OUTHDR_LEN
+            || status.flags & VRING_DESC_F_WRITE == 0
+            || status.len < 1
+        {
+            return Err(SystemError::EINVAL);
+        }
+        let data = &chain[1..chain.len() - 1];
+
+        let mut raw = [0u8; OUTHDR_LEN as usize];
+        vm.read_guest(hdr.addr, &mut raw)?;
+        let req_type = u32::from_le_bytes(raw[0..4].try_into().unwrap());
+        let sector = u64::from_le_bytes(raw[8..16].try_into().unwrap());
+
+        let mut written = 0;
+        let result = match req_type {
+            VIRTIO_BLK_T_IN => {
+                if data.iter().any(|d| d.flags & VRING_DESC_F_WRITE == 0) {
+                    return Err(SystemError::EINVAL);
+                }
+                let r = self.do_read(vm, sector, data);
+                if r.is_ok() {
+                    written = data.iter().fold(0u32, |n, d| n.saturating_add(d.len));
+                }
+                r
+            }
+            VIRTIO_BLK_T_OUT => {
+                if data.iter().any(|d| d.flags & VRING_DESC_F_WRITE != 0) {
+                    return Err(SystemError::EINVAL);
+                }
+                if self.readonly {
+                    Err(SystemError::EROFS)
+                } else {
+                    self.do_write(vm, sector, data)
+                }
+            }
+            VIRTIO_BLK_T_FLUSH => self.file.inode().sync(),
+            VIRTIO_BLK_T_GET_ID => match data.first() {
+                Some(d) if d.flags & VRING_DESC_F_WRITE != 0 => {
+                    let id = b"dragonos-kvm-vblk";
+                    let len = id.len().min(d.len as usize).min(VIRTIO_BLK_ID_BYTES);
+                    vm.write_guest(d.addr, &id[..len])?;
+                    written = len as u32;
+                    Ok(())
+                }
+                _ => return Err(SystemError::EINVAL),
+            },
+            _ => Err(SystemError::EOPNOTSUPP_OR_ENOTSUP),
+        };
+
+        let status_byte = match result {
+            Ok(()) => VIRTIO_BLK_S_OK,
+            Err(SystemError::EOPNOTSUPP_OR_ENOTSUP) => VIRTIO_BLK_S_UNSUPP,
+            Err(e) => {
+                log::warn!(
+                    "kvm virtio-blk: request {} at sector {} failed: {:?}",
+                    req_type,
+                    sector,
+                    e
+                );
+                VIRTIO_BLK_S_IOERR
+            }
+        };
+        vm.write_guest(status.addr + status.len as u64 - 1, &[status_byte])?;
+        Ok(written + 1)
+    }
+
+    /// 检查请求的范围，返回起始的字节偏移
+    fn check_range(&self, sector: u64, data: &[VringDesc]) -> Result<usize, SystemError> {
+        let len: u64 = data.iter().map(|d| d.len as u64).sum();
+        let offset = sector.checked_mul(SECTOR_SIZE).ok_or(SystemError::EIO)?;
+        if len % SECTOR_SIZE != 0
+            || offset.checked_add(len).ok_or(SystemError::EIO)? > self.capacity * SECTOR_SIZE
+        {
+            return Err(SystemError::EIO);
+        }
+        Ok(offset as usize)
+    }
+
+    fn do_read(&self, vm: &Vm, sector: u64, data: &[VringDesc]) -> Result<(), SystemError> {
+        let mut offset = self.check_range(sector, data)?;
+        let mut buf = vec![0u8; BOUNCE_SIZE];
+        for d in data {
+            let mut done = 0;
+            while done < d.len as usize {
+                let n = (d.len as usize - done).min(BOUNCE_SIZE);
+                let r = self.file.pread(offset, n, &mut buf[..n])?;
+                // 文件在创建设备之后被截断，超出的部分读出0
+                buf[r..n].fill(0);
+                vm.write_guest(d.addr + done as u64, &buf[..n])?;
+                done += n;
+                offset += n;
+            }
+        }
+        Ok(())
+    }
+
+    fn do_write(&self, vm: &Vm, sector: u64, data: &[VringDesc]) -> Result<(), SystemError> {
+        let mut offset = self.check_range(sector, data)?;
+        let mut buf = vec![0u8; BOUNCE_SIZE];
+        for d in data {
+            let mut done = 0;
+            while done < d.len as usize {
+                let n = (d.len as usize - done).min(BOUNCE_SIZE);
+                vm.read_guest(d.addr + done as u64, &mut buf[..n])?;
+                if self.file.pwrite(offset, n, &buf[..n])? != n {
+                    return Err(SystemError::EIO);
+                }
+                done += n;
+                offset += n;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl KvmIoDevice for KvmVirtioBlk {
+    fn read(
+        &self,
+        _vcpu: &mut VirtCpu,
+        _vm: &Vm,
+        offset: u64,
+        data: &mut [u8],
+    ) -> Result<(), SystemError> {
+        let mut state = self.state.lock();
+        let regs = self.regs(&state);
+        let start = offset as usize;
+        data.copy_from_slice(&regs[start..start + data.len()]);
+        // 读ISR会清除它
+        if offset == VIRTIO_PCI_ISR {
+            state.isr = 0;
+        }
+        Ok(())
+    }
+
+    fn write(
+        &self,
+        vcpu: &mut VirtCpu,
+        vm: &Vm,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), SystemError> {
+        let mut raw = [0u8; 4];
+        raw[..data.len()].copy_from_slice(data);
+        let val = u32::from_le_bytes(raw);
+
+        let mut state = self.state.lock();
+        match offset {
+            VIRTIO_PCI_GUEST_FEATURES => state.guest_features = val & self.host_features(),
+            VIRTIO_PCI_QUEUE_PFN => {
+                if state.queue_sel == 0 {
+                    state.queue_pfn = val;
+                    state.last_avail_idx = 0;
+                }
+            }
+            VIRTIO_PCI_QUEUE_SEL => state.queue_sel = val as u16,
+            VIRTIO_PCI_QUEUE_NOTIFY => {
+                if val != 0 {
+                    return Ok(());
+                }
+                match self.process_queue(vm, &mut state) {
+                    Ok(true) => {
+                        state.isr |= VIRTIO_PCI_ISR_QUEUE;
+                        vcpu.arch.queue_interrupt(self.vector, false);
+                        vcpu.request(VirtCpuRequest::KVM_REQ_EVENT);
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        log::warn!("kvm virtio-blk: broken virtqueue: {:?}", e);
+                        state.status |= VIRTIO_CONFIG_S_NEEDS_RESET;
+                    }
+                }
+            }
+            // 写0复位设备
+            VIRTIO_PCI_STATUS if val == 0 => state.reset(),
+            VIRTIO_PCI_STATUS => state.status = val as u8,
+            // 只读寄存器和设备配置，忽略写入
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn read_u16(vm: &Vm, gpa: u64) -> Result<u16, SystemError> {
+    let mut raw = [0u8; 2];
+    vm.read_guest(gpa, &mut raw)?;
+    Ok(u16::from_le_bytes(raw))
+}
@@ -91,6 +91,24 @@ impl KvmUserspaceMemoryRegion {
     }
 }
 
+/// KVM_CREATE_VIRTIO_BLK的参数：在VM的PIO总线上创建一个内核中的virtio-blk设备
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PosixKvmVirtioBlkConfig {
+    /// 后端文件或者块设备的文件描述符
+    pub fd: i32,
+    /// KVM_VIRTIO_BLK_READONLY
+    pub flags: u32,
+    /// legacy virtio-pci的I/O BAR的起始端口，由用户态在PCI配置空间中分配
+    pub port: u16,
+    /// 完成请求时注入给客户机的中断向量
+    pub vector: u8,
+    pub pad: [u8; 5],
+}
+
+/// 设备对客户机只读
+pub const KVM_VIRTIO_BLK_READONLY: u32 = 1 << 0;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct UapiKvmRun {
@@ -205,6 +205,66 @@ static void test_fork_unlock_release(const char *path) {
     close(fd);
 }
 
+static int set_posix_lock(int fd, short type) {
+    struct flock fl = {};
+    fl.l_type = type;
+    fl.l_whence = SEEK_SET;
+    fl.l_start = 0;
+    fl.l_len = 0;
+    return fcntl(fd, F_SETLK, &fl);
+}
+
+/* 在子进程中用新打开的fd尝试加锁，返回值的bit0/bit1分别表示flock/fcntl加锁成功 */
+static int probe_locks_in_child(const char *path) {
+    pid_t pid = fork();
+    if (pid < 0) {
+        return -1;
+    }
+    if (pid == 0) {
+        int fd = open_rw_file(path);
+        int rc = 0;
+        if (fd < 0) {
+            _exit(255);
+        }
+        if (flock(fd, LOCK_EX | LOCK_NB) == 0) {
+            rc |= 1;
+        }
+        if (set_posix_lock(fd, F_WRLCK) == 0) {
+            rc |= 2;
+        }
+        close(fd);
+        _exit(rc);
+    }
+
+    int status = 0;
+    if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status)) {
+        return -1;
+    }
+    return WEXITSTATUS(status);
+}
+
+static void test_independent_of_fcntl_locks(const char *path) {
+    int fd = open_rw_file(path);
+    CHECK(fd >= 0, "open fd for flock/fcntl independence test");
+    if (fd < 0) {
+        return;
+    }
+
+    CHECK(flock(fd, LOCK_EX | LOCK_NB) == 0, "acquire flock before fcntl probe");
+    CHECK(set_posix_lock(fd, F_UNLCK) == 0, "fcntl F_UNLCK on flock-locked file");
+    CHECK(probe_locks_in_child(path) == 2,
+          "flock does not block fcntl locks and survives fcntl unlock");
+    CHECK(flock(fd, LOCK_UN) == 0, "release flock after fcntl probe");
+
+    CHECK(set_posix_lock(fd, F_WRLCK) == 0, "acquire fcntl write lock");
+    CHECK(flock(fd, LOCK_UN) == 0, "flock unlock on fcntl-locked file");
+    CHECK(probe_locks_in_child(path) == 1,
+          "fcntl lock does not block flock and survives flock unlock");
+    CHECK(set_posix_lock(fd, F_UNLCK) == 0, "release fcntl write lock");
+
+    close(fd);
+}
+
 static volatile sig_atomic_t g_sigalrm_seen = 0;
 
 static void sigalrm_handler(int sig) {
@@ -478,6 +538,7 @@ int main(void) {
     test_dup_unlock_release(path);
     test_dup_last_close_release(path);
     test_fork_unlock_release(path);
+    test_independent_of_fcntl_locks(path);
     test_blocking_interrupted_by_signal(path);
     test_opath_ebadf(path);
     test_pipe_flock();
@@ -6,6 +6,7 @@
 #include <stdlib.h>
 #include <string.h>
 #include <sys/ioctl.h>
+#include <sys/mount.h>
 #include <sys/stat.h>
 #include <time.h>
 #include <unistd.h>
@@ -837,6 +838,89 @@ static int test_device_inaccessible_after_deletion(void) {
   return TEST_PASS;
 }
 
+// 测试 10: 后端文件所在的文件系统被卸载
+static int test_umount_backing_fs(void) {
+  const char *test_name = "Umount Backing Filesystem";
+  const char *mnt = "/tmp/loop_umount_test";
+  char image[128];
+  snprintf(image, sizeof(image), "%s/image.img", mnt);
+  TEST_BEGIN(test_name);
+
+  mkdir(mnt, 0755);
+  if (mount("tmpfs", mnt, "tmpfs", 0, NULL) < 0) {
+    rmdir(mnt);
+    TEST_END_FAIL(test_name, "failed to mount tmpfs");
+    return TEST_FAIL;
+  }
+
+  int minor = -1;
+  int loop_fd = -1;
+  int backing_fd = -1;
+  const char *reason = NULL;
+  char path[64];
+
+  if (create_test_file(image, TEST_FILE_SIZE_2) < 0 ||
+      (backing_fd = open(image, O_RDWR)) < 0) {
+    reason = "failed to create backing file";
+    goto out;
+  }
+  if (create_loop_device(g_control_fd, &minor) < 0) {
+    reason = "failed to create loop device";
+    goto out;
+  }
+  sprintf(path, "/dev/loop%d", minor);
+  loop_fd = open(path, O_RDWR);
+  if (loop_fd < 0 || ioctl(loop_fd, LOOP_SET_FD, backing_fd) < 0) {
+    reason = "failed to bind loop device";
+    goto out;
+  }
+  close(backing_fd);
+  backing_fd = -1;
+  LOG_STEP("Bound %s to %s", path, image);
+
+  // 绑定的 loop 设备使挂载处于忙状态
+  errno = 0;
+  if (umount(mnt) == 0 || errno != EBUSY) {
+    reason = "umount should fail with EBUSY while loop device is bound";
+    goto out;
+  }
+  LOG_STEP("umount correctly returned EBUSY");
+
+  // 延迟卸载时 loop 设备被自动解除绑定
+  if (umount2(mnt, MNT_DETACH) < 0) {
+    reason = "umount2(MNT_DETACH) failed";
+    goto out;
+  }
+  LOG_STEP("umount2(MNT_DETACH) succeeded");
+
+  struct loop_status64 status;
+  errno = 0;
+  if (ioctl(loop_fd, LOOP_GET_STATUS64, &status) == 0 || errno != ENXIO) {
+    reason = "loop device should be unbound after the backing fs is gone";
+    goto out;
+  }
+  LOG_STEP("Loop device detached (errno: %d)", errno);
+
+out:
+  if (backing_fd >= 0)
+    close(backing_fd);
+  if (loop_fd >= 0) {
+    ioctl(loop_fd, LOOP_CLR_FD, 0);
+    close(loop_fd);
+  }
+  if (minor >= 0)
+    ioctl(g_control_fd, LOOP_CTL_REMOVE, minor);
+  umount(mnt);
+  rmdir(mnt);
+
+  if (reason != NULL) {
+    TEST_END_FAIL(test_name, reason);
+    return TEST_FAIL;
+  }
+  TEST_END_PASS(test_name);
+  return TEST_PASS;
+}
+
 // ===================================================================
 // 主函数
 // ===================================================================
@@ -927,6 +1011,7 @@ int main(void) {
   test_duplicate_deletion();
   test_fd_leak_detection();
   test_device_inaccessible_after_deletion();
+  test_umount_backing_fs();
 
   // ===================================================================
   // 清理
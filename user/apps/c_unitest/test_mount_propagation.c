@@ -275,14 +275,7 @@ static void test_shared_bind_propagation(void) {
     if (marker_exists(bind_subdir, "shared_test_marker")) {
         TEST_PASS(test_name);
     } else {
-        /*
-         * Note: This may fail if bind mount doesn't properly create
-         * a peer relationship, which is a more advanced feature.
-         * For basic implementation, we just verify the API works.
-         */
-        printf("[INFO] %s: propagation not visible (may be expected for basic impl)\n",
-               test_name);
-        tests_passed++; /* Count as pass for now */
+        TEST_FAIL(test_name, "submount not propagated to the bind mount peer");
     }
 
     /* Cleanup */
@@ -913,6 +906,66 @@ static void test_recursive_bind_mount(void) {
     rmdir(bind);
 }
 
+/**
+ * Test 7a: Recursive bind mount skips unbindable submounts
+ *
+ * Setup: Create a mount with two submounts and make one of them unbindable.
+ * Test: Perform recursive bind mount and verify only the bindable submount
+ * is copied.
+ */
+static void test_recursive_bind_skips_unbindable(void) {
+    const char *test_name = "recursive_bind_skips_unbindable";
+    const char *base = "/tmp/test_rbind_unb_base";
+    const char *bind = "/tmp/test_rbind_unb_target";
+    const char *keep = "/tmp/test_rbind_unb_base/keep";
+    const char *skip = "/tmp/test_rbind_unb_base/skip";
+    const char *bind_keep = "/tmp/test_rbind_unb_target/keep";
+    const char *bind_skip = "/tmp/test_rbind_unb_target/skip";
+
+    if (ensure_dir(base) != 0 || ensure_dir(bind) != 0) {
+        TEST_FAIL_ERRNO(test_name);
+        return;
+    }
+    if (mount("", base, "ramfs", 0, NULL) != 0) {
+        TEST_FAIL_ERRNO(test_name);
+        rmdir(base);
+        rmdir(bind);
+        return;
+    }
+    if (ensure_dir(keep) != 0 || ensure_dir(skip) != 0 ||
+        mount("", keep, "ramfs", 0, NULL) != 0 ||
+        mount("", skip, "ramfs", 0, NULL) != 0 ||
+        mount(NULL, skip, NULL, MS_UNBINDABLE, NULL) != 0 ||
+        create_marker(keep, "marker_keep") != 0 ||
+        create_marker(skip, "marker_skip") != 0) {
+        TEST_FAIL_ERRNO(test_name);
+        goto out;
+    }
+
+    if (mount(base, bind, NULL, MS_BIND | MS_REC, NULL) != 0) {
+        TEST_FAIL_ERRNO(test_name);
+        goto out;
+    }
+
+    if (!marker_exists(bind_keep, "marker_keep")) {
+        TEST_FAIL(test_name, "bindable submount was not copied");
+    } else if (marker_exists(bind_skip, "marker_skip")) {
+        TEST_FAIL(test_name, "unbindable submount was copied");
+    } else {
+        TEST_PASS(test_name);
+    }
+
+    umount(bind_keep);
+    umount(bind);
+out:
+    cleanup_marker(keep, "marker_keep");
+    cleanup_marker(skip, "marker_skip");
+    cleanup_mount(keep);
+    cleanup_mount(skip);
+    cleanup_mount(base);
+    rmdir(bind);
+}
+
 /**
  * Test 7b: Non-recursive bind mount should NOT copy submounts
  *
@@ -1488,6 +1541,7 @@ int main(int argc, char *argv[]) {
     test_shared_bind_propagation();
     test_recursive_propagation();
     test_recursive_bind_mount();
+    test_recursive_bind_skips_unbindable();
     test_non_recursive_bind_mount();
     test_unbindable_prevents_bind();
     test_shared_umount_propagation();
@@ -65,7 +65,7 @@ void mount_overlayfs() {
 
 void create_directory_in_merged() {
   char dirpath[256];
-  snprintf(dirpath, sizeof(dirpath), "%s/newdir", UPPERDIR);
+  snprintf(dirpath, sizeof(dirpath), "%s/newdir", MERGEDDIR);
 
   if (mkdir(dirpath, 0755) != 0) {
     perror("Failed to create directory in merged dir");
@@ -75,9 +75,69 @@ void create_directory_in_merged() {
   printf("step4 : success\n");
 }
 
+void copy_up_on_write() {
+  char path[256];
+  char buf[64] = {0};
+  snprintf(path, sizeof(path), "%s/lowerfile.txt", MERGEDDIR);
+
+  int fd = open(path, O_WRONLY | O_APPEND);
+  if (fd < 0 || write(fd, "appended\n", 9) != 9) {
+    perror("Failed to write lower file through merged dir");
+    exit(EXIT_FAILURE);
+  }
+  close(fd);
+
+  // 修改只发生在上层的副本上，下层保持不变
+  struct stat st;
+  snprintf(path, sizeof(path), "%s/lowerfile.txt", UPPERDIR);
+  if (stat(path, &st) != 0 || st.st_size != 37) {
+    fprintf(stderr, "copy-up did not create the upper file\n");
+    exit(EXIT_FAILURE);
+  }
+  snprintf(path, sizeof(path), "%s/lowerfile.txt", LOWERDIR);
+  fd = open(path, O_RDONLY);
+  if (fd < 0 || read(fd, buf, sizeof(buf)) != 28) {
+    fprintf(stderr, "lower file was modified\n");
+    exit(EXIT_FAILURE);
+  }
+  close(fd);
+  printf("step5 : success\n");
+}
+
+void whiteout_on_unlink() {
+  char path[256];
+  snprintf(path, sizeof(path), "%s/lowerfile.txt", MERGEDDIR);
+  if (unlink(path) != 0) {
+    perror("Failed to unlink file in merged dir");
+    exit(EXIT_FAILURE);
+  }
+  if (access(path, F_OK) == 0) {
+    fprintf(stderr, "unlinked file is still visible\n");
+    exit(EXIT_FAILURE);
+  }
+
+  // 下层的文件还在，上层留下了一个0:0的字符设备
+  struct stat st;
+  snprintf(path, sizeof(path), "%s/lowerfile.txt", LOWERDIR);
+  if (access(path, F_OK) != 0) {
+    fprintf(stderr, "lower file was removed\n");
+    exit(EXIT_FAILURE);
+  }
+  snprintf(path, sizeof(path), "%s/lowerfile.txt", UPPERDIR);
+  if (stat(path, &st) != 0 || !S_ISCHR(st.st_mode) || st.st_rdev != 0) {
+    fprintf(stderr, "whiteout was not created\n");
+    exit(EXIT_FAILURE);
+  }
+  printf("step6 : success\n");
+}
+
 int main() {
   create_directories();
+  create_lower_file();
   mount_overlayfs();
   create_directory_in_merged();
+  copy_up_on_write();
+  whiteout_on_unlink();
+  umount(MERGEDDIR);
   return 0;
-}
\ No newline at end of file
+}
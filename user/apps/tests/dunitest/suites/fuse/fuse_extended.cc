@@ -4,6 +4,7 @@
 #include <sys/ioctl.h>
 #include <sys/syscall.h>
 #include <sys/wait.h>
+#include <sys/xattr.h>
 
 #include "fuse_gtest_common.h"
 
@@ -649,6 +650,85 @@ static int ext_test_p4_subtype_mount() {
     return 0;
 }
 
+static int ext_test_xattr_enosys() {
+    const char *mp = "/tmp/test_fuse_xattr";
+    if (ensure_dir(mp) != 0) {
+        printf("[FAIL] ensure_dir(%s): %s (errno=%d)\n", mp, strerror(errno), errno);
+        return -1;
+    }
+
+    int fd = open("/dev/fuse", O_RDWR);
+    if (fd < 0) {
+        printf("[FAIL] open(/dev/fuse): %s (errno=%d)\n", strerror(errno), errno);
+        rmdir(mp);
+        return -1;
+    }
+
+    volatile int stop = 0;
+    volatile int init_done = 0;
+    struct fuse_daemon_args args;
+    memset(&args, 0, sizeof(args));
+    args.fd = fd;
+    args.stop = &stop;
+    args.init_done = &init_done;
+    args.stop_on_destroy = 1;
+
+    pthread_t th;
+    if (pthread_create(&th, NULL, fuse_daemon_thread, &args) != 0) {
+        printf("[FAIL] pthread_create\n");
+        close(fd);
+        rmdir(mp);
+        return -1;
+    }
+
+    char opts[256];
+    snprintf(opts, sizeof(opts), "fd=%d,rootmode=040755,user_id=0,group_id=0", fd);
+    if (mount("none", mp, "fuse", 0, opts) != 0) {
+        printf("[FAIL] mount(fuse): %s (errno=%d)\n", strerror(errno), errno);
+        stop = 1;
+        close(fd);
+        pthread_join(th, NULL);
+        rmdir(mp);
+        return -1;
+    }
+
+    for (int i = 0; i < 200 && !init_done; i++) {
+        usleep(10 * 1000);
+    }
+
+    char file_path[256];
+    snprintf(file_path, sizeof(file_path), "%s/hello.txt", mp);
+
+    int ret = 0;
+    if (!init_done) {
+        printf("[FAIL] init handshake timeout\n");
+        ret = -1;
+    }
+    // simplefs不处理xattr请求，回复ENOSYS；第二次请求由内核直接返回
+    for (int i = 0; ret == 0 && i < 2; i++) {
+        char buf[16];
+        if (getxattr(file_path, "user.a", buf, sizeof(buf)) != -1 || errno != EOPNOTSUPP) {
+            printf("[FAIL] getxattr round %d: expected EOPNOTSUPP, errno=%d\n", i, errno);
+            ret = -1;
+        }
+    }
+    if (ret == 0 && (setxattr(file_path, "user.a", "1", 1, 0) != -1 || errno != EOPNOTSUPP)) {
+        printf("[FAIL] setxattr: expected EOPNOTSUPP, errno=%d\n", errno);
+        ret = -1;
+    }
+    if (ret == 0 && (listxattr(file_path, NULL, 0) != -1 || errno != EOPNOTSUPP)) {
+        printf("[FAIL] listxattr: expected EOPNOTSUPP, errno=%d\n", errno);
+        ret = -1;
+    }
+
+    umount(mp);
+    stop = 1;
+    close(fd);
+    pthread_join(th, NULL);
+    rmdir(mp);
+    return ret;
+}
+
 static int ext_run_child_drop_priv_and_stat(const char *mp, int expect_errno, int expect_success) {
     pid_t pid = fork();
     if (pid < 0) {
@@ -998,6 +1078,10 @@ TEST(FuseExtended, SubtypeMountFuseDotSubtype) {
     ASSERT_EQ(0, ext_test_p4_subtype_mount());
 }
 
+TEST(FuseExtended, XattrEnosysBecomesEopnotsupp) {
+    ASSERT_EQ(0, ext_test_xattr_enosys());
+}
+
 TEST(FuseExtended, PermissionModelAllowOtherDefaultPermissions) {
     if (geteuid() != 0) {
         GTEST_SKIP() << "requires root to execute setuid/setgid permission cases";
@@ -0,0 +1,151 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+#include <unistd.h>
+
+#include <algorithm>
+#include <atomic>
+#include <string>
+#include <thread>
+#include <vector>
+
+namespace {
+
+// AHCI硬盘的主设备号，与SCSI硬盘相同
+constexpr unsigned kSdMajor = 8;
+constexpr size_t kAlign = 4096;
+// 只读取第一个分区之前的区域，避免与文件系统的写入并发
+constexpr size_t kMaxRegion = 1024 * 1024;
+constexpr int kThreads = 8;
+constexpr int kReadsPerThread = 200;
+
+class AhciNcq : public ::testing::Test {
+protected:
+    void SetUp() override {
+        for (char c = 'a'; c <= 'z'; c++) {
+            std::string path = std::string("/dev/sd") + c;
+            struct stat st;
+            if (stat(path.c_str(), &st) == 0 && S_ISBLK(st.st_mode) &&
+                major(st.st_rdev) == kSdMajor) {
+                dev_ = path;
+                break;
+            }
+        }
+        if (dev_.empty()) {
+            GTEST_SKIP() << "no AHCI disk";
+        }
+
+        flags_ = O_RDONLY | O_DIRECT;
+        int fd = open(dev_.c_str(), flags_);
+        if (fd < 0 && errno == EINVAL) {
+            flags_ = O_RDONLY;
+            fd = open(dev_.c_str(), flags_);
+        }
+        ASSERT_GE(fd, 0) << dev_ << ": " << strerror(errno);
+
+        // 读取MBR，找到第一个分区的起始位置
+        void *mbr = nullptr;
+        ASSERT_EQ(0, posix_memalign(&mbr, kAlign, kAlign));
+        ASSERT_EQ((ssize_t)kAlign, pread(fd, mbr, kAlign, 0)) << strerror(errno);
+        const uint8_t *p = static_cast<const uint8_t *>(mbr);
+        uint64_t first = UINT64_MAX;
+        if (p[510] == 0x55 && p[511] == 0xaa) {
+            for (int i = 0; i < 4; i++) {
+                const uint8_t *e = p + 446 + i * 16;
+                uint32_t start, count;
+                memcpy(&start, e + 8, 4);
+                memcpy(&count, e + 12, 4);
+                if (e[4] != 0 && count != 0 && start < first) {
+                    first = start;
+                }
+            }
+        }
+        free(mbr);
+        if (first == UINT64_MAX) {
+            close(fd);
+            GTEST_SKIP() << dev_ << " has no MBR partition";
+        }
+        region_ = std::min<uint64_t>(first * 512, kMaxRegion) / kAlign * kAlign;
+        if (region_ < 16 * kAlign) {
+            close(fd);
+            GTEST_SKIP() << dev_ << ": the gap before the first partition is too small";
+        }
+
+        // 顺序读出参考数据
+        ASSERT_EQ(0, posix_memalign(reinterpret_cast<void **>(&ref_), kAlign, region_));
+        ASSERT_EQ((ssize_t)region_, pread(fd, ref_, region_, 0)) << strerror(errno);
+        close(fd);
+    }
+
+    void TearDown() override { free(ref_); }
+
+    std::string dev_;
+    int flags_ = 0;
+    size_t region_ = 0;
+    char *ref_ = nullptr;
+};
+
+TEST_F(AhciNcq, ConcurrentReadsMatchSequentialRead) {
+    // 多个线程同时发起读请求，控制器支持NCQ时它们会同时在途
+    std::atomic<int> errors{0};
+    std::atomic<int> mismatches{0};
+    std::vector<std::thread> threads;
+    for (int t = 0; t < kThreads; t++) {
+        threads.emplace_back([&, t] {
+            int fd = open(dev_.c_str(), flags_);
+            if (fd < 0) {
+                errors++;
+                return;
+            }
+            void *buf = nullptr;
+            if (posix_memalign(&buf, kAlign, 16 * kAlign) != 0) {
+                close(fd);
+                errors++;
+                return;
+            }
+            uint32_t x = 12345 + t;
+            size_t blocks = region_ / kAlign;
+            for (int i = 0; i < kReadsPerThread; i++) {
+                x = x * 1103515245u + 12345u;
+                size_t len = ((x >> 8) % 16 + 1) * kAlign;
+                size_t off = ((x >> 16) % blocks) * kAlign;
+                len = std::min(len, region_ - off);
+                if (pread(fd, buf, len, off) != (ssize_t)len) {
+                    errors++;
+                    break;
+                }
+                if (memcmp(buf, ref_ + off, len) != 0) {
+                    mismatches++;
+                }
+            }
+            free(buf);
+            close(fd);
+        });
+    }
+    for (auto &t : threads) {
+        t.join();
+    }
+    EXPECT_EQ(0, errors.load());
+    EXPECT_EQ(0, mismatches.load());
+}
+
+TEST_F(AhciNcq, ReadsSpanningManySectorsMatch) {
+    // 一个请求覆盖整个区域，会拆分成多个命令
+    int fd = open(dev_.c_str(), flags_);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    char *buf = nullptr;
+    ASSERT_EQ(0, posix_memalign(reinterpret_cast<void **>(&buf), kAlign, region_));
+    ASSERT_EQ((ssize_t)(region_ - kAlign), pread(fd, buf, region_ - kAlign, kAlign))
+        << strerror(errno);
+    EXPECT_EQ(0, memcmp(buf, ref_ + kAlign, region_ - kAlign));
+    free(buf);
+    close(fd);
+}
+
+}  // namespace
@@ -0,0 +1,168 @@
+#include <gtest/gtest.h>
+
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <time.h>
+#include <unistd.h>
+
+#include <string>
+
+#ifndef MS_LAZYTIME
+#define MS_LAZYTIME (1 << 25)
+#endif
+
+namespace {
+
+const char *kTarget = "/tmp/atime_test";
+const time_t kDay = 24 * 60 * 60;
+
+class Atime : public ::testing::Test {
+protected:
+    void TearDown() override {
+        if (mounted_) {
+            umount2(kTarget, MNT_DETACH);
+        }
+        rmdir(kTarget);
+    }
+
+    void Mount(unsigned long flags) {
+        struct stat st;
+        if (stat(kTarget, &st) != 0) {
+            ASSERT_EQ(0, mkdir(kTarget, 0755)) << strerror(errno);
+        }
+        if (mount("tmpfs", kTarget, "tmpfs", flags, nullptr) != 0) {
+            GTEST_SKIP() << "mount tmpfs: " << strerror(errno);
+        }
+        mounted_ = true;
+        file_ = std::string(kTarget) + "/f";
+        int fd = open(file_.c_str(), O_CREAT | O_WRONLY, 0644);
+        ASSERT_GE(fd, 0) << strerror(errno);
+        ASSERT_EQ(5, write(fd, "hello", 5));
+        close(fd);
+    }
+
+    // 把atime和mtime设置为现在之前的若干秒，返回设置的atime
+    time_t SetTimes(const std::string &path, time_t atime_ago, time_t mtime_ago) {
+        time_t now = time(nullptr);
+        struct timespec ts[2] = {{now - atime_ago, 0}, {now - mtime_ago, 0}};
+        EXPECT_EQ(0, utimensat(AT_FDCWD, path.c_str(), ts, 0)) << strerror(errno);
+        return now - atime_ago;
+    }
+
+    time_t GetAtime(const std::string &path) {
+        struct stat st;
+        EXPECT_EQ(0, stat(path.c_str(), &st)) << strerror(errno);
+        return st.st_atime;
+    }
+
+    void ReadFile(int extra_flags = 0) {
+        int fd = open(file_.c_str(), O_RDONLY | extra_flags);
+        ASSERT_GE(fd, 0) << strerror(errno);
+        char buf[8];
+        ASSERT_EQ(5, read(fd, buf, sizeof(buf)));
+        close(fd);
+    }
+
+    bool mounted_ = false;
+    std::string file_;
+};
+
+}  // namespace
+
+TEST_F(Atime, RelatimeUpdatesWhenOlderThanMtime) {
+    Mount(0);
+    SetTimes(file_, 100, 50);
+    time_t before = GetAtime(file_);
+    ReadFile();
+    EXPECT_GT(GetAtime(file_), before);
+}
+
+TEST_F(Atime, RelatimeSkipsRecentAtime) {
+    Mount(0);
+    // utimensat会把ctime更新为现在，atime要晚于ctime才不需要更新
+    SetTimes(file_, -50, 100);
+    time_t before = GetAtime(file_);
+    ReadFile();
+    EXPECT_EQ(before, GetAtime(file_));
+}
+
+TEST_F(Atime, RelatimeUpdatesAfterADay) {
+    Mount(0);
+    SetTimes(file_, 2 * kDay, 3 * kDay);
+    time_t before = GetAtime(file_);
+    ReadFile();
+    EXPECT_GT(GetAtime(file_), before);
+}
+
+TEST_F(Atime, StrictatimeAlwaysUpdates) {
+    Mount(MS_STRICTATIME);
+    SetTimes(file_, -50, 100);
+    time_t before = GetAtime(file_);
+    ReadFile();
+    EXPECT_NE(before, GetAtime(file_));
+}
+
+TEST_F(Atime, NoatimeNeverUpdates) {
+    Mount(MS_NOATIME);
+    SetTimes(file_, 100, 50);
+    time_t before = GetAtime(file_);
+    ReadFile();
+    EXPECT_EQ(before, GetAtime(file_));
+}
+
+TEST_F(Atime, ONoatimeSkipsUpdate) {
+    Mount(MS_STRICTATIME);
+    SetTimes(file_, 100, 50);
+    time_t before = GetAtime(file_);
+    ReadFile(O_NOATIME);
+    EXPECT_EQ(before, GetAtime(file_));
+}
+
+TEST_F(Atime, NodiratimeSkipsDirectories) {
+    Mount(MS_STRICTATIME | MS_NODIRATIME);
+    std::string dir = std::string(kTarget) + "/d";
+    ASSERT_EQ(0, mkdir(dir.c_str(), 0755));
+    SetTimes(dir, 100, 50);
+    time_t before = GetAtime(dir);
+    DIR *d = opendir(dir.c_str());
+    ASSERT_NE(nullptr, d);
+    while (readdir(d) != nullptr) {
+    }
+    closedir(d);
+    EXPECT_EQ(before, GetAtime(dir));
+
+    // 普通文件不受影响
+    SetTimes(file_, 100, 50);
+    before = GetAtime(file_);
+    ReadFile();
+    EXPECT_GT(GetAtime(file_), before);
+}
+
+TEST_F(Atime, LazytimeVisibleBeforeWriteback) {
+    Mount(MS_STRICTATIME | MS_LAZYTIME);
+    SetTimes(file_, 100, 50);
+    time_t before = GetAtime(file_);
+    ReadFile();
+    time_t after = GetAtime(file_);
+    EXPECT_GT(after, before);
+
+    int fd = open(file_.c_str(), O_RDONLY | O_NOATIME);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    EXPECT_EQ(0, fsync(fd)) << strerror(errno);
+    close(fd);
+    EXPECT_EQ(after, GetAtime(file_));
+}
+
+TEST_F(Atime, UtimensatOverridesLazyAtime) {
+    Mount(MS_STRICTATIME | MS_LAZYTIME);
+    SetTimes(file_, 100, 50);
+    ReadFile();
+    time_t atime = SetTimes(file_, 1000, 50);
+    EXPECT_EQ(atime, GetAtime(file_));
+    sync();
+    EXPECT_EQ(atime, GetAtime(file_));
+}
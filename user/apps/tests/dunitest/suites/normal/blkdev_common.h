@@ -0,0 +1,105 @@
+#pragma once
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+#include <unistd.h>
+
+// 与Linux的include/uapi/linux/loop.h一致
+#define LOOP_SET_FD 0x4C00
+#define LOOP_CLR_FD 0x4C01
+#define LOOP_CTL_GET_FREE 0x4C82
+
+#define LOOP_CONTROL_PATH "/dev/loop-control"
+
+// 创建指定大小的稀疏文件，用作块设备的后备存储
+static inline int blk_create_backing(const char *path, off_t size) {
+    int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        return -1;
+    }
+    int ret = ftruncate(fd, size);
+    int saved = errno;
+    close(fd);
+    errno = saved;
+    return ret;
+}
+
+// 把backing绑定到一个空闲的loop设备，成功时把设备路径写入dev_path
+static inline int loop_attach(const char *backing, char *dev_path, size_t len) {
+    int ctl = open(LOOP_CONTROL_PATH, O_RDWR);
+    if (ctl < 0) {
+        return -1;
+    }
+    int file = open(backing, O_RDWR);
+    if (file < 0) {
+        int saved = errno;
+        close(ctl);
+        errno = saved;
+        return -1;
+    }
+
+    int ret = -1;
+    // 其他进程可能同时拿到同一个空闲设备，EBUSY时重试
+    for (int retry = 0; retry < 10; retry++) {
+        int minor = ioctl(ctl, LOOP_CTL_GET_FREE, 0);
+        if (minor < 0) {
+            break;
+        }
+        snprintf(dev_path, len, "/dev/loop%d", minor);
+        int dev = open(dev_path, O_RDWR);
+        if (dev < 0) {
+            break;
+        }
+        ret = ioctl(dev, LOOP_SET_FD, file);
+        int saved = errno;
+        close(dev);
+        errno = saved;
+        if (ret == 0 || errno != EBUSY) {
+            break;
+        }
+    }
+
+    int saved = errno;
+    close(file);
+    close(ctl);
+    errno = saved;
+    return ret;
+}
+
+static inline int loop_detach(const char *dev_path) {
+    int dev = open(dev_path, O_RDWR);
+    if (dev < 0) {
+        return -1;
+    }
+    int ret = ioctl(dev, LOOP_CLR_FD, 0);
+    int saved = errno;
+    close(dev);
+    errno = saved;
+    return ret;
+}
+
+// 块设备的设备号，失败时返回0
+static inline dev_t blk_devnum(const char *dev_path) {
+    struct stat st;
+    if (stat(dev_path, &st) != 0 || !S_ISBLK(st.st_mode)) {
+        return 0;
+    }
+    return st.st_rdev;
+}
+
+// 用seed生成可重复的测试数据
+static inline void blk_fill_pattern(void *buf, size_t len, uint32_t seed) {
+    uint8_t *p = (uint8_t *)buf;
+    uint32_t x = seed * 2654435761u + 1;
+    for (size_t i = 0; i < len; i++) {
+        x = x * 1103515245u + 12345u;
+        p[i] = (uint8_t)(x >> 16);
+    }
+}
@@ -0,0 +1,61 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+namespace {
+
+const char *kTarget = "/tmp/cifs_mount";
+
+static int ensure_dir(const char *path) {
+    struct stat st;
+    if (stat(path, &st) == 0) {
+        return S_ISDIR(st.st_mode) ? 0 : -1;
+    }
+    return mkdir(path, 0755);
+}
+
+class CifsMount : public ::testing::Test {
+protected:
+    void SetUp() override { ASSERT_EQ(0, ensure_dir(kTarget)) << strerror(errno); }
+
+    void TearDown() override {
+        umount2(kTarget, MNT_DETACH);
+        rmdir(kTarget);
+    }
+
+    // 参数错误在连接服务器之前就会被发现，不需要真实的SMB服务器
+    static int try_mount(const char *source, const char *options) {
+        return mount(source, kTarget, "cifs", 0, options);
+    }
+};
+
+}  // namespace
+
+TEST_F(CifsMount, RejectsSourceWithoutShare) {
+    EXPECT_EQ(-1, try_mount("//192.0.2.1", "user=guest"));
+    EXPECT_EQ(EINVAL, errno);
+}
+
+TEST_F(CifsMount, RejectsNonUncSource) {
+    EXPECT_EQ(-1, try_mount("192.0.2.1:/share", "user=guest"));
+    EXPECT_EQ(EINVAL, errno);
+}
+
+TEST_F(CifsMount, RejectsSubdirectoryOfShare) {
+    EXPECT_EQ(-1, try_mount("//192.0.2.1/share/dir", "user=guest"));
+    EXPECT_EQ(EINVAL, errno);
+}
+
+TEST_F(CifsMount, RejectsHostnameWithoutServerOption) {
+    EXPECT_EQ(-1, try_mount("//fileserver/share", "user=guest"));
+    EXPECT_EQ(EINVAL, errno);
+}
+
+TEST_F(CifsMount, RejectsOutOfRangePort) {
+    EXPECT_EQ(-1, try_mount("//192.0.2.1/share", "port=70000"));
+    EXPECT_EQ(EINVAL, errno);
+}
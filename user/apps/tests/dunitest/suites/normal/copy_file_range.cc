@@ -0,0 +1,156 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#include <string>
+#include <vector>
+
+namespace {
+
+static ssize_t do_copy(int fd_in, off_t *off_in, int fd_out, off_t *off_out, size_t len) {
+    return syscall(SYS_copy_file_range, fd_in, off_in, fd_out, off_out, len, 0);
+}
+
+static std::vector<char> pattern(size_t len) {
+    std::vector<char> data(len);
+    for (size_t i = 0; i < len; i++) {
+        data[i] = static_cast<char>((i * 31 + i / 4096) & 0xff);
+    }
+    return data;
+}
+
+class CopyFileRange : public ::testing::Test {
+protected:
+    void SetUp() override {
+        src_path_ = "/tmp/cfr_src_" + std::to_string(getpid());
+        dst_path_ = "/tmp/cfr_dst_" + std::to_string(getpid());
+        src_ = open(src_path_.c_str(), O_CREAT | O_RDWR | O_TRUNC, 0644);
+        ASSERT_GE(src_, 0) << strerror(errno);
+        dst_ = open(dst_path_.c_str(), O_CREAT | O_RDWR | O_TRUNC, 0644);
+        ASSERT_GE(dst_, 0) << strerror(errno);
+    }
+
+    void TearDown() override {
+        if (src_ >= 0) close(src_);
+        if (dst_ >= 0) close(dst_);
+        unlink(src_path_.c_str());
+        unlink(dst_path_.c_str());
+    }
+
+    void fill_source(const std::vector<char> &data) {
+        ASSERT_EQ(static_cast<ssize_t>(data.size()), pwrite(src_, data.data(), data.size(), 0));
+    }
+
+    std::vector<char> read_dest(size_t len, off_t off = 0) {
+        std::vector<char> buf(len);
+        ssize_t n = pread(dst_, buf.data(), len, off);
+        buf.resize(n < 0 ? 0 : n);
+        return buf;
+    }
+
+    std::string src_path_;
+    std::string dst_path_;
+    int src_ = -1;
+    int dst_ = -1;
+};
+
+}  // namespace
+
+TEST_F(CopyFileRange, CopiesLargeFileWithFileOffsets) {
+    // 跨越多个拷贝块，结尾不按页对齐
+    const size_t kLen = 3 * 128 * 1024 + 1234;
+    std::vector<char> data = pattern(kLen);
+    fill_source(data);
+    ASSERT_EQ(0, lseek(src_, 0, SEEK_SET));
+
+    size_t total = 0;
+    while (total < kLen) {
+        ssize_t n = do_copy(src_, nullptr, dst_, nullptr, kLen - total);
+        ASSERT_GT(n, 0) << strerror(errno);
+        total += n;
+    }
+    EXPECT_EQ(static_cast<off_t>(kLen), lseek(src_, 0, SEEK_CUR));
+    EXPECT_EQ(static_cast<off_t>(kLen), lseek(dst_, 0, SEEK_CUR));
+    EXPECT_EQ(data, read_dest(kLen + 1));
+
+    // 源文件已经读到结尾
+    EXPECT_EQ(0, do_copy(src_, nullptr, dst_, nullptr, 4096));
+}
+
+TEST_F(CopyFileRange, ExplicitOffsetsDoNotMoveFilePosition) {
+    std::vector<char> data = pattern(10000);
+    fill_source(data);
+
+    off_t off_in = 100;
+    off_t off_out = 5000;
+    ASSERT_EQ(1000, do_copy(src_, &off_in, dst_, &off_out, 1000)) << strerror(errno);
+    EXPECT_EQ(1100, off_in);
+    EXPECT_EQ(6000, off_out);
+    EXPECT_EQ(0, lseek(src_, 0, SEEK_CUR));
+    EXPECT_EQ(0, lseek(dst_, 0, SEEK_CUR));
+
+    std::vector<char> expect(data.begin() + 100, data.begin() + 1100);
+    EXPECT_EQ(expect, read_dest(1000, 5000));
+    // 目标文件中偏移之前的部分是空洞
+    EXPECT_EQ(std::vector<char>(5000, 0), read_dest(5000, 0));
+}
+
+TEST_F(CopyFileRange, ShortCopyAtEndOfSource) {
+    fill_source(pattern(3000));
+    off_t off_in = 2000;
+    EXPECT_EQ(1000, do_copy(src_, &off_in, dst_, nullptr, 8192));
+    EXPECT_EQ(3000, off_in);
+
+    off_in = 5000;
+    EXPECT_EQ(0, do_copy(src_, &off_in, dst_, nullptr, 100));
+}
+
+TEST_F(CopyFileRange, SameFileRanges) {
+    std::vector<char> data = pattern(8192);
+    fill_source(data);
+
+    off_t off_in = 0;
+    off_t off_out = 100;
+    EXPECT_EQ(-1, do_copy(src_, &off_in, src_, &off_out, 1000));
+    EXPECT_EQ(EINVAL, errno);
+
+    off_in = 0;
+    off_out = 4096;
+    ASSERT_EQ(4096, do_copy(src_, &off_in, src_, &off_out, 4096)) << strerror(errno);
+    std::vector<char> buf(4096);
+    ASSERT_EQ(4096, pread(src_, buf.data(), buf.size(), 4096));
+    EXPECT_EQ(std::vector<char>(data.begin(), data.begin() + 4096), buf);
+}
+
+TEST_F(CopyFileRange, RejectsBadArguments) {
+    fill_source(pattern(100));
+
+    EXPECT_EQ(-1, syscall(SYS_copy_file_range, src_, nullptr, dst_, nullptr, 100, 1));
+    EXPECT_EQ(EINVAL, errno);
+
+    off_t negative = -1;
+    EXPECT_EQ(-1, do_copy(src_, &negative, dst_, nullptr, 100));
+    EXPECT_EQ(EINVAL, errno);
+
+    int append = open(dst_path_.c_str(), O_WRONLY | O_APPEND);
+    ASSERT_GE(append, 0) << strerror(errno);
+    EXPECT_EQ(-1, do_copy(src_, nullptr, append, nullptr, 100));
+    EXPECT_EQ(EBADF, errno);
+    close(append);
+
+    int rdonly = open(dst_path_.c_str(), O_RDONLY);
+    ASSERT_GE(rdonly, 0) << strerror(errno);
+    EXPECT_EQ(-1, do_copy(src_, nullptr, rdonly, nullptr, 100));
+    EXPECT_EQ(EBADF, errno);
+    close(rdonly);
+
+    int dir = open("/tmp", O_RDONLY | O_DIRECTORY);
+    ASSERT_GE(dir, 0) << strerror(errno);
+    EXPECT_EQ(-1, do_copy(dir, nullptr, dst_, nullptr, 100));
+    EXPECT_EQ(EISDIR, errno);
+    close(dir);
+}
@@ -0,0 +1,116 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#include <string>
+
+namespace {
+
+const char *kTarget = "/tmp/dcache_test";
+
+class Dcache : public ::testing::Test {
+protected:
+    void SetUp() override {
+        struct stat st;
+        if (stat(kTarget, &st) != 0) {
+            ASSERT_EQ(0, mkdir(kTarget, 0755)) << strerror(errno);
+        }
+        if (mount("tmpfs", kTarget, "tmpfs", 0, nullptr) != 0) {
+            GTEST_SKIP() << "mount tmpfs: " << strerror(errno);
+        }
+    }
+
+    void TearDown() override {
+        umount2(kTarget, MNT_DETACH);
+        rmdir(kTarget);
+    }
+
+    static std::string path(const char *name) { return std::string(kTarget) + "/" + name; }
+
+    static bool exists(const std::string &p) {
+        struct stat st;
+        return stat(p.c_str(), &st) == 0;
+    }
+
+    static void touch(const std::string &p) {
+        int fd = open(p.c_str(), O_CREAT | O_WRONLY, 0644);
+        ASSERT_GE(fd, 0) << strerror(errno);
+        close(fd);
+    }
+};
+
+}  // namespace
+
+TEST_F(Dcache, NegativeEntryDroppedOnCreate) {
+    std::string f = path("f");
+    // 查找两次，第二次命中负向项
+    EXPECT_FALSE(exists(f));
+    EXPECT_FALSE(exists(f));
+    touch(f);
+    EXPECT_TRUE(exists(f));
+}
+
+TEST_F(Dcache, PositiveEntryDroppedOnUnlink) {
+    std::string f = path("f");
+    touch(f);
+    EXPECT_TRUE(exists(f));
+    ASSERT_EQ(0, unlink(f.c_str())) << strerror(errno);
+    struct stat st;
+    EXPECT_EQ(-1, stat(f.c_str(), &st));
+    EXPECT_EQ(ENOENT, errno);
+}
+
+TEST_F(Dcache, RenameUpdatesBothNames) {
+    std::string a = path("a");
+    std::string b = path("b");
+    touch(a);
+    EXPECT_TRUE(exists(a));
+    EXPECT_FALSE(exists(b));
+    ASSERT_EQ(0, rename(a.c_str(), b.c_str())) << strerror(errno);
+    EXPECT_FALSE(exists(a));
+    EXPECT_TRUE(exists(b));
+}
+
+TEST_F(Dcache, RenameOverDirectory) {
+    std::string a = path("a");
+    std::string b = path("b");
+    ASSERT_EQ(0, mkdir(a.c_str(), 0755));
+    ASSERT_EQ(0, mkdir(b.c_str(), 0755));
+    touch(b + "/inner");
+    ASSERT_EQ(0, unlink((b + "/inner").c_str()));
+    EXPECT_FALSE(exists(b + "/inner"));
+
+    ASSERT_EQ(0, rename(a.c_str(), b.c_str())) << strerror(errno);
+    EXPECT_FALSE(exists(a));
+    EXPECT_FALSE(exists(b + "/inner"));
+    touch(b + "/inner");
+    EXPECT_TRUE(exists(b + "/inner"));
+}
+
+TEST_F(Dcache, RecreatedDirectoryStartsEmpty) {
+    std::string d = path("d");
+    ASSERT_EQ(0, mkdir(d.c_str(), 0755));
+    touch(d + "/x");
+    EXPECT_TRUE(exists(d + "/x"));
+    ASSERT_EQ(0, unlink((d + "/x").c_str()));
+    ASSERT_EQ(0, rmdir(d.c_str())) << strerror(errno);
+    EXPECT_FALSE(exists(d));
+
+    ASSERT_EQ(0, mkdir(d.c_str(), 0755));
+    EXPECT_FALSE(exists(d + "/x"));
+}
+
+TEST_F(Dcache, RemountDoesNotSeeOldEntries) {
+    std::string f = path("f");
+    touch(f);
+    EXPECT_TRUE(exists(f));
+    ASSERT_EQ(0, umount(kTarget)) << strerror(errno);
+    ASSERT_EQ(0, mount("tmpfs", kTarget, "tmpfs", 0, nullptr)) << strerror(errno);
+    EXPECT_FALSE(exists(f));
+}
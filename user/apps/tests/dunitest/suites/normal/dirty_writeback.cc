@@ -0,0 +1,117 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+#include <string>
+
+namespace {
+
+const char *kDirtyRatio = "/proc/sys/vm/dirty_ratio";
+const char *kDirtyBackgroundRatio = "/proc/sys/vm/dirty_background_ratio";
+const char *kDirtyWritebackCentisecs = "/proc/sys/vm/dirty_writeback_centisecs";
+
+static bool read_value(const char *path, long *value) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        return false;
+    }
+    char buf[32] = {};
+    ssize_t n = read(fd, buf, sizeof(buf) - 1);
+    close(fd);
+    if (n <= 0) {
+        return false;
+    }
+    *value = strtol(buf, nullptr, 10);
+    return true;
+}
+
+static int write_value(const char *path, long value) {
+    int fd = open(path, O_WRONLY);
+    if (fd < 0) {
+        return -1;
+    }
+    std::string s = std::to_string(value) + "\n";
+    ssize_t n = write(fd, s.c_str(), s.size());
+    int saved = errno;
+    close(fd);
+    errno = saved;
+    return n == (ssize_t)s.size() ? 0 : -1;
+}
+
+class DirtyTunables : public ::testing::Test {
+protected:
+    void SetUp() override {
+        if (access(kDirtyRatio, F_OK) != 0) {
+            GTEST_SKIP() << "no " << kDirtyRatio;
+        }
+        ASSERT_TRUE(read_value(kDirtyRatio, &ratio_));
+        ASSERT_TRUE(read_value(kDirtyBackgroundRatio, &background_));
+        ASSERT_TRUE(read_value(kDirtyWritebackCentisecs, &centisecs_));
+    }
+
+    void TearDown() override {
+        write_value(kDirtyRatio, ratio_);
+        write_value(kDirtyBackgroundRatio, background_);
+        write_value(kDirtyWritebackCentisecs, centisecs_);
+    }
+
+    long ratio_ = 0;
+    long background_ = 0;
+    long centisecs_ = 0;
+};
+
+}  // namespace
+
+TEST_F(DirtyTunables, DefaultsAreSane) {
+    EXPECT_GT(ratio_, 0);
+    EXPECT_LE(ratio_, 100);
+    EXPECT_LE(background_, 100);
+    EXPECT_GE(centisecs_, 0);
+}
+
+TEST_F(DirtyTunables, WriteAndReadBack) {
+    ASSERT_EQ(0, write_value(kDirtyRatio, 30)) << strerror(errno);
+    ASSERT_EQ(0, write_value(kDirtyBackgroundRatio, 5)) << strerror(errno);
+    ASSERT_EQ(0, write_value(kDirtyWritebackCentisecs, 100)) << strerror(errno);
+
+    long value = 0;
+    ASSERT_TRUE(read_value(kDirtyRatio, &value));
+    EXPECT_EQ(30, value);
+    ASSERT_TRUE(read_value(kDirtyBackgroundRatio, &value));
+    EXPECT_EQ(5, value);
+    ASSERT_TRUE(read_value(kDirtyWritebackCentisecs, &value));
+    EXPECT_EQ(100, value);
+}
+
+TEST_F(DirtyTunables, RejectsOutOfRangeRatio) {
+    EXPECT_EQ(-1, write_value(kDirtyRatio, 101));
+    EXPECT_EQ(EINVAL, errno);
+    EXPECT_EQ(-1, write_value(kDirtyBackgroundRatio, 101));
+    EXPECT_EQ(EINVAL, errno);
+
+    long value = 0;
+    ASSERT_TRUE(read_value(kDirtyRatio, &value));
+    EXPECT_EQ(ratio_, value);
+}
+
+TEST_F(DirtyTunables, WriteThroughLowLimitCompletes) {
+    // 上限很低时写入者会被暂停，但写回线程会把脏页降下来，写入最终能完成
+    ASSERT_EQ(0, write_value(kDirtyBackgroundRatio, 0)) << strerror(errno);
+    ASSERT_EQ(0, write_value(kDirtyRatio, 1)) << strerror(errno);
+
+    const char *path = "/tmp/dirty_writeback_file";
+    int fd = open(path, O_CREAT | O_TRUNC | O_RDWR, 0644);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    std::string chunk(64 * 1024, 'x');
+    for (int i = 0; i < 64; i++) {
+        ASSERT_EQ((ssize_t)chunk.size(), write(fd, chunk.data(), chunk.size()))
+            << strerror(errno);
+    }
+    EXPECT_EQ(0, fsync(fd)) << strerror(errno);
+    close(fd);
+    unlink(path);
+}
@@ -0,0 +1,136 @@
+#pragma once
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <unistd.h>
+
+// 与Linux的include/uapi/linux/dm-ioctl.h一致
+#define DM_VERSION_MAJOR 4
+#define DM_NAME_LEN 128
+#define DM_UUID_LEN 129
+#define DM_MAX_TYPE_NAME 16
+
+#define DM_CONTROL_PATH "/dev/mapper/control"
+
+struct dm_ioctl {
+    uint32_t version[3];
+    uint32_t data_size;
+    uint32_t data_start;
+    uint32_t target_count;
+    int32_t open_count;
+    uint32_t flags;
+    uint32_t event_nr;
+    uint32_t padding;
+    uint64_t dev;
+    char name[DM_NAME_LEN];
+    char uuid[DM_UUID_LEN];
+    char data[7];
+};
+
+struct dm_target_spec {
+    uint64_t sector_start;
+    uint64_t length;
+    int32_t status;
+    uint32_t next;
+    char target_type[DM_MAX_TYPE_NAME];
+};
+
+#define DM_IOCTL 0xfd
+#define DM_DEV_CREATE _IOWR(DM_IOCTL, 3, struct dm_ioctl)
+#define DM_DEV_REMOVE _IOWR(DM_IOCTL, 4, struct dm_ioctl)
+#define DM_DEV_SUSPEND _IOWR(DM_IOCTL, 6, struct dm_ioctl)
+#define DM_DEV_STATUS _IOWR(DM_IOCTL, 7, struct dm_ioctl)
+#define DM_TABLE_LOAD _IOWR(DM_IOCTL, 9, struct dm_ioctl)
+#define DM_TABLE_STATUS _IOWR(DM_IOCTL, 12, struct dm_ioctl)
+
+#define DM_READONLY_FLAG (1 << 0)
+#define DM_SUSPEND_FLAG (1 << 1)
+#define DM_STATUS_TABLE_FLAG (1 << 4)
+#define DM_ACTIVE_PRESENT_FLAG (1 << 5)
+#define DM_INACTIVE_PRESENT_FLAG (1 << 6)
+
+// ioctl的参数：头部之后是输入或输出的数据
+struct dm_buf {
+    struct dm_ioctl io;
+    char data[4096];
+};
+
+static inline void dm_init(struct dm_buf *buf, const char *name) {
+    memset(buf, 0, sizeof(*buf));
+    buf->io.version[0] = DM_VERSION_MAJOR;
+    buf->io.data_size = sizeof(*buf);
+    buf->io.data_start = sizeof(buf->io);
+    strncpy(buf->io.name, name, DM_NAME_LEN - 1);
+}
+
+static inline int dm_cmd(unsigned long cmd, struct dm_buf *buf) {
+    int ctl = open(DM_CONTROL_PATH, O_RDWR);
+    if (ctl < 0) {
+        return -1;
+    }
+    int ret = ioctl(ctl, cmd, buf);
+    int saved = errno;
+    close(ctl);
+    errno = saved;
+    return ret;
+}
+
+static inline int dm_simple(unsigned long cmd, const char *name, uint32_t flags) {
+    struct dm_buf buf;
+    dm_init(&buf, name);
+    buf.io.flags = flags;
+    return dm_cmd(cmd, &buf);
+}
+
+static inline int dm_create(const char *name) { return dm_simple(DM_DEV_CREATE, name, 0); }
+
+static inline int dm_remove(const char *name) { return dm_simple(DM_DEV_REMOVE, name, 0); }
+
+// 不带DM_SUSPEND_FLAG的DM_DEV_SUSPEND把加载的映射表换上并恢复设备
+static inline int dm_resume(const char *name) { return dm_simple(DM_DEV_SUSPEND, name, 0); }
+
+// 加载只有一个目标的映射表
+static inline int dm_load(const char *name, uint64_t len, const char *type, const char *params,
+                          uint32_t flags) {
+    struct dm_buf buf;
+    dm_init(&buf, name);
+    buf.io.flags = flags;
+    buf.io.target_count = 1;
+
+    struct dm_target_spec spec;
+    memset(&spec, 0, sizeof(spec));
+    spec.length = len;
+    strncpy(spec.target_type, type, DM_MAX_TYPE_NAME - 1);
+    memcpy(buf.data, &spec, sizeof(spec));
+    size_t plen = strlen(params);
+    if (sizeof(spec) + plen + 1 > sizeof(buf.data)) {
+        errno = E2BIG;
+        return -1;
+    }
+    memcpy(buf.data + sizeof(spec), params, plen + 1);
+    return dm_cmd(DM_TABLE_LOAD, &buf);
+}
+
+// 读出映射表的第一个目标和它的参数，没有映射表时target_count为0
+static inline int dm_table(const char *name, struct dm_buf *buf, struct dm_target_spec *spec,
+                           const char **params) {
+    dm_init(buf, name);
+    buf->io.flags = DM_STATUS_TABLE_FLAG;
+    if (dm_cmd(DM_TABLE_STATUS, buf) != 0) {
+        return -1;
+    }
+    if (buf->io.target_count == 0) {
+        return 0;
+    }
+    const char *base = (const char *)buf + buf->io.data_start;
+    if (spec != NULL) {
+        memcpy(spec, base, sizeof(*spec));
+    }
+    if (params != NULL) {
+        *params = base + sizeof(struct dm_target_spec);
+    }
+    return 0;
+}
@@ -0,0 +1,209 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <sys/sysmacros.h>
+#include <unistd.h>
+
+#include <string>
+#include <vector>
+
+#include "blkdev_common.h"
+#include "dm_common.h"
+
+namespace {
+
+constexpr off_t kBackingSize = 1024 * 1024;
+constexpr uint64_t kLenSectors = kBackingSize / 512;
+constexpr size_t kIoLen = 64 * 1024;
+
+const std::string kKey = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+const std::string kWrongKey = "1f1e1d1c1b1a191817161514131211100f0e0d0c0b0a09080706050403020100";
+
+// IEEE 1619-2007 XTS-AES-128 向量4的密钥，明文是两遍0x00~0xff
+const std::string kVectorKey =
+    "2718281828459045235360287471352631415926535897932384626433832795";
+// 数据单元0和1的密文的前32字节
+const unsigned char kVectorUnit0[32] = {
+    0x27, 0xa7, 0x47, 0x9b, 0xef, 0xa1, 0xd4, 0x76, 0x48, 0x9f, 0x30, 0x8c, 0xd4, 0xcf, 0xa6, 0xe2,
+    0xa9, 0x6e, 0x4b, 0xbe, 0x32, 0x08, 0xff, 0x25, 0x28, 0x7d, 0xd3, 0x81, 0x96, 0x16, 0xe8, 0x9c,
+};
+const unsigned char kVectorUnit1[32] = {
+    0xbb, 0xf9, 0xd6, 0xa7, 0x4a, 0x74, 0x65, 0xfe, 0xe2, 0x0f, 0x42, 0xad, 0xf9, 0xa6, 0x23, 0xfc,
+    0x95, 0x4f, 0x3b, 0x55, 0x58, 0x7e, 0x8e, 0x42, 0x9e, 0xec, 0x6f, 0x71, 0xe7, 0x38, 0xa3, 0x90,
+};
+
+class DmCrypt : public ::testing::Test {
+protected:
+    void SetUp() override {
+        if (access(DM_CONTROL_PATH, F_OK) != 0) {
+            GTEST_SKIP() << DM_CONTROL_PATH << " not available";
+        }
+        if (access(LOOP_CONTROL_PATH, F_OK) != 0) {
+            GTEST_SKIP() << LOOP_CONTROL_PATH << " not available";
+        }
+        backing_ = "/tmp/dm_crypt_" + std::to_string(getpid()) + ".img";
+        name_ = "dunit_crypt_" + std::to_string(getpid());
+        mapper_ = "/dev/mapper/" + name_;
+        ASSERT_EQ(0, blk_create_backing(backing_.c_str(), kBackingSize)) << strerror(errno);
+        ASSERT_EQ(0, loop_attach(backing_.c_str(), loop_, sizeof(loop_))) << strerror(errno);
+        attached_ = true;
+    }
+
+    void TearDown() override {
+        if (created_) {
+            dm_remove(name_.c_str());
+        }
+        if (attached_) {
+            loop_detach(loop_);
+        }
+        if (!backing_.empty()) {
+            unlink(backing_.c_str());
+        }
+    }
+
+    std::string Params(const std::string &key, const char *cipher = "aes-xts-plain64",
+                       int iv_offset = 0) {
+        return std::string(cipher) + " " + key + " " + std::to_string(iv_offset) + " " + loop_ +
+               " 0";
+    }
+
+    // 用给定的密钥打开加密设备，已经打开的先移除，这样读到的数据不会来自之前的块缓存
+    void Open(const std::string &key, int iv_offset = 0) {
+        if (created_) {
+            ASSERT_EQ(0, dm_remove(name_.c_str())) << strerror(errno);
+            created_ = false;
+        }
+        ASSERT_EQ(0, dm_create(name_.c_str())) << strerror(errno);
+        created_ = true;
+        ASSERT_EQ(0, dm_load(name_.c_str(), kLenSectors, "crypt",
+                             Params(key, "aes-xts-plain64", iv_offset).c_str(), 0))
+            << strerror(errno);
+        ASSERT_EQ(0, dm_resume(name_.c_str())) << strerror(errno);
+    }
+
+    void WriteMapper(const void *buf, size_t len, off_t off) {
+        int fd = open(mapper_.c_str(), O_RDWR);
+        ASSERT_GE(fd, 0) << strerror(errno);
+        ASSERT_EQ((ssize_t)len, pwrite(fd, buf, len, off)) << strerror(errno);
+        ASSERT_EQ(0, fsync(fd)) << strerror(errno);
+        close(fd);
+    }
+
+    std::vector<char> ReadMapper(size_t len, off_t off) {
+        std::vector<char> buf(len);
+        int fd = open(mapper_.c_str(), O_RDONLY);
+        EXPECT_GE(fd, 0) << strerror(errno);
+        EXPECT_EQ((ssize_t)len, pread(fd, buf.data(), len, off)) << strerror(errno);
+        close(fd);
+        return buf;
+    }
+
+    std::vector<char> ReadBacking(size_t len, off_t off) {
+        std::vector<char> buf(len);
+        int fd = open(backing_.c_str(), O_RDONLY);
+        EXPECT_GE(fd, 0) << strerror(errno);
+        EXPECT_EQ((ssize_t)len, pread(fd, buf.data(), len, off));
+        close(fd);
+        return buf;
+    }
+
+    std::string backing_;
+    std::string name_;
+    std::string mapper_;
+    char loop_[64] = {};
+    bool attached_ = false;
+    bool created_ = false;
+};
+
+TEST_F(DmCrypt, MatchesXtsKnownAnswer) {
+    // 每个扇区是一个数据单元，tweak是扇区号
+    std::vector<unsigned char> pt(1024);
+    for (size_t i = 0; i < pt.size(); i++) {
+        pt[i] = i % 256;
+    }
+    Open(kVectorKey);
+    WriteMapper(pt.data(), 512, 0);
+    WriteMapper(pt.data(), 512, 512);
+    std::vector<char> raw = ReadBacking(1024, 0);
+    EXPECT_EQ(0, memcmp(kVectorUnit0, raw.data(), sizeof(kVectorUnit0)));
+    EXPECT_EQ(0, memcmp(kVectorUnit1, raw.data() + 512, sizeof(kVectorUnit1)));
+
+    // IV偏移加到扇区号上
+    Open(kVectorKey, 1);
+    WriteMapper(pt.data(), 512, 0);
+    raw = ReadBacking(512, 0);
+    EXPECT_EQ(0, memcmp(kVectorUnit1, raw.data(), sizeof(kVectorUnit1)));
+}
+
+TEST_F(DmCrypt, DataIsEncryptedAtRestAndDecryptsWithTheSameKey) {
+    std::vector<char> data(kIoLen);
+    blk_fill_pattern(data.data(), kIoLen, 41);
+    Open(kKey);
+    WriteMapper(data.data(), kIoLen, 8192);
+
+    std::vector<char> raw = ReadBacking(kIoLen, 8192);
+    EXPECT_NE(data, raw);
+    // 不同扇区中相同的明文加密后不同
+    std::vector<char> zero(1024, 0);
+    WriteMapper(zero.data(), zero.size(), 0);
+    std::vector<char> sectors = ReadBacking(1024, 0);
+    EXPECT_NE(0, memcmp(sectors.data(), sectors.data() + 512, 512));
+
+    // 重新打开设备，读到的数据由下层设备解密得到
+    Open(kKey);
+    EXPECT_EQ(data, ReadMapper(kIoLen, 8192));
+
+    Open(kWrongKey);
+    EXPECT_NE(data, ReadMapper(kIoLen, 8192));
+}
+
+TEST_F(DmCrypt, TableStatusReportsKeyAndDevice) {
+    Open(kKey);
+
+    struct dm_buf buf;
+    struct dm_target_spec spec;
+    const char *params = nullptr;
+    ASSERT_EQ(0, dm_table(name_.c_str(), &buf, &spec, &params)) << strerror(errno);
+    ASSERT_EQ(1u, buf.io.target_count);
+    EXPECT_STREQ("crypt", spec.target_type);
+    EXPECT_EQ(kLenSectors, spec.length);
+
+    dev_t loop_dev = blk_devnum(loop_);
+    ASSERT_NE(0u, loop_dev);
+    std::string expected = "aes-xts-plain64 " + kKey + " 0 " + std::to_string(major(loop_dev)) +
+                           ":" + std::to_string(minor(loop_dev)) + " 0";
+    EXPECT_STREQ(expected.c_str(), params);
+}
+
+TEST_F(DmCrypt, LoadRejectsBadParameters) {
+    ASSERT_EQ(0, dm_create(name_.c_str())) << strerror(errno);
+    created_ = true;
+
+    // 不支持的加密方式
+    errno = 0;
+    EXPECT_EQ(-1, dm_load(name_.c_str(), kLenSectors, "crypt",
+                          Params(kKey, "aes-cbc-essiv:sha256").c_str(), 0));
+    EXPECT_EQ(EINVAL, errno);
+
+    // 密钥不是合法的十六进制，或者长度不是XTS-AES-128/256的密钥长度
+    errno = 0;
+    EXPECT_EQ(-1, dm_load(name_.c_str(), kLenSectors, "crypt", Params(kKey + "0").c_str(), 0));
+    EXPECT_EQ(EINVAL, errno);
+    errno = 0;
+    EXPECT_EQ(-1, dm_load(name_.c_str(), kLenSectors, "crypt",
+                          Params(std::string(64, 'z')).c_str(), 0));
+    EXPECT_EQ(EINVAL, errno);
+    errno = 0;
+    EXPECT_EQ(-1,
+              dm_load(name_.c_str(), kLenSectors, "crypt", Params(kKey.substr(0, 32)).c_str(), 0));
+    EXPECT_EQ(EINVAL, errno);
+    // 两半相同的弱密钥
+    errno = 0;
+    EXPECT_EQ(-1, dm_load(name_.c_str(), kLenSectors, "crypt",
+                          Params(std::string(64, '0')).c_str(), 0));
+    EXPECT_EQ(EINVAL, errno);
+}
+
+}  // namespace
@@ -0,0 +1,193 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+#include <unistd.h>
+
+#include <string>
+#include <vector>
+
+#include "blkdev_common.h"
+#include "dm_common.h"
+
+namespace {
+
+constexpr off_t kBackingSize = 2 * 1024 * 1024;
+// 映射表把dm设备的1M映射到loop设备从512K开始的位置
+constexpr uint64_t kStartSector = 1024;
+constexpr uint64_t kLenSectors = 2048;
+constexpr size_t kIoLen = 64 * 1024;
+
+class DmLinear : public ::testing::Test {
+protected:
+    void SetUp() override {
+        if (access(DM_CONTROL_PATH, F_OK) != 0) {
+            GTEST_SKIP() << DM_CONTROL_PATH << " not available";
+        }
+        if (access(LOOP_CONTROL_PATH, F_OK) != 0) {
+            GTEST_SKIP() << LOOP_CONTROL_PATH << " not available";
+        }
+        backing_ = "/tmp/dm_linear_" + std::to_string(getpid()) + ".img";
+        name_ = "dunit_linear_" + std::to_string(getpid());
+        mapper_ = "/dev/mapper/" + name_;
+        ASSERT_EQ(0, blk_create_backing(backing_.c_str(), kBackingSize)) << strerror(errno);
+        ASSERT_EQ(0, loop_attach(backing_.c_str(), loop_, sizeof(loop_))) << strerror(errno);
+        attached_ = true;
+    }
+
+    void TearDown() override {
+        if (created_) {
+            dm_remove(name_.c_str());
+        }
+        if (attached_) {
+            loop_detach(loop_);
+        }
+        if (!backing_.empty()) {
+            unlink(backing_.c_str());
+        }
+    }
+
+    void CreateAndActivate() {
+        ASSERT_EQ(0, dm_create(name_.c_str())) << strerror(errno);
+        created_ = true;
+        std::string params = std::string(loop_) + " " + std::to_string(kStartSector);
+        ASSERT_EQ(0, dm_load(name_.c_str(), kLenSectors, "linear", params.c_str(), 0))
+            << strerror(errno);
+        ASSERT_EQ(0, dm_resume(name_.c_str())) << strerror(errno);
+    }
+
+    std::string backing_;
+    std::string name_;
+    std::string mapper_;
+    char loop_[64] = {};
+    bool attached_ = false;
+    bool created_ = false;
+};
+
+TEST_F(DmLinear, TableLoadAndResumeSwapTables) {
+    ASSERT_EQ(0, dm_create(name_.c_str())) << strerror(errno);
+    created_ = true;
+
+    struct stat st;
+    ASSERT_EQ(0, stat(mapper_.c_str(), &st)) << strerror(errno);
+    EXPECT_TRUE(S_ISBLK(st.st_mode));
+
+    std::string params = std::string(loop_) + " " + std::to_string(kStartSector);
+    ASSERT_EQ(0, dm_load(name_.c_str(), kLenSectors, "linear", params.c_str(), 0))
+        << strerror(errno);
+
+    // 加载的映射表在恢复设备之前是inactive的
+    struct dm_buf buf;
+    dm_init(&buf, name_.c_str());
+    ASSERT_EQ(0, dm_cmd(DM_DEV_STATUS, &buf)) << strerror(errno);
+    EXPECT_EQ(0u, buf.io.flags & DM_ACTIVE_PRESENT_FLAG);
+    EXPECT_NE(0u, buf.io.flags & DM_INACTIVE_PRESENT_FLAG);
+    EXPECT_EQ(st.st_rdev, (dev_t)buf.io.dev);
+
+    ASSERT_EQ(0, dm_resume(name_.c_str())) << strerror(errno);
+    dm_init(&buf, name_.c_str());
+    ASSERT_EQ(0, dm_cmd(DM_DEV_STATUS, &buf)) << strerror(errno);
+    EXPECT_NE(0u, buf.io.flags & DM_ACTIVE_PRESENT_FLAG);
+    EXPECT_EQ(0u, buf.io.flags & DM_INACTIVE_PRESENT_FLAG);
+    EXPECT_EQ(0u, buf.io.flags & DM_SUSPEND_FLAG);
+}
+
+TEST_F(DmLinear, TableStatusReportsLoadedTarget) {
+    CreateAndActivate();
+
+    struct dm_buf buf;
+    struct dm_target_spec spec;
+    const char *params = nullptr;
+    ASSERT_EQ(0, dm_table(name_.c_str(), &buf, &spec, &params)) << strerror(errno);
+    ASSERT_EQ(1u, buf.io.target_count);
+    EXPECT_STREQ("linear", spec.target_type);
+    EXPECT_EQ(0u, spec.sector_start);
+    EXPECT_EQ(kLenSectors, spec.length);
+
+    // 下层设备以major:minor的形式输出
+    dev_t loop_dev = blk_devnum(loop_);
+    ASSERT_NE(0u, loop_dev);
+    std::string expected = std::to_string(major(loop_dev)) + ":" +
+                           std::to_string(minor(loop_dev)) + " " + std::to_string(kStartSector);
+    EXPECT_STREQ(expected.c_str(), params);
+
+    int fd = open(mapper_.c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    EXPECT_EQ((off_t)(kLenSectors * 512), lseek(fd, 0, SEEK_END));
+    close(fd);
+}
+
+TEST_F(DmLinear, WritesLandAtTheMappedOffset) {
+    CreateAndActivate();
+
+    std::vector<char> data(kIoLen), back(kIoLen);
+    blk_fill_pattern(data.data(), kIoLen, 1);
+
+    int fd = open(mapper_.c_str(), O_RDWR);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)kIoLen, pwrite(fd, data.data(), kIoLen, 4096)) << strerror(errno);
+    ASSERT_EQ(0, fsync(fd)) << strerror(errno);
+    close(fd);
+
+    int file = open(backing_.c_str(), O_RDONLY);
+    ASSERT_GE(file, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)kIoLen, pread(file, back.data(), kIoLen, kStartSector * 512 + 4096));
+    EXPECT_EQ(0, memcmp(data.data(), back.data(), kIoLen));
+    // 映射范围之前的数据没有被改动
+    ASSERT_EQ((ssize_t)kIoLen, pread(file, back.data(), kIoLen, kStartSector * 512 - kIoLen));
+    EXPECT_EQ(std::vector<char>(kIoLen, 0), back);
+    close(file);
+}
+
+TEST_F(DmLinear, ReadsComeFromTheMappedOffset) {
+    std::vector<char> data(kIoLen), back(kIoLen);
+    blk_fill_pattern(data.data(), kIoLen, 2);
+
+    int file = open(backing_.c_str(), O_RDWR);
+    ASSERT_GE(file, 0) << strerror(errno);
+    off_t last = (kStartSector + kLenSectors) * 512 - kIoLen;
+    ASSERT_EQ((ssize_t)kIoLen, pwrite(file, data.data(), kIoLen, last));
+    ASSERT_EQ(0, fsync(file));
+    close(file);
+
+    CreateAndActivate();
+    int fd = open(mapper_.c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)kIoLen, pread(fd, back.data(), kIoLen, kLenSectors * 512 - kIoLen))
+        << strerror(errno);
+    EXPECT_EQ(0, memcmp(data.data(), back.data(), kIoLen));
+    close(fd);
+}
+
+TEST_F(DmLinear, LoadRejectsTargetBeyondDevice) {
+    ASSERT_EQ(0, dm_create(name_.c_str())) << strerror(errno);
+    created_ = true;
+
+    // 从512K开始映射2M会超出2M的loop设备
+    std::string params = std::string(loop_) + " " + std::to_string(kStartSector);
+    errno = 0;
+    EXPECT_EQ(-1, dm_load(name_.c_str(), kLenSectors * 2, "linear", params.c_str(), 0));
+    EXPECT_EQ(EINVAL, errno);
+
+    errno = 0;
+    EXPECT_EQ(-1, dm_load(name_.c_str(), kLenSectors, "linear", "/dev/nonexistent 0", 0));
+    EXPECT_EQ(ENODEV, errno);
+}
+
+TEST_F(DmLinear, RemoveDropsTheMapperNode) {
+    CreateAndActivate();
+    ASSERT_EQ(0, dm_remove(name_.c_str())) << strerror(errno);
+    created_ = false;
+
+    EXPECT_NE(0, access(mapper_.c_str(), F_OK));
+    struct dm_buf buf;
+    dm_init(&buf, name_.c_str());
+    errno = 0;
+    EXPECT_EQ(-1, dm_cmd(DM_DEV_STATUS, &buf));
+    EXPECT_EQ(ENXIO, errno);
+}
+
+}  // namespace
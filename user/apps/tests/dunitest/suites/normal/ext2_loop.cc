@@ -0,0 +1,280 @@
+#include <gtest/gtest.h>
+
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/statfs.h>
+#include <unistd.h>
+
+#include <string>
+#include <vector>
+
+#include "blkdev_common.h"
+
+namespace {
+
+// 1K的块，只有一个块组：
+// 块1是超级块，块2是块组描述符，块3、4是块位图和inode位图，块5~12是inode表，块13是根目录
+constexpr uint32_t kBlockSize = 1024;
+constexpr uint32_t kBlocks = 1024;
+constexpr uint32_t kInodes = 64;
+constexpr uint32_t kInodeSize = 128;
+constexpr uint32_t kBlockBitmap = 3;
+constexpr uint32_t kInodeBitmap = 4;
+constexpr uint32_t kInodeTable = 5;
+constexpr uint32_t kRootBlock = kInodeTable + kInodes * kInodeSize / kBlockSize;
+constexpr uint32_t kFirstIno = 11;
+constexpr uint32_t kFreeBlocks = kBlocks - 1 - kRootBlock;
+constexpr uint32_t kFreeInodes = kInodes - (kFirstIno - 1);
+
+// 超级块中的偏移
+constexpr size_t kSbState = 58;
+
+void Put16(std::vector<uint8_t> &img, size_t off, uint16_t v) {
+    img[off] = v & 0xff;
+    img[off + 1] = v >> 8;
+}
+
+void Put32(std::vector<uint8_t> &img, size_t off, uint32_t v) {
+    for (int i = 0; i < 4; i++) {
+        img[off + i] = (v >> (8 * i)) & 0xff;
+    }
+}
+
+// 位图中前used位和nbits之后的位都置1
+void FillBitmap(std::vector<uint8_t> &img, uint32_t blk, uint32_t used, uint32_t nbits) {
+    uint8_t *p = &img[blk * kBlockSize];
+    for (uint32_t bit = 0; bit < kBlockSize * 8; bit++) {
+        if (bit < used || bit >= nbits) {
+            p[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+}
+
+size_t DirEntry(std::vector<uint8_t> &img, size_t off, uint32_t ino, uint16_t rec_len,
+                const char *name) {
+    Put32(img, off, ino);
+    Put16(img, off + 4, rec_len);
+    img[off + 6] = strlen(name);
+    img[off + 7] = 2;  // EXT2_FT_DIR
+    memcpy(&img[off + 8], name, strlen(name));
+    return off + rec_len;
+}
+
+// 按mke2fs -t ext2 -b 1024 -O none,filetype的布局生成一个空的文件系统
+std::vector<uint8_t> BuildImage() {
+    std::vector<uint8_t> img(kBlocks * kBlockSize);
+
+    size_t sb = 1024;
+    Put32(img, sb + 0, kInodes);
+    Put32(img, sb + 4, kBlocks);
+    Put32(img, sb + 12, kFreeBlocks);
+    Put32(img, sb + 16, kFreeInodes);
+    Put32(img, sb + 20, 1);     // s_first_data_block
+    Put32(img, sb + 24, 0);     // s_log_block_size
+    Put32(img, sb + 28, 0);     // s_log_frag_size
+    Put32(img, sb + 32, 8192);  // s_blocks_per_group
+    Put32(img, sb + 36, 8192);  // s_frags_per_group
+    Put32(img, sb + 40, kInodes);
+    Put16(img, sb + 54, 0xffff);  // s_max_mnt_count
+    Put16(img, sb + 56, 0xef53);
+    Put16(img, sb + kSbState, 1);  // EXT2_VALID_FS
+    Put16(img, sb + 60, 1);        // s_errors: continue
+    Put32(img, sb + 76, 1);        // s_rev_level: dynamic
+    Put32(img, sb + 84, kFirstIno);
+    Put16(img, sb + 88, kInodeSize);
+    Put32(img, sb + 96, 0x0002);  // INCOMPAT_FILETYPE
+    memcpy(&img[sb + 120], "dunitest", 8);
+
+    size_t gd = 2 * kBlockSize;
+    Put32(img, gd + 0, kBlockBitmap);
+    Put32(img, gd + 4, kInodeBitmap);
+    Put32(img, gd + 8, kInodeTable);
+    Put16(img, gd + 12, kFreeBlocks);
+    Put16(img, gd + 14, kFreeInodes);
+    Put16(img, gd + 16, 1);  // bg_used_dirs_count
+
+    // 位图的第i位对应块first_data_block + i
+    FillBitmap(img, kBlockBitmap, kRootBlock, kBlocks - 1);
+    FillBitmap(img, kInodeBitmap, kFirstIno - 1, kInodes);
+
+    // 根目录是2号inode
+    size_t root = kInodeTable * kBlockSize + (2 - 1) * kInodeSize;
+    Put16(img, root + 0, 040755);
+    Put32(img, root + 4, kBlockSize);
+    Put16(img, root + 26, 2);                // i_links_count
+    Put32(img, root + 28, kBlockSize / 512);  // i_blocks
+    Put32(img, root + 40, kRootBlock);
+
+    size_t off = DirEntry(img, kRootBlock * kBlockSize, 2, 12, ".");
+    DirEntry(img, off, 2, kBlockSize - 12, "..");
+    return img;
+}
+
+class Ext2Loop : public ::testing::Test {
+protected:
+    void SetUp() override {
+        if (access(LOOP_CONTROL_PATH, F_OK) != 0) {
+            GTEST_SKIP() << LOOP_CONTROL_PATH << " not available";
+        }
+        std::string tag = std::to_string(getpid());
+        image_ = "/tmp/ext2_loop_" + tag + ".img";
+        mnt_ = "/tmp/ext2_loop_mnt_" + tag;
+
+        std::vector<uint8_t> img = BuildImage();
+        int fd = open(image_.c_str(), O_RDWR | O_CREAT | O_TRUNC, 0644);
+        ASSERT_GE(fd, 0) << strerror(errno);
+        ASSERT_EQ((ssize_t)img.size(), write(fd, img.data(), img.size()));
+        close(fd);
+
+        ASSERT_EQ(0, loop_attach(image_.c_str(), loop_, sizeof(loop_))) << strerror(errno);
+        attached_ = true;
+        ASSERT_EQ(0, mkdir(mnt_.c_str(), 0755)) << strerror(errno);
+        Mount();
+    }
+
+    void TearDown() override {
+        if (mounted_) {
+            umount(mnt_.c_str());
+        }
+        if (!mnt_.empty()) {
+            rmdir(mnt_.c_str());
+        }
+        if (attached_) {
+            loop_detach(loop_);
+        }
+        if (!image_.empty()) {
+            unlink(image_.c_str());
+        }
+    }
+
+    void Mount() {
+        ASSERT_EQ(0, mount(loop_, mnt_.c_str(), "ext2", 0, nullptr)) << strerror(errno);
+        mounted_ = true;
+    }
+
+    void Remount() {
+        ASSERT_EQ(0, umount(mnt_.c_str())) << strerror(errno);
+        mounted_ = false;
+        Mount();
+    }
+
+    std::string Path(const char *name) { return mnt_ + "/" + name; }
+
+    std::string image_;
+    std::string mnt_;
+    char loop_[64] = {};
+    bool attached_ = false;
+    bool mounted_ = false;
+};
+
+TEST_F(Ext2Loop, MountsHandBuiltImage) {
+    struct statfs sfs;
+    ASSERT_EQ(0, statfs(mnt_.c_str(), &sfs)) << strerror(errno);
+    EXPECT_EQ(0xef53, sfs.f_type);
+    EXPECT_EQ(kBlockSize, (uint32_t)sfs.f_bsize);
+    EXPECT_EQ(kFreeBlocks, sfs.f_bfree);
+    EXPECT_EQ(kFreeInodes, sfs.f_ffree);
+
+    DIR *dir = opendir(mnt_.c_str());
+    ASSERT_NE(nullptr, dir) << strerror(errno);
+    int entries = 0;
+    while (struct dirent *de = readdir(dir)) {
+        EXPECT_TRUE(strcmp(de->d_name, ".") == 0 || strcmp(de->d_name, "..") == 0)
+            << de->d_name;
+        entries++;
+    }
+    closedir(dir);
+    EXPECT_EQ(2, entries);
+}
+
+TEST_F(Ext2Loop, CreateReadUnlink) {
+    // 超过12个直接块，用到一次间接块
+    std::vector<char> data(40 * 1024 + 123);
+    blk_fill_pattern(data.data(), data.size(), 51);
+
+    int fd = open(Path("file").c_str(), O_RDWR | O_CREAT | O_EXCL, 0640);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)data.size(), write(fd, data.data(), data.size())) << strerror(errno);
+    ASSERT_EQ(0, fsync(fd)) << strerror(errno);
+    close(fd);
+
+    struct stat st;
+    ASSERT_EQ(0, stat(Path("file").c_str(), &st)) << strerror(errno);
+    EXPECT_TRUE(S_ISREG(st.st_mode));
+    EXPECT_EQ(0640u, st.st_mode & 07777);
+    EXPECT_EQ((off_t)data.size(), st.st_size);
+    EXPECT_EQ(1u, st.st_nlink);
+
+    std::vector<char> back(data.size());
+    fd = open(Path("file").c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)back.size(), read(fd, back.data(), back.size())) << strerror(errno);
+    close(fd);
+    EXPECT_EQ(data, back);
+
+    ASSERT_EQ(0, unlink(Path("file").c_str())) << strerror(errno);
+    errno = 0;
+    EXPECT_NE(0, access(Path("file").c_str(), F_OK));
+    EXPECT_EQ(ENOENT, errno);
+}
+
+TEST_F(Ext2Loop, DataSurvivesRemount) {
+    std::vector<char> data(10000);
+    blk_fill_pattern(data.data(), data.size(), 52);
+
+    ASSERT_EQ(0, mkdir(Path("dir").c_str(), 0755)) << strerror(errno);
+    int fd = open(Path("dir/file").c_str(), O_WRONLY | O_CREAT, 0644);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)data.size(), write(fd, data.data(), data.size())) << strerror(errno);
+    close(fd);
+
+    Remount();
+
+    struct stat st;
+    ASSERT_EQ(0, stat(Path("dir").c_str(), &st)) << strerror(errno);
+    EXPECT_TRUE(S_ISDIR(st.st_mode));
+    EXPECT_EQ(2u, st.st_nlink);
+    std::vector<char> back(data.size());
+    fd = open(Path("dir/file").c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)back.size(), read(fd, back.data(), back.size())) << strerror(errno);
+    close(fd);
+    EXPECT_EQ(data, back);
+}
+
+TEST_F(Ext2Loop, UnlinkReleasesSpaceAndUmountMarksClean) {
+    std::vector<char> data(64 * 1024);
+    blk_fill_pattern(data.data(), data.size(), 53);
+    int fd = open(Path("big").c_str(), O_WRONLY | O_CREAT, 0644);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)data.size(), write(fd, data.data(), data.size())) << strerror(errno);
+    close(fd);
+
+    struct statfs sfs;
+    ASSERT_EQ(0, statfs(mnt_.c_str(), &sfs)) << strerror(errno);
+    EXPECT_LE(sfs.f_bfree, kFreeBlocks - data.size() / kBlockSize);
+    EXPECT_EQ(kFreeInodes - 1, sfs.f_ffree);
+
+    ASSERT_EQ(0, unlink(Path("big").c_str())) << strerror(errno);
+    Remount();
+    ASSERT_EQ(0, statfs(mnt_.c_str(), &sfs)) << strerror(errno);
+    EXPECT_EQ(kFreeBlocks, sfs.f_bfree);
+    EXPECT_EQ(kFreeInodes, sfs.f_ffree);
+
+    // 正常卸载时恢复“干净”标记
+    ASSERT_EQ(0, umount(mnt_.c_str())) << strerror(errno);
+    mounted_ = false;
+    uint16_t state = 0;
+    int img = open(image_.c_str(), O_RDONLY);
+    ASSERT_GE(img, 0) << strerror(errno);
+    ASSERT_EQ(2, pread(img, &state, 2, 1024 + kSbState));
+    close(img);
+    EXPECT_EQ(1, state & 1);
+}
+
+}  // namespace
@@ -0,0 +1,208 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/inotify.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#include <string>
+#include <vector>
+
+namespace {
+
+const char *kDir = "/tmp/test_inotify";
+
+struct Event {
+    int wd;
+    uint32_t mask;
+    uint32_t cookie;
+    std::string name;
+};
+
+static std::vector<Event> read_events(int fd) {
+    std::vector<Event> events;
+    alignas(struct inotify_event) char buf[4096];
+    ssize_t n = read(fd, buf, sizeof(buf));
+    if (n <= 0) {
+        return events;
+    }
+    for (char *p = buf; p < buf + n;) {
+        struct inotify_event *ev = reinterpret_cast<struct inotify_event *>(p);
+        events.push_back({ev->wd, ev->mask, ev->cookie, ev->len ? std::string(ev->name) : ""});
+        p += sizeof(struct inotify_event) + ev->len;
+    }
+    return events;
+}
+
+static std::string path_of(const char *name) {
+    return std::string(kDir) + "/" + name;
+}
+
+class Inotify : public ::testing::Test {
+  protected:
+    void SetUp() override {
+        mkdir(kDir, 0755);
+        fd_ = inotify_init1(IN_NONBLOCK | IN_CLOEXEC);
+        ASSERT_GE(fd_, 0) << strerror(errno);
+    }
+
+    void TearDown() override {
+        if (fd_ >= 0) {
+            close(fd_);
+        }
+        unlink(path_of("a").c_str());
+        unlink(path_of("b").c_str());
+        rmdir(path_of("sub").c_str());
+        rmdir(kDir);
+    }
+
+    int fd_ = -1;
+};
+
+}  // namespace
+
+TEST_F(Inotify, EmptyQueueIsNonblocking) {
+    char buf[256];
+    EXPECT_EQ(-1, read(fd_, buf, sizeof(buf)));
+    EXPECT_EQ(EAGAIN, errno);
+}
+
+TEST_F(Inotify, InvalidArguments) {
+    EXPECT_EQ(-1, inotify_init1(0x1));
+    EXPECT_EQ(EINVAL, errno);
+    EXPECT_EQ(-1, inotify_add_watch(fd_, kDir, 0));
+    EXPECT_EQ(EINVAL, errno);
+    EXPECT_EQ(-1, inotify_rm_watch(fd_, 12345));
+    EXPECT_EQ(EINVAL, errno);
+    int dirfd = open(kDir, O_RDONLY | O_DIRECTORY);
+    ASSERT_GE(dirfd, 0);
+    EXPECT_EQ(-1, inotify_add_watch(dirfd, kDir, IN_ALL_EVENTS));
+    EXPECT_EQ(EINVAL, errno);
+    close(dirfd);
+    EXPECT_EQ(-1, inotify_add_watch(fd_, "/tmp/test_inotify/missing", IN_ALL_EVENTS));
+    EXPECT_EQ(ENOENT, errno);
+}
+
+TEST_F(Inotify, SameInodeReturnsSameWd) {
+    int wd = inotify_add_watch(fd_, kDir, IN_CREATE);
+    ASSERT_GT(wd, 0);
+    EXPECT_EQ(wd, inotify_add_watch(fd_, kDir, IN_DELETE));
+    EXPECT_EQ(-1, inotify_add_watch(fd_, kDir, IN_DELETE | IN_MASK_CREATE));
+    EXPECT_EQ(EEXIST, errno);
+}
+
+TEST_F(Inotify, CreateModifyDelete) {
+    int wd = inotify_add_watch(fd_, kDir, IN_CREATE | IN_MODIFY | IN_DELETE);
+    ASSERT_GT(wd, 0);
+
+    int fd = open(path_of("a").c_str(), O_CREAT | O_WRONLY, 0644);
+    ASSERT_GE(fd, 0);
+    ASSERT_EQ(5, write(fd, "hello", 5));
+    close(fd);
+    ASSERT_EQ(0, mkdir(path_of("sub").c_str(), 0755));
+    ASSERT_EQ(0, unlink(path_of("a").c_str()));
+
+    std::vector<Event> events = read_events(fd_);
+    ASSERT_EQ(4u, events.size());
+    EXPECT_EQ((uint32_t)IN_CREATE, events[0].mask);
+    EXPECT_EQ("a", events[0].name);
+    EXPECT_EQ((uint32_t)IN_MODIFY, events[1].mask);
+    EXPECT_EQ("a", events[1].name);
+    EXPECT_EQ((uint32_t)(IN_CREATE | IN_ISDIR), events[2].mask);
+    EXPECT_EQ("sub", events[2].name);
+    EXPECT_EQ((uint32_t)IN_DELETE, events[3].mask);
+    EXPECT_EQ("a", events[3].name);
+    for (const Event &ev : events) {
+        EXPECT_EQ(wd, ev.wd);
+    }
+}
+
+TEST_F(Inotify, RenameHasMatchingCookies) {
+    int fd = open(path_of("a").c_str(), O_CREAT | O_WRONLY, 0644);
+    ASSERT_GE(fd, 0);
+    close(fd);
+
+    int dir_wd = inotify_add_watch(fd_, kDir, IN_MOVE);
+    int file_wd = inotify_add_watch(fd_, path_of("a").c_str(), IN_MOVE_SELF);
+    ASSERT_GT(dir_wd, 0);
+    ASSERT_GT(file_wd, 0);
+    ASSERT_EQ(0, rename(path_of("a").c_str(), path_of("b").c_str()));
+
+    std::vector<Event> events = read_events(fd_);
+    ASSERT_EQ(3u, events.size());
+    EXPECT_EQ((uint32_t)IN_MOVED_FROM, events[0].mask);
+    EXPECT_EQ("a", events[0].name);
+    EXPECT_EQ((uint32_t)IN_MOVED_TO, events[1].mask);
+    EXPECT_EQ("b", events[1].name);
+    EXPECT_EQ(events[0].cookie, events[1].cookie);
+    EXPECT_EQ(file_wd, events[2].wd);
+    EXPECT_EQ((uint32_t)IN_MOVE_SELF, events[2].mask);
+}
+
+TEST_F(Inotify, DeleteSelfRemovesWatch) {
+    ASSERT_EQ(0, mkdir(path_of("sub").c_str(), 0755));
+    int wd = inotify_add_watch(fd_, path_of("sub").c_str(), IN_DELETE_SELF);
+    ASSERT_GT(wd, 0);
+    ASSERT_EQ(0, rmdir(path_of("sub").c_str()));
+
+    std::vector<Event> events = read_events(fd_);
+    ASSERT_EQ(2u, events.size());
+    EXPECT_EQ((uint32_t)IN_DELETE_SELF, events[0].mask);
+    EXPECT_EQ((uint32_t)IN_IGNORED, events[1].mask);
+    EXPECT_EQ(-1, inotify_rm_watch(fd_, wd));
+    EXPECT_EQ(EINVAL, errno);
+}
+
+TEST_F(Inotify, RmWatchQueuesIgnored) {
+    int wd = inotify_add_watch(fd_, kDir, IN_CREATE);
+    ASSERT_GT(wd, 0);
+    ASSERT_EQ(0, inotify_rm_watch(fd_, wd));
+
+    int fd = open(path_of("a").c_str(), O_CREAT | O_WRONLY, 0644);
+    ASSERT_GE(fd, 0);
+    close(fd);
+
+    std::vector<Event> events = read_events(fd_);
+    ASSERT_EQ(1u, events.size());
+    EXPECT_EQ(wd, events[0].wd);
+    EXPECT_EQ((uint32_t)IN_IGNORED, events[0].mask);
+}
+
+TEST_F(Inotify, OneshotAndPollAndFionread) {
+    int wd = inotify_add_watch(fd_, kDir, IN_CREATE | IN_ONESHOT);
+    ASSERT_GT(wd, 0);
+
+    struct pollfd pfd = {fd_, POLLIN, 0};
+    EXPECT_EQ(0, poll(&pfd, 1, 0));
+
+    int fd = open(path_of("a").c_str(), O_CREAT | O_WRONLY, 0644);
+    ASSERT_GE(fd, 0);
+    close(fd);
+    fd = open(path_of("b").c_str(), O_CREAT | O_WRONLY, 0644);
+    ASSERT_GE(fd, 0);
+    close(fd);
+
+    EXPECT_EQ(1, poll(&pfd, 1, 0));
+    EXPECT_TRUE(pfd.revents & POLLIN);
+
+    int avail = 0;
+    ASSERT_EQ(0, ioctl(fd_, FIONREAD, &avail));
+    // IN_CREATE("a")：16字节头部 + 16字节名字；IN_IGNORED：16字节头部
+    EXPECT_EQ(48, avail);
+
+    // 缓冲区放不下第一个事件
+    char small[8];
+    EXPECT_EQ(-1, read(fd_, small, sizeof(small)));
+    EXPECT_EQ(EINVAL, errno);
+
+    std::vector<Event> events = read_events(fd_);
+    ASSERT_EQ(2u, events.size());
+    EXPECT_EQ((uint32_t)IN_CREATE, events[0].mask);
+    EXPECT_EQ("a", events[0].name);
+    EXPECT_EQ((uint32_t)IN_IGNORED, events[1].mask);
+}
@@ -0,0 +1,154 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <sys/klog.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+#include <atomic>
+#include <string>
+#include <vector>
+
+namespace {
+
+const char *kLogRing = "/dev/logring";
+const uint32_t kMagic = 0x4c4f4752;
+const size_t kAlign = 8;
+const uint8_t kRecPad = 1;
+
+struct LogRingHeader {
+    uint32_t magic;
+    uint32_t version;
+    uint32_t data_offset;
+    uint32_t data_size;
+    std::atomic<uint64_t> head;
+    std::atomic<uint64_t> tail;
+    std::atomic<uint64_t> dropped;
+};
+
+struct LogRingRecord {
+    uint16_t len;
+    uint8_t level;
+    uint8_t flags;
+};
+
+class LogRing : public ::testing::Test {
+protected:
+    void SetUp() override {
+        fd_ = open(kLogRing, O_RDWR);
+        ASSERT_GE(fd_, 0) << strerror(errno);
+        size_ = sysconf(_SC_PAGESIZE) + 64 * 1024;
+        void *p = mmap(nullptr, size_, PROT_READ | PROT_WRITE, MAP_SHARED, fd_, 0);
+        ASSERT_NE(MAP_FAILED, p) << strerror(errno);
+        base_ = static_cast<uint8_t *>(p);
+        header_ = reinterpret_cast<LogRingHeader *>(base_);
+    }
+
+    void TearDown() override {
+        if (base_ != nullptr) {
+            munmap(base_, size_);
+        }
+        if (fd_ >= 0) {
+            close(fd_);
+        }
+    }
+
+    // 按/dev/logring的格式追加一条记录，空间不够时返回false
+    bool push(const std::string &text, uint8_t level) {
+        uint64_t size = header_->data_size;
+        uint8_t *data = base_ + header_->data_offset;
+        size_t total = (sizeof(LogRingRecord) + text.size() + kAlign - 1) / kAlign * kAlign;
+        uint64_t head = header_->head.load(std::memory_order_relaxed);
+        uint64_t tail = header_->tail.load(std::memory_order_acquire);
+        size_t off = head % size;
+        size_t pad = size - off < total ? size - off : 0;
+        if (size - (head - tail) < pad + total) {
+            return false;
+        }
+        if (pad != 0) {
+            LogRingRecord rec = {0, 0, kRecPad};
+            memcpy(data + off, &rec, sizeof(rec));
+            head += pad;
+            off = 0;
+        }
+        LogRingRecord rec = {static_cast<uint16_t>(text.size()), level, 0};
+        memcpy(data + off, &rec, sizeof(rec));
+        memcpy(data + off + sizeof(rec), text.data(), text.size());
+        header_->head.store(head + total, std::memory_order_release);
+        return true;
+    }
+
+    int fd_ = -1;
+    size_t size_ = 0;
+    uint8_t *base_ = nullptr;
+    LogRingHeader *header_ = nullptr;
+};
+
+static std::string read_kmsg() {
+    int len = klogctl(10 /* SYSLOG_ACTION_SIZE_BUFFER */, nullptr, 0);
+    if (len <= 0) {
+        len = 1 << 20;
+    }
+    std::vector<char> buf(len);
+    int n = klogctl(3 /* SYSLOG_ACTION_READ_ALL */, buf.data(), len);
+    if (n < 0) {
+        return std::string();
+    }
+    return std::string(buf.data(), n);
+}
+
+}  // namespace
+
+TEST_F(LogRing, HeaderDescribesRing) {
+    EXPECT_EQ(kMagic, header_->magic);
+    EXPECT_EQ(1u, header_->version);
+    EXPECT_EQ(static_cast<uint32_t>(sysconf(_SC_PAGESIZE)), header_->data_offset);
+    EXPECT_EQ(64u * 1024, header_->data_size);
+}
+
+TEST_F(LogRing, RecordsReachKmsg) {
+    std::string tag = "logring-test-" + std::to_string(getpid());
+    for (int i = 0; i < 3; i++) {
+        ASSERT_TRUE(push(tag + "-" + std::to_string(i) + "\n", 6));
+    }
+    ASSERT_EQ(0, fsync(fd_)) << strerror(errno);
+    EXPECT_EQ(header_->head.load(), header_->tail.load());
+
+    std::string kmsg = read_kmsg();
+    for (int i = 0; i < 3; i++) {
+        EXPECT_NE(std::string::npos, kmsg.find(tag + "-" + std::to_string(i)))
+            << "missing record " << i;
+    }
+}
+
+TEST_F(LogRing, WrapsAroundDataArea) {
+    std::string line(1000, 'x');
+    // 写满几圈，每次写不下时先让内核取走
+    for (int i = 0; i < 300; i++) {
+        if (!push(line, 7)) {
+            ASSERT_EQ(0, fsync(fd_));
+            ASSERT_TRUE(push(line, 7));
+        }
+    }
+    ASSERT_EQ(0, fsync(fd_));
+    EXPECT_EQ(header_->head.load(), header_->tail.load());
+    EXPECT_EQ(0u, header_->dropped.load());
+}
+
+TEST_F(LogRing, RejectsMappingPastEnd) {
+    void *p = mmap(nullptr, size_ + sysconf(_SC_PAGESIZE), PROT_READ | PROT_WRITE, MAP_SHARED,
+                   fd_, 0);
+    EXPECT_EQ(MAP_FAILED, p);
+    EXPECT_EQ(EINVAL, errno);
+}
+
+TEST_F(LogRing, ReadWriteNotSupported) {
+    char buf[8];
+    EXPECT_EQ(-1, read(fd_, buf, sizeof(buf)));
+    EXPECT_EQ(EINVAL, errno);
+    EXPECT_EQ(-1, write(fd_, "x", 1));
+    EXPECT_EQ(EINVAL, errno);
+}
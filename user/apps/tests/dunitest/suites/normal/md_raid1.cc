@@ -0,0 +1,268 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <unistd.h>
+
+#include <fstream>
+#include <sstream>
+#include <string>
+#include <vector>
+
+#include "blkdev_common.h"
+
+namespace {
+
+// 与Linux的include/uapi/linux/raid/md_u.h、md_p.h一致
+struct mdu_array_info {
+    int32_t major_version;
+    int32_t minor_version;
+    int32_t patch_version;
+    uint32_t ctime;
+    int32_t level;
+    int32_t size;
+    int32_t nr_disks;
+    int32_t raid_disks;
+    int32_t md_minor;
+    int32_t not_persistent;
+    uint32_t utime;
+    int32_t state;
+    int32_t active_disks;
+    int32_t working_disks;
+    int32_t failed_disks;
+    int32_t spare_disks;
+    int32_t layout;
+    int32_t chunk_size;
+};
+
+struct mdu_disk_info {
+    int32_t number;
+    int32_t major;
+    int32_t minor;
+    int32_t raid_disk;
+    int32_t state;
+};
+
+struct mdu_param {
+    int32_t personality;
+    int32_t chunk_size;
+    int32_t max_fault;
+};
+
+#define MD_IOCTL 9
+#define GET_ARRAY_INFO _IOR(MD_IOCTL, 0x11, struct mdu_array_info)
+#define GET_DISK_INFO _IOR(MD_IOCTL, 0x12, struct mdu_disk_info)
+#define ADD_NEW_DISK _IOW(MD_IOCTL, 0x21, struct mdu_disk_info)
+#define HOT_REMOVE_DISK _IO(MD_IOCTL, 0x22)
+#define SET_ARRAY_INFO _IOW(MD_IOCTL, 0x23, struct mdu_array_info)
+#define SET_DISK_FAULTY _IO(MD_IOCTL, 0x29)
+#define RUN_ARRAY _IOW(MD_IOCTL, 0x30, struct mdu_param)
+#define STOP_ARRAY _IO(MD_IOCTL, 0x32)
+
+#define MD_SB_CLEAN 0
+#define MD_DISK_FAULTY 0
+#define MD_DISK_ACTIVE 1
+#define MD_DISK_SYNC 2
+#define MD_DISK_REMOVED 3
+
+constexpr int kDisks = 2;
+constexpr off_t kBackingSize = 1024 * 1024;
+constexpr size_t kIoLen = 64 * 1024;
+// SetUp在组建阵列之前直接向每个成员盘的这个位置写入相同的数据
+constexpr off_t kSeedOff = 512 * 1024;
+
+class MdRaid1 : public ::testing::Test {
+protected:
+    void SetUp() override {
+        if (access(LOOP_CONTROL_PATH, F_OK) != 0) {
+            GTEST_SKIP() << LOOP_CONTROL_PATH << " not available";
+        }
+        md_ = OpenFreeArray();
+        if (md_ < 0) {
+            GTEST_SKIP() << "no unused /dev/mdN";
+        }
+
+        seed_.resize(kIoLen);
+        blk_fill_pattern(seed_.data(), kIoLen, 7);
+        for (int i = 0; i < kDisks; i++) {
+            backing_[i] = "/tmp/md_raid1_" + std::to_string(getpid()) + "_" + std::to_string(i);
+            ASSERT_EQ(0, blk_create_backing(backing_[i].c_str(), kBackingSize)) << strerror(errno);
+            int fd = open(backing_[i].c_str(), O_RDWR);
+            ASSERT_GE(fd, 0) << strerror(errno);
+            ASSERT_EQ((ssize_t)kIoLen, pwrite(fd, seed_.data(), kIoLen, kSeedOff));
+            close(fd);
+            ASSERT_EQ(0, loop_attach(backing_[i].c_str(), loop_[i], sizeof(loop_[i])))
+                << strerror(errno);
+            attached_[i] = true;
+            dev_[i] = blk_devnum(loop_[i]);
+            ASSERT_NE(0u, dev_[i]);
+        }
+
+        // 没有superblock、数据已经一致的阵列，不需要同步
+        struct mdu_array_info info = {};
+        info.level = 1;
+        info.raid_disks = kDisks;
+        info.not_persistent = 1;
+        info.state = 1 << MD_SB_CLEAN;
+        ASSERT_EQ(0, ioctl(md_, SET_ARRAY_INFO, &info)) << strerror(errno);
+        configured_ = true;
+        for (int i = 0; i < kDisks; i++) {
+            struct mdu_disk_info disk = {};
+            disk.number = i;
+            disk.major = major(dev_[i]);
+            disk.minor = minor(dev_[i]);
+            disk.raid_disk = i;
+            disk.state = (1 << MD_DISK_ACTIVE) | (1 << MD_DISK_SYNC);
+            ASSERT_EQ(0, ioctl(md_, ADD_NEW_DISK, &disk)) << strerror(errno);
+        }
+        ASSERT_EQ(0, ioctl(md_, RUN_ARRAY, 0)) << strerror(errno);
+    }
+
+    void TearDown() override {
+        if (md_ >= 0) {
+            if (configured_) {
+                ioctl(md_, STOP_ARRAY, 0);
+            }
+            close(md_);
+        }
+        for (int i = 0; i < kDisks; i++) {
+            if (attached_[i]) {
+                loop_detach(loop_[i]);
+            }
+            if (!backing_[i].empty()) {
+                unlink(backing_[i].c_str());
+            }
+        }
+    }
+
+    // 打开一个还没有配置的md设备
+    int OpenFreeArray() {
+        for (int i = 0; i < 4; i++) {
+            std::string path = "/dev/md" + std::to_string(i);
+            int fd = open(path.c_str(), O_RDWR);
+            if (fd < 0) {
+                continue;
+            }
+            struct mdu_array_info info;
+            if (ioctl(fd, GET_ARRAY_INFO, &info) != 0 && errno == ENODEV) {
+                return fd;
+            }
+            close(fd);
+        }
+        return -1;
+    }
+
+    std::string ReadBacking(int i, off_t off, size_t len) {
+        std::string buf(len, '\0');
+        int fd = open(backing_[i].c_str(), O_RDONLY);
+        EXPECT_GE(fd, 0) << strerror(errno);
+        EXPECT_EQ((ssize_t)len, pread(fd, &buf[0], len, off));
+        close(fd);
+        return buf;
+    }
+
+    std::string MdStat() {
+        std::ifstream f("/proc/mdstat");
+        std::stringstream ss;
+        ss << f.rdbuf();
+        return ss.str();
+    }
+
+    int md_ = -1;
+    bool configured_ = false;
+    std::string backing_[kDisks];
+    char loop_[kDisks][64] = {};
+    bool attached_[kDisks] = {};
+    dev_t dev_[kDisks] = {};
+    std::vector<char> seed_;
+};
+
+TEST_F(MdRaid1, ArrayInfoReportsBothMirrors) {
+    struct mdu_array_info info = {};
+    ASSERT_EQ(0, ioctl(md_, GET_ARRAY_INFO, &info)) << strerror(errno);
+    EXPECT_EQ(1, info.level);
+    EXPECT_EQ(kDisks, info.raid_disks);
+    EXPECT_EQ(kDisks, info.nr_disks);
+    EXPECT_EQ(kDisks, info.active_disks);
+    EXPECT_EQ(kDisks, info.working_disks);
+    EXPECT_EQ(0, info.failed_disks);
+    EXPECT_NE(0, info.state & (1 << MD_SB_CLEAN));
+    EXPECT_EQ(kBackingSize / 1024, info.size);
+
+    EXPECT_EQ(kBackingSize, lseek(md_, 0, SEEK_END));
+    EXPECT_NE(std::string::npos, MdStat().find("[2/2] [UU]"));
+}
+
+TEST_F(MdRaid1, WritesReachEveryMirror) {
+    std::vector<char> data(kIoLen);
+    blk_fill_pattern(data.data(), kIoLen, 11);
+    ASSERT_EQ((ssize_t)kIoLen, pwrite(md_, data.data(), kIoLen, 8192)) << strerror(errno);
+    ASSERT_EQ(0, fsync(md_)) << strerror(errno);
+
+    std::string expected(data.begin(), data.end());
+    for (int i = 0; i < kDisks; i++) {
+        EXPECT_EQ(expected, ReadBacking(i, 8192, kIoLen)) << "mirror " << i;
+    }
+}
+
+TEST_F(MdRaid1, DegradedArrayReadsFromTheSurvivingMirror) {
+    ASSERT_EQ(0, ioctl(md_, SET_DISK_FAULTY, (unsigned long)dev_[0])) << strerror(errno);
+
+    struct mdu_array_info info = {};
+    ASSERT_EQ(0, ioctl(md_, GET_ARRAY_INFO, &info)) << strerror(errno);
+    EXPECT_EQ(1, info.failed_disks);
+    EXPECT_EQ(1, info.working_disks);
+    EXPECT_EQ(1, info.active_disks);
+
+    struct mdu_disk_info disk = {};
+    disk.number = 0;
+    ASSERT_EQ(0, ioctl(md_, GET_DISK_INFO, &disk)) << strerror(errno);
+    EXPECT_NE(0, disk.state & (1 << MD_DISK_FAULTY));
+
+    std::string stat = MdStat();
+    EXPECT_NE(std::string::npos, stat.find("(F)")) << stat;
+    EXPECT_NE(std::string::npos, stat.find("[2/1] [_U]")) << stat;
+
+    // 破坏故障盘上的数据，读到的仍然应该是另一个成员盘上的内容
+    int fd = open(backing_[0].c_str(), O_RDWR);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    std::vector<char> zero(kIoLen, 0);
+    ASSERT_EQ((ssize_t)kIoLen, pwrite(fd, zero.data(), kIoLen, kSeedOff));
+    ASSERT_EQ(0, fsync(fd));
+    close(fd);
+
+    std::vector<char> back(kIoLen);
+    ASSERT_EQ((ssize_t)kIoLen, pread(md_, back.data(), kIoLen, kSeedOff)) << strerror(errno);
+    EXPECT_EQ(seed_, back);
+
+    // 降级的阵列仍然可以写入，数据只落在正常的成员盘上
+    std::vector<char> data(kIoLen);
+    blk_fill_pattern(data.data(), kIoLen, 13);
+    ASSERT_EQ((ssize_t)kIoLen, pwrite(md_, data.data(), kIoLen, 0)) << strerror(errno);
+    ASSERT_EQ(0, fsync(md_)) << strerror(errno);
+    EXPECT_EQ(std::string(data.begin(), data.end()), ReadBacking(1, 0, kIoLen));
+    EXPECT_EQ(std::string(kIoLen, '\0'), ReadBacking(0, 0, kIoLen));
+}
+
+TEST_F(MdRaid1, LastMirrorCannotFail) {
+    ASSERT_EQ(0, ioctl(md_, SET_DISK_FAULTY, (unsigned long)dev_[0])) << strerror(errno);
+    errno = 0;
+    EXPECT_EQ(-1, ioctl(md_, SET_DISK_FAULTY, (unsigned long)dev_[1]));
+    EXPECT_EQ(EBUSY, errno);
+
+    // 故障盘可以移除，之后按编号查询时是空位
+    ASSERT_EQ(0, ioctl(md_, HOT_REMOVE_DISK, (unsigned long)dev_[0])) << strerror(errno);
+    struct mdu_disk_info disk = {};
+    disk.number = 0;
+    ASSERT_EQ(0, ioctl(md_, GET_DISK_INFO, &disk)) << strerror(errno);
+    EXPECT_NE(0, disk.state & (1 << MD_DISK_REMOVED));
+
+    std::vector<char> back(kIoLen);
+    ASSERT_EQ((ssize_t)kIoLen, pread(md_, back.data(), kIoLen, kSeedOff)) << strerror(errno);
+    EXPECT_EQ(seed_, back);
+}
+
+}  // namespace
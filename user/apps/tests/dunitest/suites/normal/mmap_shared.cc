@@ -0,0 +1,127 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+namespace {
+
+const char *kPath = "/tmp/test_mmap_shared.tmp";
+
+class MmapShared : public ::testing::Test {
+protected:
+    void SetUp() override {
+        page_ = sysconf(_SC_PAGESIZE);
+        fd_ = open(kPath, O_CREAT | O_RDWR | O_TRUNC, 0644);
+        ASSERT_GE(fd_, 0) << strerror(errno);
+        ASSERT_EQ(0, ftruncate(fd_, 2 * page_)) << strerror(errno);
+    }
+
+    void TearDown() override {
+        if (fd_ >= 0) {
+            close(fd_);
+        }
+        unlink(kPath);
+    }
+
+    char read_byte(off_t off) {
+        char c = 0;
+        EXPECT_EQ(1, pread(fd_, &c, 1, off)) << strerror(errno);
+        return c;
+    }
+
+    long page_ = 0;
+    int fd_ = -1;
+};
+
+}  // namespace
+
+TEST_F(MmapShared, SharedWriteVisibleToRead) {
+    char *p = (char *)mmap(nullptr, 2 * page_, PROT_READ | PROT_WRITE, MAP_SHARED, fd_, 0);
+    ASSERT_NE(MAP_FAILED, p) << strerror(errno);
+
+    // 先读后写：读缺页建立的映射不能让之后的写入绕过脏页标记
+    EXPECT_EQ(0, p[page_]);
+    p[page_] = 'a';
+    EXPECT_EQ('a', read_byte(page_));
+
+    ASSERT_EQ(0, munmap(p, 2 * page_));
+}
+
+TEST_F(MmapShared, WriteAfterMsyncIsWrittenBack) {
+    char *p = (char *)mmap(nullptr, page_, PROT_READ | PROT_WRITE, MAP_SHARED, fd_, 0);
+    ASSERT_NE(MAP_FAILED, p) << strerror(errno);
+
+    p[0] = 'a';
+    ASSERT_EQ(0, msync(p, page_, MS_SYNC)) << strerror(errno);
+    p[0] = 'b';
+    ASSERT_EQ(0, msync(p, page_, MS_SYNC)) << strerror(errno);
+    ASSERT_EQ(0, munmap(p, page_));
+
+    // 丢弃干净的缓存页，从文件重新读取
+    posix_fadvise(fd_, 0, page_, POSIX_FADV_DONTNEED);
+    EXPECT_EQ('b', read_byte(0));
+}
+
+TEST_F(MmapShared, SharedMappingsSeeEachOther) {
+    char *p = (char *)mmap(nullptr, page_, PROT_READ | PROT_WRITE, MAP_SHARED, fd_, 0);
+    ASSERT_NE(MAP_FAILED, p) << strerror(errno);
+
+    pid_t pid = fork();
+    ASSERT_GE(pid, 0) << strerror(errno);
+    if (pid == 0) {
+        p[1] = 'c';
+        _exit(msync(p, page_, MS_SYNC) == 0 ? 0 : 1);
+    }
+    int status = 0;
+    ASSERT_EQ(pid, waitpid(pid, &status, 0));
+    ASSERT_TRUE(WIFEXITED(status));
+    EXPECT_EQ(0, WEXITSTATUS(status));
+    EXPECT_EQ('c', p[1]);
+    EXPECT_EQ('c', read_byte(1));
+
+    ASSERT_EQ(0, munmap(p, page_));
+}
+
+TEST_F(MmapShared, PrivateWriteNotPropagated) {
+    ASSERT_EQ(1, pwrite(fd_, "x", 1, 0));
+    char *p = (char *)mmap(nullptr, page_, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd_, 0);
+    ASSERT_NE(MAP_FAILED, p) << strerror(errno);
+
+    EXPECT_EQ('x', p[0]);
+    p[0] = 'y';
+    EXPECT_EQ('x', read_byte(0));
+
+    ASSERT_EQ(0, munmap(p, page_));
+}
+
+TEST_F(MmapShared, MprotectKeepsDirtyTracking) {
+    char *p = (char *)mmap(nullptr, page_, PROT_READ, MAP_SHARED, fd_, 0);
+    ASSERT_NE(MAP_FAILED, p) << strerror(errno);
+    EXPECT_EQ(0, p[0]);
+
+    ASSERT_EQ(0, mprotect(p, page_, PROT_READ | PROT_WRITE)) << strerror(errno);
+    p[0] = 'd';
+    ASSERT_EQ(0, msync(p, page_, MS_SYNC)) << strerror(errno);
+    ASSERT_EQ(0, munmap(p, page_));
+
+    posix_fadvise(fd_, 0, page_, POSIX_FADV_DONTNEED);
+    EXPECT_EQ('d', read_byte(0));
+}
+
+TEST_F(MmapShared, MsyncInvalidArguments) {
+    char *p = (char *)mmap(nullptr, page_, PROT_READ | PROT_WRITE, MAP_SHARED, fd_, 0);
+    ASSERT_NE(MAP_FAILED, p) << strerror(errno);
+
+    EXPECT_EQ(-1, msync(p + 1, page_, MS_SYNC));
+    EXPECT_EQ(EINVAL, errno);
+    EXPECT_EQ(-1, msync(p, page_, MS_SYNC | MS_ASYNC));
+    EXPECT_EQ(EINVAL, errno);
+
+    ASSERT_EQ(0, munmap(p, page_));
+    EXPECT_EQ(-1, msync(p, page_, MS_SYNC));
+    EXPECT_EQ(ENOMEM, errno);
+}
@@ -0,0 +1,390 @@
+#include <gtest/gtest.h>
+
+#include <endian.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include <atomic>
+#include <memory>
+#include <string>
+#include <thread>
+#include <vector>
+
+#include "blkdev_common.h"
+
+namespace {
+
+// 与Linux的include/uapi/linux/nbd.h一致
+#define NBD_SET_SOCK _IO(0xab, 0)
+#define NBD_SET_SIZE _IO(0xab, 2)
+#define NBD_DO_IT _IO(0xab, 3)
+#define NBD_CLEAR_SOCK _IO(0xab, 4)
+#define NBD_DISCONNECT _IO(0xab, 8)
+#define NBD_SET_FLAGS _IO(0xab, 10)
+// DragonOS扩展：由内核在socket上完成fixed newstyle握手
+#define NBD_NEGOTIATE _IO(0xab, 0x40)
+
+#define NBD_FLAG_FIXED_NEWSTYLE (1 << 0)
+#define NBD_FLAG_NO_ZEROES (1 << 1)
+
+#define NBD_FLAG_HAS_FLAGS (1 << 0)
+#define NBD_FLAG_SEND_FLUSH (1 << 2)
+
+#define NBD_CMD_READ 0
+#define NBD_CMD_WRITE 1
+#define NBD_CMD_DISC 2
+#define NBD_CMD_FLUSH 3
+
+constexpr uint64_t kNbdMagic = 0x4e42444d41474943ULL;
+constexpr uint64_t kIHaveOpt = 0x49484156454f5054ULL;
+constexpr uint64_t kOptReplyMagic = 0x0003e889045565a9ULL;
+constexpr uint32_t kRequestMagic = 0x25609513;
+constexpr uint32_t kReplyMagic = 0x67446698;
+constexpr uint32_t kOptGo = 7;
+constexpr uint32_t kRepAck = 1;
+constexpr uint32_t kRepInfo = 3;
+constexpr uint32_t kRepErrUnknown = (1u << 31) | 6;
+
+constexpr size_t kExportSize = 1024 * 1024;
+constexpr size_t kIoLen = 64 * 1024;
+constexpr const char *kExportName = "dunitest";
+
+bool ReadFull(int fd, void *buf, size_t len) {
+    char *p = static_cast<char *>(buf);
+    while (len > 0) {
+        ssize_t n = recv(fd, p, len, 0);
+        if (n <= 0) {
+            return false;
+        }
+        p += n;
+        len -= n;
+    }
+    return true;
+}
+
+bool WriteFull(int fd, const void *buf, size_t len) {
+    const char *p = static_cast<const char *>(buf);
+    while (len > 0) {
+        // 对端被内核关闭时不要产生SIGPIPE
+        ssize_t n = send(fd, p, len, MSG_NOSIGNAL);
+        if (n <= 0) {
+            return false;
+        }
+        p += n;
+        len -= n;
+    }
+    return true;
+}
+
+// 在socketpair的一端上用内存中的镜像提供导出的服务器
+class Server {
+public:
+    explicit Server(int fd) : fd_(fd), image_(kExportSize) {}
+
+    // fixed newstyle握手，只支持NBD_OPT_GO
+    bool Handshake() {
+        uint64_t magic[2] = {htobe64(kNbdMagic), htobe64(kIHaveOpt)};
+        uint16_t hs_flags = htobe16(NBD_FLAG_FIXED_NEWSTYLE | NBD_FLAG_NO_ZEROES);
+        uint32_t client_flags;
+        if (!WriteFull(fd_, magic, sizeof(magic)) || !WriteFull(fd_, &hs_flags, 2) ||
+            !ReadFull(fd_, &client_flags, 4)) {
+            return false;
+        }
+        for (;;) {
+            uint64_t opt_magic;
+            uint32_t opt, len;
+            if (!ReadFull(fd_, &opt_magic, 8) || !ReadFull(fd_, &opt, 4) ||
+                !ReadFull(fd_, &len, 4) || be64toh(opt_magic) != kIHaveOpt) {
+                return false;
+            }
+            std::vector<char> data(be32toh(len));
+            if (!ReadFull(fd_, data.data(), data.size()) || be32toh(opt) != kOptGo) {
+                return false;
+            }
+            uint32_t name_len = 0;
+            memcpy(&name_len, data.data(), 4);
+            name_len = be32toh(name_len);
+            std::string name(data.data() + 4, name_len);
+            if (name != kExportName) {
+                SendOptReply(kOptGo, kRepErrUnknown, nullptr, 0);
+                continue;
+            }
+            // NBD_INFO_EXPORT: u16类型、u64大小、u16传输标志
+            char info[12] = {};
+            uint64_t size = htobe64(kExportSize);
+            uint16_t flags = htobe16(NBD_FLAG_HAS_FLAGS | NBD_FLAG_SEND_FLUSH);
+            memcpy(info + 2, &size, 8);
+            memcpy(info + 10, &flags, 2);
+            return SendOptReply(kOptGo, kRepInfo, info, sizeof(info)) &&
+                   SendOptReply(kOptGo, kRepAck, nullptr, 0);
+        }
+    }
+
+    // 处理传输阶段的请求，直到收到NBD_CMD_DISC或者连接断开
+    void Serve() {
+        for (;;) {
+            char req[28];
+            if (!ReadFull(fd_, req, sizeof(req))) {
+                return;
+            }
+            uint32_t magic, len;
+            uint16_t type;
+            uint64_t handle, offset;
+            memcpy(&magic, req, 4);
+            memcpy(&type, req + 6, 2);
+            memcpy(&handle, req + 8, 8);
+            memcpy(&offset, req + 16, 8);
+            memcpy(&len, req + 24, 4);
+            type = be16toh(type);
+            offset = be64toh(offset);
+            len = be32toh(len);
+            if (be32toh(magic) != kRequestMagic) {
+                return;
+            }
+
+            uint32_t error = 0;
+            if ((type == NBD_CMD_READ || type == NBD_CMD_WRITE) && offset + len > kExportSize) {
+                error = EINVAL;
+            }
+            switch (type) {
+            case NBD_CMD_READ:
+                reads_++;
+                if (!SendReply(handle, error) ||
+                    (error == 0 && !WriteFull(fd_, &image_[offset], len))) {
+                    return;
+                }
+                break;
+            case NBD_CMD_WRITE: {
+                writes_++;
+                std::vector<char> data(len);
+                if (!ReadFull(fd_, data.data(), len)) {
+                    return;
+                }
+                if (error == 0) {
+                    memcpy(&image_[offset], data.data(), len);
+                }
+                if (!SendReply(handle, error)) {
+                    return;
+                }
+                break;
+            }
+            case NBD_CMD_FLUSH:
+                flushes_++;
+                if (!SendReply(handle, 0)) {
+                    return;
+                }
+                break;
+            case NBD_CMD_DISC:
+                disconnected_ = true;
+                return;
+            default:
+                if (!SendReply(handle, EINVAL)) {
+                    return;
+                }
+            }
+        }
+    }
+
+    std::vector<char> &image() { return image_; }
+
+    std::atomic<int> reads_{0};
+    std::atomic<int> writes_{0};
+    std::atomic<int> flushes_{0};
+    std::atomic<bool> disconnected_{false};
+
+private:
+    bool SendOptReply(uint32_t opt, uint32_t type, const void *data, uint32_t len) {
+        uint64_t magic = htobe64(kOptReplyMagic);
+        uint32_t hdr[3] = {htobe32(opt), htobe32(type), htobe32(len)};
+        return WriteFull(fd_, &magic, 8) && WriteFull(fd_, hdr, sizeof(hdr)) &&
+               (len == 0 || WriteFull(fd_, data, len));
+    }
+
+    bool SendReply(uint64_t handle, uint32_t error) {
+        uint32_t hdr[2] = {htobe32(kReplyMagic), htobe32(error)};
+        return WriteFull(fd_, hdr, sizeof(hdr)) && WriteFull(fd_, &handle, 8);
+    }
+
+    int fd_;
+    std::vector<char> image_;
+};
+
+class Nbd : public ::testing::Test {
+protected:
+    void SetUp() override {
+        ASSERT_EQ(0, socketpair(AF_UNIX, SOCK_STREAM, 0, sv_)) << strerror(errno);
+        server_.reset(new Server(sv_[1]));
+
+        // 找一个没有在使用的设备
+        for (int i = 0; i < 16; i++) {
+            std::string path = "/dev/nbd" + std::to_string(i);
+            int fd = open(path.c_str(), O_RDWR);
+            if (fd < 0) {
+                break;
+            }
+            if (ioctl(fd, NBD_SET_SOCK, sv_[0]) == 0) {
+                dev_ = path;
+                nbd_ = fd;
+                return;
+            }
+            close(fd);
+        }
+        GTEST_SKIP() << "no unused /dev/nbdN";
+    }
+
+    void TearDown() override {
+        if (nbd_ >= 0) {
+            // 运行时强制断开，没有运行时只清除socket
+            ioctl(nbd_, NBD_CLEAR_SOCK, 0);
+        }
+        if (sv_[0] >= 0) {
+            shutdown(sv_[0], SHUT_RDWR);
+        }
+        if (do_it_.joinable()) {
+            do_it_.join();
+        }
+        if (serve_.joinable()) {
+            serve_.join();
+        }
+        if (nbd_ >= 0) {
+            close(nbd_);
+        }
+        for (int fd : sv_) {
+            if (fd >= 0) {
+                close(fd);
+            }
+        }
+    }
+
+    // 在另一个线程中运行NBD_DO_IT，等待设备可以读写
+    void Start() {
+        serve_ = std::thread([this] { server_->Serve(); });
+        do_it_ = std::thread([this] {
+            do_it_ret_ = ioctl(nbd_, NBD_DO_IT, 0);
+            do_it_errno_ = errno;
+        });
+        char buf[512];
+        for (int i = 0; i < 200; i++) {
+            int fd = open(dev_.c_str(), O_RDONLY);
+            if (fd >= 0) {
+                ssize_t n = pread(fd, buf, sizeof(buf), 0);
+                close(fd);
+                if (n == (ssize_t)sizeof(buf)) {
+                    return;
+                }
+            }
+            usleep(10 * 1000);
+        }
+        FAIL() << "NBD_DO_IT did not start";
+    }
+
+    // 正常断开连接，等待NBD_DO_IT返回
+    void Stop() {
+        ASSERT_EQ(0, ioctl(nbd_, NBD_DISCONNECT, 0)) << strerror(errno);
+        do_it_.join();
+        serve_.join();
+        EXPECT_EQ(0, do_it_ret_) << strerror(do_it_errno_);
+        EXPECT_TRUE(server_->disconnected_);
+    }
+
+    int sv_[2] = {-1, -1};
+    std::unique_ptr<Server> server_;
+    std::string dev_;
+    int nbd_ = -1;
+    std::thread serve_;
+    std::thread do_it_;
+    int do_it_ret_ = -1;
+    int do_it_errno_ = 0;
+};
+
+TEST_F(Nbd, DoItRequiresSize) {
+    errno = 0;
+    EXPECT_EQ(-1, ioctl(nbd_, NBD_DO_IT, 0));
+    EXPECT_EQ(EINVAL, errno);
+
+    // 已经设置了socket
+    errno = 0;
+    EXPECT_EQ(-1, ioctl(nbd_, NBD_SET_SOCK, sv_[0]));
+    EXPECT_EQ(EBUSY, errno);
+}
+
+TEST_F(Nbd, NegotiateAndRoundTrip) {
+    std::thread hs([this] { EXPECT_TRUE(server_->Handshake()); });
+    int ret = ioctl(nbd_, NBD_NEGOTIATE, kExportName);
+    int saved = errno;
+    hs.join();
+    ASSERT_EQ(0, ret) << strerror(saved);
+
+    blk_fill_pattern(&server_->image()[512 * 1024], kIoLen, 21);
+    Start();
+    int fd = open(dev_.c_str(), O_RDWR);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    EXPECT_EQ((off_t)kExportSize, lseek(fd, 0, SEEK_END));
+
+    // 读到的是服务器上的数据
+    std::vector<char> back(kIoLen);
+    ASSERT_EQ((ssize_t)kIoLen, pread(fd, back.data(), kIoLen, 512 * 1024)) << strerror(errno);
+    EXPECT_EQ(0, memcmp(&server_->image()[512 * 1024], back.data(), kIoLen));
+
+    // 写入的数据在fsync之后到达服务器，服务器支持时发送NBD_CMD_FLUSH
+    std::vector<char> data(kIoLen);
+    blk_fill_pattern(data.data(), kIoLen, 22);
+    ASSERT_EQ((ssize_t)kIoLen, pwrite(fd, data.data(), kIoLen, 4096)) << strerror(errno);
+    ASSERT_EQ(0, fsync(fd)) << strerror(errno);
+    EXPECT_EQ(0, memcmp(&server_->image()[4096], data.data(), kIoLen));
+    EXPECT_GT(server_->writes_, 0);
+    EXPECT_GT(server_->flushes_, 0);
+    close(fd);
+
+    Stop();
+    // 断开之后设备容量清零
+    fd = open(dev_.c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    EXPECT_EQ(0, lseek(fd, 0, SEEK_END));
+    close(fd);
+}
+
+TEST_F(Nbd, NegotiateUnknownExport) {
+    std::thread hs([this] {
+        // 第一次请求的导出不存在，服务器回复错误后等待下一个选项
+        server_->Handshake();
+    });
+    errno = 0;
+    EXPECT_EQ(-1, ioctl(nbd_, NBD_NEGOTIATE, "missing"));
+    EXPECT_EQ(ENOENT, errno);
+    shutdown(sv_[0], SHUT_RDWR);
+    hs.join();
+}
+
+TEST_F(Nbd, ManualSizeWithoutHandshake) {
+    // 由用户态完成握手（例如nbd-client）时直接设置容量和传输标志
+    ASSERT_EQ(0, ioctl(nbd_, NBD_SET_SIZE, 256 * 1024)) << strerror(errno);
+    ASSERT_EQ(0, ioctl(nbd_, NBD_SET_FLAGS, NBD_FLAG_HAS_FLAGS)) << strerror(errno);
+    blk_fill_pattern(server_->image().data(), kIoLen, 31);
+    Start();
+
+    int fd = open(dev_.c_str(), O_RDWR);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    EXPECT_EQ(256 * 1024, lseek(fd, 0, SEEK_END));
+    std::vector<char> back(kIoLen);
+    ASSERT_EQ((ssize_t)kIoLen, pread(fd, back.data(), kIoLen, 0)) << strerror(errno);
+    EXPECT_EQ(0, memcmp(server_->image().data(), back.data(), kIoLen));
+
+    // 服务器不支持NBD_CMD_FLUSH时fsync只写回数据
+    std::vector<char> data(kIoLen);
+    blk_fill_pattern(data.data(), kIoLen, 32);
+    ASSERT_EQ((ssize_t)kIoLen, pwrite(fd, data.data(), kIoLen, 128 * 1024)) << strerror(errno);
+    ASSERT_EQ(0, fsync(fd)) << strerror(errno);
+    EXPECT_EQ(0, memcmp(&server_->image()[128 * 1024], data.data(), kIoLen));
+    EXPECT_EQ(0, server_->flushes_);
+    close(fd);
+
+    Stop();
+}
+
+}  // namespace
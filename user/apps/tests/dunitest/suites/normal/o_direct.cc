@@ -0,0 +1,111 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+#include <string>
+#include <vector>
+
+namespace {
+
+constexpr size_t kAlign = 4096;
+// 超过内核每次固定的用户缓冲区（1M）和每个BIO的大小（128K）
+constexpr size_t kLen = 1536 * 1024;
+
+class ODirect : public ::testing::Test {
+protected:
+    void SetUp() override {
+        path_ = "/o_direct_test_" + std::to_string(getpid());
+        fd_ = open(path_.c_str(), O_RDWR | O_CREAT | O_TRUNC | O_DIRECT, 0644);
+        if (fd_ < 0 && errno == EINVAL) {
+            GTEST_SKIP() << "O_DIRECT not supported: " << strerror(errno);
+        }
+        ASSERT_GE(fd_, 0) << strerror(errno);
+        ASSERT_EQ(0, posix_memalign(&buf_, kAlign, kLen));
+
+        // 不支持直接I/O的文件系统按普通读写处理O_DIRECT，不检查对齐
+        char *unaligned = static_cast<char *>(buf_) + 1;
+        if (pwrite(fd_, unaligned, 512, 0) != -1 || errno != EINVAL) {
+            GTEST_SKIP() << "filesystem of / serves O_DIRECT from the cache";
+        }
+    }
+
+    void TearDown() override {
+        if (fd_ >= 0) {
+            close(fd_);
+            unlink(path_.c_str());
+        }
+        free(buf_);
+    }
+
+    void Fill(unsigned char *p, size_t len) {
+        for (size_t i = 0; i < len; i++) {
+            p[i] = static_cast<unsigned char>(i * 7 + i / 4096);
+        }
+    }
+
+    std::string path_;
+    int fd_ = -1;
+    void *buf_ = nullptr;
+};
+
+TEST_F(ODirect, UnalignedBufferAndOffsetRejected) {
+    char *p = static_cast<char *>(buf_);
+    errno = 0;
+    EXPECT_EQ(-1, pread(fd_, p + 1, 512, 0));
+    EXPECT_EQ(EINVAL, errno);
+
+    errno = 0;
+    EXPECT_EQ(-1, pwrite(fd_, p, 512, 1));
+    EXPECT_EQ(EINVAL, errno);
+
+    errno = 0;
+    EXPECT_EQ(-1, pwrite(fd_, p, 100, 0));
+    EXPECT_EQ(EINVAL, errno);
+}
+
+TEST_F(ODirect, RoundTripIntoUntouchedMapping) {
+    Fill(static_cast<unsigned char *>(buf_), kLen);
+    ASSERT_EQ((ssize_t)kLen, write(fd_, buf_, kLen)) << strerror(errno);
+
+    // 缓冲区的页还没有被访问过，内核需要先调入它们再让设备写入
+    void *map = mmap(nullptr, kLen, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    ASSERT_NE(MAP_FAILED, map);
+    ASSERT_EQ((ssize_t)kLen, pread(fd_, map, kLen, 0)) << strerror(errno);
+    EXPECT_EQ(0, memcmp(map, buf_, kLen));
+
+    // 不带O_DIRECT的读取看到的是同样的数据
+    int fd = open(path_.c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0);
+    std::vector<unsigned char> cached(kLen);
+    ASSERT_EQ((ssize_t)kLen, pread(fd, cached.data(), kLen, 0));
+    EXPECT_EQ(0, memcmp(cached.data(), buf_, kLen));
+    close(fd);
+    munmap(map, kLen);
+}
+
+TEST_F(ODirect, ReadStopsAtEndOfFile) {
+    Fill(static_cast<unsigned char *>(buf_), kLen);
+    ASSERT_EQ(4096, pwrite(fd_, buf_, 4096, 0));
+
+    memset(buf_, 0, kLen);
+    ASSERT_EQ(4096, pread(fd_, buf_, 8192, 0));
+    ASSERT_EQ(0, pread(fd_, buf_, 4096, 4096));
+}
+
+TEST_F(ODirect, ReadIntoReadOnlyMappingFails) {
+    ASSERT_EQ(4096, pwrite(fd_, buf_, 4096, 0));
+
+    void *map = mmap(nullptr, 4096, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    ASSERT_NE(MAP_FAILED, map);
+    errno = 0;
+    EXPECT_EQ(-1, pread(fd_, map, 4096, 0));
+    EXPECT_EQ(EFAULT, errno);
+    munmap(map, 4096);
+}
+
+}  // namespace
@@ -0,0 +1,118 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include <string>
+
+namespace {
+
+const char *kPanicOnOops = "/proc/sys/kernel/panic_on_oops";
+const char *kTainted = "/proc/sys/kernel/tainted";
+
+static bool read_file(const char *path, std::string *out) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        return false;
+    }
+    char buf[64];
+    ssize_t n = read(fd, buf, sizeof(buf));
+    close(fd);
+    if (n < 0) {
+        return false;
+    }
+    out->assign(buf, n);
+    return true;
+}
+
+static bool write_file(const char *path, const char *value) {
+    int fd = open(path, O_WRONLY);
+    if (fd < 0) {
+        return false;
+    }
+    ssize_t n = write(fd, value, strlen(value));
+    close(fd);
+    return n == (ssize_t)strlen(value);
+}
+
+// 在子进程中执行fn，返回杀死子进程的信号，正常退出时返回0
+static int run_in_child(void (*fn)()) {
+    pid_t pid = fork();
+    if (pid == 0) {
+        fn();
+        _exit(0);
+    }
+    int status = 0;
+    if (waitpid(pid, &status, 0) != pid) {
+        return -1;
+    }
+    return WIFSIGNALED(status) ? WTERMSIG(status) : 0;
+}
+
+static void divide_by_zero() {
+    volatile int zero = 0;
+    volatile int x = 1 / zero;
+    (void)x;
+}
+
+#if defined(__x86_64__)
+static void undefined_opcode() {
+    __asm__ volatile("ud2");
+}
+#endif
+
+}  // namespace
+
+TEST(Oops, PanicOnOopsRoundTrip) {
+    std::string old;
+    ASSERT_TRUE(read_file(kPanicOnOops, &old)) << strerror(errno);
+    ASSERT_TRUE(old == "0\n" || old == "1\n") << old;
+
+    ASSERT_TRUE(write_file(kPanicOnOops, "1\n"));
+    std::string value;
+    ASSERT_TRUE(read_file(kPanicOnOops, &value));
+    EXPECT_EQ("1\n", value);
+
+    ASSERT_TRUE(write_file(kPanicOnOops, old.c_str()));
+    ASSERT_TRUE(read_file(kPanicOnOops, &value));
+    EXPECT_EQ(old, value);
+
+    EXPECT_FALSE(write_file(kPanicOnOops, "abc"));
+}
+
+TEST(Oops, TaintedCannotBeCleared) {
+    std::string before;
+    ASSERT_TRUE(read_file(kTainted, &before)) << strerror(errno);
+    ASSERT_TRUE(write_file(kTainted, "0"));
+    std::string after;
+    ASSERT_TRUE(read_file(kTainted, &after));
+    EXPECT_EQ(before, after);
+    EXPECT_FALSE(write_file(kTainted, "-1"));
+}
+
+TEST(Oops, TaintedWriteSetsKnownBits) {
+    const unsigned long kTaintUser = 1UL << 6;
+    ASSERT_TRUE(write_file(kTainted, "64"));
+    std::string value;
+    ASSERT_TRUE(read_file(kTainted, &value));
+    unsigned long mask = strtoul(value.c_str(), nullptr, 10);
+    EXPECT_NE(0UL, mask & kTaintUser) << value;
+
+    // 未定义的位被忽略
+    ASSERT_TRUE(write_file(kTainted, "1073741824"));
+    std::string after;
+    ASSERT_TRUE(read_file(kTainted, &after));
+    EXPECT_EQ(value, after);
+}
+
+TEST(Oops, UserFaultsRaiseSignals) {
+    EXPECT_EQ(SIGFPE, run_in_child(divide_by_zero));
+#if defined(__x86_64__)
+    EXPECT_EQ(SIGILL, run_in_child(undefined_opcode));
+#endif
+}
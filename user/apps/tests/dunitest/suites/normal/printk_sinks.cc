@@ -0,0 +1,96 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+
+#include <string>
+
+namespace {
+
+const char *kPrintkSinks = "/proc/sys/kernel/printk_sinks";
+
+static bool read_file(const char *path, std::string *out) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        return false;
+    }
+    char buf[256];
+    ssize_t n = read(fd, buf, sizeof(buf));
+    close(fd);
+    if (n < 0) {
+        return false;
+    }
+    out->assign(buf, n);
+    return true;
+}
+
+// 返回write的结果，失败时返回-errno
+static ssize_t write_file(const char *path, const char *value) {
+    int fd = open(path, O_WRONLY);
+    if (fd < 0) {
+        return -errno;
+    }
+    ssize_t n = write(fd, value, strlen(value));
+    int err = errno;
+    close(fd);
+    return n < 0 ? -err : n;
+}
+
+class PrintkSinks : public ::testing::Test {
+protected:
+    void SetUp() override {
+        ASSERT_TRUE(read_file(kPrintkSinks, &saved_)) << strerror(errno);
+    }
+
+    void TearDown() override {
+        write_file(kPrintkSinks, saved_.c_str());
+    }
+
+    std::string saved_;
+};
+
+}  // namespace
+
+TEST_F(PrintkSinks, ListsAllSinks) {
+    EXPECT_NE(std::string::npos, saved_.find("kmsg:"));
+    EXPECT_NE(std::string::npos, saved_.find("serial:"));
+    EXPECT_NE(std::string::npos, saved_.find("screen:"));
+    EXPECT_NE(std::string::npos, saved_.find("netconsole:"));
+    EXPECT_EQ('\n', saved_.back());
+}
+
+TEST_F(PrintkSinks, SetLevelsAndOptions) {
+    ASSERT_GT(write_file(kPrintkSinks,
+                         "kmsg:all,serial:debug screen:warn:nocolor netconsole:off\n"),
+              0);
+    std::string value;
+    ASSERT_TRUE(read_file(kPrintkSinks, &value));
+    EXPECT_EQ("kmsg:7 serial:7 screen:4:nocolor netconsole:off\n", value);
+
+    // 只修改提到的输出端
+    ASSERT_GT(write_file(kPrintkSinks, "screen:6:color"), 0);
+    ASSERT_TRUE(read_file(kPrintkSinks, &value));
+    EXPECT_EQ("kmsg:7 serial:7 screen:6 netconsole:off\n", value);
+
+    // 读出的内容可以原样写回
+    ASSERT_GT(write_file(kPrintkSinks, value.c_str()), 0);
+    std::string again;
+    ASSERT_TRUE(read_file(kPrintkSinks, &again));
+    EXPECT_EQ(value, again);
+}
+
+TEST_F(PrintkSinks, RejectsInvalidConfig) {
+    EXPECT_EQ(-EINVAL, write_file(kPrintkSinks, "floppy:7"));
+    EXPECT_EQ(-EINVAL, write_file(kPrintkSinks, "serial:8"));
+    EXPECT_EQ(-EINVAL, write_file(kPrintkSinks, "serial:debug:blink"));
+    EXPECT_EQ(-EINVAL, write_file(kPrintkSinks, "kmsg:7:color"));
+    EXPECT_EQ(-EINVAL, write_file(kPrintkSinks, "\n"));
+
+    // 有一项不合法时整个配置都不生效
+    EXPECT_EQ(-EINVAL, write_file(kPrintkSinks, "serial:3,screen:bogus"));
+    std::string value;
+    ASSERT_TRUE(read_file(kPrintkSinks, &value));
+    EXPECT_EQ(saved_, value);
+}
@@ -0,0 +1,100 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#include <string>
+#include <vector>
+
+namespace {
+
+struct MapLine {
+    uintptr_t start;
+    uintptr_t end;
+    std::string name;
+};
+
+static std::vector<MapLine> read_maps() {
+    std::vector<MapLine> lines;
+    FILE *fp = fopen("/proc/self/maps", "r");
+    if (fp == nullptr) {
+        return lines;
+    }
+    char buf[512];
+    while (fgets(buf, sizeof(buf), fp) != nullptr) {
+        MapLine line;
+        unsigned long start, end;
+        int consumed = 0;
+        if (sscanf(buf, "%lx-%lx %*s %*s %*s %*s%n", &start, &end, &consumed) < 2) {
+            continue;
+        }
+        line.start = start;
+        line.end = end;
+        std::string rest(buf + consumed);
+        size_t first = rest.find_first_not_of(" \n");
+        size_t last = rest.find_last_not_of(" \n");
+        line.name = first == std::string::npos ? "" : rest.substr(first, last - first + 1);
+        lines.push_back(line);
+    }
+    fclose(fp);
+    return lines;
+}
+
+}  // namespace
+
+TEST(ProcMaps, HeapLabelDoesNotCoverAdjacentMapping) {
+    const uintptr_t page = sysconf(_SC_PAGESIZE);
+    uintptr_t old_brk = syscall(SYS_brk, 0);
+    ASSERT_NE(0u, old_brk);
+    uintptr_t new_brk = old_brk + 4 * page;
+    ASSERT_EQ(new_brk, static_cast<uintptr_t>(syscall(SYS_brk, new_brk)));
+    // 触碰新的堆空间，确保已经映射
+    memset(reinterpret_cast<void *>(old_brk), 0x5a, 4 * page);
+
+    std::vector<MapLine> maps = read_maps();
+    const MapLine *heap = nullptr;
+    for (const MapLine &line : maps) {
+        if (line.name == "[heap]") {
+            ASSERT_EQ(nullptr, heap) << "more than one [heap] mapping";
+            heap = &line;
+        }
+    }
+    ASSERT_NE(nullptr, heap);
+    EXPECT_LE(heap->start, old_brk);
+    EXPECT_GE(heap->end, new_brk);
+    uintptr_t heap_start = heap->start;
+
+    // 在堆的正下方放一个只读映射，它结束于brk_start，不能被标记为[heap]
+    void *below = reinterpret_cast<void *>(heap_start - page);
+    bool mapped = false;
+    bool occupied = false;
+    for (const MapLine &line : maps) {
+        occupied |= line.start < heap_start && line.end > heap_start - page;
+    }
+    if (!occupied) {
+        void *p = mmap(below, page, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, -1, 0);
+        ASSERT_EQ(below, p) << strerror(errno);
+        mapped = true;
+        maps = read_maps();
+    }
+
+    const MapLine *neighbor = nullptr;
+    for (const MapLine &line : maps) {
+        if (line.end == heap_start) {
+            neighbor = &line;
+        }
+    }
+    ASSERT_NE(nullptr, neighbor);
+    EXPECT_NE("[heap]", neighbor->name);
+
+    if (mapped) {
+        munmap(below, page);
+    }
+    syscall(SYS_brk, old_brk);
+}
@@ -0,0 +1,155 @@
+#include <gtest/gtest.h>
+
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#include <map>
+#include <set>
+#include <string>
+#include <vector>
+
+namespace {
+
+const char *kTarget = "/tmp/readdir_cookie";
+const int kFiles = 64;
+
+std::string Name(int i) {
+    return "f" + std::to_string(i);
+}
+
+class ReaddirCookie : public ::testing::TestWithParam<bool> {
+protected:
+    void SetUp() override {
+        struct stat st;
+        if (stat(kTarget, &st) != 0) {
+            ASSERT_EQ(0, mkdir(kTarget, 0755)) << strerror(errno);
+        }
+        // 参数为true时在tmpfs上测试按创建顺序分配的位置，否则测试默认的哈希位置
+        if (GetParam()) {
+            if (mount("tmpfs", kTarget, "tmpfs", 0, nullptr) != 0) {
+                GTEST_SKIP() << "mount tmpfs: " << strerror(errno);
+            }
+            mounted_ = true;
+        }
+        for (int i = 0; i < kFiles; i++) {
+            Touch(Name(i));
+        }
+    }
+
+    void TearDown() override {
+        if (mounted_) {
+            umount2(kTarget, MNT_DETACH);
+        } else {
+            DIR *d = opendir(kTarget);
+            if (d != nullptr) {
+                while (struct dirent *e = readdir(d)) {
+                    if (strcmp(e->d_name, ".") != 0 && strcmp(e->d_name, "..") != 0) {
+                        unlink(Path(e->d_name).c_str());
+                    }
+                }
+                closedir(d);
+            }
+        }
+        rmdir(kTarget);
+    }
+
+    static std::string Path(const std::string &name) {
+        return std::string(kTarget) + "/" + name;
+    }
+
+    static void Touch(const std::string &name) {
+        int fd = open(Path(name).c_str(), O_CREAT | O_WRONLY, 0644);
+        ASSERT_GE(fd, 0) << strerror(errno);
+        close(fd);
+    }
+
+    bool mounted_ = false;
+};
+
+}  // namespace
+
+TEST_P(ReaddirCookie, MutationDuringIterationKeepsPosition) {
+    DIR *d = opendir(kTarget);
+    ASSERT_NE(nullptr, d);
+
+    std::map<std::string, int> seen;
+    for (int i = 0; i < kFiles / 2 + 2; i++) {
+        struct dirent *e = readdir(d);
+        ASSERT_NE(nullptr, e);
+        seen[e->d_name]++;
+    }
+
+    // 删除一些已经读过的项和一些还没有读到的项，再新建一些项
+    std::set<std::string> removed;
+    for (int i = 0; i < kFiles; i += 4) {
+        ASSERT_EQ(0, unlink(Path(Name(i)).c_str()));
+        removed.insert(Name(i));
+    }
+    for (int i = 0; i < 8; i++) {
+        Touch("new" + std::to_string(i));
+    }
+
+    while (struct dirent *e = readdir(d)) {
+        seen[e->d_name]++;
+    }
+    closedir(d);
+
+    for (const auto &it : seen) {
+        EXPECT_EQ(1, it.second) << it.first << " returned more than once";
+    }
+    for (int i = 0; i < kFiles; i++) {
+        if (removed.count(Name(i)) == 0) {
+            EXPECT_EQ(1u, seen.count(Name(i))) << Name(i) << " was skipped";
+        }
+    }
+}
+
+TEST_P(ReaddirCookie, SeekdirReturnsToSameEntry) {
+    DIR *d = opendir(kTarget);
+    ASSERT_NE(nullptr, d);
+
+    std::vector<std::pair<long, std::string>> positions;
+    long pos = telldir(d);
+    while (struct dirent *e = readdir(d)) {
+        positions.emplace_back(pos, e->d_name);
+        pos = telldir(d);
+    }
+    ASSERT_GE(positions.size(), static_cast<size_t>(kFiles));
+
+    // 删除一个靠前的项之后，其他项的位置不变
+    ASSERT_EQ(0, unlink(Path(positions[2].second).c_str()));
+    for (size_t i = 3; i < positions.size(); i += 7) {
+        seekdir(d, positions[i].first);
+        struct dirent *e = readdir(d);
+        ASSERT_NE(nullptr, e);
+        EXPECT_STREQ(positions[i].second.c_str(), e->d_name);
+    }
+    closedir(d);
+}
+
+TEST_P(ReaddirCookie, DoffMatchesNextPosition) {
+    DIR *d = opendir(kTarget);
+    ASSERT_NE(nullptr, d);
+    struct dirent *e = readdir(d);
+    ASSERT_NE(nullptr, e);
+    long next = e->d_off;
+    std::string second;
+    if ((e = readdir(d)) != nullptr) {
+        second = e->d_name;
+    }
+    seekdir(d, next);
+    e = readdir(d);
+    ASSERT_NE(nullptr, e);
+    EXPECT_EQ(second, e->d_name);
+    closedir(d);
+}
+
+INSTANTIATE_TEST_SUITE_P(Fs, ReaddirCookie, ::testing::Values(true, false),
+                         [](const ::testing::TestParamInfo<bool> &info) {
+                             return info.param ? "Tmpfs" : "Default";
+                         });
@@ -7,7 +7,9 @@
 #include <string.h>
 #include <sys/mount.h>
 #include <sys/stat.h>
+#include <sys/sysmacros.h>
 #include <sys/types.h>
+#include <sys/wait.h>
 #include <sys/xattr.h>
 #include <unistd.h>
 
@@ -497,6 +499,87 @@ TEST(MountReconfigure, BindRemountStrictatimeNotPersisted) {
     cleanup_mount(source);
 }
 
+TEST(MountReconfigure, RemountFlagsEnforced) {
+    const char *root = "/tmp/test_mount_flags";
+    const char *file = "/tmp/test_mount_flags/prog.sh";
+    const char *dev = "/tmp/test_mount_flags/null";
+    int fd;
+
+    ensure_dir("/tmp");
+    ensure_dir(root);
+
+    if (unshare(CLONE_NEWNS) != 0) {
+        GTEST_SKIP() << strerror(errno);
+    }
+
+    mount(NULL, "/", NULL, MS_REC | MS_PRIVATE, NULL);
+
+    if (mount("", root, "ramfs", 0, NULL) != 0) {
+        GTEST_SKIP() << strerror(errno);
+    }
+
+    fd = open(file, O_CREAT | O_WRONLY | O_TRUNC, 0755);
+    if (fd < 0) {
+        cleanup_mount(root);
+        FAIL() << "create file failed: " << strerror(errno);
+    }
+    EXPECT_EQ(18, write(fd, "#!/bin/sh\nexit 0\n", 18));
+    close(fd);
+    bool have_dev = mknod(dev, S_IFCHR | 0666, makedev(1, 3)) == 0;
+
+    if (mount("", root, NULL, MS_REMOUNT | MS_RDONLY | MS_NOEXEC | MS_NODEV, NULL) != 0) {
+        cleanup_mount(root);
+        FAIL() << "remount failed: " << strerror(errno);
+    }
+    EXPECT_TRUE(mount_has_option(root, "ro"));
+    EXPECT_TRUE(mount_has_option(root, "noexec"));
+    EXPECT_TRUE(mount_has_option(root, "nodev"));
+
+    // 只读：不能以写方式打开、截断，access(W_OK)返回EROFS
+    EXPECT_EQ(-1, open(file, O_WRONLY));
+    EXPECT_EQ(EROFS, errno);
+    EXPECT_EQ(-1, open(file, O_RDONLY | O_TRUNC));
+    EXPECT_EQ(EROFS, errno);
+    EXPECT_EQ(-1, access(file, W_OK));
+    EXPECT_EQ(EROFS, errno);
+    fd = open(file, O_RDONLY);
+    EXPECT_GE(fd, 0) << strerror(errno);
+    if (fd >= 0) {
+        close(fd);
+    }
+
+    // noexec：不能执行
+    EXPECT_EQ(-1, access(file, X_OK));
+    EXPECT_EQ(EACCES, errno);
+    pid_t pid = fork();
+    if (pid == 0) {
+        execl(file, file, (char *)NULL);
+        _exit(errno == EACCES ? 0 : 1);
+    }
+    int status = 0;
+    EXPECT_EQ(pid, waitpid(pid, &status, 0));
+    EXPECT_TRUE(WIFEXITED(status) && WEXITSTATUS(status) == 0) << "exec on noexec mount";
+
+    // nodev：不能打开设备文件
+    if (have_dev) {
+        EXPECT_EQ(-1, open(dev, O_RDONLY));
+        EXPECT_EQ(EACCES, errno);
+    }
+
+    // 重新挂载为读写之后恢复正常
+    EXPECT_EQ(0, mount("", root, NULL, MS_REMOUNT, NULL)) << strerror(errno);
+    fd = open(file, O_WRONLY);
+    EXPECT_GE(fd, 0) << strerror(errno);
+    if (fd >= 0) {
+        close(fd);
+    }
+    EXPECT_EQ(0, access(file, X_OK)) << strerror(errno);
+
+    unlink(dev);
+    unlink(file);
+    cleanup_mount(root);
+}
+
 int main(int argc, char **argv) {
     ::testing::InitGoogleTest(&argc, argv);
     return RUN_ALL_TESTS();
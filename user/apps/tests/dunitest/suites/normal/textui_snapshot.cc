@@ -0,0 +1,107 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+#include <string>
+
+namespace {
+
+const char *kTextPath = "/sys/kernel/debug/textui/text";
+const char *kCellsPath = "/sys/kernel/debug/textui/cells";
+const char *kPpmPath = "/sys/kernel/debug/textui/screen.ppm";
+
+// 读取整个文件。textui没有初始化（例如没有帧缓冲区）时返回false
+static bool read_all(const char *path, std::string *out) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        return false;
+    }
+    char buf[4096];
+    ssize_t n;
+    out->clear();
+    while ((n = read(fd, buf, sizeof(buf))) > 0) {
+        out->append(buf, n);
+    }
+    close(fd);
+    return n == 0;
+}
+
+static uint32_t le32(const std::string &s, size_t off) {
+    const unsigned char *p = reinterpret_cast<const unsigned char *>(s.data() + off);
+    return p[0] | (p[1] << 8) | (p[2] << 16) | ((uint32_t)p[3] << 24);
+}
+
+}  // namespace
+
+TEST(TextuiSnapshot, CellsMatchText) {
+    std::string cells;
+    std::string text;
+    if (!read_all(kCellsPath, &cells) || !read_all(kTextPath, &text)) {
+        GTEST_SKIP() << "textui snapshot unavailable: " << strerror(errno);
+    }
+
+    ASSERT_GE(cells.size(), 12u);
+    ASSERT_EQ(0, memcmp(cells.data(), "TXUI", 4));
+    uint32_t rows = le32(cells, 4);
+    uint32_t cols = le32(cells, 8);
+    ASSERT_GT(rows, 0u);
+    ASSERT_GT(cols, 0u);
+    ASSERT_EQ(12u + (size_t)rows * cols * 12, cells.size());
+
+    // 纯文本的每一行都对应一行单元格
+    size_t lines = 0;
+    size_t line_start = 0;
+    for (size_t i = 0; i < text.size(); i++) {
+        if (text[i] != '\n') {
+            continue;
+        }
+        std::string line = text.substr(line_start, i - line_start);
+        for (size_t col = 0; col < line.size(); col++) {
+            uint32_t c = le32(cells, 12 + ((size_t)lines * cols + col) * 12);
+            if ((unsigned char)line[col] < 0x80) {
+                EXPECT_EQ((uint32_t)(unsigned char)line[col], c == 0 ? ' ' : c)
+                    << "row " << lines << " col " << col;
+            }
+        }
+        lines++;
+        line_start = i + 1;
+    }
+    EXPECT_EQ(rows, lines);
+}
+
+TEST(TextuiSnapshot, PpmMatchesCells) {
+    std::string cells;
+    std::string ppm;
+    if (!read_all(kCellsPath, &cells) || !read_all(kPpmPath, &ppm)) {
+        GTEST_SKIP() << "textui snapshot unavailable: " << strerror(errno);
+    }
+    ASSERT_GE(cells.size(), 12u);
+    uint32_t rows = le32(cells, 4);
+    uint32_t cols = le32(cells, 8);
+
+    unsigned width = 0;
+    unsigned height = 0;
+    unsigned maxval = 0;
+    int header_len = 0;
+    ASSERT_EQ(3, sscanf(ppm.c_str(), "P6\n%u %u\n%u\n%n", &width, &height, &maxval, &header_len));
+    EXPECT_EQ(cols * 8, width);
+    EXPECT_EQ(rows * 16, height);
+    EXPECT_EQ(255u, maxval);
+    EXPECT_EQ((size_t)header_len + (size_t)width * height * 3, ppm.size());
+}
+
+TEST(TextuiSnapshot, ReadOnly) {
+    int fd = open(kTextPath, O_WRONLY);
+    if (fd < 0) {
+        EXPECT_TRUE(errno == EACCES || errno == ENOENT || errno == ENODEV || errno == EPERM)
+            << strerror(errno);
+        return;
+    }
+    EXPECT_LT(write(fd, "x", 1), 0);
+    close(fd);
+}
@@ -0,0 +1,162 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <sched.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/statfs.h>
+#include <sys/types.h>
+#include <unistd.h>
+
+#ifndef CLONE_NEWNS
+#define CLONE_NEWNS 0x00020000
+#endif
+
+#ifndef MS_REC
+#define MS_REC 16384
+#endif
+
+namespace {
+
+const char *kMountPoint = "/tmp/test_tmpfs_limits";
+
+static int ensure_dir(const char *path) {
+    struct stat st;
+    if (stat(path, &st) == 0) {
+        return S_ISDIR(st.st_mode) ? 0 : -1;
+    }
+    return mkdir(path, 0755);
+}
+
+static ssize_t write_file(const char *name, size_t len) {
+    char path[256];
+    char buf[4096];
+    size_t done = 0;
+    int fd;
+
+    snprintf(path, sizeof(path), "%s/%s", kMountPoint, name);
+    fd = open(path, O_CREAT | O_WRONLY | O_APPEND, 0644);
+    if (fd < 0) {
+        return -1;
+    }
+    memset(buf, 'x', sizeof(buf));
+    while (done < len) {
+        size_t n = len - done < sizeof(buf) ? len - done : sizeof(buf);
+        ssize_t ret = write(fd, buf, n);
+        if (ret < 0) {
+            int saved = errno;
+            close(fd);
+            errno = saved;
+            return -1;
+        }
+        done += ret;
+    }
+    close(fd);
+    return done;
+}
+
+class TmpfsLimits : public ::testing::Test {
+protected:
+    void SetUp() override {
+        ensure_dir("/tmp");
+        ensure_dir(kMountPoint);
+        if (unshare(CLONE_NEWNS) != 0) {
+            GTEST_SKIP() << strerror(errno);
+        }
+        mount(NULL, "/", NULL, MS_REC | MS_PRIVATE, NULL);
+        ASSERT_EQ(0, mount("tmpfs", kMountPoint, "tmpfs", 0, "size=64k,nr_inodes=4"))
+            << strerror(errno);
+        mounted_ = true;
+    }
+
+    void TearDown() override {
+        if (mounted_) {
+            umount(kMountPoint);
+        }
+        rmdir(kMountPoint);
+    }
+
+    bool mounted_ = false;
+};
+
+}  // namespace
+
+TEST_F(TmpfsLimits, StatfsReportsLimits) {
+    struct statfs st;
+    ASSERT_EQ(0, statfs(kMountPoint, &st)) << strerror(errno);
+    EXPECT_EQ(64 * 1024, (long)(st.f_blocks * st.f_bsize));
+    EXPECT_EQ(4, (long)st.f_files);
+    // 根目录占用一个inode
+    EXPECT_EQ(3, (long)st.f_ffree);
+}
+
+TEST_F(TmpfsLimits, SizeLimitReturnsEnospc) {
+    ASSERT_EQ(64 * 1024, write_file("a", 64 * 1024)) << strerror(errno);
+    EXPECT_EQ(-1, write_file("a", 1));
+    EXPECT_EQ(ENOSPC, errno);
+
+    // 删除后空间被归还
+    char path[256];
+    snprintf(path, sizeof(path), "%s/a", kMountPoint);
+    ASSERT_EQ(0, unlink(path));
+    EXPECT_EQ(4096, write_file("b", 4096)) << strerror(errno);
+}
+
+TEST_F(TmpfsLimits, InodeLimitReturnsEnospc) {
+    char path[256];
+    ASSERT_EQ(0, write_file("f1", 0));
+    ASSERT_EQ(0, write_file("f2", 0));
+    snprintf(path, sizeof(path), "%s/d", kMountPoint);
+    ASSERT_EQ(0, mkdir(path, 0755)) << strerror(errno);
+
+    snprintf(path, sizeof(path), "%s/f3", kMountPoint);
+    EXPECT_EQ(-1, open(path, O_CREAT | O_WRONLY, 0644));
+    EXPECT_EQ(ENOSPC, errno);
+
+    // rmdir 之后inode被归还
+    snprintf(path, sizeof(path), "%s/d", kMountPoint);
+    ASSERT_EQ(0, rmdir(path));
+    EXPECT_EQ(0, write_file("f3", 0)) << strerror(errno);
+}
+
+TEST_F(TmpfsLimits, RemountAdjustsLimits) {
+    ASSERT_EQ(64 * 1024, write_file("a", 64 * 1024)) << strerror(errno);
+
+    // 新的上限不能小于已经使用的空间
+    EXPECT_EQ(-1, mount("tmpfs", kMountPoint, "tmpfs", MS_REMOUNT, "size=16k"));
+    EXPECT_EQ(EINVAL, errno);
+
+    ASSERT_EQ(0, mount("tmpfs", kMountPoint, "tmpfs", MS_REMOUNT, "size=128k"))
+        << strerror(errno);
+    EXPECT_EQ(64 * 1024, write_file("a", 64 * 1024)) << strerror(errno);
+    EXPECT_EQ(-1, write_file("a", 1));
+    EXPECT_EQ(ENOSPC, errno);
+
+    struct statfs st;
+    ASSERT_EQ(0, statfs(kMountPoint, &st)) << strerror(errno);
+    EXPECT_EQ(128 * 1024, (long)(st.f_blocks * st.f_bsize));
+}
+
+TEST_F(TmpfsLimits, RemountOptionErrors) {
+    // 值的格式不对、缺少值时返回EINVAL，挂载保持不变
+    EXPECT_EQ(-1, mount("tmpfs", kMountPoint, "tmpfs", MS_REMOUNT, "size=abc"));
+    EXPECT_EQ(EINVAL, errno);
+    EXPECT_EQ(-1, mount("tmpfs", kMountPoint, "tmpfs", MS_REMOUNT, "nr_inodes"));
+    EXPECT_EQ(EINVAL, errno);
+
+    // 不认识的选项被忽略
+    EXPECT_EQ(0, mount("tmpfs", kMountPoint, "tmpfs", MS_REMOUNT, "size=1M,huge=never"))
+        << strerror(errno);
+
+    struct statfs st;
+    ASSERT_EQ(0, statfs(kMountPoint, &st)) << strerror(errno);
+    EXPECT_EQ(1024 * 1024, (long)(st.f_blocks * st.f_bsize));
+}
+
+int main(int argc, char **argv) {
+    ::testing::InitGoogleTest(&argc, argv);
+    return RUN_ALL_TESTS();
+}
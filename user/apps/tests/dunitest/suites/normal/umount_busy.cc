@@ -0,0 +1,120 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <limits.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#include <string>
+
+namespace {
+
+const char *kTarget = "/tmp/umount_busy";
+
+static int ensure_dir(const char *path) {
+    struct stat st;
+    if (stat(path, &st) == 0) {
+        return S_ISDIR(st.st_mode) ? 0 : -1;
+    }
+    return mkdir(path, 0755);
+}
+
+static bool is_mounted(const char *path) {
+    int fd = open("/proc/self/mountinfo", O_RDONLY);
+    if (fd < 0) {
+        return false;
+    }
+    std::string content;
+    char buf[1024];
+    ssize_t n;
+    while ((n = read(fd, buf, sizeof(buf))) > 0) {
+        content.append(buf, n);
+    }
+    close(fd);
+    return content.find(std::string(" ") + path + " ") != std::string::npos;
+}
+
+class UmountBusy : public ::testing::Test {
+protected:
+    void SetUp() override {
+        ASSERT_EQ(0, ensure_dir(kTarget)) << strerror(errno);
+        if (mount("tmpfs", kTarget, "tmpfs", 0, nullptr) != 0) {
+            GTEST_SKIP() << "mount tmpfs: " << strerror(errno);
+        }
+    }
+
+    void TearDown() override {
+        umount2(kTarget, MNT_DETACH);
+        rmdir(kTarget);
+    }
+};
+
+}  // namespace
+
+TEST_F(UmountBusy, OpenFileKeepsMountBusy) {
+    std::string file = std::string(kTarget) + "/f";
+    int fd = open(file.c_str(), O_CREAT | O_RDWR, 0644);
+    ASSERT_GE(fd, 0) << strerror(errno);
+
+    EXPECT_EQ(-1, umount(kTarget));
+    EXPECT_EQ(EBUSY, errno);
+    EXPECT_TRUE(is_mounted(kTarget));
+
+    close(fd);
+    EXPECT_EQ(0, umount(kTarget)) << strerror(errno);
+    EXPECT_FALSE(is_mounted(kTarget));
+}
+
+TEST_F(UmountBusy, CwdKeepsMountBusy) {
+    char old_cwd[PATH_MAX];
+    ASSERT_NE(nullptr, getcwd(old_cwd, sizeof(old_cwd)));
+    ASSERT_EQ(0, chdir(kTarget)) << strerror(errno);
+
+    EXPECT_EQ(-1, umount(kTarget));
+    EXPECT_EQ(EBUSY, errno);
+
+    ASSERT_EQ(0, chdir(old_cwd));
+    EXPECT_EQ(0, umount(kTarget)) << strerror(errno);
+}
+
+TEST_F(UmountBusy, ChildMountKeepsMountBusy) {
+    std::string child = std::string(kTarget) + "/child";
+    ASSERT_EQ(0, mkdir(child.c_str(), 0755)) << strerror(errno);
+    ASSERT_EQ(0, mount("tmpfs", child.c_str(), "tmpfs", 0, nullptr)) << strerror(errno);
+
+    EXPECT_EQ(-1, umount(kTarget));
+    EXPECT_EQ(EBUSY, errno);
+
+    EXPECT_EQ(0, umount(child.c_str())) << strerror(errno);
+    EXPECT_EQ(0, umount(kTarget)) << strerror(errno);
+}
+
+TEST_F(UmountBusy, LazyDetachKeepsOpenFilesUsable) {
+    std::string file = std::string(kTarget) + "/f";
+    std::string child = std::string(kTarget) + "/child";
+    int fd = open(file.c_str(), O_CREAT | O_RDWR, 0644);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ(0, mkdir(child.c_str(), 0755)) << strerror(errno);
+    ASSERT_EQ(0, mount("tmpfs", child.c_str(), "tmpfs", 0, nullptr)) << strerror(errno);
+
+    ASSERT_EQ(0, umount2(kTarget, MNT_DETACH)) << strerror(errno);
+    // 挂载和子挂载立即从挂载树上消失
+    EXPECT_FALSE(is_mounted(kTarget));
+    EXPECT_FALSE(is_mounted(child.c_str()));
+    struct stat st;
+    EXPECT_NE(0, stat(file.c_str(), &st));
+
+    // 已经打开的文件仍然可以读写
+    ASSERT_EQ(5, write(fd, "hello", 5));
+    char buf[8] = {};
+    ASSERT_EQ(5, pread(fd, buf, sizeof(buf), 0));
+    EXPECT_STREQ("hello", buf);
+    close(fd);
+
+    // 挂载点可以重新使用
+    ASSERT_EQ(0, mount("tmpfs", kTarget, "tmpfs", 0, nullptr)) << strerror(errno);
+    EXPECT_TRUE(is_mounted(kTarget));
+}
@@ -0,0 +1,157 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <sys/xattr.h>
+#include <unistd.h>
+
+#include <set>
+#include <string>
+
+namespace {
+
+const char *kPath = "/tmp/test_xattr.tmp";
+const char *kLink = "/tmp/test_xattr.lnk";
+
+class Xattr : public ::testing::Test {
+protected:
+    void SetUp() override {
+        int fd = open(kPath, O_CREAT | O_RDWR | O_TRUNC, 0644);
+        ASSERT_GE(fd, 0) << strerror(errno);
+        close(fd);
+    }
+
+    void TearDown() override {
+        unlink(kLink);
+        unlink(kPath);
+    }
+
+    static std::set<std::string> list_names(const char *path) {
+        std::set<std::string> names;
+        char buf[1024];
+        ssize_t n = listxattr(path, buf, sizeof(buf));
+        EXPECT_GE(n, 0) << strerror(errno);
+        for (ssize_t i = 0; i < n; i += strlen(buf + i) + 1) {
+            names.insert(buf + i);
+        }
+        return names;
+    }
+};
+
+}  // namespace
+
+TEST_F(Xattr, SetGetRoundTrip) {
+    ASSERT_EQ(0, setxattr(kPath, "user.foo", "bar", 3, 0)) << strerror(errno);
+
+    EXPECT_EQ(3, getxattr(kPath, "user.foo", nullptr, 0));
+    char buf[16] = {};
+    ASSERT_EQ(3, getxattr(kPath, "user.foo", buf, sizeof(buf))) << strerror(errno);
+    EXPECT_EQ(0, memcmp(buf, "bar", 3));
+
+    // 覆盖已有的属性
+    ASSERT_EQ(0, setxattr(kPath, "user.foo", "bazz", 4, 0)) << strerror(errno);
+    ASSERT_EQ(4, getxattr(kPath, "user.foo", buf, sizeof(buf)));
+    EXPECT_EQ(0, memcmp(buf, "bazz", 4));
+
+    // 空的属性值
+    ASSERT_EQ(0, setxattr(kPath, "user.empty", "", 0, 0)) << strerror(errno);
+    EXPECT_EQ(0, getxattr(kPath, "user.empty", buf, sizeof(buf)));
+}
+
+TEST_F(Xattr, ListAndRemove) {
+    ASSERT_EQ(0, setxattr(kPath, "user.a", "1", 1, 0)) << strerror(errno);
+    ASSERT_EQ(0, setxattr(kPath, "user.b", "2", 1, 0)) << strerror(errno);
+
+    std::set<std::string> names = list_names(kPath);
+    EXPECT_EQ(1u, names.count("user.a"));
+    EXPECT_EQ(1u, names.count("user.b"));
+    EXPECT_EQ((ssize_t)strlen("user.a") + 1 + (ssize_t)strlen("user.b") + 1,
+              listxattr(kPath, nullptr, 0));
+
+    char small[4];
+    EXPECT_EQ(-1, listxattr(kPath, small, sizeof(small)));
+    EXPECT_EQ(ERANGE, errno);
+
+    ASSERT_EQ(0, removexattr(kPath, "user.a")) << strerror(errno);
+    names = list_names(kPath);
+    EXPECT_EQ(0u, names.count("user.a"));
+    EXPECT_EQ(1u, names.count("user.b"));
+
+    EXPECT_EQ(-1, getxattr(kPath, "user.a", nullptr, 0));
+    EXPECT_EQ(ENODATA, errno);
+    EXPECT_EQ(-1, removexattr(kPath, "user.a"));
+    EXPECT_EQ(ENODATA, errno);
+}
+
+TEST_F(Xattr, CreateAndReplaceFlags) {
+    EXPECT_EQ(-1, setxattr(kPath, "user.x", "1", 1, XATTR_REPLACE));
+    EXPECT_EQ(ENODATA, errno);
+
+    ASSERT_EQ(0, setxattr(kPath, "user.x", "1", 1, XATTR_CREATE)) << strerror(errno);
+    EXPECT_EQ(-1, setxattr(kPath, "user.x", "2", 1, XATTR_CREATE));
+    EXPECT_EQ(EEXIST, errno);
+
+    ASSERT_EQ(0, setxattr(kPath, "user.x", "3", 1, XATTR_REPLACE)) << strerror(errno);
+    char c = 0;
+    ASSERT_EQ(1, getxattr(kPath, "user.x", &c, 1));
+    EXPECT_EQ('3', c);
+
+    EXPECT_EQ(-1, setxattr(kPath, "user.x", "4", 1, XATTR_CREATE | XATTR_REPLACE | 0x10));
+    EXPECT_EQ(EINVAL, errno);
+}
+
+TEST_F(Xattr, BufferTooSmall) {
+    ASSERT_EQ(0, setxattr(kPath, "user.long", "0123456789", 10, 0)) << strerror(errno);
+    char buf[4];
+    EXPECT_EQ(-1, getxattr(kPath, "user.long", buf, sizeof(buf)));
+    EXPECT_EQ(ERANGE, errno);
+}
+
+TEST_F(Xattr, InvalidNames) {
+    EXPECT_EQ(-1, setxattr(kPath, "foo.bar", "1", 1, 0));
+    EXPECT_EQ(EOPNOTSUPP, errno);
+    EXPECT_EQ(-1, getxattr(kPath, "foo.bar", nullptr, 0));
+    EXPECT_EQ(EOPNOTSUPP, errno);
+
+    EXPECT_EQ(-1, setxattr(kPath, "user.", "1", 1, 0));
+    EXPECT_EQ(EINVAL, errno);
+
+    EXPECT_EQ(-1, setxattr(kPath, "", "1", 1, 0));
+    EXPECT_EQ(ERANGE, errno);
+
+    std::string long_name = "user." + std::string(300, 'a');
+    EXPECT_EQ(-1, setxattr(kPath, long_name.c_str(), "1", 1, 0));
+    EXPECT_EQ(ERANGE, errno);
+}
+
+TEST_F(Xattr, FdVariants) {
+    int fd = open(kPath, O_RDWR);
+    ASSERT_GE(fd, 0) << strerror(errno);
+
+    ASSERT_EQ(0, fsetxattr(fd, "user.fd", "v", 1, 0)) << strerror(errno);
+    char c = 0;
+    ASSERT_EQ(1, fgetxattr(fd, "user.fd", &c, 1)) << strerror(errno);
+    EXPECT_EQ('v', c);
+    EXPECT_EQ((ssize_t)strlen("user.fd") + 1, flistxattr(fd, nullptr, 0));
+    ASSERT_EQ(0, fremovexattr(fd, "user.fd")) << strerror(errno);
+    EXPECT_EQ(0, flistxattr(fd, nullptr, 0));
+
+    close(fd);
+    EXPECT_EQ(-1, fgetxattr(fd, "user.fd", &c, 1));
+    EXPECT_EQ(EBADF, errno);
+}
+
+TEST_F(Xattr, UserNamespaceNotOnSymlink) {
+    ASSERT_EQ(0, symlink(kPath, kLink)) << strerror(errno);
+
+    // 不跟随符号链接时，user属性不能设置在符号链接本身上
+    EXPECT_EQ(-1, lsetxattr(kLink, "user.foo", "1", 1, 0));
+    EXPECT_EQ(EPERM, errno);
+
+    // 跟随符号链接时设置在目标文件上
+    ASSERT_EQ(0, setxattr(kLink, "user.foo", "1", 1, 0)) << strerror(errno);
+    EXPECT_EQ(1, getxattr(kPath, "user.foo", nullptr, 0));
+    EXPECT_EQ(-1, lgetxattr(kLink, "user.foo", nullptr, 0));
+    EXPECT_EQ(ENODATA, errno);
+}
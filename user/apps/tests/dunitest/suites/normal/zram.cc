@@ -0,0 +1,218 @@
+#include <gtest/gtest.h>
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+#include <algorithm>
+#include <sstream>
+#include <string>
+#include <vector>
+
+#include "blkdev_common.h"
+
+namespace {
+
+constexpr size_t kPage = 4096;
+constexpr size_t kDiskSize = 4 * 1024 * 1024;
+constexpr int kTextPages = 8;
+constexpr int kRandomPages = 4;
+constexpr int kSamePages = 4;
+constexpr size_t kDataLen = (kTextPages + kRandomPages + kSamePages) * kPage;
+
+class Zram : public ::testing::Test {
+protected:
+    void SetUp() override {
+        // 找一个还没有设置容量的设备
+        for (int i = 0; i < 8; i++) {
+            std::string name = "zram" + std::to_string(i);
+            if (access(("/dev/" + name).c_str(), F_OK) != 0) {
+                break;
+            }
+            if (ReadAttr(name, "initstate") == "0") {
+                name_ = name;
+                break;
+            }
+        }
+        if (name_.empty()) {
+            GTEST_SKIP() << "no unused /dev/zramN";
+        }
+        dev_ = "/dev/" + name_;
+        ASSERT_EQ(0, WriteAttr("disksize", "4M")) << strerror(errno);
+    }
+
+    void TearDown() override {
+        if (!name_.empty()) {
+            WriteAttr("reset", "1");
+        }
+    }
+
+    std::string ReadAttr(const std::string &name, const char *attr) {
+        std::string path = "/sys/block/" + name + "/" + attr;
+        char buf[256] = {};
+        int fd = open(path.c_str(), O_RDONLY);
+        if (fd < 0) {
+            return "";
+        }
+        ssize_t n = read(fd, buf, sizeof(buf) - 1);
+        close(fd);
+        std::string s(buf, n > 0 ? n : 0);
+        while (!s.empty() && s.back() == '\n') {
+            s.pop_back();
+        }
+        return s;
+    }
+
+    std::string ReadAttr(const char *attr) { return ReadAttr(name_, attr); }
+
+    int WriteAttr(const char *attr, const char *val) {
+        std::string path = "/sys/block/" + name_ + "/" + attr;
+        int fd = open(path.c_str(), O_WRONLY);
+        if (fd < 0) {
+            return -1;
+        }
+        ssize_t n = write(fd, val, strlen(val));
+        int saved = errno;
+        close(fd);
+        errno = saved;
+        return n == (ssize_t)strlen(val) ? 0 : -1;
+    }
+
+    // mm_stat的各项数值
+    std::vector<uint64_t> MmStat() {
+        std::istringstream in(ReadAttr("mm_stat"));
+        std::vector<uint64_t> v;
+        uint64_t x;
+        while (in >> x) {
+            v.push_back(x);
+        }
+        return v;
+    }
+
+    // 可压缩的文本页、不可压缩的随机页和整页同一个值的页
+    static std::vector<char> MakeData() {
+        std::vector<char> data(kDataLen);
+        size_t off = 0;
+        for (int i = 0; i < kTextPages; i++, off += kPage) {
+            for (size_t j = 0; j < kPage;) {
+                char line[64];
+                int n = snprintf(line, sizeof(line), "zram dunitest page %d line %zu\n", i, j);
+                size_t len = std::min((size_t)n, kPage - j);
+                memcpy(&data[off + j], line, len);
+                j += len;
+            }
+        }
+        for (int i = 0; i < kRandomPages; i++, off += kPage) {
+            blk_fill_pattern(&data[off], kPage, 100 + i);
+        }
+        for (int i = 0; i < kSamePages; i++, off += kPage) {
+            memset(&data[off], 0x5a + i, kPage);
+        }
+        return data;
+    }
+
+    std::string name_;
+    std::string dev_;
+};
+
+TEST_F(Zram, DisksizeSetsCapacityOnce) {
+    EXPECT_EQ(std::to_string(kDiskSize), ReadAttr("disksize"));
+    EXPECT_EQ("1", ReadAttr("initstate"));
+    EXPECT_NE(std::string::npos, ReadAttr("comp_algorithm").find("[lz4]"));
+
+    int fd = open(dev_.c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    EXPECT_EQ((off_t)kDiskSize, lseek(fd, 0, SEEK_END));
+    close(fd);
+
+    errno = 0;
+    EXPECT_EQ(-1, WriteAttr("disksize", "8M"));
+    EXPECT_EQ(EBUSY, errno);
+}
+
+TEST_F(Zram, WriteReadRoundTrip) {
+    std::vector<char> data = MakeData();
+    int fd = open(dev_.c_str(), O_RDWR);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)kDataLen, pwrite(fd, data.data(), kDataLen, kPage)) << strerror(errno);
+    ASSERT_EQ(0, fsync(fd)) << strerror(errno);
+    close(fd);
+
+    std::vector<uint64_t> st = MmStat();
+    ASSERT_EQ(9u, st.size());
+    // orig_data_size compr_data_size mem_used_total ... same_pages pages_compacted huge_pages
+    EXPECT_GE(st[0], kDataLen);
+    EXPECT_LT(st[1], (uint64_t)(kTextPages + kRandomPages) * kPage);
+    EXPECT_GT(st[2], 0u);
+    EXPECT_GE(st[5], (uint64_t)kSamePages);
+    EXPECT_GE(st[7], (uint64_t)kRandomPages);
+
+    std::vector<char> back(kDataLen);
+    fd = open(dev_.c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)kDataLen, pread(fd, back.data(), kDataLen, kPage)) << strerror(errno);
+    EXPECT_EQ(data, back);
+    // 没有写入过的页读出来全是0
+    std::vector<char> page(kPage, 1);
+    ASSERT_EQ((ssize_t)kPage, pread(fd, page.data(), kPage, 0)) << strerror(errno);
+    EXPECT_EQ(std::vector<char>(kPage, 0), page);
+    close(fd);
+}
+
+TEST_F(Zram, OverwriteReplacesStoredPages) {
+    std::vector<char> data = MakeData();
+    int fd = open(dev_.c_str(), O_RDWR);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)kDataLen, pwrite(fd, data.data(), kDataLen, 0)) << strerror(errno);
+    ASSERT_EQ(0, fsync(fd)) << strerror(errno);
+
+    // 把所有页改写成同一个值的页，压缩的数据都应该被释放
+    std::vector<char> same(kDataLen, 0x11);
+    ASSERT_EQ((ssize_t)kDataLen, pwrite(fd, same.data(), kDataLen, 0)) << strerror(errno);
+    ASSERT_EQ(0, fsync(fd)) << strerror(errno);
+    close(fd);
+
+    std::vector<uint64_t> st = MmStat();
+    ASSERT_EQ(9u, st.size());
+    EXPECT_EQ(0u, st[1]);
+    EXPECT_EQ(kDataLen / kPage, st[5]);
+    EXPECT_EQ(0u, st[7]);
+
+    std::vector<char> back(kDataLen);
+    fd = open(dev_.c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)kDataLen, pread(fd, back.data(), kDataLen, 0)) << strerror(errno);
+    EXPECT_EQ(same, back);
+    close(fd);
+}
+
+TEST_F(Zram, ResetDropsDataAndCapacity) {
+    std::vector<char> data = MakeData();
+    int fd = open(dev_.c_str(), O_RDWR);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)kDataLen, pwrite(fd, data.data(), kDataLen, 0)) << strerror(errno);
+    ASSERT_EQ(0, fsync(fd)) << strerror(errno);
+
+    // 设备打开时不能重置
+    errno = 0;
+    EXPECT_EQ(-1, WriteAttr("reset", "1"));
+    EXPECT_EQ(EBUSY, errno);
+    close(fd);
+
+    ASSERT_EQ(0, WriteAttr("reset", "1")) << strerror(errno);
+    EXPECT_EQ("0", ReadAttr("disksize"));
+    EXPECT_EQ("0", ReadAttr("initstate"));
+
+    ASSERT_EQ(0, WriteAttr("disksize", "4M")) << strerror(errno);
+    std::vector<char> back(kDataLen, 1);
+    fd = open(dev_.c_str(), O_RDONLY);
+    ASSERT_GE(fd, 0) << strerror(errno);
+    ASSERT_EQ((ssize_t)kDataLen, pread(fd, back.data(), kDataLen, 0)) << strerror(errno);
+    EXPECT_EQ(std::vector<char>(kDataLen, 0), back);
+    close(fd);
+}
+
+}  // namespace